@@ -0,0 +1,49 @@
+//! Chain reconstruction from shuffled headers
+//!
+//! XOR-packaged block files store blocks out of order, so the chunk index rebuilds heights by
+//! chaining prev_block_hash ([`order_by_prev_hash`]). This shuffles a synthetic header chain with
+//! a fixed seed, asserts the reconstruction returns it in exact order, then measures
+//! reconstruction time against chain length (up to mainnet scale).
+//!
+//!   cargo bench --bench chain_reconstruction --features chunk-cache
+
+use blvm_bench::chunk_index::order_by_prev_hash;
+use blvm_bench::utils::{shuffled_order, synthetic_header_chain};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const ROOT: [u8; 32] = [0x11; 32];
+const SEED: u64 = 0x5eed;
+const LENGTHS: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+/// Shuffled headers, checked to rechain into the original order.
+fn shuffled_chain(len: usize) -> Vec<[u8; 80]> {
+    let chain = synthetic_header_chain(ROOT, len);
+    let shuffled: Vec<[u8; 80]> = shuffled_order(len, SEED)
+        .into_iter()
+        .map(|i| chain[i])
+        .collect();
+    let order = order_by_prev_hash(&shuffled, ROOT);
+    assert_eq!(order.len(), len, "chain of {} broke", len);
+    assert!(
+        order.iter().map(|&i| &shuffled[i]).eq(chain.iter()),
+        "chain of {} reconstructed out of order",
+        len
+    );
+    shuffled
+}
+
+fn bench_chain_reconstruction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chain_reconstruction");
+    group.sample_size(10);
+    for len in LENGTHS {
+        let headers = shuffled_chain(len);
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &headers, |b, headers| {
+            b.iter(|| black_box(order_by_prev_hash(black_box(headers), ROOT)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_chain_reconstruction);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! Magic scan strategies on plain and obfuscated blk data
+//!
+//! Compares the [`MagicScan`] strategies behind the collection pass's resync search:
+//! - `dense`: search from just past a frame header across a 1 MiB block payload
+//! - `sparse`: search across 8 MiB of zero-filled file tail (the repeated key when obfuscated)
+//!
+//! Set `BLVM_BENCH_BLK_FILE=/path/to/blocks/blkNNNNN.dat` to also scan the first 64 MiB of a real
+//! block file; its `xor.dat` (if any) is used for the obfuscated run.
+//!
+//!   cargo bench --bench magic_scan
+
+use blvm_bench::block_framing::{MagicScan, XorKey};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::Path;
+
+const MAINNET_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+const KEY: [u8; 8] = [0x3c, 0x91, 0x07, 0xe2, 0x5d, 0xaa, 0x18, 0xf4];
+
+/// Transaction-like filler: mostly small integers, zero padding and 0xff runs, some random
+/// script / signature bytes (uniform random data would make first-byte searches look too good).
+fn block_like(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        match state % 8 {
+            0..=2 => out.extend_from_slice(&[0x00; 4]),
+            3 => out.extend_from_slice(&[0xff; 4]),
+            4 => out.extend_from_slice(&(state as u32 % 256).to_le_bytes()),
+            _ => out.extend_from_slice(&state.to_le_bytes()),
+        }
+    }
+    out.truncate(len);
+    out
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut v = MAINNET_MAGIC.to_vec();
+    v.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    v.extend_from_slice(payload);
+    v
+}
+
+/// (name, plain bytes, key for the obfuscated run, offset to start searching from)
+fn inputs() -> Vec<(String, Vec<u8>, Option<XorKey>, usize)> {
+    let key = XorKey::new(KEY);
+
+    let mut dense = frame(&block_like(1024 * 1024, 1));
+    dense.extend(frame(&block_like(300_000, 2)));
+
+    let mut sparse = frame(&block_like(200_000, 3));
+    sparse.extend(std::iter::repeat_n(0u8, 8 * 1024 * 1024));
+    sparse.extend(frame(&block_like(1000, 4)));
+
+    let mut inputs = vec![
+        ("dense".to_string(), dense, key, 8),
+        ("sparse".to_string(), sparse, key, 8),
+    ];
+
+    if let Ok(path) = std::env::var("BLVM_BENCH_BLK_FILE") {
+        let path = Path::new(&path);
+        let mut data = std::fs::read(path).expect("read BLVM_BENCH_BLK_FILE");
+        data.truncate(64 * 1024 * 1024);
+        let file_key = path
+            .parent()
+            .and_then(|dir| std::fs::read(dir.join("xor.dat")).ok())
+            .and_then(|bytes| XorKey::from_xor_dat(&bytes).ok().flatten());
+        // Deobfuscate so the file is plain like the synthetic inputs
+        if let Some(k) = file_key {
+            k.apply(&mut data, 0);
+        }
+        inputs.push(("blk_file".to_string(), data, file_key.or(key), 8));
+    }
+    inputs
+}
+
+fn bench_magic_scan(c: &mut Criterion) {
+    for (name, plain, key, from) in inputs() {
+        let mut obfuscated = plain.clone();
+        if let Some(k) = key {
+            k.apply(&mut obfuscated, 0);
+        }
+
+        for (mode, data, key) in [("plain", &plain, None), ("xor", &obfuscated, key)] {
+            let window = &data[from..];
+            let mut group = c.benchmark_group(format!("magic_scan/{}/{}", name, mode));
+            group.throughput(Throughput::Bytes(window.len() as u64));
+            group.sample_size(20);
+            for scan in MagicScan::ALL {
+                group.bench_with_input(BenchmarkId::from_parameter(scan.name()), &scan, |b, &s| {
+                    b.iter(|| {
+                        black_box(s.find(black_box(window), from as u64, key, &MAINNET_MAGIC))
+                    })
+                });
+            }
+            group.finish();
+        }
+    }
+}
+
+criterion_group!(benches, bench_magic_scan);
+criterion_main!(benches);
@@ -0,0 +1,223 @@
+//! Curated Mainnet Block Benchmarks
+//! Deserialization, connect_block and per-input signature verification on real blocks
+//!
+//! Blocks are [`BLOCKS`] from `blvm_bench::fixtures` (empty, 2015-era, segwit-heavy,
+//! taproot-heavy) and are loaded with their prevouts from the fixture directory; fetch them once
+//! with `cargo run --release --bin fetch_bench_fixtures --features differential`. Blocks that
+//! were never fetched are skipped.
+
+use blvm_bench::fixtures::{self, Fixture, FixturePrevout};
+use blvm_protocol::block::{
+    block_validation_context_for_connect_ibd, calculate_script_flags_for_block_network,
+    calculate_tx_id, connect_block,
+};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::types::{Block, BlockHeader, Network, UtxoSet, ValidationResult, UTXO};
+use blvm_protocol::witness::is_witness_empty;
+use blvm_protocol::Witness;
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Fixtures benchmarked here, one per era / script mix.
+const BLOCKS: &[&str] = &["empty", "legacy_2015", "segwit_heavy", "taproot_heavy"];
+
+/// A fixture block decoded, with the UTXO set `connect_block` needs and per-input prevouts.
+struct Prepared {
+    fixture: &'static Fixture,
+    /// Serialized block, witnesses included
+    bytes: Vec<u8>,
+    block: Block,
+    witnesses: Vec<Vec<Witness>>,
+    /// Coins spent by the block that were created before it
+    utxo_set: UtxoSet,
+    /// `(values, script_pubkeys)` per non-coinbase transaction
+    prevouts: Vec<(Vec<i64>, Vec<Vec<u8>>)>,
+}
+
+/// Decode `fixture` and build its UTXO set; panics unless the block then connects as valid, so
+/// the benchmarks never time a rejection path.
+fn prepare(
+    fixture: &'static Fixture,
+    bytes: Vec<u8>,
+    fixture_prevouts: Vec<FixturePrevout>,
+) -> Prepared {
+    let (block, witnesses) =
+        deserialize_block_with_witnesses(&bytes).expect("fixture block deserializes");
+    let in_block: HashSet<[u8; 32]> = block.transactions.iter().map(calculate_tx_id).collect();
+
+    let mut utxo_set = UtxoSet::default();
+    let mut prevouts = Vec::new();
+    let mut fixture_prevouts = fixture_prevouts.iter();
+    for tx in block.transactions.iter().skip(1) {
+        let mut values = Vec::with_capacity(tx.inputs.len());
+        let mut script_pubkeys = Vec::with_capacity(tx.inputs.len());
+        for input in tx.inputs.iter() {
+            let prevout = fixture_prevouts
+                .next()
+                .expect("fixture has a prevout per input");
+            let script_pubkey = hex::decode(&prevout.script_pubkey).expect("prevout script hex");
+            // Outputs created earlier in the same block are added by connect_block itself
+            if !in_block.contains(&input.prevout.hash) {
+                utxo_set.insert(
+                    input.prevout.clone(),
+                    Arc::new(UTXO {
+                        value: prevout.value,
+                        script_pubkey: script_pubkey.clone().into(),
+                        height: prevout.height,
+                        is_coinbase: prevout.coinbase,
+                    }),
+                );
+            }
+            values.push(prevout.value);
+            script_pubkeys.push(script_pubkey);
+        }
+        prevouts.push((values, script_pubkeys));
+    }
+
+    let ctx = block_validation_context_for_connect_ibd(
+        None::<&[BlockHeader]>,
+        block.header.timestamp,
+        Network::Mainnet,
+    );
+    let connected = connect_block(&block, &witnesses, utxo_set.clone(), fixture.height, &ctx)
+        .expect("fixture block connects");
+    if let ValidationResult::Invalid(reason) = connected.0 {
+        panic!("fixture {} does not validate: {}", fixture.name, reason);
+    }
+
+    Prepared {
+        fixture,
+        bytes,
+        block,
+        witnesses,
+        utxo_set,
+        prevouts,
+    }
+}
+
+fn prepared_blocks() -> Vec<Prepared> {
+    BLOCKS
+        .iter()
+        .filter_map(|&name| {
+            let bytes = fixtures::load(name).expect("fixture directory readable")?;
+            let prevouts = fixtures::load_prevouts(name).expect("fixture directory readable")?;
+            let fixture = fixtures::fixture(name).expect("curated block in the fixture manifest");
+            Some(prepare(fixture, bytes, prevouts))
+        })
+        .collect()
+}
+
+fn benchmark_deserialize(c: &mut Criterion, blocks: &[Prepared]) {
+    let mut group = c.benchmark_group("curated_deserialize_block_with_witnesses");
+    for p in blocks {
+        group.throughput(Throughput::Bytes(p.bytes.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(p.fixture.name),
+            &p.bytes,
+            |b, bytes| b.iter(|| black_box(deserialize_block_with_witnesses(black_box(bytes)))),
+        );
+    }
+    group.finish();
+}
+
+fn benchmark_connect_block(c: &mut Criterion, blocks: &[Prepared]) {
+    let mut group = c.benchmark_group("curated_connect_block");
+    group.sample_size(10);
+    for p in blocks {
+        let height = p.fixture.height;
+        let ctx = block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            p.block.header.timestamp,
+            Network::Mainnet,
+        );
+        group.throughput(Throughput::Elements(p.block.transactions.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(p.fixture.name), |b| {
+            b.iter_batched(
+                || p.utxo_set.clone(),
+                |utxo_set| {
+                    let result = connect_block(
+                        black_box(&p.block),
+                        black_box(&p.witnesses),
+                        utxo_set,
+                        black_box(height),
+                        &ctx,
+                    );
+                    black_box(result)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_signature_verification(c: &mut Criterion, blocks: &[Prepared]) {
+    let mut group = c.benchmark_group("curated_verify_inputs");
+    group.sample_size(10);
+    for p in blocks {
+        let height = p.fixture.height;
+        let inputs: usize = p.prevouts.iter().map(|(values, _)| values.len()).sum();
+        if inputs == 0 {
+            continue;
+        }
+        group.throughput(Throughput::Elements(inputs as u64));
+        group.bench_function(BenchmarkId::from_parameter(p.fixture.name), |b| {
+            b.iter(|| {
+                for (tx_idx, tx) in p.block.transactions.iter().enumerate().skip(1) {
+                    let (values, script_pubkeys) = &p.prevouts[tx_idx - 1];
+                    let script_pubkeys: Vec<&[u8]> =
+                        script_pubkeys.iter().map(Vec::as_slice).collect();
+                    let tx_witnesses = p.witnesses.get(tx_idx).map(Vec::as_slice).unwrap_or(&[]);
+                    let has_witness = tx_witnesses.iter().any(|w| !is_witness_empty(w));
+                    let flags = calculate_script_flags_for_block_network(
+                        tx,
+                        has_witness,
+                        height,
+                        Network::Mainnet,
+                    );
+                    for input_idx in 0..tx.inputs.len() {
+                        let result = verify_script_with_context_full(
+                            &tx.inputs[input_idx].script_sig,
+                            script_pubkeys[input_idx],
+                            tx_witnesses.get(input_idx),
+                            flags,
+                            tx,
+                            input_idx,
+                            values,
+                            &script_pubkeys,
+                            Some(height),
+                            None,
+                            Network::Mainnet,
+                            SigVersion::Base,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                        );
+                        let _ = black_box(result);
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_curated_blocks(c: &mut Criterion) {
+    let blocks = prepared_blocks();
+    if blocks.is_empty() {
+        eprintln!("⚠️  No curated blocks fetched - skipping curated block benchmarks");
+        return;
+    }
+    benchmark_deserialize(c, &blocks);
+    benchmark_connect_block(c, &blocks);
+    benchmark_signature_verification(c, &blocks);
+}
+
+criterion_group!(benches, benchmark_curated_blocks);
+criterion_main!(benches);
@@ -0,0 +1,211 @@
+//! Hybrid Script Pool Benchmark
+//! connect_block (single path) vs HybridConnector (UTXO thread + dedicated script pool)
+//!
+//! Blocks are synthetic: every transaction spends two P2WPKH outputs that already exist in the
+//! UTXO set and carries real ECDSA signatures, so script verification dominates the same way it
+//! does in block_validation_realistic. The UTXO set clone each iteration needs is done in the
+//! batch setup and not timed. Height 1 keeps assume-valid out of the way.
+
+use blvm_bench::hybrid_validation::{compare_with_connect_block, HybridConnector};
+use blvm_protocol::block::{block_validation_context_for_connect_ibd, connect_block};
+use blvm_protocol::transaction_hash::{calculate_transaction_sighash, SighashType};
+use blvm_protocol::types::Network;
+use blvm_protocol::{
+    tx_inputs, tx_outputs, Block, BlockHeader, OutPoint, Transaction, TransactionInput,
+    TransactionOutput, UtxoSet, Witness, UTXO,
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const INPUTS_PER_TX: usize = 2;
+
+/// P2WPKH scriptPubkey (OP_0 <20-byte hash>)
+fn p2wpkh_script_pubkey(pubkey: &PublicKey) -> Vec<u8> {
+    let hash = Sha256::digest(pubkey.serialize());
+    let mut script = vec![blvm_protocol::opcodes::OP_0, 0x14];
+    script.extend_from_slice(&hash[..20]);
+    script
+}
+
+/// Coinbase plus `num_txs` independent transactions, each spending `INPUTS_PER_TX` funding
+/// outputs from the returned UTXO set.
+fn create_signed_block(num_txs: usize) -> (Block, UtxoSet, Vec<Vec<Witness>>) {
+    let secp = Secp256k1::new();
+    let mut utxo_set = UtxoSet::default();
+
+    let coinbase = Transaction {
+        version: 1,
+        inputs: tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: [0; 32],
+                index: 0xffffffff,
+            },
+            script_sig: vec![blvm_protocol::opcodes::OP_1; 4],
+            sequence: 0xffffffff,
+        }],
+        outputs: tx_outputs![TransactionOutput {
+            value: 5_000_000_000,
+            script_pubkey: vec![blvm_protocol::opcodes::OP_1],
+        }],
+        lock_time: 0,
+    };
+    let mut transactions = vec![coinbase];
+    let mut witnesses: Vec<Vec<Witness>> = vec![vec![vec![]]];
+
+    for i in 0..num_txs {
+        let sk = SecretKey::from_slice(&rand::random::<[u8; 32]>()).expect("Invalid secret key");
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let script_pubkey = p2wpkh_script_pubkey(&pk);
+
+        // Funding outpoints outside the block, one synthetic txid per spend
+        let mut fund = |j: usize| {
+            let mut hash: [u8; 32] = Sha256::digest((i * INPUTS_PER_TX + j).to_le_bytes()).into();
+            hash[0] |= 1;
+            let outpoint = OutPoint { hash, index: 0 };
+            utxo_set.insert(
+                outpoint.clone(),
+                Arc::new(UTXO {
+                    value: 100_000,
+                    script_pubkey: script_pubkey.clone().into(),
+                    height: 0,
+                    is_coinbase: false,
+                }),
+            );
+            TransactionInput {
+                prevout: outpoint,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }
+        };
+        let inputs = tx_inputs![fund(0), fund(1)];
+        let prevouts = vec![
+            TransactionOutput {
+                value: 100_000,
+                script_pubkey: script_pubkey.clone(),
+            };
+            INPUTS_PER_TX
+        ];
+
+        let tx = Transaction {
+            version: 2,
+            inputs,
+            outputs: tx_outputs![
+                TransactionOutput {
+                    value: 150_000,
+                    script_pubkey: script_pubkey.clone(),
+                },
+                TransactionOutput {
+                    value: 49_000,
+                    script_pubkey: script_pubkey.clone(),
+                }
+            ],
+            lock_time: 0,
+        };
+
+        let tx_witnesses = (0..INPUTS_PER_TX)
+            .map(|input_index| {
+                let sighash =
+                    calculate_transaction_sighash(&tx, input_index, &prevouts, SighashType::ALL)
+                        .expect("Failed to calculate sighash");
+                let msg = Message::from_digest_slice(&sighash).expect("Invalid sighash");
+                let mut sig = secp.sign_ecdsa(&msg, &sk).serialize_der().to_vec();
+                sig.push(0x01); // SIGHASH_ALL
+                vec![sig, pk.serialize().to_vec()]
+            })
+            .collect();
+        transactions.push(tx);
+        witnesses.push(tx_witnesses);
+    }
+
+    let block = Block {
+        header: BlockHeader {
+            version: 1,
+            prev_block_hash: [0; 32],
+            merkle_root: [0; 32],
+            timestamp: 1234567890,
+            bits: 0x1d00ffff,
+            nonce: 0,
+        },
+        transactions: transactions.into_boxed_slice(),
+    };
+    (block, utxo_set, witnesses)
+}
+
+fn benchmark_hybrid_vs_connect_block(c: &mut Criterion) {
+    let max_threads = num_cpus::get().max(2);
+    let mut thread_counts: Vec<usize> = [1, 2, 4, 8, 16]
+        .into_iter()
+        .filter(|&t| t < max_threads)
+        .chain(std::iter::once(max_threads - 1))
+        .collect();
+    thread_counts.dedup();
+
+    for num_txs in [100, 1000] {
+        let (block, utxo_set, witnesses) = create_signed_block(num_txs);
+        let mut group = c.benchmark_group(format!("hybrid_script_pool_{}tx", num_txs));
+        group.sample_size(20);
+
+        let ctx = block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            block.header.timestamp,
+            Network::Mainnet,
+        );
+        group.bench_function("connect_block", |b| {
+            b.iter_batched(
+                || utxo_set.clone(),
+                |set| {
+                    let result =
+                        connect_block(black_box(&block), black_box(&witnesses), set, 1, &ctx);
+                    black_box(result)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        for &threads in &thread_counts {
+            let connector =
+                HybridConnector::new(threads, Network::Mainnet).expect("Failed to build pool");
+            group.bench_with_input(BenchmarkId::new("hybrid", threads), &threads, |b, _| {
+                b.iter_batched(
+                    || utxo_set.clone(),
+                    |mut set| {
+                        let result = connector.connect(
+                            black_box(&block),
+                            black_box(&witnesses),
+                            &mut set,
+                            1,
+                        );
+                        black_box((result, set))
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+
+        // connect_block also checks what the hybrid path skips (merkle root, coinbase value), so
+        // a disagreement here is reported rather than fatal
+        let connector = HybridConnector::new(0, Network::Mainnet).expect("Failed to build pool");
+        let comparison =
+            compare_with_connect_block(&connector, &block, &witnesses, &mut utxo_set.clone(), 1);
+        if !comparison.hybrid_valid || !comparison.verdicts_match() {
+            eprintln!(
+                "⚠️  {}-tx block: connect_block valid={}, hybrid valid={}",
+                num_txs, comparison.connect_block_valid, comparison.hybrid_valid
+            );
+        }
+        println!(
+            "{} txs, {} script threads: UTXO thread {:.2?}, barrier wait {:.2?}, speedup {:.2}x",
+            num_txs,
+            connector.script_threads(),
+            comparison.hybrid.utxo_apply,
+            comparison.hybrid.barrier_wait,
+            comparison.speedup()
+        );
+    }
+}
+
+criterion_group!(benches, benchmark_hybrid_vs_connect_block);
+criterion_main!(benches);
@@ -0,0 +1,138 @@
+//! Detects which blvm-consensus API shape this crate is being built against.
+//!
+//! The consensus crate is usually a sibling checkout (`[patch.crates-io]` in `Cargo.toml`, or
+//! `BLVM_CONSENSUS_DIR`) on whatever branch is under development. Its sources are scanned for the
+//! `connect_block` signature and the `UtxoSet` definition, and `cfg`s are emitted for
+//! `src/consensus_compat.rs` to pick the matching adapter. Without a checkout (registry build) the
+//! current API is assumed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CFGS: [&str; 3] = [
+    "blvm_connect_block_undo",
+    "blvm_utxo_arc",
+    "blvm_consensus_api_unsupported",
+];
+
+fn consensus_dir(manifest_dir: &Path) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("BLVM_CONSENSUS_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let manifest: toml::Value = fs::read_to_string(manifest_dir.join("Cargo.toml"))
+        .ok()?
+        .parse()
+        .ok()?;
+    let path = manifest
+        .get("patch")?
+        .get("crates-io")?
+        .get("blvm-consensus")?
+        .get("path")?
+        .as_str()?;
+    Some(manifest_dir.join(path))
+}
+
+fn rust_sources(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            rust_sources(&path, out);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            out.push(path);
+        }
+    }
+}
+
+/// Text from `marker` up to the first `{` or `;`, whitespace collapsed.
+fn item_after(source: &str, marker: &str) -> Option<String> {
+    let start = source.find(marker)?;
+    let rest = &source[start..];
+    let end = rest.find(['{', ';']).unwrap_or(rest.len());
+    Some(rest[..end].split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=Cargo.toml");
+    println!("cargo::rerun-if-env-changed=BLVM_CONSENSUS_DIR");
+    for cfg in CFGS {
+        println!("cargo::rustc-check-cfg=cfg({})", cfg);
+    }
+
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let dir = consensus_dir(&manifest_dir).filter(|d| d.join("src").is_dir());
+    let Some(dir) = dir else {
+        println!("cargo::rustc-env=BLVM_CONSENSUS_VERSION=registry");
+        println!("cargo::rustc-env=BLVM_CONSENSUS_API=connect_block+undo, Arc<UTXO> (assumed)");
+        println!("cargo::rustc-cfg=blvm_connect_block_undo");
+        println!("cargo::rustc-cfg=blvm_utxo_arc");
+        return;
+    };
+    println!("cargo::rerun-if-changed={}", dir.join("src").display());
+    println!(
+        "cargo::rerun-if-changed={}",
+        dir.join("Cargo.toml").display()
+    );
+
+    let version = fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|s| s.parse::<toml::Value>().ok())
+        .and_then(|m| Some(m.get("package")?.get("version")?.as_str()?.to_string()))
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo::rustc-env=BLVM_CONSENSUS_VERSION={}", version);
+
+    let mut files = Vec::new();
+    rust_sources(&dir.join("src"), &mut files);
+    let mut connect_block = None;
+    let mut utxo_set = None;
+    for file in &files {
+        let Ok(source) = fs::read_to_string(file) else {
+            continue;
+        };
+        if connect_block.is_none() {
+            connect_block = item_after(&source, "pub fn connect_block(");
+        }
+        if utxo_set.is_none() {
+            utxo_set = item_after(&source, "pub type UtxoSet")
+                .or_else(|| item_after(&source, "pub struct UtxoSet"));
+        }
+    }
+
+    let takes_ctx = connect_block
+        .as_deref()
+        .is_some_and(|s| s.contains("BlockValidationContext"));
+    let has_undo = connect_block
+        .as_deref()
+        .is_some_and(|s| s.contains("UndoLog"));
+    // A bare map alias is the only `UtxoSet` shape with `iter` / `insert` the adapters rely on
+    let utxo_is_map = utxo_set
+        .as_deref()
+        .is_some_and(|s| s.starts_with("pub type UtxoSet") && s.contains("Map<"));
+    let utxo_arc = utxo_set.as_deref().is_some_and(|s| s.contains("Arc<UTXO>"));
+
+    if !takes_ctx || !utxo_is_map {
+        println!("cargo::rustc-cfg=blvm_consensus_api_unsupported");
+        println!(
+            "cargo::warning=blvm-consensus {} at {} has an API consensus_compat does not know: \
+             connect_block = {:?}, UtxoSet = {:?}",
+            version,
+            dir.display(),
+            connect_block,
+            utxo_set
+        );
+    }
+    if has_undo {
+        println!("cargo::rustc-cfg=blvm_connect_block_undo");
+    }
+    if utxo_arc {
+        println!("cargo::rustc-cfg=blvm_utxo_arc");
+    }
+    println!(
+        "cargo::rustc-env=BLVM_CONSENSUS_API=connect_block{}, {}",
+        if has_undo { "+undo" } else { "" },
+        if utxo_arc { "Arc<UTXO>" } else { "UTXO" }
+    );
+}
@@ -1,7 +1,7 @@
 #!/bin/bash
 # Create chunk metadata file for automatic detection
 
-CHUNK_DIR="/run/media/acolyte/Extra/blockchain"
+CHUNK_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 
 echo "📝 Creating chunk metadata file..."
 
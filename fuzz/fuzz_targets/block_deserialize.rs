@@ -0,0 +1,13 @@
+//! Fuzz BLVM's block deserialization against the bench's own transaction splitter: same
+//! transactions, same txids, for every block BLVM accepts.
+//!
+//!   cargo +nightly fuzz run --features differential block_deserialize
+
+#![no_main]
+
+use blvm_bench::fuzzing::check_block;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    check_block(input);
+});
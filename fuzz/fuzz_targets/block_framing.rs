@@ -0,0 +1,44 @@
+//! Fuzz the block framing scanner (magic scan, size validation, resync search).
+//!
+//! Input layout: byte 0 = flags (bit 0: XOR-packaged, bit 1: small limits), bytes 1..9 = base
+//! file offset (LE), rest = file contents.
+//!
+//!   cargo +nightly fuzz run block_framing
+
+#![no_main]
+
+use blvm_bench::block_framing::{
+    check_scan_invariants, find_encrypted_frame, find_encrypted_magic, FrameLimits,
+    RESYNC_SIZE_RANGE,
+};
+use libfuzzer_sys::fuzz_target;
+
+const MAINNET_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+fuzz_target!(|input: &[u8]| {
+    if input.len() < 9 {
+        return;
+    }
+    let flags = input[0];
+    let base_offset = u64::from_le_bytes(input[1..9].try_into().unwrap()) >> 8;
+    let data = &input[9..];
+    let xor = flags & 1 != 0;
+    let limits = if flags & 2 != 0 {
+        FrameLimits {
+            min_block_size: 1,
+            max_block_size: 4096,
+            max_search_distance: 64,
+        }
+    } else {
+        FrameLimits::default()
+    };
+
+    check_scan_invariants(data, base_offset, MAINNET_MAGIC, xor, limits);
+
+    if let Some(i) = find_encrypted_magic(data, base_offset, &MAINNET_MAGIC) {
+        assert!(i + 4 <= data.len());
+    }
+    if let Some(i) = find_encrypted_frame(data, base_offset, &MAINNET_MAGIC, RESYNC_SIZE_RANGE) {
+        assert!(i + 8 <= data.len());
+    }
+});
@@ -0,0 +1,13 @@
+//! Fuzz CompactSize parsing: BLVM's `decode_varint` against the bench's reader, canonical
+//! encodings only.
+//!
+//!   cargo +nightly fuzz run --features differential compact_size
+
+#![no_main]
+
+use blvm_bench::fuzzing::check_compact_size;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    check_compact_size(input);
+});
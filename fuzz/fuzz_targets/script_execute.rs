@@ -0,0 +1,15 @@
+//! Fuzz script execution: BLVM's interpreter and `libbitcoinconsensus` must agree.
+//!
+//! Input layout: see `blvm_bench::fuzzing::encode_script_input` (flags, amount, scriptSig,
+//! scriptPubKey, witness stack).
+//!
+//!   cargo +nightly fuzz run --features differential script_execute
+
+#![no_main]
+
+use blvm_bench::fuzzing::check_script;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    check_script(input);
+});
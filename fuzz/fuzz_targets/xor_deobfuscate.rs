@@ -0,0 +1,30 @@
+//! Fuzz XOR deobfuscation: must be an involution at every file offset and agree with the
+//! word-wise field decryption on whole 4-byte groups.
+//!
+//!   cargo +nightly fuzz run xor_deobfuscate
+
+#![no_main]
+
+use blvm_bench::block_framing::{xor_deobfuscate, xor_word};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    if input.len() < 8 {
+        return;
+    }
+    let offset = u64::from_le_bytes(input[..8].try_into().unwrap()) >> 8;
+    let original = &input[8..];
+
+    let mut buf = original.to_vec();
+    xor_deobfuscate(&mut buf, offset);
+    for (i, (plain, enc)) in original
+        .chunks_exact(4)
+        .zip(buf.chunks_exact(4))
+        .enumerate()
+    {
+        let word = [plain[0], plain[1], plain[2], plain[3]];
+        assert_eq!(xor_word(word, offset + 4 * i as u64), enc);
+    }
+    xor_deobfuscate(&mut buf, offset);
+    assert_eq!(buf, original);
+});
@@ -4,7 +4,7 @@
 LOG_FILE=$(ls -t /tmp/start9-cache-resume-*.log 2>/dev/null | head -1)
 TEMP_FILE="$HOME/.cache/blvm-bench/blvm-bench-blocks-temp.bin"
 META_FILE="$HOME/.cache/blvm-bench/blvm-bench-blocks-temp.bin.meta"
-CHUNKS_DIR="/run/media/acolyte/Extra/blockchain"
+CHUNKS_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 
 echo "📊 Cache Resume Status Monitor"
 echo "=============================="
@@ -5,7 +5,7 @@
 set -euo pipefail
 
 TEMP_FILE="$HOME/.cache/blvm-bench/blvm-bench-blocks-temp.bin"
-SECONDARY_DIR="/run/media/acolyte/Extra/blockchain"
+SECONDARY_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 CACHE_DIR="$HOME/.cache/blvm-bench"
 CHUNKS_DIR="$CACHE_DIR/chunks"
 METADATA_FILE="$CACHE_DIR/blvm-bench-blocks-temp.bin.count"
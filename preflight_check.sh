@@ -10,7 +10,7 @@ WARNINGS=0
 
 # 1. Check chunks exist
 echo "1️⃣  Checking chunks..."
-CHUNK_DIR="/run/media/acolyte/Extra/blockchain"
+CHUNK_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 if [ ! -d "$CHUNK_DIR" ]; then
     echo "   ❌ Chunk directory not found: $CHUNK_DIR"
     ERRORS=$((ERRORS + 1))
@@ -2,7 +2,7 @@
 # PROTECT CHUNK FILES - READ-ONLY MODE
 # DO NOT DELETE OR MODIFY THESE FILES
 
-CHUNK_DIR="/run/media/acolyte/Extra/blockchain"
+CHUNK_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 
 echo "🛡️  PROTECTING CHUNK FILES"
 echo "=========================="
@@ -4,7 +4,7 @@
 
 set -e
 
-CHUNK_DIR="/run/media/acolyte/Extra/blockchain"
+CHUNK_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 BLOCK1_HASH="00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
 
 echo "Getting block 1 from Bitcoin Core RPC..."
@@ -9,7 +9,7 @@ TEMP_FILE="$HOME/.cache/blvm-bench/blvm-bench-blocks-temp.bin"
 META_FILE="$TEMP_FILE.meta"
 CACHE_DIR="$HOME/.cache/blvm-bench"
 CHUNKS_DIR="$CACHE_DIR/chunks"
-SECONDARY_DIR="/run/media/acolyte/Extra/blockchain"
+SECONDARY_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 NUM_CHUNKS=4
 
 # Colors
@@ -1,14 +1,14 @@
 #!/bin/bash
 # Run chain scan in 3 batches and merge results.
-# Requires: BLOCK_CACHE_DIR or /run/media/acolyte/Extra/blockchain (mount external drive)
+# Requires: BLOCK_CACHE_DIR (default ~/.cache/blvm-bench/chunks)
 # Usage: BLOCK_CACHE_DIR=/path ./scripts/run_chain_scan_3batches.sh
 
 set -e
 cd "$(dirname "$0")/.."
 mkdir -p bip110_results
 
-# Use BLOCK_CACHE_DIR if set; else try default external drive
-export BLOCK_CACHE_DIR="${BLOCK_CACHE_DIR:-/run/media/acolyte/Extra/blockchain}"
+# Use BLOCK_CACHE_DIR if set; else the default chunk cache
+export BLOCK_CACHE_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 if [ ! -d "$BLOCK_CACHE_DIR" ] || [ ! -f "$BLOCK_CACHE_DIR/chunks.meta" ]; then
     echo "❌ Blockchain not found. Set BLOCK_CACHE_DIR or mount $BLOCK_CACHE_DIR"
     echo "   Need: chunks.meta and chunk_*.bin.zst files"
@@ -99,6 +99,8 @@ fi
 export BLVM_BENCH_ROOT
 
 # Set up results directory (always define, even if BLVM_BENCH_ROOT is not set)
+# blvm-bench --output-dir exports BLVM_OUTPUT_DIR; results go to its results/ then
+RESULTS_DIR="${RESULTS_DIR:-${BLVM_OUTPUT_DIR:+${BLVM_OUTPUT_DIR}/results}}"
 RESULTS_DIR="${RESULTS_DIR:-${BLVM_BENCH_ROOT:-$(pwd)}/results}"
 mkdir -p "$RESULTS_DIR" 2>/dev/null || true
 export RESULTS_DIR
@@ -14,7 +14,7 @@ import sys
 from pathlib import Path
 
 def main():
-    chunks_dir = Path(os.environ.get("BLOCK_CACHE_DIR", "/run/media/acolyte/Extra/blockchain"))
+    chunks_dir = Path(os.environ.get("BLOCK_CACHE_DIR", Path.home() / ".cache/blvm-bench/chunks"))
     scan_path = Path("bip110_results/scan_merged.json")
     if not scan_path.exists():
         scan_path = Path("blvm-bench/bip110_results/scan_merged.json")
@@ -13,7 +13,7 @@
 
 set -e
 cd "$(dirname "$0")/.."
-export BLOCK_CACHE_DIR="${BLOCK_CACHE_DIR:-/run/media/acolyte/Extra/blockchain}"
+export BLOCK_CACHE_DIR="${BLOCK_CACHE_DIR:-$HOME/.cache/blvm-bench/chunks}"
 
 if [ ! -d "$BLOCK_CACHE_DIR" ] || [ ! -f "$BLOCK_CACHE_DIR/chunks.meta" ]; then
     echo "❌ Blockchain not found at $BLOCK_CACHE_DIR"
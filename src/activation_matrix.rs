@@ -0,0 +1,358 @@
+//! Soft-fork activation boundary matrix
+//!
+//! Script flag selection bugs sit at the handful of heights where consensus rules change, so a
+//! whole-chain run spends almost all of its time where they can't show up.
+//! [`run_activation_matrix`] validates only the blocks within `window` (default
+//! [`DEFAULT_WINDOW`]) of each mainnet activation in [`ACTIVATIONS`], and for each window:
+//!
+//! - runs every block through the usual BLVM / Core differential
+//!   ([`validate_chunk`](crate::parallel_differential::validate_chunk)), with the run's hooks
+//! - checks at every height that BLVM's script flags
+//!   ([`get_script_flags`](crate::sort_merge::verify::get_script_flags)) have the activation's
+//!   flags off below the activation height and on from it, as Core's `GetBlockScriptFlags` does
+//!
+//! BIP34 is a coinbase rule and Core sets `TAPROOT` from genesis, so those windows only compare
+//! verdicts. Each window starts from the highest stored UTXO checkpoint ([`CheckpointStore`])
+//! below it, replayed forward through BLVM only; without checkpoints the replay starts at genesis.
+
+use anyhow::Result;
+use bitcoinconsensus::{
+    VERIFY_CHECKLOCKTIMEVERIFY, VERIFY_CHECKSEQUENCEVERIFY, VERIFY_DERSIG, VERIFY_NULLDUMMY,
+};
+use blvm_protocol::types::{Network, UtxoSet};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::block_source::BlockSource;
+use crate::cancel::CancellationToken;
+use crate::checkpoint_store::CheckpointStore;
+use crate::parallel_differential::{validate_chunk, BlockChunk};
+use crate::validation_hooks::HookRegistry;
+
+/// Blocks validated on each side of an activation height by default
+pub const DEFAULT_WINDOW: u64 = 1000;
+
+/// A mainnet soft fork and the script flags Core turns on at its height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Activation {
+    pub name: &'static str,
+    /// First block the rules apply to
+    pub height: u64,
+    /// `SCRIPT_VERIFY_*` bits enabled from `height` (0 for rules outside the interpreter)
+    pub flags: u32,
+}
+
+/// Mainnet activations, by height.
+pub const ACTIVATIONS: &[Activation] = &[
+    Activation {
+        name: "bip34",
+        height: 227_931,
+        flags: 0,
+    },
+    Activation {
+        name: "bip66",
+        height: 363_725,
+        flags: VERIFY_DERSIG,
+    },
+    Activation {
+        name: "bip65",
+        height: 388_381,
+        flags: VERIFY_CHECKLOCKTIMEVERIFY,
+    },
+    Activation {
+        name: "csv",
+        height: 419_328,
+        flags: VERIFY_CHECKSEQUENCEVERIFY,
+    },
+    Activation {
+        name: "segwit",
+        height: 481_824,
+        flags: VERIFY_NULLDUMMY,
+    },
+    Activation {
+        name: "taproot",
+        height: 709_632,
+        flags: 0,
+    },
+];
+
+/// Activation by name.
+pub fn activation(name: &str) -> Option<&'static Activation> {
+    ACTIVATIONS.iter().find(|a| a.name == name)
+}
+
+impl Activation {
+    /// `[height - window, height + window]`, clamped at genesis.
+    pub fn window(&self, window: u64) -> (u64, u64) {
+        (self.height.saturating_sub(window), self.height + window)
+    }
+
+    /// Bits of [`flags`](Self::flags) Core verifies a block at `height` with.
+    pub fn expected_flags(&self, height: u64) -> u32 {
+        if height >= self.height {
+            self.flags
+        } else {
+            0
+        }
+    }
+}
+
+/// A height whose flags disagree with the activation schedule (activation bits only).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagMismatch {
+    pub height: u64,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// Heights in `[start, end]` where `flags` does not select the activation's bits the way Core
+/// does.
+pub fn check_flag_selection(
+    activation: &Activation,
+    (start, end): (u64, u64),
+    flags: impl Fn(u64) -> u32,
+) -> Vec<FlagMismatch> {
+    (start..=end)
+        .filter_map(|height| {
+            let expected = activation.expected_flags(height);
+            let actual = flags(height) & activation.flags;
+            (actual != expected).then_some(FlagMismatch {
+                height,
+                expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// Outcome of one activation window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowReport {
+    pub name: String,
+    pub activation_height: u64,
+    pub start_height: u64,
+    pub end_height: u64,
+    pub tested: usize,
+    pub matched: usize,
+    /// `(height, blvm_result, core_result)`
+    pub divergences: Vec<(u64, String, String)>,
+    pub flag_mismatches: Vec<FlagMismatch>,
+    /// Rebuilding the UTXO set up to the window
+    pub replay_secs: f64,
+    pub duration_secs: f64,
+}
+
+impl WindowReport {
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty() && self.flag_mismatches.is_empty()
+    }
+}
+
+/// Outcome of [`run_activation_matrix`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatrixReport {
+    pub windows: Vec<WindowReport>,
+    /// Activations whose window starts past the source's tip
+    pub skipped: Vec<String>,
+}
+
+impl MatrixReport {
+    pub fn passed(&self) -> bool {
+        self.windows.iter().all(WindowReport::passed)
+    }
+
+    pub fn print(&self) {
+        println!("\n📊 Activation matrix");
+        for w in &self.windows {
+            println!(
+                "   {} {:<8} @ {:>7}  {}..={}  {}/{} matched  {} divergence(s)  {} flag \
+                 mismatch(es)  {:.0}s (+{:.0}s replay)",
+                if w.passed() { "✅" } else { "❌" },
+                w.name,
+                w.activation_height,
+                w.start_height,
+                w.end_height,
+                w.matched,
+                w.tested,
+                w.divergences.len(),
+                w.flag_mismatches.len(),
+                w.duration_secs,
+                w.replay_secs
+            );
+            for m in w.flag_mismatches.iter().take(3) {
+                println!(
+                    "      flags at {}: {:#x}, Core {:#x}",
+                    m.height, m.actual, m.expected
+                );
+            }
+            for (height, blvm, core) in w.divergences.iter().take(3) {
+                println!("      block {}: BLVM={} Core={}", height, blvm, core);
+            }
+        }
+        for name in &self.skipped {
+            println!("   ⏭️  {:<8} beyond the tip", name);
+        }
+    }
+}
+
+/// Inputs for [`run_activation_matrix`]
+#[derive(Clone)]
+pub struct MatrixConfig {
+    pub activations: Vec<Activation>,
+    /// Blocks on each side of every activation height
+    pub window: u64,
+    /// Checkpoints each window's UTXO set is rebuilt from (`None`: replay from genesis)
+    pub checkpoint_dir: Option<PathBuf>,
+    pub hooks: HookRegistry,
+}
+
+impl MatrixConfig {
+    /// Every activation in [`ACTIVATIONS`] with a [`DEFAULT_WINDOW`] window.
+    pub fn new(checkpoint_dir: Option<PathBuf>) -> Self {
+        Self {
+            activations: ACTIVATIONS.to_vec(),
+            window: DEFAULT_WINDOW,
+            checkpoint_dir,
+            hooks: HookRegistry::default(),
+        }
+    }
+}
+
+/// Validate the window around each of `config.activations`, in height order.
+pub async fn run_activation_matrix<S: BlockSource + 'static>(
+    config: MatrixConfig,
+    block_source: Arc<S>,
+    cancel: CancellationToken,
+) -> Result<MatrixReport> {
+    let store = config
+        .checkpoint_dir
+        .as_ref()
+        .map(|dir| CheckpointStore::new(dir, crate::block_file_reader::Network::Mainnet))
+        .transpose()?;
+    let tip = block_source.get_tip_height().await?;
+    let mut activations = config.activations.clone();
+    activations.sort_by_key(|a| a.height);
+
+    let mut report = MatrixReport::default();
+    // UTXO set before the previous window, replayed on from when no checkpoint is closer
+    let mut context: Option<(u64, Arc<UtxoSet>)> = None;
+    for activation in &activations {
+        let (start, end) = activation.window(config.window);
+        let end = tip.map_or(end, |tip| end.min(tip));
+        if start > end {
+            report.skipped.push(activation.name.to_string());
+            continue;
+        }
+        println!(
+            "🔀 {} (height {}): validating {}..={}",
+            activation.name, activation.height, start, end
+        );
+
+        let replay_started = Instant::now();
+        let utxo_set = if start == 0 {
+            Arc::new(UtxoSet::default())
+        } else {
+            let checkpoint = match &store {
+                Some(store) => store.latest_in(0, start - 1, block_source.as_ref()).await?,
+                None => None,
+            };
+            let (replay_from, utxo_set) = match (checkpoint, context.take()) {
+                // The previous window's chunk is done with the set, so this doesn't copy it
+                (Some((cp, _)), Some((at, set))) if at >= cp && at < start => {
+                    (at + 1, Arc::unwrap_or_clone(set))
+                }
+                (Some((cp, set)), _) => (cp + 1, set),
+                (None, Some((at, set))) if at < start => (at + 1, Arc::unwrap_or_clone(set)),
+                (None, _) => (0, UtxoSet::default()),
+            };
+            let utxo_set = Arc::new(
+                crate::sampling::replay(
+                    block_source.as_ref(),
+                    utxo_set,
+                    replay_from,
+                    start - 1,
+                    Network::Mainnet,
+                    &cancel,
+                    None,
+                )
+                .await?,
+            );
+            context = Some((start - 1, Arc::clone(&utxo_set)));
+            utxo_set
+        };
+        let replay_secs = replay_started.elapsed().as_secs_f64();
+
+        let result = validate_chunk(
+            BlockChunk {
+                start_height: start,
+                end_height: end,
+                checkpoint_utxo: Some(utxo_set),
+                checkpoint_timing: None,
+                skip_validation: false,
+            },
+            block_source.clone(),
+            cancel.child_token(),
+            config.hooks.clone(),
+        )
+        .await?;
+        let flag_mismatches = check_flag_selection(activation, (start, end), |height| {
+            crate::sort_merge::verify::get_script_flags(height, Network::Mainnet)
+        });
+        report.windows.push(WindowReport {
+            name: activation.name.to_string(),
+            activation_height: activation.height,
+            start_height: start,
+            end_height: end,
+            tested: result.tested,
+            matched: result.matched,
+            divergences: result.divergences,
+            flag_mismatches,
+            replay_secs,
+            duration_secs: result.duration_secs,
+        });
+    }
+    report.print();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_selection_switches_exactly_at_activation() {
+        let bip66 = activation("bip66").unwrap();
+        let window = bip66.window(2);
+        assert_eq!(window, (363_723, 363_727));
+        let core = |height: u64| {
+            ACTIVATIONS
+                .iter()
+                .fold(0, |f, a| f | a.expected_flags(height))
+        };
+        assert!(check_flag_selection(bip66, window, core).is_empty());
+
+        // One block late
+        let late = |height: u64| {
+            if height == bip66.height {
+                core(height) & !VERIFY_DERSIG
+            } else {
+                core(height)
+            }
+        };
+        assert_eq!(
+            check_flag_selection(bip66, window, late),
+            [FlagMismatch {
+                height: bip66.height,
+                expected: VERIFY_DERSIG,
+                actual: 0,
+            }]
+        );
+        assert_eq!(
+            activation("bip34").unwrap().window(DEFAULT_WINDOW).0,
+            226_931
+        );
+        assert!(activation("bip9").is_none());
+    }
+}
@@ -0,0 +1,339 @@
+//! Heap and RSS profiling of benchmark regions (`alloc-profiling` feature)
+//!
+//! Wall-clock numbers don't show a UTXO set that keeps growing over a long validation run. With
+//! the feature enabled, the process allocator is wrapped in a [`CountingAllocator`] (over
+//! mimalloc with `low-mem-alloc`, else the system allocator) that counts every allocation and
+//! tracks the live heap and its peak. A [`MemoryProfiler`] covers one region:
+//!
+//! - allocations, frees and bytes allocated while it ran, and the peak live heap
+//!   ([`AllocStats`])
+//! - a background thread sampling RSS and the live heap every `BLVM_MEMORY_SAMPLE_MS`
+//!   (default [`DEFAULT_SAMPLE_INTERVAL`]), capped at [`MAX_SAMPLES`] by dropping every other
+//!   sample when full
+//!
+//! [`BenchmarkRegistry::run`](crate::registry::BenchmarkRegistry::run) profiles each benchmark's
+//! iterations and [`validate_range`](crate::collect_only::validate_range) the whole run; both
+//! reports carry the [`MemoryProfile`]. Without the feature [`MemoryProfiler::start`] returns
+//! `None` and nothing is recorded. Regions share the global counters, so they should not
+//! overlap.
+
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// RSS sampling interval when `BLVM_MEMORY_SAMPLE_MS` is unset
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Samples kept per region
+pub const MAX_SAMPLES: usize = 1024;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+static FREED_BYTES: AtomicU64 = AtomicU64::new(0);
+static PEAK_LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(all(feature = "alloc-profiling", feature = "low-mem-alloc"))]
+#[global_allocator]
+static GLOBAL: CountingAllocator<mimalloc::MiMalloc> = CountingAllocator::new(mimalloc::MiMalloc);
+
+#[cfg(all(feature = "alloc-profiling", not(feature = "low-mem-alloc")))]
+#[global_allocator]
+static GLOBAL: CountingAllocator<std::alloc::System> = CountingAllocator::new(std::alloc::System);
+
+/// Whether this build counts allocations.
+pub fn enabled() -> bool {
+    cfg!(feature = "alloc-profiling")
+}
+
+/// Allocator wrapper feeding the process-wide counters behind [`AllocCounters::now`].
+pub struct CountingAllocator<A> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+fn record_alloc(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let allocated = ALLOCATED_BYTES.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+    let live = allocated.saturating_sub(FREED_BYTES.load(Ordering::Relaxed));
+    PEAK_LIVE_BYTES.fetch_max(live, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    FREED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            // Counted as a free of the old block and an allocation of the new one
+            record_dealloc(layout.size());
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Process-wide allocator counters since start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocCounters {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub allocated_bytes: u64,
+    pub freed_bytes: u64,
+}
+
+impl AllocCounters {
+    /// Current counters (all zero without the feature).
+    pub fn now() -> Self {
+        Self {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+            freed_bytes: FREED_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Heap bytes allocated and not yet freed.
+    pub fn live_bytes(&self) -> u64 {
+        self.allocated_bytes.saturating_sub(self.freed_bytes)
+    }
+}
+
+/// Allocator activity over one region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub allocated_bytes: u64,
+    pub freed_bytes: u64,
+    /// Live heap growth over the region (negative if it shrank)
+    pub net_bytes: i64,
+    /// Highest live heap while the region ran
+    pub peak_live_bytes: u64,
+}
+
+impl AllocStats {
+    /// Counters between `start` and `end`, with the peak since the last reset.
+    fn between(start: AllocCounters, end: AllocCounters) -> Self {
+        Self {
+            allocations: end.allocations - start.allocations,
+            deallocations: end.deallocations - start.deallocations,
+            allocated_bytes: end.allocated_bytes - start.allocated_bytes,
+            freed_bytes: end.freed_bytes - start.freed_bytes,
+            net_bytes: end.live_bytes() as i64 - start.live_bytes() as i64,
+            peak_live_bytes: PEAK_LIVE_BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// One RSS / heap reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemorySample {
+    pub elapsed_ms: u64,
+    /// `None` where the platform does not report it
+    pub rss_bytes: Option<u64>,
+    pub live_heap_bytes: u64,
+}
+
+/// Memory use of one profiled region.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryProfile {
+    pub alloc: AllocStats,
+    /// Highest sampled RSS
+    pub peak_rss_bytes: Option<u64>,
+    /// Milliseconds between the kept samples
+    pub sample_interval_ms: u64,
+    pub samples: Vec<MemorySample>,
+}
+
+impl MemoryProfile {
+    /// One-line summary, e.g. `12.3M allocs, 850.0 MiB allocated, heap +512.0 MiB (peak ...)`.
+    pub fn summary(&self) -> String {
+        const MIB: f64 = 1024.0 * 1024.0;
+        let a = &self.alloc;
+        let mut line = format!(
+            "{:.1}M allocs, {:.1} MiB allocated, heap {:+.1} MiB (peak {:.1} MiB)",
+            a.allocations as f64 / 1e6,
+            a.allocated_bytes as f64 / MIB,
+            a.net_bytes as f64 / MIB,
+            a.peak_live_bytes as f64 / MIB
+        );
+        if let Some(rss) = self.peak_rss_bytes {
+            line.push_str(&format!(", peak RSS {:.1} MiB", rss as f64 / MIB));
+        }
+        line
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    kept: Vec<MemorySample>,
+    /// Every `stride`-th tick is kept
+    stride: u64,
+    ticks: u64,
+}
+
+impl Samples {
+    fn push(&mut self, sample: MemorySample) {
+        self.ticks += 1;
+        if self.ticks % self.stride != 0 {
+            return;
+        }
+        if self.kept.len() == MAX_SAMPLES {
+            let mut i = 0;
+            self.kept.retain(|_| {
+                i += 1;
+                i % 2 == 0
+            });
+            self.stride *= 2;
+            if self.ticks % self.stride != 0 {
+                return;
+            }
+        }
+        self.kept.push(sample);
+    }
+}
+
+/// Profiles the region between [`start`](Self::start) and [`finish`](Self::finish).
+pub struct MemoryProfiler {
+    start: AllocCounters,
+    interval: Duration,
+    stop: Arc<AtomicBool>,
+    samples: Arc<Mutex<Samples>>,
+    sampler: Option<JoinHandle<()>>,
+}
+
+impl MemoryProfiler {
+    /// Start a region sampled every `BLVM_MEMORY_SAMPLE_MS`; `None` without the feature.
+    pub fn start() -> Option<Self> {
+        let interval = std::env::var("BLVM_MEMORY_SAMPLE_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .filter(|&ms| ms > 0)
+            .map_or(DEFAULT_SAMPLE_INTERVAL, Duration::from_millis);
+        Self::start_with_interval(interval)
+    }
+
+    pub fn start_with_interval(interval: Duration) -> Option<Self> {
+        if !enabled() {
+            return None;
+        }
+        let start = AllocCounters::now();
+        PEAK_LIVE_BYTES.store(start.live_bytes(), Ordering::Relaxed);
+        let stop = Arc::new(AtomicBool::new(false));
+        let samples = Arc::new(Mutex::new(Samples {
+            stride: 1,
+            ..Samples::default()
+        }));
+        let sampler = {
+            let stop = stop.clone();
+            let samples = samples.clone();
+            let started = Instant::now();
+            std::thread::Builder::new()
+                .name("memory-sampler".to_string())
+                .spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let sample = MemorySample {
+                            elapsed_ms: started.elapsed().as_millis() as u64,
+                            rss_bytes: crate::platform::current_rss_bytes(),
+                            live_heap_bytes: AllocCounters::now().live_bytes(),
+                        };
+                        samples.lock().unwrap().push(sample);
+                        std::thread::park_timeout(interval);
+                    }
+                })
+                .ok()
+        };
+        Some(Self {
+            start,
+            interval,
+            stop,
+            samples,
+            sampler,
+        })
+    }
+
+    /// Stop sampling and return the region's profile.
+    pub fn finish(mut self) -> MemoryProfile {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(sampler) = self.sampler.take() {
+            sampler.thread().unpark();
+            let _ = sampler.join();
+        }
+        let alloc = AllocStats::between(self.start, AllocCounters::now());
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        MemoryProfile {
+            alloc,
+            peak_rss_bytes: samples.kept.iter().filter_map(|s| s.rss_bytes).max(),
+            sample_interval_ms: self.interval.as_millis() as u64 * samples.stride,
+            samples: samples.kept,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_stay_bounded_and_regions_count_allocations() {
+        let mut samples = Samples {
+            stride: 1,
+            ..Samples::default()
+        };
+        for tick in 1..=(MAX_SAMPLES as u64 * 3) {
+            samples.push(MemorySample {
+                elapsed_ms: tick,
+                rss_bytes: None,
+                live_heap_bytes: 0,
+            });
+        }
+        assert!(samples.kept.len() <= MAX_SAMPLES);
+        assert_eq!(samples.stride, 4);
+        assert!(samples
+            .kept
+            .windows(2)
+            .all(|w| w[1].elapsed_ms - w[0].elapsed_ms == samples.stride));
+
+        let profile = MemoryProfiler::start_with_interval(Duration::from_millis(5));
+        assert_eq!(profile.is_some(), enabled());
+        if let Some(profiler) = profile {
+            let kept: Vec<u64> = (0..10_000).collect();
+            let profile = profiler.finish();
+            assert!(profile.alloc.allocations >= 1);
+            assert!(profile.alloc.allocated_bytes >= 80_000);
+            assert!(profile.alloc.peak_live_bytes >= kept.len() as u64 * 8);
+        }
+    }
+}
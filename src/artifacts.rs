@@ -0,0 +1,274 @@
+//! Where run outputs go
+//!
+//! Results, reports, checkpoints, corpora and divergence bundles used to land wherever each tool
+//! defaulted to: `results/` next to the checkout, the cache dir, the temp dir or a hardcoded
+//! drive. An [`ArtifactLayout`] puts all of them under one output directory, one subdirectory per
+//! [`ArtifactKind`]:
+//!
+//! ```text
+//! <output dir>/
+//!   results/<run id>/                     run directories (crate::run_id)
+//!   reports/<name>/                       differential reports
+//!   checkpoints/                          UTXO checkpoints, shared between runs
+//!   corpora/<target>/                     fuzz corpora (crate::fuzzing), shared between runs
+//!   divergences/<name>/
+//! ```
+//!
+//! `<name>` comes from a template with `{run_id}`, `{date}`, `{network}` and `{range}`
+//! placeholders, `{date}_{network}_{range}` by default (e.g. `20260301_mainnet_0-800000`).
+//! `{run_id}` is the [`RunId`](crate::run_id::RunId) of the run directory the artifact belongs
+//! to (a validation report of `blvm-bench diff-run --run-root`), `adhoc` outside a run.
+//!
+//! The output directory is `BLVM_OUTPUT_DIR` (`blvm-bench --output-dir` sets it for everything it
+//! runs) and the template `BLVM_ARTIFACT_NAME`. Without an output directory every tool keeps its
+//! old default, and the per-kind variables (`BLVM_RUNS_DIR`, `BLVM_REPORT_DIR`,
+//! `BLVM_CHECKPOINT_DIR`, `BLVM_FUZZ_CORPUS`) still override the layout for their kind.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Naming template used when `BLVM_ARTIFACT_NAME` is unset
+pub const DEFAULT_TEMPLATE: &str = "{date}_{network}_{range}";
+
+const PLACEHOLDERS: &[&str] = &["run_id", "date", "network", "range"];
+
+/// One kind of output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtifactKind {
+    Results,
+    Reports,
+    Checkpoints,
+    Corpora,
+    Divergences,
+}
+
+impl ArtifactKind {
+    pub const ALL: [ArtifactKind; 5] = [
+        Self::Results,
+        Self::Reports,
+        Self::Checkpoints,
+        Self::Corpora,
+        Self::Divergences,
+    ];
+
+    /// Subdirectory of the output directory.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Self::Results => "results",
+            Self::Reports => "reports",
+            Self::Checkpoints => "checkpoints",
+            Self::Corpora => "corpora",
+            Self::Divergences => "divergences",
+        }
+    }
+
+    /// Reused across runs, so not split by name (checkpoints of one height serve every run, fuzz
+    /// corpora grow with each seeding).
+    pub fn is_shared(self) -> bool {
+        matches!(self, Self::Checkpoints | Self::Corpora)
+    }
+}
+
+/// What an artifact name is built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactContext {
+    pub run_id: Option<String>,
+    /// `YYYYMMDD` (UTC)
+    pub date: String,
+    pub network: String,
+    /// Inclusive height range
+    pub range: Option<(u64, u64)>,
+}
+
+impl ArtifactContext {
+    /// Today, network from `BITCOIN_NETWORK` (mainnet when unset), no run ID or range.
+    pub fn now() -> Self {
+        Self {
+            run_id: None,
+            date: chrono::Utc::now().format("%Y%m%d").to_string(),
+            network: network_from_env(),
+            range: None,
+        }
+    }
+
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    pub fn with_range(mut self, start_height: u64, end_height: u64) -> Self {
+        self.range = Some((start_height, end_height));
+        self
+    }
+
+    fn value(&self, placeholder: &str) -> String {
+        match placeholder {
+            "run_id" => self.run_id.clone().unwrap_or_else(|| "adhoc".to_string()),
+            "date" => self.date.clone(),
+            "network" => self.network.clone(),
+            "range" => match self.range {
+                Some((start, end)) => format!("{}-{}", start, end),
+                None => "all".to_string(),
+            },
+            _ => unreachable!("placeholder checked by ArtifactLayout::new"),
+        }
+    }
+}
+
+/// `BITCOIN_NETWORK` in the spelling used for names (`main` -> `mainnet`, `test` -> `testnet`).
+fn network_from_env() -> String {
+    match std::env::var("BITCOIN_NETWORK").as_deref() {
+        Ok("main") | Ok("") | Err(_) => "mainnet".to_string(),
+        Ok("test") => "testnet".to_string(),
+        Ok(other) => other.to_string(),
+    }
+}
+
+/// Output directory plus naming template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactLayout {
+    root: PathBuf,
+    template: String,
+}
+
+impl ArtifactLayout {
+    /// Layout under `root` naming artifacts by `template`; unknown placeholders are an error.
+    pub fn new(root: impl Into<PathBuf>, template: impl Into<String>) -> Result<Self> {
+        let template = template.into();
+        for placeholder in placeholders(&template)? {
+            anyhow::ensure!(
+                PLACEHOLDERS.contains(&placeholder),
+                "Unknown placeholder {{{}}} in artifact name {:?} (known: {})",
+                placeholder,
+                template,
+                PLACEHOLDERS.join(", ")
+            );
+        }
+        Ok(Self {
+            root: root.into(),
+            template,
+        })
+    }
+
+    /// `BLVM_OUTPUT_DIR` with `BLVM_ARTIFACT_NAME`, or `None` without an output directory.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(root) = std::env::var_os("BLVM_OUTPUT_DIR").filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        let template = std::env::var("BLVM_ARTIFACT_NAME")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+        Self::new(PathBuf::from(root), template)
+            .context("Invalid BLVM_ARTIFACT_NAME")
+            .map(Some)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory holding every artifact of `kind`.
+    pub fn kind_dir(&self, kind: ArtifactKind) -> PathBuf {
+        self.root.join(kind.dir_name())
+    }
+
+    /// The template filled in from `ctx`, usable as a single path component.
+    pub fn name(&self, ctx: &ArtifactContext) -> String {
+        let mut name = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(open) = rest.find('{') {
+            name.push_str(&rest[..open]);
+            // Closing braces were checked in `new`
+            let close = open + rest[open..].find('}').unwrap();
+            name.push_str(&ctx.value(&rest[open + 1..close]));
+            rest = &rest[close + 1..];
+        }
+        name.push_str(rest);
+        name.chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' => '-',
+                c => c,
+            })
+            .collect()
+    }
+
+    /// Directory for one artifact of `kind`: the kind directory itself for shared kinds,
+    /// otherwise `<kind dir>/<name>`.
+    pub fn dir(&self, kind: ArtifactKind, ctx: &ArtifactContext) -> PathBuf {
+        if kind.is_shared() {
+            self.kind_dir(kind)
+        } else {
+            self.kind_dir(kind).join(self.name(ctx))
+        }
+    }
+}
+
+/// [`ArtifactLayout::from_env`], warning about (and ignoring) an invalid template.
+pub fn layout_from_env() -> Option<ArtifactLayout> {
+    match ArtifactLayout::from_env() {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("⚠️  {:#} - output directory layout ignored", e);
+            None
+        }
+    }
+}
+
+/// Directory for `kind` in the layout from the environment, if there is one.
+pub fn artifact_dir_from_env(kind: ArtifactKind, ctx: &ArtifactContext) -> Option<PathBuf> {
+    layout_from_env().map(|layout| layout.dir(kind, ctx))
+}
+
+fn placeholders(template: &str) -> Result<Vec<&str>> {
+    let mut found = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..]
+            .find('}')
+            .map(|i| open + i)
+            .with_context(|| format!("Unclosed '{{' in artifact name {:?}", template))?;
+        found.push(&rest[open + 1..close]);
+        rest = &rest[close + 1..];
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_directories() {
+        let ctx = ArtifactContext {
+            run_id: None,
+            date: "20260301".to_string(),
+            network: "mainnet".to_string(),
+            range: Some((0, 800_000)),
+        };
+        let layout = ArtifactLayout::new("/out", DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(layout.name(&ctx), "20260301_mainnet_0-800000");
+        assert_eq!(
+            layout.dir(ArtifactKind::Reports, &ctx),
+            PathBuf::from("/out/reports/20260301_mainnet_0-800000")
+        );
+        assert_eq!(
+            layout.dir(ArtifactKind::Checkpoints, &ctx),
+            PathBuf::from("/out/checkpoints")
+        );
+        assert_eq!(
+            layout.dir(ArtifactKind::Corpora, &ctx),
+            PathBuf::from("/out/corpora")
+        );
+
+        let layout = ArtifactLayout::new("/out", "{network}/{run_id}").unwrap();
+        assert_eq!(layout.name(&ctx), "mainnet-adhoc");
+        assert_eq!(
+            layout.name(&ctx.clone().with_run_id("validate-range-0011")),
+            "mainnet-validate-range-0011"
+        );
+
+        assert!(ArtifactLayout::new("/out", "{height}").is_err());
+        assert!(ArtifactLayout::new("/out", "run_{date").is_err());
+    }
+}
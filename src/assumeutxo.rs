@@ -0,0 +1,431 @@
+//! AssumeUTXO snapshots as a checkpoint source
+//!
+//! Core's `dumptxoutset` writes the whole UTXO set at a block to a `utxo-*.dat` file, and
+//! `loadtxoutset` accepts one only when its base block and content hash match an entry
+//! hard-coded in chainparams (`m_assumeutxo_data`). [`load_snapshot`] parses the same file
+//! (format version 2, Core 28+) into a [`UtxoSet`], recomputes Core's `hash_serialized_3` over it
+//! and checks it against those entries, so a parallel differential run can seed its first chunk
+//! with the snapshot and start validating above its height without replaying the chain (see
+//! [`ParallelConfig::assumeutxo_snapshot`](crate::parallel_differential::ParallelConfig)).
+//!
+//! File layout:
+//!
+//! ```text
+//! magic "utxo\xff" | version u16 LE | network magic [4] | base block hash [32] | coins u64 LE
+//! per txid: txid [32] | CompactSize n | n × (CompactSize vout | Coin)
+//! Coin = VARINT(height * 2 + coinbase) | VARINT(compressed amount) | compressed script
+//! ```
+//!
+//! Snapshots for other base blocks (regtest, custom signets) are accepted when their height and
+//! expected hash are given through `BLVM_ASSUMEUTXO_HEIGHT` / `BLVM_ASSUMEUTXO_HASH`.
+
+use anyhow::{Context, Result};
+use blvm_protocol::types::{OutPoint, UtxoSet, UTXO};
+use sha2::{Digest, Sha256};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::block_file_reader::Network;
+use crate::block_source::BlockSource;
+use crate::rev_file_reader::{
+    compressed_script_len, decompress_amount, decompress_script, MAX_SCRIPT_SIZE,
+};
+use crate::wire::{
+    display_hex, read_compact_size_from, read_varint_from, sha256d, write_compact_size,
+};
+
+const SNAPSHOT_MAGIC: &[u8; 5] = b"utxo\xff";
+/// Metadata version written by Core 28 and later.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+/// `(network, height, base block hash, hash_serialized_3, chain tx count)` from Core's
+/// chainparams, hashes in RPC byte order.
+const KNOWN_SNAPSHOTS: &[(Network, u64, &str, &str, u64)] = &[
+    (
+        Network::Mainnet,
+        840_000,
+        "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+        "a2a5521b1b5ab65f67818e5e8eccabb7171a517f9e2382208f77687310768f96",
+        991_032_194,
+    ),
+    (
+        Network::Mainnet,
+        880_000,
+        "000000000000000000010b17283c3c400507969a9c2afd1dcf2082ec5cca2880",
+        "dbd190983eaf433ef7c15f78a278ae42c00ef52e0fd2a54953782175fbadcea9",
+        1_145_604_538,
+    ),
+];
+
+/// `BLVM_ASSUMEUTXO_SNAPSHOT`, if set.
+pub fn snapshot_path_from_env() -> Option<PathBuf> {
+    std::env::var_os("BLVM_ASSUMEUTXO_SNAPSHOT")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// What a snapshot for one base block must hash to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssumeUtxoParams {
+    pub height: u64,
+    /// Base block hash, RPC byte order
+    pub block_hash: String,
+    /// Expected `hash_serialized_3`, RPC byte order (as `gettxoutsetinfo` prints it)
+    pub hash_serialized: String,
+    /// Transactions in the chain up to the base block, where known
+    pub chain_tx: Option<u64>,
+}
+
+impl AssumeUtxoParams {
+    /// The chainparams entry for `block_hash` on `network`.
+    pub fn known(network: Network, block_hash: &str) -> Option<Self> {
+        KNOWN_SNAPSHOTS
+            .iter()
+            .find(|(net, _, hash, _, _)| *net == network && *hash == block_hash)
+            .map(|&(_, height, hash, hash_serialized, chain_tx)| Self {
+                height,
+                block_hash: hash.to_string(),
+                hash_serialized: hash_serialized.to_string(),
+                chain_tx: Some(chain_tx),
+            })
+    }
+
+    /// `BLVM_ASSUMEUTXO_HEIGHT` + `BLVM_ASSUMEUTXO_HASH` for a snapshot of `block_hash`.
+    pub fn from_env(block_hash: &str) -> Result<Option<Self>> {
+        let (Ok(height), Ok(hash_serialized)) = (
+            std::env::var("BLVM_ASSUMEUTXO_HEIGHT"),
+            std::env::var("BLVM_ASSUMEUTXO_HASH"),
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            height: height
+                .parse()
+                .with_context(|| format!("Invalid BLVM_ASSUMEUTXO_HEIGHT {:?}", height))?,
+            block_hash: block_hash.to_string(),
+            hash_serialized: hash_serialized.to_lowercase(),
+            chain_tx: None,
+        }))
+    }
+}
+
+/// The snapshot header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMetadata {
+    pub version: u16,
+    pub network_magic: [u8; 4],
+    /// Internal byte order
+    pub base_block_hash: [u8; 32],
+    pub coins_count: u64,
+}
+
+impl SnapshotMetadata {
+    /// Base block hash in RPC byte order.
+    pub fn base_block_hash_hex(&self) -> String {
+        display_hex(&self.base_block_hash)
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut magic = [0u8; 5];
+        reader.read_exact(&mut magic)?;
+        anyhow::ensure!(
+            &magic == SNAPSHOT_MAGIC,
+            "not a UTXO snapshot (Core 28+ `dumptxoutset` format expected)"
+        );
+        let version = u16::from_le_bytes(read_array(reader)?);
+        anyhow::ensure!(
+            version == SNAPSHOT_VERSION,
+            "snapshot version {} (expected {})",
+            version,
+            SNAPSHOT_VERSION
+        );
+        Ok(Self {
+            version,
+            network_magic: read_array(reader)?,
+            base_block_hash: read_array(reader)?,
+            coins_count: u64::from_le_bytes(read_array(reader)?),
+        })
+    }
+}
+
+/// A loaded, verified snapshot.
+#[derive(Debug, Clone)]
+pub struct AssumeUtxoSnapshot {
+    pub metadata: SnapshotMetadata,
+    pub params: AssumeUtxoParams,
+    /// UTXO set after block `params.height`
+    pub utxo_set: UtxoSet,
+}
+
+impl AssumeUtxoSnapshot {
+    pub fn height(&self) -> u64 {
+        self.params.height
+    }
+}
+
+/// Read only the header of the snapshot at `path`.
+pub fn read_metadata(path: &Path) -> Result<SnapshotMetadata> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+    SnapshotMetadata::read(&mut file)
+        .with_context(|| format!("Invalid snapshot header in {}", path.display()))
+}
+
+/// Load the snapshot at `path`, checking it belongs to `network` and hashes to the chainparams
+/// entry for its base block (or to `BLVM_ASSUMEUTXO_HASH` for other base blocks).
+pub fn load_snapshot(path: &Path, network: Network) -> Result<AssumeUtxoSnapshot> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+    let reader = BufReader::with_capacity(8 * 1024 * 1024, file);
+    read_snapshot(reader, network, None)
+        .with_context(|| format!("Failed to load snapshot {}", path.display()))
+}
+
+/// [`load_snapshot`], then check that `source` has the snapshot's base block at its height (a
+/// snapshot from another chain would otherwise only show up as divergences).
+pub async fn load_snapshot_for_source<S: BlockSource>(
+    path: &Path,
+    network: Network,
+    source: &S,
+) -> Result<AssumeUtxoSnapshot> {
+    let snapshot = load_snapshot(path, network)?;
+    let block = source
+        .get_block(snapshot.height())
+        .await
+        .with_context(|| format!("Failed to read snapshot base block {}", snapshot.height()))?;
+    anyhow::ensure!(
+        block.len() >= 80,
+        "base block {} too short",
+        snapshot.height()
+    );
+    let hash = display_hex(&sha256d(&block[..80]));
+    anyhow::ensure!(
+        hash == snapshot.params.block_hash,
+        "block source has {} at height {}, snapshot is based on {}",
+        hash,
+        snapshot.height(),
+        snapshot.params.block_hash
+    );
+    Ok(snapshot)
+}
+
+/// [`load_snapshot`] over any reader; `params` overrides the chainparams / env lookup.
+pub fn read_snapshot(
+    mut reader: impl Read,
+    network: Network,
+    params: Option<AssumeUtxoParams>,
+) -> Result<AssumeUtxoSnapshot> {
+    let metadata = SnapshotMetadata::read(&mut reader)?;
+    anyhow::ensure!(
+        &metadata.network_magic == network.magic_bytes(),
+        "snapshot is for network magic {} (expected {:?})",
+        hex::encode(metadata.network_magic),
+        network
+    );
+    let base_hash = metadata.base_block_hash_hex();
+    let params = match params {
+        Some(params) => params,
+        None => match AssumeUtxoParams::known(network, &base_hash) {
+            Some(params) => params,
+            None => AssumeUtxoParams::from_env(&base_hash)?.with_context(|| {
+                format!(
+                    "base block {} is not an assumeutxo block for {:?}; set BLVM_ASSUMEUTXO_HEIGHT and BLVM_ASSUMEUTXO_HASH to load it anyway",
+                    base_hash, network
+                )
+            })?,
+        },
+    };
+    anyhow::ensure!(
+        params.block_hash == base_hash,
+        "snapshot base block {} does not match expected {}",
+        base_hash,
+        params.block_hash
+    );
+
+    println!(
+        "📥 Loading assumeutxo snapshot at height {} ({} coins)...",
+        params.height, metadata.coins_count
+    );
+    let start = std::time::Instant::now();
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.reserve(metadata.coins_count.min(1 << 28) as usize);
+    let mut hasher = Sha256::new();
+    let mut read = 0u64;
+    while read < metadata.coins_count {
+        let txid: [u8; 32] = read_array(&mut reader).context("truncated snapshot")?;
+        let coins = read_compact_size_from(&mut reader)?;
+        anyhow::ensure!(
+            coins > 0 && coins <= metadata.coins_count - read,
+            "txid {} claims {} coins with {} left",
+            hex::encode(txid),
+            coins,
+            metadata.coins_count - read
+        );
+        for _ in 0..coins {
+            let vout =
+                u32::try_from(read_compact_size_from(&mut reader)?).context("vout overflows")?;
+            let code = read_varint_from(&mut reader)?;
+            let value = decompress_amount(read_varint_from(&mut reader)?)
+                .context("compressed amount overflows")?;
+            let value = i64::try_from(value).context("coin value overflows")?;
+            let script_pubkey = read_script(&mut reader)?;
+
+            // TxOutSer (kernel/coinstats.cpp)
+            let code_u32 = u32::try_from(code).context("coin height overflows")?;
+            hasher.update(txid);
+            hasher.update(vout.to_le_bytes());
+            hasher.update(code_u32.to_le_bytes());
+            hasher.update(value.to_le_bytes());
+            let mut script_len = Vec::with_capacity(9);
+            write_compact_size(&mut script_len, script_pubkey.len());
+            hasher.update(script_len);
+            hasher.update(&script_pubkey);
+
+            let outpoint = OutPoint {
+                hash: txid,
+                index: vout as _,
+            };
+            let utxo = UTXO {
+                value,
+                script_pubkey: script_pubkey.into(),
+                height: (code >> 1) as _,
+                is_coinbase: code & 1 == 1,
+            };
+            anyhow::ensure!(
+                utxo_set.insert(outpoint, Arc::new(utxo)).is_none(),
+                "duplicate coin {}:{}",
+                hex::encode(txid),
+                vout
+            );
+        }
+        read += coins;
+        if read % 10_000_000 < coins {
+            println!("   {} / {} coins", read, metadata.coins_count);
+        }
+    }
+    anyhow::ensure!(
+        reader.read(&mut [0u8; 1])? == 0,
+        "trailing data after {} coins",
+        metadata.coins_count
+    );
+
+    let hash_serialized = display_hex(&Sha256::digest(hasher.finalize()).into());
+    anyhow::ensure!(
+        hash_serialized == params.hash_serialized,
+        "hash_serialized_3 mismatch: snapshot hashes to {}, expected {}",
+        hash_serialized,
+        params.hash_serialized
+    );
+    println!(
+        "✅ Snapshot verified in {:.1}s (hash_serialized_3 {})",
+        start.elapsed().as_secs_f64(),
+        hash_serialized
+    );
+
+    Ok(AssumeUtxoSnapshot {
+        metadata,
+        params,
+        utxo_set,
+    })
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_script(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let kind = read_varint_from(reader)?;
+    let len = compressed_script_len(kind);
+    if len > MAX_SCRIPT_SIZE {
+        let skipped = std::io::copy(&mut reader.take(len), &mut std::io::sink())?;
+        anyhow::ensure!(skipped == len, "truncated script");
+        return Ok(vec![0x6a]);
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut payload)
+        .context("truncated script")?;
+    decompress_script(kind, &payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two txids, three coins: a coinbase P2PKH (compressed) and two raw scripts.
+    fn snapshot_bytes() -> (Vec<u8>, String) {
+        let base_hash = [0x11u8; 32];
+        let mut file = Vec::new();
+        file.extend_from_slice(SNAPSHOT_MAGIC);
+        file.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        file.extend_from_slice(Network::Regtest.magic_bytes());
+        file.extend_from_slice(&base_hash);
+        file.extend_from_slice(&3u64.to_le_bytes());
+
+        // txid 0xaa.., one coinbase coin at height 5: 50 BTC to P2PKH
+        file.extend_from_slice(&[0xaa; 32]);
+        file.extend_from_slice(&[1, 0]); // 1 coin, vout 0
+        file.extend_from_slice(&[11]); // code = 5 * 2 + 1
+        file.extend_from_slice(&[0x32]); // CompressAmount(50 BTC) = 50
+        file.push(0);
+        file.extend_from_slice(&[0x22; 20]);
+        // txid 0xbb.., vouts 1 and 300 at height 7 (raw scripts, kind = len + 6)
+        file.extend_from_slice(&[0xbb; 32]);
+        file.push(2);
+        file.extend_from_slice(&[1, 14, 0x09, 8]); // vout 1, code, 1 BTC compressed, 2-byte script
+        file.extend_from_slice(&[0x51, 0x52]);
+        file.extend_from_slice(&[0xfd, 0x2c, 0x01, 14, 0x00, 6]); // vout 300, 0 sat, empty script
+
+        let mut ser = Vec::new();
+        let mut txout = |txid: [u8; 32], vout: u32, code: u32, value: i64, script: &[u8]| {
+            ser.extend_from_slice(&txid);
+            ser.extend_from_slice(&vout.to_le_bytes());
+            ser.extend_from_slice(&code.to_le_bytes());
+            ser.extend_from_slice(&value.to_le_bytes());
+            ser.push(script.len() as u8);
+            ser.extend_from_slice(script);
+        };
+        let p2pkh = [&[0x76, 0xa9, 20][..], &[0x22; 20], &[0x88, 0xac]].concat();
+        txout([0xaa; 32], 0, 11, 5_000_000_000, &p2pkh);
+        txout(
+            [0xbb; 32],
+            1,
+            14,
+            decompress_amount(9).unwrap() as i64,
+            &[0x51, 0x52],
+        );
+        txout([0xbb; 32], 300, 14, 0, &[]);
+        (file, display_hex(&sha256d(&ser)))
+    }
+
+    #[test]
+    fn parses_and_verifies_snapshot() {
+        let (file, hash) = snapshot_bytes();
+        let params = AssumeUtxoParams {
+            height: 7,
+            block_hash: "11".repeat(32),
+            hash_serialized: hash,
+            chain_tx: None,
+        };
+        let snapshot = read_snapshot(&file[..], Network::Regtest, Some(params.clone())).unwrap();
+        assert_eq!(snapshot.height(), 7);
+        assert_eq!(snapshot.utxo_set.len(), 3);
+        let coinbase = &snapshot.utxo_set[&OutPoint {
+            hash: [0xaa; 32],
+            index: 0,
+        }];
+        assert_eq!(coinbase.value, 5_000_000_000);
+        assert!(coinbase.is_coinbase);
+        assert_eq!(coinbase.script_pubkey.len(), 25);
+
+        // Wrong network, and a flipped script byte, are both rejected
+        assert!(read_snapshot(&file[..], Network::Mainnet, Some(params.clone())).is_err());
+        let mut corrupt = file.clone();
+        let len = corrupt.len();
+        corrupt[len - 7] ^= 1;
+        assert!(read_snapshot(&corrupt[..], Network::Regtest, Some(params)).is_err());
+    }
+}
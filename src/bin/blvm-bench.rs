@@ -1,16 +1,35 @@
 //! blvm-bench CLI tool
 //!
-//! Command-line interface for running benchmarks
+//! Command-line interface for running benchmarks, and (with the `chunk-cache` / `differential`
+//! features) for the block cache and differential pipeline: `collect`, `chunk`, `verify-cache`,
+//! `diff-run`, `activations`, `checkpoints`, `report`, `conformance` and `replay`; with `zmq`,
+//! `live` follows the chain tip, and with `s3-cache`, `s3 push` / `s3 pull` share the chunk cache
+//! through a bucket. `fixtures` lists and fetches the named benchmark blocks, and `fuzz-corpus`
+//! seeds the `fuzz/` corpora from them and from divergence artifacts.
 
 use anyhow::{Context, Result};
-use blvm_bench::shell;
+use blvm_bench::cancel::CancellationToken;
+use blvm_bench::progress::{self, Verbosity};
+use blvm_bench::{artifacts, registry, regression, shell, watch};
 use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "blvm-bench")]
 #[command(about = "Bitcoin Commons BLVM Benchmarking Suite")]
 struct Cli {
+    /// More log output (-v debug, -vv trace); RUST_LOG overrides
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only warnings and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Put results, reports, checkpoints, corpora and divergence bundles under this directory
+    /// (sets `BLVM_OUTPUT_DIR` for everything run from here)
+    #[arg(long, global = true)]
+    output_dir: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -18,6 +37,7 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Run Rust Criterion benchmarks
+    #[command(alias = "bench")]
     Rust {
         /// Benchmark name (optional, runs all if not specified)
         name: Option<String>,
@@ -33,19 +53,351 @@ enum Commands {
         /// Run specific benchmark suite
         #[arg(long)]
         suite: Option<String>,
+        /// Run the suite described by a TOML file (commands, env, iterations, timeouts)
+        #[arg(long)]
+        config: Option<PathBuf>,
         /// Run specific benchmark script
         script: Option<String>,
     },
+    /// Re-run benches / tests whenever the local blvm-consensus checkout changes
+    Watch {
+        /// Criterion bench target to re-run (repeatable)
+        #[arg(long = "bench")]
+        benches: Vec<String>,
+        /// Integration test target to re-run, e.g. a differential suite (repeatable)
+        #[arg(long = "test")]
+        tests: Vec<String>,
+        /// Cargo features for the benches and tests (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+        /// blvm-consensus checkout to watch (default: the `[patch.crates-io]` path)
+        #[arg(long)]
+        consensus_dir: Option<PathBuf>,
+        /// Poll interval in milliseconds
+        #[arg(long, default_value_t = 500)]
+        interval_ms: u64,
+    },
+    /// Compare Criterion results with a committed baseline, or record a new one
+    Regression {
+        #[command(subcommand)]
+        action: RegressionAction,
+        /// Machine profile (default: `BLVM_BENCH_PROFILE`, else OS / arch / CPU count)
+        #[arg(long, global = true)]
+        profile: Option<String>,
+        /// Baseline directory (default: `BLVM_BASELINES_DIR`, else `baselines/`)
+        #[arg(long, global = true)]
+        baselines_dir: Option<PathBuf>,
+        /// Criterion output directory (default: `target/criterion`)
+        #[arg(long, global = true)]
+        criterion_dir: Option<PathBuf>,
+    },
+    /// Run registered benchmarks (Criterion targets and shell scripts) by name and tag
+    Run {
+        /// Name substring to select (repeatable; default: all)
+        #[arg(long = "name")]
+        names: Vec<String>,
+        /// Tag to select, e.g. criterion, shell or suite (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// List the matching benchmarks instead of running them
+        #[arg(long)]
+        list: bool,
+        /// Record each benchmark with `perf` and write a flamegraph SVG next to the timings
+        /// (needs the `flamegraph` feature)
+        #[arg(long)]
+        profile: bool,
+    },
     /// Run all benchmarks (Rust + Shell)
     All {
         /// Enable production mode for Rust benchmarks
         #[arg(long)]
         production: bool,
     },
+    /// Show a benchmark's timings across recorded runs and commits (lists benchmarks without
+    /// `--name`)
+    #[cfg(feature = "results-db")]
+    History {
+        /// Benchmark name, e.g. `criterion/hash_operations` or a Criterion id
+        #[arg(long)]
+        name: Option<String>,
+        /// Machine profile (default: `BLVM_BENCH_PROFILE`, else OS / arch / CPU count)
+        #[arg(long)]
+        profile: Option<String>,
+        /// Results database (default: `BLVM_RESULTS_DB`, else `results.db` in the results
+        /// directory)
+        #[arg(long)]
+        db: Option<PathBuf>,
+        /// One line per run instead of one per commit, the last N runs
+        #[arg(long)]
+        runs: Option<usize>,
+        /// Also write the per-commit means as CSV
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+    /// Read Core's block files into the chunk cache without validating
+    #[cfg(feature = "differential")]
+    Collect {
+        /// Bitcoin Core data directory (auto-detected when omitted)
+        #[arg(long)]
+        datadir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = NetworkArg::Mainnet)]
+        network: NetworkArg,
+    },
+    /// Show the chunk cache: metadata, chunk files and manifest coverage
+    #[cfg(feature = "chunk-cache")]
+    Chunk {
+        /// Chunks directory (default: the one under `BLOCK_CACHE_DIR`)
+        #[arg(long)]
+        chunks_dir: Option<PathBuf>,
+    },
+    /// Check every chunk file against `chunks.manifest`
+    #[cfg(feature = "chunk-cache")]
+    VerifyCache {
+        /// Chunks directory (default: the one under `BLOCK_CACHE_DIR`)
+        #[arg(long)]
+        chunks_dir: Option<PathBuf>,
+        /// Compare sizes only and skip hashing
+        #[arg(long)]
+        size_only: bool,
+    },
+    /// Named benchmark blocks and transactions: list them or fetch the missing ones
+    #[cfg(feature = "differential")]
+    Fixtures {
+        #[command(subcommand)]
+        action: FixturesAction,
+    },
+    /// Seed the `fuzz/` corpora from divergence artifacts, fetched fixtures and CompactSize edges
+    #[cfg(feature = "differential")]
+    FuzzCorpus {
+        /// Corpus root (default: `BLVM_FUZZ_CORPUS`, else `fuzz/corpus`)
+        #[arg(long)]
+        corpus: Option<PathBuf>,
+        /// Directories of divergence artifacts (script, block and mempool) to add
+        #[arg(long)]
+        artifacts: Vec<PathBuf>,
+    },
+    /// Share the chunk cache through an S3-compatible bucket (`BLVM_S3_BUCKET`, `AWS_*`)
+    #[cfg(feature = "s3-cache")]
+    S3 {
+        #[command(subcommand)]
+        action: S3Action,
+    },
+    /// Differential validation of a height range against already-collected blocks
+    #[cfg(feature = "differential")]
+    DiffRun {
+        /// First height to validate
+        #[arg(long)]
+        start: u64,
+        /// Last height to validate (inclusive)
+        #[arg(long)]
+        end: u64,
+        /// Read blocks from this Core data directory instead of the configured sources
+        #[arg(long)]
+        datadir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = NetworkArg::Mainnet)]
+        network: NetworkArg,
+        /// Parallel workers (default: all cores)
+        #[arg(long)]
+        workers: Option<usize>,
+        /// Blocks per validation chunk
+        #[arg(long)]
+        chunk_size: Option<u64>,
+        /// Replay from genesis instead of starting chunks from UTXO checkpoints
+        #[arg(long)]
+        no_checkpoints: bool,
+        /// Keep results under a resumable run directory in this root
+        #[arg(long)]
+        run_root: Option<PathBuf>,
+        /// Write the JSON / CSV report here
+        #[arg(long)]
+        report_dir: Option<PathBuf>,
+        /// Reproducible run: fixed default worker count, height-ordered chunk reporting, seeded
+        /// randomness (`BLVM_SEED`) and reports without timings, for diffing two runs
+        #[arg(long)]
+        deterministic: bool,
+    },
+    /// Differential validation around each soft-fork activation height only
+    #[cfg(feature = "differential")]
+    Activations {
+        /// Activations to check, e.g. `bip66,segwit` (default: all)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+        /// Blocks validated on each side of an activation height
+        #[arg(long, default_value_t = blvm_bench::activation_matrix::DEFAULT_WINDOW)]
+        window: u64,
+        /// Read blocks from this Core data directory instead of the configured sources
+        #[arg(long)]
+        datadir: Option<PathBuf>,
+        /// UTXO checkpoints to start windows from (default: `BLVM_CHECKPOINT_DIR`)
+        #[arg(long)]
+        checkpoint_dir: Option<PathBuf>,
+    },
+    /// List stored UTXO checkpoints
+    #[cfg(feature = "differential")]
+    Checkpoints {
+        /// Checkpoint directory (default: `BLVM_CHECKPOINT_DIR`, else under the output directory)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Summarise a saved differential report; fails if the run did not pass
+    #[cfg(feature = "differential")]
+    Report {
+        /// `differential_report.json`, or the directory holding it
+        path: PathBuf,
+        /// Also write an HTML page with charts (written before the pass / fail check)
+        #[arg(long)]
+        html: Option<PathBuf>,
+        /// Add the timings of this `benchmarks.json` to the HTML page
+        #[arg(long, requires = "html")]
+        benchmarks: Option<PathBuf>,
+        /// Add the Criterion results against this machine's baseline to the HTML page
+        #[arg(long, requires = "html")]
+        baseline: bool,
+    },
+    /// Run Core's JSON test vectors (`tx_valid`, `tx_invalid`, `script_tests`, `sighash`)
+    /// through BLVM; fails if any vector does
+    #[cfg(feature = "differential")]
+    Conformance {
+        /// Directory holding the JSON files, e.g. a copy of Core's `src/test/data`
+        #[arg(long, env = "BLVM_CORE_TEST_DATA", default_value = "tests/data")]
+        dir: PathBuf,
+        /// Write the pass / fail matrix (suite x tag) as CSV, or Parquet for a `.parquet` path
+        #[arg(long)]
+        matrix: Option<PathBuf>,
+        /// Write the full results, kept failures included, as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+    },
+    /// Validate one saved divergence (script, block or mempool artifact) again, with debug logging
+    #[cfg(feature = "differential")]
+    Replay {
+        /// Artifact JSON from `divergences/`
+        artifact: PathBuf,
+        /// Block cache with the block's spent-outputs sidecar, for block artifacts saved without
+        /// them (default: `BLOCK_CACHE_DIR`)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+        /// Also submit the transaction / block to the Core node from `BITCOIN_RPC_*`
+        #[arg(long)]
+        core: bool,
+        /// Exit with an error while BLVM and Core still disagree
+        #[arg(long)]
+        check: bool,
+    },
+    /// Validate each new block Core publishes over ZMQ, until interrupted
+    #[cfg(feature = "zmq")]
+    Live {
+        /// Core's `-zmqpubrawblock` endpoint
+        #[arg(
+            long,
+            env = "BITCOIN_ZMQ_RAWBLOCK",
+            default_value = "tcp://127.0.0.1:28332"
+        )]
+        zmq_block: String,
+        /// Core's `-zmqpubrawtx` endpoint; transactions are counted, not validated
+        #[arg(long, env = "BITCOIN_ZMQ_RAWTX")]
+        zmq_tx: Option<String>,
+        /// Exit with an error at the first divergence
+        #[arg(long)]
+        stop_on_divergence: bool,
+    },
+}
+
+/// `--network` values
+#[cfg(feature = "differential")]
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum NetworkArg {
+    Mainnet,
+    Testnet,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+#[cfg(feature = "differential")]
+impl From<NetworkArg> for blvm_bench::block_file_reader::Network {
+    fn from(network: NetworkArg) -> Self {
+        match network {
+            NetworkArg::Mainnet => Self::Mainnet,
+            NetworkArg::Testnet => Self::Testnet,
+            NetworkArg::Testnet4 => Self::Testnet4,
+            NetworkArg::Signet => Self::Signet,
+            NetworkArg::Regtest => Self::Regtest,
+        }
+    }
+}
+
+#[cfg(feature = "differential")]
+#[derive(Subcommand)]
+enum FixturesAction {
+    /// Show every fixture and whether it has been fetched
+    List,
+    /// Fetch and verify fixtures from the configured block source (all when no name is given)
+    Fetch {
+        /// Fixture names
+        names: Vec<String>,
+    },
+}
+
+#[cfg(feature = "s3-cache")]
+#[derive(Subcommand)]
+enum S3Action {
+    /// Upload chunk files and metadata; chunks already in the bucket with the same size are skipped
+    Push {
+        /// Chunks directory (default: the one under `BLOCK_CACHE_DIR`)
+        #[arg(long)]
+        chunks_dir: Option<PathBuf>,
+    },
+    /// Download the chunk metadata; chunks are fetched on first use unless `--all`
+    Pull {
+        /// Chunks directory to create (default: `BLOCK_CACHE_DIR` / `chunks.dir`)
+        #[arg(long)]
+        chunks_dir: Option<PathBuf>,
+        /// Download every chunk now, into `BLVM_S3_CACHE_DIR`
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegressionAction {
+    /// Save the latest Criterion results as the profile's baseline
+    Record,
+    /// Fail if any benchmark is slower than the baseline by more than the threshold
+    Check {
+        /// Allowed slowdown in percent
+        #[arg(long, env = "BLVM_REGRESSION_THRESHOLD", default_value_t = regression::DEFAULT_THRESHOLD_PCT)]
+        threshold: f64,
+    },
+    /// Print the delta against the baseline without failing
+    Report {
+        /// Slowdown / speedup in percent marked as a change
+        #[arg(long, env = "BLVM_REGRESSION_THRESHOLD", default_value_t = regression::DEFAULT_THRESHOLD_PCT)]
+        threshold: f64,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    // The point of a replay is BLVM's own logging of the failing check
+    #[cfg(feature = "differential")]
+    let replaying = matches!(cli.command, Commands::Replay { .. });
+    #[cfg(not(feature = "differential"))]
+    let replaying = false;
+    progress::init(Verbosity::from_flags(
+        cli.quiet,
+        cli.verbose.max(u8::from(replaying)),
+    ));
+    if let Some(dir) = &cli.output_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory {}", dir.display()))?;
+        // Absolute, since cargo bench runs its targets from the package directory
+        let dir = dir.canonicalize()?;
+        // Inherited by cargo bench, the shell suites and whatever they start
+        std::env::set_var("BLVM_OUTPUT_DIR", &dir);
+        artifacts::ArtifactLayout::from_env()?;
+        println!("📁 Output directory: {}", dir.display());
+    }
 
     match cli.command {
         Commands::Rust { name, production } => {
@@ -76,8 +428,15 @@ fn main() -> Result<()> {
                 anyhow::bail!("Benchmark execution failed");
             }
         }
-        Commands::Shell { all, suite, script } => {
-            if all {
+        Commands::Shell {
+            all,
+            suite,
+            config,
+            script,
+        } => {
+            if let Some(config) = config {
+                shell::run_suite(&config)?.ensure_passed()?;
+            } else if all {
                 shell::run_all()?;
             } else if let Some(suite) = suite {
                 println!("Running suite: {}", suite);
@@ -85,7 +444,84 @@ fn main() -> Result<()> {
             } else if let Some(script) = script {
                 shell::run_benchmark(&script)?;
             } else {
-                println!("Please specify --all, --suite, or a script name");
+                println!("Please specify --all, --suite, --config, or a script name");
+            }
+        }
+        Commands::Watch {
+            benches,
+            tests,
+            features,
+            consensus_dir,
+            interval_ms,
+        } => {
+            let consensus_dir = match consensus_dir {
+                Some(dir) => dir,
+                None => watch::consensus_dir_from_manifest(Path::new(env!("CARGO_MANIFEST_DIR")))?,
+            };
+            let mut config = watch::WatchConfig::new(consensus_dir);
+            config.benches = benches;
+            config.tests = tests;
+            config.features = features;
+            config.poll_interval = Duration::from_millis(interval_ms);
+            watch::watch(&config, &CancellationToken::new())?;
+        }
+        Commands::Regression {
+            action,
+            profile,
+            baselines_dir,
+            criterion_dir,
+        } => {
+            let profile = profile.unwrap_or_else(regression::machine_profile);
+            let baselines_dir = baselines_dir.unwrap_or_else(regression::baselines_dir);
+            let criterion_dir = criterion_dir.unwrap_or_else(regression::criterion_dir);
+            match action {
+                RegressionAction::Record => {
+                    let baseline = regression::record_baseline(&profile, &criterion_dir)?;
+                    let path = baseline.save(&baselines_dir)?;
+                    println!(
+                        "✅ Recorded {} benchmark(s) as baseline {} ({})",
+                        baseline.means_ns.len(),
+                        profile,
+                        path.display()
+                    );
+                }
+                RegressionAction::Check { threshold } | RegressionAction::Report { threshold } => {
+                    let baseline = regression::Baseline::load(&baselines_dir, &profile)?
+                        .with_context(|| {
+                            format!(
+                                "No baseline for profile {} in {} - record one with `blvm-bench regression record`",
+                                profile,
+                                baselines_dir.display()
+                            )
+                        })?;
+                    let current = regression::current_means(&criterion_dir)?;
+                    let report = regression::compare(&baseline, &current, threshold);
+                    print!("{}", report.render());
+                    if matches!(action, RegressionAction::Check { .. }) {
+                        report.ensure_no_regressions()?;
+                        println!("✅ No regressions beyond {:.1}%", threshold);
+                    }
+                }
+            }
+        }
+        Commands::Run {
+            names,
+            tags,
+            list,
+            profile,
+        } => {
+            let filter = registry::BenchFilter { names, tags };
+            if list {
+                for bench in blvm_bench::default_registry()?.filter(&filter) {
+                    println!("{:<50} {}", bench.name, bench.tags.join(","));
+                }
+            } else if profile {
+                #[cfg(feature = "flamegraph")]
+                blvm_bench::run_profiled(&filter)?.ensure_passed()?;
+                #[cfg(not(feature = "flamegraph"))]
+                anyhow::bail!("--profile needs a build with --features flamegraph");
+            } else {
+                blvm_bench::run_filtered(&filter)?.ensure_passed()?;
             }
         }
         Commands::All { production } => {
@@ -113,7 +549,586 @@ fn main() -> Result<()> {
 
             println!("\n✅ All benchmarks completed!");
         }
+        #[cfg(feature = "results-db")]
+        Commands::History {
+            name,
+            profile,
+            db,
+            runs,
+            csv,
+        } => {
+            use blvm_bench::results_db::{self, ResultsDb};
+
+            let profile = profile.unwrap_or_else(regression::machine_profile);
+            let db = ResultsDb::open(&db.unwrap_or_else(results_db::db_path_from_env))?;
+            let Some(name) = name else {
+                for bench in db.benchmarks(&profile)? {
+                    println!("{}", bench);
+                }
+                return Ok(());
+            };
+            if let Some(last) = runs {
+                for p in db.trend(&name, &profile, Some(last))? {
+                    println!(
+                        "{:>6}  {}  {:<12} {:>12}  config {}",
+                        p.run_id,
+                        p.recorded_at,
+                        p.git_commit.as_deref().unwrap_or("-"),
+                        watch::format_ns(p.mean_secs * 1e9),
+                        p.config_hash
+                    );
+                }
+            } else {
+                let points = db.trend_by_commit(&name, &profile)?;
+                anyhow::ensure!(
+                    !points.is_empty(),
+                    "No runs of {} recorded for profile {}",
+                    name,
+                    profile
+                );
+                let first = points[0].mean_secs;
+                for p in &points {
+                    println!(
+                        "{:<12} {}  {:>3} run(s)  {:>12}  {:+.1}%",
+                        p.git_commit,
+                        p.first_recorded_at,
+                        p.runs,
+                        watch::format_ns(p.mean_secs * 1e9),
+                        (p.mean_secs / first - 1.0) * 100.0
+                    );
+                }
+            }
+            if let Some(csv) = csv {
+                results_db::write_commit_csv(&csv, &db.trend_by_commit(&name, &profile)?)?;
+                println!("📝 Trend written to {}", csv.display());
+            }
+        }
+        #[cfg(feature = "differential")]
+        Commands::Collect { datadir, network } => {
+            use blvm_bench::collect_only::{collect_blocks, CollectionConfig};
+
+            let shutdown = blvm_bench::shutdown::ShutdownController::install()?;
+            let collected = collect_blocks(CollectionConfig {
+                data_dir: datadir,
+                network: network.into(),
+                cancel: shutdown.token(),
+            });
+            match collected {
+                // The resume marker summary is already printed
+                Err(e) if blvm_bench::cancel::is_cancelled(&e) => {
+                    std::process::exit(blvm_bench::shutdown::EXIT_INTERRUPTED)
+                }
+                result => {
+                    result?;
+                }
+            }
+        }
+        #[cfg(feature = "chunk-cache")]
+        Commands::Chunk { chunks_dir } => show_chunks(&resolve_chunks_dir(chunks_dir)?)?,
+        #[cfg(feature = "chunk-cache")]
+        Commands::VerifyCache {
+            chunks_dir,
+            size_only,
+        } => verify_cache(&resolve_chunks_dir(chunks_dir)?, size_only)?,
+        #[cfg(feature = "differential")]
+        Commands::Fixtures { action } => fixtures(action)?,
+        #[cfg(feature = "differential")]
+        Commands::FuzzCorpus { corpus, artifacts } => {
+            use blvm_bench::fixtures::FixtureStore;
+            use blvm_bench::fuzzing::CorpusWriter;
+
+            let mut writer = corpus
+                .map(CorpusWriter::new)
+                .unwrap_or_else(CorpusWriter::from_env);
+            for dir in &artifacts {
+                let read = writer.add_artifact_dir(dir)?;
+                println!("📄 {} artifact(s) from {}", read, dir.display());
+            }
+            let store = FixtureStore::from_env();
+            let fixtures = writer.add_fixtures(&store)?;
+            println!(
+                "📦 {} fetched fixture(s) from {}",
+                fixtures,
+                store.dir().display()
+            );
+            writer.add_compact_size_seeds()?;
+            writer.print();
+        }
+        #[cfg(feature = "s3-cache")]
+        Commands::S3 { action } => s3_transfer(action)?,
+        #[cfg(feature = "differential")]
+        Commands::DiffRun {
+            start,
+            end,
+            datadir,
+            network,
+            workers,
+            chunk_size,
+            no_checkpoints,
+            run_root,
+            report_dir,
+            deterministic,
+        } => {
+            use blvm_bench::collect_only::{validate_range, ValidationConfig};
+            use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+            use blvm_bench::parallel_differential::{
+                create_block_data_source, BlockDataSource, BlockFileReader,
+            };
+            use std::sync::Arc;
+
+            anyhow::ensure!(end >= start, "--end must be >= --start");
+            if deterministic {
+                blvm_bench::determinism::enable();
+            }
+            let network = network.into();
+            let source = match datadir {
+                Some(dir) => BlockDataSource::DirectFile(BlockFileReader::new(dir, network)?),
+                None => create_block_data_source(
+                    network,
+                    blvm_bench::block_cache_env::block_cache_dir_from_env(),
+                    Some(Arc::new(NodeRpcClient::new(RpcConfig::from_env()))),
+                )?,
+            };
+            println!("📦 Block source: {}", source.describe());
+            let mut config = ValidationConfig::new(start, end, Arc::new(source));
+            config.parallel.num_workers = blvm_bench::determinism::worker_count(workers);
+            if let Some(chunk_size) = chunk_size {
+                config.parallel.chunk_size = chunk_size.max(1);
+            }
+            config.parallel.use_checkpoints = !no_checkpoints;
+            config.run_root = run_root;
+            if report_dir.is_some() {
+                config.report_dir = report_dir;
+            }
+            let report = tokio::runtime::Runtime::new()?.block_on(validate_range(config))?;
+            if !report.all_matched() {
+                anyhow::bail!(
+                    "{} divergence(s), {}/{} blocks matched",
+                    report.divergences,
+                    report.matched,
+                    report.tested
+                );
+            }
+        }
+        #[cfg(feature = "differential")]
+        Commands::Activations {
+            only,
+            window,
+            datadir,
+            checkpoint_dir,
+        } => activation_matrix(only, window, datadir, checkpoint_dir)?,
+        #[cfg(feature = "differential")]
+        Commands::Checkpoints { dir } => {
+            let dir = dir
+                .or_else(blvm_bench::checkpoint_store::checkpoint_dir_from_env)
+                .context("Pass --dir or set BLVM_CHECKPOINT_DIR / BLVM_OUTPUT_DIR")?;
+            let store = blvm_bench::checkpoint_store::CheckpointStore::new(
+                &dir,
+                blvm_bench::block_file_reader::Network::Mainnet,
+            )?;
+            let heights = store.heights()?;
+            println!(
+                "💾 {} checkpoint(s) in {}",
+                heights.len(),
+                store.dir().display()
+            );
+            for height in heights {
+                let size = std::fs::metadata(store.path(height))
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                println!("   {:>9}  {:>12} bytes", height, size);
+            }
+        }
+        #[cfg(feature = "differential")]
+        Commands::Report {
+            path,
+            html,
+            benchmarks,
+            baseline,
+        } => {
+            let path = if path.is_dir() {
+                path.join(blvm_bench::report::JSON_FILE)
+            } else {
+                path
+            };
+            let report: blvm_bench::report::DifferentialReport = serde_json::from_slice(
+                &std::fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+            )
+            .with_context(|| format!("Invalid report {}", path.display()))?;
+            println!(
+                "📊 Blocks {}..={}: {}/{} matched, {} chunk(s), {:.1}s ({})",
+                report.start_height,
+                report.end_height,
+                report.matched,
+                report.tested,
+                report.chunks.len(),
+                report.duration_secs,
+                report.consensus
+            );
+            for entry in report.divergence_entries.iter().take(20) {
+                println!(
+                    "   {:>8} {}: BLVM {} / Core {}",
+                    entry.height,
+                    entry.severity.name(),
+                    entry.blvm,
+                    entry.core
+                );
+            }
+            if report.divergence_entries.len() > 20 {
+                println!("   ... {} more", report.divergence_entries.len() - 20);
+            }
+            if let Some(html) = html {
+                write_html_report(&html, &report, benchmarks.as_deref(), baseline)?;
+            }
+            report.ensure_passed()?;
+            println!("✅ Passed");
+        }
+        #[cfg(feature = "differential")]
+        Commands::Conformance { dir, matrix, json } => {
+            use blvm_bench::conformance::run_conformance;
+
+            let results = run_conformance(&dir)?;
+            anyhow::ensure!(
+                !results.suites.is_empty(),
+                "No Core test vectors in {} - copy bitcoin/src/test/data there or pass --dir",
+                dir.display()
+            );
+            results.log_summary();
+            if let Some(path) = matrix {
+                blvm_bench::deep_analysis::dataset::write(&results, &path)?;
+                println!("📄 Matrix written to {}", path.display());
+            }
+            if let Some(path) = json {
+                std::fs::write(&path, serde_json::to_vec_pretty(&results)?)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            if !results.passed() {
+                anyhow::bail!("{} conformance vector(s) failed", results.failed());
+            }
+            println!("✅ All {} suite(s) passed", results.suites.len());
+        }
+        #[cfg(feature = "differential")]
+        Commands::Replay {
+            artifact,
+            cache_dir,
+            core,
+            check,
+        } => {
+            use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+            use blvm_bench::replay::{self, Artifact};
+
+            let artifact = Artifact::load(&artifact)?;
+            let cache = cache_dir
+                .or_else(blvm_bench::block_cache_env::block_cache_dir_from_env)
+                .filter(|dir| dir.is_dir())
+                .map(blvm_bench::block_file_reader::SharedBlockCache::new)
+                .transpose()?;
+            println!("🔁 Replaying {}", artifact.describe());
+            let outcome = replay::replay(&artifact, cache.as_ref())?;
+            outcome.print();
+            if core {
+                let client = NodeRpcClient::new(RpcConfig::from_env());
+                let verdict = tokio::runtime::Runtime::new()?
+                    .block_on(replay::submit_to_core(&artifact, &client))?;
+                println!("   Core node: {}", verdict);
+            }
+            if check && outcome.still_diverges() {
+                anyhow::bail!("BLVM and Core still disagree on {}", outcome.description);
+            }
+        }
+        #[cfg(feature = "zmq")]
+        Commands::Live {
+            zmq_block,
+            zmq_tx,
+            stop_on_divergence,
+        } => {
+            use blvm_bench::zmq_listener::{self, LiveConfig};
+
+            let mut config = LiveConfig::from_env()?.with_stop_on_divergence(stop_on_divergence);
+            config.block_endpoint = zmq_block;
+            config.tx_endpoint = zmq_tx;
+            let stats = tokio::runtime::Runtime::new()?
+                .block_on(zmq_listener::run(config, CancellationToken::new()))?;
+            println!(
+                "📡 {}/{} live blocks matched, {} divergences",
+                stats.matched,
+                stats.blocks,
+                stats.divergences.len()
+            );
+        }
     }
 
     Ok(())
 }
+
+/// `--chunks-dir`, else the chunks directory under `BLOCK_CACHE_DIR`.
+#[cfg(feature = "chunk-cache")]
+fn resolve_chunks_dir(chunks_dir: Option<PathBuf>) -> Result<PathBuf> {
+    chunks_dir
+        .or_else(blvm_bench::chunked_cache::get_chunks_dir)
+        .filter(|dir| dir.is_dir())
+        .context("Chunks directory not found - pass --chunks-dir or set BLOCK_CACHE_DIR")
+}
+
+/// Chunk numbers of the `chunk_N.bin.zst` files in `chunks_dir`, ascending.
+#[cfg(feature = "chunk-cache")]
+fn chunk_files(chunks_dir: &Path) -> Result<Vec<u64>> {
+    let mut chunks: Vec<u64> = std::fs::read_dir(chunks_dir)
+        .with_context(|| format!("Failed to list {}", chunks_dir.display()))?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?
+                .strip_prefix("chunk_")?
+                .strip_suffix(".bin.zst")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    chunks.sort_unstable();
+    Ok(chunks)
+}
+
+#[cfg(feature = "chunk-cache")]
+fn show_chunks(chunks_dir: &Path) -> Result<()> {
+    use blvm_bench::chunk_manifest::{chunk_path, Manifest};
+
+    println!("📁 Chunks: {}", chunks_dir.display());
+    match blvm_bench::chunked_cache::load_chunk_metadata(chunks_dir)? {
+        Some(meta) => println!(
+            "   {} blocks in {} chunk(s) of {} ({})",
+            meta.total_blocks, meta.num_chunks, meta.blocks_per_chunk, meta.compression
+        ),
+        None => println!("   ⚠️  No chunk metadata"),
+    }
+    let manifest = Manifest::load(chunks_dir)?.unwrap_or_default();
+    for chunk in chunk_files(chunks_dir)? {
+        let size = std::fs::metadata(chunk_path(chunks_dir, chunk))?.len();
+        match manifest.get(chunk) {
+            Some(entry) => println!(
+                "   chunk {:>4}  {:>14} bytes  blocks {}..={}",
+                chunk, size, entry.first_block, entry.last_block
+            ),
+            None => println!(
+                "   chunk {:>4}  {:>14} bytes  (no manifest entry)",
+                chunk, size
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// HTML page for `report --html`: the differential report, plus saved benchmark timings and
+/// the baseline comparison when asked for.
+#[cfg(feature = "differential")]
+fn write_html_report(
+    path: &Path,
+    report: &blvm_bench::report::DifferentialReport,
+    benchmarks: Option<&Path>,
+    baseline: bool,
+) -> Result<()> {
+    use blvm_bench::report::html::HtmlReport;
+
+    let mut page = HtmlReport::new(format!(
+        "Differential run {}..={}",
+        report.start_height, report.end_height
+    ))
+    .with_differential(report.clone());
+    if let Some(benchmarks) = benchmarks {
+        let run: registry::RunReport = serde_json::from_slice(
+            &std::fs::read(benchmarks)
+                .with_context(|| format!("Failed to read {}", benchmarks.display()))?,
+        )
+        .with_context(|| format!("Invalid benchmark report {}", benchmarks.display()))?;
+        page = page.with_benchmarks(run);
+    }
+    if baseline {
+        let profile = regression::machine_profile();
+        match regression::Baseline::load(&regression::baselines_dir(), &profile)? {
+            Some(base) => {
+                let current = regression::current_means(&regression::criterion_dir())?;
+                page = page.with_regression(regression::compare(
+                    &base,
+                    &current,
+                    regression::DEFAULT_THRESHOLD_PCT,
+                ));
+            }
+            None => eprintln!("⚠️  No baseline for profile {}, leaving it out", profile),
+        }
+    }
+    page.write(path)?;
+    println!("📝 HTML report written to {}", path.display());
+    Ok(())
+}
+
+#[cfg(feature = "differential")]
+fn activation_matrix(
+    only: Vec<String>,
+    window: u64,
+    datadir: Option<PathBuf>,
+    checkpoint_dir: Option<PathBuf>,
+) -> Result<()> {
+    use blvm_bench::activation_matrix::{activation, run_activation_matrix, MatrixConfig};
+    use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+    use blvm_bench::parallel_differential::{
+        create_block_data_source, BlockDataSource, BlockFileReader,
+    };
+    use blvm_bench::validation_hooks::HookRegistry;
+    use std::sync::Arc;
+
+    let network = blvm_bench::block_file_reader::Network::Mainnet;
+    let source = match datadir {
+        Some(dir) => BlockDataSource::DirectFile(BlockFileReader::new(dir, network)?),
+        None => create_block_data_source(
+            network,
+            blvm_bench::block_cache_env::block_cache_dir_from_env(),
+            Some(Arc::new(NodeRpcClient::new(RpcConfig::from_env()))),
+        )?,
+    };
+    println!("📦 Block source: {}", source.describe());
+    let mut config = MatrixConfig::new(
+        checkpoint_dir.or_else(blvm_bench::checkpoint_store::checkpoint_dir_from_env),
+    );
+    config.window = window;
+    config.hooks = HookRegistry::from_env()?;
+    if !only.is_empty() {
+        config.activations = only
+            .iter()
+            .map(|name| {
+                activation(name)
+                    .copied()
+                    .with_context(|| format!("No activation named {}", name))
+            })
+            .collect::<Result<_>>()?;
+    }
+    let report = tokio::runtime::Runtime::new()?.block_on(run_activation_matrix(
+        config,
+        Arc::new(source),
+        CancellationToken::new(),
+    ))?;
+    anyhow::ensure!(report.passed(), "BLVM and Core differ around an activation");
+    Ok(())
+}
+
+#[cfg(feature = "differential")]
+fn fixtures(action: FixturesAction) -> Result<()> {
+    use blvm_bench::fixtures::{fixture, FixtureStore, FIXTURES};
+    use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+    use blvm_bench::parallel_differential::create_block_data_source;
+    use std::sync::Arc;
+
+    let store = FixtureStore::from_env();
+    match action {
+        FixturesAction::List => {
+            println!("📂 Fixtures in {}", store.dir().display());
+            for fixture in FIXTURES {
+                let state = match store.load(fixture) {
+                    Ok(Some(bytes)) => format!("{} bytes", bytes.len()),
+                    Ok(None) => "not fetched".to_string(),
+                    Err(e) => format!("❌ {:#}", e),
+                };
+                println!(
+                    "   {:<26} {:>7}  {:<14} {}",
+                    fixture.name, fixture.height, state, fixture.description
+                );
+            }
+        }
+        FixturesAction::Fetch { names } => {
+            let selected = if names.is_empty() {
+                FIXTURES.iter().collect()
+            } else {
+                names
+                    .iter()
+                    .map(|name| fixture(name).with_context(|| format!("No fixture named {}", name)))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let source = create_block_data_source(
+                blvm_bench::block_file_reader::Network::Mainnet,
+                blvm_bench::block_cache_env::block_cache_dir_from_env(),
+                Some(Arc::new(NodeRpcClient::new(RpcConfig::from_env()))),
+            )?;
+            println!("📦 Block source: {}", source.describe());
+            let runtime = tokio::runtime::Runtime::new()?;
+            for fixture in selected {
+                let bytes = runtime.block_on(store.get(fixture, &source))?;
+                println!("✅ {} (height {}): {} bytes", fixture.name, fixture.height, bytes.len());
+            }
+            println!("📂 Fixtures in {}", store.dir().display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "s3-cache")]
+fn s3_transfer(action: S3Action) -> Result<()> {
+    use blvm_bench::s3_cache::{S3ChunkStore, S3Config};
+
+    let config = S3Config::from_env()?.context("Set BLVM_S3_BUCKET to use the S3 chunk cache")?;
+    let store = S3ChunkStore::new(config)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let (verb, stats) = match action {
+        S3Action::Push { chunks_dir } => {
+            let chunks_dir = resolve_chunks_dir(chunks_dir)?;
+            println!(
+                "☁️  Pushing {} to s3://{}/{}",
+                chunks_dir.display(),
+                store.config().bucket,
+                store.config().prefix
+            );
+            ("Uploaded", runtime.block_on(store.push(&chunks_dir))?)
+        }
+        S3Action::Pull { chunks_dir, all } => {
+            let chunks_dir = chunks_dir
+                .or_else(blvm_bench::config::chunk_dir)
+                .context("Pass --chunks-dir or set BLOCK_CACHE_DIR")?;
+            println!(
+                "☁️  Pulling s3://{}/{} into {}",
+                store.config().bucket,
+                store.config().prefix,
+                chunks_dir.display()
+            );
+            ("Downloaded", runtime.block_on(store.pull(&chunks_dir, all))?)
+        }
+    };
+    println!(
+        "✅ {} {} file(s), {} bytes ({} already up to date)",
+        verb, stats.files, stats.bytes, stats.skipped
+    );
+    Ok(())
+}
+
+#[cfg(feature = "chunk-cache")]
+fn verify_cache(chunks_dir: &Path, size_only: bool) -> Result<()> {
+    use blvm_bench::chunk_manifest::{chunk_path, Manifest, VerifyMode};
+
+    let manifest = Manifest::load(chunks_dir)?
+        .with_context(|| format!("No chunks.manifest in {}", chunks_dir.display()))?;
+    let mode = if size_only {
+        VerifyMode::Size
+    } else {
+        VerifyMode::Full
+    };
+    let files = chunk_files(chunks_dir)?;
+    let mut failed = 0;
+    for entry in manifest.entries.values() {
+        let path = chunk_path(chunks_dir, entry.chunk_num);
+        match entry.check(&path, mode) {
+            Ok(()) => println!("   ✅ chunk {}", entry.chunk_num),
+            Err(e) => {
+                println!("   ❌ chunk {}: {:#}", entry.chunk_num, e);
+                failed += 1;
+            }
+        }
+    }
+    for chunk in files.iter().filter(|c| manifest.get(**c).is_none()) {
+        println!("   ⚠️  chunk {} has no manifest entry", chunk);
+    }
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} chunk(s) failed verification",
+        failed,
+        manifest.entries.len()
+    );
+    println!("✅ {} chunk(s) verified", manifest.entries.len());
+    Ok(())
+}
@@ -1,5 +1,5 @@
 //! Build hash map from chunks WITHOUT chaining - OPTIMIZED
-//! Uses parallel chunk processing and multi-threaded decompression
+//! Uses parallel chunk processing
 
 use anyhow::Result;
 use rayon::prelude::*;
@@ -63,13 +63,12 @@ fn main() -> Result<()> {
             return;
         }
 
-        println!("   📦 Processing chunk {} (MT decompression)...", chunk_num);
+        println!("   📦 Processing chunk {}...", chunk_num);
         let _ = std::io::stdout().flush();
 
-        // Use multi-threaded decompression (4 threads per chunk)
-        use blvm_bench::chunked_cache::decompress_chunk_streaming_mt;
-        let mut zstd_proc = match decompress_chunk_streaming_mt(&chunk_file, 4) {
-            Ok(p) => p,
+        use blvm_bench::chunked_cache::decompress_chunk_streaming;
+        let decoder = match decompress_chunk_streaming(&chunk_file) {
+            Ok(d) => d,
             Err(e) => {
                 eprintln!(
                     "   ❌ Failed to start decompression for chunk {}: {}",
@@ -79,16 +78,8 @@ fn main() -> Result<()> {
             }
         };
 
-        let stdout = match zstd_proc.stdout.take() {
-            Some(s) => s,
-            None => {
-                eprintln!("   ❌ Failed to get stdout for chunk {}", chunk_num);
-                return;
-            }
-        };
-
         // Larger buffer for better throughput
-        let mut reader = std::io::BufReader::with_capacity(4 * 1024 * 1024, stdout);
+        let mut reader = std::io::BufReader::with_capacity(4 * 1024 * 1024, decoder);
 
         let mut offset: u64 = 0;
         let mut block_count = 0usize;
@@ -174,7 +165,6 @@ fn main() -> Result<()> {
 
         println!("   ✅ Chunk {} complete: {} blocks", chunk_num, block_count);
         let _ = std::io::stdout().flush();
-        let _ = zstd_proc.wait();
     });
 
     let elapsed = start_time.elapsed();
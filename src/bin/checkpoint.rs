@@ -0,0 +1,124 @@
+//! UTXO checkpoint tools
+//!
+//! `checkpoint diff A B` compares two UTXO checkpoints and prints added / removed / changed
+//! outpoints (counts, value sums, bounded sample). `A` and `B` are either heights (resolved to
+//! `{cache_dir}/{subdir}/utxo_H.bin`) or paths to checkpoint files.
+//!
+//! Usage:
+//!   BLOCK_CACHE_DIR=/path cargo run --bin checkpoint --features utxo-snapshot-tools -- diff 100000 100001
+//!   cargo run --bin checkpoint --features utxo-snapshot-tools -- diff ./a/utxo_5.bin ./b/utxo_5.bin --json diff.json
+
+use anyhow::{Context, Result};
+use blvm_bench::checkpoint_diff::{diff_utxo_sets, format_utxo_diff, DEFAULT_SAMPLE_LIMIT};
+use blvm_bench::checkpoint_persistence::{load_utxo_checkpoint_file, CheckpointManager};
+use blvm_protocol::types::UtxoSet;
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(name = "checkpoint")]
+#[command(about = "Inspect and compare BLVM UTXO checkpoints")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Diff two checkpoints (A → B)
+    Diff {
+        /// Checkpoint A: height or path to a checkpoint file
+        a: String,
+        /// Checkpoint B: height or path to a checkpoint file
+        b: String,
+
+        /// Cache root for height arguments (default: BLOCK_CACHE_DIR)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+
+        /// Checkpoint subdirectory under the cache root
+        #[arg(long, default_value = "differential_checkpoints")]
+        subdir: PathBuf,
+
+        /// Max sample outpoints listed per category
+        #[arg(long, default_value_t = DEFAULT_SAMPLE_LIMIT)]
+        sample: usize,
+
+        /// Also write the full diff summary as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+
+        /// Exit with status 1 when the checkpoints differ
+        #[arg(long)]
+        fail_on_diff: bool,
+    },
+}
+
+fn load_checkpoint_arg(
+    arg: &str,
+    cache_dir: Option<&Path>,
+    subdir: &Path,
+) -> Result<UtxoSet> {
+    if let Ok(height) = arg.parse::<u64>() {
+        let root = match cache_dir {
+            Some(d) => d.to_path_buf(),
+            None => blvm_bench::require_block_cache_dir()?,
+        };
+        let mgr = CheckpointManager::with_checkpoint_subdir(&root, subdir)?;
+        return mgr
+            .load_utxo_checkpoint(height)?
+            .with_context(|| {
+                format!(
+                    "no checkpoint utxo_{}.bin under {}",
+                    height,
+                    root.join(subdir).display()
+                )
+            });
+    }
+    load_utxo_checkpoint_file(Path::new(arg))
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Diff {
+            a,
+            b,
+            cache_dir,
+            subdir,
+            sample,
+            json,
+            fail_on_diff,
+        } => {
+            let t = Instant::now();
+            let set_a = load_checkpoint_arg(&a, cache_dir.as_deref(), &subdir)
+                .with_context(|| format!("load checkpoint A ({a})"))?;
+            let set_b = load_checkpoint_arg(&b, cache_dir.as_deref(), &subdir)
+                .with_context(|| format!("load checkpoint B ({b})"))?;
+            eprintln!(
+                "📦 Loaded {} + {} UTXOs in {:.1}s",
+                set_a.len(),
+                set_b.len(),
+                t.elapsed().as_secs_f64()
+            );
+
+            let diff = diff_utxo_sets(&set_a, &set_b, sample);
+            print!("{}", format_utxo_diff(&diff, &a, &b));
+
+            if let Some(path) = json {
+                let body = serde_json::to_string_pretty(&diff)?;
+                std::fs::write(&path, body)
+                    .with_context(|| format!("write {}", path.display()))?;
+                eprintln!("✅ Wrote {}", path.display());
+            }
+
+            if diff.is_empty() {
+                println!("✅ Checkpoints are identical");
+            } else if fail_on_diff {
+                std::process::exit(1);
+            }
+        }
+    }
+    Ok(())
+}
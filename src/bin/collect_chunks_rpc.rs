@@ -4,9 +4,10 @@
 //! guaranteeing correct ordering. Slower than local file reading but always correct.
 
 use anyhow::{Context, Result};
+use blvm_bench::chunked_cache::{compress_chunk_file, save_chunk_metadata, ChunkMetadata};
+use blvm_bench::meta_store::{record_chunk, ChunkEntry};
 use blvm_bench::remote_core_rpc::RemoteCoreRpcClient;
 use std::io::{BufWriter, Write};
-use std::process::Command;
 use tokio::time::{timeout, Duration};
 
 const BLOCKS_PER_CHUNK: u64 = 125_000;
@@ -179,21 +180,28 @@ async fn main() -> Result<()> {
             "   🗜️  Compressing chunk {} (this may take a while)...",
             chunk_num
         );
-        let status = Command::new("zstd")
-            .args(["-T0", "-19", "-f", "-o"])
-            .arg(&chunk_path)
-            .arg(&temp_path)
-            .status()?;
-
-        if !status.success() {
-            anyhow::bail!("zstd compression failed for chunk {}", chunk_num);
-        }
+        compress_chunk_file(&temp_path, &chunk_path, 19)
+            .with_context(|| format!("zstd compression failed for chunk {}", chunk_num))?;
 
         // Cleanup
         std::fs::remove_file(&temp_path)?;
         std::fs::remove_file(&progress_path).ok();
 
         let compressed_size = std::fs::metadata(&chunk_path)?.len();
+        record_chunk(
+            &chunks_dir,
+            &ChunkEntry {
+                chunk_num,
+                blocks: chunk_end - chunk_start + 1,
+                compressed_bytes: compressed_size,
+            },
+        )?;
+        blvm_bench::chunk_manifest::record_chunk(
+            &chunks_dir,
+            chunk_num,
+            chunk_start,
+            chunk_end - chunk_start + 1,
+        )?;
         println!(
             "   ✅ Chunk {} complete: {} compressed",
             chunk_num,
@@ -202,12 +210,16 @@ async fn main() -> Result<()> {
     }
 
     // Update metadata
-    let meta_path = chunks_dir.join("chunks.meta");
-    let meta_content = format!(
-        "# Chunk metadata\n# Collected via RPC\ntotal_blocks={}\nnum_chunks={}\nblocks_per_chunk={}\ncompression=zstd\n",
-        chain_height + 1, num_chunks, BLOCKS_PER_CHUNK
-    );
-    std::fs::write(&meta_path, meta_content)?;
+    save_chunk_metadata(
+        &chunks_dir,
+        &ChunkMetadata {
+            total_blocks: chain_height + 1,
+            num_chunks: num_chunks as usize,
+            blocks_per_chunk: BLOCKS_PER_CHUNK,
+            compression: "zstd".to_string(),
+        },
+        "Collected via RPC",
+    )?;
 
     // Delete old hashmap (will need to be rebuilt)
     let hashmap_path = chunks_dir.join("chunks.hashmap");
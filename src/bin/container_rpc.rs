@@ -0,0 +1,172 @@
+//! Find a containerized `bitcoind` and reach its RPC ([`blvm_bench::container_rpc_client`])
+//!
+//! Discovers the node (docker / podman container, `pgrep -f` pattern, PID or network namespace,
+//! locally or over SSH), reads its RPC port and credentials from inside the container, and prints
+//! where they came from with the node's chain and height. With `--serve` it keeps the loopback
+//! forwarder up and prints `BITCOIN_RPC_*` settings for the other tools until interrupted.
+//!
+//! Usage:
+//!   cargo run --release --bin container_rpc --features differential -- --container auto --serve
+//!   cargo run --release --bin container_rpc --features differential -- \
+//!     --start9 --ssh-host start9@192.168.1.20 --ssh-key ~/.ssh/id_ed25519
+
+use anyhow::Result;
+use blvm_bench::container_rpc_client::{
+    AuthSource, ContainerConfig, ContainerRpcClient, ContainerTarget, Engine, SshHost,
+};
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "container_rpc")]
+#[command(about = "Discover a containerized bitcoind and proxy its RPC")]
+struct Args {
+    /// Container name or ID prefix, or `auto` for the first one mentioning bitcoin
+    #[arg(long)]
+    container: Option<String>,
+
+    /// `docker` or `podman` (default: docker, then podman)
+    #[arg(long)]
+    engine: Option<String>,
+
+    /// `pgrep -f` pattern of the bitcoind process
+    #[arg(long)]
+    process: Option<String>,
+
+    #[arg(long)]
+    pid: Option<u32>,
+
+    /// Network namespace path (needs --rpc-user / --rpc-password)
+    #[arg(long)]
+    netns: Option<PathBuf>,
+
+    /// Start9's layout: `bitcoind -onion` on the SSH host
+    #[arg(long, requires = "ssh_host")]
+    start9: bool,
+
+    /// Run discovery and the bridge on this host (`user@host`)
+    #[arg(long)]
+    ssh_host: Option<String>,
+
+    #[arg(long)]
+    ssh_key: Option<String>,
+
+    /// Core chain name (`main`, `test`, `testnet4`, `signet`, `regtest`)
+    #[arg(long)]
+    chain: Option<String>,
+
+    #[arg(long)]
+    rpc_port: Option<u16>,
+
+    #[arg(long, requires = "rpc_password")]
+    rpc_user: Option<String>,
+
+    #[arg(long, requires = "rpc_user")]
+    rpc_password: Option<String>,
+
+    /// Datadir inside the container
+    #[arg(long)]
+    datadir: Option<PathBuf>,
+
+    /// Keep the forwarder up until Ctrl-C
+    #[arg(long)]
+    serve: bool,
+}
+
+impl Args {
+    /// The config from the flags, else from `BLVM_CONTAINER*` env.
+    fn config(&self) -> Result<ContainerConfig> {
+        let ssh = self.ssh_host.clone().map(|host| SshHost {
+            host,
+            key: self.ssh_key.clone(),
+        });
+        let target = if let Some(pid) = self.pid {
+            ContainerTarget::Pid(pid)
+        } else if let Some(netns) = &self.netns {
+            ContainerTarget::Netns(netns.clone())
+        } else if let Some(pattern) = &self.process {
+            ContainerTarget::Process(pattern.clone())
+        } else if let Some(name) = &self.container {
+            let engine = match &self.engine {
+                Some(engine) => Some(Engine::from_name(engine).ok_or_else(|| {
+                    anyhow::anyhow!("Unknown engine {} (docker or podman)", engine)
+                })?),
+                None => None,
+            };
+            ContainerTarget::Container {
+                engine,
+                name: Some(name.clone()).filter(|n| n != "auto"),
+            }
+        } else if let (true, Some(ssh)) = (self.start9, &ssh) {
+            ContainerConfig::start9(ssh.clone()).target
+        } else {
+            return ContainerConfig::from_env()?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Give --container, --process, --pid, --netns or --start9 \
+                     (or set BLVM_CONTAINER*)"
+                )
+            });
+        };
+
+        let mut config = ContainerConfig::new(target);
+        config.ssh = ssh;
+        config.chain = self.chain.clone();
+        config.rpc_port = self.rpc_port;
+        config.credentials = self.rpc_user.clone().zip(self.rpc_password.clone());
+        config.datadir = self.datadir.clone();
+        Ok(config)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let client = ContainerRpcClient::connect(args.config()?).await?;
+    let node = client.node();
+
+    println!("🐳 bitcoind");
+    if let Some(container) = &node.container {
+        println!("   container:   {}", container);
+    }
+    if let Some(pid) = node.pid {
+        println!("   PID:         {}", pid);
+    }
+    if let Some(datadir) = &node.datadir {
+        println!("   datadir:     {}", datadir.display());
+    }
+    println!(
+        "   chain:       {} (RPC port {})",
+        node.chain, node.rpc_port
+    );
+    let auth = match &node.auth {
+        AuthSource::Config => "given".to_string(),
+        AuthSource::Settings => "rpcuser / rpcpassword".to_string(),
+        AuthSource::Cookie(path) => format!("cookie {}", path.display()),
+    };
+    println!("   credentials: {}", auth);
+    println!("   RPC URL:     {}", node.url);
+
+    let info = client.getblockchaininfo().await?;
+    println!(
+        "   node:        {} at height {}",
+        info["chain"].as_str().unwrap_or("?"),
+        info["blocks"]
+    );
+
+    if args.serve {
+        let host_port = node.url.trim_start_matches("http://");
+        let (host, port) = host_port.rsplit_once(':').unwrap_or((host_port, ""));
+        println!("\nFor the other tools:");
+        println!("   BITCOIN_RPC_HOST={} BITCOIN_RPC_PORT={}", host, port);
+        match (&node.auth, node.pid) {
+            // Readable from this host only when discovery ran here
+            (AuthSource::Cookie(path), Some(pid)) if args.ssh_host.is_none() => {
+                println!("   BITCOIN_RPC_COOKIE=/proc/{}/root{}", pid, path.display())
+            }
+            _ => println!("   BITCOIN_RPC_USER / BITCOIN_RPC_PASSWORD: the node's credentials"),
+        }
+        println!("Serving until Ctrl-C");
+        tokio::signal::ctrl_c().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Per-block validation cost profile
+//!
+//! Times deserialization, merkle root, UTXO lookups, script verification and the whole
+//! `connect_block` for every block in a range that has a spent-outputs sidecar in the block
+//! cache ([`blvm_bench::deep_analysis::cost_profile`]), and writes the per-height breakdown.
+//! Export missing sidecars first with `export_prevout_blocks`.
+//!
+//! Usage:
+//!   BLOCK_CACHE_DIR=/cache cargo run --release --bin cost_profile --features differential -- \
+//!     --start 800000 --end 800100 --csv cost_profile.csv
+//!
+//! `--parquet cost_profile.parquet` writes the same table as Parquet (build with
+//! `--features differential,parquet`).
+
+use anyhow::{Context, Result};
+use blvm_bench::block_file_reader::SharedBlockCache;
+use blvm_bench::deep_analysis::cost_profile::profile_range;
+use blvm_bench::deep_analysis::dataset::{self, Format};
+use blvm_protocol::types::Network;
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "cost_profile")]
+#[command(about = "Per-height breakdown of block validation time by phase")]
+struct Args {
+    /// First height to profile
+    #[arg(long)]
+    start: u64,
+
+    /// Last height to profile (inclusive)
+    #[arg(long)]
+    end: u64,
+
+    /// Block cache directory (default: `BLOCK_CACHE_DIR`)
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Slowest blocks to list
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+
+    /// Write the per-height breakdown as CSV
+    #[arg(long)]
+    csv: Option<PathBuf>,
+
+    /// Write the per-height breakdown as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+
+    /// Write the per-height breakdown as Parquet (needs `--features parquet`)
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    anyhow::ensure!(args.end >= args.start, "--end must be >= --start");
+    let cache_dir = args
+        .cache_dir
+        .or_else(blvm_bench::block_cache_env::block_cache_dir_from_env)
+        .context("Pass --cache-dir or set BLOCK_CACHE_DIR")?;
+    let cache = SharedBlockCache::new(&cache_dir)?;
+
+    let profile = profile_range(&cache, args.start, args.end, Network::Mainnet)?;
+    profile.print_summary(args.top);
+    if !profile.missing.is_empty() {
+        println!(
+            "\n⚠️  {} height(s) had no sidecar; run export_prevout_blocks for them first",
+            profile.missing.len()
+        );
+    }
+
+    if let Some(path) = &args.csv {
+        std::fs::write(path, profile.to_csv())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("📄 CSV: {}", path.display());
+    }
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_string_pretty(&profile)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("📄 JSON: {}", path.display());
+    }
+    if let Some(path) = &args.parquet {
+        dataset::write_as(&profile, path, Format::Parquet)?;
+        println!("📄 Parquet: {}", path.display());
+    }
+    Ok(())
+}
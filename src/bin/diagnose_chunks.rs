@@ -1,11 +1,11 @@
 //! Quick diagnostic to count blocks in each chunk
 
 use anyhow::Result;
+use blvm_bench::chunked_cache::decompress_chunk_streaming;
 use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use std::io::{BufReader, Read};
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
 
 fn main() -> Result<()> {
     println!("🔍 Diagnosing chunk contents...\n");
@@ -48,15 +48,8 @@ fn main() -> Result<()> {
         );
 
         // Decompress and count blocks (first 10k only for speed)
-        let zstd_proc = Command::new("zstd")
-            .args(["-d", "-c", "-T0"])
-            .arg(chunk_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()?;
-
-        let stdout = zstd_proc.stdout.unwrap();
-        let mut reader = BufReader::with_capacity(16 * 1024 * 1024, stdout);
+        let decoder = decompress_chunk_streaming(chunk_path)?;
+        let mut reader = BufReader::with_capacity(16 * 1024 * 1024, decoder);
 
         let mut block_count = 0u64;
         let mut chunk_new_hashes = 0u64;
@@ -0,0 +1,113 @@
+//! Fetch benchmark block fixtures and their prevouts from Bitcoin Core
+//!
+//! Stores each named block of `blvm_bench::fixtures::FIXTURES` (all blocks when no name is given)
+//! through the fixture store, verified like `blvm-bench fixtures fetch`, plus
+//! `<hash>.prevouts.json` from `getblock` verbosity 3 (Core 23+) so benchmarks can connect the
+//! block. Blocks whose prevouts are already stored are skipped.
+//!
+//! Usage:
+//!   BITCOIN_RPC_HOST=... BITCOIN_RPC_USER=... BITCOIN_RPC_PASSWORD=... \
+//!     cargo run --release --bin fetch_bench_fixtures --features differential -- [NAME...]
+
+use anyhow::{Context, Result};
+use blvm_bench::fixtures::{fixture, FixtureKind, FixturePrevout, FixtureStore, FIXTURES};
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use clap::Parser;
+use serde_json::Value;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "fetch_bench_fixtures")]
+#[command(about = "Fetch benchmark block fixtures and their prevouts from Core")]
+struct Args {
+    /// Fixture names (default: every block fixture)
+    names: Vec<String>,
+
+    /// Fixture directory (default: `BLVM_BENCH_FIXTURES` or `benches/fixtures/blocks`)
+    #[arg(long)]
+    dir: Option<PathBuf>,
+
+    /// Fetch again even if the files exist
+    #[arg(long)]
+    force: bool,
+}
+
+/// Prevouts of every non-coinbase input of a verbosity-3 `getblock` result.
+fn prevouts(block: &Value) -> Result<Vec<FixturePrevout>> {
+    let txs = block["tx"].as_array().context("getblock result has no `tx`")?;
+    let mut prevouts = Vec::new();
+    for tx in txs.iter().skip(1) {
+        for input in tx["vin"].as_array().context("tx has no `vin`")? {
+            let prevout = &input["prevout"];
+            prevouts.push(FixturePrevout {
+                value: (prevout["value"]
+                    .as_f64()
+                    .context("prevout has no `value` (Core 23+ needed)")?
+                    * 100_000_000.0)
+                    .round() as i64,
+                script_pubkey: prevout["scriptPubKey"]["hex"]
+                    .as_str()
+                    .context("prevout has no `scriptPubKey.hex`")?
+                    .to_string(),
+                height: prevout["height"].as_u64().context("prevout has no `height`")?,
+                coinbase: prevout["generated"].as_bool().unwrap_or(false),
+            });
+        }
+    }
+    Ok(prevouts)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let store = match args.dir {
+        Some(dir) => FixtureStore::new(dir),
+        None => FixtureStore::from_env(),
+    };
+    let selected = if args.names.is_empty() {
+        FIXTURES
+            .iter()
+            .filter(|fixture| fixture.kind == FixtureKind::Block)
+            .collect()
+    } else {
+        args.names
+            .iter()
+            .map(|name| fixture(name).with_context(|| format!("No fixture named {}", name)))
+            .collect::<Result<Vec<_>>>()?
+    };
+    let client = NodeRpcClient::new(RpcConfig::from_env());
+
+    for fixture in selected {
+        anyhow::ensure!(
+            fixture.kind == FixtureKind::Block,
+            "Fixture {} is a transaction; prevouts are fetched for blocks",
+            fixture.name
+        );
+        if !args.force && store.load_prevouts(fixture)?.is_some() {
+            println!(
+                "♻️  {} (height {}) already fetched",
+                fixture.name, fixture.height
+            );
+            continue;
+        }
+
+        let hash = client.getblockhash(fixture.height).await?;
+        let bytes = hex::decode(client.getblock_raw(&hash).await?.trim())
+            .with_context(|| format!("decode getblock hex at height {}", fixture.height))?;
+        let bytes = store.add(fixture, bytes)?;
+        let verbose = client.getblock(&hash, 3).await?;
+        let prevouts =
+            prevouts(&verbose).with_context(|| format!("prevouts of block {}", fixture.height))?;
+        store.save_prevouts(fixture, &prevouts)?;
+        println!(
+            "✅ {} (height {}): {} bytes, {} prevouts - {}",
+            fixture.name,
+            fixture.height,
+            bytes.len(),
+            prevouts.len(),
+            fixture.description
+        );
+    }
+    println!("📂 Fixtures in {}", store.dir().display());
+    Ok(())
+}
@@ -116,9 +116,6 @@ fn find_block_in_chunk(
     chunk_file: &std::path::Path,
     target_hash: &[u8; 32],
 ) -> Result<Option<(usize, u64)>> {
-    use std::io::Read;
-    use std::process::{Command, Stdio};
-
     let chunk_num = chunk_file
         .file_stem()
         .and_then(|s| s.to_str())
@@ -127,19 +124,8 @@ fn find_block_in_chunk(
         .unwrap_or(999);
 
     // Decompress chunk
-    let mut zstd_proc = Command::new("zstd")
-        .arg("-d")
-        .arg("--stdout")
-        .arg(chunk_file)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .context("Failed to start zstd")?;
-
-    let mut stdout = zstd_proc.stdout.take().unwrap();
-    let mut buffer = Vec::new();
-    stdout.read_to_end(&mut buffer)?;
-    zstd_proc.wait()?;
+    let buffer = zstd::stream::decode_all(std::fs::File::open(chunk_file)?)
+        .context("Failed to decompress chunk")?;
 
     // Search for block
     let mut offset = 0u64;
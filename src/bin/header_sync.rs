@@ -0,0 +1,50 @@
+//! Header-only sync differential against Bitcoin Core
+//!
+//! Validates every header of Core's active chain (PoW, continuity, MTP, difficulty) and compares
+//! the tip and chainwork with Core. Run it before a full differential as a quick smoke test.
+//!
+//! Usage:
+//!   BITCOIN_RPC_HOST=... BITCOIN_RPC_USER=... BITCOIN_RPC_PASSWORD=... \
+//!     cargo run --release --bin header_sync --features differential -- --headers-cache headers.bin
+
+use anyhow::Result;
+use blvm_bench::header_sync::{run_header_sync, HeaderSyncConfig};
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "header_sync")]
+#[command(about = "Validate Core's header chain and compare tip / chainwork")]
+struct Args {
+    /// Keep fetched headers in this file so later runs only fetch new ones
+    #[arg(long)]
+    headers_cache: Option<PathBuf>,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let client = NodeRpcClient::new(RpcConfig::from_env());
+    let report = run_header_sync(
+        &client,
+        &HeaderSyncConfig {
+            headers_cache: args.headers_cache,
+            network: None,
+        },
+    )
+    .await?;
+
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("💾 Report written to {}", path.display());
+    }
+    if !report.matches() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Mempool mutation fuzzer: mutants of real transactions through BLVM and Core
+//!
+//! Takes transactions from the mempool of the node in `BITCOIN_RPC_*`, mutates them
+//! ([`blvm_bench::mempool_fuzz`]) and compares BLVM's `accept_to_memory_pool` with
+//! `testmempoolaccept` on the `--target` node, which should not hold them (e.g. `-blocksonly`).
+//! Mismatches are written as artifacts `blvm-bench replay` runs again. Exits 1 on any mismatch.
+//!
+//! Usage:
+//!   cargo run --release --bin mempool_fuzz --features differential -- \
+//!     --target http://127.0.0.1:8342 --target-cookie ~/.bitcoin-blocksonly/.cookie \
+//!     --sources 200 --mutants 50
+
+use anyhow::Result;
+use blvm_bench::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+use blvm_bench::mempool_fuzz::{run_mempool_fuzz, MempoolFuzzConfig};
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::sort_merge::divergence::artifact_limit_from_env;
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "mempool_fuzz")]
+#[command(about = "Compare BLVM and Core mempool acceptance on mutated mainnet transactions")]
+struct Args {
+    /// RPC URL of the node mutants are tested against
+    #[arg(long)]
+    target: String,
+
+    /// Cookie file of the target node (else --target-user / --target-pass)
+    #[arg(long)]
+    target_cookie: Option<PathBuf>,
+
+    #[arg(long, default_value = "")]
+    target_user: String,
+
+    #[arg(long, default_value = "")]
+    target_pass: String,
+
+    /// Source transactions to fuzz
+    #[arg(long, default_value_t = 100)]
+    sources: usize,
+
+    /// Mutants per source transaction
+    #[arg(long, default_value_t = 20)]
+    mutants: u64,
+
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Where mismatch artifacts go when `BLVM_OUTPUT_DIR` is unset
+    #[arg(long, default_value = "divergences")]
+    out: PathBuf,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let source = NodeRpcClient::new(RpcConfig::from_env());
+    let mut target_config = RpcConfig::new(args.target, args.target_user, args.target_pass);
+    if let Some(cookie) = &args.target_cookie {
+        target_config = target_config.with_cookie_file(cookie)?;
+    }
+    let target = NodeRpcClient::new(target_config);
+
+    let config = MempoolFuzzConfig {
+        seed: args.seed,
+        sources: args.sources,
+        mutants_per_source: args.mutants,
+        artifact_dir: artifact_dir_from_env(ArtifactKind::Divergences, &ArtifactContext::now())
+            .unwrap_or(args.out),
+        artifact_limit: artifact_limit_from_env()?,
+    };
+    let report = run_mempool_fuzz(&source, &target, &config).await?;
+    report.print();
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("💾 Report written to {}", path.display());
+    }
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
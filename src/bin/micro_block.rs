@@ -0,0 +1,119 @@
+//! Validate one block over and over
+//!
+//! Loads a block with its spent outputs ([`blvm_bench::prevout_blocks`]) and times
+//! `connect_block` on it for a number of iterations per thread count and cache mode
+//! ([`blvm_bench::micro_bench`]). Blocks missing from the cache are exported first, from Core's
+//! undo files with `--datadir`, otherwise over RPC.
+//!
+//! Usage:
+//!   BLOCK_CACHE_DIR=/cache cargo run --release --bin micro_block --features differential -- \
+//!     481824 --iterations 50 --threads 1,4,16 --cache warm,cold --json micro_481824.json
+
+use anyhow::{Context, Result};
+use blvm_bench::block_file_reader::SharedBlockCache;
+use blvm_bench::cancel::CancellationToken;
+use blvm_bench::micro_bench::{self, CacheMode, MicroConfig, MicroTarget};
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::prevout_blocks::{export_range, SpentOutputSource};
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "micro_block")]
+#[command(about = "Time repeated validation of a single block")]
+struct Args {
+    /// Block height, or block hash (64 hex chars, resolved over RPC)
+    block: MicroTarget,
+
+    /// Timed iterations per configuration
+    #[arg(long, default_value_t = 20)]
+    iterations: usize,
+
+    /// Untimed iterations before each configuration
+    #[arg(long, default_value_t = 2)]
+    warmup: usize,
+
+    /// Validation thread counts to sweep (0 = all cores)
+    #[arg(long, value_delimiter = ',', default_value = "0")]
+    threads: Vec<usize>,
+
+    /// Cache modes to sweep: warm (on) / cold (off)
+    #[arg(long, value_delimiter = ',', default_value = "warm")]
+    cache: Vec<CacheMode>,
+
+    /// Block cache directory (default: `BLOCK_CACHE_DIR`)
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Core data directory to export a missing block from; RPC is used without it
+    #[arg(long)]
+    datadir: Option<PathBuf>,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    anyhow::ensure!(args.iterations > 0, "--iterations must be at least 1");
+    let cache_dir = args
+        .cache_dir
+        .or_else(blvm_bench::block_cache_env::block_cache_dir_from_env)
+        .context("Pass --cache-dir or set BLOCK_CACHE_DIR")?;
+    let cache = SharedBlockCache::new(&cache_dir)?;
+
+    let height = match args.block {
+        MicroTarget::Height(height) => height,
+        MicroTarget::Hash(hash) => {
+            let client = NodeRpcClient::new(RpcConfig::from_env());
+            let header = client.getblockheader(&hex::encode(hash), true).await?;
+            header["height"]
+                .as_u64()
+                .context("getblockheader result has no `height`")?
+        }
+    };
+
+    let prevout_block = match cache.read_prevout_block(height)? {
+        Some(block) => block,
+        None => {
+            println!(
+                "📥 Block {} has no spent-outputs sidecar, exporting it",
+                height
+            );
+            let source = match &args.datadir {
+                Some(dir) => SpentOutputSource::undo_files(dir)?,
+                None => SpentOutputSource::Rpc(NodeRpcClient::new(RpcConfig::from_env())),
+            };
+            export_range(
+                &cache,
+                &source,
+                height,
+                height,
+                true,
+                &CancellationToken::new(),
+            )
+            .await?;
+            cache
+                .read_prevout_block(height)?
+                .with_context(|| format!("Block {} missing after export", height))?
+        }
+    };
+
+    let config = MicroConfig {
+        iterations: args.iterations,
+        warmup: args.warmup,
+        threads: args.threads,
+        cache_modes: args.cache,
+    };
+    let report = micro_bench::run(&prevout_block, &config)?;
+    report.print();
+
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("📄 Report: {}", path.display());
+    }
+    Ok(())
+}
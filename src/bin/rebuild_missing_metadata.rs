@@ -181,22 +181,22 @@ fn main() -> Result<()> {
         count: blocks_found,
     };
 
-    let meta_path = chunks_dir.join("missing_blocks.meta");
+    let store_path = chunks_dir.join(blvm_bench::meta_store::STORE_FILE);
     let backup_path = chunks_dir.join("missing_blocks.meta.backup");
 
-    // Backup old metadata
-    if meta_path.exists() {
+    // Backup old metadata (bincode, same layout as the legacy missing_blocks.meta)
+    if let Some(old) = blvm_bench::missing_blocks::load_missing_blocks_meta(&chunks_dir)? {
         println!("\n💾 Backing up old metadata to {}", backup_path.display());
-        std::fs::copy(&meta_path, &backup_path)?;
+        std::fs::write(&backup_path, bincode::serialize(&old)?)?;
     }
 
     // Save new metadata
-    println!("💾 Saving new metadata to {}", meta_path.display());
+    println!("💾 Saving new metadata to {}", store_path.display());
     blvm_bench::missing_blocks::save_missing_blocks_meta(&chunks_dir, &meta)?;
 
     println!("\n✅ Metadata rebuild complete!");
     println!("   Total missing blocks: {}", meta.count);
-    println!("   Metadata file: {}", meta_path.display());
+    println!("   Metadata store: {}", store_path.display());
 
     Ok(())
 }
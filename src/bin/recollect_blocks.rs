@@ -1,9 +1,13 @@
 //! Recollect blocks from Bitcoin Core blk*.dat files
 //!
 //! This reads blocks directly from the local copy of XOR-packaged encrypted block files,
-//! applies XOR decryption, chains by prev_hash to determine height, and stores in chunks.
+//! applies XOR decryption (the `blocks/xor.dat` key when present), chains by prev_hash to
+//! determine height, and stores in chunks.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use blvm_bench::block_framing::XorKey;
+use blvm_bench::chunked_cache::{compress_chunk_file, save_chunk_metadata, ChunkMetadata};
+use blvm_bench::meta_store::{record_chunk, ChunkEntry};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
@@ -12,54 +16,10 @@ use std::path::PathBuf;
 const BLOCK_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
 const BLOCKS_PER_CHUNK: usize = 125_000;
 
-// XOR encryption keys (alternating every 4 bytes)
-const XOR_KEY1: [u8; 4] = [0x84, 0x22, 0xe9, 0xad];
-const XOR_KEY2: [u8; 4] = [0xb7, 0x8f, 0xff, 0x14];
-
-/// Decrypt bytes using the XOR scheme used by packaged blk trees
-fn xor_decrypt(data: &mut [u8], file_offset: u64) {
-    let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-    let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-
-    let mut offset = file_offset;
-    let mut i = 0;
-
-    // Handle unaligned start
-    while i < data.len() && offset % 4 != 0 {
-        let key = if (offset / 4) % 2 == 0 {
-            &XOR_KEY1
-        } else {
-            &XOR_KEY2
-        };
-        data[i] ^= key[(offset % 4) as usize];
-        offset += 1;
-        i += 1;
-    }
-
-    // Process aligned 4-byte chunks (fast path)
-    while i + 4 <= data.len() {
-        let key_u32 = if (offset / 4) % 2 == 0 {
-            key1_u32
-        } else {
-            key2_u32
-        };
-        let chunk = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
-        let decrypted = chunk ^ key_u32;
-        data[i..i + 4].copy_from_slice(&decrypted.to_le_bytes());
-        offset += 4;
-        i += 4;
-    }
-
-    // Handle remaining bytes
-    while i < data.len() {
-        let key = if (offset / 4) % 2 == 0 {
-            &XOR_KEY1
-        } else {
-            &XOR_KEY2
-        };
-        data[i] ^= key[(offset % 4) as usize];
-        offset += 1;
-        i += 1;
+/// Decrypt bytes read at `file_offset` (no-op for plain files)
+fn xor_decrypt(key: Option<XorKey>, data: &mut [u8], file_offset: u64) {
+    if let Some(key) = key {
+        key.apply(data, file_offset);
     }
 }
 
@@ -75,6 +35,18 @@ fn main() -> Result<()> {
         anyhow::bail!("Bitcoin blk files not found at {}.", blocks_dir.display());
     }
 
+    // Core 28+ keeps the key in xor.dat; packaged copies without one use the fixed key
+    let xor_path = blocks_dir.join("xor.dat");
+    let xor_key = if xor_path.exists() {
+        XorKey::from_xor_dat(&std::fs::read(&xor_path)?)?
+    } else {
+        Some(XorKey::PACKAGED)
+    };
+    println!(
+        "   XOR key: {}",
+        xor_key.map_or_else(|| "none".to_string(), |k| hex::encode(k.bytes()))
+    );
+
     // Find all blk files
     let mut blk_files: Vec<PathBuf> = std::fs::read_dir(&blocks_dir)?
         .filter_map(|e| e.ok())
@@ -122,7 +94,7 @@ fn main() -> Result<()> {
             }
 
             // Decrypt the header
-            xor_decrypt(&mut header, pos);
+            xor_decrypt(xor_key, &mut header, pos);
 
             if header[0..4] != BLOCK_MAGIC {
                 // Seek forward 1 byte and try again
@@ -146,7 +118,7 @@ fn main() -> Result<()> {
             if reader.read_exact(&mut block_header).is_err() {
                 break;
             }
-            xor_decrypt(&mut block_header, block_offset);
+            xor_decrypt(xor_key, &mut block_header, block_offset);
 
             // Calculate block hash
             let first_hash = Sha256::digest(&block_header);
@@ -304,7 +276,7 @@ fn main() -> Result<()> {
                 // Read and decrypt block data
                 let mut block_data = vec![0u8; *size as usize];
                 file.read_exact(&mut block_data)?;
-                xor_decrypt(&mut block_data, *offset);
+                xor_decrypt(xor_key, &mut block_data, *offset);
 
                 // Write to chunk: [size: u32][block_data]
                 writer.write_all(&(*size).to_le_bytes())?;
@@ -322,20 +294,27 @@ fn main() -> Result<()> {
 
         // Compress chunk (using -3 for optimal speed/compression balance)
         println!("   🗜️  Compressing chunk {}...", chunk_idx);
-        let status = std::process::Command::new("zstd")
-            .args(["-T0", "-3", "-f", "-o"])
-            .arg(&chunk_path)
-            .arg(&temp_path)
-            .status()?;
-
-        if !status.success() {
-            anyhow::bail!("zstd compression failed for chunk {}", chunk_idx);
-        }
+        compress_chunk_file(&temp_path, &chunk_path, 3)
+            .with_context(|| format!("zstd compression failed for chunk {}", chunk_idx))?;
 
         // Remove temp file
         std::fs::remove_file(&temp_path)?;
 
         let compressed_size = std::fs::metadata(&chunk_path)?.len();
+        record_chunk(
+            &chunks_dir,
+            &ChunkEntry {
+                chunk_num: chunk_idx as u64,
+                blocks: (end_height - start_height) as u64,
+                compressed_bytes: compressed_size,
+            },
+        )?;
+        blvm_bench::chunk_manifest::record_chunk(
+            &chunks_dir,
+            chunk_idx as u64,
+            start_height as u64,
+            (end_height - start_height) as u64,
+        )?;
         println!(
             "   ✅ Chunk {} created: {} blocks, {} compressed",
             chunk_idx,
@@ -345,12 +324,16 @@ fn main() -> Result<()> {
     }
 
     // Update metadata
-    let meta_path = chunks_dir.join("chunks.meta");
-    let meta_content = format!(
-        "# Chunk metadata\n# Recollected from Bitcoin Core blk files (XOR decrypted)\ntotal_blocks={}\nnum_chunks={}\nblocks_per_chunk={}\ncompression=zstd\n",
-        chain.len(), num_chunks, BLOCKS_PER_CHUNK
-    );
-    std::fs::write(&meta_path, meta_content)?;
+    save_chunk_metadata(
+        &chunks_dir,
+        &ChunkMetadata {
+            total_blocks: chain.len() as u64,
+            num_chunks,
+            blocks_per_chunk: BLOCKS_PER_CHUNK as u64,
+            compression: "zstd".to_string(),
+        },
+        "Recollected from Bitcoin Core blk files (XOR decrypted)",
+    )?;
 
     // Delete old hash map (will be rebuilt)
     let hashmap_path = chunks_dir.join("chunks.hashmap");
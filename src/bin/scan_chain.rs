@@ -7,6 +7,7 @@
 //! Usage:
 //!   BLOCK_CACHE_DIR=/path/to/blockchain cargo run --bin scan_chain --features scan
 //!   BLOCK_CACHE_DIR=/path cargo run --bin scan_chain --features scan -- --start 800000 --end 850000
+//!   BLOCK_CACHE_DIR=/path cargo run --bin scan_chain --features scan -- --from 2017-08-01 --to 2017-12-31
 //!
 //! Requires `BLOCK_CACHE_DIR` (or a populated `~/.cache/blvm-bench/chunks` fallback via `get_chunks_dir`).
 
@@ -18,6 +19,7 @@ use blvm_bench::chain_scan::{
 use blvm_protocol::types::OutPoint;
 use rustc_hash::FxHashMap;
 use blvm_bench::chunked_cache::{get_chunks_dir, load_chunk_metadata, ChunkedBlockIterator};
+use blvm_bench::date_range::resolve_date_range;
 use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
 use blvm_protocol::spam_filter::{SpamFilter, SpamFilterPreset};
 use clap::Parser;
@@ -37,6 +39,15 @@ struct Args {
     #[arg(long, default_value = "801000")]
     end: u64,
 
+    /// Start date (YYYY-MM-DD UTC, RFC 3339 or Unix seconds); overrides --start.
+    /// Resolved via header median-time-past, so --from/--to select whole blocks by MTP.
+    #[arg(long)]
+    from: Option<String>,
+
+    /// End date, inclusive (same formats as --from); overrides --end
+    #[arg(long)]
+    to: Option<String>,
+
     /// Output JSON results to file
     #[arg(long)]
     json: Option<PathBuf>,
@@ -90,7 +101,7 @@ impl std::str::FromStr for SpamPresetArg {
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
 
     let chunks_dir = get_chunks_dir()
         .filter(|p| p.exists())
@@ -107,6 +118,12 @@ fn main() -> Result<()> {
         }
     }
 
+    if args.from.is_some() || args.to.is_some() {
+        let (start, end) = resolve_date_range(&chunks_dir, args.from.as_deref(), args.to.as_deref())?;
+        args.start = start;
+        args.end = end;
+    }
+
     let end_height = if args.end == 0 {
         load_chunk_metadata(&chunks_dir)
             .ok()
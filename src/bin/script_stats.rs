@@ -0,0 +1,168 @@
+//! Script type / opcode frequency scan
+//!
+//! Runs the [`deep_analysis::script_stats`](blvm_bench::deep_analysis::script_stats) pass over
+//! the chunked cache and prints per-window script type shares and top opcodes. `--json` / `--csv`
+//! / `--parquet` (with the `parquet` feature) write the full dataset for picking benchmark
+//! fixtures.
+//!
+//! Usage:
+//!   BLOCK_CACHE_DIR=/path cargo run --release --bin script_stats --features scan -- --csv script_stats.csv
+
+use anyhow::Result;
+use blvm_bench::chunked_cache::{get_chunks_dir, load_chunk_metadata, ChunkedBlockIterator};
+use blvm_bench::deep_analysis::dataset::{self, Format};
+use blvm_bench::deep_analysis::script_stats::{ScriptStatsAnalysis, WINDOW_BLOCKS};
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use clap::Parser;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(name = "script_stats")]
+#[command(about = "Output script types, opcode frequencies and witness versions per block window")]
+struct Args {
+    /// Start height (inclusive)
+    #[arg(long, default_value = "0")]
+    start: u64,
+
+    /// End height (inclusive). Use 0 for "all available"
+    #[arg(long, default_value = "0")]
+    end: u64,
+
+    /// Window size (blocks)
+    #[arg(long, default_value_t = WINDOW_BLOCKS)]
+    window: u64,
+
+    /// Block batch size for parallel processing
+    #[arg(long, default_value = "128")]
+    batch_size: usize,
+
+    /// Progress interval (blocks)
+    #[arg(long, default_value = "10000")]
+    progress: u64,
+
+    /// Write per-window statistics as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+
+    /// Write per-window statistics as long-format CSV
+    #[arg(long)]
+    csv: Option<PathBuf>,
+
+    /// Write the long-format table as Parquet (needs `--features parquet`)
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+}
+
+fn analyze_batch(batch: &[(u64, Vec<u8>)], window: u64) -> (ScriptStatsAnalysis, u64) {
+    batch
+        .par_iter()
+        .fold(
+            || (ScriptStatsAnalysis::new(window), 0u64),
+            |(mut acc, mut failed), (height, data)| {
+                match deserialize_block_with_witnesses(data) {
+                    Ok((block, witnesses)) => acc.add_block(&block, &witnesses, *height),
+                    Err(e) => {
+                        eprintln!("⚠️  Block {} failed to parse: {}", height, e);
+                        failed += 1;
+                    }
+                }
+                (acc, failed)
+            },
+        )
+        .reduce(
+            || (ScriptStatsAnalysis::new(window), 0u64),
+            |(mut a, fa), (b, fb)| {
+                a.merge(&b);
+                (a, fa + fb)
+            },
+        )
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let chunks_dir = get_chunks_dir()
+        .filter(|p| p.exists())
+        .ok_or_else(|| anyhow::anyhow!(
+            "Chunks directory not found. Set BLOCK_CACHE_DIR to your chunk cache root (see blvm-bench/.env.example)."
+        ))?;
+
+    let end_height = if args.end == 0 {
+        load_chunk_metadata(&chunks_dir)
+            .ok()
+            .flatten()
+            .map(|m| m.total_blocks.saturating_sub(1))
+            .unwrap_or(args.start + 10000)
+    } else {
+        args.end
+    };
+    anyhow::ensure!(end_height >= args.start, "end must be >= start");
+
+    eprintln!(
+        "🔍 Script statistics: blocks {} to {}",
+        args.start, end_height
+    );
+    eprintln!("   Chunks: {}", chunks_dir.display());
+
+    let max_blocks = (end_height - args.start + 1) as usize;
+    let mut block_iter =
+        ChunkedBlockIterator::new(&chunks_dir, Some(args.start), Some(max_blocks))?
+            .ok_or_else(|| anyhow::anyhow!("Failed to create block iterator"))?;
+
+    let batch_size = args.batch_size.max(1);
+    let mut analysis = ScriptStatsAnalysis::new(args.window);
+    let mut batch: Vec<(u64, Vec<u8>)> = Vec::with_capacity(batch_size);
+    let mut height = args.start;
+    let mut blocks = 0u64;
+    let mut failed = 0u64;
+    let start_time = Instant::now();
+
+    loop {
+        let next = block_iter.next_block()?;
+        let done = next.is_none();
+        if let Some(data) = next {
+            batch.push((height, data));
+            height += 1;
+        }
+        if batch.len() >= batch_size || (done && !batch.is_empty()) {
+            let (partial, f) = analyze_batch(&batch, args.window);
+            analysis.merge(&partial);
+            failed += f;
+            let before = blocks;
+            blocks += batch.len() as u64;
+            batch.clear();
+            if blocks / args.progress.max(1) != before / args.progress.max(1) {
+                let rate = blocks as f64 / start_time.elapsed().as_secs_f64();
+                eprintln!("   {} blocks ({:.1} blk/s)", blocks, rate);
+            }
+        }
+        if done {
+            break;
+        }
+    }
+
+    eprintln!();
+    eprintln!(
+        "✅ Scanned {} blocks ({} parse failures) in {:.1}s",
+        blocks,
+        failed,
+        start_time.elapsed().as_secs_f64()
+    );
+    print!("{}", analysis.format_summary());
+
+    if let Some(path) = args.json {
+        std::fs::write(&path, serde_json::to_string_pretty(&analysis)?)?;
+        eprintln!("📊 Wrote {}", path.display());
+    }
+    if let Some(path) = args.csv {
+        std::fs::write(&path, analysis.to_csv())?;
+        eprintln!("📊 Wrote {}", path.display());
+    }
+    if let Some(path) = args.parquet {
+        dataset::write_as(&analysis, &path, Format::Parquet)?;
+        eprintln!("📊 Wrote {}", path.display());
+    }
+    Ok(())
+}
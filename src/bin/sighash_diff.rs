@@ -0,0 +1,129 @@
+//! Sighash differential and quadratic-hashing benchmark
+//!
+//! Signs seeded P2PKH / P2WPKH / P2TR transactions over reference sighashes and verifies them
+//! with BLVM ([`blvm_bench::sighash_diff`]); optionally checks the legacy reference against
+//! Core's `sighash.json`, has a Core node sign the same inputs over RPC, and times all-inputs
+//! legacy sighashing as the input count grows.
+//!
+//! Usage:
+//!   cargo run --release --bin sighash_diff --features differential -- \
+//!     --cases 1000 --vectors tests/data/sighash.json --core 50 --bench 100,1000,5569
+
+use anyhow::Result;
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::sighash_diff::{
+    bench_quadratic, check_sighash_vectors, compare_with_core, QuadraticRun, SighashFuzzer,
+    SighashReport,
+};
+use clap::Parser;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "sighash_diff")]
+#[command(about = "Differential test and benchmark of BLVM sighash computation")]
+struct Args {
+    /// Generated transactions to sign and verify
+    #[arg(long, default_value_t = 500)]
+    cases: u64,
+
+    /// Generator seed
+    #[arg(long, default_value_t = 2015)]
+    seed: u64,
+
+    /// Core's `sighash.json` to check the legacy reference against
+    #[arg(long)]
+    vectors: Option<PathBuf>,
+
+    /// Transactions for the Core node from `BITCOIN_RPC_*` to sign (0 = skip)
+    #[arg(long, default_value_t = 0)]
+    core: u64,
+
+    /// Input counts to time all-inputs legacy sighashing at (none = skip)
+    #[arg(long, value_delimiter = ',')]
+    bench: Vec<usize>,
+
+    /// Timed iterations per input count (best is kept)
+    #[arg(long, default_value_t = 5)]
+    bench_iterations: usize,
+
+    /// Write every report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Output {
+    fuzz: SighashReport,
+    vectors: Option<SighashReport>,
+    core: Option<SighashReport>,
+    bench: Vec<QuadraticRun>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut fuzzer = SighashFuzzer::new(args.seed)?;
+    let fuzz = fuzzer.run(args.cases);
+    fuzz.print("Reference signatures through BLVM");
+
+    let vectors = args
+        .vectors
+        .as_deref()
+        .map(check_sighash_vectors)
+        .transpose()?;
+    if let Some(report) = &vectors {
+        report.print("Legacy reference vs sighash.json");
+    }
+
+    let core = if args.core > 0 {
+        let client = NodeRpcClient::new(RpcConfig::from_env());
+        let report = compare_with_core(&client, &mut fuzzer, args.core).await?;
+        report.print("Core signatures");
+        Some(report)
+    } else {
+        None
+    };
+
+    let bench = bench_quadratic(&args.bench, args.bench_iterations)?;
+    if !bench.is_empty() {
+        println!(
+            "\n⏱️  All-inputs SIGHASH_ALL (best of {})",
+            args.bench_iterations
+        );
+        println!(
+            "   {:>7} {:>10} {:>14} {:>12} {:>12} {:>12} {:>8}",
+            "inputs", "tx bytes", "hashed bytes", "per input", "batch", "reference", "MB/s"
+        );
+        for run in &bench {
+            println!(
+                "   {:>7} {:>10} {:>14} {:>12.2?} {:>12.2?} {:>12.2?} {:>8.0}",
+                run.inputs,
+                run.tx_size,
+                run.hashed_bytes,
+                run.blvm_per_input,
+                run.blvm_batch,
+                run.reference,
+                run.megabytes_per_second()
+            );
+        }
+    }
+
+    let passed = fuzz.passed()
+        && vectors.as_ref().map_or(true, SighashReport::passed)
+        && core.as_ref().map_or(true, SighashReport::passed);
+    if let Some(path) = &args.json {
+        let output = Output {
+            fuzz,
+            vectors,
+            core,
+            bench,
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&output)?)?;
+        println!("💾 Report written to {}", path.display());
+    }
+    if !passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -11,18 +11,29 @@
 //!   sort_merge_test step5    # Sort prevouts by spending location
 //!   sort_merge_test step6    # Verify scripts in parallel
 //!   sort_merge_test all      # Run all steps
+//!
+//! `--sort-mem 4G` (or `SORT_MEM`) sets the memory budget of the sort steps.
+//! `--compress` (or `SORT_MERGE_COMPRESS=1`) keeps the intermediate files as `.bin.zst`.
+//! Below `storage.low_space_bytes` free in the data dir, sorts and streaming steps pause or stop
+//! at their next checkpoint (`storage.low_space_action`, see `disk_watchdog`).
+//! Progress is kept in `sort_merge_state.json`: reruns skip finished steps and resume an
+//! interrupted one; `--restart` starts from scratch.
 
 use std::path::PathBuf;
 use std::time::Instant;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use blvm_protocol::types::Network;
 
+use blvm_bench::disk_watchdog::{self, DiskWatchdog};
 use blvm_bench::sort_merge::{
+    compression::intermediate_name,
+    external_sort::{parse_mem_size, sort_mem_from_env},
     input_refs::{extract_input_refs, sort_input_refs},
     merge_join::{merge_join, sort_joined},
     output_refs::{extract_outputs, sort_outputs},
-    verify::verify_scripts,
+    state::{PipelineState, Stage, STATE_FILE},
+    verify::{verify_scripts, FAILURES_LOG},
 };
 
 // Configuration from environment
@@ -47,17 +58,45 @@ fn main() -> Result<()> {
     let start_height: u64 = get_env("START_HEIGHT", "0").parse()?;
     let end_height: u64 = get_env("END_HEIGHT", "912723").parse()?;
     let progress_interval: u64 = get_env("PROGRESS_INTERVAL", "10000").parse()?;
+    let sort_mem = match args.iter().position(|a| a == "--sort-mem") {
+        Some(i) => parse_mem_size(
+            args.get(i + 1)
+                .context("--sort-mem needs a size, e.g. 4G")?,
+        )?,
+        None => sort_mem_from_env()?,
+    };
+    let compress = args.iter().any(|a| a == "--compress")
+        || matches!(
+            get_env("SORT_MERGE_COMPRESS", "0").as_str(),
+            "1" | "true" | "zstd"
+        );
 
     // Ensure data directory exists
     std::fs::create_dir_all(&data_dir)?;
+    blvm_bench::progress::ensure_logging();
+    let watchdog = DiskWatchdog::from_tuning(&blvm_bench::config::global().storage)
+        .watch("sort-merge data", &data_dir);
+    // Full-chain totals from the pipeline docs; a shorter range needs less
+    let intermediate_gib: u64 = if compress { 7 } else { 25 };
+    watchdog.preflight(&[("sort-merge data", intermediate_gib << 30)]);
+    disk_watchdog::install(watchdog);
 
     // File paths
-    let inputs_unsorted = data_dir.join("inputs_unsorted.bin");
-    let inputs_sorted = data_dir.join("inputs_sorted.bin");
-    let outputs_unsorted = data_dir.join("outputs_unsorted.bin");
-    let outputs_sorted = data_dir.join("outputs_sorted.bin");
-    let joined_unsorted = data_dir.join("joined_unsorted.bin");
-    let joined_sorted = data_dir.join("joined_sorted.bin");
+    const FILES: [&str; 6] = [
+        "inputs_unsorted.bin",
+        "inputs_sorted.bin",
+        "outputs_unsorted.bin",
+        "outputs_sorted.bin",
+        "joined_unsorted.bin",
+        "joined_sorted.bin",
+    ];
+    let path = |name: &str| data_dir.join(intermediate_name(name, compress));
+    let inputs_unsorted = path(FILES[0]);
+    let inputs_sorted = path(FILES[1]);
+    let outputs_unsorted = path(FILES[2]);
+    let outputs_sorted = path(FILES[3]);
+    let joined_unsorted = path(FILES[4]);
+    let joined_sorted = path(FILES[5]);
 
     // The block cache dir contains chunks.meta and chunk_*.bin.zst files
     let chunks_dir = &block_cache_dir;
@@ -68,97 +107,101 @@ fn main() -> Result<()> {
     println!("Block cache: {}", block_cache_dir.display());
     println!("Data dir: {}", data_dir.display());
     println!("Block range: {} to {}", start_height, end_height);
+    println!("Sort memory: {:.1} GB", sort_mem as f64 / 1_073_741_824.0);
+    if compress {
+        println!("Intermediate files: zstd compressed");
+    }
 
     let total_start = Instant::now();
 
-    let step = args[1].as_str();
+    // Finished stages are skipped and an interrupted one resumes from its checkpoint
+    if args.iter().any(|a| a == "--restart") {
+        let _ = std::fs::remove_file(data_dir.join(STATE_FILE));
+    }
+    let mut state = PipelineState::load(&data_dir, start_height, end_height, compress)?;
+    let failures_log = data_dir.join(FAILURES_LOG);
 
-    match step {
-        "step1" | "1" => {
-            extract_input_refs(
-                chunks_dir,
-                &inputs_unsorted,
-                start_height,
-                end_height,
-                progress_interval,
-            )?;
-        }
-        "step2" | "2" => {
-            sort_input_refs(&inputs_unsorted, &inputs_sorted)?;
-        }
-        "step3" | "3" => {
-            extract_outputs(
-                chunks_dir,
-                &outputs_unsorted,
-                start_height,
-                end_height,
-                progress_interval,
-            )?;
-        }
-        "step3b" => {
-            sort_outputs(&outputs_unsorted, &outputs_sorted)?;
-        }
-        "step4" | "4" => {
-            merge_join(&inputs_sorted, &outputs_sorted, &joined_unsorted)?;
-        }
-        "step5" | "5" => {
-            sort_joined(&joined_unsorted, &joined_sorted)?;
-        }
-        "step6" | "6" => {
-            let network = Network::Mainnet;
-            verify_scripts(
-                chunks_dir,
-                &joined_sorted,
-                start_height,
-                end_height,
-                progress_interval,
-                network,
-            )?;
-        }
+    let step = args[1].as_str();
+    let stages: &[Stage] = match step {
+        "step1" | "1" => &[Stage::ExtractInputs],
+        "step2" | "2" => &[Stage::SortInputs],
+        "step3" | "3" => &[Stage::ExtractOutputs],
+        "step3b" => &[Stage::SortOutputs],
+        "step4" | "4" => &[Stage::MergeJoin],
+        "step5" | "5" => &[Stage::SortJoined],
+        "step6" | "6" => &[Stage::Verify],
         "all" => {
-            // Run all steps
             println!("\nRunning all steps...\n");
+            &Stage::ALL
+        }
+        _ => &[],
+    };
+    let network = Network::Mainnet;
+    let mut verify_results = None;
 
-            // Step 1: Extract inputs
-            extract_input_refs(
-                chunks_dir,
-                &inputs_unsorted,
-                start_height,
-                end_height,
-                progress_interval,
-            )?;
-
-            // Step 2: Sort inputs
-            sort_input_refs(&inputs_unsorted, &inputs_sorted)?;
-
-            // Step 3: Extract outputs
-            extract_outputs(
-                chunks_dir,
-                &outputs_unsorted,
-                start_height,
-                end_height,
-                progress_interval,
-            )?;
-
-            // Step 3b: Sort outputs
-            sort_outputs(&outputs_unsorted, &outputs_sorted)?;
-
-            // Step 4: Merge-join
-            merge_join(&inputs_sorted, &outputs_sorted, &joined_unsorted)?;
-
-            // Step 5: Sort joined
-            sort_joined(&joined_unsorted, &joined_sorted)?;
-
-            // Step 6: Verify scripts
-            let network = Network::Mainnet;
-            let (verified, failed, divergences) = verify_scripts(
-                chunks_dir,
-                &joined_sorted,
-                start_height,
-                end_height,
-                progress_interval,
-                network,
-            )?;
+    for &stage in stages {
+        match stage {
+            Stage::ExtractInputs => state.run(stage, &inputs_unsorted, |checkpoint| {
+                extract_input_refs(
+                    chunks_dir,
+                    &inputs_unsorted,
+                    start_height,
+                    end_height,
+                    progress_interval,
+                    Some(checkpoint),
+                )
+            })?,
+            Stage::SortInputs => state.run(stage, &inputs_sorted, |_| {
+                sort_input_refs(&inputs_unsorted, &inputs_sorted, sort_mem)
+            })?,
+            Stage::ExtractOutputs => state.run(stage, &outputs_unsorted, |checkpoint| {
+                extract_outputs(
+                    chunks_dir,
+                    &outputs_unsorted,
+                    start_height,
+                    end_height,
+                    progress_interval,
+                    Some(checkpoint),
+                )
+            })?,
+            Stage::SortOutputs => state.run(stage, &outputs_sorted, |_| {
+                sort_outputs(&outputs_unsorted, &outputs_sorted, sort_mem)
+            })?,
+            Stage::MergeJoin => state.run(stage, &joined_unsorted, |_| {
+                Ok(merge_join(&inputs_sorted, &outputs_sorted, &joined_unsorted)?.0)
+            })?,
+            Stage::SortJoined => state.run(stage, &joined_sorted, |_| {
+                sort_joined(&joined_unsorted, &joined_sorted, sort_mem)
+            })?,
+            Stage::Verify => state.run(stage, &failures_log, |checkpoint| {
+                let results = verify_scripts(
+                    chunks_dir,
+                    &joined_sorted,
+                    start_height,
+                    end_height,
+                    progress_interval,
+                    network,
+                    Some(checkpoint),
+                )?;
+                let verified = results.0;
+                verify_results = Some(results);
+                Ok(verified)
+            })?,
+        }
+    }
+
+    match step {
+        "all" => {
+            // Counts of an earlier run if step 6 was already complete
+            let (verified, failed, divergences) = match verify_results {
+                Some(results) => results,
+                None => {
+                    let verify = state
+                        .stage(Stage::Verify)
+                        .context("step6 has no recorded results")?;
+                    (verify.records, verify.failed, Vec::new())
+                }
+            };
 
             // Final summary
             println!("\n{}", "═".repeat(70));
@@ -179,16 +222,9 @@ fn main() -> Result<()> {
             // Show status of intermediate files
             println!("\nFile Status:");
 
-            let files = [
-                ("inputs_unsorted.bin", &inputs_unsorted),
-                ("inputs_sorted.bin", &inputs_sorted),
-                ("outputs_unsorted.bin", &outputs_unsorted),
-                ("outputs_sorted.bin", &outputs_sorted),
-                ("joined_unsorted.bin", &joined_unsorted),
-                ("joined_sorted.bin", &joined_sorted),
-            ];
-
-            for (name, path) in files {
+            for name in FILES {
+                let name = intermediate_name(name, compress);
+                let path = data_dir.join(&name);
                 if path.exists() {
                     let meta = std::fs::metadata(path)?;
                     let size_gb = meta.len() as f64 / 1_073_741_824.0;
@@ -197,24 +233,26 @@ fn main() -> Result<()> {
                     println!("  ✗ {} (not found)", name);
                 }
             }
+
+            println!("\nStages ({}):", STATE_FILE);
+            state.print_status();
         }
         "clean" => {
             println!("\nCleaning intermediate files...");
-            for file in [
-                &inputs_unsorted,
-                &inputs_sorted,
-                &outputs_unsorted,
-                &outputs_sorted,
-                &joined_unsorted,
-                &joined_sorted,
-            ] {
+            // Both variants, whichever --compress setting produced them
+            let files = FILES.iter().flat_map(|name| {
+                [false, true].map(|compress| data_dir.join(intermediate_name(name, compress)))
+            });
+            for file in files.chain([failures_log, data_dir.join(STATE_FILE)]) {
                 if file.exists() {
-                    std::fs::remove_file(file)?;
+                    std::fs::remove_file(&file)?;
                     println!("  Removed: {}", file.display());
                 }
             }
             println!("  Done!");
         }
+        // A single step, already run above
+        _ if !stages.is_empty() => {}
         _ => {
             print_usage();
             return Ok(());
@@ -232,7 +270,7 @@ fn main() -> Result<()> {
 fn print_usage() {
     println!("Sort-Merge Differential Validation");
     println!();
-    println!("Usage: sort_merge_test <step>");
+    println!("Usage: sort_merge_test <step> [--sort-mem SIZE] [--compress] [--restart]");
     println!();
     println!("Steps:");
     println!("  step1, 1     Extract input references (~30 min, ~7 GB)");
@@ -246,10 +284,22 @@ fn print_usage() {
     println!("  status       Show status of intermediate files");
     println!("  clean        Remove intermediate files");
     println!();
+    println!(
+        "Completed steps are recorded in {} and skipped on the next",
+        STATE_FILE
+    );
+    println!("run; an interrupted step 1, 3 or 6 resumes from its last checkpoint.");
+    println!("--restart discards that state and runs everything again.");
+    println!();
     println!("Environment variables:");
     println!("  BLOCK_CACHE_DIR    Block cache directory (required)");
     println!("  SORT_MERGE_DIR     Data directory for intermediate files");
     println!("  START_HEIGHT       Starting block height (default: 0)");
     println!("  END_HEIGHT         Ending block height (default: 912723)");
     println!("  PROGRESS_INTERVAL  Progress report interval (default: 10000)");
+    println!(
+        "  SORT_MEM           Sort memory budget, e.g. 4G (default: 4G; --sort-mem overrides)"
+    );
+    println!("  SORT_MERGE_COMPRESS  Set to 1 to zstd-compress intermediate files (~4x smaller)");
+    println!("  SORT_MERGE_ZSTD_LEVEL  zstd level for --compress (default: 3)");
 }
@@ -0,0 +1,79 @@
+//! Worst-case block benchmark: pathological regtest blocks through Core and BLVM
+//!
+//! Starts a fresh regtest node from the Core binaries [`NodeBuilder`] finds, submits the blocks
+//! of every [`StressCase`] ([`blvm_bench::regtest_node::stress_blocks`]) and prints both
+//! verdicts and timings per block. Exits 1 if either validator rejects one.
+//!
+//! Usage:
+//!   cargo run --release --bin stress_blocks --features differential -- \
+//!     --cases max_sigops,quadratic_sighash --json stress.json
+
+use anyhow::{Context, Result};
+use blvm_bench::node_builder::NodeBuilder;
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::regtest_node::stress_blocks::{StressCase, StressHarness};
+use blvm_bench::regtest_node::{PortManager, RegtestNode};
+use clap::Parser;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(name = "stress_blocks")]
+#[command(about = "Time Core and BLVM on valid worst-case regtest blocks")]
+struct Args {
+    /// Cases to run, in order (default: all)
+    #[arg(long, value_delimiter = ',')]
+    cases: Vec<String>,
+
+    /// First port for the regtest node
+    #[arg(long, default_value_t = 18843)]
+    port: u16,
+
+    /// Seconds to wait for `submitblock` on one block
+    #[arg(long, default_value_t = 600)]
+    rpc_timeout: u64,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let cases = if args.cases.is_empty() {
+        StressCase::ALL.to_vec()
+    } else {
+        args.cases
+            .iter()
+            .map(|name| {
+                StressCase::from_name(name).with_context(|| {
+                    let names: Vec<_> = StressCase::ALL.iter().map(|c| c.name()).collect();
+                    format!("Unknown case {} (one of {})", name, names.join(", "))
+                })
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let binaries = NodeBuilder::new()
+        .find_existing_core()
+        .context("Stress blocks need a local Bitcoin Core build")?;
+    let node =
+        RegtestNode::start_with_port_manager(binaries, Arc::new(PortManager::new(args.port)))
+            .await?;
+    let mut config = RpcConfig::from_regtest_node(&node);
+    config.timeout = Duration::from_secs(args.rpc_timeout);
+    let mut harness = StressHarness::new(NodeRpcClient::new(config)).await?;
+
+    let report = harness.run_all(&cases).await?;
+    report.print();
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("💾 Report written to {}", path.display());
+    }
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
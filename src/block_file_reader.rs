@@ -5,103 +5,53 @@
 
 use anyhow::{Context, Result};
 use hex;
-use memchr::memchr_iter;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tracing::{debug, error, info, warn};
+
+use crate::block_framing::{is_padding, XorKey, RESYNC_SIZE_RANGE};
+use crate::block_index::{BlockLocation, CoreBlockIndex};
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::config::BenchConfig;
+use crate::location_index::{Fingerprint, LocationStore};
+use crate::sanity::SanityStage;
+use crate::storage_tiers::{chunk_file_name, StorageTiers};
 
 /// Standard Bitcoin block file format (blk*.dat):
 /// - Magic bytes: 4 bytes (0xf9beb4d9 for mainnet)
 /// - Block size: 4 bytes (little-endian)
 /// - Block data: variable size
-const BLOCK_MAGIC_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+pub const BLOCK_MAGIC_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
 const BLOCK_MAGIC_TESTNET: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
 const BLOCK_MAGIC_REGTEST: [u8; 4] = [0xfa, 0xbf, 0xb5, 0xda];
+const BLOCK_MAGIC_TESTNET4: [u8; 4] = [0x1c, 0x16, 0x3f, 0x28];
+/// Default signet only; custom signets derive their magic from the challenge script
+const BLOCK_MAGIC_SIGNET: [u8; 4] = [0x0a, 0x03, 0xcf, 0x40];
 
-// ============================================================================
-// Performance tuning constants - adjust these to optimize for your system
-// ============================================================================
-// Tuned for: Intel i7-8700K (6 cores, 12 threads), 15GB RAM, NVMe SSD
-// ============================================================================
-
-/// I/O buffer size for file reading and writing (in bytes)
-/// Larger buffers reduce system calls and improve throughput for large files
-/// Tuned: 128MB for NVMe SSD (excellent sequential I/O performance)
-/// For HDD: use 64MB, for NVMe: 128MB+ is optimal
-const IO_BUFFER_SIZE: usize = 128 * 1024 * 1024;
-
-/// Search buffer size for pattern matching (in bytes)
-/// Used when searching for block magic bytes in encrypted/out-of-order files
-/// Tuned: 128MB to match IO_BUFFER_SIZE and leverage available RAM
-const SEARCH_BUFFER_SIZE: usize = 128 * 1024 * 1024;
-
-/// Chunk size for processing blocks when building hash maps (number of blocks)
-/// Smaller chunks use less memory but may be slower
-/// Tuned: 500 blocks (reduced from 2000 to prevent OOM - we only need headers, not full blocks)
-/// With 500 blocks @ 1.5MB avg = ~750MB per chunk (safe for 15GB RAM)
-const HASH_MAP_CHUNK_SIZE: usize = 500;
-
-/// Maximum number of threads for parallel file reading
-/// REDUCED: 8 threads (from 16) to prevent OOM kills - cuts I/O buffer memory in half
-/// 8 threads × 128MB = 1GB buffers (vs 2GB with 16 threads)
-/// Still provides good parallelism for I/O-bound SSHFS operations
-const MAX_PARALLEL_READ_THREADS: usize = 8;
-
-/// Batch size for parallel file reading (files processed in parallel per batch)
-/// REDUCED: 12 files per batch (from 24) to reduce concurrent operations and memory pressure
-/// Still provides good parallelism while reducing peak memory usage
-const PARALLEL_FILE_BATCH_SIZE: usize = 12;
-
-/// Number of files to pre-copy ahead of current reading position
-/// Tuned: 200 files ahead to ensure local cache is ready before reading
-/// Larger lookahead ensures files are cached before we need them
-const PRE_COPY_LOOKAHEAD: usize = 200;
-
-/// Number of worker threads for background file copying
-/// Used when copying files from remote mounts (SSHFS, etc.)
-/// REDUCED: 8 threads (from 12) to reduce background memory usage
-/// Still provides good parallelism for file copying operations
-const FILE_COPY_WORKER_THREADS: usize = 8;
-
-/// Progress reporting interval (number of blocks)
-/// How often to print progress updates during long operations
-/// Tuned: 10000 (good balance - not too frequent, not too sparse)
-const PROGRESS_REPORT_INTERVAL: usize = 10000;
-
-/// Flush interval for temp file writes (number of blocks)
-/// How often to flush buffers to disk to prevent data loss on SIGKILL
-/// Tuned: 500 blocks (more frequent flushes for safety with larger buffers)
-const TEMP_FILE_FLUSH_INTERVAL: usize = 500;
-
-/// Integrity check interval for temp file (number of blocks)
-/// How often to verify blocks written to temp file are valid and readable
-/// Tuned: 10000 blocks (balance between safety and performance)
-const TEMP_FILE_INTEGRITY_CHECK_INTERVAL: usize = 10000;
-
-/// Chunk size for incremental chunking during collection (number of blocks)
-/// When this many blocks are collected, compress and move to secondary drive
-/// Tuned: 125000 blocks per chunk (matches chunking script)
-const INCREMENTAL_CHUNK_SIZE: usize = 125000;
+// Buffer sizes, thread counts and chunking intervals come from the reader's
+// [`BenchConfig`] (`blvm-bench.toml` / env, see [`crate::config`]).
 
 /// Default under-repo cache when `BLOCK_CACHE_DIR` is unset
 const FALLBACK_CHUNK_DIR: &str = ".cache/blvm-bench/chunks";
 
 fn incremental_chunk_destination() -> std::path::PathBuf {
-    std::env::var("BLOCK_CACHE_DIR")
-        .ok()
-        .filter(|s| !s.is_empty())
-        .map(std::path::PathBuf::from)
-        .unwrap_or_else(|| std::path::PathBuf::from(FALLBACK_CHUNK_DIR))
+    crate::config::chunk_dir().unwrap_or_else(|| std::path::PathBuf::from(FALLBACK_CHUNK_DIR))
 }
 
-/// Maximum block size for validation (Bitcoin max is ~4MB, but allow up to 10MB for safety)
-const MAX_VALID_BLOCK_SIZE: usize = 10 * 1024 * 1024;
+/// Scratch and archive tiers for new chunks, with the chunk destination as the first archive.
+fn chunk_tiers(tuning: &BenchConfig) -> StorageTiers {
+    StorageTiers::new(Some(incremental_chunk_destination()), &tuning.storage)
+}
 
-/// Minimum block size (magic + size + header = 88 bytes minimum)
-const MIN_VALID_BLOCK_SIZE: usize = 88;
+/// Framing bounds used when walking length-prefixed temp files (see [`crate::sanity`]).
+const MAX_VALID_BLOCK_SIZE: usize = crate::sanity::DEFAULT_MAX_BLOCK_SIZE;
+const MIN_VALID_BLOCK_SIZE: usize = crate::sanity::DEFAULT_MIN_BLOCK_SIZE;
 
 fn blvm_bench_cache_root() -> Option<PathBuf> {
     dirs::cache_dir()
@@ -125,27 +75,35 @@ fn ordered_blocks_cache_path_for_read() -> Option<PathBuf> {
     None
 }
 
-/// Create a chunk from temp file and move to secondary drive
+/// Bytes a zstd file decompresses to; errors if any frame is damaged or truncated.
+fn decoded_len(path: &Path) -> Result<u64> {
+    let mut decoder = zstd::stream::read::Decoder::new(File::open(path)?)?;
+    std::io::copy(&mut decoder, &mut std::io::sink())
+        .with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+/// Create a chunk from temp file in the scratch dir and move it to an archive tier
 /// Temp file contains exactly chunk_size blocks
 impl BlockFileReader {
     pub fn create_and_move_chunk_from_file(
         temp_file: &std::path::Path,
         chunk_num: usize,
         chunk_size: usize,
+        tuning: &BenchConfig,
     ) -> Result<()> {
         use std::io::{Read, Write};
 
+        // Metadata and the manifest live in the chunk dir; the chunk file may go to any archive
+        let tiers = chunk_tiers(tuning);
         let chunks_dir = incremental_chunk_destination();
         std::fs::create_dir_all(&chunks_dir)?;
 
-        let local_chunk = temp_file
-            .parent()
-            .unwrap_or_else(|| std::path::Path::new("."))
-            .join("chunks")
-            .join(format!("chunk_{}.bin.zst", chunk_num));
+        let local_chunk = tiers
+            .scratch_dir(temp_file)
+            .join(chunk_file_name(chunk_num as u64));
         std::fs::create_dir_all(local_chunk.parent().unwrap())?;
 
-        eprintln!(
+        info!(
             "   🔧 Compressing chunk {} ({} blocks)...",
             chunk_num, chunk_size
         );
@@ -153,31 +111,36 @@ impl BlockFileReader {
         // Open temp file - it contains exactly chunk_size blocks
         let mut temp_reader = std::fs::File::open(temp_file)?;
 
-        // Compress chunk with zstd
-        // OPTIMIZATION: Use -3 instead of -1 for better compression (10-15% better) with minimal speed loss
-        let mut zstd_proc = std::process::Command::new("zstd")
-            .args(&["-3", "--stdout"])
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::fs::File::create(&local_chunk)?)
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| anyhow::anyhow!("Failed to start zstd: {}", e))?;
-
-        // OPTIMIZATION: Use buffered writer for zstd stdin (faster than unbuffered writes)
+        // Compress chunk with zstd in-process (level and workers from `[chunks]` tuning)
+        let threads = match tuning.chunks.compression_threads {
+            0 => num_cpus::get() as u32,
+            n => n,
+        };
+        let mut encoder = zstd::stream::write::Encoder::new(
+            std::fs::File::create(&local_chunk)?,
+            tuning.chunks.compression_level,
+        )
+        .context("Failed to create zstd encoder")?;
+        encoder
+            .multithread(threads)
+            .context("Failed to enable multithreaded zstd")?;
+
+        // OPTIMIZATION: Buffer the small length-prefix / block writes in front of the encoder
         use std::io::BufWriter;
-        let mut zstd_stdin = BufWriter::with_capacity(
-            IO_BUFFER_SIZE,
-            zstd_proc
-                .stdin
-                .take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdin"))?,
-        );
+        let mut zstd_stdin = BufWriter::with_capacity(tuning.io.buffer_size, encoder);
 
         // Read and compress blocks
         // OPTIMIZATION: Skip corrupted blocks and continue (they're unusable anyway)
         let mut blocks_in_chunk = 0;
+        let mut uncompressed_bytes = 0u64;
         let mut skipped_blocks = 0;
         let mut current_block_index = 0;
+        let sanity = crate::sanity::filter(SanityStage::CacheLoad);
+        let progress = crate::progress::Progress::new(
+            format!("compress chunk {}", chunk_num),
+            chunk_size as u64,
+            "blocks",
+        );
 
         while blocks_in_chunk < chunk_size {
             let mut len_buf = [0u8; 4];
@@ -190,23 +153,23 @@ impl BlockFileReader {
             let block_len = u32::from_le_bytes(len_buf) as usize;
 
             // Validate size - skip corrupted blocks
-            if block_len > MAX_VALID_BLOCK_SIZE || block_len < MIN_VALID_BLOCK_SIZE {
-                eprintln!(
-                    "   ⚠️  WARNING: Skipping corrupted block {} in chunk {} (size: {} bytes)",
-                    current_block_index, chunk_num, block_len
+            if let Err(rejection) = sanity.check_len(block_len) {
+                warn!(
+                    "   ⚠️  WARNING: Skipping corrupted block {} in chunk {} ({})",
+                    current_block_index, chunk_num, rejection
                 );
                 skipped_blocks += 1;
                 current_block_index += 1;
                 // Try to skip past this corrupted block if size is reasonable
                 // If size is absurdly large, we can't seek past it - break
                 if block_len > 10 * 1024 * 1024 * 1024 {
-                    eprintln!("   ⚠️  ERROR: Corrupted block size too large to skip ({} bytes), stopping chunk", block_len);
+                    error!("   ⚠️  ERROR: Corrupted block size too large to skip ({} bytes), stopping chunk", block_len);
                     break;
                 }
                 // Seek past the corrupted block data
                 use std::io::Seek;
                 if let Err(e) = temp_reader.seek(std::io::SeekFrom::Current(block_len as i64)) {
-                    eprintln!(
+                    error!(
                         "   ⚠️  ERROR: Cannot seek past corrupted block: {}, stopping chunk",
                         e
                     );
@@ -220,7 +183,7 @@ impl BlockFileReader {
             match temp_reader.read_exact(&mut block_data) {
                 Ok(_) => {}
                 Err(e) => {
-                    eprintln!(
+                    warn!(
                         "   ⚠️  WARNING: Cannot read block {} in chunk {}: {}, skipping",
                         current_block_index, chunk_num, e
                     );
@@ -232,89 +195,21 @@ impl BlockFileReader {
 
             // VALIDATION: Validate block structure during chunking
             // This is where we validate blocks that were collected without validation
-            let mut is_valid = true;
-            if block_data.len() >= 4 {
-                let version = u32::from_le_bytes([
-                    block_data[0],
-                    block_data[1],
-                    block_data[2],
-                    block_data[3],
-                ]);
-                // CRITICAL FIX: Bitcoin block versions can be much higher than 10
-                // Valid versions include: 1-4 (standard), 0x20000000+ (BIP9), etc.
-                // Only reject obviously invalid: version == 0 or version > 0x7fffffff (would be negative if signed)
-                if version == 0 || version > 0x7fffffff {
-                    eprintln!(
-                        "   ⚠️  WARNING: Skipping block {} in chunk {} (invalid version: {})",
-                        current_block_index, chunk_num, version
-                    );
+            let is_valid = match sanity.check(&block_data) {
+                Ok(()) => true,
+                Err(rejection) => {
+                    // Only log first few to avoid I/O overhead
+                    if skipped_blocks < 10 {
+                        warn!(
+                            "   ⚠️  WARNING: Skipping block {} in chunk {} ({})",
+                            current_block_index, chunk_num, rejection
+                        );
+                    }
                     skipped_blocks += 1;
                     current_block_index += 1;
-                    is_valid = false;
-                }
-            }
-
-            // Additional validation: check block has reasonable structure
-            if is_valid && block_data.len() < MIN_VALID_BLOCK_SIZE {
-                eprintln!(
-                    "   ⚠️  WARNING: Skipping block {} in chunk {} (too small: {} bytes)",
-                    current_block_index,
-                    chunk_num,
-                    block_data.len()
-                );
-                skipped_blocks += 1;
-                current_block_index += 1;
-                is_valid = false;
-            }
-
-            // OPTIMIZED VALIDATION: Only check suspicious blocks (fast path for 99.9% of blocks)
-            // Check prev_hash first (cheap array check) - only do expensive hash if needed
-            if is_valid && block_data.len() >= 80 {
-                let prev_hash = &block_data[4..36];
-                let is_genesis = prev_hash.iter().all(|&b| b == 0);
-
-                // Only do expensive hash calculation if prev_hash is suspicious (all zeros)
-                if is_genesis {
-                    use sha2::{Digest, Sha256};
-                    let header = &block_data[0..80];
-
-                    // Calculate block hash to verify if this is actually genesis
-                    let first_hash = Sha256::digest(header);
-                    let second_hash = Sha256::digest(&first_hash);
-
-                    // Check if hash is all zeros (indicates corruption)
-                    if second_hash.iter().all(|&b| b == 0) {
-                        // Only log first few to avoid I/O overhead
-                        if skipped_blocks < 10 {
-                            eprintln!("   ⚠️  WARNING: Skipping block {} in chunk {} (all-zero hash - corrupted)", current_block_index, chunk_num);
-                        }
-                        skipped_blocks += 1;
-                        current_block_index += 1;
-                        is_valid = false;
-                    } else {
-                        // Fast byte comparison instead of hex::encode (10x faster)
-                        // Genesis hash prefix in big-endian: 000000000019d668
-                        let mut block_hash = [0u8; 32];
-                        block_hash.copy_from_slice(&second_hash);
-                        block_hash.reverse(); // Big-endian for comparison
-
-                        // Compare first 8 bytes directly (no string allocation)
-                        const GENESIS_PREFIX: [u8; 8] =
-                            [0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0xd6, 0x68];
-                        if block_hash[..8] != GENESIS_PREFIX {
-                            // Only log first few to avoid I/O overhead
-                            if skipped_blocks < 10 {
-                                eprintln!("   ⚠️  WARNING: Skipping block {} in chunk {} (prev_hash all zeros but not genesis - corrupted)", current_block_index, chunk_num);
-                            }
-                            skipped_blocks += 1;
-                            current_block_index += 1;
-                            is_valid = false;
-                        }
-                    }
+                    false
                 }
-                // For non-genesis blocks (99.9% of blocks), skip expensive validation
-                // They'll be validated during indexing/validation phase if needed
-            }
+            };
 
             // Only write valid blocks
             if is_valid {
@@ -322,151 +217,324 @@ impl BlockFileReader {
                 zstd_stdin.write_all(&len_buf)?;
                 zstd_stdin.write_all(&block_data)?;
                 blocks_in_chunk += 1;
-
-                // OPTIMIZATION: Reduce progress reporting frequency (less I/O overhead)
-                if blocks_in_chunk % 25000 == 0 {
-                    eprintln!(
-                        "     Compressed {}/{} blocks... ({} skipped)",
-                        blocks_in_chunk, chunk_size, skipped_blocks
-                    );
-                }
+                uncompressed_bytes += 4 + block_len as u64;
+                progress.inc(1);
+            } else {
+                progress.set_message(format!("{} skipped", skipped_blocks));
             }
 
             current_block_index += 1;
         }
 
-        // OPTIMIZATION: Flush buffer before dropping
-        zstd_stdin.flush()?;
-        drop(zstd_stdin);
-        let output = zstd_proc.wait_with_output()?;
+        drop(progress);
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "zstd compression failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
+        // Flush the buffer and write the final zstd frame; dropping the encoder would not
+        let encoder = zstd_stdin
+            .into_inner()
+            .map_err(|e| anyhow::anyhow!("zstd compression failed: {}", e.error()))?;
+        encoder
+            .finish()
+            .context("zstd compression failed")?
+            .sync_all()?;
 
         if skipped_blocks > 0 {
-            eprintln!(
+            warn!(
                 "   ⚠️  Chunk {} compressed: {} valid blocks ({} corrupted blocks skipped)",
                 chunk_num, blocks_in_chunk, skipped_blocks
             );
         } else {
-            eprintln!(
+            info!(
                 "   ✅ Chunk {} compressed: {} blocks",
                 chunk_num, blocks_in_chunk
             );
         }
 
-        // Move to secondary drive
-        let secondary_chunk = chunks_dir.join(format!("chunk_{}.bin.zst", chunk_num));
-
-        // CRITICAL FIX: Check if chunk already exists before overwriting
-        if secondary_chunk.exists() {
-            let existing_size = std::fs::metadata(&secondary_chunk)?.len();
+        // CRITICAL FIX: Check if chunk already exists (in any archive) before overwriting.
+        // With a manifest entry, a chunk that fails verification (truncated by an interrupted
+        // copy) is replaced, and an intact one only by a chunk that decodes in full and holds at
+        // least as many blocks. Without one, a much smaller chunk never replaces a large one.
+        let existing = tiers
+            .locate(&chunks_dir, chunk_num as u64)
+            .map(|dir| dir.join(chunk_file_name(chunk_num as u64)));
+        if let Some(secondary_chunk) = &existing {
+            let entry = crate::chunk_manifest::Manifest::load(&chunks_dir)?
+                .as_ref()
+                .and_then(|m| m.get(chunk_num as u64))
+                .cloned();
+            let existing_size = std::fs::metadata(secondary_chunk)?.len();
             let new_size = std::fs::metadata(&local_chunk)?.len();
-            if existing_size > 1000 && new_size < existing_size / 10 {
-                // Existing chunk is much larger - don't overwrite with tiny file
-                eprintln!("   ⚠️  ERROR: chunk_{}.bin.zst already exists ({} bytes) and new chunk is much smaller ({} bytes) - SKIPPING to prevent corruption", 
-                         chunk_num, existing_size, new_size);
+            let refusal = match entry {
+                Some(entry)
+                    if entry
+                        .check(secondary_chunk, crate::chunk_manifest::VerifyMode::Full)
+                        .is_err() =>
+                {
+                    warn!(
+                        "   ⚠️  chunk_{}.bin.zst fails manifest verification - replacing it",
+                        chunk_num
+                    );
+                    None
+                }
+                Some(entry) => {
+                    let decoded = decoded_len(&local_chunk).ok();
+                    if decoded != Some(uncompressed_bytes)
+                        || (blocks_in_chunk as u64) < entry.blocks
+                    {
+                        Some(format!(
+                            "existing chunk is intact ({} blocks) and the new one ({} blocks, decodes to {:?} of {} bytes) does not replace it",
+                            entry.blocks, blocks_in_chunk, decoded, uncompressed_bytes
+                        ))
+                    } else {
+                        warn!(
+                            "   ⚠️  Replacing intact chunk_{}.bin.zst ({} blocks) with a verified one ({} blocks)",
+                            chunk_num, entry.blocks, blocks_in_chunk
+                        );
+                        None
+                    }
+                }
+                None if existing_size > 1000 && new_size < existing_size / 10 => Some(format!(
+                    "existing chunk is much larger ({} bytes) than the new one ({} bytes)",
+                    existing_size, new_size
+                )),
+                None => None,
+            };
+            if let Some(reason) = refusal {
+                error!(
+                    "   ⚠️  ERROR: chunk_{}.bin.zst already exists - SKIPPING to prevent corruption: {}",
+                    chunk_num, reason
+                );
                 return Err(anyhow::anyhow!(
-                    "Chunk {} already exists and is much larger - refusing to overwrite",
-                    chunk_num
+                    "Chunk {} already exists - refusing to overwrite: {}",
+                    chunk_num,
+                    reason
                 ));
             }
         }
 
-        eprintln!("   📦 Moving chunk {} to secondary drive...", chunk_num);
+        // Move to the first archive with room
+        let secondary_chunk = match existing {
+            Some(path) => path,
+            None => tiers
+                .place(std::fs::metadata(&local_chunk)?.len())?
+                .join(chunk_file_name(chunk_num as u64)),
+        };
+        info!(
+            "   📦 Moving chunk {} to {}...",
+            chunk_num,
+            secondary_chunk.display()
+        );
         std::fs::copy(&local_chunk, &secondary_chunk)?;
 
         // Verify copy
-        let local_size = std::fs::metadata(&local_chunk)?.len();
-        let secondary_size = std::fs::metadata(&secondary_chunk)?.len();
+        let (local_size, local_sha256) = crate::chunk_manifest::hash_file(&local_chunk)?;
+        let (secondary_size, secondary_sha256) =
+            crate::chunk_manifest::hash_file(&secondary_chunk)?;
 
-        if local_size != secondary_size {
+        if local_size != secondary_size || local_sha256 != secondary_sha256 {
             return Err(anyhow::anyhow!(
-                "Copy verification failed: {} != {}",
+                "Copy verification failed: {} bytes ({}) != {} bytes ({})",
                 local_size,
-                secondary_size
+                hex::encode(local_sha256),
+                secondary_size,
+                hex::encode(secondary_sha256)
             ));
         }
 
         // CRITICAL SAFEGUARD: NEVER delete chunks from final destination
         // But allow deletion of temporary cache copies after successful move
         // Check if local_chunk is in cache (temporary) vs final destination (protected)
-        let is_cache_copy = local_chunk
-            .parent()
-            .and_then(|p| p.to_str())
-            .map(|s| s.contains(".cache") || s.contains("temp"))
-            .unwrap_or(false);
-
-        let is_final_destination = local_chunk.parent().map_or(false, |parent| {
-            std::env::var_os("BLOCK_CACHE_DIR")
-                .map(std::path::PathBuf::from)
-                .filter(|root| !root.as_os_str().is_empty())
-                .is_some_and(|root| parent.starts_with(&root))
+        let is_cache_copy = local_chunk.parent().map_or(false, |parent| {
+            tiers.is_scratch(parent)
+                || parent
+                    .to_str()
+                    .is_some_and(|s| s.contains(".cache") || s.contains("temp"))
         });
 
+        let is_final_destination = tiers.is_archived(&local_chunk);
+
         if is_final_destination {
             // Trying to delete from final destination - BLOCKED
-            eprintln!(
+            warn!(
                 "   ⚠️  Skipping deletion of {} (protected final chunk)",
                 local_chunk.display()
             );
         } else if is_cache_copy {
             // Safe to delete - it's a temporary cache copy that was successfully moved
             std::fs::remove_file(&local_chunk)?;
-            eprintln!(
+            info!(
                 "   ✅ Deleted temporary cache copy: {}",
                 local_chunk.display()
             );
         } else {
             // Unknown location - be safe and don't delete
-            eprintln!(
+            warn!(
                 "   ⚠️  Skipping deletion of {} (unknown location)",
                 local_chunk.display()
             );
         }
 
-        eprintln!(
-            "   ✅ Chunk {} moved to secondary drive ({} bytes)",
+        info!(
+            "   ✅ Chunk {} moved to archive ({} bytes)",
             chunk_num, secondary_size
         );
 
+        crate::meta_store::record_chunk(
+            &chunks_dir,
+            &crate::meta_store::ChunkEntry {
+                chunk_num: chunk_num as u64,
+                blocks: blocks_in_chunk as u64,
+                compressed_bytes: secondary_size,
+            },
+        )?;
+        crate::chunk_manifest::record(
+            &chunks_dir,
+            crate::chunk_manifest::ManifestEntry {
+                chunk_num: chunk_num as u64,
+                first_block: (chunk_num * tuning.chunks.incremental_chunk_size) as u64,
+                last_block: (chunk_num * tuning.chunks.incremental_chunk_size + blocks_in_chunk)
+                    .saturating_sub(1) as u64,
+                blocks: blocks_in_chunk as u64,
+                compressed_bytes: secondary_size,
+                sha256: secondary_sha256,
+            },
+        )?;
+
         Ok(())
     }
 }
 
+/// Outcome of a collection pass ([`BlockFileReader::collect_ordered`])
+#[derive(Debug, Clone)]
+pub struct CollectionReport {
+    /// Blocks in the temp file after this pass (including blocks resumed from earlier runs)
+    pub blocks_collected: u64,
+    /// Block files processed
+    pub files_processed: usize,
+    /// Length-prefixed temp file the blocks were written to (never deleted)
+    pub temp_file: PathBuf,
+    /// Chunk cache directory, if one is configured
+    pub chunks_dir: Option<PathBuf>,
+    /// Blocks covered by the chunk metadata (0 if no metadata yet)
+    pub chunked_blocks: u64,
+    /// Chunked block count treated as the whole chain (`chunks.full_chain_blocks`)
+    pub full_chain_blocks: u64,
+    pub duration_secs: f64,
+}
+
+impl CollectionReport {
+    /// Whether the chunks cover the whole chain, so ordered reads can come from the chunk cache.
+    pub fn is_complete(&self) -> bool {
+        self.chunked_blocks >= self.full_chain_blocks
+    }
+}
+
+/// Chunk cache directory and the block count recorded in its metadata (0 if missing/unreadable).
+fn chunk_collection_status() -> (Option<PathBuf>, u64) {
+    let chunks_dir = crate::chunked_cache::get_chunks_dir().filter(|p| p.exists());
+    let chunked_blocks = chunks_dir
+        .as_deref()
+        .and_then(|p| crate::chunked_cache::load_chunk_metadata(p).ok().flatten())
+        .map(|m| m.total_blocks)
+        .unwrap_or(0);
+    (chunks_dir, chunked_blocks)
+}
+
+/// Obfuscation key from `<blocks_dir>/xor.dat` (Bitcoin Core 28+).
+///
+/// `None` when the file is missing or holds the all-zero key.
+pub fn load_xor_key(blocks_dir: &Path) -> Result<Option<XorKey>> {
+    let path = blocks_dir.join("xor.dat");
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    XorKey::from_xor_dat(&contents).with_context(|| format!("Invalid {}", path.display()))
+}
+
 /// Block file reader for standard blk*.dat format
+#[derive(Clone)]
 pub struct BlockFileReader {
     data_dir: PathBuf,
     network: Network,
     block_files: Vec<PathBuf>,
     local_cache_dir: Option<PathBuf>, // For incremental local copying
     file_index: Option<std::collections::HashSet<usize>>, // Pre-scanned index of files with blocks
+    cancel: CancellationToken,        // Checked per file / per block during collection
+    core_index: Arc<OnceLock<Option<Arc<CoreBlockIndex>>>>, // Core's blocks/index, read on first use
+    header_index: Arc<OnceLock<Option<Arc<CoreBlockIndex>>>>, // Headers-first scan (XOR-packaged trees)
+    xor_key: Option<XorKey>,  // blk*.dat obfuscation key (None: plain files)
+    tuning: Arc<BenchConfig>, // Buffer sizes / thread counts (crate::config)
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Network {
     Mainnet,
+    /// testnet3
     Testnet,
+    Testnet4,
+    /// Default signet
+    Signet,
     Regtest,
 }
 
 impl Network {
-    fn magic_bytes(&self) -> &[u8; 4] {
+    pub(crate) fn magic_bytes(&self) -> &[u8; 4] {
         match self {
             Network::Mainnet => &BLOCK_MAGIC_MAINNET,
             Network::Testnet => &BLOCK_MAGIC_TESTNET,
+            Network::Testnet4 => &BLOCK_MAGIC_TESTNET4,
+            Network::Signet => &BLOCK_MAGIC_SIGNET,
             Network::Regtest => &BLOCK_MAGIC_REGTEST,
         }
     }
+
+    /// Folder Core keeps this network's `blocks/` in, under the datadir (`None` for mainnet).
+    pub fn datadir_subdir(&self) -> Option<&'static str> {
+        match self {
+            Network::Mainnet => None,
+            Network::Testnet => Some("testnet3"),
+            Network::Testnet4 => Some("testnet4"),
+            Network::Signet => Some("signet"),
+            Network::Regtest => Some("regtest"),
+        }
+    }
+
+    /// `datadir` joined with [`datadir_subdir`](Self::datadir_subdir).
+    pub fn network_data_dir(&self, datadir: &Path) -> PathBuf {
+        match self.datadir_subdir() {
+            Some(sub) => datadir.join(sub),
+            None => datadir.to_path_buf(),
+        }
+    }
+
+    /// Genesis block hash (display order hex).
+    pub fn genesis_hash(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            Network::Testnet => "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            Network::Testnet4 => "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043",
+            Network::Signet => "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            Network::Regtest => "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        }
+    }
+
+    /// Consensus rules blocks of this network connect under (testnet4 and signet use testnet's).
+    pub fn consensus_network(&self) -> blvm_protocol::types::Network {
+        match self {
+            Network::Mainnet => blvm_protocol::types::Network::Mainnet,
+            Network::Testnet | Network::Testnet4 | Network::Signet => {
+                blvm_protocol::types::Network::Testnet
+            }
+            Network::Regtest => blvm_protocol::types::Network::Regtest,
+        }
+    }
 }
 
 impl BlockFileReader {
     /// Create a new block file reader
     pub fn new(data_dir: impl AsRef<Path>, network: Network) -> Result<Self> {
+        crate::progress::ensure_logging();
         let data_dir = data_dir.as_ref().to_path_buf();
         let blocks_dir = data_dir.join("blocks");
 
@@ -491,7 +559,7 @@ impl BlockFileReader {
                         }
                         Err(e) => {
                             // Permission error or other issue - continue trying other entries
-                            eprintln!("⚠️  Warning: Could not read directory entry: {}", e);
+                            warn!("⚠️  Warning: Could not read directory entry: {}", e);
                         }
                     }
                 }
@@ -511,6 +579,15 @@ impl BlockFileReader {
 
         block_files.sort(); // Process in order (blk00000.dat, blk00001.dat, etc.)
 
+        // Core 28+ records the key in xor.dat; packaged trees without one use a fixed key
+        let xor_key = if blocks_dir.join("xor.dat").exists() {
+            load_xor_key(&blocks_dir)?
+        } else if crate::block_cache_env::remote_core_xor_blockfiles_hint(&data_dir) {
+            Some(XorKey::PACKAGED)
+        } else {
+            None
+        };
+
         // Set up local cache directory for incremental copying (if data_dir is remote/SSHFS)
         let local_cache_dir =
             if crate::block_cache_env::remote_core_xor_blockfiles_hint(data_dir.as_path()) {
@@ -530,7 +607,7 @@ impl BlockFileReader {
         // This allows us to skip empty files entirely without opening them
         let file_index = if block_files.len() > 1000 {
             // For large file sets, pre-scan to build index
-            println!(
+            debug!(
                 "🔍 Pre-scanning {} files to build index (skip empty files)...",
                 block_files.len()
             );
@@ -585,7 +662,7 @@ impl BlockFileReader {
                 let _ = handle.join();
             }
 
-            println!(
+            info!(
                 "   ✅ Index built: {} files have blocks ({} empty files skipped)",
                 index.len(),
                 block_files.len() - index.len()
@@ -601,24 +678,48 @@ impl BlockFileReader {
             block_files,
             local_cache_dir,
             file_index,
+            cancel: CancellationToken::new(),
+            core_index: Arc::new(OnceLock::new()),
+            header_index: Arc::new(OnceLock::new()),
+            xor_key,
+            tuning: Arc::new(crate::config::global().clone()),
         })
     }
 
+    /// Stop collection / iteration once `token` is cancelled. Iterators created afterwards
+    /// return a [`Cancelled`](crate::cancel::Cancelled) error at the next file or block boundary.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Use `tuning` instead of the process-wide [`crate::config::global`] values for buffers,
+    /// read threads and chunking.
+    pub fn with_tuning(mut self, tuning: BenchConfig) -> Self {
+        self.tuning = Arc::new(tuning);
+        self
+    }
+
+    pub fn tuning(&self) -> &BenchConfig {
+        &self.tuning
+    }
+
     /// Auto-detect Bitcoin data directory from `BITCOIN_DATA_DIR*` env, then common local paths.
+    ///
+    /// Non-mainnet networks look in the network's subfolder (`signet/blocks`, ...). Env dirs
+    /// may also point at that subfolder directly; default paths never fall back to the mainnet
+    /// `blocks/`, whose files would just not match the network's magic.
     pub fn auto_detect(network: Network) -> Result<Self> {
-        let mut possible_dirs: Vec<PathBuf> = crate::block_cache_env::bitcoin_data_dir_candidates();
-        for extra in [
-            dirs::home_dir().map(|h| h.join(".bitcoin")),
-            Some(PathBuf::from("/root/.bitcoin")),
-            Some(PathBuf::from("/var/lib/bitcoind")),
-        ]
-        .into_iter()
-        .flatten()
-        {
-            if !possible_dirs.iter().any(|e| e == &extra) {
-                possible_dirs.push(extra);
-            }
+        let mut possible_dirs: Vec<PathBuf> = Vec::new();
+        for base in crate::block_cache_env::bitcoin_data_dir_candidates() {
+            possible_dirs.push(network.network_data_dir(&base));
+            possible_dirs.push(base);
+        }
+        for base in crate::platform::default_bitcoin_data_dirs() {
+            possible_dirs.push(network.network_data_dir(&base));
         }
+        let mut seen = std::collections::HashSet::new();
+        possible_dirs.retain(|d| seen.insert(d.clone()));
 
         for dir in possible_dirs {
             let blocks_dir = dir.join("blocks");
@@ -628,7 +729,7 @@ impl BlockFileReader {
                     Ok(reader) => return Ok(reader),
                     Err(e) => {
                         // Log but continue trying other locations
-                        eprintln!("⚠️  Could not read from {}: {}", dir.display(), e);
+                        warn!("⚠️  Could not read from {}: {}", dir.display(), e);
                         continue;
                     }
                 }
@@ -638,13 +739,165 @@ impl BlockFileReader {
         anyhow::bail!("Could not auto-detect Bitcoin data directory with readable blocks")
     }
 
-    /// Read a block by height (requires index or sequential scan)
+    /// Core's block index (`blocks/index`), read on first use and shared by clones.
+    ///
+    /// `None` when the data dir has no index or it cannot be parsed (logged once).
+    pub fn core_block_index(&self) -> Option<Arc<CoreBlockIndex>> {
+        self.core_index
+            .get_or_init(|| {
+                if !self.data_dir.join("blocks").join("index").is_dir() {
+                    return None;
+                }
+                match CoreBlockIndex::open(&self.data_dir) {
+                    Ok(index) => Some(Arc::new(index)),
+                    Err(e) => {
+                        warn!("⚠️  Could not read Core block index: {:#}", e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// Height index built from the block headers alone ([`crate::header_index`]), for
+    /// XOR-packaged trees that ship without `blocks/index`. Loaded from the
+    /// [location store](crate::location_index) when the block files are unchanged since the last
+    /// scan, else scanned on first use and saved. Shared by clones; `None` if the headers do not
+    /// chain from genesis (logged once).
+    pub fn header_index(&self) -> Option<Arc<CoreBlockIndex>> {
+        self.header_index
+            .get_or_init(|| match self.load_or_build_header_index() {
+                Ok(index) => Some(Arc::new(index)),
+                Err(e) => {
+                    warn!("⚠️  Could not build header index: {:#}", e);
+                    None
+                }
+            })
+            .clone()
+    }
+
+    fn open_location_store(&self) -> Result<Option<(LocationStore, Fingerprint)>> {
+        let Some(store) = LocationStore::for_data_dir(&self.data_dir)? else {
+            return Ok(None);
+        };
+        Ok(Some((store, Fingerprint::of(&self.block_files)?)))
+    }
+
+    fn load_or_build_header_index(&self) -> Result<CoreBlockIndex> {
+        let encrypted = self.xor_key.is_some();
+        let store = self.open_location_store().unwrap_or_else(|e| {
+            warn!("⚠️  Location store unavailable: {:#}", e);
+            None
+        });
+        if let Some((store, fingerprint)) = &store {
+            match store.load(fingerprint) {
+                Ok(Some(stored)) if stored.iter().all(|s| s.encrypted == encrypted) => {
+                    info!(
+                        "🧭 Loaded {} block locations from {}",
+                        stored.len(),
+                        store.path().display()
+                    );
+                    let headers = stored.len();
+                    return CoreBlockIndex::from_chain(
+                        stored.into_iter().map(|s| s.location).collect(),
+                        headers,
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "⚠️  Ignoring location store {}: {:#}",
+                    store.path().display(),
+                    e
+                ),
+            }
+        }
+
+        info!(
+            "🧭 Building header index of {} block files",
+            self.block_files.len()
+        );
+        let chain = crate::header_index::build(
+            &self.block_files,
+            self.network.magic_bytes(),
+            self.xor_key,
+            self.network.genesis_hash(),
+            &self.cancel,
+        )?;
+        let index = chain.index()?;
+        if let Some((store, fingerprint)) = &store {
+            match store.save_chain(fingerprint, &chain, encrypted) {
+                Ok(()) => info!("💾 Saved block locations to {}", store.path().display()),
+                Err(e) => warn!("⚠️  Could not save block locations: {:#}", e),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Core's block index, or for XOR-packaged trees without one, the [header index](Self::header_index).
+    pub fn chain_index(&self) -> Option<Arc<CoreBlockIndex>> {
+        self.core_block_index().or_else(|| {
+            if self.is_xor_packaged() {
+                self.header_index()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Read a block by height via the [chain index](Self::chain_index) (one seek, no scanning)
     ///
-    /// Note: This is slower than RPC for random access, but faster for sequential access
-    /// because we can read blocks directly from disk without network overhead.
-    pub fn read_block_by_height(&self, _height: u64) -> Result<Vec<u8>> {
-        // Future: map height via Bitcoin Core LevelDB `blocks/index/*` or an internal height index.
-        anyhow::bail!("Direct height lookup not yet implemented. Use read_block_by_hash or sequential reading.")
+    /// Fails if there is no chain index, the height is above the indexed tip, or the block was
+    /// pruned.
+    pub fn read_block_by_height(&self, height: u64) -> Result<Vec<u8>> {
+        let index = self.chain_index().context(
+            "No readable Core block index (blocks/index) - use read_blocks_sequential instead",
+        )?;
+        let location = index.location(height).with_context(|| {
+            format!(
+                "Height {} is pruned or above the indexed tip {}",
+                height,
+                index.tip_height()
+            )
+        })?;
+        self.read_block_at(&location)
+            .with_context(|| format!("Failed to read block {} via the block index", height))
+    }
+
+    /// Read the block at an index location and check it hashes to the indexed hash.
+    pub fn read_block_at(&self, location: &BlockLocation) -> Result<Vec<u8>> {
+        let path = self.data_dir.join("blocks").join(location.file_name());
+        let mut file =
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let size_offset = u64::from(location.data_pos)
+            .checked_sub(4)
+            .context("Block data position precedes its size field")?;
+        file.seek(SeekFrom::Start(size_offset))?;
+        let mut size_bytes = [0u8; 4];
+        file.read_exact(&mut size_bytes)?;
+        if let Some(key) = self.xor_key {
+            key.apply(&mut size_bytes, size_offset);
+        }
+        let size = u32::from_le_bytes(size_bytes) as usize;
+        anyhow::ensure!(
+            (80..=MAX_VALID_BLOCK_SIZE).contains(&size),
+            "Implausible block size {} at {}:{}",
+            size,
+            location.file_name(),
+            location.data_pos
+        );
+        let mut block = vec![0u8; size];
+        file.read_exact(&mut block)?;
+        if let Some(key) = self.xor_key {
+            key.apply(&mut block, u64::from(location.data_pos));
+        }
+        let hash = crate::wire::sha256d(&block[..80]);
+        anyhow::ensure!(
+            hash == location.hash,
+            "Block at {}:{} does not match the block index hash",
+            location.file_name(),
+            location.data_pos
+        );
+        Ok(block)
     }
 
     /// Read blocks sequentially from block files
@@ -654,24 +907,75 @@ impl BlockFileReader {
     /// 2. No RPC serialization overhead
     /// 3. Direct disk I/O (can be cached by OS)
     ///
-    /// For XOR-packaged `blk*.dat` trees, blocks may be stored out of order.
-    /// This method reads all blocks and chains them by previous block hash.
+    /// For XOR-packaged `blk*.dat` trees, blocks may be stored out of order. Reads then come from
+    /// the chunk cache if it covers the chain, else through the [header index](Self::header_index)
+    /// one block at a time; only if the headers cannot be chained does a collection pass
+    /// ([`collect_ordered`](Self::collect_ordered)) run first.
     pub fn read_blocks_sequential(
         &self,
         start_height: Option<u64>,
         max_blocks: Option<usize>,
     ) -> Result<BlockIterator> {
-        let xor_packaged = crate::block_cache_env::remote_core_xor_blockfiles_hint(&self.data_dir);
-
-        if xor_packaged {
+        if self.is_xor_packaged() {
             // Read all blocks and chain them by previous block hash
             BlockIterator::new_ordered(self, start_height, max_blocks)
+        } else if let Some(index) = self.core_block_index() {
+            // Core's block index gives height order and lets us start anywhere without a scan
+            Ok(BlockIterator::new_indexed(
+                self,
+                index,
+                start_height,
+                max_blocks,
+            ))
         } else {
             // Standard format - blocks are in order
             BlockIterator::new(self, start_height, max_blocks)
         }
     }
 
+    /// Bitcoin Core data directory this reader was opened on.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Key the `blk*.dat` files are obfuscated with: `blocks/xor.dat` (Core 28+), else
+    /// [`XorKey::PACKAGED`] for trees flagged XOR-packaged, else `None`.
+    pub fn xor_key(&self) -> Option<XorKey> {
+        self.xor_key
+    }
+
+    /// Whether this tree stores blocks XOR-packaged and out of order (needs a collection pass).
+    pub fn is_xor_packaged(&self) -> bool {
+        crate::block_cache_env::remote_core_xor_blockfiles_hint(&self.data_dir)
+    }
+
+    /// Whether ordered reads still depend on a collection pass: the tree is XOR-packaged, the
+    /// chunk cache does not yet cover the whole chain and no [header index](Self::header_index)
+    /// can be built.
+    pub fn needs_collection(&self) -> bool {
+        self.is_xor_packaged()
+            && chunk_collection_status().1 < self.tuning.chunks.full_chain_blocks
+            && self.header_index().is_none()
+    }
+
+    /// Collection only: read every block file into the temp file and the incremental chunk cache
+    /// without yielding blocks. Resumes where a previous (possibly cancelled) run stopped.
+    ///
+    /// Only meaningful for XOR-packaged trees; standard trees are already ordered on disk.
+    pub fn collect_ordered(&self) -> Result<CollectionReport> {
+        if !self.is_xor_packaged() {
+            anyhow::bail!(
+                "{} is not an XOR-packaged block tree - blocks are already in order, nothing to collect",
+                self.data_dir.display()
+            );
+        }
+        BlockIterator::collect_all(self)
+    }
+
     /// Read a block by hash (requires scanning or index)
     pub fn read_block_by_hash(&self, block_hash: &[u8; 32]) -> Result<Vec<u8>> {
         // Scan through block files to find matching hash
@@ -699,11 +1003,66 @@ impl BlockFileReader {
     }
 }
 
+/// Why [`BlockIterator`] passed over part of a block file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Neither a frame nor zero padding where a frame should start. Plain files end there,
+    /// obfuscated ones resync on the next magic.
+    Garbage,
+    /// Size field out of range. Plain files end there, obfuscated ones take the bytes up to the
+    /// next frame as the block (dropped if there is none).
+    BadSize,
+    /// A frame (or its header) runs past the end of the file
+    Truncated,
+    /// Obfuscated frame rejected by the read-stage sanity filter; reading continues after it
+    Rejected,
+    /// No frame within the search distance; rest of the file dropped
+    SearchLimit,
+    /// Non-empty file too small to hold a block
+    TooSmall,
+    /// I/O error other than a short read; rest of the file dropped
+    ReadError,
+}
+
+/// One [`SkipReason`] at a file offset, see [`BlockIterator::skips`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkipEvent {
+    /// Index of the `blk*.dat` file, in read order
+    pub file: usize,
+    /// Where the frame, or the bytes passed over, start
+    pub offset: u64,
+    pub reason: SkipReason,
+}
+
+/// What one [`BlockIterator::read_frame`] call found
+enum FrameRead {
+    Block(Vec<u8>),
+    /// A frame the sanity filter rejected; the file is positioned after it
+    Rejected,
+    /// Nothing more to read from this file
+    End,
+}
+
+/// Whether the bytes from `at` to the end of `file` (a short read: fewer than a frame header)
+/// are anything but padding, i.e. a frame cut short
+fn cut_short(
+    file: &mut BufReader<crate::platform::SequentialFile>,
+    at: u64,
+    key: Option<XorKey>,
+) -> bool {
+    let mut tail = Vec::new();
+    if file.seek(SeekFrom::Start(at)).is_err() || file.take(8).read_to_end(&mut tail).is_err() {
+        return false;
+    }
+    !is_padding(&tail, at, key)
+}
+
 /// Iterator over blocks in block files
 pub struct BlockIterator {
     reader: BlockFileReader,
     current_file_idx: usize,
-    current_file: Option<BufReader<File>>,
+    current_file: Option<BufReader<crate::platform::SequentialFile>>,
     current_height: u64,
     start_height: Option<u64>,
     max_blocks: Option<usize>,
@@ -724,14 +1083,44 @@ pub struct BlockIterator {
     failed_files: std::collections::HashSet<usize>,
     // Track which file index we're currently reading from (for error tracking)
     current_reading_file_idx: Option<usize>,
-    // CRITICAL FIX: Temp file writer for sequential reading mode (when ordered_blocks = None)
-    // This allows the iterator to write blocks to temp file and create chunks incrementally
-    temp_writer: Option<std::io::BufWriter<std::fs::File>>,
-    temp_file_path: Option<std::path::PathBuf>,
-    blocks_written_to_temp: u64, // Track blocks written to temp file for chunking
+    // Everything the sequential file path passed over, in read order
+    skips: Vec<SkipEvent>,
+    // Standard trees with a readable Core block index: read each height by seeking
+    core_index: Option<Arc<CoreBlockIndex>>,
 }
 
 impl BlockIterator {
+    /// Flush collection state so a later run can resume, then build the cancellation error.
+    fn collection_cancelled(
+        temp_writer: &mut std::io::BufWriter<File>,
+        temp_file: &Path,
+        read_count: usize,
+        processed_files: usize,
+    ) -> anyhow::Error {
+        // Flush and fsync before recording progress, so the metadata never counts blocks the
+        // temp file lost
+        if let Err(e) = temp_writer
+            .flush()
+            .and_then(|()| temp_writer.get_ref().sync_all())
+        {
+            error!("   ⚠️  ERROR: Failed to sync temp file on cancel: {}", e);
+        }
+        // The resume position stays at the last batch checkpoint: the current file may be
+        // partially written, so it is read again on resume.
+        if let Err(e) = crate::meta_store::record_temp_progress(temp_file, read_count as u64, None)
+        {
+            error!("   ⚠️  Warning: Failed to update metadata on cancel: {}", e);
+        }
+        let marker =
+            crate::shutdown::ResumeMarker::new(temp_file, read_count as u64, processed_files);
+        if let Err(e) = marker.write() {
+            error!("   ⚠️  Warning: Failed to write resume marker: {}", e);
+        }
+        // Printed rather than logged: it is the last thing an interrupted run says
+        eprintln!("{}", marker.summary());
+        Cancelled(format!("block collection at file {}", processed_files)).into()
+    }
+
     /// Process a chunk of blocks to build hash map (helper for OOM fix)
     /// OPTIMIZED: Process in parallel but with reduced chunk size and direct insertion
     fn process_chunk(
@@ -788,13 +1177,7 @@ impl BlockIterator {
         max_blocks: Option<usize>,
     ) -> Result<Self> {
         let mut iter = Self {
-            reader: BlockFileReader {
-                data_dir: reader.data_dir.clone(),
-                network: reader.network,
-                block_files: reader.block_files.clone(),
-                local_cache_dir: reader.local_cache_dir.clone(),
-                file_index: reader.file_index.clone(),
-            },
+            reader: reader.clone(),
             current_file_idx: 0,
             current_file: None,
             current_height: 0,
@@ -804,14 +1187,13 @@ impl BlockIterator {
             ordered_blocks: None,
             ordered_index: 0,
             chunked_iterator: None,
-            search_buffer: vec![0u8; SEARCH_BUFFER_SIZE],
+            search_buffer: vec![0u8; reader.tuning.io.search_buffer_size],
             copy_sender: None,
             last_copy_start_idx: 0,
             failed_files: std::collections::HashSet::new(), // Track files that failed to avoid retries
+            skips: Vec::new(),
             current_reading_file_idx: None,                 // Track which file we're reading from
-            temp_writer: None, // Will be initialized if needed for sequential reading
-            temp_file_path: None,
-            blocks_written_to_temp: 0,
+            core_index: None,
         };
 
         // Set chunked_iterator if available (will be set in new_ordered)
@@ -824,7 +1206,7 @@ impl BlockIterator {
             // Spawn worker threads for file copying (share receiver via Arc<Mutex>)
             // Increased to 20 workers for better throughput with sparse files
             let rx = std::sync::Arc::new(std::sync::Mutex::new(rx));
-            for _ in 0..FILE_COPY_WORKER_THREADS {
+            for _ in 0..reader.tuning.io.file_copy_worker_threads {
                 let rx = rx.clone();
                 std::thread::spawn(move || {
                     loop {
@@ -848,8 +1230,9 @@ impl BlockIterator {
         // Open first file with larger buffer for faster I/O
         if !iter.reader.block_files.is_empty() {
             let file_path = iter.get_local_or_remote_path(0)?;
-            let file = File::open(&file_path)?;
-            let mut buf_reader = BufReader::with_capacity(IO_BUFFER_SIZE, file);
+            let file =
+                crate::platform::open_sequential(&file_path, reader.tuning.io.uring_queue_depth)?;
+            let mut buf_reader = BufReader::with_capacity(reader.tuning.io.buffer_size, file);
             // CRITICAL: Ensure file starts at position 0
             use std::io::Seek;
             buf_reader.seek(std::io::SeekFrom::Start(0))?;
@@ -871,12 +1254,12 @@ impl BlockIterator {
     ) -> Result<Self> {
         // CRITICAL OPTIMIZATION: Check for chunks FIRST before reading any files!
         // BUT: Only skip file reading if chunks are COMPLETE (check metadata first)
-        println!("   📍 DEBUG: new_ordered: Checking for chunks first...");
+        debug!("   📍 DEBUG: new_ordered: Checking for chunks first...");
         let chunks_dir = crate::chunked_cache::get_chunks_dir();
         let mut chunked_iterator: Option<crate::chunked_cache::ChunkedBlockIterator> = None;
 
         if let Some(ref chunks_path) = chunks_dir {
-            println!(
+            debug!(
                 "   📍 DEBUG: Chunks dir: {:?}, exists: {}",
                 chunks_path,
                 chunks_path.exists()
@@ -886,26 +1269,25 @@ impl BlockIterator {
                 let should_use_chunks = if let Ok(Some(metadata)) =
                     crate::chunked_cache::load_chunk_metadata(chunks_path)
                 {
-                    let expected_blocks = 900000u64; // Approximate full chain size
-                    if metadata.total_blocks >= expected_blocks {
-                        println!(
+                    if metadata.total_blocks >= reader.tuning.chunks.full_chain_blocks {
+                        info!(
                             "   ✅ Chunks are complete ({} blocks >= {}k) - can use chunks",
                             metadata.total_blocks,
-                            expected_blocks / 1000
+                            reader.tuning.chunks.full_chain_blocks / 1000
                         );
                         true
                     } else {
-                        println!("   ⚠️  Chunks exist but incomplete ({} blocks < {}k) - continuing file reading", metadata.total_blocks, expected_blocks / 1000);
+                        warn!("   ⚠️  Chunks exist but incomplete ({} blocks < {}k) - continuing file reading", metadata.total_blocks, reader.tuning.chunks.full_chain_blocks / 1000);
                         false
                     }
                 } else {
                     // No metadata or can't read - assume incomplete, continue collection
-                    println!("   ⚠️  Chunks exist but no metadata - continuing file reading to ensure completeness...");
+                    warn!("   ⚠️  Chunks exist but no metadata - continuing file reading to ensure completeness...");
                     false
                 };
 
                 if should_use_chunks {
-                    println!("   📍 DEBUG: Chunks dir exists and complete, trying to create ChunkedBlockIterator...");
+                    debug!("   📍 DEBUG: Chunks dir exists and complete, trying to create ChunkedBlockIterator...");
                     // Try streaming iterator first (for large ranges)
                     match crate::chunked_cache::ChunkedBlockIterator::new(
                         chunks_path,
@@ -913,18 +1295,12 @@ impl BlockIterator {
                         max_blocks,
                     ) {
                         Ok(Some(iter)) => {
-                            println!("   ✅ Using streaming chunked cache iterator (skipping file reading entirely)");
-                            println!("   📍 DEBUG: Successfully created chunked iterator, returning early");
+                            info!("   ✅ Using streaming chunked cache iterator (skipping file reading entirely)");
+                            debug!("   📍 DEBUG: Successfully created chunked iterator, returning early");
                             chunked_iterator = Some(iter);
                             // Skip ALL file reading - chunks are already ordered!
                             return Ok(Self {
-                                reader: BlockFileReader {
-                                    data_dir: reader.data_dir.clone(),
-                                    network: reader.network,
-                                    block_files: reader.block_files.clone(),
-                                    local_cache_dir: reader.local_cache_dir.clone(),
-                                    file_index: reader.file_index.clone(),
-                                },
+                                reader: reader.clone(),
                                 current_file_idx: 0,
                                 current_file: None,
                                 current_height: start_height.unwrap_or(0),
@@ -934,42 +1310,44 @@ impl BlockIterator {
                                 ordered_blocks: None, // Use chunked_iterator instead
                                 ordered_index: 0,
                                 chunked_iterator,
-                                search_buffer: vec![0u8; SEARCH_BUFFER_SIZE],
+                                search_buffer: vec![0u8; reader.tuning.io.search_buffer_size],
                                 copy_sender: None,
                                 last_copy_start_idx: 0,
                                 failed_files: std::collections::HashSet::new(),
+                                skips: Vec::new(),
                                 current_reading_file_idx: None,
-                                temp_writer: None,
-                                temp_file_path: None,
-                                blocks_written_to_temp: 0,
+                                core_index: None,
                             });
                         }
                         Ok(None) => {
-                            println!("   📍 DEBUG: ChunkedBlockIterator::new returned None (no chunks for this range)");
+                            debug!("   📍 DEBUG: ChunkedBlockIterator::new returned None (no chunks for this range)");
                             // Chunked cache doesn't exist for this range, continue with file reading
                         }
                         Err(e) => {
-                            eprintln!("   ⚠️  Failed to create chunked cache iterator: {} - falling back to file reading", e);
-                            eprintln!("   📍 DEBUG: Error details: {:?}", e);
+                            error!("   ⚠️  Failed to create chunked cache iterator: {} - falling back to file reading", e);
+                            debug!("   📍 DEBUG: Error details: {:?}", e);
                             // Fallback to file reading
                         }
                     }
                 } else {
-                    println!("   📍 DEBUG: Chunks incomplete - will continue with file reading");
+                    debug!("   📍 DEBUG: Chunks incomplete - will continue with file reading");
                 }
             } else {
-                println!("   📍 DEBUG: Chunks dir does not exist");
+                debug!("   📍 DEBUG: Chunks dir does not exist");
             }
         } else {
-            println!("   📍 DEBUG: No chunks dir found");
+            debug!("   📍 DEBUG: No chunks dir found");
         }
 
-        // No chunks available - proceed with file reading (original logic)
-        println!("   📍 DEBUG: No chunks available, proceeding with file reading logic");
-        use sha2::{Digest, Sha256};
-        use std::path::PathBuf;
+        // Headers-first: chain the 80-byte headers, then read each full block by location on
+        // demand - no temp file
+        if let Some(index) = reader.header_index() {
+            return Ok(Self::new_indexed(reader, index, start_height, max_blocks));
+        }
 
-        // Define cache file path (used for both old format and temp file location)
+        // No header index - proceed with file reading (original logic)
+        debug!("   📍 DEBUG: No chunks available, proceeding with file reading logic");
+        // Define cache file path (old single-file cache format)
         let cache_file = ordered_blocks_cache_path_for_read();
 
         // Check for chunked cache first (new format)
@@ -987,7 +1365,7 @@ impl BlockIterator {
                     max_blocks,
                 ) {
                     Ok(Some(iter)) => {
-                        println!("   ✅ Using streaming chunked cache iterator (no memory limit)");
+                        info!("   ✅ Using streaming chunked cache iterator (no memory limit)");
                         chunked_iterator = Some(iter);
                         // Don't set ordered_blocks - we'll use chunked_iterator in the iterator
                     }
@@ -995,7 +1373,7 @@ impl BlockIterator {
                         // Chunked cache doesn't exist, try old format
                     }
                     Err(e) => {
-                        eprintln!("   ⚠️  Failed to create chunked cache iterator: {} - trying load_chunked_cache", e);
+                        error!("   ⚠️  Failed to create chunked cache iterator: {} - trying load_chunked_cache", e);
                         // Fallback to loading all blocks (only for small ranges)
                         match crate::chunked_cache::load_chunked_cache(
                             chunks_path,
@@ -1003,14 +1381,14 @@ impl BlockIterator {
                             max_blocks,
                         ) {
                             Ok(Some(blocks)) => {
-                                println!("   ✅ Loaded {} blocks from chunked cache", blocks.len());
+                                info!("   ✅ Loaded {} blocks from chunked cache", blocks.len());
                                 ordered_blocks = Some(blocks);
                             }
                             Ok(None) => {
                                 // Chunked cache doesn't exist, try old format
                             }
                             Err(e2) => {
-                                eprintln!(
+                                error!(
                                     "   ⚠️  Failed to load chunked cache: {} - trying old format",
                                     e2
                                 );
@@ -1026,7 +1404,7 @@ impl BlockIterator {
             // Try to load from old cache format
             if let Some(ref cache_path) = cache_file {
                 if cache_path.exists() {
-                    println!(
+                    info!(
                         "📂 Loading ordered block list from cache: {}",
                         cache_path.display()
                     );
@@ -1070,13 +1448,13 @@ impl BlockIterator {
                                 }
 
                                 if blocks.len() == block_count && block_count > 0 {
-                                    println!("   ✅ Loaded {} blocks from cache", blocks.len());
+                                    info!("   ✅ Loaded {} blocks from cache", blocks.len());
                                     ordered_blocks = Some(blocks);
                                 } else {
                                     if block_count == 0 || blocks.len() == 0 {
-                                        eprintln!("   ⚠️  Cache file is empty ({} blocks) - will read from files", block_count);
+                                        warn!("   ⚠️  Cache file is empty ({} blocks) - will read from files", block_count);
                                     } else {
-                                        eprintln!("   ⚠️  Cache file corrupted (expected {} blocks, got {}) - will read from files", block_count, blocks.len());
+                                        warn!("   ⚠️  Cache file corrupted (expected {} blocks, got {}) - will read from files", block_count, blocks.len());
                                     }
                                     // Don't set ordered_blocks - let it read from files
                                     ordered_blocks = None;
@@ -1084,2050 +1462,1892 @@ impl BlockIterator {
                             }
                         }
                         Err(e) => {
-                            eprintln!("   ⚠️  Failed to read cache: {}", e);
+                            error!("   ⚠️  Failed to read cache: {}", e);
                         }
                     }
                 }
             }
         }
 
-        // If cache miss, read and order all blocks
+        // Cache miss: collect every block from the files (see `BlockFileReader::collect_ordered`),
+        // then iterate the chunks it produced once they cover the whole chain
         if ordered_blocks.is_none() {
-            println!("📦 Reading ALL blocks from file to order them by previous block hash...");
-            println!(
-                "   (Blocks are stored out of order, so we need to read all to find the chain)"
-            );
-            println!("   This is a one-time operation - results will be cached for future runs");
-
-            // XOR-packaged files: blocks are out of order, so we need to read ALL blocks
-            // to find the ones we need. This is a one-time cost per file.
-            // To avoid OOM, write blocks to temp file as we read them
-            // Use cache directory if available (has more space), otherwise use temp dir
-            //
-            // ⚠️  CRITICAL: NEVER DELETE THIS TEMP FILE
-            // This file represents DAYS of work reading and processing blocks from disk.
-            // It can be used to resume if the process is interrupted.
-            // The temp file is a valuable backup even after cache is saved.
-            // CODE MUST NEVER DELETE THIS FILE - Users can manually delete if desired.
-            use std::io::{BufWriter, Write};
-            let temp_file = if let Some(ref cache_path) = cache_file {
-                if let Some(parent) = cache_path.parent() {
-                    // Ensure parent directory exists
-                    let _ = std::fs::create_dir_all(parent);
-                    parent.join("blvm-bench-blocks-temp.bin")
-                } else {
-                    std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
-                }
-            } else {
-                std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
-            };
-
-            // CRITICAL FIX: Check for existing chunks and calculate starting point
-            // This prevents overwriting existing chunks when restarting collection
-            let chunks_dir = incremental_chunk_destination();
-            let mut existing_chunks = Vec::new();
-            let mut starting_block_count = 0;
-
-            if chunks_dir.exists() {
-                // Find all existing chunks
-                for entry in std::fs::read_dir(&chunks_dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                        if file_name.starts_with("chunk_") && file_name.ends_with(".bin.zst") {
-                            // Extract chunk number
-                            if let Some(chunk_num_str) = file_name
-                                .strip_prefix("chunk_")
-                                .and_then(|s| s.strip_suffix(".bin.zst"))
-                            {
-                                if let Ok(chunk_num) = chunk_num_str.parse::<usize>() {
-                                    existing_chunks.push(chunk_num);
-                                }
-                            }
-                        }
-                    }
-                }
-                existing_chunks.sort();
-
-                if !existing_chunks.is_empty() {
-                    // CRITICAL FIX: Check for missing chunks (gaps in sequence)
-                    // If chunk_0 is missing but chunk_1 exists, we need to recreate chunk_0
-                    let min_chunk = *existing_chunks.iter().min().unwrap();
-                    let max_chunk = *existing_chunks.iter().max().unwrap();
-
-                    // Check if chunk_0 is missing
-                    if min_chunk > 0 {
-                        println!("   ⚠️  WARNING: Missing chunks detected! Chunks start at {} but chunk_0 is missing", min_chunk);
-                        println!("   🔄 Will recreate missing chunks starting from chunk_0");
-                        starting_block_count = 0; // Start from beginning to recreate missing chunks
-                    } else {
-                        // Check for gaps in the sequence
-                        let mut missing_chunks = Vec::new();
-                        for i in 0..=max_chunk {
-                            if !existing_chunks.contains(&i) {
-                                missing_chunks.push(i);
-                            }
+            let report = Self::collect_all(reader)?;
+            match report.chunks_dir.as_deref() {
+                Some(chunks_path) if report.is_complete() => {
+                    match crate::chunked_cache::ChunkedBlockIterator::new(
+                        chunks_path,
+                        start_height,
+                        max_blocks,
+                    ) {
+                        Ok(Some(iter)) => {
+                            info!(
+                                "   ✅ All chunks complete ({} blocks) - using chunked iterator",
+                                report.chunked_blocks
+                            );
+                            chunked_iterator = Some(iter);
                         }
-
-                        if !missing_chunks.is_empty() {
-                            println!(
-                                "   ⚠️  WARNING: Missing chunks detected: {:?}",
-                                missing_chunks
+                        _ => {
+                            info!(
+                                "   ✅ All chunks complete ({} blocks) - collection done",
+                                report.chunked_blocks
                             );
-                            println!("   🔄 Will recreate missing chunks");
-                            starting_block_count = missing_chunks[0] * INCREMENTAL_CHUNK_SIZE;
-                        } else {
-                            // No gaps - calculate starting block count based on existing chunks
-                            // If we have chunks 0, 1, 2, then we've collected (3 * 125000) = 375,000 blocks
-                            starting_block_count = (max_chunk + 1) * INCREMENTAL_CHUNK_SIZE;
+                            ordered_blocks = Some(Vec::new()); // Empty - collection is done
                         }
                     }
-
-                    println!(
-                        "   📦 Found {} existing chunk(s): {:?}",
-                        existing_chunks.len(),
-                        existing_chunks
-                    );
-                    println!(
-                        "   📊 Resuming from block {} (will recreate missing chunks)",
-                        starting_block_count
-                    );
-                    if starting_block_count == 0 {
-                        println!(
-                            "   ✅ Will create chunk 0 next (blocks 0 to {})",
-                            INCREMENTAL_CHUNK_SIZE - 1
-                        );
-                    } else {
-                        let next_chunk = starting_block_count / INCREMENTAL_CHUNK_SIZE;
-                        println!(
-                            "   ✅ Will create chunk {} next (blocks {} to {})",
-                            next_chunk,
-                            starting_block_count,
-                            starting_block_count + INCREMENTAL_CHUNK_SIZE - 1
-                        );
-                    }
                 }
-            }
-
-            // CRITICAL FIX: XOR-packaged files store blocks OUT OF ORDER
-            // We can't skip blocks during collection because we don't know their heights
-            // until we parse and order them. So we collect ALL blocks, then chunk them.
-            // If we have existing chunks, we still need to collect all blocks (they might
-            // be in the temp file from a previous run), but we'll handle chunking based
-            // on actual block heights after ordering.
-            if starting_block_count > 0 && temp_file.exists() {
-                let temp_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
-                if temp_size > 0 {
-                    println!(
-                        "   ⚠️  Temp file exists with blocks, but chunks exist up to block {}",
-                        starting_block_count
+                Some(_) if report.chunked_blocks > 0 => {
+                    warn!(
+                        "   ⚠️  Partial chunks exist ({} blocks, need ~{}k) - continuing collection...",
+                        report.chunked_blocks,
+                        reader.tuning.chunks.full_chain_blocks / 1000
                     );
-                    println!("   📊 Will collect all blocks (out of order), then chunk based on actual heights");
-                    // Don't delete temp file - it may have blocks we need
                 }
+                // No chunks yet - continue reading from files
+                _ => {}
             }
+        }
 
-            // Check if temp file exists and resume from it
-            let (mut temp_writer, mut read_count, start_time) = if temp_file.exists() {
-                // OPTIMIZATION: Try to read count from metadata file first (instant)
-                // FIX: Use binary u64 format instead of ASCII text for better reliability
-                let metadata_file = temp_file.with_extension("bin.meta");
-                let metadata_count = if metadata_file.exists() {
-                    // Try binary format first (u64, 8 bytes)
-                    match std::fs::read(&metadata_file) {
-                        Ok(data) if data.len() == 8 => {
-                            // Binary format: u64 little-endian
-                            Some(u64::from_le_bytes([
-                                data[0], data[1], data[2], data[3], data[4], data[5], data[6],
-                                data[7],
-                            ]) as usize)
-                        }
-                        Ok(data) if data.len() < 8 => {
-                            // Too short, try ASCII fallback for old format
-                            match std::str::from_utf8(&data) {
-                                Ok(s) => s.trim().parse::<usize>().ok(),
-                                Err(_) => None,
-                            }
-                        }
-                        Ok(_) => {
-                            // Wrong size, try ASCII fallback for old format
-                            match std::fs::read_to_string(&metadata_file) {
-                                Ok(content) => content.trim().parse::<usize>().ok(),
-                                Err(_) => None,
-                            }
-                        }
-                        Err(_) => None,
-                    }
-                } else {
-                    None
-                };
-
-                let existing_count = if let Some(count) = metadata_count {
-                    // Use cached count - instant!
-                    println!(
-                        "   ✅ Found existing temp file with {} blocks (from metadata)",
-                        count
-                    );
-                    count
-                } else {
-                    // No metadata - estimate from file size (instant, allows immediate start)
-                    // Rough estimate: average block size ~6.5MB based on previous runs
-                    let file_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
-                    let estimated_count = (file_size as f64 / (6.5 * 1024.0 * 1024.0)) as usize;
-
-                    println!(
-                        "   ⚡ No metadata found - estimating {} blocks from file size ({:.2} GB)",
-                        estimated_count,
-                        file_size as f64 / 1_073_741_824.0
-                    );
-                    println!("   🚀 Starting parallel reading immediately (counting continues in background)");
-
-                    // Start background counting thread to get accurate count
-                    let temp_file_clone = temp_file.clone();
-                    let metadata_file_clone = metadata_file.clone();
-                    std::thread::spawn(move || {
-                        // Background counting - doesn't block main process
-                        let mut temp_file_handle = match std::fs::File::open(&temp_file_clone) {
-                            Ok(f) => f,
-                            Err(_) => return,
-                        };
-                        use std::io::{Read, Seek, SeekFrom};
-                        let mut count = 0;
-                        let count_start = std::time::Instant::now();
-                        let mut last_progress = std::time::Instant::now();
-                        let file_size = std::fs::metadata(&temp_file_clone)
-                            .map(|m| m.len())
-                            .unwrap_or(0) as f64;
-
-                        loop {
-                            let mut len_buf = [0u8; 4];
-                            match temp_file_handle.read_exact(&mut len_buf) {
-                                Ok(_) => {}
-                                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                                Err(_) => break,
-                            }
+        // Handle ordered_blocks (if loaded from cache)
+        // CRITICAL: If we have a chunked_iterator, use it instead of ordered_blocks
+        let filtered_blocks: Option<Vec<Vec<u8>>> = if chunked_iterator.is_some() {
+            None // Use chunked_iterator instead - chunks are already ordered, no need to read files
+        } else if let Some(ref mut blocks) = ordered_blocks {
+            // Apply start_height and max_blocks filters
+            let start_idx = start_height.unwrap_or(0) as usize;
+            let end_idx = if let Some(max) = max_blocks {
+                (start_idx + max).min(blocks.len())
+            } else {
+                blocks.len()
+            };
 
-                            let block_len = u32::from_le_bytes(len_buf) as usize;
+            if end_idx > start_idx {
+                Some(blocks.drain(start_idx..end_idx).collect())
+            } else {
+                Some(Vec::new())
+            }
+        } else {
+            None // Continue reading from files
+        };
 
-                            // VALIDATION: Check block size is reasonable
-                            if block_len > MAX_VALID_BLOCK_SIZE || block_len < MIN_VALID_BLOCK_SIZE
-                            {
-                                eprintln!("   [Background] ⚠️  ERROR: Block {} has invalid size: {} bytes - stopping count", count, block_len);
-                                break; // Stop counting if corruption detected
-                            }
+        Ok(Self {
+            reader: reader.clone(),
+            // If neither chunks nor the old cache cover the range, fall back to reading files from
+            // file 0. The iterator only reads - collection (temp file, chunking) happened above.
+            current_file_idx: 0,
+            current_file: None,
+            current_height: start_height.unwrap_or(0),
+            start_height,
+            max_blocks,
+            blocks_read: 0,
+            ordered_blocks: filtered_blocks,
+            ordered_index: 0,
+            chunked_iterator, // Use the streaming iterator we created
+            search_buffer: vec![0u8; reader.tuning.io.search_buffer_size],
+            copy_sender: None, // Not needed for ordered iterator
+            last_copy_start_idx: 0,
+            failed_files: std::collections::HashSet::new(), // Track files that failed to avoid retries
+            skips: Vec::new(),
+            current_reading_file_idx: None,                 // Track which file we're reading from
+            core_index: None,
+        })
+    }
 
-                            count += 1;
+    /// Iterator that reads each height through Core's block index (standard trees only).
+    fn new_indexed(
+        reader: &BlockFileReader,
+        index: Arc<CoreBlockIndex>,
+        start_height: Option<u64>,
+        max_blocks: Option<usize>,
+    ) -> Self {
+        Self {
+            reader: reader.clone(),
+            current_file_idx: 0,
+            current_file: None,
+            current_height: start_height.unwrap_or(0),
+            start_height,
+            max_blocks,
+            blocks_read: 0,
+            ordered_blocks: None,
+            ordered_index: 0,
+            chunked_iterator: None,
+            search_buffer: Vec::new(),
+            copy_sender: None,
+            last_copy_start_idx: 0,
+            failed_files: std::collections::HashSet::new(),
+            skips: Vec::new(),
+            current_reading_file_idx: None,
+            core_index: Some(index),
+        }
+    }
 
-                            // Progress every 100k blocks or every 10 seconds (less frequent for background)
-                            if count % 100000 == 0 || last_progress.elapsed().as_secs() >= 10 {
-                                let elapsed = count_start.elapsed().as_secs_f64();
-                                let rate = count as f64 / elapsed;
-                                let pos = temp_file_handle.stream_position().unwrap_or(0) as f64;
-                                let progress_pct = if file_size > 0.0 {
-                                    (pos / file_size * 100.0).min(100.0)
-                                } else {
-                                    0.0
-                                };
-                                eprintln!("   [Background] Counting: {} blocks ({:.0} blocks/sec, {:.1}% of file)", 
-                                         count, rate, progress_pct);
-                                last_progress = std::time::Instant::now();
-                            }
+    /// Collection phase for XOR-packaged trees: read every block file into the temp file and cut
+    /// incremental chunks as blocks accumulate. Yields nothing - blocks are out of order on disk,
+    /// so iteration can only start from the chunks this produces.
+    ///
+    /// Resumes from an existing temp file / chunk set and honours the reader's cancellation token.
+    fn collect_all(reader: &BlockFileReader) -> Result<CollectionReport> {
+        // Copied out so the reader / copy threads below can capture them
+        let tuning = reader.tuning();
+        let io_buffer_size = tuning.io.buffer_size;
+        let search_buffer_size = tuning.io.search_buffer_size;
+        let max_parallel_read_threads = tuning.io.max_parallel_read_threads;
+        let parallel_file_batch_size = tuning.io.parallel_file_batch_size;
+        let collection_pipeline_depth = tuning.io.collection_pipeline_depth;
+        let pre_copy_lookahead = tuning.io.pre_copy_lookahead;
+        let hash_map_chunk_size = tuning.collection.hash_map_chunk_size;
+        let progress_report_interval = tuning.collection.progress_report_interval;
+        let temp_file_flush_interval = tuning.collection.temp_file_flush_interval;
+        let temp_file_integrity_check_interval =
+            tuning.collection.temp_file_integrity_check_interval;
+        let incremental_chunk_size = tuning.chunks.incremental_chunk_size;
+
+        let collection_start = std::time::Instant::now();
+        let cache_file = ordered_blocks_cache_path_for_read();
+        info!("📦 Reading ALL blocks from file to order them by previous block hash...");
+        info!("   (Blocks are stored out of order, so we need to read all to find the chain)");
+        info!("   This is a one-time operation - results will be cached for future runs");
+
+        // XOR-packaged files: blocks are out of order, so we need to read ALL blocks
+        // to find the ones we need. This is a one-time cost per file.
+        // To avoid OOM, write blocks to temp file as we read them
+        // Use cache directory if available (has more space), otherwise use temp dir
+        //
+        // ⚠️  CRITICAL: NEVER DELETE THIS TEMP FILE
+        // This file represents DAYS of work reading and processing blocks from disk.
+        // It can be used to resume if the process is interrupted.
+        // The temp file is a valuable backup even after cache is saved.
+        // CODE MUST NEVER DELETE THIS FILE - Users can manually delete if desired.
+        use std::io::{BufWriter, Write};
+        let temp_file = if let Some(ref cache_path) = cache_file {
+            if let Some(parent) = cache_path.parent() {
+                // Ensure parent directory exists
+                let _ = std::fs::create_dir_all(parent);
+                parent.join("blvm-bench-blocks-temp.bin")
+            } else {
+                std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
+            }
+        } else {
+            std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
+        };
+        match crate::shutdown::ResumeMarker::load(&temp_file) {
+            Ok(Some(marker)) => info!(
+                "   ⏯️  Resuming collection stopped ({}) at {} after {} blocks",
+                marker.reason, marker.stopped_at, marker.blocks
+            ),
+            Ok(None) => {}
+            Err(e) => warn!("   ⚠️  Ignoring resume marker: {}", e),
+        }
 
-                            match temp_file_handle.seek(SeekFrom::Current(block_len as i64)) {
-                                Ok(_) => {}
-                                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                                Err(_) => break,
+        // CRITICAL FIX: Check for existing chunks and calculate starting point
+        // This prevents overwriting existing chunks when restarting collection
+        let chunks_dir = incremental_chunk_destination();
+        let mut existing_chunks = Vec::new();
+        let mut starting_block_count = 0;
+
+        let archives: Vec<PathBuf> = chunk_tiers(tuning)
+            .archives()
+            .iter()
+            .filter(|dir| dir.exists())
+            .cloned()
+            .collect();
+        if !archives.is_empty() {
+            // Find all existing chunks, in every archive tier
+            let listings = archives
+                .iter()
+                .map(std::fs::read_dir)
+                .collect::<std::io::Result<Vec<_>>>()?;
+            for entry in listings.into_iter().flatten() {
+                let entry = entry?;
+                let path = entry.path();
+                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+                    if file_name.starts_with("chunk_") && file_name.ends_with(".bin.zst") {
+                        // Extract chunk number
+                        if let Some(chunk_num_str) = file_name
+                            .strip_prefix("chunk_")
+                            .and_then(|s| s.strip_suffix(".bin.zst"))
+                        {
+                            if let Ok(chunk_num) = chunk_num_str.parse::<usize>() {
+                                existing_chunks.push(chunk_num);
                             }
                         }
-
-                        let elapsed = count_start.elapsed().as_secs_f64();
-                        eprintln!(
-                            "   [Background] ✅ Finished counting: {} blocks in {:.1} seconds",
-                            count, elapsed
-                        );
-
-                        // Save to metadata for next time
-                        // FIX: Use binary u64 format instead of ASCII text
-                        let count_bytes = (count as u64).to_le_bytes();
-                        if let Err(e) = std::fs::write(&metadata_file_clone, count_bytes) {
-                            eprintln!(
-                                "   [Background] ⚠️  Warning: Could not save metadata file: {}",
-                                e
-                            );
+                    }
+                }
+            }
+            existing_chunks.sort();
+            existing_chunks.dedup();
+
+            if !existing_chunks.is_empty() {
+                // CRITICAL FIX: Check for missing chunks (gaps in sequence)
+                // If chunk_0 is missing but chunk_1 exists, we need to recreate chunk_0
+                let min_chunk = *existing_chunks.iter().min().unwrap();
+                let max_chunk = *existing_chunks.iter().max().unwrap();
+
+                // Check if chunk_0 is missing
+                if min_chunk > 0 {
+                    warn!("   ⚠️  WARNING: Missing chunks detected! Chunks start at {} but chunk_0 is missing", min_chunk);
+                    info!("   🔄 Will recreate missing chunks starting from chunk_0");
+                    starting_block_count = 0; // Start from beginning to recreate missing chunks
+                } else {
+                    // Check for gaps in the sequence
+                    let mut missing_chunks = Vec::new();
+                    for i in 0..=max_chunk {
+                        if !existing_chunks.contains(&i) {
+                            missing_chunks.push(i);
                         }
-                    });
+                    }
 
-                    estimated_count
-                };
+                    if !missing_chunks.is_empty() {
+                        warn!(
+                            "   ⚠️  WARNING: Missing chunks detected: {:?}",
+                            missing_chunks
+                        );
+                        info!("   🔄 Will recreate missing chunks");
+                        starting_block_count = missing_chunks[0] * incremental_chunk_size;
+                    } else {
+                        // No gaps - calculate starting block count based on existing chunks
+                        // If we have chunks 0, 1, 2, then we've collected (3 * 125000) = 375,000 blocks
+                        starting_block_count = (max_chunk + 1) * incremental_chunk_size;
+                    }
+                }
 
-                if existing_count > 0 {
-                    println!(
-                        "   ✅ Resuming from {} existing blocks in temp file",
-                        existing_count
+                info!(
+                    "   📦 Found {} existing chunk(s): {:?}",
+                    existing_chunks.len(),
+                    existing_chunks
+                );
+                info!(
+                    "   📊 Resuming from block {} (will recreate missing chunks)",
+                    starting_block_count
+                );
+                if starting_block_count == 0 {
+                    info!(
+                        "   ✅ Will create chunk 0 next (blocks 0 to {})",
+                        incremental_chunk_size - 1
                     );
-                    // Open in append mode
-                    let file = std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(&temp_file)?;
-                    (
-                        BufWriter::with_capacity(IO_BUFFER_SIZE, file),
-                        existing_count,
-                        std::time::Instant::now(),
-                    )
                 } else {
-                    // File exists but is empty/corrupted - start fresh
-                    println!("   ⚠️  Temp file exists but is empty/corrupted - starting fresh");
-                    (
-                        BufWriter::with_capacity(
-                            IO_BUFFER_SIZE,
-                            std::fs::File::create(&temp_file)?,
-                        ),
-                        0,
-                        std::time::Instant::now(),
-                    )
+                    let next_chunk = starting_block_count / incremental_chunk_size;
+                    info!(
+                        "   ✅ Will create chunk {} next (blocks {} to {})",
+                        next_chunk,
+                        starting_block_count,
+                        starting_block_count + incremental_chunk_size - 1
+                    );
                 }
-            } else {
-                // No temp file - start fresh
-                (
-                    BufWriter::with_capacity(IO_BUFFER_SIZE, std::fs::File::create(&temp_file)?),
-                    0,
-                    std::time::Instant::now(),
-                )
-            };
+            }
+        }
 
-            // DEBUG: Verify we reach this point (disabled to reduce log spam)
-            // eprintln!("   🔍 DEBUG: Reached parallel reading section, read_count={}, temp_file exists={}",
-            //          read_count, temp_file.exists());
-
-            // OPTIMIZATION: Parallel batch file reading
-            // Read multiple files in parallel batches for faster processing, especially in sparse regions
-            // Use maximum threads for I/O-bound workload (local LAN SSHFS can handle more parallelism)
-            let num_threads = MAX_PARALLEL_READ_THREADS;
-
-            // Create a custom thread pool for this operation
-            // Global pool might already be initialized, so we use a scoped pool
-            let pool = rayon::ThreadPoolBuilder::new()
-                .num_threads(num_threads)
-                .build()
-                .context("Failed to create rayon thread pool")?;
-
-            println!(
-                "   🚀 Using parallel batch reading ({} threads)",
-                num_threads
-            );
-            // eprintln!("   🔍 DEBUG: Parallel reading initialized with {} threads", num_threads);
-
-            // Estimate total blocks (rough estimate based on typical blockchain size)
-            let estimated_total = 926000u64; // Rough estimate
-
-            // Helper function to read all blocks from a single file
-            // Uses full pattern searching logic to ensure no blocks are missed
-            let network = reader.network;
-            let file_index_clone = reader.file_index.clone();
-            let local_cache_dir_clone = reader.local_cache_dir.clone();
-            let read_blocks_from_file = move |file_idx: usize,
-                                              file_path: &PathBuf|
-                  -> Result<Vec<Vec<u8>>> {
-                use std::io::{BufReader, Read, Seek, SeekFrom};
-                use std::time::{Duration, Instant};
-
-                const XOR_KEY1: [u8; 4] = [0x84, 0x22, 0xe9, 0xad];
-                const XOR_KEY2: [u8; 4] = [0xb7, 0x8f, 0xff, 0x14];
-                const ENCRYPTED_MAGIC: [u8; 4] = [0x7d, 0x9c, 0x5d, 0x74];
-                const MAX_FILE_PROCESSING_TIME: Duration = Duration::from_secs(300); // 5 minutes per file max
-
-                // Check if file should be skipped (from pre-scan index)
-                if let Some(ref index) = file_index_clone {
-                    if !index.contains(&file_idx) {
-                        return Ok(Vec::new()); // Empty file - skip
-                    }
-                }
+        // CRITICAL FIX: XOR-packaged files store blocks OUT OF ORDER
+        // We can't skip blocks during collection because we don't know their heights
+        // until we parse and order them. So we collect ALL blocks, then chunk them.
+        // If we have existing chunks, we still need to collect all blocks (they might
+        // be in the temp file from a previous run), but we'll handle chunking based
+        // on actual block heights after ordering.
+        if starting_block_count > 0 && temp_file.exists() {
+            let temp_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
+            if temp_size > 0 {
+                warn!(
+                    "   ⚠️  Temp file exists with blocks, but chunks exist up to block {}",
+                    starting_block_count
+                );
+                info!("   📊 Will collect all blocks (out of order), then chunk based on actual heights");
+                // Don't delete temp file - it may have blocks we need
+            }
+        }
+
+        // Check if temp file exists and resume from it
+        let (mut temp_writer, mut read_count, start_time) = if temp_file.exists() {
+            // OPTIMIZATION: Try the metadata store first (instant); legacy `.bin.meta`
+            // files are imported by `temp_block_count`.
+            let metadata_count =
+                crate::meta_store::temp_block_count(&temp_file).map(|c| c as usize);
+
+            let existing_count = if let Some(count) = metadata_count {
+                // Use cached count - instant!
+                info!(
+                    "   ✅ Found existing temp file with {} blocks (from metadata)",
+                    count
+                );
+                count
+            } else {
+                // No metadata - estimate from file size (instant, allows immediate start)
+                // Rough estimate: average block size ~6.5MB based on previous runs
+                let file_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
+                let estimated_count = (file_size as f64 / (6.5 * 1024.0 * 1024.0)) as usize;
+
+                info!(
+                    "   ⚡ No metadata found - estimating {} blocks from file size ({:.2} GB)",
+                    estimated_count,
+                    file_size as f64 / 1_073_741_824.0
+                );
+                info!("   🚀 Starting parallel reading immediately (counting continues in background)");
 
-                // OPTIMIZATION: Use local cache if available (much faster than SSHFS)
-                let path_to_use = if let Some(ref cache_dir) = local_cache_dir_clone {
-                    let file_name = file_path
-                        .file_name()
-                        .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?;
-                    let local_path = cache_dir.join(file_name);
+                // Start background counting thread to get accurate count
+                let temp_file_clone = temp_file.clone();
+                std::thread::spawn(move || {
+                    // Background counting - doesn't block main process
+                    let mut temp_file_handle = match std::fs::File::open(&temp_file_clone) {
+                        Ok(f) => f,
+                        Err(_) => return,
+                    };
+                    use std::io::{Read, Seek, SeekFrom};
+                    let mut count = 0;
+                    let count_start = std::time::Instant::now();
+                    let mut last_progress = std::time::Instant::now();
+                    let file_size = std::fs::metadata(&temp_file_clone)
+                        .map(|m| m.len())
+                        .unwrap_or(0) as f64;
 
-                    // If local copy exists, use it (fast local disk I/O)
-                    if local_path.exists() {
-                        local_path
-                    } else {
-                        // Local copy doesn't exist yet - try to copy it synchronously
-                        // This is slower but ensures we use local cache for subsequent reads
-                        if let Err(e) = std::fs::copy(file_path, &local_path) {
-                            // Copy failed - fall back to remote
-                            file_path.clone()
-                        } else {
-                            // Copy succeeded - use local
-                            local_path
+                    loop {
+                        let mut len_buf = [0u8; 4];
+                        match temp_file_handle.read_exact(&mut len_buf) {
+                            Ok(_) => {}
+                            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                            Err(_) => break,
                         }
-                    }
-                } else {
-                    // No local cache - use remote directly
-                    file_path.clone()
-                };
 
-                // Try to open file (from local cache if available, otherwise remote)
-                let file = match File::open(&path_to_use) {
-                    Ok(f) => f,
-                    Err(_) => return Ok(Vec::new()), // Skip if can't open
-                };
+                        let block_len = u32::from_le_bytes(len_buf) as usize;
 
-                let mut file_reader = BufReader::with_capacity(IO_BUFFER_SIZE, file);
-                // OPTIMIZATION: Pre-allocate blocks vector with estimated capacity
-                // Average file has ~1000-5000 blocks, pre-allocate to reduce reallocations
-                let mut blocks = Vec::with_capacity(2000);
-                let magic = network.magic_bytes();
-                // OPTIMIZATION: Check string once, cache result
-                let is_xor_encrypted =
-                    crate::block_cache_env::remote_core_xor_blockfiles_hint(file_path);
-
-                // Pre-allocate search buffer for pattern matching (same as original)
-                // OPTIMIZATION: Reuse buffer instead of allocating each time
-                let mut search_buffer = vec![0u8; SEARCH_BUFFER_SIZE];
-
-                // CRITICAL FIX: Add timeout to prevent getting stuck on problematic files
-                let file_start_time = Instant::now();
-                let mut last_progress_time = Instant::now();
-                let mut blocks_read_from_file = 0;
-
-                // Read blocks from file using full pattern searching logic
-                loop {
-                    // Check timeout - skip file if it's taking too long
-                    if file_start_time.elapsed() > MAX_FILE_PROCESSING_TIME {
-                        eprintln!("⚠️  File {} processing timeout ({}s) - skipping remaining blocks (read {} blocks so far)", 
-                                 file_idx, MAX_FILE_PROCESSING_TIME.as_secs(), blocks_read_from_file);
-                        break; // Return what we have so far
-                    }
+                        // VALIDATION: Check block size is reasonable
+                        if block_len > MAX_VALID_BLOCK_SIZE || block_len < MIN_VALID_BLOCK_SIZE {
+                            error!("   [Background] ⚠️  ERROR: Block {} has invalid size: {} bytes - stopping count", count, block_len);
+                            break; // Stop counting if corruption detected
+                        }
 
-                    // Progress reporting every 30 seconds for long-running files
-                    if last_progress_time.elapsed().as_secs() >= 30 {
-                        eprintln!(
-                            "   🔄 File {} still processing... ({} blocks read, {:.1}s elapsed)",
-                            file_idx,
-                            blocks_read_from_file,
-                            file_start_time.elapsed().as_secs_f64()
-                        );
-                        last_progress_time = Instant::now();
-                    }
-                    // CRITICAL FIX: Track file position BEFORE reading magic
-                    // Needed for correct XOR key rotation in these files
-                    let magic_start_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
+                        count += 1;
 
-                    let mut magic_buf = [0u8; 4];
-                    match file_reader.read_exact(&mut magic_buf) {
-                        Ok(_) => {}
-                        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                        Err(_) => break, // Skip on error
+                        // Progress every 100k blocks or every 10 seconds (less frequent for background)
+                        if count % 100000 == 0 || last_progress.elapsed().as_secs() >= 10 {
+                            let elapsed = count_start.elapsed().as_secs_f64();
+                            let rate = count as f64 / elapsed;
+                            let pos = temp_file_handle.stream_position().unwrap_or(0) as f64;
+                            let progress_pct = if file_size > 0.0 {
+                                (pos / file_size * 100.0).min(100.0)
+                            } else {
+                                0.0
+                            };
+                            info!("   [Background] Counting: {} blocks ({:.0} blocks/sec, {:.1}% of file)", 
+                                     count, rate, progress_pct);
+                            last_progress = std::time::Instant::now();
+                        }
+
+                        match temp_file_handle.seek(SeekFrom::Current(block_len as i64)) {
+                            Ok(_) => {}
+                            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                            Err(_) => break,
+                        }
                     }
 
-                    // Check if file is XOR encrypted (packaged blk*.dat)
-                    let mut encrypted_magic_bytes = magic_buf;
-                    let is_encrypted = if is_xor_encrypted {
-                        magic_buf == ENCRYPTED_MAGIC
-                    } else {
-                        false
-                    };
+                    let elapsed = count_start.elapsed().as_secs_f64();
+                    info!(
+                        "   [Background] ✅ Finished counting: {} blocks in {:.1} seconds",
+                        count, elapsed
+                    );
 
-                    if is_encrypted {
-                        // CRITICAL FIX: Decrypt magic using correct key based on FILE OFFSET
-                        // Magic is at file offset magic_start_pos, all 4 bytes in same chunk - use u32 XOR
-                        let use_key1 = (magic_start_pos / 4) % 2 == 0;
-                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-
-                        // Decrypt entire 4-byte magic at once using u32 XOR
-                        let magic_u32 = u32::from_le_bytes(magic_buf);
-                        let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                        magic_buf = decrypted_magic_u32.to_le_bytes();
+                    // Save to the metadata store for next time
+                    if let Err(e) = crate::meta_store::record_temp_progress(
+                        &temp_file_clone,
+                        count as u64,
+                        None,
+                    ) {
+                        warn!(
+                            "   [Background] ⚠️  Warning: Could not save metadata file: {}",
+                            e
+                        );
                     }
+                });
 
-                    if magic_buf != *magic {
-                        // Not a block start - try pattern search if encrypted
-                        if is_xor_encrypted {
-                            // Seek back and try pattern search
-                            file_reader.seek(SeekFrom::Current(-3)).ok();
-                            let current_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
-                            let file_size = file_reader
-                                .get_ref()
-                                .metadata()
-                                .map(|m| m.len())
-                                .unwrap_or(0);
-                            let mut search_pos = current_pos;
-                            let mut found = false;
-                            const MAX_SEARCH_DISTANCE: u64 = 10 * 1024 * 1024; // Max 10MB search per block
-                            let search_start = search_pos;
-
-                            // Pattern search for encrypted magic
-                            loop {
-                                // CRITICAL FIX: Limit search distance to prevent infinite loops
-                                if search_pos - search_start > MAX_SEARCH_DISTANCE {
-                                    eprintln!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next file", 
-                                             MAX_SEARCH_DISTANCE / (1024 * 1024), search_pos);
-                                    break;
-                                }
-
-                                // Don't search past end of file
-                                if search_pos >= file_size {
-                                    break;
-                                }
+                estimated_count
+            };
 
-                                let bytes_read = match file_reader.read(&mut search_buffer) {
-                                    Ok(0) => break,
-                                    Ok(n) => n,
-                                    Err(_) => break,
-                                };
+            if existing_count > 0 {
+                info!(
+                    "   ✅ Resuming from {} existing blocks in temp file",
+                    existing_count
+                );
+                // Open in append mode
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&temp_file)?;
+                (
+                    BufWriter::with_capacity(io_buffer_size, file),
+                    existing_count,
+                    std::time::Instant::now(),
+                )
+            } else {
+                // File exists but is empty/corrupted - start fresh
+                warn!("   ⚠️  Temp file exists but is empty/corrupted - starting fresh");
+                (
+                    BufWriter::with_capacity(io_buffer_size, std::fs::File::create(&temp_file)?),
+                    0,
+                    std::time::Instant::now(),
+                )
+            }
+        } else {
+            // No temp file - start fresh
+            (
+                BufWriter::with_capacity(io_buffer_size, std::fs::File::create(&temp_file)?),
+                0,
+                std::time::Instant::now(),
+            )
+        };
 
-                                // Use memchr for fast pattern searching
-                                let first_byte = ENCRYPTED_MAGIC[0];
-                                for i in memchr_iter(first_byte, &search_buffer[..bytes_read]) {
-                                    if i + 3 >= bytes_read {
-                                        continue;
-                                    }
+        // DEBUG: Verify we reach this point (disabled to reduce log spam)
+        // debug!("   🔍 DEBUG: Reached parallel reading section, read_count={}, temp_file exists={}",
+        //          read_count, temp_file.exists());
 
-                                    if search_buffer[i + 1] == ENCRYPTED_MAGIC[1]
-                                        && search_buffer[i + 2] == ENCRYPTED_MAGIC[2]
-                                        && search_buffer[i + 3] == ENCRYPTED_MAGIC[3]
-                                    {
-                                        let file_offset = search_pos + i as u64;
-                                        // Verify: decrypt and check
-                                        let mut test_magic = [0u8; 4];
-                                        test_magic.copy_from_slice(&search_buffer[i..i + 4]);
-
-                                        // Use u32 XOR for magic verification
-                                        let magic_u32 = u32::from_le_bytes(test_magic);
-                                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                                        let use_key1 = (file_offset / 4) % 2 == 0;
-                                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                                        let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                                        test_magic = decrypted_magic_u32.to_le_bytes();
-
-                                        if test_magic == *magic {
-                                            // Found next block - seek to it
-                                            file_reader.seek(SeekFrom::Start(file_offset))?;
-                                            found = true;
-                                            break;
-                                        }
-                                    }
-                                }
+        // OPTIMIZATION: Parallel batch file reading
+        // Read multiple files in parallel batches for faster processing, especially in sparse regions
+        // Use maximum threads for I/O-bound workload (local LAN SSHFS can handle more parallelism)
+        let num_threads = max_parallel_read_threads;
 
-                                if found {
-                                    break;
-                                }
+        info!(
+            "   🚀 Using parallel batch reading ({} threads, {} blocks in flight per file)",
+            num_threads, collection_pipeline_depth
+        );
+        // debug!("   🔍 DEBUG: Parallel reading initialized with {} threads", num_threads);
+
+        // Estimate total blocks (rough estimate based on typical blockchain size)
+        let estimated_total = 926000u64; // Rough estimate
+
+        // Helper function to read all blocks from a single file
+        // Uses full pattern searching logic to ensure no blocks are missed
+        let network = reader.network;
+        let xor_key = reader.xor_key;
+        let file_index_clone = reader.file_index.clone();
+        let local_cache_dir_clone = reader.local_cache_dir.clone();
+        // Blocks are handed to `emit` as they are decoded; `emit` returns false once the
+        // writer side has gone away, which stops the read early.
+        let read_blocks_from_file = move |file_idx: usize,
+                                          file_path: &PathBuf,
+                                          emit: &mut dyn FnMut(Vec<u8>) -> bool|
+              -> Result<usize> {
+            use std::io::{BufReader, Read, Seek, SeekFrom};
+            use std::time::{Duration, Instant};
+
+            const MAX_FILE_PROCESSING_TIME: Duration = Duration::from_secs(300); // 5 minutes per file max
+
+            // Check if file should be skipped (from pre-scan index)
+            if let Some(ref index) = file_index_clone {
+                if !index.contains(&file_idx) {
+                    return Ok(0); // Empty file - skip
+                }
+            }
 
-                                search_pos += bytes_read as u64;
-                                if bytes_read < search_buffer.len() {
-                                    break;
-                                }
-                            }
+            // OPTIMIZATION: Use local cache if available (much faster than SSHFS)
+            let path_to_use = if let Some(ref cache_dir) = local_cache_dir_clone {
+                let file_name = file_path
+                    .file_name()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?;
+                let local_path = cache_dir.join(file_name);
 
-                            if !found {
-                                break; // No more blocks
-                            }
-                            continue; // Retry reading magic at new position
-                        } else {
-                            // Not encrypted - just seek back and continue
-                            file_reader.seek(SeekFrom::Current(-3)).ok();
-                            continue;
-                        }
+                // If local copy exists, use it (fast local disk I/O)
+                if local_path.exists() {
+                    local_path
+                } else {
+                    // Local copy doesn't exist yet - try to copy it synchronously
+                    // This is slower but ensures we use local cache for subsequent reads
+                    if let Err(e) = std::fs::copy(file_path, &local_path) {
+                        // Copy failed - fall back to remote
+                        file_path.clone()
+                    } else {
+                        // Copy succeeded - use local
+                        local_path
                     }
+                }
+            } else {
+                // No local cache - use remote directly
+                file_path.clone()
+            };
 
-                    // Read size field (at file offset magic_start_pos + 4)
-                    let mut size_buf = [0u8; 4];
-                    if file_reader.read_exact(&mut size_buf).is_err() {
-                        break;
-                    }
+            // Try to open file (from local cache if available, otherwise remote)
+            let file = match File::open(&path_to_use) {
+                Ok(f) => f,
+                Err(_) => return Ok(0), // Skip if can't open
+            };
 
-                    // CRITICAL FIX: Use magic_start_pos as block_start_offset for XOR decryption
-                    // This is the file offset where the block's magic bytes start
-                    let block_start_offset = if is_xor_encrypted {
-                        Some(magic_start_pos)
-                    } else {
-                        None
-                    };
+            let mut file_reader = BufReader::with_capacity(io_buffer_size, file);
+            let magic = network.magic_bytes();
+            let is_xor_encrypted = xor_key.is_some();
+
+            // Pre-allocate search buffer for pattern matching (same as original)
+            // OPTIMIZATION: Reuse buffer instead of allocating each time
+            let mut search_buffer = vec![0u8; search_buffer_size];
+
+            // CRITICAL FIX: Add timeout to prevent getting stuck on problematic files
+            let file_start_time = Instant::now();
+            let mut last_progress_time = Instant::now();
+            let mut blocks_read_from_file = 0;
+
+            // Read blocks from file using full pattern searching logic
+            loop {
+                // Check timeout - skip file if it's taking too long
+                if file_start_time.elapsed() > MAX_FILE_PROCESSING_TIME {
+                    warn!("⚠️  File {} processing timeout ({}s) - skipping remaining blocks (read {} blocks so far)", 
+                             file_idx, MAX_FILE_PROCESSING_TIME.as_secs(), blocks_read_from_file);
+                    break; // Keep what we have emitted so far
+                }
 
-                    let mut block_size = if is_xor_encrypted {
-                        // CRITICAL FIX: Decrypt size field using correct key based on FILE OFFSET
-                        // Size field is at file offset magic_start_pos + 4
-                        // All 4 bytes of size field are in the same 4-byte chunk, so use u32 XOR
-                        let size_offset = magic_start_pos + 4;
-                        let use_key1 = (size_offset / 4) % 2 == 0;
-                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-
-                        // Decrypt entire 4-byte size field at once using u32 XOR
-                        let size_u32 = u32::from_le_bytes(size_buf);
-                        let decrypted_size_u32 = size_u32 ^ key_u32;
-                        decrypted_size_u32 as usize
-                    } else {
-                        u32::from_le_bytes(size_buf) as usize
-                    };
+                // Progress reporting every 30 seconds for long-running files
+                if last_progress_time.elapsed().as_secs() >= 30 {
+                    info!(
+                        "   🔄 File {} still processing... ({} blocks read, {:.1}s elapsed)",
+                        file_idx,
+                        blocks_read_from_file,
+                        file_start_time.elapsed().as_secs_f64()
+                    );
+                    last_progress_time = Instant::now();
+                }
+                // CRITICAL FIX: Track file position BEFORE reading magic
+                // Needed for correct XOR key rotation in these files
+                let magic_start_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
 
-                    // Validate size
-                    if block_size < 80 || block_size > 32 * 1024 * 1024 {
-                        // Invalid size - try pattern search if encrypted
-                        if is_xor_encrypted {
-                            // Seek back and try pattern search
-                            if let Some(start_pos) = block_start_offset {
-                                file_reader.seek(SeekFrom::Start(start_pos + 8)).ok();
-                            }
-                            continue;
-                        } else {
-                            break;
-                        }
-                    }
+                let mut magic_buf = [0u8; 4];
+                match file_reader.read_exact(&mut magic_buf) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                    Err(_) => break, // Skip on error
+                }
 
-                    // Check file size before reading (avoid "failed to fill whole buffer" errors)
-                    if is_xor_encrypted {
+                // Decrypt magic with the key bytes for its FILE OFFSET
+                let encrypted_magic_bytes = magic_buf;
+                if let Some(key) = xor_key {
+                    magic_buf = key.word(magic_buf, magic_start_pos);
+                }
+
+                if magic_buf != *magic {
+                    // Not a block start - try pattern search if encrypted
+                    if let Some(key) = xor_key {
+                        // Seek back and try pattern search
+                        file_reader.seek(SeekFrom::Current(-3)).ok();
                         let current_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
                         let file_size = file_reader
                             .get_ref()
                             .metadata()
                             .map(|m| m.len())
                             .unwrap_or(0);
-                        let required_size = current_pos + block_size as u64;
-                        if required_size > file_size {
-                            // File too small - skip this file
-                            break;
-                        }
-                    }
-
-                    // OPTIMIZATION: Pre-allocate with exact size (avoids reallocations)
-                    let mut block_data = vec![0u8; block_size];
-                    match file_reader.read_exact(&mut block_data) {
-                        Ok(_) => {}
-                        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                            // File ended unexpectedly - skip
-                            break;
-                        }
-                        Err(_) => {
-                            // Other error - skip
-                            break;
-                        }
-                    }
-
-                    // Decrypt if needed
-                    if is_xor_encrypted {
-                        let block_start = block_start_offset.unwrap();
-                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                        let mut full_block = Vec::with_capacity(8 + block_size);
-                        full_block.extend_from_slice(&encrypted_magic_bytes);
-                        full_block.extend_from_slice(&size_buf);
-                        full_block.extend_from_slice(&block_data);
-
-                        // OPTIMIZATION: Batch XOR decryption (process 4 bytes at a time, then remaining)
-                        // This is faster than byte-by-byte and avoids repeated key lookups
-                        let mut i = 0;
-                        // Process 4-byte chunks (most of the block)
-                        while i + 4 <= full_block.len() {
-                            let file_offset = block_start + i as u64;
-                            let use_key1 = (file_offset / 4) % 2 == 0;
-                            let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                            // OPTIMIZATION: Use u32 XOR directly (faster than byte-by-byte)
-                            let chunk = u32::from_le_bytes([
-                                full_block[i],
-                                full_block[i + 1],
-                                full_block[i + 2],
-                                full_block[i + 3],
-                            ]);
-                            let decrypted = chunk ^ key_u32;
-                            let bytes = decrypted.to_le_bytes();
-                            full_block[i..i + 4].copy_from_slice(&bytes);
-                            i += 4;
-                        }
-                        // Handle remaining bytes (0-3 bytes)
-                        while i < full_block.len() {
-                            let byte_offset = block_start + i as u64;
-                            let use_key1 = (byte_offset / 4) % 2 == 0;
-                            let key = if use_key1 { &XOR_KEY1 } else { &XOR_KEY2 };
-                            full_block[i] ^= key[(byte_offset % 4) as usize];
-                            i += 1;
-                        }
-                        block_data = full_block[8..].to_vec();
+                        let mut search_pos = current_pos;
+                        let mut found = false;
+                        const MAX_SEARCH_DISTANCE: u64 = 10 * 1024 * 1024; // Max 10MB search per block
+                        let search_start = search_pos;
 
-                        // Seek past any padding to find next block
-                        let current_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
-                        let mut test_magic_buf = [0u8; 4];
-                        let mut need_search = true;
-
-                        match file_reader.read_exact(&mut test_magic_buf) {
-                            Ok(_) => {
-                                if test_magic_buf == ENCRYPTED_MAGIC {
-                                    // Quick verify: decrypt and check
-                                    let mut verify_magic = test_magic_buf;
-                                    for j in 0..4 {
-                                        let byte_offset = current_pos + j as u64;
-                                        let key = if (byte_offset / 4) % 2 == 0 {
-                                            &XOR_KEY1
-                                        } else {
-                                            &XOR_KEY2
-                                        };
-                                        verify_magic[j] ^= key[(byte_offset % 4) as usize];
-                                    }
-                                    if verify_magic == *magic {
-                                        // Found it immediately - no search needed
-                                        file_reader.seek(SeekFrom::Start(current_pos))?;
-                                        need_search = false;
-                                    } else {
-                                        file_reader.seek(SeekFrom::Start(current_pos))?;
-                                    }
-                                } else {
-                                    file_reader.seek(SeekFrom::Start(current_pos))?;
-                                }
-                            }
-                            Err(_) => {
-                                file_reader.seek(SeekFrom::Start(current_pos))?;
+                        // Pattern search for encrypted magic
+                        loop {
+                            // CRITICAL FIX: Limit search distance to prevent infinite loops
+                            if search_pos - search_start > MAX_SEARCH_DISTANCE {
+                                warn!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next file", 
+                                         MAX_SEARCH_DISTANCE / (1024 * 1024), search_pos);
+                                break;
                             }
-                        }
-
-                        // If we need to search (blocks are not sequential)
-                        if need_search {
-                            let mut search_pos = current_pos;
-                            let mut found_next = false;
-                            let file_size = file_reader
-                                .get_ref()
-                                .metadata()
-                                .map(|m| m.len())
-                                .unwrap_or(0);
-                            const MAX_SEARCH_DISTANCE: u64 = 10 * 1024 * 1024; // Max 10MB search per block
-                            let search_start = search_pos;
-
-                            loop {
-                                // CRITICAL FIX: Limit search distance to prevent infinite loops
-                                if search_pos - search_start > MAX_SEARCH_DISTANCE {
-                                    eprintln!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next block/file", 
-                                             MAX_SEARCH_DISTANCE / (1024 * 1024), search_pos);
-                                    break;
-                                }
-
-                                // Don't search past end of file
-                                if search_pos >= file_size {
-                                    break;
-                                }
 
-                                let bytes_read = match file_reader.read(&mut search_buffer) {
-                                    Ok(0) => break,
-                                    Ok(n) => n,
-                                    Err(_) => break,
-                                };
+                            // Don't search past end of file
+                            if search_pos >= file_size {
+                                break;
+                            }
 
-                                // Use memchr for fast pattern searching
-                                let first_byte = ENCRYPTED_MAGIC[0];
-                                for i in memchr_iter(first_byte, &search_buffer[..bytes_read]) {
-                                    if i + 3 >= bytes_read {
-                                        continue;
-                                    }
+                            let bytes_read = match file_reader.read(&mut search_buffer) {
+                                Ok(0) => break,
+                                Ok(n) => n,
+                                Err(_) => break,
+                            };
 
-                                    if search_buffer[i + 1] == ENCRYPTED_MAGIC[1]
-                                        && search_buffer[i + 2] == ENCRYPTED_MAGIC[2]
-                                        && search_buffer[i + 3] == ENCRYPTED_MAGIC[3]
-                                    {
-                                        let file_offset = search_pos + i as u64;
-                                        // Quick verify: decrypt and check
-                                        let mut test_magic = [0u8; 4];
-                                        test_magic.copy_from_slice(&search_buffer[i..i + 4]);
-
-                                        let magic_u32 = u32::from_le_bytes(test_magic);
-                                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                                        let use_key1 = (file_offset / 4) % 2 == 0;
-                                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                                        let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                                        test_magic = decrypted_magic_u32.to_le_bytes();
-
-                                        if test_magic == *magic {
-                                            // Found next block - seek to it
-                                            file_reader.seek(SeekFrom::Start(file_offset))?;
-                                            found_next = true;
-                                            break;
-                                        }
-                                    }
-                                }
+                            // Encrypted-magic scan (decrypt-verify at each file offset)
+                            if let Some(i) =
+                                key.find_magic(&search_buffer[..bytes_read], search_pos, magic)
+                            {
+                                // Found next block - seek to it
+                                file_reader.seek(SeekFrom::Start(search_pos + i as u64))?;
+                                found = true;
+                            }
 
-                                if found_next {
-                                    break;
-                                }
+                            if found {
+                                break;
+                            }
 
-                                search_pos += bytes_read as u64;
-                                if bytes_read < search_buffer.len() {
-                                    break;
-                                }
+                            search_pos += bytes_read as u64;
+                            if bytes_read < search_buffer.len() {
+                                break;
                             }
                         }
-                    }
 
-                    if block_data.len() >= 80 {
-                        blocks.push(block_data);
-                        blocks_read_from_file += 1;
+                        if !found {
+                            break; // No more blocks
+                        }
+                        continue; // Retry reading magic at new position
+                    } else {
+                        // Not encrypted - just seek back and continue
+                        file_reader.seek(SeekFrom::Current(-3)).ok();
+                        continue;
                     }
                 }
 
-                Ok(blocks)
-            };
-
-            // Process files in parallel batches
-            // When resuming, we need to track which file we were on
-            // FIX: Instead of estimating, we'll track the last processed file in metadata
-            // For now, use a more conservative estimate and validate blocks as we go
-            let start_file_idx = if read_count > 0 {
-                // More conservative estimate: ~50 blocks per file (to avoid skipping files)
-                // This ensures we don't miss any blocks, even if it means re-reading some
-                let estimated = (read_count as f64 / 50.0 * 0.7) as usize; // 70% of estimate to be very safe
-                estimated.min(reader.block_files.len())
-            } else {
-                0
-            };
-
-            if read_count > 0 && start_file_idx > 0 {
-                println!("   📍 Resuming: starting at file {} (conservative estimate based on {} existing blocks)", start_file_idx, read_count);
-                println!("   ⚠️  NOTE: Some files may be re-read to ensure no blocks are missed");
-            }
+                // Read size field (at file offset magic_start_pos + 4)
+                let mut size_buf = [0u8; 4];
+                if file_reader.read_exact(&mut size_buf).is_err() {
+                    break;
+                }
 
-            let file_paths: Vec<_> = reader.block_files.iter().skip(start_file_idx).collect();
-            // Use tunable batch size - optimized for local LAN SSHFS (I/O bound, not CPU bound)
-            let batch_size = PARALLEL_FILE_BATCH_SIZE;
-            let mut last_progress_time = start_time;
-            let mut last_progress_count = read_count;
-            let mut processed_files = start_file_idx;
-            let mut last_file_idx = start_file_idx;
-
-            // OPTIMIZATION: Pre-copy files ahead in large batches before starting to read
-            // This ensures local cache is populated before we need the files
-            // Start pre-copy from current position (not from beginning if resuming)
-            // CRITICAL FIX: Make pre-copy non-blocking so we can start reading immediately
-            if let Some(ref cache_dir) = reader.local_cache_dir {
-                let precopy_count = PRE_COPY_LOOKAHEAD.min(file_paths.len());
-                println!("   📦 Pre-copying {} files ahead (starting from file {}) to local cache (background)...", 
-                         precopy_count, start_file_idx);
+                // CRITICAL FIX: Use magic_start_pos as block_start_offset for XOR decryption
+                // This is the file offset where the block's magic bytes start
+                let block_start_offset = if is_xor_encrypted {
+                    Some(magic_start_pos)
+                } else {
+                    None
+                };
 
-                // Clone paths for parallel processing (starting from current position)
-                let files_to_precopy: Vec<PathBuf> = file_paths
-                    .iter()
-                    .take(precopy_count)
-                    .map(|p| (*p).clone())
-                    .collect();
-                let cache_dir_clone = cache_dir.clone();
+                // Size field is at file offset magic_start_pos + 4
+                let block_size = match xor_key {
+                    Some(key) => u32::from_le_bytes(key.word(size_buf, magic_start_pos + 4)),
+                    None => u32::from_le_bytes(size_buf),
+                } as usize;
 
-                // CRITICAL FIX: Spawn pre-copy in background thread so it doesn't block reading
-                std::thread::spawn(move || {
-                    let pool = rayon::ThreadPoolBuilder::new()
-                        .num_threads(MAX_PARALLEL_READ_THREADS)
-                        .build();
-                    if let Ok(pool) = pool {
-                        pool.install(|| {
-                            files_to_precopy.par_iter().for_each(|file_path| {
-                                let file_name = match file_path.file_name() {
-                                    Some(name) => name,
-                                    None => return,
-                                };
-                                let local_path = cache_dir_clone.join(file_name);
-
-                                // Copy if not already cached (skip if exists)
-                                if !local_path.exists() {
-                                    let _ = std::fs::copy(file_path, &local_path);
-                                }
-                            });
-                        });
+                // Validate size
+                if block_size < 80 || block_size > 32 * 1024 * 1024 {
+                    // Invalid size - try pattern search if encrypted
+                    if is_xor_encrypted {
+                        // Seek back and try pattern search
+                        if let Some(start_pos) = block_start_offset {
+                            file_reader.seek(SeekFrom::Start(start_pos + 8)).ok();
+                        }
+                        continue;
+                    } else {
+                        break;
                     }
-                    eprintln!(
-                        "   ✅ Background pre-copy complete - {} files ready in local cache",
-                        precopy_count
-                    );
-                });
-                println!(
-                    "   ⚡ Starting block reading immediately (pre-copy running in background)..."
-                );
-            }
-
-            // Track which files we've pre-copied to continue copying ahead
-            // Start from where initial pre-copy ended (relative to start_file_idx)
-            let mut last_precopy_idx = PRE_COPY_LOOKAHEAD.min(file_paths.len());
-
-            // CRITICAL FIX: Add debug output and ensure loop starts
-            let total_batches = (file_paths.len() + batch_size - 1) / batch_size;
-            println!(
-                "   🚀 Starting to process {} files in {} batches (batch size: {})...",
-                file_paths.len(),
-                total_batches,
-                batch_size
-            );
-
-            for (batch_num, batch) in file_paths.chunks(batch_size).enumerate() {
-                // CRITICAL FIX: Add progress output at start of EVERY batch (not just every 10th)
-                eprintln!(
-                    "   📦 Processing batch {}/{} (files {}-{})...",
-                    batch_num + 1,
-                    total_batches,
-                    processed_files,
-                    processed_files + batch.len().min(batch_size) - 1
-                );
-                // Continue pre-copying ahead as we progress
-                // Keep 200 files ahead of current reading position
-                if let Some(ref cache_dir) = reader.local_cache_dir {
-                    // Current position relative to start of file_paths (which starts at start_file_idx)
-                    let current_pos_in_paths = (processed_files - start_file_idx) + batch.len();
-                    let next_precopy_start = last_precopy_idx.max(current_pos_in_paths);
-                    let next_precopy_end =
-                        (next_precopy_start + PRE_COPY_LOOKAHEAD).min(file_paths.len());
-
-                    if next_precopy_start < file_paths.len()
-                        && next_precopy_end > next_precopy_start
-                    {
-                        // Clone paths for background thread (must own the data)
-                        let files_to_precopy: Vec<PathBuf> = file_paths
-                            [next_precopy_start..next_precopy_end]
-                            .iter()
-                            .map(|p| (*p).clone())
-                            .collect();
-
-                        // Pre-copy in background (don't wait)
-                        let cache_dir_clone = cache_dir.clone();
-                        std::thread::spawn(move || {
-                            let pool = rayon::ThreadPoolBuilder::new()
-                                .num_threads(MAX_PARALLEL_READ_THREADS)
-                                .build();
-                            if let Ok(pool) = pool {
-                                pool.install(|| {
-                                    files_to_precopy.par_iter().for_each(|file_path| {
-                                        let file_name = match file_path.file_name() {
-                                            Some(name) => name,
-                                            None => return,
-                                        };
-                                        let local_path = cache_dir_clone.join(file_name);
-
-                                        if !local_path.exists() {
-                                            let _ = std::fs::copy(file_path, &local_path);
-                                        }
-                                    });
-                                });
-                            }
-                        });
+                }
 
-                        last_precopy_idx = next_precopy_end;
+                // Check file size before reading (avoid "failed to fill whole buffer" errors)
+                if is_xor_encrypted {
+                    let current_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
+                    let file_size = file_reader
+                        .get_ref()
+                        .metadata()
+                        .map(|m| m.len())
+                        .unwrap_or(0);
+                    let required_size = current_pos + block_size as u64;
+                    if required_size > file_size {
+                        // File too small - skip this file
+                        break;
                     }
                 }
 
-                // Read blocks from all files in batch in parallel using custom thread pool
-                // Files should now be in local cache for fast local disk access
-                let batch_start_time = std::time::Instant::now();
-                eprintln!(
-                    "   🔍 Starting parallel read of {} files in batch {}...",
-                    batch.len(),
-                    batch_num + 1
-                );
-                let batch_results: Vec<_> = pool.install(|| {
-                    batch
-                        .par_iter()
-                        .enumerate()
-                        .map(|(batch_idx, file_path)| {
-                            let file_idx = processed_files + batch_idx;
-                            read_blocks_from_file(file_idx, file_path)
-                        })
-                        .collect()
-                });
-                let batch_duration = batch_start_time.elapsed();
-                eprintln!(
-                    "   ✅ Completed parallel read of batch {} ({} files processed) in {:.1}s",
-                    batch_num + 1,
-                    batch.len(),
-                    batch_duration.as_secs_f64()
-                );
-
-                // CRITICAL FIX: Warn if batch takes too long (might indicate stuck file)
-                if batch_duration.as_secs() > 300 {
-                    eprintln!("   ⚠️  WARNING: Batch {} took {:.1} minutes - some files may be problematic", 
-                             batch_num + 1, batch_duration.as_secs_f64() / 60.0);
+                // OPTIMIZATION: Pre-allocate with exact size (avoids reallocations)
+                let mut block_data = vec![0u8; block_size];
+                match file_reader.read_exact(&mut block_data) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        // File ended unexpectedly - skip
+                        break;
+                    }
+                    Err(_) => {
+                        // Other error - skip
+                        break;
+                    }
                 }
 
-                // Write all blocks from batch sequentially to temp file
-                // Track blocks in current chunk (resets after each chunk)
-                let mut blocks_in_current_chunk = read_count % INCREMENTAL_CHUNK_SIZE;
-
-                for (batch_idx, file_blocks_result) in batch_results.into_iter().enumerate() {
-                    let file_idx = processed_files + batch_idx;
-
-                    match file_blocks_result {
-                        Ok(file_blocks) => {
-                            if !file_blocks.is_empty() && file_idx != last_file_idx {
-                                eprintln!(
-                                    "   📂 Now reading from file {}: {}",
-                                    file_idx,
-                                    reader
-                                        .block_files
-                                        .get(file_idx)
-                                        .map(|p| p.display().to_string())
-                                        .unwrap_or_else(|| "unknown".to_string())
-                                );
-                                last_file_idx = file_idx;
+                // Decrypt if needed
+                if let Some(key) = xor_key {
+                    let block_start = block_start_offset.unwrap();
+                    let mut full_block = Vec::with_capacity(8 + block_size);
+                    full_block.extend_from_slice(&encrypted_magic_bytes);
+                    full_block.extend_from_slice(&size_buf);
+                    full_block.extend_from_slice(&block_data);
+                    key.apply(&mut full_block, block_start);
+                    block_data = full_block[8..].to_vec();
+
+                    // Seek past any padding to find next block
+                    let current_pos = file_reader.seek(SeekFrom::Current(0)).unwrap_or(0);
+                    let mut test_magic_buf = [0u8; 4];
+                    let mut need_search = true;
+
+                    match file_reader.read_exact(&mut test_magic_buf) {
+                        Ok(_) => {
+                            // Quick verify: decrypt and check
+                            if key.word(test_magic_buf, current_pos) == *magic {
+                                // Found it immediately - no search needed
+                                need_search = false;
                             }
+                            file_reader.seek(SeekFrom::Start(current_pos))?;
+                        }
+                        Err(_) => {
+                            file_reader.seek(SeekFrom::Start(current_pos))?;
+                        }
+                    }
 
-                            for block_data in file_blocks {
-                                // CRITICAL VALIDATION: Verify block before writing
-                                if block_data.len() < MIN_VALID_BLOCK_SIZE {
-                                    eprintln!("   ⚠️  ERROR: Block {} has invalid size: {} bytes (minimum {}) - SKIPPING", 
-                                             read_count, block_data.len(), MIN_VALID_BLOCK_SIZE);
-                                    continue; // Skip invalid block
-                                }
-
-                                if block_data.len() > MAX_VALID_BLOCK_SIZE {
-                                    eprintln!("   ⚠️  ERROR: Block {} has suspiciously large size: {} bytes (maximum {}) - SKIPPING", 
-                                             read_count, block_data.len(), MAX_VALID_BLOCK_SIZE);
-                                    continue; // Skip invalid block
-                                }
+                    // If we need to search (blocks are not sequential)
+                    if need_search {
+                        let mut search_pos = current_pos;
+                        let mut found_next = false;
+                        let file_size = file_reader
+                            .get_ref()
+                            .metadata()
+                            .map(|m| m.len())
+                            .unwrap_or(0);
+                        const MAX_SEARCH_DISTANCE: u64 = 10 * 1024 * 1024; // Max 10MB search per block
+                        let search_start = search_pos;
 
-                                // OPTIMIZATION: For collection-only mode, skip strict version validation
-                                // Version validation will happen during chunking when blocks are validated
-                                // This allows collection to proceed even if some blocks have questionable versions
-                                // (XOR-packaged blocks may have edge cases that are valid after full processing)
-                                // Only do basic sanity check - version should be in reasonable range
-                                if block_data.len() >= 4 {
-                                    let version = u32::from_le_bytes([
-                                        block_data[0],
-                                        block_data[1],
-                                        block_data[2],
-                                        block_data[3],
-                                    ]);
-                                    // Very lenient check for collection - only reject obviously invalid values
-                                    // Full validation happens during chunking
-                                    // CRITICAL: Only skip if version is clearly garbage (XOR decryption failure)
-                                    // Don't skip blocks with high but valid versions (BIP9 uses 0x20000000+)
-                                    if version > 0x7fffffff {
-                                        // Version > 2^31 is definitely invalid (would be negative if signed)
-                                        // This usually indicates XOR decryption failed or we read from wrong position
-                                        eprintln!("   ⚠️  ERROR: Block {} has obviously invalid version: {} (>{}) - likely XOR decryption failure, SKIPPING", 
-                                                 read_count, version, 0x7fffffff);
-                                        continue; // Skip invalid block (XOR decryption failed)
-                                    }
-                                    // Otherwise accept it - validation will catch real issues during chunking
-                                    // High versions (0x20000000+) are valid for BIP9 activation
-                                }
+                        loop {
+                            // CRITICAL FIX: Limit search distance to prevent infinite loops
+                            if search_pos - search_start > MAX_SEARCH_DISTANCE {
+                                warn!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next block/file", 
+                                         MAX_SEARCH_DISTANCE / (1024 * 1024), search_pos);
+                                break;
+                            }
 
-                                // CRITICAL FIX: Don't skip blocks during collection!
-                                // Blocks are stored OUT OF ORDER in XOR-packaged files, so we can't know
-                                // which block we're reading until we parse it. We need to collect
-                                // ALL blocks, then order them later. The chunking logic will handle
-                                // skipping blocks that are already in chunks.
-
-                                // Write block to temp file: [len: u32][data...]
-                                let block_len = block_data.len() as u32;
-                                // OPTIMIZATION: Pre-compute length bytes once
-                                let len_bytes = block_len.to_le_bytes();
-
-                                // CRITICAL: Verify block data integrity before writing
-                                // Double-check version is reasonable (basic sanity check)
-                                if block_data.len() >= 4 {
-                                    let version_check = u32::from_le_bytes([
-                                        block_data[0],
-                                        block_data[1],
-                                        block_data[2],
-                                        block_data[3],
-                                    ]);
-                                    // If version is clearly invalid (like the corrupted ones we saw: 536870912, etc.)
-                                    // this suggests the block data itself is corrupted
-                                    if version_check > 0x7fffffff {
-                                        eprintln!("   ⚠️  ERROR: Block {} has corrupted data (version: {}) - SKIPPING before write", read_count, version_check);
-                                        continue; // Skip this block entirely
-                                    }
-                                }
+                            // Don't search past end of file
+                            if search_pos >= file_size {
+                                break;
+                            }
 
-                                temp_writer.write_all(&len_bytes).map_err(|e| {
-                                    anyhow::anyhow!(
-                                        "Failed to write block length for block {}: {}",
-                                        read_count,
-                                        e
-                                    )
-                                })?;
-                                temp_writer.write_all(&block_data).map_err(|e| {
-                                    anyhow::anyhow!(
-                                        "Failed to write block data for block {}: {}",
-                                        read_count,
-                                        e
-                                    )
-                                })?;
-                                read_count += 1;
-
-                                // INCREMENTAL CHUNKING: When we have enough blocks for a chunk, compress and move it
-                                if read_count > 0 && read_count % INCREMENTAL_CHUNK_SIZE == 0 {
-                                    // CRITICAL FIX: Calculate chunk number correctly based on total blocks collected
-                                    // chunk_num = (read_count / INCREMENTAL_CHUNK_SIZE) - 1
-                                    // For read_count = 125000: chunk_num = (125000 / 125000) - 1 = 0
-                                    // For read_count = 250000: chunk_num = (250000 / 125000) - 1 = 1
-                                    let chunk_num = (read_count / INCREMENTAL_CHUNK_SIZE) - 1;
-
-                                    // CRITICAL FIX: Check if chunk already exists to prevent overwriting
-                                    let chunk_file =
-                                        chunks_dir.join(format!("chunk_{}.bin.zst", chunk_num));
-                                    if chunk_file.exists() {
-                                        eprintln!("   ⚠️  WARNING: chunk_{}.bin.zst already exists - SKIPPING to avoid overwrite", chunk_num);
-                                        eprintln!("   📊 This suggests collection is restarting - continuing to next chunk...");
-                                        // Don't create the chunk, just continue collecting
-                                        // The temp file will accumulate blocks for the next chunk
-                                        blocks_in_current_chunk = 0;
-                                        continue;
-                                    }
+                            let bytes_read = match file_reader.read(&mut search_buffer) {
+                                Ok(0) => break,
+                                Ok(n) => n,
+                                Err(_) => break,
+                            };
 
-                                    eprintln!(
-                                        "   📦 Collected {} blocks - creating chunk {}...",
-                                        read_count, chunk_num
-                                    );
+                            // Encrypted-magic scan (decrypt-verify at each file offset)
+                            if let Some(i) =
+                                key.find_magic(&search_buffer[..bytes_read], search_pos, magic)
+                            {
+                                // Found next block - seek to it
+                                file_reader.seek(SeekFrom::Start(search_pos + i as u64))?;
+                                found_next = true;
+                            }
 
-                                    // Flush temp file to ensure all data is written
-                                    temp_writer.flush()?;
-                                    drop(temp_writer);
+                            if found_next {
+                                break;
+                            }
 
-                                    // Create chunk from temp file (it contains exactly INCREMENTAL_CHUNK_SIZE blocks)
-                                    BlockFileReader::create_and_move_chunk_from_file(
-                                        &temp_file,
-                                        chunk_num,
-                                        INCREMENTAL_CHUNK_SIZE,
-                                    )?;
+                            search_pos += bytes_read as u64;
+                            if bytes_read < search_buffer.len() {
+                                break;
+                            }
+                        }
+                    }
+                }
 
-                                    // Clear temp file for next chunk
-                                    // CRITICAL: temp_writer was already dropped above, so we can't use it here
-                                    // Verify temp file is the expected size before truncating
-                                    let temp_size_before = std::fs::metadata(&temp_file)?.len();
-                                    let expected_size = INCREMENTAL_CHUNK_SIZE as u64 * 1024 * 1024; // Rough estimate
-                                    if temp_size_before > 0 && temp_size_before < expected_size / 10
-                                    {
-                                        eprintln!("   ⚠️  WARNING: Temp file size ({}) seems unusually small before truncation", temp_size_before);
-                                    }
+                if block_data.len() >= 80 {
+                    if !emit(block_data) {
+                        break; // Writer stopped - nobody left to consume this file
+                    }
+                    blocks_read_from_file += 1;
+                }
+            }
 
-                                    // Open with truncate to clear for next chunk
-                                    let file = std::fs::OpenOptions::new()
-                                        .write(true)
-                                        .truncate(true)
-                                        .open(&temp_file)?;
-
-                                    // Verify file is actually empty after truncation
-                                    let temp_size_after = std::fs::metadata(&temp_file)?.len();
-                                    if temp_size_after != 0 {
-                                        eprintln!("   ⚠️  ERROR: Temp file not properly truncated (size: {} bytes)", temp_size_after);
-                                        return Err(anyhow::anyhow!(
-                                            "Temp file truncation failed - file not empty"
-                                        ));
-                                    }
+            Ok(blocks_read_from_file)
+        };
+        let read_blocks_from_file = Arc::new(read_blocks_from_file);
+
+        // Process files in parallel batches
+        // When resuming, start at the next file recorded with the last batch checkpoint in the
+        // metadata store. Without one (runs from before the store), fall back to a
+        // conservative estimate and validate blocks as we go.
+        let stored_resume = if read_count > 0 {
+            crate::meta_store::temp_resume_file(&temp_file)
+        } else {
+            None
+        };
+        let start_file_idx = if let Some(idx) = stored_resume {
+            idx.min(reader.block_files.len())
+        } else if read_count > 0 {
+            // More conservative estimate: ~50 blocks per file (to avoid skipping files)
+            // This ensures we don't miss any blocks, even if it means re-reading some
+            let estimated = (read_count as f64 / 50.0 * 0.7) as usize; // 70% of estimate to be very safe
+            estimated.min(reader.block_files.len())
+        } else {
+            0
+        };
 
-                                    temp_writer = BufWriter::with_capacity(IO_BUFFER_SIZE, file);
+        if stored_resume.is_some() {
+            info!(
+                "   📍 Resuming: starting at file {} (recorded with {} existing blocks)",
+                start_file_idx, read_count
+            );
+        } else if read_count > 0 && start_file_idx > 0 {
+            info!("   📍 Resuming: starting at file {} (conservative estimate based on {} existing blocks)", start_file_idx, read_count);
+            warn!("   ⚠️  NOTE: Some files may be re-read to ensure no blocks are missed");
+        }
 
-                                    // Reset block count for current chunk (temp file is now empty)
-                                    blocks_in_current_chunk = 0;
+        let file_paths: Vec<_> = reader.block_files.iter().skip(start_file_idx).collect();
+        // The temp file takes the remaining block files about verbatim; chunks compress them,
+        // so the same again is an upper bound
+        let remaining_bytes: u64 = file_paths
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        let tiers = chunk_tiers(tuning);
+        let mut watchdog = crate::disk_watchdog::DiskWatchdog::from_tuning(&tuning.storage)
+            .with_cancellation(reader.cancel.clone())
+            .watch("temp", temp_file.parent().unwrap_or_else(|| Path::new(".")))
+            .watch("chunk scratch", tiers.scratch_dir(&temp_file))
+            // New chunks spill over to the next archive, so only all of them full is low
+            .watch_any("chunk archives", tiers.archives().iter().cloned());
+        if let Some(ref cache_dir) = reader.local_cache_dir {
+            watchdog = watchdog.watch("local block cache", cache_dir);
+        }
+        watchdog.preflight(&[
+            ("temp", remaining_bytes),
+            ("chunk archives", remaining_bytes),
+        ]);
+        // Use tunable batch size - optimized for local LAN SSHFS (I/O bound, not CPU bound)
+        let batch_size = parallel_file_batch_size;
+        let mut processed_files = start_file_idx;
+        let progress = crate::progress::Progress::new("collect", estimated_total, "blocks");
+        progress.set_position(read_count as u64);
+        let mut last_file_idx = start_file_idx;
+
+        // OPTIMIZATION: Pre-copy files ahead in large batches before starting to read
+        // This ensures local cache is populated before we need the files
+        // Start pre-copy from current position (not from beginning if resuming)
+        // CRITICAL FIX: Make pre-copy non-blocking so we can start reading immediately
+        if let Some(ref cache_dir) = reader.local_cache_dir {
+            let precopy_count = pre_copy_lookahead.min(file_paths.len());
+            info!("   📦 Pre-copying {} files ahead (starting from file {}) to local cache (background)...", 
+                     precopy_count, start_file_idx);
+
+            // Clone paths for parallel processing (starting from current position)
+            let files_to_precopy: Vec<PathBuf> = file_paths
+                .iter()
+                .take(precopy_count)
+                .map(|p| (*p).clone())
+                .collect();
+            let cache_dir_clone = cache_dir.clone();
+
+            // CRITICAL FIX: Spawn pre-copy in background thread so it doesn't block reading
+            std::thread::spawn(move || {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(max_parallel_read_threads)
+                    .build();
+                if let Ok(pool) = pool {
+                    pool.install(|| {
+                        files_to_precopy.par_iter().for_each(|file_path| {
+                            let file_name = match file_path.file_name() {
+                                Some(name) => name,
+                                None => return,
+                            };
+                            let local_path = cache_dir_clone.join(file_name);
 
-                                    eprintln!(
-                                        "   ✅ Chunk {} complete and moved to secondary drive",
-                                        chunk_num
-                                    );
-                                    eprintln!("   📝 Continuing collection for next chunk...");
-                                }
+                            // Copy if not already cached (skip if exists)
+                            if !local_path.exists() {
+                                let _ = std::fs::copy(file_path, &local_path);
+                            }
+                        });
+                    });
+                }
+                info!(
+                    "   ✅ Background pre-copy complete - {} files ready in local cache",
+                    precopy_count
+                );
+            });
+            info!("   ⚡ Starting block reading immediately (pre-copy running in background)...");
+        }
 
-                                // Update blocks in current chunk
-                                blocks_in_current_chunk += 1;
-
-                                // Flush buffer periodically to prevent data loss on SIGKILL
-                                if read_count % TEMP_FILE_FLUSH_INTERVAL == 0 {
-                                    if let Err(e) = temp_writer.flush() {
-                                        eprintln!("   ⚠️  ERROR: Failed to flush temp file: {}", e);
-                                        return Err(anyhow::anyhow!(
-                                            "Temp file flush failed at block {}: {}",
-                                            read_count,
-                                            e
-                                        ));
-                                    }
+        // Track which files we've pre-copied to continue copying ahead
+        // Start from where initial pre-copy ended (relative to start_file_idx)
+        let mut last_precopy_idx = pre_copy_lookahead.min(file_paths.len());
+
+        // CRITICAL FIX: Add debug output and ensure loop starts
+        let total_batches = (file_paths.len() + batch_size - 1) / batch_size;
+        info!(
+            "   🚀 Starting to process {} files in {} batches (batch size: {})...",
+            file_paths.len(),
+            total_batches,
+            batch_size
+        );
 
-                                    // OPTIMIZATION: Update metadata file every 10k blocks
-                                    // This ensures we have an accurate count even if process is killed
-                                    // FIX: Use binary u64 format instead of ASCII text
-                                    if read_count % PROGRESS_REPORT_INTERVAL == 0 {
-                                        let metadata_file = temp_file.with_extension("bin.meta");
-                                        let count_bytes = (read_count as u64).to_le_bytes();
-                                        if let Err(e) = std::fs::write(&metadata_file, count_bytes)
-                                        {
-                                            eprintln!(
-                                                "   ⚠️  Warning: Failed to update metadata: {}",
-                                                e
-                                            );
-                                        }
+        for (batch_num, batch) in file_paths.chunks(batch_size).enumerate() {
+            if reader.cancel.is_cancelled() {
+                return Err(Self::collection_cancelled(
+                    &mut temp_writer,
+                    &temp_file,
+                    read_count,
+                    processed_files,
+                ));
+            }
+            // CRITICAL FIX: Add progress output at start of EVERY batch (not just every 10th)
+            info!(
+                "   📦 Processing batch {}/{} (files {}-{})...",
+                batch_num + 1,
+                total_batches,
+                processed_files,
+                processed_files + batch.len().min(batch_size) - 1
+            );
+            // Continue pre-copying ahead as we progress
+            // Keep 200 files ahead of current reading position
+            if let Some(ref cache_dir) = reader.local_cache_dir {
+                // Current position relative to start of file_paths (which starts at start_file_idx)
+                let current_pos_in_paths = (processed_files - start_file_idx) + batch.len();
+                let next_precopy_start = last_precopy_idx.max(current_pos_in_paths);
+                let next_precopy_end =
+                    (next_precopy_start + pre_copy_lookahead).min(file_paths.len());
+
+                if next_precopy_start < file_paths.len() && next_precopy_end > next_precopy_start {
+                    // Clone paths for background thread (must own the data)
+                    let files_to_precopy: Vec<PathBuf> = file_paths
+                        [next_precopy_start..next_precopy_end]
+                        .iter()
+                        .map(|p| (*p).clone())
+                        .collect();
+
+                    // Pre-copy in background (don't wait)
+                    let cache_dir_clone = cache_dir.clone();
+                    std::thread::spawn(move || {
+                        let pool = rayon::ThreadPoolBuilder::new()
+                            .num_threads(max_parallel_read_threads)
+                            .build();
+                        if let Ok(pool) = pool {
+                            pool.install(|| {
+                                files_to_precopy.par_iter().for_each(|file_path| {
+                                    let file_name = match file_path.file_name() {
+                                        Some(name) => name,
+                                        None => return,
+                                    };
+                                    let local_path = cache_dir_clone.join(file_name);
+
+                                    if !local_path.exists() {
+                                        let _ = std::fs::copy(file_path, &local_path);
                                     }
+                                });
+                            });
+                        }
+                    });
 
-                                    // INTEGRITY CHECK: Periodically verify blocks written to temp file
-                                    // Use blocks_in_current_chunk instead of read_count (total) because
-                                    // temp file only contains current chunk after truncation
-                                    if blocks_in_current_chunk > 0
-                                        && blocks_in_current_chunk
-                                            % TEMP_FILE_INTEGRITY_CHECK_INTERVAL
-                                            == 0
-                                    {
-                                        // Flush first to ensure data is on disk
-                                        temp_writer.flush()?;
-
-                                        // Verify last few blocks can be read back correctly
-                                        // Use blocks_in_current_chunk (not read_count) since temp file only has current chunk
-                                        let verify_count = 10.min(blocks_in_current_chunk); // Verify last 10 blocks
-                                        let verify_start = blocks_in_current_chunk - verify_count;
-
-                                        // Open temp file for reading
-                                        let mut verify_file = std::fs::File::open(&temp_file)?;
-                                        use std::io::{Read, Seek, SeekFrom};
-
-                                        // OPTIMIZATION: Read sequentially instead of seeking (much faster)
-                                        // Read all blocks up to verification start, then verify last few
-                                        // Note: current_block is relative to current chunk (0-based within chunk)
-                                        let mut current_block = 0;
-                                        while current_block < verify_start {
-                                            let mut len_buf = [0u8; 4];
-                                            match verify_file.read_exact(&mut len_buf) {
-                                                Ok(_) => {}
-                                                Err(e) => {
-                                                    // If we can't read, it might be because we're at EOF (not enough blocks yet)
-                                                    // This is OK - just skip the integrity check for now
-                                                    eprintln!("   ⚠️  WARNING: Integrity check skipped - cannot read block {} from temp file (only {} blocks in current chunk): {}", 
-                                                                 current_block, blocks_in_current_chunk, e);
-                                                    break; // Exit integrity check early, continue collection
-                                                }
-                                            }
-
-                                            let block_len = u32::from_le_bytes(len_buf) as usize;
-
-                                            // Validate block length
-                                            // OPTIMIZATION: For collection-only mode, be more resilient
-                                            // Skip corrupted blocks and continue - full validation happens during chunking
-                                            if block_len > MAX_VALID_BLOCK_SIZE
-                                                || block_len < MIN_VALID_BLOCK_SIZE
-                                            {
-                                                eprintln!("   ⚠️  WARNING: Integrity check found corrupted block {} (size: {} bytes) - skipping in verification", current_block, block_len);
-                                                // Try to recover by seeking to next potential block boundary
-                                                // Look for next valid block start (magic bytes pattern)
-                                                // For now, just skip this block and continue
-                                                current_block += 1;
-                                                continue;
-                                            }
-
-                                            // OPTIMIZATION: For large blocks, use seek instead of reading (faster)
-                                            // For small blocks, reading is faster due to buffer locality
-                                            if block_len > 64 * 1024 {
-                                                // Large block: seek past it (faster than reading)
-                                                verify_file
-                                                    .seek(SeekFrom::Current(block_len as i64))?;
-                                            } else {
-                                                // Small block: read into buffer (better cache locality)
-                                                let mut skip_buf = vec![0u8; block_len];
-                                                verify_file.read_exact(&mut skip_buf)?;
-                                            }
-
-                                            current_block += 1;
-                                        }
-
-                                        // Verify the last few blocks
-                                        // OPTIMIZATION: For collection-only mode, be very lenient
-                                        // Just check that we can read blocks, don't fail on validation
-                                        // Full validation happens during chunking
-                                        let mut verified_count = 0;
-                                        for i in 0..verify_count {
-                                            let mut len_buf = [0u8; 4];
-                                            match verify_file.read_exact(&mut len_buf) {
-                                                Ok(_) => {}
-                                                Err(_) => {
-                                                    // Can't read length - skip this block
-                                                    eprintln!("   ⚠️  WARNING: Cannot read block {} length - skipping in verification", verify_start + i);
-                                                    continue;
-                                                }
-                                            }
-
-                                            let block_len = u32::from_le_bytes(len_buf) as usize;
-
-                                            // Validate size - skip obviously invalid blocks
-                                            if block_len > MAX_VALID_BLOCK_SIZE
-                                                || block_len < MIN_VALID_BLOCK_SIZE
-                                            {
-                                                eprintln!("   ⚠️  WARNING: Integrity check found corrupted block {} (size: {} bytes) - will be caught during chunking", verify_start + i, block_len);
-                                                // Try to skip past this block and continue
-                                                // Seek past the invalid block if possible
-                                                if block_len < 10 * 1024 * 1024 * 1024 {
-                                                    // Don't seek if size is absurdly large
-                                                    if let Err(_) = verify_file
-                                                        .seek(SeekFrom::Current(block_len as i64))
-                                                    {
-                                                        // Can't seek - file might be corrupted, but continue anyway
-                                                    }
-                                                }
-                                                continue;
-                                            }
-
-                                            // Read block data
-                                            let mut block_data = vec![0u8; block_len];
-                                            match verify_file.read_exact(&mut block_data) {
-                                                Ok(_) => {
-                                                    // Block read successfully - count as verified
-                                                    verified_count += 1;
-                                                }
-                                                Err(_) => {
-                                                    eprintln!("   ⚠️  WARNING: Cannot read block {} data - skipping in verification", verify_start + i);
-                                                    continue;
-                                                }
-                                            }
-
-                                            // OPTIMIZATION: Skip version validation during collection
-                                            // Full validation happens during chunking
-                                        }
-
-                                        if verified_count > 0 {
-                                            eprintln!("   ✅ Integrity check: verified {} of {} recent blocks in current chunk (some may be skipped due to corruption)", verified_count, verify_count);
-                                        } else {
-                                            eprintln!("   ⚠️  WARNING: Could not verify any recent blocks in current chunk - collection continues, validation will happen during chunking");
-                                        }
-                                    }
+                    last_precopy_idx = next_precopy_end;
+                }
+            }
 
-                                    // OPTIMIZATION: Progress reporting less frequently (reduces I/O overhead)
-                                    // Flush more frequently for safety, but report less often
-                                    if read_count % TEMP_FILE_FLUSH_INTERVAL == 0 {
-                                        if let Err(e) = temp_writer.flush() {
-                                            eprintln!(
-                                                "   ⚠️  ERROR: Failed to flush temp file: {}",
-                                                e
-                                            );
-                                            return Err(anyhow::anyhow!(
-                                                "Temp file flush failed at block {}: {}",
-                                                read_count,
-                                                e
-                                            ));
-                                        }
+            // Read blocks from all files in batch through a bounded pipeline: one
+            // `sync_channel` per file keeps blocks in file order for the writer, and readers
+            // block as soon as the writer falls behind instead of buffering whole files.
+            // Jobs are taken strictly in file order, so the file the writer is draining is
+            // always being read by some thread (no deadlock on full channels).
+            let batch_start_time = std::time::Instant::now();
+            debug!(
+                "   🔍 Starting parallel read of {} files in batch {}...",
+                batch.len(),
+                batch_num + 1
+            );
+            let mut receivers = Vec::with_capacity(batch.len());
+            let mut jobs = Vec::with_capacity(batch.len());
+            for (batch_idx, file_path) in batch.iter().enumerate() {
+                let (tx, rx) =
+                    std::sync::mpsc::sync_channel::<Result<Vec<u8>>>(collection_pipeline_depth);
+                receivers.push(rx);
+                jobs.push((processed_files + batch_idx, (*file_path).clone(), tx));
+            }
+            let jobs = Arc::new(std::sync::Mutex::new(jobs.into_iter()));
+            for _ in 0..num_threads.min(batch.len()) {
+                let jobs = Arc::clone(&jobs);
+                let read_blocks_from_file = Arc::clone(&read_blocks_from_file);
+                let cancel = reader.cancel.clone();
+                std::thread::spawn(move || loop {
+                    let job = jobs.lock().ok().and_then(|mut it| it.next());
+                    let Some((file_idx, file_path, tx)) = job else {
+                        break;
+                    };
+                    let mut emit =
+                        |block: Vec<u8>| !cancel.is_cancelled() && tx.send(Ok(block)).is_ok();
+                    if let Err(e) = read_blocks_from_file(file_idx, &file_path, &mut emit) {
+                        let _ = tx.send(Err(e));
+                    }
+                });
+            }
 
-                                        let elapsed_since_last =
-                                            last_progress_time.elapsed().as_secs_f64();
-                                        let blocks_since_last = read_count - last_progress_count;
-                                        let current_rate = if elapsed_since_last > 0.0 {
-                                            blocks_since_last as f64 / elapsed_since_last
-                                        } else {
-                                            0.0
-                                        };
-
-                                        let total_elapsed = start_time.elapsed().as_secs_f64();
-                                        let avg_rate = if total_elapsed > 0.0 {
-                                            read_count as f64 / total_elapsed
-                                        } else {
-                                            0.0
-                                        };
-
-                                        if read_count % 5000 == 0 {
-                                            let progress_pct = (read_count as f64
-                                                / estimated_total as f64
-                                                * 100.0)
-                                                .min(100.0);
-                                            let eta_seconds = if avg_rate > 0.0 {
-                                                ((estimated_total - read_count as u64) as f64
-                                                    / avg_rate)
-                                                    as u64
-                                            } else {
-                                                0
-                                            };
-                                            println!("   📊 Progress: {}/{} blocks ({:.1}%) | Rate: {:.0} blocks/sec (avg: {:.0}) | ETA: {} min | File: {}", 
-                                                     read_count, estimated_total, progress_pct, current_rate, avg_rate, eta_seconds / 60, file_idx);
-                                        } else {
-                                            println!("   📊 Progress: {}/{} blocks ({:.1}%) | Rate: {:.0} blocks/sec | File: {}",
-                                                     read_count, estimated_total,
-                                                     (read_count as f64 / estimated_total as f64 * 100.0).min(100.0),
-                                                     current_rate, file_idx);
-                                        }
+            // Write all blocks from batch sequentially to temp file
+            // Track blocks in current chunk (resets after each chunk)
+            let mut blocks_in_current_chunk = read_count % incremental_chunk_size;
+
+            let mut batch_file_states = Vec::with_capacity(batch.len());
+            for (batch_idx, rx) in receivers.into_iter().enumerate() {
+                let file_idx = processed_files + batch_idx;
+                // Pausing here holds the readers back through the bounded channels
+                if watchdog.checkpoint().is_err() || reader.cancel.is_cancelled() {
+                    return Err(Self::collection_cancelled(
+                        &mut temp_writer,
+                        &temp_file,
+                        read_count,
+                        file_idx,
+                    ));
+                }
 
-                                        last_progress_time = std::time::Instant::now();
-                                        last_progress_count = read_count;
-                                    }
-                                }
-                            }
-                        }
+                let blocks_before_file = read_count;
+                for item in rx {
+                    if reader.cancel.is_cancelled() {
+                        break; // Reported at the next file boundary above
+                    }
+                    let block_data = match item {
+                        Ok(block_data) => block_data,
                         Err(e) => {
-                            eprintln!(
+                            warn!(
                                 "   ⚠️  Error reading blocks from file {}: {} - continuing",
                                 file_idx, e
                             );
+                            continue;
                         }
+                    };
+                    if file_idx != last_file_idx {
+                        info!(
+                            "   📂 Now reading from file {}: {}",
+                            file_idx,
+                            reader
+                                .block_files
+                                .get(file_idx)
+                                .map(|p| p.display().to_string())
+                                .unwrap_or_else(|| "unknown".to_string())
+                        );
+                        last_file_idx = file_idx;
                     }
-                }
 
-                processed_files += batch.len();
-            }
+                    // CRITICAL VALIDATION: Verify block before writing
+                    // Lenient read-stage rules: size + obviously garbage versions
+                    // (XOR decryption failure). Full checks happen during chunking.
+                    if let Err(rejection) =
+                        crate::sanity::filter(SanityStage::Read).check(&block_data)
+                    {
+                        error!(
+                            "   ⚠️  ERROR: Block {} failed sanity check ({}) - SKIPPING",
+                            read_count, rejection
+                        );
+                        continue; // Skip invalid block
+                    }
 
-            // Final flush and integrity check (temp_writer, read_count, temp_file are in scope here)
-            temp_writer.flush()?;
-            drop(temp_writer);
-
-            // Handle final chunk if there are remaining blocks
-            // CRITICAL FIX: Count actual blocks in temp file, not read_count
-            // (read_count includes skipped blocks, temp file only has written blocks)
-            if temp_file.exists() {
-                let temp_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
-                if temp_size > 0 {
-                    // Count actual blocks in temp file
-                    use std::io::{Read, Seek, SeekFrom};
-                    let mut temp_reader = std::fs::File::open(&temp_file)?;
-                    temp_reader.seek(SeekFrom::Start(0))?;
-                    let mut blocks_in_temp = 0;
-                    let mut offset = 0u64;
+                    // CRITICAL FIX: Don't skip blocks during collection!
+                    // Blocks are stored OUT OF ORDER in XOR-packaged files, so we can't know
+                    // which block we're reading until we parse it. We need to collect
+                    // ALL blocks, then order them later. The chunking logic will handle
+                    // skipping blocks that are already in chunks.
 
-                    loop {
-                        let mut len_buf = [0u8; 4];
-                        match temp_reader.read_exact(&mut len_buf) {
-                            Ok(_) => {}
-                            Err(_) => break, // End of file
-                        }
-                        let block_len = u32::from_le_bytes(len_buf) as u64;
-                        if block_len < 80 || block_len > 32 * 1024 * 1024 {
-                            break; // Invalid size, stop counting
-                        }
-                        offset += 4 + block_len;
-                        if offset > temp_size {
-                            break; // Past end of file
+                    // Write block to temp file: [len: u32][data...]
+                    let block_len = block_data.len() as u32;
+                    // OPTIMIZATION: Pre-compute length bytes once
+                    let len_bytes = block_len.to_le_bytes();
+
+                    temp_writer.write_all(&len_bytes).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Failed to write block length for block {}: {}",
+                            read_count,
+                            e
+                        )
+                    })?;
+                    temp_writer.write_all(&block_data).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Failed to write block data for block {}: {}",
+                            read_count,
+                            e
+                        )
+                    })?;
+                    read_count += 1;
+                    progress.inc(1);
+
+                    // INCREMENTAL CHUNKING: When we have enough blocks for a chunk, compress and move it
+                    if read_count > 0 && read_count % incremental_chunk_size == 0 {
+                        // CRITICAL FIX: Calculate chunk number correctly based on total blocks collected
+                        // chunk_num = (read_count / incremental_chunk_size) - 1
+                        // For read_count = 125000: chunk_num = (125000 / 125000) - 1 = 0
+                        // For read_count = 250000: chunk_num = (250000 / 125000) - 1 = 1
+                        let chunk_num = (read_count / incremental_chunk_size) - 1;
+
+                        // CRITICAL FIX: Check if chunk already exists to prevent overwriting
+                        if chunk_tiers(tuning)
+                            .locate(&chunks_dir, chunk_num as u64)
+                            .is_some()
+                        {
+                            warn!("   ⚠️  WARNING: chunk_{}.bin.zst already exists - SKIPPING to avoid overwrite", chunk_num);
+                            info!("   📊 This suggests collection is restarting - continuing to next chunk...");
+                            // Don't create the chunk, just continue collecting
+                            // The temp file will accumulate blocks for the next chunk
+                            blocks_in_current_chunk = 0;
+                            continue;
                         }
-                        temp_reader.seek(SeekFrom::Start(offset))?;
-                        blocks_in_temp += 1;
-                    }
 
-                    if blocks_in_temp > 0 {
-                        // Calculate chunk number based on starting_block_count + blocks_in_temp
-                        let total_blocks_collected =
-                            starting_block_count as u64 + blocks_in_temp as u64;
-                        let final_chunk_num =
-                            total_blocks_collected / INCREMENTAL_CHUNK_SIZE as u64;
-                        let final_chunk_blocks = blocks_in_temp;
-
-                        // CRITICAL FIX: Check if chunk already exists before trying to create it
-                        let chunk_file =
-                            chunks_dir.join(format!("chunk_{}.bin.zst", final_chunk_num));
-                        if chunk_file.exists() {
-                            eprintln!("   ⚠️  Final chunk {} already exists - SKIPPING to prevent overwrite", final_chunk_num);
-                            eprintln!("   📊 Temp file has {} blocks but chunk {} already exists - preserving temp file for resume", blocks_in_temp, final_chunk_num);
-                            // Don't delete temp file - preserve it for resume
-                        } else {
-                            eprintln!(
-                                "   📦 Creating final chunk {} with {} blocks from temp file...",
-                                final_chunk_num, final_chunk_blocks
-                            );
+                        info!(
+                            "   📦 Collected {} blocks - creating chunk {}...",
+                            read_count, chunk_num
+                        );
 
-                            BlockFileReader::create_and_move_chunk_from_file(
-                                &temp_file,
-                                final_chunk_num as usize,
-                                final_chunk_blocks as usize,
-                            )?;
+                        // Flush temp file to ensure all data is written
+                        temp_writer.flush()?;
+                        drop(temp_writer);
 
-                            // Clear temp file only after successful chunk creation
-                            std::fs::remove_file(&temp_file)?;
+                        // Create chunk from temp file (it contains exactly incremental_chunk_size blocks)
+                        BlockFileReader::create_and_move_chunk_from_file(
+                            &temp_file,
+                            chunk_num,
+                            incremental_chunk_size,
+                            &reader.tuning,
+                        )?;
+
+                        // Clear temp file for next chunk
+                        // CRITICAL: temp_writer was already dropped above, so we can't use it here
+                        // Verify temp file is the expected size before truncating
+                        let temp_size_before = std::fs::metadata(&temp_file)?.len();
+                        let expected_size = incremental_chunk_size as u64 * 1024 * 1024; // Rough estimate
+                        if temp_size_before > 0 && temp_size_before < expected_size / 10 {
+                            warn!("   ⚠️  WARNING: Temp file size ({}) seems unusually small before truncation", temp_size_before);
+                        }
 
-                            eprintln!(
-                                "   ✅ Final chunk {} complete and moved to secondary drive",
-                                final_chunk_num
+                        // Open with truncate to clear for next chunk
+                        let file = std::fs::OpenOptions::new()
+                            .write(true)
+                            .truncate(true)
+                            .open(&temp_file)?;
+
+                        // Verify file is actually empty after truncation
+                        let temp_size_after = std::fs::metadata(&temp_file)?.len();
+                        if temp_size_after != 0 {
+                            error!(
+                                "   ⚠️  ERROR: Temp file not properly truncated (size: {} bytes)",
+                                temp_size_after
                             );
+                            return Err(anyhow::anyhow!(
+                                "Temp file truncation failed - file not empty"
+                            ));
                         }
-                    } else {
-                        eprintln!("   ⚠️  Temp file exists but contains no valid blocks - preserving for resume");
-                    }
-                } else {
-                    eprintln!("   ⚠️  Temp file is empty - no final chunk to create");
-                }
-            }
 
-            // Final integrity check: verify last 100 blocks (only if temp file still exists)
-            if temp_file.exists() {
-                eprintln!("   🔍 Running final integrity check...");
-                let mut verify_file = std::fs::File::open(&temp_file)?;
-                use std::io::{Read, Seek, SeekFrom};
+                        temp_writer = BufWriter::with_capacity(io_buffer_size, file);
 
-                let verify_count = 100.min(read_count);
-                let verify_start = read_count - verify_count;
+                        // Reset block count for current chunk (temp file is now empty)
+                        blocks_in_current_chunk = 0;
 
-                // Skip to verification start
-                let mut pos = 0u64;
-                for _ in 0..verify_start {
-                    let mut len_buf = [0u8; 4];
-                    verify_file.read_exact(&mut len_buf)?;
-                    let block_len = u32::from_le_bytes(len_buf) as u64;
-                    if block_len > MAX_VALID_BLOCK_SIZE as u64
-                        || block_len < MIN_VALID_BLOCK_SIZE as u64
-                    {
-                        return Err(anyhow::anyhow!(
-                            "Final integrity check failed: block has invalid size {}",
-                            block_len
-                        ));
+                        info!("   ✅ Chunk {} complete and moved to archive", chunk_num);
+                        info!("   📝 Continuing collection for next chunk...");
                     }
-                    pos += 4 + block_len;
-                    verify_file.seek(SeekFrom::Start(pos))?;
-                }
 
-                // Verify last blocks
-                for i in 0..verify_count {
-                    let mut len_buf = [0u8; 4];
-                    verify_file.read_exact(&mut len_buf)?;
-                    let block_len = u32::from_le_bytes(len_buf) as usize;
-
-                    if block_len > MAX_VALID_BLOCK_SIZE || block_len < MIN_VALID_BLOCK_SIZE {
-                        return Err(anyhow::anyhow!(
-                            "Final integrity check failed: block {} has invalid size {}",
-                            verify_start + i,
-                            block_len
-                        ));
-                    }
+                    // Update blocks in current chunk
+                    blocks_in_current_chunk += 1;
 
-                    let mut block_data = vec![0u8; block_len];
-                    verify_file.read_exact(&mut block_data)?;
-
-                    if block_data.len() >= 4 {
-                        let version = u32::from_le_bytes([
-                            block_data[0],
-                            block_data[1],
-                            block_data[2],
-                            block_data[3],
-                        ]);
-                        // CRITICAL FIX: Bitcoin block versions can be much higher than 10
-                        // Only reject obviously invalid: version == 0 or version > 0x7fffffff
-                        if version == 0 || version > 0x7fffffff {
+                    // Flush buffer periodically to prevent data loss on SIGKILL
+                    if read_count % temp_file_flush_interval == 0 {
+                        if let Err(e) = temp_writer.flush() {
+                            error!("   ⚠️  ERROR: Failed to flush temp file: {}", e);
                             return Err(anyhow::anyhow!(
-                                "Final integrity check failed: block {} has invalid version {}",
-                                verify_start + i,
-                                version
+                                "Temp file flush failed at block {}: {}",
+                                read_count,
+                                e
                             ));
                         }
-                    }
-                }
 
-                eprintln!(
-                    "   ✅ Final integrity check passed: verified last {} blocks",
-                    verify_count
-                );
-            }
+                        // OPTIMIZATION: Update the stored count every 10k blocks
+                        // This ensures we have an accurate count even if process is killed
+                        if read_count % progress_report_interval == 0 {
+                            if let Err(e) = crate::meta_store::record_temp_progress(
+                                &temp_file,
+                                read_count as u64,
+                                None,
+                            ) {
+                                error!("   ⚠️  Warning: Failed to update metadata: {}", e);
+                            }
+                        }
 
-            eprintln!(
-                "   ℹ️  Finished reading {} blocks from {} files",
-                read_count, processed_files
-            );
+                        // INTEGRITY CHECK: Periodically verify blocks written to temp file
+                        // Use blocks_in_current_chunk instead of read_count (total) because
+                        // temp file only contains current chunk after truncation
+                        if blocks_in_current_chunk > 0
+                            && blocks_in_current_chunk % temp_file_integrity_check_interval == 0
+                        {
+                            // Flush first to ensure data is on disk
+                            temp_writer.flush()?;
+
+                            // Verify last few blocks can be read back correctly
+                            // Use blocks_in_current_chunk (not read_count) since temp file only has current chunk
+                            let verify_count = 10.min(blocks_in_current_chunk); // Verify last 10 blocks
+                            let verify_start = blocks_in_current_chunk - verify_count;
+
+                            // Open temp file for reading
+                            let mut verify_file = std::fs::File::open(&temp_file)?;
+                            use std::io::{Read, Seek, SeekFrom};
+
+                            // OPTIMIZATION: Read sequentially instead of seeking (much faster)
+                            // Read all blocks up to verification start, then verify last few
+                            // Note: current_block is relative to current chunk (0-based within chunk)
+                            let mut current_block = 0;
+                            while current_block < verify_start {
+                                let mut len_buf = [0u8; 4];
+                                match verify_file.read_exact(&mut len_buf) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        // If we can't read, it might be because we're at EOF (not enough blocks yet)
+                                        // This is OK - just skip the integrity check for now
+                                        warn!("   ⚠️  WARNING: Integrity check skipped - cannot read block {} from temp file (only {} blocks in current chunk): {}", 
+                                                     current_block, blocks_in_current_chunk, e);
+                                        break; // Exit integrity check early, continue collection
+                                    }
+                                }
 
-            let total_time = start_time.elapsed();
-            println!(
-                "   ✅ Read {} total blocks from file in {:.1} minutes",
-                read_count,
-                total_time.as_secs_f64() / 60.0
-            );
+                                let block_len = u32::from_le_bytes(len_buf) as usize;
 
-            // CRITICAL FIX: After batch reading finishes, if temp file was truncated (chunk created),
-            // we need to continue reading files. But instead of restarting from file 0, we should
-            // continue from where we left off. However, since all files were already processed,
-            // we should just continue with the iterator which will read from files sequentially.
-            // The iterator will start from file 0, but that's OK - it will collect any remaining blocks.
-            // Store the last processed file index so iterator can potentially skip already-processed files
-            let last_processed_file_idx = processed_files;
-
-            // Now read blocks back from temp file and build hash map
-            // CRITICAL FIX: Check if temp file exists before trying to read it
-            // In collection-only mode with incremental chunking, the temp file may be truncated after chunking
-            // BUT: This doesn't mean collection is complete - we need to continue reading files
-            // Only stop if we've actually read all files, not just because temp file was truncated
-            if !temp_file.exists() {
-                println!("   ℹ️  Temp file no longer exists (truncated after chunking) - will continue reading from files");
-                println!(
-                    "   📍 Last processed file: {} (will continue from there in iterator)",
-                    last_processed_file_idx
-                );
-                // DON'T stop - continue reading from files to collect more blocks
-                // The temp file will be recreated as we read more blocks
-                ordered_blocks = None; // Continue reading from files
-            }
+                                // Validate block length
+                                // OPTIMIZATION: For collection-only mode, be more resilient
+                                // Skip corrupted blocks and continue - full validation happens during chunking
+                                if block_len > MAX_VALID_BLOCK_SIZE
+                                    || block_len < MIN_VALID_BLOCK_SIZE
+                                {
+                                    warn!("   ⚠️  WARNING: Integrity check found corrupted block {} (size: {} bytes) - skipping in verification", current_block, block_len);
+                                    // Try to recover by seeking to next potential block boundary
+                                    // Look for next valid block start (magic bytes pattern)
+                                    // For now, just skip this block and continue
+                                    current_block += 1;
+                                    continue;
+                                }
 
-            // CRITICAL FIX: If temp file doesn't exist or is empty (truncated after chunking),
-            // continue reading from files instead of stopping. Collection is NOT complete just
-            // because temp file was truncated - we need to read ALL files first.
-            if !temp_file.exists() {
-                println!("   ℹ️  Temp file doesn't exist (was truncated after chunking) - continuing file reading");
-                ordered_blocks = None; // Continue reading from files - DON'T STOP COLLECTION
-            } else {
-                println!("   📖 Reading blocks from temp file to build hash map...");
-                // OPTIMIZATION: Use larger buffer for temp file reading (faster sequential reads)
-                match std::fs::File::open(&temp_file) {
-                    Ok(f) => {
-                        let mut temp_reader = std::io::BufReader::with_capacity(IO_BUFFER_SIZE, f);
-                        use std::io::Read;
-
-                        // FIX OOM: Process blocks in chunks instead of loading all into memory
-                        // Build hash map incrementally, storing file offsets instead of block data
-                        // This avoids loading 400+ GB into RAM
-                        println!(
-                            "   📖 Processing blocks in chunks to build hash map (avoiding OOM)..."
-                        );
-                        let estimated_blocks = read_count;
+                                // OPTIMIZATION: For large blocks, use seek instead of reading (faster)
+                                // For small blocks, reading is faster due to buffer locality
+                                if block_len > 64 * 1024 {
+                                    // Large block: seek past it (faster than reading)
+                                    verify_file.seek(SeekFrom::Current(block_len as i64))?;
+                                } else {
+                                    // Small block: read into buffer (better cache locality)
+                                    let mut skip_buf = vec![0u8; block_len];
+                                    verify_file.read_exact(&mut skip_buf)?;
+                                }
+
+                                current_block += 1;
+                            }
 
-                        // Hash map: prev_hash -> (file_offset, block_len)
-                        // We'll re-read blocks from temp file when chaining (slower but no OOM)
-                        // OPTIMIZATION: Pre-allocate hash map with estimated capacity to reduce rehashing
-                        let mut blocks_by_prev_hash: HashMap<Vec<u8>, (u64, usize)> =
-                            HashMap::with_capacity(estimated_blocks.min(1_000_000));
-                        let mut genesis_block: Option<(u64, usize)> = None;
+                            // Verify the last few blocks
+                            // OPTIMIZATION: For collection-only mode, be very lenient
+                            // Just check that we can read blocks, don't fail on validation
+                            // Full validation happens during chunking
+                            let mut verified_count = 0;
+                            for i in 0..verify_count {
+                                let mut len_buf = [0u8; 4];
+                                match verify_file.read_exact(&mut len_buf) {
+                                    Ok(_) => {}
+                                    Err(_) => {
+                                        // Can't read length - skip this block
+                                        warn!("   ⚠️  WARNING: Cannot read block {} length - skipping in verification", verify_start + i);
+                                        continue;
+                                    }
+                                }
 
-                        const CHUNK_SIZE: usize = HASH_MAP_CHUNK_SIZE;
-                        // OPTIMIZATION: Pre-allocate chunk vector with exact capacity
-                        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
-                        let mut blocks_processed = 0;
-                        let mut current_offset: u64 = 0;
+                                let block_len = u32::from_le_bytes(len_buf) as usize;
 
-                        loop {
-                            // Read block length
-                            let block_start_offset = current_offset; // Points to start of [len: u32]
-                            let mut len_buf = [0u8; 4];
-                            match temp_reader.read_exact(&mut len_buf) {
-                                Ok(_) => {}
-                                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                                    // Process remaining blocks in chunk
-                                    if !chunk.is_empty() {
-                                        Self::process_chunk(
-                                            &chunk,
-                                            &mut blocks_by_prev_hash,
-                                            &mut genesis_block,
-                                        )?;
-                                        chunk.clear();
+                                // Validate size - skip obviously invalid blocks
+                                if block_len > MAX_VALID_BLOCK_SIZE
+                                    || block_len < MIN_VALID_BLOCK_SIZE
+                                {
+                                    warn!("   ⚠️  WARNING: Integrity check found corrupted block {} (size: {} bytes) - will be caught during chunking", verify_start + i, block_len);
+                                    // Try to skip past this block and continue
+                                    // Seek past the invalid block if possible
+                                    if block_len < 10 * 1024 * 1024 * 1024 {
+                                        // Don't seek if size is absurdly large
+                                        if let Err(_) =
+                                            verify_file.seek(SeekFrom::Current(block_len as i64))
+                                        {
+                                            // Can't seek - file might be corrupted, but continue anyway
+                                        }
                                     }
-                                    break;
+                                    continue;
                                 }
-                                Err(e) => return Err(e.into()),
-                            }
-                            current_offset += 4;
-                            let block_len = u32::from_le_bytes(len_buf) as usize;
-
-                            // CRITICAL OOM FIX: Read only block header (80 bytes) instead of full block
-                            // We only need prev_hash (bytes 4-36) to build the hash map
-                            // This reduces memory usage by ~18,750x (80 bytes vs 1.5MB average)
-                            let mut header = vec![0u8; 80.min(block_len)]; // Read up to 80 bytes (or block_len if smaller)
-                            temp_reader.read_exact(&mut header)?;
-
-                            // Skip the rest of the block (we don't need it for hash map building)
-                            if block_len > 80 {
-                                temp_reader
-                                    .seek(std::io::SeekFrom::Current((block_len - 80) as i64))?;
-                            }
-                            current_offset += block_len as u64;
-
-                            // Store only header (80 bytes) instead of full block (1.5MB average)
-                            chunk.push((header, block_start_offset, block_len));
-                            blocks_processed += 1;
-
-                            // Process chunk when full
-                            if chunk.len() >= CHUNK_SIZE {
-                                Self::process_chunk(
-                                    &chunk,
-                                    &mut blocks_by_prev_hash,
-                                    &mut genesis_block,
-                                )?;
-                                chunk.clear();
-
-                                if blocks_processed % PROGRESS_REPORT_INTERVAL == 0 {
-                                    println!(
-                                        "   📖 Processed {}/{} blocks...",
-                                        blocks_processed, read_count
-                                    );
+
+                                // Read block data
+                                let mut block_data = vec![0u8; block_len];
+                                match verify_file.read_exact(&mut block_data) {
+                                    Ok(_) => {
+                                        // Block read successfully - count as verified
+                                        verified_count += 1;
+                                    }
+                                    Err(_) => {
+                                        warn!("   ⚠️  WARNING: Cannot read block {} data - skipping in verification", verify_start + i);
+                                        continue;
+                                    }
                                 }
-                            }
-                        }
 
-                        println!(
-                            "   ✅ Built hash map with {} entries",
-                            blocks_by_prev_hash.len()
-                        );
+                                // OPTIMIZATION: Skip version validation during collection
+                                // Full validation happens during chunking
+                            }
 
-                        if genesis_block.is_none() {
-                            eprintln!(
-                                "⚠️  Warning: Genesis block not found in {} blocks read",
-                                read_count
-                            );
+                            if verified_count > 0 {
+                                info!("   ✅ Integrity check: verified {} of {} recent blocks in current chunk (some may be skipped due to corruption)", verified_count, verify_count);
+                            } else {
+                                warn!("   ⚠️  WARNING: Could not verify any recent blocks in current chunk - collection continues, validation will happen during chunking");
+                            }
                         }
 
-                        eprintln!(
-                            "   Found {} blocks with previous hashes (excluding genesis)",
-                            blocks_by_prev_hash.len()
-                        );
-                    }
-                    Err(e) => {
-                        // Temp file can't be opened (maybe truncated after chunking) - continue reading from files
-                        eprintln!("   ⚠️  Warning: Could not open temp file for hash map building: {} - continuing file reading", e);
-                        ordered_blocks = None; // Continue reading from files - DON'T STOP COLLECTION
+                        // Flush periodically for safety; the bar only needs the current file
+                        if read_count % temp_file_flush_interval == 0 {
+                            if let Err(e) = temp_writer.flush() {
+                                error!("   ⚠️  ERROR: Failed to flush temp file: {}", e);
+                                return Err(anyhow::anyhow!(
+                                    "Temp file flush failed at block {}: {}",
+                                    read_count,
+                                    e
+                                ));
+                            }
+                            progress.set_message(format!("file {}", file_idx));
+                        }
                     }
                 }
+                if !reader.cancel.is_cancelled() {
+                    batch_file_states.push((file_idx, (read_count - blocks_before_file) as u64));
+                }
             }
 
-            // Define cache file path (for old format, if needed)
-            let cache_file = ordered_blocks_cache_path_for_write();
-
-            // Check if chunked cache already exists - if so, skip building old format
-            // CRITICAL: Also skip if temp file doesn't exist (was truncated after chunking)
-            let chunks_dir = crate::chunked_cache::get_chunks_dir();
-            let should_build_old_cache = if !temp_file.exists() {
-                // Temp file doesn't exist (truncated after chunking) - skip cache build, continue reading files
-                false
-            } else if let Some(ref chunks_path) = chunks_dir {
-                !chunks_path.exists() || !chunks_path.join("chunks.meta").exists()
-            } else {
-                true
-            };
+            let batch_duration = batch_start_time.elapsed();
+            info!(
+                "   ✅ Completed batch {} ({} files read and written) in {:.1}s",
+                batch_num + 1,
+                batch.len(),
+                batch_duration.as_secs_f64()
+            );
 
-            if !should_build_old_cache {
-                if !temp_file.exists() {
-                    println!("   ℹ️  Temp file doesn't exist (truncated after chunking) - skipping cache build, continuing file reading");
-                } else {
-                    println!(
-                        "   ✅ Chunked cache already exists - skipping old format cache build"
-                    );
-                    println!("   💡 Use chunked cache for better space efficiency");
-                }
-            } else {
-                // OPTIMIZATION: Skip chaining during cache build - just copy blocks sequentially
-                // Chaining is expensive (memory mapping, header reading, chain following, sorting)
-                // For cache building, we don't need chain order - blocks can be stored as-is
-                // Chaining can be done later when reading from cache if needed
-                // NOTE: With chunked cache, we typically don't build the old single-file cache
-                // This code path is kept for backward compatibility
-                println!(
-                    "   💾 Building cache (skipping chaining for speed - blocks stored as-is)..."
-                );
-                println!(
-                    "   ⚠️  Note: Consider using chunked cache format for better space efficiency"
+            // CRITICAL FIX: Warn if batch takes too long (might indicate stuck file)
+            if batch_duration.as_secs() > 300 {
+                warn!(
+                    "   ⚠️  WARNING: Batch {} took {:.1} minutes - some files may be problematic",
+                    batch_num + 1,
+                    batch_duration.as_secs_f64() / 60.0
                 );
-                let cache_start = std::time::Instant::now();
+            }
+
+            processed_files += batch.len();
+
+            // Batch checkpoint: every file before `processed_files` is in the temp file, so the
+            // count, resume position and per-file states are committed together.
+            if !reader.cancel.is_cancelled() {
+                temp_writer.flush()?;
+                let file_states: Vec<_> = batch_file_states
+                    .iter()
+                    .filter_map(|&(idx, blocks)| {
+                        let path = reader.block_files.get(idx)?;
+                        let state = crate::meta_store::FileState {
+                            size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+                            blocks,
+                            complete: true,
+                        };
+                        Some((path.as_path(), state))
+                    })
+                    .collect();
+                if let Err(e) = crate::meta_store::record_collection_batch(
+                    &temp_file,
+                    read_count as u64,
+                    processed_files,
+                    &file_states,
+                ) {
+                    error!("   ⚠️  Warning: Failed to checkpoint batch metadata: {}", e);
+                }
+            }
+        }
+
+        if reader.cancel.is_cancelled() {
+            return Err(Self::collection_cancelled(
+                &mut temp_writer,
+                &temp_file,
+                read_count,
+                processed_files,
+            ));
+        }
 
-                // Open cache file for streaming writes
-                let mut cache_writer: Option<std::io::BufWriter<std::fs::File>> = None;
-                let mut total_blocks_written = 0u64;
-                let save_start = std::time::Instant::now();
+        // Final flush and integrity check (temp_writer, read_count, temp_file are in scope here)
+        temp_writer.flush()?;
+        drop(temp_writer);
+
+        // Handle final chunk if there are remaining blocks
+        // CRITICAL FIX: Count actual blocks in temp file, not read_count
+        // (read_count includes skipped blocks, temp file only has written blocks)
+        if temp_file.exists() {
+            let temp_size = std::fs::metadata(&temp_file).map(|m| m.len()).unwrap_or(0);
+            if temp_size > 0 {
+                // Count actual blocks in temp file
+                use std::io::{Read, Seek, SeekFrom};
+                let mut temp_reader = std::fs::File::open(&temp_file)?;
+                temp_reader.seek(SeekFrom::Start(0))?;
+                let mut blocks_in_temp = 0;
+                let mut offset = 0u64;
 
-                if let Some(ref cache_path) = cache_file {
-                    if let Some(parent) = cache_path.parent() {
-                        let _ = std::fs::create_dir_all(parent);
+                loop {
+                    let mut len_buf = [0u8; 4];
+                    match temp_reader.read_exact(&mut len_buf) {
+                        Ok(_) => {}
+                        Err(_) => break, // End of file
+                    }
+                    let block_len = u32::from_le_bytes(len_buf) as u64;
+                    if block_len < 80 || block_len > 32 * 1024 * 1024 {
+                        break; // Invalid size, stop counting
+                    }
+                    offset += 4 + block_len;
+                    if offset > temp_size {
+                        break; // Past end of file
                     }
-                    // Reserve space for block count (u64) at start, will update at end
-                    let cache_file_handle = std::fs::File::create(cache_path)?;
-                    let mut writer =
-                        std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, cache_file_handle);
-                    // Write placeholder for block count (will update at end)
-                    writer.write_all(&0u64.to_le_bytes())?;
-                    cache_writer = Some(writer);
+                    temp_reader.seek(SeekFrom::Start(offset))?;
+                    blocks_in_temp += 1;
                 }
 
-                // OPTIMIZATION: Use memory-mapped file for fast sequential reading
-                // Read blocks directly from temp file in order and write to cache
-                // This is MUCH faster than chaining - just a simple sequential copy
-                println!("   🗺️  Memory-mapping temp file for fast sequential copy...");
-                use memmap2::MmapOptions;
-                let file = std::fs::File::open(&temp_file)?;
-                let mmap = unsafe { MmapOptions::new().map(&file)? };
-                println!(
-                    "   ✅ Memory-mapped {} GB file",
-                    mmap.len() as f64 / 1_073_741_824.0
-                );
+                if blocks_in_temp > 0 {
+                    // Calculate chunk number based on starting_block_count + blocks_in_temp
+                    let total_blocks_collected =
+                        starting_block_count as u64 + blocks_in_temp as u64;
+                    let final_chunk_num = total_blocks_collected / incremental_chunk_size as u64;
+                    let final_chunk_blocks = blocks_in_temp;
+
+                    // CRITICAL FIX: Check if chunk already exists before trying to create it
+                    if chunk_tiers(tuning)
+                        .locate(&chunks_dir, final_chunk_num)
+                        .is_some()
+                    {
+                        warn!(
+                            "   ⚠️  Final chunk {} already exists - SKIPPING to prevent overwrite",
+                            final_chunk_num
+                        );
+                        info!("   📊 Temp file has {} blocks but chunk {} already exists - preserving temp file for resume", blocks_in_temp, final_chunk_num);
+                        // Don't delete temp file - preserve it for resume
+                    } else {
+                        info!(
+                            "   📦 Creating final chunk {} with {} blocks from temp file...",
+                            final_chunk_num, final_chunk_blocks
+                        );
 
-                // OPTIMIZATION: Sequential copy is already optimal for NVMe SSDs
-                // Memory-mapped reads are instant (no I/O wait), sequential writes are fastest
-                // Parallelizing would add overhead without benefit (can't parallelize single-file writes)
-                // The 128MB buffer ensures maximum throughput for sequential I/O
-                println!("   📖 Copying blocks from temp file to cache (sequential, optimized for NVMe)...");
-                let mut pos = 0usize;
-                let mut blocks_copied = 0;
+                        BlockFileReader::create_and_move_chunk_from_file(
+                            &temp_file,
+                            final_chunk_num as usize,
+                            final_chunk_blocks as usize,
+                            &reader.tuning,
+                        )?;
 
-                // Pre-allocate block length buffer to avoid repeated allocations
-                let mut len_buf = [0u8; 4];
+                        // Clear temp file only after successful chunk creation
+                        std::fs::remove_file(&temp_file)?;
 
-                while pos + 4 <= mmap.len() {
-                    // Read block length (4 bytes) - use pre-allocated buffer
-                    len_buf.copy_from_slice(&mmap[pos..pos + 4]);
-                    let block_len = u32::from_le_bytes(len_buf) as usize;
-                    pos += 4;
-
-                    // Read block data
-                    if pos + block_len > mmap.len() {
-                        eprintln!(
-                            "   ⚠️  Warning: Block at offset {} extends beyond file end, stopping",
-                            pos - 4
+                        info!(
+                            "   ✅ Final chunk {} complete and moved to archive",
+                            final_chunk_num
                         );
-                        break;
                     }
+                } else {
+                    warn!("   ⚠️  Temp file exists but contains no valid blocks - preserving for resume");
+                }
+            } else {
+                warn!("   ⚠️  Temp file is empty - no final chunk to create");
+            }
+        }
 
-                    let block_data = &mmap[pos..pos + block_len];
+        // Final integrity check: verify last 100 blocks (only if temp file still exists)
+        if temp_file.exists() {
+            debug!("   🔍 Running final integrity check...");
+            let mut verify_file = std::fs::File::open(&temp_file)?;
+            use std::io::{Read, Seek, SeekFrom};
 
-                    // Write block to cache immediately (streaming, no accumulation)
-                    // Large 128MB buffer ensures minimal system calls
-                    if let Some(ref mut writer) = cache_writer {
-                        writer.write_all(&len_buf)?;
-                        writer.write_all(block_data)?;
-                        total_blocks_written += 1;
-                    }
+            let verify_count = 100.min(read_count);
+            let verify_start = read_count - verify_count;
 
-                    pos += block_len;
-                    blocks_copied += 1;
+            let sanity = crate::sanity::filter(SanityStage::CacheLoad);
 
-                    if blocks_copied % PROGRESS_REPORT_INTERVAL == 0 {
-                        let elapsed = cache_start.elapsed().as_secs();
-                        let rate = if elapsed > 0 {
-                            blocks_copied as f64 / elapsed as f64
-                        } else {
-                            0.0
-                        };
-                        let progress_pct = if read_count > 0 {
-                            (blocks_copied as f64 / read_count as f64 * 100.0).min(100.0)
-                        } else {
-                            0.0
-                        };
-                        println!(
-                            "   📊 Copied {}/{} blocks ({:.1}%) | Rate: {:.0} blocks/sec",
-                            blocks_copied, read_count, progress_pct, rate
-                        );
-                        // Flush periodically to ensure progress is saved
-                        if let Some(ref mut writer) = cache_writer {
-                            let _ = writer.flush();
-                        }
-                    }
+            // Skip to verification start
+            let mut pos = 0u64;
+            for _ in 0..verify_start {
+                let mut len_buf = [0u8; 4];
+                verify_file.read_exact(&mut len_buf)?;
+                let block_len = u32::from_le_bytes(len_buf) as usize;
+                if let Err(rejection) = sanity.check_len(block_len) {
+                    return Err(anyhow::anyhow!(
+                        "Final integrity check failed: block has invalid size ({})",
+                        rejection
+                    ));
                 }
+                pos += 4 + block_len as u64;
+                verify_file.seek(SeekFrom::Start(pos))?;
+            }
 
-                let cache_time = cache_start.elapsed();
-                println!(
-                    "   ✅ Copied {} blocks to cache in {:.1} minutes (skipped chaining for speed)",
-                    total_blocks_written,
-                    cache_time.as_secs_f64() / 60.0
-                );
+            // Verify last blocks
+            for i in 0..verify_count {
+                let mut len_buf = [0u8; 4];
+                verify_file.read_exact(&mut len_buf)?;
+                let block_len = u32::from_le_bytes(len_buf) as usize;
+
+                if let Err(rejection) = sanity.check_len(block_len) {
+                    return Err(anyhow::anyhow!(
+                        "Final integrity check failed: block {} has invalid size ({})",
+                        verify_start + i,
+                        rejection
+                    ));
+                }
 
-                // Finalize cache file - update block count at start
-                if let Some(ref mut writer) = cache_writer {
-                    writer.flush()?;
-                    drop(writer); // Close file so we can update it
-
-                    // Update block count at start of file
-                    if let Some(ref cache_path) = cache_file {
-                        let mut file = std::fs::OpenOptions::new().write(true).open(cache_path)?;
-                        use std::io::Seek;
-                        file.seek(std::io::SeekFrom::Start(0))?;
-                        file.write_all(&total_blocks_written.to_le_bytes())?;
-                        file.sync_all()?;
-
-                        let save_time = save_start.elapsed();
-                        let cache_size = std::fs::metadata(cache_path)?.len();
-                        let cache_size_gb = cache_size as f64 / 1_073_741_824.0;
-                        println!(
-                            "   ✅ Cached ordered block list to: {}",
-                            cache_path.display()
-                        );
-                        println!(
-                            "      Cache size: {:.2} GB | Write time: {:.1} seconds",
-                            cache_size_gb,
-                            save_time.as_secs_f64()
-                        );
+                let mut block_data = vec![0u8; block_len];
+                verify_file.read_exact(&mut block_data)?;
 
-                        // CRITICAL: NEVER DELETE THE TEMP FILE
-                        // This file represents days of work reading and processing blocks.
-                        // It can be used to resume if the process is interrupted.
-                        // The temp file is a valuable backup even after cache is saved.
-                        // Users can manually delete it if they want, but code should NEVER do it.
-                        // Note: Memory map is automatically dropped when it goes out of scope
-                        println!("   💾 Temp file preserved at: {} (contains {} blocks, {:.2} GB of work)", 
-                                 temp_file.display(),
-                                 read_count,
-                                 std::fs::metadata(&temp_file).map(|m| m.len() as f64 / 1_073_741_824.0).unwrap_or(0.0));
-                        println!("   ⚠️  DO NOT DELETE THIS FILE - It represents days of processing work");
-                    }
+                if let Err(rejection) = sanity.check(&block_data) {
+                    return Err(anyhow::anyhow!(
+                        "Final integrity check failed: block {} ({})",
+                        verify_start + i,
+                        rejection
+                    ));
                 }
-                // Memory map is automatically dropped when it goes out of scope
             }
 
-            // CRITICAL FIX: After creating chunks, check if they exist and use them
-            // BUT: Only stop collection if ALL blocks are collected (check metadata)
-            // Re-check for chunks (they may have been created during file reading)
-            let chunks_dir_after = crate::chunked_cache::get_chunks_dir();
-            if let Some(ref chunks_path) = chunks_dir_after {
-                if chunks_path.exists() {
-                    // Check if collection is actually complete by checking metadata
-                    if let Ok(Some(metadata)) =
-                        crate::chunked_cache::load_chunk_metadata(chunks_path)
-                    {
-                        // Check if we have all blocks (rough estimate: 900k+ blocks for full chain)
-                        let expected_blocks = 900000u64; // Approximate full chain size
-                        if metadata.total_blocks >= expected_blocks {
-                            // Collection is complete - use chunks
-                            match crate::chunked_cache::ChunkedBlockIterator::new(
-                                chunks_path,
-                                start_height,
-                                max_blocks,
-                            ) {
-                                Ok(Some(iter)) => {
-                                    println!("   ✅ All chunks complete ({} blocks) - using chunked iterator", metadata.total_blocks);
-                                    chunked_iterator = Some(iter);
-                                    ordered_blocks = None; // Will use chunked_iterator instead
-                                }
-                                _ => {
-                                    println!(
-                                        "   ✅ All chunks complete ({} blocks) - collection done",
-                                        metadata.total_blocks
-                                    );
-                                    ordered_blocks = Some(Vec::new()); // Empty - collection is done
+            info!(
+                "   ✅ Final integrity check passed: verified last {} blocks",
+                verify_count
+            );
+        }
+
+        progress.finish(format!(
+            "finished reading {} blocks from {} files",
+            read_count, processed_files
+        ));
+
+        let total_time = start_time.elapsed();
+        info!(
+            "   ✅ Read {} total blocks from file in {:.1} minutes",
+            read_count,
+            total_time.as_secs_f64() / 60.0
+        );
+
+        // CRITICAL FIX: After batch reading finishes, if temp file was truncated (chunk created),
+        // we need to continue reading files. But instead of restarting from file 0, we should
+        // continue from where we left off. However, since all files were already processed,
+        // we should just continue with the iterator which will read from files sequentially.
+        // The iterator will start from file 0, but that's OK - it will collect any remaining blocks.
+        // Store the last processed file index so iterator can potentially skip already-processed files
+        let last_processed_file_idx = processed_files;
+
+        // Now read blocks back from temp file and build hash map
+        // CRITICAL FIX: Check if temp file exists before trying to read it
+        // In collection-only mode with incremental chunking, the temp file may be truncated after chunking
+        // BUT: This doesn't mean collection is complete - we need to continue reading files
+        // Only stop if we've actually read all files, not just because temp file was truncated
+        if !temp_file.exists() {
+            info!("   ℹ️  Temp file no longer exists (truncated after chunking) - will continue reading from files");
+            info!(
+                "   📍 Last processed file: {} (will continue from there in iterator)",
+                last_processed_file_idx
+            );
+            // DON'T stop - continue reading from files to collect more blocks
+            // The temp file will be recreated as we read more blocks
+        }
+
+        // CRITICAL FIX: If temp file doesn't exist or is empty (truncated after chunking),
+        // continue reading from files instead of stopping. Collection is NOT complete just
+        // because temp file was truncated - we need to read ALL files first.
+        if !temp_file.exists() {
+            info!("   ℹ️  Temp file doesn't exist (was truncated after chunking) - continuing file reading");
+        } else {
+            info!("   📖 Reading blocks from temp file to build hash map...");
+            // OPTIMIZATION: Use larger buffer for temp file reading (faster sequential reads)
+            match std::fs::File::open(&temp_file) {
+                Ok(f) => {
+                    let mut temp_reader = std::io::BufReader::with_capacity(io_buffer_size, f);
+                    use std::io::Read;
+
+                    // FIX OOM: Process blocks in chunks instead of loading all into memory
+                    // Build hash map incrementally, storing file offsets instead of block data
+                    // This avoids loading 400+ GB into RAM
+                    info!("   📖 Processing blocks in chunks to build hash map (avoiding OOM)...");
+                    let estimated_blocks = read_count;
+
+                    // Hash map: prev_hash -> (file_offset, block_len)
+                    // We'll re-read blocks from temp file when chaining (slower but no OOM)
+                    // OPTIMIZATION: Pre-allocate hash map with estimated capacity to reduce rehashing
+                    let mut blocks_by_prev_hash: HashMap<Vec<u8>, (u64, usize)> =
+                        HashMap::with_capacity(estimated_blocks.min(1_000_000));
+                    let mut genesis_block: Option<(u64, usize)> = None;
+
+                    // OPTIMIZATION: Pre-allocate chunk vector with exact capacity
+                    let mut chunk = Vec::with_capacity(hash_map_chunk_size);
+                    let mut blocks_processed = 0;
+                    let mut current_offset: u64 = 0;
+
+                    loop {
+                        // Read block length
+                        let block_start_offset = current_offset; // Points to start of [len: u32]
+                        let mut len_buf = [0u8; 4];
+                        match temp_reader.read_exact(&mut len_buf) {
+                            Ok(_) => {}
+                            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                                // Process remaining blocks in chunk
+                                if !chunk.is_empty() {
+                                    Self::process_chunk(
+                                        &chunk,
+                                        &mut blocks_by_prev_hash,
+                                        &mut genesis_block,
+                                    )?;
+                                    chunk.clear();
                                 }
+                                break;
+                            }
+                            Err(e) => return Err(e.into()),
+                        }
+                        current_offset += 4;
+                        let block_len = u32::from_le_bytes(len_buf) as usize;
+
+                        // CRITICAL OOM FIX: Read only block header (80 bytes) instead of full block
+                        // We only need prev_hash (bytes 4-36) to build the hash map
+                        // This reduces memory usage by ~18,750x (80 bytes vs 1.5MB average)
+                        let mut header = vec![0u8; 80.min(block_len)]; // Read up to 80 bytes (or block_len if smaller)
+                        temp_reader.read_exact(&mut header)?;
+
+                        // Skip the rest of the block (we don't need it for hash map building)
+                        if block_len > 80 {
+                            temp_reader
+                                .seek(std::io::SeekFrom::Current((block_len - 80) as i64))?;
+                        }
+                        current_offset += block_len as u64;
+
+                        // Store only header (80 bytes) instead of full block (1.5MB average)
+                        chunk.push((header, block_start_offset, block_len));
+                        blocks_processed += 1;
+
+                        // Process chunk when full
+                        if chunk.len() >= hash_map_chunk_size {
+                            Self::process_chunk(
+                                &chunk,
+                                &mut blocks_by_prev_hash,
+                                &mut genesis_block,
+                            )?;
+                            chunk.clear();
+
+                            if blocks_processed % progress_report_interval == 0 {
+                                info!(
+                                    "   📖 Processed {}/{} blocks...",
+                                    blocks_processed, read_count
+                                );
                             }
-                        } else {
-                            // Chunks exist but incomplete - continue collection
-                            println!("   ⚠️  Partial chunks exist ({} blocks, need ~{}k) - continuing collection...", 
-                                     metadata.total_blocks, expected_blocks / 1000);
-                            ordered_blocks = None; // Continue reading from files
                         }
-                    } else {
-                        // No metadata or can't read - assume incomplete, continue collection
-                        println!("   ⚠️  Chunks exist but no metadata - continuing collection to ensure completeness...");
-                        ordered_blocks = None; // Continue reading from files
                     }
-                } else {
-                    // No chunks - need to read from files and order them
-                    ordered_blocks = None; // None = continue reading from files, not from cache
+
+                    info!(
+                        "   ✅ Built hash map with {} entries",
+                        blocks_by_prev_hash.len()
+                    );
+
+                    if genesis_block.is_none() {
+                        warn!(
+                            "⚠️  Warning: Genesis block not found in {} blocks read",
+                            read_count
+                        );
+                    }
+
+                    info!(
+                        "   Found {} blocks with previous hashes (excluding genesis)",
+                        blocks_by_prev_hash.len()
+                    );
+                }
+                Err(e) => {
+                    // Temp file can't be opened (maybe truncated after chunking) - continue reading from files
+                    warn!("   ⚠️  Warning: Could not open temp file for hash map building: {} - continuing file reading", e);
                 }
-            } else {
-                // No chunks dir - continue reading from files
-                ordered_blocks = None;
             }
         }
 
-        // Handle ordered_blocks (if loaded from cache)
-        // CRITICAL: If we have a chunked_iterator, use it instead of ordered_blocks
-        let filtered_blocks: Option<Vec<Vec<u8>>> = if chunked_iterator.is_some() {
-            None // Use chunked_iterator instead - chunks are already ordered, no need to read files
-        } else if let Some(ref mut blocks) = ordered_blocks {
-            // Apply start_height and max_blocks filters
-            let start_idx = start_height.unwrap_or(0) as usize;
-            let end_idx = if let Some(max) = max_blocks {
-                (start_idx + max).min(blocks.len())
-            } else {
-                blocks.len()
-            };
+        // Define cache file path (for old format, if needed)
+        let cache_file = ordered_blocks_cache_path_for_write();
 
-            if end_idx > start_idx {
-                Some(blocks.drain(start_idx..end_idx).collect())
-            } else {
-                Some(Vec::new())
-            }
+        // Check if chunked cache already exists - if so, skip building old format
+        // CRITICAL: Also skip if temp file doesn't exist (was truncated after chunking)
+        let chunks_dir = crate::chunked_cache::get_chunks_dir();
+        let should_build_old_cache = if !temp_file.exists() {
+            // Temp file doesn't exist (truncated after chunking) - skip cache build, continue reading files
+            false
+        } else if let Some(ref chunks_path) = chunks_dir {
+            !chunks_path.exists() || !crate::chunked_cache::has_chunk_metadata(chunks_path)
         } else {
-            None // Continue reading from files
+            true
         };
 
-        // Check if we need temp writer (before moving filtered_blocks and chunked_iterator)
-        let needs_temp_writer = filtered_blocks.is_none() && chunked_iterator.is_none();
+        if !should_build_old_cache {
+            if !temp_file.exists() {
+                info!("   ℹ️  Temp file doesn't exist (truncated after chunking) - skipping cache build, continuing file reading");
+            } else {
+                info!("   ✅ Chunked cache already exists - skipping old format cache build");
+                info!("   💡 Use chunked cache for better space efficiency");
+            }
+        } else {
+            // OPTIMIZATION: Skip chaining during cache build - just copy blocks sequentially
+            // Chaining is expensive (memory mapping, header reading, chain following, sorting)
+            // For cache building, we don't need chain order - blocks can be stored as-is
+            // Chaining can be done later when reading from cache if needed
+            // NOTE: With chunked cache, we typically don't build the old single-file cache
+            // This code path is kept for backward compatibility
+            info!("   💾 Building cache (skipping chaining for speed - blocks stored as-is)...");
+            warn!("   ⚠️  Note: Consider using chunked cache format for better space efficiency");
+            let cache_start = std::time::Instant::now();
+
+            // Open cache file for streaming writes
+            let mut cache_writer: Option<std::io::BufWriter<std::fs::File>> = None;
+            let mut total_blocks_written = 0u64;
+            let save_start = std::time::Instant::now();
 
-        // Get temp file path if needed
-        let (temp_writer, temp_file_path) = if needs_temp_writer {
-            use std::io::Write;
-            let cache_file = ordered_blocks_cache_path_for_write();
-            let temp_file = if let Some(ref cache_path) = cache_file {
+            if let Some(ref cache_path) = cache_file {
                 if let Some(parent) = cache_path.parent() {
                     let _ = std::fs::create_dir_all(parent);
-                    parent.join("blvm-bench-blocks-temp.bin")
-                } else {
-                    std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
                 }
-            } else {
-                std::env::temp_dir().join("blvm-bench-blocks-temp.bin")
-            };
+                // Reserve space for block count (u64) at start, will update at end
+                let cache_file_handle = std::fs::File::create(cache_path)?;
+                let mut writer =
+                    std::io::BufWriter::with_capacity(io_buffer_size, cache_file_handle);
+                // Write placeholder for block count (will update at end)
+                writer.write_all(&0u64.to_le_bytes())?;
+                cache_writer = Some(writer);
+            }
 
-            let writer = if temp_file.exists() {
-                // Append to existing temp file
-                match std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(&temp_file)
-                {
-                    Ok(file) => {
-                        println!(
-                            "   📝 Appending to existing temp file: {}",
-                            temp_file.display()
-                        );
-                        Some(std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, file))
-                    }
-                    Err(e) => {
-                        eprintln!("   ⚠️  Warning: Could not open temp file for appending: {} - creating new", e);
-                        match std::fs::File::create(&temp_file) {
-                            Ok(file) => {
-                                Some(std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, file))
-                            }
-                            Err(e2) => {
-                                eprintln!("   ⚠️  Error: Could not create temp file: {} - blocks will not be saved!", e2);
-                                None
-                            }
-                        }
-                    }
+            // OPTIMIZATION: Use memory-mapped file for fast sequential reading
+            // Read blocks directly from temp file in order and write to cache
+            // This is MUCH faster than chaining - just a simple sequential copy
+            info!("   🗺️  Memory-mapping temp file for fast sequential copy...");
+            use memmap2::MmapOptions;
+            let file = std::fs::File::open(&temp_file)?;
+            let mmap = unsafe { MmapOptions::new().map(&file)? };
+            info!(
+                "   ✅ Memory-mapped {} GB file",
+                mmap.len() as f64 / 1_073_741_824.0
+            );
+
+            // OPTIMIZATION: Sequential copy is already optimal for NVMe SSDs
+            // Memory-mapped reads are instant (no I/O wait), sequential writes are fastest
+            // Parallelizing would add overhead without benefit (can't parallelize single-file writes)
+            // The 128MB buffer ensures maximum throughput for sequential I/O
+            info!(
+                "   📖 Copying blocks from temp file to cache (sequential, optimized for NVMe)..."
+            );
+            let mut pos = 0usize;
+            let mut blocks_copied = 0;
+
+            // Pre-allocate block length buffer to avoid repeated allocations
+            let mut len_buf = [0u8; 4];
+
+            while pos + 4 <= mmap.len() {
+                // Read block length (4 bytes) - use pre-allocated buffer
+                len_buf.copy_from_slice(&mmap[pos..pos + 4]);
+                let block_len = u32::from_le_bytes(len_buf) as usize;
+                pos += 4;
+
+                // Read block data
+                if pos + block_len > mmap.len() {
+                    warn!(
+                        "   ⚠️  Warning: Block at offset {} extends beyond file end, stopping",
+                        pos - 4
+                    );
+                    break;
                 }
-            } else {
-                // Create new temp file
-                match std::fs::File::create(&temp_file) {
-                    Ok(file) => {
-                        println!(
-                            "   📝 Creating new temp file for sequential reading: {}",
-                            temp_file.display()
-                        );
-                        Some(std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, file))
-                    }
-                    Err(e) => {
-                        eprintln!("   ⚠️  Error: Could not create temp file: {} - blocks will not be saved!", e);
-                        None
+
+                let block_data = &mmap[pos..pos + block_len];
+
+                // Write block to cache immediately (streaming, no accumulation)
+                // Large 128MB buffer ensures minimal system calls
+                if let Some(ref mut writer) = cache_writer {
+                    writer.write_all(&len_buf)?;
+                    writer.write_all(block_data)?;
+                    total_blocks_written += 1;
+                }
+
+                pos += block_len;
+                blocks_copied += 1;
+
+                if blocks_copied % progress_report_interval == 0 {
+                    let elapsed = cache_start.elapsed().as_secs();
+                    let rate = if elapsed > 0 {
+                        blocks_copied as f64 / elapsed as f64
+                    } else {
+                        0.0
+                    };
+                    let progress_pct = if read_count > 0 {
+                        (blocks_copied as f64 / read_count as f64 * 100.0).min(100.0)
+                    } else {
+                        0.0
+                    };
+                    info!(
+                        "   📊 Copied {}/{} blocks ({:.1}%) | Rate: {:.0} blocks/sec",
+                        blocks_copied, read_count, progress_pct, rate
+                    );
+                    // Flush periodically to ensure progress is saved
+                    if let Some(ref mut writer) = cache_writer {
+                        let _ = writer.flush();
                     }
                 }
-            };
-            (writer, Some(temp_file))
-        } else {
-            (None, None)
-        };
+            }
 
-        Ok(Self {
-            reader: BlockFileReader {
-                data_dir: reader.data_dir.clone(),
-                network: reader.network,
-                block_files: reader.block_files.clone(),
-                local_cache_dir: reader.local_cache_dir.clone(),
-                file_index: reader.file_index.clone(),
-            },
-            // CRITICAL FIX: If ordered_blocks is None (continuing file reading after batch processing),
-            // start from the last processed file index instead of file 0 to avoid re-reading all files.
-            // However, if we've already processed all files (processed_files >= block_files.len()),
-            // we should start from 0 because we need to re-read to collect any remaining blocks.
-            // For now, always start from 0 when ordered_blocks is None - the iterator will handle
-            // reading files and collecting blocks. This is inefficient but ensures we don't miss blocks.
-            current_file_idx: 0,
-            current_file: None,
-            current_height: start_height.unwrap_or(0),
-            start_height,
-            max_blocks,
-            blocks_read: 0,
-            ordered_blocks: filtered_blocks,
-            ordered_index: 0,
-            chunked_iterator, // Use the streaming iterator we created
-            search_buffer: vec![0u8; SEARCH_BUFFER_SIZE],
-            copy_sender: None, // Not needed for ordered iterator
-            last_copy_start_idx: 0,
-            failed_files: std::collections::HashSet::new(), // Track files that failed to avoid retries
-            current_reading_file_idx: None,                 // Track which file we're reading from
-            temp_writer,
-            temp_file_path,
-            blocks_written_to_temp: 0,
+            let cache_time = cache_start.elapsed();
+            info!(
+                "   ✅ Copied {} blocks to cache in {:.1} minutes (skipped chaining for speed)",
+                total_blocks_written,
+                cache_time.as_secs_f64() / 60.0
+            );
+
+            // Finalize cache file - update block count at start
+            if let Some(ref mut writer) = cache_writer {
+                writer.flush()?;
+                drop(writer); // Close file so we can update it
+
+                // Update block count at start of file
+                if let Some(ref cache_path) = cache_file {
+                    let mut file = std::fs::OpenOptions::new().write(true).open(cache_path)?;
+                    use std::io::Seek;
+                    file.seek(std::io::SeekFrom::Start(0))?;
+                    file.write_all(&total_blocks_written.to_le_bytes())?;
+                    file.sync_all()?;
+
+                    let save_time = save_start.elapsed();
+                    let cache_size = std::fs::metadata(cache_path)?.len();
+                    let cache_size_gb = cache_size as f64 / 1_073_741_824.0;
+                    info!(
+                        "   ✅ Cached ordered block list to: {}",
+                        cache_path.display()
+                    );
+                    info!(
+                        "      Cache size: {:.2} GB | Write time: {:.1} seconds",
+                        cache_size_gb,
+                        save_time.as_secs_f64()
+                    );
+
+                    // CRITICAL: NEVER DELETE THE TEMP FILE
+                    // This file represents days of work reading and processing blocks.
+                    // It can be used to resume if the process is interrupted.
+                    // The temp file is a valuable backup even after cache is saved.
+                    // Users can manually delete it if they want, but code should NEVER do it.
+                    // Note: Memory map is automatically dropped when it goes out of scope
+                    info!(
+                        "   💾 Temp file preserved at: {} (contains {} blocks, {:.2} GB of work)",
+                        temp_file.display(),
+                        read_count,
+                        std::fs::metadata(&temp_file)
+                            .map(|m| m.len() as f64 / 1_073_741_824.0)
+                            .unwrap_or(0.0)
+                    );
+                    warn!("   ⚠️  DO NOT DELETE THIS FILE - It represents days of processing work");
+                }
+            }
+            // Memory map is automatically dropped when it goes out of scope
+        }
+
+        if let Err(e) = crate::shutdown::ResumeMarker::clear(&temp_file) {
+            warn!("   ⚠️  Failed to remove resume marker: {}", e);
+        }
+        let (chunks_dir, chunked_blocks) = chunk_collection_status();
+        Ok(CollectionReport {
+            blocks_collected: read_count as u64,
+            files_processed: processed_files,
+            temp_file,
+            chunks_dir,
+            chunked_blocks,
+            full_chain_blocks: reader.tuning.chunks.full_chain_blocks,
+            duration_secs: collection_start.elapsed().as_secs_f64(),
         })
     }
 
-    /// Read next block from current file
+    /// Read next block from current file, past any frames the sanity filter rejects
     fn read_next_from_file(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            match self.read_frame()? {
+                FrameRead::Block(block) => return Ok(Some(block)),
+                FrameRead::Rejected => continue,
+                FrameRead::End => return Ok(None),
+            }
+        }
+    }
+
+    /// Read the next frame from the current file, recording anything passed over in `skips`
+    fn read_frame(&mut self) -> Result<FrameRead> {
         let file = match &mut self.current_file {
             Some(f) => f,
-            None => return Ok(None),
+            None => return Ok(FrameRead::End),
         };
 
         let magic = self.reader.network.magic_bytes();
         let mut magic_buf = [0u8; 4];
 
         // Try to read magic bytes
-        // XOR-packaged format uses ALTERNATING keys:
-        // - KEY1: 0x8422e9ad (for bytes 0-3, 8-11, 16-19, ...)
-        // - KEY2: 0xb78fff14 (for bytes 4-7, 12-15, 20-23, ...)
-        // Keys alternate every 4 bytes starting from file offset 0
-        const XOR_KEY1: [u8; 4] = [0x84, 0x22, 0xe9, 0xad]; // First key
-        const XOR_KEY2: [u8; 4] = [0xb7, 0x8f, 0xff, 0x14]; // Second key (alternating)
-        const ENCRYPTED_MAGIC: [u8; 4] = [0x7d, 0x9c, 0x5d, 0x74];
-        let mut is_xor_encrypted = false;
+        // Obfuscated files XOR the byte at file offset `o` with `key[o % 8]` (see `XorKey`)
+        let xor_key = self.reader.xor_key;
+        let is_xor_encrypted = xor_key.is_some();
         let mut encrypted_magic_bytes = [0u8; 4]; // Save original encrypted magic for reconstruction
 
         // CRITICAL FIX: Track file position BEFORE reading magic
@@ -3139,29 +3359,16 @@ impl BlockIterator {
 
         loop {
             if retry_count > MAX_RETRIES {
-                return Ok(None);
+                return Ok(FrameRead::End);
             }
 
             match file.read_exact(&mut magic_buf) {
                 Ok(_) => {
-                    // Check if file is XOR encrypted (packaged blk*.dat)
-                    // Encrypted magic is 0x7d9c5d74
-                    is_xor_encrypted = magic_buf == ENCRYPTED_MAGIC;
-
-                    if is_xor_encrypted {
+                    if let Some(key) = xor_key {
                         // Save original encrypted magic before decrypting
                         encrypted_magic_bytes = magic_buf;
-                        // CRITICAL FIX: Decrypt magic using correct key based on FILE OFFSET
-                        // Magic is at file offset magic_start_pos, all 4 bytes in same chunk - use u32 XOR
-                        let use_key1 = (magic_start_pos / 4) % 2 == 0;
-                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-
-                        // Decrypt entire 4-byte magic at once using u32 XOR
-                        let magic_u32 = u32::from_le_bytes(magic_buf);
-                        let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                        magic_buf = decrypted_magic_u32.to_le_bytes();
+                        // Decrypt magic with the key bytes for its FILE OFFSET
+                        magic_buf = key.word(magic_buf, magic_start_pos);
                     }
 
                     if magic_buf == *magic {
@@ -3171,13 +3378,13 @@ impl BlockIterator {
                         let verify_pos = file.stream_position()?;
                         let expected_pos = magic_start_pos + 4;
                         if verify_pos != expected_pos {
-                            eprintln!("⚠️  WARNING: After reading magic, position is {} but expected {} - seeking to correct", verify_pos, expected_pos);
+                            warn!("⚠️  WARNING: After reading magic, position is {} but expected {} - seeking to correct", verify_pos, expected_pos);
                             file.seek(std::io::SeekFrom::Start(expected_pos))?;
                             // Verify seek worked
                             let verify_pos2 = file.stream_position()?;
                             if verify_pos2 != expected_pos {
-                                eprintln!("⚠️  CRITICAL: Cannot seek to position {} (got {}) - aborting block read", expected_pos, verify_pos2);
-                                return Ok(None);
+                                warn!("⚠️  CRITICAL: Cannot seek to position {} (got {}) - aborting block read", expected_pos, verify_pos2);
+                                return Ok(FrameRead::End);
                             }
                         }
                         break;
@@ -3185,15 +3392,15 @@ impl BlockIterator {
 
                     // Not a block start - we're not at a block boundary
                     // CRITICAL FIX: If we're in an encrypted file, try to find the next block boundary
-                    if is_xor_encrypted {
+                    if let Some(key) = xor_key {
                         // Seek back to where we started reading magic
                         file.seek(std::io::SeekFrom::Start(magic_start_pos))?;
 
                         // Try to find the next block boundary using pattern search
+                        let skipped_from = magic_start_pos;
                         let mut search_pos = magic_start_pos;
                         let mut found = false;
-                        let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                        let key2_u32 = u32::from_le_bytes(XOR_KEY2);
+                        let mut padding_only = true;
 
                         // Search for next block (read in chunks)
                         loop {
@@ -3203,51 +3410,21 @@ impl BlockIterator {
                                 Err(_) => break,
                             };
 
-                            // Search for encrypted magic pattern
-                            let first_byte = ENCRYPTED_MAGIC[0];
-                            for i in memchr_iter(first_byte, &self.search_buffer[..bytes_read]) {
-                                if i + 7 >= bytes_read {
-                                    continue;
-                                }
-
-                                if self.search_buffer[i + 1] == ENCRYPTED_MAGIC[1]
-                                    && self.search_buffer[i + 2] == ENCRYPTED_MAGIC[2]
-                                    && self.search_buffer[i + 3] == ENCRYPTED_MAGIC[3]
-                                {
-                                    let file_offset = search_pos + i as u64;
-
-                                    // Verify magic decrypts correctly
-                                    let mut test_magic = [0u8; 4];
-                                    test_magic.copy_from_slice(&self.search_buffer[i..i + 4]);
-                                    let magic_u32 = u32::from_le_bytes(test_magic);
-                                    let use_key1 = (file_offset / 4) % 2 == 0;
-                                    let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                                    let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                                    test_magic = decrypted_magic_u32.to_le_bytes();
-
-                                    if test_magic == *magic {
-                                        // Also verify size field is reasonable
-                                        let mut test_size = [0u8; 4];
-                                        test_size
-                                            .copy_from_slice(&self.search_buffer[i + 4..i + 8]);
-                                        let size_offset = file_offset + 4;
-                                        let use_key1_size = (size_offset / 4) % 2 == 0;
-                                        let key_u32_size =
-                                            if use_key1_size { key1_u32 } else { key2_u32 };
-                                        let size_u32 = u32::from_le_bytes(test_size);
-                                        let decrypted_size_u32 = size_u32 ^ key_u32_size;
-                                        let size_hint_test = decrypted_size_u32 as usize;
-
-                                        if size_hint_test >= 80 && size_hint_test <= 4 * 1024 * 1024
-                                        {
-                                            // Found valid block boundary - seek to it and retry
-                                            file.seek(std::io::SeekFrom::Start(file_offset))?;
-                                            magic_start_pos = file_offset;
-                                            found = true;
-                                            break;
-                                        }
-                                    }
-                                }
+                            // Search for encrypted magic followed by a plausible size field
+                            let found_at = key.find_frame(
+                                &self.search_buffer[..bytes_read],
+                                search_pos,
+                                magic,
+                                RESYNC_SIZE_RANGE,
+                            );
+                            let scanned = &self.search_buffer[..found_at.unwrap_or(bytes_read)];
+                            padding_only &= is_padding(scanned, search_pos, Some(key));
+                            if let Some(i) = found_at {
+                                // Found valid block boundary - seek to it and retry
+                                let file_offset = search_pos + i as u64;
+                                file.seek(std::io::SeekFrom::Start(file_offset))?;
+                                magic_start_pos = file_offset;
+                                found = true;
                             }
 
                             if found {
@@ -3260,6 +3437,14 @@ impl BlockIterator {
                             }
                         }
 
+                        if !padding_only {
+                            self.skips.push(SkipEvent {
+                                file: self.current_file_idx,
+                                offset: skipped_from,
+                                reason: SkipReason::Garbage,
+                            });
+                        }
+
                         if found {
                             // Retry reading from the correct position
                             retry_count += 1;
@@ -3267,12 +3452,26 @@ impl BlockIterator {
                         }
                     }
 
-                    // Not a block start, might be end of file or corrupted
-                    return Ok(None);
+                    // Not a block start: the zero tail after the last frame, or corrupted
+                    if xor_key.is_none() && magic_buf != [0; 4] {
+                        self.skips.push(SkipEvent {
+                            file: self.current_file_idx,
+                            offset: magic_start_pos,
+                            reason: SkipReason::Garbage,
+                        });
+                    }
+                    return Ok(FrameRead::End);
                 }
                 Err(_) => {
-                    // End of file
-                    return Ok(None);
+                    // End of file, unless a frame was cut short inside its magic
+                    if cut_short(file, magic_start_pos, xor_key) {
+                        self.skips.push(SkipEvent {
+                            file: self.current_file_idx,
+                            offset: magic_start_pos,
+                            reason: SkipReason::Truncated,
+                        });
+                    }
+                    return Ok(FrameRead::End);
                 }
             }
         }
@@ -3283,7 +3482,7 @@ impl BlockIterator {
             let current_pos_after_magic = file.stream_position()?;
             let expected_pos = magic_start_pos + 4;
             if current_pos_after_magic != expected_pos {
-                eprintln!("⚠️  File position mismatch before reading size: expected {}, got {} - seeking to correct position", expected_pos, current_pos_after_magic);
+                warn!("⚠️  File position mismatch before reading size: expected {}, got {} - seeking to correct position", expected_pos, current_pos_after_magic);
                 file.seek(std::io::SeekFrom::Start(expected_pos))?;
             }
         }
@@ -3308,13 +3507,13 @@ impl BlockIterator {
             // Get current position
             let current_pos = file.stream_position()?;
             if current_pos != expected_size_pos {
-                // eprintln!("🔍 DEBUG: Seeking to size field position: current={}, expected={}", current_pos, expected_size_pos);
+                // debug!("🔍 DEBUG: Seeking to size field position: current={}, expected={}", current_pos, expected_size_pos);
                 file.seek(std::io::SeekFrom::Start(expected_size_pos))?;
                 // Verify position is correct
                 let verify_pos = file.stream_position()?;
                 if verify_pos != expected_size_pos {
-                    eprintln!("⚠️  CRITICAL ERROR: Cannot seek to size field position {} (got {}) - file may be corrupted", expected_size_pos, verify_pos);
-                    return Ok(None);
+                    error!("⚠️  CRITICAL ERROR: Cannot seek to size field position {} (got {}) - file may be corrupted", expected_size_pos, verify_pos);
+                    return Ok(FrameRead::End);
                 }
             }
         }
@@ -3322,8 +3521,13 @@ impl BlockIterator {
         match file.read_exact(&mut size_buf) {
             Ok(_) => {}
             Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                // End of file
-                return Ok(None);
+                // A magic without its size field: cut short
+                self.skips.push(SkipEvent {
+                    file: self.current_file_idx,
+                    offset: magic_start_pos,
+                    reason: SkipReason::Truncated,
+                });
+                return Ok(FrameRead::End);
             }
             Err(e) => {
                 // Other read error - might be permission or corruption
@@ -3333,34 +3537,20 @@ impl BlockIterator {
 
         // For XOR-packaged files, decrypt the size field and use it as a HINT
         // Then verify the next block's magic is at the expected position
-        let block_data = if is_xor_encrypted {
-            // CRITICAL FIX: Decrypt size field using correct key based on FILE OFFSET
+        let block_data = if let Some(key) = xor_key {
             // Size field is at file offset magic_start_pos + 4
-            // All 4 bytes of size field are in the same 4-byte chunk, so use u32 XOR
             let size_offset = magic_start_pos + 4;
-            let use_key1 = (size_offset / 4) % 2 == 0;
-            let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-            let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-            let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-
-            // Decrypt entire 4-byte size field at once using u32 XOR
-            let size_u32 = u32::from_le_bytes(size_buf);
-            let decrypted_size_u32 = size_u32 ^ key_u32;
-            let size_hint = decrypted_size_u32 as usize;
+            let size_hint = u32::from_le_bytes(key.word(size_buf, size_offset)) as usize;
 
             // DEBUG: Always log size field decryption for debugging (disabled to reduce log spam)
-            // eprintln!("🔍 DEBUG: Size field at offset {}: encrypted={:02x}{:02x}{:02x}{:02x} (0x{:08x}), key={}, decrypted={} (0x{:08x})",
-            //          size_offset,
-            //          size_buf[0], size_buf[1], size_buf[2], size_buf[3],
-            //          size_u32,
-            //          if use_key1 { "KEY1" } else { "KEY2" },
-            //          size_hint, decrypted_size_u32);
+            // debug!("🔍 DEBUG: Size field at offset {}: encrypted={:02x?}, decrypted={}",
+            //          size_offset, size_buf, size_hint);
 
             // Also verify the actual file position matches what we expect
             let actual_pos = file.stream_position()?;
             let expected_pos_after_size = magic_start_pos + 8;
             if actual_pos != expected_pos_after_size {
-                eprintln!(
+                warn!(
                     "⚠️  WARNING: After reading size, position is {} but expected {}",
                     actual_pos, expected_pos_after_size
                 );
@@ -3370,12 +3560,8 @@ impl BlockIterator {
             // DEBUG: Log ALL size field decryptions, not just invalid ones (disabled to reduce log spam)
             if size_hint > 4 * 1024 * 1024 {
                 // Only log invalid sizes, not every decryption
-                // eprintln!("🔍 DEBUG: Size field at offset {}: encrypted={:02x}{:02x}{:02x}{:02x} (0x{:08x}), key={}, decrypted={} (0x{:08x}) - INVALID",
-                //          size_offset,
-                //          size_buf[0], size_buf[1], size_buf[2], size_buf[3],
-                //          size_u32,
-                //          if use_key1 { "KEY1" } else { "KEY2" },
-                //          size_hint, decrypted_size_u32);
+                // debug!("🔍 DEBUG: Size field at offset {}: encrypted={:02x?}, decrypted={} - INVALID",
+                //          size_offset, size_buf, size_hint);
             }
 
             if size_hint >= 80 && size_hint <= 4 * 1024 * 1024 {
@@ -3386,14 +3572,19 @@ impl BlockIterator {
                 let required_size = current_pos + size_hint as u64;
                 if required_size > file_size {
                     // File doesn't have enough data - mark as failed and skip
+                    self.skips.push(SkipEvent {
+                        file: self.current_file_idx,
+                        offset: magic_start_pos,
+                        reason: SkipReason::Truncated,
+                    });
                     if let Some(file_idx) = self.current_reading_file_idx {
-                        eprintln!("⚠️  Error reading block: file too small (need {} bytes, have {} bytes) - marking file {} as failed", 
+                        error!("⚠️  Error reading block: file too small (need {} bytes, have {} bytes) - marking file {} as failed", 
                                  required_size, file_size, file_idx);
                         self.failed_files.insert(file_idx);
                     }
                     self.current_file = None; // Close the file
                     self.current_reading_file_idx = None;
-                    return Ok(None); // Skip this file
+                    return Ok(FrameRead::End); // Skip this file
                 }
 
                 // Use size field - it's the correct logical block size
@@ -3402,18 +3593,28 @@ impl BlockIterator {
                     Ok(_) => {}
                     Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                         // File ended unexpectedly - mark as failed and skip
+                        self.skips.push(SkipEvent {
+                            file: self.current_file_idx,
+                            offset: magic_start_pos,
+                            reason: SkipReason::Truncated,
+                        });
                         if let Some(file_idx) = self.current_reading_file_idx {
-                            eprintln!("⚠️  Error reading block: failed to fill whole buffer - marking file {} as failed", file_idx);
+                            error!("⚠️  Error reading block: failed to fill whole buffer - marking file {} as failed", file_idx);
                             self.failed_files.insert(file_idx);
                         }
                         self.current_file = None; // Close the file
                         self.current_reading_file_idx = None;
-                        return Ok(None); // Skip this file
+                        return Ok(FrameRead::End); // Skip this file
                     }
                     Err(e) => {
                         // Other error - mark as failed and skip
+                        self.skips.push(SkipEvent {
+                            file: self.current_file_idx,
+                            offset: magic_start_pos,
+                            reason: SkipReason::ReadError,
+                        });
                         if let Some(file_idx) = self.current_reading_file_idx {
-                            eprintln!(
+                            error!(
                                 "⚠️  Error reading block: {} - marking file {} as failed",
                                 e, file_idx
                             );
@@ -3434,30 +3635,12 @@ impl BlockIterator {
 
                 match file.read_exact(&mut test_magic_buf) {
                     Ok(_) => {
-                        if test_magic_buf == ENCRYPTED_MAGIC {
-                            // Quick verify: decrypt and check
-                            let mut verify_magic = test_magic_buf;
-                            for j in 0..4 {
-                                let byte_offset = current_pos + j as u64;
-                                let key = if (byte_offset / 4) % 2 == 0 {
-                                    &XOR_KEY1
-                                } else {
-                                    &XOR_KEY2
-                                };
-                                verify_magic[j] ^= key[(byte_offset % 4) as usize];
-                            }
-                            if verify_magic == *magic {
-                                // Found it immediately - no search needed!
-                                file.seek(std::io::SeekFrom::Start(current_pos))?;
-                                need_search = false;
-                            } else {
-                                // Not a valid block - need to search
-                                file.seek(std::io::SeekFrom::Start(current_pos))?;
-                            }
-                        } else {
-                            // Not at expected position - need to search
-                            file.seek(std::io::SeekFrom::Start(current_pos))?;
+                        // Quick verify: decrypt and check
+                        if key.word(test_magic_buf, current_pos) == *magic {
+                            // Found it immediately - no search needed!
+                            need_search = false;
                         }
+                        file.seek(std::io::SeekFrom::Start(current_pos))?;
                     }
                     Err(_) => {
                         // EOF or error - seek back
@@ -3471,21 +3654,27 @@ impl BlockIterator {
                 let mut search_pos = current_pos;
                 let search_start = current_pos;
                 let mut found_next = false;
+                let mut padding_only = true;
                 const MAX_SEARCH_DISTANCE: u64 = 10 * 1024 * 1024; // Max 10MB search per block
 
                 if need_search {
                     loop {
                         // CRITICAL FIX: Limit search distance to prevent infinite loops
                         if search_pos - search_start > MAX_SEARCH_DISTANCE {
-                            eprintln!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next file", 
+                            warn!("⚠️  Pattern search exceeded {}MB limit at file offset {} - skipping to next file", 
                                  MAX_SEARCH_DISTANCE / (1024 * 1024), search_pos);
+                            self.skips.push(SkipEvent {
+                                file: self.current_file_idx,
+                                offset: search_start,
+                                reason: SkipReason::SearchLimit,
+                            });
                             // Mark file as failed to avoid retrying
                             if let Some(file_idx) = self.current_reading_file_idx {
                                 self.failed_files.insert(file_idx);
                             }
                             self.current_file = None;
                             self.current_reading_file_idx = None;
-                            return Ok(None); // Skip this file
+                            return Ok(FrameRead::End); // Skip this file
                         }
 
                         // Use pre-allocated buffer from struct
@@ -3499,7 +3688,7 @@ impl BlockIterator {
                                 break;
                             }
                             Err(e) => {
-                                eprintln!(
+                                warn!(
                                     "⚠️  Error searching for next block: {} - stopping search",
                                     e
                                 );
@@ -3507,40 +3696,15 @@ impl BlockIterator {
                             }
                         };
 
-                        // OPTIMIZATION: Use memchr for fast pattern searching (2-3x faster than byte-by-byte)
-                        let first_byte = ENCRYPTED_MAGIC[0];
-                        for i in memchr_iter(first_byte, &self.search_buffer[..bytes_read]) {
-                            // Check if we have enough bytes remaining
-                            if i + 3 >= bytes_read {
-                                continue;
-                            }
-
-                            // Potential match - verify all 4 bytes
-                            if self.search_buffer[i + 1] == ENCRYPTED_MAGIC[1]
-                                && self.search_buffer[i + 2] == ENCRYPTED_MAGIC[2]
-                                && self.search_buffer[i + 3] == ENCRYPTED_MAGIC[3]
-                            {
-                                let file_offset = search_pos + i as u64;
-                                // Quick verify: decrypt and check
-                                let mut test_magic = [0u8; 4];
-                                test_magic.copy_from_slice(&self.search_buffer[i..i + 4]);
-
-                                // OPTIMIZATION: Use u32 XOR for magic verification (faster than byte-by-byte)
-                                let magic_u32 = u32::from_le_bytes(test_magic);
-                                let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                                let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-                                let use_key1 = (file_offset / 4) % 2 == 0;
-                                let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                                let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                                test_magic = decrypted_magic_u32.to_le_bytes();
-
-                                if test_magic == *magic {
-                                    // Found next block - seek to it
-                                    file.seek(std::io::SeekFrom::Start(file_offset))?;
-                                    found_next = true;
-                                    break;
-                                }
-                            }
+                        // Scan for the encrypted magic, decrypt-verified at its file offset
+                        let found_at =
+                            key.find_magic(&self.search_buffer[..bytes_read], search_pos, magic);
+                        let scanned = &self.search_buffer[..found_at.unwrap_or(bytes_read)];
+                        padding_only &= is_padding(scanned, search_pos, Some(key));
+                        if let Some(i) = found_at {
+                            // Found next block - seek to it
+                            file.seek(std::io::SeekFrom::Start(search_pos + i as u64))?;
+                            found_next = true;
                         }
 
                         if found_next {
@@ -3556,72 +3720,47 @@ impl BlockIterator {
                     }
                 } // end if need_search
 
-                // If we didn't find the next block and we're not at EOF,
-                // the file pointer is at an unknown position. This shouldn't happen,
-                // but if it does, we'll try to continue on the next read.
-                // The next call to read_next_from_file() will try to read magic bytes,
-                // and if they don't match, it will return Ok(None), moving to the next file.
+                // Anything but padding between this block and the next frame was passed over.
+                // Without a next frame, go back to where it should start so the next read
+                // reports what is there (a frame cut short, garbage).
+                if !padding_only {
+                    if found_next {
+                        self.skips.push(SkipEvent {
+                            file: self.current_file_idx,
+                            offset: search_start,
+                            reason: SkipReason::Garbage,
+                        });
+                    } else {
+                        file.seek(std::io::SeekFrom::Start(search_start))?;
+                    }
+                }
 
                 block_data
             } else {
                 // Size field is invalid - use pattern search
-                eprintln!(
+                warn!(
                     "⚠️  Invalid size field hint ({}) - using pattern search",
                     size_hint
                 );
+                self.skips.push(SkipEvent {
+                    file: self.current_file_idx,
+                    offset: magic_start_pos,
+                    reason: SkipReason::BadSize,
+                });
                 let bytes_read = file.read(&mut self.search_buffer)?;
 
                 if bytes_read == 0 {
-                    return Ok(Some(Vec::new()));
+                    return Ok(FrameRead::End);
                 }
 
                 let start_file_pos = block_start_offset.unwrap();
-                let mut found_at = None;
-
-                // Use memchr for faster searching
-                let first_byte = ENCRYPTED_MAGIC[0];
-                let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-                let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-
-                for i in memchr_iter(first_byte, &self.search_buffer[..bytes_read]) {
-                    if i + 7 >= bytes_read {
-                        continue;
-                    }
-
-                    if self.search_buffer[i + 1] == ENCRYPTED_MAGIC[1]
-                        && self.search_buffer[i + 2] == ENCRYPTED_MAGIC[2]
-                        && self.search_buffer[i + 3] == ENCRYPTED_MAGIC[3]
-                    {
-                        let file_offset = start_file_pos + 8 + i as u64;
-
-                        // CRITICAL FIX: Verify magic decrypts correctly using u32 XOR
-                        let mut test_magic = [0u8; 4];
-                        test_magic.copy_from_slice(&self.search_buffer[i..i + 4]);
-                        let magic_u32 = u32::from_le_bytes(test_magic);
-                        let use_key1 = (file_offset / 4) % 2 == 0;
-                        let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-                        let decrypted_magic_u32 = magic_u32 ^ key_u32;
-                        test_magic = decrypted_magic_u32.to_le_bytes();
-
-                        if test_magic == *magic {
-                            // CRITICAL FIX: Also verify size field is reasonable
-                            let mut test_size = [0u8; 4];
-                            test_size.copy_from_slice(&self.search_buffer[i + 4..i + 8]);
-                            let size_offset = file_offset + 4;
-                            let use_key1_size = (size_offset / 4) % 2 == 0;
-                            let key_u32_size = if use_key1_size { key1_u32 } else { key2_u32 };
-                            let size_u32 = u32::from_le_bytes(test_size);
-                            let decrypted_size_u32 = size_u32 ^ key_u32_size;
-                            let size_hint_test = decrypted_size_u32 as usize;
-
-                            // Only accept if size is reasonable (prevents false positives)
-                            if size_hint_test >= 80 && size_hint_test <= 4 * 1024 * 1024 {
-                                found_at = Some(i);
-                                break;
-                            }
-                        }
-                    }
-                }
+                // Next encrypted magic whose size field is plausible (prevents false positives)
+                let found_at = key.find_frame(
+                    &self.search_buffer[..bytes_read],
+                    start_file_pos + 8,
+                    magic,
+                    RESYNC_SIZE_RANGE,
+                );
 
                 if let Some(i) = found_at {
                     let data = self.search_buffer[..i].to_vec();
@@ -3633,26 +3772,41 @@ impl BlockIterator {
                     // This would cause valid blocks to be skipped as "invalid"
                     // Instead, return None to move to next file - the block will be read correctly
                     // when we restart from the correct position
-                    eprintln!("⚠️  Pattern search failed to find next block - moving to next file to avoid skipping valid blocks");
-                    return Ok(None);
+                    error!("⚠️  Pattern search failed to find next block - moving to next file to avoid skipping valid blocks");
+                    return Ok(FrameRead::End);
                 }
             }
         } else {
             // Standard format: use size field (it's reliable for non-encrypted files)
             let block_size = u32::from_le_bytes(size_buf) as usize;
             if block_size < 80 || block_size > 32 * 1024 * 1024 {
+                self.skips.push(SkipEvent {
+                    file: self.current_file_idx,
+                    offset: magic_start_pos,
+                    reason: SkipReason::BadSize,
+                });
                 anyhow::bail!("Invalid block size: {} bytes", block_size);
             }
             let mut block_data = vec![0u8; block_size];
-            file.read_exact(&mut block_data)?;
+            if let Err(e) = file.read_exact(&mut block_data) {
+                let reason = if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                    SkipReason::Truncated
+                } else {
+                    SkipReason::ReadError
+                };
+                self.skips.push(SkipEvent {
+                    file: self.current_file_idx,
+                    offset: magic_start_pos,
+                    reason,
+                });
+                return Err(e.into());
+            }
             block_data
         };
 
-        // XOR-packaged format: the ENTIRE file is encrypted with ALTERNATING keys
-        // Pattern: KEY1 (bytes 0-3), KEY2 (bytes 4-7), KEY1 (bytes 8-11), KEY2 (bytes 12-15), ...
-        // Keys alternate every 4 bytes starting from file offset 0
+        // Obfuscated files: the ENTIRE file is XORed with the 8-byte key
         // CRITICAL: Key rotation is based on FILE OFFSET, not block offset!
-        let final_block_data = if is_xor_encrypted {
+        let final_block_data = if let Some(key) = xor_key {
             let start_offset =
                 block_start_offset.expect("block_start_offset should be set for encrypted blocks");
             // Reconstruct full encrypted block: magic + size + data
@@ -3662,40 +3816,8 @@ impl BlockIterator {
             full_encrypted.extend_from_slice(&size_buf); // Encrypted size
             full_encrypted.extend_from_slice(&block_data); // Encrypted block data
 
-            // Decrypt with alternating keys based on FILE OFFSET
-            // OPTIMIZATION: Use u32 XOR operations for aligned 4-byte chunks (much faster than byte-by-byte)
-            let mut i = 0;
-            let key1_u32 = u32::from_le_bytes(XOR_KEY1);
-            let key2_u32 = u32::from_le_bytes(XOR_KEY2);
-
-            // Process aligned 4-byte chunks with u32 XOR
-            while i + 4 <= full_encrypted.len() {
-                let file_offset = start_offset + i as u64;
-                let use_key1 = (file_offset / 4) % 2 == 0;
-                let key_u32 = if use_key1 { key1_u32 } else { key2_u32 };
-
-                // XOR 4 bytes at once using u32
-                let chunk = u32::from_le_bytes([
-                    full_encrypted[i],
-                    full_encrypted[i + 1],
-                    full_encrypted[i + 2],
-                    full_encrypted[i + 3],
-                ]);
-                let decrypted_chunk = chunk ^ key_u32;
-                let decrypted_bytes = decrypted_chunk.to_le_bytes();
-                full_encrypted[i..i + 4].copy_from_slice(&decrypted_bytes);
-
-                i += 4;
-            }
-
-            // Handle remaining bytes (< 4) with byte-by-byte XOR
-            while i < full_encrypted.len() {
-                let byte_offset = start_offset + i as u64;
-                let use_key1 = (byte_offset / 4) % 2 == 0;
-                let key = if use_key1 { &XOR_KEY1 } else { &XOR_KEY2 };
-                full_encrypted[i] ^= key[(byte_offset % 4) as usize];
-                i += 1;
-            }
+            // Decrypt based on FILE OFFSET
+            key.apply(&mut full_encrypted, start_offset);
 
             // Extract just the block data (skip magic + size)
             // After decryption:
@@ -3704,27 +3826,20 @@ impl BlockIterator {
             // - full_encrypted[8:] = decrypted block data (what we want)
             let decrypted = full_encrypted[8..].to_vec();
 
-            // Verify the decrypted block is valid
-            // If it's too large, we might have included padding or the next block
-            if decrypted.len() > 32 * 1024 * 1024 {
-                // Block is unreasonably large - likely read too much
-                // CRITICAL FIX: Return None instead of bailing - skip this corrupted block
-                eprintln!("⚠️  Skipping corrupted block (size {} bytes exceeds 32MB limit) - continuing search", decrypted.len());
-                return Ok(None);
-            }
-
-            // Verify block header is valid (basic sanity check)
-            if decrypted.len() >= 80 {
-                // Check version is reasonable (Bitcoin blocks use version 1-4 typically, but can be higher)
-                let version =
-                    u32::from_le_bytes([decrypted[0], decrypted[1], decrypted[2], decrypted[3]]);
-                if version == 0 || version > 0x7fffffff {
-                    // Invalid version - likely read too much data or corrupted block
-                    // CRITICAL FIX: Return None instead of bailing - this allows the iterator
-                    // to skip this corrupted block and continue searching for the next valid block
-                    eprintln!("⚠️  Skipping corrupted block (invalid version {} at size {} bytes) - continuing search", version, decrypted.len());
-                    return Ok(None);
-                }
+            // Verify the decrypted block is valid (size + version). If it's too large, we might
+            // have included padding or the next block; an invalid version usually means we read
+            // too much data. Skip it instead of bailing: the file is already at the next frame.
+            if let Err(rejection) = crate::sanity::filter(SanityStage::Read).check(&decrypted) {
+                warn!(
+                    "⚠️  Skipping corrupted block ({}) - continuing search",
+                    rejection
+                );
+                self.skips.push(SkipEvent {
+                    file: self.current_file_idx,
+                    offset: start_offset,
+                    reason: SkipReason::Rejected,
+                });
+                return Ok(FrameRead::Rejected);
             }
 
             decrypted
@@ -3732,7 +3847,7 @@ impl BlockIterator {
             block_data
         };
 
-        Ok(Some(final_block_data))
+        Ok(FrameRead::Block(final_block_data))
     }
 
     /// Get local copy path if available, otherwise return remote path
@@ -3791,7 +3906,7 @@ impl BlockIterator {
             let local = local_path.clone();
             std::thread::spawn(move || {
                 if let Err(e) = std::fs::copy(&remote, &local) {
-                    eprintln!(
+                    error!(
                         "⚠️  Failed to copy {} to local cache: {}",
                         remote.display(),
                         e
@@ -3886,7 +4001,7 @@ impl BlockIterator {
 
             // Safety check: if we've skipped too many files, something is wrong
             if skip_count >= MAX_SKIPS {
-                eprintln!(
+                warn!(
                     "⚠️  CRITICAL: Skipped {} files in a row - possible infinite loop, stopping",
                     skip_count
                 );
@@ -3970,6 +4085,13 @@ impl BlockIterator {
                             // Use 100 bytes as threshold to account for any padding/overhead
                             if file_size < 100 {
                                 // File is too small to contain valid blocks - skip entirely
+                                if file_size > 0 {
+                                    self.skips.push(SkipEvent {
+                                        file: self.current_file_idx,
+                                        offset: 0,
+                                        reason: SkipReason::TooSmall,
+                                    });
+                                }
                                 self.failed_files.insert(self.current_file_idx);
                                 self.current_file_idx += 1; // CRITICAL FIX: Increment before continue to avoid infinite loop
                                 skip_count += 1;
@@ -4009,7 +4131,10 @@ impl BlockIterator {
             }
 
             // Try to open the file, skip if permission denied
-            match File::open(&path_to_use) {
+            match crate::platform::open_sequential(
+                &path_to_use,
+                self.reader.tuning.io.uring_queue_depth,
+            ) {
                 Ok(file) => {
                     use std::io::Seek;
                     let mut buf_reader = BufReader::with_capacity(64 * 1024 * 1024, file); // 64MB buffer (optimized for large files)
@@ -4017,7 +4142,7 @@ impl BlockIterator {
                     buf_reader.seek(std::io::SeekFrom::Start(0))?;
                     let verify_pos = buf_reader.stream_position()?;
                     if verify_pos != 0 {
-                        eprintln!("⚠️  WARNING: File {} opened at position {} instead of 0 - seeking to 0", self.current_file_idx, verify_pos);
+                        warn!("⚠️  WARNING: File {} opened at position {} instead of 0 - seeking to 0", self.current_file_idx, verify_pos);
                         buf_reader.seek(std::io::SeekFrom::Start(0))?;
                     }
                     self.current_file = Some(buf_reader);
@@ -4025,7 +4150,7 @@ impl BlockIterator {
                     return Ok(true);
                 }
                 Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
-                    eprintln!(
+                    warn!(
                         "⚠️  Permission denied for file {} - skipping",
                         file_path.display()
                     );
@@ -4036,7 +4161,7 @@ impl BlockIterator {
                 }
                 Err(e) => {
                     // Other errors - log and try next file
-                    eprintln!(
+                    warn!(
                         "⚠️  Error opening file {}: {} - skipping",
                         file_path.display(),
                         e
@@ -4062,6 +4187,28 @@ impl Iterator for BlockIterator {
             }
         }
 
+        if let Err(e) = crate::cancel::check(&self.reader.cancel, || {
+            format!("block iteration at height {}", self.current_height)
+        }) {
+            return Some(Err(e));
+        }
+
+        if let Some(index) = &self.core_index {
+            if self.current_height > index.tip_height() {
+                return None;
+            }
+            let height = self.current_height;
+            let result = match index.location(height) {
+                Some(location) => self.reader.read_block_at(&location).with_context(|| {
+                    format!("Failed to read block {} via the block index", height)
+                }),
+                None => Err(anyhow::anyhow!("Height {} is pruned", height)),
+            };
+            self.current_height += 1;
+            self.blocks_read += 1;
+            return Some(result);
+        }
+
         // CRITICAL FIX: If we have a chunked iterator, use it (streaming, no memory limit)
         if let Some(ref mut chunked_iter) = self.chunked_iterator {
             match chunked_iter.next_block() {
@@ -4075,7 +4222,7 @@ impl Iterator for BlockIterator {
                     return None;
                 }
                 Err(e) => {
-                    eprintln!("⚠️  Error reading from chunked iterator: {}", e);
+                    warn!("⚠️  Error reading from chunked iterator: {}", e);
                     return Some(Err(e));
                 }
             }
@@ -4108,7 +4255,7 @@ impl Iterator for BlockIterator {
                     return None;
                 }
                 Err(e) => {
-                    eprintln!("⚠️  Error opening first file: {} - no blocks to read", e);
+                    warn!("⚠️  Error opening first file: {} - no blocks to read", e);
                     return None;
                 }
             }
@@ -4124,100 +4271,6 @@ impl Iterator for BlockIterator {
                     }
                 }
 
-                // CRITICAL FIX: Write block to temp file if we're in sequential reading mode
-                let should_create_chunk = if let Some(ref mut writer) = self.temp_writer {
-                    use std::io::Write;
-                    let block_len = block_data.len() as u32;
-                    let len_bytes = block_len.to_le_bytes();
-
-                    // Write block length and data
-                    let write_ok = writer.write_all(&len_bytes).is_ok()
-                        && writer.write_all(&block_data).is_ok();
-
-                    if write_ok {
-                        self.blocks_written_to_temp += 1;
-
-                        // Update metadata file periodically
-                        if self.blocks_written_to_temp % 10000 == 0 {
-                            if let Some(ref temp_path) = self.temp_file_path {
-                                let metadata_file = temp_path.with_extension("bin.meta");
-                                let count_bytes =
-                                    (self.blocks_written_to_temp as u64).to_le_bytes();
-                                let _ = std::fs::write(&metadata_file, count_bytes);
-                            }
-                        }
-
-                        // Flush periodically to prevent data loss
-                        if self.blocks_written_to_temp % 1000 == 0 {
-                            let _ = writer.flush();
-                        }
-
-                        // Check if we need to create a chunk
-                        self.blocks_written_to_temp > 0
-                            && self.blocks_written_to_temp % INCREMENTAL_CHUNK_SIZE as u64 == 0
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                };
-
-                // CRITICAL: Create chunk when temp file reaches INCREMENTAL_CHUNK_SIZE blocks
-                if should_create_chunk {
-                    // Flush and drop temp writer before creating chunk (need exclusive access to file)
-                    if let Some(mut writer) = self.temp_writer.take() {
-                        let _ = writer.flush();
-                        drop(writer);
-                    }
-
-                    // Calculate chunk number
-                    let chunk_num =
-                        (self.blocks_written_to_temp / INCREMENTAL_CHUNK_SIZE as u64) as usize - 1;
-
-                    if let Some(ref temp_path) = self.temp_file_path {
-                        println!(
-                            "   📦 Creating chunk {} from temp file ({} blocks)...",
-                            chunk_num, INCREMENTAL_CHUNK_SIZE
-                        );
-
-                        // Create chunk from temp file
-                        if let Err(e) = BlockFileReader::create_and_move_chunk_from_file(
-                            temp_path,
-                            chunk_num,
-                            INCREMENTAL_CHUNK_SIZE,
-                        ) {
-                            eprintln!("   ⚠️  Error creating chunk {}: {}", chunk_num, e);
-                        } else {
-                            println!("   ✅ Chunk {} created successfully", chunk_num);
-
-                            // Truncate temp file for next chunk
-                            if let Ok(mut file) = std::fs::OpenOptions::new()
-                                .write(true)
-                                .truncate(true)
-                                .open(temp_path)
-                            {
-                                let _ = file.set_len(0);
-                            }
-
-                            // Recreate temp writer for next chunk
-                            match std::fs::File::create(temp_path) {
-                                Ok(file) => {
-                                    self.temp_writer = Some(std::io::BufWriter::with_capacity(
-                                        IO_BUFFER_SIZE,
-                                        file,
-                                    ));
-                                }
-                                Err(e) => {
-                                    eprintln!(
-                                        "   ⚠️  Error recreating temp file after chunking: {}",
-                                        e
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-
                 self.current_height += 1;
                 self.blocks_read += 1;
                 Some(Ok(block_data))
@@ -4229,7 +4282,7 @@ impl Iterator for BlockIterator {
                     Ok(false) => None,       // No more files
                     Err(e) => {
                         // Error moving to next file - log and try to continue
-                        eprintln!("⚠️  Error moving to next file: {} - trying to continue", e);
+                        warn!("⚠️  Error moving to next file: {} - trying to continue", e);
                         // Try to manually advance to next file
                         self.current_file_idx += 1;
                         if self.current_file_idx < self.reader.block_files.len() {
@@ -4242,10 +4295,15 @@ impl Iterator for BlockIterator {
             }
             Err(e) => {
                 // Error reading block - close current file and try next file instead of stopping
-                eprintln!(
+                warn!(
                     "⚠️  Error reading block: {} - closing file and trying next",
                     e
                 );
+                // Mark it failed: with no file open, next_file() would reopen the same index and
+                // yield its blocks again
+                if let Some(file_idx) = self.current_reading_file_idx.take() {
+                    self.failed_files.insert(file_idx);
+                }
                 // Close current file (drop it)
                 self.current_file = None;
                 // Try to move to next file and continue
@@ -4262,7 +4320,7 @@ impl Iterator for BlockIterator {
                         }
                         Err(e2) => {
                             // Error moving to next file - log and try to manually advance
-                            eprintln!("⚠️  Error moving to next file: {} - manually advancing", e2);
+                            warn!("⚠️  Error moving to next file: {} - manually advancing", e2);
                             self.current_file_idx += 1;
                             if self.current_file_idx >= self.reader.block_files.len() {
                                 // Truly no more files
@@ -4277,12 +4335,355 @@ impl Iterator for BlockIterator {
     }
 }
 
+/// A block from [`BlockIterator::parsed`], deserialized with its witnesses.
+#[derive(Debug, Clone)]
+pub struct ParsedBlock {
+    /// `start_height` plus the number of blocks yielded before this one; exact for ordered
+    /// sources (chunks, Core's index, standard files), a hint for anything else
+    pub height_hint: u64,
+    pub block: blvm_protocol::Block,
+    pub witnesses: Vec<Vec<blvm_protocol::segwit::Witness>>,
+    /// Serialized size of the block
+    pub raw_len: usize,
+}
+
+/// Iterator adapter returned by [`BlockIterator::parsed`].
+///
+/// Pulls up to `batch_size` raw blocks, deserializes them in parallel on the rayon pool and
+/// yields them in their original order. A block that fails to deserialize becomes an `Err`
+/// item for that height; iteration continues with the next one.
+pub struct ParsedBlockIterator {
+    inner: BlockIterator,
+    batch_size: usize,
+    next_height: u64,
+    ready: std::collections::VecDeque<Result<ParsedBlock>>,
+    exhausted: bool,
+}
+
+impl BlockIterator {
+    /// What reading block files sequentially passed over so far, in read order. Always empty for
+    /// reads served by the chunk cache, Core's block index or a collection pass.
+    pub fn skips(&self) -> &[SkipEvent] {
+        &self.skips
+    }
+
+    /// Yield [`ParsedBlock`]s instead of raw bytes, deserializing on the rayon pool in batches
+    /// of 4 blocks per worker thread.
+    pub fn parsed(self) -> ParsedBlockIterator {
+        self.parsed_with_batch(rayon::current_num_threads() * 4)
+    }
+
+    /// [`parsed`](Self::parsed) with an explicit batch size (blocks held in memory at once).
+    pub fn parsed_with_batch(self, batch_size: usize) -> ParsedBlockIterator {
+        ParsedBlockIterator {
+            next_height: self.start_height.unwrap_or(0),
+            inner: self,
+            batch_size: batch_size.max(1),
+            ready: std::collections::VecDeque::new(),
+            exhausted: false,
+        }
+    }
+}
+
+impl ParsedBlockIterator {
+    fn fill(&mut self) {
+        let mut raw = Vec::with_capacity(self.batch_size);
+        while raw.len() < self.batch_size {
+            match self.inner.next() {
+                Some(item) => {
+                    raw.push((self.next_height, item));
+                    self.next_height += 1;
+                }
+                None => {
+                    self.exhausted = true;
+                    break;
+                }
+            }
+        }
+        let parsed: Vec<Result<ParsedBlock>> = raw
+            .into_par_iter()
+            .map(|(height, item)| {
+                let bytes = item?;
+                let (block, witnesses) =
+                    blvm_protocol::serialization::block::deserialize_block_with_witnesses(&bytes)
+                        .with_context(|| format!("Failed to deserialize block ~{}", height))?;
+                Ok(ParsedBlock {
+                    height_hint: height,
+                    block,
+                    witnesses,
+                    raw_len: bytes.len(),
+                })
+            })
+            .collect();
+        self.ready.extend(parsed);
+    }
+}
+
+impl Iterator for ParsedBlockIterator {
+    type Item = Result<ParsedBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() && !self.exhausted {
+            self.fill();
+        }
+        self.ready.pop_front()
+    }
+}
+
+/// Iterator adapter returned by [`BlockIterator::prefetched`].
+///
+/// A background thread drives the inner iterator and keeps up to `depth` blocks (read and
+/// de-obfuscated) queued in a bounded channel, so the next blocks come off disk while the caller
+/// validates the current one. Items, errors included, arrive in the inner iterator's order.
+/// Dropping the adapter stops the thread after the block it is reading.
+pub struct PrefetchedBlocks {
+    source: PrefetchSource,
+}
+
+enum PrefetchSource {
+    Inline(Box<BlockIterator>),
+    Thread(std::sync::mpsc::Receiver<Result<Vec<u8>>>),
+}
+
+impl BlockIterator {
+    /// Read ahead on a background thread, `io.prefetch_depth` blocks deep (see
+    /// [`crate::config::IoTuning`]).
+    pub fn prefetched(self) -> PrefetchedBlocks {
+        let depth = self.reader.tuning.io.prefetch_depth;
+        self.prefetched_with_depth(depth)
+    }
+
+    /// [`prefetched`](Self::prefetched) with an explicit depth; 0 keeps reads on the caller's
+    /// thread.
+    pub fn prefetched_with_depth(self, depth: usize) -> PrefetchedBlocks {
+        if depth == 0 {
+            return PrefetchedBlocks {
+                source: PrefetchSource::Inline(Box::new(self)),
+            };
+        }
+        let (tx, rx) = std::sync::mpsc::sync_channel(depth);
+        std::thread::spawn(move || {
+            for item in self {
+                // The receiver is gone: the consumer stopped early
+                if tx.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        PrefetchedBlocks {
+            source: PrefetchSource::Thread(rx),
+        }
+    }
+}
+
+impl Iterator for PrefetchedBlocks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            PrefetchSource::Inline(inner) => inner.next(),
+            // Disconnected once the thread has drained the inner iterator
+            PrefetchSource::Thread(rx) => rx.recv().ok(),
+        }
+    }
+}
+
+/// Zero-copy sequential reader over memory-mapped `blk*.dat` files, from
+/// [`BlockFileReader::read_blocks_mmap`].
+///
+/// Frames are walked with [`FrameScanner`](crate::block_framing::FrameScanner) directly over the
+/// map, so plain files yield slices of the mapping itself; obfuscated files are decoded into one
+/// scratch buffer that is reused for every block. Not an [`Iterator`] because each slice borrows
+/// the iterator until the next call:
+///
+/// ```ignore
+/// let mut blocks = reader.read_blocks_mmap(None, None)?;
+/// while let Some(block) = blocks.next_block()? {
+///     total += block.len();
+/// }
+/// ```
+///
+/// Like [`BlockIterator`] on a standard tree, heights assume blocks are stored in chain order.
+pub struct MmapBlockIterator {
+    reader: BlockFileReader,
+    limits: crate::block_framing::FrameLimits,
+    next_file_idx: usize,
+    mmap: Option<memmap2::Mmap>,
+    pos: usize,
+    scratch: Vec<u8>,
+    to_skip: u64,
+    remaining: Option<usize>,
+    next_height: u64,
+    resyncs: u64,
+}
+
+impl BlockFileReader {
+    /// Memory-mapped variant of [`read_blocks_sequential`](Self::read_blocks_sequential) for
+    /// standard (in-order) trees: no per-block allocation and no seeking.
+    pub fn read_blocks_mmap(
+        &self,
+        start_height: Option<u64>,
+        max_blocks: Option<usize>,
+    ) -> Result<MmapBlockIterator> {
+        if self.is_xor_packaged() {
+            anyhow::bail!(
+                "{} is XOR-packaged (blocks out of order) - use read_blocks_sequential",
+                self.data_dir.display()
+            );
+        }
+        Ok(MmapBlockIterator {
+            reader: self.clone(),
+            limits: crate::block_framing::FrameLimits {
+                max_block_size: MAX_VALID_BLOCK_SIZE,
+                ..Default::default()
+            },
+            next_file_idx: 0,
+            mmap: None,
+            pos: 0,
+            scratch: Vec::new(),
+            to_skip: start_height.unwrap_or(0),
+            remaining: max_blocks,
+            next_height: start_height.unwrap_or(0),
+            resyncs: 0,
+        })
+    }
+}
+
+impl MmapBlockIterator {
+    /// The next block (header first, no magic/size prefix), or `None` after the last file.
+    pub fn next_block(&mut self) -> Result<Option<&[u8]>> {
+        use crate::block_framing::{FrameEvent, FrameScanner};
+
+        if self.remaining == Some(0) {
+            return Ok(None);
+        }
+        let magic = *self.reader.network.magic_bytes();
+        let key = self.reader.xor_key;
+        let payload = loop {
+            let Some(mmap) = &self.mmap else {
+                if !self.map_next_file()? {
+                    return Ok(None);
+                }
+                continue;
+            };
+            let event = FrameScanner::new(&mmap[self.pos..], self.pos as u64, magic, false)
+                .with_key(key)
+                .with_limits(self.limits)
+                .next();
+            match event {
+                Some(FrameEvent::Block { payload, .. }) => {
+                    let payload = self.pos + payload.start..self.pos + payload.end;
+                    self.pos = payload.end;
+                    if self.to_skip > 0 {
+                        self.to_skip -= 1;
+                        continue;
+                    }
+                    break payload;
+                }
+                Some(FrameEvent::Resync { to, .. }) => {
+                    self.resyncs += 1;
+                    self.pos += to;
+                }
+                // Truncated tail, preallocated zeros or end of data: on to the next file
+                _ => self.mmap = None,
+            }
+        };
+
+        self.next_height += 1;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        let mmap = self
+            .mmap
+            .as_ref()
+            .expect("frame came from the current mapping");
+        match key {
+            Some(key) => {
+                self.scratch.clear();
+                self.scratch.extend_from_slice(&mmap[payload.clone()]);
+                key.apply(&mut self.scratch, payload.start as u64);
+                Ok(Some(&self.scratch))
+            }
+            None => Ok(Some(&mmap[payload])),
+        }
+    }
+
+    /// Height of the block the next [`next_block`](Self::next_block) call returns.
+    pub fn next_height(&self) -> u64 {
+        self.next_height
+    }
+
+    /// Damaged regions skipped by searching for the next magic so far.
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs
+    }
+
+    /// Map the next non-empty block file; `false` once all files are done.
+    fn map_next_file(&mut self) -> Result<bool> {
+        while self.next_file_idx < self.reader.block_files.len() {
+            let path = &self.reader.block_files[self.next_file_idx];
+            self.next_file_idx += 1;
+            crate::cancel::check(&self.reader.cancel, || {
+                format!("mmap block iteration at {}", path.display())
+            })?;
+            let file =
+                File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            if file.metadata()?.len() == 0 {
+                continue;
+            }
+            // Core only appends to blk files; a file truncated underneath the map would fault
+            let mmap = unsafe { memmap2::Mmap::map(&file) }
+                .with_context(|| format!("Failed to mmap {}", path.display()))?;
+            #[cfg(unix)]
+            let _ = mmap.advise(memmap2::Advice::Sequential);
+            self.mmap = Some(mmap);
+            self.pos = 0;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Index of [`SharedBlockCache`] entries: one `<height> <len> <sha256 hex>` line per block
+/// written or verified, appended as it happens; the last line for a height wins.
+const CACHE_INDEX_FILE: &str = "index.log";
+
+/// Length and checksum of one cached `block_N.bin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CacheEntry {
+    len: u64,
+    sha256: [u8; 32],
+}
+
+impl CacheEntry {
+    fn of(bytes: &[u8]) -> Self {
+        Self {
+            len: bytes.len() as u64,
+            sha256: Sha256::digest(bytes).into(),
+        }
+    }
+
+    fn parse_line(line: &str) -> Option<(u64, Self)> {
+        let mut fields = line.split_whitespace();
+        let height = fields.next()?.parse().ok()?;
+        let len = fields.next()?.parse().ok()?;
+        let sha256 = hex::decode(fields.next()?).ok()?.try_into().ok()?;
+        Some((height, Self { len, sha256 }))
+    }
+}
+
 /// Shared block cache for reference node and Commons
 ///
 /// Downloads blocks once and stores them in a shared location
 /// that both reference node and Commons can access.
+///
+/// Every block is checksummed in `index.log`; a cached block that no longer matches (truncated
+/// or overwritten file) is dropped and fetched again instead of being handed to validation.
+/// Files from before the index existed are adopted on first read if they still deserialize.
 pub struct SharedBlockCache {
     cache_dir: PathBuf,
+    index: std::sync::Mutex<HashMap<u64, CacheEntry>>,
+    corrupted: std::sync::atomic::AtomicU64,
 }
 
 impl SharedBlockCache {
@@ -4291,7 +4692,178 @@ impl SharedBlockCache {
         let cache_dir = cache_dir.as_ref().to_path_buf();
         std::fs::create_dir_all(&cache_dir)?;
 
-        Ok(Self { cache_dir })
+        let mut index = HashMap::new();
+        let index_path = cache_dir.join(CACHE_INDEX_FILE);
+        match std::fs::read_to_string(&index_path) {
+            Ok(text) => {
+                let mut malformed = 0usize;
+                for line in text.lines() {
+                    match CacheEntry::parse_line(line) {
+                        Some((height, entry)) => {
+                            index.insert(height, entry);
+                        }
+                        // A torn last line after a crash; that block is re-verified on read
+                        None => malformed += 1,
+                    }
+                }
+                if malformed > 0 {
+                    warn!(
+                        "⚠️  Ignored {} malformed line(s) in {}",
+                        malformed,
+                        index_path.display()
+                    );
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", index_path.display()))
+            }
+        }
+
+        Ok(Self {
+            cache_dir,
+            index: std::sync::Mutex::new(index),
+            corrupted: std::sync::atomic::AtomicU64::new(0),
+        })
+    }
+
+    fn block_path(&self, height: u64) -> PathBuf {
+        self.cache_dir.join(format!("block_{}.bin", height))
+    }
+
+    /// The cached block at `height` if it passes its checksum; a corrupt file is removed.
+    fn read_cached(&self, height: u64) -> Result<Option<Vec<u8>>> {
+        let path = self.block_path(height);
+        let cached = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let expected = self.index.lock().unwrap().get(&height).copied();
+        let problem = match expected {
+            Some(entry) if entry == CacheEntry::of(&cached) => return Ok(Some(cached)),
+            Some(entry) if entry.len != cached.len() as u64 => {
+                format!("{} bytes, index says {}", cached.len(), entry.len)
+            }
+            Some(_) => "checksum mismatch".to_string(),
+            None => {
+                // Written before the index existed: keep it if it is still a whole block
+                match blvm_protocol::serialization::block::deserialize_block_with_witnesses(&cached)
+                {
+                    Ok(_) => {
+                        self.record(height, CacheEntry::of(&cached))?;
+                        return Ok(Some(cached));
+                    }
+                    Err(e) => format!("unindexed and does not deserialize ({:?})", e),
+                }
+            }
+        };
+        self.corrupted
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        warn!(
+            "⚠️  Cached block {} is corrupt ({}) - discarding and re-fetching",
+            height, problem
+        );
+        let _ = std::fs::remove_file(&path);
+        Ok(None)
+    }
+
+    /// Write `block_bytes` through a temp file and record its checksum.
+    fn store(&self, height: u64, block_bytes: &[u8]) -> Result<()> {
+        let path = self.block_path(height);
+        let tmp = path.with_extension("bin.tmp");
+        std::fs::write(&tmp, block_bytes)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        self.record(height, CacheEntry::of(block_bytes))
+    }
+
+    fn record(&self, height: u64, entry: CacheEntry) -> Result<()> {
+        let mut index = self.index.lock().unwrap();
+        let index_path = self.cache_dir.join(CACHE_INDEX_FILE);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&index_path)
+            .with_context(|| format!("Failed to open {}", index_path.display()))?;
+        writeln!(
+            file,
+            "{} {} {}",
+            height,
+            entry.len,
+            hex::encode(entry.sha256)
+        )?;
+        index.insert(height, entry);
+        Ok(())
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Cached block at `height` with its spent-outputs sidecar ([`crate::prevout_blocks`]).
+    ///
+    /// `None` when either is missing or the sidecar belongs to a different block (the block was
+    /// re-fetched after a reorg); a sidecar that does not parse is an error.
+    pub fn read_prevout_block(
+        &self,
+        height: u64,
+    ) -> Result<Option<crate::prevout_blocks::PrevoutBlock>> {
+        use crate::prevout_blocks::{block_hash, decode_sidecar, sidecar_path, PrevoutBlock};
+        let path = sidecar_path(&self.cache_dir, height);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let Some(block_bytes) = self.read_cached(height)? else {
+            return Ok(None);
+        };
+        let (hash, spent) =
+            decode_sidecar(&data).with_context(|| format!("Invalid {}", path.display()))?;
+        if hash != block_hash(&block_bytes)? {
+            return Ok(None);
+        }
+        Ok(Some(PrevoutBlock {
+            height,
+            block_bytes,
+            spent,
+        }))
+    }
+
+    /// Cache the block (if not already) and write its sidecar; returns the sidecar size.
+    pub fn store_prevout_block(&self, block: &crate::prevout_blocks::PrevoutBlock) -> Result<u64> {
+        use crate::prevout_blocks::{block_hash, encode_sidecar, sidecar_path};
+        if self.read_cached(block.height)?.as_deref() != Some(block.block_bytes.as_slice()) {
+            self.store(block.height, &block.block_bytes)?;
+        }
+        let data = encode_sidecar(&block_hash(&block.block_bytes)?, &block.spent);
+        let path = sidecar_path(&self.cache_dir, block.height);
+        let tmp = path.with_extension("spent.tmp");
+        std::fs::write(&tmp, &data)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        Ok(data.len() as u64)
+    }
+
+    /// Cached block at `height`, else the result of `fetch`, cached for next time. For sources
+    /// other than RPC / block files (e.g. [`crate::esplora_source`]).
+    pub async fn get_or_fetch_with<F, Fut>(&self, height: u64, fetch: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Vec<u8>>>,
+    {
+        let cached = self.read_cached(height)?;
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().record_cache("block_cache", cached.is_some());
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+        let block_bytes = fetch().await?;
+        self.store(height, &block_bytes)?;
+        Ok(block_bytes)
     }
 
     /// Get block from cache or download it
@@ -4300,14 +4872,14 @@ impl SharedBlockCache {
         height: u64,
         rpc_client: Option<&crate::core_rpc_client::CoreRpcClient>,
     ) -> Result<Vec<u8>> {
-        let cache_path = self.cache_dir.join(format!("block_{}.bin", height));
-
         // Check cache first
-        if cache_path.exists() {
-            let cached = std::fs::read(&cache_path)?;
+        let cached = self.read_cached(height)?;
+        #[cfg(feature = "metrics")]
+        crate::metrics::global().record_cache("block_cache", cached.is_some());
+        if let Some(cached) = cached {
             #[cfg(debug_assertions)]
             if height == 16 || height <= 2 {
-                eprintln!(
+                debug!(
                     "DEBUG get_or_fetch_block {}: Using cached block ({} bytes)",
                     height,
                     cached.len()
@@ -4319,7 +4891,7 @@ impl SharedBlockCache {
                     use blvm_protocol::crypto::OptimizedSha256;
                     let hasher = OptimizedSha256::new();
                     let block_hash = hex::encode(hasher.hash256(header));
-                    eprintln!(
+                    debug!(
                         "DEBUG get_or_fetch_block {}: Cached block hash = {}",
                         height, block_hash
                     );
@@ -4337,16 +4909,16 @@ impl SharedBlockCache {
                         Ok(block_hex) => {
                             let block_bytes = hex::decode(&block_hex)?;
                             // Cache it for next time
-                            std::fs::write(&cache_path, &block_bytes)?;
+                            self.store(height, &block_bytes)?;
                             return Ok(block_bytes);
                         }
                         Err(e) => {
-                            eprintln!("⚠️  RPC getblock_raw failed for height {}: {}", height, e);
+                            error!("⚠️  RPC getblock_raw failed for height {}: {}", height, e);
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("⚠️  RPC getblockhash failed for height {}: {}", height, e);
+                    error!("⚠️  RPC getblockhash failed for height {}: {}", height, e);
                 }
             }
         }
@@ -4354,8 +4926,7 @@ impl SharedBlockCache {
         // If RPC failed or not available, try DirectFile as fallback
         // Try known mount points directly (bypass auto-detect which may fail due to permissions)
         let mut possible_dirs = crate::block_cache_env::bitcoin_data_dir_candidates();
-        if let Some(h) = dirs::home_dir() {
-            let pb = h.join(".bitcoin");
+        for pb in crate::platform::default_bitcoin_data_dirs() {
             if !possible_dirs.iter().any(|e| e == &pb) {
                 possible_dirs.push(pb);
             }
@@ -4369,7 +4940,7 @@ impl SharedBlockCache {
                     if let Some(block_result) = iterator.next() {
                         let block_bytes = block_result?;
                         // Cache it for next time
-                        std::fs::write(&cache_path, &block_bytes)?;
+                        self.store(height, &block_bytes)?;
                         return Ok(block_bytes);
                     }
                 }
@@ -4382,32 +4953,91 @@ impl SharedBlockCache {
         )
     }
 
-    /// Pre-fetch a range of blocks
+    /// Pre-fetch a range of blocks (stops early with [`Cancelled`] once `cancel` fires)
+    ///
+    /// Uncached blocks are fetched `rpc_client.batch_size()` at a time, with one batched
+    /// `getblockhash` and one batched `getblock` request per batch. Blocks whose calls fail are
+    /// retried one by one through [`get_or_fetch_block`](Self::get_or_fetch_block).
     pub async fn prefetch_range(
         &self,
         start_height: u64,
         end_height: u64,
         rpc_client: &crate::core_rpc_client::CoreRpcClient,
+        cancel: &CancellationToken,
     ) -> Result<()> {
-        println!(
+        info!(
             "📥 Pre-fetching blocks {}-{} to shared cache...",
             start_height, end_height
         );
 
-        for height in start_height..=end_height {
-            if height % 1000 == 0 {
-                println!(
+        let heights: Vec<u64> = (start_height..=end_height).collect();
+        let mut done = 0;
+        for batch in heights.chunks(rpc_client.batch_size()) {
+            crate::cancel::check(cancel, || format!("prefetch at height {}", batch[0]))?;
+            let missing: Vec<u64> = batch
+                .iter()
+                .copied()
+                .filter(|&height| !self.block_path(height).exists())
+                .collect();
+            if !missing.is_empty() {
+                self.fetch_batch(&missing, rpc_client).await?;
+            }
+
+            if done / 1000 != (done + batch.len()) / 1000 {
+                info!(
                     "   Progress: {}/{} ({:.1}%)",
-                    height - start_height,
-                    end_height - start_height,
-                    100.0 * (height - start_height) as f64 / (end_height - start_height) as f64
+                    done + batch.len(),
+                    heights.len(),
+                    100.0 * (done + batch.len()) as f64 / heights.len() as f64
                 );
             }
+            done += batch.len();
+        }
+
+        info!("✅ Pre-fetch complete!");
+        Ok(())
+    }
+
+    /// Fetch and store `heights` with batched RPC calls, falling back to single fetches.
+    async fn fetch_batch(
+        &self,
+        heights: &[u64],
+        rpc_client: &crate::core_rpc_client::CoreRpcClient,
+    ) -> Result<()> {
+        let mut retry = Vec::new();
+        let mut hashed = Vec::with_capacity(heights.len());
+        for (&height, hash) in heights
+            .iter()
+            .zip(rpc_client.getblockhashes(heights).await?)
+        {
+            match hash {
+                Ok(hash) => hashed.push((height, hash)),
+                Err(e) => {
+                    warn!(
+                        "⚠️  Batched getblockhash failed for height {}: {}",
+                        height, e
+                    );
+                    retry.push(height);
+                }
+            }
+        }
 
-            let _ = self.get_or_fetch_block(height, Some(rpc_client)).await?;
+        let hashes: Vec<String> = hashed.iter().map(|(_, hash)| hash.clone()).collect();
+        for ((height, _), block) in hashed.iter().zip(rpc_client.getblocks_raw(&hashes).await?) {
+            match block
+                .and_then(|block_hex| hex::decode(block_hex.trim()).context("Invalid block hex"))
+            {
+                Ok(block_bytes) => self.store(*height, &block_bytes)?,
+                Err(e) => {
+                    warn!("⚠️  Batched getblock failed for height {}: {}", height, e);
+                    retry.push(*height);
+                }
+            }
         }
 
-        println!("✅ Pre-fetch complete!");
+        for height in retry {
+            self.get_or_fetch_block(height, Some(rpc_client)).await?;
+        }
         Ok(())
     }
 
@@ -4430,6 +5060,8 @@ impl SharedBlockCache {
         Ok(CacheStats {
             total_blocks,
             total_size_bytes: total_size,
+            indexed_blocks: self.index.lock().unwrap().len(),
+            corrupted_blocks: self.corrupted.load(std::sync::atomic::Ordering::Relaxed),
         })
     }
 }
@@ -4438,4 +5070,137 @@ impl SharedBlockCache {
 pub struct CacheStats {
     pub total_blocks: usize,
     pub total_size_bytes: u64,
+    /// Heights with a checksum in `index.log`
+    pub indexed_blocks: usize,
+    /// Cached blocks discarded as corrupt since this cache was opened
+    pub corrupted_blocks: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_cache_detects_corrupt_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SharedBlockCache::new(dir.path()).unwrap();
+        let block = vec![0x42u8; 300];
+        cache.store(7, &block).unwrap();
+        assert_eq!(cache.read_cached(7).unwrap(), Some(block.clone()));
+
+        // The index survives reopening; a truncated file is caught and removed
+        let cache = SharedBlockCache::new(dir.path()).unwrap();
+        std::fs::write(cache.block_path(7), &block[..200]).unwrap();
+        assert_eq!(cache.read_cached(7).unwrap(), None);
+        assert!(!cache.block_path(7).exists());
+
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!(stats.indexed_blocks, 1);
+        assert_eq!(stats.corrupted_blocks, 1);
+        assert_eq!(stats.total_blocks, 0);
+    }
+
+    #[test]
+    fn decoded_len_rejects_truncated_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chunk_0.bin.zst");
+        let data: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        std::fs::write(&path, zstd::encode_all(&data[..], 3).unwrap()).unwrap();
+        assert_eq!(decoded_len(&path).unwrap(), data.len() as u64);
+
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &compressed[..compressed.len() / 2]).unwrap();
+        assert!(decoded_len(&path).is_err());
+    }
+
+    #[test]
+    fn prefetched_blocks_match_inline_reads() {
+        let blocks: Vec<Vec<u8>> = (1..=8u8).map(|i| vec![i; 80 + i as usize * 10]).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let blocks_dir = dir.path().join("blocks");
+        std::fs::create_dir_all(&blocks_dir).unwrap();
+        for (n, range) in [(0, 0..4), (1, 4..8)] {
+            let mut file = Vec::new();
+            for block in &blocks[range] {
+                file.extend_from_slice(&BLOCK_MAGIC_MAINNET);
+                file.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                file.extend_from_slice(block);
+            }
+            std::fs::write(blocks_dir.join(format!("blk{:05}.dat", n)), file).unwrap();
+        }
+
+        let reader = BlockFileReader::new(dir.path(), Network::Mainnet).unwrap();
+        let read = |start, max, depth| -> Vec<Vec<u8>> {
+            reader
+                .read_blocks_sequential(start, max)
+                .unwrap()
+                .prefetched_with_depth(depth)
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+        let inline = read(None, None, 0);
+        assert_eq!(inline.len(), blocks.len());
+        assert_eq!(read(None, None, 2), inline);
+        assert_eq!(read(Some(3), Some(4), 1), inline[3..7]);
+
+        // Stopping early leaves the prefetch thread to exit on its own
+        let mut early = reader
+            .read_blocks_sequential(None, None)
+            .unwrap()
+            .prefetched_with_depth(1);
+        assert_eq!(early.next().unwrap().unwrap(), inline[0]);
+    }
+
+    #[test]
+    fn mmap_iterator_matches_framed_blocks() {
+        let blocks: Vec<Vec<u8>> = (1..=6u8).map(|i| vec![i; 80 + i as usize * 10]).collect();
+        for key in [
+            None,
+            XorKey::new([0x31, 0x00, 0xa7, 0x5c, 0x02, 0xee, 0x90, 0x18]),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let blocks_dir = dir.path().join("blocks");
+            std::fs::create_dir_all(&blocks_dir).unwrap();
+            // Two files: junk between frames in the first, a preallocated zero tail in the second
+            for (n, range) in [(0, 0..3), (1, 3..6)] {
+                let mut file = Vec::new();
+                for block in &blocks[range] {
+                    file.extend_from_slice(&BLOCK_MAGIC_MAINNET);
+                    file.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                    file.extend_from_slice(block);
+                    if n == 0 {
+                        file.extend_from_slice(b"junk");
+                    }
+                }
+                if n == 1 {
+                    file.resize(file.len() + 4096, 0);
+                }
+                if let Some(key) = key {
+                    key.apply(&mut file, 0);
+                }
+                std::fs::write(blocks_dir.join(format!("blk{:05}.dat", n)), file).unwrap();
+            }
+            if let Some(key) = key {
+                std::fs::write(blocks_dir.join("xor.dat"), key.bytes()).unwrap();
+            }
+
+            let reader = BlockFileReader::new(dir.path(), Network::Mainnet).unwrap();
+            let mut iter = reader.read_blocks_mmap(None, None).unwrap();
+            let mut read = Vec::new();
+            while let Some(block) = iter.next_block().unwrap() {
+                read.push(block.to_vec());
+            }
+            assert_eq!(read, blocks);
+            assert_eq!(iter.resyncs(), 2);
+            assert_eq!(iter.next_height(), 6);
+
+            let mut iter = reader.read_blocks_mmap(Some(2), Some(3)).unwrap();
+            assert_eq!(iter.next_height(), 2);
+            let mut read = Vec::new();
+            while let Some(block) = iter.next_block().unwrap() {
+                read.push(block.to_vec());
+            }
+            assert_eq!(read, blocks[2..5]);
+        }
+    }
 }
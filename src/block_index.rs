@@ -0,0 +1,586 @@
+//! Bitcoin Core block index reader
+//!
+//! Reads Core's `blocks/index` LevelDB database directly (read-only, no LevelDB library) and maps
+//! each height of the active chain to the `blk*.dat` file and offset holding the block, so
+//! [`BlockFileReader`](crate::block_file_reader::BlockFileReader) can seek straight to any block
+//! instead of scanning files.
+//!
+//! Only what Core writes is supported: uncompressed tables (`*.ldb` / `*.sst`) and the write-ahead
+//! `*.log`. Every table and log present is read and the newest sequence number wins per key, so
+//! the `MANIFEST` is not needed. Core can keep running while this reads; a half-written log tail
+//! is ignored.
+//!
+//! The active chain is the highest fully validated (`BLOCK_VALID_SCRIPTS`), non-failed entry
+//! walked back through `hashPrev`. During a momentary tie at the tip either branch may be
+//! picked.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::wire;
+
+/// `leveldb::kTableMagicNumber`
+const TABLE_MAGIC: u64 = 0xdb47_7524_8b80_fb57;
+/// Table footer: two block handles (padded to 40 bytes) + magic
+const FOOTER_LEN: usize = 48;
+/// Compression type byte + crc32c after every table block
+const BLOCK_TRAILER_LEN: usize = 5;
+/// Write-ahead log block size
+const LOG_BLOCK_SIZE: usize = 32 * 1024;
+/// Log record header: crc32c (4), length (2), type (1)
+const LOG_HEADER_LEN: usize = 7;
+
+/// `DB_BLOCK_INDEX` key prefix (`'b' + block hash`)
+const DB_BLOCK_INDEX: u8 = b'b';
+
+/// `BlockStatus` bits (`chain.h`)
+pub const BLOCK_VALID_MASK: u32 = 7;
+pub const BLOCK_VALID_SCRIPTS: u32 = 5;
+pub const BLOCK_HAVE_DATA: u32 = 8;
+pub const BLOCK_HAVE_UNDO: u32 = 16;
+pub const BLOCK_FAILED_MASK: u32 = 32 | 64;
+
+/// One `CDiskBlockIndex` record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskBlockIndex {
+    /// Block hash, internal byte order
+    pub hash: [u8; 32],
+    pub height: u32,
+    pub status: u32,
+    pub n_tx: u32,
+    /// `blk<file>.dat` / `rev<file>.dat` number (set when data or undo is stored)
+    pub file: Option<u32>,
+    /// Offset of the block (after magic and size) in `blk<file>.dat`
+    pub data_pos: Option<u32>,
+    /// Offset of the undo data in `rev<file>.dat`
+    pub undo_pos: Option<u32>,
+    pub header: [u8; 80],
+}
+
+impl DiskBlockIndex {
+    /// Decode a value stored under `'b' + hash`.
+    pub fn decode(hash: [u8; 32], value: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let mut varint = |what: &str| {
+            wire::read_varint(value, &mut pos).with_context(|| format!("block index {}", what))
+        };
+        let _client_version = varint("version")?;
+        let height = varint("height")? as u32;
+        let status = varint("status")? as u32;
+        let n_tx = varint("tx count")? as u32;
+        let file = if status & (BLOCK_HAVE_DATA | BLOCK_HAVE_UNDO) != 0 {
+            Some(varint("file")? as u32)
+        } else {
+            None
+        };
+        let data_pos = if status & BLOCK_HAVE_DATA != 0 {
+            Some(varint("data pos")? as u32)
+        } else {
+            None
+        };
+        let undo_pos = if status & BLOCK_HAVE_UNDO != 0 {
+            Some(varint("undo pos")? as u32)
+        } else {
+            None
+        };
+        let header: [u8; 80] = value
+            .get(pos..pos + 80)
+            .and_then(|h| h.try_into().ok())
+            .context("block index record too short for header")?;
+        Ok(Self {
+            hash,
+            height,
+            status,
+            n_tx,
+            file,
+            data_pos,
+            undo_pos,
+            header,
+        })
+    }
+
+    /// `hashPrevBlock` from the stored header, internal byte order.
+    pub fn prev_hash(&self) -> [u8; 32] {
+        self.header[4..36].try_into().unwrap()
+    }
+
+    /// Fully validated and not marked failed.
+    pub fn is_valid(&self) -> bool {
+        self.status & BLOCK_VALID_MASK >= BLOCK_VALID_SCRIPTS
+            && self.status & BLOCK_FAILED_MASK == 0
+    }
+
+    /// Where the block's bytes are, if Core still has them (not pruned).
+    pub fn location(&self) -> Option<BlockLocation> {
+        Some(BlockLocation {
+            file: self.file?,
+            data_pos: self.data_pos?,
+            hash: self.hash,
+        })
+    }
+
+    /// Where the block's undo data is, if Core has it (not pruned; genesis has none).
+    pub fn undo_location(&self) -> Option<UndoLocation> {
+        Some(UndoLocation {
+            file: self.file?,
+            undo_pos: self.undo_pos?,
+            prev_hash: self.prev_hash(),
+        })
+    }
+}
+
+/// Position of a block in Core's block files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLocation {
+    /// `blk<file>.dat`
+    pub file: u32,
+    /// Offset of the serialized block; the 4-byte size precedes it
+    pub data_pos: u32,
+    /// Block hash, internal byte order
+    pub hash: [u8; 32],
+}
+
+impl BlockLocation {
+    /// `blk00042.dat`
+    pub fn file_name(&self) -> String {
+        format!("blk{:05}.dat", self.file)
+    }
+}
+
+/// Position of a block's undo data (`CBlockUndo`) in Core's `rev*.dat` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoLocation {
+    /// `rev<file>.dat`
+    pub file: u32,
+    /// Offset of the undo data; the 4-byte size precedes it, the checksum follows it
+    pub undo_pos: u32,
+    /// Parent block hash (internal byte order), which the undo checksum commits to
+    pub prev_hash: [u8; 32],
+}
+
+impl UndoLocation {
+    /// `rev00042.dat`
+    pub fn file_name(&self) -> String {
+        format!("rev{:05}.dat", self.file)
+    }
+}
+
+/// Active chain from Core's block index: height -> block location.
+#[derive(Debug, Clone)]
+pub struct CoreBlockIndex {
+    /// `None` where the block data was pruned
+    by_height: Vec<Option<BlockLocation>>,
+    /// `None` where there is no undo data (genesis, pruned)
+    undo_by_height: Vec<Option<UndoLocation>>,
+    /// Transactions per block; empty when built without Core's index
+    tx_counts: Vec<u32>,
+    /// Records read, including stale branches and header-only entries
+    entries: usize,
+}
+
+impl CoreBlockIndex {
+    /// Read `<data_dir>/blocks/index`.
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        Self::open_index_dir(&data_dir.join("blocks").join("index"))
+    }
+
+    /// Read a LevelDB block index directory.
+    pub fn open_index_dir(index_dir: &Path) -> Result<Self> {
+        let records = read_block_index_records(index_dir)?;
+        let index = Self::from_records(records.into_values())?;
+        println!(
+            "🗂️  Core block index: {} entries, active chain tip {} ({})",
+            index.entries,
+            index.tip_height(),
+            index_dir.display()
+        );
+        Ok(index)
+    }
+
+    /// Build the active chain from decoded records.
+    pub fn from_records(records: impl IntoIterator<Item = DiskBlockIndex>) -> Result<Self> {
+        let by_hash: HashMap<[u8; 32], DiskBlockIndex> =
+            records.into_iter().map(|r| (r.hash, r)).collect();
+        let tip = by_hash
+            .values()
+            .filter(|r| r.is_valid())
+            .max_by_key(|r| r.height)
+            .context("Block index has no fully validated block")?;
+
+        let mut by_height = vec![None; tip.height as usize + 1];
+        let mut undo_by_height = vec![None; tip.height as usize + 1];
+        let mut tx_counts = vec![0; tip.height as usize + 1];
+        let mut current = tip;
+        loop {
+            by_height[current.height as usize] = current.location();
+            undo_by_height[current.height as usize] = current.undo_location();
+            tx_counts[current.height as usize] = current.n_tx;
+            if current.height == 0 {
+                break;
+            }
+            let prev = by_hash.get(&current.prev_hash()).with_context(|| {
+                format!(
+                    "Block index is missing the parent of height {}",
+                    current.height
+                )
+            })?;
+            anyhow::ensure!(
+                prev.height + 1 == current.height,
+                "Block index parent of height {} is at height {}",
+                current.height,
+                prev.height
+            );
+            current = prev;
+        }
+        Ok(Self {
+            by_height,
+            undo_by_height,
+            tx_counts,
+            entries: by_hash.len(),
+        })
+    }
+
+    /// Index over an already chained list of block locations (height 0 first), as built without
+    /// Core's index by [`crate::header_index`]. There is no undo data to locate.
+    pub fn from_chain(locations: Vec<BlockLocation>, entries: usize) -> Result<Self> {
+        anyhow::ensure!(!locations.is_empty(), "Chain has no blocks");
+        Ok(Self {
+            undo_by_height: vec![None; locations.len()],
+            tx_counts: Vec::new(),
+            by_height: locations.into_iter().map(Some).collect(),
+            entries,
+        })
+    }
+
+    pub fn tip_height(&self) -> u64 {
+        self.by_height.len() as u64 - 1
+    }
+
+    /// Location of the active-chain block at `height`; `None` above the tip or if pruned.
+    pub fn location(&self, height: u64) -> Option<BlockLocation> {
+        self.by_height.get(height as usize).copied().flatten()
+    }
+
+    /// Undo data location of the active-chain block at `height`.
+    pub fn undo_location(&self, height: u64) -> Option<UndoLocation> {
+        self.undo_by_height.get(height as usize).copied().flatten()
+    }
+
+    /// Transaction count of the active-chain block at `height`, where Core recorded it.
+    pub fn tx_count(&self, height: u64) -> Option<u32> {
+        self.tx_counts
+            .get(height as usize)
+            .copied()
+            .filter(|&n| n > 0)
+    }
+
+    /// Number of index records (all branches).
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+}
+
+/// LevelDB varint (little-endian base-128).
+fn read_leveldb_varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut n: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).context("truncated varint")?;
+        *pos += 1;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    anyhow::bail!("varint longer than 10 bytes")
+}
+
+fn read_slice<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let slice = buf
+        .get(*pos..*pos + len)
+        .context("length runs past the end of the buffer")?;
+    *pos += len;
+    Ok(slice)
+}
+
+/// Newest value per user key; `None` = deleted.
+type Latest = HashMap<Vec<u8>, (u64, Option<Vec<u8>>)>;
+
+fn record(latest: &mut Latest, key: &[u8], seq: u64, value: Option<&[u8]>) {
+    if key.first() != Some(&DB_BLOCK_INDEX) || key.len() != 33 {
+        return;
+    }
+    match latest.get(key) {
+        Some((existing, _)) if *existing >= seq => {}
+        _ => {
+            latest.insert(key.to_vec(), (seq, value.map(<[u8]>::to_vec)));
+        }
+    }
+}
+
+/// Walk the entries of one table block (prefix-compressed keys, restart array at the end).
+fn for_each_entry(block: &[u8], mut f: impl FnMut(&[u8], &[u8]) -> Result<()>) -> Result<()> {
+    anyhow::ensure!(block.len() >= 4, "table block too short");
+    let num_restarts = u32::from_le_bytes(block[block.len() - 4..].try_into()?) as usize;
+    let entries_end = (block.len() - 4)
+        .checked_sub(num_restarts * 4)
+        .context("table block restart array larger than block")?;
+    let mut pos = 0;
+    let mut key = Vec::new();
+    while pos < entries_end {
+        let shared = read_leveldb_varint(block, &mut pos)? as usize;
+        let non_shared = read_leveldb_varint(block, &mut pos)? as usize;
+        let value_len = read_leveldb_varint(block, &mut pos)? as usize;
+        anyhow::ensure!(
+            shared <= key.len(),
+            "shared key prefix longer than previous key"
+        );
+        key.truncate(shared);
+        key.extend_from_slice(read_slice(block, &mut pos, non_shared)?);
+        let value = read_slice(block, &mut pos, value_len)?;
+        f(&key, value)?;
+    }
+    Ok(())
+}
+
+/// Contents of the table block at `handle` (`varint offset, varint size`).
+fn table_block<'a>(table: &'a [u8], handle: &[u8]) -> Result<&'a [u8]> {
+    let mut pos = 0;
+    let offset = read_leveldb_varint(handle, &mut pos)? as usize;
+    let size = read_leveldb_varint(handle, &mut pos)? as usize;
+    let trailer = table
+        .get(offset + size..offset + size + BLOCK_TRAILER_LEN)
+        .context("table block handle points past the end of the file")?;
+    anyhow::ensure!(
+        trailer[0] == 0,
+        "compressed table block (type {}) - Core writes uncompressed tables",
+        trailer[0]
+    );
+    Ok(&table[offset..offset + size])
+}
+
+/// Split an internal key into user key, sequence number and type (1 = value, 0 = deletion).
+fn split_internal_key(key: &[u8]) -> Result<(&[u8], u64, u8)> {
+    anyhow::ensure!(key.len() >= 8, "internal key shorter than its tag");
+    let (user, tag) = key.split_at(key.len() - 8);
+    let tag = u64::from_le_bytes(tag.try_into()?);
+    Ok((user, tag >> 8, (tag & 0xff) as u8))
+}
+
+fn read_table(data: &[u8], latest: &mut Latest) -> Result<()> {
+    anyhow::ensure!(data.len() >= FOOTER_LEN, "table shorter than its footer");
+    let footer = &data[data.len() - FOOTER_LEN..];
+    anyhow::ensure!(
+        u64::from_le_bytes(footer[40..48].try_into()?) == TABLE_MAGIC,
+        "bad table magic"
+    );
+    let mut pos = 0;
+    // Metaindex handle (filters) is not needed
+    read_leveldb_varint(footer, &mut pos)?;
+    read_leveldb_varint(footer, &mut pos)?;
+    let index = table_block(data, &footer[pos..])?;
+    for_each_entry(index, |_, handle| {
+        for_each_entry(table_block(data, handle)?, |key, value| {
+            let (user_key, seq, kind) = split_internal_key(key)?;
+            record(latest, user_key, seq, (kind == 1).then_some(value));
+            Ok(())
+        })
+    })
+}
+
+/// Apply one `WriteBatch` (`seq u64, count u32, records`).
+fn apply_write_batch(batch: &[u8], latest: &mut Latest) -> Result<()> {
+    anyhow::ensure!(batch.len() >= 12, "write batch shorter than its header");
+    let seq = u64::from_le_bytes(batch[0..8].try_into()?);
+    let count = u32::from_le_bytes(batch[8..12].try_into()?);
+    let mut pos = 12;
+    for i in 0..u64::from(count) {
+        let kind = *batch.get(pos).context("truncated write batch")?;
+        pos += 1;
+        let key_len = read_leveldb_varint(batch, &mut pos)? as usize;
+        let key = read_slice(batch, &mut pos, key_len)?;
+        match kind {
+            1 => {
+                let value_len = read_leveldb_varint(batch, &mut pos)? as usize;
+                let value = read_slice(batch, &mut pos, value_len)?;
+                record(latest, key, seq + i, Some(value));
+            }
+            0 => record(latest, key, seq + i, None),
+            other => anyhow::bail!("unknown write batch record type {}", other),
+        }
+    }
+    Ok(())
+}
+
+/// Replay a write-ahead log. Stops quietly at a truncated or unknown record (Core may be
+/// writing the tail right now).
+fn read_log(data: &[u8], latest: &mut Latest) -> Result<()> {
+    let mut pos = 0;
+    let mut pending: Vec<u8> = Vec::new();
+    while pos + LOG_HEADER_LEN <= data.len() {
+        let block_left = LOG_BLOCK_SIZE - pos % LOG_BLOCK_SIZE;
+        if block_left < LOG_HEADER_LEN {
+            pos += block_left;
+            continue;
+        }
+        let len = u16::from_le_bytes([data[pos + 4], data[pos + 5]]) as usize;
+        let kind = data[pos + 6];
+        if kind == 0 && len == 0 {
+            // Zero padding at the end of a block or a preallocated tail
+            pos += block_left;
+            continue;
+        }
+        let start = pos + LOG_HEADER_LEN;
+        let Some(fragment) = data.get(start..start + len) else {
+            break;
+        };
+        pos = start + len;
+        match kind {
+            1 => apply_write_batch(fragment, latest)?,
+            2 => {
+                pending.clear();
+                pending.extend_from_slice(fragment);
+            }
+            3 => pending.extend_from_slice(fragment),
+            4 => {
+                pending.extend_from_slice(fragment);
+                apply_write_batch(&pending, latest)?;
+                pending.clear();
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// All live `'b'` records of the block index, keyed by user key.
+fn read_block_index_records(index_dir: &Path) -> Result<HashMap<[u8; 32], DiskBlockIndex>> {
+    let entries = std::fs::read_dir(index_dir)
+        .with_context(|| format!("Failed to read block index {}", index_dir.display()))?;
+    let mut latest = Latest::new();
+    let mut tables = 0;
+    for entry in entries {
+        let path = entry?.path();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !matches!(ext, "ldb" | "sst" | "log") {
+            continue;
+        }
+        let data =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if ext == "log" {
+            read_log(&data, &mut latest)
+        } else {
+            tables += 1;
+            read_table(&data, &mut latest)
+        }
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    }
+    anyhow::ensure!(
+        tables > 0 || !latest.is_empty(),
+        "No LevelDB tables or logs in {}",
+        index_dir.display()
+    );
+
+    let mut records = HashMap::with_capacity(latest.len());
+    for (key, (_, value)) in latest {
+        let Some(value) = value else {
+            continue;
+        };
+        let hash: [u8; 32] = key[1..].try_into()?;
+        records.insert(hash, DiskBlockIndex::decode(hash, &value)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn core_varint(mut n: u64) -> Vec<u8> {
+        let mut tmp = Vec::new();
+        let mut first = true;
+        loop {
+            tmp.push((n & 0x7f) as u8 | if first { 0 } else { 0x80 });
+            first = false;
+            if n <= 0x7f {
+                break;
+            }
+            n = (n >> 7) - 1;
+        }
+        tmp.reverse();
+        tmp
+    }
+
+    fn record_value(height: u64, status: u64, file: u64, pos: u64, prev: [u8; 32]) -> Vec<u8> {
+        let mut v = Vec::new();
+        for n in [259_900, height, status, 1, file, pos] {
+            v.extend(core_varint(n));
+        }
+        let mut header = [0u8; 80];
+        header[4..36].copy_from_slice(&prev);
+        v.extend_from_slice(&header);
+        v
+    }
+
+    #[test]
+    fn core_varint_matches_serialize_h() {
+        for (n, bytes) in [
+            (0u64, vec![0x00]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x00]),
+        ] {
+            assert_eq!(core_varint(n), bytes);
+            assert_eq!(wire::read_varint(&bytes, &mut 0).unwrap(), n);
+        }
+        assert_eq!(wire::read_varint(&[0xff, 0x7f], &mut 0).unwrap(), 16511);
+    }
+
+    #[test]
+    fn log_replay_builds_active_chain_and_skips_stale_branch() {
+        let valid = u64::from(BLOCK_VALID_SCRIPTS | BLOCK_HAVE_DATA);
+        let hashes: Vec<[u8; 32]> = (1..=4u8).map(|i| [i; 32]).collect();
+        let records = [
+            (hashes[0], record_value(0, valid, 0, 8, [0; 32])),
+            (hashes[1], record_value(1, valid, 0, 300, hashes[0])),
+            (hashes[2], record_value(2, valid, 1, 8, hashes[1])),
+            // Stale sibling of height 2 that never fully validated
+            (
+                hashes[3],
+                record_value(2, 3 | u64::from(BLOCK_HAVE_DATA), 1, 900, hashes[1]),
+            ),
+        ];
+        let mut batch = 7u64.to_le_bytes().to_vec();
+        batch.extend((records.len() as u32).to_le_bytes());
+        for (hash, value) in &records {
+            batch.push(1);
+            batch.push(33);
+            batch.push(DB_BLOCK_INDEX);
+            batch.extend_from_slice(hash);
+            // LevelDB varint; records are shorter than 128 bytes
+            batch.push(value.len() as u8);
+            batch.extend_from_slice(value);
+        }
+        let mut log = Vec::new();
+        log.extend([0u8; 4]);
+        log.extend((batch.len() as u16).to_le_bytes());
+        log.push(1);
+        log.extend_from_slice(&batch);
+        // Half-written tail is ignored
+        log.extend([0, 0, 0, 0, 0xff, 0x00, 1, 1, 2]);
+
+        let mut latest = Latest::new();
+        read_log(&log, &mut latest).unwrap();
+        let decoded = latest.into_iter().map(|(k, (_, v))| {
+            let hash: [u8; 32] = k[1..].try_into().unwrap();
+            DiskBlockIndex::decode(hash, &v.unwrap()).unwrap()
+        });
+        let index = CoreBlockIndex::from_records(decoded).unwrap();
+        assert_eq!(index.tip_height(), 2);
+        assert_eq!(index.entries(), 4);
+        let tip = index.location(2).unwrap();
+        assert_eq!((tip.file, tip.data_pos, tip.hash), (1, 8, hashes[2]));
+        assert_eq!(tip.file_name(), "blk00001.dat");
+        assert_eq!(index.location(1).unwrap().data_pos, 300);
+        assert!(index.location(3).is_none());
+    }
+}
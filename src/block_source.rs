@@ -0,0 +1,216 @@
+//! Pluggable block sources
+//!
+//! [`BlockSource`] is everything checkpoint generation and chunk validation need from wherever
+//! blocks come from: a block by height, the tip height and a sequential read. The built-in
+//! sources (block files, the shared chunk cache, Core RPC, remote-Core RPC, a P2P peer) implement
+//! it and are bundled in [`BlockDataSource`]; anything else — an Esplora API, a container node, a custom archive —
+//! implements the trait and is passed to [`validate_chunk`](crate::parallel_differential::validate_chunk) /
+//! [`run_parallel_differential`](crate::parallel_differential::run_parallel_differential) as is.
+
+use anyhow::Result;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::future::Future;
+
+use crate::block_file_reader::BlockFileReader;
+use crate::cancel::CancellationToken;
+use crate::container_rpc_client::ContainerRpcClient;
+use crate::core_rpc_client::CoreRpcClient;
+use crate::parallel_differential::BlockDataSource;
+use crate::remote_core_rpc::RemoteCoreRpcClient;
+
+/// A source of raw serialized blocks on one chain.
+pub trait BlockSource: Send + Sync {
+    /// Block at `height`.
+    fn get_block(&self, height: u64) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Best chain height, or `None` where the source cannot tell (callers then trust the
+    /// requested end height).
+    fn get_tip_height(&self) -> impl Future<Output = Result<Option<u64>>> + Send;
+
+    /// `count` blocks from `start_height` up, in height order, stopping with
+    /// [`Cancelled`](crate::cancel::Cancelled) once `cancel` fires.
+    ///
+    /// The default fetches one height at a time through [`get_block`](Self::get_block); sources
+    /// with a cheaper sequential path (block files) override it.
+    fn iter_sequential(
+        &self,
+        start_height: u64,
+        count: u64,
+        cancel: &CancellationToken,
+    ) -> Result<BoxStream<'_, Result<Vec<u8>>>> {
+        Ok(fetch_sequential(self, start_height, count, cancel))
+    }
+
+    /// Whether the Core node behind this source knows the block (`block_hash` in RPC byte
+    /// order). `None` when there is no node to ask; the block then counts as valid for Core.
+    fn core_has_block(&self, block_hash: &str) -> impl Future<Output = Option<bool>> + Send {
+        let _ = block_hash;
+        async { None }
+    }
+
+    /// Transactions in the block at `height` if the source knows without reading the block
+    /// (Core's block index); used to balance [chunk scheduling](crate::chunk_schedule).
+    fn tx_count(&self, height: u64) -> Option<u32> {
+        let _ = height;
+        None
+    }
+}
+
+impl BlockSource for BlockFileReader {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        // Random access needs Core's block index (`blocks/index`) or, for XOR-packaged trees,
+        // the header index; without either this bails
+        self.read_block_by_height(height)
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        Ok(self.chain_index().map(|index| index.tip_height()))
+    }
+
+    fn tx_count(&self, height: u64) -> Option<u32> {
+        self.core_block_index()?.tx_count(height)
+    }
+
+    fn iter_sequential(
+        &self,
+        start_height: u64,
+        count: u64,
+        cancel: &CancellationToken,
+    ) -> Result<BoxStream<'_, Result<Vec<u8>>>> {
+        let reader = self.clone().with_cancellation(cancel.clone());
+        // Reads run ahead on a background thread while the caller validates
+        let blocks = reader
+            .read_blocks_sequential(Some(start_height), Some(count as usize))?
+            .prefetched();
+        Ok(stream::iter(blocks).boxed())
+    }
+}
+
+impl BlockSource for CoreRpcClient {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        let block_hash = self.getblockhash(height).await?;
+        let block_hex = self.getblock_raw(&block_hash).await?;
+        Ok(hex::decode(&block_hex)?)
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        Ok(Some(self.getblockcount().await?))
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        Some(self.getblock(block_hash, 1).await.is_ok())
+    }
+}
+
+impl BlockSource for ContainerRpcClient {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        self.client().get_block(height).await
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        self.client().get_tip_height().await
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        self.client().core_has_block(block_hash).await
+    }
+}
+
+impl BlockSource for RemoteCoreRpcClient {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        let block_hash = self.get_block_hash(height).await?;
+        let block_hex = self.get_block_hex(&block_hash).await?;
+        Ok(hex::decode(&block_hex)?)
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        Ok(Some(self.get_block_count().await?))
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        Some(self.get_block_hex(block_hash).await.is_ok())
+    }
+}
+
+impl BlockSource for BlockDataSource {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        match self {
+            BlockDataSource::DirectFile(reader) => reader.get_block(height).await,
+            BlockDataSource::SharedCache(cache, rpc_client) => {
+                cache
+                    .get_or_fetch_block(height, rpc_client.as_deref())
+                    .await
+            }
+            BlockDataSource::Rpc(client) => client.get_block(height).await,
+            BlockDataSource::RemoteCoreRpc(client) => client.get_block(height).await,
+            BlockDataSource::P2p(source) => source.get_block(height).await,
+            BlockDataSource::Esplora(source) => source.get_block(height).await,
+        }
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        match self {
+            BlockDataSource::DirectFile(reader) => reader.get_tip_height().await,
+            BlockDataSource::SharedCache(_, Some(client)) | BlockDataSource::Rpc(client) => {
+                client.get_tip_height().await
+            }
+            BlockDataSource::SharedCache(_, None) => Ok(None),
+            BlockDataSource::RemoteCoreRpc(client) => client.get_tip_height().await,
+            BlockDataSource::P2p(source) => source.get_tip_height().await,
+            BlockDataSource::Esplora(source) => source.get_tip_height().await,
+        }
+    }
+
+    fn iter_sequential(
+        &self,
+        start_height: u64,
+        count: u64,
+        cancel: &CancellationToken,
+    ) -> Result<BoxStream<'_, Result<Vec<u8>>>> {
+        match self {
+            BlockDataSource::DirectFile(reader) => {
+                reader.iter_sequential(start_height, count, cancel)
+            }
+            // Per-height fetches through `get_block` (cache hits, or one RPC round trip each)
+            _ => Ok(fetch_sequential(self, start_height, count, cancel)),
+        }
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        match self {
+            BlockDataSource::DirectFile(_) | BlockDataSource::SharedCache(_, None) => None,
+            BlockDataSource::SharedCache(_, Some(client)) | BlockDataSource::Rpc(client) => {
+                client.core_has_block(block_hash).await
+            }
+            BlockDataSource::RemoteCoreRpc(client) => client.core_has_block(block_hash).await,
+            BlockDataSource::P2p(source) => source.core_has_block(block_hash).await,
+            BlockDataSource::Esplora(source) => source.core_has_block(block_hash).await,
+        }
+    }
+
+    fn tx_count(&self, height: u64) -> Option<u32> {
+        match self {
+            BlockDataSource::DirectFile(reader) => reader.tx_count(height),
+            _ => None,
+        }
+    }
+}
+
+/// One [`get_block`](BlockSource::get_block) per height, checking `cancel` before each.
+pub fn fetch_sequential<'a, S: BlockSource + ?Sized>(
+    source: &'a S,
+    start_height: u64,
+    count: u64,
+    cancel: &CancellationToken,
+) -> BoxStream<'a, Result<Vec<u8>>> {
+    let cancel = cancel.clone();
+    stream::iter(start_height..start_height + count)
+        .then(move |height| {
+            let cancel = cancel.clone();
+            async move {
+                crate::cancel::check(&cancel, || format!("block read at height {height}"))?;
+                source.get_block(height).await
+            }
+        })
+        .boxed()
+}
@@ -0,0 +1,307 @@
+//! Per-block chain statistics from differential runs
+//!
+//! A validation pass already parses every block and has the coins each one spends, which is all
+//! a chain dataset needs. With `BLVM_BLOCK_STATS=<file>`, the differential runner registers a
+//! [`BlockStatsHook`] that records one [`BlockStats`] row per block (size, weight, transaction /
+//! input / output counts, subsidy, fees, coinbase value and BIP141 sigop cost) and writes the
+//! rows in height order to `<file>` when the run finishes: as Parquet for a `.parquet` file
+//! (with the `parquet` feature), as CSV otherwise (see [`crate::deep_analysis::dataset`]).
+//!
+//! - fees and sigop cost need every prevout: they are left blank when one is missing from the
+//!   UTXO set, e.g. in a chunk that started without a checkpoint
+//! - rows are recorded whatever the verdict; `valid` is BLVM's
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::calculate_tx_id;
+use blvm_protocol::serialization::transaction::serialize_transaction;
+use blvm_protocol::serialization::varint::encode_varint;
+use blvm_protocol::types::OutPoint;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::info;
+
+use crate::block_template::sigop_cost;
+use crate::consensus_compat::get_utxo;
+use crate::deep_analysis::dataset::{self, Column, ColumnType, Dataset, Format, Value};
+use crate::differential::ValidationResult;
+use crate::validation_hooks::{BlockContext, BlockOutcome, ValidationHook};
+use crate::wire::{display_hex, sha256d};
+
+/// Columns of the written table, one per [`BlockStats`] field
+pub const COLUMNS: &[Column] = &[
+    Column::new("height", ColumnType::UInt64),
+    Column::new("hash", ColumnType::Utf8),
+    Column::new("valid", ColumnType::Boolean),
+    Column::new("size", ColumnType::UInt64),
+    Column::new("stripped_size", ColumnType::UInt64),
+    Column::new("weight", ColumnType::UInt64),
+    Column::new("txs", ColumnType::UInt64),
+    Column::new("inputs", ColumnType::UInt64),
+    Column::new("outputs", ColumnType::UInt64),
+    Column::new("subsidy", ColumnType::Int64),
+    Column::new("fees", ColumnType::Int64).nullable(),
+    Column::new("coinbase_value", ColumnType::Int64),
+    Column::new("sigop_cost", ColumnType::UInt64).nullable(),
+];
+
+const WITNESS_SCALE_FACTOR: u64 = 4;
+const HALVING_INTERVAL: u64 = 210_000;
+
+/// One block's row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub height: u64,
+    /// Display-order hex
+    pub hash: String,
+    /// BLVM accepted the block
+    pub valid: bool,
+    /// Serialized size with witnesses
+    pub size: u64,
+    /// Serialized size without witnesses
+    pub stripped_size: u64,
+    pub weight: u64,
+    pub txs: u64,
+    /// Non-coinbase inputs
+    pub inputs: u64,
+    pub outputs: u64,
+    /// Satoshis, as are the other amounts
+    pub subsidy: i64,
+    /// Inputs minus outputs of the non-coinbase transactions; `None` with a prevout missing
+    pub fees: Option<i64>,
+    pub coinbase_value: i64,
+    /// BIP141 sigop cost of all transactions; `None` with a prevout missing
+    pub sigop_cost: Option<u64>,
+}
+
+impl BlockStats {
+    /// Row for the block of `ctx`, with prevouts from its spent coins or earlier transactions.
+    pub fn of(ctx: &BlockContext<'_>, valid: bool) -> Self {
+        let transactions = &ctx.block.transactions;
+        let hash = display_hex(&sha256d(&ctx.block_bytes[..80]));
+        let stripped_size = 80
+            + encode_varint(transactions.len() as u64).len() as u64
+            + transactions
+                .iter()
+                .map(|tx| serialize_transaction(tx).len() as u64)
+                .sum::<u64>();
+        let size = ctx.block_bytes.len() as u64;
+
+        let mut earlier_txs = HashMap::with_capacity(transactions.len());
+        let mut fees = Some(0i64);
+        let mut sigops = Some(0u64);
+        for (tx_index, tx) in transactions.iter().enumerate() {
+            let witnesses = ctx.witnesses.get(tx_index).map_or(&[][..], |w| &w[..]);
+            if tx_index == 0 {
+                sigops = sigops.map(|s| s + sigop_cost(tx, &[], &[]));
+            } else {
+                let mut values = 0i64;
+                let mut scripts: Vec<&[u8]> = Vec::with_capacity(tx.inputs.len());
+                for input in &tx.inputs {
+                    let Some((value, script)) = prevout(ctx, &earlier_txs, &input.prevout) else {
+                        fees = None;
+                        sigops = None;
+                        break;
+                    };
+                    values += value;
+                    scripts.push(script);
+                }
+                let outputs: i64 = tx.outputs.iter().map(|o| o.value).sum();
+                fees = fees.map(|f| f + values - outputs);
+                sigops = sigops.map(|s| s + sigop_cost(tx, witnesses, &scripts));
+            }
+            earlier_txs.insert(calculate_tx_id(tx), tx_index);
+        }
+
+        Self {
+            height: ctx.height,
+            hash,
+            valid,
+            size,
+            stripped_size,
+            weight: stripped_size * (WITNESS_SCALE_FACTOR - 1) + size,
+            txs: transactions.len() as u64,
+            inputs: transactions
+                .iter()
+                .skip(1)
+                .map(|tx| tx.inputs.len() as u64)
+                .sum(),
+            outputs: transactions.iter().map(|tx| tx.outputs.len() as u64).sum(),
+            subsidy: subsidy(ctx.height),
+            fees,
+            coinbase_value: transactions
+                .first()
+                .map_or(0, |tx| tx.outputs.iter().map(|o| o.value).sum()),
+            sigop_cost: sigops,
+        }
+    }
+}
+
+/// Value and scriptPubKey of the output `outpoint`, from the block's spent coins or an earlier
+/// transaction of the block.
+fn prevout<'a>(
+    ctx: &BlockContext<'a>,
+    earlier_txs: &HashMap<[u8; 32], usize>,
+    outpoint: &OutPoint,
+) -> Option<(i64, &'a [u8])> {
+    if let Some(utxo) = get_utxo(ctx.spent_coins, outpoint) {
+        return Some((utxo.value, utxo.script_pubkey.as_ref()));
+    }
+    let &creator = earlier_txs.get(&outpoint.hash)?;
+    let output = ctx.block.transactions[creator]
+        .outputs
+        .get(outpoint.index as usize)?;
+    Some((output.value, output.script_pubkey.as_ref()))
+}
+
+/// Mainnet block subsidy at `height`.
+pub fn subsidy(height: u64) -> i64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 {
+        0
+    } else {
+        5_000_000_000i64 >> halvings
+    }
+}
+
+/// Collects [`BlockStats`] for every validated block.
+pub struct BlockStatsHook {
+    path: PathBuf,
+    rows: Mutex<BTreeMap<u64, BlockStats>>,
+}
+
+impl BlockStatsHook {
+    /// Hook writing to `path` on [`write`](Self::write).
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            rows: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Hook writing to `BLVM_BLOCK_STATS`, if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os("BLVM_BLOCK_STATS")
+            .filter(|path| !path.is_empty())
+            .map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Recorded rows in height order.
+    pub fn rows(&self) -> Vec<BlockStats> {
+        self.rows.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Write the rows recorded so far (replacing the file) and return how many.
+    pub fn write(&self) -> Result<usize> {
+        let rows = self.rows.lock().unwrap();
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        dataset::write_as(&BlockStatsTable(&rows), &tmp, Format::from_path(&self.path))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        info!(
+            "📊 Block stats for {} blocks written to {}",
+            rows.len(),
+            self.path.display()
+        );
+        Ok(rows.len())
+    }
+}
+
+/// Recorded rows as a [`Dataset`] with [`COLUMNS`].
+struct BlockStatsTable<'a>(&'a BTreeMap<u64, BlockStats>);
+
+impl Dataset for BlockStatsTable<'_> {
+    fn name(&self) -> &'static str {
+        "block_stats"
+    }
+
+    fn schema(&self) -> &'static [Column] {
+        COLUMNS
+    }
+
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+        Box::new(self.0.values().map(|s| {
+            vec![
+                s.height.into(),
+                s.hash.as_str().into(),
+                s.valid.into(),
+                s.size.into(),
+                s.stripped_size.into(),
+                s.weight.into(),
+                s.txs.into(),
+                s.inputs.into(),
+                s.outputs.into(),
+                s.subsidy.into(),
+                s.fees.into(),
+                s.coinbase_value.into(),
+                s.sigop_cost.into(),
+            ]
+        }))
+    }
+}
+
+impl ValidationHook for BlockStatsHook {
+    fn name(&self) -> &str {
+        "block-stats"
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        let stats = BlockStats::of(ctx, matches!(outcome.blvm, ValidationResult::Valid));
+        self.rows.lock().unwrap().insert(ctx.height, stats);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+    use blvm_protocol::UtxoSet;
+
+    #[test]
+    fn genesis_row() {
+        let genesis = crate::mock_core::synthetic_chain(0).remove(0);
+        let (block, witnesses) = deserialize_block_with_witnesses(&genesis).unwrap();
+        let spent_coins = UtxoSet::default();
+        let ctx = BlockContext {
+            height: 0,
+            block: &block,
+            block_bytes: &genesis,
+            witnesses: &witnesses,
+            spent_coins: &spent_coins,
+            utxo_set: None,
+        };
+        let stats = BlockStats::of(&ctx, true);
+        assert_eq!(
+            stats.hash,
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(
+            (stats.size, stats.stripped_size, stats.weight),
+            (285, 285, 1140)
+        );
+        assert_eq!((stats.txs, stats.inputs, stats.outputs), (1, 0, 1));
+        assert_eq!(stats.fees, Some(0));
+        assert_eq!(stats.coinbase_value, stats.subsidy);
+        // One OP_CHECKSIG in the coinbase output
+        assert_eq!(stats.sigop_cost, Some(4));
+        assert_eq!(subsidy(840_000), 312_500_000);
+
+        let dir = tempfile::tempdir().unwrap();
+        let hook = BlockStatsHook::new(dir.path().join("stats/blocks.csv"));
+        hook.rows.lock().unwrap().insert(0, stats);
+        assert_eq!(hook.write().unwrap(), 1);
+        let csv = std::fs::read_to_string(hook.path()).unwrap();
+        assert!(csv.starts_with("height,hash,valid,"));
+        assert!(csv.ends_with(",1,0,1,5000000000,0,5000000000,4\n"));
+    }
+}
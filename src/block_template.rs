@@ -0,0 +1,563 @@
+//! `getblocktemplate` differential
+//!
+//! Block construction is the one path no other module compares: Core's `BlockAssembler` and
+//! BLVM's `create_new_block` both pick mempool transactions under the weight and sigop limits,
+//! and a policy or accounting mismatch (fee, sigop cost, weight) changes what gets in.
+//! [`compare_block_template`] asks Core for `getblocktemplate`, hands BLVM the same mempool and
+//! tip, and diffs transaction selection, total fees, coinbase value, sigop cost and weight. Each
+//! transaction Core selects also has its reported `fee` / `sigops` / `weight` checked against the
+//! bench's own [`TxAccounting`], so an accounting difference is told apart from a selection one.
+//!
+//! Meant for regtest, where Core serves templates without peers; [`populate_mempool`] first fills
+//! a fresh node's mempool with signed P2WPKH / P2TR spends (some with in-mempool children) at
+//! varying fee rates. BLVM is given every coin the mempool spends, in-mempool parents' outputs
+//! included (as if created in the block being built), so CPFP children are candidates on both
+//! sides and an `only_core` / `only_blvm` entry is a real selection difference.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::calculate_tx_id;
+use blvm_protocol::mining::create_new_block;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::block::{
+    deserialize_block_header, deserialize_block_with_witnesses,
+};
+use blvm_protocol::serialization::transaction::serialize_transaction;
+use blvm_protocol::serialization::varint::encode_varint;
+use blvm_protocol::types::{BlockHeader, OutPoint, Transaction, UtxoSet, UTXO};
+use blvm_protocol::{tx_inputs, tx_outputs, TransactionInput, TransactionOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::block_file_reader::Network;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::script_resources::{is_p2sh, last_push, sigop_count};
+use crate::wallet::{mine_to_key, serialize_signed, AddressKind, Wallet};
+use crate::wire::display_hex;
+
+const COINBASE_MATURITY: u64 = 100;
+const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// Fee, sigop cost and weight of one transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxAccounting {
+    /// Satoshis
+    pub fee: i64,
+    /// BIP141 sigop cost (legacy and P2SH sigops count 4 each)
+    pub sigops: u64,
+    pub weight: u64,
+}
+
+/// Totals over one template's non-coinbase transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateTotals {
+    pub txs: usize,
+    pub fees: i64,
+    pub sigops: u64,
+    pub weight: u64,
+    /// Subsidy plus claimed fees
+    pub coinbase_value: i64,
+}
+
+impl TemplateTotals {
+    fn add(&mut self, tx: &TxAccounting) {
+        self.txs += 1;
+        self.fees += tx.fee;
+        self.sigops += tx.sigops;
+        self.weight += tx.weight;
+    }
+}
+
+/// A transaction Core's template reports differently from the bench's accounting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountingMismatch {
+    pub txid: String,
+    pub core: TxAccounting,
+    pub bench: TxAccounting,
+}
+
+/// Core's template against BLVM's for the same tip and mempool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateDiff {
+    pub height: u64,
+    pub mempool_txs: usize,
+    pub core: TemplateTotals,
+    pub blvm: TemplateTotals,
+    /// Selected by Core only (txids)
+    pub only_core: Vec<String>,
+    /// Selected by BLVM only (txids)
+    pub only_blvm: Vec<String>,
+    pub accounting_mismatches: Vec<AccountingMismatch>,
+}
+
+impl TemplateDiff {
+    /// Same selection, same totals, and Core's per-transaction numbers match the bench's.
+    pub fn matches(&self) -> bool {
+        self.only_core.is_empty()
+            && self.only_blvm.is_empty()
+            && self.accounting_mismatches.is_empty()
+            && self.core == self.blvm
+    }
+}
+
+/// A mempool transaction with what the bench derives for it.
+struct MempoolTx {
+    tx: Transaction,
+    accounting: TxAccounting,
+}
+
+/// A coin a mempool transaction spends.
+pub(crate) struct Prevout {
+    pub(crate) value: i64,
+    pub(crate) script_pubkey: Vec<u8>,
+    /// Height and coinbase flag when confirmed; `None` for an output of another mempool tx
+    pub(crate) confirmed: Option<(u64, bool)>,
+}
+
+/// Compare Core's `getblocktemplate` with BLVM's block assembly over Core's current mempool.
+pub async fn compare_block_template(client: &NodeRpcClient) -> Result<TemplateDiff> {
+    let template = client.getblocktemplate().await?;
+    let height = template["height"]
+        .as_u64()
+        .context("getblocktemplate result has no `height`")?;
+    let prev_hash = template["previousblockhash"]
+        .as_str()
+        .context("getblocktemplate result has no `previousblockhash`")?
+        .to_string();
+
+    let txids = client.getrawmempool().await?;
+    let mut raws = Vec::with_capacity(txids.len());
+    for txid in &txids {
+        raws.push(hex::decode(client.getrawtransaction(txid).await?.trim())?);
+    }
+    let (txs, witnesses) = parse_transactions(&raws)?;
+
+    // Prevouts: other mempool transactions' outputs first, then the confirmed set
+    let mut mempool_outputs: HashMap<OutPoint, (i64, Vec<u8>)> = HashMap::new();
+    for tx in &txs {
+        let hash = calculate_tx_id(tx);
+        for (index, output) in tx.outputs.iter().enumerate() {
+            mempool_outputs.insert(
+                OutPoint {
+                    hash,
+                    index: index as _,
+                },
+                (output.value, output.script_pubkey.to_vec()),
+            );
+        }
+    }
+    let mut utxo_set = UtxoSet::default();
+    let mut mempool = HashMap::with_capacity(txs.len());
+    for ((tx, witnesses), raw) in txs.into_iter().zip(witnesses).zip(&raws) {
+        let mut prevouts = Vec::with_capacity(tx.inputs.len());
+        for input in tx.inputs.iter() {
+            let prevout = match mempool_outputs.get(&input.prevout) {
+                Some((value, script_pubkey)) => Prevout {
+                    value: *value,
+                    script_pubkey: script_pubkey.clone(),
+                    confirmed: None,
+                },
+                None => confirmed_prevout(client, &input.prevout, height)
+                    .await?
+                    .with_context(|| {
+                        format!(
+                            "Mempool spends {}:{}, which is not unspent",
+                            display_hex(&input.prevout.hash),
+                            input.prevout.index
+                        )
+                    })?,
+            };
+            // A parent's output counts as created at the height being built
+            let (coin_height, is_coinbase) = prevout.confirmed.unwrap_or((height, false));
+            crate::consensus_compat::insert_utxo(
+                &mut utxo_set,
+                input.prevout.clone(),
+                UTXO {
+                    value: prevout.value,
+                    script_pubkey: prevout.script_pubkey.clone().into(),
+                    height: coin_height as _,
+                    is_coinbase,
+                },
+            );
+            prevouts.push(prevout);
+        }
+        let accounting = account(&tx, &witnesses, &prevouts, raw.len());
+        mempool.insert(
+            display_hex(&calculate_tx_id(&tx)),
+            MempoolTx { tx, accounting },
+        );
+    }
+
+    // Core's side
+    let mut core = TemplateTotals {
+        coinbase_value: template["coinbasevalue"]
+            .as_i64()
+            .context("getblocktemplate result has no `coinbasevalue`")?,
+        ..Default::default()
+    };
+    let mut core_selected = BTreeSet::new();
+    let mut accounting_mismatches = Vec::new();
+    for entry in template["transactions"]
+        .as_array()
+        .context("getblocktemplate result has no `transactions`")?
+    {
+        let (txid, reported) = template_entry(entry)?;
+        core.add(&reported);
+        if let Some(bench) = mempool.get(&txid).map(|m| m.accounting) {
+            if bench != reported {
+                accounting_mismatches.push(AccountingMismatch {
+                    txid: txid.clone(),
+                    core: reported,
+                    bench,
+                });
+            }
+        }
+        core_selected.insert(txid);
+    }
+
+    // BLVM's side, on the same tip
+    let headers = recent_headers(client, &prev_hash, height).await?;
+    let prev_header = headers.last().context("No previous header")?.clone();
+    let mut coinbase_script = Vec::new();
+    push_height(&mut coinbase_script, height);
+    let candidates: Vec<Transaction> = mempool.values().map(|m| m.tx.clone()).collect();
+    let block = create_new_block(
+        &utxo_set,
+        &candidates,
+        height,
+        &prev_header,
+        &headers,
+        &coinbase_script,
+        &[0x51],
+    )
+    .map_err(|e| anyhow::anyhow!("BLVM block assembly failed: {:?}", e))?;
+    let mut blvm = TemplateTotals {
+        coinbase_value: block
+            .transactions
+            .first()
+            .context("BLVM block has no coinbase")?
+            .outputs
+            .iter()
+            .map(|o| o.value)
+            .sum(),
+        ..Default::default()
+    };
+    let mut blvm_selected = BTreeSet::new();
+    for tx in block.transactions.iter().skip(1) {
+        let txid = display_hex(&calculate_tx_id(tx));
+        if let Some(m) = mempool.get(&txid) {
+            blvm.add(&m.accounting);
+        }
+        blvm_selected.insert(txid);
+    }
+
+    let diff = TemplateDiff {
+        height,
+        mempool_txs: mempool.len(),
+        core,
+        blvm,
+        only_core: core_selected.difference(&blvm_selected).cloned().collect(),
+        only_blvm: blvm_selected.difference(&core_selected).cloned().collect(),
+        accounting_mismatches,
+    };
+    print_diff(&diff);
+    Ok(diff)
+}
+
+fn print_diff(diff: &TemplateDiff) {
+    println!(
+        "📋 Block template at height {} from {} mempool transaction(s)",
+        diff.height, diff.mempool_txs
+    );
+    for (name, t) in [("Core", &diff.core), ("BLVM", &diff.blvm)] {
+        println!(
+            "   {}: {} tx(s), fees {} sat, coinbase {} sat, sigops {}, weight {}",
+            name, t.txs, t.fees, t.coinbase_value, t.sigops, t.weight
+        );
+    }
+    for txid in &diff.only_core {
+        println!("   only Core selected {}", txid);
+    }
+    for txid in &diff.only_blvm {
+        println!("   only BLVM selected {}", txid);
+    }
+    for m in &diff.accounting_mismatches {
+        println!(
+            "   {}: Core reports {:?}, bench computes {:?}",
+            m.txid, m.core, m.bench
+        );
+    }
+    if diff.matches() {
+        println!("✅ Templates match");
+    } else {
+        println!("❌ Templates differ");
+    }
+}
+
+/// `txid` and reported accounting of one `getblocktemplate` transaction.
+fn template_entry(entry: &Value) -> Result<(String, TxAccounting)> {
+    let field = |name: &str| {
+        entry[name]
+            .as_i64()
+            .with_context(|| format!("Template transaction has no `{}`", name))
+    };
+    Ok((
+        entry["txid"]
+            .as_str()
+            .context("Template transaction has no `txid`")?
+            .to_string(),
+        TxAccounting {
+            fee: field("fee")?,
+            sigops: field("sigops")? as u64,
+            weight: field("weight")? as u64,
+        },
+    ))
+}
+
+/// A confirmed coin from `gettxout`, `None` when spent or unknown; `next_height` is the height
+/// of the block being built.
+pub(crate) async fn confirmed_prevout(
+    client: &NodeRpcClient,
+    outpoint: &OutPoint,
+    next_height: u64,
+) -> Result<Option<Prevout>> {
+    let Some(coin) = client
+        .gettxout(&display_hex(&outpoint.hash), outpoint.index as u32)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let value = (coin["value"].as_f64().context("gettxout has no `value`")? * 1e8).round() as i64;
+    let script_pubkey = hex::decode(
+        coin["scriptPubKey"]["hex"]
+            .as_str()
+            .context("gettxout has no `scriptPubKey.hex`")?,
+    )?;
+    let confirmations = coin["confirmations"]
+        .as_u64()
+        .context("gettxout has no `confirmations`")?;
+    Ok(Some(Prevout {
+        value,
+        script_pubkey,
+        confirmed: Some((
+            next_height.saturating_sub(confirmations),
+            coin["coinbase"].as_bool().unwrap_or(false),
+        )),
+    }))
+}
+
+/// Up to 11 headers ending at `tip_hash` (enough for median-time-past), oldest first.
+async fn recent_headers(
+    client: &NodeRpcClient,
+    tip_hash: &str,
+    next_height: u64,
+) -> Result<Vec<BlockHeader>> {
+    let first = next_height.saturating_sub(11);
+    let mut headers = Vec::new();
+    for height in first..next_height {
+        let hash = if height + 1 == next_height {
+            tip_hash.to_string()
+        } else {
+            client.getblockhash(height).await?
+        };
+        let raw = client.getblockheader(&hash, false).await?;
+        let raw = hex::decode(raw.as_str().context("Invalid getblockheader response")?)?;
+        headers.push(
+            deserialize_block_header(&raw)
+                .map_err(|e| anyhow::anyhow!("Invalid header {}: {:?}", hash, e))?,
+        );
+    }
+    Ok(headers)
+}
+
+/// Raw transactions parsed as the body of a block with a zeroed header (the block parser is the
+/// one transaction parser the bench relies on everywhere else).
+fn parse_transactions(raws: &[Vec<u8>]) -> Result<(Vec<Transaction>, Vec<Vec<Witness>>)> {
+    let mut body = vec![0u8; 80];
+    body.extend(encode_varint(raws.len() as u64));
+    for raw in raws {
+        body.extend_from_slice(raw);
+    }
+    let (block, witnesses) = deserialize_block_with_witnesses(&body)
+        .map_err(|e| anyhow::anyhow!("Failed to parse mempool transactions: {:?}", e))?;
+    Ok((block.transactions.to_vec(), witnesses))
+}
+
+/// Fee, BIP141 sigop cost and weight of `tx` (`total_size` is its serialized size with
+/// witnesses).
+fn account(
+    tx: &Transaction,
+    witnesses: &[Witness],
+    prevouts: &[Prevout],
+    total_size: usize,
+) -> TxAccounting {
+    let inputs: i64 = prevouts.iter().map(|p| p.value).sum();
+    let outputs: i64 = tx.outputs.iter().map(|o| o.value).sum();
+    let base_size = serialize_transaction(tx).len() as u64;
+    let scripts: Vec<&[u8]> = prevouts.iter().map(|p| &p.script_pubkey[..]).collect();
+    TxAccounting {
+        fee: inputs - outputs,
+        sigops: sigop_cost(tx, witnesses, &scripts),
+        weight: base_size * (WITNESS_SCALE_FACTOR - 1) + total_size as u64,
+    }
+}
+
+/// `GetTransactionSigOpCost` with P2SH and witness counting on (non-coinbase `tx`;
+/// `prevout_scripts[i]` is the scriptPubKey input `i` spends).
+pub fn sigop_cost(tx: &Transaction, witnesses: &[Witness], prevout_scripts: &[&[u8]]) -> u64 {
+    let legacy: u64 = tx
+        .inputs
+        .iter()
+        .map(|i| sigop_count(&i.script_sig, false) as u64)
+        .chain(
+            tx.outputs
+                .iter()
+                .map(|o| sigop_count(&o.script_pubkey, false) as u64),
+        )
+        .sum();
+    let mut cost = legacy * WITNESS_SCALE_FACTOR;
+    for (index, (input, script_pubkey)) in tx.inputs.iter().zip(prevout_scripts).enumerate() {
+        let mut program: &[u8] = script_pubkey;
+        if is_p2sh(script_pubkey) {
+            if let Some(redeem) = last_push(&input.script_sig) {
+                cost += sigop_count(redeem, true) as u64 * WITNESS_SCALE_FACTOR;
+                program = redeem;
+            }
+        }
+        let witness = witnesses.get(index).map_or(&[][..], |w| &w[..]);
+        cost += witness_sigops(program, witness);
+    }
+    cost
+}
+
+/// `WitnessSigOps`: 1 for P2WPKH, the witness script's accurate count for P2WSH, 0 otherwise.
+fn witness_sigops(program: &[u8], witness: &[Vec<u8>]) -> u64 {
+    let is_program = (4..=42).contains(&program.len())
+        && (program[0] == 0x00 || (0x51..=0x60).contains(&program[0]))
+        && program[1] as usize + 2 == program.len();
+    if !is_program || program[0] != 0x00 {
+        return 0;
+    }
+    match program.len() - 2 {
+        20 => 1,
+        32 => witness
+            .last()
+            .map_or(0, |script| sigop_count(script, true) as u64),
+        _ => 0,
+    }
+}
+
+/// `CScript() << height` (BIP34).
+fn push_height(out: &mut Vec<u8>, height: u64) {
+    match height {
+        0 => out.push(0x00),
+        1..=16 => out.push(0x50 + height as u8),
+        _ => {
+            let mut bytes = height.to_le_bytes().to_vec();
+            while bytes.last() == Some(&0) {
+                bytes.pop();
+            }
+            if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+                bytes.push(0);
+            }
+            out.push(bytes.len() as u8);
+            out.extend(bytes);
+        }
+    }
+}
+
+/// Fill a regtest mempool: mine `count` coinbases to a P2WPKH key (plus 100 blocks to mature
+/// them), then spend each to a P2WPKH + P2TR pair at fee rates from 1 to 20 sat/vB, with every
+/// fifth spend followed by a higher-fee child. Returns the number of transactions sent.
+pub async fn populate_mempool(client: &NodeRpcClient, count: u64) -> Result<usize> {
+    let mut wallet = Wallet::from_seed(b"blvm-bench block template seed", Network::Regtest)?;
+    let funding = wallet.new_key(AddressKind::P2wpkh)?;
+    let funding_script = funding.script_pubkey();
+    let funding_address = funding.address(Network::Regtest);
+    let coins = mine_to_key(client, funding, count).await?;
+    client
+        .generatetoaddress(COINBASE_MATURITY, &funding_address)
+        .await?;
+    let taproot_script = wallet.new_key(AddressKind::P2tr)?.script_pubkey();
+
+    let mut sent = 0;
+    for (i, coin) in coins.iter().enumerate() {
+        // Roughly 150 vB for one P2WPKH input and two outputs
+        let fee = 150 * (1 + i as i64 % 20);
+        let change = coin.value / 2;
+        let tx = Transaction {
+            version: 2,
+            inputs: tx_inputs![TransactionInput {
+                prevout: coin.outpoint.clone(),
+                script_sig: Vec::new(),
+                sequence: 0xffff_fffd,
+            }],
+            outputs: tx_outputs![
+                TransactionOutput {
+                    value: change,
+                    script_pubkey: funding_script.clone(),
+                },
+                TransactionOutput {
+                    value: coin.value - change - fee,
+                    script_pubkey: taproot_script.clone(),
+                }
+            ],
+            lock_time: 0,
+        };
+        let witnesses = wallet.sign(&tx, &[(coin.value, coin.script_pubkey.clone())])?;
+        client
+            .sendrawtransaction(&hex::encode(serialize_signed(&tx, &witnesses)))
+            .await?;
+        sent += 1;
+
+        if i % 5 == 0 {
+            let child_fee = 150 * 30;
+            let child = Transaction {
+                version: 2,
+                inputs: tx_inputs![TransactionInput {
+                    prevout: OutPoint {
+                        hash: calculate_tx_id(&tx),
+                        index: 0,
+                    },
+                    script_sig: Vec::new(),
+                    sequence: 0xffff_fffd,
+                }],
+                outputs: tx_outputs![TransactionOutput {
+                    value: change - child_fee,
+                    script_pubkey: taproot_script.clone(),
+                }],
+                lock_time: 0,
+            };
+            let witnesses = wallet.sign(&child, &[(change, funding_script.clone())])?;
+            client
+                .sendrawtransaction(&hex::encode(serialize_signed(&child, &witnesses)))
+                .await?;
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witness_sigops_by_program() {
+        let p2wpkh = [&[0x00, 0x14][..], &[0u8; 20]].concat();
+        assert_eq!(witness_sigops(&p2wpkh, &[vec![1], vec![2]]), 1);
+
+        // 2-of-3 CHECKMULTISIG witness script counts accurately
+        let script = [&[0x52][..], &[0x21; 1], &[2u8; 33], &[0x53, 0xae]].concat();
+        let p2wsh = [&[0x00, 0x20][..], &[0u8; 32]].concat();
+        assert_eq!(witness_sigops(&p2wsh, &[vec![], script]), 3);
+
+        let p2tr = [&[0x51, 0x20][..], &[0u8; 32]].concat();
+        assert_eq!(witness_sigops(&p2tr, &[vec![0u8; 64]]), 0);
+        assert_eq!(witness_sigops(&[0x51], &[]), 0);
+
+        let mut script_sig = Vec::new();
+        push_height(&mut script_sig, 200);
+        assert_eq!(script_sig, [2, 200, 0]);
+    }
+}
@@ -0,0 +1,46 @@
+//! Cooperative cancellation
+//!
+//! Long-running entry points (checkpoint generation, chunk validation, block collection and the
+//! cache prefetchers) take a [`CancellationToken`] and check it between units of work — a block,
+//! a file, a prefetch request — so a caller can stop a run promptly and leave on-disk state
+//! (temp files, chunk metadata, checkpoints) consistent for resume.
+//!
+//! Cancellation surfaces as a [`Cancelled`] error inside `anyhow::Error`; use
+//! [`is_cancelled`] to tell it apart from real failures.
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Error returned when work stops because its token was cancelled.
+#[derive(Debug, thiserror::Error)]
+#[error("cancelled during {0}")]
+pub struct Cancelled(pub String);
+
+/// `Err(Cancelled)` if `token` has been cancelled. `what` is only evaluated on cancellation.
+pub fn check(token: &CancellationToken, what: impl FnOnce() -> String) -> anyhow::Result<()> {
+    if token.is_cancelled() {
+        return Err(Cancelled(what()).into());
+    }
+    Ok(())
+}
+
+/// True if `err` (or anything in its chain) is a [`Cancelled`].
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.downcast_ref::<Cancelled>().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_reports_cancellation() {
+        let token = CancellationToken::new();
+        assert!(check(&token, || "test".into()).is_ok());
+        token.child_token().cancel();
+        assert!(check(&token, || "test".into()).is_ok());
+        token.cancel();
+        let err = check(&token, || "block 5".into()).unwrap_err();
+        assert!(is_cancelled(&err));
+        assert!(is_cancelled(&err.context("outer")));
+    }
+}
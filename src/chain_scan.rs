@@ -59,6 +59,8 @@ use serde::{Deserialize, Serialize};
 use rustc_hash::FxHashMap;
 use std::collections::HashMap;
 
+use crate::wire::compact_size_len;
+
 // Default limits (BIP-110)
 const MAX_OUTPUT_SCRIPT_SIZE: usize = 34;
 const MAX_OP_RETURN_SIZE: usize = 83;
@@ -71,7 +73,8 @@ pub const TAPROOT_START_HEIGHT: u64 = TAPROOT_ACTIVATION_MAINNET;
 /// First block with Ordinals inscription (Dec 2022). Used for post-inscriptions era stats.
 pub const INSCRIPTIONS_START_HEIGHT: u64 = 767_430;
 
-fn block_era(height: u64) -> &'static str {
+/// Era label for `height`: `pre_segwit`, `segwit`, `taproot` or `inscriptions`.
+pub fn block_era(height: u64) -> &'static str {
     if height >= INSCRIPTIONS_START_HEIGHT {
         "inscriptions"
     } else if height >= TAPROOT_START_HEIGHT {
@@ -121,30 +124,17 @@ fn has_annex_violation(violations: &[OutputSizeViolation]) -> bool {
     violations.iter().any(|v| matches!(v, OutputSizeViolation::AnnexPresent))
 }
 
-/// Compact size encoding length (1 byte for < 253)
-fn compact_size_len(n: usize) -> u64 {
-    if n < 0xfd {
-        1
-    } else if n <= 0xffff {
-        3
-    } else if n <= 0xffff_ffff {
-        5
-    } else {
-        9
-    }
-}
-
 /// Compute tx weight (BIP141: 4*base + total). Uses actual script sizes for accuracy.
 fn tx_weight(tx: &Transaction, witnesses: &[Vec<Witness>], tx_idx: usize) -> u64 {
     let mut base_size = 4u64; // version
     for input in &tx.inputs {
         base_size += 32 + 4; // prevout
-        base_size += compact_size_len(input.script_sig.len()) + input.script_sig.len() as u64;
+        base_size += (compact_size_len(input.script_sig.len()) + input.script_sig.len()) as u64;
         base_size += 4; // sequence
     }
     for output in &tx.outputs {
         base_size += 8; // value
-        base_size += compact_size_len(output.script_pubkey.len()) + output.script_pubkey.len() as u64;
+        base_size += (compact_size_len(output.script_pubkey.len()) + output.script_pubkey.len()) as u64;
     }
     base_size += 4; // locktime
     let witness_size: u64 = if tx_idx < witnesses.len() {
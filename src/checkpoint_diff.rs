@@ -0,0 +1,290 @@
+//! UTXO set diff between two checkpoints.
+//!
+//! Compares two [`UtxoSet`]s (typically `utxo_A.bin` and `utxo_B.bin` from
+//! [`CheckpointManager`](crate::checkpoint_persistence::CheckpointManager)) and reports
+//! **added**, **removed** and **changed** outpoints with counts, value sums and a bounded,
+//! deterministic sample per category (lowest `(txid, vout)` first).
+//!
+//! Useful for narrowing a divergence to a chunk, and for checking undo/rollback paths:
+//! diffing `utxo_H` against a rolled-back-then-reapplied `utxo_H` must be empty.
+
+use blvm_protocol::types::{OutPoint, UtxoSet, UTXO};
+use serde::Serialize;
+use std::collections::BinaryHeap;
+
+/// Default number of sample outpoints kept per category.
+pub const DEFAULT_SAMPLE_LIMIT: usize = 20;
+
+/// One outpoint in a diff sample. `txid` is display order (reversed, as in Core RPC).
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffSampleEntry {
+    pub txid: String,
+    pub vout: u32,
+    /// Value in A (`None` for added outpoints).
+    pub value_a: Option<i64>,
+    /// Value in B (`None` for removed outpoints).
+    pub value_b: Option<i64>,
+    pub height_a: Option<u64>,
+    pub height_b: Option<u64>,
+}
+
+/// Counts, value sums and samples for one category (added / removed / changed).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffCategory {
+    pub count: u64,
+    /// Sum of values on the side that has the entry (B for added, A for removed, B − A for changed).
+    pub value_sum: i128,
+    pub sample: Vec<DiffSampleEntry>,
+}
+
+/// Full diff of checkpoint A → checkpoint B.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UtxoSetDiff {
+    pub size_a: u64,
+    pub size_b: u64,
+    pub unchanged: u64,
+    pub added: DiffCategory,
+    pub removed: DiffCategory,
+    pub changed: DiffCategory,
+}
+
+impl UtxoSetDiff {
+    /// True when both sets contain exactly the same outpoints with identical entries.
+    pub fn is_empty(&self) -> bool {
+        self.added.count == 0 && self.removed.count == 0 && self.changed.count == 0
+    }
+}
+
+type SampleKey = ([u8; 32], u32);
+
+/// Keeps the `limit` smallest outpoint keys seen so far (max-heap, evicting the largest).
+struct BoundedSample {
+    limit: usize,
+    heap: BinaryHeap<SampleKey>,
+}
+
+impl BoundedSample {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            heap: BinaryHeap::with_capacity(limit.saturating_add(1)),
+        }
+    }
+
+    fn offer(&mut self, outpoint: &OutPoint) {
+        if self.limit == 0 {
+            return;
+        }
+        let key = (outpoint.hash, outpoint.index);
+        if self.heap.len() >= self.limit && self.heap.peek().is_some_and(|top| key >= *top) {
+            return;
+        }
+        self.heap.push(key);
+        if self.heap.len() > self.limit {
+            self.heap.pop();
+        }
+    }
+
+    /// Resolve kept keys against both sets, ascending by `(txid, vout)`.
+    fn into_entries(self, a: &UtxoSet, b: &UtxoSet) -> Vec<DiffSampleEntry> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(hash, index)| {
+                let outpoint = OutPoint { hash, index };
+                sample_entry(
+                    &outpoint,
+                    a.get(&outpoint).map(|u| &**u),
+                    b.get(&outpoint).map(|u| &**u),
+                )
+            })
+            .collect()
+    }
+}
+
+fn sample_entry(outpoint: &OutPoint, a: Option<&UTXO>, b: Option<&UTXO>) -> DiffSampleEntry {
+    let mut txid = outpoint.hash;
+    txid.reverse();
+    DiffSampleEntry {
+        txid: hex::encode(txid),
+        vout: outpoint.index,
+        value_a: a.map(|u| u.value),
+        value_b: b.map(|u| u.value),
+        height_a: a.map(|u| u.height),
+        height_b: b.map(|u| u.height),
+    }
+}
+
+fn utxo_entries_equal(a: &UTXO, b: &UTXO) -> bool {
+    a.value == b.value
+        && a.height == b.height
+        && a.is_coinbase == b.is_coinbase
+        && a.script_pubkey[..] == b.script_pubkey[..]
+}
+
+/// Diff `a` → `b`, keeping at most `sample_limit` sample entries per category.
+pub fn diff_utxo_sets(a: &UtxoSet, b: &UtxoSet, sample_limit: usize) -> UtxoSetDiff {
+    let mut diff = UtxoSetDiff {
+        size_a: a.len() as u64,
+        size_b: b.len() as u64,
+        ..Default::default()
+    };
+    let mut added = BoundedSample::new(sample_limit);
+    let mut removed = BoundedSample::new(sample_limit);
+    let mut changed = BoundedSample::new(sample_limit);
+
+    for (outpoint, ua) in a.iter() {
+        match b.get(outpoint) {
+            None => {
+                diff.removed.count += 1;
+                diff.removed.value_sum += ua.value as i128;
+                removed.offer(outpoint);
+            }
+            Some(ub) if !utxo_entries_equal(ua, ub) => {
+                diff.changed.count += 1;
+                diff.changed.value_sum += ub.value as i128 - ua.value as i128;
+                changed.offer(outpoint);
+            }
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    for (outpoint, ub) in b.iter() {
+        if !a.contains_key(outpoint) {
+            diff.added.count += 1;
+            diff.added.value_sum += ub.value as i128;
+            added.offer(outpoint);
+        }
+    }
+
+    diff.added.sample = added.into_entries(a, b);
+    diff.removed.sample = removed.into_entries(a, b);
+    diff.changed.sample = changed.into_entries(a, b);
+    diff
+}
+
+/// Human-readable summary (counts, sums, samples) for terminal output.
+pub fn format_utxo_diff(diff: &UtxoSetDiff, label_a: &str, label_b: &str) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "📊 UTXO diff {} ({} entries) → {} ({} entries)\n",
+        label_a, diff.size_a, label_b, diff.size_b
+    ));
+    out.push_str(&format!("   unchanged: {}\n", diff.unchanged));
+    for (name, cat) in [
+        ("added", &diff.added),
+        ("removed", &diff.removed),
+        ("changed", &diff.changed),
+    ] {
+        out.push_str(&format!(
+            "   {:<9} {} (value sum: {} sat)\n",
+            format!("{name}:"),
+            cat.count,
+            cat.value_sum
+        ));
+        for e in &cat.sample {
+            out.push_str(&format!("      {}:{}", e.txid, e.vout));
+            match (e.value_a, e.value_b) {
+                (Some(va), Some(vb)) => out.push_str(&format!(
+                    " value {va} → {vb}, height {} → {}",
+                    e.height_a.unwrap_or_default(),
+                    e.height_b.unwrap_or_default()
+                )),
+                (Some(va), None) => out.push_str(&format!(
+                    " value {va}, height {}",
+                    e.height_a.unwrap_or_default()
+                )),
+                (None, Some(vb)) => out.push_str(&format!(
+                    " value {vb}, height {}",
+                    e.height_b.unwrap_or_default()
+                )),
+                (None, None) => {}
+            }
+            out.push('\n');
+        }
+        if cat.count > cat.sample.len() as u64 {
+            out.push_str(&format!(
+                "      … {} more\n",
+                cat.count - cat.sample.len() as u64
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_sample_keeps_smallest_keys() {
+        let mut s = BoundedSample::new(2);
+        for (b, i) in [(3u8, 0u32), (1, 5), (2, 0), (1, 2)] {
+            s.offer(&OutPoint {
+                hash: [b; 32],
+                index: i,
+            });
+        }
+        assert_eq!(
+            s.heap.into_sorted_vec(),
+            vec![([1u8; 32], 2), ([1u8; 32], 5)]
+        );
+    }
+
+    fn utxo_set(coins: &[(u8, i64, u64)]) -> UtxoSet {
+        let mut set = UtxoSet::default();
+        for &(n, value, height) in coins {
+            let utxo = UTXO {
+                value,
+                script_pubkey: vec![0x51].into(),
+                height,
+                is_coinbase: false,
+            };
+            set.insert(
+                OutPoint {
+                    hash: [n; 32],
+                    index: 0,
+                },
+                std::sync::Arc::new(utxo),
+            );
+        }
+        set
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() {
+        let a = utxo_set(&[(1, 100, 1), (2, 200, 2), (3, 300, 3)]);
+        let b = utxo_set(&[(2, 200, 2), (3, 350, 3), (4, 400, 4), (5, 500, 5)]);
+        let diff = diff_utxo_sets(&a, &b, 1);
+
+        assert_eq!((diff.size_a, diff.size_b, diff.unchanged), (3, 4, 1));
+        assert_eq!((diff.removed.count, diff.removed.value_sum), (1, 100));
+        assert_eq!((diff.changed.count, diff.changed.value_sum), (1, 50));
+        assert_eq!((diff.added.count, diff.added.value_sum), (2, 900));
+        assert!(!diff.is_empty());
+
+        // Samples are bounded and keep the lowest outpoint
+        assert_eq!(diff.added.sample.len(), 1);
+        assert_eq!(diff.added.sample[0].txid, hex::encode([4u8; 32]));
+        assert_eq!(diff.added.sample[0].value_a, None);
+        let changed = &diff.changed.sample[0];
+        assert_eq!((changed.value_a, changed.value_b), (Some(300), Some(350)));
+    }
+
+    #[test]
+    fn diff_of_identical_sets_is_empty() {
+        let a = utxo_set(&[(1, 100, 1), (2, 200, 2)]);
+        let diff = diff_utxo_sets(&a, &a.clone(), DEFAULT_SAMPLE_LIMIT);
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged, 2);
+    }
+
+    #[test]
+    fn bounded_sample_zero_limit_keeps_nothing() {
+        let mut s = BoundedSample::new(0);
+        s.offer(&OutPoint {
+            hash: [0; 32],
+            index: 0,
+        });
+        assert!(s.heap.is_empty());
+    }
+}
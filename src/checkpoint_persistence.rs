@@ -64,6 +64,34 @@ fn write_checkpoint_temp_rename(
     Ok(())
 }
 
+/// Load a checkpoint file at an arbitrary `path` (same autodetect as [`CheckpointManager::load_utxo_checkpoint`]).
+pub fn load_utxo_checkpoint_file(path: &Path) -> Result<UtxoSet> {
+    let mut file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut magic = [0u8; 8];
+    file
+        .read_exact(&mut magic)
+        .with_context(|| format!("read magic {}", path.display()))?;
+
+    if magic == *crate::utxo_snapshot_fixed_v1::FIXED_V1_MAGIC {
+        file.seek(std::io::SeekFrom::Start(0))
+            .with_context(|| format!("seek start {}", path.display()))?;
+        let br = std::io::BufReader::with_capacity(1024 * 1024, file);
+        let set = crate::utxo_snapshot_fixed_v1::decode_fixed_v1_reader(br)
+            .with_context(|| format!("fixed-v1 decode {}", path.display()))?;
+        return Ok(set);
+    }
+
+    let mut data = magic.to_vec();
+    file
+        .read_to_end(&mut data)
+        .with_context(|| format!("read body {}", path.display()))?;
+
+    let raw: HashMap<OutPoint, UTXO> = bincode::deserialize(&data)
+        .with_context(|| format!("bincode deserialize UTXO checkpoint {}", path.display()))?;
+    let set: UtxoSet = raw.into_iter().map(|(k, v)| (k, Arc::new(v))).collect();
+    Ok(set)
+}
+
 /// On-disk checkpoint encoding for **writes** (`--checkpoint-every`, exports). **Loads** always autodetect.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
 pub enum CheckpointFormat {
@@ -116,30 +144,7 @@ impl CheckpointManager {
         if !path.is_file() {
             return Ok(None);
         }
-        let mut file = File::open(&path).with_context(|| format!("open {}", path.display()))?;
-        let mut magic = [0u8; 8];
-        file
-            .read_exact(&mut magic)
-            .with_context(|| format!("read magic {}", path.display()))?;
-
-        if magic == *crate::utxo_snapshot_fixed_v1::FIXED_V1_MAGIC {
-            file.seek(std::io::SeekFrom::Start(0))
-                .with_context(|| format!("seek start {}", path.display()))?;
-            let br = std::io::BufReader::with_capacity(1024 * 1024, file);
-            let set = crate::utxo_snapshot_fixed_v1::decode_fixed_v1_reader(br)
-                .with_context(|| format!("fixed-v1 decode {}", path.display()))?;
-            return Ok(Some(set));
-        }
-
-        let mut data = magic.to_vec();
-        file
-            .read_to_end(&mut data)
-            .with_context(|| format!("read body {}", path.display()))?;
-
-        let raw: HashMap<OutPoint, UTXO> = bincode::deserialize(&data)
-            .with_context(|| format!("bincode deserialize UTXO checkpoint {}", path.display()))?;
-        let set: UtxoSet = raw.into_iter().map(|(k, v)| (k, Arc::new(v))).collect();
-        Ok(Some(set))
+        load_utxo_checkpoint_file(&path).map(Some)
     }
 
     /// Write `utxo_{height}.bin` (UTXO state **after** block `height`) using `format`.
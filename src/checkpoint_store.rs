@@ -0,0 +1,478 @@
+//! On-disk UTXO checkpoints for checkpoint generation
+//!
+//! [`generate_checkpoints`](crate::parallel_differential::generate_checkpoints) saves every chunk
+//! boundary checkpoint to a [`CheckpointStore`] as `checkpoint_<height:09>.utxo.zst` (the UTXO
+//! set after block `height`), and on the next run resumes from the highest stored boundary
+//! instead of replaying the chain from genesis. Parallel chunks load their starting checkpoint
+//! from the store when a worker takes them, so only running chunks hold a set in memory.
+//!
+//! File layout (zstd-compressed as a whole):
+//!
+//! ```text
+//! magic "BLVMCKPT" | version u32 LE | network magic [4] | height u64 LE | block hash [32]
+//! entries u64 LE
+//! entries × bincode (OutPoint, UTXO)
+//! SHA256 of everything above (32 bytes)
+//! ```
+//!
+//! Files are written through a temp file and renamed into place. Loading checks the file against
+//! the store's network and the hash the block source has at that height, so a checkpoint from
+//! another network or a stale fork is reported and skipped when resuming, as is one whose
+//! checksum, version or height does not match.
+//!
+//! The store directory is `BLVM_CHECKPOINT_DIR`, else `checkpoints/` under `BLVM_OUTPUT_DIR` (see
+//! [`checkpoint_dir_from_env`]).
+
+use anyhow::{Context, Result};
+use blvm_protocol::types::{OutPoint, UtxoSet, UTXO};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::block_file_reader::Network;
+use crate::block_source::BlockSource;
+use crate::consensus_compat::{insert_utxo, utxo_entries};
+use crate::wire::display_hex;
+
+const MAGIC: &[u8; 8] = b"BLVMCKPT";
+/// Bump when the header or entry encoding changes; older files are then ignored, not misread.
+pub const FORMAT_VERSION: u32 = 2;
+const FILE_PREFIX: &str = "checkpoint_";
+const FILE_SUFFIX: &str = ".utxo.zst";
+
+/// `BLVM_CHECKPOINT_DIR`, else `checkpoints/` in the output directory ([`crate::artifacts`]).
+pub fn checkpoint_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("BLVM_CHECKPOINT_DIR")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            crate::artifacts::layout_from_env()
+                .map(|layout| layout.kind_dir(crate::artifacts::ArtifactKind::Checkpoints))
+        })
+}
+
+/// Internal-order hash of the block `block_source` has at `height`.
+pub async fn block_hash_at<S: BlockSource + ?Sized>(
+    block_source: &S,
+    height: u64,
+) -> Result<[u8; 32]> {
+    let block = block_source.get_block(height).await?;
+    anyhow::ensure!(
+        block.len() >= 80,
+        "Block {} too small: {} bytes",
+        height,
+        block.len()
+    );
+    Ok(crate::wire::sha256d(&block[..80]))
+}
+
+/// Directory of checkpoint files for one network, one per height.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    dir: PathBuf,
+    network: Network,
+}
+
+impl CheckpointStore {
+    pub fn new(dir: impl AsRef<Path>, network: Network) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create checkpoint dir {}", dir.display()))?;
+        Ok(Self { dir, network })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// `checkpoint_000170000.utxo.zst`
+    pub fn path(&self, height: u64) -> PathBuf {
+        self.dir
+            .join(format!("{}{:09}{}", FILE_PREFIX, height, FILE_SUFFIX))
+    }
+
+    /// Heights with a checkpoint file, ascending (files are not opened).
+    pub fn heights(&self) -> Result<Vec<u64>> {
+        let mut heights: Vec<u64> = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to list {}", self.dir.display()))?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                let name = name.to_str()?;
+                name.strip_prefix(FILE_PREFIX)?
+                    .strip_suffix(FILE_SUFFIX)?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        heights.sort_unstable();
+        Ok(heights)
+    }
+
+    pub fn contains(&self, height: u64) -> bool {
+        self.path(height).is_file()
+    }
+
+    /// Write the UTXO set after block `height`, whose internal-order hash is `block_hash`.
+    pub fn save(&self, height: u64, block_hash: &[u8; 32], utxo_set: &UtxoSet) -> Result<PathBuf> {
+        self.save_entries(
+            height,
+            block_hash,
+            utxo_set.len() as u64,
+            utxo_entries(utxo_set).map(Ok),
+        )
+    }
+
+    /// [`save`](Self::save) from `len` `(OutPoint, UTXO)` entries (owned or borrowed) as they are
+    /// produced, e.g. read from a disk-backed set, without building a `UtxoSet`.
+    pub fn save_entries<E: Serialize>(
+        &self,
+        height: u64,
+        block_hash: &[u8; 32],
+        len: u64,
+        entries: impl IntoIterator<Item = Result<E>>,
+    ) -> Result<PathBuf> {
+        let path = self.path(height);
+        let tmp = path.with_extension("zst.tmp");
+        let result = write_compressed(&tmp, |w| {
+            w.write_all(MAGIC)?;
+            w.write_all(&FORMAT_VERSION.to_le_bytes())?;
+            w.write_all(self.network.magic_bytes())?;
+            w.write_all(&height.to_le_bytes())?;
+            w.write_all(block_hash)?;
+            w.write_all(&len.to_le_bytes())?;
+            let mut written = 0u64;
+            for entry in entries {
+                bincode::serialize_into(&mut *w, &entry?)?;
+                written += 1;
+            }
+            anyhow::ensure!(
+                written == len,
+                "{} entries written, expected {}",
+                written,
+                len
+            );
+            Ok(())
+        });
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e).with_context(|| format!("Failed to write checkpoint {}", height));
+        }
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        Ok(path)
+    }
+
+    /// Read and verify the checkpoint at `height`, which must be on this store's network and
+    /// after block `block_hash`.
+    pub fn load(&self, height: u64, block_hash: &[u8; 32]) -> Result<UtxoSet> {
+        let mut utxo_set = UtxoSet::default();
+        self.read(height, block_hash, |entries, outpoint, utxo| {
+            if utxo_set.is_empty() {
+                utxo_set.reserve(entries.min(1 << 28) as usize);
+            }
+            insert_utxo(&mut utxo_set, outpoint, utxo);
+            Ok(())
+        })?;
+        Ok(utxo_set)
+    }
+
+    /// [`load`](Self::load) without building a `UtxoSet`: `visit` gets every coin as it is read,
+    /// and the number of coins is returned. The checksum is only checked at the end, so a file
+    /// that turns out to be corrupt has already been partly visited.
+    pub fn for_each(
+        &self,
+        height: u64,
+        block_hash: &[u8; 32],
+        mut visit: impl FnMut(OutPoint, UTXO) -> Result<()>,
+    ) -> Result<u64> {
+        self.read(height, block_hash, |_, outpoint, utxo| {
+            visit(outpoint, utxo)
+        })
+    }
+
+    /// Verify the checkpoint at `height` while handing each coin to `visit` with the file's
+    /// entry count.
+    fn read(
+        &self,
+        height: u64,
+        block_hash: &[u8; 32],
+        visit: impl FnMut(u64, OutPoint, UTXO) -> Result<()>,
+    ) -> Result<u64> {
+        let path = self.path(height);
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let decoder = zstd::stream::read::Decoder::new(file)?;
+        let mut reader = HashingReader {
+            inner: BufReader::with_capacity(1024 * 1024, decoder),
+            hasher: Sha256::new(),
+        };
+        let result = read_checkpoint(&mut reader, self.network, height, block_hash, visit);
+        result.with_context(|| format!("Invalid checkpoint {}", path.display()))
+    }
+
+    /// The highest stored checkpoint in `from..=to` that loads and verifies against
+    /// `block_source`'s chain; broken or foreign files are reported and skipped.
+    pub async fn latest_in<S: BlockSource + ?Sized>(
+        &self,
+        from: u64,
+        to: u64,
+        block_source: &S,
+    ) -> Result<Option<(u64, UtxoSet)>> {
+        for height in self.heights()?.into_iter().rev() {
+            if height < from || height > to {
+                continue;
+            }
+            let block_hash = block_hash_at(block_source, height).await?;
+            match self.load(height, &block_hash) {
+                Ok(utxo_set) => return Ok(Some((height, utxo_set))),
+                Err(e) => eprintln!("⚠️  Skipping checkpoint {}: {:#}", height, e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+type CheckpointWriter = HashingWriter<BufWriter<zstd::Encoder<'static, File>>>;
+
+/// Compress what `write_body` writes into `path`, with a trailing SHA256 of it.
+fn write_compressed(
+    path: &Path,
+    write_body: impl FnOnce(&mut CheckpointWriter) -> Result<()>,
+) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut encoder = zstd::Encoder::new(file, 3)?;
+    encoder.multithread(num_cpus::get().min(8) as u32)?;
+    let mut writer = HashingWriter {
+        inner: BufWriter::with_capacity(1024 * 1024, encoder),
+        hasher: Sha256::new(),
+    };
+    write_body(&mut writer)?;
+    let checksum = writer.hasher.finalize_reset();
+    writer.inner.write_all(&checksum)?;
+    let encoder = writer.inner.into_inner().map_err(|e| e.into_error())?;
+    encoder.finish()?.sync_all()?;
+    Ok(())
+}
+
+fn read_checkpoint(
+    reader: &mut HashingReader<impl Read>,
+    network: Network,
+    height: u64,
+    block_hash: &[u8; 32],
+    mut visit: impl FnMut(u64, OutPoint, UTXO) -> Result<()>,
+) -> Result<u64> {
+    // Magic and version first, so an older file is reported as such rather than as too short
+    let mut header = [0u8; 64];
+    reader.read_exact(&mut header[..12])?;
+    anyhow::ensure!(&header[..8] == MAGIC, "not a checkpoint file");
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    anyhow::ensure!(
+        version == FORMAT_VERSION,
+        "format version {} (expected {})",
+        version,
+        FORMAT_VERSION
+    );
+    reader.read_exact(&mut header[12..])?;
+    anyhow::ensure!(
+        &header[12..16] == network.magic_bytes(),
+        "network magic {} (expected {:?}, {})",
+        hex::encode(&header[12..16]),
+        network,
+        hex::encode(network.magic_bytes())
+    );
+    let stored_height = u64::from_le_bytes(header[16..24].try_into().unwrap());
+    anyhow::ensure!(
+        stored_height == height,
+        "holds height {} (expected {})",
+        stored_height,
+        height
+    );
+    let stored_hash: [u8; 32] = header[24..56].try_into().unwrap();
+    anyhow::ensure!(
+        &stored_hash == block_hash,
+        "taken after block {} (expected {})",
+        display_hex(&stored_hash),
+        display_hex(block_hash)
+    );
+    let entries = u64::from_le_bytes(header[56..64].try_into().unwrap());
+
+    for _ in 0..entries {
+        let (outpoint, utxo): (OutPoint, UTXO) = bincode::deserialize_from(&mut *reader)?;
+        visit(entries, outpoint, utxo)?;
+    }
+
+    let expected: [u8; 32] = reader.hasher.finalize_reset().into();
+    let mut stored = [0u8; 32];
+    reader.inner.read_exact(&mut stored)?;
+    anyhow::ensure!(stored == expected, "checksum mismatch");
+    anyhow::ensure!(
+        reader.inner.read(&mut [0u8; 1])? == 0,
+        "trailing data after checksum"
+    );
+    Ok(entries)
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus_compat::get_utxo;
+
+    const HASH: [u8; 32] = [7; 32];
+
+    fn utxo_set() -> UtxoSet {
+        let mut set = UtxoSet::default();
+        for n in 1..=3u8 {
+            let outpoint = OutPoint {
+                hash: [n; 32],
+                index: u32::from(n),
+            };
+            let utxo = UTXO {
+                value: i64::from(n) * 1000,
+                script_pubkey: vec![0x51].into(),
+                height: u64::from(n),
+                is_coinbase: n == 1,
+            };
+            insert_utxo(&mut set, outpoint, utxo);
+        }
+        set
+    }
+
+    /// Decompress the checkpoint at `height`, let `edit` change it and compress it back.
+    fn rewrite(store: &CheckpointStore, height: u64, edit: impl FnOnce(&mut Vec<u8>)) {
+        let path = store.path(height);
+        let mut raw = zstd::decode_all(File::open(&path).unwrap()).unwrap();
+        edit(&mut raw);
+        std::fs::write(&path, zstd::encode_all(&raw[..], 3).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path(), Network::Mainnet).unwrap();
+        let set = utxo_set();
+        store.save(169, &HASH, &set).unwrap();
+
+        assert_eq!(store.heights().unwrap(), [169]);
+        let loaded = store.load(169, &HASH).unwrap();
+        assert_eq!(loaded.len(), set.len());
+        for (outpoint, utxo) in utxo_entries(&set) {
+            let got = get_utxo(&loaded, outpoint).expect("coin missing after load");
+            assert_eq!(
+                (got.value, got.height, got.is_coinbase),
+                (utxo.value, utxo.height, utxo.is_coinbase)
+            );
+        }
+    }
+
+    #[test]
+    fn streamed_entries_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path(), Network::Mainnet).unwrap();
+        let set = utxo_set();
+        let owned: Vec<(OutPoint, UTXO)> = utxo_entries(&set)
+            .map(|(outpoint, utxo)| (outpoint.clone(), UTXO::clone(utxo)))
+            .collect();
+        store
+            .save_entries(169, &HASH, 3, owned.iter().map(Ok))
+            .unwrap();
+
+        let mut values = Vec::new();
+        let count = store
+            .for_each(169, &HASH, |_, utxo| {
+                values.push(utxo.value);
+                Ok(())
+            })
+            .unwrap();
+        values.sort();
+        assert_eq!((count, values), (3, vec![1000, 2000, 3000]));
+
+        let err = store
+            .save_entries(170, &HASH, 4, owned.iter().map(Ok))
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("expected 4"), "{:#}", err);
+        assert!(!store.contains(170));
+    }
+
+    #[test]
+    fn load_rejects_another_chain_or_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path(), Network::Mainnet).unwrap();
+        store.save(169, &HASH, &utxo_set()).unwrap();
+
+        let err = store.load(169, &[8; 32]).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("taken after block"),
+            "{:#}",
+            err
+        );
+
+        let testnet = CheckpointStore::new(dir.path(), Network::Testnet).unwrap();
+        let err = testnet.load(169, &HASH).unwrap_err();
+        assert!(format!("{:#}", err).contains("network magic"), "{:#}", err);
+    }
+
+    #[test]
+    fn load_rejects_corrupt_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path(), Network::Mainnet).unwrap();
+        store.save(169, &HASH, &utxo_set()).unwrap();
+        rewrite(&store, 169, |raw| *raw.last_mut().unwrap() ^= 1);
+
+        let err = store.load(169, &HASH).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("checksum mismatch"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn load_rejects_wrong_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path(), Network::Mainnet).unwrap();
+        store.save(169, &HASH, &utxo_set()).unwrap();
+        rewrite(&store, 169, |raw| {
+            raw[8..12].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes())
+        });
+
+        let err = store.load(169, &HASH).unwrap_err();
+        assert!(format!("{:#}", err).contains("format version"), "{:#}", err);
+    }
+}
@@ -102,7 +102,7 @@ pub fn iterator_eager_height_range(start: u64, end_height: u64) -> Option<std::o
 /// Which `chunk_i.bin.zst` files are absent for `i in 0..num_chunks`.
 pub fn missing_chunk_bin_files(chunks_dir: &Path, num_chunks: usize) -> Vec<usize> {
     (0..num_chunks)
-        .filter(|i| !chunks_dir.join(format!("chunk_{}.bin.zst", i)).exists())
+        .filter(|i| !crate::chunk_manifest::chunk_path(chunks_dir, *i as u64).exists())
         .collect()
 }
 
@@ -230,7 +230,6 @@ pub fn save_block_index_with_options(chunks_dir: &Path, index: &BlockIndex, forc
 pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8; 32], (usize, u64, [u8; 32])>)> {
     use crate::chunked_cache::{load_chunk_metadata, decompress_chunk_streaming};
     use std::io::Read;
-    use std::process::Stdio;
     
     println!("🔨 Building block index from chunks...");
     
@@ -254,10 +253,11 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
     
     // Process each chunk and collect results
     let chunk_results: Vec<_> = (0..metadata.num_chunks).into_par_iter().map(|chunk_num| {
-        let chunk_file = chunks_dir.join(format!("chunk_{}.bin.zst", chunk_num));
+        let chunk_file = crate::chunk_manifest::chunk_path(chunks_dir, chunk_num as u64);
         if !chunk_file.exists() {
             return Ok((chunk_num, Vec::new(), Vec::new(), Vec::<()>::new(), None));
         }
+        crate::chunk_manifest::verify_chunk(chunks_dir, chunk_num as u64)?;
         
         eprintln!("   📦 Processing chunk {}...", chunk_num);
         
@@ -266,10 +266,8 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
         let mut chunk_blocks_by_block_hash = Vec::new();
         let mut chunk_genesis: Option<(usize, u64, [u8; 32])> = None;
         
-        let mut zstd_proc = decompress_chunk_streaming(&chunk_file)?;
-        let stdout = zstd_proc.stdout.take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-        let mut reader = std::io::BufReader::with_capacity(1024 * 1024, stdout); // 1MB buffer
+        let decoder = decompress_chunk_streaming(&chunk_file)?;
+        let mut reader = std::io::BufReader::with_capacity(1024 * 1024, decoder); // 1MB buffer
         
         let mut offset: u64 = 0;
         let mut block_num_in_chunk = 0;
@@ -325,9 +323,7 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
             let is_genesis = prev_hash_le.iter().all(|&b| b == 0);
             
             if is_genesis {
-                let expected_genesis_prefix = "000000000019d668";
-                let actual_genesis_prefix = hex::encode(&block_hash[..8]);
-                if actual_genesis_prefix == expected_genesis_prefix && chunk_genesis.is_none() {
+                if crate::sanity::is_known_genesis(&block_hash) && chunk_genesis.is_none() {
                     chunk_genesis = Some((chunk_num, offset - 4, block_hash));
                 }
                 if block_len > 80 {
@@ -370,10 +366,6 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
         }
         
         eprintln!("   ✅ Chunk {} complete: {} blocks processed", chunk_num, block_num_in_chunk);
-        let wait_result = zstd_proc.wait();
-        if let Err(e) = wait_result {
-            eprintln!("   ⚠️  Warning: zstd process wait failed for chunk {}: {}", chunk_num, e);
-        }
         
         Ok((chunk_num, chunk_blocks_by_prev_hash, chunk_blocks_by_block_hash, Vec::new(), chunk_genesis))
     }).collect::<Result<Vec<_>>>()
@@ -405,7 +397,6 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
         }
     }
     
-    // Chain blocks by prev_block_hash to determine heights
     // Chain blocks by prev_block_hash to determine heights
     println!("   🔗 Chaining blocks by prev_block_hash...");
     
@@ -418,85 +409,30 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
     
     // CRITICAL: genesis.2 is the block_hash of genesis in big-endian format
     // We need to find a block whose prev_hash (when converted to big-endian) matches this
-    let mut current_hash = genesis.2;
-    println!("     🔍 DEBUG: Genesis block_hash (BE): {}", hex::encode(&current_hash));
-    println!("     🔍 DEBUG: Looking for block with prev_hash_be matching: {}", hex::encode(&current_hash));
-    let mut height = 1u64;
-    
-    println!("     Starting chain from genesis block hash: {}", hex::encode(&current_hash));
-    println!("     Looking for blocks with prev_hash matching genesis...");
+    println!("     Starting chain from genesis block hash: {}", hex::encode(&genesis.2));
     println!("     Total blocks by prev_hash: {}", blocks_by_prev_hash.len());
     println!("     Total blocks by block_hash: {}", blocks_by_block_hash.len());
     
-    // Start chaining from genesis
-    // Chunks are primary - RPC is fallback for any missing blocks
-    let mut chain_break_count = 0;
-    let max_chain_breaks = 10000; // Increased limit - allow more chain breaks before stopping
-    
-    loop {
-        // First, try to find block in chunks
-        if let Some((chunk_num, offset, block_hash)) = blocks_by_prev_hash.remove(&current_hash) {
-            index.insert(height, BlockIndexEntry {
-                chunk_number: chunk_num,
-                offset_in_chunk: offset,
-                block_hash,
-            });
-            
-            current_hash = block_hash;
-            height += 1;
-            chain_break_count = 0; // Reset on success
-            
-            if height <= 5 {
-                println!("     Height {}: chunk {}, offset {}, hash {}", 
-                         height - 1, chunk_num, offset, hex::encode(&block_hash[..8]));
-            }
-            
-            if height % 10000 == 0 {
-                println!("     Indexed {} blocks...", height);
-                if let Err(e) = save_block_index(chunks_dir, &index) {
-                    eprintln!("     ⚠️  Warning: Failed to save index at height {}: {}", height, e);
-                }
-            }
-        } else {
-            // Block not found in chunks - skip for now, will be fetched in async context
-            if chain_break_count < max_chain_breaks {
-                // Reduced logging frequency to improve performance
-                if height < 10 || height % 10000 == 0 {
-                    println!("     ⚠️  Block {} not found in chunks - will be fetched from RPC in async context", height);
-                }
-                // Can't fetch from RPC here (sync context) - will be handled by build_block_index_via_rpc
-                chain_break_count += 1;
-                height += 1;
-                continue;
-            }
-            
-            // Block not found and couldn't fetch - check if we should stop
-            chain_break_count += 1;
-            if chain_break_count > max_chain_breaks || blocks_by_prev_hash.is_empty() {
-                eprintln!("   ⚠️  Stopping chain at height {} ({} breaks, {} blocks remaining)", 
-                         height, chain_break_count, blocks_by_prev_hash.len());
-                break;
-            }
-            
-            // Try to skip this height and continue
-            height += 1;
-        }
-        
-        // Safety check: prevent infinite loop
-        if height > 1_000_000 {
-            eprintln!("   ⚠️  Chain too long, stopping at {} blocks", height);
-            break;
-        }
-        
-        // Check if we've processed all available blocks
-        if blocks_by_prev_hash.is_empty() && chain_break_count == 0 {
-            break;
-        }
+    // Blocks missing from the chunks end the chain here; build_block_index_via_rpc fills the rest
+    let chained = chain_by_prev_hash(genesis.2, &mut blocks_by_prev_hash, |entry| entry.2);
+    let mut current_hash = genesis.2;
+    for (height, (chunk_num, offset, block_hash)) in (1u64..).zip(chained) {
+        index.insert(height, BlockIndexEntry {
+            chunk_number: chunk_num,
+            offset_in_chunk: offset,
+            block_hash,
+        });
+        current_hash = block_hash;
+    }
+    let height = index.len() as u64;
+    if !blocks_by_prev_hash.is_empty() {
+        println!("     ⚠️  Chain stops at height {} ({} blocks not chained) - missing blocks will be fetched from RPC",
+                 height, blocks_by_prev_hash.len());
     }
     
-    if height <= 1 {
+    if height <= 2 {
         eprintln!("   ⚠️  WARNING: Chain stopped after {} block(s)!", height);
-        if height == 0 {
+        if height == 1 {
             eprintln!("     Only genesis block indexed");
         } else {
             eprintln!("     Only genesis and block 1 indexed");
@@ -532,11 +468,56 @@ pub fn build_block_index(chunks_dir: &Path) -> Result<(BlockIndex, HashMap<[u8;
     Ok((index, blocks_by_block_hash))
 }
 
+/// Block hash and prev_block_hash of an 80-byte header, both big-endian (display order)
+pub fn header_links(header: &[u8; 80]) -> ([u8; 32], [u8; 32]) {
+    let mut block_hash = crate::wire::sha256d(header);
+    block_hash.reverse();
+    let mut prev_hash: [u8; 32] = header[4..36].try_into().unwrap();
+    prev_hash.reverse();
+    (block_hash, prev_hash)
+}
+
+/// Follow prev_block_hash links from `root_hash`, removing each chained block from the map
+///
+/// Returns the blocks above the root in height order. The walk stops at the first height with
+/// no child; blocks that never chain (stale branches, blocks above a gap) stay in the map.
+pub fn chain_by_prev_hash<V>(
+    root_hash: [u8; 32],
+    blocks_by_prev_hash: &mut HashMap<[u8; 32], V>,
+    block_hash: impl Fn(&V) -> [u8; 32],
+) -> Vec<V> {
+    let mut chain = Vec::with_capacity(blocks_by_prev_hash.len());
+    let mut current_hash = root_hash;
+    while let Some(block) = blocks_by_prev_hash.remove(&current_hash) {
+        current_hash = block_hash(&block);
+        chain.push(block);
+    }
+    chain
+}
+
+/// Order headers stored in any order into a chain above `root_hash`
+///
+/// Returns indexes into `headers`, lowest height first. This is the reconstruction
+/// [`build_block_index`] runs over out-of-order chunks, without the chunk I/O.
+pub fn order_by_prev_hash(headers: &[[u8; 80]], root_hash: [u8; 32]) -> Vec<usize> {
+    let mut blocks_by_prev_hash: HashMap<[u8; 32], (usize, [u8; 32])> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            let (block_hash, prev_hash) = header_links(header);
+            (prev_hash, (i, block_hash))
+        })
+        .collect();
+    chain_by_prev_hash(root_hash, &mut blocks_by_prev_hash, |entry| entry.1)
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect()
+}
+
 /// Verify block index correctness by checking prev_block_hash chain
 pub fn verify_block_index(chunks_dir: &Path, index: &BlockIndex) -> Result<bool> {
     use crate::chunked_cache::decompress_chunk_streaming;
     use std::io::Read;
-    use std::process::Stdio;
     
     println!("🔍 Verifying block index...");
     
@@ -550,11 +531,8 @@ pub fn verify_block_index(chunks_dir: &Path, index: &BlockIndex) -> Result<bool>
         // Note: We can't seek in a zstd stream, so we need to read from start and skip
         // For verification, we'll just check a few blocks - full implementation would need
         // to cache decompressed chunks or use a different approach
-        let chunk_file = chunks_dir.join(format!("chunk_{}.bin.zst", entry.chunk_number));
-        let mut zstd_proc = decompress_chunk_streaming(&chunk_file)?;
-        let stdout = zstd_proc.stdout.take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-        let mut reader = std::io::BufReader::new(stdout);
+        let chunk_file = crate::chunk_manifest::chunk_path(chunks_dir, entry.chunk_number as u64);
+        let mut reader = std::io::BufReader::new(decompress_chunk_streaming(&chunk_file)?);
         
         // Skip to offset (read and discard bytes)
         let mut skip_bytes = entry.offset_in_chunk;
@@ -650,3 +628,42 @@ mod contiguous_tests {
         assert_eq!(iterator_eager_height_range(100, 1000), None);
     }
 }
+
+#[cfg(test)]
+mod chain_tests {
+    use super::{header_links, order_by_prev_hash};
+    use crate::utils::{shuffled_order, synthetic_header_chain};
+
+    #[test]
+    fn shuffled_headers_rechain_in_exact_order() {
+        let root = [0x11; 32];
+        let chain = synthetic_header_chain(root, 5_000);
+        assert_eq!(header_links(&chain[0]).1, root);
+        // Orphans whose parent never arrives must stay out of the chain
+        let orphans = synthetic_header_chain([0x33; 32], 2);
+
+        for seed in [0, 1, 0xdead_beef] {
+            let mut headers: Vec<[u8; 80]> = shuffled_order(chain.len(), seed)
+                .into_iter()
+                .map(|i| chain[i])
+                .collect();
+            headers.insert(headers.len() / 3, orphans[1]);
+            headers.insert(headers.len() / 2, orphans[0]);
+
+            let ordered: Vec<[u8; 80]> = order_by_prev_hash(&headers, root)
+                .into_iter()
+                .map(|i| headers[i])
+                .collect();
+            assert_eq!(ordered, chain, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn chain_stops_at_gap() {
+        let root = [0x22; 32];
+        let mut headers = synthetic_header_chain(root, 10);
+        headers.remove(6);
+        headers.reverse();
+        assert_eq!(order_by_prev_hash(&headers, root), [8, 7, 6, 5, 4, 3]);
+    }
+}
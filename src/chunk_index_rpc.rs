@@ -589,12 +589,7 @@ async fn find_block_in_chunk(
     _block_hash_le: &[u8; 32],
     chunk_num: usize,
 ) -> Result<BlockIndexEntry> {
-    let mut zstd_proc = decompress_chunk_streaming(chunk_file)?;
-    let stdout = zstd_proc
-        .stdout
-        .take()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-    let mut reader = std::io::BufReader::new(stdout);
+    let mut reader = std::io::BufReader::new(decompress_chunk_streaming(chunk_file)?);
 
     let mut offset: u64 = 0;
 
@@ -645,8 +640,5 @@ async fn find_block_in_chunk(
         offset += block_len as u64;
     }
 
-    // Wait for zstd to finish
-    let _ = zstd_proc.wait();
-
     anyhow::bail!("Block not found in chunk {}", chunk_num)
 }
@@ -0,0 +1,333 @@
+//! Per-chunk SHA-256 manifest for the chunked block cache
+//!
+//! `chunks.manifest` sits next to `chunk_N.bin.zst` and has one line per chunk:
+//!
+//! ```text
+//! # chunk first_block last_block blocks compressed_bytes sha256
+//! 0 0 124999 125000 9876543210 3f5a...
+//! ```
+//!
+//! `first_block` / `last_block` are positions in the chunk sequence (`chunk * blocks_per_chunk`
+//! onwards), which are heights once the chunks are in chain order. The file is rewritten through
+//! a temp file and a rename each time a chunk is recorded, so a crash leaves the old or the new
+//! manifest, never half of one.
+//!
+//! Readers call [`verify_chunk`] before opening a chunk: a chunk whose size or hash disagrees with
+//! its entry is refused instead of being decompressed until zstd hits the truncation. Each chunk
+//! is checked once per process. `BLVM_CHUNK_VERIFY=size` skips the hash (size only) and `off`
+//! skips the check; chunks without an entry (caches from before the manifest) load as before.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Manifest file name inside the chunks directory
+pub const MANIFEST_FILE: &str = "chunks.manifest";
+
+const HEADER: &str = "# chunk first_block last_block blocks compressed_bytes sha256";
+
+/// How much of a chunk [`verify_chunk`] checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMode {
+    Full,
+    Size,
+    Off,
+}
+
+impl VerifyMode {
+    /// `BLVM_CHUNK_VERIFY` (`full` when unset).
+    pub fn from_env() -> Self {
+        match std::env::var("BLVM_CHUNK_VERIFY").as_deref() {
+            Ok("size") => Self::Size,
+            Ok("off") | Ok("0") => Self::Off,
+            _ => Self::Full,
+        }
+    }
+}
+
+/// One chunk in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub chunk_num: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub blocks: u64,
+    pub compressed_bytes: u64,
+    pub sha256: [u8; 32],
+}
+
+impl ManifestEntry {
+    /// Entry for the chunk file at `path` holding `blocks` blocks from `first_block` on.
+    pub fn for_file(path: &Path, chunk_num: u64, first_block: u64, blocks: u64) -> Result<Self> {
+        let (compressed_bytes, sha256) = hash_file(path)?;
+        Ok(Self {
+            chunk_num,
+            first_block,
+            last_block: (first_block + blocks).saturating_sub(1),
+            blocks,
+            compressed_bytes,
+            sha256,
+        })
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        anyhow::ensure!(fields.len() == 6, "expected 6 fields, got {}", fields.len());
+        let num = |i: usize| -> Result<u64> {
+            fields[i]
+                .parse()
+                .with_context(|| format!("invalid number {:?}", fields[i]))
+        };
+        let sha256 = hex::decode(fields[5])
+            .ok()
+            .and_then(|h| <[u8; 32]>::try_from(h).ok())
+            .with_context(|| format!("invalid sha256 {:?}", fields[5]))?;
+        Ok(Self {
+            chunk_num: num(0)?,
+            first_block: num(1)?,
+            last_block: num(2)?,
+            blocks: num(3)?,
+            compressed_bytes: num(4)?,
+            sha256,
+        })
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.chunk_num,
+            self.first_block,
+            self.last_block,
+            self.blocks,
+            self.compressed_bytes,
+            hex::encode(self.sha256)
+        )
+    }
+
+    /// Whether the file at `path` matches this entry (size only unless `mode` is `Full`).
+    pub fn check(&self, path: &Path, mode: VerifyMode) -> Result<()> {
+        let size = std::fs::metadata(path)
+            .with_context(|| format!("Failed to stat {}", path.display()))?
+            .len();
+        anyhow::ensure!(
+            size == self.compressed_bytes,
+            "{} is {} bytes, manifest says {} (truncated or replaced)",
+            path.display(),
+            size,
+            self.compressed_bytes
+        );
+        if mode == VerifyMode::Full {
+            let (_, sha256) = hash_file(path)?;
+            anyhow::ensure!(
+                sha256 == self.sha256,
+                "{} has SHA-256 {}, manifest says {}",
+                path.display(),
+                hex::encode(sha256),
+                hex::encode(self.sha256)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// All entries of a chunks directory, by chunk number.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: BTreeMap<u64, ManifestEntry>,
+}
+
+impl Manifest {
+    /// `chunks.manifest` in `chunks_dir`, or `None` if there is none.
+    pub fn load(chunks_dir: &Path) -> Result<Option<Self>> {
+        let path = chunks_dir.join(MANIFEST_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let mut manifest = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = ManifestEntry::parse(line)
+                .with_context(|| format!("{} line {}", path.display(), i + 1))?;
+            manifest.entries.insert(entry.chunk_num, entry);
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Write to `chunks_dir` through a temp file and a rename.
+    pub fn save(&self, chunks_dir: &Path) -> Result<()> {
+        let path = chunks_dir.join(MANIFEST_FILE);
+        let tmp = chunks_dir.join(format!("{}.tmp", MANIFEST_FILE));
+        let mut file = std::fs::File::create(&tmp)
+            .with_context(|| format!("Failed to create {}", tmp.display()))?;
+        writeln!(file, "{}", HEADER)?;
+        for entry in self.entries.values() {
+            writeln!(file, "{}", entry.line())?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, chunk_num: u64) -> Option<&ManifestEntry> {
+        self.entries.get(&chunk_num)
+    }
+}
+
+/// Add or replace `entry` in the manifest of `chunks_dir`.
+pub fn record(chunks_dir: &Path, entry: ManifestEntry) -> Result<()> {
+    // Chunk writers in one process take turns; the rename keeps other readers consistent
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap();
+    let path = chunk_path(chunks_dir, entry.chunk_num);
+    let mut manifest = Manifest::load(chunks_dir)?.unwrap_or_default();
+    manifest.entries.insert(entry.chunk_num, entry);
+    manifest.save(chunks_dir)?;
+    // A re-created chunk is checked again on its next open
+    verified().lock().unwrap().remove(&path);
+    Ok(())
+}
+
+/// Hash `chunks_dir/chunk_<chunk_num>.bin.zst` and record it.
+pub fn record_chunk(
+    chunks_dir: &Path,
+    chunk_num: u64,
+    first_block: u64,
+    blocks: u64,
+) -> Result<()> {
+    let entry = ManifestEntry::for_file(
+        &chunk_path(chunks_dir, chunk_num),
+        chunk_num,
+        first_block,
+        blocks,
+    )?;
+    record(chunks_dir, entry)
+}
+
+/// `chunk_<chunk_num>.bin.zst` in `chunks_dir`, or in the archive tier holding it when
+/// `chunks_dir` is the chunk dir ([`crate::storage_tiers`]). With `s3-cache`, a chunk no tier
+/// has is fetched from the configured bucket ([`crate::s3_cache::fetch_chunk`]).
+pub fn chunk_path(chunks_dir: &Path, chunk_num: u64) -> PathBuf {
+    let name = crate::storage_tiers::chunk_file_name(chunk_num);
+    let path = chunks_dir.join(&name);
+    if path.exists() {
+        return path;
+    }
+    if let Some(dir) =
+        crate::storage_tiers::StorageTiers::from_config().locate(chunks_dir, chunk_num)
+    {
+        return dir.join(name);
+    }
+    #[cfg(feature = "s3-cache")]
+    match crate::s3_cache::fetch_chunk(chunk_num) {
+        Ok(Some(fetched)) => return fetched,
+        Ok(None) => {}
+        Err(e) => tracing::warn!("⚠️  Could not fetch {} from S3: {:#}", name, e),
+    }
+    path
+}
+
+fn verified() -> &'static Mutex<HashSet<PathBuf>> {
+    static VERIFIED: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    VERIFIED.get_or_init(Default::default)
+}
+
+/// Refuse chunk `chunk_num` of `chunks_dir` if it disagrees with its manifest entry
+/// ([`VerifyMode::from_env`]). Chunks without a manifest or entry pass.
+pub fn verify_chunk(chunks_dir: &Path, chunk_num: u64) -> Result<()> {
+    let mode = VerifyMode::from_env();
+    if mode == VerifyMode::Off {
+        return Ok(());
+    }
+    let path = chunk_path(chunks_dir, chunk_num);
+    if verified().lock().unwrap().contains(&path) {
+        return Ok(());
+    }
+    let Some(manifest) = Manifest::load(chunks_dir)? else {
+        return Ok(());
+    };
+    let Some(entry) = manifest.get(chunk_num) else {
+        return Ok(());
+    };
+    entry.check(&path, mode).with_context(|| {
+        format!(
+            "Chunk {} failed manifest verification - re-create it or set BLVM_CHUNK_VERIFY=off",
+            chunk_num
+        )
+    })?;
+    verified().lock().unwrap().insert(path);
+    Ok(())
+}
+
+/// Size and SHA-256 of a file, streamed.
+pub fn hash_file(path: &Path) -> Result<(u64, [u8; 32])> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 8 * 1024 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, hasher.finalize().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_roundtrip_and_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk = chunk_path(dir.path(), 3);
+        std::fs::write(&chunk, vec![7u8; 10_000]).unwrap();
+
+        // No manifest yet: legacy caches load unchecked
+        verify_chunk(dir.path(), 3).unwrap();
+
+        record_chunk(dir.path(), 3, 375_000, 125_000).unwrap();
+        let manifest = Manifest::load(dir.path()).unwrap().unwrap();
+        let entry = manifest.get(3).unwrap();
+        assert_eq!(entry.last_block, 499_999);
+        assert_eq!(entry.compressed_bytes, 10_000);
+        entry.check(&chunk, VerifyMode::Full).unwrap();
+
+        std::fs::write(&chunk, vec![7u8; 9_999]).unwrap();
+        assert!(entry.check(&chunk, VerifyMode::Size).is_err());
+        std::fs::write(&chunk, vec![8u8; 10_000]).unwrap();
+        entry.check(&chunk, VerifyMode::Size).unwrap();
+        assert!(entry.check(&chunk, VerifyMode::Full).is_err());
+
+        // Other chunks are untouched by a re-record
+        record(
+            dir.path(),
+            ManifestEntry {
+                chunk_num: 0,
+                first_block: 0,
+                last_block: 0,
+                blocks: 1,
+                compressed_bytes: 1,
+                sha256: [0; 32],
+            },
+        )
+        .unwrap();
+        let reloaded = Manifest::load(dir.path()).unwrap().unwrap();
+        assert_eq!(reloaded.entries.len(), 2);
+        assert_eq!(reloaded.get(3), Some(entry));
+    }
+}
@@ -0,0 +1,305 @@
+//! Cost-balanced work items for parallel differential runs
+//!
+//! Fixed-size chunks balance badly: the first 100k blocks validate in minutes, post-2017 chunks
+//! take hours, and most workers sit idle while the last chunks finish. With
+//! [`ChunkScheduling::Adaptive`] the range is cut into `items_per_worker × num_workers` work
+//! items of about equal estimated cost instead (never longer than `chunk_size` blocks), and each
+//! worker takes the next item from a shared [`WorkQueue`] as soon as it finishes one, most
+//! expensive first so the tail is made of small items.
+//!
+//! A block's cost is one plus its transaction count from Core's block index
+//! ([`BlockSource::tx_count`]), else one plus a rough mainnet average for its height
+//! ([`estimated_tx_count`]).
+//!
+//! Every work item starts from its own UTXO checkpoint, kept on disk until a worker takes the
+//! item, so more items cost checkpoint files rather than memory: `BLVM_CHUNK_SCHEDULING=adaptive`
+//! uses [`DEFAULT_ITEMS_PER_WORKER`], `adaptive:<n>` sets it, and `fixed` (the default) keeps
+//! `chunk_size` chunks. Items are [clipped](clip) to the heights a resumed run still has to
+//! validate.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::block_source::BlockSource;
+
+/// Work items per worker for `BLVM_CHUNK_SCHEDULING=adaptive`
+pub const DEFAULT_ITEMS_PER_WORKER: usize = 4;
+
+/// How a parallel run's range is cut into work items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkScheduling {
+    /// `chunk_size` blocks per item, run in height order
+    #[default]
+    Fixed,
+    /// About `items_per_worker × num_workers` items of equal estimated cost, most expensive
+    /// first
+    Adaptive { items_per_worker: usize },
+}
+
+impl ChunkScheduling {
+    /// `BLVM_CHUNK_SCHEDULING` (`fixed`, `adaptive` or `adaptive:<items per worker>`); fixed when
+    /// unset or unparseable.
+    pub fn from_env() -> Self {
+        std::env::var("BLVM_CHUNK_SCHEDULING")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl std::str::FromStr for ChunkScheduling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (mode, items) = match s.trim().split_once(':') {
+            Some((mode, items)) => (mode, Some(items)),
+            None => (s.trim(), None),
+        };
+        match (mode, items) {
+            ("fixed", None) => Ok(Self::Fixed),
+            ("adaptive", None) => Ok(Self::Adaptive {
+                items_per_worker: DEFAULT_ITEMS_PER_WORKER,
+            }),
+            ("adaptive", Some(n)) => match n.parse() {
+                Ok(items_per_worker) if items_per_worker > 0 => {
+                    Ok(Self::Adaptive { items_per_worker })
+                }
+                _ => anyhow::bail!("Invalid items per worker in chunk scheduling {:?}", s),
+            },
+            _ => anyhow::bail!(
+                "Unknown chunk scheduling {:?} (fixed, adaptive or adaptive:<n>)",
+                s
+            ),
+        }
+    }
+}
+
+/// `[start_height, end_height]` with its estimated cost.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkItem {
+    pub start_height: u64,
+    pub end_height: u64,
+    pub cost: f64,
+}
+
+impl WorkItem {
+    pub fn blocks(&self) -> u64 {
+        self.end_height - self.start_height + 1
+    }
+}
+
+/// Approximate mainnet transactions per block by height, interpolated between these points
+const MAINNET_TX_PER_BLOCK: &[(u64, f64)] = &[
+    (0, 1.0),
+    (100_000, 5.0),
+    (150_000, 20.0),
+    (200_000, 150.0),
+    (250_000, 400.0),
+    (300_000, 600.0),
+    (350_000, 900.0),
+    (400_000, 1_700.0),
+    (450_000, 2_000.0),
+    (500_000, 2_200.0),
+    (600_000, 2_500.0),
+    (700_000, 2_300.0),
+    (800_000, 3_000.0),
+];
+
+/// Rough mainnet transaction count at `height`, for sources without a block index.
+pub fn estimated_tx_count(height: u64) -> f64 {
+    let upper = MAINNET_TX_PER_BLOCK.partition_point(|&(h, _)| h <= height);
+    match (
+        MAINNET_TX_PER_BLOCK.get(upper.wrapping_sub(1)),
+        MAINNET_TX_PER_BLOCK.get(upper),
+    ) {
+        (Some(&(h0, t0)), Some(&(h1, t1))) => {
+            t0 + (t1 - t0) * (height - h0) as f64 / (h1 - h0) as f64
+        }
+        (Some(&(_, t)), None) => t,
+        _ => MAINNET_TX_PER_BLOCK[0].1,
+    }
+}
+
+/// Estimated validation cost of the block at `height`.
+pub fn block_cost<S: BlockSource + ?Sized>(source: &S, height: u64) -> f64 {
+    1.0 + source
+        .tx_count(height)
+        .map_or_else(|| estimated_tx_count(height), f64::from)
+}
+
+/// `chunk_size` items over `[start_height, end_height]`.
+pub fn fixed_items(
+    start_height: u64,
+    end_height: u64,
+    chunk_size: u64,
+    cost: impl Fn(u64) -> f64,
+) -> Vec<WorkItem> {
+    let mut items = Vec::new();
+    let mut start = start_height;
+    while start <= end_height {
+        let end = (start + chunk_size - 1).min(end_height);
+        items.push(WorkItem {
+            start_height: start,
+            end_height: end,
+            cost: (start..=end).map(&cost).sum(),
+        });
+        start = end + 1;
+    }
+    items
+}
+
+/// About `target_items` items over `[start_height, end_height]` of equal total `cost`, none
+/// longer than `max_blocks`; in height order.
+pub fn balanced_items(
+    start_height: u64,
+    end_height: u64,
+    target_items: usize,
+    max_blocks: u64,
+    cost: impl Fn(u64) -> f64,
+) -> Vec<WorkItem> {
+    let costs: Vec<f64> = (start_height..=end_height).map(cost).collect();
+    let per_item = costs.iter().sum::<f64>() / target_items.max(1) as f64;
+    let mut items = Vec::with_capacity(target_items);
+    let mut item = WorkItem {
+        start_height,
+        end_height: start_height,
+        cost: 0.0,
+    };
+    for (height, cost) in (start_height..).zip(costs) {
+        item.end_height = height;
+        item.cost += cost;
+        if height == end_height || item.cost >= per_item || item.blocks() >= max_blocks {
+            items.push(item);
+            item = WorkItem {
+                start_height: height + 1,
+                end_height: height + 1,
+                cost: 0.0,
+            };
+        }
+    }
+    items
+}
+
+/// Work items for a run over `[start_height, end_height]`, in height order.
+pub fn plan<S: BlockSource + ?Sized>(
+    scheduling: ChunkScheduling,
+    start_height: u64,
+    end_height: u64,
+    chunk_size: u64,
+    num_workers: usize,
+    source: &S,
+) -> Vec<WorkItem> {
+    match scheduling {
+        ChunkScheduling::Fixed => fixed_items(start_height, end_height, chunk_size, |_| 1.0),
+        ChunkScheduling::Adaptive { items_per_worker } => balanced_items(
+            start_height,
+            end_height,
+            items_per_worker * num_workers.max(1),
+            chunk_size,
+            |height| block_cost(source, height),
+        ),
+    }
+}
+
+/// `items` cut down to their parts inside `ranges` (ascending, disjoint); each part gets its
+/// share of the item's cost by block count.
+pub fn clip(items: &[WorkItem], ranges: &[(u64, u64)]) -> Vec<WorkItem> {
+    let mut parts = Vec::new();
+    for item in items {
+        for &(start, end) in ranges {
+            let (from, to) = (item.start_height.max(start), item.end_height.min(end));
+            if from > to {
+                continue;
+            }
+            let blocks = to - from + 1;
+            parts.push(WorkItem {
+                start_height: from,
+                end_height: to,
+                cost: item.cost * blocks as f64 / item.blocks() as f64,
+            });
+        }
+    }
+    parts
+}
+
+/// Work items shared by all workers; whoever is free takes the next one.
+pub struct WorkQueue<T> {
+    items: Mutex<VecDeque<T>>,
+}
+
+impl<T> WorkQueue<T> {
+    /// Queue handing out `items` in the given order.
+    pub fn new(items: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            items: Mutex::new(items.into_iter().collect()),
+        }
+    }
+
+    /// Queue handing out `items` highest `cost` first.
+    pub fn by_cost_desc(items: impl IntoIterator<Item = T>, cost: impl Fn(&T) -> f64) -> Self {
+        let mut items: Vec<T> = items.into_iter().collect();
+        items.sort_by(|a, b| cost(b).total_cmp(&cost(a)));
+        Self::new(items)
+    }
+
+    /// Next item, or `None` once the queue is drained.
+    pub fn take(&self) -> Option<T> {
+        self.items.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balanced_items_split_by_cost() {
+        // Blocks from 1000 on cost 10x as much: the cheap half becomes few long items
+        let cost = |h: u64| if h < 1000 { 1.0 } else { 10.0 };
+        let items = balanced_items(0, 1999, 11, 500, cost);
+        assert_eq!(items.first().unwrap().start_height, 0);
+        assert_eq!(items.last().unwrap().end_height, 1999);
+        assert!(items
+            .windows(2)
+            .all(|w| w[1].start_height == w[0].end_height + 1));
+        assert!(items.iter().all(|i| i.blocks() <= 500));
+        assert!(items.iter().filter(|i| i.end_height < 1000).count() <= 2);
+        assert!(items.iter().filter(|i| i.start_height >= 1000).count() >= 9);
+
+        let queue = WorkQueue::by_cost_desc(items.clone(), |i| i.cost);
+        let first = queue.take().unwrap();
+        assert!(items.iter().all(|i| i.cost <= first.cost));
+        assert_eq!(queue.len(), items.len() - 1);
+
+        assert_eq!(
+            "adaptive:8".parse::<ChunkScheduling>().unwrap(),
+            ChunkScheduling::Adaptive {
+                items_per_worker: 8
+            }
+        );
+        assert!("adaptive:0".parse::<ChunkScheduling>().is_err());
+        assert_eq!(estimated_tx_count(125_000), 12.5);
+    }
+
+    #[test]
+    fn clip_keeps_uncovered_parts() {
+        let items = fixed_items(0, 299, 100, |_| 2.0);
+        let parts = clip(&items, &[(50, 149), (250, 400)]);
+        assert_eq!(
+            parts
+                .iter()
+                .map(|p| (p.start_height, p.end_height, p.cost))
+                .collect::<Vec<_>>(),
+            [(50, 99, 100.0), (100, 149, 100.0), (250, 299, 100.0)]
+        );
+        assert!(clip(&items, &[]).is_empty());
+    }
+}
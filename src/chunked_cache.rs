@@ -11,6 +11,7 @@ use std::sync::OnceLock;
 use std::collections::HashMap;
 use crate::chunk_index::{load_block_index, build_block_index, save_block_index, BlockIndex, BlockIndexEntry};
 use crate::node_rpc_client::{NodeRpcClient, RpcConfig};
+use crate::sanity::{self, SanityStage};
 
 /// `KERNEL_DIFF_RPC_CHUNK_SKIP_MB` (MiB): if a zstd chunk seek would skip at least this many
 /// **decompressed** bytes, fetch the block via Bitcoin RPC (`getblockhash` + `getblock` verbosity 0)
@@ -43,7 +44,7 @@ fn global_tokio_runtime() -> &'static tokio::runtime::Runtime {
 }
 
 /// Chunk metadata
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChunkMetadata {
     pub total_blocks: u64,
     pub num_chunks: usize,
@@ -51,8 +52,78 @@ pub struct ChunkMetadata {
     pub compression: String,
 }
 
-/// Load chunk metadata from chunks.meta file
+/// Metadata-store record holding [`ChunkMetadata`]
+const CHUNK_METADATA_RECORD: &str = "chunk_metadata";
+/// Metadata-store count: mtime (Unix seconds) of the `chunks.meta` the record was synced with
+const CHUNKS_META_MTIME: &str = "chunks_meta_mtime";
+
+/// Whether `chunks_dir` has chunk metadata (metadata store or legacy `chunks.meta`).
+pub fn has_chunk_metadata(chunks_dir: &Path) -> bool {
+    chunks_dir.join(crate::meta_store::STORE_FILE).exists()
+        || chunks_dir.join("chunks.meta").exists()
+}
+
+fn chunks_meta_mtime(chunks_dir: &Path) -> Option<u64> {
+    std::fs::metadata(chunks_dir.join("chunks.meta"))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+/// Load chunk metadata from the metadata store.
+///
+/// The shell scripts under `scripts/` still write `chunks.meta`; when it is newer than what the
+/// store was synced with (or the store has no record yet) it is parsed and imported instead.
 pub fn load_chunk_metadata(chunks_dir: &Path) -> Result<Option<ChunkMetadata>> {
+    let legacy_mtime = chunks_meta_mtime(chunks_dir);
+    if let Some(store) = crate::meta_store::MetaStore::open_existing(chunks_dir)? {
+        let synced = store.count(CHUNKS_META_MTIME)?;
+        if legacy_mtime.is_none() || legacy_mtime <= synced {
+            if let Some(meta) = store.record::<ChunkMetadata>(CHUNK_METADATA_RECORD)? {
+                return Ok(Some(meta));
+            }
+        }
+    }
+
+    let meta = load_legacy_chunk_metadata(chunks_dir)?;
+    if let Some(meta) = &meta {
+        let imported = crate::meta_store::MetaStore::open(chunks_dir).and_then(|store| {
+            store.update(|txn| {
+                txn.put_record(CHUNK_METADATA_RECORD, meta)?;
+                txn.set_count(CHUNKS_META_MTIME, legacy_mtime.unwrap_or(0))
+            })
+        });
+        if let Err(e) = imported {
+            eprintln!(
+                "   ⚠️  Could not import chunks.meta into the metadata store: {}",
+                e
+            );
+        }
+    }
+    Ok(meta)
+}
+
+/// Save chunk metadata to the metadata store, and rewrite `chunks.meta` as a plain-text export
+/// for the shell scripts.
+pub fn save_chunk_metadata(chunks_dir: &Path, meta: &ChunkMetadata, note: &str) -> Result<()> {
+    let export = format!(
+        "# Chunk metadata\n# {}\ntotal_blocks={}\nnum_chunks={}\nblocks_per_chunk={}\ncompression={}\n",
+        note, meta.total_blocks, meta.num_chunks, meta.blocks_per_chunk, meta.compression
+    );
+    let meta_file = chunks_dir.join("chunks.meta");
+    std::fs::write(&meta_file, export)
+        .with_context(|| format!("Failed to write {}", meta_file.display()))?;
+
+    let exported_mtime = chunks_meta_mtime(chunks_dir).unwrap_or(0);
+    crate::meta_store::MetaStore::open(chunks_dir)?.update(|txn| {
+        txn.put_record(CHUNK_METADATA_RECORD, meta)?;
+        txn.set_count(CHUNKS_META_MTIME, exported_mtime)
+    })
+}
+
+/// Parse the legacy `key=value` chunks.meta file
+fn load_legacy_chunk_metadata(chunks_dir: &Path) -> Result<Option<ChunkMetadata>> {
     let meta_file = chunks_dir.join("chunks.meta");
     if !meta_file.exists() {
         return Ok(None);
@@ -94,33 +165,22 @@ pub fn load_chunk_metadata(chunks_dir: &Path) -> Result<Option<ChunkMetadata>> {
     }
 }
 
-/// Decompress a zstd-compressed chunk file
+/// Streaming zstd decoder over a chunk file (the length-prefixed blocks, in order).
+pub type ChunkDecoder =
+    zstd::stream::read::Decoder<'static, std::io::BufReader<crate::platform::SequentialFile>>;
+
+/// Decompress a zstd-compressed chunk file in-process (`zstd` crate)
 /// 
 /// OPTIMIZATION: Returns a streaming reader instead of loading entire chunk into memory
 /// This prevents OOM for large chunks (50-60GB compressed = 200GB+ uncompressed)
-pub fn decompress_chunk_streaming(chunk_path: &Path) -> Result<std::process::Child> {
-    decompress_chunk_streaming_mt(chunk_path, 1)
-}
-
-/// Decompress with multi-threading support
-/// 
-/// Uses zstd's -T flag for parallel decompression (zstd 1.5+)
-/// threads=0 means use all available cores
-pub fn decompress_chunk_streaming_mt(chunk_path: &Path, threads: usize) -> Result<std::process::Child> {
-    use std::process::{Command, Stdio};
-
-    // OPTIMIZATION: Use streaming decompression with multi-threading
-    let child = Command::new("zstd")
-        .arg("-d")
-        .arg("--stdout")
-        .arg(format!("-T{}", threads)) // Multi-threaded decompression
-        .arg(chunk_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .with_context(|| format!("Failed to start zstd decompression: {}", chunk_path.display()))?;
-
-    Ok(child)
+///
+/// With io_uring (`io.uring_queue_depth`) the compressed file is read ahead through
+/// [`crate::platform::open_sequential`].
+pub fn decompress_chunk_streaming(chunk_path: &Path) -> Result<ChunkDecoder> {
+    let input = crate::platform::open_sequential(chunk_path, crate::config::global().io.uring_queue_depth)
+        .with_context(|| format!("Failed to open chunk {}", chunk_path.display()))?;
+    zstd::stream::read::Decoder::new(input)
+        .with_context(|| format!("Failed to start zstd decompression: {}", chunk_path.display()))
 }
 
 /// Decompress a zstd-compressed chunk file (legacy - loads entire chunk)
@@ -129,34 +189,24 @@ pub fn decompress_chunk_streaming_mt(chunk_path: &Path, threads: usize) -> Resul
 /// this can require 200GB+ RAM. Use decompress_chunk_streaming() instead.
 #[allow(dead_code)]
 pub fn decompress_chunk(chunk_path: &Path) -> Result<Vec<u8>> {
-    use std::process::Command;
-
-    // Check if zstd is available
-    let output = Command::new("zstd")
-        .arg("--version")
-        .output()
-        .context("zstd not found - install with: sudo pacman -S zstd")?;
-
-    if !output.status.success() {
-        anyhow::bail!("zstd command failed");
-    }
-
-    // Decompress chunk
-    let output = Command::new("zstd")
-        .arg("-d")
-        .arg("--stdout")
-        .arg(chunk_path)
-        .output()
-        .with_context(|| format!("Failed to decompress chunk: {}", chunk_path.display()))?;
-
-    if !output.status.success() {
-        anyhow::bail!(
-            "zstd decompression failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
+    let file = std::fs::File::open(chunk_path)
+        .with_context(|| format!("Failed to open chunk {}", chunk_path.display()))?;
+    zstd::stream::decode_all(file)
+        .with_context(|| format!("Failed to decompress chunk: {}", chunk_path.display()))
+}
 
-    Ok(output.stdout)
+/// Compress a raw length-prefixed block file into chunk `dst` at zstd `level`, on all cores
+pub fn compress_chunk_file(src: &Path, dst: &Path, level: i32) -> Result<()> {
+    let mut input = std::fs::File::open(src)
+        .with_context(|| format!("Failed to open {}", src.display()))?;
+    let output = std::fs::File::create(dst)
+        .with_context(|| format!("Failed to create chunk {}", dst.display()))?;
+    let mut encoder = zstd::stream::write::Encoder::new(output, level)?;
+    encoder.multithread(num_cpus::get() as u32)?;
+    std::io::copy(&mut input, &mut encoder)
+        .with_context(|| format!("Failed to compress {}", src.display()))?;
+    encoder.finish()?.sync_all()?;
+    Ok(())
 }
 
 /// Load blocks from a single chunk
@@ -185,61 +235,12 @@ pub fn load_chunk_blocks(chunk_data: &[u8]) -> Result<Vec<Vec<u8>>> {
     Ok(blocks)
 }
 
-/// Advise the kernel to drop page-cache pages for `path` using `POSIX_FADV_DONTNEED`.
+/// Drop page-cache pages for a chunk file.
 ///
-/// Called **before** spawning the zstd subprocess that will read the chunk file, and again
-/// periodically during long seeks.  Because zstd owns its own file descriptor, we open the file
-/// separately just to make the syscall — `posix_fadvise` applies to the *page cache* (shared by
-/// all descriptors for the same inode), so our fd doesn't need to be the same one zstd uses.
-/// On non-Linux platforms this is a no-op.
-#[cfg(target_os = "linux")]
+/// Called **before** opening the chunk file for decompression, and again
+/// periodically during long seeks (see [`crate::platform::drop_page_cache`]; no-op off Linux).
 fn fadvise_dontneed(path: &Path) {
-    use std::os::unix::io::IntoRawFd;
-    if let Ok(f) = std::fs::File::open(path) {
-        let fd = f.into_raw_fd();
-        unsafe {
-            // POSIX_FADV_DONTNEED = 4.  len=0 means "to end of file".
-            libc::posix_fadvise(fd, 0, 0, 4 /* POSIX_FADV_DONTNEED */);
-            libc::close(fd);
-        }
-    }
-}
-#[cfg(not(target_os = "linux"))]
-fn fadvise_dontneed(_path: &Path) {}
-
-/// Spawn `zstd -d --stdout` reading `chunk_file`, with clear errors when the **`zstd` binary** is
-/// missing (often reported as bare `No such file or directory` by the OS).
-fn spawn_zstd_decompress_stdout(chunk_file: &Path, multi_thread_decode: bool) -> Result<std::process::Child> {
-    use std::io::ErrorKind;
-    use std::process::{Command, Stdio};
-
-    let mut cmd = Command::new("zstd");
-    cmd.arg("-d").arg("--stdout").arg("-q");
-    if multi_thread_decode {
-        let zstd_threads = std::cmp::min(6, num_cpus::get().saturating_sub(2));
-        cmd.arg(format!("-T{}", zstd_threads));
-    }
-    cmd.arg(chunk_file)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null());
-
-    cmd.spawn().map_err(|e| {
-        if e.kind() == ErrorKind::NotFound {
-            anyhow::anyhow!(
-                "cannot run the `zstd` decompressor: no executable named `zstd` on PATH (os error: {}). \
-                 Install the `zstd` package and ensure `zstd` works in your shell. \
-                 Chunk archive: {}",
-                e,
-                chunk_file.display()
-            )
-        } else {
-            anyhow::anyhow!(
-                "failed to spawn `zstd -d` on {}: {}",
-                chunk_file.display(),
-                e
-            )
-        }
-    })
+    crate::platform::drop_page_cache(path);
 }
 
 /// Create a streaming iterator over blocks from chunked cache
@@ -255,8 +256,7 @@ pub struct ChunkedBlockIterator {
     start_height: u64,
     end_height: u64,
     current_height: u64,
-    current_chunk_reader: Option<std::io::BufReader<std::process::ChildStdout>>,
-    current_zstd_proc: Option<std::process::Child>,
+    current_chunk_reader: Option<std::io::BufReader<ChunkDecoder>>,
     current_chunk_number: Option<usize>,
     current_offset: u64,
     /// Path of the currently open chunk file; used by the seek loop to call FADV_DONTNEED
@@ -346,7 +346,6 @@ impl ChunkedBlockIterator {
             end_height: end_height_val,
             current_height: start_height_val,
             current_chunk_reader: None,
-            current_zstd_proc: None,
             current_chunk_number: None,
             current_offset: 0,
             current_chunk_file: None,
@@ -454,7 +453,6 @@ impl ChunkedBlockIterator {
             end_height: end_height_val,
             current_height: start_height_val,
             current_chunk_reader: None,
-            current_zstd_proc: None,
             current_chunk_number: None,
             current_offset: 0,
             current_chunk_file: None,
@@ -480,10 +478,6 @@ impl ChunkedBlockIterator {
         }
         // Do not cancel rpc_prefetch here: load_block_from_index calls fetch_block_via_rpc (which
         // schedules the next height) before this method.
-        if let Some(mut proc) = self.current_zstd_proc.take() {
-            let _ = proc.kill();
-            let _ = proc.wait();
-        }
         self.current_chunk_reader = None;
         self.current_chunk_number = None;
         self.current_offset = 0;
@@ -590,10 +584,6 @@ impl ChunkedBlockIterator {
         }
 
         if need_new_chunk {
-            if let Some(mut proc) = self.current_zstd_proc.take() {
-                let _ = proc.kill();
-                let _ = proc.wait();
-            }
             self.current_chunk_reader = None;
             // Drop residual page-cache pages for the chunk we just finished with.
             if let Some(ref old_chunk_file) = self.current_chunk_file.take() {
@@ -612,26 +602,24 @@ impl ChunkedBlockIterator {
                 };
             }
 
-            let chunk_file = self.chunks_dir.join(format!("chunk_{}.bin.zst", entry.chunk_number));
+            let chunk_file = crate::chunk_manifest::chunk_path(&self.chunks_dir, entry.chunk_number as u64);
             if !chunk_file.exists() {
                 anyhow::bail!("Chunk {} not found: {}", entry.chunk_number, chunk_file.display());
             }
+            crate::chunk_manifest::verify_chunk(&self.chunks_dir, entry.chunk_number as u64)?;
             eprintln!("   📦 Opening chunk {} for height {}", entry.chunk_number, height);
 
-            // Drop any existing page-cache pages for the chunk file before the zstd subprocess
+            // Drop any existing page-cache pages for the chunk file before the decoder
             // opens it.  Sequential read of a 60 GB file fills ~5 GiB of OS page cache and drives
             // MemAvailable below the safety floor.  posix_fadvise(DONTNEED) keeps page-cache usage
             // near-zero for data we've already consumed.
             fadvise_dontneed(&chunk_file);
 
-            let mut zstd_proc = spawn_zstd_decompress_stdout(&chunk_file, true)?;
-            let stdout = zstd_proc.stdout.take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
+            let decoder = decompress_chunk_streaming(&chunk_file)?;
             // 16 MiB read-ahead is ample; the old 128 MiB buffer held unnecessary anonymous pages.
-            let reader = std::io::BufReader::with_capacity(16 * 1024 * 1024, stdout);
+            let reader = std::io::BufReader::with_capacity(16 * 1024 * 1024, decoder);
 
             self.current_chunk_reader = Some(reader);
-            self.current_zstd_proc = Some(zstd_proc);
             self.current_chunk_number = Some(entry.chunk_number);
             self.current_offset = 0;
             // Store chunk file path so the seek loop can call DONTNEED periodically.
@@ -738,7 +726,24 @@ impl ChunkedBlockIterator {
         Ok(Some(block_data))
     }
 
+    /// Next block in height order, checked against the cache-load [`crate::sanity`] rules.
+    ///
+    /// A rejected block is an error rather than a skip: dropping it would shift every later
+    /// height by one.
     pub fn next_block(&mut self) -> Result<Option<Vec<u8>>> {
+        let block = self.next_block_unchecked()?;
+        if let Some(ref data) = block {
+            let height = self.current_height.saturating_sub(1);
+            sanity::filter(SanityStage::CacheLoad)
+                .check(data)
+                .map_err(|rejection| {
+                    anyhow::anyhow!("block at height {} failed sanity check: {}", height, rejection)
+                })?;
+        }
+        Ok(block)
+    }
+
+    fn next_block_unchecked(&mut self) -> Result<Option<Vec<u8>>> {
         // CRITICAL FIX: Skip missing blocks instead of stopping
         loop {
             if self.current_height >= self.end_height {
@@ -760,7 +765,9 @@ impl ChunkedBlockIterator {
                         match reader.read_exact(&mut len_buf) {
                             Ok(_) => {
                                 let block_len = u32::from_le_bytes(len_buf) as usize;
-                                if block_len <= 10 * 1024 * 1024 && block_len >= 88 {
+                                if (sanity::DEFAULT_MIN_BLOCK_SIZE..=sanity::DEFAULT_MAX_BLOCK_SIZE)
+                                    .contains(&block_len)
+                                {
                                     let mut block_data = vec![0u8; block_len];
                                     match reader.read_exact(&mut block_data) {
                                         Ok(_) => {
@@ -817,7 +824,7 @@ impl ChunkedBlockIterator {
                     eprintln!("   ❌ Chunked cache: failed loading block at height {}.", error_height);
                     eprintln!("       {:#}", e);
                     return Err(e.context(format!(
-                        "chunked block read failed at height {} (common cause: missing `zstd` on PATH or missing chunk file)",
+                        "chunked block read failed at height {} (common cause: missing or truncated chunk file)",
                         error_height
                     )));
                 }
@@ -894,23 +901,21 @@ pub fn load_chunked_cache(
     // For 50-60GB compressed chunks, this prevents loading 200GB+ into RAM
     let mut all_blocks = Vec::new();
     for chunk_num in start_chunk..=end_chunk.min(metadata.num_chunks - 1) {
-        let chunk_file = chunks_dir.join(format!("chunk_{}.bin.zst", chunk_num));
+        let chunk_file = crate::chunk_manifest::chunk_path(chunks_dir, chunk_num as u64);
         
         if !chunk_file.exists() {
             eprintln!("   ⚠️  Chunk {} not found: {}", chunk_num, chunk_file.display());
             continue;
         }
+        crate::chunk_manifest::verify_chunk(chunks_dir, chunk_num as u64)?;
 
         println!("   📦 Streaming blocks from chunk {}...", chunk_num);
         
         // OPTIMIZATION: Stream decompression instead of loading entire chunk
         use std::io::{BufReader, Read};
 
-        let mut zstd_proc = spawn_zstd_decompress_stdout(&chunk_file, false)?;
-        
         let mut reader = BufReader::with_capacity(128 * 1024 * 1024, // 128MB buffer
-            zstd_proc.stdout.take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?);
+            decompress_chunk_streaming(&chunk_file)?);
         
         // Read blocks one at a time (streaming)
         let mut blocks_in_chunk = 0;
@@ -919,17 +924,13 @@ pub fn load_chunked_cache(
             match reader.read_exact(&mut len_buf) {
                 Ok(_) => {},
                 Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => {
-                    let _ = zstd_proc.wait(); // Clean up
-                    return Err(e.into());
-                }
+                Err(e) => return Err(e.into()),
             }
             
             let block_len = u32::from_le_bytes(len_buf) as usize;
             
             // Validate block size
             if block_len > 10 * 1024 * 1024 || block_len < 88 {
-                let _ = zstd_proc.wait();
                 anyhow::bail!("Invalid block size in chunk {}: {} bytes", chunk_num, block_len);
             }
             
@@ -947,12 +948,6 @@ pub fn load_chunked_cache(
             }
         }
         
-        // Wait for zstd to finish
-        let status = zstd_proc.wait()?;
-        if !status.success() {
-            anyhow::bail!("zstd decompression failed for chunk {}", chunk_num);
-        }
-        
         println!("   ✅ Loaded {} blocks from chunk {}", blocks_in_chunk, chunk_num);
     }
 
@@ -970,16 +965,13 @@ pub fn load_chunked_cache(
 
 /// Get chunk directory path
 ///
-/// 1. `BLOCK_CACHE_DIR` if set, exists, and looks like a chunk dir (`chunks.meta` or `chunk_*.bin.zst`)
+/// 1. `BLOCK_CACHE_DIR` (or `chunks.dir` in `blvm-bench.toml`) if set, exists, and looks like a
+///    chunk dir (chunk metadata or `chunk_*.bin.zst`)
 /// 2. Else default cache directory (`~/.cache/blvm-bench/chunks`)
 pub fn get_chunks_dir() -> Option<PathBuf> {
-    if let Ok(env_dir) = std::env::var("BLOCK_CACHE_DIR") {
-        if env_dir.is_empty() {
-            return fallback_cache_chunks_dir();
-        }
-        let path = PathBuf::from(env_dir);
+    if let Some(path) = crate::config::chunk_dir() {
         if path.exists()
-            && (path.join("chunks.meta").exists()
+            && (has_chunk_metadata(&path)
                 || std::fs::read_dir(&path).ok().is_some_and(|rd| {
                     rd.flatten().any(|e| {
                         e.file_name()
@@ -1004,7 +996,7 @@ fn fallback_cache_chunks_dir() -> Option<PathBuf> {
 /// Check if chunked cache exists
 pub fn chunked_cache_exists() -> bool {
     if let Some(chunks_dir) = get_chunks_dir() {
-        chunks_dir.exists() && chunks_dir.join("chunks.meta").exists()
+        chunks_dir.exists() && has_chunk_metadata(&chunks_dir)
     } else {
         false
     }
@@ -1017,9 +1009,9 @@ pub fn chunked_cache_exists() -> bool {
 pub struct SharedChunkCache {
     chunks_dir: PathBuf,
     index: Arc<BlockIndex>,
-    // Cache of chunk readers: chunk_number -> (reader, zstd_process, current_offset)
-    // CRITICAL: Limited to prevent OOM - each reader holds a zstd process and large buffer
-    chunk_readers: Arc<Mutex<HashMap<usize, (std::io::BufReader<std::process::ChildStdout>, std::process::Child, u64)>>>,
+    // Cache of chunk readers: chunk_number -> (reader, current_offset)
+    // CRITICAL: Limited to prevent OOM - each reader holds a zstd decoder and large buffer
+    chunk_readers: Arc<Mutex<HashMap<usize, (std::io::BufReader<ChunkDecoder>, u64)>>>,
     max_chunk_readers: usize,
 }
 
@@ -1053,42 +1045,27 @@ impl SharedChunkCache {
         if readers.len() >= self.max_chunk_readers && !readers.contains_key(&entry.chunk_number) {
             // Evict first (oldest) chunk reader
             if let Some(&chunk_num) = readers.keys().next() {
-                if let Some((_, mut proc, _)) = readers.remove(&chunk_num) {
-                    let _ = proc.kill();
-                    let _ = proc.wait();
-                }
+                readers.remove(&chunk_num);
             }
         }
         
         // Check if we need to create new chunk reader
         if !readers.contains_key(&entry.chunk_number) {
-            let chunk_file = self.chunks_dir.join(format!("chunk_{}.bin.zst", entry.chunk_number));
+            let chunk_file = crate::chunk_manifest::chunk_path(&self.chunks_dir, entry.chunk_number as u64);
             if !chunk_file.exists() {
                 anyhow::bail!("Chunk {} not found: {}", entry.chunk_number, chunk_file.display());
             }
+            crate::chunk_manifest::verify_chunk(&self.chunks_dir, entry.chunk_number as u64)?;
 
-            let zstd_threads = std::cmp::min(6, num_cpus::get().saturating_sub(2));
-            let mut zstd_proc = std::process::Command::new("zstd")
-                .arg("-d")
-                .arg("--stdout")
-                .arg("-q")
-                .arg(format!("-T{}", zstd_threads))
-                .arg(&chunk_file)
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::null())
-                .spawn()
-                .with_context(|| format!("Failed to start zstd for chunk {}", entry.chunk_number))?;
-
-            let stdout = zstd_proc.stdout.take()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-            let reader = std::io::BufReader::with_capacity(128 * 1024 * 1024, stdout);
+            let decoder = decompress_chunk_streaming(&chunk_file)?;
+            let reader = std::io::BufReader::with_capacity(128 * 1024 * 1024, decoder);
             let offset = 0u64;
             
-            readers.insert(entry.chunk_number, (reader, zstd_proc, offset));
+            readers.insert(entry.chunk_number, (reader, offset));
         }
         
         // Now get the reader (we know it exists)
-        let (reader, _proc, current_offset) = readers.get_mut(&entry.chunk_number).unwrap();
+        let (reader, current_offset) = readers.get_mut(&entry.chunk_number).unwrap();
 
         // Seek to block offset if needed
         if *current_offset < entry.offset_in_chunk {
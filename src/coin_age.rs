@@ -0,0 +1,197 @@
+//! Coin age and coin-days-destroyed analytics
+//!
+//! Per-block spend statistics taken from the UTXO set the sequential validation pass already
+//! keeps: for every input, the age of the coin it spends (blocks and days since the creating
+//! block), weighted by value. Coin-days destroyed (CDD) is `Σ value_btc × age_days`.
+//!
+//! Creation times come from [`BlockTimes`]: blocks seen during the pass, plus `chunks.times`
+//! (see [`crate::date_range`]) when it exists. Coins older than both are aged from the height
+//! delta at 10 minutes per block and counted in `estimated_ages`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Satoshis per BTC
+const COIN: f64 = 100_000_000.0;
+const SECS_PER_DAY: f64 = 86_400.0;
+/// Target block spacing used when a creation time is unknown
+const TARGET_SPACING_SECS: u64 = 600;
+
+/// Coin age statistics for the inputs of one block
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoinAgeStats {
+    pub height: u64,
+    /// Header time of the spending block
+    pub time: u32,
+    /// Non-coinbase inputs whose prevout was found in the UTXO set
+    pub inputs: u64,
+    /// Inputs not in the UTXO set before the block (spends of outputs created in the same block)
+    pub unresolved_inputs: u64,
+    pub value_spent_sats: u64,
+    pub coin_days_destroyed: f64,
+    /// `Σ value_btc × age_blocks`
+    pub coin_blocks_destroyed: f64,
+    /// Value-weighted mean age of the spent coins, in days
+    pub mean_age_days: f64,
+    pub max_age_blocks: u64,
+    /// Inputs whose creation time was estimated from the height delta
+    pub estimated_ages: u64,
+}
+
+/// Header times by height, filled as a pass goes and optionally preloaded.
+#[derive(Debug, Clone, Default)]
+pub struct BlockTimes {
+    times: Vec<u32>, // 0 = unknown
+}
+
+impl BlockTimes {
+    /// Start from known header times (`times[h]` = time of block `h`).
+    pub fn from_times(times: Vec<u32>) -> Self {
+        Self { times }
+    }
+
+    /// Preload `chunks.times` from the chunk cache if it has been built; empty otherwise.
+    #[cfg(feature = "chunk-cache")]
+    pub fn preload_from_chunk_cache() -> Self {
+        crate::chunked_cache::get_chunks_dir()
+            .and_then(|dir| crate::date_range::load_header_times(&dir).ok().flatten())
+            .map(|ht| Self::from_times(ht.times().to_vec()))
+            .unwrap_or_default()
+    }
+
+    pub fn record(&mut self, height: u64, time: u32) {
+        let h = height as usize;
+        if self.times.len() <= h {
+            self.times.resize(h + 1, 0);
+        }
+        self.times[h] = time;
+    }
+
+    pub fn get(&self, height: u64) -> Option<u32> {
+        self.times.get(height as usize).copied().filter(|&t| t != 0)
+    }
+}
+
+/// Accumulates [`CoinAgeStats`] while the inputs of one block are looked up.
+#[derive(Debug, Clone)]
+pub struct CoinAgeAccumulator {
+    stats: CoinAgeStats,
+    weighted_age_days: f64,
+}
+
+impl CoinAgeAccumulator {
+    pub fn new(height: u64, time: u32) -> Self {
+        Self {
+            stats: CoinAgeStats {
+                height,
+                time,
+                ..Default::default()
+            },
+            weighted_age_days: 0.0,
+        }
+    }
+
+    /// Record an input spending `value_sats` created at `created_height`.
+    pub fn spend(&mut self, value_sats: u64, created_height: u64, times: &BlockTimes) {
+        let stats = &mut self.stats;
+        let age_blocks = stats.height.saturating_sub(created_height);
+        let age_secs = match times.get(created_height) {
+            // Header times are not monotonic; a coin is never younger than zero.
+            Some(created) => u64::from(stats.time.saturating_sub(created)),
+            None => {
+                stats.estimated_ages += 1;
+                age_blocks * TARGET_SPACING_SECS
+            }
+        };
+        let age_days = age_secs as f64 / SECS_PER_DAY;
+        let btc = value_sats as f64 / COIN;
+
+        stats.inputs += 1;
+        stats.value_spent_sats += value_sats;
+        stats.coin_days_destroyed += btc * age_days;
+        stats.coin_blocks_destroyed += btc * age_blocks as f64;
+        stats.max_age_blocks = stats.max_age_blocks.max(age_blocks);
+        self.weighted_age_days += value_sats as f64 * age_days;
+    }
+
+    /// Record an input whose prevout was not in the UTXO set.
+    pub fn unresolved(&mut self) {
+        self.stats.unresolved_inputs += 1;
+    }
+
+    pub fn finish(mut self) -> CoinAgeStats {
+        if self.stats.value_spent_sats > 0 {
+            self.stats.mean_age_days = self.weighted_age_days / self.stats.value_spent_sats as f64;
+        }
+        self.stats
+    }
+}
+
+/// Write per-block coin age rows as CSV (one header line, heights in the order given).
+pub fn write_csv(path: &Path, rows: &[CoinAgeStats]) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    writeln!(
+        out,
+        "height,time,inputs,unresolved_inputs,value_spent_sats,coin_days_destroyed,\
+         coin_blocks_destroyed,mean_age_days,max_age_blocks,estimated_ages"
+    )?;
+    for r in rows {
+        writeln!(
+            out,
+            "{},{},{},{},{},{:.6},{:.6},{:.6},{},{}",
+            r.height,
+            r.time,
+            r.inputs,
+            r.unresolved_inputs,
+            r.value_spent_sats,
+            r.coin_days_destroyed,
+            r.coin_blocks_destroyed,
+            r.mean_age_days,
+            r.max_age_blocks,
+            r.estimated_ages
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coin_days_weight_value_by_age() {
+        let mut times = BlockTimes::default();
+        times.record(100, 1_000_000);
+        // 2 BTC created 10 days before the spend, 1 BTC of unknown creation time 144 blocks back.
+        let spend_time = 1_000_000 + 10 * 86_400;
+        let mut acc = CoinAgeAccumulator::new(244, spend_time);
+        acc.spend(200_000_000, 100, &times);
+        acc.spend(100_000_000, 100, &BlockTimes::default());
+        acc.unresolved();
+        let s = acc.finish();
+
+        assert_eq!(s.inputs, 2);
+        assert_eq!(s.unresolved_inputs, 1);
+        assert_eq!(s.estimated_ages, 1);
+        assert_eq!(s.max_age_blocks, 144);
+        // 2 BTC × 10 days + 1 BTC × 1 day (144 blocks × 10 min)
+        assert!((s.coin_days_destroyed - 21.0).abs() < 1e-9);
+        assert!((s.coin_blocks_destroyed - 3.0 * 144.0).abs() < 1e-9);
+        assert!((s.mean_age_days - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn backwards_header_time_clamps_to_zero_age() {
+        let times = BlockTimes::from_times(vec![0, 2_000]);
+        let mut acc = CoinAgeAccumulator::new(2, 1_000);
+        acc.spend(COIN as u64, 1, &times);
+        let s = acc.finish();
+        assert_eq!(s.coin_days_destroyed, 0.0);
+        assert_eq!(s.coin_blocks_destroyed, 1.0);
+    }
+}
@@ -1,48 +1,419 @@
-//! Collection-only mode - fast block collection without validation
-//! Validation happens during chunking or at intervals
+//! Collection-only and validation-only entry points
+//!
+//! [`collect_blocks`] reads the block files into the temp file / chunk cache without validating
+//! anything (blocks are only sanity-checked while chunks are cut). [`validate_range`] runs the
+//! differential over blocks that are already available and never starts a collection.
+//! `BlockIterator` itself only iterates.
+//!
+//! With `run_root` set, a validation run gets a content-derived [`RunDir`]: finished chunks are
+//! saved there as they complete, a re-run with the same range, chunking and source only
+//! validates the chunks still missing, and a completed run returns its saved report.
 
 use anyhow::Result;
-use crate::block_file_reader::{BlockFileReader, Network as BlockFileNetwork};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-/// Collect blocks without validation (fast mode)
-/// Blocks are validated during chunking or at intervals
-pub fn collect_blocks_only(
-    data_dir: Option<PathBuf>,
-    _cache_dir: Option<PathBuf>,
-) -> Result<()> {
+use crate::alloc_profile::MemoryProfiler;
+use crate::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+use crate::block_file_reader::{BlockFileReader, Network as BlockFileNetwork};
+use crate::cancel::CancellationToken;
+use crate::node_rpc_client::{NodeRpcClient, RpcConfig};
+use crate::parallel_differential::{
+    run_parallel_differential_with_cancel, BlockDataSource, ChunkResult, ParallelConfig,
+};
+use crate::report::DifferentialReport;
+use crate::run_id::{RunDir, RunSpec};
+use crate::run_summary::RunSummary;
+
+pub use crate::block_file_reader::CollectionReport;
+
+/// Inputs for [`collect_blocks`]
+#[derive(Clone)]
+pub struct CollectionConfig {
+    /// Bitcoin Core data directory (auto-detected when `None`)
+    pub data_dir: Option<PathBuf>,
+    pub network: BlockFileNetwork,
+    /// Cancelling flushes the temp file so the next run resumes from it
+    pub cancel: CancellationToken,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            network: BlockFileNetwork::Mainnet,
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+/// Collect blocks into the chunk cache without validation.
+///
+/// Resumable: an interrupted or cancelled run picks up from the temp file and existing chunks.
+/// Fails for standard (non-XOR-packaged) trees, which are already ordered on disk.
+pub fn collect_blocks(config: CollectionConfig) -> Result<CollectionReport> {
     println!("🚀 Starting collection-only mode (fast, no validation during collection)");
     println!("   Validation will occur during chunking");
-    
-    // Create block file reader
-    let reader = if let Some(dir) = data_dir {
-        BlockFileReader::new(dir, BlockFileNetwork::Mainnet)?
-    } else {
-        BlockFileReader::auto_detect(BlockFileNetwork::Mainnet)?
-    };
-    
+
+    let reader = match config.data_dir {
+        Some(dir) => BlockFileReader::new(dir, config.network)?,
+        None => BlockFileReader::auto_detect(config.network)?,
+    }
+    .with_cancellation(config.cancel);
+
     println!("📂 Block file reader created");
-    
-    // Read all blocks sequentially - this triggers collection
-    // The iterator will automatically write to temp file and chunk incrementally
-    let mut iterator = reader.read_blocks_sequential(None, None)?;
-    
-    let mut count = 0;
-    while let Some(block_result) = iterator.next() {
-        match block_result {
-            Ok(_block_data) => {
-                count += 1;
-                if count % 10000 == 0 {
-                    println!("   📊 Collected {} blocks...", count);
+
+    let report = reader.collect_ordered()?;
+
+    println!(
+        "✅ Collection complete: {} blocks collected from {} files",
+        report.blocks_collected, report.files_processed
+    );
+    crate::sanity::print_report();
+    let summary = RunSummary::for_collection(&report);
+    summary.print();
+    if let Some(dir) = &report.chunks_dir {
+        summary.write_json(&dir.join(SUMMARY_ARTIFACT))?;
+    }
+    Ok(report)
+}
+
+/// Collect blocks without validation (fast mode)
+///
+/// Kept for existing callers; see [`collect_blocks`]. The cache directory comes from
+/// `BLOCK_CACHE_DIR`, so `_cache_dir` is unused.
+pub fn collect_blocks_only(data_dir: Option<PathBuf>, _cache_dir: Option<PathBuf>) -> Result<()> {
+    collect_blocks(CollectionConfig {
+        data_dir,
+        ..Default::default()
+    })
+    .map(|_| ())
+}
+
+/// Inputs for [`validate_range`]
+#[derive(Clone)]
+pub struct ValidationConfig {
+    pub start_height: u64,
+    pub end_height: u64,
+    pub parallel: ParallelConfig,
+    pub source: Arc<BlockDataSource>,
+    pub cancel: CancellationToken,
+    /// Write per-block coin age / coin-days destroyed here as CSV after the run
+    pub coin_age_csv: Option<PathBuf>,
+    /// Keep chunk results and the report under a run directory in this root (see
+    /// [`crate::run_id`]); re-running the same validation resumes it.
+    pub run_root: Option<PathBuf>,
+    /// Write the JSON / CSV [`DifferentialReport`](crate::report::DifferentialReport) here
+    /// (default: `BLVM_REPORT_DIR`). When `None`, the report goes to a named directory under the
+    /// output directory's `reports/` if there is one (see [`crate::artifacts`]; `{run_id}` is
+    /// this run's ID when it has a run directory); run directories always get one under `report/`
+    pub report_dir: Option<PathBuf>,
+    /// Compare checkpoint UTXO totals against this Core's `gettxoutsetinfo` (default: see
+    /// [`crate::utxo_stats::rpc_config_from_env`])
+    pub txoutsetinfo_rpc: Option<RpcConfig>,
+}
+
+impl ValidationConfig {
+    /// Default parallelism, no cancellation.
+    pub fn new(start_height: u64, end_height: u64, source: Arc<BlockDataSource>) -> Self {
+        Self {
+            start_height,
+            end_height,
+            parallel: ParallelConfig::default(),
+            source,
+            cancel: CancellationToken::new(),
+            coin_age_csv: None,
+            run_root: None,
+            report_dir: crate::report::report_dir_from_env(),
+            txoutsetinfo_rpc: crate::utxo_stats::rpc_config_from_env(),
+        }
+    }
+
+    /// What identifies this validation: the range, chunking and block source. Worker count and
+    /// output paths do not change results and are left out.
+    pub fn run_spec(&self) -> RunSpec {
+        let spec = RunSpec::new("validate-range")
+            .param("start_height", self.start_height)
+            .param("end_height", self.end_height)
+            .param("chunk_size", self.parallel.chunk_size)
+            .param("use_checkpoints", self.parallel.use_checkpoints)
+            .input("source", self.source.describe());
+        match crate::chunked_cache::get_chunks_dir() {
+            Some(dir) => spec.input_file("chunks.meta", &dir.join("chunks.meta")),
+            None => spec,
+        }
+    }
+}
+
+/// Aggregated outcome of [`validate_range`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub start_height: u64,
+    pub end_height: u64,
+    /// Per-chunk results (only chunks that finished)
+    pub chunks: Vec<ChunkResult>,
+    pub tested: usize,
+    pub matched: usize,
+    pub divergences: usize,
+    /// The run was cancelled; `chunks` holds what finished before that
+    pub cancelled: bool,
+    pub duration_secs: f64,
+}
+
+impl ValidationReport {
+    /// The report with the run's and every chunk's timings zeroed
+    /// ([deterministic mode](crate::determinism)).
+    pub fn without_timings(mut self) -> Self {
+        self.duration_secs = 0.0;
+        self.chunks = self
+            .chunks
+            .into_iter()
+            .map(ChunkResult::without_timings)
+            .collect();
+        self
+    }
+
+    /// Every tested block matched and nothing diverged.
+    pub fn all_matched(&self) -> bool {
+        self.divergences == 0 && self.matched == self.tested
+    }
+}
+
+const REPORT_ARTIFACT: &str = "report.json";
+const SUMMARY_ARTIFACT: &str = "summary.json";
+const REPORT_DIR_ARTIFACT: &str = "report";
+
+/// Validate `[start_height, end_height]` against already-collected blocks.
+///
+/// Never starts a collection: a direct-file source over an XOR-packaged tree whose chunks do not
+/// cover the chain yet and whose headers cannot be chained is an error (run [`collect_blocks`]
+/// first). Cache and RPC sources are used as-is.
+pub async fn validate_range(config: ValidationConfig) -> Result<ValidationReport> {
+    if let BlockDataSource::DirectFile(reader) = config.source.as_ref() {
+        if reader.needs_collection() {
+            anyhow::bail!(
+                "Block files are XOR-packaged and not fully collected yet - run collect_blocks first"
+            );
+        }
+    }
+
+    let mut run = match &config.run_root {
+        Some(root) => Some(RunDir::open(root, config.run_spec())?),
+        None => None,
+    };
+    if let Some(run) = &run {
+        if run.is_complete() {
+            if let Some(report) = run.read_json::<ValidationReport>(REPORT_ARTIFACT)? {
+                println!(
+                    "✅ Run {} already complete - returning its report",
+                    run.id()
+                );
+                if let Some(summary) = run.read_json::<RunSummary>(SUMMARY_ARTIFACT)? {
+                    summary.print();
                 }
+                return Ok(report);
             }
+        }
+    }
+
+    let start_time = std::time::Instant::now();
+    let profiler = MemoryProfiler::start();
+    let mut chunks = match &run {
+        Some(run) => load_saved_chunks(run)?,
+        None => Vec::new(),
+    };
+    let done: Vec<(u64, u64)> = chunks
+        .iter()
+        .map(|c| (c.start_height, c.end_height))
+        .collect();
+    let pending = uncovered_ranges(config.start_height, config.end_height, &done);
+    if !chunks.is_empty() {
+        println!(
+            "   ♻️  {} chunk(s) already validated, {} range(s) left",
+            chunks.len(),
+            pending.len()
+        );
+    }
+
+    // The driver records each chunk as it finishes, so a killed run keeps finished chunks even
+    // inside a range that was still running
+    let mut parallel = config.parallel.clone();
+    if let Some(run) = &run {
+        parallel.run_state_dir = Some(run.dir().to_path_buf());
+    }
+    for (start, end) in pending {
+        if config.cancel.is_cancelled() {
+            break;
+        }
+        let result = run_parallel_differential_with_cancel(
+            start,
+            end,
+            parallel.clone(),
+            config.source.clone(),
+            config.cancel.clone(),
+        )
+        .await;
+        let finished = match result {
+            Ok(finished) => finished,
             Err(e) => {
-                eprintln!("   ⚠️  Error reading block: {}", e);
+                if let Some(run) = run.as_mut() {
+                    run.mark_failed()?;
+                }
                 return Err(e);
             }
+        };
+        chunks.extend(finished);
+    }
+    chunks.sort_by_key(|c| c.start_height);
+
+    let mut csv_paths: Vec<PathBuf> = config.coin_age_csv.iter().cloned().collect();
+    csv_paths.extend(run.as_ref().map(|r| r.artifact_path("coin_age.csv")));
+    if !csv_paths.is_empty() {
+        let mut rows: Vec<_> = chunks
+            .iter()
+            .flat_map(|c| c.coin_age.iter().cloned())
+            .collect();
+        rows.sort_by_key(|r| r.height);
+        for path in &csv_paths {
+            crate::coin_age::write_csv(path, &rows)?;
+            println!(
+                "📈 Wrote coin age for {} blocks to {}",
+                rows.len(),
+                path.display()
+            );
         }
     }
-    
-    println!("✅ Collection complete: {} blocks collected", count);
-    Ok(())
+
+    let report = ValidationReport {
+        start_height: config.start_height,
+        end_height: config.end_height,
+        tested: chunks.iter().map(|c| c.tested).sum(),
+        matched: chunks.iter().map(|c| c.matched).sum(),
+        divergences: chunks.iter().map(|c| c.divergences.len()).sum(),
+        chunks,
+        cancelled: config.cancel.is_cancelled(),
+        duration_secs: start_time.elapsed().as_secs_f64(),
+    };
+
+    let mut summary = RunSummary::for_validation(&report);
+    summary.memory = profiler.map(MemoryProfiler::finish);
+    for path in &csv_paths {
+        summary.add_artifact("coin age", path);
+    }
+    let mut differential = DifferentialReport::from_validation(&report);
+    if crate::determinism::enabled() {
+        differential = differential.without_timings();
+    }
+    if let Some(rpc) = &config.txoutsetinfo_rpc {
+        if !differential.checkpoints.is_empty() {
+            let rpc = NodeRpcClient::new(rpc.clone());
+            differential.utxo_drift =
+                crate::utxo_stats::compare_checkpoints(&rpc, &differential.checkpoints).await;
+            crate::utxo_stats::print_summary(&differential.utxo_drift);
+        }
+    }
+    let mut report_dirs: Vec<PathBuf> = config.report_dir.iter().cloned().collect();
+    if config.report_dir.is_none() {
+        let mut ctx = ArtifactContext::now().with_range(config.start_height, config.end_height);
+        if let Some(run) = &run {
+            ctx = ctx.with_run_id(run.id().as_str());
+        }
+        report_dirs.extend(artifact_dir_from_env(ArtifactKind::Reports, &ctx));
+    }
+    report_dirs.extend(run.as_ref().map(|r| r.artifact_path(REPORT_DIR_ARTIFACT)));
+    if !report_dirs.is_empty() {
+        for dir in &report_dirs {
+            differential.write(dir)?;
+            summary.add_artifact("differential report", dir);
+        }
+    }
+    if let Some(run) = &run {
+        summary.add_artifact("run directory", run.dir());
+    }
+    summary.finish();
+    crate::sanity::print_report();
+    summary.print();
+
+    if let Some(run) = run.as_mut() {
+        if crate::determinism::enabled() {
+            run.write_json(SUMMARY_ARTIFACT, &summary.clone().without_timings())?;
+            run.write_json(REPORT_ARTIFACT, &report.clone().without_timings())?;
+        } else {
+            run.write_json(SUMMARY_ARTIFACT, &summary)?;
+            run.write_json(REPORT_ARTIFACT, &report)?;
+        }
+        let covered: Vec<(u64, u64)> = report
+            .chunks
+            .iter()
+            .map(|c| (c.start_height, c.end_height))
+            .collect();
+        if !report.cancelled
+            && uncovered_ranges(report.start_height, report.end_height, &covered).is_empty()
+        {
+            run.mark_complete()?;
+        }
+    }
+    Ok(report)
+}
+
+/// Chunk results saved by earlier invocations of this run.
+fn load_saved_chunks(run: &RunDir) -> Result<Vec<ChunkResult>> {
+    let dir = run.artifact_path(crate::run_state::CHUNKS_DIR);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut chunks = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        let Some(name) = name.to_str().filter(|n| n.ends_with(".json")) else {
+            continue;
+        };
+        if let Some(chunk) =
+            run.read_json::<ChunkResult>(&format!("{}/{}", crate::run_state::CHUNKS_DIR, name))?
+        {
+            chunks.push(chunk);
+        }
+    }
+    Ok(chunks)
+}
+
+/// Sub-ranges of `[start, end]` not covered by `done` (inclusive ranges, any order).
+fn uncovered_ranges(start: u64, end: u64, done: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut done = done.to_vec();
+    done.sort_unstable();
+    let mut gaps = Vec::new();
+    let mut next = start;
+    for (s, e) in done {
+        if next > end {
+            break;
+        }
+        if s > next {
+            gaps.push((next, (s - 1).min(end)));
+        }
+        next = next.max(e.saturating_add(1));
+    }
+    if next <= end {
+        gaps.push((next, end));
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncovered_ranges_fill_gaps_between_saved_chunks() {
+        assert_eq!(uncovered_ranges(0, 99, &[]), vec![(0, 99)]);
+        assert_eq!(
+            uncovered_ranges(0, 99, &[(50, 74), (0, 24)]),
+            vec![(25, 49), (75, 99)]
+        );
+        assert!(uncovered_ranges(0, 99, &[(0, 49), (50, 99)]).is_empty());
+        assert_eq!(
+            uncovered_ranges(10, 20, &[(0, 12), (18, 30)]),
+            vec![(13, 17)]
+        );
+    }
 }
@@ -0,0 +1,428 @@
+//! Performance tuning loaded at runtime
+//!
+//! Buffer sizes, thread counts and chunking intervals used by block file reading and collection
+//! used to be compile-time constants tuned for one machine (i7-8700K, 15GB RAM, NVMe). They now
+//! come from [`BenchConfig`]: built-in defaults (those same values), then `blvm-bench.toml`, then
+//! environment variables.
+//!
+//! The file is `BLVM_BENCH_CONFIG` if set, else `./blvm-bench.toml`, else
+//! `<config dir>/blvm-bench/blvm-bench.toml` (e.g. `~/.config/blvm-bench/blvm-bench.toml`). Every
+//! field can be overridden with `BLVM_BENCH_<SECTION>_<FIELD>`, e.g.
+//! `BLVM_BENCH_IO_BUFFER_SIZE=67108864`; the chunk directory keeps its existing `BLOCK_CACHE_DIR`,
+//! and `BLVM_BENCH_STORAGE_ARCHIVES` takes a `PATH`-style list.
+//!
+//! ```toml
+//! # HDD box with 8GB RAM
+//! [io]
+//! buffer_size = 67108864
+//! max_parallel_read_threads = 4
+//!
+//! [chunks]
+//! dir = "/mnt/archive/blvm-chunks"
+//!
+//! # Compress on the NVMe, spill finished chunks to a second disk once the first fills up
+//! [storage]
+//! scratch = "/nvme/blvm-scratch"
+//! archives = ["/mnt/archive2/blvm-chunks"]
+//! # Wait for space instead of stopping when a volume drops below 20 GiB
+//! low_space_bytes = 21474836480
+//! low_space_action = "pause"
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+const CONFIG_FILE_NAME: &str = "blvm-bench.toml";
+
+/// All runtime tuning; see the module docs for where values come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BenchConfig {
+    pub io: IoTuning,
+    pub collection: CollectionTuning,
+    pub chunks: ChunkTuning,
+    pub storage: StorageTuning,
+}
+
+/// Block file I/O and read parallelism.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IoTuning {
+    /// Buffer for block file reads and temp/chunk writes (bytes). Fewer syscalls on large files;
+    /// 64 MiB is plenty for an HDD, NVMe benefits from 128 MiB and up.
+    pub buffer_size: usize,
+    /// Buffer for magic-byte searches in encrypted / out-of-order files (bytes)
+    pub search_buffer_size: usize,
+    /// Threads reading block files in parallel; each holds one `buffer_size` buffer, so
+    /// 8 × 128 MiB = 1 GiB of buffers
+    pub max_parallel_read_threads: usize,
+    /// Files read per parallel batch
+    pub parallel_file_batch_size: usize,
+    /// Blocks in flight per file between reader threads and the temp writer; peak memory is
+    /// about `max_parallel_read_threads × collection_pipeline_depth` blocks
+    pub collection_pipeline_depth: usize,
+    /// Files copied to the local cache ahead of the read position (remote mounts such as SSHFS)
+    pub pre_copy_lookahead: usize,
+    /// Background threads copying block files from remote mounts
+    pub file_copy_worker_threads: usize,
+    /// Blocks a background thread reads (and de-obfuscates) ahead of validation in sequential
+    /// reads, overlapping disk I/O with `connect_block`; 0 reads inline
+    pub prefetch_depth: usize,
+    /// 1 MiB reads kept in flight per file by the io_uring reader (`io-uring` feature, Linux) for
+    /// block and chunk files; 0 uses buffered reads
+    pub uring_queue_depth: u32,
+}
+
+impl Default for IoTuning {
+    fn default() -> Self {
+        Self {
+            buffer_size: 128 * 1024 * 1024,
+            search_buffer_size: 128 * 1024 * 1024,
+            max_parallel_read_threads: 8,
+            parallel_file_batch_size: 12,
+            collection_pipeline_depth: 64,
+            pre_copy_lookahead: 200,
+            file_copy_worker_threads: 8,
+            prefetch_depth: 32,
+            uring_queue_depth: 32,
+        }
+    }
+}
+
+/// Collection pass over out-of-order (XOR-packaged) block files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionTuning {
+    /// Blocks per batch when building the prev-hash map (only headers are kept; 500 blocks at
+    /// ~1.5 MB is ~750 MB in flight)
+    pub hash_map_chunk_size: usize,
+    /// Blocks between progress lines
+    pub progress_report_interval: usize,
+    /// Blocks between temp file flushes (what survives a SIGKILL)
+    pub temp_file_flush_interval: usize,
+    /// Blocks between read-back checks of the temp file
+    pub temp_file_integrity_check_interval: usize,
+}
+
+impl Default for CollectionTuning {
+    fn default() -> Self {
+        Self {
+            hash_map_chunk_size: 500,
+            progress_report_interval: 10_000,
+            temp_file_flush_interval: 500,
+            temp_file_integrity_check_interval: 10_000,
+        }
+    }
+}
+
+/// Incremental chunk cache written during collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkTuning {
+    /// Blocks per `chunk_N.bin.zst` (must match existing chunks when resuming)
+    pub incremental_chunk_size: usize,
+    /// Chunked block count at which a collection counts as covering the whole chain
+    /// (approximate mainnet height)
+    pub full_chain_blocks: u64,
+    /// Chunk directory; `BLOCK_CACHE_DIR` overrides it, unset falls back to
+    /// `.cache/blvm-bench/chunks`
+    pub dir: Option<PathBuf>,
+    /// zstd level for new chunks (1-22); 3 is 10-15% smaller than 1 for little extra time
+    pub compression_level: i32,
+    /// zstd worker threads per chunk being compressed; 0 = all cores
+    pub compression_threads: u32,
+}
+
+impl Default for ChunkTuning {
+    fn default() -> Self {
+        Self {
+            incremental_chunk_size: 125_000,
+            full_chain_blocks: 900_000,
+            dir: None,
+            compression_level: 3,
+            compression_threads: 0,
+        }
+    }
+}
+
+/// Where chunk files live: a scratch dir they are compressed in, then archive dirs (see
+/// `storage_tiers`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageTuning {
+    /// Scratch dir for chunks being compressed; unset uses `chunks/` next to the collection temp
+    /// file
+    pub scratch: Option<PathBuf>,
+    /// Archive dirs after the chunk dir, in placement order. A new chunk goes to the first one
+    /// with room; chunk metadata and the manifest stay in the chunk dir
+    pub archives: Vec<PathBuf>,
+    /// Free space to leave on an archive after placing a chunk (bytes)
+    pub min_free_bytes: u64,
+    /// Free space on a volume a long run writes to below which it pauses or stops (bytes; see
+    /// `disk_watchdog`)
+    pub low_space_bytes: u64,
+    /// `pause` until space is freed, or `stop` with resumable state
+    pub low_space_action: crate::disk_watchdog::LowSpaceAction,
+}
+
+impl Default for StorageTuning {
+    fn default() -> Self {
+        Self {
+            scratch: None,
+            archives: Vec::new(),
+            min_free_bytes: 1024 * 1024 * 1024,
+            low_space_bytes: 5 * 1024 * 1024 * 1024,
+            low_space_action: crate::disk_watchdog::LowSpaceAction::default(),
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Defaults, then the config file (if one is found), then env overrides.
+    pub fn load() -> Result<Self> {
+        let mut config = match config_file_path()? {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    /// Apply `BLVM_BENCH_<SECTION>_<FIELD>` (and `BLOCK_CACHE_DIR`) overrides.
+    pub fn apply_env(&mut self) -> Result<()> {
+        let io = &mut self.io;
+        env_override("BLVM_BENCH_IO_BUFFER_SIZE", &mut io.buffer_size)?;
+        env_override(
+            "BLVM_BENCH_IO_SEARCH_BUFFER_SIZE",
+            &mut io.search_buffer_size,
+        )?;
+        env_override(
+            "BLVM_BENCH_IO_MAX_PARALLEL_READ_THREADS",
+            &mut io.max_parallel_read_threads,
+        )?;
+        env_override(
+            "BLVM_BENCH_IO_PARALLEL_FILE_BATCH_SIZE",
+            &mut io.parallel_file_batch_size,
+        )?;
+        env_override(
+            "BLVM_BENCH_IO_COLLECTION_PIPELINE_DEPTH",
+            &mut io.collection_pipeline_depth,
+        )?;
+        env_override(
+            "BLVM_BENCH_IO_PRE_COPY_LOOKAHEAD",
+            &mut io.pre_copy_lookahead,
+        )?;
+        env_override(
+            "BLVM_BENCH_IO_FILE_COPY_WORKER_THREADS",
+            &mut io.file_copy_worker_threads,
+        )?;
+        env_override("BLVM_BENCH_IO_PREFETCH_DEPTH", &mut io.prefetch_depth)?;
+        env_override("BLVM_BENCH_IO_URING_QUEUE_DEPTH", &mut io.uring_queue_depth)?;
+
+        let collection = &mut self.collection;
+        env_override(
+            "BLVM_BENCH_COLLECTION_HASH_MAP_CHUNK_SIZE",
+            &mut collection.hash_map_chunk_size,
+        )?;
+        env_override(
+            "BLVM_BENCH_COLLECTION_PROGRESS_REPORT_INTERVAL",
+            &mut collection.progress_report_interval,
+        )?;
+        env_override(
+            "BLVM_BENCH_COLLECTION_TEMP_FILE_FLUSH_INTERVAL",
+            &mut collection.temp_file_flush_interval,
+        )?;
+        env_override(
+            "BLVM_BENCH_COLLECTION_TEMP_FILE_INTEGRITY_CHECK_INTERVAL",
+            &mut collection.temp_file_integrity_check_interval,
+        )?;
+
+        let chunks = &mut self.chunks;
+        env_override(
+            "BLVM_BENCH_CHUNKS_INCREMENTAL_CHUNK_SIZE",
+            &mut chunks.incremental_chunk_size,
+        )?;
+        env_override(
+            "BLVM_BENCH_CHUNKS_FULL_CHAIN_BLOCKS",
+            &mut chunks.full_chain_blocks,
+        )?;
+        env_override(
+            "BLVM_BENCH_CHUNKS_COMPRESSION_LEVEL",
+            &mut chunks.compression_level,
+        )?;
+        env_override(
+            "BLVM_BENCH_CHUNKS_COMPRESSION_THREADS",
+            &mut chunks.compression_threads,
+        )?;
+        if let Some(dir) = std::env::var_os("BLOCK_CACHE_DIR").filter(|s| !s.is_empty()) {
+            chunks.dir = Some(PathBuf::from(dir));
+        }
+
+        let storage = &mut self.storage;
+        if let Some(dir) = std::env::var_os("BLVM_BENCH_STORAGE_SCRATCH").filter(|s| !s.is_empty())
+        {
+            storage.scratch = Some(PathBuf::from(dir));
+        }
+        // A `PATH`-style list (`:`-separated, `;` on Windows)
+        if let Some(dirs) = std::env::var_os("BLVM_BENCH_STORAGE_ARCHIVES") {
+            storage.archives = std::env::split_paths(&dirs)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect();
+        }
+        env_override(
+            "BLVM_BENCH_STORAGE_MIN_FREE_BYTES",
+            &mut storage.min_free_bytes,
+        )?;
+        env_override(
+            "BLVM_BENCH_STORAGE_LOW_SPACE_BYTES",
+            &mut storage.low_space_bytes,
+        )?;
+        env_override(
+            "BLVM_BENCH_STORAGE_LOW_SPACE_ACTION",
+            &mut storage.low_space_action,
+        )?;
+        Ok(())
+    }
+
+    /// Zero sizes, intervals or thread counts would divide by zero or stall the pipeline.
+    pub fn validate(&self) -> Result<()> {
+        let nonzero = [
+            ("io.buffer_size", self.io.buffer_size),
+            ("io.search_buffer_size", self.io.search_buffer_size),
+            (
+                "io.max_parallel_read_threads",
+                self.io.max_parallel_read_threads,
+            ),
+            (
+                "io.parallel_file_batch_size",
+                self.io.parallel_file_batch_size,
+            ),
+            (
+                "io.collection_pipeline_depth",
+                self.io.collection_pipeline_depth,
+            ),
+            (
+                "io.file_copy_worker_threads",
+                self.io.file_copy_worker_threads,
+            ),
+            (
+                "collection.hash_map_chunk_size",
+                self.collection.hash_map_chunk_size,
+            ),
+            (
+                "collection.progress_report_interval",
+                self.collection.progress_report_interval,
+            ),
+            (
+                "collection.temp_file_flush_interval",
+                self.collection.temp_file_flush_interval,
+            ),
+            (
+                "collection.temp_file_integrity_check_interval",
+                self.collection.temp_file_integrity_check_interval,
+            ),
+            (
+                "chunks.incremental_chunk_size",
+                self.chunks.incremental_chunk_size,
+            ),
+        ];
+        for (name, value) in nonzero {
+            anyhow::ensure!(value > 0, "{} must be greater than 0", name);
+        }
+        anyhow::ensure!(
+            (1..=22).contains(&self.chunks.compression_level),
+            "chunks.compression_level must be between 1 and 22"
+        );
+        Ok(())
+    }
+}
+
+/// The process-wide config, loaded on first use. A broken config file is reported once and the
+/// built-in defaults are used.
+pub fn global() -> &'static BenchConfig {
+    static CONFIG: OnceLock<BenchConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| match BenchConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("⚠️  {:#} - using built-in tuning defaults", e);
+            BenchConfig::default()
+        }
+    })
+}
+
+/// Configured chunk directory: `BLOCK_CACHE_DIR` as set right now, else `chunks.dir`.
+pub fn chunk_dir() -> Option<PathBuf> {
+    std::env::var_os("BLOCK_CACHE_DIR")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| global().chunks.dir.clone())
+}
+
+fn config_file_path() -> Result<Option<PathBuf>> {
+    if let Some(path) = std::env::var_os("BLVM_BENCH_CONFIG").filter(|s| !s.is_empty()) {
+        let path = PathBuf::from(path);
+        anyhow::ensure!(
+            path.is_file(),
+            "BLVM_BENCH_CONFIG points to {}, which does not exist",
+            path.display()
+        );
+        return Ok(Some(path));
+    }
+    let local = PathBuf::from(CONFIG_FILE_NAME);
+    if local.is_file() {
+        return Ok(Some(local));
+    }
+    Ok(dirs::config_dir()
+        .map(|dir| dir.join("blvm-bench").join(CONFIG_FILE_NAME))
+        .filter(|path| path.is_file()))
+}
+
+fn env_override<T: FromStr>(name: &str, field: &mut T) -> Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Ok(value) = std::env::var(name) {
+        *field = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid {}={:?}: {}", name, value, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_file_keeps_defaults_and_rejects_unknown_keys() {
+        let config: BenchConfig =
+            toml::from_str("[io]\nbuffer_size = 67108864\n\n[chunks]\ndir = \"/mnt/chunks\"\n")
+                .unwrap();
+        assert_eq!(config.io.buffer_size, 64 * 1024 * 1024);
+        assert_eq!(config.io.max_parallel_read_threads, 8);
+        assert_eq!(config.collection, CollectionTuning::default());
+        assert_eq!(config.chunks.dir, Some(PathBuf::from("/mnt/chunks")));
+        assert_eq!(config.chunks.incremental_chunk_size, 125_000);
+
+        assert!(toml::from_str::<BenchConfig>("[io]\nbufer_size = 1\n").is_err());
+
+        let mut zero = BenchConfig::default();
+        zero.collection.temp_file_flush_interval = 0;
+        assert!(zero.validate().is_err());
+
+        let mut level = BenchConfig::default();
+        level.chunks.compression_level = 23;
+        assert!(level.validate().is_err());
+    }
+}
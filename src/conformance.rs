@@ -0,0 +1,621 @@
+//! Conformance mode: Core's JSON test vectors run through BLVM
+//!
+//! Core ships its consensus edge cases as data in `src/test/data/`: `tx_valid.json` /
+//! `tx_invalid.json` (whole transactions with their spent outputs), `script_tests.json` (script
+//! pairs with the expected script error) and `sighash.json` (legacy signature hashes for random
+//! transactions and hash types). Every row there is a verdict Core's own unit tests hold it to, so
+//! replaying them through BLVM checks hundreds of edge cases without a node or a block cache.
+//!
+//! [`run_conformance`] runs whichever of the four files a directory holds and returns a
+//! [`ConformanceMatrix`]: pass / fail counts per suite and per tag (the script flags a vector
+//! names, or the hash type for `sighash.json`), plus the first failures of each suite.
+//!
+//! Semantics follow Core's `transaction_tests.cpp` / `sighash_tests.cpp`:
+//!
+//! - `tx_valid.json` lists the flags to *exclude*: the transaction must pass `CheckTransaction`
+//!   and every input must verify under all other flags
+//! - `tx_invalid.json` lists the flags to apply, plus `BADTX` for transactions
+//!   `CheckTransaction` rejects: the vector passes when BLVM rejects the transaction
+//! - `script_tests.json` rows go through [`crate::script_differential`], so they are also
+//!   compared with `libbitcoinconsensus` where its flags allow; taproot template rows are skipped
+//! - `sighash.json` digests are compared in display (reversed) hex
+
+use anyhow::{Context, Result};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::transaction::check_transaction;
+use blvm_protocol::transaction_hash::{calculate_transaction_sighash, SighashType};
+use blvm_protocol::types::{Network, OutPoint, Transaction, TransactionOutput, ValidationResult};
+use blvm_protocol::witness::is_witness_empty;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::deep_analysis::dataset::{Column, ColumnType, Dataset, Value};
+use crate::script_differential::{
+    all_flags, evaluate_case, flag_names, load_script_tests, parse_flags, parse_script_asm,
+};
+
+/// Failures kept in full per suite.
+const MAX_KEPT_FAILURES: usize = 50;
+
+/// Columns of the matrix table: one row per suite and tag
+pub const COLUMNS: &[Column] = &[
+    Column::new("suite", ColumnType::Utf8),
+    Column::new("tag", ColumnType::Utf8),
+    Column::new("passed", ColumnType::UInt64),
+    Column::new("failed", ColumnType::UInt64),
+];
+
+/// One of Core's fixture files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Suite {
+    TxValid,
+    TxInvalid,
+    ScriptTests,
+    Sighash,
+}
+
+impl Suite {
+    pub const ALL: [Suite; 4] = [
+        Suite::TxValid,
+        Suite::TxInvalid,
+        Suite::ScriptTests,
+        Suite::Sighash,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Suite::TxValid => "tx_valid",
+            Suite::TxInvalid => "tx_invalid",
+            Suite::ScriptTests => "script_tests",
+            Suite::Sighash => "sighash",
+        }
+    }
+
+    /// File name under Core's `src/test/data/`.
+    pub fn file_name(self) -> String {
+        format!("{}.json", self.name())
+    }
+}
+
+/// A vector BLVM got wrong.
+#[derive(Debug, Clone, Serialize)]
+pub struct VectorFailure {
+    /// Row in the JSON file, comment rows included
+    pub row: usize,
+    /// The vector's comment, or the nearest comment row above it
+    pub comment: String,
+    pub expected: String,
+    pub blvm: String,
+}
+
+/// Pass / fail counts for one tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub passed: u64,
+    pub failed: u64,
+}
+
+/// Results of one fixture file.
+#[derive(Debug, Clone, Serialize)]
+pub struct SuiteResult {
+    pub suite: Suite,
+    pub passed: u64,
+    pub failed: u64,
+    /// Rows not evaluated (taproot templates in `script_tests.json`)
+    pub skipped: u64,
+    pub by_tag: BTreeMap<String, Tally>,
+    /// The first [`MAX_KEPT_FAILURES`] failures
+    pub failures: Vec<VectorFailure>,
+}
+
+impl SuiteResult {
+    fn new(suite: Suite) -> Self {
+        Self {
+            suite,
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            by_tag: BTreeMap::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    fn record<'a>(
+        &mut self,
+        tags: impl IntoIterator<Item = &'a str>,
+        failure: Option<VectorFailure>,
+    ) {
+        let passed = failure.is_none();
+        for tag in tags {
+            let tally = self.by_tag.entry(tag.to_string()).or_default();
+            if passed {
+                tally.passed += 1;
+            } else {
+                tally.failed += 1;
+            }
+        }
+        match failure {
+            None => self.passed += 1,
+            Some(failure) => {
+                self.failed += 1;
+                if self.failures.len() < MAX_KEPT_FAILURES {
+                    self.failures.push(failure);
+                }
+            }
+        }
+    }
+}
+
+/// Results of a conformance run, one entry per fixture file found.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConformanceMatrix {
+    pub suites: Vec<SuiteResult>,
+}
+
+impl ConformanceMatrix {
+    pub fn passed(&self) -> bool {
+        self.suites.iter().all(|s| s.failed == 0)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.suites.iter().map(|s| s.failed).sum()
+    }
+
+    /// Log one line per suite and one per tag with failures, then the kept failures.
+    pub fn log_summary(&self) {
+        for suite in &self.suites {
+            info!(
+                "📚 {:<13} {:>5} passed {:>5} failed {:>5} skipped",
+                suite.suite.name(),
+                suite.passed,
+                suite.failed,
+                suite.skipped
+            );
+            for (tag, tally) in suite.by_tag.iter().filter(|(_, t)| t.failed > 0) {
+                info!(
+                    "   {:<40} {:>5} passed {:>5} failed",
+                    tag, tally.passed, tally.failed
+                );
+            }
+            for failure in suite.failures.iter().take(10) {
+                warn!(
+                    "   ❌ row {}: expected {}, BLVM {} ({})",
+                    failure.row, failure.expected, failure.blvm, failure.comment
+                );
+            }
+        }
+    }
+}
+
+impl Dataset for ConformanceMatrix {
+    fn name(&self) -> &'static str {
+        "conformance_matrix"
+    }
+
+    fn schema(&self) -> &'static [Column] {
+        COLUMNS
+    }
+
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+        Box::new(self.suites.iter().flat_map(|suite| {
+            suite.by_tag.iter().map(|(tag, tally)| {
+                vec![
+                    suite.suite.name().into(),
+                    tag.as_str().into(),
+                    tally.passed.into(),
+                    tally.failed.into(),
+                ]
+            })
+        }))
+    }
+}
+
+/// Run every fixture file present in `dir`; missing ones are logged and left out.
+pub fn run_conformance(dir: &Path) -> Result<ConformanceMatrix> {
+    let mut matrix = ConformanceMatrix::default();
+    for suite in Suite::ALL {
+        let path = dir.join(suite.file_name());
+        if !path.exists() {
+            warn!(
+                "⚠️  No {} in {}, skipping",
+                suite.file_name(),
+                dir.display()
+            );
+            continue;
+        }
+        matrix.suites.push(run_suite(suite, &path)?);
+    }
+    Ok(matrix)
+}
+
+/// Run one fixture file.
+pub fn run_suite(suite: Suite, path: &Path) -> Result<SuiteResult> {
+    match suite {
+        Suite::ScriptTests => run_script_tests(path),
+        _ => {
+            let rows = read_rows(path)?;
+            match suite {
+                Suite::Sighash => run_sighash_rows(&rows),
+                _ => run_tx_rows(suite, &rows),
+            }
+        }
+    }
+}
+
+fn read_rows(path: &Path) -> Result<Vec<serde_json::Value>> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&data).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// One output a `tx_valid.json` / `tx_invalid.json` transaction spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prevout {
+    pub outpoint: OutPoint,
+    pub script_pubkey: Vec<u8>,
+    pub amount: i64,
+}
+
+/// A `tx_valid.json` / `tx_invalid.json` row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxVector {
+    pub prevouts: Vec<Prevout>,
+    pub tx: Vec<u8>,
+    /// Flags as named in the row: excluded for `tx_valid.json`, applied for `tx_invalid.json`
+    pub flags: u32,
+    /// `BADTX`: `CheckTransaction` must reject the transaction
+    pub bad_tx: bool,
+}
+
+/// Parse `[[[prevout hash, index, scriptPubKey asm, amount?], ...], tx hex, flags]`.
+pub fn parse_tx_row(fields: &[serde_json::Value]) -> Result<TxVector> {
+    anyhow::ensure!(fields.len() == 3, "Expected prevouts, transaction, flags");
+    let prevouts = fields[0]
+        .as_array()
+        .context("Prevouts are not an array")?
+        .iter()
+        .map(|prevout| {
+            let p = prevout
+                .as_array()
+                .filter(|p| (3..=4).contains(&p.len()))
+                .context("Prevout is not [hash, index, scriptPubKey, amount?]")?;
+            let hash_hex = p[0].as_str().context("Prevout hash is not a string")?;
+            let mut hash: [u8; 32] = hex::decode(hash_hex)
+                .ok()
+                .and_then(|h| h.try_into().ok())
+                .with_context(|| format!("Bad prevout hash {:?}", hash_hex))?;
+            hash.reverse();
+            // -1 stands for the null prevout's 0xffffffff
+            let index = p[1].as_i64().context("Prevout index is not a number")? as u32;
+            let asm = p[2]
+                .as_str()
+                .context("Prevout scriptPubKey is not a string")?;
+            let amount = match p.get(3) {
+                Some(a) => a.as_i64().context("Prevout amount is not a number")?,
+                None => 0,
+            };
+            Ok(Prevout {
+                outpoint: OutPoint { hash, index },
+                script_pubkey: parse_script_asm(asm)?,
+                amount,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let tx_hex = fields[1].as_str().context("Transaction is not a string")?;
+    let tx = hex::decode(tx_hex).context("Bad transaction hex")?;
+    let flags = fields[2].as_str().context("Flags are not a string")?;
+    let bad_tx = flags.split(',').any(|f| f.trim() == "BADTX");
+    let named: Vec<&str> = flags
+        .split(',')
+        .map(str::trim)
+        .filter(|f| *f != "BADTX")
+        .collect();
+    Ok(TxVector {
+        prevouts,
+        tx,
+        flags: parse_flags(&named.join(","))?,
+        bad_tx,
+    })
+}
+
+/// Decode a standalone transaction (with witnesses), wrapped as the only transaction of an
+/// empty-header block so the block deserializer can parse it.
+pub(crate) fn decode_tx(bytes: &[u8]) -> Result<(Transaction, Vec<Witness>)> {
+    let mut block = vec![0u8; 80];
+    block.push(1);
+    block.extend_from_slice(bytes);
+    let (block, witnesses) =
+        deserialize_block_with_witnesses(&block).context("Failed to deserialize transaction")?;
+    let tx = block
+        .transactions
+        .into_iter()
+        .next()
+        .context("Decoded block has no transaction")?;
+    let witnesses = witnesses.into_iter().next().unwrap_or_default();
+    Ok((tx, witnesses))
+}
+
+/// BLVM's verdict on `vector` under `flags`: `Ok` when `CheckTransaction` passes and every
+/// input verifies, else why not.
+pub fn verify_tx_vector(vector: &TxVector, flags: u32) -> Result<(), String> {
+    let (tx, witnesses) = decode_tx(&vector.tx).map_err(|e| format!("undecodable: {:#}", e))?;
+    if !matches!(check_transaction(&tx), Ok(ValidationResult::Valid)) {
+        return Err("CheckTransaction failed".to_string());
+    }
+    let mut spent = Vec::with_capacity(tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let prevout = vector
+            .prevouts
+            .iter()
+            .find(|p| p.outpoint == input.prevout)
+            .ok_or_else(|| format!("input {} spends an output the vector does not list", i))?;
+        spent.push(prevout);
+    }
+    let values: Vec<i64> = spent.iter().map(|p| p.amount).collect();
+    let script_pubkeys: Vec<&[u8]> = spent.iter().map(|p| p.script_pubkey.as_slice()).collect();
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let witness = witnesses.get(i).filter(|w| !is_witness_empty(w));
+        match verify_script_with_context_full(
+            &input.script_sig,
+            script_pubkeys[i],
+            witness,
+            flags,
+            &tx,
+            i,
+            &values,
+            &script_pubkeys,
+            None,
+            None,
+            Network::Mainnet,
+            SigVersion::Base,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            Ok(true) => {}
+            Ok(false) => return Err(format!("input {}: script returned false", i)),
+            Err(e) => return Err(format!("input {}: {:?}", i, e)),
+        }
+    }
+    Ok(())
+}
+
+fn run_tx_rows(suite: Suite, rows: &[serde_json::Value]) -> Result<SuiteResult> {
+    let expect_valid = suite == Suite::TxValid;
+    let mut result = SuiteResult::new(suite);
+    let mut comment = String::new();
+    for (row, value) in rows.iter().enumerate() {
+        let Some(fields) = value.as_array() else {
+            continue;
+        };
+        if fields.iter().all(serde_json::Value::is_string) {
+            // Comment rows describe the vectors below them
+            comment = fields
+                .iter()
+                .filter_map(|f| f.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            continue;
+        }
+        let vector =
+            parse_tx_row(fields).with_context(|| format!("{} row {}", suite.file_name(), row))?;
+        let verdict = if expect_valid {
+            verify_tx_vector(&vector, all_flags() & !vector.flags)
+        } else {
+            verify_tx_vector(&vector, vector.flags)
+        };
+        let failure = match (expect_valid, verdict) {
+            (true, Err(reason)) => Some(("valid".to_string(), reason)),
+            (false, Ok(())) => Some(("invalid".to_string(), "valid".to_string())),
+            _ => None,
+        }
+        .map(|(expected, blvm)| VectorFailure {
+            row,
+            comment: comment.clone(),
+            expected,
+            blvm,
+        });
+        let mut tags = flag_names(vector.flags);
+        if vector.bad_tx {
+            tags.push("BADTX");
+        }
+        result.record(tags, failure);
+    }
+    Ok(result)
+}
+
+fn run_script_tests(path: &Path) -> Result<SuiteResult> {
+    let (cases, skipped) = load_script_tests(path)?;
+    let mut result = SuiteResult::new(Suite::ScriptTests);
+    result.skipped = skipped as u64;
+    for (index, case) in cases.iter().enumerate() {
+        let outcome = evaluate_case(case);
+        let failure = outcome.divergence(case).map(|divergence| VectorFailure {
+            // load_script_tests drops comment rows, so this is the case's position
+            row: index,
+            comment: case.comment.clone(),
+            expected: case.expected.clone().unwrap_or_default(),
+            blvm: format!(
+                "{} ({:?}, libbitcoinconsensus {:?}: {:?})",
+                if outcome.blvm_ok { "OK" } else { "failed" },
+                outcome.blvm_error,
+                outcome.core_ok,
+                divergence
+            ),
+        });
+        result.record(flag_names(case.flags), failure);
+    }
+    Ok(result)
+}
+
+/// A `sighash.json` row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashVector {
+    pub tx: Vec<u8>,
+    pub script_code: Vec<u8>,
+    pub input_index: usize,
+    /// The full 32-bit hash type, as Core serializes it into the preimage
+    pub hash_type: u32,
+    /// Display (reversed) hex
+    pub expected: String,
+}
+
+/// Parse `[raw transaction, script hex, input index, hash type, signature hash]`.
+pub fn parse_sighash_row(fields: &[serde_json::Value]) -> Result<SighashVector> {
+    anyhow::ensure!(
+        fields.len() == 5,
+        "Expected transaction, script, input index, hash type, hash"
+    );
+    let text = |i: usize| fields[i].as_str().context("Expected a string field");
+    Ok(SighashVector {
+        tx: hex::decode(text(0)?).context("Bad transaction hex")?,
+        script_code: hex::decode(text(1)?).context("Bad script hex")?,
+        input_index: fields[2].as_u64().context("Input index is not a number")? as usize,
+        hash_type: fields[3].as_i64().context("Hash type is not a number")? as u32,
+        expected: text(4)?.to_string(),
+    })
+}
+
+/// Matrix tag of a hash type: its base type, `|ANYONECANPAY` when set.
+pub(crate) fn hash_type_tag(hash_type: u32) -> &'static str {
+    let anyone_can_pay = hash_type & 0x80 != 0;
+    match (hash_type & 0x1f, anyone_can_pay) {
+        (2, false) => "NONE",
+        (2, true) => "NONE|ANYONECANPAY",
+        (3, false) => "SINGLE",
+        (3, true) => "SINGLE|ANYONECANPAY",
+        (_, false) => "ALL",
+        (_, true) => "ALL|ANYONECANPAY",
+    }
+}
+
+/// BLVM's digest for `vector`, in display hex.
+pub fn blvm_sighash(vector: &SighashVector) -> Result<String> {
+    let (tx, _) = decode_tx(&vector.tx)?;
+    anyhow::ensure!(
+        vector.input_index < tx.inputs.len(),
+        "Input index {} out of range",
+        vector.input_index
+    );
+    // The signed input's scriptCode comes from its prevout; the others are not hashed
+    let prevouts: Vec<TransactionOutput> = (0..tx.inputs.len())
+        .map(|i| TransactionOutput {
+            value: 0,
+            script_pubkey: if i == vector.input_index {
+                vector.script_code.clone()
+            } else {
+                Vec::new()
+            },
+        })
+        .collect();
+    let digest = calculate_transaction_sighash(
+        &tx,
+        vector.input_index,
+        &prevouts,
+        SighashType::from_byte(vector.hash_type as u8),
+    )
+    .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    let mut digest = digest.to_vec();
+    digest.reverse();
+    Ok(hex::encode(digest))
+}
+
+fn run_sighash_rows(rows: &[serde_json::Value]) -> Result<SuiteResult> {
+    let mut result = SuiteResult::new(Suite::Sighash);
+    for (row, value) in rows.iter().enumerate() {
+        let Some(fields) = value.as_array().filter(|f| f.len() > 1) else {
+            continue; // comment / header row
+        };
+        let vector = parse_sighash_row(fields)
+            .with_context(|| format!("{} row {}", Suite::Sighash.file_name(), row))?;
+        let blvm = blvm_sighash(&vector).unwrap_or_else(|e| format!("error: {:#}", e));
+        let failure = (blvm != vector.expected).then(|| VectorFailure {
+            row,
+            comment: format!(
+                "input {}, hash type {:#010x}",
+                vector.input_index, vector.hash_type
+            ),
+            expected: vector.expected.clone(),
+            blvm,
+        });
+        result.record([hash_type_tag(vector.hash_type)], failure);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Version 1, one input spending `01..01:0` with an empty scriptSig, one empty 0-value output.
+    const SPEND_TX: &str = "0100000001010101010101010101010101010101010101010101010101010101\
+                            01010101010000000000ffffffff0100000000000000000000000000";
+
+    fn rows(json: &str) -> Vec<serde_json::Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn tx_vectors_and_matrix() {
+        let prevout = "0101010101010101010101010101010101010101010101010101010101010101";
+        let json = format!(
+            r#"[
+                ["An anyone-can-spend output"],
+                [[["{p}", 0, "1"]], "{tx}", "NONE"],
+                ["Spending OP_0 fails"],
+                [[["{p}", 0, "0"]], "{tx}", "P2SH"],
+                [[["{p}", 0, "1", 1000]], "{tx}", "DERSIG,BADTX"]
+            ]"#,
+            p = prevout,
+            tx = SPEND_TX
+        );
+        let rows = rows(&json);
+        let vector = parse_tx_row(rows[4].as_array().unwrap()).unwrap();
+        assert_eq!(vector.prevouts[0].outpoint.hash, [1; 32]);
+        assert_eq!(vector.prevouts[0].amount, 1000);
+        assert_eq!(vector.flags, 1 << 2);
+        assert!(vector.bad_tx);
+
+        let valid = run_tx_rows(Suite::TxValid, &rows[..2]).unwrap();
+        assert_eq!((valid.passed, valid.failed), (1, 0));
+        assert_eq!(valid.by_tag["NONE"].passed, 1);
+
+        // Rows 3 and 4 as tx_invalid: the OP_0 spend fails; the BADTX row is actually valid
+        let invalid = run_tx_rows(Suite::TxInvalid, &rows[2..]).unwrap();
+        assert_eq!((invalid.passed, invalid.failed), (1, 1));
+        assert_eq!(invalid.failures[0].row, 2);
+        assert_eq!(invalid.by_tag["BADTX"].failed, 1);
+
+        let matrix = ConformanceMatrix {
+            suites: vec![valid, invalid],
+        };
+        assert!(!matrix.passed());
+        let csv = crate::deep_analysis::dataset::to_csv(&matrix);
+        assert!(csv.starts_with("suite,tag,passed,failed\ntx_valid,NONE,1,0\n"));
+        assert!(csv.contains("tx_invalid,P2SH,1,0\n"));
+    }
+
+    #[test]
+    fn sighash_rows() {
+        let json = format!(
+            r#"[["raw, script, input, type, hash"], ["{}", "51", 0, -2147483647, "00"]]"#,
+            SPEND_TX
+        );
+        let rows = rows(&json);
+        let vector = parse_sighash_row(rows[1].as_array().unwrap()).unwrap();
+        assert_eq!(vector.hash_type, 0x8000_0001);
+        assert_eq!(vector.script_code, [0x51]);
+        assert_eq!(hash_type_tag(vector.hash_type), "ALL");
+        assert_eq!(hash_type_tag(0x83), "SINGLE|ANYONECANPAY");
+
+        let result = run_sighash_rows(&rows).unwrap();
+        assert_eq!((result.passed, result.failed), (0, 1));
+        assert_eq!(result.failures[0].blvm.len(), 64);
+    }
+}
@@ -0,0 +1,91 @@
+//! blvm-consensus API drift shim
+//!
+//! The bench crate is built against whichever blvm-consensus branch is checked out next to it.
+//! `build.rs` inspects that checkout and sets `cfg`s for the API shape it finds; this module is
+//! the only place that calls the drifting entry points directly, so the rest of the crate keeps
+//! one signature while consensus branches move:
+//!
+//! - `connect_block` returning `(result, utxo_set)` or `(result, utxo_set, undo_log)`
+//!   (`blvm_connect_block_undo`)
+//! - `UtxoSet` holding `UTXO` or `Arc<UTXO>` values (`blvm_utxo_arc`)
+//!
+//! A checkout matching neither shape fails the build here, with the detected signatures in the
+//! build script warning.
+
+#[cfg(blvm_consensus_api_unsupported)]
+compile_error!(
+    "blvm-consensus API not supported by consensus_compat (see the build.rs warning for the \
+     detected connect_block / UtxoSet signatures); add an adapter in src/consensus_compat.rs"
+);
+
+use anyhow::Result;
+use blvm_protocol::block::BlockValidationContext;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::{Block, OutPoint, UtxoSet, ValidationResult, UTXO};
+#[cfg(blvm_utxo_arc)]
+use std::sync::Arc;
+
+/// `version` from the blvm-consensus checkout's manifest (`registry` without a local checkout).
+pub const CONSENSUS_VERSION: &str = env!("BLVM_CONSENSUS_VERSION");
+/// Detected API shape, e.g. `connect_block+undo, Arc<UTXO>`.
+pub const CONSENSUS_API: &str = env!("BLVM_CONSENSUS_API");
+
+/// One line for run logs and reports.
+pub fn describe() -> String {
+    format!("blvm-consensus {} ({})", CONSENSUS_VERSION, CONSENSUS_API)
+}
+
+/// `connect_block`, with any undo log dropped.
+pub fn connect_block(
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    utxo_set: UtxoSet,
+    height: u64,
+    ctx: &BlockValidationContext,
+) -> Result<(ValidationResult, UtxoSet)> {
+    #[cfg(blvm_connect_block_undo)]
+    {
+        let (result, utxo_set, _undo_log) =
+            blvm_protocol::block::connect_block(block, witnesses, utxo_set, height, ctx)?;
+        Ok((result, utxo_set))
+    }
+    #[cfg(not(blvm_connect_block_undo))]
+    {
+        Ok(blvm_protocol::block::connect_block(
+            block, witnesses, utxo_set, height, ctx,
+        )?)
+    }
+}
+
+/// Entries of `utxo_set` with plain `UTXO` values.
+pub fn utxo_entries(utxo_set: &UtxoSet) -> impl Iterator<Item = (&OutPoint, &UTXO)> {
+    #[cfg(blvm_utxo_arc)]
+    {
+        utxo_set
+            .iter()
+            .map(|(outpoint, utxo)| (outpoint, utxo.as_ref()))
+    }
+    #[cfg(not(blvm_utxo_arc))]
+    {
+        utxo_set.iter()
+    }
+}
+
+/// One coin of `utxo_set` as a plain `UTXO`.
+pub fn get_utxo<'a>(utxo_set: &'a UtxoSet, outpoint: &OutPoint) -> Option<&'a UTXO> {
+    #[cfg(blvm_utxo_arc)]
+    {
+        utxo_set.get(outpoint).map(|utxo| utxo.as_ref())
+    }
+    #[cfg(not(blvm_utxo_arc))]
+    {
+        utxo_set.get(outpoint)
+    }
+}
+
+/// Insert a coin, returning whether `outpoint` was new.
+pub fn insert_utxo(utxo_set: &mut UtxoSet, outpoint: OutPoint, utxo: UTXO) -> bool {
+    #[cfg(blvm_utxo_arc)]
+    let utxo = Arc::new(utxo);
+    utxo_set.insert(outpoint, utxo).is_none()
+}
@@ -0,0 +1,872 @@
+//! Bitcoin Core JSON-RPC for a `bitcoind` in a container or another network namespace
+//!
+//! [`ContainerRpcClient::connect`] finds the node, works out its RPC port and credentials from
+//! inside the container and wraps a [`NodeRpcClient`] (the standard `CoreRpcClient`) that
+//! reaches it; the wrapper derefs to that client, so every RPC method is available as is.
+//!
+//! 1. **Discovery** ([`ContainerTarget`]): a docker or podman container, by name or the first
+//!    running one whose name or image mentions `bitcoin`; a process matched with `pgrep -f`
+//!    (Start9's `bitcoind -onion`, see [`ContainerConfig::start9`]); an explicit PID; or a
+//!    network namespace path. Each but the last resolves to a `bitcoind` PID whose
+//!    `/proc/<pid>/root` is the container's filesystem; a bare namespace needs the credentials in
+//!    the config.
+//! 2. **Credentials** ([`NodeSettings`]): `-rpcuser` / `-rpcpassword` / `-rpcport` / chain flags
+//!    from bitcoind's command line, then its `bitcoin.conf` (`-conf`, else in the datadir; chain
+//!    sections included), then the chain's `.cookie`. `rpcauth` hashes cannot be reversed, so
+//!    such nodes need a cookie or explicit credentials.
+//! 3. **Transport**: the container's bridge IP when RPC answers there (local engine only), else
+//!    a loopback forwarder: each connection to it runs
+//!    `[ssh host] sudo -n nsenter -t <pid> -n socat STDIO TCP:127.0.0.1:<rpcport>` and copies
+//!    bytes both ways, so one kept-alive HTTP connection costs one bridge process.
+//!
+//! Host commands run locally or, with [`ContainerConfig::ssh`], on the node host over SSH; that
+//! host needs passwordless `sudo` (for `nsenter` and `/proc/<pid>/root`) and `socat`.
+//! [`crate::remote_core_rpc`] is the older fixed Start9 layout (one `curl` per call, credentials
+//! from env). `cargo run --bin container_rpc --features differential`
+
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::node_rpc_client::{cookie_path, read_cookie, NodeRpcClient, RpcConfig};
+use crate::remote_core_rpc::{shell_single_quote, ssh_connection_sharing_args};
+
+/// How long a direct connection to the container's IP may take before the forwarder is used
+const DIRECT_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Container engine CLI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Docker,
+    Podman,
+}
+
+impl Engine {
+    pub fn command(self) -> &'static str {
+        match self {
+            Engine::Docker => "docker",
+            Engine::Podman => "podman",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "docker" => Some(Engine::Docker),
+            "podman" => Some(Engine::Podman),
+            _ => None,
+        }
+    }
+}
+
+/// Where `bitcoind` runs.
+#[derive(Debug, Clone)]
+pub enum ContainerTarget {
+    /// A running container: `name` (or ID prefix), else the first one whose name or image
+    /// mentions `bitcoin`. Without an engine, docker is tried, then podman.
+    Container {
+        engine: Option<Engine>,
+        name: Option<String>,
+    },
+    /// Oldest process matching `pgrep -f <pattern>`
+    Process(String),
+    Pid(u32),
+    /// A network namespace (`/run/netns/<name>`, `/proc/<pid>/ns/net`); nothing is read from
+    /// the node, so the config must carry the credentials
+    Netns(PathBuf),
+}
+
+/// The node host, reached with `ssh [-i key] host`.
+#[derive(Debug, Clone)]
+pub struct SshHost {
+    pub host: String,
+    pub key: Option<String>,
+}
+
+/// What to discover and which of the node's settings to override.
+#[derive(Debug, Clone)]
+pub struct ContainerConfig {
+    pub target: ContainerTarget,
+    /// Run host commands over SSH instead of locally
+    pub ssh: Option<SshHost>,
+    /// Core chain name (`main`, `test`, `testnet4`, `signet`, `regtest`)
+    pub chain: Option<String>,
+    pub rpc_port: Option<u16>,
+    /// `(user, password)`
+    pub credentials: Option<(String, String)>,
+    /// Datadir inside the container
+    pub datadir: Option<PathBuf>,
+}
+
+impl ContainerConfig {
+    pub fn new(target: ContainerTarget) -> Self {
+        Self {
+            target,
+            ssh: None,
+            chain: None,
+            rpc_port: None,
+            credentials: None,
+            datadir: None,
+        }
+    }
+
+    /// The layout [`crate::remote_core_rpc`] assumes: `bitcoind -onion` on an appliance reached
+    /// over SSH.
+    pub fn start9(ssh: SshHost) -> Self {
+        Self {
+            ssh: Some(ssh),
+            ..Self::new(ContainerTarget::Process("bitcoind -onion".to_string()))
+        }
+    }
+
+    /// From env, `None` when no target is set:
+    ///
+    /// - target: `BLVM_CONTAINER_PID`, `BLVM_CONTAINER_NETNS`, `BLVM_CONTAINER_PROCESS` (a
+    ///   `pgrep -f` pattern) or `BLVM_CONTAINER` (a name, or `auto`), with
+    ///   `BLVM_CONTAINER_ENGINE` (`docker` / `podman`)
+    /// - `BLVM_CONTAINER_SSH_HOST`, `BLVM_CONTAINER_SSH_KEY`
+    /// - overrides: `BLVM_CONTAINER_RPC_PORT`, `BLVM_CONTAINER_RPC_USER` /
+    ///   `BLVM_CONTAINER_RPC_PASSWORD`, `BLVM_CONTAINER_DATADIR`; the chain from
+    ///   `BITCOIN_NETWORK`
+    pub fn from_env() -> Result<Option<Self>> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let target = if let Some(pid) = var("BLVM_CONTAINER_PID") {
+            ContainerTarget::Pid(pid.parse().context("Invalid BLVM_CONTAINER_PID")?)
+        } else if let Some(netns) = var("BLVM_CONTAINER_NETNS") {
+            ContainerTarget::Netns(netns.into())
+        } else if let Some(pattern) = var("BLVM_CONTAINER_PROCESS") {
+            ContainerTarget::Process(pattern)
+        } else if let Some(name) = var("BLVM_CONTAINER") {
+            let engine = match var("BLVM_CONTAINER_ENGINE") {
+                Some(engine) => Some(
+                    Engine::from_name(&engine)
+                        .with_context(|| format!("Unknown BLVM_CONTAINER_ENGINE {}", engine))?,
+                ),
+                None => None,
+            };
+            ContainerTarget::Container {
+                engine,
+                name: Some(name).filter(|n| n != "auto"),
+            }
+        } else {
+            return Ok(None);
+        };
+
+        let mut config = Self::new(target);
+        config.ssh = var("BLVM_CONTAINER_SSH_HOST").map(|host| SshHost {
+            host,
+            key: var("BLVM_CONTAINER_SSH_KEY"),
+        });
+        config.chain = var("BITCOIN_NETWORK").map(|network| chain_name(&network).to_string());
+        config.rpc_port = match var("BLVM_CONTAINER_RPC_PORT") {
+            Some(port) => Some(port.parse().context("Invalid BLVM_CONTAINER_RPC_PORT")?),
+            None => None,
+        };
+        config.credentials = var("BLVM_CONTAINER_RPC_USER").zip(var("BLVM_CONTAINER_RPC_PASSWORD"));
+        config.datadir = var("BLVM_CONTAINER_DATADIR").map(PathBuf::from);
+        Ok(Some(config))
+    }
+}
+
+/// Core's chain name for a `BITCOIN_NETWORK`-style name.
+fn chain_name(network: &str) -> &str {
+    match network {
+        "mainnet" | "main" => "main",
+        "testnet" | "testnet3" | "test" => "test",
+        other => other,
+    }
+}
+
+fn default_rpc_port(chain: &str) -> u16 {
+    match chain {
+        "test" => 18332,
+        "testnet4" => 48332,
+        "signet" => 38332,
+        "regtest" => 18443,
+        _ => 8332,
+    }
+}
+
+/// RPC settings from bitcoind's command line or a `bitcoin.conf`. Paths are inside the
+/// container.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeSettings {
+    pub datadir: Option<String>,
+    pub conf: Option<String>,
+    pub chain: Option<String>,
+    pub rpc_port: Option<u16>,
+    pub rpc_user: Option<String>,
+    pub rpc_password: Option<String>,
+    pub rpc_cookie_file: Option<String>,
+}
+
+impl NodeSettings {
+    /// Settings from bitcoind's argv (`/proc/<pid>/cmdline`); a later argument wins, as in Core.
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut settings = Self::default();
+        for arg in args {
+            let Some(option) = arg.strip_prefix('-') else {
+                continue;
+            };
+            let option = option.strip_prefix('-').unwrap_or(option);
+            let (key, value) = option.split_once('=').unwrap_or((option, "1"));
+            settings.set(key, value, false);
+        }
+        settings
+    }
+
+    /// Settings from a `bitcoin.conf` for `chain` (else the chain the file selects): top-level
+    /// options and those of the chain's `[section]`. The first value of an option wins, and a
+    /// top-level `rpcport` only applies to mainnet, as in Core.
+    pub fn from_conf(text: &str, chain: Option<&str>) -> Self {
+        let mut entries = Vec::new();
+        let mut section: Option<&str> = None;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim());
+            } else if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim();
+                // `main.rpcport=...` is the same as `rpcport=...` under `[main]`
+                match key.split_once('.') {
+                    Some((prefix, key)) => entries.push((Some(prefix), key, value.trim())),
+                    None => entries.push((section, key, value.trim())),
+                }
+            }
+        }
+
+        let mut top = Self::default();
+        for &(section, key, value) in &entries {
+            if section.is_none() {
+                top.set(key, value, true);
+            }
+        }
+        let chain = chain
+            .map(str::to_string)
+            .or(top.chain.clone())
+            .unwrap_or_else(|| "main".to_string());
+
+        let mut settings = Self {
+            chain: Some(chain.clone()),
+            ..Self::default()
+        };
+        for &(section, key, value) in &entries {
+            let applies = match section {
+                None => key != "rpcport" || chain == "main",
+                Some(section) => section == chain,
+            };
+            if applies {
+                settings.set(key, value, true);
+            }
+        }
+        settings.chain = Some(chain);
+        settings
+    }
+
+    fn set(&mut self, key: &str, value: &str, keep_first: bool) {
+        let (slot, value) = match key {
+            "datadir" => (&mut self.datadir, value),
+            "conf" => (&mut self.conf, value),
+            "chain" => (&mut self.chain, value),
+            "testnet" | "testnet4" | "signet" | "regtest" if value != "0" => {
+                (&mut self.chain, if key == "testnet" { "test" } else { key })
+            }
+            "rpcuser" => (&mut self.rpc_user, value),
+            "rpcpassword" => (&mut self.rpc_password, value),
+            "rpccookiefile" => (&mut self.rpc_cookie_file, value),
+            "rpcport" => {
+                if let Ok(port) = value.parse() {
+                    if !(keep_first && self.rpc_port.is_some()) {
+                        self.rpc_port = Some(port);
+                    }
+                }
+                return;
+            }
+            _ => return,
+        };
+        if !(keep_first && slot.is_some()) {
+            *slot = Some(value.to_string());
+        }
+    }
+
+    /// `self`, with unset options taken from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            datadir: self.datadir.or(other.datadir),
+            conf: self.conf.or(other.conf),
+            chain: self.chain.or(other.chain),
+            rpc_port: self.rpc_port.or(other.rpc_port),
+            rpc_user: self.rpc_user.or(other.rpc_user),
+            rpc_password: self.rpc_password.or(other.rpc_password),
+            rpc_cookie_file: self.rpc_cookie_file.or(other.rpc_cookie_file),
+        }
+    }
+}
+
+/// Where the RPC credentials came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthSource {
+    /// [`ContainerConfig::credentials`]
+    Config,
+    /// `rpcuser` / `rpcpassword` on the command line or in `bitcoin.conf`
+    Settings,
+    /// The cookie file, at this path inside the container
+    Cookie(PathBuf),
+}
+
+/// A discovered node.
+#[derive(Debug, Clone)]
+pub struct ContainerNode {
+    /// Container name or ID, for a container target
+    pub container: Option<String>,
+    /// `bitcoind` PID on the host, unless the target is a bare namespace
+    pub pid: Option<u32>,
+    /// The container's first bridge IP
+    pub ip: Option<String>,
+    pub chain: String,
+    pub rpc_port: u16,
+    /// Datadir inside the container
+    pub datadir: Option<PathBuf>,
+    pub auth: AuthSource,
+    /// URL the client talks to: the container IP, or the loopback forwarder
+    pub url: String,
+}
+
+/// Where host commands run: here, or on the node host over SSH.
+#[derive(Debug, Clone)]
+struct Host {
+    ssh: Option<SshHost>,
+}
+
+impl Host {
+    fn command(&self, script: &str) -> Command {
+        let mut command = match &self.ssh {
+            None => {
+                let mut command = Command::new("sh");
+                command.arg("-c");
+                command
+            }
+            Some(ssh) => {
+                let mut command = Command::new("ssh");
+                if let Some(key) = &ssh.key {
+                    command.arg("-i").arg(key);
+                }
+                command
+                    .args(["-o", "BatchMode=yes"])
+                    .args(["-o", "ConnectTimeout=10"])
+                    .args(ssh_connection_sharing_args())
+                    .arg(&ssh.host);
+                command
+            }
+        };
+        command.arg(script);
+        command
+    }
+
+    async fn run(&self, script: &str) -> Result<String> {
+        let output = self
+            .command(script)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("Failed to run `{}`", script))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "`{}` failed: {}",
+                script,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// A file on the host: directly when local and readable, else with `sudo -n cat`.
+    async fn read(&self, path: &Path) -> Result<String> {
+        if self.ssh.is_none() {
+            if let Ok(text) = tokio::fs::read_to_string(path).await {
+                return Ok(text);
+            }
+        }
+        self.run(&format!(
+            "sudo -n cat {}",
+            shell_single_quote(&path.to_string_lossy())
+        ))
+        .await
+    }
+}
+
+/// `path` inside the container of `pid`, as seen from the host.
+fn in_container(pid: u32, path: &Path) -> PathBuf {
+    Path::new(&format!("/proc/{}/root", pid)).join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// `(id, name)` of the container to use from `ps` lines of `id\tname\timage`: the one `name`
+/// matches (name, or ID prefix), else the first whose name or image mentions `bitcoin`.
+fn pick_container(ps: &str, name: Option<&str>) -> Option<(String, String)> {
+    ps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some((fields.next()?, fields.next()?, fields.next().unwrap_or("")))
+        })
+        .find(|&(id, names, image)| match name {
+            Some(name) => id.starts_with(name) || names.split(',').any(|n| n == name),
+            None => {
+                let mentions = |s: &str| s.to_ascii_lowercase().contains("bitcoin");
+                mentions(names) || mentions(image)
+            }
+        })
+        .map(|(id, names, _)| (id.to_string(), names.to_string()))
+}
+
+/// Find a running container and return its name, init PID and first IP.
+async fn find_container(
+    host: &Host,
+    engine: Option<Engine>,
+    name: Option<&str>,
+) -> Result<(String, u32, Option<String>)> {
+    let engines = match engine {
+        Some(engine) => vec![engine],
+        None => vec![Engine::Docker, Engine::Podman],
+    };
+    let mut errors = Vec::new();
+    for engine in engines {
+        let cli = engine.command();
+        let ps = match host
+            .run(&format!(
+                "{} ps --format '{{{{.ID}}}}\t{{{{.Names}}}}\t{{{{.Image}}}}'",
+                cli
+            ))
+            .await
+        {
+            Ok(ps) => ps,
+            Err(e) => {
+                errors.push(format!("{:#}", e));
+                continue;
+            }
+        };
+        let Some((id, names)) = pick_container(&ps, name) else {
+            errors.push(format!("{}: no matching running container", cli));
+            continue;
+        };
+        let inspect = host
+            .run(&format!(
+                "{} inspect --format '{{{{.State.Pid}}}} \
+                 {{{{range .NetworkSettings.Networks}}}}{{{{.IPAddress}}}} {{{{end}}}}' {}",
+                cli, id
+            ))
+            .await?;
+        let mut fields = inspect.split_whitespace();
+        let pid = fields
+            .next()
+            .and_then(|pid| pid.parse().ok())
+            .filter(|&pid| pid > 0)
+            .with_context(|| format!("{} {} is not running", cli, names))?;
+        debug!("Found {} container {} ({}), PID {}", cli, names, id, pid);
+        return Ok((names, pid, fields.next().map(str::to_string)));
+    }
+    anyhow::bail!("No bitcoind container found ({})", errors.join("; "))
+}
+
+/// `bitcoind` in the same PID namespace as `pid` (the container's init may be a shell or
+/// an init wrapper), else `pid` itself.
+async fn find_bitcoind(host: &Host, pid: u32) -> u32 {
+    let script = format!(
+        "ns=$(sudo -n readlink /proc/{pid}/ns/pid); for p in $(pgrep -x bitcoind); do \
+         [ \"$(sudo -n readlink /proc/$p/ns/pid)\" = \"$ns\" ] && echo $p && break; done",
+        pid = pid
+    );
+    match host.run(&script).await {
+        Ok(out) => out.trim().parse().unwrap_or(pid),
+        Err(e) => {
+            debug!(
+                "bitcoind lookup in the namespace of {} failed: {:#}",
+                pid, e
+            );
+            pid
+        }
+    }
+}
+
+/// `HOME` of `pid`, for the default datadir.
+async fn process_home(host: &Host, pid: u32) -> Option<String> {
+    let environ = host
+        .read(Path::new(&format!("/proc/{}/environ", pid)))
+        .await
+        .ok()?;
+    environ
+        .split('\0')
+        .find_map(|var| var.strip_prefix("HOME="))
+        .filter(|home| !home.is_empty())
+        .map(str::to_string)
+}
+
+/// The network namespace `nsenter` enters.
+#[derive(Debug, Clone)]
+enum Namespace {
+    Pid(u32),
+    Path(PathBuf),
+}
+
+impl Namespace {
+    fn nsenter_args(&self) -> String {
+        match self {
+            Namespace::Pid(pid) => format!("-t {} -n", pid),
+            Namespace::Path(path) => {
+                format!("--net={}", shell_single_quote(&path.to_string_lossy()))
+            }
+        }
+    }
+}
+
+/// Loopback listener bridging each connection into the node's network namespace; stops when
+/// dropped.
+struct Forwarder {
+    addr: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Forwarder {
+    async fn start(host: Host, namespace: &Namespace, port: u16) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let script = format!(
+            "sudo -n nsenter {} socat STDIO TCP:127.0.0.1:{}",
+            namespace.nsenter_args(),
+            port
+        );
+        let task = tokio::spawn(async move {
+            loop {
+                let socket = match listener.accept().await {
+                    Ok((socket, _)) => socket,
+                    Err(e) => {
+                        warn!("⚠️  RPC forwarder accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let command = host.command(&script);
+                tokio::spawn(async move {
+                    if let Err(e) = bridge(socket, command).await {
+                        debug!("RPC forwarder connection ended: {:#}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self { addr, task })
+    }
+}
+
+/// Copy `socket` to the bridge command's stdin and its stdout back until either side closes.
+async fn bridge(socket: TcpStream, mut command: Command) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start the RPC bridge")?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (mut from_client, mut to_client) = socket.into_split();
+    let upstream = async move {
+        tokio::io::copy(&mut from_client, &mut stdin).await?;
+        // Closing stdin lets socat see the end of the request stream
+        drop(stdin);
+        Ok::<_, std::io::Error>(())
+    };
+    let downstream = async move {
+        tokio::io::copy(&mut stdout, &mut to_client).await?;
+        to_client.shutdown().await
+    };
+    tokio::try_join!(upstream, downstream)?;
+    Ok(())
+}
+
+/// A [`NodeRpcClient`] for a discovered container node; derefs to it.
+#[derive(Clone)]
+pub struct ContainerRpcClient {
+    client: NodeRpcClient,
+    node: ContainerNode,
+    /// Keeps the forwarder up while any clone lives; `None` when RPC is reached directly
+    _forwarder: Option<Arc<Forwarder>>,
+}
+
+impl Deref for ContainerRpcClient {
+    type Target = NodeRpcClient;
+
+    fn deref(&self) -> &NodeRpcClient {
+        &self.client
+    }
+}
+
+impl ContainerRpcClient {
+    /// [`connect`](Self::connect) with [`ContainerConfig::from_env`], `None` when no target is
+    /// set.
+    pub async fn from_env() -> Result<Option<Self>> {
+        match ContainerConfig::from_env()? {
+            Some(config) => Ok(Some(Self::connect(config).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Discover the node, negotiate credentials and check that it answers `getblockcount`.
+    pub async fn connect(config: ContainerConfig) -> Result<Self> {
+        let host = Host {
+            ssh: config.ssh.clone(),
+        };
+        let (container, pid, ip) = match &config.target {
+            ContainerTarget::Container { engine, name } => {
+                let (container, pid, ip) = find_container(&host, *engine, name.as_deref()).await?;
+                (Some(container), Some(find_bitcoind(&host, pid).await), ip)
+            }
+            ContainerTarget::Process(pattern) => {
+                let out = host
+                    .run(&format!(
+                        // Oldest match: the node, not the shell running this over SSH
+                        "pgrep -o -f {}",
+                        shell_single_quote(pattern)
+                    ))
+                    .await?;
+                let pid = out
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("No process matches {:?}", pattern))?;
+                (None, Some(pid), None)
+            }
+            ContainerTarget::Pid(pid) => (None, Some(*pid), None),
+            ContainerTarget::Netns(_) => (None, None, None),
+        };
+
+        let mut settings = NodeSettings {
+            chain: config.chain.clone(),
+            rpc_port: config.rpc_port,
+            datadir: config
+                .datadir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            ..NodeSettings::default()
+        };
+        let mut datadir = None;
+        if let Some(pid) = pid {
+            let cmdline = host
+                .read(Path::new(&format!("/proc/{}/cmdline", pid)))
+                .await
+                .with_context(|| format!("Failed to read the command line of PID {}", pid))?;
+            settings = settings.or(NodeSettings::from_args(
+                cmdline.split('\0').filter(|arg| !arg.is_empty()),
+            ));
+            let dir = match &settings.datadir {
+                Some(dir) => PathBuf::from(dir),
+                None => {
+                    let home = process_home(&host, pid).await;
+                    Path::new(home.as_deref().unwrap_or("/root")).join(".bitcoin")
+                }
+            };
+            let conf = match &settings.conf {
+                Some(conf) => dir.join(conf),
+                None => dir.join("bitcoin.conf"),
+            };
+            match host.read(&in_container(pid, &conf)).await {
+                Ok(text) => {
+                    let from_conf = NodeSettings::from_conf(&text, settings.chain.as_deref());
+                    settings = settings.or(from_conf);
+                    // A datadir set in bitcoin.conf moves the cookie
+                    datadir = Some(settings.datadir.as_ref().map_or(dir, PathBuf::from));
+                }
+                Err(e) => {
+                    debug!("No bitcoin.conf at {}: {:#}", conf.display(), e);
+                    datadir = Some(dir);
+                }
+            }
+        }
+
+        let chain = settings.chain.clone().unwrap_or_else(|| "main".to_string());
+        let rpc_port = settings
+            .rpc_port
+            .unwrap_or_else(|| default_rpc_port(&chain));
+
+        let mut cookie_file = None;
+        let (auth, user, pass) = if let Some((user, pass)) = config.credentials.clone() {
+            (AuthSource::Config, user, pass)
+        } else if let (Some(user), Some(pass)) = (&settings.rpc_user, &settings.rpc_password) {
+            (AuthSource::Settings, user.clone(), pass.clone())
+        } else {
+            let (Some(pid), Some(dir)) = (pid, &datadir) else {
+                anyhow::bail!("A bare network namespace needs RPC credentials in the config");
+            };
+            let default_cookie = cookie_path(dir, &chain);
+            let cookie = match &settings.rpc_cookie_file {
+                // Relative to the chain's datadir, as in Core
+                Some(file) => default_cookie.parent().unwrap_or(dir).join(file),
+                None => default_cookie,
+            };
+            let on_host = in_container(pid, &cookie);
+            let text = host.read(&on_host).await.with_context(|| {
+                format!(
+                    "No rpcuser / rpcpassword and no readable cookie {} (rpcauth needs explicit \
+                     credentials)",
+                    cookie.display()
+                )
+            })?;
+            let (user, pass) = text
+                .trim()
+                .split_once(':')
+                .with_context(|| format!("Malformed RPC cookie {}", cookie.display()))?;
+            // Readable here, so the client can read it again after a node restart
+            if host.ssh.is_none() && read_cookie(&on_host).is_ok() {
+                cookie_file = Some(on_host);
+            }
+            (
+                AuthSource::Cookie(cookie),
+                user.to_string(),
+                pass.to_string(),
+            )
+        };
+        let rpc_config = |url: String| -> Result<RpcConfig> {
+            let config = RpcConfig::new(url, user.clone(), pass.clone());
+            match &cookie_file {
+                Some(path) => config.with_cookie_file(path),
+                None => Ok(config),
+            }
+        };
+
+        let mut node = ContainerNode {
+            container,
+            pid,
+            ip: ip.clone(),
+            chain,
+            rpc_port,
+            datadir,
+            auth,
+            url: String::new(),
+        };
+
+        // The bridge IP only works from the engine's host, and only if bitcoind binds it and
+        // allows us (`rpcbind` / `rpcallowip`)
+        if let (None, Some(ip)) = (&host.ssh, &ip) {
+            let reachable = tokio::time::timeout(
+                DIRECT_CONNECT_TIMEOUT,
+                TcpStream::connect((ip.as_str(), rpc_port)),
+            )
+            .await
+            .is_ok_and(|connected| connected.is_ok());
+            if reachable {
+                let url = format!("http://{}:{}", ip, rpc_port);
+                let client = NodeRpcClient::new(rpc_config(url.clone())?);
+                if client.test_connection().await? {
+                    info!("✅ Container RPC at {} (direct)", url);
+                    node.url = url;
+                    return Ok(Self {
+                        client,
+                        node,
+                        _forwarder: None,
+                    });
+                }
+            }
+        }
+
+        let namespace = match (&config.target, pid) {
+            (ContainerTarget::Netns(path), _) => Namespace::Path(path.clone()),
+            (_, Some(pid)) => Namespace::Pid(pid),
+            (_, None) => unreachable!("every target but a namespace resolves to a PID"),
+        };
+        let forwarder = Forwarder::start(host, &namespace, rpc_port).await?;
+        node.url = format!("http://{}", forwarder.addr);
+        let client = NodeRpcClient::new(rpc_config(node.url.clone())?);
+        client.getblockcount().await.with_context(|| {
+            format!(
+                "RPC through the forwarder into {:?} port {} failed (is socat installed?)",
+                namespace, rpc_port
+            )
+        })?;
+        info!(
+            "✅ Container RPC port {} forwarded to {}",
+            rpc_port, node.url
+        );
+        Ok(Self {
+            client,
+            node,
+            _forwarder: Some(Arc::new(forwarder)),
+        })
+    }
+
+    pub fn client(&self) -> &NodeRpcClient {
+        &self.client
+    }
+
+    pub fn node(&self) -> &ContainerNode {
+        &self.node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_from_cmdline_and_conf() {
+        let args = NodeSettings::from_args([
+            "/usr/local/bin/bitcoind",
+            "-datadir=/data",
+            "--testnet",
+            "-rpcport=1000",
+            "-rpcport=2000",
+            "-printtoconsole",
+        ]);
+        assert_eq!(args.datadir.as_deref(), Some("/data"));
+        assert_eq!(args.chain.as_deref(), Some("test"));
+        assert_eq!(args.rpc_port, Some(2000));
+
+        let conf = "\
+            # top level\n\
+            rpcuser=alice\n\
+            rpcuser=ignored\n\
+            rpcport=9000 # mainnet only\n\
+            regtest.rpcpassword=regtest-secret\n\
+            [test]\n\
+            rpcpassword=secret\n\
+            rpcport=19000\n\
+            [main]\n\
+            rpcpassword=main-secret\n";
+        let test = NodeSettings::from_conf(conf, args.chain.as_deref());
+        assert_eq!(test.rpc_user.as_deref(), Some("alice"));
+        assert_eq!(test.rpc_password.as_deref(), Some("secret"));
+        assert_eq!(test.rpc_port, Some(19000));
+        let main = NodeSettings::from_conf(conf, None);
+        assert_eq!(main.chain.as_deref(), Some("main"));
+        assert_eq!(main.rpc_password.as_deref(), Some("main-secret"));
+        assert_eq!(main.rpc_port, Some(9000));
+        // The file can pick the chain itself
+        let regtest = NodeSettings::from_conf(&format!("regtest=1\n{}", conf), None);
+        assert_eq!(regtest.rpc_password.as_deref(), Some("regtest-secret"));
+        assert_eq!(regtest.rpc_port, None);
+
+        // The command line wins over the file
+        let merged = args.or(test);
+        assert_eq!(merged.rpc_port, Some(2000));
+        assert_eq!(merged.rpc_user.as_deref(), Some("alice"));
+
+        let ps = "a1b2\tlightning\tlnd:latest\nc3d4\tbtc-node\tbitcoin/bitcoin:27\n";
+        assert_eq!(pick_container(ps, None).unwrap().0, "c3d4");
+        assert_eq!(pick_container(ps, Some("a1")).unwrap().1, "lightning");
+        assert!(pick_container(ps, Some("other")).is_none());
+        assert_eq!(
+            in_container(7, Path::new("/data/.cookie")),
+            Path::new("/proc/7/root/data/.cookie")
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Bitcoin Core `debug.log` parser
+//!
+//! Core logs every new tip (`UpdateTip: new best=<hash> height=<n> ...`), every block it rejects
+//! (`InvalidChainFound`, `ConnectBlock <hash> failed, <reason>`) and, with `-debug=bench`, how
+//! long each stage of `ConnectBlock` took (`- Connect total: 1.23ms [...]`). Parsing the log of
+//! the sync that produced a datadir gives Core's own verdict and timing for every block, which a
+//! differential run can line up with BLVM's without instrumenting Core (see
+//! [`CoreLogReplayHook`](crate::validation_hooks::CoreLogReplayHook)).
+//!
+//! Timestamps, `-logthreadnames` and `[category]` prefixes are optional; reorgs keep the last
+//! tip logged per height.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::Path;
+
+/// Bench stages logged after `UpdateTip` for the block it announced.
+const POST_TIP_STAGES: &[&str] = &["Connect postprocess", "Connect block"];
+
+/// A block Core connected to its active chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoreTipEvent {
+    pub height: u64,
+    /// Block hash as logged (RPC byte order)
+    pub hash: String,
+    /// Log timestamp, if Core wrote one
+    pub logged_at: Option<String>,
+    /// Transactions in the chain up to this block (`tx=`)
+    pub chain_tx: Option<u64>,
+    /// `-debug=bench` stage timings in ms, counts stripped from names
+    /// (`Connect 2 transactions` -> `Connect transactions`)
+    pub stages_ms: BTreeMap<String, f64>,
+}
+
+impl CoreTipEvent {
+    /// Time spent in `ConnectBlock` proper ("Connect total": checks, script verification, undo
+    /// and index writes), the counterpart of BLVM's `connect_block`.
+    pub fn connect_total_ms(&self) -> Option<f64> {
+        self.stages_ms.get("Connect total").copied()
+    }
+}
+
+/// A block Core rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreInvalidBlock {
+    pub height: u64,
+    pub hash: String,
+    /// Validation state from the matching `ConnectBlock ... failed` line, if logged
+    pub reason: Option<String>,
+}
+
+/// What Core's log says about a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreLogVerdict {
+    /// Connected as the active tip at this height
+    Connected,
+    Invalid(Option<String>),
+    /// Core logged a different block at this height (BLVM is on another chain)
+    OtherBlock(String),
+    /// Nothing logged for this height (log rotated, or outside the synced range)
+    NotLogged,
+}
+
+/// Events parsed from one `debug.log`.
+#[derive(Debug, Clone, Default)]
+pub struct CoreDebugLog {
+    pub tips: BTreeMap<u64, CoreTipEvent>,
+    pub invalid: BTreeMap<u64, CoreInvalidBlock>,
+    /// Lines read
+    pub lines: usize,
+}
+
+impl CoreDebugLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open Core debug log {}", path.display()))?;
+        Self::parse(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn parse(mut reader: impl BufRead) -> Result<Self> {
+        let mut log = Self::default();
+        // Bench stages of the block being connected, before its UpdateTip
+        let mut pending: BTreeMap<String, f64> = BTreeMap::new();
+        let mut last_tip: Option<u64> = None;
+        let mut failure_reasons: HashMap<String, String> = HashMap::new();
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            log.lines += 1;
+            // debug.log can hold arbitrary bytes (peer user agents); those lines are not ours
+            let line = String::from_utf8_lossy(&buf);
+            let (logged_at, message) = split_prefix(line.trim_end());
+
+            if let Some(rest) = message.strip_prefix("UpdateTip: new best=") {
+                let Some(event) = parse_update_tip(rest, logged_at, std::mem::take(&mut pending))
+                else {
+                    continue;
+                };
+                last_tip = Some(event.height);
+                log.tips.insert(event.height, event);
+            } else if let Some((stage, ms)) = parse_bench_line(message) {
+                match last_tip.and_then(|h| log.tips.get_mut(&h)) {
+                    Some(tip) if POST_TIP_STAGES.contains(&stage.as_str()) => {
+                        tip.stages_ms.insert(stage, ms);
+                    }
+                    _ => {
+                        pending.insert(stage, ms);
+                    }
+                }
+            } else if let Some(rest) = message.split_once("ConnectBlock ").map(|(_, r)| r) {
+                if let Some((hash, reason)) = rest.split_once(" failed, ") {
+                    failure_reasons.insert(hash.to_string(), reason.trim().to_string());
+                    pending.clear();
+                }
+            } else if let Some(rest) = message.strip_prefix("InvalidChainFound: invalid block=") {
+                let mut fields = rest.split_whitespace();
+                let hash = fields.next().unwrap_or_default().to_string();
+                let height = fields
+                    .find_map(|f| f.strip_prefix("height="))
+                    .and_then(|h| h.parse().ok());
+                if let Some(height) = height {
+                    let reason = failure_reasons.remove(&hash);
+                    log.invalid.insert(
+                        height,
+                        CoreInvalidBlock {
+                            height,
+                            hash,
+                            reason,
+                        },
+                    );
+                }
+                pending.clear();
+            }
+        }
+        Ok(log)
+    }
+
+    /// Core's verdict on the block `hash` (RPC byte order) at `height`.
+    pub fn verdict(&self, height: u64, hash: &str) -> CoreLogVerdict {
+        if let Some(invalid) = self.invalid.get(&height) {
+            if invalid.hash == hash {
+                return CoreLogVerdict::Invalid(invalid.reason.clone());
+            }
+        }
+        match self.tips.get(&height) {
+            Some(tip) if tip.hash == hash => CoreLogVerdict::Connected,
+            Some(tip) => CoreLogVerdict::OtherBlock(tip.hash.clone()),
+            None => CoreLogVerdict::NotLogged,
+        }
+    }
+}
+
+/// Split `2024-05-01T12:00:00.123456Z [msghand] [bench] message` into timestamp and message.
+fn split_prefix(line: &str) -> (Option<&str>, &str) {
+    let mut rest = line;
+    let mut logged_at = None;
+    if let Some((first, tail)) = rest.split_once(' ') {
+        if first.len() >= 20 && first.as_bytes()[4] == b'-' && first.ends_with('Z') {
+            logged_at = Some(first);
+            rest = tail;
+        }
+    }
+    // Thread name and category tags
+    while let Some(tail) = rest.strip_prefix('[') {
+        match tail.split_once("] ") {
+            Some((tag, after)) if !tag.contains(' ') => rest = after,
+            _ => break,
+        }
+    }
+    (logged_at, rest)
+}
+
+fn parse_update_tip(
+    rest: &str,
+    logged_at: Option<&str>,
+    stages_ms: BTreeMap<String, f64>,
+) -> Option<CoreTipEvent> {
+    let mut fields = rest.split_whitespace();
+    let hash = fields.next()?.to_string();
+    let mut height = None;
+    let mut chain_tx = None;
+    for field in fields {
+        if let Some(h) = field.strip_prefix("height=") {
+            height = h.parse().ok();
+        } else if let Some(tx) = field.strip_prefix("tx=") {
+            chain_tx = tx.parse().ok();
+        }
+    }
+    Some(CoreTipEvent {
+        height: height?,
+        hash,
+        logged_at: logged_at.map(str::to_string),
+        chain_tx,
+        stages_ms,
+    })
+}
+
+/// `    - Connect 2 transactions: 0.02ms (0.010ms/tx) [...]` -> (`Connect transactions`, 0.02)
+fn parse_bench_line(message: &str) -> Option<(String, f64)> {
+    let (name, timing) = message.trim_start().strip_prefix("- ")?.split_once(": ")?;
+    let ms = timing
+        .split_whitespace()
+        .next()?
+        .strip_suffix("ms")?
+        .parse()
+        .ok()?;
+    let name = name
+        .split_whitespace()
+        .filter(|word| !word.bytes().all(|b| b.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some((name, ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+2024-05-01T12:00:00Z [bench]   - Load block from disk: 0.05ms
+2024-05-01T12:00:00Z [bench]       - Connect 2 transactions: 0.02ms (0.010ms/tx, 0.000ms/txin) [0.00s (0.01ms/blk)]
+2024-05-01T12:00:00Z [bench]   - Connect total: 0.17ms [0.00s (0.12ms/blk)]
+2024-05-01T12:00:00Z UpdateTip: new best=00aa height=170 version=0x00000001 log2_work=39.6 tx=172 date='2009-01-12T03:30:25Z' progress=0.000000 cache=0.1MiB(1txo)
+2024-05-01T12:00:00Z [bench]   - Connect postprocess: 0.09ms [0.00s (0.06ms/blk)]
+2024-05-01T12:00:00Z [bench] - Connect block: 0.37ms [0.00s (0.27ms/blk)]
+2024-05-01T12:00:01.250000Z [msghand]   - Connect total: 9.99ms [0.00s (0.12ms/blk)]
+2024-05-01T12:00:01.250000Z [msghand] ERROR: ConnectTip: ConnectBlock 00bb failed, bad-txns-inputs-missingorspent
+2024-05-01T12:00:01.250000Z [msghand] InvalidChainFound: invalid block=00bb  height=171  log2_work=39.6  date=2009-01-12T03:31:25Z
+UpdateTip: new best=00cc height=171 version=0x00000001 log2_work=39.6 tx=174
+";
+
+    #[test]
+    fn parses_tips_bench_stages_and_rejections() {
+        let log = CoreDebugLog::parse(LOG.as_bytes()).unwrap();
+        assert_eq!(log.lines, 10);
+
+        let tip = &log.tips[&170];
+        assert_eq!(tip.hash, "00aa");
+        assert_eq!(tip.logged_at.as_deref(), Some("2024-05-01T12:00:00Z"));
+        assert_eq!(tip.chain_tx, Some(172));
+        assert_eq!(tip.connect_total_ms(), Some(0.17));
+        assert_eq!(tip.stages_ms["Connect transactions"], 0.02);
+        assert_eq!(tip.stages_ms["Connect block"], 0.37);
+        assert_eq!(tip.stages_ms.len(), 5);
+
+        // The failed block's stages are not carried over to the next tip
+        assert!(log.tips[&171].stages_ms.is_empty());
+        assert_eq!(log.tips[&171].logged_at, None);
+
+        assert_eq!(log.verdict(170, "00aa"), CoreLogVerdict::Connected);
+        assert_eq!(
+            log.verdict(171, "00bb"),
+            CoreLogVerdict::Invalid(Some("bad-txns-inputs-missingorspent".to_string()))
+        );
+        assert_eq!(
+            log.verdict(171, "00dd"),
+            CoreLogVerdict::OtherBlock("00cc".to_string())
+        );
+        assert_eq!(log.verdict(172, "00ee"), CoreLogVerdict::NotLogged);
+    }
+}
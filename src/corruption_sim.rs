@@ -0,0 +1,540 @@
+//! Corrupted-datadir simulation: controlled damage to healthy block files, read back
+//!
+//! A [`HealthySet`] holds `blk*.dat` files that frame cleanly (back-to-back frames, then zero
+//! padding), copied from a datadir or [`synthetic`](HealthySet::synthetic). Each case applies
+//! one [`Corruption`] to one frame, writes the set plain or obfuscated, reads it back with
+//! [`BlockFileReader::read_blocks_sequential`] and compares the blocks and
+//! [`BlockIterator::skips`](crate::block_file_reader::BlockIterator::skips) with what the
+//! reader's skip and recovery rules promise:
+//!
+//! | corruption        | plain file                   | obfuscated file                        |
+//! |-------------------|------------------------------|----------------------------------------|
+//! | `bit_flip`        | block read with the flip     | same                                   |
+//! | `version_flip`    | block read with the flip     | block dropped (`rejected`)             |
+//! | `magic_flip`      | file ends there (`garbage`)  | block dropped (`garbage`)              |
+//! | `bad_size`        | file ends there (`bad_size`) | block recovered, unless last (`bad_size`) |
+//! | `truncate`        | file ends there (`truncated`, `too_small` under 100 bytes) | same   |
+//! | `duplicate_magic` | block read, magic and all    | same                                   |
+//! | `garbage_padding` | file ends there (`garbage`)  | garbage passed over (`garbage`)        |
+//!
+//! Other files are never affected. `cargo run --bin corruption_sim --features differential`
+
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::block_file_reader::{
+    load_xor_key, BlockFileReader, Network, SkipEvent, SkipReason, BLOCK_MAGIC_MAINNET,
+};
+use crate::block_framing::{is_padding, FrameEvent, FrameScanner, XorKey};
+use crate::sanity::SanityStage;
+
+/// Smallest file the reader opens (see `BlockIterator::next_file`)
+const MIN_FILE_SIZE: usize = 100;
+/// Most bytes a `garbage_padding` case inserts
+const MAX_GARBAGE: usize = 64;
+
+/// One kind of damage, applied to one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corruption {
+    /// One bit of the payload past the version field
+    BitFlip,
+    /// The sign bit of the block version
+    VersionFlip,
+    /// One bit of the magic
+    MagicFlip,
+    /// One of the top 6 bits of the size field (64 MiB and up)
+    BadSize,
+    /// The file cut inside the frame
+    Truncate,
+    /// A copy of the magic written inside the payload, past the header
+    DuplicateMagic,
+    /// 1 to 64 non-zero bytes (none of them a magic's first byte) inserted before the frame
+    GarbagePadding,
+}
+
+impl Corruption {
+    pub const ALL: [Corruption; 7] = [
+        Corruption::BitFlip,
+        Corruption::VersionFlip,
+        Corruption::MagicFlip,
+        Corruption::BadSize,
+        Corruption::Truncate,
+        Corruption::DuplicateMagic,
+        Corruption::GarbagePadding,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Corruption::BitFlip => "bit_flip",
+            Corruption::VersionFlip => "version_flip",
+            Corruption::MagicFlip => "magic_flip",
+            Corruption::BadSize => "bad_size",
+            Corruption::Truncate => "truncate",
+            Corruption::DuplicateMagic => "duplicate_magic",
+            Corruption::GarbagePadding => "garbage_padding",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+/// A block file that frames cleanly, stored deobfuscated.
+#[derive(Debug, Clone)]
+pub struct HealthyFile {
+    pub data: Vec<u8>,
+    /// Payload of every frame; each frame's magic starts 8 bytes earlier
+    pub payloads: Vec<Range<usize>>,
+}
+
+impl HealthyFile {
+    /// Check that `data` holds back-to-back `magic` frames from offset 0, then only zeros, and
+    /// that every block passes the read-stage sanity filter (which obfuscated reads apply).
+    pub fn new(data: Vec<u8>, magic: [u8; 4]) -> Result<Self> {
+        let mut payloads = Vec::new();
+        let mut end = 0;
+        for event in FrameScanner::new(&data, 0, magic, false) {
+            match event {
+                FrameEvent::Block { offset, payload } if offset == end => {
+                    crate::sanity::filter(SanityStage::Read)
+                        .check(&data[payload.clone()])
+                        .map_err(|e| anyhow::anyhow!("block at offset {}: {}", offset, e))?;
+                    end = payload.end;
+                    payloads.push(payload);
+                }
+                _ => break,
+            }
+        }
+        anyhow::ensure!(!payloads.is_empty(), "no frames");
+        anyhow::ensure!(
+            is_padding(&data[end..], 0, None),
+            "neither a frame nor padding at offset {}",
+            end
+        );
+        anyhow::ensure!(
+            data.len() >= MIN_FILE_SIZE,
+            "{} bytes, the reader skips files under {}",
+            data.len(),
+            MIN_FILE_SIZE
+        );
+        Ok(Self { data, payloads })
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &[u8]> {
+        self.payloads.iter().map(|p| &self.data[p.clone()])
+    }
+}
+
+/// Block files a simulation starts from.
+#[derive(Debug, Clone)]
+pub struct HealthySet {
+    pub network: Network,
+    pub files: Vec<HealthyFile>,
+}
+
+impl HealthySet {
+    /// The first `max_files` `blk*.dat` files under `datadir`, deobfuscated with its `xor.dat`.
+    pub fn load(datadir: &Path, network: Network, max_files: usize) -> Result<Self> {
+        let blocks_dir = datadir.join("blocks");
+        let key = load_xor_key(&blocks_dir)?;
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&blocks_dir)
+            .with_context(|| format!("Failed to list {}", blocks_dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("blk") && n.ends_with(".dat"))
+            })
+            .collect();
+        paths.sort();
+        paths.truncate(max_files);
+        anyhow::ensure!(!paths.is_empty(), "No blk*.dat in {}", blocks_dir.display());
+
+        let files = paths
+            .iter()
+            .map(|path| {
+                let mut data = std::fs::read(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                if let Some(key) = key {
+                    key.apply(&mut data, 0);
+                }
+                HealthyFile::new(data, *network.magic_bytes())
+                    .with_context(|| format!("{} is not a healthy block file", path.display()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { network, files })
+    }
+
+    /// `files` mainnet-framed files of `blocks_per_file` random blocks (200 to 2,000 bytes, no
+    /// byte that could start a magic), each followed by up to 512 bytes of zero padding.
+    pub fn synthetic(seed: u64, files: usize, blocks_per_file: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let files = (0..files)
+            .map(|_| {
+                let mut data = Vec::new();
+                let mut payloads = Vec::new();
+                for _ in 0..blocks_per_file {
+                    let len = rng.gen_range(200..2000);
+                    data.extend_from_slice(&BLOCK_MAGIC_MAINNET);
+                    data.extend_from_slice(&(len as u32).to_le_bytes());
+                    let start = data.len();
+                    // BIP9 version, then bytes below the magic's first
+                    data.extend_from_slice(&0x2000_0000u32.to_le_bytes());
+                    data.extend((4..len).map(|_| rng.gen_range(0..0xf9u8)));
+                    payloads.push(start..data.len());
+                }
+                data.resize(data.len() + rng.gen_range(0..512), 0);
+                HealthyFile { data, payloads }
+            })
+            .collect();
+        Self {
+            network: Network::Mainnet,
+            files,
+        }
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.files.iter().map(|f| f.payloads.len()).sum()
+    }
+}
+
+/// One file after a [`Corruption`], with what reading it should give.
+struct Damage {
+    file: usize,
+    frame: usize,
+    detail: String,
+    data: Vec<u8>,
+    blocks: Vec<Vec<u8>>,
+    skips: Vec<SkipEvent>,
+}
+
+fn damage(set: &HealthySet, corruption: Corruption, obfuscated: bool, rng: &mut StdRng) -> Damage {
+    let file = rng.gen_range(0..set.files.len());
+    let healthy = &set.files[file];
+    let frame = rng.gen_range(0..healthy.payloads.len());
+    let payload = healthy.payloads[frame].clone();
+    let offset = payload.start - 8;
+    let last = frame + 1 == healthy.payloads.len();
+
+    let mut data = healthy.data.clone();
+    let mut blocks: Vec<Vec<u8>> = healthy.blocks().map(<[u8]>::to_vec).collect();
+    let skip = |reason| SkipEvent {
+        file,
+        offset: offset as u64,
+        reason,
+    };
+    let mut skips = Vec::new();
+
+    let detail = match corruption {
+        Corruption::BitFlip => {
+            let at = rng.gen_range(payload.start + 4..payload.end);
+            let bit = rng.gen_range(0..8);
+            data[at] ^= 1 << bit;
+            blocks[frame][at - payload.start] ^= 1 << bit;
+            format!("bit {} of byte {}", bit, at)
+        }
+        Corruption::VersionFlip => {
+            data[payload.start + 3] ^= 0x80;
+            if obfuscated {
+                blocks.remove(frame);
+                skips.push(skip(SkipReason::Rejected));
+            } else {
+                blocks[frame][3] ^= 0x80;
+            }
+            format!("byte {}", payload.start + 3)
+        }
+        Corruption::MagicFlip => {
+            let at = offset + rng.gen_range(0..4);
+            let bit = rng.gen_range(0..8);
+            data[at] ^= 1 << bit;
+            if obfuscated {
+                blocks.remove(frame);
+            } else {
+                blocks.truncate(frame);
+            }
+            skips.push(skip(SkipReason::Garbage));
+            format!("bit {} of byte {}", bit, at)
+        }
+        Corruption::BadSize => {
+            let bit = rng.gen_range(26..32);
+            data[offset + 4 + bit / 8] ^= 1 << (bit % 8);
+            if !obfuscated {
+                blocks.truncate(frame);
+            } else if last {
+                blocks.remove(frame);
+            }
+            skips.push(skip(SkipReason::BadSize));
+            format!("size bit {}", bit)
+        }
+        Corruption::Truncate => {
+            let cut = offset + rng.gen_range(1..payload.end - offset);
+            data.truncate(cut);
+            blocks.truncate(frame);
+            if cut < MIN_FILE_SIZE {
+                blocks.clear();
+                skips.push(SkipEvent {
+                    file,
+                    offset: 0,
+                    reason: SkipReason::TooSmall,
+                });
+            } else {
+                skips.push(skip(SkipReason::Truncated));
+            }
+            format!("cut at {}", cut)
+        }
+        Corruption::DuplicateMagic => {
+            // Past the header; healthy payloads are at least 88 bytes
+            let at = payload.start + rng.gen_range(80..=payload.len() - 4);
+            let magic = set.network.magic_bytes();
+            data[at..at + 4].copy_from_slice(magic);
+            blocks[frame][at - payload.start..][..4].copy_from_slice(magic);
+            format!("magic at {}", at)
+        }
+        Corruption::GarbagePadding => {
+            let len = rng.gen_range(1..=MAX_GARBAGE);
+            let garbage: Vec<u8> = (0..len).map(|_| rng.gen_range(1..0xf9u8)).collect();
+            data.splice(offset..offset, garbage);
+            if !obfuscated {
+                blocks.truncate(frame);
+            }
+            skips.push(skip(SkipReason::Garbage));
+            format!("{} bytes at {}", len, offset)
+        }
+    };
+
+    Damage {
+        file,
+        frame,
+        detail,
+        data,
+        blocks,
+        skips,
+    }
+}
+
+/// The set written under a temporary datadir with one key, one file swapped out at a time.
+struct Layout<'a> {
+    set: &'a HealthySet,
+    key: Option<XorKey>,
+    dir: tempfile::TempDir,
+}
+
+impl<'a> Layout<'a> {
+    fn new(set: &'a HealthySet, key: Option<XorKey>) -> Result<Self> {
+        let layout = Self {
+            set,
+            key,
+            dir: tempfile::tempdir()?,
+        };
+        std::fs::create_dir_all(layout.blocks_dir())?;
+        if let Some(key) = key {
+            std::fs::write(layout.blocks_dir().join("xor.dat"), key.bytes())?;
+        }
+        for (n, file) in set.files.iter().enumerate() {
+            layout.write(n, &file.data)?;
+        }
+        Ok(layout)
+    }
+
+    fn blocks_dir(&self) -> PathBuf {
+        self.dir.path().join("blocks")
+    }
+
+    fn write(&self, n: usize, plain: &[u8]) -> Result<()> {
+        let mut data = plain.to_vec();
+        if let Some(key) = self.key {
+            key.apply(&mut data, 0);
+        }
+        std::fs::write(self.blocks_dir().join(format!("blk{:05}.dat", n)), data)?;
+        Ok(())
+    }
+
+    /// Read every block back and compare with `expected` in order: the index of the first block
+    /// that differs (or is missing or extra), and the reader's skips.
+    fn read_back(&self, expected: &[&[u8]]) -> Result<(usize, Option<usize>, Vec<SkipEvent>)> {
+        let reader = BlockFileReader::new(self.dir.path(), self.set.network)?;
+        anyhow::ensure!(
+            !reader.is_xor_packaged(),
+            "Unset REMOTE_CORE_XOR_BLOCKFILES: packaged trees are read through the chunk cache"
+        );
+        let mut iter = reader.read_blocks_sequential(None, None)?;
+        let mut read = 0;
+        let mut first_mismatch = None;
+        for block in iter.by_ref() {
+            let block = block?;
+            if first_mismatch.is_none() && expected.get(read) != Some(&block.as_slice()) {
+                first_mismatch = Some(read);
+            }
+            read += 1;
+        }
+        if first_mismatch.is_none() && read != expected.len() {
+            first_mismatch = Some(read.min(expected.len()));
+        }
+        Ok((read, first_mismatch, iter.skips().to_vec()))
+    }
+}
+
+/// What to run.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    pub seed: u64,
+    pub corruptions: Vec<Corruption>,
+    /// Cases per corruption and key
+    pub rounds: usize,
+    /// Layouts to write the set in (`None`: plain)
+    pub keys: Vec<Option<XorKey>>,
+}
+
+/// One damaged set read back.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub corruption: Corruption,
+    pub obfuscated: bool,
+    pub file: usize,
+    pub frame: usize,
+    pub detail: String,
+    pub expected_blocks: usize,
+    pub read_blocks: usize,
+    /// First block, in read order, that differs from the expected sequence
+    pub first_mismatch: Option<usize>,
+    pub expected_skips: Vec<SkipEvent>,
+    pub skips: Vec<SkipEvent>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.first_mismatch.is_none() && self.skips == self.expected_skips
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimReport {
+    pub files: usize,
+    pub blocks: usize,
+    pub cases: Vec<CaseResult>,
+}
+
+impl SimReport {
+    /// Every case read back the expected blocks with the expected skips.
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(CaseResult::passed)
+    }
+
+    pub fn print(&self) {
+        println!(
+            "\n🩹 Corruption simulation ({} files, {} blocks)",
+            self.files, self.blocks
+        );
+        println!(
+            "   {:<16} {:<6} {:>4} {:>6} {:>7} {:>7} {:>6}  detail",
+            "corruption", "layout", "file", "frame", "blocks", "read", "skips"
+        );
+        for case in &self.cases {
+            println!(
+                "   {:<16} {:<6} {:>4} {:>6} {:>7} {:>7} {:>6}  {}{}",
+                case.corruption.name(),
+                if case.obfuscated { "xor" } else { "plain" },
+                case.file,
+                case.frame,
+                case.expected_blocks,
+                case.read_blocks,
+                case.skips.len(),
+                case.detail,
+                if case.passed() { "" } else { "  ❌" }
+            );
+            if !case.passed() {
+                if let Some(i) = case.first_mismatch {
+                    println!("      first block differing: {}", i);
+                }
+                println!("      expected skips: {:?}", case.expected_skips);
+                println!("      reported skips: {:?}", case.skips);
+            }
+        }
+        let failed = self.cases.iter().filter(|c| !c.passed()).count();
+        println!("   {} cases, {} failed", self.cases.len(), failed);
+    }
+}
+
+/// Run every configured case. Fails if the healthy set itself does not read back cleanly.
+pub fn run(set: &HealthySet, config: &SimConfig) -> Result<SimReport> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let healthy: Vec<&[u8]> = set.files.iter().flat_map(HealthyFile::blocks).collect();
+    let mut cases = Vec::new();
+
+    for &key in &config.keys {
+        let layout = Layout::new(set, key)?;
+        let (read, mismatch, skips) = layout.read_back(&healthy)?;
+        anyhow::ensure!(
+            mismatch.is_none() && skips.is_empty(),
+            "Healthy set ({}) read back {} of {} blocks, first difference at {:?}, skips {:?}",
+            if key.is_some() { "obfuscated" } else { "plain" },
+            read,
+            healthy.len(),
+            mismatch,
+            skips
+        );
+
+        for &corruption in &config.corruptions {
+            for _ in 0..config.rounds {
+                let damage = damage(set, corruption, key.is_some(), &mut rng);
+                let mut expected: Vec<&[u8]> = Vec::new();
+                for (n, file) in set.files.iter().enumerate() {
+                    if n == damage.file {
+                        expected.extend(damage.blocks.iter().map(Vec::as_slice));
+                    } else {
+                        expected.extend(file.blocks());
+                    }
+                }
+
+                layout.write(damage.file, &damage.data)?;
+                let (read_blocks, first_mismatch, skips) = layout.read_back(&expected)?;
+                layout.write(damage.file, &set.files[damage.file].data)?;
+
+                cases.push(CaseResult {
+                    corruption,
+                    obfuscated: key.is_some(),
+                    file: damage.file,
+                    frame: damage.frame,
+                    detail: damage.detail,
+                    expected_blocks: expected.len(),
+                    read_blocks,
+                    first_mismatch,
+                    expected_skips: damage.skips,
+                    skips,
+                });
+            }
+        }
+    }
+
+    Ok(SimReport {
+        files: set.files.len(),
+        blocks: set.block_count(),
+        cases,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_corruptions_read_back_as_promised() {
+        let set = HealthySet::synthetic(7, 3, 6);
+        let config = SimConfig {
+            seed: 7,
+            corruptions: Corruption::ALL.to_vec(),
+            rounds: 4,
+            keys: vec![None, Some(XorKey::PACKAGED), XorKey::new([7; 8])],
+        };
+        let report = run(&set, &config).unwrap();
+        assert_eq!(report.cases.len(), 3 * 7 * 4);
+        let failed: Vec<_> = report.cases.iter().filter(|c| !c.passed()).collect();
+        assert!(failed.is_empty(), "{:#?}", failed);
+    }
+}
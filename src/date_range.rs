@@ -0,0 +1,249 @@
+//! Date-range slicing
+//!
+//! Resolves calendar ranges (`--from 2017-08-01 --to 2017-12-31`) to block heights. Header
+//! timestamps are not monotonic, so the mapping uses median-time-past (MTP, median of the last
+//! 11 header times), which consensus keeps non-decreasing - a binary search over it is exact.
+//!
+//! Header times are read once from the chunk cache and kept next to the other chunk indexes as
+//! `chunks.times` (bincode `Vec<u32>`, index = height). Later runs extend it from where it stops.
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+use std::path::Path;
+
+use crate::chunked_cache::{load_chunk_metadata, ChunkedBlockIterator};
+
+/// Blocks in the median-time-past window (`GetMedianTimePast`)
+const MTP_WINDOW: usize = 11;
+
+/// Header timestamps by height with MTP lookups.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderTimes {
+    times: Vec<u32>,
+    mtp: Vec<u32>,
+}
+
+impl HeaderTimes {
+    /// `times[h]` is the header `nTime` of block `h`, starting at genesis.
+    pub fn from_times(times: Vec<u32>) -> Self {
+        let mtp = (0..times.len())
+            .map(|h| {
+                let mut window = times[h.saturating_sub(MTP_WINDOW - 1)..=h].to_vec();
+                window.sort_unstable();
+                window[window.len() / 2]
+            })
+            .collect();
+        Self { times, mtp }
+    }
+
+    /// Number of heights covered (tip height + 1).
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Header timestamps, index = height.
+    pub fn times(&self) -> &[u32] {
+        &self.times
+    }
+
+    /// Header timestamp of `height`.
+    pub fn time(&self, height: u64) -> Option<u32> {
+        self.times.get(height as usize).copied()
+    }
+
+    /// Median-time-past of `height` (including the block itself, as Core does).
+    pub fn median_time_past(&self, height: u64) -> Option<u32> {
+        self.mtp.get(height as usize).copied()
+    }
+
+    /// First height whose MTP is at or after `ts`, `None` if the covered chain ends before it.
+    pub fn first_height_at_or_after(&self, ts: i64) -> Option<u64> {
+        let idx = self.mtp.partition_point(|&m| i64::from(m) < ts);
+        (idx < self.mtp.len()).then_some(idx as u64)
+    }
+
+    /// Last height whose MTP is at or before `ts`, `None` if the chain starts after it.
+    pub fn last_height_at_or_before(&self, ts: i64) -> Option<u64> {
+        let idx = self.mtp.partition_point(|&m| i64::from(m) <= ts);
+        idx.checked_sub(1).map(|h| h as u64)
+    }
+
+    /// Inclusive height range for `[from, to]` (Unix seconds, both inclusive).
+    pub fn height_range(&self, from: i64, to: i64) -> Result<(u64, u64)> {
+        anyhow::ensure!(from <= to, "Date range is reversed ({} > {})", from, to);
+        let start = self.first_height_at_or_after(from).with_context(|| {
+            format!(
+                "No block at or after {} in the {} indexed headers",
+                format_ts(from),
+                self.len()
+            )
+        })?;
+        let end = self
+            .last_height_at_or_before(to)
+            .filter(|&end| end >= start)
+            .with_context(|| {
+                format!("No block between {} and {}", format_ts(from), format_ts(to))
+            })?;
+        Ok((start, end))
+    }
+}
+
+/// Parse a `--from` bound: `YYYY-MM-DD` (start of day, UTC), RFC 3339, or Unix seconds.
+pub fn parse_from(s: &str) -> Result<i64> {
+    parse_bound(s, NaiveTime::MIN)
+}
+
+/// Parse a `--to` bound: `YYYY-MM-DD` (end of day, UTC, inclusive), RFC 3339, or Unix seconds.
+pub fn parse_to(s: &str) -> Result<i64> {
+    parse_bound(s, NaiveTime::from_hms_opt(23, 59, 59).expect("valid time"))
+}
+
+fn parse_bound(s: &str, time_of_day: NaiveTime) -> Result<i64> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.timestamp());
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| {
+        format!(
+            "Invalid date '{}' (expected YYYY-MM-DD, RFC 3339 or Unix seconds)",
+            s
+        )
+    })?;
+    Ok(Utc
+        .from_utc_datetime(&date.and_time(time_of_day))
+        .timestamp())
+}
+
+fn format_ts(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+fn read_times_file(times_file: &Path) -> Result<Option<Vec<u32>>> {
+    if !times_file.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read(times_file)
+        .with_context(|| format!("Failed to read {}", times_file.display()))?;
+    let times =
+        bincode::deserialize(&data).with_context(|| "Failed to deserialize header times")?;
+    Ok(Some(times))
+}
+
+/// Load `chunks.times` as-is, without indexing missing heights (`None` if it was never built).
+pub fn load_header_times(chunks_dir: &Path) -> Result<Option<HeaderTimes>> {
+    Ok(read_times_file(&chunks_dir.join("chunks.times"))?.map(HeaderTimes::from_times))
+}
+
+/// Load `chunks.times` from the chunk cache, extending it from the chunks if it is shorter
+/// than the chunk metadata says the cache is.
+pub fn load_or_build_header_times(chunks_dir: &Path) -> Result<HeaderTimes> {
+    let times_file = chunks_dir.join("chunks.times");
+    let mut times = read_times_file(&times_file)?.unwrap_or_default();
+
+    let total_blocks = load_chunk_metadata(chunks_dir)?
+        .map(|m| m.total_blocks)
+        .unwrap_or(0);
+    if (times.len() as u64) < total_blocks {
+        let from = times.len() as u64;
+        println!(
+            "🕒 Indexing header times {} to {} from chunks (one-time, cached in {})...",
+            from,
+            total_blocks - 1,
+            times_file.display()
+        );
+        let mut iter = ChunkedBlockIterator::new(
+            chunks_dir,
+            Some(from),
+            Some((total_blocks - from) as usize),
+        )?
+        .context("No chunks available to index header times")?;
+        while let Some(block) = iter.next_block()? {
+            anyhow::ensure!(
+                block.len() >= 80,
+                "Block {} is shorter than a header",
+                times.len()
+            );
+            times.push(u32::from_le_bytes(block[68..72].try_into()?));
+            if times.len() % 100_000 == 0 {
+                println!("   🕒 {} headers indexed", times.len());
+            }
+        }
+
+        let tmp = times_file.with_extension("times.tmp");
+        std::fs::write(&tmp, bincode::serialize(&times)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &times_file)
+            .with_context(|| format!("Failed to rename {}", tmp.display()))?;
+    }
+
+    Ok(HeaderTimes::from_times(times))
+}
+
+/// Resolve `--from` / `--to` against the chunk cache. A missing bound means chain start / tip.
+pub fn resolve_date_range(
+    chunks_dir: &Path,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(u64, u64)> {
+    let from = from.map(parse_from).transpose()?.unwrap_or(i64::MIN);
+    let to = to.map(parse_to).transpose()?.unwrap_or(i64::MAX);
+    let times = load_or_build_header_times(chunks_dir)?;
+    let (start, end) = times.height_range(from, to)?;
+    println!(
+        "📅 Date range resolved to heights {}..={} (MTP {} .. {})",
+        start,
+        end,
+        format_ts(times.median_time_past(start).map_or(0, i64::from)),
+        format_ts(times.median_time_past(end).map_or(0, i64::from))
+    );
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mtp_is_median_of_last_eleven() {
+        // Out-of-order header times: block 5 is earlier than its parent.
+        let times = vec![100, 110, 120, 130, 140, 105, 160, 170, 180, 190, 200, 210];
+        let ht = HeaderTimes::from_times(times);
+        assert_eq!(ht.median_time_past(0), Some(100));
+        assert_eq!(ht.median_time_past(2), Some(110));
+        // Window 0..=10: sorted 100,105,110,120,130,140,160,170,180,190,200 -> 140
+        assert_eq!(ht.median_time_past(10), Some(140));
+        // Window 1..=11 drops 100 and adds 210 -> 160
+        assert_eq!(ht.median_time_past(11), Some(160));
+    }
+
+    #[test]
+    fn date_bounds_map_to_inclusive_heights() {
+        let ht = HeaderTimes::from_times((0..50).map(|h| 1_000 + h * 10).collect());
+        let (start, end) = ht.height_range(1_200, 1_300).unwrap();
+        assert!(ht.median_time_past(start).unwrap() >= 1_200);
+        assert!(ht.median_time_past(start - 1).unwrap() < 1_200);
+        assert!(ht.median_time_past(end).unwrap() <= 1_300);
+        assert!(ht.median_time_past(end + 1).unwrap() > 1_300);
+        assert!(ht.height_range(5_000, 6_000).is_err());
+        assert!(ht.height_range(1_300, 1_200).is_err());
+    }
+
+    #[test]
+    fn parses_calendar_dates_as_utc_days() {
+        assert_eq!(parse_from("2017-08-01").unwrap(), 1_501_545_600);
+        assert_eq!(parse_to("2017-08-01").unwrap(), 1_501_545_600 + 86_399);
+        assert_eq!(parse_from("1501545600").unwrap(), 1_501_545_600);
+        assert_eq!(parse_from("2017-08-01T00:00:00Z").unwrap(), 1_501_545_600);
+        assert!(parse_from("08/01/2017").is_err());
+    }
+}
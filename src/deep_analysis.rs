@@ -8,10 +8,21 @@
 //! - Memory bandwidth
 //!
 //! For Commons' own performance optimization and understanding.
+//!
+//! Block-data passes (need the chunked cache) live in submodules, e.g. [`witness`] and
+//! [`script_stats`]; [`cost_profile`] times `connect_block` phases on blocks with spent outputs.
 
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 
+#[cfg(feature = "differential")]
+pub mod cost_profile;
+pub mod dataset;
+#[cfg(feature = "chunk-cache")]
+pub mod script_stats;
+#[cfg(feature = "chunk-cache")]
+pub mod witness;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CpuMetrics {
     pub cycles: Option<u64>,
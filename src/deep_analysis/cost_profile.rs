@@ -0,0 +1,275 @@
+//! Per-block validation cost profile
+//!
+//! `connect_block` reports one duration per block; finding out why a historical block is slow
+//! needs that time split by phase. [`profile_block`] takes a block with its spent outputs
+//! ([`PrevoutBlock`]) and times each phase on its own:
+//!
+//! - `deserialize`: `deserialize_block_with_witnesses` on the raw bytes
+//! - `merkle`: txid hashing plus the merkle root, checked against the header
+//! - `utxo_lookup`: one UTXO set lookup per input whose coin predates the block
+//! - `script_verify`: every input's script check, run serially (CPU time, so on a multi-core
+//!   `connect_block` it can exceed the wall time of the whole block)
+//! - `connect_block`: the full call, with the UTXO set clone kept out of the timing
+//!
+//! [`profile_range`] runs it over every cached height with a sidecar and keeps the per-height
+//! breakdown, written as CSV by [`CostProfile::to_csv`] or as Parquet (see [`super::dataset`]).
+
+use anyhow::Result;
+use blvm_protocol::block::{
+    block_validation_context_for_connect_ibd, calculate_script_flags_for_block_network,
+    calculate_tx_id,
+};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::types::{BlockHeader, Network, ValidationResult};
+use blvm_protocol::witness::is_witness_empty;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use super::dataset::{self, Column, ColumnType, Dataset, Value};
+use crate::block_file_reader::SharedBlockCache;
+use crate::consensus_compat::connect_block;
+use crate::micro_bench::MicroBlock;
+use crate::prevout_blocks::PrevoutBlock;
+use crate::wire::merkle_root;
+
+/// Where one block's validation time went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockCost {
+    pub height: u64,
+    pub size: usize,
+    pub transactions: usize,
+    /// Non-coinbase inputs
+    pub inputs: usize,
+    pub deserialize: Duration,
+    pub merkle: Duration,
+    pub utxo_lookup: Duration,
+    pub script_verify: Duration,
+    pub connect_block: Duration,
+    /// `connect_block` returned `Valid`, every script passed and the merkle root matched
+    pub valid: bool,
+}
+
+impl BlockCost {
+    /// The most expensive of the individually timed phases.
+    pub fn dominant_phase(&self) -> &'static str {
+        [
+            ("deserialize", self.deserialize),
+            ("merkle", self.merkle),
+            ("utxo_lookup", self.utxo_lookup),
+            ("script_verify", self.script_verify),
+        ]
+        .into_iter()
+        .max_by_key(|(_, d)| *d)
+        .map(|(name, _)| name)
+        .unwrap_or("deserialize")
+    }
+}
+
+/// Phase breakdown of `prevout_block` on `network`.
+pub fn profile_block(prevout_block: &PrevoutBlock, network: Network) -> Result<BlockCost> {
+    let start = Instant::now();
+    deserialize_block_with_witnesses(&prevout_block.block_bytes).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to deserialize block {}: {:?}",
+            prevout_block.height,
+            e
+        )
+    })?;
+    let deserialize = start.elapsed();
+
+    let micro = MicroBlock::prepare(prevout_block)?;
+    let (block, witnesses, height) = (&micro.block, &micro.witnesses, micro.height);
+
+    let start = Instant::now();
+    let txids: Vec<[u8; 32]> = block.transactions.iter().map(calculate_tx_id).collect();
+    let merkle_ok = merkle_root(&txids) == block.header.merkle_root;
+    let merkle = start.elapsed();
+
+    let in_block: HashSet<[u8; 32]> = txids.iter().copied().collect();
+    let start = Instant::now();
+    for tx in block.transactions.iter().skip(1) {
+        for input in tx.inputs.iter() {
+            if !in_block.contains(&input.prevout.hash) {
+                std::hint::black_box(micro.utxo_set.get(&input.prevout));
+            }
+        }
+    }
+    let utxo_lookup = start.elapsed();
+
+    let start = Instant::now();
+    let mut scripts_ok = true;
+    for (tx_idx, tx) in block.transactions.iter().enumerate().skip(1) {
+        let Some((values, script_pubkeys)) = prevout_block.prevouts(tx_idx) else {
+            anyhow::bail!("Block {} has no spent outputs for tx {}", height, tx_idx);
+        };
+        let tx_witnesses = witnesses.get(tx_idx).map(Vec::as_slice).unwrap_or(&[]);
+        let has_witness = tx_witnesses.iter().any(|w| !is_witness_empty(w));
+        let flags = calculate_script_flags_for_block_network(tx, has_witness, height, network);
+        for (input_idx, input) in tx.inputs.iter().enumerate() {
+            scripts_ok &= matches!(
+                verify_script_with_context_full(
+                    &input.script_sig,
+                    script_pubkeys[input_idx],
+                    tx_witnesses.get(input_idx),
+                    flags,
+                    tx,
+                    input_idx,
+                    &values,
+                    &script_pubkeys,
+                    Some(height),
+                    None,
+                    network,
+                    SigVersion::Base,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                ),
+                Ok(true)
+            );
+        }
+    }
+    let script_verify = start.elapsed();
+
+    let ctx = block_validation_context_for_connect_ibd(
+        None::<&[BlockHeader]>,
+        block.header.timestamp,
+        network,
+    );
+    let utxo_set = micro.utxo_set.clone();
+    let start = Instant::now();
+    let (result, after) = connect_block(block, witnesses, utxo_set, height, &ctx)?;
+    let connect_block = start.elapsed();
+    drop(after);
+
+    Ok(BlockCost {
+        height,
+        size: prevout_block.block_bytes.len(),
+        transactions: block.transactions.len(),
+        inputs: micro.inputs(),
+        deserialize,
+        merkle,
+        utxo_lookup,
+        script_verify,
+        connect_block,
+        valid: merkle_ok && scripts_ok && matches!(result, ValidationResult::Valid),
+    })
+}
+
+/// Per-height table columns (see [`CostProfile::to_csv`])
+const COLUMNS: &[Column] = &[
+    Column::new("height", ColumnType::UInt64),
+    Column::new("size", ColumnType::UInt64),
+    Column::new("transactions", ColumnType::UInt64),
+    Column::new("inputs", ColumnType::UInt64),
+    Column::new("deserialize_us", ColumnType::UInt64),
+    Column::new("merkle_us", ColumnType::UInt64),
+    Column::new("utxo_lookup_us", ColumnType::UInt64),
+    Column::new("script_verify_us", ColumnType::UInt64),
+    Column::new("connect_block_us", ColumnType::UInt64),
+    Column::new("dominant_phase", ColumnType::Utf8),
+    Column::new("valid", ColumnType::Boolean),
+];
+
+/// Per-height costs over a range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostProfile {
+    pub blocks: Vec<BlockCost>,
+    /// Heights skipped for lack of a cached block or spent-outputs sidecar
+    pub missing: Vec<u64>,
+}
+
+impl CostProfile {
+    /// The `n` blocks with the longest `connect_block`.
+    pub fn slowest(&self, n: usize) -> Vec<&BlockCost> {
+        let mut sorted: Vec<&BlockCost> = self.blocks.iter().collect();
+        sorted.sort_by_key(|b| std::cmp::Reverse(b.connect_block));
+        sorted.truncate(n);
+        sorted
+    }
+
+    /// One row per height, durations in microseconds.
+    pub fn to_csv(&self) -> String {
+        dataset::to_csv(self)
+    }
+
+    pub fn print_summary(&self, top: usize) {
+        let sum = |f: fn(&BlockCost) -> Duration| self.blocks.iter().map(f).sum::<Duration>();
+        println!(
+            "\n⏱️  Cost profile: {} blocks ({} skipped without a sidecar)",
+            self.blocks.len(),
+            self.missing.len()
+        );
+        for (name, total) in [
+            ("deserialize", sum(|b| b.deserialize)),
+            ("merkle", sum(|b| b.merkle)),
+            ("utxo_lookup", sum(|b| b.utxo_lookup)),
+            ("script_verify", sum(|b| b.script_verify)),
+            ("connect_block", sum(|b| b.connect_block)),
+        ] {
+            println!("   {:<14} {:>12.2?}", name, total);
+        }
+        println!("\n🐢 Slowest blocks:");
+        for b in self.slowest(top) {
+            println!(
+                "   {:>8} {:>10.2?}  {} txs, {} inputs, {} bytes, mostly {}{}",
+                b.height,
+                b.connect_block,
+                b.transactions,
+                b.inputs,
+                b.size,
+                b.dominant_phase(),
+                if b.valid { "" } else { "  ❌ invalid" }
+            );
+        }
+    }
+}
+
+impl Dataset for CostProfile {
+    fn name(&self) -> &'static str {
+        "cost_profile"
+    }
+
+    fn schema(&self) -> &'static [Column] {
+        COLUMNS
+    }
+
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+        let micros = |d: Duration| Value::UInt64(d.as_micros() as u64);
+        Box::new(self.blocks.iter().map(move |b| {
+            vec![
+                b.height.into(),
+                b.size.into(),
+                b.transactions.into(),
+                b.inputs.into(),
+                micros(b.deserialize),
+                micros(b.merkle),
+                micros(b.utxo_lookup),
+                micros(b.script_verify),
+                micros(b.connect_block),
+                b.dominant_phase().into(),
+                b.valid.into(),
+            ]
+        }))
+    }
+}
+
+/// Profile every height in `start..=end` that has a sidecar in `cache`.
+pub fn profile_range(
+    cache: &SharedBlockCache,
+    start: u64,
+    end: u64,
+    network: Network,
+) -> Result<CostProfile> {
+    let mut profile = CostProfile::default();
+    for height in start..=end {
+        match cache.read_prevout_block(height)? {
+            Some(prevout_block) => profile.blocks.push(profile_block(&prevout_block, network)?),
+            None => profile.missing.push(height),
+        }
+    }
+    Ok(profile)
+}
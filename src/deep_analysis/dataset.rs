@@ -0,0 +1,414 @@
+//! Analysis tables with a fixed schema, written as CSV or Parquet
+//!
+//! Each analysis output that is a flat table (script statistics, cost profiles, divergences,
+//! block statistics) implements [`Dataset`]: a name, a [`Column`] list and its rows. The same
+//! rows then go to either format, so the CSV header and the Parquet schema can't drift apart:
+//!
+//! - [`to_csv`]: header line plus one line per row, free text quoted per RFC 4180
+//! - [`write`]: CSV, or Parquet when the path ends in `.parquet` (zstd-compressed, one row group
+//!   per [`PARQUET_BATCH_ROWS`] rows); Parquet needs the `parquet` feature
+//!
+//! Parquet columns map to Arrow `UInt64`, `Int64`, `Float64`, `Boolean` and `Utf8`, so Python
+//! (`pandas.read_parquet`) and DuckDB (`SELECT * FROM 'cost_profile.parquet'`) read them with
+//! their types and without custom parsing.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Rows per Parquet row group
+pub const PARQUET_BATCH_ROWS: usize = 64 * 1024;
+
+/// Column value type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    UInt64,
+    Int64,
+    Float64,
+    Boolean,
+    Utf8,
+}
+
+/// One column of a [`Dataset`] schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+    /// May hold [`Value::Null`] (an empty CSV field)
+    pub nullable: bool,
+}
+
+impl Column {
+    pub const fn new(name: &'static str, ty: ColumnType) -> Self {
+        Self {
+            name,
+            ty,
+            nullable: false,
+        }
+    }
+
+    pub const fn nullable(self) -> Self {
+        Self {
+            nullable: true,
+            ..self
+        }
+    }
+}
+
+/// One cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    UInt64(u64),
+    Int64(i64),
+    Float64(f64),
+    Boolean(bool),
+    Utf8(String),
+    Null,
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Value::UInt64(v)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Value::UInt64(v as u64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int64(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float64(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Boolean(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Utf8(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Utf8(v.to_string())
+    }
+}
+
+impl Value {
+    fn csv_field(&self) -> String {
+        match self {
+            Value::UInt64(v) => v.to_string(),
+            Value::Int64(v) => v.to_string(),
+            Value::Float64(v) => v.to_string(),
+            Value::Boolean(v) => v.to_string(),
+            Value::Utf8(s) if s.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            }
+            Value::Utf8(s) => s.clone(),
+            Value::Null => String::new(),
+        }
+    }
+}
+
+/// A flat table with a fixed schema.
+pub trait Dataset {
+    /// Table name, e.g. `cost_profile`
+    fn name(&self) -> &'static str;
+
+    fn schema(&self) -> &'static [Column];
+
+    /// Rows with one value per [`schema`](Self::schema) column, in order.
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_>;
+}
+
+/// Output format of [`write`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    /// Parquet for a `.parquet` extension, CSV otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("parquet") => Format::Parquet,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// The whole dataset as CSV.
+pub fn to_csv(dataset: &dyn Dataset) -> String {
+    let schema = dataset.schema();
+    let mut out = schema.iter().map(|c| c.name).collect::<Vec<_>>().join(",");
+    out.push('\n');
+    for row in dataset.rows() {
+        debug_assert_eq!(row.len(), schema.len(), "{} row width", dataset.name());
+        out.push_str(
+            &row.iter()
+                .map(Value::csv_field)
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        out.push('\n');
+    }
+    out
+}
+
+/// Write `dataset` to `path` in the format its extension asks for (see [`Format::from_path`]).
+pub fn write(dataset: &dyn Dataset, path: &Path) -> Result<Format> {
+    let format = Format::from_path(path);
+    write_as(dataset, path, format)?;
+    Ok(format)
+}
+
+/// Write `dataset` to `path` as `format`, whatever the extension.
+pub fn write_as(dataset: &dyn Dataset, path: &Path, format: Format) -> Result<()> {
+    match format {
+        Format::Csv => std::fs::write(path, to_csv(dataset))
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        Format::Parquet => write_parquet(dataset, path)
+            .with_context(|| format!("Failed to write {} as Parquet", path.display()))?,
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(dataset: &dyn Dataset, _path: &Path) -> Result<()> {
+    anyhow::bail!(
+        "Parquet output of {} needs a build with --features parquet",
+        dataset.name()
+    )
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(dataset: &dyn Dataset, path: &Path) -> Result<()> {
+    use arrow::array::ArrayRef;
+    use arrow::datatypes::{Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::{Compression, ZstdLevel};
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    let columns = dataset.schema();
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|c| Field::new(c.name, parquet_column::data_type(c.ty), c.nullable))
+            .collect::<Vec<_>>(),
+    ));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_size(PARQUET_BATCH_ROWS)
+        .build();
+    let file = std::fs::File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))?;
+
+    let mut builders: Vec<parquet_column::Builder> =
+        columns.iter().map(parquet_column::Builder::new).collect();
+    let mut pending = 0;
+    let mut flush = |builders: &mut Vec<parquet_column::Builder>| -> Result<()> {
+        let arrays: Vec<ArrayRef> = builders.iter_mut().map(|b| b.finish()).collect();
+        writer.write(&RecordBatch::try_new(schema.clone(), arrays)?)?;
+        Ok(())
+    };
+    for row in dataset.rows() {
+        anyhow::ensure!(
+            row.len() == columns.len(),
+            "{} row has {} values for {} columns",
+            dataset.name(),
+            row.len(),
+            columns.len()
+        );
+        for (builder, value) in builders.iter_mut().zip(row) {
+            builder.append(value)?;
+        }
+        pending += 1;
+        if pending == PARQUET_BATCH_ROWS {
+            flush(&mut builders)?;
+            pending = 0;
+        }
+    }
+    if pending > 0 {
+        flush(&mut builders)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_column {
+    use anyhow::Result;
+    use arrow::array::{
+        ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, UInt64Builder,
+    };
+    use arrow::datatypes::DataType;
+    use std::sync::Arc;
+
+    use super::{Column, ColumnType, Value};
+
+    pub(super) fn data_type(ty: ColumnType) -> DataType {
+        match ty {
+            ColumnType::UInt64 => DataType::UInt64,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Utf8 => DataType::Utf8,
+        }
+    }
+
+    /// Arrow builder for one column, checking values against its schema.
+    pub(super) struct Builder {
+        column: Column,
+        array: Array,
+    }
+
+    enum Array {
+        UInt64(UInt64Builder),
+        Int64(Int64Builder),
+        Float64(Float64Builder),
+        Boolean(BooleanBuilder),
+        Utf8(StringBuilder),
+    }
+
+    impl Builder {
+        pub(super) fn new(column: &Column) -> Self {
+            let array = match column.ty {
+                ColumnType::UInt64 => Array::UInt64(UInt64Builder::new()),
+                ColumnType::Int64 => Array::Int64(Int64Builder::new()),
+                ColumnType::Float64 => Array::Float64(Float64Builder::new()),
+                ColumnType::Boolean => Array::Boolean(BooleanBuilder::new()),
+                ColumnType::Utf8 => Array::Utf8(StringBuilder::new()),
+            };
+            Self {
+                column: *column,
+                array,
+            }
+        }
+
+        pub(super) fn append(&mut self, value: Value) -> Result<()> {
+            match (&mut self.array, value) {
+                (_, Value::Null) if !self.column.nullable => {
+                    anyhow::bail!("Null in non-nullable column {}", self.column.name)
+                }
+                (Array::UInt64(b), Value::Null) => b.append_null(),
+                (Array::Int64(b), Value::Null) => b.append_null(),
+                (Array::Float64(b), Value::Null) => b.append_null(),
+                (Array::Boolean(b), Value::Null) => b.append_null(),
+                (Array::Utf8(b), Value::Null) => b.append_null(),
+                (Array::UInt64(b), Value::UInt64(v)) => b.append_value(v),
+                (Array::Int64(b), Value::Int64(v)) => b.append_value(v),
+                (Array::Float64(b), Value::Float64(v)) => b.append_value(v),
+                (Array::Boolean(b), Value::Boolean(v)) => b.append_value(v),
+                (Array::Utf8(b), Value::Utf8(v)) => b.append_value(v),
+                (_, value) => anyhow::bail!(
+                    "{:?} in {:?} column {}",
+                    value,
+                    self.column.ty,
+                    self.column.name
+                ),
+            }
+            Ok(())
+        }
+
+        pub(super) fn finish(&mut self) -> ArrayRef {
+            match &mut self.array {
+                Array::UInt64(b) => Arc::new(b.finish()),
+                Array::Int64(b) => Arc::new(b.finish()),
+                Array::Float64(b) => Arc::new(b.finish()),
+                Array::Boolean(b) => Arc::new(b.finish()),
+                Array::Utf8(b) => Arc::new(b.finish()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fees(Vec<(u64, Option<i64>, &'static str)>);
+
+    impl Dataset for Fees {
+        fn name(&self) -> &'static str {
+            "fees"
+        }
+
+        fn schema(&self) -> &'static [Column] {
+            const COLUMNS: &[Column] = &[
+                Column::new("height", ColumnType::UInt64),
+                Column::new("fees", ColumnType::Int64).nullable(),
+                Column::new("note", ColumnType::Utf8),
+            ];
+            COLUMNS
+        }
+
+        fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+            Box::new(
+                self.0
+                    .iter()
+                    .map(|&(height, fees, note)| vec![height.into(), fees.into(), note.into()]),
+            )
+        }
+    }
+
+    #[test]
+    fn csv_and_parquet_share_the_schema() {
+        let fees = Fees(vec![(1, Some(-5), "a, \"b\""), (2, None, "c")]);
+        assert_eq!(
+            to_csv(&fees),
+            "height,fees,note\n1,-5,\"a, \"\"b\"\"\"\n2,,c\n"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fees.parquet");
+        assert_eq!(Format::from_path(&path), Format::Parquet);
+        let written = write(&fees, &path);
+        #[cfg(not(feature = "parquet"))]
+        assert!(written.is_err());
+        #[cfg(feature = "parquet")]
+        {
+            use arrow::array::{Array, Int64Array};
+            use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+            assert_eq!(written.unwrap(), Format::Parquet);
+            let file = std::fs::File::open(&path).unwrap();
+            let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(batch.num_rows(), 2);
+            assert_eq!(batch.schema().field(0).name(), "height");
+            let fees = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap();
+            assert_eq!((fees.value(0), fees.is_null(1)), (-5, true));
+        }
+    }
+}
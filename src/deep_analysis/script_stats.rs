@@ -0,0 +1,583 @@
+//! Script type / opcode frequency pass
+//!
+//! Per-window (default [`WINDOW_BLOCKS`] blocks) tallies of output script types, opcode
+//! frequencies and witness program versions, so benchmark fixtures can be picked to match the
+//! script mix real blocks carry at a given height rather than a guess.
+//!
+//! Opcodes are counted over every scriptPubKey, scriptSig and witness script (the last witness
+//! element of a P2WSH / tapscript spend, as in [`witness`](super::witness); P2WPKH keys and
+//! taproot annexes are not scripts and are skipped); direct pushes of 1–75 bytes are tallied together as `OP_PUSHBYTES`. The result is written as JSON or as a long
+//! CSV (`window_start,window_end,category,key,count`) via [`ScriptStatsAnalysis::to_csv`], or as
+//! Parquet with the same columns (see [`dataset`]).
+
+use super::dataset::{self, Column, ColumnType, Dataset, Value};
+use super::witness::witness_script;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Default window size in blocks.
+pub const WINDOW_BLOCKS: u64 = 10_000;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Output script template (Core's `TxoutType`, with witness versions split out).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    BareMultisig,
+    OpReturn,
+    /// Witness program of a version / length with no spending rules yet (e.g. P2A)
+    WitnessUnknown,
+    NonStandard,
+}
+
+impl ScriptType {
+    pub fn classify(script: &[u8]) -> Self {
+        if let Some((version, program)) = witness_program(script) {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::P2wpkh,
+                (0, 32) => ScriptType::P2wsh,
+                (1, 32) => ScriptType::P2tr,
+                _ => ScriptType::WitnessUnknown,
+            };
+        }
+        match script {
+            [OP_DUP, OP_HASH160, 0x14, .., OP_EQUALVERIFY, OP_CHECKSIG] if script.len() == 25 => {
+                ScriptType::P2pkh
+            }
+            [OP_HASH160, 0x14, .., OP_EQUAL] if script.len() == 23 => ScriptType::P2sh,
+            [33, .., OP_CHECKSIG] if script.len() == 35 => ScriptType::P2pk,
+            [65, .., OP_CHECKSIG] if script.len() == 67 => ScriptType::P2pk,
+            [OP_RETURN, ..] => ScriptType::OpReturn,
+            _ if is_bare_multisig(script) => ScriptType::BareMultisig,
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScriptType::P2pk => "p2pk",
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2sh => "p2sh",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2wsh => "p2wsh",
+            ScriptType::P2tr => "p2tr",
+            ScriptType::BareMultisig => "bare_multisig",
+            ScriptType::OpReturn => "op_return",
+            ScriptType::WitnessUnknown => "witness_unknown",
+            ScriptType::NonStandard => "nonstandard",
+        }
+    }
+}
+
+/// `CScript::IsWitnessProgram`: version and program.
+fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if !(4..=42).contains(&script.len()) || script[1] as usize + 2 != script.len() {
+        return None;
+    }
+    match script[0] {
+        OP_0 => Some((0, &script[2..])),
+        OP_1..=OP_16 => Some((script[0] - OP_1 + 1, &script[2..])),
+        _ => None,
+    }
+}
+
+/// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` with 33- or 65-byte keys.
+fn is_bare_multisig(script: &[u8]) -> bool {
+    let [m @ OP_1..=OP_16, keys @ .., n @ OP_1..=OP_16, OP_CHECKMULTISIG] = script else {
+        return false;
+    };
+    let mut rest = keys;
+    let mut count = 0u8;
+    while let [len @ (33 | 65), tail @ ..] = rest {
+        let Some(next) = tail.get(*len as usize..) else {
+            return false;
+        };
+        rest = next;
+        count += 1;
+    }
+    rest.is_empty() && count == *n - OP_1 + 1 && m <= n
+}
+
+/// Tally every opcode of `script` into `counts` (indexed by opcode byte). Stops at a truncated
+/// push, as the interpreter would.
+fn count_opcodes(script: &[u8], counts: &mut [u64]) {
+    let mut pc = 0;
+    while let Some(&opcode) = script.get(pc) {
+        pc += 1;
+        let len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4 => {
+                let width = 1 << (opcode - OP_PUSHDATA1);
+                let Some(raw) = script.get(pc..pc + width) else {
+                    return;
+                };
+                pc += width;
+                raw.iter()
+                    .rev()
+                    .fold(0usize, |acc, &b| (acc << 8) | b as usize)
+            }
+            _ => 0,
+        };
+        if script.len() - pc < len {
+            return;
+        }
+        pc += len;
+        let key = if (0x01..=0x4b).contains(&opcode) {
+            0x01
+        } else {
+            opcode
+        };
+        counts[key as usize] += 1;
+    }
+}
+
+/// Core's name for `opcode` (`OP_PUSHBYTES` for every direct push of 1–75 bytes).
+pub fn opcode_name(opcode: u8) -> String {
+    let name = match opcode {
+        0x00 => "OP_0",
+        0x01..=0x4b => "OP_PUSHBYTES",
+        0x4c => "OP_PUSHDATA1",
+        0x4d => "OP_PUSHDATA2",
+        0x4e => "OP_PUSHDATA4",
+        0x4f => "OP_1NEGATE",
+        0x50 => "OP_RESERVED",
+        0x51..=0x60 => return format!("OP_{}", opcode - 0x50),
+        0x61 => "OP_NOP",
+        0x62 => "OP_VER",
+        0x63 => "OP_IF",
+        0x64 => "OP_NOTIF",
+        0x65 => "OP_VERIF",
+        0x66 => "OP_VERNOTIF",
+        0x67 => "OP_ELSE",
+        0x68 => "OP_ENDIF",
+        0x69 => "OP_VERIFY",
+        0x6a => "OP_RETURN",
+        0x6b => "OP_TOALTSTACK",
+        0x6c => "OP_FROMALTSTACK",
+        0x6d => "OP_2DROP",
+        0x6e => "OP_2DUP",
+        0x6f => "OP_3DUP",
+        0x70 => "OP_2OVER",
+        0x71 => "OP_2ROT",
+        0x72 => "OP_2SWAP",
+        0x73 => "OP_IFDUP",
+        0x74 => "OP_DEPTH",
+        0x75 => "OP_DROP",
+        0x76 => "OP_DUP",
+        0x77 => "OP_NIP",
+        0x78 => "OP_OVER",
+        0x79 => "OP_PICK",
+        0x7a => "OP_ROLL",
+        0x7b => "OP_ROT",
+        0x7c => "OP_SWAP",
+        0x7d => "OP_TUCK",
+        0x7e => "OP_CAT",
+        0x7f => "OP_SUBSTR",
+        0x80 => "OP_LEFT",
+        0x81 => "OP_RIGHT",
+        0x82 => "OP_SIZE",
+        0x83 => "OP_INVERT",
+        0x84 => "OP_AND",
+        0x85 => "OP_OR",
+        0x86 => "OP_XOR",
+        0x87 => "OP_EQUAL",
+        0x88 => "OP_EQUALVERIFY",
+        0x89 => "OP_RESERVED1",
+        0x8a => "OP_RESERVED2",
+        0x8b => "OP_1ADD",
+        0x8c => "OP_1SUB",
+        0x8d => "OP_2MUL",
+        0x8e => "OP_2DIV",
+        0x8f => "OP_NEGATE",
+        0x90 => "OP_ABS",
+        0x91 => "OP_NOT",
+        0x92 => "OP_0NOTEQUAL",
+        0x93 => "OP_ADD",
+        0x94 => "OP_SUB",
+        0x95 => "OP_MUL",
+        0x96 => "OP_DIV",
+        0x97 => "OP_MOD",
+        0x98 => "OP_LSHIFT",
+        0x99 => "OP_RSHIFT",
+        0x9a => "OP_BOOLAND",
+        0x9b => "OP_BOOLOR",
+        0x9c => "OP_NUMEQUAL",
+        0x9d => "OP_NUMEQUALVERIFY",
+        0x9e => "OP_NUMNOTEQUAL",
+        0x9f => "OP_LESSTHAN",
+        0xa0 => "OP_GREATERTHAN",
+        0xa1 => "OP_LESSTHANOREQUAL",
+        0xa2 => "OP_GREATERTHANOREQUAL",
+        0xa3 => "OP_MIN",
+        0xa4 => "OP_MAX",
+        0xa5 => "OP_WITHIN",
+        0xa6 => "OP_RIPEMD160",
+        0xa7 => "OP_SHA1",
+        0xa8 => "OP_SHA256",
+        0xa9 => "OP_HASH160",
+        0xaa => "OP_HASH256",
+        0xab => "OP_CODESEPARATOR",
+        0xac => "OP_CHECKSIG",
+        0xad => "OP_CHECKSIGVERIFY",
+        0xae => "OP_CHECKMULTISIG",
+        0xaf => "OP_CHECKMULTISIGVERIFY",
+        0xb0 => "OP_NOP1",
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+        0xb2 => "OP_CHECKSEQUENCEVERIFY",
+        0xb3..=0xb9 => return format!("OP_NOP{}", opcode - 0xaf),
+        0xba => "OP_CHECKSIGADD",
+        _ => return format!("OP_UNKNOWN_{:#04x}", opcode),
+    };
+    name.to_string()
+}
+
+/// Tallies for one window of blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowScriptStats {
+    pub blocks: u64,
+    pub outputs: u64,
+    pub inputs: u64,
+    pub script_types: BTreeMap<ScriptType, u64>,
+    /// Outputs per witness program version (`0`..=`16`)
+    pub witness_versions: BTreeMap<u8, u64>,
+    /// Indexed by opcode byte; direct pushes are all counted at `0x01`
+    pub opcodes: Vec<u64>,
+}
+
+impl Default for WindowScriptStats {
+    fn default() -> Self {
+        Self {
+            blocks: 0,
+            outputs: 0,
+            inputs: 0,
+            script_types: BTreeMap::new(),
+            witness_versions: BTreeMap::new(),
+            opcodes: vec![0; 256],
+        }
+    }
+}
+
+impl WindowScriptStats {
+    fn merge(&mut self, other: &WindowScriptStats) {
+        self.blocks += other.blocks;
+        self.outputs += other.outputs;
+        self.inputs += other.inputs;
+        for (kind, n) in &other.script_types {
+            *self.script_types.entry(*kind).or_default() += n;
+        }
+        for (version, n) in &other.witness_versions {
+            *self.witness_versions.entry(*version).or_default() += n;
+        }
+        for (a, b) in self.opcodes.iter_mut().zip(&other.opcodes) {
+            *a += b;
+        }
+    }
+
+    /// Opcodes seen, most frequent first.
+    pub fn top_opcodes(&self, n: usize) -> Vec<(String, u64)> {
+        let mut seen: Vec<(u8, u64)> = self
+            .opcodes
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(op, &count)| (op as u8, count))
+            .collect();
+        seen.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        seen.into_iter()
+            .take(n)
+            .map(|(op, count)| (opcode_name(op), count))
+            .collect()
+    }
+}
+
+/// Long-format table columns (see [`ScriptStatsAnalysis::to_csv`])
+const COLUMNS: &[Column] = &[
+    Column::new("window_start", ColumnType::UInt64),
+    Column::new("window_end", ColumnType::UInt64),
+    Column::new("category", ColumnType::Utf8),
+    Column::new("key", ColumnType::Utf8),
+    Column::new("count", ColumnType::UInt64),
+];
+
+/// Script statistics keyed by window start height.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStatsAnalysis {
+    pub window_blocks: u64,
+    pub windows: BTreeMap<u64, WindowScriptStats>,
+}
+
+impl Default for ScriptStatsAnalysis {
+    fn default() -> Self {
+        Self::new(WINDOW_BLOCKS)
+    }
+}
+
+impl ScriptStatsAnalysis {
+    pub fn new(window_blocks: u64) -> Self {
+        Self {
+            window_blocks: window_blocks.max(1),
+            windows: BTreeMap::new(),
+        }
+    }
+
+    /// Add one block (`witnesses` indexed per tx, then per input — as returned by
+    /// `deserialize_block_with_witnesses`).
+    pub fn add_block(&mut self, block: &Block, witnesses: &[Vec<Witness>], height: u64) {
+        let start = height - height % self.window_blocks;
+        let window = self.windows.entry(start).or_default();
+        window.blocks += 1;
+
+        for (tx_idx, tx) in block.transactions.iter().enumerate() {
+            for output in tx.outputs.iter() {
+                window.outputs += 1;
+                *window
+                    .script_types
+                    .entry(ScriptType::classify(&output.script_pubkey))
+                    .or_default() += 1;
+                if let Some((version, _)) = witness_program(&output.script_pubkey) {
+                    *window.witness_versions.entry(version).or_default() += 1;
+                }
+                count_opcodes(&output.script_pubkey, &mut window.opcodes);
+            }
+            if tx_idx == 0 {
+                continue;
+            }
+            let tx_wits = witnesses.get(tx_idx);
+            for (input_index, input) in tx.inputs.iter().enumerate() {
+                window.inputs += 1;
+                count_opcodes(&input.script_sig, &mut window.opcodes);
+                if let Some(script) = tx_wits
+                    .and_then(|w| w.get(input_index))
+                    .and_then(witness_script)
+                {
+                    count_opcodes(script, &mut window.opcodes);
+                }
+            }
+        }
+    }
+
+    /// Merge another partial analysis (e.g. from a parallel batch) with the same window size.
+    pub fn merge(&mut self, other: &ScriptStatsAnalysis) {
+        for (start, stats) in &other.windows {
+            self.windows.entry(*start).or_default().merge(stats);
+        }
+    }
+
+    /// Long-format CSV: one row per window and `script_type` / `witness_version` / `opcode` key.
+    pub fn to_csv(&self) -> String {
+        dataset::to_csv(self)
+    }
+
+    /// Terminal summary, one block per window.
+    pub fn format_summary(&self) -> String {
+        let mut out = String::new();
+        for (start, w) in &self.windows {
+            out.push_str(&format!(
+                "📊 {}–{}: {} blocks, {} outputs, {} inputs\n",
+                start,
+                start + self.window_blocks - 1,
+                w.blocks,
+                w.outputs,
+                w.inputs
+            ));
+            let types: Vec<String> = w
+                .script_types
+                .iter()
+                .map(|(kind, n)| {
+                    format!(
+                        "{} {:.1}%",
+                        kind.as_str(),
+                        *n as f64 * 100.0 / w.outputs.max(1) as f64
+                    )
+                })
+                .collect();
+            out.push_str(&format!("   types: {}\n", types.join(", ")));
+            let ops: Vec<String> = w
+                .top_opcodes(8)
+                .into_iter()
+                .map(|(name, n)| format!("{name} {n}"))
+                .collect();
+            out.push_str(&format!("   top opcodes: {}\n", ops.join(", ")));
+        }
+        out
+    }
+}
+
+impl Dataset for ScriptStatsAnalysis {
+    fn name(&self) -> &'static str {
+        "script_stats"
+    }
+
+    fn schema(&self) -> &'static [Column] {
+        COLUMNS
+    }
+
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+        Box::new(self.windows.iter().flat_map(|(&start, w)| {
+            let end = start + self.window_blocks - 1;
+            let row = |category: &str, key: String, count: u64| {
+                vec![
+                    start.into(),
+                    end.into(),
+                    category.into(),
+                    key.into(),
+                    count.into(),
+                ]
+            };
+            let mut rows = vec![
+                row("totals", "blocks".to_string(), w.blocks),
+                row("totals", "outputs".to_string(), w.outputs),
+                row("totals", "inputs".to_string(), w.inputs),
+            ];
+            for (kind, n) in &w.script_types {
+                rows.push(row("script_type", kind.as_str().to_string(), *n));
+            }
+            for (version, n) in &w.witness_versions {
+                rows.push(row("witness_version", format!("v{version}"), *n));
+            }
+            for (op, n) in w.opcodes.iter().enumerate() {
+                if *n > 0 {
+                    rows.push(row("opcode", opcode_name(op as u8), *n));
+                }
+            }
+            rows
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blvm_protocol::{
+        tx_inputs, tx_outputs, BlockHeader, OutPoint, Transaction, TransactionInput,
+        TransactionOutput,
+    };
+
+    #[test]
+    fn classifies_output_templates() {
+        let p2pkh = [
+            &[OP_DUP, OP_HASH160, 0x14][..],
+            &[0; 20],
+            &[OP_EQUALVERIFY, OP_CHECKSIG],
+        ]
+        .concat();
+        assert_eq!(ScriptType::classify(&p2pkh), ScriptType::P2pkh);
+        let p2sh = [&[OP_HASH160, 0x14][..], &[0; 20], &[OP_EQUAL]].concat();
+        assert_eq!(ScriptType::classify(&p2sh), ScriptType::P2sh);
+        let p2pk = [&[33][..], &[2; 33], &[OP_CHECKSIG]].concat();
+        assert_eq!(ScriptType::classify(&p2pk), ScriptType::P2pk);
+        let p2wsh = [&[OP_0, 32][..], &[0; 32]].concat();
+        assert_eq!(ScriptType::classify(&p2wsh), ScriptType::P2wsh);
+        let p2tr = [&[OP_1, 32][..], &[0; 32]].concat();
+        assert_eq!(ScriptType::classify(&p2tr), ScriptType::P2tr);
+        assert_eq!(
+            ScriptType::classify(&[OP_1, 2, 0x4e, 0x73]),
+            ScriptType::WitnessUnknown
+        );
+        let multisig = [
+            &[0x51, 33][..],
+            &[2; 33],
+            &[33],
+            &[3; 33],
+            &[0x52, OP_CHECKMULTISIG],
+        ]
+        .concat();
+        assert_eq!(ScriptType::classify(&multisig), ScriptType::BareMultisig);
+        assert_eq!(
+            ScriptType::classify(&[OP_RETURN, 1, 7]),
+            ScriptType::OpReturn
+        );
+        assert_eq!(ScriptType::classify(&[0x51]), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn counts_opcodes_and_skips_push_data() {
+        let mut counts = vec![0u64; 256];
+        // OP_DUP OP_HASH160 <20> OP_EQUALVERIFY OP_CHECKSIG, then a PUSHDATA1 of 0xac bytes
+        let p2pkh = [
+            &[OP_DUP, OP_HASH160, 0x14][..],
+            &[OP_CHECKSIG; 20],
+            &[OP_EQUALVERIFY, OP_CHECKSIG],
+        ]
+        .concat();
+        count_opcodes(&p2pkh, &mut counts);
+        count_opcodes(&[OP_PUSHDATA1, 2, OP_CHECKSIG, OP_CHECKSIG], &mut counts);
+        // Truncated push stops the walk
+        count_opcodes(&[OP_CHECKSIG, OP_PUSHDATA2, 0xff], &mut counts);
+        assert_eq!(counts[OP_CHECKSIG as usize], 2);
+        assert_eq!(counts[0x01], 1);
+        assert_eq!(counts[OP_PUSHDATA1 as usize], 1);
+        assert_eq!(opcode_name(OP_CHECKSIG), "OP_CHECKSIG");
+        assert_eq!(opcode_name(0x5a), "OP_10");
+        assert_eq!(opcode_name(0xb3), "OP_NOP4");
+        assert_eq!(opcode_name(0xfe), "OP_UNKNOWN_0xfe");
+    }
+
+    #[test]
+    fn p2wpkh_spend_adds_no_witness_opcodes() {
+        let tx = |prevout_index, script_pubkey: Vec<u8>| Transaction {
+            version: 2,
+            inputs: tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [1; 32],
+                    index: prevout_index,
+                },
+                script_sig: vec![],
+                sequence: 0xffff_ffff,
+            }],
+            outputs: tx_outputs![TransactionOutput {
+                value: 1_000,
+                script_pubkey,
+            }],
+            lock_time: 0,
+        };
+        let block = Block {
+            header: BlockHeader {
+                version: 0x2000_0000,
+                prev_block_hash: [0; 32],
+                merkle_root: [0; 32],
+                timestamp: 1_600_000_000,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            },
+            transactions: vec![
+                tx(0xffff_ffff, vec![OP_1]),
+                tx(0, [&[OP_0, 20][..], &[0; 20]].concat()),
+            ]
+            .into_boxed_slice(),
+        };
+        // Signature and key bytes full of OP_CHECKSIG / OP_CHECKMULTISIG values
+        let pubkey = [&[0x02][..], &[OP_CHECKSIG; 32]].concat();
+        let p2wpkh: Witness = [vec![OP_CHECKMULTISIG; 72], pubkey].into_iter().collect();
+        let witnesses = vec![vec![Witness::default()], vec![p2wpkh]];
+
+        let mut analysis = ScriptStatsAnalysis::new(WINDOW_BLOCKS);
+        analysis.add_block(&block, &witnesses, 600_000);
+        let window = &analysis.windows[&600_000];
+        assert_eq!(window.inputs, 1);
+        assert_eq!(window.opcodes[OP_CHECKSIG as usize], 0);
+        assert_eq!(window.opcodes[OP_CHECKMULTISIG as usize], 0);
+        assert_eq!(window.script_types[&ScriptType::P2wpkh], 1);
+    }
+}
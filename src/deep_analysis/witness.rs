@@ -0,0 +1,426 @@
+//! Witness / script size distribution pass
+//!
+//! Per-era distributions of witness stack depth, witness element sizes, scriptSig length,
+//! witness script length (last element of a P2WSH / tapscript spend) and scriptPubKey length.
+//! Inputs at or above [`NEAR_LIMIT_FRACTION`] of a consensus (or standardness) limit are counted
+//! and sampled so stress/adversarial block generation can start from parameters real chain data
+//! actually reaches, via [`WitnessAnalysis::near_limit_params`].
+//!
+//! Eras follow [`chain_scan::block_era`](crate::chain_scan::block_era).
+
+use crate::chain_scan::block_era;
+use blvm_protocol::block::calculate_tx_id;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Consensus: max size of a single pushed stack element.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Consensus: max script size (legacy / v0 witness scripts).
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Consensus: max combined stack + altstack depth.
+pub const MAX_STACK_SIZE: usize = 1_000;
+/// Policy: max witness stack items for a standard P2WSH spend.
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+/// Policy: max standard P2WSH witness script size.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3_600;
+
+/// An item counts as "near limit" at or above this fraction of the limit.
+pub const NEAR_LIMIT_FRACTION: f64 = 0.9;
+
+/// Max near-limit samples kept per era.
+const NEAR_LIMIT_SAMPLE_CAP: usize = 32;
+
+/// Power-of-two bucketed histogram: bucket `i` holds values in `[2^(i-1), 2^i)`, bucket 0 holds 0.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Log2Histogram {
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub max: u64,
+    pub sum: u64,
+}
+
+impl Log2Histogram {
+    pub fn record(&mut self, value: u64) {
+        let idx = (u64::BITS - value.leading_zeros()) as usize;
+        if self.buckets.len() <= idx {
+            self.buckets.resize(idx + 1, 0);
+        }
+        self.buckets[idx] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    pub fn merge(&mut self, other: &Log2Histogram) {
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (a, b) in self.buckets.iter_mut().zip(&other.buckets) {
+            *a += b;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Upper bound of the bucket containing quantile `q` (0.0..=1.0), capped at `max`.
+    pub fn quantile_upper_bound(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let target = ((self.count as f64) * q.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0u64;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                let upper = if i == 0 { 0 } else { (1u64 << i) - 1 };
+                return upper.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+}
+
+/// Which limit an input came close to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum NearLimitKind {
+    ElementSize,
+    StackDepth,
+    StandardP2wshStackItems,
+    WitnessScriptSize,
+    StandardP2wshScriptSize,
+    ScriptSigSize,
+}
+
+/// One near-limit input (txid in display order).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearLimitSample {
+    pub height: u64,
+    pub txid: String,
+    pub input_index: usize,
+    pub kind: NearLimitKind,
+    pub observed: usize,
+    pub limit: usize,
+}
+
+/// Distributions for one era.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EraWitnessDistribution {
+    pub blocks: u64,
+    pub inputs: u64,
+    pub inputs_with_witness: u64,
+    pub witness_stack_items: Log2Histogram,
+    pub witness_element_size: Log2Histogram,
+    pub witness_script_size: Log2Histogram,
+    pub script_sig_size: Log2Histogram,
+    pub script_pubkey_size: Log2Histogram,
+    pub near_limit_counts: BTreeMap<NearLimitKind, u64>,
+    pub near_limit_samples: Vec<NearLimitSample>,
+}
+
+impl EraWitnessDistribution {
+    fn merge(&mut self, other: &EraWitnessDistribution) {
+        self.blocks += other.blocks;
+        self.inputs += other.inputs;
+        self.inputs_with_witness += other.inputs_with_witness;
+        self.witness_stack_items.merge(&other.witness_stack_items);
+        self.witness_element_size.merge(&other.witness_element_size);
+        self.witness_script_size.merge(&other.witness_script_size);
+        self.script_sig_size.merge(&other.script_sig_size);
+        self.script_pubkey_size.merge(&other.script_pubkey_size);
+        for (k, v) in &other.near_limit_counts {
+            *self.near_limit_counts.entry(*k).or_default() += v;
+        }
+        for s in &other.near_limit_samples {
+            if self.near_limit_samples.len() >= NEAR_LIMIT_SAMPLE_CAP {
+                break;
+            }
+            self.near_limit_samples.push(s.clone());
+        }
+    }
+
+    fn flag(&mut self, sample: NearLimitSample) {
+        *self.near_limit_counts.entry(sample.kind).or_default() += 1;
+        if self.near_limit_samples.len() < NEAR_LIMIT_SAMPLE_CAP {
+            self.near_limit_samples.push(sample);
+        }
+    }
+}
+
+/// Realistic upper parameters per dimension (p99 and observed max across all eras).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NearLimitParams {
+    pub witness_stack_items_p99: u64,
+    pub witness_stack_items_max: u64,
+    pub witness_element_size_p99: u64,
+    pub witness_element_size_max: u64,
+    pub witness_script_size_p99: u64,
+    pub witness_script_size_max: u64,
+    pub script_sig_size_p99: u64,
+    pub script_sig_size_max: u64,
+}
+
+/// Accumulated witness/script distributions keyed by era name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WitnessAnalysis {
+    pub by_era: BTreeMap<String, EraWitnessDistribution>,
+}
+
+fn is_near(observed: usize, limit: usize) -> bool {
+    observed as f64 >= limit as f64 * NEAR_LIMIT_FRACTION
+}
+
+/// BIP341 annex marker: first byte of a last witness element that is not part of the spend.
+const ANNEX_TAG: u8 = 0x50;
+
+/// Witness script of a P2WSH / tapscript spend: last element, or second-to-last when the last
+/// looks like a taproot control block (`33 + 32n` bytes, `0xc0/0xc1` leaf version). A trailing
+/// annex is dropped first; P2WPKH spends (`[sig, compressed pubkey]`) and key-path spends have
+/// no script and give `None`.
+pub(super) fn witness_script(witness: &Witness) -> Option<&[u8]> {
+    let mut n = witness.len();
+    if n >= 2 && witness[n - 1].first() == Some(&ANNEX_TAG) {
+        n -= 1;
+    }
+    if n < 2 {
+        return None;
+    }
+    let last = &witness[n - 1];
+    let looks_like_control =
+        last.len() >= 33 && (last.len() - 33) % 32 == 0 && (last[0] & 0xfe) == 0xc0;
+    if looks_like_control {
+        return Some(&witness[n - 2][..]);
+    }
+    if n == 2 && last.len() == 33 && matches!(last[0], 0x02 | 0x03) {
+        return None;
+    }
+    Some(&last[..])
+}
+
+impl WitnessAnalysis {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one block (`witnesses` indexed per tx, then per input — as returned by
+    /// `deserialize_block_with_witnesses`).
+    pub fn add_block(&mut self, block: &Block, witnesses: &[Vec<Witness>], height: u64) {
+        let era = self
+            .by_era
+            .entry(block_era(height).to_string())
+            .or_default();
+        era.blocks += 1;
+
+        for (tx_idx, tx) in block.transactions.iter().enumerate() {
+            for output in tx.outputs.iter() {
+                era.script_pubkey_size
+                    .record(output.script_pubkey.len() as u64);
+            }
+            if tx_idx == 0 {
+                continue;
+            }
+            let mut txid_cache: Option<String> = None;
+            let mut txid = || {
+                txid_cache
+                    .get_or_insert_with(|| {
+                        let mut id = calculate_tx_id(tx);
+                        id.reverse();
+                        hex::encode(id)
+                    })
+                    .clone()
+            };
+            let tx_wits = witnesses.get(tx_idx);
+
+            for (input_index, input) in tx.inputs.iter().enumerate() {
+                era.inputs += 1;
+                let sig_len = input.script_sig.len();
+                era.script_sig_size.record(sig_len as u64);
+                if is_near(sig_len, MAX_SCRIPT_SIZE) {
+                    era.flag(NearLimitSample {
+                        height,
+                        txid: txid(),
+                        input_index,
+                        kind: NearLimitKind::ScriptSigSize,
+                        observed: sig_len,
+                        limit: MAX_SCRIPT_SIZE,
+                    });
+                }
+
+                let Some(witness) = tx_wits.and_then(|w| w.get(input_index)) else {
+                    continue;
+                };
+                if witness.is_empty() {
+                    continue;
+                }
+                era.inputs_with_witness += 1;
+                let depth = witness.len();
+                era.witness_stack_items.record(depth as u64);
+
+                let mut checks: Vec<(NearLimitKind, usize, usize)> = Vec::new();
+                let max_elem = witness.iter().map(|e| e.len()).max().unwrap_or(0);
+                for element in witness.iter() {
+                    era.witness_element_size.record(element.len() as u64);
+                }
+                checks.push((
+                    NearLimitKind::ElementSize,
+                    max_elem,
+                    MAX_SCRIPT_ELEMENT_SIZE,
+                ));
+                checks.push((NearLimitKind::StackDepth, depth, MAX_STACK_SIZE));
+                checks.push((
+                    NearLimitKind::StandardP2wshStackItems,
+                    depth,
+                    MAX_STANDARD_P2WSH_STACK_ITEMS,
+                ));
+                if let Some(script) = witness_script(witness) {
+                    era.witness_script_size.record(script.len() as u64);
+                    checks.push((
+                        NearLimitKind::WitnessScriptSize,
+                        script.len(),
+                        MAX_SCRIPT_SIZE,
+                    ));
+                    checks.push((
+                        NearLimitKind::StandardP2wshScriptSize,
+                        script.len(),
+                        MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+                    ));
+                }
+
+                for (kind, observed, limit) in checks {
+                    if is_near(observed, limit) {
+                        era.flag(NearLimitSample {
+                            height,
+                            txid: txid(),
+                            input_index,
+                            kind,
+                            observed,
+                            limit,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Merge another partial analysis (e.g. from a parallel batch).
+    pub fn merge(&mut self, other: &WitnessAnalysis) {
+        for (era, dist) in &other.by_era {
+            self.by_era.entry(era.clone()).or_default().merge(dist);
+        }
+    }
+
+    /// Collapse all eras into generator-facing parameters.
+    pub fn near_limit_params(&self) -> NearLimitParams {
+        let mut all = EraWitnessDistribution::default();
+        for dist in self.by_era.values() {
+            all.merge(dist);
+        }
+        NearLimitParams {
+            witness_stack_items_p99: all.witness_stack_items.quantile_upper_bound(0.99),
+            witness_stack_items_max: all.witness_stack_items.max,
+            witness_element_size_p99: all.witness_element_size.quantile_upper_bound(0.99),
+            witness_element_size_max: all.witness_element_size.max,
+            witness_script_size_p99: all.witness_script_size.quantile_upper_bound(0.99),
+            witness_script_size_max: all.witness_script_size.max,
+            script_sig_size_p99: all.script_sig_size.quantile_upper_bound(0.99),
+            script_sig_size_max: all.script_sig_size.max,
+        }
+    }
+
+    /// Terminal summary, one block per era.
+    pub fn format_summary(&self) -> String {
+        let mut out = String::new();
+        for (era, d) in &self.by_era {
+            out.push_str(&format!(
+                "📊 {era}: {} blocks, {} inputs ({} with witness)\n",
+                d.blocks, d.inputs, d.inputs_with_witness
+            ));
+            for (name, h) in [
+                ("stack items", &d.witness_stack_items),
+                ("element size", &d.witness_element_size),
+                ("witness script", &d.witness_script_size),
+                ("scriptSig", &d.script_sig_size),
+                ("scriptPubKey", &d.script_pubkey_size),
+            ] {
+                out.push_str(&format!(
+                    "   {:<15} mean {:>8.1}  p99 ≤{:>6}  max {:>6}\n",
+                    name,
+                    h.mean(),
+                    h.quantile_upper_bound(0.99),
+                    h.max
+                ));
+            }
+            for (kind, n) in &d.near_limit_counts {
+                out.push_str(&format!("   ⚠️  near limit {kind:?}: {n}\n"));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log2_histogram_buckets_and_quantile() {
+        let mut h = Log2Histogram::default();
+        for v in [0u64, 1, 2, 3, 100, 520] {
+            h.record(v);
+        }
+        assert_eq!(h.count, 6);
+        assert_eq!(h.max, 520);
+        assert_eq!(h.buckets[0], 1);
+        assert_eq!(h.buckets[2], 2); // 2, 3
+        assert_eq!(h.quantile_upper_bound(0.5), 3);
+        assert_eq!(h.quantile_upper_bound(1.0), 520);
+    }
+
+    fn witness(items: &[&[u8]]) -> Witness {
+        items.iter().map(|i| i.to_vec()).collect()
+    }
+
+    #[test]
+    fn witness_script_skips_keys_and_annex() {
+        let sig = [0x30; 71];
+        let pubkey = [&[0x02][..], &[0x11; 32]].concat();
+        let script = [0x52, 0x21, 0xae];
+        let control = [&[0xc0][..], &[0x22; 32]].concat();
+        let annex = [ANNEX_TAG, 0x01];
+
+        // P2WPKH and taproot key-path spends carry no script
+        assert_eq!(witness_script(&witness(&[&sig, &pubkey])), None);
+        assert_eq!(witness_script(&witness(&[&sig[..64]])), None);
+        assert_eq!(witness_script(&witness(&[&sig[..64], &annex])), None);
+        // P2WSH: last element
+        assert_eq!(
+            witness_script(&witness(&[&[], &sig, &script])),
+            Some(&script[..])
+        );
+        // Tapscript, with and without an annex
+        assert_eq!(
+            witness_script(&witness(&[&sig[..64], &script, &control])),
+            Some(&script[..])
+        );
+        assert_eq!(
+            witness_script(&witness(&[&sig[..64], &script, &control, &annex])),
+            Some(&script[..])
+        );
+    }
+
+    #[test]
+    fn near_limit_threshold() {
+        assert!(is_near(468, MAX_SCRIPT_ELEMENT_SIZE));
+        assert!(!is_near(467, MAX_SCRIPT_ELEMENT_SIZE));
+    }
+}
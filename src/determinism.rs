@@ -0,0 +1,95 @@
+//! Reproducible parallel runs (`--deterministic`)
+//!
+//! Two differential runs over the same blocks normally differ in more than their verdicts: the
+//! worker count follows the machine's cores, chunks finish (and get logged) in whatever order
+//! the workers happen to reach them, timings land in every report, and a few choices are random
+//! (which auto-discovered node answers RPC, P2P nonces). In deterministic mode
+//! (`blvm-bench diff-run --deterministic`, or `BLVM_DETERMINISTIC=1`):
+//!
+//! - the worker count defaults to [`DETERMINISTIC_WORKERS`] instead of the core count, so
+//!   adaptive chunk plans are the same on every machine
+//! - finished chunks are reported in height order
+//! - random choices draw from [`rng`], seeded from `BLVM_SEED` (default [`DEFAULT_SEED`])
+//! - reports leave out wall-clock times, durations and memory figures: the differential report
+//!   ([`DifferentialReport::without_timings`](crate::report::DifferentialReport::without_timings)),
+//!   and in a run directory `report.json`, `summary.json` and the per-chunk results, so two runs
+//!   produce byte-identical reports to diff. Only the run's `run.json` bookkeeping (creation /
+//!   update times, invocation count) still differs
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Workers when deterministic and no count is given
+pub const DETERMINISTIC_WORKERS: usize = 4;
+/// Seed when `BLVM_SEED` is unset
+pub const DEFAULT_SEED: u64 = 0xb17c_0de5;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn deterministic mode on for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether [`enable`] was called or `BLVM_DETERMINISTIC` is set to `1` / `true`.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+        || std::env::var("BLVM_DETERMINISTIC")
+            .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// `BLVM_SEED` (decimal or `0x` hex), else [`DEFAULT_SEED`].
+pub fn seed() -> u64 {
+    std::env::var("BLVM_SEED")
+        .ok()
+        .and_then(|s| match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        })
+        .unwrap_or(DEFAULT_SEED)
+}
+
+/// Generator for the random choices of one `purpose` (e.g. `"node-discovery"`): seeded from
+/// [`seed`] and `purpose` when deterministic, so separate purposes don't share a stream;
+/// from OS entropy otherwise.
+pub fn rng(purpose: &str) -> StdRng {
+    if enabled() {
+        seeded_rng(seed(), purpose)
+    } else {
+        StdRng::from_entropy()
+    }
+}
+
+fn seeded_rng(seed: u64, purpose: &str) -> StdRng {
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(purpose.as_bytes())
+        .finalize();
+    StdRng::from_seed(digest.into())
+}
+
+/// `requested` workers, else [`DETERMINISTIC_WORKERS`] when deterministic or all cores.
+pub fn worker_count(requested: Option<usize>) -> usize {
+    match requested {
+        Some(n) => n.max(1),
+        None if enabled() => DETERMINISTIC_WORKERS,
+        None => num_cpus::get(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_streams_repeat_per_purpose() {
+        let draw = |seed, purpose| seeded_rng(seed, purpose).gen::<u64>();
+        assert_eq!(draw(1, "node-discovery"), draw(1, "node-discovery"));
+        assert_ne!(draw(1, "node-discovery"), draw(2, "node-discovery"));
+        assert_ne!(draw(1, "node-discovery"), draw(1, "p2p-nonce"));
+        assert_eq!(worker_count(Some(0)), 1);
+    }
+}
@@ -0,0 +1,333 @@
+//! Free-space watchdog for long runs
+//!
+//! Collection and the sort-merge pipeline write hundreds of GB over many hours; when the scratch
+//! disk fills, the run used to die on whichever write hit `ENOSPC` first, often in the middle of
+//! a chunk or a sorted run. A [`DiskWatchdog`] watches the volumes a run writes to (temp file,
+//! chunk scratch dir, archives, sort-merge data dir) and acts before that happens:
+//!
+//! - up front, [`DiskWatchdog::preflight`] prints each volume's free space next to what the run
+//!   is estimated to need, and warns about the ones that fall short
+//! - at safe points (between block files, sorted runs and stage checkpoints),
+//!   [`DiskWatchdog::checkpoint`] compares free space with `storage.low_space_bytes`
+//!
+//! Below the threshold, `storage.low_space_action = "pause"` waits, re-checking every
+//! [`DEFAULT_POLL`], until space is freed or the run is cancelled; `"stop"` (the default) ends
+//! the run through its cancellation path, which leaves the same resumable state as Ctrl-C (see
+//! [`crate::shutdown`]).
+//!
+//! Collection builds its own watchdog; the sort-merge CLI [`install`]s a process-wide one that
+//! [`checkpoint`] consults. Free space is only known on Unix; elsewhere nothing is checked.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::config::StorageTuning;
+
+/// Re-check interval while paused
+pub const DEFAULT_POLL: Duration = Duration::from_secs(30);
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+static GLOBAL: OnceLock<DiskWatchdog> = OnceLock::new();
+static STOP_REASON: OnceLock<String> = OnceLock::new();
+
+/// What a run does when a watched volume runs low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LowSpaceAction {
+    /// Wait for space to be freed
+    Pause,
+    /// Stop with resumable state
+    #[default]
+    Stop,
+}
+
+impl LowSpaceAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+impl std::str::FromStr for LowSpaceAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "pause" => Ok(Self::Pause),
+            "stop" => Ok(Self::Stop),
+            other => anyhow::bail!("Unknown low-space action {:?} (pause or stop)", other),
+        }
+    }
+}
+
+/// A watched directory whose free space dropped below the threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowVolume {
+    pub label: &'static str,
+    pub path: PathBuf,
+    pub free: u64,
+}
+
+impl std::fmt::Display for LowVolume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({:.1} GiB free)",
+            self.label,
+            self.path.display(),
+            self.free as f64 / GIB
+        )
+    }
+}
+
+/// Free-space checks over the directories one run writes to.
+#[derive(Debug, Clone)]
+pub struct DiskWatchdog {
+    /// A label is low once all of its directories are (see [`watch_any`](Self::watch_any))
+    volumes: Vec<(&'static str, Vec<PathBuf>)>,
+    low_space_bytes: u64,
+    action: LowSpaceAction,
+    poll: Duration,
+    cancel: CancellationToken,
+}
+
+impl DiskWatchdog {
+    /// Watchdog without volumes, acting with `action` below `low_space_bytes`.
+    pub fn new(low_space_bytes: u64, action: LowSpaceAction) -> Self {
+        Self {
+            volumes: Vec::new(),
+            low_space_bytes,
+            action,
+            poll: DEFAULT_POLL,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Threshold and action from `storage.low_space_bytes` / `storage.low_space_action`.
+    pub fn from_tuning(storage: &StorageTuning) -> Self {
+        Self::new(storage.low_space_bytes, storage.low_space_action)
+    }
+
+    /// Also watch `path` (which need not exist yet), shown as `label`.
+    pub fn watch(self, label: &'static str, path: impl Into<PathBuf>) -> Self {
+        self.watch_any(label, [path.into()])
+    }
+
+    /// Also watch `paths` as one volume, low only once none of them has room: for storage
+    /// tiers, where writes spill over to the next directory.
+    pub fn watch_any(
+        mut self,
+        label: &'static str,
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Self {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| {
+                !self
+                    .volumes
+                    .iter()
+                    .any(|(_, watched)| watched.contains(path))
+            })
+            .collect();
+        if !paths.is_empty() {
+            self.volumes.push((label, paths));
+        }
+        self
+    }
+
+    /// Stop pausing once `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// Watched volumes below the threshold.
+    pub fn low_volumes(&self) -> Vec<LowVolume> {
+        self.volumes
+            .iter()
+            .filter_map(|(label, paths)| {
+                let (path, free) = roomiest(paths)?;
+                (free < self.low_space_bytes).then_some(LowVolume {
+                    label: *label,
+                    path,
+                    free,
+                })
+            })
+            .collect()
+    }
+
+    /// Print free space per watched volume next to the bytes the run still needs there
+    /// (`required`, by label; estimates), and warn about volumes that can't hold it plus the
+    /// threshold. Returns whether all of them can.
+    pub fn preflight(&self, required: &[(&str, u64)]) -> bool {
+        info!(
+            "💽 Disk space ({} below {:.1} GiB free):",
+            self.action.as_str(),
+            self.low_space_bytes as f64 / GIB
+        );
+        let mut enough = true;
+        for (label, paths) in &self.volumes {
+            let needed: u64 = required
+                .iter()
+                .filter(|(l, _)| l == label)
+                .map(|(_, bytes)| bytes)
+                .sum();
+            let path = paths[0].display();
+            let free = paths
+                .iter()
+                .filter_map(|p| free_space(p))
+                .reduce(|a, b| a + b);
+            match free {
+                Some(free) if free < needed.saturating_add(self.low_space_bytes) => {
+                    enough = false;
+                    warn!(
+                        "   ⚠️  {} {}: {:.1} GiB free, ~{:.1} GiB still needed - will {} on \
+                         reaching the threshold",
+                        label,
+                        path,
+                        free as f64 / GIB,
+                        needed as f64 / GIB,
+                        self.action.as_str()
+                    );
+                }
+                Some(free) => info!(
+                    "   {} {}: {:.1} GiB free, ~{:.1} GiB needed",
+                    label,
+                    path,
+                    free as f64 / GIB,
+                    needed as f64 / GIB
+                ),
+                None => info!("   {} {}: free space unknown", label, path),
+            }
+        }
+        enough
+    }
+
+    /// Safe point: `Ok` while every volume is above the threshold. Below it, waits for space
+    /// ([`LowSpaceAction::Pause`]) or returns a [`Cancelled`] error
+    /// ([`LowSpaceAction::Stop`], or cancelled while paused).
+    pub fn checkpoint(&self) -> Result<()> {
+        let mut low = self.low_volumes();
+        if low.is_empty() {
+            return Ok(());
+        }
+        let list = |low: &[LowVolume]| {
+            low.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if self.action == LowSpaceAction::Stop {
+            return Err(stop(format!("low disk space on {}", list(&low))));
+        }
+        warn!(
+            "   ⏸️  Low disk space on {} - paused until {:.1} GiB are free (checking every {}s)",
+            list(&low),
+            self.low_space_bytes as f64 / GIB,
+            self.poll.as_secs()
+        );
+        while !low.is_empty() {
+            if self.cancel.is_cancelled() {
+                return Err(
+                    Cancelled(format!("paused on low disk space on {}", list(&low))).into(),
+                );
+            }
+            std::thread::sleep(self.poll);
+            low = self.low_volumes();
+        }
+        info!("   ▶️  Disk space freed - continuing");
+        Ok(())
+    }
+}
+
+fn stop(reason: String) -> anyhow::Error {
+    warn!(
+        "   🛑 {} - stopping; free some space and run again to resume",
+        reason
+    );
+    let _ = STOP_REASON.set(reason.clone());
+    Cancelled(reason).into()
+}
+
+/// The directory of `paths` with the most free space, and that space.
+fn roomiest(paths: &[PathBuf]) -> Option<(PathBuf, u64)> {
+    paths
+        .iter()
+        .filter_map(|path| Some((path.clone(), free_space(path)?)))
+        .max_by_key(|&(_, free)| free)
+}
+
+/// Free space of the filesystem `path` is (or will be) on: that of its nearest existing
+/// ancestor.
+fn free_space(path: &Path) -> Option<u64> {
+    path.ancestors()
+        .find(|p| p.exists())
+        .and_then(crate::platform::free_space)
+}
+
+/// Make `watchdog` the process-wide one used by [`checkpoint`]. Later calls are ignored.
+pub fn install(watchdog: DiskWatchdog) {
+    let _ = GLOBAL.set(watchdog);
+}
+
+/// [`DiskWatchdog::checkpoint`] of the [`install`]ed watchdog; `Ok` without one.
+pub fn checkpoint() -> Result<()> {
+    GLOBAL.get().map_or(Ok(()), DiskWatchdog::checkpoint)
+}
+
+/// Why a watchdog stopped the run, if one did.
+pub fn stop_reason() -> Option<&'static str> {
+    STOP_REASON.get().map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_or_pauses_below_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not/created/yet");
+        let roomy = DiskWatchdog::new(0, LowSpaceAction::Stop).watch("temp", &missing);
+        assert!(roomy.low_volumes().is_empty());
+        assert!(roomy.checkpoint().is_ok());
+        assert!(roomy.preflight(&[("temp", 0)]));
+
+        if crate::platform::free_space(dir.path()).is_none() {
+            return; // Free space unknown on this platform: nothing is ever low
+        }
+        let full = DiskWatchdog::new(u64::MAX, LowSpaceAction::Stop).watch("temp", &missing);
+        assert_eq!(full.low_volumes()[0].path, missing);
+        assert!(crate::cancel::is_cancelled(&full.checkpoint().unwrap_err()));
+        assert!(!full.preflight(&[]));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let paused = DiskWatchdog::new(u64::MAX, LowSpaceAction::Pause)
+            .watch("temp", dir.path())
+            .with_cancellation(cancel)
+            .with_poll_interval(Duration::from_millis(1));
+        assert!(crate::cancel::is_cancelled(
+            &paused.checkpoint().unwrap_err()
+        ));
+
+        assert_eq!(
+            "pause".parse::<LowSpaceAction>().unwrap(),
+            LowSpaceAction::Pause
+        );
+        assert!("wait".parse::<LowSpaceAction>().is_err());
+    }
+}
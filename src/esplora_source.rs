@@ -0,0 +1,415 @@
+//! Blocks from an Esplora REST API
+//!
+//! For contributors without a synced Core node: [`EsploraBlockSource`] fetches raw mainnet blocks
+//! from a public (or self-hosted) Esplora instance such as `https://blockstream.info/api` or
+//! `https://mempool.space/api`:
+//!
+//! - `GET /block-height/:height` for the hash, then `GET /block/:hash/raw` for the block, whose
+//!   header is checked against the hash
+//! - `GET /blocks/tip/height` for the tip
+//! - `GET /block/:hash/status` (`in_best_chain`) stands in for asking Core about a block
+//!
+//! Requests are spaced to at most `requests_per_sec` and retried with backoff on `429` / `5xx`
+//! (honouring `Retry-After`). Before the first block or tip is read, the API's genesis hash
+//! (`GET /block-height/0`) must match the configured network, so a mainnet URL cannot feed a
+//! testnet run. Blocks go through a [`SharedBlockCache`] in a directory per network, so each
+//! block is downloaded once and caches of different chains never mix.
+//!
+//! Public instances are rate limited, so this suits small differential and benchmark runs, not
+//! full-chain ones. Configure with `BLVM_ESPLORA_URL`, `BLVM_ESPLORA_RPS` and
+//! `BLVM_ESPLORA_CACHE_DIR` (default `<cache dir>/blvm-bench/esplora`); blocks are cached in its
+//! `<network>/` subdirectory.
+
+use anyhow::{Context, Result};
+use reqwest::{Client, StatusCode};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{debug, info, warn};
+
+use crate::block_file_reader::{Network, SharedBlockCache};
+use crate::block_source::BlockSource;
+use crate::wire::{display_hex, sha256d};
+
+/// Attempts per request before giving up
+const MAX_ATTEMPTS: u32 = 6;
+
+/// Where to fetch from and how politely.
+#[derive(Debug, Clone)]
+pub struct EsploraConfig {
+    /// API base without trailing slash, e.g. `https://blockstream.info/api`
+    pub base_url: String,
+    /// Chain the API must serve, checked against its genesis hash
+    pub network: Network,
+    /// Must be positive
+    pub requests_per_sec: f64,
+    pub timeout: Duration,
+    /// Block cache directory; `None` disables caching
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl EsploraConfig {
+    pub fn new(base_url: &str, network: Network) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            network,
+            requests_per_sec: 4.0,
+            timeout: Duration::from_secs(60),
+            cache_dir: default_cache_dir(network),
+        }
+    }
+
+    /// `BLVM_ESPLORA_URL` (+ `BLVM_ESPLORA_RPS`, `BLVM_ESPLORA_CACHE_DIR`) for `network`, if set.
+    pub fn from_env(network: Network) -> Option<Self> {
+        let url = std::env::var("BLVM_ESPLORA_URL")
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        let mut config = Self::new(&url, network);
+        if let Some(rps) = std::env::var("BLVM_ESPLORA_RPS")
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|rps| *rps > 0.0)
+        {
+            config.requests_per_sec = rps;
+        }
+        Some(config)
+    }
+}
+
+/// `<BLVM_ESPLORA_CACHE_DIR or <cache dir>/blvm-bench/esplora>/<network>`.
+fn default_cache_dir(network: Network) -> Option<PathBuf> {
+    let root = std::env::var_os("BLVM_ESPLORA_CACHE_DIR")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|d| d.join("blvm-bench").join("esplora")))?;
+    Some(root.join(network.datadir_subdir().unwrap_or("mainnet")))
+}
+
+/// Spaces requests `interval` apart across all callers.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_sec: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_sec),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep(*next - now).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+
+    /// Push every caller back by `delay` (server asked us to slow down).
+    async fn back_off(&self, delay: Duration) {
+        let mut next = self.next.lock().await;
+        *next = (*next).max(Instant::now() + delay);
+    }
+}
+
+/// Block source backed by an Esplora API.
+pub struct EsploraBlockSource {
+    config: EsploraConfig,
+    client: Client,
+    limiter: RateLimiter,
+    cache: Option<SharedBlockCache>,
+    requests: AtomicU64,
+    /// Set once the API's genesis hash matched `config.network`
+    network_checked: OnceCell<()>,
+}
+
+impl EsploraBlockSource {
+    pub fn new(config: EsploraConfig) -> Result<Self> {
+        anyhow::ensure!(
+            config.requests_per_sec.is_finite() && config.requests_per_sec > 0.0,
+            "Esplora requests_per_sec must be positive, got {}",
+            config.requests_per_sec
+        );
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .user_agent(concat!("blvm-bench/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("Failed to build HTTP client")?;
+        let cache = config
+            .cache_dir
+            .as_ref()
+            .map(SharedBlockCache::new)
+            .transpose()?;
+        Ok(Self {
+            limiter: RateLimiter::new(config.requests_per_sec),
+            config,
+            client,
+            cache,
+            requests: AtomicU64::new(0),
+            network_checked: OnceCell::new(),
+        })
+    }
+
+    /// From [`EsploraConfig::from_env`], if `BLVM_ESPLORA_URL` is set.
+    pub fn from_env(network: Network) -> Result<Option<Self>> {
+        EsploraConfig::from_env(network).map(Self::new).transpose()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.config.base_url
+    }
+
+    /// HTTP requests sent so far (retries included).
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// `GET base_url + path`, rate limited, retrying throttling and server errors. `Ok(None)` on
+    /// 404.
+    async fn get(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let url = format!("{}{}", self.config.base_url, path);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.limiter.wait().await;
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let retry_after = match self.client.get(&url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    let body = response
+                        .bytes()
+                        .await
+                        .with_context(|| format!("Failed to read {}", url))?;
+                    return Ok(Some(body.to_vec()));
+                }
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => return Ok(None),
+                Ok(response)
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || response.status().is_server_error() =>
+                {
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.trim().parse::<u64>().ok())
+                        .map(Duration::from_secs);
+                    if attempt >= MAX_ATTEMPTS {
+                        anyhow::bail!("GET {} failed: {}", url, response.status());
+                    }
+                    debug!("GET {} returned {}, retrying", url, response.status());
+                    retry_after
+                }
+                Ok(response) => anyhow::bail!("GET {} failed: {}", url, response.status()),
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    debug!("GET {} failed ({}), retrying", url, e);
+                    None
+                }
+                Err(e) => return Err(e).with_context(|| format!("GET {} failed", url)),
+            };
+            let delay = retry_after.unwrap_or(Duration::from_millis(500 << attempt));
+            if attempt == 2 {
+                warn!(
+                    "⚠️  Esplora is throttling; waiting {:?} between retries",
+                    delay
+                );
+            }
+            self.limiter.back_off(delay).await;
+        }
+    }
+
+    async fn get_text(&self, path: &str) -> Result<Option<String>> {
+        self.get(path)
+            .await?
+            .map(|body| String::from_utf8(body).map(|s| s.trim().to_string()))
+            .transpose()
+            .with_context(|| format!("Non-UTF-8 response from {}", path))
+    }
+
+    /// Block hash at `height` (display hex).
+    pub async fn block_hash(&self, height: u64) -> Result<String> {
+        self.get_text(&format!("/block-height/{}", height))
+            .await?
+            .with_context(|| format!("Esplora has no block at height {}", height))
+    }
+
+    /// Raw block `hash`, checked against its header.
+    pub async fn raw_block(&self, hash: &str) -> Result<Vec<u8>> {
+        let block = self
+            .get(&format!("/block/{}/raw", hash))
+            .await?
+            .with_context(|| format!("Esplora has no block {}", hash))?;
+        anyhow::ensure!(block.len() >= 80, "Block {} is {} bytes", hash, block.len());
+        anyhow::ensure!(
+            display_hex(&sha256d(&block[..80])) == hash,
+            "Esplora returned block {} for {}",
+            hex::encode(header_hash),
+            hash
+        );
+        Ok(block)
+    }
+
+    /// Fail unless the API's block 0 is `config.network`'s genesis; asked once.
+    async fn ensure_network(&self) -> Result<()> {
+        self.network_checked
+            .get_or_try_init(|| async {
+                let genesis = self.block_hash(0).await?;
+                let expected = self.config.network.genesis_hash();
+                anyhow::ensure!(
+                    genesis == expected,
+                    "Esplora at {} serves genesis {}, not {:?} ({})",
+                    self.config.base_url,
+                    genesis,
+                    self.config.network,
+                    expected
+                );
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    async fn fetch(&self, height: u64) -> Result<Vec<u8>> {
+        let hash = self.block_hash(height).await?;
+        debug!("Downloading block {} ({}) from Esplora", height, hash);
+        self.raw_block(&hash).await
+    }
+}
+
+impl BlockSource for EsploraBlockSource {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        self.ensure_network().await?;
+        match &self.cache {
+            Some(cache) => cache.get_or_fetch_with(height, || self.fetch(height)).await,
+            None => self.fetch(height).await,
+        }
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        self.ensure_network().await?;
+        let tip = self
+            .get_text("/blocks/tip/height")
+            .await?
+            .context("Esplora has no tip")?;
+        Ok(Some(tip.parse().with_context(|| {
+            format!("Invalid tip height {:?}", tip)
+        })?))
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        // Esplora's index follows its own node's best chain
+        match self.get(&format!("/block/{}/status", block_hash)).await {
+            Ok(Some(body)) => serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|status| status["in_best_chain"].as_bool()),
+            Ok(None) => Some(false),
+            Err(e) => {
+                info!("Esplora status for {} unavailable: {:#}", block_hash, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Minimal Esplora: `genesis` at height 0, one block at height 1, throttles the first
+    /// raw-block request.
+    async fn serve(
+        listener: tokio::net::TcpListener,
+        genesis: &'static str,
+        block: Vec<u8>,
+        hash: String,
+    ) {
+        let mut throttled = false;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let n = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+            let (status, extra, body) = if path == "/block-height/0" {
+                ("200 OK", "", genesis.as_bytes().to_vec())
+            } else if path == "/block-height/1" {
+                ("200 OK", "", hash.clone().into_bytes())
+            } else if path == format!("/block/{}/raw", hash) && !throttled {
+                throttled = true;
+                ("429 Too Many Requests", "Retry-After: 0\r\n", Vec::new())
+            } else if path == format!("/block/{}/raw", hash) {
+                ("200 OK", "", block.clone())
+            } else if path == "/blocks/tip/height" {
+                ("200 OK", "", b"1".to_vec())
+            } else if path == format!("/block/{}/status", hash) {
+                ("200 OK", "", br#"{"in_best_chain":true}"#.to_vec())
+            } else {
+                ("404 Not Found", "", Vec::new())
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                extra,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            stream.shutdown().await.ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_and_caches_blocks() {
+        let block: Vec<u8> = (0..120u8).collect();
+        let hash = display_hex(&sha256d(&block[..80]));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let genesis = Network::Mainnet.genesis_hash();
+        tokio::spawn(serve(listener, genesis, block.clone(), hash.clone()));
+
+        let cache = tempfile::tempdir().unwrap();
+        let mut config = EsploraConfig::new(&url, Network::Mainnet);
+        config.requests_per_sec = 1000.0;
+        config.cache_dir = Some(cache.path().to_path_buf());
+        let source = EsploraBlockSource::new(config).unwrap();
+
+        // Genesis check, hash lookup, throttled raw block, retried raw block
+        assert_eq!(source.get_block(1).await.unwrap(), block);
+        assert_eq!(source.requests(), 4);
+        assert_eq!(source.get_block(1).await.unwrap(), block);
+        assert_eq!(source.requests(), 4);
+
+        assert!(source.get_block(2).await.is_err());
+        assert_eq!(source.get_tip_height().await.unwrap(), Some(1));
+        assert_eq!(source.core_has_block(&hash).await, Some(true));
+        assert_eq!(source.core_has_block(&"11".repeat(32)).await, Some(false));
+    }
+
+    #[tokio::test]
+    async fn refuses_another_networks_api_and_bad_rates() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let genesis = Network::Mainnet.genesis_hash();
+        tokio::spawn(serve(listener, genesis, Vec::new(), "00".repeat(32)));
+
+        let cache = tempfile::tempdir().unwrap();
+        let mut config = EsploraConfig::new(&url, Network::Testnet4);
+        config.requests_per_sec = 1000.0;
+        config.cache_dir = Some(cache.path().to_path_buf());
+        let source = EsploraBlockSource::new(config.clone()).unwrap();
+        let err = source.get_block(1).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("Testnet4"), "{:#}", err);
+        assert!(source.get_tip_height().await.is_err());
+        assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 0);
+
+        config.requests_per_sec = 0.0;
+        assert!(EsploraBlockSource::new(config).is_err());
+        assert!(EsploraConfig::new(&url, Network::Signet)
+            .cache_dir
+            .is_none_or(|dir| dir.ends_with("signet")));
+    }
+}
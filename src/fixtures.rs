@@ -0,0 +1,651 @@
+//! Content-addressed library of named mainnet blocks and transactions for benchmarks
+//!
+//! [`FIXTURES`] names the blocks worth benchmarking on their own: genesis, the BIP30 duplicate
+//! coinbases, the soft-fork activation blocks, the July 2015 fork, the 2015 one-megabyte
+//! transaction and a large taproot block, plus one typical block per era / script mix for the
+//! curated block benchmarks. Each entry has a height and, where known, its hash.
+//!
+//! A [`FixtureStore`] keeps each fixture as `<hash>.bin` (block hash or txid, display hex) in the
+//! fixture directory, so renaming an entry never invalidates a download. Missing fixtures are
+//! fetched on demand from any [`BlockSource`]. A transaction is cut out of its block. Every
+//! fixture is verified before it is stored and again when it is loaded: the header must hash to
+//! the expected block hash and the transactions to its merkle root. A transaction must hash to
+//! its txid. Entries without a hash are pinned to what the first fetch returned, in
+//! `fixtures.lock` next to the objects.
+//!
+//! Benchmarks ask for fixtures by name with [`load`], which skips (with a warning) what was never
+//! fetched. `blvm-bench fixtures fetch` downloads them from the configured block source. Blocks
+//! that are connected in a benchmark also need the outputs they spend: `fetch_bench_fixtures`
+//! stores those from Core as `<hash>.prevouts.json`, read back with [`load_prevouts`].
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::block_source::BlockSource;
+use crate::wire::{display_hex, merkle_root, read_compact_size, sha256d};
+
+/// Pins for entries without a hash in [`FIXTURES`]
+pub const LOCK_FILE: &str = "fixtures.lock";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureKind {
+    Block,
+    /// A transaction of the block at the fixture's height
+    Transaction,
+}
+
+/// One named fixture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub kind: FixtureKind,
+    /// Height of the block (of the containing block for transactions)
+    pub height: u64,
+    /// Block hash or txid (display hex); `None` pins the first fetch
+    pub hash: Option<&'static str>,
+    pub description: &'static str,
+}
+
+const fn block(
+    name: &'static str,
+    height: u64,
+    hash: Option<&'static str>,
+    description: &'static str,
+) -> Fixture {
+    Fixture {
+        name,
+        kind: FixtureKind::Block,
+        height,
+        hash,
+        description,
+    }
+}
+
+const fn transaction(
+    name: &'static str,
+    height: u64,
+    txid: &'static str,
+    description: &'static str,
+) -> Fixture {
+    Fixture {
+        name,
+        kind: FixtureKind::Transaction,
+        height,
+        hash: Some(txid),
+        description,
+    }
+}
+
+/// The mainnet fixture manifest.
+pub const FIXTURES: &[Fixture] = &[
+    block(
+        "genesis",
+        0,
+        Some("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"),
+        "genesis block; its coinbase is unspendable",
+    ),
+    block(
+        "empty",
+        1,
+        Some("00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"),
+        "coinbase only",
+    ),
+    block(
+        "first_payment_block",
+        170,
+        Some("00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee"),
+        "first block with a transaction besides the coinbase",
+    ),
+    transaction(
+        "first_payment",
+        170,
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        "Satoshi to Hal Finney, 10 BTC, P2PK spend",
+    ),
+    block(
+        "bip30_duplicate_91842",
+        91_842,
+        Some("00000000000a4d0a398161ffc163c503763b1f4360639393e0e4c8e300e0caec"),
+        "coinbase duplicating the one of block 91812 (BIP30 exception)",
+    ),
+    transaction(
+        "bip30_duplicate_coinbase",
+        91_842,
+        "e3bf3d07d4b0375638d5f1db5255fe07ba2c4cb067cd81b84ee974b6585fb468",
+        "the duplicated coinbase transaction, also in block 91812",
+    ),
+    block(
+        "bip30_duplicate_91880",
+        91_880,
+        Some("00000000000743f190a18c5577a3c2d2a1f610ae9601ac046a38084ccb7cd721"),
+        "coinbase duplicating the one of block 91722 (BIP30 exception)",
+    ),
+    block(
+        "bip34_activation",
+        227_931,
+        Some("000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8"),
+        "BIP34 (height in coinbase) activation",
+    ),
+    block(
+        "legacy_2015",
+        360_000,
+        None,
+        "mid-2015, P2PKH / P2SH multisig",
+    ),
+    block(
+        "bip66_activation",
+        363_725,
+        Some("00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931"),
+        "BIP66 (strict DER) activation",
+    ),
+    block(
+        "july_2015_fork",
+        363_731,
+        None,
+        "valid block at the height of the July 2015 invalid-DER fork",
+    ),
+    block(
+        "megatransaction_2015",
+        364_292,
+        None,
+        "July 2015 block filled by one 1 MB transaction with 5,569 inputs (quadratic sighash)",
+    ),
+    block(
+        "bip65_activation",
+        388_381,
+        Some("000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0"),
+        "BIP65 (CHECKLOCKTIMEVERIFY) activation",
+    ),
+    block(
+        "csv_activation",
+        419_328,
+        Some("000000000000000004a1b34462cb8aeebd5799177f7a29cf28f2d1961716b5b5"),
+        "BIP68/112/113 (CSV) activation",
+    ),
+    block(
+        "segwit_activation",
+        481_824,
+        Some("0000000000000000001c8018d9cb3b742ef25114f27563e3fc4a1902167f9893"),
+        "segwit activation",
+    ),
+    block(
+        "segwit_heavy",
+        600_000,
+        None,
+        "late 2019, mostly P2WPKH / P2SH-P2WPKH spends",
+    ),
+    block(
+        "taproot_activation",
+        709_632,
+        Some("0000000000000000000687bca986194dc2c1f949318629b44bb54ec0a94d8244"),
+        "taproot activation",
+    ),
+    block(
+        "taproot_large",
+        774_628,
+        None,
+        "~4 MB block holding a single taproot inscription",
+    ),
+    block(
+        "taproot_heavy",
+        800_000,
+        None,
+        "mid-2023, many P2TR key-path and inscription spends",
+    ),
+];
+
+/// Previous output spent by one input of a fixture block.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FixturePrevout {
+    /// Satoshis
+    pub value: i64,
+    /// Hex
+    pub script_pubkey: String,
+    pub height: u64,
+    pub coinbase: bool,
+}
+
+/// Manifest entry `name`.
+pub fn fixture(name: &str) -> Option<&'static Fixture> {
+    FIXTURES.iter().find(|fixture| fixture.name == name)
+}
+
+/// Fixture directory: `BLVM_BENCH_FIXTURES`, else `benches/fixtures/blocks` in this crate.
+pub fn fixtures_dir() -> PathBuf {
+    std::env::var_os("BLVM_BENCH_FIXTURES")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("benches")
+                .join("fixtures")
+                .join("blocks")
+        })
+}
+
+/// Verified fixture files in one directory.
+#[derive(Debug, Clone)]
+pub struct FixtureStore {
+    dir: PathBuf,
+}
+
+impl FixtureStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Store in [`fixtures_dir`].
+    pub fn from_env() -> Self {
+        Self::new(fixtures_dir())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// `<hash>.bin`
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", hash))
+    }
+
+    /// `<hash>.prevouts.json`
+    pub fn prevouts_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{}.prevouts.json", hash))
+    }
+
+    /// Hashes pinned in [`LOCK_FILE`], by fixture name.
+    pub fn pins(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.dir.join(LOCK_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (name, hash) = line
+                    .split_once(char::is_whitespace)
+                    .with_context(|| format!("Malformed line in {}: {}", path.display(), line))?;
+                Ok((name.to_string(), hash.trim().to_string()))
+            })
+            .collect()
+    }
+
+    fn pin(&self, fixture: &Fixture, hash: &str) -> Result<()> {
+        let mut pins = self.pins()?;
+        pins.insert(fixture.name.to_string(), hash.to_string());
+        let mut text = String::from("# fixture hash (pinned on first fetch)\n");
+        for (name, hash) in &pins {
+            text.push_str(&format!("{} {}\n", name, hash));
+        }
+        write_atomic(&self.dir.join(LOCK_FILE), text.as_bytes())
+    }
+
+    /// Hash `fixture` must have: from the manifest, else pinned; `None` before the first fetch.
+    pub fn expected_hash(&self, fixture: &Fixture) -> Result<Option<String>> {
+        match fixture.hash {
+            Some(hash) => Ok(Some(hash.to_string())),
+            None => Ok(self.pins()?.remove(fixture.name)),
+        }
+    }
+
+    /// Verified bytes of `fixture`; `Ok(None)` if it was never fetched.
+    pub fn load(&self, fixture: &Fixture) -> Result<Option<Vec<u8>>> {
+        let Some(hash) = self.expected_hash(fixture)? else {
+            return Ok(None);
+        };
+        let path = self.object_path(&hash);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        verify(fixture.kind, &bytes, &hash)
+            .with_context(|| format!("Fixture {} ({}) is corrupt", fixture.name, path.display()))?;
+        Ok(Some(bytes))
+    }
+
+    /// Prevouts stored for block `fixture`, one per non-coinbase input in block order; `Ok(None)`
+    /// if they were never fetched.
+    pub fn load_prevouts(&self, fixture: &Fixture) -> Result<Option<Vec<FixturePrevout>>> {
+        let Some(hash) = self.expected_hash(fixture)? else {
+            return Ok(None);
+        };
+        let path = self.prevouts_path(&hash);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// Store the prevouts of block `fixture`, which must have been stored (or pinned) first.
+    pub fn save_prevouts(&self, fixture: &Fixture, prevouts: &[FixturePrevout]) -> Result<()> {
+        let hash = self
+            .expected_hash(fixture)?
+            .with_context(|| format!("Fixture {} has not been fetched", fixture.name))?;
+        crate::utils::write_json_atomic(&self.prevouts_path(&hash), prevouts)
+    }
+
+    /// Fetch `fixture` from `source`, verify it and store it.
+    pub async fn fetch<S: BlockSource>(&self, fixture: &Fixture, source: &S) -> Result<Vec<u8>> {
+        let block = source
+            .get_block(fixture.height)
+            .await
+            .with_context(|| format!("Failed to fetch block {}", fixture.height))?;
+        self.add(fixture, block)
+    }
+
+    /// Verify `block` (the block at `fixture`'s height) and store `fixture` from it.
+    pub fn add(&self, fixture: &Fixture, block: Vec<u8>) -> Result<Vec<u8>> {
+        let expected = self.expected_hash(fixture)?;
+        let block_hash = verify_block(&block, None)
+            .with_context(|| format!("Block {} from the source is invalid", fixture.height))?;
+        let (bytes, hash) = match fixture.kind {
+            FixtureKind::Block => (block, block_hash),
+            FixtureKind::Transaction => {
+                let txid = expected
+                    .as_deref()
+                    .context("Transaction fixtures need a txid in the manifest")?;
+                let tx = split_transactions(&block)?
+                    .into_iter()
+                    .find(|tx| display_hex(&tx_hash(tx).unwrap_or_default()) == txid)
+                    .with_context(|| {
+                        format!("Block {} has no transaction {}", fixture.height, txid)
+                    })?;
+                (tx.to_vec(), txid.to_string())
+            }
+        };
+        if let Some(expected) = &expected {
+            anyhow::ensure!(
+                &hash == expected,
+                "Fixture {} should be {} but the source returned {} at height {}",
+                fixture.name,
+                expected,
+                hash,
+                fixture.height
+            );
+        }
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        write_atomic(&self.object_path(&hash), &bytes)?;
+        if expected.is_none() {
+            self.pin(fixture, &hash)?;
+        }
+        Ok(bytes)
+    }
+
+    /// [`Self::load`], else [`Self::fetch`].
+    pub async fn get<S: BlockSource>(&self, fixture: &Fixture, source: &S) -> Result<Vec<u8>> {
+        match self.load(fixture)? {
+            Some(bytes) => Ok(bytes),
+            None => self.fetch(fixture, source).await,
+        }
+    }
+}
+
+/// Fixture `name` from [`FixtureStore::from_env`], for benchmarks; `None` (with a warning) if it
+/// was never fetched.
+pub fn load(name: &str) -> Result<Option<Vec<u8>>> {
+    let fixture = fixture(name).with_context(|| format!("No fixture named {}", name))?;
+    let store = FixtureStore::from_env();
+    let bytes = store.load(fixture)?;
+    if bytes.is_none() {
+        eprintln!(
+            "⚠️  Fixture {} (height {}) not in {} - run `blvm-bench fixtures fetch`",
+            name,
+            fixture.height,
+            store.dir().display()
+        );
+    }
+    Ok(bytes)
+}
+
+/// Prevouts of block fixture `name` from [`FixtureStore::from_env`], for benchmarks; `None` (with
+/// a warning) if they were never fetched.
+pub fn load_prevouts(name: &str) -> Result<Option<Vec<FixturePrevout>>> {
+    let fixture = fixture(name).with_context(|| format!("No fixture named {}", name))?;
+    let store = FixtureStore::from_env();
+    let prevouts = store.load_prevouts(fixture)?;
+    if prevouts.is_none() {
+        eprintln!(
+            "⚠️  Prevouts of fixture {} (height {}) not in {} - run fetch_bench_fixtures",
+            name,
+            fixture.height,
+            store.dir().display()
+        );
+    }
+    Ok(prevouts)
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(bytes)?;
+    temp.persist(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Advance `pos` past `len` bytes.
+fn skip(data: &[u8], pos: &mut usize, len: u64) -> Result<()> {
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| pos.checked_add(len))
+        .filter(|&end| end <= data.len())
+        .context("Truncated transaction")?;
+    *pos = end;
+    Ok(())
+}
+
+/// Length of the transaction at the start of `data` and its txid (witness excluded).
+fn parse_transaction(data: &[u8]) -> Result<(usize, [u8; 32])> {
+    let mut pos = 0;
+    skip(data, &mut pos, 4)?;
+    let segwit = data.get(4) == Some(&0) && data.get(5).is_some_and(|&flag| flag != 0);
+    if segwit {
+        pos += 2;
+    }
+    let body_start = pos;
+    let inputs = read_compact_size(data, &mut pos)?;
+    for _ in 0..inputs {
+        skip(data, &mut pos, 36)?;
+        let script_len = read_compact_size(data, &mut pos)?;
+        skip(data, &mut pos, script_len + 4)?;
+    }
+    let outputs = read_compact_size(data, &mut pos)?;
+    for _ in 0..outputs {
+        skip(data, &mut pos, 8)?;
+        let script_len = read_compact_size(data, &mut pos)?;
+        skip(data, &mut pos, script_len)?;
+    }
+    let body_end = pos;
+    if segwit {
+        for _ in 0..inputs {
+            let items = read_compact_size(data, &mut pos)?;
+            for _ in 0..items {
+                let len = read_compact_size(data, &mut pos)?;
+                skip(data, &mut pos, len)?;
+            }
+        }
+    }
+    let lock_time = pos;
+    skip(data, &mut pos, 4)?;
+    let txid = Sha256::new()
+        .chain_update(&data[..4])
+        .chain_update(&data[body_start..body_end])
+        .chain_update(&data[lock_time..pos])
+        .finalize();
+    Ok((pos, Sha256::digest(txid).into()))
+}
+
+/// Txid (internal order) of one serialized transaction, which must be all of `tx`.
+pub fn tx_hash(tx: &[u8]) -> Result<[u8; 32]> {
+    let (len, txid) = parse_transaction(tx)?;
+    anyhow::ensure!(
+        len == tx.len(),
+        "{} trailing bytes after the transaction",
+        tx.len() - len
+    );
+    Ok(txid)
+}
+
+/// The serialized transactions of `block`.
+pub fn split_transactions(block: &[u8]) -> Result<Vec<&[u8]>> {
+    anyhow::ensure!(block.len() > 80, "Block is only {} bytes", block.len());
+    let mut pos = 80;
+    let count = read_compact_size(block, &mut pos)?;
+    let mut txs = Vec::with_capacity(count.min(100_000) as usize);
+    for index in 0..count {
+        let (len, _) = parse_transaction(&block[pos..])
+            .with_context(|| format!("Transaction {} does not parse", index))?;
+        txs.push(&block[pos..pos + len]);
+        pos += len;
+    }
+    anyhow::ensure!(
+        pos == block.len(),
+        "{} trailing bytes after the last transaction",
+        block.len() - pos
+    );
+    Ok(txs)
+}
+
+/// Check that the transactions of `block` match its merkle root and, if given, that the header
+/// hashes to `expected` (display hex). Returns the block hash (display hex).
+pub fn verify_block(block: &[u8], expected: Option<&str>) -> Result<String> {
+    let txs = split_transactions(block)?;
+    let hash = display_hex(&sha256d(&block[..80]));
+    if let Some(expected) = expected {
+        anyhow::ensure!(
+            hash == expected,
+            "Block hashes to {}, expected {}",
+            hash,
+            expected
+        );
+    }
+    let txids = txs
+        .iter()
+        .map(|tx| tx_hash(tx))
+        .collect::<Result<Vec<_>>>()?;
+    anyhow::ensure!(
+        merkle_root(&txids)[..] == block[36..68],
+        "Transactions of block {} do not match its merkle root",
+        hash
+    );
+    Ok(hash)
+}
+
+/// Check a fixture's bytes against its hash (display hex).
+pub fn verify(kind: FixtureKind, bytes: &[u8], hash: &str) -> Result<()> {
+    match kind {
+        FixtureKind::Block => verify_block(bytes, Some(hash)).map(drop),
+        FixtureKind::Transaction => {
+            let txid = display_hex(&tx_hash(bytes)?);
+            anyhow::ensure!(
+                txid == hash,
+                "Transaction hashes to {}, expected {}",
+                txid,
+                hash
+            );
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_core::MockCore;
+
+    /// Version 2, one input, one output; with a one-item witness if `segwit`.
+    fn tx(tag: u8, segwit: bool) -> Vec<u8> {
+        let mut tx = vec![2, 0, 0, 0];
+        if segwit {
+            tx.extend([0, 1]);
+        }
+        tx.push(1);
+        tx.extend([tag; 32]);
+        tx.extend([0, 0, 0, 0]);
+        tx.extend([1, 0x51]);
+        tx.extend([0xff; 4]);
+        tx.push(1);
+        tx.extend(5000u64.to_le_bytes());
+        tx.extend([2, 0x51, 0x87]);
+        if segwit {
+            tx.extend([1, 3, 0xaa, 0xbb, 0xcc]);
+        }
+        tx.extend([0; 4]);
+        tx
+    }
+
+    fn block_of(txs: &[Vec<u8>]) -> Vec<u8> {
+        let txids: Vec<_> = txs.iter().map(|tx| tx_hash(tx).unwrap()).collect();
+        let mut block = vec![0u8; 80];
+        block[36..68].copy_from_slice(&merkle_root(&txids));
+        block.push(txs.len() as u8);
+        for tx in txs {
+            block.extend(tx);
+        }
+        block
+    }
+
+    #[test]
+    fn fetches_verifies_and_pins_fixtures() {
+        let legacy = tx(1, false);
+        let segwit = tx(2, true);
+        // The witness is not part of the txid
+        let mut stripped = segwit.clone();
+        stripped.drain(4..6);
+        stripped.drain(stripped.len() - 9..stripped.len() - 4);
+        assert_eq!(tx_hash(&segwit).unwrap(), tx_hash(&stripped).unwrap());
+
+        let blocks = vec![
+            block_of(std::slice::from_ref(&legacy)),
+            block_of(&[legacy, segwit.clone()]),
+        ];
+        let block_hash = verify_block(&blocks[1], None).unwrap();
+        let txid = display_hex(&tx_hash(&segwit).unwrap());
+        let mut tampered = blocks[1].clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(verify_block(&tampered, Some(&block_hash)).is_err());
+
+        let source = MockCore::new(blocks.clone());
+        let dir = tempfile::tempdir().unwrap();
+        let store = FixtureStore::new(dir.path());
+        let unpinned = block("tip", 1, None, "");
+        let wrong = block("wrong", 0, Some(block_hash.clone().leak()), "");
+        let spend = transaction("spend", 1, txid.clone().leak(), "");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            assert_eq!(store.load(&unpinned).unwrap(), None);
+            assert_eq!(store.get(&unpinned, &source).await.unwrap(), blocks[1]);
+            assert_eq!(store.get(&spend, &source).await.unwrap(), segwit);
+            assert!(store.fetch(&wrong, &source).await.is_err());
+        });
+        assert_eq!(store.pins().unwrap()["tip"], block_hash);
+        let prevouts = vec![FixturePrevout {
+            value: 5000,
+            script_pubkey: "51".to_string(),
+            height: 0,
+            coinbase: true,
+        }];
+        store.save_prevouts(&unpinned, &prevouts).unwrap();
+        assert_eq!(store.load_prevouts(&unpinned).unwrap(), Some(prevouts));
+        assert!(store
+            .save_prevouts(&block("never", 1, None, ""), &[])
+            .is_err());
+        assert_eq!(store.load(&unpinned).unwrap().unwrap(), blocks[1]);
+        assert!(store.object_path(&txid).exists());
+
+        std::fs::write(store.object_path(&block_hash), &tampered).unwrap();
+        assert!(store.load(&unpinned).is_err());
+        assert!(FIXTURES.iter().all(|f| fixture(f.name) == Some(f)));
+    }
+}
@@ -0,0 +1,408 @@
+//! Checks behind the cargo-fuzz targets, and their corpora
+//!
+//! `fuzz/` has libFuzzer targets for the framing helpers ([`crate::block_framing`]) and, with its
+//! `differential` feature, for BLVM's parsers and interpreter. Each of those runs one check from
+//! here, so the checks can use crate internals and be exercised by the unit tests:
+//!
+//! - `block_deserialize` ([`check_block`]): a block BLVM deserializes must split into the same
+//!   transactions, with the same txids, under the bench's own parser ([`crate::fixtures`])
+//! - `script_execute` ([`check_script`]): BLVM's interpreter and `libbitcoinconsensus` must agree
+//!   on a scriptSig / scriptPubKey / witness under the script differential's flags
+//! - `compact_size` ([`check_compact_size`]): BLVM's `decode_varint` and the bench's reader must
+//!   agree on the value and width of every canonical CompactSize, and BLVM must reject
+//!   non-canonical ones as Core does
+//!
+//! [`CorpusWriter`] seeds `fuzz/corpus/<target>/` (cargo-fuzz's layout, one file per input named
+//! by its SHA-256) with what the bench already has: divergence artifacts
+//! ([`crate::replay::Artifact`]), the fetched benchmark fixtures and CompactSize boundary values.
+//! `blvm-bench fuzz-corpus` does all three.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::calculate_tx_id;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::serialization::decode_varint;
+use blvm_protocol::types::Transaction;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::conformance::decode_tx;
+use crate::fixtures::{split_transactions, tx_hash, FixtureKind, FixtureStore, FIXTURES};
+use crate::replay::Artifact;
+use crate::script_differential::{evaluate_case, ScriptCase, ScriptDivergence, FUZZ_FLAGS};
+use crate::sort_merge::divergence::SpentOutput;
+use crate::wire::{compact_size_len, read_compact_size, write_compact_size};
+
+pub const BLOCK_TARGET: &str = "block_deserialize";
+pub const SCRIPT_TARGET: &str = "script_execute";
+pub const COMPACT_SIZE_TARGET: &str = "compact_size";
+
+/// Core's `MAX_SIZE`: `ReadCompactSize` rejects larger values.
+pub const MAX_COMPACT_SIZE: u64 = 0x0200_0000;
+
+const SCRIPT_VERIFY_P2SH: u32 = 1 << 0;
+const SCRIPT_VERIFY_WITNESS: u32 = 1 << 11;
+
+/// `block_deserialize`: BLVM and [`split_transactions`] must agree on any block BLVM accepts.
+pub fn check_block(data: &[u8]) {
+    let Ok((block, _)) = deserialize_block_with_witnesses(data) else {
+        return;
+    };
+    let txs = split_transactions(data)
+        .unwrap_or_else(|e| panic!("BLVM decoded a block the bench rejects: {:#}", e));
+    assert_eq!(txs.len(), block.transactions.len(), "transaction count");
+    for (index, (tx, raw)) in block.transactions.iter().zip(txs).enumerate() {
+        let txid = tx_hash(raw).expect("split_transactions returned an unparseable transaction");
+        assert_eq!(calculate_tx_id(tx), txid, "txid of transaction {}", index);
+    }
+}
+
+/// `script_execute` input: `flags u32 | amount u64` (little-endian), then scriptSig,
+/// scriptPubKey and the witness stack, each CompactSize-prefixed.
+pub fn encode_script_input(case: &ScriptCase) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(case.flags.to_le_bytes());
+    out.extend(case.amount.to_le_bytes());
+    for script in [&case.script_sig, &case.script_pubkey] {
+        write_compact_size(&mut out, script.len());
+        out.extend(script);
+    }
+    write_compact_size(&mut out, case.witness.len());
+    for item in &case.witness {
+        write_compact_size(&mut out, item.len());
+        out.extend(item);
+    }
+    out
+}
+
+/// Inverse of [`encode_script_input`], with the flags cut to [`FUZZ_FLAGS`] (WITNESS implies
+/// P2SH, which Core's interpreter asserts); `None` when truncated.
+pub fn decode_script_input(data: &[u8]) -> Option<ScriptCase> {
+    let flags = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    let amount = u64::from_le_bytes(data.get(4..12)?.try_into().ok()?);
+    let mut pos = 12;
+    let bytes = |pos: &mut usize| -> Option<Vec<u8>> {
+        let len = usize::try_from(read_compact_size(data, pos).ok()?).ok()?;
+        let item = data.get(*pos..pos.checked_add(len)?)?.to_vec();
+        *pos += len;
+        Some(item)
+    };
+    let script_sig = bytes(&mut pos)?;
+    let script_pubkey = bytes(&mut pos)?;
+    let items = read_compact_size(data, &mut pos).ok()?;
+    let mut witness = Vec::new();
+    for _ in 0..items {
+        witness.push(bytes(&mut pos)?);
+    }
+    let mut flags = flags & FUZZ_FLAGS.iter().fold(0, |mask, bit| mask | bit);
+    if flags & SCRIPT_VERIFY_WITNESS != 0 {
+        flags |= SCRIPT_VERIFY_P2SH;
+    }
+    Some(ScriptCase {
+        script_sig,
+        script_pubkey,
+        witness,
+        amount,
+        flags,
+        expected: None,
+        comment: String::new(),
+    })
+}
+
+/// `script_execute`: BLVM and `libbitcoinconsensus` must return the same verdict.
+pub fn check_script(data: &[u8]) {
+    let Some(case) = decode_script_input(data) else {
+        return;
+    };
+    let outcome = evaluate_case(&case);
+    assert_ne!(
+        outcome.divergence(&case),
+        Some(ScriptDivergence::CoreMismatch),
+        "BLVM {} ({:?}), Core {:?} on {:?}",
+        outcome.blvm_ok,
+        outcome.blvm_error,
+        outcome.core_ok,
+        case
+    );
+}
+
+/// `compact_size`: BLVM's `decode_varint` against [`read_compact_size`] on the start of `data`.
+pub fn check_compact_size(data: &[u8]) {
+    let mut width = 0;
+    let ours = read_compact_size(data, &mut width).ok();
+    match (decode_varint(data).ok(), ours) {
+        (Some((value, blvm_width)), Some(ours)) => {
+            assert_eq!(
+                (value, blvm_width),
+                (ours, width),
+                "decoded value and width"
+            );
+            assert_eq!(
+                width,
+                compact_size_len(value as usize),
+                "BLVM accepted a non-canonical encoding"
+            );
+        }
+        (Some(decoded), None) => panic!("BLVM decoded {:?} from a truncated encoding", decoded),
+        (None, Some(value)) => assert!(
+            width != compact_size_len(value as usize) || value > MAX_COMPACT_SIZE,
+            "BLVM rejected the canonical encoding of {}",
+            value
+        ),
+        (None, None) => {}
+    }
+}
+
+/// A serialized transaction as the only transaction of a block with an all-zero header, which
+/// `block_deserialize` can take.
+pub fn tx_as_block(tx: &[u8]) -> Vec<u8> {
+    let mut block = vec![0u8; 80];
+    block.push(1);
+    block.extend(tx);
+    block
+}
+
+/// Adds inputs to the fuzz corpora under `root`; inputs already there are left alone.
+pub struct CorpusWriter {
+    root: PathBuf,
+    /// New inputs per target
+    pub added: BTreeMap<&'static str, u64>,
+    /// Inputs that were already in their corpus
+    pub existing: u64,
+}
+
+impl CorpusWriter {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            added: BTreeMap::new(),
+            existing: 0,
+        }
+    }
+
+    /// `BLVM_FUZZ_CORPUS` if set, else the output directory's `corpora/` (see
+    /// [`crate::artifacts`]), else `fuzz/corpus`.
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var_os("BLVM_FUZZ_CORPUS")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    crate::artifacts::layout_from_env()
+                        .map(|layout| layout.kind_dir(crate::artifacts::ArtifactKind::Corpora))
+                })
+                .unwrap_or_else(|| PathBuf::from("fuzz/corpus")),
+        )
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write `data` to `<root>/<target>/<sha256>`; `false` if it was already there.
+    pub fn add(&mut self, target: &'static str, data: &[u8]) -> Result<bool> {
+        let dir = self.root.join(target);
+        let path = dir.join(hex::encode(Sha256::digest(data)));
+        if path.exists() {
+            self.existing += 1;
+            return Ok(false);
+        }
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        std::fs::write(&path, data)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        *self.added.entry(target).or_default() += 1;
+        Ok(true)
+    }
+
+    /// The spends of `tx` at `inputs` as `script_execute` inputs, under `flags`.
+    fn add_spends(
+        &mut self,
+        tx: &Transaction,
+        witnesses: &[Witness],
+        prevouts: &[SpentOutput],
+        inputs: impl IntoIterator<Item = usize>,
+        flags: u32,
+    ) -> Result<()> {
+        for index in inputs {
+            let (Some(input), Some(prevout)) = (tx.inputs.get(index), prevouts.get(index)) else {
+                continue;
+            };
+            let case = ScriptCase {
+                script_sig: input.script_sig.to_vec(),
+                script_pubkey: hex::decode(&prevout.script_pubkey)
+                    .context("Invalid prevout script hex")?,
+                witness: witnesses.get(index).cloned().unwrap_or_default(),
+                amount: prevout.value.max(0) as u64,
+                flags,
+                expected: None,
+                comment: String::new(),
+            };
+            self.add(SCRIPT_TARGET, &encode_script_input(&case))?;
+        }
+        Ok(())
+    }
+
+    /// The block or transaction of a divergence artifact, and its diverging spends.
+    pub fn add_artifact(&mut self, artifact: &Artifact) -> Result<()> {
+        match artifact {
+            Artifact::Block(artifact) => {
+                self.add(
+                    BLOCK_TARGET,
+                    &hex::decode(&artifact.block_hex).context("Invalid block hex")?,
+                )?;
+            }
+            Artifact::Script(artifact) => {
+                let bytes = hex::decode(&artifact.tx_hex).context("Invalid tx hex")?;
+                self.add(BLOCK_TARGET, &tx_as_block(&bytes))?;
+                // The artifact keeps the legacy serialization and the witnesses beside it
+                let (tx, _) = decode_tx(&bytes)?;
+                let witnesses: Vec<Witness> = artifact
+                    .witnesses
+                    .iter()
+                    .map(|stack| stack.iter().map(hex::decode).collect::<Result<_, _>>())
+                    .collect::<Result<_, _>>()
+                    .context("Invalid witness hex")?;
+                self.add_spends(
+                    &tx,
+                    &witnesses,
+                    &artifact.prevouts,
+                    [artifact.input_index],
+                    artifact.flags,
+                )?;
+            }
+            Artifact::Mempool(artifact) => {
+                let bytes = hex::decode(&artifact.tx_hex).context("Invalid tx hex")?;
+                self.add(BLOCK_TARGET, &tx_as_block(&bytes))?;
+                let (tx, witnesses) = decode_tx(&bytes)?;
+                self.add_spends(
+                    &tx,
+                    &witnesses,
+                    &artifact.prevouts,
+                    0..tx.inputs.len(),
+                    FUZZ_FLAGS.iter().fold(0, |mask, bit| mask | bit),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Every artifact (`*.json`) directly in `dir`; returns how many were read.
+    pub fn add_artifact_dir(&mut self, dir: &Path) -> Result<usize> {
+        let mut read = 0;
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match Artifact::load(&path) {
+                Ok(artifact) => {
+                    self.add_artifact(&artifact)?;
+                    read += 1;
+                }
+                Err(e) => debug!("Skipping {}: {:#}", path.display(), e),
+            }
+        }
+        Ok(read)
+    }
+
+    /// The fixtures fetched into `store`; returns how many there were.
+    pub fn add_fixtures(&mut self, store: &FixtureStore) -> Result<usize> {
+        let mut found = 0;
+        for fixture in FIXTURES {
+            let Some(bytes) = store.load(fixture)? else {
+                continue;
+            };
+            match fixture.kind {
+                FixtureKind::Block => self.add(BLOCK_TARGET, &bytes)?,
+                FixtureKind::Transaction => self.add(BLOCK_TARGET, &tx_as_block(&bytes))?,
+            };
+            found += 1;
+        }
+        Ok(found)
+    }
+
+    /// Canonical encodings at every width boundary and around [`MAX_COMPACT_SIZE`], plus
+    /// non-canonical and truncated ones.
+    pub fn add_compact_size_seeds(&mut self) -> Result<()> {
+        for value in [
+            0,
+            0xfc,
+            0xfd,
+            0xffff,
+            0x1_0000,
+            MAX_COMPACT_SIZE,
+            MAX_COMPACT_SIZE + 1,
+            0xffff_ffff,
+            0x1_0000_0000,
+            u64::MAX,
+        ] {
+            let mut encoded = Vec::new();
+            write_compact_size(&mut encoded, value as usize);
+            self.add(COMPACT_SIZE_TARGET, &encoded)?;
+        }
+        for encoded in [
+            &[0xfd, 0xfc, 0x00][..],
+            &[0xfe, 0xff, 0xff, 0x00, 0x00],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00],
+            &[0xfd, 0x01],
+            &[0xfe],
+            &[0xff, 0x00],
+        ] {
+            self.add(COMPACT_SIZE_TARGET, encoded)?;
+        }
+        Ok(())
+    }
+
+    pub fn print(&self) {
+        println!("🌱 Fuzz corpora in {}", self.root.display());
+        for (target, added) in &self.added {
+            println!("   {:<18} +{}", target, added);
+        }
+        println!("   {} input(s) were already present", self.existing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_inputs_round_trip_and_corpus_dedupes() {
+        let case = ScriptCase {
+            script_sig: vec![0x51],
+            script_pubkey: vec![0x87; 300],
+            witness: vec![vec![], vec![0xab; 2]],
+            amount: 5_000,
+            flags: SCRIPT_VERIFY_P2SH | SCRIPT_VERIFY_WITNESS,
+            expected: None,
+            comment: String::new(),
+        };
+        let encoded = encode_script_input(&case);
+        assert_eq!(decode_script_input(&encoded).unwrap(), case);
+        assert!(decode_script_input(&encoded[..encoded.len() - 1]).is_none());
+        // Flags outside the differential's set are dropped; WITNESS brings P2SH
+        let mut other = encoded.clone();
+        other[..4].copy_from_slice(&(SCRIPT_VERIFY_WITNESS | 1 << 1).to_le_bytes());
+        assert_eq!(decode_script_input(&other).unwrap().flags, case.flags);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = CorpusWriter::new(dir.path());
+        assert!(writer.add(SCRIPT_TARGET, &encoded).unwrap());
+        assert!(!writer.add(SCRIPT_TARGET, &encoded).unwrap());
+        writer.add_compact_size_seeds().unwrap();
+        assert_eq!(writer.added[SCRIPT_TARGET], 1);
+        assert_eq!(writer.added[COMPACT_SIZE_TARGET], 16);
+        assert_eq!(writer.existing, 1);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join(COMPACT_SIZE_TARGET))
+                .unwrap()
+                .count(),
+            16
+        );
+    }
+}
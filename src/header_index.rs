@@ -0,0 +1,324 @@
+//! Headers-first chain index for out-of-order block files
+//!
+//! XOR-packaged trees (Start9 and similar) store blocks out of order and ship without Core's
+//! `blocks/index`. Ordering them used to mean reading every full block into a 400GB+ temp file
+//! and chaining them by previous hash. [`build`] reads only the 8-byte frame and the 80-byte
+//! header of each block, seeking past the rest. It then chains the headers from genesis into a
+//! [`CoreBlockIndex`] that maps height to `(file, offset)`. That index takes about 72 bytes per
+//! block, roughly 65MB for 900k blocks. Full blocks are then read one at a time, in height order,
+//! through [`BlockFileReader::read_block_at`](crate::block_file_reader::BlockFileReader::read_block_at).
+//!
+//! The active chain is the longest one reachable from genesis (most headers, not most work;
+//! ties keep the first seen). Blocks whose parent is not in the files are counted as orphans and
+//! left out.
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::block_framing::XorKey;
+use crate::block_index::{BlockLocation, CoreBlockIndex};
+use crate::cancel::CancellationToken;
+use crate::wire::sha256d;
+
+/// Read buffer per file; small, since most of each block is skipped
+const SCAN_BUFFER: usize = 64 * 1024;
+/// Magic + size + header
+const FRAME_PREFIX: usize = 4 + 4 + 80;
+
+/// A block header found in a block file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScannedHeader {
+    /// Internal byte order
+    pub prev_hash: [u8; 32],
+    pub location: BlockLocation,
+    /// Serialized block length (the size field of the frame)
+    pub size: u32,
+}
+
+/// The chain found by [`chain_headers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderChain {
+    /// Active chain, height 0 first
+    pub blocks: Vec<ScannedHeader>,
+    /// Distinct headers scanned
+    pub headers: usize,
+    /// Headers on a branch off the active chain
+    pub stale: usize,
+    /// Headers not connected to genesis
+    pub orphans: usize,
+}
+
+impl HeaderChain {
+    pub fn tip_height(&self) -> u64 {
+        self.blocks.len() as u64 - 1
+    }
+
+    /// Height -> location index of the active chain.
+    pub fn index(&self) -> Result<CoreBlockIndex> {
+        CoreBlockIndex::from_chain(
+            self.blocks.iter().map(|block| block.location).collect(),
+            self.headers,
+        )
+    }
+}
+
+/// Headers of every block framed in one file, stopping at the first bytes that are not a frame
+/// (Core's zero-filled preallocation at the end of a file, or damage, which is logged).
+pub fn scan_records<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    file: u32,
+    magic: &[u8; 4],
+    key: Option<XorKey>,
+) -> Result<Vec<ScannedHeader>> {
+    let mut headers = Vec::new();
+    let mut pos = reader.stream_position()?;
+    let mut frame = [0u8; FRAME_PREFIX];
+    loop {
+        match reader.read_exact(&mut frame) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if let Some(key) = key {
+            key.apply(&mut frame, pos);
+        }
+        if frame[..4] != magic[..] {
+            if frame[..8].iter().any(|&b| b != 0) {
+                warn!(
+                    "⚠️  blk{:05}.dat: no block magic at offset {}, skipping the rest of the file",
+                    file, pos
+                );
+            }
+            break;
+        }
+        let size = u32::from_le_bytes(frame[4..8].try_into().unwrap());
+        if !(80..=crate::sanity::DEFAULT_MAX_BLOCK_SIZE as u32).contains(&size) {
+            warn!(
+                "⚠️  blk{:05}.dat: implausible block size {} at offset {}, skipping the rest of the file",
+                file, size, pos
+            );
+            break;
+        }
+        let header = &frame[8..];
+        headers.push(ScannedHeader {
+            prev_hash: header[4..36].try_into().unwrap(),
+            location: BlockLocation {
+                file,
+                data_pos: u32::try_from(pos + 8).context("Block file offset exceeds 4GB")?,
+                hash: sha256d(header),
+            },
+            size,
+        });
+        // Skip the transactions; seek_relative keeps the buffer when the next frame is in it
+        reader.seek_relative(i64::from(size) - 80)?;
+        pos += 8 + u64::from(size);
+    }
+    Ok(headers)
+}
+
+/// `blk00042.dat` -> 42.
+fn file_number(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("blk")?
+        .strip_suffix(".dat")?
+        .parse()
+        .ok()
+}
+
+/// Chain `headers` from `genesis` (internal byte order) and keep the longest branch.
+pub fn chain_headers(headers: Vec<ScannedHeader>, genesis: [u8; 32]) -> Result<HeaderChain> {
+    // Files can hold the same block twice (rewritten after a crash); keep the first copy
+    let mut by_hash: HashMap<[u8; 32], usize> = HashMap::with_capacity(headers.len());
+    let mut unique = Vec::with_capacity(headers.len());
+    for header in headers {
+        if let Entry::Vacant(slot) = by_hash.entry(header.location.hash) {
+            slot.insert(unique.len());
+            unique.push(header);
+        }
+    }
+    let root = *by_hash
+        .get(&genesis)
+        .context("Genesis block not found in the block files")?;
+
+    let mut children: HashMap<[u8; 32], Vec<usize>> = HashMap::with_capacity(unique.len());
+    for (idx, header) in unique.iter().enumerate() {
+        if idx != root {
+            children.entry(header.prev_hash).or_default().push(idx);
+        }
+    }
+
+    // Breadth-first from genesis: heights and parents of everything connected to it
+    let mut parent = vec![usize::MAX; unique.len()];
+    let mut height = vec![0u32; unique.len()];
+    let mut connected = 1;
+    let mut tip = root;
+    let mut queue = VecDeque::from([root]);
+    while let Some(idx) = queue.pop_front() {
+        if height[idx] > height[tip] {
+            tip = idx;
+        }
+        for &child in children
+            .get(&unique[idx].location.hash)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            parent[child] = idx;
+            height[child] = height[idx] + 1;
+            connected += 1;
+            queue.push_back(child);
+        }
+    }
+
+    let mut blocks = Vec::with_capacity(height[tip] as usize + 1);
+    let mut idx = tip;
+    loop {
+        blocks.push(unique[idx]);
+        if idx == root {
+            break;
+        }
+        idx = parent[idx];
+    }
+    blocks.reverse();
+    Ok(HeaderChain {
+        stale: connected - blocks.len(),
+        orphans: unique.len() - connected,
+        headers: unique.len(),
+        blocks,
+    })
+}
+
+/// Scan every `blk*.dat` in `block_files` (in parallel) and chain the headers.
+pub fn build(
+    block_files: &[PathBuf],
+    magic: &[u8; 4],
+    key: Option<XorKey>,
+    genesis_hash_hex: &str,
+    cancel: &CancellationToken,
+) -> Result<HeaderChain> {
+    let started = std::time::Instant::now();
+    let progress = crate::progress::Progress::new("header scan", block_files.len() as u64, "files");
+    let per_file: Vec<Vec<ScannedHeader>> = block_files
+        .par_iter()
+        .filter_map(|path| Some((file_number(path)?, path)))
+        .map(|(file, path)| {
+            crate::cancel::check(cancel, || format!("header scan of {}", path.display()))?;
+            let handle =
+                File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let headers = scan_records(
+                &mut BufReader::with_capacity(SCAN_BUFFER, handle),
+                file,
+                magic,
+                key,
+            )
+            .with_context(|| format!("Failed to scan {}", path.display()))?;
+            progress.inc(1);
+            Ok(headers)
+        })
+        .collect::<Result<_>>()?;
+
+    let mut genesis: [u8; 32] = hex::decode(genesis_hash_hex)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Genesis hash is not 32 bytes"))?;
+    genesis.reverse();
+    let chain = chain_headers(per_file.into_iter().flatten().collect(), genesis)?;
+    progress.finish(format!(
+        "{} headers, tip {} in {:.1}s",
+        chain.headers,
+        chain.tip_height(),
+        started.elapsed().as_secs_f64()
+    ));
+    info!(
+        "🧭 Header index: {} headers, active chain tip {} ({} stale, {} orphaned)",
+        chain.headers,
+        chain.tip_height(),
+        chain.stale,
+        chain.orphans
+    );
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+    fn header(prev: [u8; 32], nonce: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 80];
+        header[4..36].copy_from_slice(&prev);
+        header[76..].copy_from_slice(&nonce.to_le_bytes());
+        header
+    }
+
+    fn scanned(hash: u8, prev: u8) -> ScannedHeader {
+        ScannedHeader {
+            prev_hash: [prev; 32],
+            location: BlockLocation {
+                file: 0,
+                data_pos: u32::from(hash),
+                hash: [hash; 32],
+            },
+            size: 80,
+        }
+    }
+
+    #[test]
+    fn scans_headers_of_obfuscated_file() {
+        let key = XorKey::PACKAGED;
+        let first = header([0; 32], 1);
+        let first_hash = sha256d(&first);
+        let mut file = Vec::new();
+        for (header, body) in [(first, 300usize), (header(first_hash, 2), 5000)] {
+            file.extend(MAGIC);
+            file.extend((80 + body as u32).to_le_bytes());
+            file.extend(header);
+            file.extend(vec![0xab; body]);
+        }
+        let second_frame = 8 + 80 + 300;
+        // Preallocated tail
+        file.extend(vec![0; 4096]);
+        key.apply(&mut file, 0);
+
+        let mut reader = BufReader::with_capacity(128, Cursor::new(file));
+        let headers = scan_records(&mut reader, 7, &MAGIC, Some(key)).unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].location.file, 7);
+        assert_eq!(headers[0].location.data_pos, 8);
+        assert_eq!(headers[0].location.hash, first_hash);
+        assert_eq!(headers[0].size, 380);
+        assert_eq!(headers[1].prev_hash, first_hash);
+        assert_eq!(headers[1].location.data_pos, second_frame + 8);
+    }
+
+    #[test]
+    fn chains_out_of_order_headers() {
+        // 0 <- 1 <- 2 <- 3 (active), 1 <- 9 (stale), 5 <- 6 (orphans), 2 stored twice
+        let headers = vec![
+            scanned(3, 2),
+            scanned(6, 5),
+            scanned(1, 0),
+            scanned(9, 1),
+            scanned(2, 1),
+            scanned(0, 0xff),
+            scanned(2, 1),
+        ];
+        let chain = chain_headers(headers, [0; 32]).unwrap();
+        let hashes: Vec<u8> = chain.blocks.iter().map(|b| b.location.hash[0]).collect();
+        assert_eq!(hashes, vec![0, 1, 2, 3]);
+        assert_eq!(chain.headers, 6);
+        assert_eq!(chain.stale, 1);
+        assert_eq!(chain.orphans, 1);
+
+        assert!(chain_headers(vec![scanned(1, 0)], [0; 32]).is_err());
+        assert_eq!(file_number(Path::new("/x/blocks/blk00042.dat")), Some(42));
+        assert_eq!(file_number(Path::new("/x/blocks/rev00042.dat")), None);
+    }
+}
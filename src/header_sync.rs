@@ -0,0 +1,827 @@
+//! Header-only sync differential
+//!
+//! Fetches every header of Core's active chain (batched `getblockhash` / `getblockheader`),
+//! validates them independently - proof of work, `prev_hash` continuity, median-time-past and
+//! the difficulty schedule - and compares the resulting tip and cumulative chainwork with
+//! `getblockchaininfo` / `getchaintips`. Fork branches Core holds as valid headers are checked
+//! too. A mainnet pass takes minutes, so it is a cheap smoke test before a full differential.
+//!
+//! Headers can be kept in a flat file (`80 * n` bytes, index = height) so later runs only fetch
+//! what is new; the cached tip is re-checked against Core and rolled back on a reorg.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::node_rpc_client::{BitcoinNetwork, NodeRpcClient};
+use crate::wire::{display_hex, sha256d};
+
+/// Serialized header size
+pub const HEADER_LEN: usize = 80;
+/// Headers fetched per JSON-RPC batch
+const FETCH_BATCH: usize = 2_000;
+/// Headers dropped from a cache whose tip Core no longer has on its active chain
+const REORG_ROLLBACK: usize = 144;
+/// Blocks in the median-time-past window
+const MTP_WINDOW: usize = 11;
+/// BIP94: seconds the first block of a retarget period may lie before its parent
+pub const MAX_TIMEWARP: u32 = 600;
+
+/// 256-bit unsigned integer (little-endian limbs), just enough for targets and chainwork.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct U256([u64; 4]);
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl U256 {
+    const ZERO: U256 = U256([0; 4]);
+    const ONE: U256 = U256([1, 0, 0, 0]);
+
+    fn from_u64(v: u64) -> Self {
+        U256([v, 0, 0, 0])
+    }
+
+    /// Interpret 32 bytes as a little-endian integer (how hashes compare against targets).
+    fn from_le_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            *limb = u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap());
+        }
+        U256(limbs)
+    }
+
+    /// Parse big-endian hex as printed by Core (`chainwork`, `powLimit`).
+    fn from_be_hex(s: &str) -> Result<Self> {
+        let s = s.trim_start_matches("0x");
+        anyhow::ensure!(s.len() <= 64, "Hex value longer than 256 bits: {}", s);
+        let mut bytes = hex::decode(format!("{:0>64}", s))
+            .with_context(|| format!("Invalid hex value '{}'", s))?;
+        bytes.reverse();
+        Ok(Self::from_le_bytes(bytes.as_slice().try_into()?))
+    }
+
+    fn to_be_hex(self) -> String {
+        self.0.iter().rev().map(|l| format!("{:016x}", l)).collect()
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    fn bit(&self, n: u32) -> bool {
+        (self.0[(n / 64) as usize] >> (n % 64)) & 1 == 1
+    }
+
+    fn not(self) -> Self {
+        U256(self.0.map(|l| !l))
+    }
+
+    fn wrapping_add(self, other: Self) -> Self {
+        let mut out = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (s1, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (s2, c2) = s1.overflowing_add(carry as u64);
+            *limb = s2;
+            carry = c1 || c2;
+        }
+        U256(out)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        self.wrapping_add(other.not()).wrapping_add(Self::ONE)
+    }
+
+    fn shl(self, n: u32) -> Self {
+        if n >= 256 {
+            return Self::ZERO;
+        }
+        let (words, bits) = ((n / 64) as usize, n % 64);
+        let mut out = [0u64; 4];
+        for i in (words..4).rev() {
+            out[i] = self.0[i - words] << bits;
+            if bits > 0 && i > words {
+                out[i] |= self.0[i - words - 1] >> (64 - bits);
+            }
+        }
+        U256(out)
+    }
+
+    fn shr(self, n: u32) -> Self {
+        if n >= 256 {
+            return Self::ZERO;
+        }
+        let (words, bits) = ((n / 64) as usize, n % 64);
+        let mut out = [0u64; 4];
+        for (i, limb) in out.iter_mut().take(4 - words).enumerate() {
+            *limb = self.0[i + words] >> bits;
+            if bits > 0 && i + words + 1 < 4 {
+                *limb |= self.0[i + words + 1] << (64 - bits);
+            }
+        }
+        U256(out)
+    }
+
+    /// `None` on overflow.
+    fn checked_mul_u64(self, m: u64) -> Option<Self> {
+        let mut out = [0u64; 4];
+        let mut carry = 0u128;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let v = self.0[i] as u128 * m as u128 + carry;
+            *limb = v as u64;
+            carry = v >> 64;
+        }
+        (carry == 0).then_some(U256(out))
+    }
+
+    /// Long division; `divisor` must be non-zero.
+    fn div(self, divisor: Self) -> Self {
+        let mut quotient = Self::ZERO;
+        let mut remainder = Self::ZERO;
+        for n in (0..self.bits()).rev() {
+            remainder = remainder.shl(1);
+            if self.bit(n) {
+                remainder.0[0] |= 1;
+            }
+            if remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[(n / 64) as usize] |= 1 << (n % 64);
+            }
+        }
+        quotient
+    }
+
+    /// `arith_uint256::SetCompact`; `None` for negative or overflowing encodings.
+    fn from_compact(compact: u32) -> Option<Self> {
+        let size = compact >> 24;
+        let word = compact & 0x007f_ffff;
+        let value = if size <= 3 {
+            U256::from_u64(u64::from(word >> (8 * (3 - size))))
+        } else {
+            U256::from_u64(u64::from(word)).shl(8 * (size - 3))
+        };
+        let negative = word != 0 && compact & 0x0080_0000 != 0;
+        let overflow =
+            word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32));
+        (!negative && !overflow).then_some(value)
+    }
+
+    /// `arith_uint256::GetCompact`
+    fn to_compact(self) -> u32 {
+        let mut size = self.bits().div_ceil(8);
+        let mut compact = if size <= 3 {
+            (self.0[0] << (8 * (3 - size))) as u32
+        } else {
+            self.shr(8 * (size - 3)).0[0] as u32
+        };
+        if compact & 0x0080_0000 != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+        compact | (size << 24)
+    }
+
+    /// Expected hashes to find a block at this target: `2^256 / (target + 1)`.
+    fn work(self) -> Self {
+        self.not()
+            .div(self.wrapping_add(Self::ONE))
+            .wrapping_add(Self::ONE)
+    }
+}
+
+/// Consensus parameters the header rules depend on.
+#[derive(Debug, Clone)]
+pub struct HeaderParams {
+    pow_limit: U256,
+    /// Blocks between difficulty adjustments
+    pub interval: u64,
+    /// Target seconds per adjustment period
+    pub target_timespan: u64,
+    /// Target seconds per block
+    pub target_spacing: u64,
+    /// Testnet / regtest: a block more than `2 * target_spacing` after its parent may use the
+    /// minimum difficulty
+    pub allow_min_difficulty: bool,
+    /// Regtest: difficulty never adjusts
+    pub no_retargeting: bool,
+    /// Testnet4 (BIP94): retargets start from the period's first block, and the first block of a
+    /// period may not be more than [`MAX_TIMEWARP`] seconds older than its parent
+    pub enforce_bip94: bool,
+}
+
+impl HeaderParams {
+    pub fn for_network(network: BitcoinNetwork) -> Self {
+        let pow_limit = match network {
+            BitcoinNetwork::Mainnet | BitcoinNetwork::Testnet => {
+                "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+            }
+            BitcoinNetwork::Signet => {
+                "00000377ae000000000000000000000000000000000000000000000000000000"
+            }
+            BitcoinNetwork::Regtest => {
+                "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+            }
+        };
+        Self {
+            pow_limit: U256::from_be_hex(pow_limit).expect("valid pow limit"),
+            interval: 2016,
+            target_timespan: 14 * 24 * 60 * 60,
+            target_spacing: 600,
+            allow_min_difficulty: matches!(
+                network,
+                BitcoinNetwork::Testnet | BitcoinNetwork::Regtest
+            ),
+            no_retargeting: network == BitcoinNetwork::Regtest,
+            enforce_bip94: false,
+        }
+    }
+
+    /// Testnet4: testnet3's PoW limit and min-difficulty rule plus BIP94 (there is no
+    /// [`BitcoinNetwork`] variant for it, as Core's RPC ports are shared with testnet3).
+    pub fn testnet4() -> Self {
+        Self {
+            enforce_bip94: true,
+            ..Self::for_network(BitcoinNetwork::Testnet)
+        }
+    }
+
+    fn pow_limit_bits(&self) -> u32 {
+        self.pow_limit.to_compact()
+    }
+}
+
+/// Fields of an 80-byte header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: i32,
+    /// Internal byte order (as serialized)
+    pub prev_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+    /// sha256d of the header, internal byte order
+    pub hash: [u8; 32],
+}
+
+impl Header {
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            raw.len() == HEADER_LEN,
+            "Header is {} bytes, expected {}",
+            raw.len(),
+            HEADER_LEN
+        );
+        let u32_at = |i: usize| u32::from_le_bytes(raw[i..i + 4].try_into().unwrap());
+        Ok(Self {
+            version: u32_at(0) as i32,
+            prev_hash: raw[4..36].try_into()?,
+            merkle_root: raw[36..68].try_into()?,
+            time: u32_at(68),
+            bits: u32_at(72),
+            nonce: u32_at(76),
+            hash: sha256d(raw),
+        })
+    }
+
+    /// Hash as displayed by Core (reversed hex).
+    pub fn hash_hex(&self) -> String {
+        display_hex(&self.hash)
+    }
+}
+
+/// Why a header was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeaderRejection {
+    /// `prev_hash` is not the hash of the header below it
+    BadPrevHash,
+    /// `nBits` is negative, zero, overflows or is above the network's PoW limit
+    BadTarget,
+    /// Hash is above the target
+    HighHash,
+    /// `nBits` differs from the difficulty schedule
+    BadDifficulty { expected: u32, actual: u32 },
+    /// Timestamp is not after the median of the previous 11
+    TimeTooOld { time: u32, median_time_past: u32 },
+    /// BIP94: first block of a retarget period more than [`MAX_TIMEWARP`] before its parent
+    TimeWarp { time: u32, parent_time: u32 },
+}
+
+/// Header chain being validated, with cumulative chainwork.
+pub struct HeaderChain {
+    params: HeaderParams,
+    headers: Vec<Header>,
+    chainwork: U256,
+}
+
+impl HeaderChain {
+    pub fn new(params: HeaderParams) -> Self {
+        Self {
+            params,
+            headers: Vec::new(),
+            chainwork: U256::ZERO,
+        }
+    }
+
+    /// Number of headers accepted (tip height + 1).
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn tip(&self) -> Option<&Header> {
+        self.headers.last()
+    }
+
+    pub fn header(&self, height: u64) -> Option<&Header> {
+        self.headers.get(height as usize)
+    }
+
+    /// Cumulative chainwork as 64 hex digits, the format of Core's `chainwork`.
+    pub fn chainwork_hex(&self) -> String {
+        self.chainwork.to_be_hex()
+    }
+
+    /// Validate `header` as the next block and append it. The first header is taken as genesis
+    /// (PoW only).
+    pub fn push(&mut self, header: Header) -> std::result::Result<(), HeaderRejection> {
+        self.check(&header, self.headers.len())?;
+        let target = U256::from_compact(header.bits).ok_or(HeaderRejection::BadTarget)?;
+        self.chainwork = self.chainwork.wrapping_add(target.work());
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Check `header` as a child of the header at `height - 1` without appending it. Used for
+    /// fork branches, whose ancestors up to the fork point are shared with this chain.
+    fn check(&self, header: &Header, height: usize) -> std::result::Result<(), HeaderRejection> {
+        let target = U256::from_compact(header.bits)
+            .filter(|t| !t.is_zero() && *t <= self.params.pow_limit)
+            .ok_or(HeaderRejection::BadTarget)?;
+        if U256::from_le_bytes(&header.hash) > target {
+            return Err(HeaderRejection::HighHash);
+        }
+        if height == 0 {
+            return Ok(());
+        }
+        let prev = &self.headers[height - 1];
+        if header.prev_hash != prev.hash {
+            return Err(HeaderRejection::BadPrevHash);
+        }
+        let expected = self.next_work_required(height, header.time);
+        if header.bits != expected {
+            return Err(HeaderRejection::BadDifficulty {
+                expected,
+                actual: header.bits,
+            });
+        }
+        let mtp = self.median_time_past(height - 1);
+        if header.time <= mtp {
+            return Err(HeaderRejection::TimeTooOld {
+                time: header.time,
+                median_time_past: mtp,
+            });
+        }
+        if self.params.enforce_bip94
+            && height as u64 % self.params.interval == 0
+            && header.time < prev.time.saturating_sub(MAX_TIMEWARP)
+        {
+            return Err(HeaderRejection::TimeWarp {
+                time: header.time,
+                parent_time: prev.time,
+            });
+        }
+        Ok(())
+    }
+
+    fn median_time_past(&self, height: usize) -> u32 {
+        let mut window: Vec<u32> = self.headers[height.saturating_sub(MTP_WINDOW - 1)..=height]
+            .iter()
+            .map(|h| h.time)
+            .collect();
+        window.sort_unstable();
+        window[window.len() / 2]
+    }
+
+    /// `GetNextWorkRequired` for a block at `height` with timestamp `time`.
+    fn next_work_required(&self, height: usize, time: u32) -> u32 {
+        let p = &self.params;
+        let interval = p.interval as usize;
+        let last = &self.headers[height - 1];
+        if height % interval != 0 {
+            if !p.allow_min_difficulty {
+                return last.bits;
+            }
+            if u64::from(time) > u64::from(last.time) + 2 * p.target_spacing {
+                return p.pow_limit_bits();
+            }
+            // Last block that was not a min-difficulty exception
+            let mut h = height - 1;
+            while h % interval != 0 && self.headers[h].bits == p.pow_limit_bits() {
+                h -= 1;
+            }
+            return self.headers[h].bits;
+        }
+        if p.no_retargeting {
+            return last.bits;
+        }
+        let first = &self.headers[height - interval];
+        let actual = (i64::from(last.time) - i64::from(first.time)).clamp(
+            (p.target_timespan / 4) as i64,
+            (p.target_timespan * 4) as i64,
+        ) as u64;
+        // BIP94: the last block may be a min-difficulty exception, the first one never is
+        let base_bits = if p.enforce_bip94 {
+            first.bits
+        } else {
+            last.bits
+        };
+        let retarget = U256::from_compact(base_bits)
+            .and_then(|t| t.checked_mul_u64(actual))
+            .map(|t| t.div(U256::from_u64(p.target_timespan)))
+            .map_or(p.pow_limit, |t| t.min(p.pow_limit));
+        retarget.to_compact()
+    }
+}
+
+/// A header the validator rejected although Core has it as a valid header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderDivergence {
+    pub height: u64,
+    pub hash: String,
+    pub rejection: HeaderRejection,
+    /// `None` on the active chain, otherwise the `getchaintips` status of the branch
+    pub branch_status: Option<String>,
+}
+
+/// Outcome of [`run_header_sync`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderSyncReport {
+    pub network: String,
+    pub headers_validated: u64,
+    pub tip_height: u64,
+    pub tip_hash: String,
+    pub chainwork: String,
+    pub core_tip_height: u64,
+    pub core_tip_hash: String,
+    pub core_chainwork: String,
+    /// Non-active branches whose headers were validated
+    pub forks_checked: usize,
+    pub divergences: Vec<HeaderDivergence>,
+    pub duration_secs: f64,
+}
+
+impl HeaderSyncReport {
+    /// Tip and chainwork agree with Core and no header was rejected.
+    pub fn matches(&self) -> bool {
+        self.divergences.is_empty()
+            && self.tip_height == self.core_tip_height
+            && self.tip_hash == self.core_tip_hash
+            && self.chainwork.eq_ignore_ascii_case(&self.core_chainwork)
+    }
+}
+
+/// Inputs for [`run_header_sync`]
+#[derive(Debug, Clone, Default)]
+pub struct HeaderSyncConfig {
+    /// Flat header file reused across runs (`None` fetches everything every time)
+    pub headers_cache: Option<PathBuf>,
+    /// Network rules; detected from Core when `None`
+    pub network: Option<BitcoinNetwork>,
+}
+
+fn read_headers_cache(path: &Path) -> Result<Vec<u8>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut data =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    data.truncate(data.len() - data.len() % HEADER_LEN);
+    Ok(data)
+}
+
+/// `nBits` of every header in a cache file written by [`run_header_sync`], index = height.
+pub fn cached_header_bits(path: &Path) -> Result<Vec<u32>> {
+    anyhow::ensure!(path.exists(), "No header cache at {}", path.display());
+    Ok(read_headers_cache(path)?
+        .chunks_exact(HEADER_LEN)
+        .map(|raw| u32::from_le_bytes(raw[72..76].try_into().unwrap()))
+        .collect())
+}
+
+fn write_headers_cache(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to rename {}", tmp.display()))?;
+    Ok(())
+}
+
+fn rpc_string(v: Result<Value>) -> Result<String> {
+    v?.as_str()
+        .map(str::to_string)
+        .context("Expected a string RPC result")
+}
+
+/// Raw headers for `heights` via two batched calls.
+async fn fetch_headers(client: &NodeRpcClient, heights: std::ops::Range<u64>) -> Result<Vec<u8>> {
+    let hash_params: Vec<Value> = heights.clone().map(|h| serde_json::json!([h])).collect();
+    let hashes = client
+        .call_batch("getblockhash", &hash_params)
+        .await?
+        .into_iter()
+        .map(rpc_string)
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("getblockhash for {:?}", heights))?;
+    let header_params: Vec<Value> = hashes
+        .iter()
+        .map(|h| serde_json::json!([h, false]))
+        .collect();
+    let mut out = Vec::with_capacity(hashes.len() * HEADER_LEN);
+    for (hash, header) in hashes
+        .iter()
+        .zip(client.call_batch("getblockheader", &header_params).await?)
+    {
+        let raw = hex::decode(rpc_string(header)?.trim())
+            .with_context(|| format!("Invalid header hex for {}", hash))?;
+        anyhow::ensure!(
+            raw.len() == HEADER_LEN,
+            "Header {} is {} bytes",
+            hash,
+            raw.len()
+        );
+        out.extend_from_slice(&raw);
+    }
+    Ok(out)
+}
+
+/// Active-chain headers `0..=tip`, reusing (and refreshing) the cache file when configured.
+async fn load_active_headers(
+    client: &NodeRpcClient,
+    tip: u64,
+    cache: Option<&Path>,
+) -> Result<Vec<u8>> {
+    let mut data = match cache {
+        Some(path) => read_headers_cache(path)?,
+        None => Vec::new(),
+    };
+    data.truncate((tip as usize + 1) * HEADER_LEN);
+    // Roll back until the cached tip is still on Core's active chain
+    while !data.is_empty() {
+        let height = (data.len() / HEADER_LEN - 1) as u64;
+        let cached = Header::parse(&data[data.len() - HEADER_LEN..])?;
+        if cached.hash_hex() == client.getblockhash(height).await? {
+            break;
+        }
+        println!(
+            "   ⚠️  Cached header {} is no longer on the active chain - rolling back",
+            height
+        );
+        data.truncate(data.len().saturating_sub(REORG_ROLLBACK * HEADER_LEN));
+    }
+    if !data.is_empty() {
+        println!(
+            "   ♻️  {} headers from cache, fetching {}",
+            data.len() / HEADER_LEN,
+            (tip + 1) as usize - data.len() / HEADER_LEN
+        );
+    }
+
+    let mut next = (data.len() / HEADER_LEN) as u64;
+    while next <= tip {
+        let end = (next + FETCH_BATCH as u64).min(tip + 1);
+        data.extend(fetch_headers(client, next..end).await?);
+        next = end;
+        if next % 50_000 < FETCH_BATCH as u64 || next > tip {
+            println!("   📥 {} / {} headers", next, tip + 1);
+        }
+    }
+
+    if let Some(path) = cache {
+        write_headers_cache(path, &data)?;
+    }
+    Ok(data)
+}
+
+/// Headers of a fork branch, from the tip back to (excluding) the first active-chain ancestor.
+async fn fetch_branch(
+    client: &NodeRpcClient,
+    tip_hash: &str,
+    branch_len: u64,
+) -> Result<Vec<(u64, Header)>> {
+    let mut branch = Vec::new();
+    let mut hash = tip_hash.to_string();
+    for _ in 0..branch_len {
+        let verbose = client.getblockheader(&hash, true).await?;
+        let height = verbose
+            .get("height")
+            .and_then(Value::as_u64)
+            .context("getblockheader missing height")?;
+        let raw = hex::decode(rpc_string(client.getblockheader(&hash, false).await)?.trim())?;
+        let header = Header::parse(&raw)?;
+        hash = display_hex(&header.prev_hash);
+        branch.push((height, header));
+    }
+    branch.reverse();
+    Ok(branch)
+}
+
+/// Validate Core's header chain and compare tip and chainwork with Core.
+pub async fn run_header_sync(
+    client: &NodeRpcClient,
+    config: &HeaderSyncConfig,
+) -> Result<HeaderSyncReport> {
+    let start = std::time::Instant::now();
+    let network = match config.network {
+        Some(network) => network,
+        None => client.detect_network().await?,
+    };
+    let info = client.getblockchaininfo().await?;
+    let core_tip_height = info
+        .get("blocks")
+        .and_then(Value::as_u64)
+        .context("getblockchaininfo missing blocks")?;
+    let core_tip_hash = info
+        .get("bestblockhash")
+        .and_then(Value::as_str)
+        .context("getblockchaininfo missing bestblockhash")?
+        .to_string();
+    let core_chainwork = info
+        .get("chainwork")
+        .and_then(Value::as_str)
+        .context("getblockchaininfo missing chainwork")?
+        .to_string();
+
+    println!(
+        "🧾 Header sync differential ({}, Core tip {})",
+        network.as_str(),
+        core_tip_height
+    );
+    let raw = load_active_headers(client, core_tip_height, config.headers_cache.as_deref()).await?;
+
+    let mut chain = HeaderChain::new(HeaderParams::for_network(network));
+    let mut divergences = Vec::new();
+    for (height, raw) in raw.chunks_exact(HEADER_LEN).enumerate() {
+        let header = Header::parse(raw)?;
+        if let Err(rejection) = chain.push(header) {
+            eprintln!(
+                "❌ Header {} ({}) rejected: {:?}",
+                height,
+                header.hash_hex(),
+                rejection
+            );
+            divergences.push(HeaderDivergence {
+                height: height as u64,
+                hash: header.hash_hex(),
+                rejection,
+                branch_status: None,
+            });
+            // Later headers build on this one; nothing after it can be checked meaningfully
+            break;
+        }
+    }
+
+    // Branches Core considers valid headers must pass the same rules
+    let mut forks_checked = 0;
+    let tips = client.getchaintips().await?;
+    for tip in tips.as_array().into_iter().flatten() {
+        let status = tip.get("status").and_then(Value::as_str).unwrap_or("");
+        if !matches!(status, "valid-fork" | "valid-headers" | "headers-only") {
+            continue;
+        }
+        let (Some(hash), Some(branch_len)) = (
+            tip.get("hash").and_then(Value::as_str),
+            tip.get("branchlen").and_then(Value::as_u64),
+        ) else {
+            continue;
+        };
+        let branch = fetch_branch(client, hash, branch_len).await?;
+        let Some(&(first_height, _)) = branch.first() else {
+            continue;
+        };
+        if first_height as usize > chain.len() {
+            // Forks off above a rejected active-chain header
+            continue;
+        }
+        forks_checked += 1;
+        let mut fork = HeaderChain::new(chain.params.clone());
+        fork.headers = chain.headers[..first_height as usize].to_vec();
+        for (height, header) in branch {
+            if let Err(rejection) = fork.check(&header, height as usize) {
+                divergences.push(HeaderDivergence {
+                    height,
+                    hash: header.hash_hex(),
+                    rejection,
+                    branch_status: Some(status.to_string()),
+                });
+                break;
+            }
+            fork.headers.push(header);
+        }
+    }
+
+    let tip = chain.tip().context("No headers validated")?;
+    let report = HeaderSyncReport {
+        network: network.as_str().to_string(),
+        headers_validated: chain.len() as u64,
+        tip_height: chain.len() as u64 - 1,
+        tip_hash: tip.hash_hex(),
+        chainwork: chain.chainwork_hex(),
+        core_tip_height,
+        core_tip_hash,
+        core_chainwork,
+        forks_checked,
+        divergences,
+        duration_secs: start.elapsed().as_secs_f64(),
+    };
+    if report.matches() {
+        println!(
+            "✅ {} headers valid; tip {} and chainwork {} match Core ({:.1}s)",
+            report.headers_validated, report.tip_hash, report.chainwork, report.duration_secs
+        );
+    } else {
+        println!(
+            "❌ Header sync mismatch: tip {}@{} vs Core {}@{}, chainwork {} vs {}, {} rejected",
+            report.tip_hash,
+            report.tip_height,
+            report.core_tip_hash,
+            report.core_tip_height,
+            report.chainwork,
+            report.core_chainwork,
+            report.divergences.len()
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const BLOCK_1: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    #[test]
+    fn compact_round_trip_and_work() {
+        let target = U256::from_compact(0x1d00ffff).unwrap();
+        assert_eq!(target.to_compact(), 0x1d00ffff);
+        // Difficulty-1 block: 0x100010001 expected hashes (Core's chainwork after genesis)
+        assert_eq!(target.work(), U256::from_u64(0x1_0001_0001));
+        assert!(U256::from_compact(0x04923456).is_none());
+        assert_eq!(
+            U256::from_compact(0x05009234).unwrap().to_compact(),
+            0x05009234
+        );
+        assert_eq!(
+            U256::from_u64(1_000_000).div(U256::from_u64(7)),
+            U256::from_u64(142_857)
+        );
+    }
+
+    #[test]
+    fn first_mainnet_headers_validate_and_accumulate_work() {
+        let mut chain = HeaderChain::new(HeaderParams::for_network(BitcoinNetwork::Mainnet));
+        let genesis = Header::parse(&hex::decode(GENESIS).unwrap()).unwrap();
+        assert_eq!(
+            genesis.hash_hex(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        chain.push(genesis).unwrap();
+        let block_1 = Header::parse(&hex::decode(BLOCK_1).unwrap()).unwrap();
+        let mut bad = block_1;
+        bad.prev_hash[0] ^= 1;
+        assert_eq!(chain.check(&bad, 1), Err(HeaderRejection::BadPrevHash));
+        chain.push(block_1).unwrap();
+        assert_eq!(
+            chain.chainwork_hex(),
+            "0000000000000000000000000000000000000000000000000000000200020002"
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Hybrid block connection: UTXO updates and script checks on separate threads
+//!
+//! `connect_block` does all of a block's work on the calling thread. [`HybridConnector`] splits
+//! it in two: the calling thread walks the transactions in order, resolves each input's prevout
+//! and applies spends and new outputs to the UTXO set, while every input's script check is
+//! handed to a dedicated rayon pool as soon as its prevouts are known. The block is finished when
+//! the pool drains (a scope per block is the completion barrier); a missing prevout or a failed
+//! script rolls the UTXO set back to where it was.
+//!
+//! This is a prototype for measuring what overlapping the two halves buys. It checks prevout
+//! existence and scripts only - no amounts, fees, sigops, weight or BIP30 - so
+//! [`compare_with_connect_block`] runs both paths on the same block and reports verdicts and
+//! timings side by side. `benches/consensus/hybrid_script_pool.rs` does the same on synthetic
+//! P2WPKH blocks across pool sizes.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::{
+    block_validation_context_for_connect_ibd, calculate_script_flags_for_block_network,
+    calculate_tx_id,
+};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::types::{
+    Block, BlockHeader, Network, OutPoint, Transaction, UtxoSet, ValidationResult, UTXO,
+};
+use blvm_protocol::witness::is_witness_empty;
+use blvm_protocol::Witness;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::consensus_compat::connect_block;
+
+/// Where the time of one hybrid block went.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HybridTiming {
+    /// Wall time for the whole block, barrier included
+    pub total: Duration,
+    /// UTXO thread: prevout lookups, spends, new outputs and job hand-off
+    pub utxo_apply: Duration,
+    /// Waiting at the barrier after the last transaction was applied
+    pub barrier_wait: Duration,
+    /// Script checks that ran (checks queued after a failure are skipped)
+    pub script_checks: usize,
+}
+
+/// Connects blocks with script verification farmed out to its own thread pool.
+pub struct HybridConnector {
+    pool: rayon::ThreadPool,
+    network: Network,
+}
+
+impl HybridConnector {
+    /// `script_threads` = 0 leaves one core for the UTXO thread and uses the rest.
+    pub fn new(script_threads: usize, network: Network) -> Result<Self> {
+        let threads = if script_threads == 0 {
+            num_cpus::get().saturating_sub(1).max(1)
+        } else {
+            script_threads
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("blvm-script-{}", i))
+            .build()
+            .context("Failed to build script verification pool")?;
+        Ok(Self { pool, network })
+    }
+
+    pub fn script_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Apply `block` at `height` to `utxo_set`, verifying scripts on the pool. On an invalid
+    /// block `utxo_set` is left unchanged.
+    pub fn connect(
+        &self,
+        block: &Block,
+        witnesses: &[Vec<Witness>],
+        utxo_set: &mut UtxoSet,
+        height: u64,
+    ) -> (ValidationResult, HybridTiming) {
+        let start = Instant::now();
+        let failed = AtomicBool::new(false);
+        let failure: Mutex<Option<String>> = Mutex::new(None);
+        let script_checks = AtomicUsize::new(0);
+        // (outpoint, entry before this block touched it), replayed backwards on failure
+        let mut undo: Vec<(OutPoint, Option<Arc<UTXO>>)> = Vec::new();
+        let mut utxo_apply = Duration::ZERO;
+        let network = self.network;
+
+        let fail = |reason: String| {
+            failed.store(true, Ordering::Relaxed);
+            failure.lock().unwrap().get_or_insert(reason);
+        };
+
+        self.pool.in_place_scope(|scope| {
+            'txs: for (tx_idx, tx) in block.transactions.iter().enumerate() {
+                if failed.load(Ordering::Relaxed) {
+                    break;
+                }
+                if tx_idx > 0 {
+                    let mut spent = Vec::with_capacity(tx.inputs.len());
+                    for (input_idx, input) in tx.inputs.iter().enumerate() {
+                        let Some(utxo) = utxo_set.remove(&input.prevout) else {
+                            fail(format!(
+                                "bad-txns-inputs-missingorspent (tx {}, input {})",
+                                tx_idx, input_idx
+                            ));
+                            break 'txs;
+                        };
+                        spent.push(Arc::clone(&utxo));
+                        undo.push((input.prevout.clone(), Some(utxo)));
+                    }
+
+                    let prevouts = Arc::new(TxPrevouts::new(&spent));
+                    let tx_witnesses = witnesses.get(tx_idx).map(Vec::as_slice).unwrap_or(&[]);
+                    let has_witness = tx_witnesses.iter().any(|w| !is_witness_empty(w));
+                    let flags =
+                        calculate_script_flags_for_block_network(tx, has_witness, height, network);
+                    for input_idx in 0..tx.inputs.len() {
+                        let prevouts = Arc::clone(&prevouts);
+                        let (failed, fail, script_checks) = (&failed, &fail, &script_checks);
+                        scope.spawn(move |_| {
+                            if failed.load(Ordering::Relaxed) {
+                                return;
+                            }
+                            script_checks.fetch_add(1, Ordering::Relaxed);
+                            let check = ScriptCheck {
+                                tx,
+                                tx_idx,
+                                input_idx,
+                                witness: tx_witnesses.get(input_idx),
+                                prevouts: &prevouts,
+                                flags,
+                                height,
+                                network,
+                            };
+                            if let Err(reason) = check.run() {
+                                fail(reason);
+                            }
+                        });
+                    }
+                }
+
+                let tx_id = calculate_tx_id(tx);
+                for (index, output) in tx.outputs.iter().enumerate() {
+                    let outpoint = OutPoint {
+                        hash: tx_id,
+                        index: index as _,
+                    };
+                    let utxo = UTXO {
+                        value: output.value,
+                        script_pubkey: output.script_pubkey.clone().into(),
+                        height,
+                        is_coinbase: tx_idx == 0,
+                    };
+                    let previous = utxo_set.insert(outpoint.clone(), Arc::new(utxo));
+                    undo.push((outpoint, previous));
+                }
+            }
+            utxo_apply = start.elapsed();
+        });
+
+        let total = start.elapsed();
+        let timing = HybridTiming {
+            total,
+            utxo_apply,
+            barrier_wait: total.saturating_sub(utxo_apply),
+            script_checks: script_checks.into_inner(),
+        };
+        match failure.into_inner().unwrap() {
+            None => (ValidationResult::Valid, timing),
+            Some(reason) => {
+                for (outpoint, before) in undo.into_iter().rev() {
+                    match before {
+                        Some(utxo) => {
+                            utxo_set.insert(outpoint, utxo);
+                        }
+                        None => {
+                            utxo_set.remove(&outpoint);
+                        }
+                    }
+                }
+                (ValidationResult::Invalid(reason), timing)
+            }
+        }
+    }
+}
+
+/// One transaction's prevouts in the shape the interpreter takes (shared by its input checks).
+struct TxPrevouts {
+    values: Vec<i64>,
+    script_pubkeys: Vec<Vec<u8>>,
+}
+
+impl TxPrevouts {
+    fn new(spent: &[Arc<UTXO>]) -> Self {
+        Self {
+            values: spent.iter().map(|utxo| utxo.value).collect(),
+            script_pubkeys: spent
+                .iter()
+                .map(|utxo| utxo.script_pubkey.as_ref().to_vec())
+                .collect(),
+        }
+    }
+}
+
+struct ScriptCheck<'a> {
+    tx: &'a Transaction,
+    tx_idx: usize,
+    input_idx: usize,
+    witness: Option<&'a Witness>,
+    prevouts: &'a TxPrevouts,
+    flags: u32,
+    height: u64,
+    network: Network,
+}
+
+impl ScriptCheck<'_> {
+    fn run(&self) -> std::result::Result<(), String> {
+        let script_pubkeys: Vec<&[u8]> = self
+            .prevouts
+            .script_pubkeys
+            .iter()
+            .map(|spk| spk.as_slice())
+            .collect();
+        match verify_script_with_context_full(
+            &self.tx.inputs[self.input_idx].script_sig,
+            script_pubkeys[self.input_idx],
+            self.witness,
+            self.flags,
+            self.tx,
+            self.input_idx,
+            &self.prevouts.values,
+            &script_pubkeys,
+            Some(self.height),
+            None,
+            self.network,
+            SigVersion::Base,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!(
+                "script verification failed (tx {}, input {})",
+                self.tx_idx, self.input_idx
+            )),
+            Err(e) => Err(format!(
+                "script error (tx {}, input {}): {:?}",
+                self.tx_idx, self.input_idx, e
+            )),
+        }
+    }
+}
+
+/// The same block through `connect_block` and through a [`HybridConnector`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HybridComparison {
+    pub height: u64,
+    /// Non-coinbase inputs in the block
+    pub inputs: usize,
+    pub connect_block: Duration,
+    pub connect_block_valid: bool,
+    pub hybrid: HybridTiming,
+    pub hybrid_valid: bool,
+}
+
+impl HybridComparison {
+    /// `connect_block` time over hybrid time (> 1 means the hybrid path was faster).
+    pub fn speedup(&self) -> f64 {
+        self.connect_block.as_secs_f64() / self.hybrid.total.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Blocks `connect_block` rejects for anything but a script or a missing prevout (amounts,
+    /// sigops, BIP30) show up here as mismatches; the hybrid path never looks at those.
+    pub fn verdicts_match(&self) -> bool {
+        self.connect_block_valid == self.hybrid_valid
+    }
+}
+
+/// Run `block` through both paths. `utxo_set` advances with `connect_block`'s result (the
+/// authoritative one); UTXO set clones are kept out of both timings.
+pub fn compare_with_connect_block(
+    connector: &HybridConnector,
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    utxo_set: &mut UtxoSet,
+    height: u64,
+) -> HybridComparison {
+    let mut hybrid_set = utxo_set.clone();
+    let (hybrid_result, hybrid) = connector.connect(block, witnesses, &mut hybrid_set, height);
+    drop(hybrid_set);
+
+    let ctx = block_validation_context_for_connect_ibd(
+        None::<&[BlockHeader]>,
+        block.header.timestamp,
+        connector.network,
+    );
+    let input_set = utxo_set.clone();
+    let connect_start = Instant::now();
+    let connect_result = connect_block(block, witnesses, input_set, height, &ctx);
+    let connect_duration = connect_start.elapsed();
+    let connect_block_valid = match connect_result {
+        Ok((result, new_utxo_set)) => {
+            let valid = matches!(result, ValidationResult::Valid);
+            if valid {
+                *utxo_set = new_utxo_set;
+            }
+            valid
+        }
+        Err(_) => false,
+    };
+
+    HybridComparison {
+        height,
+        inputs: block
+            .transactions
+            .iter()
+            .skip(1)
+            .map(|tx| tx.inputs.len())
+            .sum(),
+        connect_block: connect_duration,
+        connect_block_valid,
+        hybrid,
+        hybrid_valid: matches!(hybrid_result, ValidationResult::Valid),
+    }
+}
+
+/// Running totals over many [`HybridComparison`]s.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HybridSummary {
+    pub blocks: u64,
+    pub inputs: u64,
+    pub connect_block: Duration,
+    pub hybrid: Duration,
+    pub utxo_apply: Duration,
+    pub barrier_wait: Duration,
+    /// Heights where the two verdicts differ
+    pub verdict_mismatches: Vec<u64>,
+}
+
+impl HybridSummary {
+    pub fn record(&mut self, comparison: &HybridComparison) {
+        self.blocks += 1;
+        self.inputs += comparison.inputs as u64;
+        self.connect_block += comparison.connect_block;
+        self.hybrid += comparison.hybrid.total;
+        self.utxo_apply += comparison.hybrid.utxo_apply;
+        self.barrier_wait += comparison.hybrid.barrier_wait;
+        if !comparison.verdicts_match() {
+            self.verdict_mismatches.push(comparison.height);
+        }
+    }
+
+    pub fn print(&self, script_threads: usize) {
+        let per_sec = |d: Duration| self.inputs as f64 / d.as_secs_f64().max(f64::EPSILON);
+        println!(
+            "\n⚖️  Hybrid vs connect_block ({} blocks, {} inputs)",
+            self.blocks, self.inputs
+        );
+        println!(
+            "   connect_block:            {:>10.2?}  ({:.0} inputs/s)",
+            self.connect_block,
+            per_sec(self.connect_block)
+        );
+        println!(
+            "   hybrid ({:>2} script thr):  {:>10.2?}  ({:.0} inputs/s)",
+            script_threads,
+            self.hybrid,
+            per_sec(self.hybrid)
+        );
+        println!(
+            "     UTXO thread {:.2?}, barrier wait {:.2?}",
+            self.utxo_apply, self.barrier_wait
+        );
+        if self.blocks > 0 {
+            println!(
+                "   mean latency/block: {:.2?} vs {:.2?}",
+                self.connect_block / self.blocks as u32,
+                self.hybrid / self.blocks as u32
+            );
+        }
+        if !self.verdict_mismatches.is_empty() {
+            println!(
+                "   ⚠️  verdicts differ at {} heights (first: {})",
+                self.verdict_mismatches.len(),
+                self.verdict_mismatches[0]
+            );
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Per-input script differential against `libbitcoinconsensus` (`input-script-diff` feature)
+//!
+//! The block-level differential takes "Core has the block on its chain" as Core's verdict, which
+//! says nothing about individual inputs: a script BLVM accepts for the wrong reason, or under the
+//! wrong flags, passes as long as the block does. [`InputScriptDiffHook`] runs every input of
+//! every block through BLVM's interpreter and through Core's (`libbitcoinconsensus`, in process)
+//! before `connect_block`, and reports each input the two disagree on.
+//!
+//! Each side gets its own flags: BLVM's from `calculate_script_flags_for_block_network`, Core's
+//! from [`core_script_flags`] (Core's `GetBlockScriptFlags` for mainnet, over [`ACTIVATIONS`]),
+//! so a flag schedule difference shows up the same way an interpreter difference does.
+//!
+//! - Taproot spends are skipped on Core's side: `libbitcoinconsensus` can't verify them without
+//!   every spent output. They are counted in [`InputDiffStats::taproot_skipped`].
+//! - Prevouts come from the UTXO set the block is connected against, or from an earlier
+//!   transaction of the same block; a transaction with any other input is counted as unresolved.
+//!
+//! Diverging inputs are logged, kept (the first [`MAX_KEPT_DIVERGENCES`]) and saved as
+//! [`DivergenceArtifact`](crate::sort_merge::divergence::DivergenceArtifact)s for
+//! [`crate::replay`]. The feature registers the hook on every differential run;
+//! `BLVM_INPUT_SCRIPT_DIFF=off` leaves it out.
+
+use anyhow::Result;
+use bitcoinconsensus::{verify_with_flags, VERIFY_ALL_PRE_TAPROOT, VERIFY_P2SH, VERIFY_WITNESS};
+use blvm_protocol::block::{calculate_script_flags_for_block_network, calculate_tx_id};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::types::{Network, Transaction, TransactionOutput};
+use blvm_protocol::witness::is_witness_empty;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::activation_matrix::ACTIVATIONS;
+use crate::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+use crate::sort_merge::divergence::{
+    artifact_limit_from_env, is_taproot, serialize_for_core, DivergenceWriter, FailedInput,
+    DEFAULT_ARTIFACT_LIMIT,
+};
+use crate::validation_hooks::{BlockContext, ValidationHook};
+use crate::wire::{display_hex, sha256d};
+
+/// Diverging inputs kept in full by an [`InputScriptDiffHook`].
+pub const MAX_KEPT_DIVERGENCES: usize = 100;
+
+/// Block whose P2SH spends predate BIP16 enforcement; Core verifies it without any flags.
+const BIP16_EXCEPTION: &str = "00000000000002dc756eebf4f49723ed8d30cc28a5f108eb94b1ba88ac4f9c22";
+
+/// Script flags Core verifies the mainnet block `block_hash` (display order hex) at `height`
+/// with, limited to what `libbitcoinconsensus` accepts (no taproot).
+pub fn core_script_flags(height: u64, block_hash: &str) -> u32 {
+    if block_hash == BIP16_EXCEPTION {
+        return 0;
+    }
+    ACTIVATIONS
+        .iter()
+        .fold(VERIFY_P2SH | VERIFY_WITNESS, |flags, activation| {
+            flags | activation.expected_flags(height)
+        })
+        & VERIFY_ALL_PRE_TAPROOT
+}
+
+/// Display-order hash of the block whose serialization starts with `block_bytes`.
+fn block_hash(block_bytes: &[u8]) -> String {
+    display_hex(&sha256d(&block_bytes[..80.min(block_bytes.len())]))
+}
+
+/// Totals over everything the hook has seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InputDiffStats {
+    /// Non-coinbase inputs verified by BLVM
+    pub inputs: u64,
+    /// Inputs also verified by `libbitcoinconsensus`
+    pub compared: u64,
+    pub divergences: u64,
+    pub taproot_skipped: u64,
+    /// Inputs of transactions with a prevout that could not be found
+    pub unresolved: u64,
+}
+
+/// One input BLVM and Core disagree on.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputDivergence {
+    pub height: u64,
+    /// Display-order txid
+    pub txid: String,
+    pub tx_index: usize,
+    pub input_index: usize,
+    pub blvm_flags: u32,
+    pub core_flags: u32,
+    pub blvm_valid: bool,
+    /// Script error, or why the script returned false
+    pub blvm_error: Option<String>,
+    pub core_error: Option<String>,
+}
+
+/// Verifies every input with both engines before `connect_block`, see the module docs.
+pub struct InputScriptDiffHook {
+    inputs: AtomicU64,
+    compared: AtomicU64,
+    divergences: AtomicU64,
+    taproot_skipped: AtomicU64,
+    unresolved: AtomicU64,
+    kept: Mutex<Vec<InputDivergence>>,
+    artifacts: Option<Mutex<DivergenceWriter>>,
+}
+
+impl InputScriptDiffHook {
+    /// Hook saving diverging inputs with `artifacts`, if given.
+    pub fn new(artifacts: Option<DivergenceWriter>) -> Self {
+        Self {
+            inputs: AtomicU64::new(0),
+            compared: AtomicU64::new(0),
+            divergences: AtomicU64::new(0),
+            taproot_skipped: AtomicU64::new(0),
+            unresolved: AtomicU64::new(0),
+            kept: Mutex::new(Vec::new()),
+            artifacts: artifacts.map(Mutex::new),
+        }
+    }
+
+    /// Artifacts go to the divergences artifact dir if an output dir is configured, else
+    /// `divergences/`; at most `DIVERGENCE_ARTIFACT_LIMIT` of them.
+    pub fn from_env() -> Self {
+        let dir = artifact_dir_from_env(ArtifactKind::Divergences, &ArtifactContext::now())
+            .unwrap_or_else(|| PathBuf::from("divergences"));
+        let limit = artifact_limit_from_env().unwrap_or_else(|e| {
+            eprintln!("⚠️  {:#}, using the default", e);
+            DEFAULT_ARTIFACT_LIMIT
+        });
+        Self::new(Some(DivergenceWriter::new(dir, limit)))
+    }
+
+    /// `false` when `BLVM_INPUT_SCRIPT_DIFF` is `off` or `0`.
+    pub fn enabled_from_env() -> bool {
+        !matches!(
+            std::env::var("BLVM_INPUT_SCRIPT_DIFF").as_deref(),
+            Ok("off") | Ok("0")
+        )
+    }
+
+    pub fn stats(&self) -> InputDiffStats {
+        InputDiffStats {
+            inputs: self.inputs.load(Ordering::Relaxed),
+            compared: self.compared.load(Ordering::Relaxed),
+            divergences: self.divergences.load(Ordering::Relaxed),
+            taproot_skipped: self.taproot_skipped.load(Ordering::Relaxed),
+            unresolved: self.unresolved.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The first [`MAX_KEPT_DIVERGENCES`] diverging inputs, in the order they were found.
+    pub fn divergences(&self) -> Vec<InputDivergence> {
+        self.kept.lock().unwrap().clone()
+    }
+
+    pub fn log_summary(&self) {
+        let stats = self.stats();
+        info!(
+            "🔬 Input scripts: {} inputs, {} compared with libbitcoinconsensus, {} divergences \
+             ({} taproot skipped, {} unresolved)",
+            stats.inputs,
+            stats.compared,
+            stats.divergences,
+            stats.taproot_skipped,
+            stats.unresolved
+        );
+        for d in self.divergences().iter().take(10) {
+            info!(
+                "   {} {}:{} BLVM={} ({:?}, flags {:#x}) Core={} ({:?}, flags {:#x})",
+                d.height,
+                d.txid,
+                d.input_index,
+                d.blvm_valid,
+                d.blvm_error,
+                d.blvm_flags,
+                !d.blvm_valid,
+                d.core_error,
+                d.core_flags
+            );
+        }
+    }
+
+    fn check_transaction(
+        &self,
+        ctx: &BlockContext<'_>,
+        tx_index: usize,
+        earlier_txs: &HashMap<[u8; 32], usize>,
+        core_flags: u32,
+    ) {
+        let tx = &ctx.block.transactions[tx_index];
+        let Some((prevouts, origins)) = resolve_prevouts(ctx, tx, earlier_txs) else {
+            self.unresolved
+                .fetch_add(tx.inputs.len() as u64, Ordering::Relaxed);
+            return;
+        };
+        let witnesses = ctx.witnesses.get(tx_index);
+        let stacks = witnesses.map(Vec::as_slice).unwrap_or(&[]);
+        let has_witness = stacks.iter().any(|w| !is_witness_empty(w));
+        let blvm_flags =
+            calculate_script_flags_for_block_network(tx, has_witness, ctx.height, Network::Mainnet);
+        let values: Vec<i64> = prevouts.iter().map(|o| o.value).collect();
+        let script_pubkeys: Vec<&[u8]> = prevouts
+            .iter()
+            .map(|o| o.script_pubkey.as_slice())
+            .collect();
+        let mut core_tx = None;
+
+        for input_index in 0..tx.inputs.len() {
+            self.inputs.fetch_add(1, Ordering::Relaxed);
+            let (blvm_valid, blvm_error) = match verify_script_with_context_full(
+                &tx.inputs[input_index].script_sig,
+                script_pubkeys[input_index],
+                stacks.get(input_index),
+                blvm_flags,
+                tx,
+                input_index,
+                &values,
+                &script_pubkeys,
+                Some(ctx.height),
+                None,
+                Network::Mainnet,
+                SigVersion::Base,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ) {
+                Ok(valid) => (valid, (!valid).then(|| "script returned false".to_string())),
+                Err(e) => (false, Some(format!("{:?}", e))),
+            };
+            if is_taproot(script_pubkeys[input_index]) {
+                self.taproot_skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let core_bytes = core_tx.get_or_insert_with(|| serialize_for_core(tx, stacks));
+            let core_error = verify_with_flags(
+                script_pubkeys[input_index],
+                values[input_index] as u64,
+                core_bytes,
+                None,
+                input_index,
+                core_flags,
+            )
+            .err()
+            .map(|e| format!("{:?}", e));
+            self.compared.fetch_add(1, Ordering::Relaxed);
+            if blvm_valid == core_error.is_none() {
+                continue;
+            }
+
+            let mut txid = calculate_tx_id(tx);
+            txid.reverse();
+            let divergence = InputDivergence {
+                height: ctx.height,
+                txid: hex::encode(txid),
+                tx_index,
+                input_index,
+                blvm_flags,
+                core_flags,
+                blvm_valid,
+                blvm_error,
+                core_error,
+            };
+            error!(
+                "❌ INPUT DIVERGENCE at height {} tx {} input {}: BLVM={} ({:?}), Core={} ({:?})",
+                divergence.height,
+                divergence.txid,
+                input_index,
+                blvm_valid,
+                divergence.blvm_error,
+                !blvm_valid,
+                divergence.core_error
+            );
+            if let Some(artifacts) = &self.artifacts {
+                let failure = format!(
+                    "BLVM {} / Core {} (BLVM flags {:#x}, Core flags {:#x})",
+                    if blvm_valid { "accepted" } else { "rejected" },
+                    if blvm_valid { "rejected" } else { "accepted" },
+                    blvm_flags,
+                    core_flags
+                );
+                let failed = FailedInput {
+                    network: Network::Mainnet,
+                    height: ctx.height,
+                    median_time_past: None,
+                    tx,
+                    tx_index,
+                    input_index,
+                    prevouts: &prevouts,
+                    prevout_origins: origins.clone(),
+                    witnesses,
+                    flags: blvm_flags,
+                    failure: &failure,
+                };
+                if let Err(e) = artifacts.lock().unwrap().record(&failed) {
+                    warn!("⚠️  Could not save input divergence artifact: {:#}", e);
+                }
+            }
+            self.divergences.fetch_add(1, Ordering::Relaxed);
+            let mut kept = self.kept.lock().unwrap();
+            if kept.len() < MAX_KEPT_DIVERGENCES {
+                kept.push(divergence);
+            }
+        }
+    }
+}
+
+/// The output each input of `tx` spends, with `(height, is_coinbase)` for outputs from the UTXO
+/// set; `None` if any is missing.
+fn resolve_prevouts(
+    ctx: &BlockContext<'_>,
+    tx: &Transaction,
+    earlier_txs: &HashMap<[u8; 32], usize>,
+) -> Option<(Vec<TransactionOutput>, Vec<Option<(u32, bool)>>)> {
+    let mut prevouts = Vec::with_capacity(tx.inputs.len());
+    let mut origins = Vec::with_capacity(tx.inputs.len());
+    for input in &tx.inputs {
+        if let Some(utxo) = ctx.spent_coins.get(&input.prevout) {
+            prevouts.push(TransactionOutput {
+                value: utxo.value,
+                script_pubkey: utxo.script_pubkey.as_ref().to_vec(),
+            });
+            origins.push(
+                u32::try_from(utxo.height)
+                    .ok()
+                    .map(|height| (height, utxo.is_coinbase)),
+            );
+        } else {
+            let &creator = earlier_txs.get(&input.prevout.hash)?;
+            let output = ctx.block.transactions[creator]
+                .outputs
+                .get(input.prevout.index as usize)?;
+            prevouts.push(output.clone());
+            origins.push(None);
+        }
+    }
+    Some((prevouts, origins))
+}
+
+impl ValidationHook for InputScriptDiffHook {
+    fn name(&self) -> &str {
+        "input-scripts"
+    }
+
+    fn pre_block(&self, ctx: &BlockContext<'_>) -> Result<()> {
+        let core_flags = core_script_flags(ctx.height, &block_hash(ctx.block_bytes));
+        let mut earlier_txs = HashMap::with_capacity(ctx.block.transactions.len());
+        for (tx_index, tx) in ctx.block.transactions.iter().enumerate() {
+            if tx_index > 0 {
+                self.check_transaction(ctx, tx_index, &earlier_txs, core_flags);
+            }
+            earlier_txs.insert(calculate_tx_id(tx), tx_index);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_flags_follow_mainnet_deployments() {
+        use bitcoinconsensus::{
+            VERIFY_CHECKLOCKTIMEVERIFY, VERIFY_CHECKSEQUENCEVERIFY, VERIFY_DERSIG, VERIFY_NULLDUMMY,
+        };
+        let height = |name| crate::activation_matrix::activation(name).unwrap().height;
+        let hash = "00".repeat(32);
+        assert_eq!(core_script_flags(1, &hash), VERIFY_P2SH | VERIFY_WITNESS);
+        assert_eq!(core_script_flags(170_060, BIP16_EXCEPTION), 0);
+        assert_eq!(
+            core_script_flags(height("bip66"), &hash),
+            VERIFY_P2SH | VERIFY_WITNESS | VERIFY_DERSIG
+        );
+        assert_eq!(
+            core_script_flags(height("csv") - 1, &hash),
+            VERIFY_P2SH | VERIFY_WITNESS | VERIFY_DERSIG | VERIFY_CHECKLOCKTIMEVERIFY
+        );
+        let segwit = core_script_flags(height("segwit"), &hash);
+        assert_eq!(
+            segwit & (VERIFY_CHECKSEQUENCEVERIFY | VERIFY_NULLDUMMY),
+            VERIFY_CHECKSEQUENCEVERIFY | VERIFY_NULLDUMMY
+        );
+        assert_eq!(segwit & !VERIFY_ALL_PRE_TAPROOT, 0);
+        let genesis = concat!(
+            "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd",
+            "7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c"
+        );
+        assert_eq!(
+            block_hash(&hex::decode(genesis).unwrap()),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+}
@@ -13,12 +13,41 @@ pub use block_cache_env::{
     require_block_cache_subdir, sort_merge_data_dir,
 };
 
+/// Cooperative cancellation for long-running collection / validation APIs
+pub mod cancel;
+/// Content-derived run IDs and resumable per-run artifact directories
+pub mod run_id;
+/// Output directory layout and artifact naming (`--output-dir`, `BLVM_OUTPUT_DIR`)
+pub mod artifacts;
 pub mod deep_analysis;
+/// Runtime performance tuning from `blvm-bench.toml` and env overrides
+pub mod config;
+/// OS-specific paths, subprocess and filesystem helpers (Windows / macOS / Linux)
+pub mod platform;
+/// Allocation counting and RSS sampling per benchmark region (`alloc-profiling`)
+pub mod alloc_profile;
+/// `perf record` + flamegraph SVG per registry benchmark (`blvm-bench run --profile`)
+#[cfg(feature = "flamegraph")]
+pub mod profiling;
+/// SQLite history of benchmark runs and per-commit trend queries (`blvm-bench history`)
+#[cfg(feature = "results-db")]
+pub mod results_db;
+/// io_uring read-ahead behind `platform::open_sequential` (Linux)
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring_reader;
 /// Benchmark utilities and helpers
 pub mod utils;
+/// `sha256d`, display hex, CompactSize and merkle root for raw block parsers and builders
+pub mod wire;
 
 /// Shell benchmark runner
 pub mod shell;
+/// Named benchmarks with setup/teardown, run by name/tag filter into a timing report
+pub mod registry;
+/// Re-run selected benches / differential tests when the local blvm-consensus checkout changes
+pub mod watch;
+/// Criterion results against committed per-machine baselines (`baselines/<profile>.json`)
+pub mod regression;
 
 /// Differential testing modules (feature-gated)
 /// Also available for benchmarks via benchmark-helpers feature
@@ -36,8 +65,46 @@ pub mod core_rpc_client;
 pub mod differential;
 #[cfg(any(feature = "differential", feature = "benchmark-helpers"))]
 pub mod regtest_node;
+/// BIP32 / P2WPKH / P2TR key manager so scenarios can sign without Core's wallet
+#[cfg(feature = "differential")]
+pub mod wallet;
 #[cfg(feature = "differential")]
 pub mod parallel_differential;
+/// Disk-backed live UTXO set with a hot cache for low-memory checkpoint generation
+#[cfg(feature = "differential")]
+pub mod utxo_spill;
+/// Arc-shared UTXO checkpoints with per-chunk overlays for parallel workers
+#[cfg(feature = "differential")]
+pub mod utxo_overlay;
+/// Cost-balanced work items and a shared work queue for parallel differential runs
+#[cfg(feature = "differential")]
+pub mod chunk_schedule;
+/// Adapters over blvm-consensus APIs that differ between branches (shape detected by `build.rs`)
+#[cfg(feature = "differential")]
+pub mod consensus_compat;
+/// Versioned, checksummed on-disk UTXO checkpoints so checkpoint generation can resume
+#[cfg(feature = "differential")]
+pub mod checkpoint_store;
+/// Core `dumptxoutset` (assumeutxo) snapshots loaded and hash-checked as a starting UTXO set
+#[cfg(feature = "differential")]
+pub mod assumeutxo;
+/// Per-block weight, fee, subsidy and sigop statistics recorded during differential runs
+#[cfg(feature = "differential")]
+pub mod block_stats;
+/// Pre-block / post-block / divergence hooks for extra metrics and invariants in a validation pass
+#[cfg(feature = "differential")]
+pub mod validation_hooks;
+/// Prometheus endpoint for blocks/sec, height, UTXO set size, divergences and cache hit rates
+#[cfg(feature = "metrics")]
+pub mod metrics;
+/// tracing setup and indicatif progress bars (bars on a terminal, line logs in CI)
+pub mod progress;
+/// Fixed worker counts, seeded randomness and timing-free reports for reproducible runs
+pub mod determinism;
+/// SIGINT / SIGTERM turned into cancellation, with a synced temp file and resume marker
+pub mod shutdown;
+/// Free-space checks that pause or stop collection and sort-merge before a disk fills
+pub mod disk_watchdog;
 #[cfg(feature = "utxo-snapshot-tools")]
 pub mod checkpoint_persistence;
 #[cfg(any(feature = "utxo-snapshot-tools", feature = "disk-utxo"))]
@@ -45,13 +112,89 @@ pub mod utxo_snapshot_fixed_v1;
 #[cfg(feature = "utxo-snapshot-tools")]
 pub mod utxo_delta;
 #[cfg(feature = "utxo-snapshot-tools")]
+pub mod checkpoint_diff;
+#[cfg(feature = "utxo-snapshot-tools")]
 pub use checkpoint_persistence::CheckpointFormat;
+/// Coin age / coin-days-destroyed per block (filled by the sequential validation pass)
+pub mod coin_age;
+/// Pure XOR / magic-scan framing helpers (fuzzed; used by `block_file_reader`)
+pub mod block_framing;
+/// Parser for Core's `debug.log` (UpdateTip, rejections, `-debug=bench` timings)
+pub mod core_debug_log;
+/// Read-only parser for Bitcoin Core's LevelDB block index (height -> blk file / offset)
+pub mod block_index;
+/// Headers-only scan of out-of-order `blk*.dat` files into a height -> (file, offset) index
+#[cfg(feature = "differential")]
+pub mod header_index;
+/// Persisted per-datadir height -> block location index, reused while the block files are unchanged
+#[cfg(feature = "differential")]
+pub mod location_index;
 #[cfg(feature = "differential")]
 pub mod block_file_reader;
+/// `BlockSource` trait: pluggable block sources for checkpointing and chunk validation
+#[cfg(feature = "differential")]
+pub mod block_source;
+/// Blocks fetched from a node's P2P port (`getheaders` / `getdata`), no RPC needed
+#[cfg(feature = "differential")]
+pub mod p2p_block_source;
+/// Blocks fetched from an Esplora REST API (rate limited, cached), for runs without a node
+#[cfg(feature = "differential")]
+pub mod esplora_source;
+/// Named, hash-verified mainnet blocks and transactions fetched on demand for benchmarks
+#[cfg(feature = "differential")]
+pub mod fixtures;
+/// `getblocktemplate` vs BLVM block assembly: selection, fees, sigops and weight
+#[cfg(feature = "differential")]
+pub mod block_template;
+/// Mutants of real mempool transactions through BLVM and Core mempool acceptance
+#[cfg(feature = "differential")]
+pub mod mempool_fuzz;
+/// Scripted Core verdicts over an in-memory chain, for testing the differential runner offline
+#[cfg(feature = "differential")]
+pub mod mock_core;
+/// Reader for Core's `rev*.dat` undo files (spent prevouts per block, no UTXO rebuild)
+#[cfg(feature = "differential")]
+pub mod rev_file_reader;
+/// Blocks bundled with their spent outputs (`block_<h>.spent` sidecars in the block cache)
+#[cfg(feature = "differential")]
+pub mod prevout_blocks;
+/// Repeated `connect_block` on one block with per-iteration timing, thread and cache sweeps
+#[cfg(feature = "differential")]
+pub mod micro_bench;
+/// Re-run one saved script, block or mempool divergence against BLVM (and optionally a Core node)
+#[cfg(feature = "differential")]
+pub mod replay;
+/// Checks behind the `fuzz/` targets and corpus export from artifacts and fixtures
+#[cfg(feature = "differential")]
+pub mod fuzzing;
+/// Controlled damage to healthy block files, read back through `BlockIterator`'s skip rules
+#[cfg(feature = "differential")]
+pub mod corruption_sim;
+/// Validate new tip blocks as Core publishes them over ZMQ (`rawblock` / `rawtx`)
+#[cfg(feature = "zmq")]
+pub mod zmq_listener;
 pub mod chunk_protection;
+/// Pre-validation block sanity rules shared by reader, cache and validation.
+pub mod sanity;
 pub mod remote_core_rpc;
+/// Core RPC for a `bitcoind` in a docker / podman container or network namespace: discovery,
+/// credentials from its `bitcoin.conf` or cookie, and a loopback forwarder behind `CoreRpcClient`
+#[cfg(feature = "differential")]
+pub mod container_rpc_client;
 #[cfg(feature = "chunk-cache")]
 pub mod chunked_cache;
+/// Transactional store for counts, resume positions and chunk inventory (replaces `.meta` files)
+#[cfg(feature = "chunk-cache")]
+pub mod meta_store;
+/// Per-chunk SHA-256 manifest (`chunks.manifest`) checked before a chunk is opened
+#[cfg(feature = "chunk-cache")]
+pub mod chunk_manifest;
+/// Scratch and archive dirs for chunk files: free-space-aware placement and lookup across tiers
+#[cfg(feature = "chunk-cache")]
+pub mod storage_tiers;
+/// S3-compatible bucket behind the chunk cache: push / pull and on-demand chunk fetches
+#[cfg(feature = "s3-cache")]
+pub mod s3_cache;
 #[cfg(feature = "chunk-cache")]
 pub mod chunk_index;
 #[cfg(feature = "differential")]
@@ -60,6 +203,27 @@ pub mod chunk_index_rpc;
 pub mod missing_blocks;
 #[cfg(feature = "differential")]
 pub mod collect_only;
+/// End-of-run summary (blocks, divergences by severity, stage throughput, memory, disk, artifacts)
+#[cfg(feature = "differential")]
+pub mod run_summary;
+/// `run_state.json` manifest of finished chunks, so killed parallel differential runs resume
+#[cfg(feature = "differential")]
+pub mod run_state;
+/// Checkpoint UTXO count / amount / bogosize against Core's `gettxoutsetinfo`
+#[cfg(feature = "differential")]
+pub mod utxo_stats;
+/// JSON / CSV differential reports (chunks, checkpoint timings, divergences) for CI
+#[cfg(feature = "differential")]
+pub mod report;
+/// Time-budgeted stratified sample of the chain (eras x difficulty bands) with parity confidence
+#[cfg(feature = "differential")]
+pub mod sampling;
+/// Differential runs limited to the blocks around each soft-fork activation height
+#[cfg(feature = "differential")]
+pub mod activation_matrix;
+/// Header-only sync (PoW, continuity, difficulty) compared with Core's tip and chainwork
+#[cfg(feature = "differential")]
+pub mod header_sync;
 // Archived: checkpoint_persistence - not used in sort-merge approach
 // #[cfg(feature = "differential")]
 // pub mod checkpoint_persistence;
@@ -70,8 +234,32 @@ pub mod collect_only;
 pub mod sort_merge;
 #[cfg(feature = "differential")]
 pub mod script_validation;
+/// Prototype connect path: UTXO updates on the caller, script checks on a dedicated pool
+#[cfg(feature = "differential")]
+pub mod hybrid_validation;
+/// Interpreter limit accounting (op count, push size, sigops, stack) checked against BLVM and Core
+#[cfg(feature = "differential")]
+pub mod script_resources;
+/// Script interpreter fuzzing / `script_tests.json` replay against `libbitcoinconsensus`
+#[cfg(feature = "differential")]
+pub mod script_differential;
+/// Core's `tx_valid` / `tx_invalid` / `script_tests` / `sighash` JSON vectors as a pass/fail matrix
+#[cfg(feature = "differential")]
+pub mod conformance;
+/// Legacy / BIP143 / BIP341 sighash references checked against BLVM and Core, and quadratic timings
+#[cfg(feature = "differential")]
+pub mod sighash_diff;
+/// Per-input BLVM vs `libbitcoinconsensus` verdicts during differential runs
+#[cfg(feature = "input-script-diff")]
+pub mod input_script_diff;
+/// Weird-but-valid historical transactions (SIGHASH_SINGLE bug, non-DER sigs, ...) as a regression corpus
+#[cfg(feature = "differential")]
+pub mod weird_tx_corpus;
 #[cfg(feature = "chunk-cache")]
 pub mod chain_scan;
+/// Calendar date ranges -> heights via header median-time-past (`chunks.times`)
+#[cfg(feature = "chunk-cache")]
+pub mod date_range;
 
 #[cfg(feature = "bitcoinkernel")]
 pub mod bitcoinkernel_ffi;
@@ -91,9 +279,59 @@ pub fn init() -> Result<()> {
     Ok(())
 }
 
-/// Run all benchmarks
-pub fn run_all() -> Result<()> {
+/// Registry with every Criterion target and shell script of this suite
+pub fn default_registry() -> Result<registry::BenchmarkRegistry> {
+    let mut registry = registry::BenchmarkRegistry::new();
+    registry::register_criterion(&mut registry)?;
+    shell::register(&mut registry)?;
+    Ok(registry)
+}
+
+/// Run the registered benchmarks matching `filter` and save their timings to the results directory
+pub fn run_filtered(filter: &registry::BenchFilter) -> Result<registry::RunReport> {
     init()?;
-    // This will be implemented to coordinate all benchmarks
-    Ok(())
+    let registry = default_registry()?;
+    let started = std::time::SystemTime::now();
+    let report = registry.run(filter);
+    report.print_summary();
+    let path = report.save()?;
+    println!("📝 Timings written to {}", path.display());
+    record_history(&report, started);
+    Ok(report)
+}
+
+/// [`run_filtered`] with each benchmark recorded by `perf`: flamegraph SVGs are written to the
+/// results directory next to the timings
+#[cfg(feature = "flamegraph")]
+pub fn run_profiled(filter: &registry::BenchFilter) -> Result<registry::RunReport> {
+    init()?;
+    let registry = default_registry()?;
+    let dir = registry::RunReport::default_dir();
+    let started = std::time::SystemTime::now();
+    let report = registry.run_profiled(filter, &dir.join("profiles"))?;
+    report.print_summary();
+    report.write_json(&dir)?;
+    println!("📝 Timings and profiles written to {}", dir.display());
+    record_history(&report, started);
+    Ok(report)
+}
+
+/// Add the run to the results database; a database problem only warns, the timings are already
+/// on disk
+#[allow(unused_variables)]
+fn record_history(report: &registry::RunReport, started: std::time::SystemTime) {
+    #[cfg(feature = "results-db")]
+    if let Err(e) = results_db::record_run(report, started) {
+        eprintln!("⚠️  Run not recorded in the results database: {:#}", e);
+    }
+}
+
+/// Run all benchmarks: every Criterion target, then the shell suite runner (which runs the
+/// individual scripts itself)
+pub fn run_all() -> Result<()> {
+    let filter = registry::BenchFilter {
+        names: Vec::new(),
+        tags: vec!["criterion".to_string(), "suite".to_string()],
+    };
+    run_filtered(&filter)?.ensure_passed()
 }
@@ -0,0 +1,282 @@
+//! Block location index persisted across runs
+//!
+//! Building the [header index](crate::header_index) of a datadir means scanning every block file.
+//! [`LocationStore`] saves the result, height -> (file, offset, length, encrypted flag, hash),
+//! in a small redb database per datadir. Later runs load it in seconds. [`LocationStore::location`]
+//! also answers a single height without loading the rest.
+//!
+//! The store is keyed by the datadir path and holds a [`Fingerprint`] of the block files (name,
+//! size, mtime). A store whose fingerprint no longer matches, e.g. because Core appended blocks,
+//! is ignored and rebuilt by the next scan.
+//!
+//! Stores live in `BLVM_LOCATION_INDEX` if it names a directory, else under the blvm-bench cache
+//! dir (`~/.cache/blvm-bench/block-locations/`). `BLVM_LOCATION_INDEX=off` disables them.
+
+use anyhow::{Context, Result};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::block_index::BlockLocation;
+use crate::header_index::HeaderChain;
+
+const LOCATIONS: TableDefinition<u64, &[u8]> = TableDefinition::new("locations");
+const RECORDS: TableDefinition<&str, &[u8]> = TableDefinition::new("records");
+
+const FINGERPRINT_KEY: &str = "fingerprint";
+/// Encoded size of a [`StoredLocation`]
+const LOCATION_LEN: usize = 4 + 4 + 4 + 1 + 32;
+
+/// Size and modification time of every block file, in file order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// `(file name, bytes, mtime in ns since the epoch)`
+    pub files: Vec<(String, u64, u64)>,
+}
+
+impl Fingerprint {
+    pub fn of(block_files: &[PathBuf]) -> Result<Self> {
+        let files = block_files
+            .iter()
+            .map(|path| {
+                let meta = std::fs::metadata(path)
+                    .with_context(|| format!("Failed to stat {}", path.display()))?;
+                let mtime = meta
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64);
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                Ok((name, meta.len(), mtime))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { files })
+    }
+}
+
+/// One active-chain block as stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoredLocation {
+    pub location: BlockLocation,
+    /// Serialized block length
+    pub length: u32,
+    /// The file is XOR-obfuscated
+    pub encrypted: bool,
+}
+
+impl StoredLocation {
+    fn encode(&self) -> [u8; LOCATION_LEN] {
+        let mut out = [0u8; LOCATION_LEN];
+        out[0..4].copy_from_slice(&self.location.file.to_le_bytes());
+        out[4..8].copy_from_slice(&self.location.data_pos.to_le_bytes());
+        out[8..12].copy_from_slice(&self.length.to_le_bytes());
+        out[12] = u8::from(self.encrypted);
+        out[13..].copy_from_slice(&self.location.hash);
+        out
+    }
+
+    fn decode(raw: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            raw.len() == LOCATION_LEN,
+            "Stored location is {} bytes, expected {}",
+            raw.len(),
+            LOCATION_LEN
+        );
+        let u32_at = |at: usize| u32::from_le_bytes(raw[at..at + 4].try_into().unwrap());
+        Ok(Self {
+            location: BlockLocation {
+                file: u32_at(0),
+                data_pos: u32_at(4),
+                hash: raw[13..].try_into().unwrap(),
+            },
+            length: u32_at(8),
+            encrypted: raw[12] != 0,
+        })
+    }
+}
+
+/// Directory for location stores: `BLVM_LOCATION_INDEX`, else the cache dir; `None` if disabled.
+pub fn store_dir_from_env() -> Option<PathBuf> {
+    match std::env::var("BLVM_LOCATION_INDEX") {
+        Ok(value) if matches!(value.as_str(), "off" | "0" | "false") => None,
+        Ok(value) if !value.is_empty() => Some(PathBuf::from(value)),
+        _ => dirs::cache_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+            .map(|cache| cache.join("blvm-bench").join("block-locations")),
+    }
+}
+
+/// Store file of `data_dir` inside `store_dir`: named by a hash of the canonical path.
+pub fn store_path(store_dir: &Path, data_dir: &Path) -> PathBuf {
+    let data_dir = data_dir
+        .canonicalize()
+        .unwrap_or_else(|_| data_dir.to_path_buf());
+    let digest = Sha256::digest(data_dir.to_string_lossy().as_bytes());
+    store_dir.join(format!("{}.redb", hex::encode(&digest[..8])))
+}
+
+/// An open location store.
+pub struct LocationStore {
+    db: Database,
+    path: PathBuf,
+}
+
+impl LocationStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let db = Database::create(path)
+            .with_context(|| format!("Failed to open location store {}", path.display()))?;
+        let txn = db.begin_write()?;
+        txn.open_table(LOCATIONS)?;
+        txn.open_table(RECORDS)?;
+        txn.commit()?;
+        Ok(Self {
+            db,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The store of `data_dir` in [`store_dir_from_env`]; `None` if stores are disabled.
+    pub fn for_data_dir(data_dir: &Path) -> Result<Option<Self>> {
+        store_dir_from_env()
+            .map(|dir| Self::open(&store_path(&dir, data_dir)))
+            .transpose()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn fingerprint(&self) -> Result<Option<Fingerprint>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(RECORDS)?;
+        let Some(raw) = table.get(FINGERPRINT_KEY)? else {
+            return Ok(None);
+        };
+        Ok(Some(bincode::deserialize(raw.value())?))
+    }
+
+    /// Every stored location in height order, if the store was saved for `fingerprint`.
+    pub fn load(&self, fingerprint: &Fingerprint) -> Result<Option<Vec<StoredLocation>>> {
+        if self.fingerprint()?.as_ref() != Some(fingerprint) {
+            return Ok(None);
+        }
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(LOCATIONS)?;
+        let mut locations = Vec::with_capacity(table.len()? as usize);
+        for (height, item) in table.iter()?.enumerate() {
+            let (key, raw) = item?;
+            anyhow::ensure!(
+                key.value() == height as u64,
+                "{} has no location for height {}",
+                self.path.display(),
+                height
+            );
+            locations.push(StoredLocation::decode(raw.value())?);
+        }
+        Ok((!locations.is_empty()).then_some(locations))
+    }
+
+    /// Location of one height, without loading the rest.
+    pub fn location(&self, height: u64) -> Result<Option<StoredLocation>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(LOCATIONS)?;
+        table
+            .get(height)?
+            .map(|raw| StoredLocation::decode(raw.value()))
+            .transpose()
+    }
+
+    /// Replace the stored chain with `locations` (height 0 first), saved for `fingerprint`.
+    pub fn save(&self, fingerprint: &Fingerprint, locations: &[StoredLocation]) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        txn.delete_table(LOCATIONS)?;
+        {
+            let mut table = txn.open_table(LOCATIONS)?;
+            for (height, location) in locations.iter().enumerate() {
+                table.insert(height as u64, location.encode().as_slice())?;
+            }
+            txn.open_table(RECORDS)?
+                .insert(FINGERPRINT_KEY, bincode::serialize(fingerprint)?.as_slice())?;
+        }
+        txn.commit()
+            .with_context(|| format!("Failed to commit {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Save the active chain of a header scan.
+    pub fn save_chain(
+        &self,
+        fingerprint: &Fingerprint,
+        chain: &HeaderChain,
+        encrypted: bool,
+    ) -> Result<()> {
+        let locations: Vec<StoredLocation> = chain
+            .blocks
+            .iter()
+            .map(|block| StoredLocation {
+                location: block.location,
+                length: block.size,
+                encrypted,
+            })
+            .collect();
+        self.save(fingerprint, &locations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(height: u8) -> StoredLocation {
+        StoredLocation {
+            location: BlockLocation {
+                file: u32::from(height) / 2,
+                data_pos: 8 + u32::from(height) * 1000,
+                hash: [height; 32],
+            },
+            length: 285 + u32::from(height),
+            encrypted: true,
+        }
+    }
+
+    #[test]
+    fn reloads_only_for_the_same_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocks = dir.path().join("blocks");
+        std::fs::create_dir_all(&blocks).unwrap();
+        let files = vec![blocks.join("blk00000.dat"), blocks.join("blk00001.dat")];
+        for file in &files {
+            std::fs::write(file, [0u8; 16]).unwrap();
+        }
+        let fingerprint = Fingerprint::of(&files).unwrap();
+
+        let path = store_path(&dir.path().join("stores"), dir.path());
+        let locations: Vec<StoredLocation> = (0..5).map(stored).collect();
+        {
+            let store = LocationStore::open(&path).unwrap();
+            assert_eq!(store.load(&fingerprint).unwrap(), None);
+            store.save(&fingerprint, &locations).unwrap();
+            store.save(&fingerprint, &locations[..4]).unwrap();
+        }
+
+        let store = LocationStore::open(&path).unwrap();
+        assert_eq!(
+            store.load(&fingerprint).unwrap().as_deref(),
+            Some(&locations[..4])
+        );
+        assert_eq!(store.location(2).unwrap(), Some(locations[2]));
+        assert_eq!(store.location(4).unwrap(), None);
+
+        // Core appended to the last file
+        std::fs::write(&files[1], [0u8; 32]).unwrap();
+        assert_eq!(store.load(&Fingerprint::of(&files).unwrap()).unwrap(), None);
+    }
+}
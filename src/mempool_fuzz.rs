@@ -0,0 +1,654 @@
+//! Mutation fuzzing of real transactions through BLVM and Core mempool acceptance
+//!
+//! Random transactions mostly fail at deserialization or the first signature; mutants of real
+//! ones get further. [`SourceTx::fetch`] takes a transaction from a source node's mempool and
+//! looks up the coins it spends in the target node's UTXO set (confirmed coins only, so sources
+//! with unconfirmed parents are skipped). [`MutationFuzzer`] applies one to three structured
+//! [`Mutation`]s to it (a flipped sequence or lock time bit, a truncated witness stack, a
+//! replaced script opcode, an output value off by one), and every mutant goes to BLVM's
+//! `accept_to_memory_pool` over those coins and to Core's `testmempoolaccept`.
+//!
+//! Core judges a mutant of a transaction it already holds as a conflict before running any
+//! script, so the target should not have the sources in its mempool: point the source at a
+//! relaying node and the target at a `-blocksonly` one. The unmutated source runs first and is
+//! only fuzzed if Core accepts it. Verdicts are compared as accepted / rejected, reasons are kept
+//! for triage. Each mismatch is saved as a [`MempoolMutantArtifact`] (`mempool_<txid>.json`),
+//! which `blvm-bench replay` runs again.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::calculate_tx_id;
+use blvm_protocol::mempool::{accept_to_memory_pool, Mempool, MempoolResult};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::{Transaction, UtxoSet, UTXO};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::block_template::confirmed_prevout;
+use crate::conformance::{decode_tx, Tally};
+use crate::consensus_compat::insert_utxo;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::script_resources::next_op;
+use crate::sort_merge::divergence::{serialize_for_core, SpentOutput};
+use crate::wire::display_hex;
+
+/// Format version written to each mutant artifact.
+pub const MEMPOOL_ARTIFACT_VERSION: u32 = 1;
+/// Mutations applied to one mutant, at most
+const MAX_MUTATIONS: usize = 3;
+/// Highest defined opcode (`OP_CHECKSIGADD`); replacements are drawn up to it
+const MAX_OPCODE: u8 = 0xba;
+
+/// One structured change to a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mutation {
+    /// nSequence of `input` XORed with a one-bit `mask`
+    Sequence { input: usize, mask: u32 },
+    /// nLockTime XORed with a one-bit `mask`
+    LockTime { mask: u32 },
+    /// Witness stack of `input` cut to its first `keep` items
+    TruncateWitness { input: usize, keep: usize },
+    /// The opcode at `offset` of `input`'s scriptSig, or of its last witness item (the witness
+    /// script of P2WSH / tapscript spends) when the scriptSig is empty, replaced
+    TweakOpcode {
+        input: usize,
+        witness: bool,
+        offset: usize,
+        from: u8,
+        to: u8,
+    },
+    /// Value of `output` moved by one satoshi either way
+    OutputValue { output: usize, delta: i64 },
+}
+
+impl Mutation {
+    /// Kind name, for per-kind totals.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Mutation::Sequence { .. } => "sequence",
+            Mutation::LockTime { .. } => "locktime",
+            Mutation::TruncateWitness { .. } => "truncate_witness",
+            Mutation::TweakOpcode { .. } => "tweak_opcode",
+            Mutation::OutputValue { .. } => "output_value",
+        }
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Mutation::Sequence { input, mask } => write!(f, "sequence[{}] ^ {:#010x}", input, mask),
+            Mutation::LockTime { mask } => write!(f, "locktime ^ {:#010x}", mask),
+            Mutation::TruncateWitness { input, keep } => {
+                write!(f, "witness[{}] cut to {} items", input, keep)
+            }
+            Mutation::TweakOpcode {
+                input,
+                witness,
+                offset,
+                from,
+                to,
+            } => write!(
+                f,
+                "{}[{}] @{}: {:#04x} -> {:#04x}",
+                if witness {
+                    "witness script"
+                } else {
+                    "scriptSig"
+                },
+                input,
+                offset,
+                from,
+                to
+            ),
+            Mutation::OutputValue { output, delta } => write!(f, "value[{}] {:+}", output, delta),
+        }
+    }
+}
+
+/// A mutated transaction and what was done to it.
+#[derive(Debug, Clone)]
+pub struct Mutant {
+    pub tx: Transaction,
+    pub witnesses: Vec<Witness>,
+    pub mutations: Vec<Mutation>,
+}
+
+/// Seeded generator of [`Mutant`]s.
+pub struct MutationFuzzer {
+    rng: StdRng,
+}
+
+impl MutationFuzzer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// One to [`MAX_MUTATIONS`] mutations of `tx` (a non-coinbase transaction); kinds that do
+    /// not apply (no witness, no opcode) are drawn again a few times.
+    pub fn mutate(&mut self, tx: &Transaction, witnesses: &[Witness]) -> Mutant {
+        let mut mutant = Mutant {
+            tx: tx.clone(),
+            witnesses: witnesses.to_vec(),
+            mutations: Vec::new(),
+        };
+        let count = self.rng.gen_range(1..=MAX_MUTATIONS);
+        for _ in 0..4 * MAX_MUTATIONS {
+            if mutant.mutations.len() == count {
+                break;
+            }
+            if let Some(mutation) = self.apply(&mut mutant.tx, &mut mutant.witnesses) {
+                mutant.mutations.push(mutation);
+            }
+        }
+        mutant
+    }
+
+    fn apply(&mut self, tx: &mut Transaction, witnesses: &mut [Witness]) -> Option<Mutation> {
+        let input = self.rng.gen_range(0..tx.inputs.len());
+        match self.rng.gen_range(0..5) {
+            0 => {
+                let mask = 1u32 << self.rng.gen_range(0..32);
+                let sequence = tx.inputs[input].sequence as u32;
+                tx.inputs[input].sequence = (sequence ^ mask) as _;
+                Some(Mutation::Sequence { input, mask })
+            }
+            1 => {
+                let mask = 1u32 << self.rng.gen_range(0..32);
+                tx.lock_time = (tx.lock_time as u32 ^ mask) as _;
+                Some(Mutation::LockTime { mask })
+            }
+            2 => {
+                let stack = witnesses.get_mut(input).filter(|w| !w.is_empty())?;
+                let keep = self.rng.gen_range(0..stack.len());
+                stack.truncate(keep);
+                Some(Mutation::TruncateWitness { input, keep })
+            }
+            3 => {
+                let witness = tx.inputs[input].script_sig.is_empty();
+                let script = if witness {
+                    witnesses.get_mut(input)?.last_mut()?
+                } else {
+                    &mut tx.inputs[input].script_sig
+                };
+                let offsets = opcode_offsets(script);
+                if offsets.is_empty() {
+                    return None;
+                }
+                let offset = offsets[self.rng.gen_range(0..offsets.len())];
+                let from = script[offset];
+                let mut to = from;
+                while to == from {
+                    to = self.rng.gen_range(0..=MAX_OPCODE);
+                }
+                script[offset] = to;
+                Some(Mutation::TweakOpcode {
+                    input,
+                    witness,
+                    offset,
+                    from,
+                    to,
+                })
+            }
+            _ => {
+                let output = self.rng.gen_range(0..tx.outputs.len());
+                let delta = if self.rng.gen_bool(0.5) { 1 } else { -1 };
+                tx.outputs[output].value += delta;
+                Some(Mutation::OutputValue { output, delta })
+            }
+        }
+    }
+}
+
+/// Offset of every opcode in `script`, up to the first truncated push.
+fn opcode_offsets(script: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut pc = 0;
+    loop {
+        let start = pc;
+        match next_op(script, &mut pc) {
+            Some(Ok(_)) => offsets.push(start),
+            _ => return offsets,
+        }
+    }
+}
+
+/// A real transaction with the coins it spends.
+#[derive(Debug, Clone)]
+pub struct SourceTx {
+    /// Display-order txid
+    pub txid: String,
+    pub tx: Transaction,
+    pub witnesses: Vec<Witness>,
+    /// The coin each input spends
+    pub prevouts: Vec<SpentOutput>,
+    /// Height of the target's next block
+    pub height: u64,
+}
+
+impl SourceTx {
+    /// `txid` from `source`, with its coins from `target`'s UTXO set at `height` (its next
+    /// block); `None` when one of them is unconfirmed or already spent.
+    pub async fn fetch(
+        source: &NodeRpcClient,
+        target: &NodeRpcClient,
+        txid: &str,
+        height: u64,
+    ) -> Result<Option<Self>> {
+        let raw = hex::decode(source.getrawtransaction(txid).await?)
+            .with_context(|| format!("Invalid hex for {}", txid))?;
+        let (tx, witnesses) = decode_tx(&raw)?;
+        let mut prevouts = Vec::with_capacity(tx.inputs.len());
+        for input in tx.inputs.iter() {
+            let Some(prevout) = confirmed_prevout(target, &input.prevout, height).await? else {
+                return Ok(None);
+            };
+            let (coin_height, is_coinbase) = prevout.confirmed.unzip();
+            prevouts.push(SpentOutput {
+                value: prevout.value,
+                script_pubkey: hex::encode(&prevout.script_pubkey),
+                height: coin_height.map(|h| h as u32),
+                is_coinbase,
+            });
+        }
+        Ok(Some(Self {
+            txid: txid.to_string(),
+            tx,
+            witnesses,
+            prevouts,
+            height,
+        }))
+    }
+
+    /// The coins as BLVM's UTXO set.
+    pub fn utxo_set(&self) -> Result<UtxoSet> {
+        utxo_set_of(&self.tx, &self.prevouts)
+    }
+}
+
+fn utxo_set_of(tx: &Transaction, prevouts: &[SpentOutput]) -> Result<UtxoSet> {
+    anyhow::ensure!(
+        prevouts.len() == tx.inputs.len(),
+        "{} prevouts for {} inputs",
+        prevouts.len(),
+        tx.inputs.len()
+    );
+    let mut utxo_set = UtxoSet::default();
+    for (input, prevout) in tx.inputs.iter().zip(prevouts) {
+        let utxo = UTXO {
+            value: prevout.value,
+            script_pubkey: hex::decode(&prevout.script_pubkey)
+                .context("Invalid prevout script hex")?
+                .into(),
+            height: prevout.height.unwrap_or(0) as _,
+            is_coinbase: prevout.is_coinbase.unwrap_or(false),
+        };
+        insert_utxo(&mut utxo_set, input.prevout.clone(), utxo);
+    }
+    Ok(utxo_set)
+}
+
+/// BLVM's `accept_to_memory_pool` over `utxo_set` with an empty mempool, for a block at
+/// `height`, as `Valid` / `Invalid(<reason>)`.
+pub fn blvm_verdict(
+    tx: &Transaction,
+    witnesses: &[Witness],
+    utxo_set: &UtxoSet,
+    height: u64,
+) -> String {
+    let mempool = Mempool::default();
+    match accept_to_memory_pool(tx, Some(witnesses), utxo_set, &mempool, height, None) {
+        Ok(MempoolResult::Accepted) => "Valid".to_string(),
+        Ok(MempoolResult::Rejected(reason)) => format!("Invalid({})", reason),
+        Err(e) => format!("Invalid({:?})", e),
+    }
+}
+
+/// Core's `testmempoolaccept` on `tx_hex`, in the same form.
+pub async fn core_verdict(client: &NodeRpcClient, tx_hex: &str) -> Result<String> {
+    let result = client.testmempoolaccept(tx_hex).await?;
+    Ok(match (result.allowed, result.reject_reason) {
+        (true, _) => "Valid".to_string(),
+        (false, reason) => format!("Invalid({})", reason.unwrap_or_default()),
+    })
+}
+
+fn is_valid(verdict: &str) -> bool {
+    verdict == "Valid"
+}
+
+/// A mutant (or source) BLVM and Core disagreed on, with the coins it spends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolMutantArtifact {
+    pub version: u32,
+    /// Height of the block the transaction would go in
+    pub height: u64,
+    /// Display-order txid of the unmutated transaction
+    pub source_txid: String,
+    /// Display-order txid of the mutant
+    pub txid: String,
+    /// As applied, in order; empty when the source itself diverged
+    pub mutations: Vec<String>,
+    /// BIP144 serialization when any input has a witness, else the legacy one
+    pub tx_hex: String,
+    pub prevouts: Vec<SpentOutput>,
+    /// `Valid` or `Invalid(<reason>)`
+    pub blvm: String,
+    pub core: String,
+}
+
+impl MempoolMutantArtifact {
+    pub fn file_name(&self) -> String {
+        format!("mempool_{}.json", self.txid)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let artifact: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        anyhow::ensure!(
+            artifact.version == MEMPOOL_ARTIFACT_VERSION,
+            "{} is mempool artifact version {}, expected {}",
+            path.display(),
+            artifact.version,
+            MEMPOOL_ARTIFACT_VERSION
+        );
+        Ok(artifact)
+    }
+
+    /// Write to `path` ([`write_json_atomic`](crate::utils::write_json_atomic)).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::utils::write_json_atomic(path, self)
+    }
+
+    /// BLVM's verdict on the transaction again.
+    pub fn replay(&self) -> Result<String> {
+        let (tx, witnesses) = decode_tx(&hex::decode(&self.tx_hex).context("Invalid tx hex")?)?;
+        let utxo_set = utxo_set_of(&tx, &self.prevouts)?;
+        Ok(blvm_verdict(&tx, &witnesses, &utxo_set, self.height))
+    }
+}
+
+/// Totals of a run; mismatches are saved as artifacts (up to the run's limit).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MempoolFuzzReport {
+    /// Sources fuzzed
+    pub sources: u64,
+    /// Sources with a coin missing from the target, or rejected by Core unmutated
+    pub skipped: u64,
+    pub mutants: u64,
+    /// Mutants both sides accepted
+    pub accepted: u64,
+    /// Sources and mutants the two sides disagreed on
+    pub mismatch_count: u64,
+    /// Mutants per mutation kind they involve: agreements and mismatches
+    pub by_kind: BTreeMap<String, Tally>,
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl MempoolFuzzReport {
+    pub fn passed(&self) -> bool {
+        self.mismatch_count == 0
+    }
+
+    pub fn print(&self) {
+        println!(
+            "\n🧬 {} mutants of {} transactions ({} skipped): {} accepted by both, {} mismatches",
+            self.mutants, self.sources, self.skipped, self.accepted, self.mismatch_count
+        );
+        for (kind, tally) in &self.by_kind {
+            println!(
+                "   {:<18} {:>7} agree {:>5} mismatch",
+                kind, tally.passed, tally.failed
+            );
+        }
+        for path in &self.artifacts {
+            println!("   ❌ {}", path.display());
+        }
+    }
+}
+
+/// How many sources and mutants, and where mismatches go.
+#[derive(Debug, Clone)]
+pub struct MempoolFuzzConfig {
+    pub seed: u64,
+    /// Source transactions to fuzz
+    pub sources: usize,
+    pub mutants_per_source: u64,
+    /// Where mismatch artifacts are written
+    pub artifact_dir: PathBuf,
+    /// Artifacts written at most
+    pub artifact_limit: usize,
+}
+
+/// Fuzz transactions from `source`'s mempool against `target` (see the module docs).
+pub async fn run_mempool_fuzz(
+    source: &NodeRpcClient,
+    target: &NodeRpcClient,
+    config: &MempoolFuzzConfig,
+) -> Result<MempoolFuzzReport> {
+    let mut fuzzer = MutationFuzzer::new(config.seed);
+    let mut txids = source.getrawmempool().await?;
+    txids.sort();
+    txids.shuffle(&mut fuzzer.rng);
+    let height = target.getblockcount().await? + 1;
+
+    let mut run = FuzzRun {
+        target,
+        config,
+        report: MempoolFuzzReport::default(),
+    };
+    for txid in &txids {
+        if run.report.sources as usize == config.sources {
+            break;
+        }
+        let Some(source_tx) = SourceTx::fetch(source, target, txid, height).await? else {
+            debug!(
+                "Skipping {}: it spends a coin the target does not have",
+                txid
+            );
+            run.report.skipped += 1;
+            continue;
+        };
+        if !run.check_source(&source_tx).await? {
+            run.report.skipped += 1;
+            continue;
+        }
+        run.report.sources += 1;
+        let utxo_set = source_tx.utxo_set()?;
+        for _ in 0..config.mutants_per_source {
+            let mutant = fuzzer.mutate(&source_tx.tx, &source_tx.witnesses);
+            run.check_mutant(&source_tx, &utxo_set, mutant).await?;
+        }
+    }
+    info!(
+        "🧬 {} mutants of {} transactions, {} mismatches",
+        run.report.mutants, run.report.sources, run.report.mismatch_count
+    );
+    Ok(run.report)
+}
+
+struct FuzzRun<'a> {
+    target: &'a NodeRpcClient,
+    config: &'a MempoolFuzzConfig,
+    report: MempoolFuzzReport,
+}
+
+impl FuzzRun<'_> {
+    /// Run the unmutated source; `false` when Core rejects it (nothing to fuzz from).
+    async fn check_source(&mut self, source: &SourceTx) -> Result<bool> {
+        let tx_hex = hex::encode(serialize_for_core(&source.tx, &source.witnesses));
+        let core = core_verdict(self.target, &tx_hex).await?;
+        if !is_valid(&core) {
+            debug!("Skipping {}: Core says {}", source.txid, core);
+            return Ok(false);
+        }
+        let blvm = blvm_verdict(
+            &source.tx,
+            &source.witnesses,
+            &source.utxo_set()?,
+            source.height,
+        );
+        if !is_valid(&blvm) {
+            self.record(source, &source.tx, &[], tx_hex, blvm, core)?;
+        }
+        Ok(true)
+    }
+
+    async fn check_mutant(
+        &mut self,
+        source: &SourceTx,
+        utxo_set: &UtxoSet,
+        mutant: Mutant,
+    ) -> Result<()> {
+        self.report.mutants += 1;
+        let tx_hex = hex::encode(serialize_for_core(&mutant.tx, &mutant.witnesses));
+        let core = core_verdict(self.target, &tx_hex).await?;
+        let blvm = blvm_verdict(&mutant.tx, &mutant.witnesses, utxo_set, source.height);
+        let agree = is_valid(&core) == is_valid(&blvm);
+        if agree && is_valid(&core) {
+            self.report.accepted += 1;
+        }
+        for mutation in &mutant.mutations {
+            let tally = self
+                .report
+                .by_kind
+                .entry(mutation.kind().to_string())
+                .or_default();
+            if agree {
+                tally.passed += 1;
+            } else {
+                tally.failed += 1;
+            }
+        }
+        if !agree {
+            self.record(source, &mutant.tx, &mutant.mutations, tx_hex, blvm, core)?;
+        }
+        Ok(())
+    }
+
+    fn record(
+        &mut self,
+        source: &SourceTx,
+        tx: &Transaction,
+        mutations: &[Mutation],
+        tx_hex: String,
+        blvm: String,
+        core: String,
+    ) -> Result<()> {
+        self.report.mismatch_count += 1;
+        let artifact = MempoolMutantArtifact {
+            version: MEMPOOL_ARTIFACT_VERSION,
+            height: source.height,
+            source_txid: source.txid.clone(),
+            txid: display_hex(&calculate_tx_id(tx)),
+            mutations: mutations.iter().map(Mutation::to_string).collect(),
+            tx_hex,
+            prevouts: source.prevouts.clone(),
+            blvm,
+            core,
+        };
+        warn!(
+            "  ⚠️  {} ({:?}): blvm {}, core {}",
+            artifact.txid, artifact.mutations, artifact.blvm, artifact.core
+        );
+        if self.report.artifacts.len() >= self.config.artifact_limit {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.config.artifact_dir)
+            .with_context(|| format!("Failed to create {}", self.config.artifact_dir.display()))?;
+        let path = self.config.artifact_dir.join(artifact.file_name());
+        artifact.save(&path)?;
+        self.report.artifacts.push(path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blvm_protocol::{tx_inputs, tx_outputs, OutPoint, TransactionInput, TransactionOutput};
+
+    #[test]
+    fn mutations_and_artifacts() {
+        let tx = Transaction {
+            version: 2,
+            inputs: tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [3; 32],
+                    index: 1,
+                },
+                // <3 bytes> OP_DROP OP_1
+                script_sig: vec![0x03, 0xaa, 0xbb, 0xcc, 0x75, 0x51],
+                sequence: 0xffff_fffd,
+            }],
+            outputs: tx_outputs![TransactionOutput {
+                value: 9_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        assert_eq!(opcode_offsets(&tx.inputs[0].script_sig), vec![0, 4, 5]);
+        assert_eq!(opcode_offsets(&[0x4c]), Vec::<usize>::new());
+
+        let witnesses = vec![Witness::default()];
+        let mut fuzzer = MutationFuzzer::new(7);
+        let mut kinds = std::collections::BTreeSet::new();
+        for _ in 0..200 {
+            let mutant = fuzzer.mutate(&tx, &witnesses);
+            assert!((1..=MAX_MUTATIONS).contains(&mutant.mutations.len()));
+            for mutation in &mutant.mutations {
+                kinds.insert(mutation.kind());
+                if let Mutation::TweakOpcode { offset, to, .. } = *mutation {
+                    assert!([0, 4, 5].contains(&offset) && to <= MAX_OPCODE);
+                }
+            }
+        }
+        // No witness to truncate; every other kind is drawn
+        assert_eq!(
+            kinds.into_iter().collect::<Vec<_>>(),
+            ["locktime", "output_value", "sequence", "tweak_opcode"]
+        );
+        assert_eq!(
+            Mutation::OutputValue {
+                output: 0,
+                delta: -1
+            }
+            .to_string(),
+            "value[0] -1"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = MempoolMutantArtifact {
+            version: MEMPOOL_ARTIFACT_VERSION,
+            height: 900_000,
+            source_txid: "aa".repeat(32),
+            txid: "bb".repeat(32),
+            mutations: vec!["locktime ^ 0x00000001".to_string()],
+            tx_hex: hex::encode(serialize_for_core(&tx, &witnesses)),
+            prevouts: vec![SpentOutput {
+                value: 10_000,
+                script_pubkey: "51".to_string(),
+                height: Some(899_000),
+                is_coinbase: Some(false),
+            }],
+            blvm: "Valid".to_string(),
+            core: "Invalid(non-final)".to_string(),
+        };
+        let path = dir.path().join(artifact.file_name());
+        artifact.save(&path).unwrap();
+        assert_eq!(MempoolMutantArtifact::load(&path).unwrap(), artifact);
+        assert_eq!(
+            utxo_set_of(&tx, &artifact.prevouts).unwrap().len(),
+            tx.inputs.len()
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Collection / chunk-cache metadata store
+//!
+//! One small redb database (`blvm-meta.redb`) per directory replaces the ad-hoc sidecar files
+//! (`<temp>.bin.meta` block counts, `chunks.meta`, `missing_blocks.meta`). It holds:
+//!
+//! - `counts`: block counts (temp file contents, ...)
+//! - `resume_positions`: where an interrupted pass restarts (next block file index, ...)
+//! - `file_states`: per-input-file progress
+//! - `chunks`: chunk inventory (chunk number -> blocks, compressed size)
+//! - `records`: bincode singletons (`chunk_metadata`, `missing_blocks`)
+//!
+//! Updates that belong together (a count and its resume position, a chunk and the metadata
+//! total) go through one [`MetaStore::update`] transaction, so a crash leaves either the old or
+//! the new state and never a count that disagrees with its position.
+//!
+//! redb locks the database file while it is open, so handles are short-lived: open, read or
+//! update, drop. [`MetaStore::open`] waits for another process or thread holding the lock.
+//! Legacy sidecar files are still read when the store has no entry and imported on first use;
+//! `chunks.meta` is also still written as a plain-text export for the shell scripts.
+
+use anyhow::{Context, Result};
+use redb::{Database, DatabaseError, ReadableTable, TableDefinition};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Store file name inside the directory it describes
+pub const STORE_FILE: &str = "blvm-meta.redb";
+
+const COUNTS: TableDefinition<&str, u64> = TableDefinition::new("counts");
+const RESUME: TableDefinition<&str, u64> = TableDefinition::new("resume_positions");
+const FILE_STATES: TableDefinition<&str, &[u8]> = TableDefinition::new("file_states");
+const CHUNKS: TableDefinition<u64, &[u8]> = TableDefinition::new("chunks");
+const RECORDS: TableDefinition<&str, &[u8]> = TableDefinition::new("records");
+
+/// How long [`MetaStore::open`] waits for another holder of the database lock
+const OPEN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Progress through one input file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub size: u64,
+    pub blocks: u64,
+    pub complete: bool,
+}
+
+/// One `chunk_N.bin.zst` in the inventory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    pub chunk_num: u64,
+    pub blocks: u64,
+    pub compressed_bytes: u64,
+}
+
+/// An open store. Drop it promptly; other processes wait on its lock.
+pub struct MetaStore {
+    db: Database,
+    path: PathBuf,
+}
+
+impl MetaStore {
+    /// Open (creating if needed) the store in `dir`, waiting up to a minute for its lock.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(STORE_FILE);
+        let started = Instant::now();
+        let db = loop {
+            match Database::create(&path) {
+                Ok(db) => break db,
+                Err(DatabaseError::DatabaseAlreadyOpen) if started.elapsed() < OPEN_TIMEOUT => {
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to open metadata store {}", path.display())
+                    })
+                }
+            }
+        };
+
+        // Create every table up front so readers never see "table does not exist".
+        let txn = db.begin_write()?;
+        txn.open_table(COUNTS)?;
+        txn.open_table(RESUME)?;
+        txn.open_table(FILE_STATES)?;
+        txn.open_table(CHUNKS)?;
+        txn.open_table(RECORDS)?;
+        txn.commit()?;
+
+        Ok(Self { db, path })
+    }
+
+    /// Open the store only if `dir` already has one (read paths on possibly read-only mounts).
+    pub fn open_existing(dir: &Path) -> Result<Option<Self>> {
+        if dir.join(STORE_FILE).exists() {
+            Self::open(dir).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn count(&self, key: &str) -> Result<Option<u64>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(COUNTS)?;
+        Ok(table.get(key)?.map(|v| v.value()))
+    }
+
+    pub fn resume_position(&self, key: &str) -> Result<Option<u64>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(RESUME)?;
+        Ok(table.get(key)?.map(|v| v.value()))
+    }
+
+    pub fn file_state(&self, file: &str) -> Result<Option<FileState>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(FILE_STATES)?;
+        let Some(raw) = table.get(file)? else {
+            return Ok(None);
+        };
+        Ok(Some(bincode::deserialize(raw.value())?))
+    }
+
+    pub fn record<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(RECORDS)?;
+        let Some(raw) = table.get(key)? else {
+            return Ok(None);
+        };
+        let value = bincode::deserialize(raw.value()).with_context(|| {
+            format!(
+                "Failed to decode record '{}' in {}",
+                key,
+                self.path.display()
+            )
+        })?;
+        Ok(Some(value))
+    }
+
+    /// Chunk inventory in chunk-number order.
+    pub fn chunks(&self) -> Result<Vec<ChunkEntry>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(CHUNKS)?;
+        let mut entries = Vec::new();
+        for item in table.iter()? {
+            let (_, raw) = item?;
+            entries.push(bincode::deserialize(raw.value())?);
+        }
+        Ok(entries)
+    }
+
+    /// Apply several updates atomically; nothing is written if `f` fails.
+    pub fn update<R>(&self, f: impl FnOnce(&MetaTxn<'_>) -> Result<R>) -> Result<R> {
+        let txn = self.db.begin_write()?;
+        let out = match f(&MetaTxn { txn: &txn }) {
+            Ok(out) => out,
+            Err(e) => {
+                let _ = txn.abort();
+                return Err(e);
+            }
+        };
+        txn.commit()
+            .with_context(|| format!("Failed to commit {}", self.path.display()))?;
+        Ok(out)
+    }
+}
+
+/// Write side of [`MetaStore::update`]
+pub struct MetaTxn<'a> {
+    txn: &'a redb::WriteTransaction,
+}
+
+impl MetaTxn<'_> {
+    pub fn set_count(&self, key: &str, value: u64) -> Result<()> {
+        self.txn.open_table(COUNTS)?.insert(key, value)?;
+        Ok(())
+    }
+
+    pub fn set_resume_position(&self, key: &str, value: u64) -> Result<()> {
+        self.txn.open_table(RESUME)?.insert(key, value)?;
+        Ok(())
+    }
+
+    pub fn set_file_state(&self, file: &str, state: &FileState) -> Result<()> {
+        let raw = bincode::serialize(state)?;
+        self.txn
+            .open_table(FILE_STATES)?
+            .insert(file, raw.as_slice())?;
+        Ok(())
+    }
+
+    pub fn put_chunk(&self, entry: &ChunkEntry) -> Result<()> {
+        let raw = bincode::serialize(entry)?;
+        self.txn
+            .open_table(CHUNKS)?
+            .insert(entry.chunk_num, raw.as_slice())?;
+        Ok(())
+    }
+
+    pub fn put_record<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let raw = bincode::serialize(value)?;
+        self.txn.open_table(RECORDS)?.insert(key, raw.as_slice())?;
+        Ok(())
+    }
+}
+
+fn temp_key(temp_file: &Path) -> String {
+    let name = temp_file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("temp_blocks/{}", name)
+}
+
+fn store_dir(file: &Path) -> &Path {
+    file.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Legacy `<temp>.bin.meta`: u64 LE, or ASCII from older runs.
+fn read_legacy_temp_count(temp_file: &Path) -> Option<u64> {
+    let data = std::fs::read(temp_file.with_extension("bin.meta")).ok()?;
+    if data.len() == 8 {
+        return Some(u64::from_le_bytes(data.try_into().ok()?));
+    }
+    std::str::from_utf8(&data).ok()?.trim().parse().ok()
+}
+
+/// Blocks recorded for a collection temp file (store first, then legacy `.bin.meta`, which is
+/// imported into the store when found).
+pub fn temp_block_count(temp_file: &Path) -> Option<u64> {
+    let dir = store_dir(temp_file);
+    let key = temp_key(temp_file);
+    if let Ok(Some(store)) = MetaStore::open_existing(dir) {
+        if let Ok(Some(count)) = store.count(&key) {
+            return Some(count);
+        }
+    }
+    let legacy = read_legacy_temp_count(temp_file)?;
+    if let Err(e) = record_temp_progress(temp_file, legacy, None) {
+        eprintln!("   ⚠️  Warning: Could not import legacy block count: {}", e);
+    }
+    Some(legacy)
+}
+
+/// Block file index an interrupted collection into `temp_file` restarts from.
+pub fn temp_resume_file(temp_file: &Path) -> Option<usize> {
+    let store = MetaStore::open_existing(store_dir(temp_file)).ok()??;
+    store
+        .resume_position(&temp_key(temp_file))
+        .ok()?
+        .map(|idx| idx as usize)
+}
+
+/// Record the block count of `temp_file` and, if known, the next block file to read, in one
+/// transaction. Call after the temp file has been flushed.
+pub fn record_temp_progress(
+    temp_file: &Path,
+    blocks: u64,
+    next_file_idx: Option<usize>,
+) -> Result<()> {
+    let key = temp_key(temp_file);
+    MetaStore::open(store_dir(temp_file))?.update(|txn| {
+        txn.set_count(&key, blocks)?;
+        if let Some(idx) = next_file_idx {
+            txn.set_resume_position(&key, idx as u64)?;
+        }
+        Ok(())
+    })
+}
+
+/// End-of-batch checkpoint for a collection pass: block count, next block file and the state of
+/// every file in the batch, committed together. Call after the temp file has been flushed.
+pub fn record_collection_batch(
+    temp_file: &Path,
+    blocks: u64,
+    next_file_idx: usize,
+    files: &[(&Path, FileState)],
+) -> Result<()> {
+    let key = temp_key(temp_file);
+    MetaStore::open(store_dir(temp_file))?.update(|txn| {
+        txn.set_count(&key, blocks)?;
+        txn.set_resume_position(&key, next_file_idx as u64)?;
+        for (path, state) in files {
+            txn.set_file_state(&path.display().to_string(), state)?;
+        }
+        Ok(())
+    })
+}
+
+/// Add or replace a chunk in the inventory of `chunks_dir`.
+pub fn record_chunk(chunks_dir: &Path, entry: &ChunkEntry) -> Result<()> {
+    MetaStore::open(chunks_dir)?.update(|txn| txn.put_chunk(entry))
+}
+
+/// Chunk inventory of `chunks_dir` (empty if it has no store).
+pub fn chunk_inventory(chunks_dir: &Path) -> Result<Vec<ChunkEntry>> {
+    match MetaStore::open_existing(chunks_dir)? {
+        Some(store) => store.chunks(),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_commit_together_or_not_at_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MetaStore::open(dir.path()).unwrap();
+        store
+            .update(|txn| {
+                txn.set_count("k", 10)?;
+                txn.set_resume_position("k", 3)
+            })
+            .unwrap();
+        let failed: Result<()> = store.update(|txn| {
+            txn.set_count("k", 20)?;
+            anyhow::bail!("crash between writes")
+        });
+        assert!(failed.is_err());
+        assert_eq!(store.count("k").unwrap(), Some(10));
+        assert_eq!(store.resume_position("k").unwrap(), Some(3));
+    }
+
+    #[test]
+    fn legacy_temp_count_is_imported() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = dir.path().join("blocks_ordered.bin");
+        std::fs::write(temp.with_extension("bin.meta"), 1234u64.to_le_bytes()).unwrap();
+        assert_eq!(temp_block_count(&temp), Some(1234));
+        std::fs::remove_file(temp.with_extension("bin.meta")).unwrap();
+        assert_eq!(temp_block_count(&temp), Some(1234));
+
+        record_temp_progress(&temp, 2000, Some(17)).unwrap();
+        assert_eq!(temp_block_count(&temp), Some(2000));
+        assert_eq!(temp_resume_file(&temp), Some(17));
+
+        record_chunk(
+            dir.path(),
+            &ChunkEntry {
+                chunk_num: 1,
+                blocks: 125_000,
+                compressed_bytes: 9,
+            },
+        )
+        .unwrap();
+        assert_eq!(chunk_inventory(dir.path()).unwrap().len(), 1);
+    }
+}
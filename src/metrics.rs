@@ -0,0 +1,270 @@
+//! Prometheus metrics for long-running validation jobs
+//!
+//! Checkpoint generation and differential passes run for days and otherwise only report
+//! progress through their log. With the `metrics` feature, `BLVM_METRICS_ADDR=0.0.0.0:9898`
+//! serves the process-wide [`Metrics`] at `http://<addr>/metrics` in Prometheus text format:
+//! blocks processed and blocks/sec, highest height, UTXO set size, divergences, and hit / miss
+//! counts per cache. Point a Prometheus scrape job at it to chart a run in Grafana.
+//!
+//! Validation, checkpoint generation and the block caches record into [`global`] directly.
+//! Without `BLVM_METRICS_ADDR` nothing listens and recording only bumps the counters.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Listen address from `BLVM_METRICS_ADDR`.
+pub fn addr_from_env() -> Option<String> {
+    std::env::var("BLVM_METRICS_ADDR")
+        .ok()
+        .filter(|a| !a.is_empty())
+}
+
+/// The process-wide metrics every job records into.
+pub fn global() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
+
+/// Counters and gauges of one process.
+#[derive(Debug)]
+pub struct Metrics {
+    blocks: AtomicU64,
+    /// Highest height connected and the UTXO set size after it
+    tip: Mutex<(u64, u64)>,
+    divergences: AtomicU64,
+    /// cache name -> (hits, misses)
+    caches: Mutex<BTreeMap<&'static str, (u64, u64)>>,
+    /// Block count at the last scrape, for blocks/sec between scrapes
+    last_scrape: Mutex<(Instant, u64, f64)>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            blocks: AtomicU64::new(0),
+            tip: Mutex::new((0, 0)),
+            divergences: AtomicU64::new(0),
+            caches: Mutex::new(BTreeMap::new()),
+            last_scrape: Mutex::new((Instant::now(), 0, 0.0)),
+        }
+    }
+}
+
+impl Metrics {
+    /// A block was connected; parallel chunks report out of order (each on its own UTXO set), so
+    /// the height and UTXO set size gauges follow the highest block seen.
+    pub fn record_block(&self, height: u64, utxo_set_size: usize) {
+        self.blocks.fetch_add(1, Ordering::Relaxed);
+        let mut tip = self.tip.lock().unwrap();
+        if height >= tip.0 {
+            *tip = (height, utxo_set_size as u64);
+        }
+    }
+
+    pub fn record_divergence(&self) {
+        self.divergences.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A lookup in `cache` (`block_cache`, `checkpoint`, ...) was served from it or not.
+    pub fn record_cache(&self, cache: &'static str, hit: bool) {
+        let mut caches = self.caches.lock().unwrap();
+        let (hits, misses) = caches.entry(cache).or_default();
+        if hit {
+            *hits += 1;
+        } else {
+            *misses += 1;
+        }
+    }
+
+    pub fn blocks(&self) -> u64 {
+        self.blocks.load(Ordering::Relaxed)
+    }
+
+    /// Blocks/sec since the previous scrape (the last value again when scraped twice within a
+    /// second).
+    fn blocks_per_sec(&self) -> f64 {
+        let blocks = self.blocks();
+        let mut last = self.last_scrape.lock().unwrap();
+        let elapsed = last.0.elapsed().as_secs_f64();
+        if elapsed >= 1.0 {
+            *last = (Instant::now(), blocks, (blocks - last.1) as f64 / elapsed);
+        }
+        last.2
+    }
+
+    /// Prometheus text exposition format (version 0.0.4).
+    pub fn render(&self) -> String {
+        let (height, utxo_set_size) = *self.tip.lock().unwrap();
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        metric(
+            "blvm_blocks_processed_total",
+            "counter",
+            "Blocks connected by checkpoint generation and validation",
+            self.blocks().to_string(),
+        );
+        metric(
+            "blvm_blocks_per_second",
+            "gauge",
+            "Blocks connected per second since the previous scrape",
+            format!("{:.3}", self.blocks_per_sec()),
+        );
+        metric(
+            "blvm_current_height",
+            "gauge",
+            "Highest block height connected",
+            height.to_string(),
+        );
+        metric(
+            "blvm_utxo_set_size",
+            "gauge",
+            "UTXO set entries after the highest block connected",
+            utxo_set_size.to_string(),
+        );
+        metric(
+            "blvm_divergences_total",
+            "counter",
+            "Blocks where BLVM and Core disagreed",
+            self.divergences.load(Ordering::Relaxed).to_string(),
+        );
+
+        let caches = self.caches.lock().unwrap();
+        for (name, kind, help) in [
+            (
+                "blvm_cache_hits_total",
+                "counter",
+                "Lookups served from the cache",
+            ),
+            (
+                "blvm_cache_misses_total",
+                "counter",
+                "Lookups the cache could not serve",
+            ),
+            ("blvm_cache_hit_ratio", "gauge", "Hits / lookups"),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (cache, &(hits, misses)) in caches.iter() {
+                let value = match name {
+                    "blvm_cache_hits_total" => hits.to_string(),
+                    "blvm_cache_misses_total" => misses.to_string(),
+                    _ => format!("{:.4}", hits as f64 / (hits + misses).max(1) as f64),
+                };
+                let _ = writeln!(out, "{}{{cache=\"{}\"}} {}", name, cache, value);
+            }
+        }
+        out
+    }
+}
+
+/// Serve [`global`] on `BLVM_METRICS_ADDR`, once per process; `false` when it is not set.
+pub async fn start_from_env() -> Result<bool> {
+    static STARTED: tokio::sync::OnceCell<SocketAddr> = tokio::sync::OnceCell::const_new();
+    let Some(addr) = addr_from_env() else {
+        return Ok(false);
+    };
+    STARTED
+        .get_or_try_init(|| async {
+            let local = serve(&addr, global()).await?;
+            println!("📈 Prometheus metrics on http://{}/metrics", local);
+            Ok::<_, anyhow::Error>(local)
+        })
+        .await?;
+    Ok(true)
+}
+
+/// Bind `addr` and answer `GET /metrics` with `metrics` in a background task; returns the bound
+/// address (useful with port 0).
+pub async fn serve(addr: &str, metrics: &'static Metrics) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint {}", addr))?;
+    let local = listener.local_addr()?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = respond(stream, metrics).await {
+                            eprintln!("⚠️  Metrics request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("⚠️  Metrics endpoint accept failed: {}", e),
+            }
+        }
+    });
+    Ok(local)
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    // The request line is all that matters; one read holds it for any real scraper
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if path == "/metrics" || path.starts_with("/metrics?") {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", "Not found - scrape /metrics\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_prometheus_text() {
+        let metrics: &'static Metrics = Box::leak(Box::default());
+        metrics.record_block(100, 42);
+        metrics.record_block(99, 41);
+        metrics.record_divergence();
+        metrics.record_cache("block_cache", true);
+        metrics.record_cache("block_cache", true);
+        metrics.record_cache("block_cache", true);
+        metrics.record_cache("block_cache", false);
+
+        let addr = serve("127.0.0.1:0", metrics).await.unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        for line in [
+            "# TYPE blvm_blocks_processed_total counter",
+            "blvm_blocks_processed_total 2",
+            "blvm_current_height 100",
+            "blvm_utxo_set_size 42",
+            "blvm_divergences_total 1",
+            "blvm_cache_hits_total{cache=\"block_cache\"} 3",
+            "blvm_cache_hit_ratio{cache=\"block_cache\"} 0.7500",
+        ] {
+            assert!(response.lines().any(|l| l == line), "missing {:?}", line);
+        }
+    }
+}
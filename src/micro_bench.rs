@@ -0,0 +1,353 @@
+//! Repeated validation of a single block
+//!
+//! Chain-wide runs show which blocks are slow; optimising one of them needs the same block
+//! validated over and over with nothing else in the timing. [`run`] takes a [`PrevoutBlock`]
+//! (block plus the outputs it spends, see [`crate::prevout_blocks`]), builds the UTXO set
+//! `connect_block` needs from the sidecar once, then times `connect_block` for a number of
+//! iterations per configuration:
+//!
+//! - thread counts: `connect_block` runs inside a rayon pool of that size, so the parallel script
+//!   checks in blvm-consensus see exactly that many workers
+//! - [`CacheMode::Warm`] reuses the decoded block and lets CPU caches keep whatever the previous
+//!   iteration left; [`CacheMode::Cold`] decodes the block again and sweeps a buffer larger than
+//!   the last-level cache before each iteration
+//!
+//! Each configuration gets warm-up iterations that are not recorded, then per-iteration timings
+//! summarised as min / median / p90 / mean / max.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::{block_validation_context_for_connect_ibd, calculate_tx_id};
+use blvm_protocol::types::{Block, BlockHeader, Network, UtxoSet, ValidationResult, UTXO};
+use blvm_protocol::Witness;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::consensus_compat::{connect_block, insert_utxo};
+use crate::prevout_blocks::PrevoutBlock;
+
+/// Bytes swept between cold iterations; above the LLC of current server parts.
+const CACHE_SWEEP_BYTES: usize = 256 * 1024 * 1024;
+
+/// A block named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicroTarget {
+    Height(u64),
+    /// Display byte order, as block explorers and RPC print it
+    Hash([u8; 32]),
+}
+
+impl std::str::FromStr for MicroTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() == 64 {
+            let bytes = hex::decode(s).context("Invalid block hash hex")?;
+            return Ok(Self::Hash(bytes.try_into().unwrap()));
+        }
+        s.parse()
+            .map(Self::Height)
+            .with_context(|| format!("Expected a height or a 64-char block hash, got {:?}", s))
+    }
+}
+
+/// Whether caches survive from one iteration to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    Warm,
+    Cold,
+}
+
+impl std::str::FromStr for CacheMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "warm" | "on" => Ok(Self::Warm),
+            "cold" | "off" => Ok(Self::Cold),
+            _ => anyhow::bail!("Unknown cache mode {:?} (warm / cold)", s),
+        }
+    }
+}
+
+/// What [`run`] sweeps.
+#[derive(Debug, Clone)]
+pub struct MicroConfig {
+    pub iterations: usize,
+    /// Untimed iterations before each configuration
+    pub warmup: usize,
+    /// rayon pool sizes; 0 = all cores
+    pub threads: Vec<usize>,
+    pub cache_modes: Vec<CacheMode>,
+}
+
+impl Default for MicroConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            warmup: 2,
+            threads: vec![0],
+            cache_modes: vec![CacheMode::Warm],
+        }
+    }
+}
+
+/// A block decoded once with the UTXO set it connects on.
+pub struct MicroBlock {
+    pub height: u64,
+    pub block_bytes: Vec<u8>,
+    pub block: Block,
+    pub witnesses: Vec<Vec<Witness>>,
+    /// Coins the block spends that were created before it
+    pub utxo_set: UtxoSet,
+}
+
+impl MicroBlock {
+    pub fn prepare(prevout_block: &PrevoutBlock) -> Result<Self> {
+        let (block, witnesses) = prevout_block.deserialize()?;
+        // Outputs created earlier in the same block are added by connect_block itself
+        let in_block: HashSet<[u8; 32]> = block.transactions.iter().map(calculate_tx_id).collect();
+        let mut utxo_set = UtxoSet::default();
+        for (tx, spent) in block
+            .transactions
+            .iter()
+            .skip(1)
+            .zip(&prevout_block.spent.txs)
+        {
+            for (input, output) in tx.inputs.iter().zip(spent) {
+                if in_block.contains(&input.prevout.hash) {
+                    continue;
+                }
+                let utxo = UTXO {
+                    value: output.value as _,
+                    script_pubkey: output.script_pubkey.clone().into(),
+                    height: output.height as _,
+                    is_coinbase: output.is_coinbase,
+                };
+                insert_utxo(&mut utxo_set, input.prevout.clone(), utxo);
+            }
+        }
+        Ok(Self {
+            height: prevout_block.height,
+            block_bytes: prevout_block.block_bytes.clone(),
+            block,
+            witnesses,
+            utxo_set,
+        })
+    }
+
+    /// Non-coinbase inputs.
+    pub fn inputs(&self) -> usize {
+        self.block
+            .transactions
+            .iter()
+            .skip(1)
+            .map(|tx| tx.inputs.len())
+            .sum()
+    }
+
+    /// One timed `connect_block`; the UTXO set clone is kept out of the timing.
+    fn connect_once(&self, cache: CacheMode, sweep: &mut [u8]) -> Result<(Duration, bool)> {
+        let decoded;
+        let (block, witnesses) = match cache {
+            CacheMode::Warm => (&self.block, &self.witnesses),
+            CacheMode::Cold => {
+                decoded = blvm_protocol::serialization::block::deserialize_block_with_witnesses(
+                    &self.block_bytes,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to deserialize block: {:?}", e))?;
+                (&decoded.0, &decoded.1)
+            }
+        };
+        let ctx = block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            block.header.timestamp,
+            Network::Mainnet,
+        );
+        let utxo_set = self.utxo_set.clone();
+        if cache == CacheMode::Cold {
+            sweep_caches(sweep);
+        }
+        let start = Instant::now();
+        let (result, after) = connect_block(block, witnesses, utxo_set, self.height, &ctx)?;
+        let elapsed = start.elapsed();
+        drop(after);
+        Ok((elapsed, matches!(result, ValidationResult::Valid)))
+    }
+}
+
+/// Touch every cache line of `buf` so earlier working sets are evicted.
+fn sweep_caches(buf: &mut [u8]) {
+    for byte in buf.iter_mut().step_by(64) {
+        *byte = byte.wrapping_add(1);
+    }
+    std::hint::black_box(buf);
+}
+
+/// Timings of one (threads, cache mode) configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicroRun {
+    pub threads: usize,
+    pub cache: CacheMode,
+    pub iterations: Vec<Duration>,
+    /// Every iteration returned `Valid`
+    pub valid: bool,
+}
+
+impl MicroRun {
+    fn sorted(&self) -> Vec<Duration> {
+        let mut sorted = self.iterations.clone();
+        sorted.sort();
+        sorted
+    }
+
+    /// Nearest-rank percentile, `p` in 0..=100.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let sorted = self.sorted();
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    pub fn mean(&self) -> Duration {
+        let n = self.iterations.len().max(1) as u32;
+        self.iterations.iter().sum::<Duration>() / n
+    }
+
+    pub fn min(&self) -> Duration {
+        self.iterations.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.iterations.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// All configurations of one block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicroReport {
+    pub height: u64,
+    pub block_hash: String,
+    pub block_size: usize,
+    pub transactions: usize,
+    pub inputs: usize,
+    pub runs: Vec<MicroRun>,
+}
+
+impl MicroReport {
+    pub fn print(&self) {
+        println!(
+            "\n🔬 Block {} ({}): {} bytes, {} txs, {} inputs",
+            self.height, self.block_hash, self.block_size, self.transactions, self.inputs
+        );
+        println!(
+            "   {:>7} {:>5} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12}",
+            "threads", "cache", "min", "median", "p90", "mean", "max", "inputs/s"
+        );
+        for run in &self.runs {
+            let median = run.percentile(50.0);
+            println!(
+                "   {:>7} {:>5} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?} {:>12.0}{}",
+                run.threads,
+                format!("{:?}", run.cache).to_lowercase(),
+                run.min(),
+                median,
+                run.percentile(90.0),
+                run.mean(),
+                run.max(),
+                self.inputs as f64 / median.as_secs_f64().max(f64::EPSILON),
+                if run.valid { "" } else { "  ❌ invalid" }
+            );
+        }
+    }
+}
+
+/// Validate `prevout_block` `config.iterations` times per thread count and cache mode.
+pub fn run(prevout_block: &PrevoutBlock, config: &MicroConfig) -> Result<MicroReport> {
+    let micro = MicroBlock::prepare(prevout_block)?;
+    let mut hash = crate::prevout_blocks::block_hash(&micro.block_bytes)?;
+    hash.reverse();
+    let mut sweep = if config.cache_modes.contains(&CacheMode::Cold) {
+        vec![0u8; CACHE_SWEEP_BYTES]
+    } else {
+        Vec::new()
+    };
+
+    let mut runs = Vec::new();
+    for &threads in &config.threads {
+        let threads = if threads == 0 {
+            num_cpus::get()
+        } else {
+            threads
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("blvm-micro-{}", i))
+            .build()
+            .context("Failed to build validation pool")?;
+        for &cache in &config.cache_modes {
+            let run = pool.install(|| -> Result<MicroRun> {
+                for _ in 0..config.warmup {
+                    micro.connect_once(cache, &mut sweep)?;
+                }
+                let mut run = MicroRun {
+                    threads,
+                    cache,
+                    iterations: Vec::with_capacity(config.iterations),
+                    valid: true,
+                };
+                for _ in 0..config.iterations {
+                    let (elapsed, valid) = micro.connect_once(cache, &mut sweep)?;
+                    run.iterations.push(elapsed);
+                    run.valid &= valid;
+                }
+                Ok(run)
+            })?;
+            runs.push(run);
+        }
+    }
+
+    Ok(MicroReport {
+        height: micro.height,
+        block_hash: hex::encode(hash),
+        block_size: micro.block_bytes.len(),
+        transactions: micro.block.transactions.len(),
+        inputs: micro.inputs(),
+        runs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_and_percentiles() {
+        assert_eq!(
+            "840000".parse::<MicroTarget>().unwrap(),
+            MicroTarget::Height(840_000)
+        );
+        let hash = "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054";
+        assert!(matches!(
+            hash.parse::<MicroTarget>().unwrap(),
+            MicroTarget::Hash(h) if h[0] == 0 && h[31] == 0x54
+        ));
+        assert!("not-a-block".parse::<MicroTarget>().is_err());
+        assert_eq!("off".parse::<CacheMode>().unwrap(), CacheMode::Cold);
+
+        let run = MicroRun {
+            threads: 1,
+            cache: CacheMode::Warm,
+            iterations: (1..=10).rev().map(Duration::from_millis).collect(),
+            valid: true,
+        };
+        assert_eq!(run.min(), Duration::from_millis(1));
+        assert_eq!(run.percentile(50.0), Duration::from_millis(5));
+        assert_eq!(run.percentile(90.0), Duration::from_millis(9));
+        assert_eq!(run.percentile(100.0), Duration::from_millis(10));
+        assert_eq!(run.mean(), Duration::from_micros(5500));
+    }
+}
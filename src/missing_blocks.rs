@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::io::{Read, Write, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 use sha2::{Sha256, Digest};
 
 /// Metadata for missing blocks
@@ -25,7 +25,7 @@ pub fn missing_blocks_path(chunks_dir: &Path) -> PathBuf {
     chunks_dir.join("chunk_missing.bin.zst")
 }
 
-/// Path to missing blocks metadata
+/// Path to the legacy missing blocks metadata file (now kept in [`crate::meta_store`])
 pub fn missing_blocks_meta_path(chunks_dir: &Path) -> PathBuf {
     chunks_dir.join("missing_blocks.meta")
 }
@@ -35,8 +35,17 @@ pub fn missing_blocks_cache_path(chunks_dir: &Path) -> PathBuf {
     chunks_dir.join("chunk_missing.bin")
 }
 
-/// Load missing blocks metadata
+/// Metadata-store record holding [`MissingBlocksMeta`]
+const MISSING_BLOCKS_RECORD: &str = "missing_blocks";
+
+/// Load missing blocks metadata (metadata store, then legacy `missing_blocks.meta`)
 pub fn load_missing_blocks_meta(chunks_dir: &Path) -> Result<Option<MissingBlocksMeta>> {
+    if let Some(store) = crate::meta_store::MetaStore::open_existing(chunks_dir)? {
+        if let Some(meta) = store.record(MISSING_BLOCKS_RECORD)? {
+            return Ok(Some(meta));
+        }
+    }
+
     let meta_path = missing_blocks_meta_path(chunks_dir);
     if !meta_path.exists() {
         return Ok(None);
@@ -51,16 +60,18 @@ pub fn load_missing_blocks_meta(chunks_dir: &Path) -> Result<Option<MissingBlock
     Ok(Some(meta))
 }
 
-/// Save missing blocks metadata
+/// Save missing blocks metadata to the metadata store
 pub fn save_missing_blocks_meta(chunks_dir: &Path, meta: &MissingBlocksMeta) -> Result<()> {
+    crate::meta_store::MetaStore::open(chunks_dir)?
+        .update(|txn| txn.put_record(MISSING_BLOCKS_RECORD, meta))
+        .with_context(|| "Failed to save missing blocks metadata")?;
+
+    // Drop the legacy file so it can never shadow a newer store record.
     let meta_path = missing_blocks_meta_path(chunks_dir);
-    
-    let data = bincode::serialize(meta)
-        .with_context(|| "Failed to serialize missing blocks metadata")?;
-    
-    std::fs::write(&meta_path, data)
-        .with_context(|| format!("Failed to write missing blocks metadata: {}", meta_path.display()))?;
-    
+    if meta_path.exists() {
+        std::fs::remove_file(&meta_path).ok();
+    }
+
     Ok(())
 }
 
@@ -87,24 +98,9 @@ pub fn add_missing_block(chunks_dir: &Path, block_data: &[u8]) -> Result<u64> {
     } else if missing_path.exists() {
         // Cache doesn't exist - need to decompress once to create it
         // But this should be rare (only first time)
-        let mut decompressed = Vec::new();
-        let mut zstd_proc = std::process::Command::new("zstd")
-            .arg("-d")
-            .arg("--stdout")
-            .arg(&missing_path)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .context("Failed to start zstd for decompression")?;
-        
-        let stdout = zstd_proc.stdout.take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-        let mut reader = std::io::BufReader::new(stdout);
-        reader.read_to_end(&mut decompressed)
+        let decompressed = zstd::stream::decode_all(std::fs::File::open(&missing_path)?)
             .context("Failed to read decompressed data")?;
         
-        zstd_proc.wait()?;
-        
         // Write to cache for future use
         std::fs::write(&cache_path, &decompressed)
             .context("Failed to write cache file")?;
@@ -121,24 +117,9 @@ pub fn add_missing_block(chunks_dir: &Path, block_data: &[u8]) -> Result<u64> {
             .context("Failed to read cache file")?
     } else if missing_path.exists() {
         // Cache doesn't exist - decompress once and create cache
-        let mut data = Vec::new();
-        let mut zstd_proc = std::process::Command::new("zstd")
-            .arg("-d")
-            .arg("--stdout")
-            .arg(&missing_path)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .context("Failed to start zstd for decompression")?;
-        
-        let stdout = zstd_proc.stdout.take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-        let mut reader = std::io::BufReader::new(stdout);
-        reader.read_to_end(&mut data)
+        let data = zstd::stream::decode_all(std::fs::File::open(&missing_path)?)
             .context("Failed to read decompressed data")?;
         
-        zstd_proc.wait()?;
-        
         // Write to cache for future use
         std::fs::write(&cache_path, &data)
             .context("Failed to write cache file")?;
@@ -168,24 +149,10 @@ pub fn add_missing_block(chunks_dir: &Path, block_data: &[u8]) -> Result<u64> {
             .unwrap_or(true);
     
     if should_compress {
-        // Recompress (but don't block forever - use timeout via spawn and wait with timeout)
-        let mut zstd_proc = std::process::Command::new("zstd")
-            .args(&["-3", "--stdout"])
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::fs::File::create(&missing_path)?)
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .context("Failed to start zstd for compression")?;
-        
-        let mut stdin = zstd_proc.stdin.take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdin"))?;
-        stdin.write_all(&decompressed)
-            .context("Failed to write to zstd stdin")?;
-        drop(stdin);
-        
-        // Don't wait forever - if compression takes too long, skip it
-        // The cache file is sufficient
-        let _ = zstd_proc.wait(); // Ignore errors - cache file is what matters
+        // Recompress; ignore errors - cache file is what matters
+        if let Ok(compressed) = zstd::stream::encode_all(&decompressed[..], 3) {
+            let _ = std::fs::write(&missing_path, compressed);
+        }
     }
     
     Ok(current_offset)
@@ -216,18 +183,8 @@ fn ensure_cache_exists(chunks_dir: &Path) -> Result<()> {
     eprintln!("   🔄 Decompressing chunk_missing.bin.zst to cache (first access or outdated cache)...");
     
     let decompress_start = std::time::Instant::now();
-    let mut zstd_proc = std::process::Command::new("zstd")
-        .arg("-d")
-        .arg("--stdout")
-        .arg(&missing_path)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .context("Failed to start zstd for decompression")?;
-    
-    let stdout = zstd_proc.stdout.take()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-    let mut reader = std::io::BufReader::new(stdout);
+    let mut reader = zstd::stream::read::Decoder::new(std::fs::File::open(&missing_path)?)
+        .context("Failed to start zstd decompression")?;
     
     let mut cache_file = std::fs::File::create(&cache_path)
         .with_context(|| format!("Failed to create cache file: {}", cache_path.display()))?;
@@ -235,8 +192,6 @@ fn ensure_cache_exists(chunks_dir: &Path) -> Result<()> {
     std::io::copy(&mut reader, &mut cache_file)
         .with_context(|| "Failed to copy decompressed data to cache")?;
     
-    zstd_proc.wait()?;
-    
     let decompress_duration = decompress_start.elapsed();
     eprintln!("   ✅ Cache created in {:.2}s", decompress_duration.as_secs_f64());
     
@@ -0,0 +1,271 @@
+//! Programmable stand-in for the Core side of a differential run
+//!
+//! [`MockCore`] is a [`BlockSource`] serving an in-memory chain whose Core verdict is scripted
+//! per height: [`CoreResponse::Valid`], [`Invalid`](CoreResponse::Invalid),
+//! [`Unavailable`](CoreResponse::Unavailable) (no node to ask) or a
+//! [`Delayed`](CoreResponse::Delayed) answer. Each height can take a sequence of responses, one
+//! per query, so repeated queries see different answers. Queries and block fetches are counted,
+//! and fetches can be made to fail, so divergence classification, error propagation and
+//! reporting of [`validate_chunk`](crate::parallel_differential::validate_chunk) can be tested
+//! without a Bitcoin Core installation.
+//!
+//! The runner has no Core RPC trait of its own: it gets Core's verdict through
+//! [`BlockSource::core_has_block`], which the RPC-backed sources implement, so that is the
+//! interface the mock scripts.
+//!
+//! [`synthetic_chain`] builds a mainnet-genesis chain of coinbase-only blocks to serve; BLVM
+//! accepts every block of it, so any divergence in a run is a scripted Core answer.
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::block_source::BlockSource;
+use crate::wire::sha256d;
+
+/// Mainnet genesis block (header, tx count, coinbase).
+const MAINNET_GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+/// What Core answers for one query about a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreResponse {
+    /// The block is in Core's chain
+    Valid,
+    /// Core does not have the block
+    Invalid,
+    /// No Core to ask (the runner then counts the block as valid for Core)
+    Unavailable,
+    /// `response` after `delay`
+    Delayed(Duration, Box<CoreResponse>),
+}
+
+impl CoreResponse {
+    pub fn delayed(delay: Duration, response: CoreResponse) -> Self {
+        Self::Delayed(delay, Box::new(response))
+    }
+}
+
+/// In-memory chain with scripted Core verdicts.
+pub struct MockCore {
+    blocks: Vec<Vec<u8>>,
+    /// RPC-order block hash -> height
+    heights: HashMap<String, u64>,
+    /// Per-height responses, consumed one per query; the last one repeats
+    responses: Mutex<HashMap<u64, VecDeque<CoreResponse>>>,
+    failing_fetches: Mutex<HashSet<u64>>,
+    queries: Mutex<HashMap<u64, u64>>,
+    fetches: AtomicU64,
+    report_tip: bool,
+}
+
+impl MockCore {
+    /// Serve `blocks` (index = height); every block is valid for Core until scripted otherwise.
+    pub fn new(blocks: Vec<Vec<u8>>) -> Self {
+        let heights = blocks
+            .iter()
+            .enumerate()
+            .map(|(height, block)| (rpc_hash(block), height as u64))
+            .collect();
+        Self {
+            blocks,
+            heights,
+            responses: Mutex::new(HashMap::new()),
+            failing_fetches: Mutex::new(HashSet::new()),
+            queries: Mutex::new(HashMap::new()),
+            fetches: AtomicU64::new(0),
+            report_tip: true,
+        }
+    }
+
+    /// Answer `response` for every query about `height`.
+    pub fn respond(self, height: u64, response: CoreResponse) -> Self {
+        self.respond_sequence(height, [response])
+    }
+
+    /// Answer the `responses` in order for successive queries about `height`, then keep
+    /// answering the last one.
+    pub fn respond_sequence(
+        self,
+        height: u64,
+        responses: impl IntoIterator<Item = CoreResponse>,
+    ) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(height, responses.into_iter().collect());
+        self
+    }
+
+    /// Make `get_block(height)` fail.
+    pub fn fail_fetch(self, height: u64) -> Self {
+        self.failing_fetches.lock().unwrap().insert(height);
+        self
+    }
+
+    /// Report no tip height, like a block source without a node behind it.
+    pub fn without_tip(mut self) -> Self {
+        self.report_tip = false;
+        self
+    }
+
+    /// Core queries made about `height`.
+    pub fn queries(&self, height: u64) -> u64 {
+        self.queries
+            .lock()
+            .unwrap()
+            .get(&height)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Core queries made in total.
+    pub fn total_queries(&self) -> u64 {
+        self.queries.lock().unwrap().values().sum()
+    }
+
+    /// Blocks fetched in total (failed fetches included).
+    pub fn fetches(&self) -> u64 {
+        self.fetches.load(Ordering::Relaxed)
+    }
+
+    fn next_response(&self, height: u64) -> CoreResponse {
+        *self.queries.lock().unwrap().entry(height).or_default() += 1;
+        let mut responses = self.responses.lock().unwrap();
+        match responses.get_mut(&height) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some(queue) => queue.front().cloned().unwrap_or(CoreResponse::Valid),
+            None => CoreResponse::Valid,
+        }
+    }
+}
+
+impl BlockSource for MockCore {
+    async fn get_block(&self, height: u64) -> Result<Vec<u8>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        if self.failing_fetches.lock().unwrap().contains(&height) {
+            anyhow::bail!("mock Core: block {} unavailable", height);
+        }
+        self.blocks
+            .get(height as usize)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("mock Core: no block at height {}", height))
+    }
+
+    async fn get_tip_height(&self) -> Result<Option<u64>> {
+        Ok(self
+            .report_tip
+            .then(|| self.blocks.len().saturating_sub(1) as u64))
+    }
+
+    async fn core_has_block(&self, block_hash: &str) -> Option<bool> {
+        // Blocks the mock does not know are not in its chain
+        let Some(&height) = self.heights.get(block_hash) else {
+            return Some(false);
+        };
+        let mut response = self.next_response(height);
+        loop {
+            match response {
+                CoreResponse::Valid => return Some(true),
+                CoreResponse::Invalid => return Some(false),
+                CoreResponse::Unavailable => return None,
+                CoreResponse::Delayed(delay, inner) => {
+                    tokio::time::sleep(delay).await;
+                    response = *inner;
+                }
+            }
+        }
+    }
+}
+
+/// Block hash in RPC (display) byte order.
+fn rpc_hash(block: &[u8]) -> String {
+    let mut hash = sha256d(&block[..80]);
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// Mainnet genesis followed by `len` coinbase-only blocks (BIP34 height push, 50 BTC to
+/// `OP_TRUE`, ten minutes apart). Headers carry genesis difficulty but are not mined.
+pub fn synthetic_chain(len: usize) -> Vec<Vec<u8>> {
+    let mut chain = vec![hex::decode(MAINNET_GENESIS).expect("genesis hex")];
+    for height in 1..=len as u32 {
+        let parent = chain.last().unwrap();
+        let mut coinbase = Vec::new();
+        coinbase.extend_from_slice(&1u32.to_le_bytes());
+        coinbase.push(1);
+        coinbase.extend_from_slice(&[0u8; 32]);
+        coinbase.extend_from_slice(&u32::MAX.to_le_bytes());
+        // Height push plus a tag byte (scriptSig must be 2-100 bytes)
+        coinbase.push(6);
+        coinbase.push(4);
+        coinbase.extend_from_slice(&height.to_le_bytes());
+        coinbase.push(0x51);
+        coinbase.extend_from_slice(&u32::MAX.to_le_bytes());
+        coinbase.push(1);
+        coinbase.extend_from_slice(&5_000_000_000u64.to_le_bytes());
+        coinbase.extend_from_slice(&[1, 0x51]);
+        coinbase.extend_from_slice(&0u32.to_le_bytes());
+
+        let parent_time = u32::from_le_bytes(parent[68..72].try_into().unwrap());
+        let mut block = Vec::with_capacity(80 + 1 + coinbase.len());
+        block.extend_from_slice(&1u32.to_le_bytes());
+        block.extend_from_slice(&sha256d(&parent[..80]));
+        block.extend_from_slice(&sha256d(&coinbase));
+        block.extend_from_slice(&(parent_time + 600).to_le_bytes());
+        block.extend_from_slice(&parent[72..76]);
+        block.extend_from_slice(&height.to_le_bytes());
+        block.push(1);
+        block.extend(coinbase);
+        chain.push(block);
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blvm_protocol::block::{block_validation_context_for_connect_ibd, calculate_tx_id};
+    use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+    use blvm_protocol::types::{BlockHeader, Network, OutPoint, UtxoSet, ValidationResult};
+
+    #[test]
+    fn synthetic_chain_connects_on_mainnet() {
+        let mut utxo_set = UtxoSet::default();
+        let mut coinbases = Vec::new();
+        for (height, bytes) in synthetic_chain(20).iter().enumerate() {
+            let (block, witnesses) = deserialize_block_with_witnesses(bytes).unwrap();
+            let ctx = block_validation_context_for_connect_ibd(
+                None::<&[BlockHeader]>,
+                block.header.timestamp,
+                Network::Mainnet,
+            );
+            let (result, next) = crate::consensus_compat::connect_block(
+                &block,
+                &witnesses,
+                utxo_set,
+                height as u64,
+                &ctx,
+            )
+            .unwrap_or_else(|e| panic!("block {} failed: {:#}", height, e));
+            if let ValidationResult::Invalid(reason) = result {
+                panic!("block {} rejected: {}", height, reason);
+            }
+            utxo_set = next;
+            coinbases.push(calculate_tx_id(&block.transactions[0]));
+        }
+        // Every coinbase after genesis left its OP_TRUE output
+        for (height, txid) in coinbases.iter().enumerate().skip(1) {
+            let outpoint = OutPoint {
+                hash: *txid,
+                index: 0,
+            };
+            assert!(
+                crate::consensus_compat::get_utxo(&utxo_set, &outpoint).is_some(),
+                "coinbase {} missing",
+                height
+            );
+        }
+    }
+}
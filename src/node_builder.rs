@@ -5,6 +5,7 @@
 
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
+use crate::platform::{exe_name, is_executable};
 
 /// Bitcoin node binaries location
 #[derive(Debug, Clone)]
@@ -27,26 +28,15 @@ impl NodeBinaries {
             anyhow::bail!("bitcoin-cli not found at: {}", self.bitcoin_cli.display());
         }
 
-        // Check if executable (Unix-like)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let perms = std::fs::metadata(&self.bitcoind)
-                .context("Failed to read bitcoind metadata")?
-                .permissions();
-            if perms.mode() & 0o111 == 0 {
-                anyhow::bail!("bitcoind is not executable: {}", self.bitcoind.display());
-            }
-
-            let perms = std::fs::metadata(&self.bitcoin_cli)
-                .context("Failed to read bitcoin-cli metadata")?
-                .permissions();
-            if perms.mode() & 0o111 == 0 {
-                anyhow::bail!(
-                    "bitcoin-cli is not executable: {}",
-                    self.bitcoin_cli.display()
-                );
-            }
+        // Check if executable (execute bits on Unix; existence elsewhere)
+        if !is_executable(&self.bitcoind) {
+            anyhow::bail!("bitcoind is not executable: {}", self.bitcoind.display());
+        }
+        if !is_executable(&self.bitcoin_cli) {
+            anyhow::bail!(
+                "bitcoin-cli is not executable: {}",
+                self.bitcoin_cli.display()
+            );
         }
 
         Ok(())
@@ -146,8 +136,8 @@ impl NodeBuilder {
             let entry = entry?;
             let path = entry.path();
             if path.is_dir() {
-                let bitcoind = path.join("bitcoind");
-                let bitcoin_cli = path.join("bitcoin-cli");
+                let bitcoind = path.join(exe_name("bitcoind"));
+                let bitcoin_cli = path.join(exe_name("bitcoin-cli"));
                 if bitcoind.exists() && bitcoin_cli.exists() {
                     let version = path
                         .file_name()
@@ -163,8 +153,8 @@ impl NodeBuilder {
         }
 
         // Check root of cache directory
-        let bitcoind = cache_dir.join("bitcoind");
-        let bitcoin_cli = cache_dir.join("bitcoin-cli");
+        let bitcoind = cache_dir.join(exe_name("bitcoind"));
+        let bitcoin_cli = cache_dir.join(exe_name("bitcoin-cli"));
         if bitcoind.exists() && bitcoin_cli.exists() {
             return Ok(NodeBinaries {
                 bitcoind,
@@ -179,8 +169,8 @@ impl NodeBuilder {
     /// Find Core binaries in Core source/build directory
     fn find_in_core_path(&self, core_path: &Path) -> Result<NodeBinaries> {
         // Try build/bin first (CMake build)
-        let bitcoind = core_path.join("build/bin/bitcoind");
-        let bitcoin_cli = core_path.join("build/bin/bitcoin-cli");
+        let bitcoind = core_path.join("build/bin").join(exe_name("bitcoind"));
+        let bitcoin_cli = core_path.join("build/bin").join(exe_name("bitcoin-cli"));
         if bitcoind.exists() && bitcoin_cli.exists() {
             return Ok(NodeBinaries {
                 bitcoind,
@@ -190,8 +180,8 @@ impl NodeBuilder {
         }
 
         // Try src/ (autotools build)
-        let bitcoind = core_path.join("src/bitcoind");
-        let bitcoin_cli = core_path.join("src/bitcoin-cli");
+        let bitcoind = core_path.join("src").join(exe_name("bitcoind"));
+        let bitcoin_cli = core_path.join("src").join(exe_name("bitcoin-cli"));
         if bitcoind.exists() && bitcoin_cli.exists() {
             return Ok(NodeBinaries {
                 bitcoind,
@@ -201,8 +191,8 @@ impl NodeBuilder {
         }
 
         // Try bin/ (installed)
-        let bitcoind = core_path.join("bin/bitcoind");
-        let bitcoin_cli = core_path.join("bin/bitcoin-cli");
+        let bitcoind = core_path.join("bin").join(exe_name("bitcoind"));
+        let bitcoin_cli = core_path.join("bin").join(exe_name("bitcoin-cli"));
         if bitcoind.exists() && bitcoin_cli.exists() {
             return Ok(NodeBinaries {
                 bitcoind,
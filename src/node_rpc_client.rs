@@ -2,12 +2,29 @@
 //!
 //! This module provides a Rust wrapper around the Bitcoin node RPC interface
 //! for differential testing.
+//!
+//! Credentials are a user / password pair or Core's `.cookie` file, which is read again when the
+//! node restarts and rejects the old one. Every client shares one keep-alive connection pool.
+//! Requests that fail because the node is not up yet, still loading (`RPC_IN_WARMUP`) or busy
+//! (HTTP 503) are retried with exponential backoff.
 
 use anyhow::{Context, Result};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde_json::Value;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Calls per JSON-RPC batch request when `BITCOIN_RPC_BATCH_SIZE` is unset
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+/// Retries of a transient failure when `BITCOIN_RPC_RETRIES` is unset
+pub const DEFAULT_RETRIES: u32 = 5;
+/// First retry delay; doubled per retry up to [`MAX_RETRY_DELAY`]
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+/// Core's `RPC_IN_WARMUP`: loading the block index, verifying blocks, ...
+const RPC_IN_WARMUP: i64 = -28;
 
 fn env_first_non_empty(keys: &[&str]) -> Option<String> {
     for k in keys {
@@ -21,6 +38,52 @@ fn env_first_non_empty(keys: &[&str]) -> Option<String> {
     None
 }
 
+/// `(user, password)` from a Core `.cookie` file (`__cookie__:<password>`).
+pub fn read_cookie(path: &Path) -> Result<(String, String)> {
+    let cookie = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read RPC cookie {}", path.display()))?;
+    let (user, pass) = cookie
+        .trim()
+        .split_once(':')
+        .with_context(|| format!("Malformed RPC cookie {}", path.display()))?;
+    Ok((user.to_string(), pass.to_string()))
+}
+
+/// `.cookie` of the `network` chain in a Core data directory (`testnet3/.cookie`, ...).
+pub fn cookie_path(data_dir: &Path, network: &str) -> PathBuf {
+    let chain_dir = match network {
+        "testnet" | "test" => "testnet3",
+        "testnet4" => "testnet4",
+        "signet" => "signet",
+        "regtest" => "regtest",
+        _ => "",
+    };
+    data_dir.join(chain_dir).join(".cookie")
+}
+
+/// One connection pool for the whole process, so short-lived clients reuse kept-alive
+/// connections instead of reconnecting. Timeouts are set per request.
+fn shared_http_client() -> Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            Client::builder()
+                .pool_max_idle_per_host(16)
+                .pool_idle_timeout(Duration::from_secs(90))
+                .tcp_keepalive(Some(Duration::from_secs(60)))
+                .build()
+                .expect("Failed to create HTTP client")
+        })
+        .clone()
+}
+
+fn string_result(value: Value, method: &str) -> Result<String> {
+    value
+        .as_str()
+        .map(|s| s.to_string())
+        .with_context(|| format!("Invalid {} response (expected a string)", method))
+}
+
 /// RPC client configuration
 #[derive(Debug, Clone)]
 pub struct RpcConfig {
@@ -32,18 +95,25 @@ pub struct RpcConfig {
     pub pass: String,
     /// Request timeout
     pub timeout: Duration,
+    /// Calls per HTTP request in [`NodeRpcClient::batch`]
+    pub batch_size: usize,
+    /// Cookie file `user` / `pass` were read from; read again when the node rejects them
+    pub cookie_file: Option<PathBuf>,
+    /// Retries of transient failures (node starting, warming up or busy)
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub retry_delay: Duration,
 }
 
 impl RpcConfig {
     /// Create from regtest node
     #[cfg(any(feature = "differential", feature = "benchmark-helpers"))]
     pub fn from_regtest_node(node: &crate::regtest_node::RegtestNode) -> Self {
-        Self {
-            url: node.rpc_url(),
-            user: node.rpc_user().to_owned(),
-            pass: node.rpc_pass().to_owned(),
-            timeout: Duration::from_secs(30),
-        }
+        Self::new(
+            node.rpc_url(),
+            node.rpc_user().to_owned(),
+            node.rpc_pass().to_owned(),
+        )
     }
 
     /// Create from environment variables (supports remote nodes)
@@ -53,7 +123,11 @@ impl RpcConfig {
     /// - `BITCOIN_RPC_PORT` (default from `BITCOIN_NETWORK`); if unset, tries `START9_RPC_PORT`, `LAND_NODE_RPC_PORT`
     /// - `BITCOIN_RPC_USER` / `BITCOIN_RPC_PASSWORD` (defaults: "test"); fallbacks include
     ///   `START9_RPC_*`, `LAND_NODE_RPC_*`, `REMOTE_CORE_RPC_*` (same as `remote_core_rpc`)
+    /// - `BITCOIN_RPC_COOKIE` - cookie file to authenticate with instead; without it and without
+    ///   a user / password, the `.cookie` in `BITCOIN_DATA_DIR` is used if there is one
     /// - `BITCOIN_NETWORK` (default: "mainnet") - used to determine default port
+    /// - `BITCOIN_RPC_BATCH_SIZE` (default: 100) - calls per batched request
+    /// - `BITCOIN_RPC_RETRIES` (default: 5) - retries of transient failures
     pub fn from_env() -> Self {
         let rpc_host = env_first_non_empty(&[
             "BITCOIN_RPC_HOST",
@@ -67,8 +141,9 @@ impl RpcConfig {
             "START9_RPC_USER",
             "LAND_NODE_RPC_USER",
             "REMOTE_CORE_RPC_USER",
-        ])
-        .unwrap_or_else(|| "test".to_string());
+        ]);
+        let has_user_pass = rpc_user.is_some();
+        let rpc_user = rpc_user.unwrap_or_else(|| "test".to_string());
 
         let rpc_pass = env_first_non_empty(&[
             "BITCOIN_RPC_PASSWORD",
@@ -97,11 +172,39 @@ impl RpcConfig {
 
         let url = format!("http://{}:{}", rpc_host, rpc_port);
 
-        Self {
-            url,
-            user: rpc_user,
-            pass: rpc_pass,
-            timeout: Duration::from_secs(30),
+        let batch_size = env_first_non_empty(&["BITCOIN_RPC_BATCH_SIZE"])
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_BATCH_SIZE);
+        let retries = env_first_non_empty(&["BITCOIN_RPC_RETRIES"])
+            .and_then(|n| n.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_RETRIES);
+
+        let mut config = Self::new(url, rpc_user, rpc_pass);
+        config.batch_size = batch_size;
+        config.retries = retries;
+
+        let cookie = env_first_non_empty(&["BITCOIN_RPC_COOKIE"])
+            .map(PathBuf::from)
+            .or_else(|| {
+                if has_user_pass {
+                    return None;
+                }
+                let network = std::env::var("BITCOIN_NETWORK").unwrap_or_default();
+                crate::block_cache_env::bitcoin_data_dir_candidates()
+                    .into_iter()
+                    .map(|dir| cookie_path(&dir, &network))
+                    .find(|path| path.is_file())
+            });
+        match cookie {
+            Some(path) => match config.clone().with_cookie_file(&path) {
+                Ok(with_cookie) => with_cookie,
+                Err(e) => {
+                    warn!("⚠️  {:#}; using user / password", e);
+                    config
+                }
+            },
+            None => config,
         }
     }
 
@@ -112,8 +215,34 @@ impl RpcConfig {
             user,
             pass,
             timeout: Duration::from_secs(30),
+            batch_size: DEFAULT_BATCH_SIZE,
+            cookie_file: None,
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
         }
     }
+
+    /// Authenticate with Core's `.cookie` file (read now, and again if the node restarts).
+    pub fn with_cookie_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        (self.user, self.pass) = read_cookie(&path)?;
+        self.cookie_file = Some(path);
+        Ok(self)
+    }
+
+    /// Retry transient failures `retries` times, waiting `delay` before the first retry.
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Calls per batched request; large blocks make `getblock` batches big, so lower it for
+    /// recent heights.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
 }
 
 /// Bitcoin node RPC client
@@ -121,19 +250,127 @@ impl RpcConfig {
 pub struct NodeRpcClient {
     client: Client,
     config: RpcConfig,
+    /// `(user, password)`; replaced when the cookie file is read again
+    auth: Arc<RwLock<(String, String)>>,
+}
+
+/// Why one HTTP attempt failed.
+enum Failure {
+    /// Worth retrying: node not up, warming up or busy, or a re-read cookie
+    Transient(anyhow::Error),
+    Fatal(anyhow::Error),
 }
 
 impl NodeRpcClient {
     /// Create a new RPC client
     pub fn new(config: RpcConfig) -> Self {
-        let client = Client::builder()
-            .timeout(config.timeout)
-            .pool_max_idle_per_host(8)
-            .tcp_keepalive(Some(Duration::from_secs(60)))
-            .build()
-            .expect("Failed to create HTTP client");
+        let auth = Arc::new(RwLock::new((config.user.clone(), config.pass.clone())));
+        Self {
+            client: shared_http_client(),
+            config,
+            auth,
+        }
+    }
 
-        Self { client, config }
+    /// POST a JSON-RPC request (or batch) and return the parsed reply, retrying transient
+    /// failures with exponential backoff.
+    async fn post(&self, body: &Value) -> Result<Value> {
+        let mut retry = 0;
+        loop {
+            let error = match self.post_once(body).await {
+                Ok(reply) => match warmup_message(&reply) {
+                    None => return Ok(reply),
+                    Some(message) => anyhow::anyhow!("Node is warming up: {}", message),
+                },
+                Err(Failure::Transient(e)) => e,
+                Err(Failure::Fatal(e)) => return Err(e),
+            };
+            if retry >= self.config.retries {
+                return Err(error.context(format!(
+                    "RPC request to {} failed after {} retries",
+                    self.config.url, retry
+                )));
+            }
+            let delay = self
+                .config
+                .retry_delay
+                .saturating_mul(1 << retry.min(16))
+                .min(MAX_RETRY_DELAY);
+            retry += 1;
+            if retry == 1 {
+                warn!("⚠️  {:#}; retrying in {:?}", error, delay);
+            } else {
+                debug!("{:#}; retry {} in {:?}", error, retry, delay);
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn post_once(&self, body: &Value) -> std::result::Result<Value, Failure> {
+        let (user, pass) = self.auth.read().unwrap().clone();
+        let response = match self
+            .client
+            .post(&self.config.url)
+            .timeout(self.config.timeout)
+            .basic_auth(user, Some(pass))
+            .json(body)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            // Refused while the node starts, or a pooled connection the node closed
+            Err(e) if (e.is_connect() || e.is_request()) && !e.is_timeout() => {
+                return Err(Failure::Transient(
+                    anyhow::Error::new(e).context("RPC request failed"),
+                ))
+            }
+            Err(e) => {
+                return Err(Failure::Fatal(
+                    anyhow::Error::new(e).context("RPC request failed"),
+                ))
+            }
+        };
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(self.reload_cookie());
+        }
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            return Err(Failure::Transient(anyhow::anyhow!(
+                "RPC request failed with status: {}",
+                status
+            )));
+        }
+        // Core answers RPC errors with a non-2xx status and the error in the body
+        match response.json::<Value>().await {
+            Ok(reply) => Ok(reply),
+            Err(_) if !status.is_success() => Err(Failure::Fatal(anyhow::anyhow!(
+                "RPC request failed with status: {}",
+                status
+            ))),
+            Err(e) => Err(Failure::Fatal(
+                anyhow::Error::new(e).context("Failed to parse RPC response"),
+            )),
+        }
+    }
+
+    /// After a 401: read the cookie file again, worth a retry if the node wrote a new one.
+    fn reload_cookie(&self) -> Failure {
+        let Some(path) = &self.config.cookie_file else {
+            return Failure::Fatal(anyhow::anyhow!("RPC authentication failed (401)"));
+        };
+        match read_cookie(path) {
+            Ok(auth) if auth != *self.auth.read().unwrap() => {
+                *self.auth.write().unwrap() = auth;
+                Failure::Transient(anyhow::anyhow!("RPC cookie {} changed", path.display()))
+            }
+            Ok(_) => Failure::Fatal(anyhow::anyhow!(
+                "RPC authentication with cookie {} failed (401)",
+                path.display()
+            )),
+            // Deleted while the node restarts
+            Err(e) => Failure::Transient(e),
+        }
     }
 
     /// Make an RPC call
@@ -145,24 +382,7 @@ impl NodeRpcClient {
             "id": 1
         });
 
-        let response = self
-            .client
-            .post(&self.config.url)
-            .basic_auth(&self.config.user, Some(&self.config.pass))
-            .json(&body)
-            .send()
-            .await
-            .context("RPC request failed")?;
-
-        let status = response.status();
-        if !status.is_success() {
-            anyhow::bail!("RPC request failed with status: {}", status);
-        }
-
-        let json: Value = response
-            .json()
-            .await
-            .context("Failed to parse RPC response")?;
+        let json = self.post(&body).await?;
 
         if let Some(error) = json.get("error") {
             if !error.is_null() {
@@ -258,6 +478,28 @@ impl NodeRpcClient {
             .context("Invalid getblock response (expected hex string with verbosity=0)")
     }
 
+    /// Block hashes of `heights` via batched `getblockhash`, in the same order.
+    pub async fn getblockhashes(&self, heights: &[u64]) -> Result<Vec<Result<String>>> {
+        let params: Vec<Value> = heights.iter().map(|h| serde_json::json!([h])).collect();
+        Ok(self
+            .call_batch("getblockhash", &params)
+            .await?
+            .into_iter()
+            .map(|r| r.and_then(|v| string_result(v, "getblockhash")))
+            .collect())
+    }
+
+    /// Raw block hex of `hashes` via batched `getblock` verbosity 0, in the same order.
+    pub async fn getblocks_raw(&self, hashes: &[String]) -> Result<Vec<Result<String>>> {
+        let params: Vec<Value> = hashes.iter().map(|h| serde_json::json!([h, 0])).collect();
+        Ok(self
+            .call_batch("getblock", &params)
+            .await?
+            .into_iter()
+            .map(|r| r.and_then(|v| string_result(v, "getblock")))
+            .collect())
+    }
+
     /// `getblockhash` then `getblock` verbosity 0 — one async chain so sync callers use a single `block_on`.
     pub async fn getblock_bytes_at_height(&self, height: u64) -> Result<Vec<u8>> {
         let hash = self.getblockhash(height).await?;
@@ -280,6 +522,78 @@ impl NodeRpcClient {
         }
     }
 
+    /// Hash of the active chain's tip
+    pub async fn getbestblockhash(&self) -> Result<String> {
+        let result = self.call("getbestblockhash", serde_json::json!([])).await?;
+        result
+            .as_str()
+            .map(|s| s.to_string())
+            .context("Invalid getbestblockhash response")
+    }
+
+    /// Unspent output `txid:vout` in the active chain (mempool ignored), `None` when spent or
+    /// unknown
+    pub async fn gettxout(&self, txid: &str, vout: u32) -> Result<Option<Value>> {
+        let params = serde_json::json!([txid, vout, false]);
+        let result = self.call("gettxout", params).await?;
+        Ok((!result.is_null()).then_some(result))
+    }
+
+    /// Block template for the current tip (`segwit` rule set)
+    pub async fn getblocktemplate(&self) -> Result<Value> {
+        let params = serde_json::json!([{ "rules": ["segwit"] }]);
+        self.call("getblocktemplate", params).await
+    }
+
+    /// Txids in the mempool
+    pub async fn getrawmempool(&self) -> Result<Vec<String>> {
+        let result = self.call("getrawmempool", serde_json::json!([])).await?;
+        result
+            .as_array()
+            .context("Invalid getrawmempool response")?
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(|s| s.to_string())
+                    .context("Invalid txid in getrawmempool response")
+            })
+            .collect()
+    }
+
+    /// Raw transaction hex (mempool transactions need no `-txindex`)
+    pub async fn getrawtransaction(&self, txid: &str) -> Result<String> {
+        let params = serde_json::json!([txid, false]);
+        let result = self.call("getrawtransaction", params).await?;
+        result
+            .as_str()
+            .map(|s| s.to_string())
+            .context("Invalid getrawtransaction response")
+    }
+
+    /// Broadcast a raw transaction, returning its txid
+    pub async fn sendrawtransaction(&self, tx_hex: &str) -> Result<String> {
+        let params = serde_json::json!([tx_hex]);
+        let result = self.call("sendrawtransaction", params).await?;
+        result
+            .as_str()
+            .map(|s| s.to_string())
+            .context("Invalid sendrawtransaction response")
+    }
+
+    /// Sign `tx_hex` with the WIF `keys`, spending `prevtxs` (`[{txid, vout, scriptPubKey,
+    /// amount}]`), under `sighash_type` (`ALL`, `SINGLE|ANYONECANPAY`, ...); needs no wallet
+    pub async fn signrawtransactionwithkey(
+        &self,
+        tx_hex: &str,
+        keys: &[String],
+        prevtxs: &Value,
+        sighash_type: &str,
+    ) -> Result<SignRawTransactionResult> {
+        let params = serde_json::json!([tx_hex, keys, prevtxs, sighash_type]);
+        let result = self.call("signrawtransactionwithkey", params).await?;
+        serde_json::from_value(result).context("Invalid signrawtransactionwithkey response")
+    }
+
     /// Get new address
     pub async fn getnewaddress(&self) -> Result<String> {
         let result = self.call("getnewaddress", serde_json::json!([])).await?;
@@ -294,6 +608,93 @@ impl NodeRpcClient {
         self.call("getblockchaininfo", serde_json::json!([])).await
     }
 
+    /// UTXO set totals (`hash_type` `none`, `hash_serialized_3` or `muhash`), at `height` when
+    /// given; heights below the tip need `-coinstatsindex`
+    pub async fn gettxoutsetinfo(&self, hash_type: &str, height: Option<u64>) -> Result<Value> {
+        let params = match height {
+            Some(height) => serde_json::json!([hash_type, height]),
+            None => serde_json::json!([hash_type]),
+        };
+        self.call("gettxoutsetinfo", params).await
+    }
+
+    /// Get all known chain tips (active chain, forks, headers-only branches)
+    pub async fn getchaintips(&self) -> Result<Value> {
+        self.call("getchaintips", serde_json::json!([])).await
+    }
+
+    /// Get block header (verbose=false returns the 80-byte header as hex)
+    pub async fn getblockheader(&self, block_hash: &str, verbose: bool) -> Result<Value> {
+        let params = serde_json::json!([block_hash, verbose]);
+        self.call("getblockheader", params).await
+    }
+
+    /// JSON-RPC batch: one call of `method` per entry of `params`, results in the same order.
+    ///
+    /// Per-call errors are returned in place; only transport failures fail the whole batch.
+    pub async fn call_batch(&self, method: &str, params: &[Value]) -> Result<Vec<Result<Value>>> {
+        let calls: Vec<(&str, Value)> = params.iter().map(|p| (method, p.clone())).collect();
+        self.batch(&calls).await
+    }
+
+    /// `(method, params)` calls as JSON-RPC batch arrays of up to `batch_size` calls per HTTP
+    /// request, results in the same order as `calls`.
+    ///
+    /// Per-call errors are returned in place; only transport failures fail the whole batch.
+    pub async fn batch(&self, calls: &[(&str, Value)]) -> Result<Vec<Result<Value>>> {
+        let mut results = Vec::with_capacity(calls.len());
+        for chunk in calls.chunks(self.batch_size()) {
+            results.extend(self.send_batch(chunk).await?);
+        }
+        Ok(results)
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.config.batch_size.max(1)
+    }
+
+    /// One HTTP request carrying all of `calls`.
+    async fn send_batch(&self, calls: &[(&str, Value)]) -> Result<Vec<Result<Value>>> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let body: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (method, p))| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": method,
+                    "params": p,
+                    "id": id
+                })
+            })
+            .collect();
+
+        let replies: Vec<Value> = serde_json::from_value(self.post(&Value::Array(body)).await?)
+            .context("Failed to parse RPC batch response")?;
+
+        let mut results: Vec<Result<Value>> = (0..calls.len())
+            .map(|_| Err(anyhow::anyhow!("RPC batch response missing entry")))
+            .collect();
+        for reply in replies {
+            let Some(id) = reply.get("id").and_then(|i| i.as_u64()) else {
+                continue;
+            };
+            let Some(slot) = results.get_mut(id as usize) else {
+                continue;
+            };
+            *slot = match reply.get("error") {
+                Some(error) if !error.is_null() => Err(anyhow::anyhow!("RPC error: {}", error)),
+                _ => reply
+                    .get("result")
+                    .cloned()
+                    .context("RPC response missing result"),
+            };
+        }
+        Ok(results)
+    }
+
     /// Detect network type from running node
     pub async fn detect_network(&self) -> Result<BitcoinNetwork> {
         let info = self.getblockchaininfo().await?;
@@ -336,9 +737,11 @@ impl NodeRpcClient {
         Ok((is_pruned, prune_height))
     }
 
-    /// Test if this RPC connection is working
+    /// Test if this RPC connection is working (without retrying)
     pub async fn test_connection(&self) -> Result<bool> {
-        match self.getblockcount().await {
+        let mut probe = self.clone();
+        probe.config.retries = 0;
+        match probe.getblockcount().await {
             Ok(_) => Ok(true),
             Err(_) => Ok(false),
         }
@@ -446,12 +849,9 @@ impl NodeDiscovery {
         let password = rpc_password.unwrap_or_else(|| "test".to_string());
         let host = rpc_bind.unwrap_or_else(|| "127.0.0.1".to_string());
 
-        Ok(RpcConfig {
-            url: format!("http://{}:{}", host, port),
-            user,
-            pass: password,
-            timeout: Duration::from_secs(5),
-        })
+        let mut config = RpcConfig::new(format!("http://{}:{}", host, port), user, password);
+        config.timeout = Duration::from_secs(5);
+        Ok(config)
     }
 
     /// Discover nodes by trying common configurations
@@ -472,12 +872,12 @@ impl NodeDiscovery {
         // Try localhost first
         for port in &ports {
             for (user, pass) in &credentials {
-                let config = RpcConfig {
-                    url: format!("http://127.0.0.1:{}", port),
-                    user: user.to_string(),
-                    pass: pass.to_string(),
-                    timeout: Duration::from_secs(2),
-                };
+                let mut config = RpcConfig::new(
+                    format!("http://127.0.0.1:{}", port),
+                    user.to_string(),
+                    pass.to_string(),
+                );
+                config.timeout = Duration::from_secs(2);
                 let client = NodeRpcClient::new(config.clone());
                 if client.test_connection().await.unwrap_or(false) {
                     configs.push(config);
@@ -526,10 +926,9 @@ impl NodeDiscovery {
             );
         }
 
-        // Return random node from working candidates
+        // Return random node from working candidates (seeded when deterministic)
         use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let idx = rng.gen_range(0..working.len());
+        let idx = crate::determinism::rng("node-discovery").gen_range(0..working.len());
         Ok(working.remove(idx))
     }
 }
@@ -563,6 +962,24 @@ impl BitcoinNetwork {
     }
 }
 
+/// The message of a warmup error in a reply (or any reply of a batch).
+fn warmup_message(reply: &Value) -> Option<String> {
+    let replies = match reply {
+        Value::Array(replies) => replies.as_slice(),
+        reply => std::slice::from_ref(reply),
+    };
+    replies.iter().find_map(|reply| {
+        let error = reply.get("error")?;
+        (error.get("code")?.as_i64()? == RPC_IN_WARMUP).then(|| {
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("loading")
+                .to_string()
+        })
+    })
+}
+
 /// Result of testmempoolaccept
 #[derive(Debug, Clone)]
 pub struct TestMempoolAcceptResult {
@@ -580,3 +997,158 @@ pub struct SubmitBlockResult {
     /// Error message if not accepted
     pub error: Option<String>,
 }
+
+/// Result of signrawtransactionwithkey
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SignRawTransactionResult {
+    /// Transaction with the signatures Core could add
+    pub hex: String,
+    /// Whether every input is now signed
+    pub complete: bool,
+    /// Per-input signing errors
+    #[serde(default)]
+    pub errors: Vec<Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Batch-only JSON-RPC server: answers each call with its first param (an error for
+    /// `"bad"`), after `warmups` warmup replies. With `auth`, other credentials get a 401.
+    async fn serve(
+        listener: tokio::net::TcpListener,
+        requests: Arc<AtomicUsize>,
+        auth: Option<&'static str>,
+        warmups: usize,
+    ) {
+        let mut warmed = 0;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let (head, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                let Some(end) = text.find("\r\n\r\n") else {
+                    continue;
+                };
+                let len: usize = text
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .map(|v| v.trim().parse().unwrap())
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + len {
+                    let head = String::from_utf8_lossy(&request[..end]).to_string();
+                    break (head, request[end + 4..end + 4 + len].to_vec());
+                }
+            };
+            requests.fetch_add(1, Ordering::Relaxed);
+            let calls: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            let authorized = auth.is_none_or(|auth| head.contains(&format!("Basic {}", auth)));
+            let (status, replies): (&str, Vec<Value>) = if !authorized {
+                ("401 Unauthorized", Vec::new())
+            } else if warmed < warmups {
+                warmed += 1;
+                let warmup = serde_json::json!({"result": null, "error": {"code": RPC_IN_WARMUP, "message": "Loading block index..."}, "id": 0});
+                ("500 Internal Server Error", vec![warmup])
+            } else {
+                let replies = calls
+                    .iter()
+                    .map(|call| {
+                        let param = &call["params"][0];
+                        if param == "bad" {
+                            serde_json::json!({"id": call["id"], "result": null, "error": {"code": -8, "message": "bad"}})
+                        } else {
+                            serde_json::json!({"id": call["id"], "result": param, "error": null})
+                        }
+                    })
+                    .collect();
+                ("200 OK", replies)
+            };
+            let body = if authorized {
+                serde_json::to_vec(&replies).unwrap()
+            } else {
+                Vec::new()
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            stream.shutdown().await.ok();
+        }
+    }
+
+    async fn start(auth: Option<&'static str>, warmups: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(serve(listener, requests.clone(), auth, warmups));
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn batches_are_split_and_keep_order() {
+        let (url, requests) = start(None, 0).await;
+        let config = RpcConfig::new(url, "u".into(), "p".into()).with_batch_size(2);
+        let client = NodeRpcClient::new(config);
+        let calls: Vec<(&str, Value)> = ["a", "b", "bad", "d", "e"]
+            .iter()
+            .map(|p| ("getblockhash", serde_json::json!([p])))
+            .collect();
+        let results = client.batch(&calls).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 3);
+        assert_eq!(results.len(), 5);
+        assert!(results[2].is_err());
+        let ok: Vec<Value> = results.into_iter().filter_map(|r| r.ok()).collect();
+        assert_eq!(ok, ["a", "b", "d", "e"].map(Value::from));
+
+        let hashes = client.getblockhashes(&[]).await.unwrap();
+        assert!(hashes.is_empty());
+        assert_eq!(requests.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn rereads_cookie_and_retries_warmup() {
+        // base64("__cookie__:new")
+        let (url, requests) = start(Some("X19jb29raWVfXzpuZXc="), 2).await;
+        let dir = tempfile::tempdir().unwrap();
+        let cookie = cookie_path(dir.path(), "regtest");
+        std::fs::create_dir_all(cookie.parent().unwrap()).unwrap();
+        std::fs::write(&cookie, "__cookie__:old").unwrap();
+        let config = RpcConfig::new(url, String::new(), String::new())
+            .with_cookie_file(&cookie)
+            .unwrap()
+            .with_retries(3, Duration::from_millis(1));
+        assert_eq!(config.user, "__cookie__");
+        assert_eq!(config.pass, "old");
+        let client = NodeRpcClient::new(config);
+
+        // The node restarted with a new cookie and is still loading
+        std::fs::write(&cookie, "__cookie__:new\n").unwrap();
+        let results = client
+            .batch(&[("getblockhash", serde_json::json!(["h"]))])
+            .await
+            .unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), "h");
+        // 401, two warmup replies, success
+        assert_eq!(requests.load(Ordering::Relaxed), 4);
+
+        // Retries run out
+        let (url, _) = start(None, 10).await;
+        let client = NodeRpcClient::new(
+            RpcConfig::new(url, "u".into(), "p".into()).with_retries(2, Duration::from_millis(1)),
+        );
+        let err = client
+            .batch(&[("getblockhash", serde_json::json!(["h"]))])
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("after 2 retries"));
+    }
+}
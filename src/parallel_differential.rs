@@ -6,13 +6,30 @@
 
 use anyhow::{Context, Result};
 use blvm_protocol::UtxoSet;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tokio::sync::Semaphore;
+use tracing::{debug, error, info, warn};
+
+use crate::block_source::BlockSource;
+use crate::cancel::CancellationToken;
+use crate::checkpoint_store::CheckpointStore;
+use crate::chunk_schedule::{ChunkScheduling, WorkQueue};
+use crate::coin_age::{BlockTimes, CoinAgeAccumulator, CoinAgeStats};
+use crate::consensus_compat::insert_utxo;
+use crate::run_state::RunStateStore;
+use crate::utxo_overlay::OverlayUtxoSet;
+use crate::utxo_spill::UtxoStore;
+use crate::utxo_stats::UtxoSetStats;
+use crate::validation_hooks::{BlockContext, BlockOutcome, DivergenceEvent, HookRegistry};
 
 // Re-export block file reader for convenience
 pub use crate::block_file_reader::{BlockFileReader, Network as BlockFileNetwork, SharedBlockCache};
 
 /// Block data source - optimized to avoid RPC when possible
+///
+/// The built-in [`BlockSource`] implementations; other sources implement the trait directly.
 pub enum BlockDataSource {
     /// Direct file reading (fastest - 10-50x faster than RPC)
     DirectFile(BlockFileReader),
@@ -22,25 +39,65 @@ pub enum BlockDataSource {
     Rpc(Arc<crate::core_rpc_client::CoreRpcClient>),
     /// Remote Core RPC via SSH+nsenter (works when datadir files are encrypted)
     RemoteCoreRpc(Arc<crate::remote_core_rpc::RemoteCoreRpcClient>),
+    /// A node's P2P port (no RPC credentials or datadir access needed)
+    P2p(Arc<crate::p2p_block_source::P2pBlockSource>),
+    /// An Esplora REST API (rate limited, for runs without a node)
+    Esplora(Arc<crate::esplora_source::EsploraBlockSource>),
+}
+
+impl BlockDataSource {
+    /// Where blocks come from, for run IDs and logs (`files:<datadir>`, `cache:<dir>`, `rpc`, ...).
+    pub fn describe(&self) -> String {
+        match self {
+            BlockDataSource::DirectFile(reader) => format!("files:{}", reader.data_dir().display()),
+            BlockDataSource::SharedCache(cache, _) => {
+                format!("cache:{}", cache.cache_dir().display())
+            }
+            BlockDataSource::Rpc(_) => "rpc".to_string(),
+            BlockDataSource::RemoteCoreRpc(_) => "remote-core-rpc".to_string(),
+            BlockDataSource::P2p(source) => format!("p2p:{}", source.peer()),
+            BlockDataSource::Esplora(source) => format!("esplora:{}", source.base_url()),
+        }
+    }
 }
 
 /// Configuration for parallel differential testing
 #[derive(Debug, Clone)]
 pub struct ParallelConfig {
-    /// Number of parallel workers
+    /// Number of parallel workers (default: all cores, or a fixed count when
+    /// [deterministic](crate::determinism))
     pub num_workers: usize,
-    /// Chunk size (blocks per chunk)
+    /// Chunk size (blocks per chunk; the longest work item with adaptive scheduling)
     pub chunk_size: u64,
+    /// Fixed `chunk_size` chunks or cost-balanced work items (default: `BLVM_CHUNK_SCHEDULING`,
+    /// see [`crate::chunk_schedule`])
+    pub scheduling: ChunkScheduling,
     /// Whether to use UTXO checkpoints (requires sequential pass first)
     pub use_checkpoints: bool,
+    /// Called around every validated block (not during checkpoint generation)
+    pub hooks: HookRegistry,
+    /// Persist checkpoints here and resume generation from them (default: `BLVM_CHECKPOINT_DIR`;
+    /// without one they go to a scratch dir removed after the run)
+    pub checkpoint_dir: Option<std::path::PathBuf>,
+    /// Start from this Core `dumptxoutset` snapshot instead of genesis (default:
+    /// `BLVM_ASSUMEUTXO_SNAPSHOT`); validation begins at the block after its base
+    pub assumeutxo_snapshot: Option<std::path::PathBuf>,
+    /// Record finished chunks in `run_state.json` here and skip them on restart (default:
+    /// `BLVM_RUN_STATE_DIR`; see [`crate::run_state`])
+    pub run_state_dir: Option<std::path::PathBuf>,
 }
 
 impl Default for ParallelConfig {
     fn default() -> Self {
         Self {
-            num_workers: num_cpus::get(),
+            num_workers: crate::determinism::worker_count(None),
             chunk_size: 100_000, // 100k blocks per chunk
+            scheduling: ChunkScheduling::from_env(),
             use_checkpoints: true,
+            hooks: HookRegistry::default(),
+            checkpoint_dir: crate::checkpoint_store::checkpoint_dir_from_env(),
+            assumeutxo_snapshot: crate::assumeutxo::snapshot_path_from_env(),
+            run_state_dir: crate::run_state::run_state_dir_from_env(),
         }
     }
 }
@@ -50,12 +107,15 @@ impl Default for ParallelConfig {
 pub struct BlockChunk {
     pub start_height: u64,
     pub end_height: u64,
-    pub checkpoint_utxo: Option<UtxoSet>,
+    /// Shared with the other chunks starting from the same checkpoint (see utxo_overlay)
+    pub checkpoint_utxo: Option<Arc<UtxoSet>>,
+    /// How long `checkpoint_utxo` took to build or load
+    pub checkpoint_timing: Option<CheckpointTiming>,
     pub skip_validation: bool, // If true, just read blocks for cache building, don't validate
 }
 
 /// Result from validating a chunk
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkResult {
     pub start_height: u64,
     pub end_height: u64,
@@ -63,13 +123,46 @@ pub struct ChunkResult {
     pub matched: usize,
     pub divergences: Vec<(u64, String, String)>, // (height, blvm_result, core_result)
     pub duration_secs: f64,
+    /// Per-block coin age / coin-days destroyed, in height order
+    pub coin_age: Vec<CoinAgeStats>,
+    /// The checkpoint this chunk started from
+    #[serde(default)]
+    pub checkpoint: Option<CheckpointTiming>,
+}
+
+impl ChunkResult {
+    /// The result with its duration and checkpoint timing zeroed
+    /// ([deterministic mode](crate::determinism)).
+    pub fn without_timings(mut self) -> Self {
+        self.duration_secs = 0.0;
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.secs = 0.0;
+            checkpoint.loaded = false;
+        }
+        self
+    }
+}
+
+/// Cost of one UTXO checkpoint from [`generate_checkpoints`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointTiming {
+    /// Last block the checkpoint includes
+    pub height: u64,
+    pub utxo_count: usize,
+    /// Totals compared against Core's `gettxoutsetinfo` (see [`crate::utxo_stats`])
+    #[serde(default)]
+    pub stats: UtxoSetStats,
+    /// Replay time since the previous checkpoint, or load time when `loaded`
+    pub secs: f64,
+    /// Read from the [`CheckpointStore`] instead of replayed
+    pub loaded: bool,
 }
 
 /// Create optimized block data source
 ///
 /// Tries direct file reading from env-configured Bitcoin Core datadirs first (see
 /// [`crate::block_cache_env::bitcoin_data_dir_candidates`]), then remote-Core RPC if `REMOTE_CORE_*` (or legacy `LAND_NODE_*` / `START9_*`) env is set,
-/// then shared chunk cache, then standard RPC.
+/// then a P2P peer if `BLVM_P2P_PEER` is set, then shared chunk cache, then standard RPC.
 pub fn create_block_data_source(
     network: BlockFileNetwork,
     cache_dir: Option<impl AsRef<std::path::Path>>,
@@ -83,14 +176,14 @@ pub fn create_block_data_source(
         }
         match BlockFileReader::new(dir, network) {
             Ok(reader) => {
-                println!(
+                info!(
                     "✅ Using direct block file reading from BITCOIN_DATA_DIR* {} (fast path)",
                     dir.display()
                 );
                 return Ok(BlockDataSource::DirectFile(reader));
             }
             Err(e) => {
-                eprintln!(
+                error!(
                     "⚠️  Direct file reading from {} failed: {}. Trying next candidate or fallback.",
                     dir.display(),
                     e
@@ -101,56 +194,46 @@ pub fn create_block_data_source(
 
     if crate::block_cache_env::remote_core_rpc_env_ready() {
         let remote_core_client = Arc::new(crate::remote_core_rpc::RemoteCoreRpcClient::new());
-        println!(
+        info!(
             "✅ Using remote-Core RPC (REMOTE_CORE_* or legacy LAND_NODE_* / START9_* env set; no usable direct datadir from BITCOIN_DATA_DIR*)"
         );
         return Ok(BlockDataSource::RemoteCoreRpc(remote_core_client));
     }
 
+    if let Some(p2p) = crate::p2p_block_source::P2pBlockSource::from_env() {
+        info!("✅ Using P2P block download from {} (BLVM_P2P_PEER)", p2p.peer());
+        return Ok(BlockDataSource::P2p(Arc::new(p2p)));
+    }
+
+    if let Some(esplora) = crate::esplora_source::EsploraBlockSource::from_env(network)? {
+        info!(
+            "✅ Using Esplora block download from {} (BLVM_ESPLORA_URL)",
+            esplora.base_url()
+        );
+        return Ok(BlockDataSource::Esplora(Arc::new(esplora)));
+    }
+
     if let Some(cache_path) = cache_dir {
         let cache = SharedBlockCache::new(cache_path)?;
-        println!("✅ Using shared block cache (BLOCK_CACHE_DIR)");
-        println!("   Cache will use RPC or populate from your configured sources");
+        info!("✅ Using shared block cache (BLOCK_CACHE_DIR)");
+        info!("   Cache will use RPC or populate from your configured sources");
         return Ok(BlockDataSource::SharedCache(cache, rpc_client));
     }
 
     if let Some(client) = rpc_client {
-        println!("⚠️  Using Bitcoin Core RPC only (set BITCOIN_DATA_DIR or BLOCK_CACHE_DIR for faster paths)");
+        warn!("⚠️  Using Bitcoin Core RPC only (set BITCOIN_DATA_DIR or BLOCK_CACHE_DIR for faster paths)");
         return Ok(BlockDataSource::Rpc(client));
     }
 
     anyhow::bail!(
         "No block data source: set BITCOIN_DATA_DIR (and/or BITCOIN_DATA_DIRS) with a blocks/ subdir, \
-         or BLOCK_CACHE_DIR for chunks, or REMOTE_CORE_* (or legacy LAND_NODE_* / START9_*) for remote-Core RPC, or run with a reachable Core RPC client."
+         or BLOCK_CACHE_DIR for chunks, or REMOTE_CORE_* (or legacy LAND_NODE_* / START9_*) for remote-Core RPC, or BLVM_P2P_PEER for a node's P2P port, or BLVM_ESPLORA_URL for an Esplora API, or run with a reachable Core RPC client."
     )
 }
 
 /// Get block data from optimized source
-pub async fn get_block_data(
-    source: &BlockDataSource,
-    height: u64,
-) -> Result<Vec<u8>> {
-    match source {
-        BlockDataSource::DirectFile(reader) => {
-            // For direct file reading, we need to iterate sequentially
-            // This is a limitation - we'll need to cache blocks or use index
-            // For now, fall back to RPC for random access
-            anyhow::bail!("Direct file reading requires sequential access. Use generate_checkpoints_sequential or provide RPC client for random access.")
-        }
-        BlockDataSource::SharedCache(cache, rpc_client) => {
-            cache.get_or_fetch_block(height, rpc_client.as_deref()).await
-        }
-        BlockDataSource::Rpc(client) => {
-            let block_hash = client.getblockhash(height).await?;
-            let block_hex = client.getblock_raw(&block_hash).await?;
-            Ok(hex::decode(&block_hex)?)
-        }
-        BlockDataSource::RemoteCoreRpc(client) => {
-            let block_hash = client.get_block_hash(height).await?;
-            let block_hex = client.get_block_hex(&block_hash).await?;
-            Ok(hex::decode(&block_hex)?)
-        }
-    }
+pub async fn get_block_data<S: BlockSource>(source: &S, height: u64) -> Result<Vec<u8>> {
+    source.get_block(height).await
 }
 
 /// Generate UTXO checkpoints at chunk boundaries
@@ -159,463 +242,437 @@ pub async fn get_block_data(
 /// at chunk boundaries for parallel execution.
 /// 
 /// Uses optimized block data source (direct file reading if available).
-pub async fn generate_checkpoints(
+/// Checkpoints are saved to `store` and only their timings returned, so the sets stay on disk
+/// until a chunk loads one. Boundaries already stored are verified instead of replayed and
+/// generation resumes after the last one.
+/// Returns [`Cancelled`](crate::cancel::Cancelled) as soon as `cancel` fires.
+pub async fn generate_checkpoints<S: BlockSource>(
     start_height: u64,
     end_height: u64,
     chunk_size: u64,
-    block_source: &BlockDataSource,
-) -> Result<Vec<(u64, UtxoSet)>> {
-    use blvm_protocol::block::connect_block;
+    block_source: &S,
+    base_utxo: Option<&UtxoSet>,
+    store: &CheckpointStore,
+    cancel: &CancellationToken,
+) -> Result<Vec<CheckpointTiming>> {
+    let items = crate::chunk_schedule::fixed_items(start_height, end_height, chunk_size, |_| 1.0);
+    let boundaries: Vec<u64> = items
+        .iter()
+        .map(|item| item.end_height)
+        .collect();
+    generate_checkpoints_at(start_height, &boundaries, block_source, base_utxo, store, cancel).await
+}
+
+/// [`generate_checkpoints`] at the end of each work item: one checkpoint after every height in
+/// `boundaries` (ascending, the last one is the end of the run) up to the chain tip, plus one at
+/// the tip if it comes first.
+pub async fn generate_checkpoints_at<S: BlockSource>(
+    start_height: u64,
+    boundaries: &[u64],
+    block_source: &S,
+    base_utxo: Option<&UtxoSet>,
+    store: &CheckpointStore,
+    cancel: &CancellationToken,
+) -> Result<Vec<CheckpointTiming>> {
+    crate::progress::ensure_logging();
     use blvm_protocol::segwit::Witness;
     use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
     use blvm_protocol::types::Network;
 
-    // OPTIMIZATION: Pre-allocate checkpoints vector (estimate: ~10 checkpoints for 1M blocks)
-    let estimated_checkpoints = ((end_height - start_height) / chunk_size + 1) as usize;
-    let mut checkpoints = Vec::with_capacity(estimated_checkpoints.min(100));
-    let mut utxo_set = UtxoSet::default();
+    let end_height = *boundaries.last().context("No checkpoint boundaries")?;
+    let mut checkpoints = Vec::with_capacity(boundaries.len().min(100));
+    // Empty at genesis; an assumeutxo snapshot's set when starting above its base block.
+    // In memory, or on disk with BLVM_UTXO_BACKEND=spill (see utxo_spill)
+    let mut utxo_set = UtxoStore::from_env(base_utxo.cloned().unwrap_or_default())?;
     let mut previous_block_hash: Option<[u8; 32]> = None; // Track previous block hash for verification
     
-    // If starting from height 0, we start with empty UTXO set
-    // Otherwise, we'd need to load from a previous checkpoint
+    let chain_height = block_source.get_tip_height().await?.unwrap_or(end_height);
+    let actual_end = end_height.min(chain_height);
+    
+    // Boundaries past the tip collapse into one at the tip
+    let mut boundaries: Vec<u64> = boundaries.iter().copied().filter(|&b| b < actual_end).collect();
+    boundaries.push(actual_end);
+    info!("🔧 Generating {} UTXO checkpoints from {} to {}", 
+             boundaries.len(), start_height, actual_end);
+    #[cfg(feature = "metrics")]
+    crate::metrics::start_from_env().await?;
     
-    // Get chain height (need RPC for this)
-    let chain_height = match block_source {
-        BlockDataSource::Rpc(client) => client.getblockcount().await?,
-        BlockDataSource::RemoteCoreRpc(client) => client.get_block_count().await?,
-        BlockDataSource::SharedCache(_, Some(client)) => client.getblockcount().await?,
-        _ => {
-            // For direct file reading, we don't know chain height
-            // Use end_height as estimate
-            end_height
+    let mut next_boundary = 0;
+    
+    // Chunk boundaries already in the store are verified instead of replayed (read through, not
+    // kept); the last one seeds the replay
+    let mut resume_height = start_height;
+    let mut seed = None;
+    loop {
+        let boundary = boundaries[next_boundary];
+        if !store.contains(boundary) {
+            break;
         }
-    };
-    let actual_end = end_height.min(chain_height);
+        let load_start = std::time::Instant::now();
+        let block_hash = crate::checkpoint_store::block_hash_at(block_source, boundary).await?;
+        let mut stats = UtxoSetStats::default();
+        let verified = store.for_each(boundary, &block_hash, |_, utxo| {
+            stats.add(&utxo);
+            Ok(())
+        });
+        match verified {
+            Ok(utxo_count) => {
+                info!("📂 Loaded stored checkpoint at height {} (UTXO count: {})", boundary, utxo_count);
+                #[cfg(feature = "metrics")]
+                crate::metrics::global().record_cache("checkpoint", true);
+                checkpoints.push(CheckpointTiming {
+                    height: boundary,
+                    utxo_count: utxo_count as usize,
+                    stats,
+                    secs: load_start.elapsed().as_secs_f64(),
+                    loaded: true,
+                });
+                seed = Some((boundary, block_hash));
+                resume_height = boundary + 1;
+                next_boundary += 1;
+            }
+            Err(e) => {
+                warn!("⚠️  {:#} - regenerating from height {}", e, resume_height);
+                break;
+            }
+        }
+        if boundary == actual_end {
+            info!("✅ All {} checkpoints loaded from {}", checkpoints.len(), store.dir().display());
+            return Ok(checkpoints);
+        }
+    }
+    if let Some((height, block_hash)) = seed {
+        utxo_set.reset_from(store, height, &block_hash)?;
+    }
     
-    println!("🔧 Generating UTXO checkpoints from {} to {} (chunk size: {})", 
-             start_height, actual_end, chunk_size);
+    // Block files read sequentially; cache and RPC sources fetch height by height
+    let mut blocks = block_source
+        .iter_sequential(resume_height, actual_end - resume_height + 1, cancel)?
+        .enumerate();
+    info!("✅ Block stream ready, starting block processing...");
+    let progress = crate::progress::Progress::new("checkpoints", actual_end - start_height + 1, "blocks");
+    progress.set_position(resume_height - start_height);
     
-    let mut next_checkpoint = start_height + chunk_size;
+    let mut last_log_time = std::time::Instant::now();
+    let mut last_checkpoint_time = std::time::Instant::now();
+    let mut blocks_processed = 0u64;
     
-    // Use optimized block reading for sequential access
-    match block_source {
-        BlockDataSource::DirectFile(reader) => {
-            // Direct file reading - sequential iterator (fastest!)
-            println!("📂 Using direct file reading for checkpoint generation");
-            let iterator = reader.read_blocks_sequential(Some(start_height), Some((actual_end - start_height + 1) as usize))?;
-            println!("✅ Iterator created, starting block processing...");
-            
-            let mut last_log_time = std::time::Instant::now();
-            let mut blocks_processed = 0u64;
-            
-            for (idx, block_result) in iterator.enumerate() {
-                let height = start_height + idx as u64;
-                
-                // CRITICAL: Log every block for first 100, then every 10, then every 1000
-                // This ensures we can see exactly where it gets stuck
+    while let Some((idx, block_result)) = blocks.next().await {
+        let height = resume_height + idx as u64;
+        
+        // CRITICAL: Log every block for first 100, then every 10, then every 1000
+        // This ensures we can see exactly where it gets stuck
+        if height < 100 {
+            debug!("   🔄 [{}] Getting block {} from iterator...", idx, height);
+        } else if height < 1000 && height % 10 == 0 {
+            debug!("   🔄 [{}] Getting block {} from iterator...", idx, height);
+        } else if height % 1000 == 0 {
+            debug!("   🔄 [{}] Getting block {} from iterator...", idx, height);
+        }
+        
+        // Timeout detection - if we haven't made progress in 30 seconds, log warning
+        let now = std::time::Instant::now();
+        if now.duration_since(last_log_time).as_secs() > 30 && blocks_processed > 0 {
+            warn!("   ⚠️  WARNING: No progress for 30+ seconds! Last block: {}", height - 1);
+            warn!("   ⚠️  Iterator may be stuck. Current index: {}", idx);
+            last_log_time = now;
+        }
+        
+        let block_bytes = match block_result {
+            Ok(bytes) => {
                 if height < 100 {
-                    println!("   🔄 [{}] Getting block {} from iterator...", idx, height);
-                } else if height < 1000 && height % 10 == 0 {
-                    println!("   🔄 [{}] Getting block {} from iterator...", idx, height);
-                } else if height % 1000 == 0 {
-                    println!("   🔄 [{}] Getting block {} from iterator...", idx, height);
-                }
-                
-                // Timeout detection - if we haven't made progress in 30 seconds, log warning
-                let now = std::time::Instant::now();
-                if now.duration_since(last_log_time).as_secs() > 30 && blocks_processed > 0 {
-                    eprintln!("   ⚠️  WARNING: No progress for 30+ seconds! Last block: {}", height - 1);
-                    eprintln!("   ⚠️  Iterator may be stuck. Current index: {}", idx);
-                    last_log_time = now;
+                    debug!("   ✅ [{}] Got block {} ({} bytes)", idx, height, bytes.len());
                 }
-                
-                let block_bytes = match block_result {
-                    Ok(bytes) => {
-                        if height < 100 {
-                            println!("   ✅ [{}] Got block {} ({} bytes)", idx, height, bytes.len());
-                        }
-                        bytes
-                    },
-                    Err(e) => {
-                        eprintln!("❌ Failed to read block at height {}: {}", height, e);
-                        return Err(e.into());
-                    }
-                };
-                
-                blocks_processed += 1;
-                last_log_time = now;
-                
-                // Validate block size
-                if block_bytes.len() < 80 {
-                    anyhow::bail!("Block {} too small: {} bytes (minimum 80 for header)", height, block_bytes.len());
-                }
-                
-                // Verify previous block hash matches (if not genesis) - this helps detect block boundary issues
-                if height > 0 {
-                    let prev_hash_in_header = &block_bytes[4..36]; // Previous block hash is at bytes 4-36 (little-endian)
-                    // We'll verify this after parsing the block
-                }
-                
+                bytes
+            },
+            Err(e) => {
+                error!("❌ Failed to read block at height {}: {}", height, e);
+                return Err(e.into());
+            }
+        };
+        
+        blocks_processed += 1;
+        last_log_time = now;
+        
+        // Validate block size
+        if block_bytes.len() < 80 {
+            anyhow::bail!("Block {} too small: {} bytes (minimum 80 for header)", height, block_bytes.len());
+        }
+        
+        // Verify previous block hash matches (if not genesis) - this helps detect block boundary issues
+        if height > 0 {
+            let prev_hash_in_header = &block_bytes[4..36]; // Previous block hash is at bytes 4-36 (little-endian)
+            // We'll verify this after parsing the block
+        }
+        
+        if height < 100 {
+            debug!("   🔄 [{}] Deserializing block {}...", idx, height);
+        }
+        
+        let (block, witnesses) = match deserialize_block_with_witnesses(&block_bytes) {
+            Ok(result) => {
                 if height < 100 {
-                    println!("   🔄 [{}] Deserializing block {}...", idx, height);
-                }
-                
-                let (block, witnesses) = match deserialize_block_with_witnesses(&block_bytes) {
-                    Ok(result) => {
-                        if height < 100 {
-                            println!("   ✅ [{}] Deserialized block {} ({} txs)", idx, height, result.0.transactions.len());
-                        }
-                        result
-                    },
-                    Err(e) => {
-                        eprintln!("❌ Failed to deserialize block at height {}: {}", height, e);
-                        eprintln!("   Block size: {} bytes", block_bytes.len());
-                        eprintln!("   First 80 bytes (header, hex): {}", hex::encode(&block_bytes[0..80.min(block_bytes.len())]));
-                        if block_bytes.len() > 80 {
-                            eprintln!("   Bytes 80-100 (hex): {}", hex::encode(&block_bytes[80..100.min(block_bytes.len())]));
-                        }
-                        // For XOR-packaged remote block files, if deserialization fails, the block boundary might be wrong
-                        // Try to continue - this will help us identify all problematic blocks
-                        eprintln!("⚠️  Block {} deserialization failed - likely block boundary issue. Skipping.", height);
-                        continue; // Skip this block and continue
-                    }
-                };
-                
-                // Debug: Check if previous blocks had non-coinbase transactions
-                if height <= 16 {
-                    let non_coinbase_count = block.transactions.iter().filter(|tx| !blvm_protocol::transaction::is_coinbase(tx)).count();
-                    if non_coinbase_count > 0 {
-                        eprintln!("🔍 Block {}: {} non-coinbase transactions", height, non_coinbase_count);
-                        // For each non-coinbase transaction, show what it's spending
-                        for (tx_idx, tx) in block.transactions.iter().enumerate() {
-                            if !blvm_protocol::transaction::is_coinbase(tx) {
-                                use blvm_protocol::block::calculate_tx_id;
-                                let txid = calculate_tx_id(tx);
-                                let txid_str: String = txid.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                eprintln!("   TX {} (non-coinbase): {} inputs, {} outputs, TXID: {}...", 
-                                         tx_idx, tx.inputs.len(), tx.outputs.len(), txid_str);
-                                if !tx.inputs.is_empty() {
-                                    let hash_str: String = tx.inputs[0].prevout.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                    eprintln!("      Spending: {}:{}", hash_str, tx.inputs[0].prevout.index);
-                                }
-                            }
-                        }
-                    }
+                    debug!("   ✅ [{}] Deserialized block {} ({} txs)", idx, height, result.0.transactions.len());
                 }
-                
-                // Debug: Check UTXO set after each block to see if outputs are being added
-                if height <= 16 {
-                    let non_coinbase_utxos: Vec<_> = utxo_set.iter()
-                        .filter(|(_, utxo)| !utxo.is_coinbase)
-                        .collect();
-                    if !non_coinbase_utxos.is_empty() {
-                        eprintln!("🔍 After block {}: {} non-coinbase UTXOs in set", height, non_coinbase_utxos.len());
-                        for (outpoint, utxo) in non_coinbase_utxos.iter().take(3) {
-                            let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                            eprintln!("   Non-coinbase UTXO: {}:{} (value={}, height={})", 
-                                     hash_str, outpoint.index, utxo.value, utxo.height);
-                        }
-                    }
+                result
+            },
+            Err(e) => {
+                error!("❌ Failed to deserialize block at height {}: {}", height, e);
+                info!("   Block size: {} bytes", block_bytes.len());
+                info!("   First 80 bytes (header, hex): {}", hex::encode(&block_bytes[0..80.min(block_bytes.len())]));
+                if block_bytes.len() > 80 {
+                    info!("   Bytes 80-100 (hex): {}", hex::encode(&block_bytes[80..100.min(block_bytes.len())]));
                 }
-                
-                // Debug: Print transaction details for block 15
-                if height == 15 {
-                    eprintln!("🔍 DEBUG Block 15: {} transactions", block.transactions.len());
-                    eprintln!("   UTXO set size: {}", utxo_set.len());
-                    // List all UTXOs in the set
-                    eprintln!("   All UTXOs in set:");
-                    for (outpoint, utxo) in utxo_set.iter() {
-                        let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                        eprintln!("      {}:{} (value={}, height={}, coinbase={})", 
-                                 hash_str, outpoint.index, utxo.value, utxo.height, utxo.is_coinbase);
-                    }
-                    for (tx_idx, tx) in block.transactions.iter().enumerate() {
-                        eprintln!("   TX {}: {} inputs, {} outputs", tx_idx, tx.inputs.len(), tx.outputs.len());
-                        if !tx.inputs.is_empty() {
-                            let hash_str: String = tx.inputs[0].prevout.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                            eprintln!("      First input prevout: {}:{}", hash_str, tx.inputs[0].prevout.index);
-                            // Check if UTXO exists
-                            if let Some(utxo) = utxo_set.get(&tx.inputs[0].prevout) {
-                                eprintln!("      UTXO exists: value={}, height={}, coinbase={}", 
-                                         utxo.value, utxo.height, utxo.is_coinbase);
-                            } else {
-                                eprintln!("      UTXO MISSING!");
-                                // The prevout hash should be a transaction ID from a previous block
-                                // Let's check if we can find it in the UTXO set by searching for matching txids
-                                eprintln!("      Looking for TX that created this UTXO...");
-                                let target_hash = tx.inputs[0].prevout.hash;
-                                let target_index = tx.inputs[0].prevout.index;
-                                let mut found_match = false;
-                                for (outpoint, utxo) in utxo_set.iter() {
-                                    if outpoint.hash == target_hash {
-                                        found_match = true;
-                                        let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                        eprintln!("      Found matching TX ID in UTXO set: {}:{} (target index: {})", 
-                                                 hash_str, outpoint.index, target_index);
-                                        eprintln!("      UTXO details: value={}, height={}, coinbase={}", 
-                                                 utxo.value, utxo.height, utxo.is_coinbase);
-                                    }
-                                }
-                                if !found_match {
-                                    let hash_str: String = target_hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                    eprintln!("      No UTXO found with TX ID: {} (index: {})", hash_str, target_index);
-                                    eprintln!("      This UTXO should have been created in a previous block");
-                                    // Check if this TX ID matches any coinbase TX ID in the UTXO set
-                                    eprintln!("      Checking if this matches any coinbase TX ID...");
-                                    let mut found_coinbase_match = false;
-                                    for (outpoint, utxo) in utxo_set.iter() {
-                                        if utxo.is_coinbase {
-                                            let outpoint_hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                            if outpoint.hash == target_hash {
-                                                found_coinbase_match = true;
-                                                eprintln!("      ✅ Found matching coinbase TX ID: {}:{} (but index {} doesn't match)", 
-                                                         outpoint_hash_str, outpoint.index, target_index);
-                                                eprintln!("      This suggests the transaction is trying to spend the wrong output index");
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    if !found_coinbase_match {
-                                        eprintln!("      ❌ No matching coinbase TX ID found - this UTXO was never created");
-                                    }
-                                }
-                            }
-                        }
-                        // Calculate TX ID
+                // For XOR-packaged remote block files, if deserialization fails, the block boundary might be wrong
+                // Try to continue - this will help us identify all problematic blocks
+                error!("⚠️  Block {} deserialization failed - likely block boundary issue. Skipping.", height);
+                continue; // Skip this block and continue
+            }
+        };
+        
+        // Debug: Check if previous blocks had non-coinbase transactions
+        if height <= 16 {
+            let non_coinbase_count = block.transactions.iter().filter(|tx| !blvm_protocol::transaction::is_coinbase(tx)).count();
+            if non_coinbase_count > 0 {
+                debug!("🔍 Block {}: {} non-coinbase transactions", height, non_coinbase_count);
+                // For each non-coinbase transaction, show what it's spending
+                for (tx_idx, tx) in block.transactions.iter().enumerate() {
+                    if !blvm_protocol::transaction::is_coinbase(tx) {
                         use blvm_protocol::block::calculate_tx_id;
                         let txid = calculate_tx_id(tx);
                         let txid_str: String = txid.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                        eprintln!("      TX ID: {}...", txid_str);
-                    }
-                }
-                
-                // Calculate this block's hash for next block verification
-                // OPTIMIZATION: Cache hash calculation (only compute once per block)
-                use sha2::{Digest, Sha256};
-                let header = &block_bytes[0..80];
-                let first_hash = Sha256::digest(header);
-                let second_hash = Sha256::digest(&first_hash);
-                // OPTIMIZATION: Use array directly instead of Vec allocation
-                let mut current_block_hash: [u8; 32] = second_hash.as_slice().try_into()
-                    .map_err(|_| anyhow::anyhow!("Invalid hash length"))?;
-                current_block_hash.reverse(); // Convert to big-endian
-                
-                // Verify previous block hash matches (if not genesis)
-                // For XOR-packaged block files, if prev hash doesn't match, the block boundary detection
-                // is likely wrong. We'll skip validation errors for now and continue to identify
-                // which blocks work correctly.
-                if height > 0 {
-                    let prev_hash_in_header = &block_bytes[4..36]; // Previous block hash is at bytes 4-36 (little-endian, as stored)
-                    if let Some(prev_hash) = previous_block_hash {
-                        // Convert previous_block_hash (big-endian) to little-endian for comparison
-                        let prev_hash_le: Vec<u8> = prev_hash.iter().rev().copied().collect();
-                        if prev_hash_in_header != prev_hash_le.as_slice() {
-                            // This indicates we're reading too much data - block boundary is wrong
-                            eprintln!("⚠️  Block {}: Previous block hash mismatch - block boundary detection issue!", height);
-                            eprintln!("   Header has (LE): {}", hex::encode(prev_hash_in_header));
-                            eprintln!("   Expected (LE):   {}", hex::encode(&prev_hash_le));
-                            eprintln!("   Block size: {} bytes (likely reading too much - should use size field or verify hash)", block_bytes.len());
-                        }
-                    }
-                }
-                
-                // Update previous block hash for next iteration
-                previous_block_hash = Some(current_block_hash);
-                
-                // Debug: Check transaction count and verify block hash for problematic blocks
-                if height == 15 || height == 10 {
-                    let block_hash_hex = hex::encode(&current_block_hash[..8]);
-                    eprintln!("DEBUG Block {}: Parsed {} transactions, block hash (first 8 bytes) = {}, block size = {} bytes", 
-                             height, block.transactions.len(), block_hash_hex, block_bytes.len());
-                    for (i, tx) in block.transactions.iter().enumerate() {
-                        eprintln!("DEBUG Block {}: TX {} has {} inputs, {} outputs", height, i, tx.inputs.len(), tx.outputs.len());
-                    }
-                }
-                
-                // Debug: Verify we're calculating the correct coinbase txid
-                #[cfg(debug_assertions)]
-                if height <= 2 {
-                    use blvm_protocol::block::calculate_tx_id;
-                    if let Some(coinbase) = block.transactions.first() {
-                        let txid = calculate_tx_id(coinbase);
-                        eprintln!("DEBUG Block {}: coinbase txid = {}", height, hex::encode(txid));
-                        eprintln!("DEBUG Block {}: UTXO set size = {}", height, utxo_set.len());
-                        // List all coinbase UTXOs in the set
-                        let mut coinbase_utxos = Vec::new();
-                        for (outpoint, utxo) in utxo_set.iter() {
-                            if utxo.is_coinbase {
-                                coinbase_utxos.push((hex::encode(outpoint.hash), utxo.height));
-                            }
-                        }
-                        if !coinbase_utxos.is_empty() {
-                            eprintln!("DEBUG Block {}: Coinbase UTXOs in set: {:?}", height, coinbase_utxos);
+                        info!("   TX {} (non-coinbase): {} inputs, {} outputs, TXID: {}...", 
+                                 tx_idx, tx.inputs.len(), tx.outputs.len(), txid_str);
+                        if !tx.inputs.is_empty() {
+                            let hash_str: String = tx.inputs[0].prevout.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                            info!("      Spending: {}:{}", hash_str, tx.inputs[0].prevout.index);
                         }
                     }
                 }
-                
-                // Validate with BLVM
-                if height < 100 {
-                    println!("   🔄 [{}] Calling connect_block for block {}...", idx, height);
-                }
-
-                let ctx = blvm_protocol::block::block_validation_context_for_connect_ibd(
-                    None::<&[blvm_protocol::types::BlockHeader]>,
-                    block.header.timestamp,
-                    Network::Mainnet,
-                );
-                let connect_start = std::time::Instant::now();
-                let (result, new_utxo_set, _undo_log) = connect_block(
-                    &block,
-                    &witnesses,
-                    utxo_set.clone(),
-                    height,
-                    &ctx,
-                )?;
-                
-                let connect_duration = connect_start.elapsed();
-                if height < 100 {
-                    println!("   ✅ [{}] connect_block completed for block {} in {:.2}ms", idx, height, connect_duration.as_millis());
-                } else if connect_duration.as_secs() > 1 {
-                    eprintln!("   ⚠️  [{}] connect_block took {:.2}s for block {} (slow!)", idx, connect_duration.as_secs_f64(), height);
-                }
-                
-                if matches!(result, blvm_protocol::types::ValidationResult::Valid) {
-                    utxo_set = new_utxo_set;
-                    if height < 100 {
-                        println!("   ✅ [{}] Block {} validated successfully, UTXO set size: {}", idx, height, utxo_set.len());
-                    }
-                } else {
-                    // OPTIMIZATION: Use string reference instead of clone
-                    let error_msg = match &result {
-                        blvm_protocol::types::ValidationResult::Invalid(msg) => msg.as_str(),
-                        _ => "Unknown error",
-                    };
-                    eprintln!("❌ Block {} validation failed: {}", height, error_msg);
-                    anyhow::bail!("Block {} failed validation during checkpoint generation: {}", height, error_msg);
-                }
-                
-                // Save checkpoint at chunk boundaries
-                // CRITICAL: Save checkpoint at the END of each chunk (before the next chunk starts)
-                // For chunk 0-169, save at height 169 (after processing block 169)
-                // For chunk 170-339, save at height 339 (after processing block 339)
-                // This ensures the checkpoint contains UTXOs from blocks 0-169, not 0-170
-                if height == next_checkpoint - 1 || height == actual_end {
-                    println!("✅ Checkpoint at height {} (UTXO count: {})", height, utxo_set.len());
-                    // NOTE: Must clone here because we continue processing after checkpoint
-                    checkpoints.push((height, utxo_set.clone()));
-                    next_checkpoint += chunk_size;
-                }
-                
-                // Progress indicator - more frequent for early blocks to catch issues
-                if height < 100 && height % 10 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
-                } else if height < 1000 && height % 100 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
-                } else if height % 10_000 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
-                }
-                
-                if height < 100 {
-                    println!("   ✅ [{}] Finished processing block {}, moving to next...", idx, height);
+            }
+        }
+        
+        // Debug: Check UTXO set after each block to see if outputs are being added
+        if height <= 16 {
+            let utxo_set = utxo_set.snapshot()?; // A few coins this early
+            let non_coinbase_utxos: Vec<_> = utxo_set.iter()
+                .filter(|(_, utxo)| !utxo.is_coinbase)
+                .collect();
+            if !non_coinbase_utxos.is_empty() {
+                debug!("🔍 After block {}: {} non-coinbase UTXOs in set", height, non_coinbase_utxos.len());
+                for (outpoint, utxo) in non_coinbase_utxos.iter().take(3) {
+                    let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                    info!("   Non-coinbase UTXO: {}:{} (value={}, height={})", 
+                             hash_str, outpoint.index, utxo.value, utxo.height);
                 }
             }
         }
-        _ => {
-            // For cache/RPC, fetch blocks sequentially (async)
-            for height in start_height..=actual_end {
-                let block_bytes = get_block_data(block_source, height).await?;
-                
-                let (block, witnesses) = deserialize_block_with_witnesses(&block_bytes)?;
-                
-                // DEBUG: Always log BIP30-relevant info for early blocks to diagnose issue
-                if height <= 10 {
-                    use blvm_protocol::block::calculate_tx_id;
-                    use sha2::{Digest, Sha256};
-                    
-                    if let Some(coinbase) = block.transactions.first() {
-                        let txid = calculate_tx_id(coinbase);
-                        eprintln!("   📍 DEBUG Block {}: coinbase txid (first 8) = {}", height, hex::encode(&txid[..8]));
-                        eprintln!("   📍 DEBUG Block {}: UTXO set size before connect_block = {}", height, utxo_set.len());
-                        
-                        // Check for matching UTXOs (what BIP30 will check)
-                        let mut matches = Vec::new();
+        
+        // Debug: Print transaction details for block 15
+        if height == 15 {
+            let utxo_set = utxo_set.snapshot()?;
+            debug!("🔍 DEBUG Block 15: {} transactions", block.transactions.len());
+            info!("   UTXO set size: {}", utxo_set.len());
+            // List all UTXOs in the set
+            info!("   All UTXOs in set:");
+            for (outpoint, utxo) in utxo_set.iter() {
+                let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                info!("      {}:{} (value={}, height={}, coinbase={})", 
+                         hash_str, outpoint.index, utxo.value, utxo.height, utxo.is_coinbase);
+            }
+            for (tx_idx, tx) in block.transactions.iter().enumerate() {
+                info!("   TX {}: {} inputs, {} outputs", tx_idx, tx.inputs.len(), tx.outputs.len());
+                if !tx.inputs.is_empty() {
+                    let hash_str: String = tx.inputs[0].prevout.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                    info!("      First input prevout: {}:{}", hash_str, tx.inputs[0].prevout.index);
+                    // Check if UTXO exists
+                    if let Some(utxo) = utxo_set.get(&tx.inputs[0].prevout) {
+                        info!("      UTXO exists: value={}, height={}, coinbase={}", 
+                                 utxo.value, utxo.height, utxo.is_coinbase);
+                    } else {
+                        info!("      UTXO MISSING!");
+                        // The prevout hash should be a transaction ID from a previous block
+                        // Let's check if we can find it in the UTXO set by searching for matching txids
+                        info!("      Looking for TX that created this UTXO...");
+                        let target_hash = tx.inputs[0].prevout.hash;
+                        let target_index = tx.inputs[0].prevout.index;
+                        let mut found_match = false;
                         for (outpoint, utxo) in utxo_set.iter() {
-                            if outpoint.hash == txid {
-                                matches.push((outpoint.index, utxo.is_coinbase, utxo.height));
+                            if outpoint.hash == target_hash {
+                                found_match = true;
+                                let hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                                info!("      Found matching TX ID in UTXO set: {}:{} (target index: {})", 
+                                         hash_str, outpoint.index, target_index);
+                                info!("      UTXO details: value={}, height={}, coinbase={}", 
+                                         utxo.value, utxo.height, utxo.is_coinbase);
                             }
                         }
-                        if !matches.is_empty() {
-                            eprintln!("   📍 DEBUG Block {}: Found {} UTXO(s) with matching txid:", height, matches.len());
-                            for (idx, is_cb, h) in matches.iter().take(5) {
-                                eprintln!("      OutPoint index: {}, is_coinbase: {}, height: {}", idx, is_cb, h);
+                        if !found_match {
+                            let hash_str: String = target_hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                            info!("      No UTXO found with TX ID: {} (index: {})", hash_str, target_index);
+                            info!("      This UTXO should have been created in a previous block");
+                            // Check if this TX ID matches any coinbase TX ID in the UTXO set
+                            info!("      Checking if this matches any coinbase TX ID...");
+                            let mut found_coinbase_match = false;
+                            for (outpoint, utxo) in utxo_set.iter() {
+                                if utxo.is_coinbase {
+                                    let outpoint_hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                                    if outpoint.hash == target_hash {
+                                        found_coinbase_match = true;
+                                        info!("      ✅ Found matching coinbase TX ID: {}:{} (but index {} doesn't match)", 
+                                                 outpoint_hash_str, outpoint.index, target_index);
+                                        info!("      This suggests the transaction is trying to spend the wrong output index");
+                                        break;
+                                    }
+                                }
+                            }
+                            if !found_coinbase_match {
+                                error!("      ❌ No matching coinbase TX ID found - this UTXO was never created");
                             }
-                        } else {
-                            eprintln!("   📍 DEBUG Block {}: No UTXOs with matching txid found", height);
                         }
                     }
                 }
-                
-                let ctx = blvm_protocol::block::block_validation_context_for_connect_ibd(
-                    None::<&[blvm_protocol::types::BlockHeader]>,
-                    block.header.timestamp,
-                    Network::Mainnet,
-                );
-                let (result, new_utxo_set, _undo_log) = connect_block(
-                    &block,
-                    &witnesses,
-                    utxo_set.clone(),
-                    height,
-                    &ctx,
-                )?;
-                
-                if matches!(result, blvm_protocol::types::ValidationResult::Valid) {
-                    utxo_set = new_utxo_set;
-                } else {
-                    // OPTIMIZATION: Use string reference instead of clone
-                    let error_msg = match &result {
-                        blvm_protocol::types::ValidationResult::Invalid(msg) => msg.as_str(),
-                        _ => "Unknown error",
-                    };
-                    eprintln!("❌ Block {} validation failed: {}", height, error_msg);
-                    anyhow::bail!("Block {} failed validation during checkpoint generation: {}", height, error_msg);
+                // Calculate TX ID
+                use blvm_protocol::block::calculate_tx_id;
+                let txid = calculate_tx_id(tx);
+                let txid_str: String = txid.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                info!("      TX ID: {}...", txid_str);
+            }
+        }
+        
+        // Calculate this block's hash for next block verification
+        // OPTIMIZATION: Cache hash calculation (only compute once per block)
+        use sha2::{Digest, Sha256};
+        let header = &block_bytes[0..80];
+        let first_hash = Sha256::digest(header);
+        let second_hash = Sha256::digest(&first_hash);
+        // OPTIMIZATION: Use array directly instead of Vec allocation
+        let mut current_block_hash: [u8; 32] = second_hash.as_slice().try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid hash length"))?;
+        current_block_hash.reverse(); // Convert to big-endian
+        
+        // Verify previous block hash matches (if not genesis)
+        // For XOR-packaged block files, if prev hash doesn't match, the block boundary detection
+        // is likely wrong. We'll skip validation errors for now and continue to identify
+        // which blocks work correctly.
+        if height > 0 {
+            let prev_hash_in_header = &block_bytes[4..36]; // Previous block hash is at bytes 4-36 (little-endian, as stored)
+            if let Some(prev_hash) = previous_block_hash {
+                // Convert previous_block_hash (big-endian) to little-endian for comparison
+                let prev_hash_le: Vec<u8> = prev_hash.iter().rev().copied().collect();
+                if prev_hash_in_header != prev_hash_le.as_slice() {
+                    // This indicates we're reading too much data - block boundary is wrong
+                    warn!("⚠️  Block {}: Previous block hash mismatch - block boundary detection issue!", height);
+                    info!("   Header has (LE): {}", hex::encode(prev_hash_in_header));
+                    info!("   Expected (LE):   {}", hex::encode(&prev_hash_le));
+                    info!("   Block size: {} bytes (likely reading too much - should use size field or verify hash)", block_bytes.len());
                 }
-                
-                // Save checkpoint at chunk boundaries
-                // CRITICAL: Save checkpoint at the END of each chunk (before the next chunk starts)
-                // For chunk 0-169, save at height 169 (after processing block 169)
-                // For chunk 170-339, save at height 339 (after processing block 339)
-                // This ensures the checkpoint contains UTXOs from blocks 0-169, not 0-170
-                if height == next_checkpoint - 1 || height == actual_end {
-                    println!("✅ Checkpoint at height {} (UTXO count: {})", height, utxo_set.len());
-                    // NOTE: Must clone here because we continue processing after checkpoint
-                    // The checkpoint is saved for parallel validation later
-                    checkpoints.push((height, utxo_set.clone()));
-                    next_checkpoint += chunk_size;
+            }
+        }
+        
+        // Update previous block hash for next iteration
+        previous_block_hash = Some(current_block_hash);
+        
+        // Debug: Check transaction count and verify block hash for problematic blocks
+        if height == 15 || height == 10 {
+            let block_hash_hex = hex::encode(&current_block_hash[..8]);
+            debug!("DEBUG Block {}: Parsed {} transactions, block hash (first 8 bytes) = {}, block size = {} bytes", 
+                     height, block.transactions.len(), block_hash_hex, block_bytes.len());
+            for (i, tx) in block.transactions.iter().enumerate() {
+                debug!("DEBUG Block {}: TX {} has {} inputs, {} outputs", height, i, tx.inputs.len(), tx.outputs.len());
+            }
+        }
+        
+        // Debug: Verify we're calculating the correct coinbase txid
+        #[cfg(debug_assertions)]
+        if height <= 2 {
+            use blvm_protocol::block::calculate_tx_id;
+            let utxo_set = utxo_set.snapshot()?;
+            if let Some(coinbase) = block.transactions.first() {
+                let txid = calculate_tx_id(coinbase);
+                debug!("DEBUG Block {}: coinbase txid = {}", height, hex::encode(txid));
+                debug!("DEBUG Block {}: UTXO set size = {}", height, utxo_set.len());
+                // List all coinbase UTXOs in the set
+                let mut coinbase_utxos = Vec::new();
+                for (outpoint, utxo) in utxo_set.iter() {
+                    if utxo.is_coinbase {
+                        coinbase_utxos.push((hex::encode(outpoint.hash), utxo.height));
+                    }
                 }
-                
-                // Progress indicator - more frequent for early blocks to catch issues
-                if height < 100 && height % 10 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
-                } else if height < 1000 && height % 100 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
-                } else if height % 10_000 == 0 {
-                    println!("📊 Checkpoint generation: {}/{} ({:.1}%)", 
-                             height - start_height, actual_end - start_height,
-                             100.0 * (height - start_height) as f64 / (actual_end - start_height) as f64);
+                if !coinbase_utxos.is_empty() {
+                    debug!("DEBUG Block {}: Coinbase UTXOs in set: {:?}", height, coinbase_utxos);
                 }
             }
         }
+        
+        // Validate with BLVM
+        if height < 100 {
+            debug!("   🔄 [{}] Calling connect_block for block {}...", idx, height);
+        }
+
+        let ctx = blvm_protocol::block::block_validation_context_for_connect_ibd(
+            None::<&[blvm_protocol::types::BlockHeader]>,
+            block.header.timestamp,
+            Network::Mainnet,
+        );
+        let connect_start = std::time::Instant::now();
+        let result = utxo_set.connect(&block, &witnesses, height, &ctx)?;
+        
+        let connect_duration = connect_start.elapsed();
+        if height < 100 {
+            debug!("   ✅ [{}] connect_block completed for block {} in {:.2}ms", idx, height, connect_duration.as_millis());
+        } else if connect_duration.as_secs() > 1 {
+            warn!("   ⚠️  [{}] connect_block took {:.2}s for block {} (slow!)", idx, connect_duration.as_secs_f64(), height);
+        }
+        
+        if matches!(result, blvm_protocol::types::ValidationResult::Valid) {
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().record_block(height, utxo_set.len());
+            if height < 100 {
+                debug!("   ✅ [{}] Block {} validated successfully, UTXO set size: {}", idx, height, utxo_set.len());
+            }
+        } else {
+            // OPTIMIZATION: Use string reference instead of clone
+            let error_msg = match &result {
+                blvm_protocol::types::ValidationResult::Invalid(msg) => msg.as_str(),
+                _ => "Unknown error",
+            };
+            error!("❌ Block {} validation failed: {}", height, error_msg);
+            anyhow::bail!("Block {} failed validation during checkpoint generation: {}", height, error_msg);
+        }
+        
+        // Save checkpoint at chunk boundaries
+        // CRITICAL: Save checkpoint at the END of each chunk (before the next chunk starts)
+        // For chunk 0-169, save at height 169 (after processing block 169)
+        // For chunk 170-339, save at height 339 (after processing block 339)
+        // This ensures the checkpoint contains UTXOs from blocks 0-169, not 0-170
+        if boundaries.get(next_boundary) == Some(&height) {
+            info!("✅ Checkpoint at height {} (UTXO count: {})", height, utxo_set.len());
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().record_cache("checkpoint", false);
+            let secs = last_checkpoint_time.elapsed().as_secs_f64();
+            // Written straight from the live set; the chunk starting here loads it back, so a
+            // failed save fails the run
+            let block_hash = crate::wire::sha256d(&block_bytes[..80]);
+            let stats = utxo_set.save_checkpoint(store, height, &block_hash)?;
+            checkpoints.push(CheckpointTiming {
+                height,
+                utxo_count: utxo_set.len(),
+                stats,
+                secs,
+                loaded: false,
+            });
+            last_checkpoint_time = std::time::Instant::now();
+            next_boundary += 1;
+        }
+        
+        progress.inc(1);
+        
+        if height < 100 {
+            debug!("   ✅ [{}] Finished processing block {}, moving to next...", idx, height);
+        }
     }
+    progress.finish(format!("{} checkpoints", checkpoints.len()));
     
     Ok(checkpoints)
 }
@@ -623,12 +680,14 @@ pub async fn generate_checkpoints(
 /// Process a single block (validate with BLVM and Core)
 /// 
 /// Uses remote-Core RPC for Core validation if available, even when reading from DirectFile/chunks
-async fn process_block(
+async fn process_block<S: BlockSource>(
     block_bytes: &[u8],
     height: u64,
-    utxo_set: &mut UtxoSet,
-    block_source: &BlockDataSource,
-) -> Result<(crate::differential::ValidationResult, crate::differential::CoreValidationResult)> {
+    utxo_set: &mut OverlayUtxoSet,
+    block_times: &mut BlockTimes,
+    block_source: &S,
+    hooks: &HookRegistry,
+) -> Result<(crate::differential::ValidationResult, crate::differential::CoreValidationResult, CoinAgeStats)> {
     use crate::differential::{CoreValidationResult, ValidationResult};
     
     // OPTIMIZATION: Cache remote-Core RPC client to avoid creating new one for each block
@@ -637,11 +696,16 @@ async fn process_block(
     static REMOTE_CORE_RPC_CLIENT: Mutex<Option<Arc<crate::remote_core_rpc::RemoteCoreRpcClient>>> = Mutex::new(None);
     
     let has_remote_core_rpc = crate::block_cache_env::remote_core_rpc_env_ready();
-    use blvm_protocol::block::connect_block;
     use blvm_protocol::segwit::Witness;
     use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
     use blvm_protocol::types::Network;
     
+    if let Err(rejection) =
+        crate::sanity::filter(crate::sanity::SanityStage::Validation).check(block_bytes)
+    {
+        anyhow::bail!("Block at height {} failed sanity check: {}", height, rejection);
+    }
+
     let (block, witnesses) = match deserialize_block_with_witnesses(block_bytes) {
         Ok((b, w)) => (b, w),
         Err(e) => {
@@ -654,51 +718,51 @@ async fn process_block(
         let coinbase_count = block.transactions.iter().filter(|tx| blvm_protocol::transaction::is_coinbase(tx)).count();
         let non_coinbase_count = block.transactions.len() - coinbase_count;
         if non_coinbase_count > 0 {
-            eprintln!("   🔍 DEBUG Block {}: {} total transactions ({} coinbase, {} non-coinbase)", 
+            debug!("   🔍 DEBUG Block {}: {} total transactions ({} coinbase, {} non-coinbase)", 
                      height, block.transactions.len(), coinbase_count, non_coinbase_count);
         }
     }
     
     // DEBUG: Log UTXO set and transaction details for problematic blocks
     if height == 15 || height == 17 || height == 86 || height == 120 || height == 126 || height == 153 || height == 160 || height == 318 {
-        eprintln!("   🔍 DEBUG process_block Block {}: UTXO set size before connect_block: {}", height, utxo_set.len());
-        eprintln!("      Block has {} transactions", block.transactions.len());
+        debug!("   🔍 DEBUG process_block Block {}: UTXO set size before connect_block: {}", height, utxo_set.len());
+        info!("      Block has {} transactions", block.transactions.len());
         // Calculate txids for all transactions in this block
         use blvm_protocol::block::calculate_tx_id;
         let block_txids: Vec<_> = block.transactions.iter().map(|tx| calculate_tx_id(tx)).collect();
         for (tx_idx, tx) in block.transactions.iter().enumerate() {
             let txid_str: String = block_txids[tx_idx].iter().take(8).map(|b| format!("{:02x}", b)).collect();
-            eprintln!("      TX {}: txid (first 8) = {}", tx_idx, txid_str);
+            info!("      TX {}: txid (first 8) = {}", tx_idx, txid_str);
             if !blvm_protocol::transaction::is_coinbase(tx) && !tx.inputs.is_empty() {
                 for (input_idx, input) in tx.inputs.iter().enumerate() {
                     let hash_str: String = input.prevout.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                    eprintln!("         Input {}: prevout {}:{}", input_idx, hash_str, input.prevout.index);
+                    info!("         Input {}: prevout {}:{}", input_idx, hash_str, input.prevout.index);
                     if let Some(utxo) = utxo_set.get(&input.prevout) {
-                        eprintln!("            ✅ UTXO exists in utxo_set: value={}, height={}, coinbase={}", utxo.value, utxo.height, utxo.is_coinbase);
+                        info!("            ✅ UTXO exists in utxo_set: value={}, height={}, coinbase={}", utxo.value, utxo.height, utxo.is_coinbase);
                     } else {
-                        eprintln!("            ❌ UTXO MISSING in utxo_set");
+                        error!("            ❌ UTXO MISSING in utxo_set");
                         // Check if it might be from an earlier transaction in this block
                         if tx_idx > 0 {
-                            eprintln!("            🔍 Checking if from earlier transaction in this block...");
+                            debug!("            🔍 Checking if from earlier transaction in this block...");
                             for prev_tx_idx in 0..tx_idx {
                                 let prev_txid_str: String = block_txids[prev_tx_idx].iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                eprintln!("               Comparing with TX {}: txid (first 8) = {}", prev_tx_idx, prev_txid_str);
+                                info!("               Comparing with TX {}: txid (first 8) = {}", prev_tx_idx, prev_txid_str);
                                 if input.prevout.hash == block_txids[prev_tx_idx] {
-                                    eprintln!("               ✅ MATCH! This is from TX {} in this block, output index: {}", 
+                                    info!("               ✅ MATCH! This is from TX {} in this block, output index: {}", 
                                              prev_tx_idx, input.prevout.index);
-                                    eprintln!("               🔍 This should be available in temp_utxo_set during validation");
+                                    debug!("               🔍 This should be available in temp_utxo_set during validation");
                                 }
                             }
                         }
                         // Check if it's from a previous block (should be in utxo_set)
-                        eprintln!("            🔍 Checking if from previous blocks...");
-                        eprintln!("            🔍 Looking for prevout hash: {} (full 32 bytes)", hex::encode(&input.prevout.hash));
+                        debug!("            🔍 Checking if from previous blocks...");
+                        debug!("            🔍 Looking for prevout hash: {} (full 32 bytes)", hex::encode(&input.prevout.hash));
                         let mut found_in_prev = false;
                         let mut partial_matches = Vec::new();
                         for (outpoint, utxo) in utxo_set.iter() {
                             if outpoint.hash == input.prevout.hash {
                                 found_in_prev = true;
-                                eprintln!("            ✅ Found exact match: {}:{} (value={}, height={}, coinbase={})", 
+                                info!("            ✅ Found exact match: {}:{} (value={}, height={}, coinbase={})", 
                                          hex::encode(&outpoint.hash[..8]), outpoint.index, utxo.value, utxo.height, utxo.is_coinbase);
                             } else if outpoint.hash[..8] == input.prevout.hash[..8] {
                                 // First 8 bytes match but full hash doesn't - this is suspicious
@@ -707,17 +771,17 @@ async fn process_block(
                         }
                         if !found_in_prev {
                             if !partial_matches.is_empty() {
-                                eprintln!("            ⚠️  Found {} partial matches (first 8 bytes match but full hash doesn't):", partial_matches.len());
+                                warn!("            ⚠️  Found {} partial matches (first 8 bytes match but full hash doesn't):", partial_matches.len());
                                 for (hash8, idx, h) in partial_matches.iter().take(5) {
-                                    eprintln!("               {}:{} (height={})", hash8, idx, h);
+                                    info!("               {}:{} (height={})", hash8, idx, h);
                                 }
                             }
-                            eprintln!("            ❌ Not found in any previous block either");
+                            error!("            ❌ Not found in any previous block either");
                             // List all UTXOs in the set to see what we have
-                            eprintln!("            📋 All UTXOs in set (first 15):");
+                            info!("            📋 All UTXOs in set (first 15):");
                             for (idx, (outpoint, utxo)) in utxo_set.iter().take(15).enumerate() {
                                 let op_hash_str: String = outpoint.hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
-                                eprintln!("               {}: {}:{} (value={}, height={}, coinbase={})", 
+                                info!("               {}: {}:{} (value={}, height={}, coinbase={})", 
                                          idx, op_hash_str, outpoint.index, utxo.value, utxo.height, utxo.is_coinbase);
                             }
                         }
@@ -727,29 +791,60 @@ async fn process_block(
         }
     }
     
+    // Coin age of every input - prevouts have to be looked up before connect_block spends them
+    let block_time = block.header.timestamp as u32;
+    block_times.record(height, block_time);
+    let mut coin_age = CoinAgeAccumulator::new(height, block_time);
+    // Hooks get the same coins, so they are collected on the way
+    let mut spent_coins = UtxoSet::default();
+    for tx in block.transactions.iter().filter(|tx| !blvm_protocol::transaction::is_coinbase(tx)) {
+        for input in &tx.inputs {
+            match utxo_set.get(&input.prevout) {
+                Some(utxo) => {
+                    coin_age.spend(
+                        u64::try_from(utxo.value).unwrap_or(0),
+                        u64::from(utxo.height),
+                        block_times,
+                    );
+                    if !hooks.is_empty() {
+                        insert_utxo(&mut spent_coins, input.prevout.clone(), utxo.clone());
+                    }
+                }
+                None => coin_age.unresolved(),
+            }
+        }
+    }
+    
+    // Whole sets cost a full copy of the overlay each, so only for hooks that ask for them
+    let utxo_len_before = utxo_set.len();
+    let full_utxo_before = hooks.wants_full_utxo_set().then(|| utxo_set.to_utxo_set());
+    if !hooks.is_empty() {
+        hooks.pre_block(&BlockContext {
+            height,
+            block: &block,
+            block_bytes,
+            witnesses: &witnesses,
+            spent_coins: &spent_coins,
+            utxo_set: full_utxo_before.as_ref(),
+        })?;
+    }
+    
     let ctx = blvm_protocol::block::block_validation_context_for_connect_ibd(
         None::<&[blvm_protocol::types::BlockHeader]>,
         block.header.timestamp,
         Network::Mainnet,
     );
-    let blvm_result = match connect_block(
-        &block,
-        &witnesses,
-        utxo_set.clone(),
-        height,
-        &ctx,
-    ) {
-        Ok((result, new_utxo_set, _undo_log)) => {
-            *utxo_set = new_utxo_set;
-            match result {
-                blvm_protocol::types::ValidationResult::Valid => ValidationResult::Valid,
-                blvm_protocol::types::ValidationResult::Invalid(msg) => {
-                    ValidationResult::Invalid(msg)
-                }
-            }
-        }
+    let connect_start = std::time::Instant::now();
+    // Connected against the coins the block touches; the overlay keeps the checkpoint shared
+    let connect_result = utxo_set.connect(&block, &witnesses, height, &ctx);
+    let blvm_duration = connect_start.elapsed();
+    let blvm_result = match connect_result {
+        Ok(blvm_protocol::types::ValidationResult::Valid) => ValidationResult::Valid,
+        Ok(blvm_protocol::types::ValidationResult::Invalid(msg)) => ValidationResult::Invalid(msg),
         Err(e) => ValidationResult::Invalid(format!("{:?}", e)),
     };
+    #[cfg(feature = "metrics")]
+    crate::metrics::global().record_block(height, utxo_set.len());
     
     // Validate with Core
     // CRITICAL: Use remote-Core RPC if available, even when reading from DirectFile/chunks
@@ -781,262 +876,198 @@ async fn process_block(
             CoreValidationResult::Invalid("Block too short".to_string())
         }
     } else {
-        // Fallback to source-specific validation
-        match block_source {
-            BlockDataSource::DirectFile(_) => {
-                // No remote-Core RPC available, assume valid for direct file reading
-                CoreValidationResult::Valid
-            }
-        BlockDataSource::SharedCache(_, Some(client)) | BlockDataSource::Rpc(client) => {
-            // Calculate block hash to check with Core
-            // OPTIMIZATION: Use fixed-size array instead of Vec allocation
-            // OPTIMIZATION: Cache hash calculation if called multiple times
-            use sha2::{Digest, Sha256};
-            if block_bytes.len() >= 80 {
-                let header = &block_bytes[0..80];
-                let first_hash = Sha256::digest(header);
-                let second_hash = Sha256::digest(&first_hash);
-                // Reverse bytes for Core RPC (Core displays hashes in reverse)
-                // OPTIMIZATION: Use array instead of Vec to avoid allocation
-                let mut hash_bytes: [u8; 32] = second_hash.as_slice().try_into()
-                    .unwrap_or_else(|_| {
-                        // Fallback: create zero array if conversion fails
-                        [0u8; 32]
-                    });
-                hash_bytes.reverse();
-                let block_hash = hex::encode(hash_bytes);
-                
-                match client.getblock(&block_hash, 1).await {
-                    Ok(_) => CoreValidationResult::Valid,
-                    Err(_) => CoreValidationResult::Invalid("Block not in chain".to_string()),
-                }
-            } else {
-                CoreValidationResult::Invalid("Block too short".to_string())
-            }
-        }
-        BlockDataSource::RemoteCoreRpc(client) => {
-            // Calculate block hash to check with Core
-            // OPTIMIZATION: Use fixed-size array instead of Vec allocation
-            use sha2::{Digest, Sha256};
-            if block_bytes.len() >= 80 {
-                let header = &block_bytes[0..80];
-                let first_hash = Sha256::digest(header);
-                let second_hash = Sha256::digest(&first_hash);
-                // Reverse bytes for Core RPC (Core displays hashes in reverse)
-                // OPTIMIZATION: Use array instead of Vec to avoid allocation
-                let mut hash_bytes: [u8; 32] = second_hash.as_slice().try_into()
-                    .map_err(|_| anyhow::anyhow!("Invalid hash length"))?;
-                hash_bytes.reverse();
-                let block_hash = hex::encode(hash_bytes);
-                
-                // Remote-Core RPC - just check if we can get the block
-                match client.get_block_hex(&block_hash).await {
-                    Ok(_) => CoreValidationResult::Valid,
-                    Err(_) => CoreValidationResult::Invalid("Block not in chain".to_string()),
-                }
-            } else {
-                CoreValidationResult::Invalid("Block too short".to_string())
-            }
-        }
-            _ => {
-                // No RPC client available, assume valid
-                CoreValidationResult::Valid
+        // Fall back to the Core node behind the block source, if it has one
+        if block_bytes.len() >= 80 {
+            // Core RPC takes hashes in display order
+            let hash_hex = crate::wire::display_hex(&crate::wire::sha256d(&block_bytes[0..80]));
+            match block_source.core_has_block(&hash_hex).await {
+                Some(false) => CoreValidationResult::Invalid("Block not in chain".to_string()),
+                // No Core to ask (block files, cache without RPC): assume valid
+                Some(true) | None => CoreValidationResult::Valid,
             }
+        } else {
+            CoreValidationResult::Invalid("Block too short".to_string())
         }
     };
     
-    Ok((blvm_result, core_result))
+    if !hooks.is_empty() {
+        let full_utxo_after = full_utxo_before.as_ref().map(|_| utxo_set.to_utxo_set());
+        hooks.post_block(
+            &BlockContext {
+                height,
+                block: &block,
+                block_bytes,
+                witnesses: &witnesses,
+                spent_coins: &spent_coins,
+                utxo_set: full_utxo_before.as_ref(),
+            },
+            &BlockOutcome {
+                blvm: &blvm_result,
+                core: &core_result,
+                utxo_len_before: Some(utxo_len_before),
+                utxo_len_after: Some(utxo_set.len()),
+                utxo_set_after: full_utxo_after.as_ref(),
+                blvm_duration,
+            },
+        )?;
+    }
+    
+    Ok((blvm_result, core_result, coin_age.finish()))
+}
+
+/// A chunk waiting in the work queue; its checkpoint stays on disk until a worker takes it.
+struct QueuedChunk {
+    chunk: BlockChunk,
+    /// Height of the stored checkpoint the chunk starts from
+    checkpoint: Option<u64>,
+    cost: f64,
+}
+
+/// The stored checkpoint after block `height`, verified against `block_source`'s chain.
+async fn load_chunk_checkpoint<S: BlockSource>(
+    store: &CheckpointStore,
+    height: u64,
+    block_source: &S,
+) -> Result<Arc<UtxoSet>> {
+    let block_hash = crate::checkpoint_store::block_hash_at(block_source, height).await?;
+    Ok(Arc::new(store.load(height, &block_hash)?))
 }
 
 /// Validate a single chunk of blocks
 /// 
 /// Uses optimized block data source (direct file reading if available).
-pub async fn validate_chunk(
+/// Stops between blocks with [`Cancelled`](crate::cancel::Cancelled) once `cancel` fires.
+/// `hooks` run around each block; a hook error fails the chunk.
+pub async fn validate_chunk<S: BlockSource>(
     chunk: BlockChunk,
-    block_source: Arc<BlockDataSource>,
+    block_source: Arc<S>,
+    cancel: CancellationToken,
+    hooks: HookRegistry,
 ) -> Result<ChunkResult> {
     use crate::differential::{CoreValidationResult, ValidationResult};
     use std::time::Instant;
     
     let start_time = Instant::now();
-    let mut utxo_set = chunk.checkpoint_utxo.unwrap_or_default();
+    let mut utxo_set = OverlayUtxoSet::new(chunk.checkpoint_utxo.unwrap_or_default());
     // OPTIMIZATION: Pre-allocate divergences vector (most tests have 0-10 divergences)
     let mut divergences = Vec::with_capacity(10);
     let mut tested = 0;
     let mut matched = 0;
+    // Creation times for coins older than this chunk come from `chunks.times` when built
+    let mut block_times = BlockTimes::preload_from_chunk_cache();
+    let mut coin_age = Vec::new();
     
-    // Get chain height
-    let chain_height = match block_source.as_ref() {
-        BlockDataSource::Rpc(client) => client.getblockcount().await?,
-        BlockDataSource::RemoteCoreRpc(client) => client.get_block_count().await?,
-        BlockDataSource::SharedCache(_, Some(client)) => client.getblockcount().await?,
-        BlockDataSource::DirectFile(_) => chunk.end_height, // Don't know exact height
-        BlockDataSource::SharedCache(_, None) => chunk.end_height, // Don't know exact height
-    };
+    let chain_height = block_source.get_tip_height().await?.unwrap_or(chunk.end_height);
     let actual_end = chunk.end_height.min(chain_height);
     
-    // Process blocks based on data source
-    println!("   📍 DEBUG: validate_chunk: Processing blocks, source type: {:?}", 
-             std::mem::discriminant(block_source.as_ref()));
-    match block_source.as_ref() {
-        BlockDataSource::DirectFile(reader) => {
-            println!("   📍 DEBUG: Using DirectFile source, calling read_blocks_sequential...");
-            // Direct file reading - sequential iterator (fastest!)
-            let iterator = reader.read_blocks_sequential(
-                Some(chunk.start_height),
-                Some((actual_end - chunk.start_height + 1) as usize)
-            )?;
-            println!("   📍 DEBUG: Got iterator, starting to enumerate blocks...");
-            
-            for (idx, block_result) in iterator.enumerate() {
-                let height = chunk.start_height + idx as u64;
-                if idx == 0 {
-                    println!("   📍 DEBUG: Processing first block at height {}", height);
-                }
-                let block_bytes = match block_result {
-                    Ok(bytes) => bytes,
-                    Err(e) => {
-                        eprintln!("   ❌ ERROR: Failed to read block at index {}: {}", idx, e);
-                        return Err(e.into());
-                    }
-                };
-                
-                if idx == 0 {
-                    println!("   📍 DEBUG: Got first block ({} bytes), calling process_block...", block_bytes.len());
-                }
-                
-                // Process block (same logic for both paths)
-                let (blvm_result, core_result) = process_block(
-                    &block_bytes,
-                    height,
-                    &mut utxo_set,
-                    block_source.as_ref(),
-                ).await?;
-                
-                // Compare and record results
-                let matches = matches!(
-                    (&blvm_result, &core_result),
-                    (ValidationResult::Valid, CoreValidationResult::Valid)
-                        | (
-                            ValidationResult::Invalid(_),
-                            CoreValidationResult::Invalid(_)
-                        )
-                );
-                
-                if !matches {
-                    // OPTIMIZATION: Use format! directly instead of intermediate strings
-                    let blvm_str = match &blvm_result {
-                        ValidationResult::Valid => "Valid".to_string(),
-                        ValidationResult::Invalid(msg) => format!("Invalid({})", msg),
-                    };
-                    let core_str = match &core_result {
-                        CoreValidationResult::Valid => "Valid".to_string(),
-                        CoreValidationResult::Invalid(msg) => format!("Invalid({})", msg),
-                    };
-                    divergences.push((height, blvm_str.clone(), core_str.clone()));
-                    eprintln!("❌ DIVERGENCE at height {}: BLVM={}, Core={}", 
-                             height, blvm_str, core_str);
-                    
-                    // Log first few divergences with more detail
-                    if divergences.len() <= 5 {
-                        use sha2::{Digest, Sha256};
-                        if block_bytes.len() >= 80 {
-                            let header = &block_bytes[0..80];
-                            let first_hash = Sha256::digest(header);
-                            let second_hash = Sha256::digest(&first_hash);
-                            let mut hash_bytes = second_hash.as_slice().to_vec();
-                            hash_bytes.reverse();
-                            let block_hash = hex::encode(&hash_bytes[..8]);
-                            eprintln!("   Block hash (first 8 bytes): {}", block_hash);
-                        }
-                    }
-                } else {
-                    matched += 1;
-                }
-                
-                tested += 1;
-                
-                // Progress indicator every 100 blocks (more frequent for better feedback)
-                if tested % 100 == 0 || tested == 1 {
-                    let total = actual_end - chunk.start_height + 1;
-                    let pct = 100.0 * tested as f64 / total as f64;
-                    let elapsed = start_time.elapsed().as_secs_f64();
-                    let rate = tested as f64 / elapsed;
-                    println!("📊 Chunk [{}-{}]: {}/{} blocks ({:.1}%) @ {:.1} blocks/sec", 
-                             chunk.start_height, actual_end, tested, total, pct, rate);
-                }
+    let progress = crate::progress::Progress::new(
+        format!("chunk {}-{}", chunk.start_height, actual_end),
+        actual_end - chunk.start_height + 1,
+        "blocks",
+    );
+    let mut blocks = block_source
+        .iter_sequential(chunk.start_height, actual_end - chunk.start_height + 1, &cancel)?
+        .enumerate();
+    while let Some((idx, block_result)) = blocks.next().await {
+        let height = chunk.start_height + idx as u64;
+        if idx == 0 {
+            debug!("   📍 DEBUG: Processing first block at height {}", height);
+        }
+        let block_bytes = match block_result {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("   ❌ ERROR: Failed to read block at index {}: {}", idx, e);
+                return Err(e.into());
             }
+        };
+        
+        if idx == 0 {
+            debug!("   📍 DEBUG: Got first block ({} bytes), calling process_block...", block_bytes.len());
         }
-        _ => {
-            // For cache/RPC, fetch blocks sequentially (async)
-            for height in chunk.start_height..=actual_end {
-                let block_bytes = get_block_data(block_source.as_ref(), height).await?;
-                
-                // Process block (same logic)
-                let (blvm_result, core_result) = process_block(
-                    &block_bytes,
-                    height,
-                    &mut utxo_set,
-                    block_source.as_ref(),
-                ).await?;
-                
-                // Compare and record results
-                let matches = matches!(
-                    (&blvm_result, &core_result),
-                    (ValidationResult::Valid, CoreValidationResult::Valid)
-                        | (
-                            ValidationResult::Invalid(_),
-                            CoreValidationResult::Invalid(_)
-                        )
-                );
-                
-                if !matches {
-                    // OPTIMIZATION: Use format! directly instead of intermediate strings
-                    let blvm_str = match &blvm_result {
-                        ValidationResult::Valid => "Valid".to_string(),
-                        ValidationResult::Invalid(msg) => format!("Invalid({})", msg),
-                    };
-                    let core_str = match &core_result {
-                        CoreValidationResult::Valid => "Valid".to_string(),
-                        CoreValidationResult::Invalid(msg) => format!("Invalid({})", msg),
-                    };
-                    divergences.push((height, blvm_str.clone(), core_str.clone()));
-                    eprintln!("❌ DIVERGENCE at height {}: BLVM={}, Core={}", 
-                             height, blvm_str, core_str);
-                    
-                    // Log first few divergences with more detail
-                    if divergences.len() <= 5 {
-                        use sha2::{Digest, Sha256};
-                        if block_bytes.len() >= 80 {
-                            let header = &block_bytes[0..80];
-                            let first_hash = Sha256::digest(header);
-                            let second_hash = Sha256::digest(&first_hash);
-                            let mut hash_bytes = second_hash.as_slice().to_vec();
-                            hash_bytes.reverse();
-                            let block_hash = hex::encode(&hash_bytes[..8]);
-                            eprintln!("   Block hash (first 8 bytes): {}", block_hash);
-                        }
-                    }
-                } else {
-                    matched += 1;
-                }
-                
-                tested += 1;
-                
-                // Progress indicator every 100 blocks (more frequent for better feedback)
-                if tested % 100 == 0 || tested == 1 {
-                    let total = actual_end - chunk.start_height + 1;
-                    let pct = 100.0 * tested as f64 / total as f64;
-                    let elapsed = start_time.elapsed().as_secs_f64();
-                    let rate = tested as f64 / elapsed;
-                    println!("📊 Chunk [{}-{}]: {}/{} blocks ({:.1}%) @ {:.1} blocks/sec", 
-                             chunk.start_height, actual_end, tested, total, pct, rate);
+        
+        // Process block (same logic for both paths)
+        let (blvm_result, core_result, block_coin_age) = process_block(
+            &block_bytes,
+            height,
+            &mut utxo_set,
+            &mut block_times,
+            block_source.as_ref(),
+            &hooks,
+        ).await?;
+        coin_age.push(block_coin_age);
+        
+        // Compare and record results
+        let matches = matches!(
+            (&blvm_result, &core_result),
+            (ValidationResult::Valid, CoreValidationResult::Valid)
+                | (
+                    ValidationResult::Invalid(_),
+                    CoreValidationResult::Invalid(_)
+                )
+        );
+        
+        if !matches {
+            // OPTIMIZATION: Use format! directly instead of intermediate strings
+            let blvm_str = match &blvm_result {
+                ValidationResult::Valid => "Valid".to_string(),
+                ValidationResult::Invalid(msg) => format!("Invalid({})", msg),
+            };
+            let core_str = match &core_result {
+                CoreValidationResult::Valid => "Valid".to_string(),
+                CoreValidationResult::Invalid(msg) => format!("Invalid({})", msg),
+            };
+            #[cfg(feature = "metrics")]
+            crate::metrics::global().record_divergence();
+            hooks.on_divergence(&DivergenceEvent {
+                height,
+                block_bytes: &block_bytes,
+                blvm: &blvm_str,
+                core: &core_str,
+            })?;
+            divergences.push((height, blvm_str.clone(), core_str.clone()));
+            error!("❌ DIVERGENCE at height {}: BLVM={}, Core={}", 
+                     height, blvm_str, core_str);
+            
+            // Log first few divergences with more detail
+            if divergences.len() <= 5 {
+                use sha2::{Digest, Sha256};
+                if block_bytes.len() >= 80 {
+                    let header = &block_bytes[0..80];
+                    let first_hash = Sha256::digest(header);
+                    let second_hash = Sha256::digest(&first_hash);
+                    let mut hash_bytes = second_hash.as_slice().to_vec();
+                    hash_bytes.reverse();
+                    let block_hash = hex::encode(&hash_bytes[..8]);
+                    info!("   Block hash (first 8 bytes): {}", block_hash);
                 }
             }
+        } else {
+            matched += 1;
+        }
+        
+        tested += 1;
+        
+        progress.inc(1);
+        if !divergences.is_empty() {
+            progress.set_message(format!("{} divergences", divergences.len()));
         }
     }
-    
+
     let duration = start_time.elapsed().as_secs_f64();
+    progress.finish(format!(
+        "{}/{} matched in {:.1}s ({:.1} blocks/sec)",
+        matched,
+        tested,
+        duration,
+        tested as f64 / duration.max(f64::EPSILON)
+    ));
+    debug!(
+        "   Chunk {}-{}: {} UTXOs, {} held outside the shared checkpoint",
+        chunk.start_height,
+        actual_end,
+        utxo_set.len(),
+        utxo_set.delta_len()
+    );
     
     Ok(ChunkResult {
         start_height: chunk.start_height,
@@ -1045,37 +1076,105 @@ pub async fn validate_chunk(
         matched,
         divergences,
         duration_secs: duration,
+        coin_age,
+        checkpoint: chunk.checkpoint_timing,
     })
 }
 
 /// Run parallel differential tests
 /// 
 /// Uses optimized block data source (direct file reading if available, then cache, then RPC).
-pub async fn run_parallel_differential(
+pub async fn run_parallel_differential<S: BlockSource + 'static>(
     start_height: u64,
     end_height: u64,
     config: ParallelConfig,
-    block_source: Arc<BlockDataSource>,
+    block_source: Arc<S>,
 ) -> Result<Vec<ChunkResult>> {
-    // Get chain height
-    let chain_height = match block_source.as_ref() {
-        BlockDataSource::Rpc(client) => client.getblockcount().await?,
-        BlockDataSource::RemoteCoreRpc(client) => client.get_block_count().await?,
-        BlockDataSource::SharedCache(_, Some(client)) => client.getblockcount().await?,
-        BlockDataSource::DirectFile(_) => {
-            // For direct file reading, we don't know exact height
-            // Use end_height as estimate
-            end_height
+    run_parallel_differential_with_cancel(
+        start_height,
+        end_height,
+        config,
+        block_source,
+        CancellationToken::new(),
+    )
+    .await
+}
+
+/// [`run_parallel_differential`] with a caller-owned cancellation token.
+///
+/// Cancelling stops checkpoint generation, stops scheduling new chunks and makes in-flight chunks
+/// return [`Cancelled`](crate::cancel::Cancelled) at the next block boundary. Results of chunks
+/// that finished before the cancel are still returned.
+pub async fn run_parallel_differential_with_cancel<S: BlockSource + 'static>(
+    start_height: u64,
+    end_height: u64,
+    config: ParallelConfig,
+    block_source: Arc<S>,
+    cancel: CancellationToken,
+) -> Result<Vec<ChunkResult>> {
+    crate::progress::ensure_logging();
+    let chain_height = block_source.get_tip_height().await?.unwrap_or(end_height);
+    let actual_end = end_height.min(chain_height);
+    
+    #[cfg(feature = "metrics")]
+    crate::metrics::start_from_env().await?;
+    #[cfg(feature = "input-script-diff")]
+    let (config, input_scripts) = {
+        let mut config = config;
+        let hook = crate::input_script_diff::InputScriptDiffHook::enabled_from_env()
+            .then(|| Arc::new(crate::input_script_diff::InputScriptDiffHook::from_env()));
+        if let Some(hook) = &hook {
+            config.hooks.register(hook.clone());
         }
-        _ => end_height,
+        (config, hook)
     };
-    let actual_end = end_height.min(chain_height);
+    // Per-block chain dataset, opt-in with BLVM_BLOCK_STATS=<file>
+    let (config, block_stats) = {
+        let mut config = config;
+        let hook = crate::block_stats::BlockStatsHook::from_env().map(Arc::new);
+        if let Some(hook) = &hook {
+            config.hooks.register(hook.clone());
+        }
+        (config, hook)
+    };
+    
+    info!("🚀 Starting parallel differential test");
+    info!("   Range: {} to {}", start_height, actual_end);
+    info!("   Chunk size: {} ({:?})", config.chunk_size, config.scheduling);
+    info!("   Workers: {}", config.num_workers);
+    info!("   Use checkpoints: {}", config.use_checkpoints);
+    if !config.hooks.is_empty() {
+        info!("   Hooks: {:?}", config.hooks);
+    }
     
-    println!("🚀 Starting parallel differential test");
-    println!("   Range: {} to {}", start_height, actual_end);
-    println!("   Chunk size: {}", config.chunk_size);
-    println!("   Workers: {}", config.num_workers);
-    println!("   Use checkpoints: {}", config.use_checkpoints);
+    // An assumeutxo snapshot stands in for every block up to its base
+    let (start_height, base_utxo) = match &config.assumeutxo_snapshot {
+        Some(path) => {
+            let snapshot = crate::assumeutxo::load_snapshot_for_source(
+                path,
+                BlockFileNetwork::Mainnet,
+                block_source.as_ref(),
+            )
+            .await?;
+            let first = snapshot.height() + 1;
+            anyhow::ensure!(
+                start_height <= first,
+                "assumeutxo snapshot at height {} can seed a run starting at {} at the latest (requested {})",
+                snapshot.height(),
+                first,
+                start_height
+            );
+            if start_height < first {
+                info!("   Snapshot covers heights {}..={} - starting at {}", start_height, snapshot.height(), first);
+            }
+            (first, Some(Arc::new(snapshot.utxo_set)))
+        }
+        None => (start_height, None),
+    };
+    if start_height > actual_end {
+        info!("✅ Nothing to validate above the snapshot (tip {})", actual_end);
+        return Ok(Vec::new());
+    }
     
     // If index is incomplete, use RPC to fill missing blocks
     // Chunks are primary - RPC is fallback for any missing blocks
@@ -1090,165 +1189,247 @@ pub async fn run_parallel_differential(
             
             if index.len() <= 1 || has_gaps || missing_early_blocks {
                 // Index is incomplete - use RPC to fill missing blocks
-                println!("\n🔨 Index incomplete ({} entries, expected {}) - filling missing blocks via remote-Core RPC...", 
+                info!("\n🔨 Index incomplete ({} entries, expected {}) - filling missing blocks via remote-Core RPC...", 
                         index.len(), expected_entries);
-                println!("   💡 Chunks are primary - RPC only fills gaps");
+                info!("   💡 Chunks are primary - RPC only fills gaps");
                 
                 use crate::chunk_index_rpc::build_block_index_via_rpc;
                 let rpc_index = build_block_index_via_rpc(cache_dir, Some(actual_end)).await?;
-                println!("   ✅ Built complete index via RPC ({} entries)", rpc_index.len());
+                info!("   ✅ Built complete index via RPC ({} entries)", rpc_index.len());
                 use crate::chunk_index::save_block_index;
                 save_block_index(cache_dir, &rpc_index)?;
-                println!("   💾 Saved complete index");
+                info!("   💾 Saved complete index");
             }
         } else {
             // No index exists - build via RPC (chunks + missing blocks)
-            println!("\n🔨 No index found - building via remote-Core RPC (chunks + missing blocks)...");
+            info!("\n🔨 No index found - building via remote-Core RPC (chunks + missing blocks)...");
             use crate::chunk_index_rpc::build_block_index_via_rpc;
             let rpc_index = build_block_index_via_rpc(cache_dir, Some(actual_end)).await?;
-            println!("   ✅ Built complete index via RPC ({} entries)", rpc_index.len());
+            info!("   ✅ Built complete index via RPC ({} entries)", rpc_index.len());
             use crate::chunk_index::save_block_index;
             save_block_index(cache_dir, &rpc_index)?;
-            println!("   💾 Saved complete index");
+            info!("   💾 Saved complete index");
         }
     }
     
-    // Generate checkpoints if enabled
-    let checkpoints = if config.use_checkpoints {
-        println!("\n📌 Phase 1: Generating UTXO checkpoints...");
-        generate_checkpoints(start_height, actual_end, config.chunk_size, block_source.as_ref()).await?
-    } else {
-        Vec::new()
+    // Heights finished by an earlier invocation are returned from the run state, not re-run,
+    // however that invocation cut its chunks
+    let run_state = match &config.run_state_dir {
+        Some(dir) => Some(Arc::new(RunStateStore::open(dir)?)),
+        None => None,
     };
-    
-    // Create chunks
-    let mut chunks = Vec::new();
-    let mut current_start = start_height;
-    let mut checkpoint_idx = 0;
-    
-    while current_start <= actual_end {
-        let chunk_end = (current_start + config.chunk_size - 1).min(actual_end);
-        
-        // Find checkpoint UTXO for this chunk
-        let checkpoint_utxo = if config.use_checkpoints && checkpoint_idx > 0 {
-            // Use previous checkpoint as starting UTXO
-            checkpoints.get(checkpoint_idx - 1).map(|(_, utxo)| utxo.clone())
-        } else if current_start == start_height {
-            // First chunk starts with empty UTXO set
-            Some(UtxoSet::default())
-        } else {
-            None
-        };
-        
-        chunks.push(BlockChunk {
-            start_height: current_start,
-            end_height: chunk_end,
-            checkpoint_utxo,
-            skip_validation: !config.use_checkpoints, // Skip validation if checkpoints disabled
-        });
-        
-        current_start = chunk_end + 1;
-        if current_start <= actual_end && checkpoint_idx < checkpoints.len() {
-            checkpoint_idx += 1;
+    let resumed = match &run_state {
+        Some(store) => store.completed_within(start_height, actual_end),
+        None => Vec::new(),
+    };
+    let remaining = crate::run_state::uncovered(start_height, actual_end, &resumed);
+    if let Some(store) = &run_state {
+        if !resumed.is_empty() {
+            info!(
+                "   ♻️  {} chunk(s) already finished, {} block(s) left (run state in {})",
+                resumed.len(),
+                remaining.iter().map(|(start, end)| end - start + 1).sum::<u64>(),
+                store.dir().display()
+            );
         }
     }
-    
-    println!("\n📦 Created {} chunks for parallel execution", chunks.len());
+    if remaining.is_empty() {
+        info!("✅ All chunks already finished - nothing to validate");
+        return Ok(resumed);
+    }
     
     // If checkpoints disabled, run sequential validation (no parallel chunks, but still validate!)
     if !config.use_checkpoints {
-        println!("\n🔍 Sequential validation mode (no checkpoints - validating blocks sequentially)...");
-        println!("   This will validate blocks with both BLVM and Core, but sequentially (slower but works)");
+        debug!("\n🔍 Sequential validation mode (no checkpoints - validating blocks sequentially)...");
+        info!("   This will validate blocks with both BLVM and Core, but sequentially (slower but works)");
         
-        // Create a single chunk for sequential validation
+        // Create a single chunk for sequential validation; without checkpoints it can only start
+        // at the start of the range, so partly finished ranges are validated again
         let single_chunk = BlockChunk {
             start_height,
             end_height: actual_end,
-            checkpoint_utxo: None, // No checkpoint - will validate from genesis
+            checkpoint_utxo: base_utxo.clone(), // Snapshot, or None to validate from genesis
+            checkpoint_timing: None,
             skip_validation: false, // IMPORTANT: Actually validate!
         };
         
-        println!("   🚀 Starting sequential differential validation...");
-        println!("   📊 Range: {} to {} ({} blocks)", start_height, actual_end, actual_end - start_height + 1);
+        info!("   🚀 Starting sequential differential validation...");
+        info!("   📊 Range: {} to {} ({} blocks)", start_height, actual_end, actual_end - start_height + 1);
         
         // Validate the single chunk sequentially
-        let result = validate_chunk(single_chunk, block_source.clone()).await?;
-        
-        println!("   ✅ Sequential validation complete!");
-        println!("   📊 Results: {} tested, {} matched, {} divergences", 
-                 result.tested, result.matched, result.divergences.len());
-        
-        if result.divergences.len() > 0 {
-            println!("   ⚠️  Found {} divergences:", result.divergences.len());
-            for (height, blvm_result, core_result) in result.divergences.iter().take(10) {
-                println!("      Height {}: BLVM={}, Core={}", height, blvm_result, core_result);
+        let result = validate_chunk(single_chunk, block_source.clone(), cancel.clone(), config.hooks.clone()).await?;
+        if let Some(store) = &run_state {
+            if let Err(e) = store.record(&result) {
+                warn!("⚠️  Could not record chunk in run state: {:#}", e);
             }
-        } else {
-            println!("   ✅ All blocks matched between BLVM and Core!");
+        }
+        
+        info!("   ✅ Sequential validation complete ({} blocks, {} divergences)",
+                 result.tested, result.divergences.len());
+        #[cfg(feature = "input-script-diff")]
+        if let Some(hook) = &input_scripts {
+            hook.log_summary();
+        }
+        if let Some(hook) = &block_stats {
+            hook.write()?;
         }
         
         return Ok(vec![result]);
     }
     
-    // Run chunks in parallel with semaphore to limit concurrency
-    let semaphore = Arc::new(Semaphore::new(config.num_workers));
+    let items = crate::chunk_schedule::clip(
+        &crate::chunk_schedule::plan(
+            config.scheduling,
+            start_height,
+            actual_end,
+            config.chunk_size,
+            config.num_workers,
+            block_source.as_ref(),
+        ),
+        &remaining,
+    );
+    
+    // Checkpoints stay on disk until a worker takes their chunk; without a checkpoint dir they go
+    // to a scratch dir removed when the run ends
+    let scratch_dir;
+    let store = match &config.checkpoint_dir {
+        // Checkpoints are generated under mainnet rules
+        Some(dir) => CheckpointStore::new(dir, BlockFileNetwork::Mainnet)?,
+        None => {
+            scratch_dir = tempfile::Builder::new()
+                .prefix("blvm-checkpoints-")
+                .tempdir()
+                .context("Failed to create a scratch checkpoint dir")?;
+            info!("   Checkpoints in {} (set BLVM_CHECKPOINT_DIR to keep them)", scratch_dir.path().display());
+            CheckpointStore::new(scratch_dir.path(), BlockFileNetwork::Mainnet)?
+        }
+    };
+    
+    // Generate checkpoints: one before every chunk but the first, plus every chunk end so a later
+    // run with other chunks can reuse them
+    info!("\n📌 Phase 1: Generating UTXO checkpoints...");
+    let mut boundaries: Vec<u64> = items
+        .iter()
+        .filter(|item| item.start_height > start_height)
+        .map(|item| item.start_height - 1)
+        .chain(items.iter().map(|item| item.end_height))
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    let timings: HashMap<u64, CheckpointTiming> =
+        generate_checkpoints_at(start_height, &boundaries, block_source.as_ref(), base_utxo.as_deref(), &store, &cancel)
+            .await?
+            .into_iter()
+            .map(|timing| (timing.height, timing))
+            .collect();
+    
+    // Create chunks: each starts from the checkpoint after the block before it
+    let chunks: Vec<QueuedChunk> = items
+        .iter()
+        .map(|item| {
+            let checkpoint = (item.start_height > start_height).then(|| item.start_height - 1);
+            QueuedChunk {
+                chunk: BlockChunk {
+                    start_height: item.start_height,
+                    end_height: item.end_height,
+                    // The first chunk starts from the snapshot, or empty at genesis
+                    checkpoint_utxo: checkpoint.is_none().then(|| base_utxo.clone().unwrap_or_default()),
+                    checkpoint_timing: checkpoint.and_then(|height| timings.get(&height).cloned()),
+                    skip_validation: false,
+                },
+                checkpoint,
+                cost: item.cost,
+            }
+        })
+        .collect();
+    info!("\n📦 Created {} chunks for parallel execution", chunks.len());
+    
+    // Workers take the next chunk from a shared queue as soon as they finish one (most expensive
+    // first with adaptive scheduling), so nobody idles while long chunks are still queued
+    let workers = config.num_workers.clamp(1, chunks.len().max(1));
+    let queue = Arc::new(match config.scheduling {
+        ChunkScheduling::Fixed => WorkQueue::new(chunks),
+        ChunkScheduling::Adaptive { .. } => WorkQueue::by_cost_desc(chunks, |queued| queued.cost),
+    });
+    let checkpoints = Arc::new(store);
     let mut handles = Vec::new();
     
-    for chunk in chunks {
-        let permit = semaphore.clone().acquire_owned().await?;
-        let block_source_clone = block_source.clone();
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let checkpoints = checkpoints.clone();
+        let block_source = block_source.clone();
+        let cancel = cancel.clone();
+        let hooks = config.hooks.clone();
+        let run_state = run_state.clone();
         
         let handle = tokio::spawn(async move {
-            let _permit = permit;
-            let result = validate_chunk(chunk, block_source_clone).await;
-            result
+            let mut outcomes = Vec::new();
+            while let Some(QueuedChunk { mut chunk, checkpoint, .. }) = queue.take() {
+                if cancel.is_cancelled() {
+                    warn!("🛑 Cancelled - not starting remaining chunks");
+                    break;
+                }
+                let range = (chunk.start_height, chunk.end_height);
+                let result = async {
+                    if let Some(height) = checkpoint {
+                        chunk.checkpoint_utxo = Some(load_chunk_checkpoint(&checkpoints, height, block_source.as_ref()).await?);
+                    }
+                    validate_chunk(chunk, block_source.clone(), cancel.child_token(), hooks.clone()).await
+                }
+                .await;
+                // Recorded as each chunk finishes, not when results are collected below
+                if let (Ok(result), Some(store)) = (&result, &run_state) {
+                    if let Err(e) = store.record(result) {
+                        warn!("⚠️  Could not record chunk {}-{} in run state: {:#}",
+                                  result.start_height, result.end_height, e);
+                    }
+                }
+                outcomes.push((range, result));
+            }
+            outcomes
         });
         
         handles.push(handle);
     }
     
     // Collect results
-    println!("\n⚡ Phase 2: Running chunks in parallel...");
-    let mut results = Vec::new();
+    info!("\n⚡ Phase 2: Running chunks on {} workers...", workers);
+    let mut results = resumed;
+    let mut outcomes = Vec::new();
     for (idx, handle) in handles.into_iter().enumerate() {
         match handle.await {
-            Ok(Ok(result)) => {
-                println!("✅ Chunk {} [{}-{}]: {} blocks, {} divergences, {:.1}s", 
-                         idx + 1, result.start_height, result.end_height,
-                         result.tested, result.divergences.len(), result.duration_secs);
+            Ok(finished) => outcomes.extend(finished),
+            Err(e) => error!("❌ Worker {} panicked: {}", idx + 1, e),
+        }
+    }
+    // Which worker ran a chunk is scheduling noise; report in height order
+    outcomes.sort_by_key(|((start, _), _)| *start);
+    for ((start, end), outcome) in outcomes {
+        match outcome {
+            Ok(result) => {
+                info!("✅ Chunk [{}-{}]: {} blocks, {} divergences, {:.1}s", 
+                         start, end, result.tested, result.divergences.len(), result.duration_secs);
                 results.push(result);
             }
-            Ok(Err(e)) => {
-                eprintln!("❌ Chunk {} failed: {}", idx + 1, e);
+            Err(e) if crate::cancel::is_cancelled(&e) => {
+                warn!("🛑 Chunk {}-{} cancelled: {}", start, end, e);
             }
             Err(e) => {
-                eprintln!("❌ Chunk {} panicked: {}", idx + 1, e);
+                error!("❌ Chunk {}-{} failed: {}", start, end, e);
             }
         }
     }
-    
-    // Summary
-    let total_tested: usize = results.iter().map(|r| r.tested).sum();
-    let total_matched: usize = results.iter().map(|r| r.matched).sum();
-    let total_divergences: usize = results.iter().map(|r| r.divergences.len()).sum();
-    let total_duration: f64 = results.iter().map(|r| r.duration_secs).sum();
-    
-    println!("\n📊 Parallel Differential Test Summary:");
-    println!("   Total blocks tested: {}", total_tested);
-    println!("   Matched: {}", total_matched);
-    println!("   Divergences: {}", total_divergences);
-    println!("   Total duration: {:.1}s ({:.1} minutes)", total_duration, total_duration / 60.0);
-    println!("   Throughput: {:.1} blocks/sec", total_tested as f64 / total_duration);
-    
-    if total_divergences > 0 {
-        println!("\n❌ Divergences found:");
-        for result in &results {
-            for (height, blvm, core) in &result.divergences {
-                println!("   Height {}: BLVM={}, Core={}", height, blvm, core);
-            }
-        }
+
+    results.sort_by_key(|r| r.start_height);
+    #[cfg(feature = "input-script-diff")]
+    if let Some(hook) = &input_scripts {
+        hook.log_summary();
     }
-    
+    if let Some(hook) = &block_stats {
+        hook.write()?;
+    }
+
+    // Totals, divergences by severity, sanity counters etc. are reported once per run by
+    // `RunSummary` (see `collect_only::validate_range`)
     Ok(results)
 }
-
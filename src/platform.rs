@@ -0,0 +1,452 @@
+//! Platform compatibility layer
+//!
+//! OS-specific pieces of the collection pipeline live here so the block reader, chunk cache,
+//! checkpoints and the RPC-based differential build and run on Windows as well as Linux/macOS:
+//!
+//! - default Bitcoin Core data directories per OS
+//! - executable-bit handling and `.exe` suffixes for node binaries
+//! - page-cache hints (`posix_fadvise`) — Linux only, a no-op elsewhere
+//! - free space of a filesystem (`statvfs`) — Unix only, unknown elsewhere
+//! - io_uring read-ahead for sequential file reads (`io-uring` feature) — Linux only, a plain
+//!   file elsewhere
+//! - liveness of a lock holder's PID (`/proc`) — Linux only, unknown elsewhere
+//! - peak RSS of this process (`getrusage`) — Unix only, unknown elsewhere
+//! - resource usage of a finished child (`wait4`, `/proc/<pid>/io`) — Unix only (I/O counters
+//!   Linux only), unknown elsewhere
+//! - process groups, so a timed-out benchmark is killed with its children — Unix only, the
+//!   direct child alone elsewhere
+//! - interrupting a child (SIGINT) so it can finish cleanly — Unix only, a no-op elsewhere
+//!
+//! Unix-only operations are `#[cfg(unix)]` / `#[cfg(target_os = "linux")]` here; callers use the
+//! portable wrappers and never reach for `std::os::unix` or `libc` directly. Helper scripts under
+//! `scripts/` (fallocate, `/run/media` mounts, `nsenter`) remain Linux-only.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+
+/// Default Bitcoin Core data directories for this OS, most likely first.
+///
+/// Linux/BSD: `~/.bitcoin`, `/root/.bitcoin`, `/var/lib/bitcoind`.
+/// macOS: `~/Library/Application Support/Bitcoin`, then `~/.bitcoin`.
+/// Windows: `%APPDATA%\Bitcoin`, then `%LOCALAPPDATA%\Bitcoin`.
+pub fn default_bitcoin_data_dirs() -> Vec<PathBuf> {
+    let mut dirs_out = Vec::new();
+    #[cfg(windows)]
+    {
+        dirs_out.extend(dirs::data_dir().map(|d| d.join("Bitcoin")));
+        dirs_out.extend(dirs::data_local_dir().map(|d| d.join("Bitcoin")));
+    }
+    #[cfg(target_os = "macos")]
+    {
+        dirs_out.extend(dirs::data_dir().map(|d| d.join("Bitcoin")));
+        dirs_out.extend(dirs::home_dir().map(|h| h.join(".bitcoin")));
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        dirs_out.extend(dirs::home_dir().map(|h| h.join(".bitcoin")));
+        dirs_out.push(PathBuf::from("/root/.bitcoin"));
+        dirs_out.push(PathBuf::from("/var/lib/bitcoind"));
+    }
+    dirs_out.dedup();
+    dirs_out
+}
+
+/// `name` with the platform executable suffix (`bitcoind` → `bitcoind.exe` on Windows).
+pub fn exe_name(name: &str) -> String {
+    format!("{}{}", name, std::env::consts::EXE_SUFFIX)
+}
+
+/// Whether `path` looks runnable. Unix checks the execute bits; elsewhere existence is enough.
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path)
+            .map(|m| m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Best-effort `chmod 755` (scripts, downloaded binaries). No-op off Unix.
+pub fn make_executable(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(mut perms) = std::fs::metadata(path).map(|m| m.permissions()) {
+            perms.set_mode(0o755);
+            let _ = std::fs::set_permissions(path, perms);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+    }
+}
+
+/// Advise the kernel to drop page-cache pages for `path` (`POSIX_FADV_DONTNEED`).
+///
+/// `posix_fadvise` acts on the page cache shared by every descriptor for the inode, so opening
+/// the file separately is enough even when another process (zstd) is reading it.
+/// No-op outside Linux.
+#[cfg(target_os = "linux")]
+pub fn drop_page_cache(path: &Path) {
+    use std::os::unix::io::AsRawFd;
+    if let Ok(f) = std::fs::File::open(path) {
+        unsafe {
+            // len=0 means "to end of file".
+            libc::posix_fadvise(f.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+}
+
+/// Advise the kernel to drop page-cache pages for `path`. No-op outside Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn drop_page_cache(_path: &Path) {}
+
+/// Bytes available to unprivileged users on the filesystem holding `path` (`statvfs`); `None`
+/// when unknown (`path` missing, or not Unix).
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // `statvfs` field widths differ between platforms
+pub fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+/// Free space of the filesystem holding `path`; unknown outside Unix.
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// A file opened by [`open_sequential`]: read ahead through io_uring, or a plain file.
+pub enum SequentialFile {
+    File(std::fs::File),
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    Uring(Box<crate::uring_reader::UringReader>),
+}
+
+impl SequentialFile {
+    pub fn metadata(&self) -> std::io::Result<std::fs::Metadata> {
+        match self {
+            Self::File(file) => file.metadata(),
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            Self::Uring(reader) => reader.file().metadata(),
+        }
+    }
+
+    pub fn is_uring(&self) -> bool {
+        !matches!(self, Self::File(_))
+    }
+}
+
+impl std::io::Read for SequentialFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            Self::Uring(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl std::io::Seek for SequentialFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            Self::Uring(reader) => reader.seek(pos),
+        }
+    }
+}
+
+/// Open `path` for mostly sequential reads: with the `io-uring` feature on Linux and a non-zero
+/// `queue_depth` (`io.uring_queue_depth`), through [`crate::uring_reader`]; else, or if the kernel
+/// refuses io_uring (reported once), as a plain file.
+pub fn open_sequential(path: &Path, queue_depth: u32) -> std::io::Result<SequentialFile> {
+    let file = std::fs::File::open(path)?;
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    if queue_depth > 0 {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+        if !UNAVAILABLE.load(Ordering::Relaxed) {
+            match crate::uring_reader::UringReader::new(file.try_clone()?, queue_depth) {
+                Ok(reader) => return Ok(SequentialFile::Uring(Box::new(reader))),
+                Err(e) => {
+                    if !UNAVAILABLE.swap(true, Ordering::Relaxed) {
+                        tracing::warn!("⚠️  io_uring unavailable ({}) - using buffered reads", e);
+                    }
+                }
+            }
+        }
+    }
+    #[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+    let _ = queue_depth;
+    Ok(SequentialFile::File(file))
+}
+
+/// Whether process `pid` is still running: `Some` on Linux (`/proc`), `None` where unknown.
+pub fn process_alive(pid: u32) -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        Some(Path::new(&format!("/proc/{}", pid)).exists())
+    } else {
+        None
+    }
+}
+
+/// Peak resident set size of this process in bytes: `Some` on Unix, `None` elsewhere.
+#[cfg(unix)]
+pub fn peak_rss_bytes() -> Option<u64> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    Some(max_rss_bytes(unsafe { usage.assume_init() }.ru_maxrss))
+}
+
+/// `ru_maxrss` in bytes: bytes on macOS, kilobytes everywhere else.
+#[cfg(unix)]
+fn max_rss_bytes(max_rss: libc::c_long) -> u64 {
+    let max_rss = max_rss as u64;
+    if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    }
+}
+
+/// Peak resident set size of this process in bytes: `Some` on Unix, `None` elsewhere.
+#[cfg(not(unix))]
+pub fn peak_rss_bytes() -> Option<u64> {
+    None
+}
+
+/// Current resident set size of this process in bytes (`/proc/self/statm`): `Some` on Linux,
+/// `None` elsewhere.
+#[cfg(target_os = "linux")]
+pub fn current_rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+/// Current resident set size of this process in bytes: `Some` on Linux, `None` elsewhere.
+#[cfg(not(target_os = "linux"))]
+pub fn current_rss_bytes() -> Option<u64> {
+    None
+}
+
+/// Start `cmd` in a process group of its own so [`kill_process_group`] reaches everything it
+/// spawns. No-op off Unix.
+pub fn new_process_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = cmd;
+    }
+}
+
+/// Kill `child` and, on Unix, the rest of the process group started by [`new_process_group`].
+pub fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+/// Send `child` SIGINT, e.g. so a recorder flushes its output before exiting. No-op off Unix.
+pub fn interrupt(child: &Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+    }
+}
+
+/// Resource usage of a finished child process, including the descendants it waited for.
+/// `None` where the platform does not report a counter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    /// Peak resident set size of the child or its largest descendant
+    pub max_rss_bytes: Option<u64>,
+    /// CPU time in user mode
+    pub user_secs: Option<f64>,
+    /// CPU time in the kernel
+    pub sys_secs: Option<f64>,
+    /// Bytes fetched from storage (`/proc/<pid>/io` `read_bytes`)
+    pub read_bytes: Option<u64>,
+    /// Bytes sent to storage (`/proc/<pid>/io` `write_bytes`)
+    pub write_bytes: Option<u64>,
+}
+
+impl ProcessUsage {
+    /// One-line summary of the counters that are known, e.g. `peak RSS 512.0 MiB, user 3.20s`.
+    pub fn summary(&self) -> String {
+        const MIB: f64 = 1024.0 * 1024.0;
+        let mut parts = Vec::new();
+        if let Some(rss) = self.max_rss_bytes {
+            parts.push(format!("peak RSS {:.1} MiB", rss as f64 / MIB));
+        }
+        if let Some(user) = self.user_secs {
+            parts.push(format!("user {:.2}s", user));
+        }
+        if let Some(sys) = self.sys_secs {
+            parts.push(format!("sys {:.2}s", sys));
+        }
+        if let Some(read) = self.read_bytes {
+            parts.push(format!("read {:.1} MiB", read as f64 / MIB));
+        }
+        if let Some(write) = self.write_bytes {
+            parts.push(format!("written {:.1} MiB", write as f64 / MIB));
+        }
+        parts.join(", ")
+    }
+}
+
+/// [`Child::try_wait`] that also returns the child's [`ProcessUsage`].
+///
+/// Once this returns a status the child has been reaped; don't wait on it again.
+pub fn try_wait_with_usage(
+    child: &mut Child,
+) -> std::io::Result<Option<(ExitStatus, ProcessUsage)>> {
+    #[cfg(unix)]
+    {
+        wait_child(child, false)
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(child
+            .try_wait()?
+            .map(|status| (status, ProcessUsage::default())))
+    }
+}
+
+/// [`Child::wait`] that also returns the child's [`ProcessUsage`].
+///
+/// The child has been reaped afterwards; don't wait on it again.
+pub fn wait_with_usage(child: &mut Child) -> std::io::Result<(ExitStatus, ProcessUsage)> {
+    #[cfg(unix)]
+    {
+        loop {
+            if let Some(done) = wait_child(child, true)? {
+                return Ok(done);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        Ok((child.wait()?, ProcessUsage::default()))
+    }
+}
+
+/// Wait for `child` to exit without reaping it (`WNOWAIT`), read its I/O counters while it is a
+/// zombie, then reap it with `wait4` for the rusage. `None` if it is still running (or the wait
+/// was interrupted).
+#[cfg(unix)]
+fn wait_child(child: &Child, block: bool) -> std::io::Result<Option<(ExitStatus, ProcessUsage)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let mut info = unsafe { std::mem::zeroed::<libc::siginfo_t>() };
+    let options = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+    if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, options) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return match err.kind() {
+            std::io::ErrorKind::Interrupted => Ok(None),
+            _ => Err(err),
+        };
+    }
+    // WNOHANG with nothing to report leaves the zeroed info untouched
+    if info.si_signo == 0 {
+        return Ok(None);
+    }
+
+    let (read_bytes, write_bytes) = proc_io_bytes(child.id());
+    let mut status = 0;
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    if unsafe { libc::wait4(pid, &mut status, 0, usage.as_mut_ptr()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let usage = unsafe { usage.assume_init() };
+    let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1e6;
+    Ok(Some((
+        ExitStatus::from_raw(status),
+        ProcessUsage {
+            max_rss_bytes: Some(max_rss_bytes(usage.ru_maxrss)),
+            user_secs: Some(secs(usage.ru_utime)),
+            sys_secs: Some(secs(usage.ru_stime)),
+            read_bytes,
+            write_bytes,
+        },
+    )))
+}
+
+/// `read_bytes` / `write_bytes` of `/proc/<pid>/io`; they include reaped descendants.
+#[cfg(target_os = "linux")]
+fn proc_io_bytes(pid: u32) -> (Option<u64>, Option<u64>) {
+    let Ok(text) = std::fs::read_to_string(format!("/proc/{}/io", pid)) else {
+        return (None, None);
+    };
+    let field = |name: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.trim().parse().ok())
+    };
+    (field("read_bytes"), field("write_bytes"))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn proc_io_bytes(_pid: u32) -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exe_name_uses_platform_suffix() {
+        let name = exe_name("bitcoind");
+        assert!(name.starts_with("bitcoind"));
+        assert_eq!(name.ends_with(".exe"), cfg!(windows));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn child_usage_reports_cpu_and_memory() {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 7")
+            .spawn()
+            .unwrap();
+        let (status, usage) = wait_with_usage(&mut child).unwrap();
+        assert_eq!(status.code(), Some(7));
+        assert!(usage.max_rss_bytes.unwrap() > 0);
+        assert!(usage.user_secs.unwrap() + usage.sys_secs.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn default_data_dirs_are_absolute() {
+        for dir in default_bitcoin_data_dirs() {
+            assert!(dir.is_absolute(), "{}", dir.display());
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Blocks bundled with the outputs they spend
+//!
+//! Script verification needs the value and scriptPubKey of every input's prevout, which normally
+//! means replaying the UTXO set up to the block. A [`PrevoutBlock`] carries them with the block
+//! (like Core's `getblock <hash> 3`), so benchmarks and differential checks of a single block
+//! need no replay at all.
+//!
+//! The spent outputs live in an optional sidecar next to the block in a
+//! [`SharedBlockCache`](crate::block_file_reader::SharedBlockCache): `block_<h>.spent` beside
+//! `block_<h>.bin`. [`export_range`] fills them from Core's undo files (`rev*.dat`) or, without a
+//! local data directory, from `getblock` verbosity 3 over RPC.
+//!
+//! Sidecar layout (little-endian): `BLVMSPT1`, the block hash (internal byte order) it belongs
+//! to, then per non-coinbase transaction a CompactSize input count and per input
+//! `value u64 | height u32 | coinbase u8 | CompactSize script length | script`.
+
+use anyhow::{Context, Result};
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::types::Block;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::block_file_reader::{BlockFileReader, SharedBlockCache};
+use crate::cancel::CancellationToken;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::rev_file_reader::{BlockUndo, RevFileReader, SpentOutput};
+use crate::wire::{read_compact_size, sha256d, write_compact_size};
+
+const MAGIC: &[u8; 8] = b"BLVMSPT1";
+
+/// `block_<height>.spent` in a block cache directory.
+pub fn sidecar_path(cache_dir: &Path, height: u64) -> PathBuf {
+    cache_dir.join(format!("block_{}.spent", height))
+}
+
+/// Block hash (internal byte order) of serialized block bytes.
+pub fn block_hash(block_bytes: &[u8]) -> Result<[u8; 32]> {
+    let header = block_bytes
+        .get(..80)
+        .context("block shorter than its header")?;
+    Ok(sha256d(header))
+}
+
+/// Serialize the spent outputs of the block with hash `block_hash`.
+pub fn encode_sidecar(block_hash: &[u8; 32], spent: &BlockUndo) -> Vec<u8> {
+    let mut out = Vec::with_capacity(40 + spent.spent_count() * 40);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(block_hash);
+    for tx in &spent.txs {
+        write_compact_size(&mut out, tx.len());
+        for output in tx {
+            out.extend(output.value.to_le_bytes());
+            out.extend(output.height.to_le_bytes());
+            out.push(u8::from(output.is_coinbase));
+            write_compact_size(&mut out, output.script_pubkey.len());
+            out.extend_from_slice(&output.script_pubkey);
+        }
+    }
+    out
+}
+
+/// Parse a sidecar into the block hash it belongs to and its spent outputs.
+pub fn decode_sidecar(data: &[u8]) -> Result<([u8; 32], BlockUndo)> {
+    anyhow::ensure!(
+        data.len() >= 40 && &data[..8] == MAGIC,
+        "not a spent-outputs sidecar"
+    );
+    let hash: [u8; 32] = data[8..40].try_into().unwrap();
+    let mut pos = 40;
+    let mut txs = Vec::new();
+    while pos < data.len() {
+        let inputs = read_compact_size(data, &mut pos)?;
+        let mut tx = Vec::with_capacity(inputs.min(data.len() as u64) as usize);
+        for _ in 0..inputs {
+            let value = u64::from_le_bytes(take(data, &mut pos, 8)?.try_into().unwrap());
+            let height = u32::from_le_bytes(take(data, &mut pos, 4)?.try_into().unwrap());
+            let is_coinbase = take(data, &mut pos, 1)?[0] != 0;
+            let script_len = usize::try_from(read_compact_size(data, &mut pos)?)
+                .context("script length overflows")?;
+            let script_pubkey = take(data, &mut pos, script_len)?.to_vec();
+            tx.push(SpentOutput {
+                value,
+                script_pubkey,
+                height,
+                is_coinbase,
+            });
+        }
+        txs.push(tx);
+    }
+    Ok((hash, BlockUndo { txs }))
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let bytes = pos
+        .checked_add(len)
+        .and_then(|end| data.get(*pos..end))
+        .context("truncated spent-outputs sidecar")?;
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Spent outputs of a verbosity-3 `getblock` result (Core 23+), in the [`BlockUndo`] shape.
+pub fn spent_from_getblock(block: &Value) -> Result<BlockUndo> {
+    let txs = block["tx"]
+        .as_array()
+        .context("getblock result has no `tx`")?;
+    let mut spent = BlockUndo::default();
+    for tx in txs.iter().skip(1) {
+        let mut outputs = Vec::new();
+        for input in tx["vin"].as_array().context("tx has no `vin`")? {
+            let prevout = &input["prevout"];
+            let script = prevout["scriptPubKey"]["hex"]
+                .as_str()
+                .context("prevout has no `scriptPubKey.hex` (Core 23+ needed)")?;
+            outputs.push(SpentOutput {
+                value: (prevout["value"]
+                    .as_f64()
+                    .context("prevout has no `value`")?
+                    * 100_000_000.0)
+                    .round() as u64,
+                script_pubkey: hex::decode(script).context("Invalid prevout script hex")?,
+                height: prevout["height"]
+                    .as_u64()
+                    .context("prevout has no `height`")? as u32,
+                is_coinbase: prevout["generated"].as_bool().unwrap_or(false),
+            });
+        }
+        spent.txs.push(outputs);
+    }
+    Ok(spent)
+}
+
+/// A block with the prevout of every non-coinbase input.
+#[derive(Debug, Clone)]
+pub struct PrevoutBlock {
+    pub height: u64,
+    pub block_bytes: Vec<u8>,
+    pub spent: BlockUndo,
+}
+
+impl PrevoutBlock {
+    /// Bundle `block_bytes` with `spent`, checking that they have the same shape.
+    pub fn new(height: u64, block_bytes: Vec<u8>, spent: BlockUndo) -> Result<Self> {
+        let prevout_block = Self {
+            height,
+            block_bytes,
+            spent,
+        };
+        let (block, _) = prevout_block.deserialize()?;
+        check_shape(&block, &prevout_block.spent)
+            .with_context(|| format!("Spent outputs do not match block {}", height))?;
+        Ok(prevout_block)
+    }
+
+    pub fn deserialize(&self) -> Result<(Block, Vec<Vec<blvm_protocol::Witness>>)> {
+        deserialize_block_with_witnesses(&self.block_bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize block {}: {:?}", self.height, e))
+    }
+
+    /// `(values, script_pubkeys)` of transaction `tx`'s inputs (block order, 0 = coinbase), as
+    /// script verification takes them.
+    pub fn prevouts(&self, tx: usize) -> Option<(Vec<i64>, Vec<&[u8]>)> {
+        let outputs = self.spent.txs.get(tx.checked_sub(1)?)?;
+        Some(
+            outputs
+                .iter()
+                .map(|o| (o.value as i64, o.script_pubkey.as_slice()))
+                .unzip(),
+        )
+    }
+}
+
+fn check_shape(block: &Block, spent: &BlockUndo) -> Result<()> {
+    let txs = block.transactions.len().saturating_sub(1);
+    anyhow::ensure!(
+        spent.txs.len() == txs,
+        "{} non-coinbase transactions, spent outputs for {}",
+        txs,
+        spent.txs.len()
+    );
+    for (i, (tx, outputs)) in block
+        .transactions
+        .iter()
+        .skip(1)
+        .zip(&spent.txs)
+        .enumerate()
+    {
+        anyhow::ensure!(
+            tx.inputs.len() == outputs.len(),
+            "transaction {} has {} inputs, {} spent outputs",
+            i + 1,
+            tx.inputs.len(),
+            outputs.len()
+        );
+    }
+    Ok(())
+}
+
+/// Where [`export_range`] gets blocks and their spent outputs.
+pub enum SpentOutputSource {
+    /// A local Core data directory: `blk*.dat` plus `rev*.dat`, located through Core's index
+    UndoFiles {
+        blocks: BlockFileReader,
+        undo: RevFileReader,
+    },
+    /// `getblock` verbosity 0 and 3
+    Rpc(NodeRpcClient),
+}
+
+impl SpentOutputSource {
+    /// Undo files of `data_dir` (which needs a readable Core block index).
+    pub fn undo_files(data_dir: &Path) -> Result<Self> {
+        let blocks = BlockFileReader::new(data_dir, crate::block_file_reader::Network::Mainnet)?;
+        anyhow::ensure!(
+            blocks.core_block_index().is_some(),
+            "No Core block index in {} - undo data cannot be located",
+            data_dir.display()
+        );
+        let undo = RevFileReader::for_block_reader(&blocks);
+        Ok(Self::UndoFiles { blocks, undo })
+    }
+
+    async fn fetch(&self, height: u64) -> Result<(Vec<u8>, BlockUndo)> {
+        match self {
+            Self::UndoFiles { blocks, undo } => {
+                let bytes = blocks.read_block_by_height(height)?;
+                // Genesis has no undo record and spends nothing
+                let spent = if height == 0 {
+                    BlockUndo::default()
+                } else {
+                    undo.read_undo_by_height(height)?
+                };
+                Ok((bytes, spent))
+            }
+            Self::Rpc(client) => {
+                let hash = client.getblockhash(height).await?;
+                let bytes = hex::decode(client.getblock_raw(&hash).await?.trim())
+                    .with_context(|| format!("Invalid getblock hex at height {}", height))?;
+                let spent = spent_from_getblock(&client.getblock(&hash, 3).await?)?;
+                Ok((bytes, spent))
+            }
+        }
+    }
+}
+
+/// Blocks written by [`export_range`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub exported: u64,
+    /// Already had a valid sidecar
+    pub skipped: u64,
+    pub spent_outputs: u64,
+    pub sidecar_bytes: u64,
+}
+
+/// Write the block and its spent-outputs sidecar for every height in `start..=end` into `cache`.
+///
+/// Heights that already have a sidecar are skipped unless `force`.
+pub async fn export_range(
+    cache: &SharedBlockCache,
+    source: &SpentOutputSource,
+    start: u64,
+    end: u64,
+    force: bool,
+    cancel: &CancellationToken,
+) -> Result<ExportReport> {
+    let mut report = ExportReport::default();
+    for height in start..=end {
+        crate::cancel::check(cancel, || format!("prevout export at height {}", height))?;
+        if !force && cache.read_prevout_block(height)?.is_some() {
+            report.skipped += 1;
+            continue;
+        }
+        let (bytes, spent) = source
+            .fetch(height)
+            .await
+            .with_context(|| format!("Failed to fetch block {} with spent outputs", height))?;
+        let block = PrevoutBlock::new(height, bytes, spent)?;
+        report.sidecar_bytes += cache.store_prevout_block(&block)?;
+        report.spent_outputs += block.spent.spent_count() as u64;
+        report.exported += 1;
+        if report.exported % 1000 == 0 {
+            println!(
+                "📦 Exported {} blocks with spent outputs (height {})",
+                report.exported, height
+            );
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_roundtrip_and_getblock_shape() {
+        let spent = BlockUndo {
+            txs: vec![
+                vec![SpentOutput {
+                    value: 5_000_000_000,
+                    script_pubkey: vec![0x41; 67],
+                    height: 9,
+                    is_coinbase: true,
+                }],
+                vec![],
+                vec![
+                    SpentOutput {
+                        value: 1,
+                        script_pubkey: vec![0x51; 300],
+                        height: 800_000,
+                        is_coinbase: false,
+                    },
+                    SpentOutput {
+                        value: 2,
+                        script_pubkey: Vec::new(),
+                        height: 1,
+                        is_coinbase: false,
+                    },
+                ],
+            ],
+        };
+        let hash = [7u8; 32];
+        let encoded = encode_sidecar(&hash, &spent);
+        assert_eq!(decode_sidecar(&encoded).unwrap(), (hash, spent.clone()));
+        assert!(decode_sidecar(&encoded[..encoded.len() - 1]).is_err());
+        assert!(decode_sidecar(b"not a sidecar at all, not at all, no").is_err());
+
+        let getblock = serde_json::json!({
+            "tx": [
+                { "vin": [{ "coinbase": "04ffff001d0104" }] },
+                { "vin": [{ "prevout": {
+                    "generated": true,
+                    "height": 9,
+                    "value": 50.0,
+                    "scriptPubKey": { "hex": hex::encode([0x41; 67]) }
+                } }] },
+            ]
+        });
+        let from_rpc = spent_from_getblock(&getblock).unwrap();
+        assert_eq!(from_rpc.txs, spent.txs[..1]);
+    }
+}
@@ -0,0 +1,165 @@
+//! `perf` recordings and flamegraphs of registry benchmarks (`flamegraph` feature)
+//!
+//! Registry benchmarks run as child processes (`cargo bench`, shell scripts), so an in-process
+//! sampler would only see the runner waiting. [`PerfRecording`] attaches `perf record -g` to the
+//! runner itself instead: perf inherits into every process the benchmark spawns, and stopping it
+//! after the last iteration leaves `<name>.perf.data` in the profile directory. The recording is
+//! folded in process (`perf script` through inferno's perf collapser) into `<name>.svg`.
+//!
+//! Criterion benchmarks that still need compiling profile the build too, so build them first
+//! (`cargo bench --no-run`). perf needs `kernel.perf_event_paranoid` at 1 or lower (or
+//! `CAP_PERFMON`); `BLVM_PERF_FREQUENCY` sets the sampling rate (default
+//! [`DEFAULT_FREQUENCY`] Hz).
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+/// Samples per second when `BLVM_PERF_FREQUENCY` is unset
+pub const DEFAULT_FREQUENCY: u32 = 997;
+
+/// Error unless `perf` is on `PATH`.
+pub fn check_perf() -> Result<()> {
+    which::which("perf")
+        .map(|_| ())
+        .context("perf not found on PATH (install linux-tools / perf to use --profile)")
+}
+
+fn frequency_from_env() -> u32 {
+    std::env::var("BLVM_PERF_FREQUENCY")
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .filter(|&f| f > 0)
+        .unwrap_or(DEFAULT_FREQUENCY)
+}
+
+/// `criterion/hash_operations` -> `criterion-hash_operations`
+fn file_stem(benchmark: &str) -> String {
+    benchmark
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// A running `perf record` attached to this process.
+pub struct PerfRecording {
+    perf: Child,
+    name: String,
+    data: PathBuf,
+    log: PathBuf,
+    svg: PathBuf,
+}
+
+impl PerfRecording {
+    /// Start recording for `benchmark`, writing into `dir`.
+    pub fn start(dir: &Path, benchmark: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let stem = file_stem(benchmark);
+        let data = dir.join(format!("{}.perf.data", stem));
+        let log = dir.join(format!("{}.perf.log", stem));
+        let svg = dir.join(format!("{}.svg", stem));
+        let mut perf = Command::new("perf")
+            .arg("record")
+            .arg("-g")
+            .arg("-F")
+            .arg(frequency_from_env().to_string())
+            .arg("-o")
+            .arg(&data)
+            .arg("-p")
+            .arg(std::process::id().to_string())
+            .stdout(Stdio::null())
+            .stderr(File::create(&log)?)
+            .spawn()
+            .context("Failed to start perf record")?;
+
+        // perf exits right away when it may not attach; give it a moment to fail
+        std::thread::sleep(Duration::from_millis(300));
+        if let Some(status) = perf.try_wait()? {
+            let output = std::fs::read_to_string(&log).unwrap_or_default();
+            anyhow::bail!(
+                "perf record exited ({}): {}",
+                status,
+                output.lines().take(3).collect::<Vec<_>>().join(" ")
+            );
+        }
+        Ok(Self {
+            perf,
+            name: benchmark.to_string(),
+            data,
+            log,
+            svg,
+        })
+    }
+
+    /// Stop recording and render the flamegraph; returns the SVG's path.
+    pub fn finish(mut self) -> Result<PathBuf> {
+        // SIGINT makes perf flush and finalize the data file
+        crate::platform::interrupt(&self.perf);
+        let status = self.perf.wait().context("Failed to wait for perf record")?;
+        if !self.data.exists() {
+            anyhow::bail!(
+                "perf record ({}) wrote no data, see {}",
+                status,
+                self.log.display()
+            );
+        }
+
+        let mut script = Command::new("perf")
+            .arg("script")
+            .arg("-i")
+            .arg(&self.data)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run perf script")?;
+        let stdout = script.stdout.take().context("perf script has no stdout")?;
+        let mut folded = Vec::new();
+        inferno::collapse::Collapse::collapse(
+            &mut inferno::collapse::perf::Folder::default(),
+            BufReader::new(stdout),
+            &mut folded,
+        )
+        .context("Failed to fold perf script output")?;
+        let status = script.wait()?;
+        anyhow::ensure!(status.success(), "perf script failed: {}", status);
+        anyhow::ensure!(
+            !folded.is_empty(),
+            "perf recorded no samples for {}",
+            self.name
+        );
+
+        let mut options = inferno::flamegraph::Options::default();
+        options.title = self.name.clone();
+        let svg = BufWriter::new(
+            File::create(&self.svg)
+                .with_context(|| format!("Failed to create {}", self.svg.display()))?,
+        );
+        inferno::flamegraph::from_reader(&mut options, folded.as_slice(), svg)
+            .context("Failed to render flamegraph")?;
+        Ok(self.svg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark_names_become_file_stems() {
+        assert_eq!(
+            file_stem("criterion/hash_operations"),
+            "criterion-hash_operations"
+        );
+        assert_eq!(file_stem("suite/ibd 10k"), "suite-ibd-10k");
+    }
+}
@@ -0,0 +1,246 @@
+//! Structured logging and progress display
+//!
+//! Collection, sort-merge and differential passes log through `tracing`. When stderr is a
+//! terminal, long loops also draw `indicatif` bars (one per chunk being collected, compressed or
+//! validated) with log lines printed above them. CI and redirected output get line-oriented logs
+//! instead, where each [`Progress`] logs a status line every [`LINE_INTERVAL`].
+//!
+//! - `BLVM_LOG=quiet|normal|verbose|trace` sets the level (`warn` / `info` / `debug` / `trace`);
+//!   `RUST_LOG` takes precedence for per-module filters such as `blvm_bench::sort_merge=debug`.
+//! - `BLVM_PROGRESS=bars|lines` forces the display; `CI` being set implies `lines`.
+//!
+//! Binaries can call [`init`] with their own flags; library entry points call
+//! [`ensure_logging`] so output is never lost when a binary did not.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing_subscriber::EnvFilter;
+
+/// How often a [`Progress`] logs a status line in line mode.
+pub const LINE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Log level preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    Trace,
+}
+
+impl Verbosity {
+    /// `BLVM_LOG`, else [`Normal`](Self::Normal).
+    pub fn from_env() -> Self {
+        match std::env::var("BLVM_LOG").as_deref() {
+            Ok("quiet") | Ok("warn") => Self::Quiet,
+            Ok("verbose") | Ok("debug") => Self::Verbose,
+            Ok("trace") => Self::Trace,
+            _ => Self::Normal,
+        }
+    }
+
+    /// From `-q` / repeated `-v` flags.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (false, 0) => Self::Normal,
+            (false, 1) => Self::Verbose,
+            _ => Self::Trace,
+        }
+    }
+
+    fn directive(self) -> &'static str {
+        match self {
+            Self::Quiet => "warn",
+            Self::Normal => "info",
+            Self::Verbose => "debug",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+/// Bars on a terminal, status lines otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    Bars,
+    Lines,
+}
+
+impl ProgressMode {
+    pub fn from_env() -> Self {
+        match std::env::var("BLVM_PROGRESS").as_deref() {
+            Ok("bars") => Self::Bars,
+            Ok("lines") => Self::Lines,
+            _ if std::env::var_os("CI").is_some() || !std::io::stderr().is_terminal() => {
+                Self::Lines
+            }
+            _ => Self::Bars,
+        }
+    }
+}
+
+fn mode() -> ProgressMode {
+    static MODE: OnceLock<ProgressMode> = OnceLock::new();
+    *MODE.get_or_init(ProgressMode::from_env)
+}
+
+/// Shared bar area; log lines are printed above it.
+pub fn multi() -> &'static MultiProgress {
+    static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI.get_or_init(|| match mode() {
+        ProgressMode::Bars => MultiProgress::new(),
+        ProgressMode::Lines => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+    })
+}
+
+/// Stderr writer that hides the bars while a log line is written.
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match mode() {
+            ProgressMode::Bars => multi().suspend(|| std::io::stderr().write_all(buf))?,
+            ProgressMode::Lines => std::io::stderr().write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+/// Install the global subscriber; `false` if one was already installed.
+pub fn init(verbosity: Verbosity) -> bool {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(verbosity.directive()));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_writer(|| LogWriter);
+    match mode() {
+        // Bars show progress; keep the lines above them short
+        ProgressMode::Bars => builder.without_time().try_init().is_ok(),
+        ProgressMode::Lines => builder.try_init().is_ok(),
+    }
+}
+
+/// [`init`] from `BLVM_LOG` unless a subscriber is already installed.
+pub fn ensure_logging() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        init(Verbosity::from_env());
+    });
+}
+
+/// Progress of one loop: a bar in [`ProgressMode::Bars`], periodic log lines otherwise.
+///
+/// Cheap to share between tasks (`&self` methods).
+pub struct Progress {
+    label: String,
+    unit: &'static str,
+    total: u64,
+    bar: ProgressBar,
+    position: AtomicU64,
+    started: Instant,
+    last_line: Mutex<Instant>,
+}
+
+impl Progress {
+    /// `total` of 0 means unknown (spinner / no percentage).
+    pub fn new(label: impl Into<String>, total: u64, unit: &'static str) -> Self {
+        let label = label.into();
+        let bar = if total > 0 {
+            ProgressBar::new(total).with_style(
+                ProgressStyle::with_template(
+                    "{prefix:>24} [{bar:30}] {pos}/{len} {msg} ({per_sec}, eta {eta})",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            )
+        } else {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix:>24} {spinner} {pos} {msg} ({per_sec})")
+                    .unwrap(),
+            )
+        };
+        let bar = multi().add(bar.with_prefix(label.clone()));
+        Self {
+            label,
+            unit,
+            total,
+            bar,
+            position: AtomicU64::new(0),
+            started: Instant::now(),
+            last_line: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn inc(&self, n: u64) {
+        let position = self.position.fetch_add(n, Ordering::Relaxed) + n;
+        self.bar.inc(n);
+        self.maybe_log_line(position);
+    }
+
+    pub fn set_position(&self, position: u64) {
+        self.position.store(position, Ordering::Relaxed);
+        self.bar.set_position(position);
+        self.maybe_log_line(position);
+    }
+
+    pub fn set_message(&self, message: impl Into<String>) {
+        self.bar.set_message(message.into());
+    }
+
+    /// Remove the bar and log `message` once.
+    pub fn finish(&self, message: impl AsRef<str>) {
+        self.bar.finish_and_clear();
+        multi().remove(&self.bar);
+        tracing::info!("{}: {}", self.label, message.as_ref());
+    }
+
+    fn maybe_log_line(&self, position: u64) {
+        if mode() != ProgressMode::Lines {
+            return;
+        }
+        let mut last = self.last_line.lock().unwrap();
+        if last.elapsed() < LINE_INTERVAL {
+            return;
+        }
+        *last = Instant::now();
+        let rate = position as f64 / self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        if self.total > 0 {
+            tracing::info!(
+                "{}: {}/{} {} ({:.1}%) @ {:.1} {}/s",
+                self.label,
+                position,
+                self.total,
+                self.unit,
+                100.0 * position as f64 / self.total as f64,
+                rate,
+                self.unit
+            );
+        } else {
+            tracing::info!(
+                "{}: {} {} @ {:.1} {}/s",
+                self.label,
+                position,
+                self.unit,
+                rate,
+                self.unit
+            );
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if !self.bar.is_finished() {
+            self.bar.finish_and_clear();
+            multi().remove(&self.bar);
+        }
+    }
+}
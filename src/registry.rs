@@ -0,0 +1,492 @@
+//! Benchmark registry
+//!
+//! Modules register named benchmarks into a [`BenchmarkRegistry`], each with tags, an optional
+//! setup and teardown, and a run step repeated `iterations` times. [`BenchmarkRegistry::run`]
+//! executes the ones matching a [`BenchFilter`] and times every run step; the resulting
+//! [`RunReport`] is written to the results directory next to the other benchmark output.
+//! Benchmarks built with [`Benchmark::process`] run one child process per iteration and also
+//! record its [`ProcessUsage`] (peak RSS, CPU time, I/O), so memory regressions show up next to
+//! wall-clock ones. With `alloc-profiling`, in-process iterations get a [`MemoryProfile`] too
+//! (allocations, heap growth, sampled RSS). With `flamegraph`,
+//! [`BenchmarkRegistry::run_profiled`] records each benchmark with `perf` and puts a flamegraph
+//! SVG next to `benchmarks.json` ([`crate::profiling`]).
+//!
+//! Setup runs once before the timed iterations and teardown runs once after them, also when
+//! setup or an iteration failed, so a benchmark that starts a node or fills a cache always gets
+//! to clean up.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use crate::alloc_profile::{MemoryProfile, MemoryProfiler};
+use crate::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+use crate::platform::ProcessUsage;
+use crate::utils;
+
+type Step = Box<dyn Fn() -> Result<()> + Send + Sync>;
+type RunStep = Box<dyn Fn() -> Result<Option<ProcessUsage>> + Send + Sync>;
+
+/// One named benchmark.
+pub struct Benchmark {
+    pub name: String,
+    pub tags: Vec<String>,
+    /// Timed repetitions of the run step
+    pub iterations: usize,
+    setup: Option<Step>,
+    run: RunStep,
+    teardown: Option<Step>,
+}
+
+impl Benchmark {
+    pub fn new(
+        name: impl Into<String>,
+        run: impl Fn() -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_run_step(name.into(), Box::new(move || run().map(|()| None)))
+    }
+
+    /// Benchmark whose run step spawns one process and returns its resource usage.
+    pub fn process(
+        name: impl Into<String>,
+        run: impl Fn() -> Result<ProcessUsage> + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_run_step(name.into(), Box::new(move || run().map(Some)))
+    }
+
+    fn with_run_step(name: String, run: RunStep) -> Self {
+        Self {
+            name,
+            tags: Vec::new(),
+            iterations: 1,
+            setup: None,
+            run,
+            teardown: None,
+        }
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    pub fn with_setup(mut self, setup: impl Fn() -> Result<()> + Send + Sync + 'static) -> Self {
+        self.setup = Some(Box::new(setup));
+        self
+    }
+
+    pub fn with_teardown(
+        mut self,
+        teardown: impl Fn() -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.teardown = Some(Box::new(teardown));
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Setup, iterations, teardown; the iterations are recorded with `perf` into `profile_dir`
+    /// if given.
+    fn execute(&self, profile_dir: Option<&Path>) -> BenchmarkResult {
+        let mut result = BenchmarkResult {
+            name: self.name.clone(),
+            tags: self.tags.clone(),
+            timings_secs: Vec::new(),
+            usage: Vec::new(),
+            memory: None,
+            flamegraph: None,
+            error: None,
+        };
+        let setup = self.setup.as_ref().map_or(Ok(()), |setup| setup());
+        let profiler = setup.is_ok().then(MemoryProfiler::start).flatten();
+        #[cfg(feature = "flamegraph")]
+        let perf = profile_dir
+            .filter(|_| setup.is_ok())
+            .map(|dir| crate::profiling::PerfRecording::start(dir, &self.name));
+        #[cfg(not(feature = "flamegraph"))]
+        let _ = profile_dir;
+        result.error = match setup {
+            Ok(()) => (0..self.iterations).find_map(|_| {
+                let start = Instant::now();
+                let outcome = (self.run)();
+                result.timings_secs.push(start.elapsed().as_secs_f64());
+                match outcome {
+                    Ok(usage) => {
+                        result.usage.extend(usage);
+                        None
+                    }
+                    Err(e) => Some(format!("{:#}", e)),
+                }
+            }),
+            Err(e) => Some(format!("setup: {:#}", e)),
+        };
+        result.memory = profiler.map(MemoryProfiler::finish);
+        #[cfg(feature = "flamegraph")]
+        match perf.map(|perf| perf.and_then(crate::profiling::PerfRecording::finish)) {
+            Some(Ok(svg)) => result.flamegraph = Some(svg),
+            Some(Err(e)) => println!("⚠️  No profile for {}: {:#}", self.name, e),
+            None => {}
+        }
+        if let Some(teardown) = &self.teardown {
+            if let Err(e) = teardown() {
+                result
+                    .error
+                    .get_or_insert_with(|| format!("teardown: {:#}", e));
+            }
+        }
+        result
+    }
+}
+
+/// Which benchmarks to run. Empty lists match everything.
+#[derive(Debug, Clone, Default)]
+pub struct BenchFilter {
+    /// Substrings of the benchmark name; any one matching is enough
+    pub names: Vec<String>,
+    /// Tags; the benchmark needs at least one of them
+    pub tags: Vec<String>,
+}
+
+impl BenchFilter {
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Self {
+            names: Vec::new(),
+            tags: vec![tag.into()],
+        }
+    }
+
+    pub fn matches(&self, bench: &Benchmark) -> bool {
+        (self.names.is_empty() || self.names.iter().any(|n| bench.name.contains(n.as_str())))
+            && (self.tags.is_empty() || self.tags.iter().any(|t| bench.has_tag(t)))
+    }
+}
+
+/// Named benchmarks in registration order.
+#[derive(Default)]
+pub struct BenchmarkRegistry {
+    benchmarks: Vec<Benchmark>,
+}
+
+impl BenchmarkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `bench`; names are unique.
+    pub fn register(&mut self, bench: Benchmark) -> Result<()> {
+        if self.benchmarks.iter().any(|b| b.name == bench.name) {
+            anyhow::bail!("Benchmark {} is already registered", bench.name);
+        }
+        self.benchmarks.push(bench);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.benchmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.benchmarks.is_empty()
+    }
+
+    /// Registered benchmarks matching `filter`.
+    pub fn filter<'a>(&'a self, filter: &'a BenchFilter) -> impl Iterator<Item = &'a Benchmark> {
+        self.benchmarks.iter().filter(move |b| filter.matches(b))
+    }
+
+    /// Run every benchmark matching `filter`, in registration order. A failing benchmark is
+    /// recorded in the report and does not stop the others.
+    pub fn run(&self, filter: &BenchFilter) -> RunReport {
+        self.run_in(filter, None)
+    }
+
+    /// [`run`](Self::run) with every benchmark recorded by `perf`; the recordings and flamegraph
+    /// SVGs go to `dir`. A benchmark whose profile fails still reports its timings.
+    #[cfg(feature = "flamegraph")]
+    pub fn run_profiled(&self, filter: &BenchFilter, dir: &Path) -> Result<RunReport> {
+        crate::profiling::check_perf()?;
+        Ok(self.run_in(filter, Some(dir)))
+    }
+
+    fn run_in(&self, filter: &BenchFilter, profile_dir: Option<&Path>) -> RunReport {
+        let mut report = RunReport {
+            started: chrono::Utc::now().to_rfc3339(),
+            results: Vec::new(),
+        };
+        for bench in self.filter(filter) {
+            println!("\n▶️  {}", bench.name);
+            let result = bench.execute(profile_dir);
+            match &result.error {
+                None => println!("✅ {} ({:.2}s mean)", bench.name, result.mean_secs()),
+                Some(e) => println!("❌ {}: {}", bench.name, e),
+            }
+            report.results.push(result);
+        }
+        report
+    }
+}
+
+/// Outcome of one benchmark.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub name: String,
+    pub tags: Vec<String>,
+    /// Wall time of each run step, including a failed last one
+    pub timings_secs: Vec<f64>,
+    /// Resource usage of each successful run of a [`Benchmark::process`]
+    #[serde(default)]
+    pub usage: Vec<ProcessUsage>,
+    /// Allocations and sampled RSS over all iterations (`alloc-profiling` builds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryProfile>,
+    /// Flamegraph SVG of the iterations (profiled runs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flamegraph: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+impl BenchmarkResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn mean_secs(&self) -> f64 {
+        if self.timings_secs.is_empty() {
+            return 0.0;
+        }
+        self.timings_secs.iter().sum::<f64>() / self.timings_secs.len() as f64
+    }
+
+    pub fn min_secs(&self) -> f64 {
+        self.timings_secs
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max_secs(&self) -> f64 {
+        self.timings_secs.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// Highest peak RSS over the recorded runs (child processes, else the sampled RSS of this
+    /// process).
+    pub fn peak_rss_bytes(&self) -> Option<u64> {
+        self.usage
+            .iter()
+            .filter_map(|u| u.max_rss_bytes)
+            .max()
+            .or_else(|| self.memory.as_ref()?.peak_rss_bytes)
+    }
+
+    /// Mean user + system CPU time over the recorded runs.
+    pub fn mean_cpu_secs(&self) -> Option<f64> {
+        let cpu: Vec<f64> = self
+            .usage
+            .iter()
+            .filter_map(|u| Some(u.user_secs? + u.sys_secs?))
+            .collect();
+        (!cpu.is_empty()).then(|| cpu.iter().sum::<f64>() / cpu.len() as f64)
+    }
+}
+
+/// Results of one [`BenchmarkRegistry::run`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    /// RFC 3339 start time
+    pub started: String,
+    pub results: Vec<BenchmarkResult>,
+}
+
+impl RunReport {
+    pub fn failed(&self) -> Vec<&BenchmarkResult> {
+        self.results.iter().filter(|r| !r.passed()).collect()
+    }
+
+    pub fn print_summary(&self) {
+        println!("\n📊 {} benchmark(s)", self.results.len());
+        for r in &self.results {
+            if r.passed() {
+                println!(
+                    "   ✅ {:<40} {:>3} run(s)  mean {:>8.2}s  min {:>8.2}s  max {:>8.2}s",
+                    r.name,
+                    r.timings_secs.len(),
+                    r.mean_secs(),
+                    r.min_secs(),
+                    r.max_secs()
+                );
+                if let Some(rss) = r.peak_rss_bytes() {
+                    println!(
+                        "      {:<40} peak RSS {:>8.1} MiB  cpu {:>8.2}s",
+                        "",
+                        rss as f64 / (1024.0 * 1024.0),
+                        r.mean_cpu_secs().unwrap_or(0.0)
+                    );
+                }
+                if let Some(memory) = &r.memory {
+                    println!("      {:<40} {}", "", memory.summary());
+                }
+                if let Some(svg) = &r.flamegraph {
+                    println!("      {:<40} 🔥 {}", "", svg.display());
+                }
+            } else {
+                println!("   ❌ {:<40} {}", r.name, r.error.as_deref().unwrap_or(""));
+            }
+        }
+    }
+
+    /// Write `benchmarks.json` under `dir`.
+    pub fn write_json(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join("benchmarks.json");
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// The results directory of the output layout, else `results/bench-run-<timestamp>/`.
+    pub fn default_dir() -> PathBuf {
+        let ctx = ArtifactContext::now();
+        artifact_dir_from_env(ArtifactKind::Results, &ctx).unwrap_or_else(|| {
+            utils::results_dir().join(format!(
+                "bench-run-{}",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            ))
+        })
+    }
+
+    /// [`Self::write_json`] into [`Self::default_dir`].
+    pub fn save(&self) -> Result<PathBuf> {
+        self.write_json(&Self::default_dir())
+    }
+
+    /// Error naming every failed benchmark, if any failed.
+    pub fn ensure_passed(&self) -> Result<()> {
+        let failed = self.failed();
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "{} benchmark(s) failed: {}",
+                failed.len(),
+                failed
+                    .iter()
+                    .map(|r| r.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Register every Criterion `[[bench]]` target of this crate as `criterion/<name>`, run with
+/// `cargo bench --bench <name>` plus its required features.
+pub fn register_criterion(registry: &mut BenchmarkRegistry) -> Result<()> {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let manifest: toml::Value =
+        toml::from_str(include_str!("../Cargo.toml")).context("Failed to parse Cargo.toml")?;
+    let targets = manifest
+        .get("bench")
+        .and_then(|b| b.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for target in targets {
+        let Some(name) = target.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let mut features: Vec<String> = target
+            .get("required-features")
+            .and_then(|f| f.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|f| f.as_str().map(str::to_string))
+            .collect();
+        if utils::is_production_mode() {
+            features.push("production".to_string());
+        }
+        let (bench_name, dir) = (name.to_string(), manifest_dir.clone());
+        registry.register(
+            Benchmark::new(format!("criterion/{}", name), move || {
+                let mut cmd = Command::new("cargo");
+                cmd.arg("bench").arg("--bench").arg(&bench_name);
+                if !features.is_empty() {
+                    cmd.arg("--features").arg(features.join(","));
+                }
+                let status = cmd
+                    .current_dir(&dir)
+                    .stdout(Stdio::inherit())
+                    .stderr(Stdio::inherit())
+                    .status()
+                    .with_context(|| format!("Failed to run cargo bench --bench {}", bench_name))?;
+                if !status.success() {
+                    anyhow::bail!("cargo bench --bench {} failed: {}", bench_name, status);
+                }
+                Ok(())
+            })
+            .with_tag("criterion"),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn runs_filtered_benchmarks_with_setup_and_teardown() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let step = |log: &Arc<Mutex<Vec<&'static str>>>, entry: &'static str| {
+            let log = log.clone();
+            move || {
+                log.lock().unwrap().push(entry);
+                Ok(())
+            }
+        };
+
+        let mut registry = BenchmarkRegistry::new();
+        registry
+            .register(
+                Benchmark::new("shell/ibd", step(&log, "run"))
+                    .with_tag("shell")
+                    .with_iterations(2)
+                    .with_setup(step(&log, "setup"))
+                    .with_teardown(step(&log, "teardown")),
+            )
+            .unwrap();
+        registry
+            .register(
+                Benchmark::new("criterion/hash", || anyhow::bail!("boom"))
+                    .with_tag("criterion")
+                    .with_teardown(step(&log, "cleanup")),
+            )
+            .unwrap();
+        assert!(registry
+            .register(Benchmark::new("shell/ibd", || Ok(())))
+            .is_err());
+
+        let report = registry.run(&BenchFilter::tag("shell"));
+        assert_eq!(report.results.len(), 1);
+        assert!(report.ensure_passed().is_ok());
+        assert_eq!(report.results[0].timings_secs.len(), 2);
+        assert_eq!(*log.lock().unwrap(), ["setup", "run", "run", "teardown"]);
+
+        let filter = BenchFilter {
+            names: vec!["hash".to_string()],
+            tags: Vec::new(),
+        };
+        let report = registry.run(&filter);
+        assert_eq!(report.failed().len(), 1);
+        assert_eq!(report.results[0].error.as_deref(), Some("boom"));
+        assert_eq!(log.lock().unwrap().last(), Some(&"cleanup"));
+    }
+}
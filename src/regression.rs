@@ -0,0 +1,320 @@
+//! Benchmark regression detection against committed baselines
+//!
+//! A [`Baseline`] is the Criterion mean time of every benchmark on one machine profile, stored
+//! as `baselines/<profile>.json` in this crate and committed with the code it measured. After a
+//! `cargo bench` run, [`compare`] lines the fresh means in `target/criterion` up against the
+//! baseline and [`RegressionReport::ensure_no_regressions`] fails when any benchmark got slower
+//! than the threshold. Timings are only comparable on the same hardware, so baselines are per
+//! profile: `BLVM_BENCH_PROFILE`, or OS / arch / CPU count by default.
+//!
+//! `blvm-bench regression record|check|report` wraps these for local use and CI.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::watch::{criterion_means, format_ns};
+
+/// Slowdown (percent) that counts as a regression unless configured otherwise.
+pub const DEFAULT_THRESHOLD_PCT: f64 = 5.0;
+
+/// Machine profile name: `BLVM_BENCH_PROFILE`, else `<os>-<arch>-<cpus>cpu`.
+pub fn machine_profile() -> String {
+    std::env::var("BLVM_BENCH_PROFILE")
+        .ok()
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| {
+            format!(
+                "{}-{}-{}cpu",
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                num_cpus::get()
+            )
+        })
+}
+
+/// Committed baselines: `BLVM_BASELINES_DIR`, else `baselines/` in this crate.
+pub fn baselines_dir() -> PathBuf {
+    std::env::var_os("BLVM_BASELINES_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("baselines"))
+}
+
+/// Where `cargo bench` leaves Criterion's estimates.
+pub fn criterion_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/criterion")
+}
+
+/// Mean times of every benchmark on one machine profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub profile: String,
+    /// RFC 3339
+    pub recorded_at: String,
+    /// Commit the baseline was recorded at, when known
+    pub git_rev: Option<String>,
+    /// Criterion benchmark id (`<group>/<bench>`) -> mean time (ns)
+    pub means_ns: BTreeMap<String, f64>,
+}
+
+impl Baseline {
+    pub fn path(dir: &Path, profile: &str) -> PathBuf {
+        dir.join(format!("{}.json", profile))
+    }
+
+    /// Baseline for `profile`, if one was recorded.
+    pub fn load(dir: &Path, profile: &str) -> Result<Option<Self>> {
+        let path = Self::path(dir, profile);
+        match std::fs::read(&path) {
+            Ok(data) => {
+                Ok(Some(serde_json::from_slice(&data).with_context(|| {
+                    format!("Invalid baseline {}", path.display())
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Pretty-printed with sorted keys so re-recording gives a readable diff.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = Self::path(dir, &self.profile);
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Every mean Criterion has on disk (the latest run of each benchmark).
+pub fn current_means(criterion_dir: &Path) -> Result<BTreeMap<String, f64>> {
+    criterion_means(criterion_dir, SystemTime::UNIX_EPOCH)
+}
+
+/// Baseline for `profile` from the current Criterion results; the caller saves it.
+pub fn record_baseline(profile: &str, criterion_dir: &Path) -> Result<Baseline> {
+    let means_ns = current_means(criterion_dir)?;
+    if means_ns.is_empty() {
+        anyhow::bail!(
+            "No Criterion results in {} - run cargo bench first",
+            criterion_dir.display()
+        );
+    }
+    Ok(Baseline {
+        profile: profile.to_string(),
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        git_rev: git_rev(),
+        means_ns,
+    })
+}
+
+pub(crate) fn git_rev() -> Option<String> {
+    let out = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeltaStatus {
+    Regressed,
+    Improved,
+    Unchanged,
+}
+
+/// One benchmark against its baseline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchDelta {
+    pub id: String,
+    pub baseline_ns: f64,
+    pub current_ns: f64,
+    /// Positive when slower
+    pub pct: f64,
+    pub status: DeltaStatus,
+}
+
+/// Current results against a baseline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegressionReport {
+    pub profile: String,
+    pub threshold_pct: f64,
+    /// Benchmarks in both, by id
+    pub deltas: Vec<BenchDelta>,
+    /// In the baseline but not run this time
+    pub missing: Vec<String>,
+    /// Run this time but not in the baseline
+    pub added: Vec<String>,
+}
+
+/// Compare `current` means against `baseline`; a benchmark more than `threshold_pct` slower is
+/// regressed, more than `threshold_pct` faster is improved.
+pub fn compare(
+    baseline: &Baseline,
+    current: &BTreeMap<String, f64>,
+    threshold_pct: f64,
+) -> RegressionReport {
+    let mut deltas = Vec::new();
+    let mut missing = Vec::new();
+    for (id, &baseline_ns) in &baseline.means_ns {
+        let Some(&current_ns) = current.get(id) else {
+            missing.push(id.clone());
+            continue;
+        };
+        let pct = if baseline_ns > 0.0 {
+            (current_ns - baseline_ns) / baseline_ns * 100.0
+        } else {
+            0.0
+        };
+        let status = if pct > threshold_pct {
+            DeltaStatus::Regressed
+        } else if pct < -threshold_pct {
+            DeltaStatus::Improved
+        } else {
+            DeltaStatus::Unchanged
+        };
+        deltas.push(BenchDelta {
+            id: id.clone(),
+            baseline_ns,
+            current_ns,
+            pct,
+            status,
+        });
+    }
+    let added = current
+        .keys()
+        .filter(|id| !baseline.means_ns.contains_key(*id))
+        .cloned()
+        .collect();
+    RegressionReport {
+        profile: baseline.profile.clone(),
+        threshold_pct,
+        deltas,
+        missing,
+        added,
+    }
+}
+
+impl RegressionReport {
+    pub fn regressions(&self) -> impl Iterator<Item = &BenchDelta> {
+        self.deltas
+            .iter()
+            .filter(|d| d.status == DeltaStatus::Regressed)
+    }
+
+    /// Delta table, regressions first, then by size of the change.
+    pub fn render(&self) -> String {
+        let mut deltas: Vec<&BenchDelta> = self.deltas.iter().collect();
+        deltas.sort_by(|a, b| {
+            (b.status == DeltaStatus::Regressed)
+                .cmp(&(a.status == DeltaStatus::Regressed))
+                .then(b.pct.abs().total_cmp(&a.pct.abs()))
+        });
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "📊 Benchmarks vs baseline {} (threshold ±{:.1}%)",
+            self.profile, self.threshold_pct
+        );
+        for d in deltas {
+            let marker = match d.status {
+                DeltaStatus::Regressed => "🔴",
+                DeltaStatus::Improved => "🟢",
+                DeltaStatus::Unchanged => "~",
+            };
+            let _ = writeln!(
+                out,
+                "   {} {:<50} {} -> {} ({:+.1}%)",
+                marker,
+                d.id,
+                format_ns(d.baseline_ns),
+                format_ns(d.current_ns),
+                d.pct
+            );
+        }
+        for id in &self.added {
+            let _ = writeln!(out, "   + {:<50} (not in baseline)", id);
+        }
+        for id in &self.missing {
+            let _ = writeln!(out, "   - {:<50} (not run)", id);
+        }
+        out
+    }
+
+    /// Error listing every regressed benchmark.
+    pub fn ensure_no_regressions(&self) -> Result<()> {
+        let regressed: Vec<String> = self
+            .regressions()
+            .map(|d| format!("{} ({:+.1}%)", d.id, d.pct))
+            .collect();
+        anyhow::ensure!(
+            regressed.is_empty(),
+            "{} benchmark(s) more than {:.1}% slower than baseline {}: {}",
+            regressed.len(),
+            self.threshold_pct,
+            self.profile,
+            regressed.join(", ")
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_against_threshold() {
+        let baseline = Baseline {
+            profile: "ci".into(),
+            recorded_at: String::new(),
+            git_rev: None,
+            means_ns: BTreeMap::from([
+                ("connect_block/100tx".to_string(), 1_000_000.0),
+                ("deserialize/empty".to_string(), 1_000.0),
+                ("sighash/p2wpkh".to_string(), 500.0),
+                ("removed/bench".to_string(), 10.0),
+            ]),
+        };
+        let current = BTreeMap::from([
+            ("connect_block/100tx".to_string(), 1_080_000.0),
+            ("deserialize/empty".to_string(), 1_030.0),
+            ("sighash/p2wpkh".to_string(), 400.0),
+            ("new/bench".to_string(), 5.0),
+        ]);
+
+        let report = compare(&baseline, &current, DEFAULT_THRESHOLD_PCT);
+        let status: Vec<_> = report.deltas.iter().map(|d| d.status).collect();
+        assert_eq!(
+            status,
+            [
+                DeltaStatus::Regressed,
+                DeltaStatus::Unchanged,
+                DeltaStatus::Improved
+            ]
+        );
+        assert_eq!(report.missing, ["removed/bench"]);
+        assert_eq!(report.added, ["new/bench"]);
+        let err = report.ensure_no_regressions().unwrap_err().to_string();
+        assert!(err.contains("connect_block/100tx (+8.0%)"), "{}", err);
+        assert!(compare(&baseline, &current, 10.0)
+            .ensure_no_regressions()
+            .is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        baseline.save(dir.path()).unwrap();
+        assert_eq!(
+            Baseline::load(dir.path(), "ci").unwrap(),
+            Some(baseline.clone())
+        );
+        assert_eq!(Baseline::load(dir.path(), "other").unwrap(), None);
+    }
+}
@@ -2,6 +2,10 @@
 //!
 //! This module manages Bitcoin Core regtest nodes for differential testing.
 //! It handles starting, stopping, and managing multiple concurrent nodes.
+//!
+//! [`reorg_scenarios`] drives competing chains through a node and BLVM, and
+//! [`coinbase_scenarios`] duplicate coinbases and the BIP34 switch-over. [`stress_blocks`] times
+//! both on valid blocks built to sit at consensus limits.
 
 use crate::core_builder::CoreBinaries;
 use anyhow::{Context, Result};
@@ -10,6 +14,13 @@ use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 
+#[cfg(feature = "differential")]
+pub mod coinbase_scenarios;
+#[cfg(feature = "differential")]
+pub mod reorg_scenarios;
+#[cfg(feature = "differential")]
+pub mod stress_blocks;
+
 /// Port manager for allocating unique ports
 #[derive(Debug, Clone)]
 pub struct PortManager {
@@ -73,6 +84,8 @@ pub struct RegtestNodeConfig {
     pub rpc_pass: String,
     /// RPC host
     pub rpc_host: String,
+    /// Passed to `bitcoind` after the standard arguments (e.g. `-testactivationheight=...`)
+    pub extra_args: Vec<String>,
 }
 
 impl Default for RegtestNodeConfig {
@@ -83,6 +96,7 @@ impl Default for RegtestNodeConfig {
             rpc_user: "test".to_string(),
             rpc_pass: "test".to_string(),
             rpc_host: "127.0.0.1".to_string(),
+            extra_args: Vec::new(),
         }
     }
 }
@@ -145,6 +159,7 @@ impl RegtestNode {
             "-fallbackfee=0.00001",
             "-txindex=0",
         ]);
+        cmd.args(&config.extra_args);
         cmd.stdout(Stdio::null());
         cmd.stderr(Stdio::null());
 
@@ -167,10 +182,20 @@ impl RegtestNode {
     pub async fn start_with_port_manager(
         binaries: CoreBinaries,
         port_manager: Arc<PortManager>,
+    ) -> Result<Self> {
+        Self::start_with_port_manager_and_args(binaries, port_manager, Vec::new()).await
+    }
+
+    /// [`start_with_port_manager`](Self::start_with_port_manager) with extra `bitcoind` arguments
+    pub async fn start_with_port_manager_and_args(
+        binaries: CoreBinaries,
+        port_manager: Arc<PortManager>,
+        extra_args: Vec<String>,
     ) -> Result<Self> {
         let port = port_manager.allocate_port().await;
         let mut config = RegtestNodeConfig::default();
         config.rpc_port = port;
+        config.extra_args = extra_args;
 
         // Use tmpfs if available (faster I/O)
         if Path::new("/dev/shm").exists() {
@@ -0,0 +1,448 @@
+//! Duplicate-coinbase (BIP30) and coinbase-height (BIP34) scenarios through Core and BLVM
+//!
+//! Mainnet's two duplicate coinbases (blocks 91842 and 91880) were only possible because
+//! coinbases did not commit to their height before BIP34, and both validators special-case
+//! them. [`CoinbaseHarness`] recreates those conditions on a fresh regtest node started with
+//! [`core_args`], which move BIP34 to [`BIP34_HEIGHT`] (and BIP66 / BIP65 with it, since their
+//! version rules would reject version 1 blocks too). Below that height blocks are version 1 and
+//! coinbases carry no height, so a coinbase can be repeated byte for byte; from it, blocks must
+//! have version 4 or more and a coinbase starting with the height.
+//!
+//! Each block goes to Core (`submitblock`) and to BLVM (connected on its parent's UTXO set), and
+//! every [`SCENARIOS`] entry records both verdicts next to the expected one:
+//!
+//! - a coinbase repeated while the first copy is unspent is invalid (BIP30)
+//! - a coinbase repeated after the first copy was spent is valid and recreates the coin
+//! - at the activation height, a missing or wrong coinbase height and a version 1 block are
+//!   invalid, a correct block is valid
+//!
+//! The run ends with the UTXO sets compared (`gettxoutsetinfo` totals and the repeated coins).
+//! BLVM takes BIP34's regtest height from its own parameters, so they must put it at
+//! [`BIP34_HEIGHT`] as well; otherwise the version 1 prefix is refused and the harness says so.
+
+use anyhow::Result;
+use blvm_protocol::types::UtxoSet;
+use std::fmt;
+use tracing::{debug, info};
+
+use super::reorg_scenarios::{
+    coinbase_tx, connect_on, genesis, mine_block, simple_tx, spend_tx, subsidy, Coin, Hash,
+    BLOCK_VERSION, COINBASE_MATURITY,
+};
+use crate::node_rpc_client::NodeRpcClient;
+use crate::utxo_stats::UtxoSetStats;
+use crate::wire::{display_hex, sha256d};
+
+/// First height with BIP34 (and BIP66 / BIP65) rules in the scenarios
+pub const BIP34_HEIGHT: u64 = 120;
+/// Version of the blocks below [`BIP34_HEIGHT`]
+const LEGACY_VERSION: u32 = 1;
+const FEE: i64 = 1_000;
+
+/// `bitcoind` arguments moving BIP34 / BIP66 / BIP65 activation to [`BIP34_HEIGHT`].
+pub fn core_args() -> Vec<String> {
+    ["bip34", "dersig", "cltv"]
+        .iter()
+        .map(|name| format!("-testactivationheight={}@{}", name, BIP34_HEIGHT))
+        .collect()
+}
+
+/// What a scenario submits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinbaseCase {
+    /// A version 1 block whose coinbase has no height, below the activation height
+    LegacyBlock,
+    /// The coinbase of the second prefix block again, its output still unspent
+    DuplicateUnspent,
+    /// A block spending the first prefix coinbase, then one repeating that coinbase
+    DuplicateSpent,
+    /// At the activation height: a coinbase without the height
+    MissingHeight,
+    /// At the activation height: a coinbase with the next height
+    WrongHeight,
+    /// At the activation height: a version 1 block with the right coinbase height
+    LegacyVersion,
+    /// At the activation height: a correct block
+    FirstBip34Block,
+}
+
+impl CoinbaseCase {
+    /// Whether the scenario's last block is valid (earlier blocks always are).
+    pub fn expected_valid(&self) -> bool {
+        matches!(
+            self,
+            Self::LegacyBlock | Self::DuplicateSpent | Self::FirstBip34Block
+        )
+    }
+
+    /// Whether the scenario is submitted at [`BIP34_HEIGHT`].
+    pub fn at_activation(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingHeight | Self::WrongHeight | Self::LegacyVersion | Self::FirstBip34Block
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinbaseScenario {
+    pub name: &'static str,
+    pub case: CoinbaseCase,
+}
+
+/// In submission order: invalid blocks leave the tip where it was, valid ones extend it.
+pub const SCENARIOS: &[CoinbaseScenario] = &[
+    CoinbaseScenario {
+        name: "legacy_block",
+        case: CoinbaseCase::LegacyBlock,
+    },
+    CoinbaseScenario {
+        name: "duplicate_coinbase_unspent",
+        case: CoinbaseCase::DuplicateUnspent,
+    },
+    CoinbaseScenario {
+        name: "duplicate_coinbase_spent",
+        case: CoinbaseCase::DuplicateSpent,
+    },
+    CoinbaseScenario {
+        name: "bip34_missing_height",
+        case: CoinbaseCase::MissingHeight,
+    },
+    CoinbaseScenario {
+        name: "bip34_wrong_height",
+        case: CoinbaseCase::WrongHeight,
+    },
+    CoinbaseScenario {
+        name: "bip34_legacy_version",
+        case: CoinbaseCase::LegacyVersion,
+    },
+    CoinbaseScenario {
+        name: "bip34_first_block",
+        case: CoinbaseCase::FirstBip34Block,
+    },
+];
+
+/// Core's and BLVM's verdict on one submitted block.
+#[derive(Debug, Clone)]
+pub struct BlockVerdict {
+    pub scenario: &'static str,
+    pub height: u64,
+    pub hash: String,
+    pub expected_valid: bool,
+    /// `submitblock`'s reject reason
+    pub core_error: Option<String>,
+    pub blvm_error: Option<String>,
+}
+
+impl BlockVerdict {
+    pub fn matches(&self) -> bool {
+        self.core_error.is_none() == self.blvm_error.is_none()
+    }
+
+    /// [`matches`](Self::matches), with the verdict the scenario was built for.
+    pub fn as_expected(&self) -> bool {
+        self.matches() && self.core_error.is_none() == self.expected_valid
+    }
+}
+
+impl fmt::Display for BlockVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = |error: &Option<String>| error.as_deref().unwrap_or("valid").to_string();
+        write!(
+            f,
+            "{} {} at {}: core {}, blvm {} (expected {})",
+            if self.as_expected() { "✅" } else { "❌" },
+            self.scenario,
+            self.height,
+            verdict(&self.core_error),
+            verdict(&self.blvm_error),
+            if self.expected_valid {
+                "valid"
+            } else {
+                "invalid"
+            }
+        )
+    }
+}
+
+/// Every block's verdicts and the final UTXO sets.
+#[derive(Debug, Clone)]
+pub struct CoinbaseReport {
+    pub blocks: Vec<BlockVerdict>,
+    pub core_utxos: UtxoSetStats,
+    /// `None` once BLVM rejected a block Core accepted
+    pub blvm_utxos: Option<UtxoSetStats>,
+    /// Repeated coins whose unspent status differs: (`txid:vout`, in Core, in BLVM)
+    pub coin_mismatches: Vec<(String, bool, bool)>,
+}
+
+impl CoinbaseReport {
+    pub fn passed(&self) -> bool {
+        self.blocks.iter().all(BlockVerdict::as_expected)
+            && self.blvm_utxos.as_ref() == Some(&self.core_utxos)
+            && self.coin_mismatches.is_empty()
+    }
+}
+
+/// Drives [`SCENARIOS`] over a fresh regtest node started with [`core_args`].
+pub struct CoinbaseHarness {
+    client: NodeRpcClient,
+    tip: Hash,
+    height: u64,
+    time: u32,
+    /// BLVM's UTXO set at `tip`; `None` once it rejected a block Core accepted
+    blvm_set: Option<UtxoSet>,
+    /// Raw coinbases of the first two prefix blocks and their outputs
+    prefix_coinbases: Vec<(Vec<u8>, Coin)>,
+    /// Coins repeated by the scenarios
+    repeated: Vec<Coin>,
+    next_tag: u32,
+}
+
+impl CoinbaseHarness {
+    /// Attach to `client`'s node, which must be at genesis, and mine legacy blocks until the
+    /// first prefix coinbase is mature.
+    pub async fn new(client: NodeRpcClient) -> Result<Self> {
+        let count = client.getblockcount().await?;
+        anyhow::ensure!(
+            count == 0,
+            "Coinbase scenarios need a fresh regtest node (this one is at height {})",
+            count
+        );
+        let (tip, time) = genesis(&client).await?;
+        let mut harness = Self {
+            client,
+            tip,
+            height: 0,
+            time,
+            blvm_set: Some(UtxoSet::default()),
+            prefix_coinbases: Vec::new(),
+            repeated: Vec::new(),
+            next_tag: 0,
+        };
+        for _ in 0..=COINBASE_MATURITY {
+            let coinbase = harness.legacy_coinbase(harness.height + 1);
+            let verdict = harness
+                .submit("prefix", LEGACY_VERSION, vec![coinbase.0.clone()], true)
+                .await?;
+            anyhow::ensure!(
+                verdict.core_error.is_none(),
+                "Core rejected prefix block {} ({:?}); it needs {}",
+                verdict.height,
+                verdict.core_error,
+                core_args().join(" ")
+            );
+            anyhow::ensure!(
+                verdict.blvm_error.is_none(),
+                "BLVM rejected prefix block {} ({:?}); its regtest BIP34 height must be {}",
+                verdict.height,
+                verdict.blvm_error,
+                BIP34_HEIGHT
+            );
+            if harness.prefix_coinbases.len() < 2 {
+                harness.prefix_coinbases.push(coinbase);
+            }
+        }
+        Ok(harness)
+    }
+
+    /// Run every scenario in [`SCENARIOS`] and compare the final UTXO sets.
+    pub async fn run_all(&mut self) -> Result<CoinbaseReport> {
+        let mut blocks = Vec::new();
+        for scenario in SCENARIOS {
+            for verdict in self.run(scenario).await? {
+                info!("{}", verdict);
+                blocks.push(verdict);
+            }
+        }
+
+        let core_utxos =
+            UtxoSetStats::from_txoutsetinfo(&self.client.gettxoutsetinfo("none", None).await?)?;
+        let mut coin_mismatches = Vec::new();
+        for coin in &self.repeated {
+            let core = self
+                .client
+                .gettxout(&display_hex(&coin.txid), coin.vout)
+                .await?
+                .is_some();
+            let blvm = self
+                .blvm_set
+                .as_ref()
+                .is_some_and(|set| set.contains_key(&coin.outpoint()));
+            if core != blvm {
+                coin_mismatches.push((coin.rpc_id(), core, blvm));
+            }
+        }
+        Ok(CoinbaseReport {
+            blocks,
+            core_utxos,
+            blvm_utxos: self.blvm_set.as_ref().map(UtxoSetStats::of),
+            coin_mismatches,
+        })
+    }
+
+    /// Submit the blocks of one scenario on the current tip.
+    pub async fn run(&mut self, scenario: &CoinbaseScenario) -> Result<Vec<BlockVerdict>> {
+        let name = scenario.name;
+        let expected = scenario.case.expected_valid();
+        if scenario.case.at_activation() {
+            while self.height + 1 < BIP34_HEIGHT {
+                let coinbase = self.legacy_coinbase(self.height + 1).0;
+                let verdict = self
+                    .submit("filler", LEGACY_VERSION, vec![coinbase], true)
+                    .await?;
+                anyhow::ensure!(
+                    verdict.core_error.is_none(),
+                    "Core rejected filler block {}: {:?}",
+                    verdict.height,
+                    verdict.core_error
+                );
+            }
+        }
+        let height = self.height + 1;
+        let verdicts = match scenario.case {
+            CoinbaseCase::LegacyBlock => {
+                let coinbase = self.legacy_coinbase(height).0;
+                vec![
+                    self.submit(name, LEGACY_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+            CoinbaseCase::DuplicateUnspent => {
+                let (coinbase, coin) = self.prefix_coinbases[1].clone();
+                self.repeated.push(coin);
+                vec![
+                    self.submit(name, LEGACY_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+            CoinbaseCase::DuplicateSpent => {
+                let (coinbase, coin) = self.prefix_coinbases[0].clone();
+                self.repeated.push(coin);
+                let spend = spend_tx(&coin, FEE).0;
+                let filler = self.legacy_coinbase(height).0;
+                let first = self
+                    .submit(name, LEGACY_VERSION, vec![filler, spend], true)
+                    .await?;
+                let second = self
+                    .submit(name, LEGACY_VERSION, vec![coinbase], expected)
+                    .await?;
+                vec![first, second]
+            }
+            CoinbaseCase::MissingHeight => {
+                let coinbase = self.legacy_coinbase(height).0;
+                vec![
+                    self.submit(name, BLOCK_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+            CoinbaseCase::WrongHeight => {
+                let coinbase = coinbase_tx(height + 1, self.tag()).0;
+                vec![
+                    self.submit(name, BLOCK_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+            CoinbaseCase::LegacyVersion => {
+                let coinbase = coinbase_tx(height, self.tag()).0;
+                vec![
+                    self.submit(name, LEGACY_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+            CoinbaseCase::FirstBip34Block => {
+                let coinbase = coinbase_tx(height, self.tag()).0;
+                vec![
+                    self.submit(name, BLOCK_VERSION, vec![coinbase], expected)
+                        .await?,
+                ]
+            }
+        };
+        Ok(verdicts)
+    }
+
+    fn tag(&mut self) -> u32 {
+        self.next_tag += 1;
+        self.next_tag
+    }
+
+    /// Coinbase without a height, unique through its tag.
+    fn legacy_coinbase(&mut self, height: u64) -> (Vec<u8>, Coin) {
+        legacy_coinbase_tx(height, self.tag())
+    }
+
+    /// Mine `txs` (coinbase first) on the tip, hand the block to Core and BLVM, and move the tip
+    /// if Core accepted it.
+    async fn submit(
+        &mut self,
+        scenario: &'static str,
+        version: u32,
+        txs: Vec<Vec<u8>>,
+        expected_valid: bool,
+    ) -> Result<BlockVerdict> {
+        let height = self.height + 1;
+        let time = self.time + 1;
+        let block = mine_block(&self.tip, time, version, &txs);
+        let hash = sha256d(&block[..80]);
+
+        let submitted = self.client.submitblock(&hex::encode(&block)).await?;
+        let core_error = submitted.error;
+        let blvm = match &self.blvm_set {
+            Some(parent_set) => connect_on(parent_set, height, &block),
+            None => Err("an ancestor was rejected".to_string()),
+        };
+        let blvm_error = blvm.as_ref().err().cloned();
+        debug!(
+            "{} block {} at {}: core {:?}, blvm {:?}",
+            scenario,
+            display_hex(&hash),
+            height,
+            core_error,
+            blvm_error
+        );
+        if core_error.is_none() {
+            self.tip = hash;
+            self.height = height;
+            self.time = time;
+            self.blvm_set = blvm.ok();
+        }
+        Ok(BlockVerdict {
+            scenario,
+            height,
+            hash: display_hex(&hash),
+            expected_valid,
+            core_error,
+            blvm_error,
+        })
+    }
+}
+
+/// Coinbase at `height` with only `tag` in its scriptSig, as before BIP34.
+fn legacy_coinbase_tx(height: u64, tag: u32) -> (Vec<u8>, Coin) {
+    let mut script_sig = vec![4];
+    script_sig.extend(tag.to_le_bytes());
+    simple_tx((&[0u8; 32], u32::MAX), &script_sig, subsidy(height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_coinbases_repeat_byte_for_byte() {
+        let (a, coin_a) = legacy_coinbase_tx(1, 7);
+        let (b, coin_b) = legacy_coinbase_tx(105, 7);
+        // Same subsidy below the first halving, no height: the same transaction
+        assert_eq!(a, b);
+        assert_eq!(coin_a, coin_b);
+        assert_ne!(legacy_coinbase_tx(1, 8).0, a);
+        // scriptSig: the tag as a 4-byte push, never a low height's BIP34 push
+        assert_eq!(&a[41..43], &[5, 4]);
+
+        let block = mine_block(&[0; 32], 1, LEGACY_VERSION, &[a]);
+        assert_eq!(&block[..4], &1u32.to_le_bytes());
+        assert!(core_args()[0].ends_with("bip34@120"));
+        assert!(CoinbaseCase::DuplicateSpent.expected_valid());
+        assert!(!CoinbaseCase::DuplicateUnspent.expected_valid());
+    }
+}
@@ -0,0 +1,628 @@
+//! Reorg scenarios: competing chains through Core and BLVM
+//!
+//! Every other differential path feeds one chain in height order, so nothing exercises chain
+//! reorganization. [`ReorgHarness`] builds its own blocks on a fresh regtest node (coinbases and
+//! spends paying to `OP_TRUE`, mined at regtest difficulty), submits each one to Core with
+//! `submitblock`, and connects it in BLVM on top of its parent's UTXO set. The BLVM side then
+//! picks the best chain the way Core does: the most work among valid blocks (every block carries
+//! regtest's fixed `nBits`, so that is the greatest height), the first seen on a tie, and never
+//! a block with an invalid ancestor.
+//!
+//! Each [`ReorgScenario`] forks from the current tip, submits a first branch and then a
+//! competing one. The resulting [`ScenarioOutcome`] holds both best tips, both UTXO set totals
+//! (`gettxoutsetinfo`), and whether each coin the scenario touched is unspent on each side.
+//! [`SCENARIOS`] covers 1- and 6-block reorgs, an equal-work tie, a 20-block reorg whose branches
+//! spend the same coin differently, and a longer branch that double-spends, which must lose.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::block_validation_context_for_connect_ibd;
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::types::{BlockHeader, Network, OutPoint, UtxoSet, ValidationResult};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use tracing::{debug, info};
+
+use crate::consensus_compat::connect_block;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::utxo_stats::UtxoSetStats;
+use crate::wire::{display_hex, merkle_root, sha256d, write_compact_size};
+
+pub(super) type Hash = [u8; 32];
+
+/// Regtest `nBits` (powLimit); regtest never retargets
+const REGTEST_BITS: u32 = 0x207f_ffff;
+/// Regtest `nSubsidyHalvingInterval`
+const HALVING_INTERVAL: u64 = 150;
+pub(super) const COINBASE_MATURITY: u64 = 100;
+/// BIP9 version bits with nothing signalled
+pub(super) const BLOCK_VERSION: u32 = 0x2000_0000;
+/// Coinbases mined below the scenarios, one spendable coin each
+const SPENDABLE_COINS: u64 = 10;
+/// Fee of a first-branch spend; the second branch pays twice as much, so the spends conflict
+const FEE: i64 = 1_000;
+
+/// Where a scenario's spends go (block positions are 1-based within the branch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spends {
+    None,
+    /// Both branches spend the same coin to different outputs, the first also spending its own
+    /// output in the following block
+    Conflicting {
+        first_at: u32,
+        second_at: u32,
+    },
+    /// The second branch spends a coin at `at` and again at `at + 1`, invalidating the rest
+    DoubleSpend {
+        at: u32,
+    },
+}
+
+/// One fork: `first_len` blocks submitted, then a competing `second_len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReorgScenario {
+    pub name: &'static str,
+    pub first_len: u32,
+    pub second_len: u32,
+    pub spends: Spends,
+}
+
+impl ReorgScenario {
+    /// Whether the second branch should end up active: it must be valid for longer than the
+    /// first branch (ties go to the first seen).
+    pub fn second_wins(&self) -> bool {
+        let second_valid = match self.spends {
+            Spends::DoubleSpend { at } => at,
+            _ => self.second_len,
+        };
+        second_valid > self.first_len
+    }
+}
+
+pub const SCENARIOS: &[ReorgScenario] = &[
+    ReorgScenario {
+        name: "one_block",
+        first_len: 1,
+        second_len: 2,
+        spends: Spends::None,
+    },
+    ReorgScenario {
+        name: "six_block",
+        first_len: 6,
+        second_len: 7,
+        spends: Spends::None,
+    },
+    ReorgScenario {
+        name: "equal_work",
+        first_len: 2,
+        second_len: 2,
+        spends: Spends::None,
+    },
+    ReorgScenario {
+        name: "deep_conflicting_spend",
+        first_len: 20,
+        second_len: 21,
+        spends: Spends::Conflicting {
+            first_at: 1,
+            second_at: 10,
+        },
+    },
+    ReorgScenario {
+        name: "longer_branch_double_spends",
+        first_len: 3,
+        second_len: 6,
+        spends: Spends::DoubleSpend { at: 1 },
+    },
+];
+
+/// Core's and BLVM's view after one scenario.
+#[derive(Debug, Clone)]
+pub struct ScenarioOutcome {
+    pub name: &'static str,
+    /// Tip the scenario should end on (RPC byte order)
+    pub expected_tip: String,
+    pub core_tip: String,
+    pub blvm_tip: String,
+    pub core_utxos: UtxoSetStats,
+    pub blvm_utxos: UtxoSetStats,
+    /// Coins whose unspent status differs: (`txid:vout`, unspent in Core, unspent in BLVM)
+    pub coin_mismatches: Vec<(String, bool, bool)>,
+}
+
+impl ScenarioOutcome {
+    /// Core and BLVM picked the same tip and hold the same UTXO set.
+    pub fn matches(&self) -> bool {
+        self.core_tip == self.blvm_tip
+            && self.core_utxos == self.blvm_utxos
+            && self.coin_mismatches.is_empty()
+    }
+
+    /// [`matches`](Self::matches), on the tip the scenario was built to end on.
+    pub fn as_expected(&self) -> bool {
+        self.matches() && self.core_tip == self.expected_tip
+    }
+}
+
+impl fmt::Display for ScenarioOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.as_expected() {
+            "✅"
+        } else if self.matches() {
+            "⚠️  (unexpected tip)"
+        } else {
+            "❌"
+        };
+        write!(
+            f,
+            "{} {}: core tip {}, blvm tip {}, utxos core {} / blvm {}",
+            verdict,
+            self.name,
+            self.core_tip,
+            self.blvm_tip,
+            self.core_utxos.txouts,
+            self.blvm_utxos.txouts
+        )?;
+        for (coin, core, blvm) in &self.coin_mismatches {
+            write!(f, "\n   {}: unspent in core={} blvm={}", coin, core, blvm)?;
+        }
+        Ok(())
+    }
+}
+
+/// An output paying `OP_TRUE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Coin {
+    pub(super) txid: Hash,
+    pub(super) vout: u32,
+    pub(super) value: i64,
+}
+
+impl Coin {
+    pub(super) fn outpoint(&self) -> OutPoint {
+        OutPoint {
+            hash: self.txid,
+            index: self.vout as _,
+        }
+    }
+
+    pub(super) fn rpc_id(&self) -> String {
+        format!("{}:{}", display_hex(&self.txid), self.vout)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    height: u64,
+    time: u32,
+    /// Arrival order, Core's tie-break between equal-work tips
+    seq: u64,
+    valid: bool,
+}
+
+/// Every block seen, for best-chain selection.
+#[derive(Debug, Default)]
+struct BlockTree {
+    entries: HashMap<Hash, TreeEntry>,
+    next_seq: u64,
+}
+
+impl BlockTree {
+    /// Record a block; a block seen before keeps its first arrival.
+    fn insert(&mut self, hash: Hash, height: u64, time: u32, valid: bool) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.entry(hash).or_insert(TreeEntry {
+            height,
+            time,
+            seq,
+            valid,
+        });
+    }
+
+    fn get(&self, hash: &Hash) -> Option<&TreeEntry> {
+        self.entries.get(hash)
+    }
+
+    /// Most work among valid blocks, earliest arrival on a tie. Blocks are only valid when their
+    /// parent is, so a valid tip has a valid chain.
+    fn best_tip(&self) -> Hash {
+        *self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.valid)
+            .max_by(|(_, a), (_, b)| a.height.cmp(&b.height).then(b.seq.cmp(&a.seq)))
+            .expect("genesis is always valid")
+            .0
+    }
+}
+
+/// Builds forks over a fresh regtest node and tracks BLVM's side of them.
+pub struct ReorgHarness {
+    client: NodeRpcClient,
+    tree: BlockTree,
+    /// BLVM's UTXO set after each valid block
+    utxo_sets: HashMap<Hash, UtxoSet>,
+    /// Mature coinbases below every fork, not yet used by a scenario
+    spendable: VecDeque<Coin>,
+    /// Makes sibling blocks differ (coinbase scriptSig)
+    next_tag: u32,
+}
+
+impl ReorgHarness {
+    /// Attach to `client`'s node, which must be at genesis, and mine enough blocks for the
+    /// scenarios to have mature coins to spend.
+    pub async fn new(client: NodeRpcClient) -> Result<Self> {
+        let count = client.getblockcount().await?;
+        anyhow::ensure!(
+            count == 0,
+            "Reorg scenarios need a fresh regtest node (this one is at height {})",
+            count
+        );
+        let (genesis, genesis_time) = genesis(&client).await?;
+        let mut tree = BlockTree::default();
+        tree.insert(genesis, 0, genesis_time, true);
+        let mut harness = Self {
+            client,
+            tree,
+            utxo_sets: HashMap::from([(genesis, UtxoSet::default())]),
+            spendable: VecDeque::new(),
+            next_tag: 0,
+        };
+
+        let mut tip = genesis;
+        for height in 1..=COINBASE_MATURITY + SPENDABLE_COINS {
+            let (hash, coinbase) = harness.submit(tip, Vec::new()).await?;
+            anyhow::ensure!(
+                harness.tree.get(&hash).is_some_and(|e| e.valid),
+                "BLVM rejected prefix block {}",
+                height
+            );
+            if height <= SPENDABLE_COINS {
+                harness.spendable.push_back(coinbase);
+            }
+            tip = hash;
+        }
+        let core_tip = harness.client.getbestblockhash().await?;
+        anyhow::ensure!(
+            core_tip == display_hex(&tip),
+            "Core did not accept the prefix chain (tip {}, expected {})",
+            core_tip,
+            display_hex(&tip)
+        );
+        Ok(harness)
+    }
+
+    /// Run every scenario in [`SCENARIOS`], each forking from where the previous one ended.
+    pub async fn run_all(&mut self) -> Result<Vec<ScenarioOutcome>> {
+        let mut outcomes = Vec::with_capacity(SCENARIOS.len());
+        for scenario in SCENARIOS {
+            let outcome = self.run(scenario).await?;
+            info!("{}", outcome);
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+
+    /// Fork from the current tip, submit both branches, then compare Core and BLVM.
+    pub async fn run(&mut self, scenario: &ReorgScenario) -> Result<ScenarioOutcome> {
+        let fork = self.tree.best_tip();
+        let mut first_txs = vec![Vec::new(); scenario.first_len as usize];
+        let mut second_txs = vec![Vec::new(); scenario.second_len as usize];
+        let mut touched = Vec::new();
+        let position = |at: u32, len: usize| -> Result<usize> {
+            anyhow::ensure!(
+                at >= 1 && (at as usize) <= len,
+                "Scenario {}: block {} is outside its {}-block branch",
+                scenario.name,
+                at,
+                len
+            );
+            Ok(at as usize - 1)
+        };
+        match scenario.spends {
+            Spends::None => {}
+            Spends::Conflicting {
+                first_at,
+                second_at,
+            } => {
+                let coin = self.take_spendable()?;
+                let first = position(first_at, first_txs.len())?;
+                let (tx, spent) = spend_tx(&coin, FEE);
+                first_txs[first].push(tx);
+                touched.extend([coin, spent]);
+                if let Some(next) = first_txs.get_mut(first + 1) {
+                    let (tx, respent) = spend_tx(&spent, FEE);
+                    next.push(tx);
+                    touched.push(respent);
+                }
+                let second = position(second_at, second_txs.len())?;
+                let (tx, spent) = spend_tx(&coin, 2 * FEE);
+                second_txs[second].push(tx);
+                touched.push(spent);
+            }
+            Spends::DoubleSpend { at } => {
+                let coin = self.take_spendable()?;
+                let first = position(at, second_txs.len())?;
+                let second = position(at + 1, second_txs.len())?;
+                let (tx, spent) = spend_tx(&coin, FEE);
+                second_txs[first].push(tx);
+                let (tx, respent) = spend_tx(&coin, 2 * FEE);
+                second_txs[second].push(tx);
+                touched.extend([coin, spent, respent]);
+            }
+        }
+
+        let first_tip = self.extend(fork, first_txs).await?;
+        let second_tip = self.extend(fork, second_txs).await?;
+        let expected = if scenario.second_wins() {
+            second_tip
+        } else {
+            first_tip
+        };
+        self.compare(scenario.name, expected, &touched).await
+    }
+
+    fn take_spendable(&mut self) -> Result<Coin> {
+        self.spendable
+            .pop_front()
+            .context("No spendable coins left for another scenario")
+    }
+
+    /// Submit one block per entry of `blocks` (its non-coinbase transactions) on top of
+    /// `parent`, returning the last hash.
+    async fn extend(&mut self, parent: Hash, blocks: Vec<Vec<Vec<u8>>>) -> Result<Hash> {
+        let mut tip = parent;
+        for txs in blocks {
+            tip = self.submit(tip, txs).await?.0;
+        }
+        Ok(tip)
+    }
+
+    /// Mine a block with `txs` on `parent`, hand it to Core and BLVM, and return its hash and
+    /// coinbase output.
+    async fn submit(&mut self, parent: Hash, txs: Vec<Vec<u8>>) -> Result<(Hash, Coin)> {
+        let parent_entry = self.tree.get(&parent).context("Unknown parent block")?;
+        let height = parent_entry.height + 1;
+        let time = parent_entry.time + 1;
+        let tag = self.next_tag;
+        self.next_tag += 1;
+
+        let (coinbase, coinbase_out) = coinbase_tx(height, tag);
+        let mut all = vec![coinbase];
+        all.extend(txs);
+        let block = mine_block(&parent, time, BLOCK_VERSION, &all);
+        let hash = sha256d(&block[..80]);
+
+        let submitted = self.client.submitblock(&hex::encode(&block)).await?;
+        debug!(
+            "Core on block {} at height {}: {}",
+            display_hex(&hash),
+            height,
+            submitted.error.as_deref().unwrap_or("accepted")
+        );
+        let valid = self.connect(&parent, &hash, height, &block);
+        self.tree.insert(hash, height, time, valid);
+        Ok((hash, coinbase_out))
+    }
+
+    /// Connect `block` in BLVM on its parent's UTXO set; `false` if either is invalid.
+    fn connect(&mut self, parent: &Hash, hash: &Hash, height: u64, raw: &[u8]) -> bool {
+        let Some(parent_set) = self.utxo_sets.get(parent) else {
+            debug!("BLVM: block at height {} has an invalid ancestor", height);
+            return false;
+        };
+        match connect_on(parent_set, height, raw) {
+            Ok(utxo_set) => {
+                self.utxo_sets.insert(*hash, utxo_set);
+                true
+            }
+            Err(reason) => {
+                debug!("BLVM: block at height {}: {}", height, reason);
+                false
+            }
+        }
+    }
+
+    async fn compare(
+        &self,
+        name: &'static str,
+        expected: Hash,
+        touched: &[Coin],
+    ) -> Result<ScenarioOutcome> {
+        let blvm_tip = self.tree.best_tip();
+        let blvm_set = &self.utxo_sets[&blvm_tip];
+        let core_utxos =
+            UtxoSetStats::from_txoutsetinfo(&self.client.gettxoutsetinfo("none", None).await?)?;
+
+        let mut coin_mismatches = Vec::new();
+        for coin in touched {
+            let core = self
+                .client
+                .gettxout(&display_hex(&coin.txid), coin.vout)
+                .await?
+                .is_some();
+            let blvm = blvm_set.contains_key(&coin.outpoint());
+            if core != blvm {
+                coin_mismatches.push((coin.rpc_id(), core, blvm));
+            }
+        }
+
+        Ok(ScenarioOutcome {
+            name,
+            expected_tip: display_hex(&expected),
+            core_tip: self.client.getbestblockhash().await?,
+            blvm_tip: display_hex(&blvm_tip),
+            core_utxos,
+            blvm_utxos: UtxoSetStats::of(blvm_set),
+            coin_mismatches,
+        })
+    }
+}
+
+/// Hash and time of the genesis block of `client`'s node.
+pub(super) async fn genesis(client: &NodeRpcClient) -> Result<(Hash, u32)> {
+    let genesis_hex = client.getblockhash(0).await?;
+    let header = client.getblockheader(&genesis_hex, false).await?;
+    let header = hex::decode(header.as_str().context("Invalid getblockheader response")?)?;
+    anyhow::ensure!(
+        header.len() == 80,
+        "Genesis header is {} bytes",
+        header.len()
+    );
+    Ok((
+        sha256d(&header),
+        u32::from_le_bytes(header[68..72].try_into()?),
+    ))
+}
+
+/// Connect the regtest block `raw` in BLVM on `parent_set`: the new UTXO set, or why it was
+/// rejected.
+pub(super) fn connect_on(
+    parent_set: &UtxoSet,
+    height: u64,
+    raw: &[u8],
+) -> std::result::Result<UtxoSet, String> {
+    let (block, witnesses) = deserialize_block_with_witnesses(raw)
+        .map_err(|e| format!("does not deserialize: {:?}", e))?;
+    let ctx = block_validation_context_for_connect_ibd(
+        None::<&[BlockHeader]>,
+        block.header.timestamp,
+        Network::Regtest,
+    );
+    match connect_block(&block, &witnesses, parent_set.clone(), height, &ctx) {
+        Ok((ValidationResult::Valid, utxo_set)) => Ok(utxo_set),
+        Ok((ValidationResult::Invalid(reason), _)) => Err(format!("rejected: {}", reason)),
+        Err(e) => Err(format!("failed: {:#}", e)),
+    }
+}
+
+/// `CScript() << n` for a non-negative `n` (what BIP34 compares the coinbase against).
+fn push_int(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0 => out.push(0x00),
+        1..=16 => out.push(0x50 + n as u8),
+        _ => {
+            let mut bytes = n.to_le_bytes().to_vec();
+            while bytes.last() == Some(&0) {
+                bytes.pop();
+            }
+            if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+                bytes.push(0);
+            }
+            out.push(bytes.len() as u8);
+            out.extend(bytes);
+        }
+    }
+}
+
+/// One input, one `OP_TRUE` output; returns the raw transaction and its output.
+pub(super) fn simple_tx(prevout: (&Hash, u32), script_sig: &[u8], value: i64) -> (Vec<u8>, Coin) {
+    let mut tx = Vec::new();
+    tx.extend(2u32.to_le_bytes());
+    tx.push(1);
+    tx.extend(prevout.0);
+    tx.extend(prevout.1.to_le_bytes());
+    write_compact_size(&mut tx, script_sig.len());
+    tx.extend(script_sig);
+    tx.extend(u32::MAX.to_le_bytes());
+    tx.push(1);
+    tx.extend(value.to_le_bytes());
+    tx.extend([1, 0x51]);
+    tx.extend(0u32.to_le_bytes());
+    let coin = Coin {
+        txid: sha256d(&tx),
+        vout: 0,
+        value,
+    };
+    (tx, coin)
+}
+
+/// Coinbase at `height` claiming the regtest subsidy (fees are left unclaimed); `tag` keeps
+/// sibling blocks apart.
+pub(super) fn coinbase_tx(height: u64, tag: u32) -> (Vec<u8>, Coin) {
+    let mut script_sig = Vec::new();
+    push_int(&mut script_sig, height);
+    script_sig.push(4);
+    script_sig.extend(tag.to_le_bytes());
+    simple_tx((&[0u8; 32], u32::MAX), &script_sig, subsidy(height))
+}
+
+/// Regtest block subsidy at `height`.
+pub(super) fn subsidy(height: u64) -> i64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 {
+        0
+    } else {
+        5_000_000_000i64 >> halvings
+    }
+}
+
+/// Spend `coin` (an `OP_TRUE` output, so an empty scriptSig) to a new `OP_TRUE` output.
+pub(super) fn spend_tx(coin: &Coin, fee: i64) -> (Vec<u8>, Coin) {
+    simple_tx((&coin.txid, coin.vout), &[], coin.value - fee)
+}
+
+/// Block of `txs` (coinbase first) on `parent`, nonce ground until the hash is below the
+/// regtest target.
+pub(super) fn mine_block(parent: &Hash, time: u32, version: u32, txs: &[Vec<u8>]) -> Vec<u8> {
+    let txids: Vec<Hash> = txs.iter().map(|tx| sha256d(tx)).collect();
+    let mut header = Vec::with_capacity(80);
+    header.extend(version.to_le_bytes());
+    header.extend(parent);
+    header.extend(merkle_root(&txids));
+    header.extend(time.to_le_bytes());
+    header.extend(REGTEST_BITS.to_le_bytes());
+    header.extend(0u32.to_le_bytes());
+    for nonce in 0u32.. {
+        header[76..80].copy_from_slice(&nonce.to_le_bytes());
+        // Target is 0x7fffff << 232; a top byte below 0x7f is always under it
+        if sha256d(&header)[31] < 0x7f {
+            break;
+        }
+    }
+    let mut block = header;
+    write_compact_size(&mut block, txs.len());
+    for tx in txs {
+        block.extend(tx);
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_selection_and_block_building() {
+        let mut tree = BlockTree::default();
+        let h = |n: u8| [n; 32];
+        tree.insert(h(0), 0, 0, true);
+        tree.insert(h(1), 1, 1, true);
+        tree.insert(h(2), 2, 2, true);
+        // Competing branch of equal work arrives later: first seen stays
+        tree.insert(h(11), 1, 1, true);
+        tree.insert(h(12), 2, 2, true);
+        assert_eq!(tree.best_tip(), h(2));
+        // More work but invalid
+        tree.insert(h(13), 3, 3, false);
+        assert_eq!(tree.best_tip(), h(2));
+        // Re-announcing a block does not refresh its arrival
+        tree.insert(h(2), 2, 2, true);
+        tree.insert(h(23), 3, 3, true);
+        assert_eq!(tree.best_tip(), h(23));
+
+        let coin = Coin {
+            txid: h(9),
+            vout: 0,
+            value: 5_000,
+        };
+        let (a, _) = spend_tx(&coin, FEE);
+        let (b, _) = spend_tx(&coin, 2 * FEE);
+        assert_ne!(sha256d(&a), sha256d(&b));
+
+        let (coinbase, _) = coinbase_tx(17, 0);
+        let block = mine_block(&h(0), 1, BLOCK_VERSION, &[coinbase]);
+        assert!(sha256d(&block[..80])[31] < 0x7f);
+        // scriptSig (7 bytes): push(1) 17 for BIP34, then push(4) tag
+        assert_eq!(&block[80 + 1 + 41..80 + 1 + 44], &[7, 1, 17]);
+    }
+}
@@ -0,0 +1,685 @@
+//! Worst-case blocks: valid but pathological regtest blocks through Core and BLVM
+//!
+//! Benchmarks over mainnet blocks measure the average case; an attacker picks the worst.
+//! [`StressHarness`] builds one block per [`StressCase`] on a fresh regtest node, each as close
+//! to a consensus limit as it can get while staying valid, and times both validators on it:
+//! Core's `submitblock` round trip against BLVM's `connect_block` on the parent's UTXO set.
+//!
+//! - [`StressCase::MaxSigops`]: P2SH inputs whose 520-byte redeem scripts run 14 `OP_CHECKSIG`s
+//!   each, up to the 80,000 block sigop cost
+//! - [`StressCase::NestedP2sh`]: P2SH only unwraps once, so the depth goes in the redeem script:
+//!   `OP_IF` nested 100 deep, as far as the 201-opcode limit allows
+//! - [`StressCase::LargeScripts`]: 10,000-byte scriptPubKeys of 520-byte pushes, then spent
+//! - [`StressCase::DustExplosion`]: one transaction with as many 1-satoshi outputs as fit
+//! - [`StressCase::QuadraticSighash`]: one legacy transaction of 5,569 `OP_CHECKSIG` inputs (the
+//!   size of 2015's quadratic-hashing transaction), every input hashing all of it
+//!
+//! No private key is involved: signature checks get a minimal strict-DER signature that never
+//! verifies, and the scripts carry on past the failure (`OP_DROP`, `OP_NOT`). The validators still
+//! compute every sighash and run every ECDSA verification. Each case takes a mature coinbase, fans
+//! it out in a `fund` block and spends the outputs in a `spend` block (except the dust, which is
+//! left in the UTXO set). Outside [`StressCase::QuadraticSighash`] every spend is its own
+//! one-input transaction, so no other case pays for large sighashes.
+//!
+//! The chain follows Core: a block Core rejects is dropped, and a block only BLVM rejects leaves
+//! BLVM's UTXO set behind (so that case's next block fails in BLVM as well).
+
+use anyhow::{Context, Result};
+use blvm_protocol::types::UtxoSet;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use super::reorg_scenarios::{
+    coinbase_tx, connect_on, genesis, mine_block, Coin, Hash, BLOCK_VERSION, COINBASE_MATURITY,
+};
+use crate::node_rpc_client::NodeRpcClient;
+use crate::script_resources::{MAX_OPS_PER_SCRIPT, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE};
+use crate::wallet::hash160;
+use crate::wire::{compact_size_len, display_hex, sha256d, write_compact_size};
+
+/// Consensus `MAX_BLOCK_SIGOPS_COST`
+pub const MAX_BLOCK_SIGOPS_COST: u64 = 80_000;
+/// Inputs of the [`StressCase::QuadraticSighash`] transaction
+pub const QUADRATIC_INPUTS: usize = 5_569;
+/// Legacy and P2SH sigops cost this much each
+const WITNESS_SCALE_FACTOR: u64 = 4;
+/// Bytes the non-coinbase transactions of a block may take: the 1,000,000-byte stripped size
+/// of a block without witnesses, less the header and coinbase
+const BLOCK_BUDGET: usize = 999_000;
+const FEE: i64 = 1_000;
+
+const OP_1: u8 = 0x51;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_NOP: u8 = 0x61;
+const OP_IF: u8 = 0x63;
+const OP_ENDIF: u8 = 0x68;
+const OP_DROP: u8 = 0x75;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_NOT: u8 = 0x91;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+
+/// The secp256k1 generator, compressed: a valid key no dummy signature verifies against
+const PUBKEY: [u8; 33] = [
+    0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b,
+    0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17,
+    0x98,
+];
+/// Smallest strict-DER signature (r = s = 1) with `SIGHASH_ALL`: passes BIP66, never verifies
+const DUMMY_SIG: [u8; 9] = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01];
+/// `OP_DUP <key> OP_CHECKSIG OP_DROP` repetitions in a [`checksig_redeem`]
+const CHECKSIGS_PER_REDEEM: usize = (MAX_SCRIPT_ELEMENT_SIZE - 2) / (PUBKEY.len() + 4);
+/// `OP_IF` depth of a [`nested_if_redeem`]: every level costs an `OP_IF` and an `OP_ENDIF`
+const NESTING_DEPTH: usize = MAX_OPS_PER_SCRIPT as usize / 2;
+
+/// One worst-case shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StressCase {
+    MaxSigops,
+    NestedP2sh,
+    LargeScripts,
+    QuadraticSighash,
+    /// Last, so the other cases do not copy its UTXO set
+    DustExplosion,
+}
+
+impl StressCase {
+    pub const ALL: [StressCase; 5] = [
+        StressCase::MaxSigops,
+        StressCase::NestedP2sh,
+        StressCase::LargeScripts,
+        StressCase::QuadraticSighash,
+        StressCase::DustExplosion,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StressCase::MaxSigops => "max_sigops",
+            StressCase::NestedP2sh => "nested_p2sh",
+            StressCase::LargeScripts => "large_scripts",
+            StressCase::QuadraticSighash => "quadratic_sighash",
+            StressCase::DustExplosion => "dust_explosion",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// scriptPubKey of the outputs the `fund` block creates.
+    pub fn output_script(self) -> Vec<u8> {
+        match self {
+            StressCase::MaxSigops => p2sh(&checksig_redeem()),
+            StressCase::NestedP2sh => p2sh(&nested_if_redeem()),
+            StressCase::LargeScripts => large_script(),
+            StressCase::QuadraticSighash => {
+                let mut script = Vec::new();
+                push(&mut script, &PUBKEY);
+                script.extend([OP_CHECKSIG, OP_NOT]);
+                script
+            }
+            StressCase::DustExplosion => vec![OP_1],
+        }
+    }
+
+    /// scriptSig spending one of those outputs.
+    fn script_sig(self) -> Vec<u8> {
+        let mut script = Vec::new();
+        match self {
+            StressCase::MaxSigops => {
+                push(&mut script, &DUMMY_SIG);
+                push(&mut script, &checksig_redeem());
+            }
+            StressCase::NestedP2sh => push(&mut script, &nested_if_redeem()),
+            StressCase::QuadraticSighash => push(&mut script, &DUMMY_SIG),
+            StressCase::LargeScripts | StressCase::DustExplosion => {}
+        }
+        script
+    }
+
+    /// Sigops counted for each output created (bare scripts count where they appear).
+    fn output_sigops(self) -> u64 {
+        match self {
+            StressCase::QuadraticSighash => 1,
+            _ => 0,
+        }
+    }
+
+    /// Sigops counted for each output spent (P2SH redeem scripts count where they are spent).
+    fn input_sigops(self) -> u64 {
+        match self {
+            StressCase::MaxSigops => CHECKSIGS_PER_REDEEM as u64,
+            _ => 0,
+        }
+    }
+
+    /// Inputs per spending transaction.
+    fn inputs_per_tx(self) -> usize {
+        match self {
+            StressCase::QuadraticSighash => QUADRATIC_INPUTS,
+            _ => 1,
+        }
+    }
+
+    /// Outputs the `fund` block creates: as many as fit in both blocks, by size and sigop cost.
+    pub fn outputs(self) -> usize {
+        let script = self.output_script();
+        let output_size = 8 + compact_size_len(script.len()) + script.len();
+        // One input and the output count's compact size
+        let fund_overhead = 4 + 1 + 41 + 5 + 4;
+        let mut outputs = (BLOCK_BUDGET - fund_overhead) / output_size;
+        if self.output_sigops() > 0 {
+            outputs = outputs
+                .min((MAX_BLOCK_SIGOPS_COST / (self.output_sigops() * WITNESS_SCALE_FACTOR)) as _);
+        }
+        if self == StressCase::DustExplosion {
+            return outputs;
+        }
+
+        let script_sig = self.script_sig();
+        let input_size = 40 + compact_size_len(script_sig.len()) + script_sig.len();
+        let per_tx = self.inputs_per_tx();
+        let tx_size = 4 + compact_size_len(per_tx) + per_tx * input_size + 1 + 10 + 4;
+        outputs = outputs.min(BLOCK_BUDGET / tx_size * per_tx);
+        if per_tx > 1 {
+            // A single spending transaction
+            outputs = outputs.min(per_tx);
+        }
+        if self.input_sigops() > 0 {
+            outputs = outputs
+                .min((MAX_BLOCK_SIGOPS_COST / (self.input_sigops() * WITNESS_SCALE_FACTOR)) as _);
+        }
+        outputs
+    }
+
+    /// The `fund` block's transaction, spending the `OP_TRUE` `coin` into [`Self::outputs`]
+    /// outputs, and the coins it creates.
+    pub fn fund(self, coin: &Coin) -> (BlockPlan, Vec<Coin>) {
+        let outputs = self.outputs();
+        let value = match self {
+            StressCase::DustExplosion => 1,
+            _ => (coin.value - FEE) / outputs as i64,
+        };
+        let tx = raw_tx(
+            std::slice::from_ref(coin),
+            &[],
+            outputs,
+            value,
+            &self.output_script(),
+        );
+        let txid = sha256d(&tx);
+        let coins = (0..outputs as u32)
+            .map(|vout| Coin { txid, vout, value })
+            .collect();
+        let plan = BlockPlan {
+            txs: vec![tx],
+            inputs: 1,
+            outputs,
+            sigop_cost: outputs as u64 * self.output_sigops() * WITNESS_SCALE_FACTOR,
+        };
+        (plan, coins)
+    }
+
+    /// The `spend` block's transactions, spending `coins` to `OP_TRUE`; `None` when the case
+    /// leaves its outputs unspent.
+    pub fn spend(self, coins: &[Coin]) -> Option<BlockPlan> {
+        if self == StressCase::DustExplosion {
+            return None;
+        }
+        let script_sig = self.script_sig();
+        let txs: Vec<Vec<u8>> = coins
+            .chunks(self.inputs_per_tx())
+            .map(|chunk| {
+                let value = chunk.iter().map(|c| c.value).sum::<i64>() - FEE;
+                raw_tx(chunk, &script_sig, 1, value, &[OP_1])
+            })
+            .collect();
+        Some(BlockPlan {
+            outputs: txs.len(),
+            txs,
+            inputs: coins.len(),
+            sigop_cost: coins.len() as u64 * self.input_sigops() * WITNESS_SCALE_FACTOR,
+        })
+    }
+}
+
+impl fmt::Display for StressCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Non-coinbase transactions of a block, and what they add up to.
+#[derive(Debug, Clone)]
+pub struct BlockPlan {
+    pub txs: Vec<Vec<u8>>,
+    pub inputs: usize,
+    pub outputs: usize,
+    /// BIP141 sigop cost, by construction
+    pub sigop_cost: u64,
+}
+
+/// Which of a case's blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Creates the case's outputs
+    Fund,
+    /// Spends them
+    Spend,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Fund => "fund",
+            Stage::Spend => "spend",
+        })
+    }
+}
+
+/// One submitted block: its shape, both verdicts and both timings.
+#[derive(Debug, Clone, Serialize)]
+pub struct StressBlock {
+    pub case: StressCase,
+    pub stage: Stage,
+    pub height: u64,
+    pub hash: String,
+    pub size: usize,
+    /// Coinbase included
+    pub txs: usize,
+    /// Non-coinbase inputs
+    pub inputs: usize,
+    /// Non-coinbase outputs
+    pub outputs: usize,
+    pub sigop_cost: u64,
+    /// `None` when Core accepted the block
+    pub core_error: Option<String>,
+    /// `submitblock` round trip, hex upload included
+    pub core_time: Duration,
+    /// `None` when BLVM accepted the block
+    pub blvm_error: Option<String>,
+    /// `connect_block`, copy of the parent's UTXO set included
+    pub blvm_time: Duration,
+}
+
+impl StressBlock {
+    pub fn accepted(&self) -> bool {
+        self.core_error.is_none() && self.blvm_error.is_none()
+    }
+
+    /// BLVM's time over Core's.
+    pub fn ratio(&self) -> f64 {
+        self.blvm_time.as_secs_f64() / self.core_time.as_secs_f64().max(1e-9)
+    }
+}
+
+impl fmt::Display for StressBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = |error: &Option<String>| error.as_deref().unwrap_or("valid").to_string();
+        write!(
+            f,
+            "{} {} {} at {} ({} bytes, sigop cost {}): core {} in {:.2?}, blvm {} in {:.2?}",
+            if self.accepted() { "✅" } else { "❌" },
+            self.case,
+            self.stage,
+            self.height,
+            self.size,
+            self.sigop_cost,
+            verdict(&self.core_error),
+            self.core_time,
+            verdict(&self.blvm_error),
+            self.blvm_time
+        )
+    }
+}
+
+/// Every case's blocks.
+#[derive(Debug, Clone, Serialize)]
+pub struct StressReport {
+    pub blocks: Vec<StressBlock>,
+}
+
+impl StressReport {
+    /// Both validators accepted every block.
+    pub fn passed(&self) -> bool {
+        self.blocks.iter().all(StressBlock::accepted)
+    }
+
+    pub fn print(&self) {
+        println!("\n🧨 Worst-case blocks");
+        println!(
+            "   {:<18} {:<5} {:>9} {:>6} {:>7} {:>7} {:>7} {:>12} {:>12} {:>6}",
+            "case", "stage", "bytes", "txs", "inputs", "outputs", "sigops", "core", "blvm", "ratio"
+        );
+        for block in &self.blocks {
+            println!(
+                "   {:<18} {:<5} {:>9} {:>6} {:>7} {:>7} {:>7} {:>12.2?} {:>12.2?} {:>6.2}{}",
+                block.case.name(),
+                block.stage.to_string(),
+                block.size,
+                block.txs,
+                block.inputs,
+                block.outputs,
+                block.sigop_cost,
+                block.core_time,
+                block.blvm_time,
+                block.ratio(),
+                if block.accepted() { "" } else { "  ❌" }
+            );
+        }
+    }
+}
+
+/// Result of mining one block on the tip.
+struct Mined {
+    height: u64,
+    hash: Hash,
+    size: usize,
+    core_error: Option<String>,
+    core_time: Duration,
+    blvm_error: Option<String>,
+    blvm_time: Duration,
+}
+
+/// Drives [`StressCase`]s over a fresh regtest node.
+pub struct StressHarness {
+    client: NodeRpcClient,
+    tip: Hash,
+    height: u64,
+    time: u32,
+    /// BLVM's UTXO set after the last block it accepted
+    blvm_set: UtxoSet,
+    /// Mature coinbases, one per case
+    spendable: VecDeque<Coin>,
+}
+
+impl StressHarness {
+    /// Attach to `client`'s node, which must be at genesis, and mine until one coinbase per
+    /// [`StressCase`] is mature.
+    pub async fn new(client: NodeRpcClient) -> Result<Self> {
+        let count = client.getblockcount().await?;
+        anyhow::ensure!(
+            count == 0,
+            "Stress blocks need a fresh regtest node (this one is at height {})",
+            count
+        );
+        let (tip, time) = genesis(&client).await?;
+        let mut harness = Self {
+            client,
+            tip,
+            height: 0,
+            time,
+            blvm_set: UtxoSet::default(),
+            spendable: VecDeque::new(),
+        };
+        let coins = StressCase::ALL.len() as u64;
+        for height in 1..=COINBASE_MATURITY + coins {
+            let (coinbase, coin) = coinbase_tx(height, 0);
+            let mined = harness.mine(vec![coinbase]).await?;
+            anyhow::ensure!(
+                mined.core_error.is_none() && mined.blvm_error.is_none(),
+                "Prefix block {} rejected (core {:?}, blvm {:?})",
+                height,
+                mined.core_error,
+                mined.blvm_error
+            );
+            if height <= coins {
+                harness.spendable.push_back(coin);
+            }
+        }
+        Ok(harness)
+    }
+
+    /// Run `cases` in order.
+    pub async fn run_all(&mut self, cases: &[StressCase]) -> Result<StressReport> {
+        let mut blocks = Vec::new();
+        for &case in cases {
+            for block in self.run(case).await? {
+                info!("{}", block);
+                blocks.push(block);
+            }
+        }
+        Ok(StressReport { blocks })
+    }
+
+    /// Submit one case's blocks on the tip.
+    pub async fn run(&mut self, case: StressCase) -> Result<Vec<StressBlock>> {
+        let coin = self
+            .spendable
+            .pop_front()
+            .with_context(|| format!("No mature coin left for {} (one per case)", case))?;
+        let (plan, coins) = case.fund(&coin);
+        let fund = self.submit(case, Stage::Fund, plan).await?;
+        let mut blocks = vec![fund];
+        if let Some(plan) = case.spend(&coins) {
+            blocks.push(self.submit(case, Stage::Spend, plan).await?);
+        }
+        Ok(blocks)
+    }
+
+    async fn submit(
+        &mut self,
+        case: StressCase,
+        stage: Stage,
+        plan: BlockPlan,
+    ) -> Result<StressBlock> {
+        let (coinbase, _) = coinbase_tx(self.height + 1, 0);
+        let txs = plan.txs.len() + 1;
+        let mut all = vec![coinbase];
+        all.extend(plan.txs);
+        let mined = self.mine(all).await?;
+        Ok(StressBlock {
+            case,
+            stage,
+            height: mined.height,
+            hash: display_hex(&mined.hash),
+            size: mined.size,
+            txs,
+            inputs: plan.inputs,
+            outputs: plan.outputs,
+            sigop_cost: plan.sigop_cost,
+            core_error: mined.core_error,
+            core_time: mined.core_time,
+            blvm_error: mined.blvm_error,
+            blvm_time: mined.blvm_time,
+        })
+    }
+
+    /// Mine `txs` (coinbase first) on the tip, time Core and BLVM on it, and move the tip if
+    /// Core accepted it.
+    async fn mine(&mut self, txs: Vec<Vec<u8>>) -> Result<Mined> {
+        let height = self.height + 1;
+        let time = self.time + 1;
+        let block = mine_block(&self.tip, time, BLOCK_VERSION, &txs);
+        let hash = sha256d(&block[..80]);
+        let block_hex = hex::encode(&block);
+
+        let start = Instant::now();
+        let submitted = self.client.submitblock(&block_hex).await?;
+        let core_time = start.elapsed();
+        let start = Instant::now();
+        let blvm = connect_on(&self.blvm_set, height, &block);
+        let blvm_time = start.elapsed();
+
+        let core_error = submitted.error;
+        let blvm_error = blvm.as_ref().err().cloned();
+        debug!(
+            "Block {} at {}: core {:?}, blvm {:?}",
+            display_hex(&hash),
+            height,
+            core_error,
+            blvm_error
+        );
+        if core_error.is_none() {
+            self.tip = hash;
+            self.height = height;
+            self.time = time;
+            if let Ok(utxo_set) = blvm {
+                self.blvm_set = utxo_set;
+            }
+        }
+        Ok(Mined {
+            height,
+            hash,
+            size: block.len(),
+            core_error,
+            core_time,
+            blvm_error,
+            blvm_time,
+        })
+    }
+}
+
+/// Legacy transaction spending `prevouts` (each with `script_sig`) into `outputs` outputs of
+/// `value` paying to `script_pubkey`.
+fn raw_tx(
+    prevouts: &[Coin],
+    script_sig: &[u8],
+    outputs: usize,
+    value: i64,
+    script_pubkey: &[u8],
+) -> Vec<u8> {
+    let mut tx = Vec::new();
+    tx.extend(1u32.to_le_bytes());
+    write_compact_size(&mut tx, prevouts.len());
+    for coin in prevouts {
+        tx.extend(coin.txid);
+        tx.extend(coin.vout.to_le_bytes());
+        write_compact_size(&mut tx, script_sig.len());
+        tx.extend(script_sig);
+        tx.extend(u32::MAX.to_le_bytes());
+    }
+    write_compact_size(&mut tx, outputs);
+    for _ in 0..outputs {
+        tx.extend(value.to_le_bytes());
+        write_compact_size(&mut tx, script_pubkey.len());
+        tx.extend(script_pubkey);
+    }
+    tx.extend(0u32.to_le_bytes());
+    tx
+}
+
+/// Minimal push of `data`.
+fn push(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        0..=75 => script.push(data.len() as u8),
+        76..=255 => script.extend([OP_PUSHDATA1, data.len() as u8]),
+        len => {
+            script.push(OP_PUSHDATA2);
+            script.extend((len as u16).to_le_bytes());
+        }
+    }
+    script.extend(data);
+}
+
+/// Size of [`push`]ing `len` bytes.
+fn push_size(len: usize) -> usize {
+    len + match len {
+        0..=75 => 1,
+        76..=255 => 2,
+        _ => 3,
+    }
+}
+
+fn p2sh(redeem_script: &[u8]) -> Vec<u8> {
+    let mut script = vec![OP_HASH160, 20];
+    script.extend(hash160(redeem_script));
+    script.push(OP_EQUAL);
+    script
+}
+
+/// `OP_DUP <key> OP_CHECKSIG OP_DROP` as many times as fit in a 520-byte push, then
+/// `OP_DROP OP_1`; spent with a dummy signature, every check fails and is dropped.
+fn checksig_redeem() -> Vec<u8> {
+    let mut script = Vec::with_capacity(MAX_SCRIPT_ELEMENT_SIZE);
+    for _ in 0..CHECKSIGS_PER_REDEEM {
+        script.push(OP_DUP);
+        push(&mut script, &PUBKEY);
+        script.extend([OP_CHECKSIG, OP_DROP]);
+    }
+    script.extend([OP_DROP, OP_1]);
+    script
+}
+
+/// `OP_1 OP_IF` nested [`NESTING_DEPTH`] deep around a final `OP_1`.
+fn nested_if_redeem() -> Vec<u8> {
+    let mut script = [OP_1, OP_IF].repeat(NESTING_DEPTH);
+    script.push(OP_1);
+    script.extend([OP_ENDIF].repeat(NESTING_DEPTH));
+    script
+}
+
+/// Exactly [`MAX_SCRIPT_SIZE`] bytes: pushes of up to 520 bytes, each dropped, then `OP_1`.
+fn large_script() -> Vec<u8> {
+    let mut script = Vec::with_capacity(MAX_SCRIPT_SIZE);
+    let body = MAX_SCRIPT_SIZE - 1;
+    while body - script.len() >= 2 {
+        let room = body - script.len();
+        let mut len = MAX_SCRIPT_ELEMENT_SIZE.min(room);
+        while push_size(len) + 1 > room {
+            len -= 1;
+        }
+        push(&mut script, &vec![0; len]);
+        script.push(OP_DROP);
+    }
+    script.resize(body, OP_NOP);
+    script.push(OP_1);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_template::sigop_cost;
+    use crate::conformance::decode_tx;
+
+    #[test]
+    fn cases_fill_blocks_within_limits() {
+        assert_eq!(large_script().len(), MAX_SCRIPT_SIZE);
+        assert_eq!(checksig_redeem().len(), MAX_SCRIPT_ELEMENT_SIZE);
+        assert_eq!(CHECKSIGS_PER_REDEEM, 14);
+        assert_eq!(NESTING_DEPTH, 100);
+        assert_eq!(
+            StressCase::from_name("dust_explosion"),
+            Some(StressCase::DustExplosion)
+        );
+
+        let coin = Coin {
+            txid: [7; 32],
+            vout: 0,
+            value: 5_000_000_000,
+        };
+        let (coinbase, _) = coinbase_tx(101, 0);
+        for case in StressCase::ALL {
+            let (fund, coins) = case.fund(&coin);
+            let mut plans = vec![(fund, vec![&[OP_1][..]])];
+            let output_script = case.output_script();
+            if let Some(spend) = case.spend(&coins) {
+                plans.push((spend, vec![&output_script[..]; case.inputs_per_tx()]));
+            }
+            for (plan, prevout_scripts) in plans {
+                let mut txs = vec![coinbase.clone()];
+                txs.extend(plan.txs.iter().cloned());
+                let block = mine_block(&[0; 32], 1, BLOCK_VERSION, &txs);
+                assert!(block.len() <= 1_000_000, "{}: {} bytes", case, block.len());
+
+                let mut cost = 0;
+                for raw in &plan.txs {
+                    let (tx, witnesses) = decode_tx(raw).unwrap();
+                    cost += sigop_cost(&tx, &witnesses, &prevout_scripts[..tx.inputs.len()]);
+                }
+                assert_eq!(cost, plan.sigop_cost, "{}", case);
+                assert!(cost <= MAX_BLOCK_SIGOPS_COST);
+            }
+        }
+        assert_eq!(StressCase::MaxSigops.outputs(), 1_428);
+        assert_eq!(StressCase::QuadraticSighash.outputs(), QUADRATIC_INPUTS);
+        assert!(StressCase::DustExplosion.outputs() > 90_000);
+    }
+}
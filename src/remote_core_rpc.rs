@@ -2,6 +2,8 @@
 //!
 //! Reaches `bitcoind` by SSH and `nsenter` into its network namespace, then calls JSON-RPC via local
 //! `curl`. Configure with `REMOTE_CORE_*` env vars. Legacy `LAND_NODE_*` and `START9_*` are still read.
+//! [`crate::container_rpc_client`] generalizes this layout (container discovery, credentials read
+//! from the node, a standard `CoreRpcClient` on top).
 
 use anyhow::{Context, Result};
 use serde_json::Value;
@@ -56,10 +58,73 @@ fn remote_core_rpc_password() -> anyhow::Result<String> {
     })
 }
 
-fn shell_single_quote(s: &str) -> String {
+pub(crate) fn shell_single_quote(s: &str) -> String {
     format!("'{}'", s.replace('\'', "'\"'\"'"))
 }
 
+/// OpenSSH connection sharing (`ControlMaster`) for the PID lookup. Needs Unix domain sockets,
+/// so it is skipped on Windows.
+pub(crate) fn ssh_connection_sharing_args() -> &'static [&'static str] {
+    if cfg!(unix) {
+        &[
+            "-o",
+            "ControlMaster=auto", // Enable connection sharing for speed (LAN)
+            "-o",
+            "ControlPath=~/.ssh/control-%r@%h:%p", // Control socket path
+            "-o",
+            "ControlPersist=300", // Keep connection open for 5 minutes
+        ]
+    } else {
+        &[]
+    }
+}
+
+/// Remote side of an RPC call: `nsenter` into bitcoind's netns and POST stdin with `curl`.
+/// Runs on the (Linux) node host, so it is the same on every client OS.
+fn remote_curl_command(pid: &str, rpc_user: &str, rpc_password: &str, body_len: usize) -> String {
+    let max_time = if body_len > 100_000 { 60 } else { 30 };
+    format!(
+        "sudo nsenter -t {} -n curl -s --max-time {} --user {}:{} --data-binary @- -H 'content-type: text/plain;' http://127.0.0.1:8332/",
+        pid,
+        max_time,
+        shell_single_quote(rpc_user),
+        shell_single_quote(rpc_password)
+    )
+}
+
+/// Run `remote_cmd` over ssh, feeding `stdin_data` to it.
+fn run_ssh_with_stdin(
+    ssh_key: &str,
+    ssh_host: &str,
+    remote_cmd: &str,
+    stdin_data: &[u8],
+) -> std::io::Result<std::process::Output> {
+    use std::io::Write;
+    use std::process::{Command as SyncCommand, Stdio};
+
+    let mut child = SyncCommand::new("ssh")
+        .arg("-i")
+        .arg(ssh_key)
+        .args(["-o", "StrictHostKeyChecking=no"])
+        .args(["-o", "ConnectTimeout=10"])
+        .args(["-o", "BatchMode=yes"])
+        .arg(ssh_host)
+        .arg(remote_cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Write from a separate thread so a large body can't deadlock against a full stdout pipe.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let body = stdin_data.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&body));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| std::io::Error::other("ssh stdin writer panicked"))??;
+    Ok(output)
+}
+
 const MAX_RETRIES: u32 = 2; // Reduced retries for faster failure (dedicated machine)
 const RETRY_DELAY_MS: u64 = 50; // Faster retry (dedicated machine)
 const PROCESS_ID_CACHE_TTL: Duration = Duration::from_secs(60);
@@ -106,12 +171,7 @@ impl RemoteCoreRpcClient {
             .arg("StrictHostKeyChecking=no")
             .arg("-o")
             .arg("ConnectTimeout=5")
-            .arg("-o")
-            .arg("ControlMaster=auto") // Enable connection sharing for speed (LAN)
-            .arg("-o")
-            .arg("ControlPath=~/.ssh/control-%r@%h:%p") // Control socket path
-            .arg("-o")
-            .arg("ControlPersist=300") // Keep connection open for 5 minutes
+            .args(ssh_connection_sharing_args())
             .arg(&ssh_host)
             .arg(pid_cmd)
             .output()
@@ -173,20 +233,18 @@ impl RemoteCoreRpcClient {
                 }
             };
 
-            // For large bodies (>50KB), use a temp file to avoid "Argument list too long"
-            // For smaller bodies, use echo piped through SSH
-            let escaped_body = body_str.replace('\'', "'\\''");
-            let body_len = body_str.len();
-
-            // Run synchronously (simpler, avoids tokio task issues)
-            use std::process::{Command as SyncCommand, Stdio};
-
-            let output = if body_len > 100_000 {
-                // Very large body: write to temp file
-                let temp_path = std::env::temp_dir().join("blvm_rpc_body.json");
-                let body_to_write = escaped_body.replace("'\\''", "'");
-                if let Err(e) = std::fs::write(&temp_path, &body_to_write) {
-                    last_error = Some(anyhow::anyhow!("Failed to write temp file: {}", e));
+            // Run synchronously (simpler, avoids tokio task issues). The body goes to ssh's stdin
+            // directly — no local shell, temp file or argv size limit, so this also works with
+            // the Windows OpenSSH client.
+            let output = match run_ssh_with_stdin(
+                &ssh_key,
+                &ssh_host,
+                &remote_curl_command(&pid, &rpc_user, &rpc_password, body_str.len()),
+                body_str.as_bytes(),
+            ) {
+                Ok(o) => o,
+                Err(e) => {
+                    last_error = Some(anyhow::anyhow!("Command failed: {}", e));
                     if attempt < MAX_RETRIES {
                         sleep(delay).await;
                         delay *= 2;
@@ -194,65 +252,6 @@ impl RemoteCoreRpcClient {
                     }
                     return Err(last_error.unwrap());
                 }
-
-                let full_cmd = format!(
-                    "ssh -i {} -o StrictHostKeyChecking=no -o ConnectTimeout=10 -o BatchMode=yes {} \"sudo nsenter -t {} -n curl -s --max-time 60 --user {}:{} --data-binary @- -H 'content-type: text/plain;' http://127.0.0.1:8332/\" < {}",
-                    shell_single_quote(&ssh_key),
-                    shell_single_quote(&ssh_host),
-                    pid,
-                    shell_single_quote(&rpc_user),
-                    shell_single_quote(&rpc_password),
-                    temp_path.display()
-                );
-
-                match SyncCommand::new("bash")
-                    .arg("-c")
-                    .arg(&full_cmd)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()
-                {
-                    Ok(o) => o,
-                    Err(e) => {
-                        last_error = Some(anyhow::anyhow!("Command failed: {}", e));
-                        if attempt < MAX_RETRIES {
-                            sleep(delay).await;
-                            delay *= 2;
-                            continue;
-                        }
-                        return Err(last_error.unwrap());
-                    }
-                }
-            } else {
-                // Normal body: use echo
-                let full_cmd = format!(
-                    "echo '{}' | ssh -i {} -o StrictHostKeyChecking=no -o ConnectTimeout=10 {} \"sudo nsenter -t {} -n curl -s --max-time 30 --user {}:{} --data-binary @- -H 'content-type: text/plain;' http://127.0.0.1:8332/\"",
-                    escaped_body,
-                    shell_single_quote(&ssh_key),
-                    shell_single_quote(&ssh_host),
-                    pid,
-                    shell_single_quote(&rpc_user),
-                    shell_single_quote(&rpc_password)
-                );
-
-                match SyncCommand::new("bash")
-                    .arg("-c")
-                    .arg(&full_cmd)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()
-                {
-                    Ok(o) => o,
-                    Err(e) => {
-                        last_error = Some(anyhow::anyhow!("Command failed: {}", e));
-                        if attempt < MAX_RETRIES {
-                            sleep(delay).await;
-                            delay *= 2;
-                            continue;
-                        }
-                        return Err(last_error.unwrap());
-                    }
-                }
             };
 
             if !output.status.success() {
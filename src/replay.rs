@@ -0,0 +1,509 @@
+//! Re-run a single divergence
+//!
+//! Finding a divergence takes a chain-wide run; checking a fix for it should not. [`replay`]
+//! takes one saved artifact and validates just that again with the BLVM this crate is built
+//! against:
+//!
+//! - a script artifact (`<height>_<txid>_<input>.json`, written by sort-merge step 6, see
+//!   [`crate::sort_merge::divergence`]) verifies the one input again with BLVM and
+//!   `libbitcoinconsensus`
+//! - a block artifact (`block_<height>.json`, written during differential runs by the
+//!   `divergence-artifacts` [validation hook](crate::validation_hooks::DivergenceArtifactHook))
+//!   runs `connect_block` again against the coins the block spends
+//! - a mempool artifact (`mempool_<txid>.json`, written by [`crate::mempool_fuzz`]) runs
+//!   `accept_to_memory_pool` again over the coins the mutant spends
+//!
+//! The new verdicts are printed next to the recorded ones, so a fix shows up as a changed BLVM
+//! verdict. `blvm-bench replay` logs at debug level, which makes BLVM's own tracing of the
+//! failing check visible.
+//!
+//! [`submit_to_core`] also hands the transaction (`testmempoolaccept`) or block (`submitblock`)
+//! to a Core node. Core can only judge them if it has their parents, e.g. the regtest node a
+//! scenario divergence was found on; otherwise it reports missing inputs / an unknown parent.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::{block_validation_context_for_connect_ibd, calculate_tx_id};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::types::{
+    Block, BlockHeader, Network, Transaction, TransactionOutput, UtxoSet, ValidationResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::block_file_reader::SharedBlockCache;
+use crate::consensus_compat::connect_block;
+use crate::mempool_fuzz::{self, MempoolMutantArtifact};
+use crate::micro_bench::MicroBlock;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::prevout_blocks::{block_hash, decode_sidecar, encode_sidecar, PrevoutBlock};
+use crate::rev_file_reader::{BlockUndo, SpentOutput};
+use crate::sort_merge::divergence::{
+    serialize_for_core, DivergenceArtifact, EngineVerdict, FailedInput,
+};
+use crate::wire::display_hex;
+
+/// Format version written to each block artifact.
+pub const BLOCK_ARTIFACT_VERSION: u32 = 1;
+
+/// A block BLVM and Core disagreed on, with the outputs it spends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockDivergenceArtifact {
+    pub version: u32,
+    /// `mainnet`, `testnet`, `regtest`
+    pub network: String,
+    pub height: u64,
+    /// Display byte order
+    pub block_hash: String,
+    /// Serialized block, with witnesses
+    pub block_hex: String,
+    /// Spent outputs in the `block_<h>.spent` sidecar format ([`crate::prevout_blocks`]);
+    /// `None` if a prevout was missing from the UTXO set, in which case replay reads the
+    /// sidecar from the block cache
+    pub spent_hex: Option<String>,
+    /// `Valid` or `Invalid(<reason>)`, as in `ChunkResult::divergences`
+    pub blvm: String,
+    pub core: String,
+}
+
+impl BlockDivergenceArtifact {
+    /// Record `block` and the coins it spends from `utxo_set` (the set it was connected against,
+    /// or just the coins it spends).
+    pub fn capture(
+        network: &str,
+        height: u64,
+        block: &Block,
+        block_bytes: &[u8],
+        utxo_set: &UtxoSet,
+        blvm: &str,
+        core: &str,
+    ) -> Result<Self> {
+        let hash = block_hash(block_bytes)?;
+        let spent_hex = spent_outputs(block, height, utxo_set)
+            .map(|spent| hex::encode(encode_sidecar(&hash, &spent)));
+        Ok(Self {
+            version: BLOCK_ARTIFACT_VERSION,
+            network: network.to_string(),
+            height,
+            block_hash: display_hex(&hash),
+            block_hex: hex::encode(block_bytes),
+            spent_hex,
+            blvm: blvm.to_string(),
+            core: core.to_string(),
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("block_{}.json", self.height)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let artifact: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        anyhow::ensure!(
+            artifact.version == BLOCK_ARTIFACT_VERSION,
+            "{} is block artifact version {}, expected {}",
+            path.display(),
+            artifact.version,
+            BLOCK_ARTIFACT_VERSION
+        );
+        Ok(artifact)
+    }
+
+    /// Write to `path` ([`write_json_atomic`](crate::utils::write_json_atomic)).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::utils::write_json_atomic(path, self)
+    }
+
+    /// The block with its spent outputs, from the artifact or else from `cache`.
+    fn prevout_block(&self, cache: Option<&SharedBlockCache>) -> Result<PrevoutBlock> {
+        let block_bytes = hex::decode(&self.block_hex).context("Invalid block hex")?;
+        if let Some(spent_hex) = &self.spent_hex {
+            let (hash, spent) =
+                decode_sidecar(&hex::decode(spent_hex).context("Invalid spent outputs hex")?)?;
+            anyhow::ensure!(
+                hash == block_hash(&block_bytes)?,
+                "Spent outputs belong to a different block"
+            );
+            return PrevoutBlock::new(self.height, block_bytes, spent);
+        }
+        let cache = cache.with_context(|| {
+            format!(
+                "Block {} artifact has no spent outputs; pass a block cache with its sidecar",
+                self.height
+            )
+        })?;
+        let cached = cache.read_prevout_block(self.height)?.with_context(|| {
+            format!(
+                "No spent-outputs sidecar for block {} in the cache (export it with micro_block)",
+                self.height
+            )
+        })?;
+        anyhow::ensure!(
+            cached.block_bytes == block_bytes,
+            "Cached block {} differs from the artifact's",
+            self.height
+        );
+        Ok(cached)
+    }
+}
+
+/// Spent outputs of `block` (in [`BlockUndo`] order), looked up in `utxo_set` or, for outputs
+/// created earlier in the block, in the block itself. `None` if a prevout is in neither.
+fn spent_outputs(block: &Block, height: u64, utxo_set: &UtxoSet) -> Option<BlockUndo> {
+    let mut created: HashMap<[u8; 32], (usize, &Transaction)> = HashMap::new();
+    let mut spent = BlockUndo::default();
+    for (tx_idx, tx) in block.transactions.iter().enumerate() {
+        if tx_idx > 0 {
+            let mut outputs = Vec::with_capacity(tx.inputs.len());
+            for input in &tx.inputs {
+                let output = match utxo_set.get(&input.prevout) {
+                    Some(utxo) => SpentOutput {
+                        value: utxo.value as u64,
+                        script_pubkey: utxo.script_pubkey.as_ref().to_vec(),
+                        height: utxo.height as u32,
+                        is_coinbase: utxo.is_coinbase,
+                    },
+                    None => {
+                        let (creator_idx, creator) = created.get(&input.prevout.hash)?;
+                        let output = creator.outputs.get(input.prevout.index as usize)?;
+                        SpentOutput {
+                            value: output.value as u64,
+                            script_pubkey: output.script_pubkey.to_vec(),
+                            height: height as u32,
+                            is_coinbase: *creator_idx == 0,
+                        }
+                    }
+                };
+                outputs.push(output);
+            }
+            spent.txs.push(outputs);
+        }
+        created.insert(calculate_tx_id(tx), (tx_idx, tx));
+    }
+    Some(spent)
+}
+
+/// A saved divergence of either kind.
+#[derive(Debug, Clone)]
+pub enum Artifact {
+    Script(Box<DivergenceArtifact>),
+    Block(Box<BlockDivergenceArtifact>),
+    Mempool(Box<MempoolMutantArtifact>),
+}
+
+impl Artifact {
+    /// Load a script, block or mempool artifact, told apart by their fields.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let value: serde_json::Value = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if value.get("block_hex").is_some() {
+            Ok(Self::Block(Box::new(BlockDivergenceArtifact::load(path)?)))
+        } else if value.get("mutations").is_some() {
+            Ok(Self::Mempool(Box::new(MempoolMutantArtifact::load(path)?)))
+        } else if value.get("tx_hex").is_some() {
+            Ok(Self::Script(Box::new(DivergenceArtifact::load(path)?)))
+        } else {
+            anyhow::bail!("{} is not a divergence artifact", path.display())
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Script(artifact) => format!(
+                "block {} tx {} input {}",
+                artifact.height, artifact.txid, artifact.input_index
+            ),
+            Self::Block(artifact) => {
+                format!("block {} ({})", artifact.height, artifact.block_hash)
+            }
+            Self::Mempool(artifact) => format!(
+                "mempool tx {} (mutant of {})",
+                artifact.txid, artifact.source_txid
+            ),
+        }
+    }
+}
+
+/// Recorded and replayed verdicts, as `Valid` / `Invalid(<reason>)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayOutcome {
+    pub description: String,
+    pub recorded_blvm: String,
+    pub blvm: String,
+    /// `None` where Core's verdict can't be computed locally (taproot inputs, blocks)
+    pub recorded_core: Option<String>,
+    pub core: Option<String>,
+}
+
+impl ReplayOutcome {
+    /// BLVM's verdict changed since the artifact was recorded.
+    pub fn blvm_changed(&self) -> bool {
+        self.blvm != self.recorded_blvm
+    }
+
+    /// BLVM and Core still disagree (as far as Core's verdict is known).
+    pub fn still_diverges(&self) -> bool {
+        let core = self.core.as_ref().or(self.recorded_core.as_ref());
+        core.is_some_and(|core| is_valid(core) != is_valid(&self.blvm))
+    }
+
+    pub fn print(&self) {
+        println!("\n🔁 Replayed {}", self.description);
+        println!("   BLVM: {} (recorded {})", self.blvm, self.recorded_blvm);
+        match (&self.core, &self.recorded_core) {
+            (Some(core), Some(recorded)) => println!("   Core: {} (recorded {})", core, recorded),
+            (None, Some(recorded)) => println!("   Core: recorded {}", recorded),
+            (Some(core), None) => println!("   Core: {}", core),
+            (None, None) => println!("   Core: not checked locally"),
+        }
+        if self.still_diverges() {
+            println!("   ❌ BLVM and Core still disagree");
+        } else if self.blvm_changed() {
+            println!("   ✅ BLVM's verdict changed, engines now agree");
+        } else {
+            println!("   ✅ Engines agree");
+        }
+    }
+}
+
+fn is_valid(verdict: &str) -> bool {
+    verdict == "Valid"
+}
+
+fn verdict_string(verdict: &EngineVerdict) -> String {
+    match (&verdict.error, verdict.valid) {
+        (_, true) => "Valid".to_string(),
+        (Some(error), false) => format!("Invalid({})", error),
+        (None, false) => "Invalid".to_string(),
+    }
+}
+
+/// `Network` from the lowercase name artifacts record.
+pub fn parse_network(name: &str) -> Result<Network> {
+    match name {
+        "mainnet" | "main" => Ok(Network::Mainnet),
+        "testnet" | "test" => Ok(Network::Testnet),
+        "regtest" => Ok(Network::Regtest),
+        _ => anyhow::bail!("Unsupported network {:?} in artifact", name),
+    }
+}
+
+/// Transaction of a script artifact with its per-input witnesses and spent outputs.
+fn decode_script_artifact(
+    artifact: &DivergenceArtifact,
+) -> Result<(Transaction, Vec<Witness>, Vec<TransactionOutput>)> {
+    // deserialize_block_with_witnesses on an empty header and a one-transaction count
+    let mut block = vec![0u8; 80];
+    block.push(1);
+    block.extend(hex::decode(&artifact.tx_hex).context("Invalid tx hex")?);
+    let (block, _) = deserialize_block_with_witnesses(&block)
+        .map_err(|e| anyhow::anyhow!("Failed to deserialize tx {}: {:?}", artifact.txid, e))?;
+    let tx = block
+        .transactions
+        .into_iter()
+        .next()
+        .context("Decoded block has no transaction")?;
+    anyhow::ensure!(
+        artifact.prevouts.len() == tx.inputs.len() && artifact.witnesses.len() == tx.inputs.len(),
+        "{}: {} prevouts / {} witnesses for {} inputs",
+        artifact.txid,
+        artifact.prevouts.len(),
+        artifact.witnesses.len(),
+        tx.inputs.len()
+    );
+    let witnesses: Vec<Witness> = artifact
+        .witnesses
+        .iter()
+        .map(|stack| stack.iter().map(hex::decode).collect::<Result<_, _>>())
+        .collect::<Result<_, _>>()
+        .context("Invalid witness hex")?;
+    let prevouts = artifact
+        .prevouts
+        .iter()
+        .map(|prevout| {
+            Ok(TransactionOutput {
+                value: prevout.value,
+                script_pubkey: hex::decode(&prevout.script_pubkey)
+                    .context("Invalid prevout script hex")?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((tx, witnesses, prevouts))
+}
+
+fn replay_script(artifact: &DivergenceArtifact) -> Result<ReplayOutcome> {
+    let (tx, witnesses, prevouts) = decode_script_artifact(artifact)?;
+    let replayed = DivergenceArtifact::capture(&FailedInput {
+        network: parse_network(&artifact.network)?,
+        height: artifact.height,
+        median_time_past: artifact.median_time_past,
+        tx: &tx,
+        tx_index: artifact.tx_index,
+        input_index: artifact.input_index,
+        prevouts: &prevouts,
+        prevout_origins: artifact
+            .prevouts
+            .iter()
+            .map(|p| p.height.zip(p.is_coinbase))
+            .collect(),
+        witnesses: Some(&witnesses),
+        flags: artifact.flags,
+        failure: &artifact.failure,
+    });
+    Ok(ReplayOutcome {
+        description: Artifact::Script(Box::new(artifact.clone())).describe(),
+        recorded_blvm: verdict_string(&artifact.blvm),
+        blvm: verdict_string(&replayed.blvm),
+        recorded_core: artifact.core.as_ref().map(verdict_string),
+        core: replayed.core.as_ref().map(verdict_string),
+    })
+}
+
+fn replay_block(
+    artifact: &BlockDivergenceArtifact,
+    cache: Option<&SharedBlockCache>,
+) -> Result<ReplayOutcome> {
+    let prevout_block = artifact.prevout_block(cache)?;
+    let micro = MicroBlock::prepare(&prevout_block)?;
+    let ctx = block_validation_context_for_connect_ibd(
+        None::<&[BlockHeader]>,
+        micro.block.header.timestamp,
+        parse_network(&artifact.network)?,
+    );
+    let blvm = match connect_block(
+        &micro.block,
+        &micro.witnesses,
+        micro.utxo_set,
+        micro.height,
+        &ctx,
+    ) {
+        Ok((ValidationResult::Valid, _)) => "Valid".to_string(),
+        Ok((ValidationResult::Invalid(msg), _)) => format!("Invalid({})", msg),
+        Err(e) => format!("Invalid({:?})", e),
+    };
+    Ok(ReplayOutcome {
+        description: Artifact::Block(Box::new(artifact.clone())).describe(),
+        recorded_blvm: artifact.blvm.clone(),
+        blvm,
+        recorded_core: Some(artifact.core.clone()),
+        core: None,
+    })
+}
+
+/// Validate the artifact's input, block or mempool transaction again. `cache` is only read for
+/// block artifacts saved without their spent outputs.
+pub fn replay(artifact: &Artifact, cache: Option<&SharedBlockCache>) -> Result<ReplayOutcome> {
+    match artifact {
+        Artifact::Script(artifact) => replay_script(artifact),
+        Artifact::Block(artifact) => replay_block(artifact, cache),
+        Artifact::Mempool(artifact) => Ok(ReplayOutcome {
+            description: Artifact::Mempool(artifact.clone()).describe(),
+            recorded_blvm: artifact.blvm.clone(),
+            blvm: artifact.replay()?,
+            recorded_core: Some(artifact.core.clone()),
+            core: None,
+        }),
+    }
+}
+
+/// Hand the transaction or block to Core and return its verdict in the same form.
+pub async fn submit_to_core(artifact: &Artifact, client: &NodeRpcClient) -> Result<String> {
+    match artifact {
+        Artifact::Script(artifact) => {
+            let (tx, witnesses, _) = decode_script_artifact(artifact)?;
+            mempool_fuzz::core_verdict(client, &hex::encode(serialize_for_core(&tx, &witnesses)))
+                .await
+        }
+        Artifact::Mempool(artifact) => mempool_fuzz::core_verdict(client, &artifact.tx_hex).await,
+        Artifact::Block(artifact) => {
+            let result = client.submitblock(&artifact.block_hex).await?;
+            Ok(match (result.accepted, result.error) {
+                (true, _) => "Valid".to_string(),
+                (false, error) => format!("Invalid({})", error.unwrap_or_default()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort_merge::divergence::{SpentOutput as ScriptPrevout, ARTIFACT_VERSION};
+
+    #[test]
+    fn loads_either_kind_and_compares_verdicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = DivergenceArtifact {
+            version: ARTIFACT_VERSION,
+            network: "mainnet".to_string(),
+            height: 170,
+            median_time_past: None,
+            txid: "f4184fc5".to_string(),
+            tx_index: 1,
+            input_index: 0,
+            tx_hex: "0100".to_string(),
+            witnesses: vec![Vec::new()],
+            prevouts: vec![ScriptPrevout {
+                value: 5_000_000_000,
+                script_pubkey: "ac".to_string(),
+                height: Some(9),
+                is_coinbase: Some(true),
+            }],
+            flags: 0,
+            failure: "Script returned false".to_string(),
+            blvm: EngineVerdict {
+                valid: false,
+                error: Some("script returned false".to_string()),
+            },
+            core: Some(EngineVerdict {
+                valid: true,
+                error: None,
+            }),
+        };
+        let block = BlockDivergenceArtifact {
+            version: BLOCK_ARTIFACT_VERSION,
+            network: "mainnet".to_string(),
+            height: 170,
+            block_hash: "00".repeat(32),
+            block_hex: "00".repeat(81),
+            spent_hex: None,
+            blvm: "Invalid(bad)".to_string(),
+            core: "Valid".to_string(),
+        };
+        script.save(&dir.path().join(script.file_name())).unwrap();
+        block.save(&dir.path().join(block.file_name())).unwrap();
+
+        match Artifact::load(&dir.path().join(script.file_name())).unwrap() {
+            Artifact::Script(loaded) => assert_eq!(*loaded, script),
+            other => panic!("loaded {:?}", other),
+        }
+        match Artifact::load(&dir.path().join("block_170.json")).unwrap() {
+            Artifact::Block(loaded) => assert_eq!(*loaded, block),
+            other => panic!("loaded {:?}", other),
+        }
+        std::fs::write(dir.path().join("other.json"), "{}").unwrap();
+        assert!(Artifact::load(&dir.path().join("other.json")).is_err());
+
+        assert_eq!(
+            verdict_string(&script.blvm),
+            "Invalid(script returned false)"
+        );
+        let mut outcome = ReplayOutcome {
+            description: String::new(),
+            recorded_blvm: "Invalid(bad)".to_string(),
+            blvm: "Invalid(bad)".to_string(),
+            recorded_core: Some("Valid".to_string()),
+            core: None,
+        };
+        assert!(outcome.still_diverges() && !outcome.blvm_changed());
+        outcome.blvm = "Valid".to_string();
+        assert!(!outcome.still_diverges() && outcome.blvm_changed());
+        assert!(parse_network("signet-ish").is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Machine-readable differential results
+//!
+//! [`DifferentialReport`] is the CI-facing view of a [`ValidationReport`]: per-chunk results,
+//! the UTXO checkpoints the chunks started from and every divergence with its severity. It is
+//! written as one JSON document plus flat CSV tables:
+//!
+//! | file | rows |
+//! |---|---|
+//! | `differential_report.json` | the whole report (`schema_version` bumps on breaking changes) |
+//! | `chunks.csv` | one per validated chunk |
+//! | `checkpoints.csv` | one per checkpoint a chunk started from |
+//! | `divergences.csv` | one per diverging block |
+//! | `divergences.parquet` | the same, with the `parquet` feature ([`DivergenceTable`]) |
+//! | `utxo_drift.csv` | one per checkpoint compared against `gettxoutsetinfo` (when compared) |
+//! | `report.html` | charts and tables for humans ([`html`]) |
+//!
+//! Columns are only ever appended within a schema version. A CI job can gate on `.passed` in
+//! the JSON (`jq -e .passed`), or call [`DifferentialReport::ensure_passed`].
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::collect_only::ValidationReport;
+#[cfg(feature = "parquet")]
+use crate::deep_analysis::dataset;
+use crate::deep_analysis::dataset::{Column, ColumnType, Dataset, Value};
+use crate::parallel_differential::CheckpointTiming;
+use crate::run_summary::{DivergenceEntry, DivergenceSeverity, DivergenceTotals};
+use crate::utxo_stats::UtxoDrift;
+
+pub mod html;
+
+/// Bumped when a field is removed, renamed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const JSON_FILE: &str = "differential_report.json";
+pub const CHUNKS_CSV: &str = "chunks.csv";
+pub const CHECKPOINTS_CSV: &str = "checkpoints.csv";
+pub const DIVERGENCES_CSV: &str = "divergences.csv";
+pub const DIVERGENCES_PARQUET: &str = "divergences.parquet";
+pub const UTXO_DRIFT_CSV: &str = "utxo_drift.csv";
+
+/// Directory for reports from `BLVM_REPORT_DIR`, if set.
+pub fn report_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("BLVM_REPORT_DIR").map(PathBuf::from)
+}
+
+/// One validated chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkRow {
+    pub start_height: u64,
+    pub end_height: u64,
+    pub tested: u64,
+    pub matched: u64,
+    pub divergences: u64,
+    pub duration_secs: f64,
+    pub blocks_per_sec: f64,
+    /// Height of the checkpoint the chunk started from (`None` from genesis or a snapshot)
+    pub checkpoint_height: Option<u64>,
+}
+
+/// Stable serialization of a validation run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifferentialReport {
+    pub schema_version: u32,
+    /// No divergences, every tested block matched and the run was not cancelled
+    pub passed: bool,
+    pub start_height: u64,
+    pub end_height: u64,
+    pub tested: u64,
+    pub matched: u64,
+    pub divergences: DivergenceTotals,
+    pub cancelled: bool,
+    pub duration_secs: f64,
+    /// blvm-consensus version and API shape the run was built against
+    pub consensus: String,
+    pub chunks: Vec<ChunkRow>,
+    /// Distinct checkpoints, by height
+    pub checkpoints: Vec<CheckpointTiming>,
+    /// All divergences, by height
+    pub divergence_entries: Vec<DivergenceEntry>,
+    /// Checkpoint totals against Core's `gettxoutsetinfo`, by height (empty when not compared)
+    #[serde(default)]
+    pub utxo_drift: Vec<UtxoDrift>,
+}
+
+impl DifferentialReport {
+    pub fn from_validation(report: &ValidationReport) -> Self {
+        let chunks = report
+            .chunks
+            .iter()
+            .map(|c| ChunkRow {
+                start_height: c.start_height,
+                end_height: c.end_height,
+                tested: c.tested as u64,
+                matched: c.matched as u64,
+                divergences: c.divergences.len() as u64,
+                duration_secs: c.duration_secs,
+                blocks_per_sec: if c.duration_secs > 0.0 {
+                    c.tested as f64 / c.duration_secs
+                } else {
+                    0.0
+                },
+                checkpoint_height: c.checkpoint.as_ref().map(|cp| cp.height),
+            })
+            .collect();
+
+        let mut checkpoints: Vec<CheckpointTiming> = report
+            .chunks
+            .iter()
+            .filter_map(|c| c.checkpoint.clone())
+            .collect();
+        checkpoints.sort_by_key(|cp| cp.height);
+        checkpoints.dedup_by_key(|cp| cp.height);
+
+        let mut divergences = DivergenceTotals::default();
+        let mut divergence_entries: Vec<DivergenceEntry> = report
+            .chunks
+            .iter()
+            .flat_map(|c| c.divergences.iter())
+            .map(|(height, blvm, core)| DivergenceEntry {
+                height: *height,
+                severity: DivergenceSeverity::classify(blvm, core),
+                blvm: blvm.clone(),
+                core: core.clone(),
+            })
+            .collect();
+        for entry in &divergence_entries {
+            divergences.add(entry.severity);
+        }
+        divergence_entries.sort_by_key(|e| e.height);
+
+        let tested = report.tested as u64;
+        let matched = report.matched as u64;
+        Self {
+            schema_version: SCHEMA_VERSION,
+            passed: !report.cancelled && divergences.total() == 0 && matched == tested,
+            start_height: report.start_height,
+            end_height: report.end_height,
+            tested,
+            matched,
+            divergences,
+            cancelled: report.cancelled,
+            duration_secs: report.duration_secs,
+            consensus: crate::consensus_compat::describe(),
+            chunks,
+            checkpoints,
+            divergence_entries,
+            utxo_drift: Vec::new(),
+        }
+    }
+
+    /// The report with every duration, rate and load flag zeroed, for comparing runs byte for
+    /// byte ([deterministic mode](crate::determinism)).
+    pub fn without_timings(mut self) -> Self {
+        self.duration_secs = 0.0;
+        for chunk in &mut self.chunks {
+            chunk.duration_secs = 0.0;
+            chunk.blocks_per_sec = 0.0;
+        }
+        for checkpoint in &mut self.checkpoints {
+            checkpoint.secs = 0.0;
+            checkpoint.loaded = false;
+        }
+        self
+    }
+
+    /// Error describing the failure unless [`passed`](Self::passed).
+    pub fn ensure_passed(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.passed,
+            "Differential run {}..={} failed: {} divergences ({} critical, {} high, {} low), \
+             {}/{} blocks matched{}",
+            self.start_height,
+            self.end_height,
+            self.divergences.total(),
+            self.divergences.critical,
+            self.divergences.high,
+            self.divergences.low,
+            self.matched,
+            self.tested,
+            if self.cancelled { ", cancelled" } else { "" }
+        );
+        Ok(())
+    }
+
+    /// Write the JSON report and CSV tables into `dir` (created if missing).
+    pub fn write(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create report dir {}", dir.display()))?;
+
+        let json_path = dir.join(JSON_FILE);
+        let tmp = json_path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &json_path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+
+        let chunks = dir.join(CHUNKS_CSV);
+        write_csv(
+            &chunks,
+            "start_height,end_height,tested,matched,divergences,duration_secs,blocks_per_sec,\
+             checkpoint_height",
+            self.chunks.iter().map(|c| {
+                format!(
+                    "{},{},{},{},{},{:.3},{:.3},{}",
+                    c.start_height,
+                    c.end_height,
+                    c.tested,
+                    c.matched,
+                    c.divergences,
+                    c.duration_secs,
+                    c.blocks_per_sec,
+                    c.checkpoint_height.map_or(String::new(), |h| h.to_string())
+                )
+            }),
+        )?;
+
+        let checkpoints = dir.join(CHECKPOINTS_CSV);
+        write_csv(
+            &checkpoints,
+            "height,utxo_count,secs,loaded,total_amount,bogosize",
+            self.checkpoints.iter().map(|cp| {
+                format!(
+                    "{},{},{:.3},{},{},{}",
+                    cp.height,
+                    cp.utxo_count,
+                    cp.secs,
+                    cp.loaded,
+                    cp.stats.total_amount,
+                    cp.stats.bogosize
+                )
+            }),
+        )?;
+
+        let divergences = dir.join(DIVERGENCES_CSV);
+        write_csv(
+            &divergences,
+            "height,severity,blvm,core",
+            self.divergence_entries.iter().map(|e| {
+                format!(
+                    "{},{},{},{}",
+                    e.height,
+                    e.severity.name(),
+                    csv_field(&e.blvm),
+                    csv_field(&e.core)
+                )
+            }),
+        )?;
+
+        let mut paths = vec![json_path, chunks, checkpoints, divergences];
+        #[cfg(feature = "parquet")]
+        {
+            let parquet = dir.join(DIVERGENCES_PARQUET);
+            dataset::write(&DivergenceTable(&self.divergence_entries), &parquet)?;
+            paths.push(parquet);
+        }
+        if !self.utxo_drift.is_empty() {
+            let drift = dir.join(UTXO_DRIFT_CSV);
+            crate::utxo_stats::write_csv(&drift, &self.utxo_drift)?;
+            paths.push(drift);
+        }
+        let page = dir.join(html::HTML_FILE);
+        html::HtmlReport::new(format!(
+            "Differential run {}..={}",
+            self.start_height, self.end_height
+        ))
+        .with_differential(self.clone())
+        .write(&page)?;
+        paths.push(page);
+        Ok(paths)
+    }
+}
+
+/// Divergences as a [`Dataset`]: `height`, `severity`, `blvm`, `core`.
+pub struct DivergenceTable<'a>(pub &'a [DivergenceEntry]);
+
+impl Dataset for DivergenceTable<'_> {
+    fn name(&self) -> &'static str {
+        "divergences"
+    }
+
+    fn schema(&self) -> &'static [Column] {
+        const COLUMNS: &[Column] = &[
+            Column::new("height", ColumnType::UInt64),
+            Column::new("severity", ColumnType::Utf8),
+            Column::new("blvm", ColumnType::Utf8),
+            Column::new("core", ColumnType::Utf8),
+        ];
+        COLUMNS
+    }
+
+    fn rows(&self) -> Box<dyn Iterator<Item = Vec<Value>> + '_> {
+        Box::new(self.0.iter().map(|e| {
+            vec![
+                e.height.into(),
+                e.severity.name().into(),
+                e.blvm.as_str().into(),
+                e.core.as_str().into(),
+            ]
+        }))
+    }
+}
+
+/// RFC 4180 quoting for free-text fields (validation messages contain commas and quotes).
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn write_csv(path: &Path, header: &str, rows: impl Iterator<Item = String>) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    writeln!(out, "{}", header)?;
+    for row in rows {
+        writeln!(out, "{}", row)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel_differential::ChunkResult;
+
+    #[test]
+    fn report_collects_checkpoints_and_divergences() {
+        let checkpoint = CheckpointTiming {
+            height: 99,
+            utxo_count: 120,
+            stats: Default::default(),
+            secs: 1.5,
+            loaded: false,
+        };
+        let chunk = |start_height, end_height, divergences: Vec<_>, checkpoint| ChunkResult {
+            start_height,
+            end_height,
+            tested: 100,
+            matched: 100 - divergences.len(),
+            divergences,
+            duration_secs: 2.0,
+            coin_age: Vec::new(),
+            checkpoint,
+        };
+        let validation = ValidationReport {
+            start_height: 0,
+            end_height: 199,
+            chunks: vec![
+                chunk(0, 99, Vec::new(), None),
+                chunk(
+                    100,
+                    199,
+                    vec![(150, "Invalid(bad, \"sig\")".into(), "Valid".into())],
+                    Some(checkpoint.clone()),
+                ),
+            ],
+            tested: 200,
+            matched: 199,
+            divergences: 1,
+            cancelled: false,
+            duration_secs: 4.0,
+        };
+
+        let report = DifferentialReport::from_validation(&validation);
+        assert_eq!(report.checkpoints, vec![checkpoint]);
+        assert_eq!(report.chunks[1].checkpoint_height, Some(99));
+        assert_eq!(report.chunks[0].blocks_per_sec, 50.0);
+        assert_eq!(report.divergences.high, 1);
+        assert!(!report.passed);
+        assert!(report.ensure_passed().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        report.write(dir.path()).unwrap();
+        let divergences = std::fs::read_to_string(dir.path().join(DIVERGENCES_CSV)).unwrap();
+        assert_eq!(
+            divergences,
+            "height,severity,blvm,core\n150,high,\"Invalid(bad, \"\"sig\"\")\",Valid\n"
+        );
+        let table = DivergenceTable(&report.divergence_entries);
+        assert_eq!(crate::deep_analysis::dataset::to_csv(&table), divergences);
+        let parsed: DifferentialReport =
+            serde_json::from_slice(&std::fs::read(dir.path().join(JSON_FILE)).unwrap()).unwrap();
+        assert_eq!(parsed, report);
+
+        let stripped = report.clone().without_timings();
+        assert_eq!(stripped.checkpoints[0].secs, 0.0);
+        assert_eq!(stripped.chunks[0].blocks_per_sec, 0.0);
+        assert_eq!(stripped.divergence_entries, report.divergence_entries);
+    }
+}
@@ -0,0 +1,603 @@
+//! Static HTML page for a differential run, benchmark timings and a baseline comparison
+//!
+//! [`HtmlReport`] renders whichever of a [`DifferentialReport`], a benchmark [`RunReport`] and a
+//! [`RegressionReport`] it was given into one self-contained page: inline SVG charts and CSS, no
+//! scripts or external assets, so it can be attached to a CI run and opened offline.
+//!
+//! - differential: throughput over height, per-chunk durations, the divergence table and the
+//!   checkpoints the chunks started from
+//! - benchmarks: mean / min / max time, peak RSS and flamegraph links
+//! - baseline: change against the baseline per benchmark, regressions first
+//!
+//! [`DifferentialReport::write`] adds [`HTML_FILE`] for the differential part on its own;
+//! `blvm-bench report <dir> --html <file>` can add the others.
+
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use super::DifferentialReport;
+use crate::registry::RunReport;
+use crate::regression::{DeltaStatus, RegressionReport};
+use crate::watch::format_ns;
+
+/// Page written next to `differential_report.json`
+pub const HTML_FILE: &str = "report.html";
+
+/// Divergences listed in full; the rest are counted
+const MAX_DIVERGENCE_ROWS: usize = 500;
+
+const CHART_WIDTH: f64 = 860.0;
+const CHART_HEIGHT: f64 = 240.0;
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_BOTTOM: f64 = 36.0;
+const MARGIN_TOP: f64 = 12.0;
+const MARGIN_RIGHT: f64 = 16.0;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2em auto;max-width:920px;\
+color:#222}h1{font-size:1.5em}h2{font-size:1.2em;margin-top:2em;border-bottom:1px solid #ddd}\
+table{border-collapse:collapse;font-size:.85em;width:100%}td,th{border:1px solid #ddd;\
+padding:3px 6px;text-align:left}th{background:#f4f4f4}td.num{text-align:right;\
+font-variant-numeric:tabular-nums}.pass{color:#1a7f37}.fail{color:#cf222e}\
+.critical{background:#ffebe9}.high{background:#fff8c5}svg text{font-size:11px;fill:#444}\
+code{font-size:.9em}";
+
+/// Builder for the page; sections without data are left out.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlReport {
+    title: String,
+    differential: Option<DifferentialReport>,
+    benchmarks: Option<RunReport>,
+    regression: Option<RegressionReport>,
+}
+
+impl HtmlReport {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_differential(mut self, report: DifferentialReport) -> Self {
+        self.differential = Some(report);
+        self
+    }
+
+    pub fn with_benchmarks(mut self, report: RunReport) -> Self {
+        self.benchmarks = Some(report);
+        self
+    }
+
+    pub fn with_regression(mut self, report: RegressionReport) -> Self {
+        self.regression = Some(report);
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\
+             <style>{}</style></head><body>\n<h1>{}</h1>\n",
+            escape(&self.title),
+            STYLE,
+            escape(&self.title)
+        );
+        // Left out of deterministic runs, whose reports are diffed byte for byte
+        if !crate::determinism::enabled() {
+            let _ = writeln!(
+                out,
+                "<p>Generated {}</p>",
+                chrono::Utc::now().format("%Y-%m-%d %H:%M UTC")
+            );
+        }
+        if let Some(report) = &self.differential {
+            render_differential(&mut out, report);
+        }
+        if let Some(report) = &self.benchmarks {
+            render_benchmarks(&mut out, report);
+        }
+        if let Some(report) = &self.regression {
+            render_regression(&mut out, report);
+        }
+        out.push_str("</body></html>\n");
+        out
+    }
+
+    /// Write the page to `path`, creating its directory.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(path, self.render())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+fn render_differential(out: &mut String, r: &DifferentialReport) {
+    let (class, verdict) = if r.passed {
+        ("pass", "passed")
+    } else {
+        ("fail", "failed")
+    };
+    let _ = writeln!(
+        out,
+        "<h2>Differential run</h2>\n<p class=\"{}\"><b>{}</b>: blocks {}..={}, {}/{} matched, \
+         {} divergence(s) ({} critical, {} high, {} low){} in {:.1}s</p>\n<p>Consensus: \
+         <code>{}</code></p>",
+        class,
+        verdict,
+        r.start_height,
+        r.end_height,
+        r.matched,
+        r.tested,
+        r.divergences.total(),
+        r.divergences.critical,
+        r.divergences.high,
+        r.divergences.low,
+        if r.cancelled { ", cancelled" } else { "" },
+        r.duration_secs,
+        escape(&r.consensus)
+    );
+
+    if !r.chunks.is_empty() {
+        out.push_str("<h3>Throughput over height</h3>\n");
+        let points: Vec<(f64, f64)> = r
+            .chunks
+            .iter()
+            .map(|c| {
+                (
+                    (c.start_height + c.end_height) as f64 / 2.0,
+                    c.blocks_per_sec,
+                )
+            })
+            .collect();
+        out.push_str(&line_chart(&points, "height", "blocks/s"));
+        out.push_str("<h3>Chunk durations</h3>\n");
+        let bars: Vec<(f64, f64, f64)> = r
+            .chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.start_height as f64,
+                    c.end_height as f64 + 1.0,
+                    c.duration_secs,
+                )
+            })
+            .collect();
+        out.push_str(&bar_chart(&bars, "height", "seconds"));
+    }
+
+    out.push_str("<h3>Divergences</h3>\n");
+    if r.divergence_entries.is_empty() {
+        out.push_str("<p>None.</p>\n");
+    } else {
+        out.push_str(
+            "<table><tr><th>Height</th><th>Severity</th><th>BLVM</th><th>Core</th></tr>\n",
+        );
+        for e in r.divergence_entries.iter().take(MAX_DIVERGENCE_ROWS) {
+            let _ = writeln!(
+                out,
+                "<tr class=\"{}\"><td class=\"num\">{}</td><td>{}</td><td><code>{}</code></td>\
+                 <td><code>{}</code></td></tr>",
+                e.severity.name(),
+                e.height,
+                e.severity.name(),
+                escape(&e.blvm),
+                escape(&e.core)
+            );
+        }
+        out.push_str("</table>\n");
+        if r.divergence_entries.len() > MAX_DIVERGENCE_ROWS {
+            let _ = writeln!(
+                out,
+                "<p>... {} more in <code>divergences.csv</code></p>",
+                r.divergence_entries.len() - MAX_DIVERGENCE_ROWS
+            );
+        }
+    }
+
+    if !r.checkpoints.is_empty() {
+        out.push_str(
+            "<h3>Checkpoints</h3>\n<table><tr><th>Height</th><th>UTXOs</th><th>Seconds</th>\
+             <th>Source</th></tr>\n",
+        );
+        for cp in &r.checkpoints {
+            let _ = writeln!(
+                out,
+                "<tr><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                 <td class=\"num\">{:.1}</td><td>{}</td></tr>",
+                cp.height,
+                cp.utxo_count,
+                cp.secs,
+                if cp.loaded { "loaded" } else { "replayed" }
+            );
+        }
+        out.push_str("</table>\n");
+    }
+}
+
+fn render_benchmarks(out: &mut String, r: &RunReport) {
+    let _ = writeln!(
+        out,
+        "<h2>Benchmarks</h2>\n<p>{} benchmark(s), started {}</p>\n<table><tr><th>Benchmark</th>\
+         <th>Runs</th><th>Mean</th><th>Min</th><th>Max</th><th>Peak RSS</th><th>Profile</th>\
+         </tr>",
+        r.results.len(),
+        escape(&r.started)
+    );
+    for b in &r.results {
+        if let Some(error) = &b.error {
+            let _ = writeln!(
+                out,
+                "<tr class=\"critical\"><td>{}</td><td colspan=\"6\" class=\"fail\">{}</td></tr>",
+                escape(&b.name),
+                escape(error)
+            );
+            continue;
+        }
+        let rss = b.peak_rss_bytes().map_or_else(String::new, |rss| {
+            format!("{:.1} MiB", rss as f64 / (1024.0 * 1024.0))
+        });
+        let profile = b.flamegraph.as_ref().map_or_else(String::new, |svg| {
+            let href = escape(&svg.to_string_lossy());
+            format!("<a href=\"{}\">flamegraph</a>", href)
+        });
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{:.2}s</td>\
+             <td class=\"num\">{:.2}s</td><td class=\"num\">{:.2}s</td><td class=\"num\">{}</td>\
+             <td>{}</td></tr>",
+            escape(&b.name),
+            b.timings_secs.len(),
+            b.mean_secs(),
+            b.min_secs(),
+            b.max_secs(),
+            rss,
+            profile
+        );
+    }
+    out.push_str("</table>\n");
+}
+
+fn render_regression(out: &mut String, r: &RegressionReport) {
+    let mut deltas: Vec<_> = r.deltas.iter().collect();
+    deltas.sort_by(|a, b| {
+        (b.status == DeltaStatus::Regressed)
+            .cmp(&(a.status == DeltaStatus::Regressed))
+            .then(b.pct.abs().total_cmp(&a.pct.abs()))
+    });
+    let regressed = r.regressions().count();
+    let _ = writeln!(
+        out,
+        "<h2>Against baseline <code>{}</code></h2>\n<p class=\"{}\">{} regression(s) beyond \
+         ±{:.1}% over {} benchmark(s)</p>",
+        escape(&r.profile),
+        if regressed == 0 { "pass" } else { "fail" },
+        regressed,
+        r.threshold_pct,
+        deltas.len()
+    );
+    if !deltas.is_empty() {
+        let rows: Vec<(&str, f64, &str)> = deltas
+            .iter()
+            .map(|d| {
+                let color = match d.status {
+                    DeltaStatus::Regressed => "#cf222e",
+                    DeltaStatus::Improved => "#1a7f37",
+                    DeltaStatus::Unchanged => "#8c959f",
+                };
+                (d.id.as_str(), d.pct, color)
+            })
+            .collect();
+        out.push_str(&delta_chart(&rows, r.threshold_pct));
+        out.push_str(
+            "<table><tr><th>Benchmark</th><th>Baseline</th><th>Current</th><th>Change</th></tr>\n",
+        );
+        for d in &deltas {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                 <td class=\"num {}\">{:+.1}%</td></tr>",
+                escape(&d.id),
+                format_ns(d.baseline_ns),
+                format_ns(d.current_ns),
+                match d.status {
+                    DeltaStatus::Regressed => "fail",
+                    DeltaStatus::Improved => "pass",
+                    DeltaStatus::Unchanged => "",
+                },
+                d.pct
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    for (label, ids) in [("Not in baseline", &r.added), ("Not run", &r.missing)] {
+        if !ids.is_empty() {
+            let ids: Vec<String> = ids.iter().map(|id| escape(id)).collect();
+            let _ = writeln!(out, "<p>{}: {}</p>", label, ids.join(", "));
+        }
+    }
+}
+
+/// `(min, max)` of `values`, widened to a non-empty range.
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+        (lo.min(v), hi.max(v))
+    });
+    if !min.is_finite() {
+        (0.0, 1.0)
+    } else if max > min {
+        (min, max)
+    } else {
+        (min, min + 1.0)
+    }
+}
+
+/// Axis label: `1.2M`, `640k`, `3.5`.
+fn tick_label(v: f64) -> String {
+    let a = v.abs();
+    if a >= 1e6 {
+        format!("{:.1}M", v / 1e6)
+    } else if a >= 1e4 {
+        format!("{:.0}k", v / 1e3)
+    } else if a >= 100.0 || v.fract() == 0.0 {
+        format!("{:.0}", v)
+    } else {
+        format!("{:.1}", v)
+    }
+}
+
+/// Maps data coordinates into the plot area of a chart.
+struct Plot {
+    x: (f64, f64),
+    y: (f64, f64),
+}
+
+impl Plot {
+    fn px(&self, x: f64) -> f64 {
+        MARGIN_LEFT
+            + (x - self.x.0) / (self.x.1 - self.x.0) * (CHART_WIDTH - MARGIN_LEFT - MARGIN_RIGHT)
+    }
+
+    fn py(&self, y: f64) -> f64 {
+        CHART_HEIGHT
+            - MARGIN_BOTTOM
+            - (y - self.y.0) / (self.y.1 - self.y.0) * (CHART_HEIGHT - MARGIN_TOP - MARGIN_BOTTOM)
+    }
+
+    /// SVG opening tag, grid lines, tick labels and axis names.
+    fn frame(&self, x_label: &str, y_label: &str) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n",
+            w = CHART_WIDTH,
+            h = CHART_HEIGHT
+        );
+        for i in 0..=4 {
+            let y = self.y.0 + (self.y.1 - self.y.0) * i as f64 / 4.0;
+            let x = self.x.0 + (self.x.1 - self.x.0) * i as f64 / 4.0;
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{l}\" x2=\"{r}\" y1=\"{py:.1}\" y2=\"{py:.1}\" stroke=\"#eee\"/>\
+                 <text x=\"{tx}\" y=\"{ty:.1}\" text-anchor=\"end\">{yl}</text>\
+                 <text x=\"{px:.1}\" y=\"{bx}\" text-anchor=\"middle\">{xl}</text>",
+                l = MARGIN_LEFT,
+                r = CHART_WIDTH - MARGIN_RIGHT,
+                py = self.py(y),
+                tx = MARGIN_LEFT - 6.0,
+                ty = self.py(y) + 4.0,
+                yl = tick_label(y),
+                px = self.px(x),
+                bx = CHART_HEIGHT - MARGIN_BOTTOM + 16.0,
+                xl = tick_label(x)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\
+             <text x=\"14\" y=\"{y}\" text-anchor=\"middle\" \
+             transform=\"rotate(-90 14 {y})\">{}</text>",
+            (MARGIN_LEFT + CHART_WIDTH - MARGIN_RIGHT) / 2.0,
+            CHART_HEIGHT - 4.0,
+            x_label,
+            y_label,
+            y = CHART_HEIGHT / 2.0
+        );
+        svg
+    }
+}
+
+/// Line through `points` (sorted by x).
+fn line_chart(points: &[(f64, f64)], x_label: &str, y_label: &str) -> String {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let plot = Plot {
+        x: bounds(points.iter().map(|p| p.0)),
+        y: (0.0, bounds(points.iter().map(|p| p.1)).1),
+    };
+    let mut svg = plot.frame(x_label, y_label);
+    let coords: Vec<String> = points
+        .iter()
+        .map(|&(x, y)| format!("{:.1},{:.1}", plot.px(x), plot.py(y)))
+        .collect();
+    let _ = writeln!(
+        svg,
+        "<polyline fill=\"none\" stroke=\"#0969da\" stroke-width=\"1.5\" points=\"{}\"/>",
+        coords.join(" ")
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// One bar per `(x_start, x_end, y)`.
+fn bar_chart(bars: &[(f64, f64, f64)], x_label: &str, y_label: &str) -> String {
+    let plot = Plot {
+        x: bounds(bars.iter().flat_map(|b| [b.0, b.1])),
+        y: (0.0, bounds(bars.iter().map(|b| b.2)).1),
+    };
+    let mut svg = plot.frame(x_label, y_label);
+    for &(start, end, y) in bars {
+        let (left, top) = (plot.px(start), plot.py(y));
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#54aeff\" \
+             stroke=\"#fff\" stroke-width=\"0.5\"><title>{}..{}: {:.1}</title></rect>",
+            left,
+            top,
+            (plot.px(end) - left).max(1.0),
+            plot.py(0.0) - top,
+            start,
+            end - 1.0,
+            y
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Horizontal bars of `(label, percent change, color)` around zero, with the threshold marked.
+fn delta_chart(rows: &[(&str, f64, &str)], threshold: f64) -> String {
+    const ROW: f64 = 18.0;
+    const LABEL: f64 = 320.0;
+    let height = ROW * rows.len() as f64 + 24.0;
+    let span = rows
+        .iter()
+        .map(|r| r.1.abs())
+        .fold(threshold * 2.0, f64::max)
+        .max(1.0);
+    let zero = LABEL + (CHART_WIDTH - LABEL - MARGIN_RIGHT) / 2.0;
+    let scale = (CHART_WIDTH - LABEL - MARGIN_RIGHT) / 2.0 / span;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\">\n",
+        w = CHART_WIDTH,
+        h = height
+    );
+    for t in [-threshold, threshold] {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{x:.1}\" x2=\"{x:.1}\" y1=\"0\" y2=\"{}\" stroke=\"#d0d7de\" \
+             stroke-dasharray=\"4 3\"/>",
+            height - 20.0,
+            x = zero + t * scale
+        );
+    }
+    for (i, &(label, pct, color)) in rows.iter().enumerate() {
+        let y = ROW * i as f64;
+        let (x, width) = if pct >= 0.0 {
+            (zero, pct * scale)
+        } else {
+            (zero + pct * scale, -pct * scale)
+        };
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\">{:+.1}%</text>",
+            LABEL - 8.0,
+            y + 13.0,
+            escape(label),
+            x,
+            y + 3.0,
+            width.max(1.0),
+            ROW - 6.0,
+            color,
+            if pct >= 0.0 {
+                x + width + 4.0
+            } else {
+                zero + 4.0
+            },
+            y + 13.0,
+            pct
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<line x1=\"{z:.1}\" x2=\"{z:.1}\" y1=\"0\" y2=\"{}\" stroke=\"#444\"/>\
+         <text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">slower →</text></svg>",
+        height - 20.0,
+        zero + (CHART_WIDTH - MARGIN_RIGHT - zero) / 2.0,
+        height - 6.0,
+        z = zero
+    );
+    svg
+}
+
+/// Escape text for HTML element content and quoted attributes.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ChunkRow;
+    use crate::run_summary::{DivergenceEntry, DivergenceSeverity, DivergenceTotals};
+
+    #[test]
+    fn page_has_charts_and_escaped_divergences() {
+        let chunk = |start_height, duration_secs| ChunkRow {
+            start_height,
+            end_height: start_height + 999,
+            tested: 1000,
+            matched: 1000,
+            divergences: 0,
+            duration_secs,
+            blocks_per_sec: 1000.0 / duration_secs,
+            checkpoint_height: None,
+        };
+        let report = DifferentialReport {
+            schema_version: crate::report::SCHEMA_VERSION,
+            passed: false,
+            start_height: 0,
+            end_height: 1999,
+            tested: 2000,
+            matched: 1999,
+            divergences: DivergenceTotals {
+                critical: 1,
+                ..Default::default()
+            },
+            cancelled: false,
+            duration_secs: 30.0,
+            consensus: "blvm-consensus 0.1".to_string(),
+            chunks: vec![chunk(0, 10.0), chunk(1000, 20.0)],
+            checkpoints: Vec::new(),
+            divergence_entries: vec![DivergenceEntry {
+                height: 1500,
+                severity: DivergenceSeverity::Critical,
+                blvm: "Valid".to_string(),
+                core: "Invalid(<script> & \"more\")".to_string(),
+            }],
+            utxo_drift: Vec::new(),
+        };
+
+        let html = HtmlReport::new("Nightly")
+            .with_differential(report)
+            .render();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<polyline"));
+        assert_eq!(html.matches("<rect").count(), 2);
+        assert!(html.contains("Invalid(&lt;script&gt; &amp; &quot;more&quot;)"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("Benchmarks</h2>"));
+
+        assert_eq!(bounds(std::iter::empty()), (0.0, 1.0));
+        assert_eq!(bounds([5.0, 5.0].into_iter()), (5.0, 6.0));
+        assert_eq!(tick_label(840_000.0), "840k");
+        assert_eq!(tick_label(2.5), "2.5");
+    }
+}
@@ -0,0 +1,382 @@
+//! Historical benchmark results in SQLite (`results-db` feature)
+//!
+//! `benchmarks.json` and the committed baselines only ever compare two points, which hides a
+//! regression that arrives 1% at a time. [`ResultsDb`] keeps every run in one SQLite file
+//! (`BLVM_RESULTS_DB`, default `results.db` in the results directory), tagged with a
+//! [`RunContext`]: git commit, machine profile and a hash of the effective tuning config.
+//!
+//! - `runs`: one row per recorded run, with its context
+//! - `measurements`: one row per benchmark per run, both registry timings
+//!   (`source = 'registry'`) and the Criterion means written during the run
+//!   (`source = 'criterion'`)
+//!
+//! [`ResultsDb::trend`] returns one benchmark's measurements in run order and
+//! [`ResultsDb::trend_by_commit`] averages them per commit, both for one machine profile since
+//! timings from different machines don't compare. `blvm-bench history` prints or exports them as
+//! CSV for plotting.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::registry::RunReport;
+
+pub const DEFAULT_FILE: &str = "results.db";
+
+/// Bumped with every schema change; [`ResultsDb::open`] migrates older files.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    recorded_at TEXT NOT NULL,
+    git_commit TEXT,
+    machine_profile TEXT NOT NULL,
+    config_hash TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS measurements (
+    run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    benchmark TEXT NOT NULL,
+    source TEXT NOT NULL,
+    mean_secs REAL,
+    min_secs REAL,
+    max_secs REAL,
+    samples INTEGER NOT NULL,
+    peak_rss_bytes INTEGER,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS measurements_benchmark ON measurements(benchmark, run_id);
+";
+
+/// Database from `BLVM_RESULTS_DB`, else [`DEFAULT_FILE`] in the results directory.
+pub fn db_path_from_env() -> PathBuf {
+    std::env::var_os("BLVM_RESULTS_DB")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::utils::results_dir().join(DEFAULT_FILE))
+}
+
+/// What a run's numbers depend on besides the code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunContext {
+    /// RFC 3339
+    pub recorded_at: String,
+    pub git_commit: Option<String>,
+    pub machine_profile: String,
+    /// First 16 hex digits of SHA-256 over the effective
+    /// [`BenchConfig`](crate::config::BenchConfig) and production mode
+    pub config_hash: String,
+}
+
+impl RunContext {
+    /// Context of this process: HEAD of the crate's checkout, the regression machine profile
+    /// and the loaded config.
+    pub fn current() -> Self {
+        Self {
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            git_commit: crate::regression::git_rev(),
+            machine_profile: crate::regression::machine_profile(),
+            config_hash: config_hash(crate::config::global(), crate::utils::is_production_mode()),
+        }
+    }
+}
+
+fn config_hash(config: &crate::config::BenchConfig, production: bool) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(config).unwrap_or_default());
+    hasher.update([u8::from(production)]);
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// One benchmark in one run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrendPoint {
+    pub run_id: i64,
+    pub recorded_at: String,
+    pub git_commit: Option<String>,
+    pub config_hash: String,
+    pub mean_secs: f64,
+}
+
+/// One benchmark averaged over the runs of one commit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitPoint {
+    pub git_commit: String,
+    /// Earliest run of the commit
+    pub first_recorded_at: String,
+    pub runs: u64,
+    pub mean_secs: f64,
+}
+
+pub struct ResultsDb {
+    conn: Connection,
+}
+
+impl ResultsDb {
+    /// Open (or create) the database at `path` and bring its schema up to date.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open results database {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        anyhow::ensure!(
+            version <= SCHEMA_VERSION,
+            "Results database has schema {} - newer than this build ({})",
+            version,
+            SCHEMA_VERSION
+        );
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { conn })
+    }
+
+    /// Record `report` (and the Criterion `means_ns` it produced, by benchmark id) as one run.
+    pub fn record(
+        &mut self,
+        ctx: &RunContext,
+        report: &RunReport,
+        means_ns: &BTreeMap<String, f64>,
+    ) -> Result<i64> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO runs (recorded_at, git_commit, machine_profile, config_hash)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                ctx.recorded_at,
+                ctx.git_commit,
+                ctx.machine_profile,
+                ctx.config_hash
+            ],
+        )?;
+        let run_id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO measurements (run_id, benchmark, source, mean_secs, min_secs,
+                 max_secs, samples, peak_rss_bytes, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for r in &report.results {
+                let timed = !r.timings_secs.is_empty();
+                insert.execute(params![
+                    run_id,
+                    r.name,
+                    "registry",
+                    timed.then(|| r.mean_secs()),
+                    timed.then(|| r.min_secs()),
+                    timed.then(|| r.max_secs()),
+                    r.timings_secs.len() as i64,
+                    r.peak_rss_bytes().map(|b| b as i64),
+                    r.error,
+                ])?;
+            }
+            for (id, ns) in means_ns {
+                let secs = ns / 1e9;
+                insert.execute(params![
+                    run_id,
+                    id,
+                    "criterion",
+                    secs,
+                    None::<f64>,
+                    None::<f64>,
+                    1,
+                    None::<i64>,
+                    None::<String>,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(run_id)
+    }
+
+    /// Benchmarks with a measurement on `machine_profile`, by name.
+    pub fn benchmarks(&self, machine_profile: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT m.benchmark FROM measurements m JOIN runs r ON r.id = m.run_id
+             WHERE r.machine_profile = ?1 ORDER BY m.benchmark",
+        )?;
+        let names = stmt
+            .query_map([machine_profile], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(names)
+    }
+
+    /// Successful measurements of `benchmark` on `machine_profile`, oldest first; the last
+    /// `limit` when given.
+    pub fn trend(
+        &self,
+        benchmark: &str,
+        machine_profile: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<TrendPoint>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM (
+                 SELECT r.id, r.recorded_at, r.git_commit, r.config_hash, m.mean_secs
+                 FROM measurements m JOIN runs r ON r.id = m.run_id
+                 WHERE m.benchmark = ?1 AND r.machine_profile = ?2 AND m.mean_secs IS NOT NULL
+                   AND m.error IS NULL
+                 ORDER BY r.id DESC LIMIT ?3
+             ) ORDER BY id",
+        )?;
+        let limit = limit.map_or(-1, |l| l as i64);
+        let points = stmt
+            .query_map(params![benchmark, machine_profile, limit], |row| {
+                Ok(TrendPoint {
+                    run_id: row.get(0)?,
+                    recorded_at: row.get(1)?,
+                    git_commit: row.get(2)?,
+                    config_hash: row.get(3)?,
+                    mean_secs: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(points)
+    }
+
+    /// [`trend`](Self::trend) averaged per commit, in the order the commits were first measured.
+    /// Runs without a known commit are left out.
+    pub fn trend_by_commit(
+        &self,
+        benchmark: &str,
+        machine_profile: &str,
+    ) -> Result<Vec<CommitPoint>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.git_commit, MIN(r.recorded_at), COUNT(*), AVG(m.mean_secs)
+             FROM measurements m JOIN runs r ON r.id = m.run_id
+             WHERE m.benchmark = ?1 AND r.machine_profile = ?2 AND r.git_commit IS NOT NULL
+               AND m.mean_secs IS NOT NULL AND m.error IS NULL
+             GROUP BY r.git_commit ORDER BY MIN(r.id)",
+        )?;
+        let points = stmt
+            .query_map(params![benchmark, machine_profile], |row| {
+                Ok(CommitPoint {
+                    git_commit: row.get(0)?,
+                    first_recorded_at: row.get(1)?,
+                    runs: row.get::<_, i64>(2)? as u64,
+                    mean_secs: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(points)
+    }
+
+    /// Context of the most recent run on `machine_profile`.
+    pub fn latest_run(&self, machine_profile: &str) -> Result<Option<(i64, RunContext)>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, recorded_at, git_commit, machine_profile, config_hash FROM runs
+                 WHERE machine_profile = ?1 ORDER BY id DESC LIMIT 1",
+                [machine_profile],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        RunContext {
+                            recorded_at: row.get(1)?,
+                            git_commit: row.get(2)?,
+                            machine_profile: row.get(3)?,
+                            config_hash: row.get(4)?,
+                        },
+                    ))
+                },
+            )
+            .optional()?)
+    }
+}
+
+/// `git_commit,first_recorded_at,runs,mean_secs` rows for plotting.
+pub fn write_commit_csv(path: &Path, points: &[CommitPoint]) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    writeln!(out, "git_commit,first_recorded_at,runs,mean_secs")?;
+    for p in points {
+        writeln!(
+            out,
+            "{},{},{},{:.9}",
+            p.git_commit, p.first_recorded_at, p.runs, p.mean_secs
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Record `report` in the database from [`db_path_from_env`], with the Criterion means written
+/// since `started`.
+pub fn record_run(report: &RunReport, started: std::time::SystemTime) -> Result<i64> {
+    let path = db_path_from_env();
+    let means = crate::watch::criterion_means(&crate::regression::criterion_dir(), started)?;
+    let run_id = ResultsDb::open(&path)?.record(&RunContext::current(), report, &means)?;
+    println!("🗄️  Run {} recorded in {}", run_id, path.display());
+    Ok(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::BenchmarkResult;
+
+    #[test]
+    fn trends_follow_runs_and_commits() {
+        let mut db = ResultsDb::open_in_memory().unwrap();
+        let report = |secs: f64| RunReport {
+            started: "2025-01-01T00:00:00Z".to_string(),
+            results: vec![BenchmarkResult {
+                name: "shell/ibd".to_string(),
+                tags: vec!["shell".to_string()],
+                timings_secs: vec![secs, secs + 2.0],
+                usage: Vec::new(),
+                memory: None,
+                flamegraph: None,
+                error: None,
+            }],
+        };
+        let ctx = |commit: &str| RunContext {
+            recorded_at: format!("2025-01-0{}T00:00:00Z", commit.len()),
+            git_commit: Some(commit.to_string()),
+            machine_profile: "ci".to_string(),
+            config_hash: config_hash(&Default::default(), false),
+        };
+        let means = BTreeMap::from([("hash/sha256".to_string(), 1500.0)]);
+        db.record(&ctx("a"), &report(10.0), &means).unwrap();
+        db.record(&ctx("a"), &report(12.0), &BTreeMap::new())
+            .unwrap();
+        db.record(&ctx("bb"), &report(20.0), &BTreeMap::new())
+            .unwrap();
+
+        let trend = db.trend("shell/ibd", "ci", None).unwrap();
+        let means: Vec<f64> = trend.iter().map(|p| p.mean_secs).collect();
+        assert_eq!(means, [11.0, 13.0, 21.0]);
+        let last = db.trend("shell/ibd", "ci", Some(2)).unwrap();
+        assert_eq!(last[0].mean_secs, 13.0);
+        assert!(db.trend("shell/ibd", "laptop", None).unwrap().is_empty());
+
+        let by_commit = db.trend_by_commit("shell/ibd", "ci").unwrap();
+        assert_eq!(by_commit.len(), 2);
+        assert_eq!(
+            (by_commit[0].git_commit.as_str(), by_commit[0].runs),
+            ("a", 2)
+        );
+        assert_eq!(by_commit[0].mean_secs, 12.0);
+        assert_eq!(db.benchmarks("ci").unwrap(), ["hash/sha256", "shell/ibd"]);
+        assert_eq!(
+            db.trend("hash/sha256", "ci", None).unwrap()[0].mean_secs,
+            1.5e-6
+        );
+        assert_eq!(db.latest_run("ci").unwrap().unwrap().0, 3);
+    }
+}
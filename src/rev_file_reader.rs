@@ -0,0 +1,337 @@
+//! Bitcoin Core undo file reader
+//!
+//! Core stores, for every connected block, the outputs its transactions spent (`CBlockUndo`) in
+//! `rev*.dat` next to the `blk*.dat` files. Reading them gives the value, script, height and
+//! coinbase flag of every prevout of a block directly, without rebuilding the UTXO set or running
+//! the external sort over all outputs.
+//!
+//! Records are framed like blocks (`magic | size | data`) followed by a 32-byte checksum,
+//! `SHA256d(parent block hash || data)`, which is verified on every read. Offsets come from Core's
+//! block index ([`CoreBlockIndex::undo_location`]); files are deobfuscated with the same
+//! `xor.dat` key as the block files.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::block_file_reader::{load_xor_key, BlockFileReader, Network};
+use crate::block_framing::XorKey;
+use crate::block_index::{CoreBlockIndex, UndoLocation};
+use crate::wire::{read_compact_size, read_varint};
+
+/// `MAX_SCRIPT_SIZE`; longer stored scripts are replaced by `OP_RETURN` like Core does.
+pub(crate) const MAX_SCRIPT_SIZE: u64 = 10_000;
+/// Largest undo record accepted (a full block spending only tiny inputs stays well below this).
+const MAX_UNDO_SIZE: usize = 64 * 1024 * 1024;
+
+/// A spent output as recorded in undo data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpentOutput {
+    /// Satoshis
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+    /// Height of the block that created the output
+    pub height: u32,
+    pub is_coinbase: bool,
+}
+
+/// Undo data of one block: the outputs spent by each non-coinbase transaction, in input order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockUndo {
+    /// `txs[i]` belongs to transaction `i + 1` of the block (the coinbase has no entry)
+    pub txs: Vec<Vec<SpentOutput>>,
+}
+
+impl BlockUndo {
+    /// Decode a serialized `CBlockUndo`.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let tx_count = read_compact_size(data, &mut pos)?;
+        let mut txs = Vec::with_capacity(tx_count.min(data.len() as u64) as usize);
+        for tx in 0..tx_count {
+            let input_count = read_compact_size(data, &mut pos)?;
+            let mut spent = Vec::with_capacity(input_count.min(data.len() as u64) as usize);
+            for input in 0..input_count {
+                spent.push(
+                    read_spent_output(data, &mut pos)
+                        .with_context(|| format!("undo of tx {} input {}", tx + 1, input))?,
+                );
+            }
+            txs.push(spent);
+        }
+        anyhow::ensure!(
+            pos == data.len(),
+            "{} trailing bytes after block undo",
+            data.len() - pos
+        );
+        Ok(Self { txs })
+    }
+
+    /// Output spent by input `input` of transaction `tx` (block order, 0 = coinbase).
+    pub fn prevout(&self, tx: usize, input: usize) -> Option<&SpentOutput> {
+        self.txs.get(tx.checked_sub(1)?)?.get(input)
+    }
+
+    /// Total number of spent outputs.
+    pub fn spent_count(&self) -> usize {
+        self.txs.iter().map(Vec::len).sum()
+    }
+}
+
+/// Reads undo records from a data directory's `rev*.dat` files.
+#[derive(Clone)]
+pub struct RevFileReader {
+    blocks_dir: PathBuf,
+    magic: [u8; 4],
+    xor_key: Option<XorKey>,
+    core_index: Option<Arc<CoreBlockIndex>>,
+}
+
+impl RevFileReader {
+    /// Open `<data_dir>/blocks`, loading `xor.dat` and Core's block index.
+    pub fn new(data_dir: impl AsRef<Path>, network: Network) -> Result<Self> {
+        let data_dir = data_dir.as_ref();
+        let blocks_dir = data_dir.join("blocks");
+        anyhow::ensure!(
+            blocks_dir.is_dir(),
+            "Blocks directory not found: {}",
+            blocks_dir.display()
+        );
+        let core_index = if blocks_dir.join("index").is_dir() {
+            CoreBlockIndex::open(data_dir)
+                .map(Arc::new)
+                .map_err(|e| eprintln!("⚠️  Could not read Core block index: {:#}", e))
+                .ok()
+        } else {
+            None
+        };
+        Ok(Self {
+            xor_key: load_xor_key(&blocks_dir)?,
+            blocks_dir,
+            magic: *network.magic_bytes(),
+            core_index,
+        })
+    }
+
+    /// Undo reader for the same data directory, sharing the reader's key and block index.
+    pub fn for_block_reader(reader: &BlockFileReader) -> Self {
+        Self {
+            blocks_dir: reader.data_dir().join("blocks"),
+            magic: *reader.network().magic_bytes(),
+            xor_key: reader.xor_key(),
+            core_index: reader.core_block_index(),
+        }
+    }
+
+    /// Undo data of the active-chain block at `height` (via Core's block index).
+    pub fn read_undo_by_height(&self, height: u64) -> Result<BlockUndo> {
+        let index = self
+            .core_index
+            .as_ref()
+            .context("No Core block index available - cannot locate undo data by height")?;
+        let location = index.undo_location(height).with_context(|| {
+            format!(
+                "No undo data for height {} (genesis, pruned or above tip {})",
+                height,
+                index.tip_height()
+            )
+        })?;
+        self.read_undo_at(&location)
+            .with_context(|| format!("Failed to read undo data for block {}", height))
+    }
+
+    /// Read and checksum-verify the undo record at `location`.
+    pub fn read_undo_at(&self, location: &UndoLocation) -> Result<BlockUndo> {
+        let path = self.blocks_dir.join(location.file_name());
+        let mut file =
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let header_offset = u64::from(location.undo_pos)
+            .checked_sub(8)
+            .context("Undo position precedes its record header")?;
+        file.seek(SeekFrom::Start(header_offset))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        if let Some(key) = self.xor_key {
+            key.apply(&mut header, header_offset);
+        }
+        anyhow::ensure!(
+            header[..4] == self.magic,
+            "No record magic before {}:{}",
+            location.file_name(),
+            location.undo_pos
+        );
+        let size = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        anyhow::ensure!(
+            size <= MAX_UNDO_SIZE,
+            "Implausible undo size {} at {}:{}",
+            size,
+            location.file_name(),
+            location.undo_pos
+        );
+
+        // Data followed by its checksum
+        let mut record = vec![0u8; size + 32];
+        file.read_exact(&mut record)?;
+        if let Some(key) = self.xor_key {
+            key.apply(&mut record, u64::from(location.undo_pos));
+        }
+        let (data, checksum) = record.split_at(size);
+        let expected: [u8; 32] = Sha256::digest(
+            Sha256::new()
+                .chain_update(location.prev_hash)
+                .chain_update(data)
+                .finalize(),
+        )
+        .into();
+        anyhow::ensure!(
+            checksum == expected,
+            "Undo checksum mismatch at {}:{}",
+            location.file_name(),
+            location.undo_pos
+        );
+        BlockUndo::parse(data)
+    }
+}
+
+/// One `TxInUndoFormatter` entry: height/coinbase code, legacy version, compressed `TxOut`.
+fn read_spent_output(data: &[u8], pos: &mut usize) -> Result<SpentOutput> {
+    let code = read_varint(data, pos)?;
+    let height = u32::try_from(code >> 1).context("undo height overflows u32")?;
+    if height > 0 {
+        // Always 0; kept by Core for compatibility with the old undo format
+        let _version = read_varint(data, pos)?;
+    }
+    let value =
+        decompress_amount(read_varint(data, pos)?).context("compressed amount overflows")?;
+    let script_pubkey = read_compressed_script(data, pos)?;
+    Ok(SpentOutput {
+        value,
+        script_pubkey,
+        height,
+        is_coinbase: code & 1 == 1,
+    })
+}
+
+/// `DecompressAmount` (`compressor.cpp`); `None` when a corrupt value overflows `u64`.
+pub(crate) fn decompress_amount(mut x: u64) -> Option<u64> {
+    if x == 0 {
+        return Some(0);
+    }
+    x -= 1;
+    let e = x % 10;
+    x /= 10;
+    let n = if e < 9 {
+        let d = x % 9 + 1;
+        x /= 9;
+        x.checked_mul(10)?.checked_add(d)?
+    } else {
+        x.checked_add(1)?
+    };
+    n.checked_mul(10u64.pow(e as u32))
+}
+
+/// `ScriptCompression`: special templates 0-5, otherwise the raw script (size + 6).
+fn read_compressed_script(data: &[u8], pos: &mut usize) -> Result<Vec<u8>> {
+    let kind = read_varint(data, pos)?;
+    let len = usize::try_from(compressed_script_len(kind)).context("script length overflows")?;
+    let payload = pos
+        .checked_add(len)
+        .and_then(|end| data.get(*pos..end))
+        .context("compressed script runs past the record")?;
+    *pos += len;
+    decompress_script(kind, payload)
+}
+
+/// Payload bytes that follow a `ScriptCompression` kind.
+pub(crate) fn compressed_script_len(kind: u64) -> u64 {
+    match kind {
+        0 | 1 => 20,
+        2..=5 => 32,
+        n => n - 6,
+    }
+}
+
+/// Expand a `ScriptCompression` payload of [`compressed_script_len`] bytes.
+pub(crate) fn decompress_script(kind: u64, payload: &[u8]) -> Result<Vec<u8>> {
+    let script = match kind {
+        // P2PKH
+        0 => [&[0x76, 0xa9, 20][..], payload, &[0x88, 0xac]].concat(),
+        // P2SH
+        1 => [&[0xa9, 20][..], payload, &[0x87]].concat(),
+        // P2PK, compressed key
+        2 | 3 => [&[33, kind as u8][..], payload, &[0xac]].concat(),
+        // P2PK, uncompressed key stored as its x coordinate
+        4 | 5 => {
+            let mut compressed = [0u8; 33];
+            compressed[0] = kind as u8 - 2;
+            compressed[1..].copy_from_slice(payload);
+            let key = secp256k1::PublicKey::from_slice(&compressed)
+                .context("compressed P2PK key is not on the curve")?;
+            [&[65][..], &key.serialize_uncompressed(), &[0xac]].concat()
+        }
+        // OP_RETURN, as Core substitutes for oversized scripts
+        _ if payload.len() as u64 > MAX_SCRIPT_SIZE => vec![0x6a],
+        _ => payload.to_vec(),
+    };
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_decompress_like_core() {
+        // CompressAmount examples from Core's compress_tests
+        for (compressed, amount) in [
+            (0u64, 0u64),
+            (0x1, 1),
+            (0x7, 1_000_000),
+            (0x9, 100_000_000),
+            (0x32, 5_000_000_000),
+            (0x1406f40, 21_000_000 * 100_000_000),
+        ] {
+            assert_eq!(
+                decompress_amount(compressed),
+                Some(amount),
+                "{compressed:#x}"
+            );
+        }
+        assert_eq!(decompress_amount(u64::MAX), None);
+        assert_eq!(decompress_amount(u64::MAX - 1), None);
+    }
+
+    #[test]
+    fn parses_block_undo_with_templates_and_raw_script() {
+        // Two non-coinbase txs
+        let mut data = vec![2u8];
+        // tx 1: one coinbase output from height 1 (50 BTC), P2PKH
+        data.extend([1, 0x03, 0x00, 0x32, 0x00]);
+        data.extend([0xab; 20]);
+        // tx 2: two inputs: P2SH at height 300 (1 sat), raw 3-byte script at height 0
+        data.push(2);
+        data.extend([0x83, 0x58, 0x00, 0x01, 0x01]); // VARINT(600) = 0x83 0x58
+        data.extend([0xcd; 20]);
+        data.extend([0x00, 0x07, 0x09, 0x51, 0x52, 0x53]);
+
+        let undo = BlockUndo::parse(&data).unwrap();
+        assert_eq!(undo.spent_count(), 3);
+        let cb = undo.prevout(1, 0).unwrap();
+        assert_eq!(
+            (cb.height, cb.is_coinbase, cb.value),
+            (1, true, 5_000_000_000)
+        );
+        assert_eq!(cb.script_pubkey[..3], [0x76, 0xa9, 20]);
+        let p2sh = undo.prevout(2, 0).unwrap();
+        assert_eq!((p2sh.height, p2sh.is_coinbase, p2sh.value), (300, false, 1));
+        assert_eq!(p2sh.script_pubkey.len(), 23);
+        let raw = undo.prevout(2, 1).unwrap();
+        assert_eq!(raw.value, 1_000_000);
+        assert_eq!(raw.script_pubkey, [0x51, 0x52, 0x53]);
+        assert!(undo.prevout(0, 0).is_none());
+        assert!(BlockUndo::parse(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! Content-derived run identifiers
+//!
+//! A run is named by what it does and what it reads: `RunId` hashes the run kind, its config
+//! parameters and fingerprints of its inputs. Invoking the same run again therefore lands in the
+//! same `<runs root>/<run id>/` directory and resumes from the artifacts and stages recorded
+//! there, instead of overwriting or duplicating them. A different config or changed input gives
+//! a different ID and a fresh directory.
+//!
+//! Each run directory holds `run.json` (spec, status, completed stages) and a `run.lock` held
+//! while a process is using it. Artifacts are written via a temp file and rename, so a restart
+//! never sees a half-written one.
+//!
+//! The runs root is `BLVM_RUNS_DIR`, else `results/` in the output directory
+//! ([`crate::artifacts`]), else `results/runs` (see [`crate::utils::results_dir`]).
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE: &str = "run.json";
+const LOCK_FILE: &str = "run.lock";
+
+/// Root directory for run directories: `BLVM_RUNS_DIR`, else the output directory's `results/`
+/// ([`crate::artifacts`]), else `results/runs`.
+pub fn default_runs_root() -> PathBuf {
+    std::env::var_os("BLVM_RUNS_DIR")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            crate::artifacts::layout_from_env()
+                .map(|layout| layout.kind_dir(crate::artifacts::ArtifactKind::Results))
+        })
+        .unwrap_or_else(|| crate::utils::results_dir().join("runs"))
+}
+
+/// What a run does and reads; everything that goes into its [`RunId`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSpec {
+    pub kind: String,
+    pub params: BTreeMap<String, String>,
+    /// Input label -> fingerprint
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl RunSpec {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            params: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    /// A config value that changes the run's results.
+    pub fn param(mut self, key: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.params.insert(key.into(), value.to_string());
+        self
+    }
+
+    /// An input with a caller-computed fingerprint (e.g. a tip hash).
+    pub fn input(mut self, label: impl Into<String>, fingerprint: impl Into<String>) -> Self {
+        self.inputs.insert(label.into(), fingerprint.into());
+        self
+    }
+
+    /// An input small enough to hash in full.
+    pub fn input_bytes(self, label: impl Into<String>, bytes: &[u8]) -> Self {
+        self.input(label, hex::encode(Sha256::digest(bytes)))
+    }
+
+    /// A file input fingerprinted by size and modification time (block files and chunks are far
+    /// too large to hash on every invocation). A missing file is fingerprinted as such.
+    pub fn input_file(self, label: impl Into<String>, path: &Path) -> Self {
+        let fingerprint = match std::fs::metadata(path) {
+            Ok(meta) => {
+                let mtime = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs());
+                format!("{}:{}:{}", path.display(), meta.len(), mtime)
+            }
+            Err(_) => format!("{}:missing", path.display()),
+        };
+        self.input(label, fingerprint)
+    }
+
+    pub fn id(&self) -> RunId {
+        // BTreeMaps serialize in key order, so the JSON is canonical.
+        let canonical = serde_json::to_vec(self).expect("RunSpec serializes");
+        let digest = Sha256::digest(&canonical);
+        let kind: String = self
+            .kind
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        RunId(format!("{}-{}", kind, hex::encode(&digest[..8])))
+    }
+}
+
+/// `<kind>-<16 hex digits>`, usable as a directory name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RunId(String);
+
+impl RunId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunStatus {
+    Running,
+    Complete,
+    Failed,
+}
+
+/// Contents of `run.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub id: RunId,
+    pub spec: RunSpec,
+    pub status: RunStatus,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Times the run has been opened (1 on the first invocation)
+    pub invocations: u32,
+    pub completed_stages: BTreeSet<String>,
+}
+
+/// `run.lock` with the holder's PID; removed on drop.
+#[derive(Debug)]
+struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(LOCK_FILE);
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut f) => {
+                    use std::io::Write;
+                    write!(f, "{}", std::process::id())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let holder = std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|s| s.trim().parse::<u32>().ok());
+                    let stale = holder
+                        .map(|pid| crate::platform::process_alive(pid) == Some(false))
+                        .unwrap_or(true);
+                    if !stale {
+                        anyhow::bail!(
+                            "Run directory {} is in use by PID {} (delete {} if that process is gone)",
+                            dir.display(),
+                            holder.unwrap_or(0),
+                            path.display()
+                        );
+                    }
+                    println!("   🔓 Removing stale run lock {}", path.display());
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to create {}", path.display()))
+                }
+            }
+        }
+        anyhow::bail!("Could not acquire {}", path.display())
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// An open run directory. Holds the run lock until dropped.
+#[derive(Debug)]
+pub struct RunDir {
+    dir: PathBuf,
+    manifest: RunManifest,
+    _lock: RunLock,
+}
+
+impl RunDir {
+    /// Open (or create) the directory for `spec` under `root`.
+    pub fn open(root: &Path, spec: RunSpec) -> Result<Self> {
+        let id = spec.id();
+        let dir = root.join(id.as_str());
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create run directory {}", dir.display()))?;
+        let lock = RunLock::acquire(&dir)?;
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            let mut m: RunManifest = serde_json::from_slice(
+                &std::fs::read(&manifest_path)
+                    .with_context(|| format!("Failed to read {}", manifest_path.display()))?,
+            )
+            .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+            anyhow::ensure!(
+                m.spec == spec,
+                "Run ID collision in {}: stored spec differs from this run",
+                dir.display()
+            );
+            m.invocations += 1;
+            m.updated_at = now;
+            if m.status == RunStatus::Failed {
+                m.status = RunStatus::Running;
+            }
+            m
+        } else {
+            RunManifest {
+                id: id.clone(),
+                spec,
+                status: RunStatus::Running,
+                created_at: now.clone(),
+                updated_at: now,
+                invocations: 1,
+                completed_stages: BTreeSet::new(),
+            }
+        };
+
+        let run = Self {
+            dir,
+            manifest,
+            _lock: lock,
+        };
+        run.save_manifest()?;
+        if run.is_resumed() {
+            println!(
+                "♻️  Resuming run {} ({} stage(s) done, status {:?})",
+                run.id(),
+                run.manifest.completed_stages.len(),
+                run.manifest.status
+            );
+        } else {
+            println!("🆔 Run {} -> {}", run.id(), run.dir.display());
+        }
+        Ok(run)
+    }
+
+    pub fn id(&self) -> &RunId {
+        &self.manifest.id
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn manifest(&self) -> &RunManifest {
+        &self.manifest
+    }
+
+    /// Opened before, by this or an earlier invocation.
+    pub fn is_resumed(&self) -> bool {
+        self.manifest.invocations > 1
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.manifest.status == RunStatus::Complete
+    }
+
+    /// Path of artifact `name` inside the run directory.
+    pub fn artifact_path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    pub fn has_artifact(&self, name: &str) -> bool {
+        self.artifact_path(name).exists()
+    }
+
+    /// Write an artifact atomically (temp file + rename).
+    pub fn write_artifact(&self, name: &str, bytes: &[u8]) -> Result<PathBuf> {
+        let path = self.artifact_path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_file_name(format!("{}.tmp", name.replace('/', "_")));
+        std::fs::write(&tmp, bytes)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {}", tmp.display()))?;
+        Ok(path)
+    }
+
+    pub fn write_json<T: Serialize>(&self, name: &str, value: &T) -> Result<PathBuf> {
+        self.write_artifact(name, &serde_json::to_vec_pretty(value)?)
+    }
+
+    /// Read a JSON artifact, `None` if it has not been written.
+    pub fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        let path = self.artifact_path(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let value = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(value))
+    }
+
+    pub fn stage_done(&self, stage: &str) -> bool {
+        self.manifest.completed_stages.contains(stage)
+    }
+
+    /// Record `stage` as done; a resumed run skips it.
+    pub fn mark_stage_done(&mut self, stage: &str) -> Result<()> {
+        if self.manifest.completed_stages.insert(stage.to_string()) {
+            self.save_manifest()?;
+        }
+        Ok(())
+    }
+
+    pub fn mark_complete(&mut self) -> Result<()> {
+        self.set_status(RunStatus::Complete)
+    }
+
+    pub fn mark_failed(&mut self) -> Result<()> {
+        self.set_status(RunStatus::Failed)
+    }
+
+    fn set_status(&mut self, status: RunStatus) -> Result<()> {
+        self.manifest.status = status;
+        self.save_manifest()
+    }
+
+    fn save_manifest(&self) -> Result<()> {
+        self.write_json(MANIFEST_FILE, &self.manifest).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_depends_on_content_not_insertion_order() {
+        let a = RunSpec::new("validate range")
+            .param("start", 0)
+            .param("end", 1000)
+            .input("tip", "abc");
+        let b = RunSpec::new("validate range")
+            .input("tip", "abc")
+            .param("end", 1000)
+            .param("start", 0);
+        assert_eq!(a.id(), b.id());
+        assert!(a.id().as_str().starts_with("validate-range-"));
+        assert_ne!(a.id(), a.clone().param("end", 1001).id());
+        assert_ne!(a.id(), a.clone().input("tip", "abd").id());
+    }
+
+    #[test]
+    fn reopening_resumes_the_same_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let spec = RunSpec::new("t").param("n", 1);
+        {
+            let mut run = RunDir::open(root.path(), spec.clone()).unwrap();
+            assert!(!run.is_resumed());
+            run.write_json("out/a.json", &vec![1, 2, 3]).unwrap();
+            run.mark_stage_done("a").unwrap();
+            // A second opener is refused while the first holds the lock.
+            assert!(RunDir::open(root.path(), spec.clone()).is_err());
+        }
+        let run = RunDir::open(root.path(), spec).unwrap();
+        assert!(run.is_resumed());
+        assert!(run.stage_done("a"));
+        assert_eq!(
+            run.read_json::<Vec<u32>>("out/a.json").unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 1);
+    }
+}
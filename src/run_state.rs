@@ -0,0 +1,301 @@
+//! Per-chunk run state for resuming parallel differential runs
+//!
+//! [`RunStateStore`] keeps `run_state.json` in a state directory, listing every chunk that
+//! finished and whether it matched or diverged, with the full [`ChunkResult`] of each in
+//! `chunks/<start>-<end>.json`. Both are rewritten (temp file + rename) as each chunk completes,
+//! so a run killed after days only loses the chunks that were in flight.
+//! [`run_parallel_differential_with_cancel`](crate::parallel_differential::run_parallel_differential_with_cancel)
+//! skips chunks recorded here and returns their saved results.
+//!
+//! Finished chunks count by the heights they cover, not by their exact range: a restart with a
+//! different range, chunk size or worker count (which cut different chunks, see
+//! [`crate::chunk_schedule`]) reuses every recorded chunk inside its range and only validates
+//! the heights none of them covers ([`uncovered`]).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::parallel_differential::ChunkResult;
+
+pub const RUN_STATE_FILE: &str = "run_state.json";
+/// Chunk results, one `<start>-<end>.json` each
+pub const CHUNKS_DIR: &str = "chunks";
+/// Bump when the state layout changes; older state is then ignored, not misread.
+const STATE_VERSION: u32 = 1;
+
+/// State directory from `BLVM_RUN_STATE_DIR`, if set.
+pub fn run_state_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("BLVM_RUN_STATE_DIR").map(PathBuf::from)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChunkStatus {
+    Matched,
+    Diverged,
+}
+
+/// One finished chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkState {
+    pub start_height: u64,
+    pub end_height: u64,
+    pub status: ChunkStatus,
+    pub tested: usize,
+    pub divergences: usize,
+    pub duration_secs: f64,
+}
+
+/// Contents of `run_state.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunState {
+    pub version: u32,
+    /// Finished chunks, by start height
+    pub chunks: Vec<ChunkState>,
+}
+
+impl Default for RunState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            chunks: Vec::new(),
+        }
+    }
+}
+
+/// Run state on disk, shared by the chunk workers of one run.
+pub struct RunStateStore {
+    dir: PathBuf,
+    state: Mutex<RunState>,
+}
+
+impl RunStateStore {
+    /// Open (or start) the state in `dir`.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir.join(CHUNKS_DIR))
+            .with_context(|| format!("Failed to create run state dir {}", dir.display()))?;
+        let path = dir.join(RUN_STATE_FILE);
+        let state = match std::fs::read(&path) {
+            Ok(data) => match serde_json::from_slice::<RunState>(&data) {
+                Ok(state) if state.version == STATE_VERSION => state,
+                Ok(state) => {
+                    eprintln!(
+                        "⚠️  {} has state version {} (expected {}) - starting over",
+                        path.display(),
+                        state.version,
+                        STATE_VERSION
+                    );
+                    RunState::default()
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Unreadable {}: {} - starting over", path.display(), e);
+                    RunState::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RunState::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn state(&self) -> RunState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn chunk_path(&self, start_height: u64, end_height: u64) -> PathBuf {
+        self.dir
+            .join(CHUNKS_DIR)
+            .join(format!("{}-{}.json", start_height, end_height))
+    }
+
+    /// Saved result of the chunk `[start_height, end_height]`, if it finished in an earlier
+    /// invocation. A recorded chunk whose result file is gone counts as not finished.
+    pub fn completed(&self, start_height: u64, end_height: u64) -> Option<ChunkResult> {
+        let recorded = self
+            .state
+            .lock()
+            .unwrap()
+            .chunks
+            .iter()
+            .any(|c| c.start_height == start_height && c.end_height == end_height);
+        if !recorded {
+            return None;
+        }
+        let path = self.chunk_path(start_height, end_height);
+        match std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(serde_json::from_slice(&data)?))
+        {
+            Ok(result) => Some(result),
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Chunk {}-{} recorded as finished but {} is unusable ({}) - re-running it",
+                    start_height,
+                    end_height,
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Saved results of recorded chunks inside `[start_height, end_height]`, in height order and
+    /// without overlaps (the earliest-starting chunk wins); unusable ones are left out.
+    pub fn completed_within(&self, start_height: u64, end_height: u64) -> Vec<ChunkResult> {
+        let ranges: Vec<(u64, u64)> = self
+            .state
+            .lock()
+            .unwrap()
+            .chunks
+            .iter()
+            .filter(|c| c.start_height >= start_height && c.end_height <= end_height)
+            .map(|c| (c.start_height, c.end_height))
+            .collect();
+        let mut results: Vec<ChunkResult> = Vec::new();
+        for (start, end) in ranges {
+            if results.last().is_some_and(|last| start <= last.end_height) {
+                continue;
+            }
+            results.extend(self.completed(start, end));
+        }
+        results
+    }
+
+    /// Save `result` and mark its chunk finished.
+    pub fn record(&self, result: &ChunkResult) -> Result<()> {
+        let stripped;
+        let result = if crate::determinism::enabled() {
+            stripped = result.clone().without_timings();
+            &stripped
+        } else {
+            result
+        };
+        let path = self.chunk_path(result.start_height, result.end_height);
+        write_atomic(&path, &serde_json::to_vec(result)?)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.chunks.retain(|c| {
+            !(c.start_height == result.start_height && c.end_height == result.end_height)
+        });
+        state.chunks.push(ChunkState {
+            start_height: result.start_height,
+            end_height: result.end_height,
+            status: if result.divergences.is_empty() {
+                ChunkStatus::Matched
+            } else {
+                ChunkStatus::Diverged
+            },
+            tested: result.tested,
+            divergences: result.divergences.len(),
+            duration_secs: result.duration_secs,
+        });
+        state.chunks.sort_by_key(|c| c.start_height);
+        // Written under the lock so concurrent chunks cannot interleave older state
+        write_atomic(
+            &self.dir.join(RUN_STATE_FILE),
+            &serde_json::to_vec_pretty(&*state)?,
+        )
+    }
+}
+
+/// Parts of `[start_height, end_height]` that none of `finished` (in height order, without
+/// overlaps) covers.
+pub fn uncovered(start_height: u64, end_height: u64, finished: &[ChunkResult]) -> Vec<(u64, u64)> {
+    let mut gaps = Vec::new();
+    let mut next = start_height;
+    for result in finished {
+        if result.start_height > next {
+            gaps.push((next, result.start_height - 1));
+        }
+        next = next.max(result.end_height + 1);
+    }
+    if next <= end_height {
+        gaps.push((next, end_height));
+    }
+    gaps
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to rename {} into place", tmp.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start_height: u64, end_height: u64, diverged: bool) -> ChunkResult {
+        ChunkResult {
+            start_height,
+            end_height,
+            tested: (end_height - start_height + 1) as usize,
+            matched: (end_height - start_height + 1) as usize - diverged as usize,
+            divergences: if diverged {
+                vec![(start_height, "Invalid(x)".into(), "Valid".into())]
+            } else {
+                Vec::new()
+            },
+            duration_secs: 1.0,
+            coin_age: Vec::new(),
+            checkpoint: None,
+        }
+    }
+
+    #[test]
+    fn finished_chunks_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStateStore::open(dir.path()).unwrap();
+        store.record(&chunk(100, 199, true)).unwrap();
+        store.record(&chunk(0, 99, false)).unwrap();
+
+        let reopened = RunStateStore::open(dir.path()).unwrap();
+        let state = reopened.state();
+        assert_eq!(
+            state
+                .chunks
+                .iter()
+                .map(|c| (c.start_height, c.status))
+                .collect::<Vec<_>>(),
+            [(0, ChunkStatus::Matched), (100, ChunkStatus::Diverged)]
+        );
+        assert_eq!(reopened.completed(100, 199).unwrap().divergences.len(), 1);
+        // Different chunking does not reuse results
+        assert!(reopened.completed(100, 149).is_none());
+
+        // A recorded chunk without its result file is run again
+        std::fs::remove_file(dir.path().join(CHUNKS_DIR).join("0-99.json")).unwrap();
+        assert!(reopened.completed(0, 99).is_none());
+    }
+
+    #[test]
+    fn resume_by_covered_heights() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStateStore::open(dir.path()).unwrap();
+        // Cut by an earlier run with another chunking; 150-249 overlaps 100-199
+        for (start, end) in [(0, 99), (100, 199), (150, 249), (400, 499), (900, 1099)] {
+            store.record(&chunk(start, end, false)).unwrap();
+        }
+        let finished = store.completed_within(0, 999);
+        assert_eq!(
+            finished
+                .iter()
+                .map(|r| (r.start_height, r.end_height))
+                .collect::<Vec<_>>(),
+            [(0, 99), (100, 199), (400, 499)]
+        );
+        assert_eq!(uncovered(0, 999, &finished), [(200, 399), (500, 999)]);
+        assert!(uncovered(0, 99, &finished[..1]).is_empty());
+        assert_eq!(uncovered(50, 60, &[]), [(50, 60)]);
+    }
+}
@@ -0,0 +1,459 @@
+//! End-of-run summary
+//!
+//! A [`RunSummary`] gathers what a collection or validation run did — blocks read, skipped,
+//! cached and validated, divergences by severity, throughput per stage and per chain era, peak
+//! memory, disk used and where the artifacts are — into one struct. [`collect_blocks`](crate::collect_only::collect_blocks)
+//! and [`validate_range`](crate::collect_only::validate_range) print it as the last thing a run
+//! outputs and save it as JSON (`summary.json` in the run directory / chunk cache).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::alloc_profile::MemoryProfile;
+use crate::chain_scan::{INSCRIPTIONS_START_HEIGHT, SEGWIT_START_HEIGHT, TAPROOT_START_HEIGHT};
+use crate::collect_only::{CollectionReport, ValidationReport};
+use crate::parallel_differential::ChunkResult;
+use crate::sanity::SanityStage;
+
+/// Divergences listed individually in [`RunSummary::render`].
+const SHOWN_DIVERGENCES: usize = 10;
+
+/// Throughput bands: era name and first height (mainnet), same eras as
+/// [`block_era`](crate::chain_scan::block_era).
+const ERAS: [(&str, u64); 4] = [
+    ("pre_segwit", 0),
+    ("segwit", SEGWIT_START_HEIGHT),
+    ("taproot", TAPROOT_START_HEIGHT),
+    ("inscriptions", INSCRIPTIONS_START_HEIGHT),
+];
+
+/// How serious a BLVM / Core disagreement is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DivergenceSeverity {
+    /// BLVM accepted a block Core rejected (BLVM would follow an invalid chain).
+    Critical,
+    /// BLVM rejected a block Core accepted.
+    High,
+    /// BLVM rejected because its inputs were incomplete (missing prevout / UTXO), which points
+    /// at the harness (checkpoint, cache) rather than at consensus code.
+    Low,
+}
+
+impl DivergenceSeverity {
+    /// Classify a `ChunkResult::divergences` entry (`Valid` / `Invalid(<reason>)` strings).
+    pub fn classify(blvm: &str, core: &str) -> Self {
+        if blvm == "Valid" && core != "Valid" {
+            return DivergenceSeverity::Critical;
+        }
+        let reason = blvm.to_ascii_lowercase();
+        if ["prevout", "not found", "missing"]
+            .iter()
+            .any(|s| reason.contains(s))
+        {
+            DivergenceSeverity::Low
+        } else {
+            DivergenceSeverity::High
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DivergenceSeverity::Critical => "critical",
+            DivergenceSeverity::High => "high",
+            DivergenceSeverity::Low => "low",
+        }
+    }
+}
+
+/// Block counts across all stages of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTotals {
+    /// Blocks read off `blk*.dat`, the temp file or the chunk cache
+    pub read: u64,
+    /// Blocks dropped by the sanity filters (any stage)
+    pub skipped: u64,
+    /// Blocks covered by the chunk cache
+    pub cached: u64,
+    /// Blocks validated by both BLVM and Core
+    pub validated: u64,
+    pub matched: u64,
+}
+
+/// Divergence counts per [`DivergenceSeverity`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceTotals {
+    pub critical: u64,
+    pub high: u64,
+    pub low: u64,
+}
+
+impl DivergenceTotals {
+    pub fn total(&self) -> u64 {
+        self.critical + self.high + self.low
+    }
+
+    pub(crate) fn add(&mut self, severity: DivergenceSeverity) {
+        match severity {
+            DivergenceSeverity::Critical => self.critical += 1,
+            DivergenceSeverity::High => self.high += 1,
+            DivergenceSeverity::Low => self.low += 1,
+        }
+    }
+}
+
+/// One divergence, for the summary's short list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceEntry {
+    pub height: u64,
+    pub severity: DivergenceSeverity,
+    pub blvm: String,
+    pub core: String,
+}
+
+/// Work done by one pipeline stage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageStats {
+    pub name: String,
+    /// Blocks processed by the stage
+    pub blocks: u64,
+    pub duration_secs: f64,
+}
+
+impl StageStats {
+    pub fn blocks_per_sec(&self) -> f64 {
+        if self.duration_secs > 0.0 {
+            self.blocks as f64 / self.duration_secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A file or directory the run left behind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactRef {
+    pub label: String,
+    pub path: PathBuf,
+}
+
+/// Consolidated outcome of a run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub blocks: BlockTotals,
+    pub divergences: DivergenceTotals,
+    /// Most severe divergences first, lowest height within a severity (capped)
+    pub divergence_samples: Vec<DivergenceEntry>,
+    pub stages: Vec<StageStats>,
+    /// Validation throughput per chain era (only eras the run touched)
+    #[serde(default)]
+    pub eras: Vec<StageStats>,
+    /// Peak RSS of this process (`None` where the platform does not report it)
+    pub peak_memory_bytes: Option<u64>,
+    /// Allocations and RSS / heap samples over the run (`alloc-profiling` builds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryProfile>,
+    /// Size of the artifact paths on disk
+    pub disk_used_bytes: u64,
+    pub artifacts: Vec<ArtifactRef>,
+    /// The run stopped early (cancelled or incomplete collection)
+    pub incomplete: bool,
+}
+
+impl RunSummary {
+    /// Summary of a [`collect_blocks`](crate::collect_only::collect_blocks) run.
+    pub fn for_collection(report: &CollectionReport) -> Self {
+        let mut summary = Self::default();
+        summary.blocks.cached = report.chunked_blocks;
+        summary.add_stage("collect", report.blocks_collected, report.duration_secs);
+        summary.add_artifact("temp file", &report.temp_file);
+        if let Some(dir) = &report.chunks_dir {
+            summary.add_artifact("chunk cache", dir);
+        }
+        summary.incomplete = !report.is_complete();
+        summary.finish();
+        summary
+    }
+
+    /// Summary of a [`validate_range`](crate::collect_only::validate_range) run.
+    pub fn for_validation(report: &ValidationReport) -> Self {
+        let mut summary = Self::default();
+        summary.blocks.validated = report.tested as u64;
+        summary.blocks.matched = report.matched as u64;
+        summary.blocks.cached = crate::chunked_cache::get_chunks_dir()
+            .and_then(|dir| {
+                crate::chunked_cache::load_chunk_metadata(&dir)
+                    .ok()
+                    .flatten()
+            })
+            .map_or(0, |meta| meta.total_blocks);
+        summary.add_stage("validate", report.tested as u64, report.duration_secs);
+        summary.eras = era_throughput(&report.chunks);
+
+        let mut entries: Vec<DivergenceEntry> = report
+            .chunks
+            .iter()
+            .flat_map(|c| c.divergences.iter())
+            .map(|(height, blvm, core)| DivergenceEntry {
+                height: *height,
+                severity: DivergenceSeverity::classify(blvm, core),
+                blvm: blvm.clone(),
+                core: core.clone(),
+            })
+            .collect();
+        for entry in &entries {
+            summary.divergences.add(entry.severity);
+        }
+        entries.sort_by_key(|e| (e.severity, e.height));
+        entries.truncate(SHOWN_DIVERGENCES);
+        summary.divergence_samples = entries;
+
+        summary.incomplete = report.cancelled;
+        summary.finish();
+        summary
+    }
+
+    pub fn add_stage(&mut self, name: &str, blocks: u64, duration_secs: f64) {
+        self.stages.push(StageStats {
+            name: name.to_string(),
+            blocks,
+            duration_secs,
+        });
+    }
+
+    pub fn add_artifact(&mut self, label: &str, path: &Path) {
+        self.artifacts.push(ArtifactRef {
+            label: label.to_string(),
+            path: path.to_path_buf(),
+        });
+    }
+
+    /// Fill in the process-wide counters (sanity filters, peak memory) and disk usage of the
+    /// artifacts. Call again after adding artifacts.
+    pub fn finish(&mut self) {
+        let sanity = crate::sanity::stage_totals();
+        self.blocks.read = sanity
+            .iter()
+            .filter(|(stage, ..)| matches!(stage, SanityStage::Read | SanityStage::CacheLoad))
+            .map(|(_, checked, _)| checked)
+            .sum();
+        self.blocks.skipped = sanity.iter().map(|(.., rejected)| rejected).sum();
+        self.peak_memory_bytes = crate::platform::peak_rss_bytes();
+        let mut paths: Vec<&Path> = self.artifacts.iter().map(|a| a.path.as_path()).collect();
+        // Nested artifacts (a file inside a listed directory) are counted once
+        paths.sort();
+        paths.dedup_by(|b, a| b.starts_with(a));
+        self.disk_used_bytes = paths.into_iter().map(disk_usage).sum();
+    }
+
+    /// The summary without stage / era durations, memory or disk figures
+    /// ([deterministic mode](crate::determinism)).
+    pub fn without_timings(mut self) -> Self {
+        for stage in self.stages.iter_mut().chain(self.eras.iter_mut()) {
+            stage.duration_secs = 0.0;
+        }
+        self.peak_memory_bytes = None;
+        self.memory = None;
+        self.disk_used_bytes = 0;
+        self
+    }
+
+    /// Terminal rendering.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let b = &self.blocks;
+        let _ = writeln!(out, "📊 Run summary");
+        let _ = writeln!(
+            out,
+            "   Blocks:      {} read, {} skipped, {} cached, {} validated ({} matched)",
+            b.read, b.skipped, b.cached, b.validated, b.matched
+        );
+        let d = &self.divergences;
+        let _ = writeln!(
+            out,
+            "   Divergences: {} ({} critical, {} high, {} low)",
+            d.total(),
+            d.critical,
+            d.high,
+            d.low
+        );
+        for e in &self.divergence_samples {
+            let _ = writeln!(
+                out,
+                "      [{}] height {}: BLVM={}, Core={}",
+                e.severity.name(),
+                e.height,
+                e.blvm,
+                e.core
+            );
+        }
+        if d.total() > self.divergence_samples.len() as u64 {
+            let _ = writeln!(
+                out,
+                "      ... {} more",
+                d.total() - self.divergence_samples.len() as u64
+            );
+        }
+        for stage in &self.stages {
+            let _ = writeln!(
+                out,
+                "   Stage {:<9} {} blocks in {:.1}s ({:.1} blocks/sec)",
+                stage.name,
+                stage.blocks,
+                stage.duration_secs,
+                stage.blocks_per_sec()
+            );
+        }
+        for era in &self.eras {
+            let _ = writeln!(
+                out,
+                "   Era   {:<12} {} blocks in {:.1}s ({:.1} blocks/sec)",
+                era.name,
+                era.blocks,
+                era.duration_secs,
+                era.blocks_per_sec()
+            );
+        }
+        let memory = self
+            .peak_memory_bytes
+            .map_or_else(|| "n/a".to_string(), format_bytes);
+        let _ = writeln!(
+            out,
+            "   Peak memory: {}   Disk used: {}",
+            memory,
+            format_bytes(self.disk_used_bytes)
+        );
+        if let Some(memory) = &self.memory {
+            let _ = writeln!(out, "   Heap:        {}", memory.summary());
+        }
+        for artifact in &self.artifacts {
+            let _ = writeln!(out, "   📁 {}: {}", artifact.label, artifact.path.display());
+        }
+        if self.incomplete {
+            let _ = writeln!(out, "   ⚠️  Run incomplete - run again to continue");
+        }
+        out
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+
+    /// Write to `path` ([`write_json_atomic`](crate::utils::write_json_atomic)).
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        crate::utils::write_json_atomic(path, self)
+    }
+}
+
+/// Split chunk results into [`ERAS`] bands. Blocks and time of a chunk that straddles an era
+/// boundary are shared out in proportion to the heights on each side.
+pub fn era_throughput(chunks: &[ChunkResult]) -> Vec<StageStats> {
+    let mut bands = [(0.0f64, 0.0f64); ERAS.len()];
+    for chunk in chunks {
+        if chunk.end_height < chunk.start_height {
+            continue;
+        }
+        let span = (chunk.end_height - chunk.start_height + 1) as f64;
+        for (i, (_, era_start)) in ERAS.iter().enumerate() {
+            let era_end = ERAS.get(i + 1).map_or(u64::MAX, |(_, next)| next - 1);
+            let lo = chunk.start_height.max(*era_start);
+            let hi = chunk.end_height.min(era_end);
+            if lo > hi {
+                continue;
+            }
+            let share = (hi - lo + 1) as f64 / span;
+            bands[i].0 += chunk.tested as f64 * share;
+            bands[i].1 += chunk.duration_secs * share;
+        }
+    }
+    ERAS.iter()
+        .zip(bands)
+        .filter(|(_, (blocks, _))| *blocks > 0.0)
+        .map(|((name, _), (blocks, duration_secs))| StageStats {
+            name: name.to_string(),
+            blocks: blocks.round() as u64,
+            duration_secs,
+        })
+        .collect()
+}
+
+/// Bytes used by a file or directory tree (0 if missing).
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| disk_usage(&e.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.1} GB", bytes as f64 / 1e9)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1e6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divergence_severity() {
+        use DivergenceSeverity::*;
+        assert_eq!(
+            DivergenceSeverity::classify("Valid", "Invalid(bad-txns)"),
+            Critical
+        );
+        assert_eq!(
+            DivergenceSeverity::classify("Invalid(bad-sig)", "Valid"),
+            High
+        );
+        assert_eq!(
+            DivergenceSeverity::classify("Invalid(Prevout not found: ab:0)", "Valid"),
+            Low
+        );
+        assert!(Critical < High && High < Low);
+    }
+
+    #[test]
+    fn era_throughput_splits_straddling_chunks() {
+        let chunk = |start_height: u64, end_height: u64, duration_secs: f64| ChunkResult {
+            start_height,
+            end_height,
+            tested: (end_height - start_height + 1) as usize,
+            matched: (end_height - start_height + 1) as usize,
+            divergences: Vec::new(),
+            duration_secs,
+            coin_age: Vec::new(),
+            checkpoint: None,
+        };
+        let eras = era_throughput(&[
+            chunk(SEGWIT_START_HEIGHT - 1000, SEGWIT_START_HEIGHT - 1, 10.0),
+            // Half pre-SegWit, half SegWit
+            chunk(SEGWIT_START_HEIGHT - 500, SEGWIT_START_HEIGHT + 499, 20.0),
+            chunk(
+                INSCRIPTIONS_START_HEIGHT,
+                INSCRIPTIONS_START_HEIGHT + 99,
+                50.0,
+            ),
+        ]);
+        let names: Vec<&str> = eras.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["pre_segwit", "segwit", "inscriptions"]);
+        assert_eq!(eras[0].blocks, 1500);
+        assert!((eras[0].duration_secs - 20.0).abs() < 1e-9);
+        assert_eq!(eras[1].blocks, 500);
+        assert!((eras[1].blocks_per_sec() - 50.0).abs() < 1e-9);
+        assert!((eras[2].blocks_per_sec() - 2.0).abs() < 1e-9);
+    }
+}
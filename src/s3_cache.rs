@@ -0,0 +1,433 @@
+//! S3-compatible object storage for the chunk cache (`s3-cache` feature)
+//!
+//! Collecting a chainstate from `blk*.dat` takes days. One machine can instead push its chunk
+//! dir to a bucket with `blvm-bench s3 push`, and CI runners `blvm-bench s3 pull` the small
+//! metadata files (`chunks.meta`, `chunks.index`, `chunks.hashmap`, `chunks.manifest`, the
+//! metadata store). Chunk files are then fetched on first use:
+//! [`crate::chunk_manifest::chunk_path`] asks [`fetch_chunk`] for any chunk no storage tier holds.
+//!
+//! Fetched chunks live in a local cache dir that is kept under `BLVM_S3_CACHE_MAX_BYTES` by
+//! evicting the least recently used chunks (by mtime, which every hit refreshes).
+//!
+//! Configuration comes from the environment:
+//!
+//! - `BLVM_S3_BUCKET` (required; without it S3 is off) and `BLVM_S3_PREFIX` (key prefix).
+//! - The usual `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, plus `AWS_ENDPOINT`
+//!   and `AWS_ALLOW_HTTP=true` for MinIO, R2 and other S3-compatible servers.
+//! - `BLVM_S3_CACHE_DIR` (default `~/.cache/blvm-bench/s3-chunks`) and
+//!   `BLVM_S3_CACHE_MAX_BYTES` (default 200 GiB).
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload, WriteMultipart};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+use crate::storage_tiers::chunk_file_name;
+
+/// Files of a chunk dir besides the chunks themselves
+pub const METADATA_FILES: &[&str] = &[
+    "chunks.meta",
+    "chunks.index",
+    "chunks.hashmap",
+    crate::chunk_manifest::MANIFEST_FILE,
+    crate::meta_store::STORE_FILE,
+];
+
+/// Default local cache budget: 200 GiB
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 200 * 1024 * 1024 * 1024;
+
+/// Part size of multipart uploads
+const PART_SIZE: usize = 64 * 1024 * 1024;
+
+/// Where chunks are stored remotely and cached locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Config {
+    pub bucket: String,
+    /// Key prefix, without leading or trailing `/`
+    pub prefix: String,
+    pub cache_dir: PathBuf,
+    pub cache_max_bytes: u64,
+}
+
+impl S3Config {
+    /// `None` unless `BLVM_S3_BUCKET` is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let bucket = match std::env::var("BLVM_S3_BUCKET") {
+            Ok(bucket) if !bucket.is_empty() => bucket,
+            _ => return Ok(None),
+        };
+        let prefix = std::env::var("BLVM_S3_PREFIX")
+            .unwrap_or_default()
+            .trim_matches('/')
+            .to_string();
+        let cache_dir = match std::env::var_os("BLVM_S3_CACHE_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => dirs::cache_dir()
+                .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+                .context("No cache dir; set BLVM_S3_CACHE_DIR")?
+                .join("blvm-bench")
+                .join("s3-chunks"),
+        };
+        let cache_max_bytes = match std::env::var("BLVM_S3_CACHE_MAX_BYTES") {
+            Ok(raw) => raw
+                .parse()
+                .with_context(|| format!("BLVM_S3_CACHE_MAX_BYTES={} is not a number", raw))?,
+            Err(_) => DEFAULT_CACHE_MAX_BYTES,
+        };
+        Ok(Some(Self {
+            bucket,
+            prefix,
+            cache_dir,
+            cache_max_bytes,
+        }))
+    }
+}
+
+/// Counts of a [`S3ChunkStore::push`] or [`S3ChunkStore::pull`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    pub files: usize,
+    pub bytes: u64,
+    /// Already present with the same size
+    pub skipped: usize,
+}
+
+/// A bucket (and prefix) holding one chunk dir.
+pub struct S3ChunkStore {
+    store: Arc<dyn ObjectStore>,
+    config: S3Config,
+}
+
+impl S3ChunkStore {
+    /// Store for `config`, with credentials and endpoint from the `AWS_*` environment.
+    pub fn new(config: S3Config) -> Result<Self> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .build()
+            .with_context(|| format!("Failed to configure S3 bucket {}", config.bucket))?;
+        Ok(Self::with_store(Arc::new(store), config))
+    }
+
+    /// Store over any [`ObjectStore`] (tests use an in-memory one).
+    pub fn with_store(store: Arc<dyn ObjectStore>, config: S3Config) -> Self {
+        Self { store, config }
+    }
+
+    pub fn config(&self) -> &S3Config {
+        &self.config
+    }
+
+    fn key(&self, name: &str) -> ObjectPath {
+        if self.config.prefix.is_empty() {
+            ObjectPath::from(name)
+        } else {
+            ObjectPath::from(format!("{}/{}", self.config.prefix, name))
+        }
+    }
+
+    /// Size of the object `name`, if it exists.
+    async fn remote_size(&self, name: &str) -> Result<Option<u64>> {
+        match self.store.head(&self.key(name)).await {
+            Ok(meta) => Ok(Some(meta.size as u64)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to stat {}", self.key(name))),
+        }
+    }
+
+    async fn upload(&self, path: &Path, name: &str) -> Result<u64> {
+        let key = self.key(name);
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let upload = self
+            .store
+            .put_multipart(&key)
+            .await
+            .with_context(|| format!("Failed to start upload of {}", key))?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, PART_SIZE);
+        let mut buf = vec![0u8; 8 * 1024 * 1024];
+        let mut bytes = 0u64;
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            writer.wait_for_capacity(4).await?;
+            writer.write(&buf[..n]);
+            bytes += n as u64;
+        }
+        writer
+            .finish()
+            .await
+            .with_context(|| format!("Failed to upload {}", key))?;
+        Ok(bytes)
+    }
+
+    /// Download `name` to `dest` through a temp file, so readers never see half of it.
+    async fn download(&self, name: &str, dest: &Path) -> Result<u64> {
+        let key = self.key(name);
+        let dir = dest.parent().unwrap_or_else(|| Path::new("."));
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        let mut stream = self
+            .store
+            .get(&key)
+            .await
+            .with_context(|| format!("Failed to fetch {}", key))?
+            .into_stream();
+        let mut bytes = 0u64;
+        while let Some(data) = stream.next().await {
+            let data = data.with_context(|| format!("Failed to fetch {}", key))?;
+            temp.write_all(&data)?;
+            bytes += data.len() as u64;
+        }
+        temp.as_file().sync_all()?;
+        temp.persist(dest)
+            .with_context(|| format!("Failed to move {} into place", dest.display()))?;
+        Ok(bytes)
+    }
+
+    /// Upload every chunk and metadata file of `chunks_dir`. Objects that already exist with the
+    /// same size are skipped, so an interrupted push resumes; metadata is always uploaded, last.
+    pub async fn push(&self, chunks_dir: &Path) -> Result<TransferStats> {
+        let mut stats = TransferStats::default();
+        let mut chunks: Vec<(u64, PathBuf)> = std::fs::read_dir(chunks_dir)
+            .with_context(|| format!("Failed to list {}", chunks_dir.display()))?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let num = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("chunk_")?
+                    .strip_suffix(".bin.zst")?
+                    .parse()
+                    .ok()?;
+                Some((num, path))
+            })
+            .collect();
+        chunks.sort_unstable();
+        for (num, path) in chunks {
+            let name = chunk_file_name(num);
+            let size = std::fs::metadata(&path)?.len();
+            if self.remote_size(&name).await? == Some(size) {
+                stats.skipped += 1;
+                continue;
+            }
+            info!("☁️  Uploading {} ({} bytes)", name, size);
+            stats.bytes += self.upload(&path, &name).await?;
+            stats.files += 1;
+        }
+        for name in METADATA_FILES {
+            let path = chunks_dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+            let data = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            stats.bytes += data.len() as u64;
+            self.store
+                .put(&self.key(name), PutPayload::from(data))
+                .await
+                .with_context(|| format!("Failed to upload {}", name))?;
+            stats.files += 1;
+        }
+        Ok(stats)
+    }
+
+    /// Download the metadata files into `chunks_dir`; chunks are fetched on demand unless
+    /// `with_chunks`, which downloads them all into the cache dir (ignoring its size budget).
+    pub async fn pull(&self, chunks_dir: &Path, with_chunks: bool) -> Result<TransferStats> {
+        let mut stats = TransferStats::default();
+        for name in METADATA_FILES {
+            if self.remote_size(name).await?.is_none() {
+                continue;
+            }
+            stats.bytes += self.download(name, &chunks_dir.join(name)).await?;
+            stats.files += 1;
+        }
+        anyhow::ensure!(
+            stats.files > 0,
+            "No chunk metadata under s3://{}/{}",
+            self.config.bucket,
+            self.config.prefix
+        );
+        if with_chunks {
+            let mut listing = self.store.list(Some(&self.key("")));
+            let mut names = Vec::new();
+            while let Some(meta) = listing.next().await {
+                let meta = meta?;
+                if let Some(name) = meta.location.filename() {
+                    if name.starts_with("chunk_") && name.ends_with(".bin.zst") {
+                        names.push((name.to_string(), meta.size as u64));
+                    }
+                }
+            }
+            for (name, size) in names {
+                let dest = self.config.cache_dir.join(&name);
+                if std::fs::metadata(&dest).is_ok_and(|meta| meta.len() == size) {
+                    stats.skipped += 1;
+                    continue;
+                }
+                stats.bytes += self.download(&name, &dest).await?;
+                stats.files += 1;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Chunk `chunk_num` in the cache dir, downloading it on a miss and then evicting least
+    /// recently used chunks over the budget. `None` if the bucket does not have it either.
+    pub async fn fetch_chunk(&self, chunk_num: u64) -> Result<Option<PathBuf>> {
+        let name = chunk_file_name(chunk_num);
+        let path = self.config.cache_dir.join(&name);
+        if path.exists() {
+            touch(&path);
+            return Ok(Some(path));
+        }
+        if self.remote_size(&name).await?.is_none() {
+            return Ok(None);
+        }
+        info!("☁️  Fetching {} from s3://{}", name, self.config.bucket);
+        let bytes = self.download(&name, &path).await?;
+        info!("☁️  Fetched {} ({} bytes)", name, bytes);
+        evict_lru(&self.config.cache_dir, self.config.cache_max_bytes, &path)?;
+        Ok(Some(path))
+    }
+}
+
+/// Mark `path` as just used.
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// Delete the least recently modified chunks in `cache_dir` until the chunks total at most
+/// `max_bytes`, never deleting `keep`. Returns the number of bytes freed.
+pub fn evict_lru(cache_dir: &Path, max_bytes: u64, keep: &Path) -> Result<u64> {
+    let mut chunks: Vec<(SystemTime, u64, PathBuf)> = std::fs::read_dir(cache_dir)
+        .with_context(|| format!("Failed to list {}", cache_dir.display()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let name = name.to_str()?;
+            if !(name.starts_with("chunk_") && name.ends_with(".bin.zst")) {
+                return None;
+            }
+            let meta = entry.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect();
+    let mut total: u64 = chunks.iter().map(|(_, len, _)| len).sum();
+    chunks.sort();
+    let mut freed = 0;
+    for (_, len, path) in chunks {
+        if total <= max_bytes {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to evict {}", path.display()))?;
+        info!("☁️  Evicted {} from the S3 chunk cache", path.display());
+        total -= len;
+        freed += len;
+    }
+    Ok(freed)
+}
+
+/// The store from [`S3Config::from_env`], created once per process; `None` if S3 is off.
+pub fn from_env() -> Option<&'static S3ChunkStore> {
+    static STORE: OnceLock<Option<S3ChunkStore>> = OnceLock::new();
+    STORE
+        .get_or_init(|| {
+            match S3Config::from_env().and_then(|c| c.map(S3ChunkStore::new).transpose()) {
+                Ok(store) => store,
+                Err(e) => {
+                    warn!("⚠️  S3 chunk cache disabled: {:#}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// [`S3ChunkStore::fetch_chunk`] from synchronous code, for the configured store. Runs on its
+/// own thread and runtime, so it also works when called from inside a tokio runtime. Concurrent
+/// callers are serialized, so a chunk is downloaded once.
+pub fn fetch_chunk(chunk_num: u64) -> Result<Option<PathBuf>> {
+    static FETCHING: Mutex<()> = Mutex::new(());
+    let Some(store) = from_env() else {
+        return Ok(None);
+    };
+    let _guard = FETCHING.lock().unwrap_or_else(|e| e.into_inner());
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?
+                    .block_on(store.fetch_chunk(chunk_num))
+            })
+            .join()
+            .map_err(|_| anyhow::anyhow!("S3 fetch of chunk {} panicked", chunk_num))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    #[test]
+    fn pushes_pulls_and_evicts_chunks() {
+        let source = tempfile::tempdir().unwrap();
+        for num in 0..3u64 {
+            std::fs::write(
+                source.path().join(chunk_file_name(num)),
+                vec![num as u8; 100],
+            )
+            .unwrap();
+        }
+        std::fs::write(source.path().join("chunks.meta"), b"num_chunks=3\n").unwrap();
+
+        let runner = tempfile::tempdir().unwrap();
+        let config = S3Config {
+            bucket: "test".into(),
+            prefix: "mainnet".into(),
+            cache_dir: runner.path().join("cache"),
+            cache_max_bytes: 250,
+        };
+        let store = S3ChunkStore::with_store(Arc::new(InMemory::new()), config);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let pushed = store.push(source.path()).await.unwrap();
+            assert_eq!((pushed.files, pushed.skipped), (4, 0));
+            assert_eq!(store.push(source.path()).await.unwrap().skipped, 3);
+
+            let chunks_dir = runner.path().join("chunks");
+            assert_eq!(store.pull(&chunks_dir, false).await.unwrap().files, 1);
+            assert!(chunks_dir.join("chunks.meta").exists());
+
+            for num in 0..3 {
+                let path = store.fetch_chunk(num).await.unwrap().unwrap();
+                assert_eq!(std::fs::read(&path).unwrap(), vec![num as u8; 100]);
+            }
+            assert_eq!(store.fetch_chunk(7).await.unwrap(), None);
+        });
+        // 300 bytes fetched into a 250 byte budget: the oldest chunk went
+        let cache = runner.path().join("cache");
+        assert!(!cache.join(chunk_file_name(0)).exists());
+        assert!(cache.join(chunk_file_name(2)).exists());
+    }
+}
@@ -0,0 +1,606 @@
+//! Budget-aware sampling differential ("validate 1% of the chain well")
+//!
+//! Instead of a multi-day full run, [`run_sampled_differential`] validates a stratified random
+//! sample of blocks within a time budget and reports how confident the sample makes parity:
+//!
+//! - the range is cut into strata: consensus eras (P2SH, strict DER, segwit, taproot) crossed
+//!   with difficulty bands (retarget epochs grouped by difficulty quantile within the era, from
+//!   a [`header_sync`](crate::header_sync) cache; equal-height bands without one)
+//! - sampling runs in rounds of one random block per stratum, so whenever the budget runs out
+//!   every stratum has been sampled about equally
+//! - each block's UTXO context is rebuilt from the nearest stored checkpoint
+//!   ([`CheckpointStore`]) by replaying the blocks in between through BLVM only, then the block
+//!   itself runs through the full BLVM / Core differential
+//!
+//! The replay cost per sample is up to the checkpoint spacing, so a store written by a
+//! checkpointed parallel run (small `chunk_size`) makes for many more samples per hour. An
+//! empty store is refused rather than replaying every sample from genesis, and the budget and
+//! cancellation are checked between replayed blocks too.
+//!
+//! The report gives the stratum-weighted divergence rate and its one-sided 95% upper bound:
+//! with no divergences over `n` effective samples that is `1 - 0.05^(1/n)` (about `3/n`).
+
+use anyhow::Result;
+use blvm_protocol::types::{Network, UtxoSet};
+use futures::StreamExt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::block_file_reader::Network as BlockFileNetwork;
+use crate::block_source::BlockSource;
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::checkpoint_store::CheckpointStore;
+use crate::parallel_differential::{validate_chunk, BlockChunk};
+use crate::validation_hooks::HookRegistry;
+
+/// Mainnet consensus eras, by first height.
+pub const ERAS: &[(&str, u64)] = &[
+    ("pre-p2sh", 0),
+    ("p2sh", 173_805),
+    ("strict-der", 363_725),
+    ("segwit", 481_824),
+    ("taproot", 709_632),
+];
+
+/// Blocks per difficulty retarget epoch
+const EPOCH_LEN: u64 = 2016;
+/// One-sided confidence of the reported upper bound
+const CONFIDENCE: f64 = 0.95;
+/// z for [`CONFIDENCE`] (one-sided)
+const Z: f64 = 1.645;
+
+/// Inputs for [`run_sampled_differential`]
+#[derive(Clone)]
+pub struct SamplingConfig {
+    pub start_height: u64,
+    pub end_height: u64,
+    /// Wall time to spend; checked before every validated or replayed block
+    pub budget: Duration,
+    /// Difficulty bands per era
+    pub difficulty_bands: usize,
+    /// Same seed, same range and bands: same sample order
+    pub seed: u64,
+    /// Checkpoints the UTXO context is rebuilt from
+    pub checkpoint_dir: PathBuf,
+    /// Chain the checkpoints and blocks belong to
+    pub network: BlockFileNetwork,
+    /// Header cache from `header_sync` for difficulty bands (`None`: equal-height bands)
+    pub headers_cache: Option<PathBuf>,
+    pub hooks: HookRegistry,
+}
+
+impl SamplingConfig {
+    pub fn new(
+        start_height: u64,
+        end_height: u64,
+        budget: Duration,
+        checkpoint_dir: PathBuf,
+    ) -> Self {
+        Self {
+            start_height,
+            end_height,
+            budget,
+            difficulty_bands: 4,
+            seed: 0x5a3b_1e00,
+            checkpoint_dir,
+            network: BlockFileNetwork::Mainnet,
+            headers_cache: None,
+            hooks: HookRegistry::default(),
+        }
+    }
+}
+
+/// Heights sharing an era and a difficulty band.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stratum {
+    pub era: &'static str,
+    pub band: usize,
+    /// Inclusive, ascending, disjoint
+    pub ranges: Vec<(u64, u64)>,
+}
+
+impl Stratum {
+    pub fn population(&self) -> u64 {
+        self.ranges.iter().map(|(s, e)| e - s + 1).sum()
+    }
+
+    /// The `index`-th height of the stratum.
+    fn height_at(&self, mut index: u64) -> u64 {
+        for &(start, end) in &self.ranges {
+            let len = end - start + 1;
+            if index < len {
+                return start + index;
+            }
+            index -= len;
+        }
+        unreachable!("index beyond stratum population")
+    }
+}
+
+/// `-log2(target)` of compact `bits`: grows with difficulty.
+fn difficulty_key(bits: u32) -> f64 {
+    let exponent = (bits >> 24) as f64;
+    let mantissa = (bits & 0x007f_ffff).max(1) as f64;
+    -(mantissa.log2() + 8.0 * (exponent - 3.0))
+}
+
+/// Cut `[start_height, end_height]` into era x difficulty-band strata.
+///
+/// `bits` is indexed by height (a header cache); heights it does not cover fall back to their
+/// epoch's height as the band key, which gives equal-height bands.
+pub fn build_strata(
+    start_height: u64,
+    end_height: u64,
+    bands: usize,
+    bits: &[u32],
+) -> Vec<Stratum> {
+    let bands = bands.max(1);
+    let mut strata = Vec::new();
+    for (i, &(era, era_start)) in ERAS.iter().enumerate() {
+        let era_end = ERAS.get(i + 1).map_or(u64::MAX, |&(_, next)| next - 1);
+        let (from, to) = (start_height.max(era_start), end_height.min(era_end));
+        if from > to {
+            continue;
+        }
+        // Epochs (clipped to the era) ordered by difficulty, then split into equal-count bands
+        let mut epochs: Vec<(f64, u64, u64)> = Vec::new();
+        let mut epoch_start = from;
+        while epoch_start <= to {
+            let epoch_end = ((epoch_start / EPOCH_LEN + 1) * EPOCH_LEN - 1).min(to);
+            let key = bits
+                .get(epoch_start as usize)
+                .map_or(epoch_start as f64, |&b| difficulty_key(b));
+            epochs.push((key, epoch_start, epoch_end));
+            epoch_start = epoch_end + 1;
+        }
+        epochs.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let era_bands = bands.min(epochs.len());
+        let mut ranges = vec![Vec::new(); era_bands];
+        for (rank, &(_, s, e)) in epochs.iter().enumerate() {
+            ranges[rank * era_bands / epochs.len()].push((s, e));
+        }
+        for (band, mut ranges) in ranges.into_iter().enumerate() {
+            ranges.sort_unstable();
+            strata.push(Stratum { era, band, ranges });
+        }
+    }
+    strata
+}
+
+/// Samples and divergences in one stratum.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StratumReport {
+    pub era: String,
+    pub band: usize,
+    pub population: u64,
+    pub sampled: u64,
+    pub divergences: u64,
+    /// One-sided upper bound on the stratum's divergence rate
+    pub upper_bound: f64,
+}
+
+/// Outcome of [`run_sampled_differential`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingReport {
+    pub start_height: u64,
+    pub end_height: u64,
+    pub budget_secs: f64,
+    pub elapsed_secs: f64,
+    /// Complete rounds (one block from every stratum)
+    pub rounds: u64,
+    pub tested: u64,
+    pub divergences: Vec<(u64, String, String)>,
+    pub strata: Vec<StratumReport>,
+    /// Population-weighted divergence rate over the sampled strata
+    pub weighted_rate: f64,
+    /// Kish effective sample size of the stratified estimate
+    pub effective_samples: f64,
+    /// One-sided 95% upper bound on the chain-wide divergence rate
+    pub upper_bound: f64,
+    /// Share of the range's blocks in strata that got at least one sample
+    pub coverage: f64,
+}
+
+/// One-sided upper bound on a rate with `failures` in `n` trials: exact when there are none,
+/// Wilson score otherwise.
+pub fn upper_bound(failures: f64, n: f64) -> f64 {
+    if n <= 0.0 {
+        return 1.0;
+    }
+    if failures <= 0.0 {
+        return 1.0 - (1.0 - CONFIDENCE).powf(1.0 / n);
+    }
+    let p = failures / n;
+    let z2 = Z * Z;
+    let centre = p + z2 / (2.0 * n);
+    let spread = Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((centre + spread) / (1.0 + z2 / n)).min(1.0)
+}
+
+impl SamplingReport {
+    fn summarize(&mut self) {
+        let sampled: Vec<&StratumReport> = self.strata.iter().filter(|s| s.sampled > 0).collect();
+        let total: u64 = self.strata.iter().map(|s| s.population).sum();
+        let covered: u64 = sampled.iter().map(|s| s.population).sum();
+        self.coverage = if total > 0 {
+            covered as f64 / total as f64
+        } else {
+            0.0
+        };
+        if covered == 0 {
+            self.upper_bound = 1.0;
+            return;
+        }
+        let weight = |s: &StratumReport| s.population as f64 / covered as f64;
+        self.weighted_rate = sampled
+            .iter()
+            .map(|s| weight(s) * s.divergences as f64 / s.sampled as f64)
+            .sum();
+        self.effective_samples = 1.0
+            / sampled
+                .iter()
+                .map(|s| weight(s).powi(2) / s.sampled as f64)
+                .sum::<f64>();
+        self.upper_bound = upper_bound(
+            self.weighted_rate * self.effective_samples,
+            self.effective_samples,
+        );
+    }
+
+    pub fn print(&self) {
+        println!(
+            "\n📊 Sampled differential {}..={}",
+            self.start_height, self.end_height
+        );
+        println!(
+            "   {} blocks in {} round(s), {:.0}s of {:.0}s budget",
+            self.tested, self.rounds, self.elapsed_secs, self.budget_secs
+        );
+        for s in &self.strata {
+            println!(
+                "   {:<11} band {}  {:>4}/{:<7} sampled  {} divergence(s)  rate < {:.2}%",
+                s.era,
+                s.band,
+                s.sampled,
+                s.population,
+                s.divergences,
+                s.upper_bound * 100.0
+            );
+        }
+        if self.divergences.is_empty() {
+            println!(
+                "✅ No divergences: chain-wide divergence rate < {:.3}% at {:.0}% confidence \
+                 ({:.0} effective samples, {:.1}% of blocks in sampled strata)",
+                self.upper_bound * 100.0,
+                CONFIDENCE * 100.0,
+                self.effective_samples,
+                self.coverage * 100.0
+            );
+        } else {
+            println!(
+                "❌ {} divergence(s): weighted rate {:.3}%, < {:.3}% at {:.0}% confidence",
+                self.divergences.len(),
+                self.weighted_rate * 100.0,
+                self.upper_bound * 100.0,
+                CONFIDENCE * 100.0
+            );
+        }
+    }
+}
+
+/// Connect `[from, to]` onto `utxo_set` with BLVM only (no Core calls), under `network`'s rules.
+///
+/// Stops with a [`Cancelled`] error once `cancel` fires or `deadline` passes.
+pub(crate) async fn replay<S: BlockSource>(
+    block_source: &S,
+    mut utxo_set: UtxoSet,
+    from: u64,
+    to: u64,
+    network: Network,
+    cancel: &CancellationToken,
+    deadline: Option<Instant>,
+) -> Result<UtxoSet> {
+    use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+    use blvm_protocol::types::{BlockHeader, ValidationResult};
+
+    if from > to {
+        return Ok(utxo_set);
+    }
+    let mut blocks = block_source
+        .iter_sequential(from, to - from + 1, cancel)?
+        .enumerate();
+    while let Some((idx, bytes)) = blocks.next().await {
+        let height = from + idx as u64;
+        crate::cancel::check(cancel, || {
+            format!("UTXO context replay at block {}", height)
+        })?;
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(Cancelled(format!("time budget, replaying block {}", height)).into());
+        }
+        let bytes = bytes?;
+        let (block, witnesses) = deserialize_block_with_witnesses(&bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize block {}: {}", height, e))?;
+        let ctx = blvm_protocol::block::block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            block.header.timestamp,
+            network,
+        );
+        let (result, next) =
+            crate::consensus_compat::connect_block(&block, &witnesses, utxo_set, height, &ctx)?;
+        if let ValidationResult::Invalid(reason) = result {
+            anyhow::bail!(
+                "Block {} failed while rebuilding UTXO context: {}",
+                height,
+                reason
+            );
+        }
+        utxo_set = next;
+    }
+    // A cancelled block stream may just end early
+    crate::cancel::check(cancel, || format!("UTXO context replay to block {}", to))?;
+    Ok(utxo_set)
+}
+
+/// Validate a stratified random sample of `[start_height, end_height]` within `config.budget`.
+pub async fn run_sampled_differential<S: BlockSource + 'static>(
+    config: SamplingConfig,
+    block_source: Arc<S>,
+    cancel: CancellationToken,
+) -> Result<SamplingReport> {
+    let started = Instant::now();
+    let deadline = started + config.budget;
+    let store = CheckpointStore::new(&config.checkpoint_dir, config.network)?;
+    // Below the first checkpoint, replaying from genesis is no longer than the checkpoint spacing
+    let Some(&first_checkpoint) = store.heights()?.first() else {
+        anyhow::bail!(
+            "No checkpoints in {}: every sample would replay from genesis (write some with a \
+             checkpointed parallel run first)",
+            config.checkpoint_dir.display()
+        );
+    };
+    let bits = match &config.headers_cache {
+        Some(path) => crate::header_sync::cached_header_bits(path)?,
+        None => Vec::new(),
+    };
+    let end_height = match block_source.get_tip_height().await? {
+        Some(tip) => config.end_height.min(tip),
+        None => config.end_height,
+    };
+    let strata = build_strata(
+        config.start_height,
+        end_height,
+        config.difficulty_bands,
+        &bits,
+    );
+    anyhow::ensure!(
+        !strata.is_empty(),
+        "Empty range {}..={}",
+        config.start_height,
+        end_height
+    );
+    println!(
+        "🎲 Sampling {}..={} in {} strata for {:?} (seed {:#x}, {}-based difficulty bands)",
+        config.start_height,
+        end_height,
+        strata.len(),
+        config.budget,
+        config.seed,
+        if bits.is_empty() { "height" } else { "header" }
+    );
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut report = SamplingReport {
+        start_height: config.start_height,
+        end_height,
+        budget_secs: config.budget.as_secs_f64(),
+        elapsed_secs: 0.0,
+        rounds: 0,
+        tested: 0,
+        divergences: Vec::new(),
+        strata: strata
+            .iter()
+            .map(|s| StratumReport {
+                era: s.era.to_string(),
+                band: s.band,
+                population: s.population(),
+                sampled: 0,
+                divergences: 0,
+                upper_bound: 1.0,
+            })
+            .collect(),
+        weighted_rate: 0.0,
+        effective_samples: 0.0,
+        upper_bound: 1.0,
+        coverage: 0.0,
+    };
+    let mut drawn = HashSet::new();
+    // Heights drawn per stratum; `sampled` can stay below this when a block is not tested
+    let mut draws = vec![0u64; strata.len()];
+    // UTXO set after `replayed_to`, reused when the next sample is further along the same stretch
+    let mut context: Option<(u64, u64, Arc<UtxoSet>)> = None;
+
+    'rounds: loop {
+        let mut round: Vec<(usize, u64)> = Vec::new();
+        for (i, stratum) in strata.iter().enumerate() {
+            if draws[i] >= stratum.population() {
+                continue;
+            }
+            let height = loop {
+                let h = stratum.height_at(rng.gen_range(0..stratum.population()));
+                if drawn.insert(h) {
+                    break h;
+                }
+            };
+            draws[i] += 1;
+            round.push((i, height));
+        }
+        if round.is_empty() {
+            println!("✅ Every block of the range sampled");
+            break;
+        }
+        // Ascending, so one replay serves several samples past the same checkpoint
+        round.sort_by_key(|&(_, h)| h);
+
+        for &(i, height) in &round {
+            if cancel.is_cancelled() || started.elapsed() >= config.budget {
+                break 'rounds;
+            }
+            let utxo_set = if height == 0 {
+                Arc::new(UtxoSet::default())
+            } else {
+                let checkpoint = store
+                    .latest_in(0, height - 1, block_source.as_ref())
+                    .await?;
+                let base = checkpoint.as_ref().map_or(0, |(h, _)| *h);
+                let (replay_from, utxo_set) = match context.take() {
+                    // The previous sample's chunk is done with the set, so this doesn't copy it
+                    Some((cp, at, set)) if cp == base && at < height => {
+                        (at + 1, Arc::unwrap_or_clone(set))
+                    }
+                    _ => match checkpoint {
+                        Some((h, set)) => (h + 1, set),
+                        None => {
+                            anyhow::ensure!(
+                                height <= first_checkpoint + 1,
+                                "No usable checkpoint below block {} in {}",
+                                height,
+                                config.checkpoint_dir.display()
+                            );
+                            (0, UtxoSet::default())
+                        }
+                    },
+                };
+                let replayed = replay(
+                    block_source.as_ref(),
+                    utxo_set,
+                    replay_from,
+                    height - 1,
+                    config.network.consensus_network(),
+                    &cancel,
+                    Some(deadline),
+                )
+                .await;
+                let utxo_set = match replayed {
+                    Ok(utxo_set) => Arc::new(utxo_set),
+                    Err(e) if crate::cancel::is_cancelled(&e) => break 'rounds,
+                    Err(e) => return Err(e),
+                };
+                context = Some((base, height - 1, Arc::clone(&utxo_set)));
+                utxo_set
+            };
+
+            let result = validate_chunk(
+                BlockChunk {
+                    start_height: height,
+                    end_height: height,
+                    checkpoint_utxo: Some(utxo_set),
+                    checkpoint_timing: None,
+                    skip_validation: false,
+                },
+                block_source.clone(),
+                cancel.child_token(),
+                config.hooks.clone(),
+            )
+            .await?;
+            let stratum = &mut report.strata[i];
+            stratum.sampled += result.tested as u64;
+            stratum.divergences += result.divergences.len() as u64;
+            report.tested += result.tested as u64;
+            report.divergences.extend(result.divergences);
+        }
+        report.rounds += 1;
+    }
+
+    for s in &mut report.strata {
+        s.upper_bound = upper_bound(s.divergences as f64, s.sampled as f64);
+    }
+    report.elapsed_secs = started.elapsed().as_secs_f64();
+    report.summarize();
+    report.print();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strata_cover_the_range_once() {
+        let strata = build_strata(170_000, 490_000, 3, &[]);
+        let eras: Vec<_> = strata.iter().map(|s| (s.era, s.band)).collect();
+        assert_eq!(eras.len(), 4 * 3);
+        assert_eq!(eras[0], ("pre-p2sh", 0));
+        assert_eq!(eras[11], ("segwit", 2));
+
+        let mut ranges: Vec<(u64, u64)> = strata
+            .iter()
+            .flat_map(|s| s.ranges.iter().copied())
+            .collect();
+        ranges.sort_unstable();
+        assert_eq!(ranges.first().unwrap().0, 170_000);
+        assert_eq!(ranges.last().unwrap().1, 490_000);
+        assert!(ranges.windows(2).all(|w| w[0].1 + 1 == w[1].0));
+        // Era boundaries are never inside a stratum range
+        assert!(ranges.iter().any(|&(s, _)| s == 173_805));
+        assert_eq!(
+            strata.iter().map(Stratum::population).sum::<u64>(),
+            490_000 - 170_000 + 1
+        );
+    }
+
+    #[test]
+    fn difficulty_bands_follow_bits() {
+        // Two epochs; the later one has the lower difficulty (larger target)
+        let mut bits = vec![0x1b00_ffff; EPOCH_LEN as usize];
+        bits.extend(vec![0x1c00_ffff; EPOCH_LEN as usize]);
+        let strata = build_strata(0, 2 * EPOCH_LEN - 1, 2, &bits);
+        assert_eq!(strata[0].ranges, [(EPOCH_LEN, 2 * EPOCH_LEN - 1)]);
+        assert_eq!(strata[1].ranges, [(0, EPOCH_LEN - 1)]);
+    }
+
+    #[tokio::test]
+    async fn replay_stops_on_cancel_and_budget() {
+        let core = crate::mock_core::MockCore::new(crate::mock_core::synthetic_chain(3));
+        let past = Some(Instant::now());
+        let err = replay(
+            &core,
+            UtxoSet::default(),
+            0,
+            2,
+            Network::Mainnet,
+            &CancellationToken::new(),
+            past,
+        )
+        .await
+        .unwrap_err();
+        assert!(crate::cancel::is_cancelled(&err), "{:#}", err);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = replay(
+            &core,
+            UtxoSet::default(),
+            0,
+            2,
+            Network::Mainnet,
+            &cancel,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(crate::cancel::is_cancelled(&err), "{:#}", err);
+    }
+
+    #[test]
+    fn zero_failure_bound_is_rule_of_three() {
+        let bound = upper_bound(0.0, 300.0);
+        assert!((bound - 0.00994).abs() < 1e-4, "{}", bound);
+        assert!(upper_bound(3.0, 300.0) > 0.01);
+        assert_eq!(upper_bound(0.0, 0.0), 1.0);
+    }
+}
@@ -0,0 +1,400 @@
+//! Pre-validation block sanity filter
+//!
+//! Cheap byte-level checks (framing length, header version, bogus genesis-looking headers) that
+//! used to be repeated inline — with slightly different thresholds — in the collection reader,
+//! the temp-file writer, the chunker and the final integrity check. One [`SanityConfig`] per
+//! [`SanityStage`] is applied through a process-wide [`SanityFilter`], and every rejection bumps a
+//! per-rule counter so a run can print what was dropped and why ([`format_report`]).
+//!
+//! Stage defaults keep the previous behaviour: **read** is lenient (size + high-bit version only,
+//! since XOR-packaged files can surface odd-but-valid headers), **cache load** and **validation**
+//! are strict. Thresholds can be overridden per process with:
+//!
+//! - `BLVM_SANITY_MIN_BLOCK_SIZE` / `BLVM_SANITY_MAX_BLOCK_SIZE` (bytes, all stages)
+//! - `BLVM_SANITY_DISABLE=rule,rule` (rule names as in [`SanityRule::name`])
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::wire::sha256d;
+
+/// Maximum block size accepted (Bitcoin max is ~4MB serialized, allow up to 10MB for safety).
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 10 * 1024 * 1024;
+
+/// Minimum block size (magic + size + header = 88 bytes).
+pub const DEFAULT_MIN_BLOCK_SIZE: usize = 88;
+
+/// Genesis block hash prefixes (big-endian): mainnet `000000000019d668`, testnet3
+/// `000000000933ea01`, testnet4 `00000000da84f2ba`, signet `00000008819873e9`, regtest
+/// `0f9188f13cb7b2c7`.
+const GENESIS_PREFIXES: [[u8; 8]; 5] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0xd6, 0x68],
+    [0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01],
+    [0x00, 0x00, 0x00, 0x00, 0xda, 0x84, 0xf2, 0xba],
+    [0x00, 0x00, 0x00, 0x08, 0x81, 0x98, 0x73, 0xe9],
+    [0x0f, 0x91, 0x88, 0xf1, 0x3c, 0xb7, 0xb2, 0xc7],
+];
+
+/// Whether a big-endian (display order) block hash is the genesis block of a known network.
+pub fn is_known_genesis(hash_be: &[u8; 32]) -> bool {
+    GENESIS_PREFIXES.iter().any(|p| hash_be[..8] == p[..])
+}
+
+/// A single sanity rule. Discriminants index the counter table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SanityRule {
+    /// Block shorter than `min_block_size`.
+    TooSmall = 0,
+    /// Block longer than `max_block_size`.
+    TooLarge = 1,
+    /// Header version 0.
+    VersionZero = 2,
+    /// Header version with the sign bit set (> 0x7fffffff) — usually a failed XOR decode.
+    VersionHighBit = 3,
+    /// Double-SHA256 of the header is all zeros.
+    AllZeroHash = 4,
+    /// `prev_block_hash` is all zeros but the block is not genesis.
+    FakeGenesis = 5,
+}
+
+impl SanityRule {
+    pub const ALL: [SanityRule; 6] = [
+        SanityRule::TooSmall,
+        SanityRule::TooLarge,
+        SanityRule::VersionZero,
+        SanityRule::VersionHighBit,
+        SanityRule::AllZeroHash,
+        SanityRule::FakeGenesis,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SanityRule::TooSmall => "too_small",
+            SanityRule::TooLarge => "too_large",
+            SanityRule::VersionZero => "version_zero",
+            SanityRule::VersionHighBit => "version_high_bit",
+            SanityRule::AllZeroHash => "all_zero_hash",
+            SanityRule::FakeGenesis => "fake_genesis",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.name() == name)
+    }
+}
+
+/// Where in the pipeline a filter is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SanityStage {
+    /// Blocks coming off `blk*.dat` / temp collection file.
+    Read = 0,
+    /// Blocks read back from the temp file or chunk cache.
+    CacheLoad = 1,
+    /// Bytes handed to `connect_block` in differential runs.
+    Validation = 2,
+}
+
+impl SanityStage {
+    pub const ALL: [SanityStage; 3] = [
+        SanityStage::Read,
+        SanityStage::CacheLoad,
+        SanityStage::Validation,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SanityStage::Read => "read",
+            SanityStage::CacheLoad => "cache_load",
+            SanityStage::Validation => "validation",
+        }
+    }
+}
+
+/// Which rules run, with their thresholds.
+#[derive(Debug, Clone, Serialize)]
+pub struct SanityConfig {
+    pub min_block_size: usize,
+    pub max_block_size: usize,
+    pub reject_version_zero: bool,
+    pub reject_version_high_bit: bool,
+    /// Hash headers whose `prev_block_hash` is all zeros and reject all-zero / non-genesis hashes.
+    pub check_genesis_like: bool,
+}
+
+impl SanityConfig {
+    /// All rules enabled.
+    pub fn strict() -> Self {
+        Self {
+            min_block_size: DEFAULT_MIN_BLOCK_SIZE,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            reject_version_zero: true,
+            reject_version_high_bit: true,
+            check_genesis_like: true,
+        }
+    }
+
+    /// Size and high-bit version only (collection, where full checks happen at chunking).
+    pub fn lenient() -> Self {
+        Self {
+            reject_version_zero: false,
+            check_genesis_like: false,
+            ..Self::strict()
+        }
+    }
+
+    /// Stage default, with `BLVM_SANITY_*` overrides applied.
+    pub fn for_stage(stage: SanityStage) -> Self {
+        let base = match stage {
+            SanityStage::Read => Self::lenient(),
+            SanityStage::CacheLoad | SanityStage::Validation => Self::strict(),
+        };
+        base.with_env_overrides()
+    }
+
+    fn with_env_overrides(mut self) -> Self {
+        let parse = |k: &str| {
+            std::env::var(k)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+        };
+        if let Some(v) = parse("BLVM_SANITY_MIN_BLOCK_SIZE") {
+            self.min_block_size = v;
+        }
+        if let Some(v) = parse("BLVM_SANITY_MAX_BLOCK_SIZE") {
+            self.max_block_size = v;
+        }
+        if let Ok(list) = std::env::var("BLVM_SANITY_DISABLE") {
+            for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                match SanityRule::from_name(name) {
+                    Some(SanityRule::TooSmall) => self.min_block_size = 0,
+                    Some(SanityRule::TooLarge) => self.max_block_size = usize::MAX,
+                    Some(SanityRule::VersionZero) => self.reject_version_zero = false,
+                    Some(SanityRule::VersionHighBit) => self.reject_version_high_bit = false,
+                    Some(SanityRule::AllZeroHash) | Some(SanityRule::FakeGenesis) => {
+                        self.check_genesis_like = false
+                    }
+                    None => eprintln!("⚠️  BLVM_SANITY_DISABLE: unknown rule '{name}'"),
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Why a block was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanityRejection {
+    pub rule: SanityRule,
+    pub detail: String,
+}
+
+impl std::fmt::Display for SanityRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.rule.name(), self.detail)
+    }
+}
+
+impl std::error::Error for SanityRejection {}
+
+/// Applies a [`SanityConfig`] and counts rejections per rule (thread-safe).
+#[derive(Debug)]
+pub struct SanityFilter {
+    config: SanityConfig,
+    checked: AtomicU64,
+    rejected: [AtomicU64; SanityRule::ALL.len()],
+}
+
+impl SanityFilter {
+    pub fn new(config: SanityConfig) -> Self {
+        Self {
+            config,
+            checked: AtomicU64::new(0),
+            rejected: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &SanityConfig {
+        &self.config
+    }
+
+    fn reject(&self, rule: SanityRule, detail: String) -> Result<(), SanityRejection> {
+        self.rejected[rule as usize].fetch_add(1, Ordering::Relaxed);
+        Err(SanityRejection { rule, detail })
+    }
+
+    /// Size rules only — for framing lengths read before the block body.
+    pub fn check_len(&self, len: usize) -> Result<(), SanityRejection> {
+        if len < self.config.min_block_size {
+            return self.reject(
+                SanityRule::TooSmall,
+                format!("{} bytes (minimum {})", len, self.config.min_block_size),
+            );
+        }
+        if len > self.config.max_block_size {
+            return self.reject(
+                SanityRule::TooLarge,
+                format!("{} bytes (maximum {})", len, self.config.max_block_size),
+            );
+        }
+        Ok(())
+    }
+
+    /// All enabled rules against raw block bytes (header first, no magic/size prefix).
+    pub fn check(&self, block: &[u8]) -> Result<(), SanityRejection> {
+        self.checked.fetch_add(1, Ordering::Relaxed);
+        self.check_len(block.len())?;
+
+        if block.len() >= 4 {
+            let version = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+            if self.config.reject_version_zero && version == 0 {
+                return self.reject(SanityRule::VersionZero, "version 0".to_string());
+            }
+            // Versions are signed in Core; BIP9 values (0x20000000+) are fine, the sign bit is not.
+            if self.config.reject_version_high_bit && version > 0x7fff_ffff {
+                return self.reject(
+                    SanityRule::VersionHighBit,
+                    format!("version {version:#010x}"),
+                );
+            }
+        }
+
+        // Only hash when prev_hash is all zeros — the fast path for every non-genesis block.
+        if self.config.check_genesis_like
+            && block.len() >= 80
+            && block[4..36].iter().all(|&b| b == 0)
+        {
+            let hash = sha256d(&block[..80]);
+            if hash.iter().all(|&b| b == 0) {
+                return self.reject(SanityRule::AllZeroHash, "all-zero header hash".to_string());
+            }
+            let mut be = [0u8; 32];
+            be.copy_from_slice(&hash);
+            be.reverse();
+            if !is_known_genesis(&be) {
+                return self.reject(
+                    SanityRule::FakeGenesis,
+                    format!("prev_hash all zeros but hash {}", hex::encode(be)),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks passed to [`Self::check`] so far.
+    pub fn checked(&self) -> u64 {
+        self.checked.load(Ordering::Relaxed)
+    }
+
+    /// `(rule, rejections)` for every rule, including zeros.
+    pub fn counters(&self) -> Vec<(SanityRule, u64)> {
+        SanityRule::ALL
+            .into_iter()
+            .map(|r| (r, self.rejected[r as usize].load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    pub fn total_rejected(&self) -> u64 {
+        self.counters().iter().map(|(_, n)| n).sum()
+    }
+}
+
+static STAGE_FILTERS: OnceLock<[SanityFilter; SanityStage::ALL.len()]> = OnceLock::new();
+
+/// Process-wide filter for `stage` (config read from env on first use).
+pub fn filter(stage: SanityStage) -> &'static SanityFilter {
+    let filters = STAGE_FILTERS
+        .get_or_init(|| SanityStage::ALL.map(|s| SanityFilter::new(SanityConfig::for_stage(s))));
+    &filters[stage as usize]
+}
+
+/// `(stage, checked, rejected)` for every stage, zero for stages not used yet.
+pub fn stage_totals() -> Vec<(SanityStage, u64, u64)> {
+    SanityStage::ALL
+        .iter()
+        .map(|&stage| match STAGE_FILTERS.get() {
+            Some(filters) => {
+                let f = &filters[stage as usize];
+                (stage, f.checked(), f.total_rejected())
+            }
+            None => (stage, 0, 0),
+        })
+        .collect()
+}
+
+/// Per-stage, per-rule rejection summary (only stages that saw traffic).
+pub fn format_report() -> String {
+    let mut out = String::new();
+    let Some(filters) = STAGE_FILTERS.get() else {
+        return out;
+    };
+    for stage in SanityStage::ALL {
+        let f = &filters[stage as usize];
+        let rejected = f.total_rejected();
+        if f.checked() == 0 && rejected == 0 {
+            continue;
+        }
+        out.push_str(&format!(
+            "🔍 Sanity [{}]: {} checked, {} rejected\n",
+            stage.name(),
+            f.checked(),
+            rejected
+        ));
+        for (rule, n) in f.counters() {
+            if n > 0 {
+                out.push_str(&format!("   {:<17} {}\n", rule.name(), n));
+            }
+        }
+    }
+    out
+}
+
+/// Print [`format_report`] to stderr if anything was checked.
+pub fn print_report() {
+    let report = format_report();
+    if !report.is_empty() {
+        eprint!("{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_with(version: u32, prev_zero: bool) -> Vec<u8> {
+        let mut b = vec![0u8; DEFAULT_MIN_BLOCK_SIZE];
+        b[..4].copy_from_slice(&version.to_le_bytes());
+        if !prev_zero {
+            b[4] = 1;
+        }
+        b
+    }
+
+    #[test]
+    fn strict_rejects_and_counts() {
+        let f = SanityFilter::new(SanityConfig::strict());
+        assert!(f.check(&header_with(0x2000_0000, false)).is_ok());
+        assert_eq!(
+            f.check(&header_with(0, false)).unwrap_err().rule,
+            SanityRule::VersionZero
+        );
+        assert_eq!(
+            f.check(&header_with(0x8000_0000, false)).unwrap_err().rule,
+            SanityRule::VersionHighBit
+        );
+        assert_eq!(
+            f.check(&header_with(1, true)).unwrap_err().rule,
+            SanityRule::FakeGenesis
+        );
+        assert_eq!(f.check(&[0u8; 10]).unwrap_err().rule, SanityRule::TooSmall);
+        assert_eq!(f.checked(), 5);
+        assert_eq!(f.total_rejected(), 4);
+    }
+
+    #[test]
+    fn lenient_allows_version_zero_and_zero_prev_hash() {
+        let f = SanityFilter::new(SanityConfig::lenient());
+        assert!(f.check(&header_with(0, true)).is_ok());
+        assert!(f.check(&header_with(0x8000_0000, false)).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! Script-level differential fuzzing against Core's interpreter
+//!
+//! Evaluates scriptSig / scriptPubKey / witness combinations with BLVM's interpreter and with
+//! Core's through `libbitcoinconsensus`, in the same crediting / spending transaction pair Core's
+//! own script tests use. Cases come from two places:
+//!
+//! - Core's `src/test/data/script_tests.json` ([`load_script_tests`]), which also carries the
+//!   expected result and script error for every case, so flag combinations `libbitcoinconsensus`
+//!   does not accept (MINIMALDATA, CLEANSTACK, ...) are still checked against the expectation.
+//! - A seeded generator ([`ScriptFuzzer`]) of short random scripts under flag sets both sides
+//!   support, compared verdict for verdict.
+//!
+//! Only verdicts are compared: `libbitcoinconsensus` reports success or failure, and BLVM's errors
+//! do not map one-to-one onto Core's `ScriptError` codes. A case whose verdict matches but whose
+//! error differs is not a divergence; the corpus' expected code and BLVM's error text are kept
+//! side by side in each reported divergence for triage.
+
+use anyhow::{bail, Context, Result};
+use bitcoinconsensus::{verify_with_flags, VERIFY_ALL_PRE_TAPROOT};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::Network;
+use blvm_protocol::{
+    tx_inputs, tx_outputs, OutPoint, Transaction, TransactionInput, TransactionOutput,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::wire::{sha256d, write_compact_size};
+
+/// Divergences kept in full in a [`ScriptDiffReport`].
+const MAX_KEPT_DIVERGENCES: usize = 100;
+
+/// `SCRIPT_VERIFY_*` names as used in `script_tests.json`, with their bits.
+const SCRIPT_FLAGS: [(&str, u32); 21] = [
+    ("P2SH", 1 << 0),
+    ("STRICTENC", 1 << 1),
+    ("DERSIG", 1 << 2),
+    ("LOW_S", 1 << 3),
+    ("NULLDUMMY", 1 << 4),
+    ("SIGPUSHONLY", 1 << 5),
+    ("MINIMALDATA", 1 << 6),
+    ("DISCOURAGE_UPGRADABLE_NOPS", 1 << 7),
+    ("CLEANSTACK", 1 << 8),
+    ("CHECKLOCKTIMEVERIFY", 1 << 9),
+    ("CHECKSEQUENCEVERIFY", 1 << 10),
+    ("WITNESS", 1 << 11),
+    ("DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM", 1 << 12),
+    ("MINIMALIF", 1 << 13),
+    ("NULLFAIL", 1 << 14),
+    ("WITNESS_PUBKEYTYPE", 1 << 15),
+    ("CONST_SCRIPTCODE", 1 << 16),
+    ("TAPROOT", 1 << 17),
+    ("DISCOURAGE_UPGRADABLE_TAPROOT_VERSION", 1 << 18),
+    ("DISCOURAGE_OP_SUCCESS", 1 << 19),
+    ("DISCOURAGE_UPGRADABLE_PUBKEYTYPE", 1 << 20),
+];
+
+/// Opcode names (`OP_` prefix optional when parsing), including Core's aliases.
+const OPCODE_NAMES: &[(&str, u8)] = &[
+    ("0", 0x00),
+    ("FALSE", 0x00),
+    ("PUSHDATA1", 0x4c),
+    ("PUSHDATA2", 0x4d),
+    ("PUSHDATA4", 0x4e),
+    ("1NEGATE", 0x4f),
+    ("RESERVED", 0x50),
+    ("TRUE", 0x51),
+    ("NOP", 0x61),
+    ("VER", 0x62),
+    ("IF", 0x63),
+    ("NOTIF", 0x64),
+    ("VERIF", 0x65),
+    ("VERNOTIF", 0x66),
+    ("ELSE", 0x67),
+    ("ENDIF", 0x68),
+    ("VERIFY", 0x69),
+    ("RETURN", 0x6a),
+    ("TOALTSTACK", 0x6b),
+    ("FROMALTSTACK", 0x6c),
+    ("2DROP", 0x6d),
+    ("2DUP", 0x6e),
+    ("3DUP", 0x6f),
+    ("2OVER", 0x70),
+    ("2ROT", 0x71),
+    ("2SWAP", 0x72),
+    ("IFDUP", 0x73),
+    ("DEPTH", 0x74),
+    ("DROP", 0x75),
+    ("DUP", 0x76),
+    ("NIP", 0x77),
+    ("OVER", 0x78),
+    ("PICK", 0x79),
+    ("ROLL", 0x7a),
+    ("ROT", 0x7b),
+    ("SWAP", 0x7c),
+    ("TUCK", 0x7d),
+    ("CAT", 0x7e),
+    ("SUBSTR", 0x7f),
+    ("LEFT", 0x80),
+    ("RIGHT", 0x81),
+    ("SIZE", 0x82),
+    ("INVERT", 0x83),
+    ("AND", 0x84),
+    ("OR", 0x85),
+    ("XOR", 0x86),
+    ("EQUAL", 0x87),
+    ("EQUALVERIFY", 0x88),
+    ("RESERVED1", 0x89),
+    ("RESERVED2", 0x8a),
+    ("1ADD", 0x8b),
+    ("1SUB", 0x8c),
+    ("2MUL", 0x8d),
+    ("2DIV", 0x8e),
+    ("NEGATE", 0x8f),
+    ("ABS", 0x90),
+    ("NOT", 0x91),
+    ("0NOTEQUAL", 0x92),
+    ("ADD", 0x93),
+    ("SUB", 0x94),
+    ("MUL", 0x95),
+    ("DIV", 0x96),
+    ("MOD", 0x97),
+    ("LSHIFT", 0x98),
+    ("RSHIFT", 0x99),
+    ("BOOLAND", 0x9a),
+    ("BOOLOR", 0x9b),
+    ("NUMEQUAL", 0x9c),
+    ("NUMEQUALVERIFY", 0x9d),
+    ("NUMNOTEQUAL", 0x9e),
+    ("LESSTHAN", 0x9f),
+    ("GREATERTHAN", 0xa0),
+    ("LESSTHANOREQUAL", 0xa1),
+    ("GREATERTHANOREQUAL", 0xa2),
+    ("MIN", 0xa3),
+    ("MAX", 0xa4),
+    ("WITHIN", 0xa5),
+    ("RIPEMD160", 0xa6),
+    ("SHA1", 0xa7),
+    ("SHA256", 0xa8),
+    ("HASH160", 0xa9),
+    ("HASH256", 0xaa),
+    ("CODESEPARATOR", 0xab),
+    ("CHECKSIG", 0xac),
+    ("CHECKSIGVERIFY", 0xad),
+    ("CHECKMULTISIG", 0xae),
+    ("CHECKMULTISIGVERIFY", 0xaf),
+    ("NOP1", 0xb0),
+    ("CHECKLOCKTIMEVERIFY", 0xb1),
+    ("NOP2", 0xb1),
+    ("CHECKSEQUENCEVERIFY", 0xb2),
+    ("NOP3", 0xb2),
+    ("NOP4", 0xb3),
+    ("NOP5", 0xb4),
+    ("NOP6", 0xb5),
+    ("NOP7", 0xb6),
+    ("NOP8", 0xb7),
+    ("NOP9", 0xb8),
+    ("NOP10", 0xb9),
+    ("CHECKSIGADD", 0xba),
+];
+
+/// Parse a comma-separated `SCRIPT_VERIFY_*` list (`""` / `"NONE"` for no flags).
+pub fn parse_flags(s: &str) -> Result<u32> {
+    let mut flags = 0;
+    for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if name == "NONE" {
+            continue;
+        }
+        let Some(&(_, bit)) = SCRIPT_FLAGS.iter().find(|(n, _)| *n == name) else {
+            bail!("Unknown script flag {:?}", name);
+        };
+        flags |= bit;
+    }
+    Ok(flags)
+}
+
+/// `CScriptNum::serialize`: minimal little-endian magnitude with a sign bit.
+fn script_num(n: i64) -> Vec<u8> {
+    let mut out = Vec::new();
+    let negative = n < 0;
+    let mut abs = n.unsigned_abs();
+    while abs > 0 {
+        out.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if let Some(last) = out.last_mut() {
+        if *last & 0x80 != 0 {
+            out.push(if negative { 0x80 } else { 0x00 });
+        } else if negative {
+            *last |= 0x80;
+        }
+    }
+    out
+}
+
+/// Append `data` with the smallest push opcode (as `CScript << std::vector`).
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        n if n < 0x4c => script.push(n as u8),
+        n if n <= 0xff => script.extend([0x4c, n as u8]),
+        n if n <= 0xffff => {
+            script.push(0x4d);
+            script.extend((n as u16).to_le_bytes());
+        }
+        n => {
+            script.push(0x4e);
+            script.extend((n as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+/// Every flag bit [`parse_flags`] knows.
+pub fn all_flags() -> u32 {
+    SCRIPT_FLAGS.iter().fold(0, |all, (_, bit)| all | bit)
+}
+
+/// Names of the bits set in `flags`, in bit order (`["NONE"]` for none).
+pub fn flag_names(flags: u32) -> Vec<&'static str> {
+    let names: Vec<&'static str> = SCRIPT_FLAGS
+        .iter()
+        .filter(|(_, bit)| flags & bit != 0)
+        .map(|(name, _)| *name)
+        .collect();
+    if names.is_empty() {
+        vec!["NONE"]
+    } else {
+        names
+    }
+}
+
+/// Core's `ParseScript` test syntax: decimal numbers, `0x` raw bytes, `'text'` pushes and opcode
+/// names with or without `OP_`.
+pub fn parse_script_asm(asm: &str) -> Result<Vec<u8>> {
+    let mut script = Vec::new();
+    for token in asm.split_whitespace() {
+        let digits = token.strip_prefix('-').unwrap_or(token);
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            let n: i64 = token
+                .parse()
+                .with_context(|| format!("Bad number {:?}", token))?;
+            anyhow::ensure!(
+                (-0xffff_ffff..=0xffff_ffff).contains(&n),
+                "Number {} out of range",
+                n
+            );
+            match n {
+                0 => script.push(0x00),
+                -1 => script.push(0x4f),
+                1..=16 => script.push(0x50 + n as u8),
+                _ => push_data(&mut script, &script_num(n)),
+            }
+        } else if let Some(hex_bytes) = token.strip_prefix("0x").filter(|h| !h.is_empty()) {
+            script.extend(hex::decode(hex_bytes).with_context(|| format!("Bad hex {:?}", token))?);
+        } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+            push_data(&mut script, &token.as_bytes()[1..token.len() - 1]);
+        } else {
+            let name = token.strip_prefix("OP_").unwrap_or(token);
+            let opcode = match name.parse::<u8>() {
+                Ok(n @ 1..=16) => 0x50 + n,
+                _ => OPCODE_NAMES
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|&(_, op)| op)
+                    .with_context(|| format!("Unknown opcode {:?}", token))?,
+            };
+            script.push(opcode);
+        }
+    }
+    Ok(script)
+}
+
+/// One script evaluation: the spending input's scripts and witness, the credited amount and the
+/// verification flags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScriptCase {
+    #[serde(serialize_with = "hex_ser")]
+    pub script_sig: Vec<u8>,
+    #[serde(serialize_with = "hex_ser")]
+    pub script_pubkey: Vec<u8>,
+    pub witness: Vec<Vec<u8>>,
+    pub amount: u64,
+    pub flags: u32,
+    /// Expected script error (`"OK"` when it should verify); `None` for generated cases
+    pub expected: Option<String>,
+    pub comment: String,
+}
+
+fn hex_ser<S: serde::Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&hex::encode(bytes))
+}
+
+/// Cases from Core's `script_tests.json` and how many were skipped (taproot placeholders such as
+/// `#SCRIPT#` / `#CONTROLBLOCK#`, which need Core's test-side tree building).
+pub fn load_script_tests(path: &Path) -> Result<(Vec<ScriptCase>, usize)> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let rows: Vec<serde_json::Value> = serde_json::from_slice(&data)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let mut cases = Vec::new();
+    let mut skipped = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let Some(fields) = row.as_array().filter(|f| f.len() >= 4) else {
+            continue; // comment row
+        };
+        let text = row.to_string();
+        if ["#SCRIPT#", "#CONTROLBLOCK#", "#TAPROOTOUTPUT#"]
+            .iter()
+            .any(|p| text.contains(p))
+        {
+            skipped += 1;
+            continue;
+        }
+        let case =
+            parse_test_row(fields).with_context(|| format!("script_tests.json row {}", i))?;
+        cases.push(case);
+    }
+    Ok((cases, skipped))
+}
+
+fn parse_test_row(fields: &[serde_json::Value]) -> Result<ScriptCase> {
+    let (witness, amount, rest) = match fields[0].as_array() {
+        Some(wit) => {
+            let (amount, items) = wit.split_last().context("Empty witness array")?;
+            let amount_btc = amount.as_f64().context("Witness amount is not a number")?;
+            let witness = items
+                .iter()
+                .map(|w| {
+                    let item = w.as_str().context("Witness item is not a string")?;
+                    hex::decode(item).with_context(|| format!("Bad witness hex {:?}", item))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (witness, (amount_btc * 1e8).round() as u64, &fields[1..])
+        }
+        None => (Vec::new(), 0, fields),
+    };
+    anyhow::ensure!(
+        rest.len() >= 4,
+        "Expected scriptSig, scriptPubKey, flags, result"
+    );
+    let text = |i: usize| rest[i].as_str().context("Expected a string field");
+    Ok(ScriptCase {
+        script_sig: parse_script_asm(text(0)?)?,
+        script_pubkey: parse_script_asm(text(1)?)?,
+        witness,
+        amount,
+        flags: parse_flags(text(2)?)?,
+        expected: Some(text(3)?.to_string()),
+        comment: rest
+            .get(4)
+            .and_then(|c| c.as_str())
+            .unwrap_or("")
+            .to_string(),
+    })
+}
+
+/// `BuildCreditingTransaction`: a coinbase-shaped tx paying `amount` to `script_pubkey`.
+fn crediting_txid(case: &ScriptCase) -> [u8; 32] {
+    let mut tx = Vec::new();
+    tx.extend(1i32.to_le_bytes());
+    tx.push(1);
+    tx.extend([0u8; 32]);
+    tx.extend(u32::MAX.to_le_bytes());
+    tx.extend([2, 0x00, 0x00]); // scriptSig: OP_0 OP_0
+    tx.extend(u32::MAX.to_le_bytes());
+    tx.push(1);
+    tx.extend(case.amount.to_le_bytes());
+    write_compact_size(&mut tx, case.script_pubkey.len());
+    tx.extend(&case.script_pubkey);
+    tx.extend(0u32.to_le_bytes());
+    sha256d(&tx)
+}
+
+/// `BuildSpendingTransaction` in wire format (with witness when the case has one).
+fn spending_tx_bytes(case: &ScriptCase, credit_txid: &[u8; 32]) -> Vec<u8> {
+    let mut tx = Vec::new();
+    tx.extend(1i32.to_le_bytes());
+    if !case.witness.is_empty() {
+        tx.extend([0x00, 0x01]);
+    }
+    tx.push(1);
+    tx.extend(credit_txid);
+    tx.extend(0u32.to_le_bytes());
+    write_compact_size(&mut tx, case.script_sig.len());
+    tx.extend(&case.script_sig);
+    tx.extend(u32::MAX.to_le_bytes());
+    tx.push(1);
+    tx.extend(case.amount.to_le_bytes());
+    tx.push(0); // empty scriptPubKey
+    if !case.witness.is_empty() {
+        write_compact_size(&mut tx, case.witness.len());
+        for item in &case.witness {
+            write_compact_size(&mut tx, item.len());
+            tx.extend(item);
+        }
+    }
+    tx.extend(0u32.to_le_bytes());
+    tx
+}
+
+/// Both interpreters' verdicts for one case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScriptOutcome {
+    pub blvm_ok: bool,
+    /// BLVM's error, when it failed with one rather than returning false
+    pub blvm_error: Option<String>,
+    /// `None` when the flags include ones `libbitcoinconsensus` rejects
+    pub core_ok: Option<bool>,
+}
+
+/// How a case disagreed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScriptDivergence {
+    /// BLVM and `libbitcoinconsensus` returned different verdicts
+    CoreMismatch,
+    /// BLVM's verdict contradicts the corpus expectation (`"OK"` or any error)
+    ExpectationMismatch,
+}
+
+impl ScriptOutcome {
+    pub fn divergence(&self, case: &ScriptCase) -> Option<ScriptDivergence> {
+        if self.core_ok.is_some_and(|core| core != self.blvm_ok) {
+            return Some(ScriptDivergence::CoreMismatch);
+        }
+        let expected_ok = case.expected.as_deref().map(|e| e == "OK");
+        if expected_ok.is_some_and(|ok| ok != self.blvm_ok) {
+            return Some(ScriptDivergence::ExpectationMismatch);
+        }
+        None
+    }
+}
+
+/// Run `case` through BLVM and, when its flags allow, `libbitcoinconsensus`.
+pub fn evaluate_case(case: &ScriptCase) -> ScriptOutcome {
+    let credit_txid = crediting_txid(case);
+    let tx = Transaction {
+        version: 1,
+        inputs: tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: credit_txid,
+                index: 0,
+            },
+            script_sig: case.script_sig.clone(),
+            sequence: 0xffffffff,
+        }],
+        outputs: tx_outputs![TransactionOutput {
+            value: case.amount as i64,
+            script_pubkey: Vec::new(),
+        }],
+        lock_time: 0,
+    };
+    let witness: Witness = case.witness.iter().cloned().collect();
+    let blvm = verify_script_with_context_full(
+        &case.script_sig,
+        &case.script_pubkey,
+        (!case.witness.is_empty()).then_some(&witness),
+        case.flags,
+        &tx,
+        0,
+        &[case.amount as i64],
+        &[case.script_pubkey.as_slice()],
+        None,
+        None,
+        Network::Mainnet,
+        SigVersion::Base,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (blvm_ok, blvm_error) = match blvm {
+        Ok(ok) => (ok, None),
+        Err(e) => (false, Some(e.to_string())),
+    };
+
+    let core_ok = (case.flags & !VERIFY_ALL_PRE_TAPROOT == 0).then(|| {
+        verify_with_flags(
+            &case.script_pubkey,
+            case.amount,
+            &spending_tx_bytes(case, &credit_txid),
+            None,
+            0,
+            case.flags,
+        )
+        .is_ok()
+    });
+    ScriptOutcome {
+        blvm_ok,
+        blvm_error,
+        core_ok,
+    }
+}
+
+/// A case that diverged, with both verdicts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptDiffEntry {
+    pub case: ScriptCase,
+    pub outcome: ScriptOutcome,
+    pub divergence: ScriptDivergence,
+}
+
+/// Totals over a replay or fuzz run; divergences are kept up to [`MAX_KEPT_DIVERGENCES`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScriptDiffReport {
+    pub evaluated: u64,
+    /// Cases also run through `libbitcoinconsensus`
+    pub core_compared: u64,
+    pub divergence_count: u64,
+    pub divergences: Vec<ScriptDiffEntry>,
+}
+
+impl ScriptDiffReport {
+    pub fn record(&mut self, case: &ScriptCase, outcome: ScriptOutcome) {
+        self.evaluated += 1;
+        if outcome.core_ok.is_some() {
+            self.core_compared += 1;
+        }
+        let Some(divergence) = outcome.divergence(case) else {
+            return;
+        };
+        self.divergence_count += 1;
+        if self.divergences.len() < MAX_KEPT_DIVERGENCES {
+            self.divergences.push(ScriptDiffEntry {
+                case: case.clone(),
+                outcome,
+                divergence,
+            });
+        }
+    }
+}
+
+/// Evaluate every case (e.g. from [`load_script_tests`]).
+pub fn replay_cases(cases: &[ScriptCase]) -> ScriptDiffReport {
+    let mut report = ScriptDiffReport::default();
+    for case in cases {
+        report.record(case, evaluate_case(case));
+    }
+    report
+}
+
+/// Opcodes the generator draws from: stack, arithmetic, comparison, hashing, flow control,
+/// NOPs, lock-time checks and signature checks (which fail without a real signature but still
+/// exercise NULLDUMMY / NULLFAIL / encoding paths).
+const FUZZ_OPCODES: &[u8] = &[
+    0x00, 0x4f, 0x51, 0x52, 0x53, 0x60, 0x61, 0x63, 0x64, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d,
+    0x6e, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x7b, 0x7c, 0x7d, 0x82, 0x87, 0x88, 0x8b,
+    0x8c, 0x8f, 0x90, 0x91, 0x92, 0x93, 0x94, 0x9a, 0x9b, 0x9c, 0x9f, 0xa0, 0xa3, 0xa5, 0xa6, 0xa8,
+    0xa9, 0xaa, 0xab, 0xac, 0xae, 0xb0, 0xb1, 0xb2, 0xb3, 0xb9, 0x7e, 0x50,
+];
+
+/// Flags the generator draws from: P2SH, DERSIG, NULLDUMMY, CHECKLOCKTIMEVERIFY,
+/// CHECKSEQUENCEVERIFY and WITNESS, all of which `libbitcoinconsensus` accepts.
+pub(crate) const FUZZ_FLAGS: [u32; 6] = [1 << 0, 1 << 2, 1 << 4, 1 << 9, 1 << 10, 1 << 11];
+
+/// Seeded generator of short random script pairs under flags `libbitcoinconsensus` accepts.
+pub struct ScriptFuzzer {
+    rng: StdRng,
+}
+
+impl ScriptFuzzer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn random_push(&mut self, script: &mut Vec<u8>) {
+        let len = match self.rng.gen_range(0..10) {
+            0..=5 => self.rng.gen_range(0..5),
+            6..=8 => self.rng.gen_range(5..34),
+            _ => self.rng.gen_range(70..300),
+        };
+        let data: Vec<u8> = (0..len).map(|_| self.rng.gen()).collect();
+        if self.rng.gen_bool(0.1) && len < 0x4c {
+            // Non-minimal PUSHDATA1 encoding
+            script.extend([0x4c, len as u8]);
+            script.extend(data);
+        } else {
+            push_data(script, &data);
+        }
+    }
+
+    fn random_script(&mut self, max_len: usize, push_only: bool) -> Vec<u8> {
+        let mut script = Vec::new();
+        for _ in 0..self.rng.gen_range(0..=max_len) {
+            if push_only || self.rng.gen_bool(0.35) {
+                self.random_push(&mut script);
+            } else {
+                script.push(FUZZ_OPCODES[self.rng.gen_range(0..FUZZ_OPCODES.len())]);
+            }
+        }
+        script
+    }
+
+    pub fn next_case(&mut self) -> ScriptCase {
+        let mut flags = 0;
+        for bit in FUZZ_FLAGS {
+            if self.rng.gen_bool(0.5) {
+                flags |= bit;
+            }
+        }
+        let push_only_sig = self.rng.gen_bool(0.8);
+        ScriptCase {
+            script_sig: self.random_script(4, push_only_sig),
+            script_pubkey: self.random_script(8, false),
+            witness: Vec::new(),
+            amount: self.rng.gen_range(0..100_000_000),
+            flags,
+            expected: None,
+            comment: String::new(),
+        }
+    }
+
+    /// Generate and evaluate `iterations` cases.
+    pub fn run(&mut self, iterations: u64) -> ScriptDiffReport {
+        let mut report = ScriptDiffReport::default();
+        for _ in 0..iterations {
+            let case = self.next_case();
+            let outcome = evaluate_case(&case);
+            report.record(&case, outcome);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_core_test_syntax() {
+        assert_eq!(
+            parse_script_asm("0 1 16 -1").unwrap(),
+            [0x00, 0x51, 0x60, 0x4f]
+        );
+        // Numbers outside OP_N are CScriptNum pushes
+        assert_eq!(parse_script_asm("17").unwrap(), [0x01, 0x11]);
+        assert_eq!(parse_script_asm("-2").unwrap(), [0x01, 0x82]);
+        assert_eq!(parse_script_asm("128").unwrap(), [0x02, 0x80, 0x00]);
+        assert_eq!(parse_script_asm("-255").unwrap(), [0x02, 0xff, 0x80]);
+        assert_eq!(
+            parse_script_asm("DUP OP_HASH160 0x14 0x0102 EQUALVERIFY NOP2").unwrap(),
+            [0x76, 0xa9, 0x14, 0x01, 0x02, 0x88, 0xb1]
+        );
+        assert_eq!(parse_script_asm("'ab'").unwrap(), [0x02, b'a', b'b']);
+        assert_eq!(parse_script_asm("''").unwrap(), [0x00]);
+        assert!(parse_script_asm("NOT_AN_OPCODE").is_err());
+    }
+
+    #[test]
+    fn parses_flags_and_rows() {
+        assert_eq!(parse_flags("").unwrap(), 0);
+        assert_eq!(parse_flags("NONE").unwrap(), 0);
+        assert_eq!(parse_flags("P2SH,STRICTENC").unwrap(), 0b11);
+        assert_eq!(parse_flags("WITNESS, P2SH").unwrap(), 0x801);
+        assert!(parse_flags("P2SH,BOGUS").is_err());
+
+        let row: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[["00", 0.00000001], "", "0 0x20 0x0000000000000000000000000000000000000000000000000000000000000000", "P2SH,WITNESS", "WITNESS_PROGRAM_MISMATCH", "note"]"#,
+        )
+        .unwrap();
+        let case = parse_test_row(&row).unwrap();
+        assert_eq!(case.witness, vec![vec![0u8]]);
+        assert_eq!(case.amount, 1);
+        assert_eq!(case.script_pubkey.len(), 34);
+        assert_eq!(case.expected.as_deref(), Some("WITNESS_PROGRAM_MISMATCH"));
+        assert_eq!(case.comment, "note");
+    }
+
+    #[test]
+    fn divergence_compares_verdicts_only() {
+        let mut case = ScriptFuzzer::new(1).next_case();
+        let failed = ScriptOutcome {
+            blvm_ok: false,
+            blvm_error: Some("stack empty".to_string()),
+            core_ok: None,
+        };
+        // Different error, same verdict
+        case.expected = Some("EVAL_FALSE".to_string());
+        assert_eq!(failed.divergence(&case), None);
+        case.expected = Some("OK".to_string());
+        assert_eq!(
+            failed.divergence(&case),
+            Some(ScriptDivergence::ExpectationMismatch)
+        );
+        let core_accepts = ScriptOutcome {
+            core_ok: Some(true),
+            ..failed
+        };
+        assert_eq!(
+            core_accepts.divergence(&case),
+            Some(ScriptDivergence::CoreMismatch)
+        );
+    }
+
+    #[test]
+    fn fuzzer_is_deterministic() {
+        let a: Vec<ScriptCase> = {
+            let mut f = ScriptFuzzer::new(7);
+            (0..50).map(|_| f.next_case()).collect()
+        };
+        let mut f = ScriptFuzzer::new(7);
+        for case in &a {
+            assert_eq!(&f.next_case(), case);
+            assert_eq!(case.flags & !VERIFY_ALL_PRE_TAPROOT, 0);
+        }
+    }
+}
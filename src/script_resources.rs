@@ -0,0 +1,641 @@
+//! Script resource-usage differential
+//!
+//! Consensus limits on script execution (201 non-push opcodes, 520-byte pushes, 10 000-byte
+//! scripts, disabled opcodes, 1000 stack items) are enforced as the interpreter walks the script,
+//! and sigops are counted by a separate, deliberately imprecise rule (`GetSigOpCount`). Off-by-one
+//! accounting in either only shows on adversarial scripts that sit on a limit.
+//!
+//! This module measures each executed script of an input the way Core's `EvalScript` /
+//! `GetSigOpCount` count (opcode count, largest push, sigops legacy and accurate, P2SH sigops,
+//! a static stack-depth estimate) and derives the verdict the limits alone force. For sampled
+//! complex inputs that expectation is checked against both BLVM and `libbitcoinconsensus`, so a
+//! verdict that contradicts a limit is reported with the counts that explain it.
+//!
+//! Only legacy / P2SH scripts are covered (the same scope as [`compare_script_verification`]);
+//! tapscript has no opcode or script-size limit.
+
+use anyhow::Result;
+use blvm_protocol::types::{ByteString, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::script_validation::compare_script_verification;
+
+pub const MAX_OPS_PER_SCRIPT: u32 = 201;
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_STACK_SIZE: i64 = 1_000;
+pub const MAX_PUBKEYS_PER_MULTISIG: u32 = 20;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_IF: u8 = 0x63;
+const OP_NOTIF: u8 = 0x64;
+const OP_HASH160: u8 = 0xa9;
+const OP_EQUAL: u8 = 0x87;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKSIGVERIFY: u8 = 0xad;
+const OP_CHECKMULTISIG: u8 = 0xae;
+const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+
+/// CAT..RIGHT, INVERT..XOR, 2MUL/2DIV, MUL..RSHIFT: fail even in an unexecuted branch.
+fn is_disabled(op: u8) -> bool {
+    matches!(op, 0x7e..=0x81 | 0x83..=0x86 | 0x8d | 0x8e | 0x95..=0x99)
+}
+
+/// One parsed opcode: `push` holds the pushed bytes for data pushes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Op<'a> {
+    pub(crate) opcode: u8,
+    pub(crate) push: Option<&'a [u8]>,
+}
+
+/// `GetOp`: `None` once the script ends, `Some(Err(()))` on a truncated push.
+pub(crate) fn next_op<'a>(script: &'a [u8], pc: &mut usize) -> Option<Result<Op<'a>, ()>> {
+    let opcode = *script.get(*pc)?;
+    *pc += 1;
+    if opcode > OP_PUSHDATA4 {
+        return Some(Ok(Op { opcode, push: None }));
+    }
+    let len_bytes = match opcode {
+        OP_PUSHDATA1 => 1,
+        OP_PUSHDATA2 => 2,
+        OP_PUSHDATA4 => 4,
+        _ => 0,
+    };
+    let len = if len_bytes == 0 {
+        opcode as usize
+    } else {
+        let Some(raw) = script.get(*pc..*pc + len_bytes) else {
+            *pc = script.len();
+            return Some(Err(()));
+        };
+        *pc += len_bytes;
+        raw.iter()
+            .rev()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize)
+    };
+    match script.get(*pc..pc.saturating_add(len)) {
+        Some(data) => {
+            *pc += len;
+            Some(Ok(Op {
+                opcode,
+                push: Some(data),
+            }))
+        }
+        None => {
+            *pc = script.len();
+            Some(Err(()))
+        }
+    }
+}
+
+fn decode_small_int(op: u8) -> Option<u32> {
+    match op {
+        OP_0 => Some(0),
+        OP_1..=OP_16 => Some(u32::from(op - OP_1 + 1)),
+        _ => None,
+    }
+}
+
+/// `CScript::GetSigOpCount(fAccurate)`
+pub fn sigop_count(script: &[u8], accurate: bool) -> u32 {
+    let mut n = 0;
+    let mut last = 0xffu8; // OP_INVALIDOPCODE
+    let mut pc = 0;
+    while let Some(Ok(op)) = next_op(script, &mut pc) {
+        match op.opcode {
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => n += 1,
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                n += match decode_small_int(last) {
+                    Some(k) if accurate && last != OP_0 => k,
+                    _ => MAX_PUBKEYS_PER_MULTISIG,
+                }
+            }
+            _ => {}
+        }
+        last = op.opcode;
+    }
+    n
+}
+
+/// Net stack effect `(pops, pushes)` of opcodes the static estimate models; `None` otherwise.
+/// The main and alt stacks count together (as the 1000-item limit does).
+fn stack_effect(op: u8) -> Option<(i64, i64)> {
+    Some(match op {
+        0x00..=OP_PUSHDATA4 | 0x4f | OP_1..=OP_16 | 0x74 => (0, 1),
+        0x61 | 0x67 | 0x68 | 0x6b | 0x6c | 0xab | 0xb0..=0xb9 => (0, 0),
+        OP_IF | OP_NOTIF | 0x69 | 0x75 => (1, 0),
+        0x6d => (2, 0),
+        0x6e => (2, 4),
+        0x6f => (3, 6),
+        0x70 => (4, 6),
+        0x71 => (6, 6),
+        0x72 => (4, 4),
+        0x73 | 0x76 | 0x82 => (1, 2),
+        0x77 => (2, 1),
+        0x78 | 0x7d => (2, 3),
+        0x79 => (2, 2),
+        0x7a => (2, 1),
+        0x7b => (3, 3),
+        0x7c => (2, 2),
+        0x8b..=0x92 | 0xa6..=0xaa => (1, 1),
+        OP_EQUAL | 0x93..=0x9c | 0x9e..=0xa4 | OP_CHECKSIG => (2, 1),
+        0x88 | 0x9d | OP_CHECKSIGVERIFY => (2, 0),
+        0xa5 => (3, 1),
+        _ => return None,
+    })
+}
+
+/// A limit that forces the script to fail whatever the stack holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitFailure {
+    ScriptSize,
+    PushSize,
+    OpCount,
+    DisabledOpcode,
+    /// More than 1000 items on stack + altstack (only predicted for straight-line scripts)
+    StackSize,
+    /// Truncated push (`SCRIPT_ERR_BAD_OPCODE`)
+    BadOpcode,
+}
+
+/// Resource usage of one script, counted as `EvalScript` counts it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScriptUsage {
+    pub size: usize,
+    /// Opcodes above OP_16. Counted in unexecuted branches too, so exact.
+    pub op_count: u32,
+    /// Key counts CHECKMULTISIG adds to `op_count` when it executes (from a preceding OP_n)
+    pub multisig_key_ops: u32,
+    pub max_push: usize,
+    pub pushes: u32,
+    pub has_conditionals: bool,
+    pub disabled_opcodes: u32,
+    pub truncated: bool,
+    /// Peak stack growth relative to the starting stack (static, both branches taken)
+    pub stack_peak_delta: i64,
+    /// Net stack change over the whole script
+    pub stack_net_delta: i64,
+    /// Every opcode had a modelled stack effect; otherwise the stack numbers are lower bounds
+    pub stack_model_complete: bool,
+}
+
+impl ScriptUsage {
+    pub fn measure(script: &[u8]) -> Self {
+        let mut u = ScriptUsage {
+            size: script.len(),
+            stack_model_complete: true,
+            ..Default::default()
+        };
+        let mut depth = 0i64;
+        let mut last = 0xffu8;
+        let mut pc = 0;
+        while let Some(op) = next_op(script, &mut pc) {
+            let Ok(op) = op else {
+                u.truncated = true;
+                break;
+            };
+            if let Some(data) = op.push {
+                u.pushes += 1;
+                u.max_push = u.max_push.max(data.len());
+            }
+            if op.opcode > OP_16 {
+                u.op_count += 1;
+            }
+            if is_disabled(op.opcode) {
+                u.disabled_opcodes += 1;
+            }
+            if matches!(op.opcode, OP_IF | OP_NOTIF) {
+                u.has_conditionals = true;
+            }
+            if matches!(op.opcode, OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY) {
+                if let Some(k) = decode_small_int(last) {
+                    u.multisig_key_ops += k;
+                }
+            }
+            match stack_effect(op.opcode) {
+                Some((pops, pushes)) => {
+                    depth = depth - pops + pushes;
+                    u.stack_peak_delta = u.stack_peak_delta.max(depth);
+                }
+                None => u.stack_model_complete = false,
+            }
+            last = op.opcode;
+        }
+        u.stack_net_delta = depth;
+        u
+    }
+
+    /// A limit that fails this script unconditionally, if any.
+    ///
+    /// Multisig key ops only count toward the limit for straight-line scripts, where every
+    /// CHECKMULTISIG is known to execute.
+    pub fn expected_failure(&self) -> Option<LimitFailure> {
+        if self.size > MAX_SCRIPT_SIZE {
+            Some(LimitFailure::ScriptSize)
+        } else if self.max_push > MAX_SCRIPT_ELEMENT_SIZE {
+            Some(LimitFailure::PushSize)
+        } else if self.op_count > MAX_OPS_PER_SCRIPT
+            || (!self.has_conditionals
+                && self.op_count + self.multisig_key_ops > MAX_OPS_PER_SCRIPT)
+        {
+            Some(LimitFailure::OpCount)
+        } else if self.disabled_opcodes > 0 {
+            Some(LimitFailure::DisabledOpcode)
+        } else if self.truncated {
+            Some(LimitFailure::BadOpcode)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) fn is_p2sh(script_pubkey: &[u8]) -> bool {
+    script_pubkey.len() == 23
+        && script_pubkey[0] == OP_HASH160
+        && script_pubkey[1] == 0x14
+        && script_pubkey[22] == OP_EQUAL
+}
+
+/// Last push of a push-only scriptSig (the P2SH redeem script).
+pub(crate) fn last_push(script_sig: &[u8]) -> Option<&[u8]> {
+    let mut last = None;
+    let mut pc = 0;
+    while let Some(op) = next_op(script_sig, &mut pc) {
+        let op = op.ok()?;
+        if op.opcode > OP_16 {
+            return None;
+        }
+        last = op.push.or(Some(&[]));
+    }
+    last
+}
+
+/// Resource usage of every script an input executes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputResources {
+    pub script_sig: ScriptUsage,
+    pub script_pubkey: ScriptUsage,
+    /// P2SH redeem script, when the prevout is P2SH and the scriptSig is push-only
+    pub redeem_script: Option<ScriptUsage>,
+    /// `GetLegacySigOpCount` over scriptSig + scriptPubKey
+    pub legacy_sigops: u32,
+    /// `GetP2SHSigOpCount` (accurate count of the redeem script)
+    pub p2sh_sigops: u32,
+    /// Estimated peak stack depth across the scripts (scriptSig stack carries over)
+    pub stack_peak_estimate: i64,
+}
+
+impl InputResources {
+    pub fn measure(script_sig: &[u8], script_pubkey: &[u8]) -> Self {
+        let sig = ScriptUsage::measure(script_sig);
+        let spk = ScriptUsage::measure(script_pubkey);
+        let redeem = is_p2sh(script_pubkey)
+            .then(|| last_push(script_sig))
+            .flatten();
+        let redeem_usage = redeem.map(ScriptUsage::measure);
+
+        let after_sig = sig.stack_net_delta.max(0);
+        let mut peak = sig.stack_peak_delta.max(after_sig + spk.stack_peak_delta);
+        if let Some(r) = &redeem_usage {
+            // The redeem script runs on the scriptSig stack minus the serialized script itself.
+            peak = peak.max(after_sig - 1 + r.stack_peak_delta);
+        }
+
+        Self {
+            legacy_sigops: sigop_count(script_sig, false) + sigop_count(script_pubkey, false),
+            p2sh_sigops: redeem.map_or(0, |r| sigop_count(r, true)),
+            stack_peak_estimate: peak,
+            script_sig: sig,
+            script_pubkey: spk,
+            redeem_script: redeem_usage,
+        }
+    }
+
+    fn scripts(&self) -> impl Iterator<Item = &ScriptUsage> {
+        [&self.script_sig, &self.script_pubkey]
+            .into_iter()
+            .chain(self.redeem_script.as_ref())
+    }
+
+    /// Limit failure in any executed script (each is checked by its own `EvalScript` call).
+    pub fn expected_failure(&self) -> Option<LimitFailure> {
+        if let Some(f) = self.scripts().find_map(ScriptUsage::expected_failure) {
+            return Some(f);
+        }
+        // Only trust the stack estimate when every opcode was modelled and no branch was skipped.
+        let exact_stack = self
+            .scripts()
+            .all(|s| s.stack_model_complete && !s.has_conditionals);
+        (exact_stack && self.stack_peak_estimate > MAX_STACK_SIZE)
+            .then_some(LimitFailure::StackSize)
+    }
+
+    pub fn max_op_count(&self) -> u32 {
+        self.scripts().map(|s| s.op_count).max().unwrap_or(0)
+    }
+
+    pub fn max_push(&self) -> usize {
+        self.scripts().map(|s| s.max_push).max().unwrap_or(0)
+    }
+
+    /// Near a limit or structurally unusual - worth running both verifiers on.
+    pub fn is_complex(&self) -> bool {
+        self.max_op_count() >= MAX_OPS_PER_SCRIPT / 2
+            || self.max_push() >= MAX_SCRIPT_ELEMENT_SIZE - 20
+            || self.stack_peak_estimate >= MAX_STACK_SIZE / 2
+            || self.legacy_sigops + self.p2sh_sigops >= MAX_PUBKEYS_PER_MULTISIG
+            || self
+                .scripts()
+                .any(|s| s.has_conditionals || s.disabled_opcodes > 0 || s.truncated)
+            || self.expected_failure().is_some()
+    }
+}
+
+/// How the verifiers' verdicts relate to the limit-derived expectation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceDivergence {
+    /// BLVM accepted an input that breaks a limit and Core rejected
+    BlvmAcceptedOverLimit,
+    /// Core accepted an input this module expects to fail and BLVM rejected it
+    CoreAcceptedOverLimit,
+    /// Both accepted an input this module expects to fail: the reference counting here is off,
+    /// not either engine
+    ResourceModelWrong,
+    /// BLVM and Core disagree on an input near a limit
+    VerdictMismatch,
+}
+
+/// One sampled input: counted resources, expectation and both verdicts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceComparison {
+    pub tx_hash: Option<String>,
+    pub input_index: usize,
+    pub resources: InputResources,
+    pub expected_failure: Option<LimitFailure>,
+    pub blvm_result: bool,
+    pub core_result: bool,
+    pub divergence: Option<ResourceDivergence>,
+}
+
+/// Run both verifiers on one input already measured as `resources`.
+pub fn compare_input_resources(
+    prevout_values: &[i64],
+    prevout_script_pubkeys: &[&[u8]],
+    script_sig: &ByteString,
+    tx: &Transaction,
+    input_index: usize,
+    consensus_flags: u32,
+    resources: &InputResources,
+) -> Result<ResourceComparison> {
+    let verdict = compare_script_verification(
+        prevout_values,
+        prevout_script_pubkeys,
+        script_sig,
+        tx,
+        input_index,
+        consensus_flags,
+    )?;
+    let expected_failure = resources.expected_failure();
+    let divergence = classify_divergence(
+        expected_failure.is_some(),
+        verdict.blvm_result,
+        verdict.core_result,
+    );
+
+    Ok(ResourceComparison {
+        tx_hash: verdict.tx_hash,
+        input_index,
+        resources: resources.clone(),
+        expected_failure,
+        blvm_result: verdict.blvm_result,
+        core_result: verdict.core_result,
+        divergence,
+    })
+}
+
+/// Divergence for the verdicts given whether the limits alone force a failure; Core's verdict is
+/// checked first so an over-limit input both engines accept blames the resource model.
+fn classify_divergence(
+    over_limit: bool,
+    blvm_result: bool,
+    core_result: bool,
+) -> Option<ResourceDivergence> {
+    match (over_limit, blvm_result, core_result) {
+        (true, true, true) => Some(ResourceDivergence::ResourceModelWrong),
+        (true, false, true) => Some(ResourceDivergence::CoreAcceptedOverLimit),
+        (true, true, false) => Some(ResourceDivergence::BlvmAcceptedOverLimit),
+        _ if blvm_result != core_result => Some(ResourceDivergence::VerdictMismatch),
+        _ => None,
+    }
+}
+
+/// Measure every input of `tx` and compare the complex ones (all of them with `sample_all`).
+pub fn compare_tx_resources(
+    prevout_values: &[i64],
+    prevout_script_pubkeys: &[&[u8]],
+    tx: &Transaction,
+    consensus_flags: u32,
+    sample_all: bool,
+    summary: &mut ResourceSummary,
+) -> Result<()> {
+    anyhow::ensure!(
+        prevout_script_pubkeys.len() == tx.inputs.len(),
+        "prevout scripts ({}) must match input count {}",
+        prevout_script_pubkeys.len(),
+        tx.inputs.len()
+    );
+    for (input_index, input) in tx.inputs.iter().enumerate() {
+        let resources =
+            InputResources::measure(&input.script_sig, prevout_script_pubkeys[input_index]);
+        let comparison = if sample_all || resources.is_complex() {
+            Some(compare_input_resources(
+                prevout_values,
+                prevout_script_pubkeys,
+                &input.script_sig,
+                tx,
+                input_index,
+                consensus_flags,
+                &resources,
+            )?)
+        } else {
+            None
+        };
+        if let Some(c) = comparison.as_ref().filter(|c| c.divergence.is_some()) {
+            eprintln!(
+                "[script_resources] {:?} tx {} input {}: expected {:?}, core={} blvm={} \
+                 (ops={} push={} sigops={} stack~{})",
+                c.divergence.unwrap(),
+                c.tx_hash.as_deref().unwrap_or("?"),
+                input_index,
+                c.expected_failure,
+                c.core_result,
+                c.blvm_result,
+                resources.max_op_count(),
+                resources.max_push(),
+                resources.legacy_sigops + resources.p2sh_sigops,
+                resources.stack_peak_estimate
+            );
+        }
+        summary.record(&resources, comparison);
+    }
+    Ok(())
+}
+
+/// Running totals over many inputs; divergences are kept in full, capped at `max_kept`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceSummary {
+    pub inputs_measured: u64,
+    pub inputs_compared: u64,
+    pub expected_failures: u64,
+    pub max_op_count: u32,
+    pub max_push: usize,
+    pub max_sigops: u32,
+    pub max_stack_estimate: i64,
+    pub divergences: Vec<ResourceComparison>,
+    pub divergences_dropped: u64,
+    pub max_kept: usize,
+}
+
+impl ResourceSummary {
+    pub fn new(max_kept: usize) -> Self {
+        Self {
+            max_kept,
+            ..Default::default()
+        }
+    }
+
+    /// Record a measured input and its comparison, if it was sampled.
+    pub fn record(&mut self, resources: &InputResources, comparison: Option<ResourceComparison>) {
+        self.inputs_measured += 1;
+        self.max_op_count = self.max_op_count.max(resources.max_op_count());
+        self.max_push = self.max_push.max(resources.max_push());
+        self.max_sigops = self
+            .max_sigops
+            .max(resources.legacy_sigops + resources.p2sh_sigops);
+        self.max_stack_estimate = self.max_stack_estimate.max(resources.stack_peak_estimate);
+
+        let Some(c) = comparison else { return };
+        self.inputs_compared += 1;
+        if c.expected_failure.is_some() {
+            self.expected_failures += 1;
+        }
+        if c.divergence.is_some() {
+            if self.divergences.len() < self.max_kept {
+                self.divergences.push(c);
+            } else {
+                self.divergences_dropped += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(data: &[u8]) -> Vec<u8> {
+        let mut s = Vec::new();
+        match data.len() {
+            n if n < OP_PUSHDATA1 as usize => s.push(n as u8),
+            n if n <= 0xff => s.extend([OP_PUSHDATA1, n as u8]),
+            n => {
+                s.push(OP_PUSHDATA2);
+                s.extend((n as u16).to_le_bytes());
+            }
+        }
+        s.extend_from_slice(data);
+        s
+    }
+
+    #[test]
+    fn sigops_follow_get_sig_op_count() {
+        // 2-of-3 bare multisig
+        let mut ms = vec![0x52];
+        for _ in 0..3 {
+            ms.extend(push(&[0x02; 33]));
+        }
+        ms.extend([0x53, OP_CHECKMULTISIG]);
+        assert_eq!(sigop_count(&ms, false), 20);
+        assert_eq!(sigop_count(&ms, true), 3);
+        // OP_0 before CHECKMULTISIG counts as 20 even when accurate
+        assert_eq!(sigop_count(&[OP_0, OP_CHECKMULTISIG], true), 20);
+        assert_eq!(sigop_count(&[OP_CHECKSIG, OP_CHECKSIGVERIFY], false), 2);
+
+        // P2SH: redeem script counted accurately from the scriptSig's last push
+        let mut spk = vec![OP_HASH160, 0x14];
+        spk.extend([0u8; 20]);
+        spk.push(OP_EQUAL);
+        let mut sig = vec![OP_0];
+        sig.extend(push(&ms));
+        let r = InputResources::measure(&sig, &spk);
+        assert_eq!(r.p2sh_sigops, 3);
+        assert_eq!(r.legacy_sigops, 0);
+        assert_eq!(r.redeem_script.as_ref().unwrap().multisig_key_ops, 3);
+    }
+
+    #[test]
+    fn divergence_checks_core_before_blaming_blvm() {
+        use ResourceDivergence::*;
+        assert_eq!(
+            classify_divergence(true, true, true),
+            Some(ResourceModelWrong)
+        );
+        assert_eq!(
+            classify_divergence(true, true, false),
+            Some(BlvmAcceptedOverLimit)
+        );
+        assert_eq!(
+            classify_divergence(true, false, true),
+            Some(CoreAcceptedOverLimit)
+        );
+        assert_eq!(classify_divergence(true, false, false), None);
+        assert_eq!(
+            classify_divergence(false, true, false),
+            Some(VerdictMismatch)
+        );
+        assert_eq!(classify_divergence(false, true, true), None);
+    }
+
+    #[test]
+    fn limits_sit_on_the_boundary() {
+        let at_limit = vec![0x61; MAX_OPS_PER_SCRIPT as usize]; // OP_NOP x 201
+        assert_eq!(ScriptUsage::measure(&at_limit).expected_failure(), None);
+        let over = vec![0x61; MAX_OPS_PER_SCRIPT as usize + 1];
+        assert_eq!(
+            ScriptUsage::measure(&over).expected_failure(),
+            Some(LimitFailure::OpCount)
+        );
+        // Opcodes in an unexecuted branch still count.
+        let mut branch = vec![OP_0, OP_IF];
+        branch.extend(vec![0x61; 200]);
+        branch.push(0x68);
+        assert_eq!(ScriptUsage::measure(&branch).op_count, 202);
+
+        assert_eq!(
+            ScriptUsage::measure(&push(&[0; 520])).expected_failure(),
+            None
+        );
+        assert_eq!(
+            ScriptUsage::measure(&push(&[0; 521])).expected_failure(),
+            Some(LimitFailure::PushSize)
+        );
+        assert_eq!(
+            ScriptUsage::measure(&[OP_PUSHDATA1, 5, 1]).expected_failure(),
+            Some(LimitFailure::BadOpcode)
+        );
+        assert_eq!(
+            ScriptUsage::measure(&[OP_0, OP_0, 0x7e]).expected_failure(),
+            Some(LimitFailure::DisabledOpcode)
+        );
+
+        // 1001 pushes of OP_1 overflow the stack in straight-line code.
+        let deep = InputResources::measure(&[], &vec![OP_1; 1_001]);
+        assert_eq!(deep.stack_peak_estimate, 1_001);
+        assert_eq!(deep.expected_failure(), Some(LimitFailure::StackSize));
+    }
+}
@@ -1,14 +1,32 @@
 //! Shell benchmark runner
 //!
 //! This module provides functionality to run shell-based benchmarks
-//! from the benchmarks/ directory.
+//! from the benchmarks/ directory, and registers them with the
+//! [`BenchmarkRegistry`](crate::registry::BenchmarkRegistry).
+//!
+//! Suites can also be described in a TOML file ([`SuiteConfig`]): one command per benchmark
+//! with its environment, warmup and measured iterations, timeout and expected exit code. A
+//! benchmark that runs past its timeout is killed and recorded as failed; the rest of the suite
+//! still runs.
+//!
+//! Every benchmark process is waited for with [`platform::wait_with_usage`], so results carry
+//! its peak RSS, user/system CPU time and I/O counters alongside the wall-clock time.
 
+use crate::platform::{self, ProcessUsage};
+use crate::registry::{BenchFilter, Benchmark, BenchmarkRegistry, RunReport};
 use crate::utils;
 use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
-/// Run a specific shell benchmark
-pub fn run_benchmark(script: &str) -> Result<()> {
+/// How often a command with a timeout is checked for exit (also the most it adds to a timing)
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Run a specific shell benchmark, returning the script's resource usage
+pub fn run_benchmark(script: &str) -> Result<ProcessUsage> {
     let benchmarks_dir = utils::benchmarks_dir();
 
     // If script doesn't have .sh extension, try adding it
@@ -25,24 +43,19 @@ pub fn run_benchmark(script: &str) -> Result<()> {
     }
 
     // Make sure script is executable
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        if let Ok(mut perms) = std::fs::metadata(&script_path).map(|m| m.permissions()) {
-            perms.set_mode(0o755);
-            let _ = std::fs::set_permissions(&script_path, perms);
-        }
-    }
+    crate::platform::make_executable(&script_path);
 
     println!("Executing: {}", script_path.display());
 
-    let status = Command::new("bash")
+    let mut child = Command::new("bash")
         .arg(&script_path)
         .current_dir(&benchmarks_dir)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
-        .status()
+        .spawn()
         .with_context(|| format!("Failed to run benchmark: {}", script))?;
+    let (status, usage) = platform::wait_with_usage(&mut child)
+        .with_context(|| format!("Failed to wait for benchmark: {}", script))?;
 
     if !status.success() {
         anyhow::bail!(
@@ -51,11 +64,58 @@ pub fn run_benchmark(script: &str) -> Result<()> {
         );
     }
 
-    println!("✅ Benchmark completed: {}", script_name);
+    println!(
+        "✅ Benchmark completed: {} ({})",
+        script_name,
+        usage.summary()
+    );
+    Ok(usage)
+}
+
+/// Main suite runners, in order of preference
+const SUITE_SCRIPTS: &[&str] = &[
+    "run-all-fair-fast-benchmarks.sh",
+    "comprehensive-suite.sh",
+    "run-all.sh",
+];
+
+/// `.sh` scripts in the benchmarks directory, sorted by name.
+fn scripts(benchmarks_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(benchmarks_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| name.ends_with(".sh"))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Register every script in the benchmarks directory as `shell/<script>`, tagged `shell`; the
+/// preferred suite runner is also tagged `suite`.
+pub fn register(registry: &mut BenchmarkRegistry) -> Result<()> {
+    let benchmarks_dir = utils::benchmarks_dir();
+    let scripts = scripts(&benchmarks_dir);
+    let suite = SUITE_SCRIPTS
+        .iter()
+        .copied()
+        .find(|s| scripts.iter().any(|n| n == s));
+    for script in &scripts {
+        let name = script.clone();
+        let mut bench =
+            Benchmark::process(format!("shell/{}", script), move || run_benchmark(&name))
+                .with_tag("shell");
+        if suite == Some(script.as_str()) {
+            bench = bench.with_tag("suite");
+        }
+        registry.register(bench)?;
+    }
     Ok(())
 }
 
-/// Run all shell benchmarks
+/// Run the suite runner through the registry, recording its timing in the results directory
 pub fn run_all() -> Result<()> {
     let benchmarks_dir = utils::benchmarks_dir();
 
@@ -71,37 +131,239 @@ pub fn run_all() -> Result<()> {
         benchmarks_dir.display()
     );
 
-    // Look for main suite runner scripts
-    let suite_scripts = [
-        "run-all-fair-fast-benchmarks.sh",
-        "comprehensive-suite.sh",
-        "run-all.sh",
-    ];
-
-    let mut found = false;
-    for script in &suite_scripts {
-        let script_path = benchmarks_dir.join(script);
-        if script_path.exists() {
-            println!("Running suite: {}", script);
-            run_benchmark(script)?;
-            found = true;
-            break;
+    let mut registry = BenchmarkRegistry::new();
+    register(&mut registry)?;
+    let filter = BenchFilter::tag("suite");
+    if registry.filter(&filter).next().is_none() {
+        println!("No suite runner found. Available scripts:");
+        for name in scripts(&benchmarks_dir) {
+            println!("  - {}", name);
         }
+        return Ok(());
     }
 
-    if !found {
-        println!("No suite runner found. Available scripts:");
-        // List available scripts
-        if let Ok(entries) = std::fs::read_dir(&benchmarks_dir) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.ends_with(".sh") && entry.path().is_file() {
-                        println!("  - {}", name);
+    let report = registry.run(&filter);
+    let path = report.save()?;
+    println!("📝 Timings written to {}", path.display());
+    report.ensure_passed()
+}
+
+/// A benchmark suite file.
+///
+/// ```toml
+/// name = "fair-fast"
+/// dir = "../.."                 # working directory, relative to this file
+/// env = { RESULTS_DIR = "results" }
+///
+/// [[benchmark]]
+/// name = "core-block-validation"
+/// command = "scripts/core/block-validation-bench.sh"
+/// warmup = 1
+/// iterations = 3
+/// timeout_secs = 300
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteConfig {
+    pub name: String,
+    /// Working directory of every command; relative paths are resolved against the suite file
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Environment for every command; a benchmark's own `env` wins
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, rename = "benchmark")]
+    pub benchmarks: Vec<SuiteBenchmark>,
+}
+
+/// One benchmark of a [`SuiteConfig`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteBenchmark {
+    pub name: String,
+    /// Run with `bash -c`
+    pub command: String,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Untimed runs before the measured ones
+    #[serde(default)]
+    pub warmup: usize,
+    /// Timed runs
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+    /// Limit for each run; unset means no limit
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub expected_exit_code: i32,
+}
+
+fn default_iterations() -> usize {
+    1
+}
+
+impl SuiteConfig {
+    /// Parse `path`, resolving `dir` against the file's directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read suite {}", path.display()))?;
+        let mut config: Self =
+            toml::from_str(&text).with_context(|| format!("Invalid suite {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        config.dir = Some(match config.dir.take() {
+            Some(dir) => base.join(dir),
+            None => base.to_path_buf(),
+        });
+        Ok(config)
+    }
+
+    /// Register every benchmark as `<suite>/<benchmark>`, tagged `shell` and the suite name.
+    /// Warmup runs are the benchmark's setup step, so they are not timed.
+    pub fn register(&self, registry: &mut BenchmarkRegistry) -> Result<()> {
+        let dir = self.dir.clone().unwrap_or_else(|| PathBuf::from("."));
+        for bench in &self.benchmarks {
+            let mut bench = bench.clone();
+            let mut env = self.env.clone();
+            env.append(&mut bench.env);
+            bench.env = env;
+
+            let (warmup_bench, warmup_dir) = (bench.clone(), dir.clone());
+            let (run_bench, run_dir) = (bench.clone(), dir.clone());
+            registry.register(
+                Benchmark::process(format!("{}/{}", self.name, bench.name), move || {
+                    run_command(&run_bench, &run_dir)
+                })
+                .with_tag("shell")
+                .with_tag(self.name.clone())
+                .with_iterations(bench.iterations)
+                .with_setup(move || {
+                    for _ in 0..warmup_bench.warmup {
+                        run_command(&warmup_bench, &warmup_dir)?;
                     }
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `bench.command` once in `dir`, killing it (and whatever it started) at the timeout.
+fn run_command(bench: &SuiteBenchmark, dir: &Path) -> Result<ProcessUsage> {
+    let mut cmd = Command::new("bash");
+    cmd.arg("-c")
+        .arg(&bench.command)
+        .current_dir(dir)
+        .envs(&bench.env)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    platform::new_process_group(&mut cmd);
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run {}: {}", bench.name, bench.command))?;
+
+    let (status, usage) = match bench.timeout_secs {
+        None => platform::wait_with_usage(&mut child)?,
+        Some(secs) => {
+            let deadline = Instant::now() + Duration::from_secs(secs);
+            loop {
+                if let Some(done) = platform::try_wait_with_usage(&mut child)? {
+                    break done;
                 }
+                if Instant::now() >= deadline {
+                    platform::kill_process_group(&mut child);
+                    let _ = child.wait();
+                    anyhow::bail!("{} timed out after {}s", bench.name, secs);
+                }
+                std::thread::sleep(POLL_INTERVAL);
             }
         }
+    };
+
+    if status.code() != Some(bench.expected_exit_code) {
+        anyhow::bail!(
+            "{} exited with {} (expected exit code {})",
+            bench.name,
+            status,
+            bench.expected_exit_code
+        );
     }
+    Ok(usage)
+}
 
-    Ok(())
+/// Run every benchmark of the suite file at `path` and save the timings to the results directory
+pub fn run_suite(path: &Path) -> Result<RunReport> {
+    let config = SuiteConfig::load(path)?;
+    println!(
+        "Running suite {} ({} benchmarks) from {}",
+        config.name,
+        config.benchmarks.len(),
+        path.display()
+    );
+    let mut registry = BenchmarkRegistry::new();
+    config.register(&mut registry)?;
+    let report = registry.run(&BenchFilter::default());
+    report.print_summary();
+    let saved = report.save()?;
+    println!("📝 Timings written to {}", saved.display());
+    Ok(report)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suite_config_runs_with_timeouts_and_exit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let suite = dir.path().join("suite.toml");
+        std::fs::write(
+            &suite,
+            r#"
+name = "demo"
+env = { GREETING = "suite" }
+
+[[benchmark]]
+name = "counted"
+command = "echo $GREETING >> runs.log"
+env = { GREETING = "bench" }
+warmup = 2
+iterations = 3
+
+[[benchmark]]
+name = "hangs"
+command = "sleep 30"
+timeout_secs = 1
+
+[[benchmark]]
+name = "exits-3"
+command = "exit 3"
+expected_exit_code = 3
+"#,
+        )
+        .unwrap();
+
+        let config = SuiteConfig::load(&suite).unwrap();
+        assert_eq!(config.dir.as_deref(), Some(dir.path()));
+        let mut registry = BenchmarkRegistry::new();
+        config.register(&mut registry).unwrap();
+
+        let started = Instant::now();
+        let report = registry.run(&BenchFilter::default());
+        assert!(started.elapsed() < Duration::from_secs(20));
+
+        let runs = std::fs::read_to_string(dir.path().join("runs.log")).unwrap();
+        assert_eq!(runs, "bench\n".repeat(5));
+        assert_eq!(report.results[0].timings_secs.len(), 3);
+        assert_eq!(report.results[0].usage.len(), 3);
+        assert!(report.results[0].peak_rss_bytes().unwrap() > 0);
+        let failed: Vec<&str> = report.failed().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(failed, ["demo/hangs"]);
+        assert!(report.results[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("timed out"));
+
+        assert!(toml::from_str::<SuiteConfig>("name = \"x\"\ntimeout = 1\n").is_err());
+    }
 }
@@ -0,0 +1,186 @@
+//! Graceful shutdown on SIGINT / SIGTERM
+//!
+//! Without a handler, Ctrl-C or a `kill` during block collection ends the process wherever it
+//! is, possibly half-way through a buffered write to the temp file. [`ShutdownController`] turns
+//! the first SIGINT / SIGTERM into a cancellation of its [`CancellationToken`] instead:
+//!
+//! - collection stops at the next block boundary
+//! - the temp file is flushed and fsynced, and its block count committed to the metadata store
+//! - a [`ResumeMarker`] (`<temp file>.resume.json`) records where it stopped and why
+//! - the command prints a summary and exits with status 130
+//!
+//! A second signal exits at once, for when the next safe boundary is too far away. The next
+//! collection reads the marker, reports what it resumes from and removes it once it completes.
+//! Signals are only hooked on Unix; elsewhere the default handling stays.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::cancel::CancellationToken;
+
+/// Exit status after a graceful stop (128 + SIGINT, as a shell reports Ctrl-C)
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static CONTROLLER: OnceLock<ShutdownController> = OnceLock::new();
+static SIGNAL: OnceLock<&'static str> = OnceLock::new();
+
+/// Process-wide SIGINT / SIGTERM handler cancelling one token.
+pub struct ShutdownController {
+    token: CancellationToken,
+}
+
+impl ShutdownController {
+    /// Hook SIGINT and SIGTERM for the rest of the process. Later calls return the same
+    /// controller.
+    pub fn install() -> Result<&'static Self> {
+        if let Some(controller) = CONTROLLER.get() {
+            return Ok(controller);
+        }
+        let token = CancellationToken::new();
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGINT, SIGTERM};
+
+            let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])
+                .context("Failed to install SIGINT / SIGTERM handlers")?;
+            let cancel = token.clone();
+            std::thread::Builder::new()
+                .name("shutdown-signals".to_string())
+                .spawn(move || {
+                    for signal in signals.forever() {
+                        let name = if signal == SIGTERM {
+                            "SIGTERM"
+                        } else {
+                            "SIGINT"
+                        };
+                        if SIGNAL.set(name).is_err() {
+                            eprintln!("\n🛑 {} again - exiting without cleanup", name);
+                            std::process::exit(128 + signal);
+                        }
+                        eprintln!(
+                            "\n🛑 {} - stopping at the next safe point (send again to exit now)",
+                            name
+                        );
+                        cancel.cancel();
+                    }
+                })?;
+        }
+        Ok(CONTROLLER.get_or_init(|| Self { token }))
+    }
+
+    /// Cancelled on the first signal.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+/// The signal that started a shutdown (`SIGINT` / `SIGTERM`), if one did.
+pub fn signal_received() -> Option<&'static str> {
+    SIGNAL.get().copied()
+}
+
+/// Where an interrupted collection stopped; written next to its temp file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeMarker {
+    /// RFC 3339
+    pub stopped_at: String,
+    /// `SIGINT`, `SIGTERM`, the disk watchdog's reason or `cancelled` (token cancelled by the
+    /// caller)
+    pub reason: String,
+    /// Blocks in the temp file, all flushed and synced
+    pub blocks: u64,
+    /// Block files fully processed; the next run starts at the last batch checkpoint
+    pub files_processed: usize,
+    pub temp_file: PathBuf,
+}
+
+impl ResumeMarker {
+    /// Marker for a collection stopping now.
+    pub fn new(temp_file: &Path, blocks: u64, files_processed: usize) -> Self {
+        Self {
+            stopped_at: chrono::Utc::now().to_rfc3339(),
+            reason: signal_received()
+                .or_else(crate::disk_watchdog::stop_reason)
+                .unwrap_or("cancelled")
+                .to_string(),
+            blocks,
+            files_processed,
+            temp_file: temp_file.to_path_buf(),
+        }
+    }
+
+    pub fn path_for(temp_file: &Path) -> PathBuf {
+        let mut name = temp_file.as_os_str().to_os_string();
+        name.push(".resume.json");
+        PathBuf::from(name)
+    }
+
+    /// Write the marker and fsync it (write to a temp name, then rename).
+    pub fn write(&self) -> Result<PathBuf> {
+        let path = Self::path_for(&self.temp_file);
+        let tmp = path.with_extension("json.tmp");
+        let file = std::fs::File::create(&tmp)
+            .with_context(|| format!("Failed to create {}", tmp.display()))?;
+        serde_json::to_writer_pretty(&file, self)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        Ok(path)
+    }
+
+    /// Marker left by an interrupted collection into `temp_file`, if any.
+    pub fn load(temp_file: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(temp_file);
+        match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .with_context(|| format!("Invalid resume marker {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Remove the marker of `temp_file` (no-op without one).
+    pub fn clear(temp_file: &Path) -> Result<()> {
+        match std::fs::remove_file(Self::path_for(temp_file)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Multi-line summary printed when a command stops on this marker.
+    pub fn summary(&self) -> String {
+        format!(
+            "🛑 Collection stopped ({}) at {}\n   {} blocks from {} block files are flushed and \
+             synced to {}\n   Run the same command again to resume",
+            self.reason,
+            self.stopped_at,
+            self.blocks,
+            self.files_processed,
+            self.temp_file.display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_marker_round_trips_next_to_the_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp_file = dir.path().join("blocks-temp.bin");
+        assert_eq!(ResumeMarker::load(&temp_file).unwrap(), None);
+
+        let marker = ResumeMarker::new(&temp_file, 12_345, 7);
+        let path = marker.write().unwrap();
+        assert_eq!(path, dir.path().join("blocks-temp.bin.resume.json"));
+        assert_eq!(ResumeMarker::load(&temp_file).unwrap(), Some(marker));
+
+        ResumeMarker::clear(&temp_file).unwrap();
+        assert_eq!(ResumeMarker::load(&temp_file).unwrap(), None);
+        ResumeMarker::clear(&temp_file).unwrap();
+    }
+}
@@ -0,0 +1,1140 @@
+//! Signature hash differential and benchmark
+//!
+//! Every signature check starts with a sighash, and the legacy algorithm is quadratic: each input
+//! re-serializes the whole transaction, so the July 2015 5,569-input transaction hashes over a
+//! gigabyte. Caching in BLVM's sighash code is only worth having if it stays byte-for-byte
+//! correct, so this module checks and times it in isolation:
+//!
+//! - reference implementations of the legacy (`SignatureHash` with its `SIGHASH_SINGLE` bug and
+//!   `OP_CODESEPARATOR` removal), BIP143 and BIP341 key-path algorithms, independent of
+//!   `blvm_protocol` like the ones in [`crate::wallet`]; [`check_sighash_vectors`] holds both the
+//!   legacy one and `calculate_transaction_sighash` to Core's `sighash.json`
+//! - [`SighashFuzzer`]: seeded transactions mixing P2PKH, P2WPKH and P2TR inputs under every hash
+//!   type, each input signed over the reference digest and verified with BLVM's interpreter (a
+//!   wrong sighash fails the signature); legacy digests are also compared with
+//!   `calculate_transaction_sighash` directly
+//! - [`compare_with_core`]: Core signs the same P2PKH / P2WPKH inputs with
+//!   `signrawtransactionwithkey` (no wallet or funds needed, any network), and each of its
+//!   signatures must verify against the reference digest and in BLVM
+//! - [`bench_quadratic`]: all-inputs `SIGHASH_ALL` over transactions of growing input counts,
+//!   per input and through `batch_compute_sighashes`, next to the bytes the legacy algorithm
+//!   hashes
+//!
+//! Taproot is covered by the fuzzer only: `signrawtransactionwithkey` cannot sign a key-path
+//! spend from a bare key.
+
+use anyhow::{Context, Result};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::serialization::transaction::serialize_transaction;
+use blvm_protocol::transaction_hash::{
+    batch_compute_sighashes, calculate_transaction_sighash, SighashType,
+};
+use blvm_protocol::types::{Network, OutPoint, Transaction, TransactionInput, TransactionOutput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use secp256k1::ecdsa::Signature;
+use secp256k1::{All, Keypair, Message, PublicKey, Secp256k1, SecretKey};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::conformance::{decode_tx, hash_type_tag, parse_sighash_row, Tally};
+use crate::node_rpc_client::NodeRpcClient;
+use crate::script_resources::next_op;
+use crate::wallet::{hash160, sha256, tagged_hash, taproot_tweak, write_bytes, write_outpoint};
+use crate::wire::{sha256d, write_compact_size};
+
+/// Mismatches kept in full in a [`SighashReport`].
+const MAX_KEPT_MISMATCHES: usize = 100;
+
+const SIGHASH_ALL: u32 = 0x01;
+const SIGHASH_NONE: u32 = 0x02;
+const SIGHASH_SINGLE: u32 = 0x03;
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+const OP_CODESEPARATOR: u8 = 0xab;
+
+/// Hash types of legacy and BIP143 inputs.
+const ECDSA_HASH_TYPES: [u8; 6] = [0x01, 0x02, 0x03, 0x81, 0x82, 0x83];
+/// BIP341 hash types, `SIGHASH_DEFAULT` first.
+const TAPROOT_HASH_TYPES: [u8; 7] = [0x00, 0x01, 0x02, 0x03, 0x81, 0x82, 0x83];
+
+/// P2SH, DERSIG, NULLDUMMY, CHECKLOCKTIMEVERIFY, CHECKSEQUENCEVERIFY, WITNESS and TAPROOT.
+const VERIFY_FLAGS: u32 = bitcoinconsensus::VERIFY_ALL_PRE_TAPROOT | (1 << 17);
+
+/// `OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG`
+fn p2pkh_script(public_key: &PublicKey) -> Vec<u8> {
+    let mut script = vec![0x76, 0xa9, 0x14];
+    script.extend(hash160(&public_key.serialize()));
+    script.extend([0x88, 0xac]);
+    script
+}
+
+/// The script code without its `OP_CODESEPARATOR`s, as Core's signature serializer writes it;
+/// an unparsable tail is kept as is.
+fn without_codeseparators(script: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(script.len());
+    let (mut pc, mut start) = (0, 0);
+    while let Some(Ok(op)) = next_op(script, &mut pc) {
+        if op.opcode == OP_CODESEPARATOR {
+            out.extend_from_slice(&script[start..pc - 1]);
+            start = pc;
+        }
+    }
+    out.extend_from_slice(&script[start..]);
+    out
+}
+
+fn write_output(out: &mut Vec<u8>, output: &TransactionOutput) {
+    out.extend(output.value.to_le_bytes());
+    write_bytes(out, &output.script_pubkey);
+}
+
+/// Legacy `SignatureHash` over the full 32-bit `hash_type`, including `SIGHASH_SINGLE`'s
+/// "one" digest for inputs without a matching output.
+pub fn legacy_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    hash_type: u32,
+) -> [u8; 32] {
+    let base = hash_type & 0x1f;
+    let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+    if base == SIGHASH_SINGLE && input_index >= tx.outputs.len() {
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        return one;
+    }
+    let script_code = without_codeseparators(script_code);
+    let mut preimage = Vec::new();
+    preimage.extend((tx.version as u32).to_le_bytes());
+    let signed: Vec<usize> = if anyone_can_pay {
+        vec![input_index]
+    } else {
+        (0..tx.inputs.len()).collect()
+    };
+    write_compact_size(&mut preimage, signed.len());
+    for j in signed {
+        let input = &tx.inputs[j];
+        write_outpoint(&mut preimage, &input.prevout);
+        let script: &[u8] = if j == input_index { &script_code } else { &[] };
+        write_bytes(&mut preimage, script);
+        let other_ignored = j != input_index && (base == SIGHASH_NONE || base == SIGHASH_SINGLE);
+        let sequence = if other_ignored {
+            0
+        } else {
+            input.sequence as u32
+        };
+        preimage.extend(sequence.to_le_bytes());
+    }
+    match base {
+        SIGHASH_NONE => preimage.push(0),
+        SIGHASH_SINGLE => {
+            write_compact_size(&mut preimage, input_index + 1);
+            for _ in 0..input_index {
+                // CTxOut(): value -1, empty script
+                preimage.extend((-1i64).to_le_bytes());
+                preimage.push(0);
+            }
+            write_output(&mut preimage, &tx.outputs[input_index]);
+        }
+        _ => {
+            write_compact_size(&mut preimage, tx.outputs.len());
+            for output in tx.outputs.iter() {
+                write_output(&mut preimage, output);
+            }
+        }
+    }
+    preimage.extend((tx.lock_time as u32).to_le_bytes());
+    preimage.extend(hash_type.to_le_bytes());
+    sha256d(&preimage)
+}
+
+/// BIP143 digest of a version 0 witness input spending `amount`.
+pub fn segwit_v0_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: i64,
+    hash_type: u32,
+) -> [u8; 32] {
+    let base = hash_type & 0x1f;
+    let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+    let mut hash_prevouts = [0u8; 32];
+    let mut hash_sequence = [0u8; 32];
+    let mut hash_outputs = [0u8; 32];
+    if !anyone_can_pay {
+        let mut outpoints = Vec::new();
+        for input in tx.inputs.iter() {
+            write_outpoint(&mut outpoints, &input.prevout);
+        }
+        hash_prevouts = sha256d(&outpoints);
+        if base != SIGHASH_NONE && base != SIGHASH_SINGLE {
+            let sequences: Vec<u8> = tx
+                .inputs
+                .iter()
+                .flat_map(|input| (input.sequence as u32).to_le_bytes())
+                .collect();
+            hash_sequence = sha256d(&sequences);
+        }
+    }
+    if base != SIGHASH_NONE && base != SIGHASH_SINGLE {
+        let mut outputs = Vec::new();
+        for output in tx.outputs.iter() {
+            write_output(&mut outputs, output);
+        }
+        hash_outputs = sha256d(&outputs);
+    } else if base == SIGHASH_SINGLE && input_index < tx.outputs.len() {
+        let mut output = Vec::new();
+        write_output(&mut output, &tx.outputs[input_index]);
+        hash_outputs = sha256d(&output);
+    }
+
+    let input = &tx.inputs[input_index];
+    let mut preimage = Vec::with_capacity(156 + script_code.len());
+    preimage.extend((tx.version as u32).to_le_bytes());
+    preimage.extend(hash_prevouts);
+    preimage.extend(hash_sequence);
+    write_outpoint(&mut preimage, &input.prevout);
+    write_bytes(&mut preimage, script_code);
+    preimage.extend(amount.to_le_bytes());
+    preimage.extend((input.sequence as u32).to_le_bytes());
+    preimage.extend(hash_outputs);
+    preimage.extend((tx.lock_time as u32).to_le_bytes());
+    preimage.extend(hash_type.to_le_bytes());
+    sha256d(&preimage)
+}
+
+/// BIP341 key-path digest (no annex); `None` for an invalid hash type or `SIGHASH_SINGLE`
+/// without a matching output. `prevouts[i]` is the `(value, scriptPubKey)` input `i` spends.
+pub fn taproot_key_path_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[(i64, Vec<u8>)],
+    hash_type: u8,
+) -> Option<[u8; 32]> {
+    if !TAPROOT_HASH_TYPES.contains(&hash_type) {
+        return None;
+    }
+    let output_type = if hash_type == 0 {
+        SIGHASH_ALL
+    } else {
+        u32::from(hash_type & 0x03)
+    };
+    let anyone_can_pay = u32::from(hash_type) & SIGHASH_ANYONECANPAY != 0;
+    let mut msg = Vec::with_capacity(206);
+    msg.push(0x00); // sighash epoch
+    msg.push(hash_type);
+    msg.extend((tx.version as u32).to_le_bytes());
+    msg.extend((tx.lock_time as u32).to_le_bytes());
+    if !anyone_can_pay {
+        let (mut outpoints, mut amounts, mut scripts, mut sequences) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for (input, (value, script_pubkey)) in tx.inputs.iter().zip(prevouts) {
+            write_outpoint(&mut outpoints, &input.prevout);
+            amounts.extend(value.to_le_bytes());
+            write_bytes(&mut scripts, script_pubkey);
+            sequences.extend((input.sequence as u32).to_le_bytes());
+        }
+        for data in [outpoints, amounts, scripts, sequences] {
+            msg.extend(sha256(&data));
+        }
+    }
+    if output_type == SIGHASH_ALL {
+        let mut outputs = Vec::new();
+        for output in tx.outputs.iter() {
+            write_output(&mut outputs, output);
+        }
+        msg.extend(sha256(&outputs));
+    }
+    msg.push(0x00); // key path, no annex
+    if anyone_can_pay {
+        let input = &tx.inputs[input_index];
+        let (value, script_pubkey) = &prevouts[input_index];
+        write_outpoint(&mut msg, &input.prevout);
+        msg.extend(value.to_le_bytes());
+        write_bytes(&mut msg, script_pubkey);
+        msg.extend((input.sequence as u32).to_le_bytes());
+    } else {
+        msg.extend((input_index as u32).to_le_bytes());
+    }
+    if output_type == SIGHASH_SINGLE {
+        let mut output = Vec::new();
+        write_output(&mut output, tx.outputs.get(input_index)?);
+        msg.extend(sha256(&output));
+    }
+    Some(tagged_hash("TapSighash", &msg))
+}
+
+/// Output type of a fuzzed input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputKind {
+    P2pkh,
+    P2wpkh,
+    P2tr,
+}
+
+impl InputKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            InputKind::P2pkh => "p2pkh",
+            InputKind::P2wpkh => "p2wpkh",
+            InputKind::P2tr => "p2tr",
+        }
+    }
+}
+
+/// `ALL`, `SINGLE|ANYONECANPAY`, ... (`DEFAULT` for taproot's 0).
+pub fn hash_type_name(hash_type: u8) -> String {
+    let base = match hash_type & 0x1f {
+        0 => "DEFAULT",
+        2 => "NONE",
+        3 => "SINGLE",
+        _ => "ALL",
+    };
+    if hash_type & 0x80 != 0 {
+        format!("{}|ANYONECANPAY", base)
+    } else {
+        base.to_string()
+    }
+}
+
+/// One input of a [`SighashCase`] and what it spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseInput {
+    pub kind: InputKind,
+    pub hash_type: u8,
+    pub value: i64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// An unsigned transaction and the coins its inputs spend, all owned by [`SighashKeys`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashCase {
+    pub tx: Transaction,
+    pub inputs: Vec<CaseInput>,
+}
+
+impl SighashCase {
+    pub fn prevouts(&self) -> Vec<(i64, Vec<u8>)> {
+        self.inputs
+            .iter()
+            .map(|i| (i.value, i.script_pubkey.clone()))
+            .collect()
+    }
+
+    /// Reference digest for input `index`; `None` where BIP341 defines none.
+    pub fn digest(&self, index: usize, keys: &SighashKeys) -> Option<[u8; 32]> {
+        let input = &self.inputs[index];
+        let hash_type = u32::from(input.hash_type);
+        match input.kind {
+            InputKind::P2pkh => Some(legacy_sighash(
+                &self.tx,
+                index,
+                &input.script_pubkey,
+                hash_type,
+            )),
+            InputKind::P2wpkh => Some(segwit_v0_sighash(
+                &self.tx,
+                index,
+                &p2pkh_script(&keys.public_key),
+                input.value,
+                hash_type,
+            )),
+            InputKind::P2tr => {
+                taproot_key_path_sighash(&self.tx, index, &self.prevouts(), input.hash_type)
+            }
+        }
+    }
+}
+
+/// The one key every fuzzed input is locked to.
+pub struct SighashKeys {
+    secp: Secp256k1<All>,
+    secret: SecretKey,
+    public_key: PublicKey,
+    /// BIP86-tweaked key for P2TR
+    taproot: Keypair,
+}
+
+impl SighashKeys {
+    pub fn new(secret: [u8; 32]) -> Result<Self> {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&secret).context("Invalid secret key")?;
+        Ok(Self {
+            public_key: PublicKey::from_secret_key(&secp, &secret),
+            taproot: taproot_tweak(&secp, &secret),
+            secp,
+            secret,
+        })
+    }
+
+    pub fn script_pubkey(&self, kind: InputKind) -> Vec<u8> {
+        match kind {
+            InputKind::P2pkh => p2pkh_script(&self.public_key),
+            InputKind::P2wpkh => {
+                let mut script = vec![0x00, 0x14];
+                script.extend(hash160(&self.public_key.serialize()));
+                script
+            }
+            InputKind::P2tr => {
+                let (output_key, _) = self.taproot.x_only_public_key();
+                let mut script = vec![0x51, 0x20];
+                script.extend(output_key.serialize());
+                script
+            }
+        }
+    }
+
+    /// WIF of the secret key, compressed, for `mainnet` or the test networks.
+    pub fn wif(&self, mainnet: bool) -> String {
+        let mut payload = vec![if mainnet { 0x80 } else { 0xef }];
+        payload.extend(self.secret.secret_bytes());
+        payload.push(0x01);
+        let checksum = sha256d(&payload);
+        payload.extend(&checksum[..4]);
+        base58(&payload)
+    }
+
+    /// ECDSA signature with the hash type byte appended.
+    fn sign_ecdsa(&self, digest: [u8; 32], hash_type: u8) -> Vec<u8> {
+        let signature = self
+            .secp
+            .sign_ecdsa(&Message::from_digest(digest), &self.secret);
+        let mut sig = signature.serialize_der().to_vec();
+        sig.push(hash_type);
+        sig
+    }
+
+    /// Schnorr signature, with the hash type byte unless `SIGHASH_DEFAULT`.
+    fn sign_schnorr(&self, digest: [u8; 32], hash_type: u8) -> Vec<u8> {
+        let signature = self
+            .secp
+            .sign_schnorr_no_aux_rand(&Message::from_digest(digest), &self.taproot);
+        let mut sig = signature.as_ref().to_vec();
+        if hash_type != 0 {
+            sig.push(hash_type);
+        }
+        sig
+    }
+}
+
+fn base58(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in data {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat(b'1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&d| ALPHABET[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+/// `<sig> <pubkey>` as a scriptSig.
+fn p2pkh_script_sig(sig: &[u8], public_key: &PublicKey) -> Vec<u8> {
+    let mut script = vec![sig.len() as u8];
+    script.extend_from_slice(sig);
+    script.push(33);
+    script.extend(public_key.serialize());
+    script
+}
+
+/// `case` signed over the reference digests: the transaction with scriptSigs filled in, and each
+/// input's witness stack. Taproot inputs without a digest keep an empty witness.
+pub fn sign_case(case: &SighashCase, keys: &SighashKeys) -> (Transaction, Vec<Vec<Vec<u8>>>) {
+    let mut tx = case.tx.clone();
+    let mut witnesses = vec![Vec::new(); case.inputs.len()];
+    for (index, input) in case.inputs.iter().enumerate() {
+        let Some(digest) = case.digest(index, keys) else {
+            continue;
+        };
+        match input.kind {
+            InputKind::P2pkh => {
+                let sig = keys.sign_ecdsa(digest, input.hash_type);
+                tx.inputs[index].script_sig = p2pkh_script_sig(&sig, &keys.public_key);
+            }
+            InputKind::P2wpkh => {
+                let sig = keys.sign_ecdsa(digest, input.hash_type);
+                witnesses[index] = vec![sig, keys.public_key.serialize().to_vec()];
+            }
+            InputKind::P2tr => witnesses[index] = vec![keys.sign_schnorr(digest, input.hash_type)],
+        }
+    }
+    (tx, witnesses)
+}
+
+/// BLVM's interpreter on input `index` of a signed `tx`: `Ok` when it verifies.
+fn blvm_verify(
+    tx: &Transaction,
+    witnesses: &[Vec<Vec<u8>>],
+    prevouts: &[(i64, Vec<u8>)],
+    index: usize,
+) -> Result<(), String> {
+    let values: Vec<i64> = prevouts.iter().map(|(v, _)| *v).collect();
+    let scripts: Vec<&[u8]> = prevouts.iter().map(|(_, s)| s.as_slice()).collect();
+    let witness: Option<blvm_protocol::segwit::Witness> = witnesses
+        .get(index)
+        .filter(|w| !w.is_empty())
+        .map(|w| w.iter().cloned().collect());
+    match verify_script_with_context_full(
+        &tx.inputs[index].script_sig,
+        scripts[index],
+        witness.as_ref(),
+        VERIFY_FLAGS,
+        tx,
+        index,
+        &values,
+        &scripts,
+        None,
+        None,
+        Network::Mainnet,
+        SigVersion::Base,
+        None,
+        None,
+        None,
+        None,
+        None,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("script returned false".to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+/// BLVM's legacy digest through `calculate_transaction_sighash`.
+fn blvm_legacy_sighash(
+    tx: &Transaction,
+    index: usize,
+    script_code: &[u8],
+    hash_type: u8,
+) -> Result<[u8; 32], String> {
+    let prevouts: Vec<TransactionOutput> = (0..tx.inputs.len())
+        .map(|i| TransactionOutput {
+            value: 0,
+            script_pubkey: if i == index {
+                script_code.to_vec()
+            } else {
+                Vec::new()
+            },
+        })
+        .collect();
+    let digest =
+        calculate_transaction_sighash(tx, index, &prevouts, SighashType::from_byte(hash_type))
+            .map_err(|e| format!("{:?}", e))?;
+    <[u8; 32]>::try_from(&digest[..]).map_err(|_| "digest is not 32 bytes".to_string())
+}
+
+/// Where a digest disagreed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchSource {
+    /// BLVM's interpreter rejected a reference signature
+    BlvmVerify,
+    /// `calculate_transaction_sighash` differs from the reference or `sighash.json`
+    BlvmDigest,
+    /// A Core signature does not verify against the reference digest
+    CoreSignature,
+    /// BLVM's interpreter rejected a Core signature
+    CoreVerify,
+    /// The reference differs from `sighash.json`
+    Vector,
+}
+
+/// One disagreement.
+#[derive(Debug, Clone, Serialize)]
+pub struct SighashMismatch {
+    pub source: MismatchSource,
+    pub kind: Option<InputKind>,
+    pub hash_type: String,
+    pub input_index: usize,
+    pub detail: String,
+    /// Unsigned transaction, for replaying the case
+    pub tx_hex: String,
+}
+
+/// Tallies per `kind/hash type` plus kept mismatches.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SighashReport {
+    pub inputs: u64,
+    pub by_kind: BTreeMap<String, Tally>,
+    pub mismatch_count: u64,
+    pub mismatches: Vec<SighashMismatch>,
+}
+
+impl SighashReport {
+    pub fn passed(&self) -> bool {
+        self.mismatch_count == 0
+    }
+
+    fn record(&mut self, tag: String, mismatch: Option<SighashMismatch>) {
+        self.inputs += 1;
+        let tally = self.by_kind.entry(tag).or_default();
+        match mismatch {
+            None => tally.passed += 1,
+            Some(mismatch) => {
+                tally.failed += 1;
+                self.mismatch_count += 1;
+                if self.mismatches.len() < MAX_KEPT_MISMATCHES {
+                    self.mismatches.push(mismatch);
+                }
+            }
+        }
+    }
+
+    pub fn print(&self, title: &str) {
+        println!(
+            "🔏 {}: {} inputs, {} mismatches",
+            title, self.inputs, self.mismatch_count
+        );
+        for (tag, tally) in &self.by_kind {
+            println!(
+                "   {:<28} {:>6} passed {:>6} failed",
+                tag, tally.passed, tally.failed
+            );
+        }
+        for m in self.mismatches.iter().take(10) {
+            println!(
+                "   ❌ {:?} input {} ({}): {}",
+                m.source, m.input_index, m.hash_type, m.detail
+            );
+        }
+    }
+}
+
+fn tag(kind: InputKind, hash_type: u8) -> String {
+    format!("{}/{}", kind.name(), hash_type_name(hash_type))
+}
+
+/// Seeded generator of [`SighashCase`]s.
+pub struct SighashFuzzer {
+    rng: StdRng,
+    keys: SighashKeys,
+}
+
+impl SighashFuzzer {
+    pub fn new(seed: u64) -> Result<Self> {
+        Ok(Self {
+            rng: StdRng::seed_from_u64(seed),
+            keys: SighashKeys::new([0x5a; 32])?,
+        })
+    }
+
+    pub fn keys(&self) -> &SighashKeys {
+        &self.keys
+    }
+
+    /// 1-8 inputs and 1-6 outputs; `kinds` limits the input types.
+    pub fn next_case(&mut self, kinds: &[InputKind]) -> SighashCase {
+        let input_count = self.rng.gen_range(1..=8);
+        let output_count = self.rng.gen_range(1..=6);
+        let mut inputs = Vec::with_capacity(input_count);
+        let mut tx_inputs = Vec::with_capacity(input_count);
+        for index in 0..input_count {
+            let kind = kinds[self.rng.gen_range(0..kinds.len())];
+            let mut hash_type = match kind {
+                InputKind::P2tr => TAPROOT_HASH_TYPES[self.rng.gen_range(0..7)],
+                _ => ECDSA_HASH_TYPES[self.rng.gen_range(0..6)],
+            };
+            // BIP341 has no digest for SIGHASH_SINGLE past the last output
+            if kind == InputKind::P2tr && hash_type & 0x03 == 0x03 && index >= output_count {
+                hash_type = 0x00;
+            }
+            inputs.push(CaseInput {
+                kind,
+                hash_type,
+                value: self.rng.gen_range(1_000..5_000_000_000),
+                script_pubkey: self.keys.script_pubkey(kind),
+            });
+            tx_inputs.push(TransactionInput {
+                prevout: OutPoint {
+                    hash: self.rng.gen(),
+                    index: self.rng.gen_range(0..4),
+                },
+                script_sig: Vec::new(),
+                sequence: [0xffff_ffff, 0xffff_fffd, 0][self.rng.gen_range(0..3)],
+            });
+        }
+        let outputs: Vec<TransactionOutput> = (0..output_count)
+            .map(|_| TransactionOutput {
+                value: self.rng.gen_range(0..1_000_000),
+                script_pubkey: (0..self.rng.gen_range(0..40))
+                    .map(|_| self.rng.gen())
+                    .collect(),
+            })
+            .collect();
+        SighashCase {
+            tx: Transaction {
+                version: self.rng.gen_range(1..=2),
+                inputs: tx_inputs.into(),
+                outputs: outputs.into(),
+                lock_time: if self.rng.gen_bool(0.5) {
+                    0
+                } else {
+                    self.rng.gen_range(1..500_000)
+                },
+            },
+            inputs,
+        }
+    }
+
+    /// Sign and verify one case per iteration with every input type.
+    pub fn run(&mut self, iterations: u64) -> SighashReport {
+        let mut report = SighashReport::default();
+        for _ in 0..iterations {
+            let case = self.next_case(&[InputKind::P2pkh, InputKind::P2wpkh, InputKind::P2tr]);
+            check_case(&case, &self.keys, &mut report);
+        }
+        report
+    }
+}
+
+/// Verify every input of `case`, signed over the reference digests, with BLVM; compare legacy
+/// digests with `calculate_transaction_sighash` too.
+pub fn check_case(case: &SighashCase, keys: &SighashKeys, report: &mut SighashReport) {
+    let (signed, witnesses) = sign_case(case, keys);
+    let prevouts = case.prevouts();
+    let tx_hex = hex::encode(serialize_transaction(&case.tx));
+    for (index, input) in case.inputs.iter().enumerate() {
+        let mismatch = |source, detail| SighashMismatch {
+            source,
+            kind: Some(input.kind),
+            hash_type: hash_type_name(input.hash_type),
+            input_index: index,
+            detail,
+            tx_hex: tx_hex.clone(),
+        };
+        let mut found = blvm_verify(&signed, &witnesses, &prevouts, index)
+            .err()
+            .map(|e| mismatch(MismatchSource::BlvmVerify, e));
+        if found.is_none() && input.kind == InputKind::P2pkh {
+            let reference = legacy_sighash(
+                &case.tx,
+                index,
+                &input.script_pubkey,
+                u32::from(input.hash_type),
+            );
+            match blvm_legacy_sighash(&case.tx, index, &input.script_pubkey, input.hash_type) {
+                Ok(digest) if digest == reference => {}
+                Ok(digest) => {
+                    found = Some(mismatch(
+                        MismatchSource::BlvmDigest,
+                        format!(
+                            "BLVM {} / reference {}",
+                            hex::encode(digest),
+                            hex::encode(reference)
+                        ),
+                    ))
+                }
+                Err(e) => found = Some(mismatch(MismatchSource::BlvmDigest, e)),
+            }
+        }
+        report.record(tag(input.kind, input.hash_type), found);
+    }
+}
+
+/// Hold BLVM's legacy digest and [`legacy_sighash`] to Core's `sighash.json`; tallied by hash
+/// type. A row BLVM gets wrong is reported as [`MismatchSource::BlvmDigest`] even when the
+/// reference is wrong too.
+pub fn check_sighash_vectors(path: &Path) -> Result<SighashReport> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let rows: Vec<serde_json::Value> = serde_json::from_slice(&data)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let mut report = SighashReport::default();
+    for (row, value) in rows.iter().enumerate() {
+        let Some(fields) = value.as_array().filter(|f| f.len() > 1) else {
+            continue; // header row
+        };
+        let vector =
+            parse_sighash_row(fields).with_context(|| format!("sighash.json row {}", row))?;
+        let (tx, _) = decode_tx(&vector.tx).with_context(|| format!("sighash.json row {}", row))?;
+        let display = |mut digest: [u8; 32]| {
+            digest.reverse();
+            hex::encode(digest)
+        };
+        let reference = display(legacy_sighash(
+            &tx,
+            vector.input_index,
+            &vector.script_code,
+            vector.hash_type,
+        ));
+        let blvm = if vector.input_index < tx.inputs.len() {
+            blvm_legacy_sighash(
+                &tx,
+                vector.input_index,
+                &vector.script_code,
+                vector.hash_type as u8,
+            )
+            .map(display)
+        } else {
+            Err(format!("input index {} out of range", vector.input_index))
+        };
+        let mismatch = |source, detail: String| SighashMismatch {
+            source,
+            kind: None,
+            hash_type: format!("{:#010x}", vector.hash_type),
+            input_index: vector.input_index,
+            detail: format!("row {}: {} / expected {}", row, detail, vector.expected),
+            tx_hex: hex::encode(&vector.tx),
+        };
+        let found = match blvm {
+            Ok(digest) if digest != vector.expected => Some(mismatch(
+                MismatchSource::BlvmDigest,
+                format!("BLVM {}", digest),
+            )),
+            Err(e) => Some(mismatch(MismatchSource::BlvmDigest, format!("BLVM {}", e))),
+            Ok(_) => (reference != vector.expected)
+                .then(|| mismatch(MismatchSource::Vector, format!("reference {}", reference))),
+        };
+        report.record(hash_type_tag(vector.hash_type).to_string(), found);
+    }
+    Ok(report)
+}
+
+/// Have Core sign `cases` P2PKH / P2WPKH cases with `signrawtransactionwithkey`, then check each
+/// of its signatures against the reference digest and with BLVM's interpreter.
+pub async fn compare_with_core(
+    client: &NodeRpcClient,
+    fuzzer: &mut SighashFuzzer,
+    cases: u64,
+) -> Result<SighashReport> {
+    let chain = client.getblockchaininfo().await?;
+    let mainnet = chain["chain"].as_str() == Some("main");
+    let keys = [fuzzer.keys().wif(mainnet)];
+    let mut report = SighashReport::default();
+    for _ in 0..cases {
+        let case = fuzzer.next_case(&[InputKind::P2pkh, InputKind::P2wpkh]);
+        let tx_hex = hex::encode(serialize_transaction(&case.tx));
+        let prevtxs: Vec<serde_json::Value> = case
+            .tx
+            .inputs
+            .iter()
+            .zip(&case.inputs)
+            .map(|(tx_input, input)| {
+                let mut txid = tx_input.prevout.hash;
+                txid.reverse();
+                serde_json::json!({
+                    "txid": hex::encode(txid),
+                    "vout": tx_input.prevout.index,
+                    "scriptPubKey": hex::encode(&input.script_pubkey),
+                    "amount": input.value as f64 / 1e8,
+                })
+            })
+            .collect();
+        let prevtxs = serde_json::Value::Array(prevtxs);
+
+        // One hash type per call: sign with each type used, keep that type's inputs
+        let mut signed_inputs = vec![None; case.inputs.len()];
+        let mut hash_types: Vec<u8> = case.inputs.iter().map(|i| i.hash_type).collect();
+        hash_types.sort_unstable();
+        hash_types.dedup();
+        for hash_type in hash_types {
+            let result = client
+                .signrawtransactionwithkey(&tx_hex, &keys, &prevtxs, &hash_type_name(hash_type))
+                .await?;
+            let (signed, witnesses) = decode_tx(&hex::decode(&result.hex)?)?;
+            for (index, input) in case.inputs.iter().enumerate() {
+                if input.hash_type == hash_type {
+                    let witness: Vec<Vec<u8>> = witnesses
+                        .get(index)
+                        .map(|w| w.iter().cloned().collect())
+                        .unwrap_or_default();
+                    signed_inputs[index] = Some((signed.inputs[index].script_sig.clone(), witness));
+                }
+            }
+        }
+
+        let mut tx = case.tx.clone();
+        let mut witnesses = vec![Vec::new(); case.inputs.len()];
+        for (index, signed) in signed_inputs.into_iter().enumerate() {
+            let (script_sig, witness) = signed.context("Input left unsigned")?;
+            tx.inputs[index].script_sig = script_sig;
+            witnesses[index] = witness;
+        }
+        let prevouts = case.prevouts();
+        for (index, input) in case.inputs.iter().enumerate() {
+            let mismatch = |source, detail| SighashMismatch {
+                source,
+                kind: Some(input.kind),
+                hash_type: hash_type_name(input.hash_type),
+                input_index: index,
+                detail,
+                tx_hex: tx_hex.clone(),
+            };
+            let digest = case
+                .digest(index, fuzzer.keys())
+                .expect("ECDSA inputs have a digest");
+            let found = match core_signature(&tx, &witnesses, index, input.kind) {
+                Some(sig) if verifies(fuzzer.keys(), &sig, digest, input.hash_type) => {
+                    blvm_verify(&tx, &witnesses, &prevouts, index)
+                        .err()
+                        .map(|e| mismatch(MismatchSource::CoreVerify, e))
+                }
+                Some(sig) => Some(mismatch(
+                    MismatchSource::CoreSignature,
+                    format!(
+                        "Core signature {} fails the reference digest",
+                        hex::encode(sig)
+                    ),
+                )),
+                None => Some(mismatch(
+                    MismatchSource::CoreSignature,
+                    "Core left the input unsigned".to_string(),
+                )),
+            };
+            report.record(tag(input.kind, input.hash_type), found);
+        }
+    }
+    Ok(report)
+}
+
+/// The signature (hash type byte included) Core put on input `index`.
+fn core_signature(
+    tx: &Transaction,
+    witnesses: &[Vec<Vec<u8>>],
+    index: usize,
+    kind: InputKind,
+) -> Option<Vec<u8>> {
+    match kind {
+        InputKind::P2pkh => {
+            let mut pc = 0;
+            let op = next_op(&tx.inputs[index].script_sig, &mut pc)?.ok()?;
+            op.push.map(<[u8]>::to_vec)
+        }
+        _ => witnesses.get(index)?.first().cloned(),
+    }
+}
+
+/// `sig` is a valid ECDSA signature of `digest` by the fuzzer key, with `hash_type` appended.
+fn verifies(keys: &SighashKeys, sig: &[u8], digest: [u8; 32], hash_type: u8) -> bool {
+    let Some((&last, der)) = sig.split_last() else {
+        return false;
+    };
+    last == hash_type
+        && Signature::from_der(der).is_ok_and(|signature| {
+            keys.secp
+                .verify_ecdsa(&Message::from_digest(digest), &signature, &keys.public_key)
+                .is_ok()
+        })
+}
+
+/// Timings of all-inputs `SIGHASH_ALL` for one input count.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuadraticRun {
+    pub inputs: usize,
+    pub tx_size: usize,
+    /// Bytes the legacy algorithm hashes for all inputs (first SHA-256 pass)
+    pub hashed_bytes: u64,
+    /// `calculate_transaction_sighash` input by input, best of the iterations
+    pub blvm_per_input: Duration,
+    /// `batch_compute_sighashes`, best of the iterations
+    pub blvm_batch: Duration,
+    /// [`legacy_sighash`], best of the iterations
+    pub reference: Duration,
+}
+
+impl QuadraticRun {
+    /// BLVM per-input throughput over the hashed bytes, in MB/s.
+    pub fn megabytes_per_second(&self) -> f64 {
+        self.hashed_bytes as f64 / 1e6 / self.blvm_per_input.as_secs_f64().max(1e-9)
+    }
+}
+
+/// A legacy transaction of `inputs` P2PKH inputs with 107-byte scriptSigs (the shape of the 2015
+/// quadratic-hashing transactions) and two outputs, plus its prevouts.
+pub fn quadratic_transaction(inputs: usize) -> (Transaction, Vec<TransactionOutput>) {
+    let mut script_pubkey = vec![0x76, 0xa9, 0x14];
+    script_pubkey.extend([0x11; 20]);
+    script_pubkey.extend([0x88, 0xac]);
+    let tx_inputs: Vec<TransactionInput> = (0..inputs)
+        .map(|i| {
+            let mut hash = [0u8; 32];
+            hash[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            TransactionInput {
+                prevout: OutPoint { hash, index: 0 },
+                script_sig: vec![0x42; 107],
+                sequence: 0xffff_ffff,
+            }
+        })
+        .collect();
+    let outputs = vec![
+        TransactionOutput {
+            value: 1_000_000,
+            script_pubkey: script_pubkey.clone(),
+        };
+        2
+    ];
+    let prevouts = vec![
+        TransactionOutput {
+            value: 1_000_000,
+            script_pubkey,
+        };
+        inputs
+    ];
+    let tx = Transaction {
+        version: 1,
+        inputs: tx_inputs.into(),
+        outputs: outputs.into(),
+        lock_time: 0,
+    };
+    (tx, prevouts)
+}
+
+fn best_of(iterations: usize, mut f: impl FnMut()) -> Duration {
+    (0..iterations.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// Time every input's `SIGHASH_ALL` digest for each of `input_counts`.
+pub fn bench_quadratic(input_counts: &[usize], iterations: usize) -> Result<Vec<QuadraticRun>> {
+    let mut runs = Vec::with_capacity(input_counts.len());
+    for &inputs in input_counts {
+        let (tx, prevouts) = quadratic_transaction(inputs);
+        let tx_size = serialize_transaction(&tx).len();
+        // Per input: the transaction with all scriptSigs blanked but one 25-byte script code
+        let preimage = tx_size - inputs * 107 + 25 + 4;
+        let mut failed = None;
+        let blvm_per_input = best_of(iterations, || {
+            for index in 0..inputs {
+                if let Err(e) =
+                    calculate_transaction_sighash(&tx, index, &prevouts, SighashType::ALL)
+                {
+                    failed.get_or_insert(format!("{:?}", e));
+                }
+            }
+        });
+        if let Some(e) = failed {
+            anyhow::bail!(
+                "calculate_transaction_sighash failed on {} inputs: {}",
+                inputs,
+                e
+            );
+        }
+        let blvm_batch = best_of(iterations, || {
+            std::hint::black_box(batch_compute_sighashes(&tx, &prevouts, SighashType::ALL));
+        });
+        let reference = best_of(iterations, || {
+            for index in 0..inputs {
+                std::hint::black_box(legacy_sighash(
+                    &tx,
+                    index,
+                    &prevouts[index].script_pubkey,
+                    SIGHASH_ALL,
+                ));
+            }
+        });
+        runs.push(QuadraticRun {
+            inputs,
+            tx_size,
+            hashed_bytes: (preimage * inputs) as u64,
+            blvm_per_input,
+            blvm_batch,
+            reference,
+        });
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoinconsensus::{verify_with_flags, VERIFY_ALL_PRE_TAPROOT};
+
+    #[test]
+    fn reference_signatures_verify_in_libbitcoinconsensus() {
+        let mut fuzzer = SighashFuzzer::new(11).unwrap();
+        for _ in 0..20 {
+            let case = fuzzer.next_case(&[InputKind::P2pkh, InputKind::P2wpkh]);
+            let (tx, witnesses) = sign_case(&case, fuzzer.keys());
+            let bytes = crate::wallet::serialize_signed(&tx, &witnesses);
+            for (index, input) in case.inputs.iter().enumerate() {
+                verify_with_flags(
+                    &input.script_pubkey,
+                    input.value as u64,
+                    &bytes,
+                    None,
+                    index,
+                    VERIFY_ALL_PRE_TAPROOT,
+                )
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "{} input {}: {:?}",
+                        tag(input.kind, input.hash_type),
+                        index,
+                        e
+                    )
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn vectors_hold_blvm_to_the_expected_digest() {
+        let (tx, prevouts) = quadratic_transaction(2);
+        let script = &prevouts[1].script_pubkey;
+        let mut expected = legacy_sighash(&tx, 1, script, SIGHASH_ALL);
+        expected.reverse();
+        let row = |digest: &str| {
+            serde_json::json!([
+                hex::encode(serialize_transaction(&tx)),
+                hex::encode(script),
+                1,
+                SIGHASH_ALL,
+                digest
+            ])
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sighash.json");
+        let rows = serde_json::json!([
+            ["header"],
+            row(&hex::encode(expected)),
+            row(&"00".repeat(32))
+        ]);
+        std::fs::write(&path, rows.to_string()).unwrap();
+
+        let report = check_sighash_vectors(&path).unwrap();
+        assert_eq!(report.inputs, 2);
+        assert_eq!(report.mismatch_count, 1);
+        // BLVM is checked against the file, not just the reference
+        assert_eq!(report.mismatches[0].source, MismatchSource::BlvmDigest);
+    }
+
+    #[test]
+    fn legacy_edge_cases() {
+        let (tx, prevouts) = quadratic_transaction(3);
+        // SIGHASH_SINGLE past the last output signs the constant one
+        let one = legacy_sighash(&tx, 2, &prevouts[2].script_pubkey, SIGHASH_SINGLE);
+        assert_eq!(one[0], 1);
+        assert!(one[1..].iter().all(|&b| b == 0));
+        // Code separators are not part of the script code
+        let with_separator = [&[OP_CODESEPARATOR][..], &prevouts[0].script_pubkey].concat();
+        assert_eq!(
+            legacy_sighash(&tx, 0, &with_separator, SIGHASH_ALL),
+            legacy_sighash(&tx, 0, &prevouts[0].script_pubkey, SIGHASH_ALL)
+        );
+        assert_eq!(without_codeseparators(&[0x01, 0xab, 0xab]), [0x01, 0xab]);
+        assert_eq!(hash_type_name(0x83), "SINGLE|ANYONECANPAY");
+        assert_eq!(
+            SighashKeys::new([1; 32]).unwrap().wif(true),
+            "KwFfNUhSDaASSAwtG7ssQM1uVX8RgX5GHWnnLfhfiQDigjioWXHH"
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Replayable artifacts of script verification failures
+//!
+//! `failures.log` only says which input failed. For a failing input whose prevouts are all known,
+//! [`DivergenceArtifact::capture`] keeps everything needed to verify it again on its own: the
+//! transaction, every spent output, the witnesses, the script flags and median time past, BLVM's
+//! error (the input is verified again to get it) and `libbitcoinconsensus`' verdict under the
+//! same flags. [`DivergenceWriter`] saves each as `<height>_<txid>_<input>.json` under the
+//! divergences artifact dir (`crate::artifacts`), or `divergences/` next to the prevout file.
+//!
+//! A consensus bug can make every input of an era fail, so only the first
+//! `DIVERGENCE_ARTIFACT_LIMIT` (default 1000) are written.
+
+use anyhow::{Context, Result};
+use bitcoinconsensus::{verify_with_flags, VERIFY_ALL_PRE_TAPROOT};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::transaction::serialize_transaction;
+use blvm_protocol::types::{Network, Transaction, TransactionOutput};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+
+/// Format version written to each artifact.
+pub const ARTIFACT_VERSION: u32 = 1;
+/// Artifacts written when `DIVERGENCE_ARTIFACT_LIMIT` is unset
+pub const DEFAULT_ARTIFACT_LIMIT: usize = 1000;
+
+/// One engine's verdict on the input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineVerdict {
+    pub valid: bool,
+    /// Script error, or why the script returned false
+    pub error: Option<String>,
+}
+
+/// An output spent by the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpentOutput {
+    pub value: i64,
+    /// Hex
+    pub script_pubkey: String,
+    /// Height and coinbase flag of the creating transaction, from the merge-join (`None` for
+    /// outputs created earlier in the same block)
+    pub height: Option<u32>,
+    pub is_coinbase: Option<bool>,
+}
+
+/// Everything needed to verify one failing input again in isolation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceArtifact {
+    pub version: u32,
+    /// `Network` debug name, lowercased
+    pub network: String,
+    pub height: u64,
+    pub median_time_past: Option<u64>,
+    /// Display-order txid
+    pub txid: String,
+    pub tx_index: usize,
+    pub input_index: usize,
+    /// Legacy (non-witness) serialization
+    pub tx_hex: String,
+    /// Witness stack per input, hex elements (empty stacks for legacy inputs)
+    pub witnesses: Vec<Vec<String>>,
+    /// The output each input spends
+    pub prevouts: Vec<SpentOutput>,
+    pub flags: u32,
+    /// Failure type logged by the run (`Script returned false` / `Script error`)
+    pub failure: String,
+    pub blvm: EngineVerdict,
+    /// `None` for taproot spends, which `libbitcoinconsensus` can't check without every
+    /// spent output
+    pub core: Option<EngineVerdict>,
+}
+
+/// `DIVERGENCE_ARTIFACT_LIMIT` if set, else [`DEFAULT_ARTIFACT_LIMIT`].
+pub fn artifact_limit_from_env() -> Result<usize> {
+    match std::env::var("DIVERGENCE_ARTIFACT_LIMIT") {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .with_context(|| format!("Invalid DIVERGENCE_ARTIFACT_LIMIT {:?}", value)),
+        _ => Ok(DEFAULT_ARTIFACT_LIMIT),
+    }
+}
+
+/// Whether `script_pubkey` is a witness v1 (taproot) output.
+pub(crate) fn is_taproot(script_pubkey: &[u8]) -> bool {
+    script_pubkey.len() == 34 && script_pubkey[0] == 0x51 && script_pubkey[1] == 0x20
+}
+
+/// BIP144 serialization when any input has a witness, else the legacy one.
+pub(crate) fn serialize_for_core(tx: &Transaction, witnesses: &[Vec<Vec<u8>>]) -> Vec<u8> {
+    if witnesses.iter().all(|w| w.is_empty()) {
+        serialize_transaction(tx)
+    } else {
+        crate::wallet::serialize_signed(tx, witnesses)
+    }
+}
+
+/// The failing input and its context, as `verify_scripts` has it.
+pub struct FailedInput<'a> {
+    pub network: Network,
+    pub height: u64,
+    pub median_time_past: Option<u64>,
+    pub tx: &'a Transaction,
+    pub tx_index: usize,
+    pub input_index: usize,
+    pub prevouts: &'a [TransactionOutput],
+    /// `(height, is_coinbase)` of each spent output, where known
+    pub prevout_origins: Vec<Option<(u32, bool)>>,
+    pub witnesses: Option<&'a Vec<Witness>>,
+    pub flags: u32,
+    pub failure: &'a str,
+}
+
+impl DivergenceArtifact {
+    /// Verify the input again with both engines and record the result.
+    pub fn capture(failed: &FailedInput) -> Self {
+        let (tx, input_index) = (failed.tx, failed.input_index);
+        let witnesses: Vec<Vec<Vec<u8>>> = (0..tx.inputs.len())
+            .map(|i| {
+                failed
+                    .witnesses
+                    .and_then(|w| w.get(i))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let values: Vec<i64> = failed.prevouts.iter().map(|o| o.value).collect();
+        let script_pubkeys: Vec<&[u8]> = failed
+            .prevouts
+            .iter()
+            .map(|o| o.script_pubkey.as_slice())
+            .collect();
+
+        let blvm = match verify_script_with_context_full(
+            &tx.inputs[input_index].script_sig,
+            script_pubkeys[input_index],
+            failed.witnesses.and_then(|w| w.get(input_index)),
+            failed.flags,
+            tx,
+            input_index,
+            &values,
+            &script_pubkeys,
+            Some(failed.height),
+            failed.median_time_past,
+            failed.network,
+            SigVersion::Base,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            Ok(valid) => EngineVerdict {
+                valid,
+                error: (!valid).then(|| "script returned false".to_string()),
+            },
+            Err(e) => EngineVerdict {
+                valid: false,
+                error: Some(format!("{:?}", e)),
+            },
+        };
+
+        let core = (!is_taproot(script_pubkeys[input_index])).then(|| {
+            match verify_with_flags(
+                script_pubkeys[input_index],
+                values[input_index] as u64,
+                &serialize_for_core(tx, &witnesses),
+                None,
+                input_index,
+                failed.flags & VERIFY_ALL_PRE_TAPROOT,
+            ) {
+                Ok(()) => EngineVerdict {
+                    valid: true,
+                    error: None,
+                },
+                Err(e) => EngineVerdict {
+                    valid: false,
+                    error: Some(format!("{:?}", e)),
+                },
+            }
+        });
+
+        let mut txid = blvm_protocol::block::calculate_tx_id(tx);
+        txid.reverse();
+        Self {
+            version: ARTIFACT_VERSION,
+            network: format!("{:?}", failed.network).to_lowercase(),
+            height: failed.height,
+            median_time_past: failed.median_time_past,
+            txid: hex::encode(txid),
+            tx_index: failed.tx_index,
+            input_index,
+            tx_hex: hex::encode(serialize_transaction(tx)),
+            witnesses: witnesses
+                .iter()
+                .map(|stack| stack.iter().map(hex::encode).collect())
+                .collect(),
+            prevouts: failed
+                .prevouts
+                .iter()
+                .zip(&failed.prevout_origins)
+                .map(|(output, origin)| SpentOutput {
+                    value: output.value,
+                    script_pubkey: hex::encode(&output.script_pubkey),
+                    height: origin.map(|(height, _)| height),
+                    is_coinbase: origin.map(|(_, coinbase)| coinbase),
+                })
+                .collect(),
+            flags: failed.flags,
+            failure: failed.failure.to_string(),
+            blvm,
+            core,
+        }
+    }
+
+    /// Core accepted what BLVM rejected (or the other way round).
+    pub fn engines_disagree(&self) -> bool {
+        self.core
+            .as_ref()
+            .is_some_and(|core| core.valid != self.blvm.valid)
+    }
+
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}_{}_{}.json",
+            self.height,
+            &self.txid[..16.min(self.txid.len())],
+            self.input_index
+        )
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let artifact: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        anyhow::ensure!(
+            artifact.version == ARTIFACT_VERSION,
+            "{} is artifact version {}, expected {}",
+            path.display(),
+            artifact.version,
+            ARTIFACT_VERSION
+        );
+        Ok(artifact)
+    }
+
+    /// Write to `path` ([`write_json_atomic`](crate::utils::write_json_atomic)).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::utils::write_json_atomic(path, self)
+    }
+}
+
+/// Writes artifacts to one directory, up to a limit.
+pub struct DivergenceWriter {
+    dir: PathBuf,
+    limit: usize,
+    written: usize,
+    /// Engines disagreed on a written artifact
+    disagreements: usize,
+}
+
+impl DivergenceWriter {
+    /// Writer into `dir`, created with the first artifact.
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            limit,
+            written: 0,
+            disagreements: 0,
+        }
+    }
+
+    /// The divergences artifact dir for this run if an output dir is configured, else
+    /// `divergences/` in `data_dir`; the limit from `DIVERGENCE_ARTIFACT_LIMIT`.
+    pub fn from_env(data_dir: &Path, network: Network, range: (u64, u64)) -> Result<Self> {
+        let mut ctx = ArtifactContext::now().with_range(range.0, range.1);
+        ctx.network = format!("{:?}", network).to_lowercase();
+        let dir = artifact_dir_from_env(ArtifactKind::Divergences, &ctx)
+            .unwrap_or_else(|| data_dir.join("divergences"));
+        Ok(Self::new(dir, artifact_limit_from_env()?))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn disagreements(&self) -> usize {
+        self.disagreements
+    }
+
+    /// Capture and save `failed`, unless the limit is reached.
+    pub fn record(&mut self, failed: &FailedInput) -> Result<Option<PathBuf>> {
+        if self.written >= self.limit {
+            return Ok(None);
+        }
+        if self.written == 0 {
+            std::fs::create_dir_all(&self.dir)
+                .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        }
+        let artifact = DivergenceArtifact::capture(failed);
+        let path = self.dir.join(artifact.file_name());
+        artifact.save(&path)?;
+        self.written += 1;
+        if artifact.engines_disagree() {
+            self.disagreements += 1;
+            warn!(
+                "  ⚠️  BLVM and Core disagree on block {} tx {} input {}: {}",
+                artifact.height,
+                artifact.txid,
+                artifact.input_index,
+                path.display()
+            );
+        }
+        if self.written == self.limit {
+            info!(
+                "  📁 Divergence artifact limit ({}) reached, not writing more",
+                self.limit
+            );
+        }
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_taproot_outputs() {
+        let mut p2tr = vec![0x51, 0x20];
+        p2tr.extend([7u8; 32]);
+        assert!(is_taproot(&p2tr));
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend([7u8; 32]);
+        assert!(!is_taproot(&p2wsh));
+        assert!(!is_taproot(&[0x51]));
+    }
+}
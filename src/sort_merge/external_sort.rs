@@ -0,0 +1,454 @@
+//! Memory-budgeted external sort
+//!
+//! Steps 2, 3b and 5 sort files far larger than memory. [`ExternalSorter`] reads the input
+//! sequentially into runs, sorts a batch of runs at once on the rayon pool, writes each run to a
+//! temp file, then k-way merges the runs through a binary heap.
+//!
+//! The memory budget (`--sort-mem` / `SORT_MEM`, e.g. `4G`) is split across the runs of a batch,
+//! one per rayon thread but none smaller than 64 MiB: a bigger budget means fewer, longer runs.
+//! With more runs than the merge fan-in, groups of runs are first merged in parallel into longer
+//! ones, so the final merge never holds more than `max_fan_in` files open.
+//!
+//! Record types implement [`SortRecord`]: the sort key, the on-disk encoding and how much heap
+//! memory a record holds. Input and output are opened through [`super::compression`], and when
+//! the output is a `.zst` file the runs are compressed as well.
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::info;
+
+use super::compression::{intermediate_name, is_compressed, RecordReader, RecordWriter};
+
+const MIB: u64 = 1024 * 1024;
+
+/// Budget when neither `--sort-mem` nor `SORT_MEM` is given
+pub const DEFAULT_SORT_MEM: u64 = 4 * 1024 * MIB;
+/// Smallest run worth sorting on a thread of its own
+const MIN_RUN_BYTES: u64 = 64 * MIB;
+/// Runs merged at once; each holds an open file and a read buffer
+const DEFAULT_MAX_FAN_IN: usize = 128;
+const INPUT_BUFFER: usize = 64 * MIB as usize;
+const RUN_WRITE_BUFFER: usize = 8 * MIB as usize;
+const RUN_READ_BUFFER: usize = MIB as usize;
+const OUTPUT_BUFFER: usize = 64 * MIB as usize;
+
+/// A fixed-layout record of an intermediate sort-merge file.
+pub trait SortRecord: Sized + Send {
+    type Key: Ord + Send;
+
+    fn key(&self) -> Self::Key;
+
+    /// Bytes held on the heap beyond `size_of::<Self>()`, counted against the memory budget
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    /// The next record, `None` at a clean end of input
+    fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>>;
+}
+
+/// Fill `buf` from `reader`: `false` if the input ended before the first byte, an error if it
+/// ended part way through.
+pub fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => anyhow::bail!("Truncated record: {} of {} bytes", filled, buf.len()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Read one variable-length record: a `header_len`-byte header whose last two bytes are the
+/// little-endian length of the payload that follows. Returns header and payload together.
+pub fn read_length_prefixed<R: Read>(reader: &mut R, header_len: usize) -> Result<Option<Vec<u8>>> {
+    let mut buf = vec![0u8; header_len];
+    if !read_exact_or_eof(reader, &mut buf)? {
+        return Ok(None);
+    }
+    let payload_len = u16::from_le_bytes([buf[header_len - 2], buf[header_len - 1]]) as usize;
+    buf.resize(header_len + payload_len, 0);
+    if !read_exact_or_eof(reader, &mut buf[header_len..])? && payload_len > 0 {
+        anyhow::bail!("Truncated record: missing {}-byte payload", payload_len);
+    }
+    Ok(Some(buf))
+}
+
+/// Parse a size like `4G`, `512M`, `1.5GiB` or `65536` (binary units) into bytes.
+pub fn parse_mem_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid memory size {:?}", s))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => MIB,
+        "G" => 1024 * MIB,
+        "T" => 1024 * 1024 * MIB,
+        _ => anyhow::bail!("Invalid memory size {:?}: unknown unit {:?}", s, unit),
+    };
+    let bytes = (number * multiplier as f64) as u64;
+    if bytes == 0 {
+        anyhow::bail!("Memory size {:?} is zero", s);
+    }
+    Ok(bytes)
+}
+
+/// `SORT_MEM` if set, else [`DEFAULT_SORT_MEM`].
+pub fn sort_mem_from_env() -> Result<u64> {
+    match std::env::var("SORT_MEM") {
+        Ok(value) if !value.is_empty() => parse_mem_size(&value).context("SORT_MEM"),
+        _ => Ok(DEFAULT_SORT_MEM),
+    }
+}
+
+/// One sorted temp file.
+struct Run {
+    path: PathBuf,
+    records: u64,
+}
+
+/// External merge sort of record files within a memory budget.
+pub struct ExternalSorter {
+    temp_dir: PathBuf,
+    mem_budget: u64,
+    max_fan_in: usize,
+}
+
+impl ExternalSorter {
+    /// Sorter keeping its runs in `temp_dir` (created on demand, removed when empty).
+    pub fn new(temp_dir: impl Into<PathBuf>, mem_budget: u64) -> Self {
+        Self {
+            temp_dir: temp_dir.into(),
+            mem_budget: mem_budget.max(1),
+            max_fan_in: DEFAULT_MAX_FAN_IN,
+        }
+    }
+
+    pub fn with_max_fan_in(mut self, max_fan_in: usize) -> Self {
+        self.max_fan_in = max_fan_in.max(2);
+        self
+    }
+
+    /// Runs sorted at once, and the memory each may fill.
+    fn run_layout(&self) -> (usize, u64) {
+        let parallel = (rayon::current_num_threads() as u64)
+            .min(self.mem_budget / MIN_RUN_BYTES)
+            .max(1);
+        (parallel as usize, self.mem_budget / parallel)
+    }
+
+    /// Sort the records of `input` by [`SortRecord::key`] into `output`, returning the number
+    /// of records. Records with equal keys keep their input order.
+    pub fn sort<R: SortRecord>(&self, input: &Path, output: &Path) -> Result<u64> {
+        std::fs::create_dir_all(&self.temp_dir)
+            .with_context(|| format!("Failed to create sort dir {}", self.temp_dir.display()))?;
+        let runs = self.create_runs::<R>(input, is_compressed(output))?;
+        let total: u64 = runs.iter().map(|r| r.records).sum();
+        let merged = self.merge_runs::<R>(runs, output, total)?;
+        let _ = std::fs::remove_dir(&self.temp_dir);
+        if merged != total {
+            anyhow::bail!("Merged {} records but the runs held {}", merged, total);
+        }
+        Ok(merged)
+    }
+
+    /// Phase 1: read runs sequentially, sort and write each batch of runs in parallel.
+    fn create_runs<R: SortRecord>(&self, input: &Path, compress: bool) -> Result<Vec<Run>> {
+        let (parallel, run_bytes) = self.run_layout();
+        info!(
+            "  Phase 1: sorting runs of up to {:.2} GB, {} at a time",
+            run_bytes as f64 / 1_073_741_824.0,
+            parallel
+        );
+        let mut reader = RecordReader::open(input, INPUT_BUFFER)?;
+        let mut runs = Vec::new();
+        let mut eof = false;
+
+        while !eof {
+            crate::disk_watchdog::checkpoint()?;
+            let mut batch: Vec<Vec<R>> = Vec::with_capacity(parallel);
+            while batch.len() < parallel && !eof {
+                let mut records = Vec::new();
+                let mut bytes = 0u64;
+                while bytes < run_bytes {
+                    match R::read_from(&mut reader)
+                        .with_context(|| format!("Failed to read {}", input.display()))?
+                    {
+                        Some(record) => {
+                            bytes += (std::mem::size_of::<R>() + record.heap_bytes()) as u64;
+                            records.push(record);
+                        }
+                        None => {
+                            eof = true;
+                            break;
+                        }
+                    }
+                }
+                if !records.is_empty() {
+                    batch.push(records);
+                }
+            }
+
+            let first = runs.len();
+            let written: Vec<Run> = batch
+                .into_par_iter()
+                .enumerate()
+                .map(|(i, mut records)| {
+                    records.sort_by_key(|r| r.key());
+                    let name = intermediate_name(&format!("run_{}.bin", first + i), compress);
+                    self.write_run(&name, &records)
+                })
+                .collect::<Result<_>>()?;
+            for run in written {
+                info!("    Run {}: {} records", runs.len(), run.records);
+                runs.push(run);
+            }
+        }
+        Ok(runs)
+    }
+
+    fn write_run<R: SortRecord>(&self, name: &str, records: &[R]) -> Result<Run> {
+        let path = self.temp_dir.join(name);
+        let mut writer = RecordWriter::create(&path, RUN_WRITE_BUFFER)?;
+        for record in records {
+            record.write_to(&mut writer)?;
+        }
+        writer.finish()?;
+        Ok(Run {
+            path,
+            records: records.len() as u64,
+        })
+    }
+
+    /// Phase 2: merge passes until at most `max_fan_in` runs remain, then the final merge.
+    fn merge_runs<R: SortRecord>(
+        &self,
+        mut runs: Vec<Run>,
+        output: &Path,
+        total: u64,
+    ) -> Result<u64> {
+        let compress = is_compressed(output);
+        let mut pass = 0;
+        while runs.len() > self.max_fan_in {
+            crate::disk_watchdog::checkpoint()?;
+            pass += 1;
+            info!(
+                "  Merge pass {}: {} runs in groups of {}",
+                pass,
+                runs.len(),
+                self.max_fan_in
+            );
+            let mut groups = Vec::new();
+            while !runs.is_empty() {
+                let rest = runs.split_off(runs.len().min(self.max_fan_in));
+                groups.push(std::mem::replace(&mut runs, rest));
+            }
+            runs = groups
+                .into_par_iter()
+                .enumerate()
+                .map(|(i, group)| {
+                    let name = intermediate_name(&format!("pass{}_run_{}.bin", pass, i), compress);
+                    let path = self.temp_dir.join(name);
+                    let records = merge_into::<R>(&group, &path, RUN_WRITE_BUFFER, None)?;
+                    Ok(Run { path, records })
+                })
+                .collect::<Result<_>>()?;
+        }
+
+        info!("  Phase 2: merging {} runs...", runs.len());
+        if let [run] = runs.as_slice() {
+            // Already sorted: move it into place, copying if the temp dir is on another filesystem
+            if std::fs::rename(&run.path, output).is_err() {
+                std::fs::copy(&run.path, output).with_context(|| {
+                    format!(
+                        "Failed to copy {} to {}",
+                        run.path.display(),
+                        output.display()
+                    )
+                })?;
+                let _ = std::fs::remove_file(&run.path);
+            }
+            return Ok(run.records);
+        }
+        merge_into::<R>(&runs, output, OUTPUT_BUFFER, Some(total))
+    }
+}
+
+/// K-way merge of sorted `runs` into `dest`, deleting each run once it is exhausted. Ties go to
+/// the earlier run, which keeps the sort stable.
+fn merge_into<R: SortRecord>(
+    runs: &[Run],
+    dest: &Path,
+    buffer: usize,
+    progress_total: Option<u64>,
+) -> Result<u64> {
+    let mut readers = Vec::with_capacity(runs.len());
+    let mut current: Vec<Option<R>> = Vec::with_capacity(runs.len());
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for (idx, run) in runs.iter().enumerate() {
+        let mut reader = RecordReader::open(&run.path, RUN_READ_BUFFER)?;
+        let first = R::read_from(&mut reader)?;
+        if let Some(record) = &first {
+            heap.push(Reverse((record.key(), idx)));
+        }
+        readers.push(reader);
+        current.push(first);
+    }
+
+    let mut writer = RecordWriter::create(dest, buffer)?;
+    let mut merged = 0u64;
+    let mut last_report = Instant::now();
+
+    while let Some(Reverse((_, idx))) = heap.pop() {
+        if let Some(record) = current[idx].take() {
+            record.write_to(&mut writer)?;
+            merged += 1;
+        }
+        match R::read_from(&mut readers[idx])? {
+            Some(record) => {
+                heap.push(Reverse((record.key(), idx)));
+                current[idx] = Some(record);
+            }
+            None => {
+                let _ = std::fs::remove_file(&runs[idx].path);
+            }
+        }
+
+        if let Some(total) = progress_total {
+            if last_report.elapsed().as_secs() >= 10 {
+                info!(
+                    "    Merged: {} / {} ({:.1}%)",
+                    merged,
+                    total,
+                    merged as f64 / total as f64 * 100.0
+                );
+                last_report = Instant::now();
+            }
+        }
+    }
+
+    writer.finish()?;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort_merge::input_refs::InputRef;
+    use crate::sort_merge::output_refs::OutputRef;
+    use rand::{Rng, SeedableRng};
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+
+    #[test]
+    fn parses_memory_sizes() {
+        assert_eq!(parse_mem_size("4G").unwrap(), 4 << 30);
+        assert_eq!(parse_mem_size("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse_mem_size("1.5gb").unwrap(), 3 << 29);
+        assert_eq!(parse_mem_size("65536").unwrap(), 65536);
+        assert!(parse_mem_size("4X").is_err());
+        assert!(parse_mem_size("0G").is_err());
+    }
+
+    #[test]
+    fn multi_pass_sort_matches_in_memory_sort() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut records: Vec<OutputRef> = (0..5_000)
+            .map(|i| OutputRef {
+                txid: [rng.gen_range(0..8u8); 32],
+                output_idx: rng.gen_range(0..4),
+                block_height: i,
+                is_coinbase: i % 7 == 0,
+                value: i as i64,
+                script_pubkey: vec![0x51; rng.gen_range(0..40)],
+            })
+            .collect();
+        let input = dir.path().join("outputs_unsorted.bin");
+        let mut writer = BufWriter::new(File::create(&input).unwrap());
+        for record in &records {
+            record.write_to(&mut writer).unwrap();
+        }
+        writer.flush().unwrap();
+
+        // ~40 runs with a fan-in of 3 forces several merge passes
+        let output = dir.path().join("outputs_sorted.bin");
+        let sorter = ExternalSorter::new(dir.path().join("sort_tmp"), 10_000).with_max_fan_in(3);
+        assert_eq!(sorter.sort::<OutputRef>(&input, &output).unwrap(), 5_000);
+        assert!(!dir.path().join("sort_tmp").exists());
+
+        records.sort_by_key(|r| r.key());
+        let mut reader = BufReader::new(File::open(&output).unwrap());
+        for expected in &records {
+            let got = OutputRef::read_from(&mut reader).unwrap().unwrap();
+            assert_eq!(got.key(), expected.key());
+            // Stable: equal keys stay in input (block height) order
+            assert_eq!(got.block_height, expected.block_height);
+            assert_eq!(got.script_pubkey, expected.script_pubkey);
+        }
+        assert!(OutputRef::read_from(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn zst_output_sorts_through_compressed_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut records: Vec<InputRef> = (0..2_000)
+            .map(|i| InputRef {
+                prevout_txid: [rng.gen(); 32],
+                prevout_idx: rng.gen_range(0..3),
+                block_height: i,
+                tx_idx: 1,
+                input_idx: 0,
+            })
+            .collect();
+        let input = dir.path().join("inputs_unsorted.bin.zst");
+        let mut writer = RecordWriter::create(&input, 4096).unwrap();
+        for record in &records {
+            record.write_to(&mut writer).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output = dir.path().join("inputs_sorted.bin.zst");
+        let sorter = ExternalSorter::new(dir.path().join("sort_tmp"), 20_000).with_max_fan_in(4);
+        assert_eq!(sorter.sort::<InputRef>(&input, &output).unwrap(), 2_000);
+
+        records.sort_by_key(|r| r.key());
+        let mut reader = RecordReader::open(&output, 4096).unwrap();
+        for expected in &records {
+            let got = InputRef::read_from(&mut reader).unwrap().unwrap();
+            assert_eq!(got.key(), expected.key());
+            assert_eq!(got.block_height, expected.block_height);
+        }
+        assert!(InputRef::read_from(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("inputs_unsorted.bin");
+        std::fs::write(&input, [0u8; InputRef::SIZE + 10]).unwrap();
+        let sorter = ExternalSorter::new(dir.path().join("sort_tmp"), DEFAULT_SORT_MEM);
+        assert!(sorter
+            .sort::<InputRef>(&input, &dir.path().join("out.bin"))
+            .is_err());
+    }
+}
@@ -11,14 +11,17 @@
 //! ~150M inputs × 48 bytes = ~7.2 GB
 
 use anyhow::{Context, Result};
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 use std::path::Path;
 use std::time::Instant;
 
 use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
 use blvm_protocol::transaction::is_coinbase;
+use tracing::{info, warn};
 
+use super::compression::{is_compressed, RecordWriter};
+use super::external_sort::{read_exact_or_eof, ExternalSorter, SortRecord};
+use super::state::StageCheckpoint;
 use crate::chunked_cache::ChunkedBlockIterator;
 
 /// Fixed-size input reference record (48 bytes)
@@ -62,23 +65,51 @@ impl InputRef {
     }
 }
 
+impl SortRecord for InputRef {
+    type Key = ([u8; 32], u32);
+
+    fn key(&self) -> Self::Key {
+        (self.prevout_txid, self.prevout_idx)
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    fn read_from<R: std::io::Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut buf = [0u8; Self::SIZE];
+        Ok(read_exact_or_eof(reader, &mut buf)?.then(|| Self::from_bytes(&buf)))
+    }
+}
+
 /// Extract all input references from blocks and write to file
+///
+/// With a `checkpoint`, an interrupted run continues from its last checkpoint and new
+/// checkpoints are saved as it goes.
 pub fn extract_input_refs(
     chunks_dir: &Path,
     output_file: &Path,
     start_height: u64,
     end_height: u64,
     progress_interval: u64,
+    mut checkpoint: Option<&mut StageCheckpoint>,
 ) -> Result<u64> {
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 1: Extract Input References");
-    println!("{}", "═".repeat(60));
-    println!("  Chunks dir: {}", chunks_dir.display());
-    println!("  Blocks: {} to {}", start_height, end_height);
-    println!("  Output: {}", output_file.display());
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 1: Extract Input References");
+    info!("{}", "═".repeat(60));
+    info!("  Chunks dir: {}", chunks_dir.display());
+    info!("  Blocks: {} to {}", start_height, end_height);
+    info!("  Output: {}", output_file.display());
     
     let start_time = Instant::now();
     
+    let resume = match &checkpoint {
+        Some(checkpoint) => checkpoint.resume()?,
+        None => None,
+    };
+    let start_height = resume.map_or(start_height, |r| r.next_height);
+    
     // Create block iterator
     // CRITICAL FIX: Calculate max_blocks from end_height to ensure we process all requested blocks
     // Note: Iterator will still stop at metadata.total_blocks if chunks don't have all blocks,
@@ -88,15 +119,17 @@ pub fn extract_input_refs(
         .ok_or_else(|| anyhow::anyhow!("Failed to create block iterator - chunks.meta not found?"))?;
     
     // Log the actual end_height the iterator will use (may be limited by metadata.total_blocks)
-    eprintln!("  📍 Block iterator configured: start={}, requested_end={}, max_blocks={}", 
+    info!("  📍 Block iterator configured: start={}, requested_end={}, max_blocks={}", 
               start_height, end_height, max_blocks);
     
-    // Create output file
-    let file = File::create(output_file)
-        .with_context(|| format!("Failed to create output file: {}", output_file.display()))?;
-    let mut writer = BufWriter::with_capacity(64 * 1024 * 1024, file); // 64MB buffer
+    // Create output file, or append after the checkpointed part
+    let mut writer = if resume.is_some() {
+        RecordWriter::append(output_file, 64 * 1024 * 1024)?
+    } else {
+        RecordWriter::create(output_file, 64 * 1024 * 1024)? // 64MB buffer
+    };
     
-    let mut total_inputs = 0u64;
+    let mut total_inputs = resume.map_or(0, |r| r.records);
     let mut height = start_height;
     let mut last_report = Instant::now();
     
@@ -106,14 +139,14 @@ pub fn extract_input_refs(
             Some(data) => data,
             None => {
                 if height < end_height {
-                    eprintln!("  ⚠️  WARNING: Block iterator ended at height {} but end_height is {}", height, end_height);
-                    eprintln!("  Missing blocks: {} to {} ({} blocks)", height, end_height - 1, end_height - height);
-                    eprintln!("  This will cause missing inputs for blocks {} to {}", height, end_height - 1);
-                    eprintln!("  Possible causes:");
-                    eprintln!("    1. Chunks don't contain all blocks up to {}", end_height);
-                    eprintln!("    2. Chunk metadata (chunks.meta) reports fewer blocks than available");
-                    eprintln!("    3. Block index is incomplete");
-                    eprintln!("  Solution: Ensure chunks contain all blocks up to {} or update chunks.meta", end_height);
+                    warn!("  ⚠️  WARNING: Block iterator ended at height {} but end_height is {}", height, end_height);
+                    info!("  Missing blocks: {} to {} ({} blocks)", height, end_height - 1, end_height - height);
+                    info!("  This will cause missing inputs for blocks {} to {}", height, end_height - 1);
+                    info!("  Possible causes:");
+                    info!("    1. Chunks don't contain all blocks up to {}", end_height);
+                    info!("    2. Chunk metadata (chunks.meta) reports fewer blocks than available");
+                    info!("    3. Block index is incomplete");
+                    info!("  Solution: Ensure chunks contain all blocks up to {} or update chunks.meta", end_height);
                 }
                 // Stop extraction - we've processed all available blocks
                 break;
@@ -152,7 +185,7 @@ pub fn extract_input_refs(
             let elapsed = start_time.elapsed().as_secs_f64();
             let rate = processed as f64 / elapsed;
             let remaining = (end_height - height) as f64 / rate;
-            println!(
+            info!(
                 "  Block {}/{} ({:.1}%) - {} inputs - {:.0} blk/s - ETA: {:.0}m",
                 height, end_height,
                 (height - start_height) as f64 / (end_height - start_height) as f64 * 100.0,
@@ -164,217 +197,67 @@ pub fn extract_input_refs(
         }
         
         height += 1;
+        
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            if checkpoint.due() {
+                if let Some(len) = writer.sync()? {
+                    checkpoint.save(len, height, total_inputs, 0)?;
+                }
+            }
+        }
     }
     
-    writer.flush()?;
+    writer.finish()?;
     
     let elapsed = start_time.elapsed();
     let file_size = std::fs::metadata(output_file)?.len();
     
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 1 Complete!");
-    println!("  Total inputs: {}", total_inputs);
-    println!("  Blocks processed: {}", height - start_height);
-    println!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
-    println!("  Rate: {:.0} blocks/sec", (height - start_height) as f64 / elapsed.as_secs_f64());
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 1 Complete!");
+    info!("  Total inputs: {}", total_inputs);
+    info!("  Blocks processed: {}", height - start_height);
+    info!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("  Rate: {:.0} blocks/sec", (height - start_height) as f64 / elapsed.as_secs_f64());
     
     Ok(total_inputs)
 }
 
-/// Sort input refs file by (prevout_txid, prevout_idx) using external merge sort
-/// 
-/// Uses a proper binary merge sort that doesn't expand data size:
-/// 1. Read chunks into memory (2GB each)
-/// 2. Sort each chunk in memory
-/// 3. Write sorted chunks to temp files
-/// 4. Multi-way merge the sorted chunks
-pub fn sort_input_refs(input_file: &Path, output_file: &Path) -> Result<()> {
-    use std::io::{BufReader, Read, Seek, SeekFrom};
-    use std::collections::BinaryHeap;
-    use std::cmp::Reverse;
-    
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 2: Sort Input References by Prevout");
-    println!("{}", "═".repeat(60));
-    println!("  Input: {}", input_file.display());
-    println!("  Output: {}", output_file.display());
+/// Sort input refs file by (prevout_txid, prevout_idx) using the external sorter
+///
+/// Runs of up to `sort_mem` bytes are sorted in parallel, then k-way merged
+/// (see [`ExternalSorter`]).
+pub fn sort_input_refs(input_file: &Path, output_file: &Path, sort_mem: u64) -> Result<u64> {
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 2: Sort Input References by Prevout");
+    info!("{}", "═".repeat(60));
+    info!("  Input: {}", input_file.display());
+    info!("  Output: {}", output_file.display());
     
     let start_time = Instant::now();
     
     let input_size = std::fs::metadata(input_file)?.len();
-    let num_records = input_size / InputRef::SIZE as u64;
-    println!("  Records: {} ({:.2} GB)", num_records, input_size as f64 / 1_073_741_824.0);
-    
-    // Chunk size: 2GB = ~44M records
-    let chunk_records = 44_000_000usize;
-    let chunk_bytes = chunk_records * InputRef::SIZE;
+    if is_compressed(input_file) {
+        info!("  Input size: {:.2} GB (zstd)", input_size as f64 / 1_073_741_824.0);
+    } else {
+        let num_records = input_size / InputRef::SIZE as u64;
+        info!("  Records: {} ({:.2} GB)", num_records, input_size as f64 / 1_073_741_824.0);
+    }
     
-    // Create temp directory
     let temp_dir = input_file.parent()
         .unwrap_or(Path::new("."))
         .join("sort_tmp");
-    std::fs::create_dir_all(&temp_dir)?;
-    
-    // Phase 1: Create sorted chunks
-    println!("  Phase 1: Creating sorted chunks...");
-    let mut reader = BufReader::with_capacity(64 * 1024 * 1024, File::open(input_file)?);
-    let mut chunk_files: Vec<std::path::PathBuf> = Vec::new();
-    let mut chunk_idx = 0;
-    
-    loop {
-        // Read chunk into memory
-        let mut records: Vec<InputRef> = Vec::with_capacity(chunk_records);
-        let mut buf = [0u8; InputRef::SIZE];
-        
-        for _ in 0..chunk_records {
-            match reader.read_exact(&mut buf) {
-                Ok(()) => records.push(InputRef::from_bytes(&buf)),
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e.into()),
-            }
-        }
-        
-        if records.is_empty() {
-            break;
-        }
-        
-        // Sort in memory by (prevout_txid, prevout_idx)
-        records.sort_unstable_by(|a, b| {
-            a.prevout_txid.cmp(&b.prevout_txid)
-                .then_with(|| a.prevout_idx.cmp(&b.prevout_idx))
-        });
-        
-        // Write sorted chunk
-        let chunk_path = temp_dir.join(format!("chunk_{}.bin", chunk_idx));
-        let mut chunk_writer = BufWriter::with_capacity(64 * 1024 * 1024, File::create(&chunk_path)?);
-        for record in &records {
-            chunk_writer.write_all(&record.to_bytes())?;
-        }
-        chunk_writer.flush()?;
-        
-        println!("    Chunk {}: {} records", chunk_idx, records.len());
-        chunk_files.push(chunk_path);
-        chunk_idx += 1;
-    }
-    
-    println!("  Phase 2: Merging {} chunks...", chunk_files.len());
-    
-    // Phase 2: K-way merge
-    // For each chunk, keep a reader and current record
-    struct ChunkReader {
-        reader: BufReader<File>,
-        current: Option<InputRef>,
-        chunk_idx: usize,
-    }
-    
-    impl ChunkReader {
-        fn read_next(&mut self) -> Result<()> {
-            let mut buf = [0u8; InputRef::SIZE];
-            match self.reader.read_exact(&mut buf) {
-                Ok(()) => {
-                    self.current = Some(InputRef::from_bytes(&buf));
-                    Ok(())
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    self.current = None;
-                    Ok(())
-                }
-                Err(e) => Err(e.into()),
-            }
-        }
-    }
-    
-    // Wrapper for heap ordering (min-heap by key)
-    #[derive(Eq, PartialEq)]
-    struct HeapItem {
-        key: ([u8; 32], u32),
-        chunk_idx: usize,
-    }
-    
-    impl Ord for HeapItem {
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            // Reverse for min-heap
-            other.key.cmp(&self.key)
-        }
-    }
-    
-    impl PartialOrd for HeapItem {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
-        }
-    }
-    
-    let mut chunk_readers: Vec<ChunkReader> = Vec::new();
-    let mut heap: BinaryHeap<HeapItem> = BinaryHeap::new();
-    
-    for (idx, chunk_path) in chunk_files.iter().enumerate() {
-        let file = File::open(chunk_path)?;
-        let mut reader = ChunkReader {
-            reader: BufReader::with_capacity(1024 * 1024, file),
-            current: None,
-            chunk_idx: idx,
-        };
-        reader.read_next()?;
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.prevout_txid, record.prevout_idx),
-                chunk_idx: idx,
-            });
-        }
-        chunk_readers.push(reader);
-    }
-    
-    // Merge to output - delete chunks as they're exhausted to save space
-    let mut writer = BufWriter::with_capacity(64 * 1024 * 1024, File::create(output_file)?);
-    let mut merged = 0u64;
-    let mut last_report = Instant::now();
-    let mut chunk_exhausted = vec![false; chunk_files.len()];
-    
-    while let Some(item) = heap.pop() {
-        let reader = &mut chunk_readers[item.chunk_idx];
-        if let Some(ref record) = reader.current {
-            writer.write_all(&record.to_bytes())?;
-            merged += 1;
-        }
-        
-        reader.read_next()?;
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.prevout_txid, record.prevout_idx),
-                chunk_idx: item.chunk_idx,
-            });
-        } else if !chunk_exhausted[item.chunk_idx] {
-            // Chunk is exhausted - delete it to free disk space
-            chunk_exhausted[item.chunk_idx] = true;
-            let _ = std::fs::remove_file(&chunk_files[item.chunk_idx]);
-            println!("    Deleted exhausted chunk {}", item.chunk_idx);
-        }
-        
-        if merged % 10_000_000 == 0 || last_report.elapsed().as_secs() >= 10 {
-            println!("    Merged: {} / {} ({:.1}%)", 
-                merged, num_records, 
-                merged as f64 / num_records as f64 * 100.0);
-            last_report = Instant::now();
-        }
-    }
-    
-    writer.flush()?;
-    
-    // Cleanup any remaining temp files
-    for chunk_path in &chunk_files {
-        let _ = std::fs::remove_file(chunk_path);
-    }
-    let _ = std::fs::remove_dir(&temp_dir);
+    let merged = ExternalSorter::new(temp_dir, sort_mem).sort::<InputRef>(input_file, output_file)?;
     
     let elapsed = start_time.elapsed();
     let file_size = std::fs::metadata(output_file)?.len();
     
-    println!("  ✅ Step 2 Complete!");
-    println!("  Output: {} records ({:.2} GB)", merged, file_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("  ✅ Step 2 Complete!");
+    info!("  Output: {} records ({:.2} GB)", merged, file_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
     
-    Ok(())
+    Ok(merged)
 }
 
 #[cfg(test)]
@@ -16,10 +16,13 @@
 use anyhow::{Context, Result};
 use hex;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::time::Instant;
+use tracing::{debug, info, warn};
 
+use super::compression::{is_compressed, RecordReader, RecordWriter};
+use super::external_sort::{read_length_prefixed, ExternalSorter, SortRecord};
 use super::input_refs::InputRef;
 use super::output_refs::OutputRef;
 
@@ -91,6 +94,27 @@ impl JoinedPrevout {
     }
 }
 
+impl SortRecord for JoinedPrevout {
+    type Key = (u32, u32, u32);
+
+    fn key(&self) -> Self::Key {
+        (self.spending_block, self.spending_tx_idx, self.spending_input_idx)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.script_pubkey.capacity()
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        Ok(read_length_prefixed(reader, 27)?
+            .and_then(|buf| Self::from_bytes(&buf).map(|(record, _)| record)))
+    }
+}
+
 /// Merge-join sorted inputs with sorted outputs
 /// 
 /// Both files must be sorted by (txid, index).
@@ -100,12 +124,13 @@ pub fn merge_join(
     outputs_file: &Path,
     joined_file: &Path,
 ) -> Result<(u64, u64)> {
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 4: Merge-Join Inputs with Outputs");
-    println!("{}", "═".repeat(60));
-    println!("  Inputs: {}", inputs_file.display());
-    println!("  Outputs: {}", outputs_file.display());
-    println!("  Joined: {}", joined_file.display());
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 4: Merge-Join Inputs with Outputs");
+    info!("{}", "═".repeat(60));
+    info!("  Inputs: {}", inputs_file.display());
+    info!("  Outputs: {}", outputs_file.display());
+    info!("  Joined: {}", joined_file.display());
     
     let start_time = Instant::now();
     
@@ -114,11 +139,13 @@ pub fn merge_join(
     // the last matched input's prevout key to resume correctly
     let mut resume_from_prevout: Option<([u8; 32], u32)> = None; // (prevout_txid, prevout_idx)
     let mut existing_joined_count = 0u64;
-    let mut file_mode = std::fs::OpenOptions::new();
     let file_exists = joined_file.exists();
     
-    if file_exists {
-        println!("  📍 Joined file exists, checking if we can resume...");
+    if file_exists && is_compressed(joined_file) {
+        // Resuming needs the tail of the file and appending, neither of which a zstd stream allows
+        warn!("  ⚠️  Compressed joined file exists - cannot resume, starting over");
+    } else if file_exists {
+        info!("  📍 Joined file exists, checking if we can resume...");
         let joined_meta = std::fs::metadata(joined_file)?;
         let joined_size = joined_meta.len();
         
@@ -153,13 +180,13 @@ pub fn merge_join(
             }
             
             if let Some(record) = last_record {
-                println!("  ✅ Found last matched input: block {}, tx {}, input {}", 
+                info!("  ✅ Found last matched input: block {}, tx {}, input {}", 
                         record.spending_block, record.spending_tx_idx, record.spending_input_idx);
                 
                 // Now find this input in the inputs file to get its prevout_txid/prevout_idx
                 // (inputs are sorted by prevout, not by spending location, so we need to scan)
-                println!("  🔍 Finding input's prevout key in inputs file...");
-                let mut inputs_scan = BufReader::new(File::open(inputs_file)?);
+                debug!("  🔍 Finding input's prevout key in inputs file...");
+                let mut inputs_scan = RecordReader::open(inputs_file, 8 * 1024 * 1024)?;
                 let mut input_scan_buf = [0u8; InputRef::SIZE];
                 let mut found_prevout: Option<([u8; 32], u32)> = None;
                 let mut scanned = 0u64;
@@ -172,22 +199,22 @@ pub fn merge_join(
                        input.tx_idx == record.spending_tx_idx &&
                        input.input_idx == record.spending_input_idx {
                         found_prevout = Some((input.prevout_txid, input.prevout_idx));
-                        println!("  ✅ Found prevout key: txid={}, idx={}", 
+                        info!("  ✅ Found prevout key: txid={}, idx={}", 
                                 hex::encode(&input.prevout_txid), input.prevout_idx);
                         break;
                     }
                     
                     if scanned % 10_000_000 == 0 {
-                        println!("  ⏳ Scanned {}M inputs...", scanned / 1_000_000);
+                        info!("  ⏳ Scanned {}M inputs...", scanned / 1_000_000);
                     }
                 }
                 
                 if let Some(prevout_key) = found_prevout {
                     resume_from_prevout = Some(prevout_key);
-                    println!("  📍 Will resume from next input after prevout ({}, {})", 
+                    info!("  📍 Will resume from next input after prevout ({}, {})", 
                             hex::encode(&prevout_key.0), prevout_key.1);
                 } else {
-                    println!("  ⚠️  Could not find input in inputs file - will re-run from start");
+                    warn!("  ⚠️  Could not find input in inputs file - will re-run from start");
                 }
                 
                 // Count existing records
@@ -197,28 +224,25 @@ pub fn merge_join(
                 while let Some(_) = read_next(&mut count_reader, &mut count_leftover, &mut count_buf)? {
                     existing_joined_count += 1;
                 }
-                println!("  📊 Existing joined records: {}", existing_joined_count);
+                info!("  📊 Existing joined records: {}", existing_joined_count);
             }
         }
     }
     
-    let mut inputs_reader = BufReader::with_capacity(32 * 1024 * 1024, File::open(inputs_file)?);
-    let mut outputs_reader = BufReader::with_capacity(32 * 1024 * 1024, File::open(outputs_file)?);
+    let mut inputs_reader = RecordReader::open(inputs_file, 32 * 1024 * 1024)?;
+    let mut outputs_reader = RecordReader::open(outputs_file, 32 * 1024 * 1024)?;
     
     // Open file for append if resuming, create if new, truncate if file exists but we're not resuming
     let mut writer = if resume_from_prevout.is_some() {
         // Resuming - append to existing file
-        BufWriter::with_capacity(32 * 1024 * 1024, 
-            std::fs::OpenOptions::new().create(false).append(true).write(true).open(joined_file)?)
+        RecordWriter::append(joined_file, 32 * 1024 * 1024)?
     } else if file_exists {
         // File exists but we're not resuming - truncate and start fresh
-        println!("  ⚠️  File exists but resume not possible - will overwrite");
-        BufWriter::with_capacity(32 * 1024 * 1024, 
-            std::fs::OpenOptions::new().create(true).truncate(true).write(true).open(joined_file)?)
+        warn!("  ⚠️  File exists but resume not possible - will overwrite");
+        RecordWriter::create(joined_file, 32 * 1024 * 1024)?
     } else {
         // New file - create
-        BufWriter::with_capacity(32 * 1024 * 1024, 
-            std::fs::OpenOptions::new().create(true).write(true).open(joined_file)?)
+        RecordWriter::create(joined_file, 32 * 1024 * 1024)?
     };
     
     let mut joined_count = existing_joined_count;
@@ -236,7 +260,7 @@ pub fn merge_join(
     let mut outputs_exhausted = false;
     
     // Helper to read next output (needed for resume logic)
-    let mut read_next_output = |reader: &mut BufReader<File>, leftover: &mut Vec<u8>, buf: &mut [u8]| -> Result<Option<OutputRef>> {
+    let mut read_next_output = |reader: &mut RecordReader, leftover: &mut Vec<u8>, buf: &mut [u8]| -> Result<Option<OutputRef>> {
         loop {
             // Try to parse from leftover
             if leftover.len() >= 51 {
@@ -256,7 +280,7 @@ pub fn merge_join(
     };
     
     if let Some((resume_txid, resume_idx)) = resume_from_prevout {
-        println!("  ⏩ Skipping inputs until resume point...");
+        info!("  ⏩ Skipping inputs until resume point...");
         let mut skipped = 0u64;
         loop {
             if inputs_reader.read_exact(&mut input_buf).is_err() {
@@ -279,13 +303,13 @@ pub fn merge_join(
                     if inputs_reader.read_exact(&mut input_buf).is_ok() {
                         current_input = Some(InputRef::from_bytes(&input_buf));
                     }
-                    println!("  ✅ Resumed from input after {} skipped inputs", skipped);
+                    info!("  ✅ Resumed from input after {} skipped inputs", skipped);
                     break;
                 }
                 std::cmp::Ordering::Greater => {
                     // We've passed the resume point, use this input
                     current_input = Some(input);
-                    println!("  ✅ Resumed from input ({} skipped)", skipped);
+                    info!("  ✅ Resumed from input ({} skipped)", skipped);
                     break;
                 }
             }
@@ -293,7 +317,7 @@ pub fn merge_join(
         
         // Also need to position outputs reader at the matching output
         // Outputs are sorted by (txid, output_idx), so find the output matching resume_txid/resume_idx
-        println!("  ⏩ Positioning outputs reader at resume point...");
+        info!("  ⏩ Positioning outputs reader at resume point...");
         let mut output_pos_found = false;
         
         while let Some(output) = read_next_output(&mut outputs_reader, &mut output_leftover, &mut output_buf)? {
@@ -308,23 +332,23 @@ pub fn merge_join(
                     let idx = output.output_idx;
                     current_output = Some(output);
                     output_pos_found = true;
-                    println!("  ✅ Positioned outputs reader at txid={}, idx={}", txid_str, idx);
+                    info!("  ✅ Positioned outputs reader at txid={}, idx={}", txid_str, idx);
                     break;
                 }
             }
         }
         
         if !output_pos_found {
-            println!("  ⚠️  Could not find matching output - starting from beginning of outputs");
-            outputs_reader = BufReader::with_capacity(32 * 1024 * 1024, File::open(outputs_file)?);
+            warn!("  ⚠️  Could not find matching output - starting from beginning of outputs");
+            outputs_reader = RecordReader::open(outputs_file, 32 * 1024 * 1024)?;
             output_leftover.clear(); // Clear leftover from positioning attempt
             // CRITICAL: Initialize current_output from the beginning
             if let Some(output) = read_next_output(&mut outputs_reader, &mut output_leftover, &mut output_buf)? {
                 current_output = Some(output);
-                println!("  ✅ Initialized outputs reader from beginning");
+                info!("  ✅ Initialized outputs reader from beginning");
             } else {
                 outputs_exhausted = true;
-                println!("  ⚠️  No outputs available - outputs file may be empty");
+                warn!("  ⚠️  No outputs available - outputs file may be empty");
             }
         }
     } else {
@@ -404,9 +428,9 @@ pub fn merge_join(
                 } else {
                     // Outputs exhausted - log diagnostic info
                     if let Some(ref last_txid) = last_output_txid {
-                        eprintln!("  ⚠️  Outputs exhausted at input prevout_txid: {}", hex::encode(input.prevout_txid));
-                        eprintln!("  Last output txid: {}", hex::encode(*last_txid));
-                        eprintln!("  Input prevout_txid > Last output txid: {}", input.prevout_txid > *last_txid);
+                        warn!("  ⚠️  Outputs exhausted at input prevout_txid: {}", hex::encode(input.prevout_txid));
+                        info!("  Last output txid: {}", hex::encode(*last_txid));
+                        info!("  Input prevout_txid > Last output txid: {}", input.prevout_txid > *last_txid);
                     }
                     outputs_exhausted = true;
                 }
@@ -415,258 +439,53 @@ pub fn merge_join(
         
         // Progress report every 10 seconds
         if last_report.elapsed().as_secs() >= 10 {
-            println!("  Joined: {}, Unmatched: {}", joined_count, unmatched_inputs);
+            info!("  Joined: {}, Unmatched: {}", joined_count, unmatched_inputs);
             last_report = Instant::now();
         }
     }
     
-    writer.flush()?;
+    writer.finish()?;
     
     let elapsed = start_time.elapsed();
     let file_size = std::fs::metadata(joined_file)?.len();
     
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 4 Complete!");
-    println!("  Joined records: {}", joined_count);
-    println!("  Unmatched inputs: {} (should be 0 for valid chain)", unmatched_inputs);
-    println!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 4 Complete!");
+    info!("  Joined records: {}", joined_count);
+    info!("  Unmatched inputs: {} (should be 0 for valid chain)", unmatched_inputs);
+    info!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
     
     Ok((joined_count, unmatched_inputs))
 }
 
-/// Sort joined file by (spending_block, spending_tx_idx, spending_input_idx) using binary merge sort
+/// Sort joined file by (spending_block, spending_tx_idx, spending_input_idx) using the external sorter
 /// This puts prevouts in the exact order we'll need them during verification.
-pub fn sort_joined(input_file: &Path, output_file: &Path) -> Result<()> {
-    use std::io::Read;
-    use std::collections::BinaryHeap;
-    use std::cmp::Reverse;
-    
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 5: Sort Joined Data by Spending Location");
-    println!("{}", "═".repeat(60));
-    println!("  Input: {}", input_file.display());
-    println!("  Output: {}", output_file.display());
+pub fn sort_joined(input_file: &Path, output_file: &Path, sort_mem: u64) -> Result<u64> {
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 5: Sort Joined Data by Spending Location");
+    info!("{}", "═".repeat(60));
+    info!("  Input: {}", input_file.display());
+    info!("  Output: {}", output_file.display());
     
     let start_time = Instant::now();
     
     let input_size = std::fs::metadata(input_file)?.len();
-    println!("  Input size: {:.2} GB", input_size as f64 / 1_073_741_824.0);
-    
-    // Chunk size: ~2GB of records
-    // Average record is ~50 bytes, so ~40M records per chunk
-    let chunk_records = 40_000_000usize;
+    info!("  Input size: {:.2} GB", input_size as f64 / 1_073_741_824.0);
     
-    // Create temp directory
     let temp_dir = input_file.parent()
         .unwrap_or(Path::new("."))
         .join("sort_tmp");
-    std::fs::create_dir_all(&temp_dir)?;
-    
-    // Phase 1: Create sorted chunks
-    println!("  Phase 1: Creating sorted chunks...");
-    let mut reader = BufReader::with_capacity(64 * 1024 * 1024, File::open(input_file)?);
-    let mut chunk_files: Vec<std::path::PathBuf> = Vec::new();
-    let mut chunk_idx = 0;
-    let mut total_records = 0;
-    
-    let mut buf = vec![0u8; 256 * 1024];
-    let mut leftover = Vec::new();
-    
-    loop {
-        // Read chunk into memory (parse variable-length records)
-        let mut records: Vec<JoinedPrevout> = Vec::with_capacity(chunk_records);
-        
-        // Read and parse records until we have enough or EOF
-        while records.len() < chunk_records {
-            // Get more data if needed
-            if leftover.len() < 27 {
-                let n = reader.read(&mut buf)?;
-                if n == 0 && leftover.is_empty() {
-                    break; // EOF
-                }
-                
-                leftover.extend_from_slice(&buf[..n]);
-            }
-            
-            // Try to parse a record
-            match JoinedPrevout::from_bytes(&leftover) {
-                Some((record, consumed)) => {
-                    records.push(record);
-                    leftover.drain(..consumed);
-                }
-                None => {
-                    // Not enough data - read more if we haven't hit EOF
-                    if leftover.len() >= 1024 * 1024 {
-                        // Too much leftover, probably error
-                        anyhow::bail!("Failed to parse record: leftover too large");
-                    }
-                    
-                    let n = reader.read(&mut buf)?;
-                    if n == 0 {
-                        break; // EOF with incomplete record
-                    }
-                    leftover.extend_from_slice(&buf[..n]);
-                }
-            }
-        }
-        
-        if records.is_empty() {
-            break;
-        }
-        
-        // Sort in memory by (spending_block, spending_tx_idx, spending_input_idx)
-        records.sort_unstable_by(|a, b| {
-            a.spending_block.cmp(&b.spending_block)
-                .then_with(|| a.spending_tx_idx.cmp(&b.spending_tx_idx))
-                .then_with(|| a.spending_input_idx.cmp(&b.spending_input_idx))
-        });
-        
-        // Write sorted chunk
-        let chunk_path = temp_dir.join(format!("chunk_{}.bin", chunk_idx));
-        let mut chunk_writer = BufWriter::with_capacity(64 * 1024 * 1024, File::create(&chunk_path)?);
-        for record in &records {
-            chunk_writer.write_all(&record.to_bytes())?;
-        }
-        chunk_writer.flush()?;
-        
-        println!("    Chunk {}: {} records", chunk_idx, records.len());
-        chunk_files.push(chunk_path);
-        total_records += records.len();
-        chunk_idx += 1;
-    }
-    
-    println!("  Phase 2: Merging {} chunks...", chunk_files.len());
-    
-    // Phase 2: K-way merge
-    struct ChunkReader {
-        reader: BufReader<File>,
-        current: Option<JoinedPrevout>,
-        chunk_idx: usize,
-        leftover: Vec<u8>,
-    }
-    
-    impl ChunkReader {
-        fn read_next(&mut self) -> Result<()> {
-            let mut buf = vec![0u8; 256 * 1024];
-            
-            // Try to parse from leftover first
-            loop {
-                if self.leftover.len() >= 27 {
-                    match JoinedPrevout::from_bytes(&self.leftover) {
-                        Some((record, consumed)) => {
-                            self.current = Some(record);
-                            self.leftover.drain(..consumed);
-                            return Ok(());
-                        }
-                        None => {
-                            // Need more data
-                        }
-                    }
-                }
-                
-                // Read more data
-                match self.reader.read(&mut buf) {
-                    Ok(0) => {
-                        // EOF
-                        self.current = None;
-                        return Ok(());
-                    }
-                    Ok(n) => {
-                        self.leftover.extend_from_slice(&buf[..n]);
-                    }
-                    Err(e) => return Err(e.into()),
-                }
-            }
-        }
-    }
-    
-    // Wrapper for heap ordering (min-heap by key)
-    #[derive(Eq, PartialEq)]
-    struct HeapItem {
-        key: (u32, u32, u32),
-        chunk_idx: usize,
-    }
-    
-    impl Ord for HeapItem {
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            // Reverse for min-heap
-            other.key.cmp(&self.key)
-        }
-    }
-    
-    impl PartialOrd for HeapItem {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
-        }
-    }
-    
-    let mut chunk_readers: Vec<ChunkReader> = Vec::new();
-    let mut heap: BinaryHeap<HeapItem> = BinaryHeap::new();
-    
-    for (idx, chunk_path) in chunk_files.iter().enumerate() {
-        let file = File::open(chunk_path)?;
-        let mut reader = ChunkReader {
-            reader: BufReader::with_capacity(1024 * 1024, file),
-            current: None,
-            chunk_idx: idx,
-            leftover: Vec::new(),
-        };
-        reader.read_next()?;
-        
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.spending_block, record.spending_tx_idx, record.spending_input_idx),
-                chunk_idx: idx,
-            });
-        }
-        chunk_readers.push(reader);
-    }
-    
-    // Merge chunks
-    let mut output_writer = BufWriter::with_capacity(64 * 1024 * 1024, File::create(output_file)?);
-    let mut merged = 0u64;
-    let progress_interval = (total_records / 100) as u64;
-    
-    while let Some(item) = heap.pop() {
-        let reader = &mut chunk_readers[item.chunk_idx];
-        if let Some(record) = reader.current.take() {
-            output_writer.write_all(&record.to_bytes())?;
-            merged += 1;
-            
-            if merged % progress_interval == 0 {
-                println!("    Merged: {} / {} ({:.1}%)", merged, total_records, 
-                    merged as f64 / total_records as f64 * 100.0);
-            }
-        }
-        
-        // Read next from this chunk
-        reader.read_next()?;
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.spending_block, record.spending_tx_idx, record.spending_input_idx),
-                chunk_idx: item.chunk_idx,
-            });
-        } else {
-            // Chunk exhausted - delete the temp file
-            let chunk_path = &chunk_files[item.chunk_idx];
-            let _ = std::fs::remove_file(chunk_path);
-        }
-    }
-    
-    output_writer.flush()?;
-    
-    // Cleanup temp directory
-    let _ = std::fs::remove_dir_all(&temp_dir);
+    let merged = ExternalSorter::new(temp_dir, sort_mem).sort::<JoinedPrevout>(input_file, output_file)?;
     
     let elapsed = start_time.elapsed();
     let output_size = std::fs::metadata(output_file)?.len();
     
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 5 Complete!");
-    println!("  Output: {} records ({:.2} GB)", merged, output_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 5 Complete!");
+    info!("  Output: {} records ({:.2} GB)", merged, output_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
     
-    Ok(())
+    Ok(merged)
 }
-
@@ -13,20 +13,34 @@
 //! 4. **Merge Join**: Match inputs with outputs to get prevout data
 //! 5. **Sort by Location**: External sort by (block, tx, input)
 //! 6. **Verify Scripts**: Stream blocks + prevouts in lockstep, verify scripts in parallel
+//!    (each failing input is saved as a replayable JSON artifact, see [`divergence`])
 //!
 //! ## Memory Usage
 //!
-//! All steps are streaming - peak memory ~1-2GB for buffers.
-//! Intermediate files total ~25GB on disk.
+//! All steps are streaming - peak memory ~1-2GB for buffers, plus the sort
+//! budget (`--sort-mem` / `SORT_MEM`, default 4G) for steps 2, 3b and 5.
+//! Intermediate files total ~25GB on disk, or roughly a quarter of that with `--compress`
+//! (zstd streams, see [`compression`]).
+//!
+//! ## Resuming
+//!
+//! [`state::PipelineState`] records finished steps and checkpoints of the streaming ones in
+//! `sort_merge_state.json`, so an interrupted run picks up where it stopped.
 
+pub mod compression;
+pub mod divergence;
+pub mod external_sort;
 pub mod input_refs;
 pub mod output_refs;
 pub mod merge_join;
+pub mod state;
 pub mod verify;
 
+pub use external_sort::{parse_mem_size, ExternalSorter, SortRecord};
 pub use input_refs::extract_input_refs;
 pub use output_refs::extract_outputs;
 pub use merge_join::merge_join;
+pub use state::{PipelineState, Stage};
 pub use verify::verify_scripts;
 
 
@@ -14,16 +14,19 @@
 
 use anyhow::{Context, Result};
 use std::fs::File;
-use std::io::{BufWriter, Write, BufReader, Read};
+use std::io::{Write, BufReader, Read};
 use std::path::Path;
 use std::time::Instant;
-use rayon::prelude::*;
 
 use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
 use blvm_protocol::serialization::encode_varint;
 use blvm_protocol::transaction::is_coinbase;
 use blvm_protocol::types::Hash;
+use tracing::{info, warn};
 
+use super::compression::{is_compressed, RecordWriter};
+use super::external_sort::{read_length_prefixed, ExternalSorter, SortRecord};
+use super::state::StageCheckpoint;
 use crate::chunked_cache::ChunkedBlockIterator;
 
 /// Output record (variable size)
@@ -86,6 +89,27 @@ impl OutputRef {
     }
 }
 
+impl SortRecord for OutputRef {
+    type Key = (Hash, u32);
+
+    fn key(&self) -> Self::Key {
+        (self.txid, self.output_idx)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.script_pubkey.capacity()
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        Ok(read_length_prefixed(reader, 51)?
+            .and_then(|buf| Self::from_bytes(&buf).map(|(record, _)| record)))
+    }
+}
+
 /// Calculate txid from transaction
 /// CRITICAL: Must use the SAME txid calculation as blvm-consensus to ensure merge-join works
 /// Step 1 reads prevout.hash (which is the txid), and step 3 calculates txid - they MUST match
@@ -102,23 +126,35 @@ pub fn extract_outputs(
     start_height: u64,
     end_height: u64,
     progress_interval: u64,
+    mut checkpoint: Option<&mut StageCheckpoint>,
 ) -> Result<u64> {
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 3: Extract Transaction Outputs");
-    println!("{}", "═".repeat(60));
-    println!("  Chunks dir: {}", chunks_dir.display());
-    println!("  Blocks: {} to {}", start_height, end_height);
-    println!("  Output: {}", output_file.display());
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 3: Extract Transaction Outputs");
+    info!("{}", "═".repeat(60));
+    info!("  Chunks dir: {}", chunks_dir.display());
+    info!("  Blocks: {} to {}", start_height, end_height);
+    info!("  Output: {}", output_file.display());
     
     let start_time = Instant::now();
     
+    // With a checkpoint, resume exactly from it; the file scan below is the fallback
+    // for runs without a state file
+    let resume = match &checkpoint {
+        Some(checkpoint) => checkpoint.resume()?,
+        None => None,
+    };
+    
     // Check if output file exists and find last processed block height
-    let mut actual_start_height = start_height;
-    let mut file_mode = std::fs::OpenOptions::new();
-    let file_exists = output_file.exists();
+    let mut actual_start_height = resume.map_or(start_height, |r| r.next_height);
+    // A compressed file can't be scanned from the end or appended to, so it is rewritten
+    let file_exists = checkpoint.is_none() && output_file.exists() && !is_compressed(output_file);
+    if checkpoint.is_none() && output_file.exists() && !file_exists {
+        warn!("  ⚠️  Compressed output file exists - cannot resume, starting over");
+    }
     
     if file_exists {
-        println!("  📍 Output file exists, checking last processed block...");
+        info!("  📍 Output file exists, checking last processed block...");
         // OPTIMIZATION: Use the same approach as check_last_outputs - read last chunk and parse
         use std::io::Seek;
         let existing_file = File::open(output_file)
@@ -161,37 +197,35 @@ pub fn extract_outputs(
         // If parsing failed or gave garbage, use known value from check_last_outputs tool
         // The tool confirmed last output is from block 762154
         if max_block_height == 0 || max_block_height >= 1_000_000 {
-            println!("  ⚠️  Parsing gave invalid result ({}), using known fallback: 762154", max_block_height);
+            warn!("  ⚠️  Parsing gave invalid result ({}), using known fallback: 762154", max_block_height);
             max_block_height = 762154; // Known from check_last_outputs tool
         }
         
-        println!("  ✅ Scanned last {}MB, found {} records, max block height: {}", 
+        info!("  ✅ Scanned last {}MB, found {} records, max block height: {}", 
                  chunk_size / (1024 * 1024), records.len(), max_block_height);
         
         if max_block_height > 0 && max_block_height < 1_000_000 {
             actual_start_height = max_block_height + 1;
-            println!("  ✅ Found {} records in last {}MB, last processed block: {}", 
+            info!("  ✅ Found {} records in last {}MB, last processed block: {}", 
                      records.len(), chunk_size / (1024 * 1024), max_block_height);
-            println!("  📍 Resuming from block {} (will append to existing file)", actual_start_height);
+            info!("  📍 Resuming from block {} (will append to existing file)", actual_start_height);
             
             if actual_start_height >= end_height {
-                println!("  ✅ All blocks already processed (up to {})", max_block_height);
+                info!("  ✅ All blocks already processed (up to {})", max_block_height);
                 return Ok(records.len() as u64);
             }
         } else {
-            println!("  ⚠️  Couldn't determine last block, using fallback: 762154");
+            warn!("  ⚠️  Couldn't determine last block, using fallback: 762154");
             actual_start_height = 762155; // Resume from known last block + 1
         }
     }
     
     // Create or append to output file
-    let file = file_mode
-        .create(true)
-        .append(file_exists && actual_start_height > start_height)
-        .write(true)
-        .open(output_file)
-        .with_context(|| format!("Failed to open output file: {}", output_file.display()))?;
-    let mut writer = BufWriter::with_capacity(64 * 1024 * 1024, file); // 64MB buffer
+    let mut writer = if resume.is_some() || (file_exists && actual_start_height > start_height) {
+        RecordWriter::append(output_file, 64 * 1024 * 1024)? // 64MB buffer
+    } else {
+        RecordWriter::create(output_file, 64 * 1024 * 1024)?
+    };
     
     // Create block iterator starting from actual_start_height
     // CRITICAL FIX: Calculate max_blocks from end_height to ensure we process all requested blocks
@@ -202,10 +236,10 @@ pub fn extract_outputs(
         .ok_or_else(|| anyhow::anyhow!("Failed to create block iterator - chunks.meta not found?"))?;
     
     // Log the actual end_height the iterator will use (may be limited by metadata.total_blocks)
-    eprintln!("  📍 Block iterator configured: start={}, requested_end={}, max_blocks={}", 
+    info!("  📍 Block iterator configured: start={}, requested_end={}, max_blocks={}", 
               actual_start_height, end_height, max_blocks);
     
-    let mut total_outputs = 0u64;
+    let mut total_outputs = resume.map_or(0, |r| r.records);
     let mut height = actual_start_height;
     let mut last_report = Instant::now();
     
@@ -215,14 +249,14 @@ pub fn extract_outputs(
             Some(data) => data,
             None => {
                 if height < end_height {
-                    eprintln!("  ⚠️  WARNING: Block iterator ended at height {} but end_height is {}", height, end_height);
-                    eprintln!("  Missing blocks: {} to {} ({} blocks)", height, end_height - 1, end_height - height);
-                    eprintln!("  This will cause missing prevouts for transactions in blocks {} to {}", height, end_height - 1);
-                    eprintln!("  Possible causes:");
-                    eprintln!("    1. Chunks don't contain all blocks up to {}", end_height);
-                    eprintln!("    2. Chunk metadata (chunks.meta) reports fewer blocks than available");
-                    eprintln!("    3. Block index is incomplete");
-                    eprintln!("  Solution: Ensure chunks contain all blocks up to {} or update chunks.meta", end_height);
+                    warn!("  ⚠️  WARNING: Block iterator ended at height {} but end_height is {}", height, end_height);
+                    info!("  Missing blocks: {} to {} ({} blocks)", height, end_height - 1, end_height - height);
+                    info!("  This will cause missing prevouts for transactions in blocks {} to {}", height, end_height - 1);
+                    info!("  Possible causes:");
+                    info!("    1. Chunks don't contain all blocks up to {}", end_height);
+                    info!("    2. Chunk metadata (chunks.meta) reports fewer blocks than available");
+                    info!("    3. Block index is incomplete");
+                    info!("  Solution: Ensure chunks contain all blocks up to {} or update chunks.meta", end_height);
                 }
                 // Stop extraction - we've processed all available blocks
                 break;
@@ -263,7 +297,7 @@ pub fn extract_outputs(
             let elapsed = start_time.elapsed().as_secs_f64();
             let rate = processed as f64 / elapsed;
             let remaining = (end_height - height) as f64 / rate;
-            println!(
+            info!(
                 "  Block {}/{} ({:.1}%) - {} outputs - {:.0} blk/s - ETA: {:.0}m",
                 height, end_height,
                 (height - start_height) as f64 / (end_height - start_height) as f64 * 100.0,
@@ -275,45 +309,50 @@ pub fn extract_outputs(
         }
         
         height += 1;
+        
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            if checkpoint.due() {
+                if let Some(len) = writer.sync()? {
+                    checkpoint.save(len, height, total_outputs, 0)?;
+                }
+            }
+        }
     }
     
-    writer.flush()?;
+    writer.finish()?;
     
     let elapsed = start_time.elapsed();
     let file_size = std::fs::metadata(output_file)?.len();
     
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 3 Complete!");
-    println!("  Total outputs: {}", total_outputs);
-    println!("  Blocks processed: {}", height - start_height);
-    println!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
-    println!("  Rate: {:.0} blocks/sec", (height - start_height) as f64 / elapsed.as_secs_f64());
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 3 Complete!");
+    info!("  Total outputs: {}", total_outputs);
+    info!("  Blocks processed: {}", height - start_height);
+    info!("  File size: {:.2} GB", file_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("  Rate: {:.0} blocks/sec", (height - start_height) as f64 / elapsed.as_secs_f64());
     
     Ok(total_outputs)
 }
 
-/// Sort outputs file by (txid, output_idx) using binary external merge sort
+/// Sort outputs file by (txid, output_idx) using the external sorter
 /// 
-/// Uses binary merge sort (no hex expansion) like Step 2.
-/// Variable-length records are handled by parsing them during read.
-pub fn sort_outputs(input_file: &Path, output_file: &Path) -> Result<()> {
-    use std::io::{Read, Seek, SeekFrom};
-    use std::collections::BinaryHeap;
-    use std::cmp::Reverse;
-    
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 3b: Sort Outputs by TxID");
-    println!("{}", "═".repeat(60));
-    println!("  Input: {}", input_file.display());
-    println!("  Output: {}", output_file.display());
+/// Variable-length records are parsed as they are read; runs of up to `sort_mem` bytes are
+/// sorted in parallel, then k-way merged (see [`ExternalSorter`]).
+pub fn sort_outputs(input_file: &Path, output_file: &Path, sort_mem: u64) -> Result<u64> {
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 3b: Sort Outputs by TxID");
+    info!("{}", "═".repeat(60));
+    info!("  Input: {}", input_file.display());
+    info!("  Output: {}", output_file.display());
     
     let start_time = Instant::now();
     
     let input_meta = std::fs::metadata(input_file)?;
     let input_size = input_meta.len();
     let input_mtime = input_meta.modified()?;
-    println!("  Input size: {:.2} GB", input_size as f64 / 1_073_741_824.0);
+    info!("  Input size: {:.2} GB", input_size as f64 / 1_073_741_824.0);
     
     // SAFETY CHECK: If output exists, verify it was created from the same input file
     if output_file.exists() {
@@ -322,15 +361,15 @@ pub fn sort_outputs(input_file: &Path, output_file: &Path) -> Result<()> {
         
         // If input file is NEWER than output file, the input has been updated
         if input_mtime > output_mtime {
-            eprintln!("\n  ⚠️  WARNING: Output file exists but input file is NEWER!");
-            eprintln!("     Input modified:  {:?}", input_mtime);
-            eprintln!("     Output modified: {:?}", output_mtime);
-            eprintln!("     This means the input file was updated AFTER the output was created.");
-            eprintln!("     The output file is likely INCOMPLETE and should be regenerated.");
-            eprintln!("\n  Options:");
-            eprintln!("     1. Delete {} and re-run step3b", output_file.display());
-            eprintln!("     2. Continue anyway (NOT RECOMMENDED - will cause merge-join failures)");
-            eprintln!("\n  Aborting to prevent incomplete data. Delete the output file to proceed.");
+            warn!("\n  ⚠️  WARNING: Output file exists but input file is NEWER!");
+            info!("     Input modified:  {:?}", input_mtime);
+            info!("     Output modified: {:?}", output_mtime);
+            info!("     This means the input file was updated AFTER the output was created.");
+            info!("     The output file is likely INCOMPLETE and should be regenerated.");
+            info!("\n  Options:");
+            info!("     1. Delete {} and re-run step3b", output_file.display());
+            info!("     2. Continue anyway (NOT RECOMMENDED - will cause merge-join failures)");
+            info!("\n  Aborting to prevent incomplete data. Delete the output file to proceed.");
             anyhow::bail!("Output file is outdated - input file was modified after output was created. Delete {} to regenerate.", output_file.display());
         }
         
@@ -338,303 +377,26 @@ pub fn sort_outputs(input_file: &Path, output_file: &Path) -> Result<()> {
         let output_size = output_meta.len();
         let size_diff_pct = ((input_size as f64 - output_size as f64) / input_size as f64 * 100.0).abs();
         if size_diff_pct > 5.0 {
-            eprintln!("\n  ⚠️  WARNING: Input and output file sizes differ significantly!");
-            eprintln!("     Input size:  {:.2} GB", input_size as f64 / 1_073_741_824.0);
-            eprintln!("     Output size: {:.2} GB", output_size as f64 / 1_073_741_824.0);
-            eprintln!("     Difference: {:.1}%", size_diff_pct);
-            eprintln!("     This may indicate incomplete data.");
+            warn!("\n  ⚠️  WARNING: Input and output file sizes differ significantly!");
+            info!("     Input size:  {:.2} GB", input_size as f64 / 1_073_741_824.0);
+            info!("     Output size: {:.2} GB", output_size as f64 / 1_073_741_824.0);
+            info!("     Difference: {:.1}%", size_diff_pct);
+            info!("     This may indicate incomplete data.");
         }
     }
     
-    // Chunk size: ~2GB of records
-    // Average record is ~50 bytes, so ~40M records per chunk
-    let chunk_records = 40_000_000usize;
-    
-    // Create temp directory
     let temp_dir = input_file.parent()
         .unwrap_or(Path::new("."))
         .join("sort_tmp");
-    std::fs::create_dir_all(&temp_dir)?;
-    
-    // Phase 1: Create sorted chunks (PARALLELIZED)
-    // Process chunks in batches to avoid excessive memory usage
-    println!("  Phase 1: Creating sorted chunks (parallelized)...");
-    use std::sync::Mutex;
-    
-    let mut reader = BufReader::with_capacity(64 * 1024 * 1024, File::open(input_file)?);
-    let chunk_files = Mutex::new(Vec::new());
-    let total_records = Mutex::new(0u64);
-    let chunk_idx = Mutex::new(0usize);
-    
-    let mut buf = vec![0u8; 64 * 1024]; // Read buffer
-    let mut leftover = Vec::new();
-    
-    // Process chunks in batches (read sequentially, sort/write in parallel)
-    // Reduced to 2 to avoid OOM - each chunk is ~2GB in memory
-    const PARALLEL_BATCH_SIZE: usize = 2; // Process 2 chunks at a time
-    let mut batch = Vec::new();
-    
-    loop {
-        // Read chunk into memory (parse variable-length records)
-        let mut records: Vec<OutputRef> = Vec::with_capacity(chunk_records);
-        
-        // Read and parse records until we have enough or EOF
-        while records.len() < chunk_records {
-            // Get more data if needed
-            if leftover.len() < 51 {
-                let n = reader.read(&mut buf)?;
-                if n == 0 && leftover.is_empty() {
-                    break; // EOF
-                }
-                
-                leftover.extend_from_slice(&buf[..n]);
-            }
-            
-            // Try to parse a record
-            match OutputRef::from_bytes(&leftover) {
-                Some((record, consumed)) => {
-                    records.push(record);
-                    leftover.drain(..consumed);
-                }
-                None => {
-                    // Not enough data - read more if we haven't hit EOF
-                    if leftover.len() >= 1024 * 1024 {
-                        // Too much leftover, probably error
-                        anyhow::bail!("Failed to parse record: leftover too large");
-                    }
-                    
-                    let n = reader.read(&mut buf)?;
-                    if n == 0 {
-                        break; // EOF with incomplete record
-                    }
-                    leftover.extend_from_slice(&buf[..n]);
-                }
-            }
-        }
-        
-        if records.is_empty() {
-            // Process remaining batch
-            if !batch.is_empty() {
-                batch.into_par_iter().for_each(|mut records: Vec<OutputRef>| {
-                    // Sort in memory by (txid, output_idx)
-                    records.sort_unstable_by(|a, b| {
-                        a.txid.cmp(&b.txid)
-                            .then_with(|| a.output_idx.cmp(&b.output_idx))
-                    });
-                    
-                    // Get chunk index
-                    let current_idx = {
-                        let mut idx = chunk_idx.lock().unwrap();
-                        let idx_val = *idx;
-                        *idx += 1;
-                        idx_val
-                    };
-                    
-                    // Write sorted chunk
-                    let chunk_path = temp_dir.join(format!("chunk_{}.bin", current_idx));
-                    let mut chunk_writer = BufWriter::with_capacity(64 * 1024 * 1024, 
-                        File::create(&chunk_path).expect("Failed to create chunk file"));
-                    for record in &records {
-                        chunk_writer.write_all(&record.to_bytes())
-                            .expect("Failed to write chunk");
-                    }
-                    chunk_writer.flush().expect("Failed to flush chunk");
-                    
-                    // Update totals
-                    {
-                        let mut files = chunk_files.lock().unwrap();
-                        files.push(chunk_path);
-                        let mut total = total_records.lock().unwrap();
-                        *total += records.len() as u64;
-                    }
-                    
-                    if current_idx % 10 == 0 {
-                        println!("    Chunk {}: {} records", current_idx, records.len());
-                    }
-                });
-            }
-            break;
-        }
-        
-        batch.push(records);
-        
-        // When batch is full, process in parallel
-        if batch.len() >= PARALLEL_BATCH_SIZE {
-            let batch_to_process = std::mem::take(&mut batch);
-            batch_to_process.into_par_iter().for_each(|mut records: Vec<OutputRef>| {
-                // Sort in memory by (txid, output_idx)
-                records.sort_unstable_by(|a, b| {
-                    a.txid.cmp(&b.txid)
-                        .then_with(|| a.output_idx.cmp(&b.output_idx))
-                });
-                
-                // Get chunk index
-                let current_idx = {
-                    let mut idx = chunk_idx.lock().unwrap();
-                    let idx_val = *idx;
-                    *idx += 1;
-                    idx_val
-                };
-                
-                // Write sorted chunk
-                let chunk_path = temp_dir.join(format!("chunk_{}.bin", current_idx));
-                let mut chunk_writer = BufWriter::with_capacity(64 * 1024 * 1024, 
-                    File::create(&chunk_path).expect("Failed to create chunk file"));
-                for record in &records {
-                    chunk_writer.write_all(&record.to_bytes())
-                        .expect("Failed to write chunk");
-                }
-                chunk_writer.flush().expect("Failed to flush chunk");
-                
-                // Update totals
-                {
-                    let mut files = chunk_files.lock().unwrap();
-                    files.push(chunk_path);
-                    let mut total = total_records.lock().unwrap();
-                    *total += records.len() as u64;
-                }
-                
-                if current_idx % 10 == 0 {
-                    println!("    Chunk {}: {} records", current_idx, records.len());
-                }
-            });
-        }
-    }
-    
-    let chunk_files = chunk_files.into_inner().unwrap();
-    let total_records = total_records.into_inner().unwrap();
-    
-    println!("  Phase 2: Merging {} chunks...", chunk_files.len());
-    
-    // Phase 2: K-way merge
-    struct ChunkReader {
-        reader: BufReader<File>,
-        current: Option<OutputRef>,
-        chunk_idx: usize,
-        leftover: Vec<u8>,
-    }
-    
-    impl ChunkReader {
-        fn read_next(&mut self) -> Result<()> {
-            let mut buf = vec![0u8; 64 * 1024];
-            
-            // Try to parse from leftover first
-            loop {
-                if self.leftover.len() >= 51 {
-                    match OutputRef::from_bytes(&self.leftover) {
-                        Some((record, consumed)) => {
-                            self.current = Some(record);
-                            self.leftover.drain(..consumed);
-                            return Ok(());
-                        }
-                        None => {
-                            // Need more data
-                        }
-                    }
-                }
-                
-                // Read more data
-                match self.reader.read(&mut buf) {
-                    Ok(0) => {
-                        // EOF
-                        self.current = None;
-                        return Ok(());
-                    }
-                    Ok(n) => {
-                        self.leftover.extend_from_slice(&buf[..n]);
-                    }
-                    Err(e) => return Err(e.into()),
-                }
-            }
-        }
-    }
-    
-    // Wrapper for heap ordering (min-heap by key)
-    #[derive(Eq, PartialEq)]
-    struct HeapItem {
-        key: ([u8; 32], u32),
-        chunk_idx: usize,
-    }
-    
-    impl Ord for HeapItem {
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            // Reverse for min-heap
-            other.key.cmp(&self.key)
-        }
-    }
-    
-    impl PartialOrd for HeapItem {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
-        }
-    }
-    
-    let mut chunk_readers: Vec<ChunkReader> = Vec::new();
-    let mut heap: BinaryHeap<HeapItem> = BinaryHeap::new();
-    
-    for (idx, chunk_path) in chunk_files.iter().enumerate() {
-        let file = File::open(chunk_path)?;
-        let mut reader = ChunkReader {
-            reader: BufReader::with_capacity(1024 * 1024, file),
-            current: None,
-            chunk_idx: idx,
-            leftover: Vec::new(),
-        };
-        reader.read_next()?;
-        
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.txid, record.output_idx),
-                chunk_idx: idx,
-            });
-        }
-        chunk_readers.push(reader);
-    }
-    
-    // Merge chunks
-    let mut output_writer = BufWriter::with_capacity(64 * 1024 * 1024, File::create(output_file)?);
-    let mut merged = 0u64;
-    let progress_interval = (total_records / 100) as u64;
-    
-    while let Some(item) = heap.pop() {
-        let reader = &mut chunk_readers[item.chunk_idx];
-        if let Some(record) = reader.current.take() {
-            output_writer.write_all(&record.to_bytes())?;
-            merged += 1;
-            
-            if merged % progress_interval == 0 {
-                println!("    Merged: {} / {} ({:.1}%)", merged, total_records, 
-                    merged as f64 / total_records as f64 * 100.0);
-            }
-        }
-        
-        // Read next from this chunk
-        reader.read_next()?;
-        if let Some(ref record) = reader.current {
-            heap.push(HeapItem {
-                key: (record.txid, record.output_idx),
-                chunk_idx: item.chunk_idx,
-            });
-        } else {
-            // Chunk exhausted - delete the temp file
-            let chunk_path = &chunk_files[item.chunk_idx];
-            let _ = std::fs::remove_file(chunk_path);
-        }
-    }
-    
-    output_writer.flush()?;
-    
-    // Cleanup temp directory
-    let _ = std::fs::remove_dir_all(&temp_dir);
+    let merged = ExternalSorter::new(temp_dir, sort_mem).sort::<OutputRef>(input_file, output_file)?;
     
     let elapsed = start_time.elapsed();
     let output_size = std::fs::metadata(output_file)?.len();
     
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 3b Complete!");
-    println!("  Output: {} records ({:.2} GB)", merged, output_size as f64 / 1_073_741_824.0);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 3b Complete!");
+    info!("  Output: {} records ({:.2} GB)", merged, output_size as f64 / 1_073_741_824.0);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
     
-    Ok(())
+    Ok(merged)
 }
-
@@ -0,0 +1,385 @@
+//! Stage checkpoints of the sort-merge pipeline
+//!
+//! `sort_merge_state.json` next to the intermediate files records which stages have finished
+//! (and how long their output was then), so a rerun skips them, plus the last durable position
+//! of a streaming stage that was interrupted part way.
+//!
+//! Steps 1, 3 and 6 take a [`StageCheckpoint`]: about once a minute they fsync their output and
+//! save its length with the next block height to process. On resume the output is truncated
+//! back to that length, dropping anything written after the checkpoint, and the stage carries
+//! on from there. Compressed outputs can't be cut mid-stream, so those stages start over.
+//!
+//! Rerunning a stage invalidates every stage that consumes its output.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::compression::is_compressed;
+
+pub const STATE_FILE: &str = "sort_merge_state.json";
+/// How often streaming stages make their progress durable
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One step of the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    ExtractInputs,
+    SortInputs,
+    ExtractOutputs,
+    SortOutputs,
+    MergeJoin,
+    SortJoined,
+    Verify,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::ExtractInputs,
+        Stage::SortInputs,
+        Stage::ExtractOutputs,
+        Stage::SortOutputs,
+        Stage::MergeJoin,
+        Stage::SortJoined,
+        Stage::Verify,
+    ];
+
+    /// Name used on the command line and in the state file
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::ExtractInputs => "step1",
+            Stage::SortInputs => "step2",
+            Stage::ExtractOutputs => "step3",
+            Stage::SortOutputs => "step3b",
+            Stage::MergeJoin => "step4",
+            Stage::SortJoined => "step5",
+            Stage::Verify => "step6",
+        }
+    }
+
+    /// Stages whose output this one reads.
+    fn depends_on(self) -> &'static [Stage] {
+        match self {
+            Stage::ExtractInputs | Stage::ExtractOutputs => &[],
+            Stage::SortInputs => &[Stage::ExtractInputs],
+            Stage::SortOutputs => &[Stage::ExtractOutputs],
+            Stage::MergeJoin => &[Stage::SortInputs, Stage::SortOutputs],
+            Stage::SortJoined => &[Stage::MergeJoin],
+            Stage::Verify => &[Stage::SortJoined],
+        }
+    }
+
+    /// Whether this stage reads, directly or not, the output of `other`.
+    fn downstream_of(self, other: Stage) -> bool {
+        self.depends_on()
+            .iter()
+            .any(|&dep| dep == other || dep.downstream_of(other))
+    }
+}
+
+/// What the state file knows about one stage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageState {
+    pub completed: bool,
+    /// Length of the output at completion or at the last checkpoint
+    pub output_len: u64,
+    /// Next block height to process after the last checkpoint
+    #[serde(default)]
+    pub next_height: Option<u64>,
+    /// Records written (scripts verified for step 6)
+    pub records: u64,
+    /// Scripts that failed (step 6 only)
+    #[serde(default)]
+    pub failed: u64,
+    /// RFC 3339 time of the last change
+    pub updated: String,
+}
+
+/// Where an interrupted stage picks up.
+#[derive(Debug, Clone, Copy)]
+pub struct ResumePoint {
+    pub next_height: u64,
+    pub records: u64,
+    pub failed: u64,
+}
+
+/// The sidecar state file of one data directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PipelineState {
+    /// Block range and compression the files were built with; any change starts over
+    pub start_height: u64,
+    pub end_height: u64,
+    pub compressed: bool,
+    pub stages: BTreeMap<String, StageState>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl PipelineState {
+    /// State of `data_dir`, or an empty one if there is none or it was made for another run.
+    pub fn load(
+        data_dir: &Path,
+        start_height: u64,
+        end_height: u64,
+        compressed: bool,
+    ) -> Result<Self> {
+        let path = data_dir.join(STATE_FILE);
+        let fresh = Self {
+            start_height,
+            end_height,
+            compressed,
+            stages: BTreeMap::new(),
+            path: path.clone(),
+        };
+        if !path.exists() {
+            return Ok(fresh);
+        }
+        let json =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut state: Self = match serde_json::from_slice(&json) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("  ⚠️  Ignoring unreadable {}: {}", path.display(), e);
+                return Ok(fresh);
+            }
+        };
+        if (state.start_height, state.end_height, state.compressed)
+            != (start_height, end_height, compressed)
+        {
+            warn!(
+                "  ⚠️  {} is for blocks {}..{} (compressed: {}) - starting over",
+                path.display(),
+                state.start_height,
+                state.end_height,
+                state.compressed
+            );
+            return Ok(fresh);
+        }
+        state.path = path;
+        Ok(state)
+    }
+
+    /// Write the state file atomically.
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+        Ok(())
+    }
+
+    pub fn stage(&self, stage: Stage) -> Option<&StageState> {
+        self.stages.get(stage.name())
+    }
+
+    /// `stage` finished and its output is still the one it produced.
+    pub fn is_complete(&self, stage: Stage, output: &Path) -> bool {
+        self.stage(stage).is_some_and(|s| {
+            s.completed && std::fs::metadata(output).is_ok_and(|m| m.len() == s.output_len)
+        })
+    }
+
+    /// Run `stage` unless it is already complete. Starting it drops the state of every stage
+    /// downstream, and a successful run records the output's length and the record count `run`
+    /// returns.
+    pub fn run<F>(&mut self, stage: Stage, output: &Path, run: F) -> Result<()>
+    where
+        F: FnOnce(&mut StageCheckpoint) -> Result<u64>,
+    {
+        if self.is_complete(stage, output) {
+            info!(
+                "  ⏭️  {} already complete ({}), skipping",
+                stage.name(),
+                output.display()
+            );
+            return Ok(());
+        }
+        self.stages.retain(|name, _| {
+            !Stage::ALL
+                .iter()
+                .any(|s| s.name() == name && s.downstream_of(stage))
+        });
+        if let Some(state) = self.stages.get_mut(stage.name()) {
+            state.completed = false;
+        }
+        self.save()?;
+
+        let records = run(&mut StageCheckpoint {
+            state: self,
+            stage,
+            output: output.to_path_buf(),
+            last_saved: Instant::now(),
+        })?;
+
+        let output_len = std::fs::metadata(output)
+            .with_context(|| format!("{} produced no {}", stage.name(), output.display()))?
+            .len();
+        let failed = self.stage(stage).map_or(0, |s| s.failed);
+        self.stages.insert(
+            stage.name().to_string(),
+            StageState {
+                completed: true,
+                output_len,
+                next_height: None,
+                records,
+                failed,
+                updated: Utc::now().to_rfc3339(),
+            },
+        );
+        self.save()
+    }
+
+    pub fn print_status(&self) {
+        for stage in Stage::ALL {
+            match self.stage(stage) {
+                Some(s) if s.completed => println!(
+                    "  ✓ {:<7} complete: {} records ({})",
+                    stage.name(),
+                    s.records,
+                    s.updated
+                ),
+                Some(s) => println!(
+                    "  ◐ {:<7} interrupted: resumes at block {} ({} records)",
+                    stage.name(),
+                    s.next_height.map_or("?".to_string(), |h| h.to_string()),
+                    s.records
+                ),
+                None => println!("  ✗ {:<7} not run", stage.name()),
+            }
+        }
+    }
+}
+
+/// Progress handle of a running stage.
+pub struct StageCheckpoint<'a> {
+    state: &'a mut PipelineState,
+    stage: Stage,
+    output: PathBuf,
+    last_saved: Instant,
+}
+
+impl StageCheckpoint<'_> {
+    /// The last checkpoint of an interrupted run, if the output still holds everything written
+    /// up to it. The output is truncated back to the checkpointed length.
+    pub fn resume(&self) -> Result<Option<ResumePoint>> {
+        let Some(state) = self.state.stage(self.stage) else {
+            return Ok(None);
+        };
+        let Some(next_height) = state.next_height else {
+            return Ok(None);
+        };
+        let len = std::fs::metadata(&self.output).map_or(0, |m| m.len());
+        if state.completed || is_compressed(&self.output) || len < state.output_len {
+            return Ok(None);
+        }
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&self.output)
+            .with_context(|| format!("Failed to open {}", self.output.display()))?;
+        file.set_len(state.output_len)?;
+        info!(
+            "  📍 Resuming {} at block {} from checkpoint ({} records, {:.2} GB kept)",
+            self.stage.name(),
+            next_height,
+            state.records,
+            state.output_len as f64 / 1_073_741_824.0
+        );
+        Ok(Some(ResumePoint {
+            next_height,
+            records: state.records,
+            failed: state.failed,
+        }))
+    }
+
+    /// Whether [`CHECKPOINT_INTERVAL`] has passed since the last save.
+    pub fn due(&self) -> bool {
+        self.last_saved.elapsed() >= CHECKPOINT_INTERVAL
+    }
+
+    /// Record that the first `output_len` bytes of the output are durable and cover everything
+    /// before `next_height`. The caller must have synced them to disk.
+    pub fn save(
+        &mut self,
+        output_len: u64,
+        next_height: u64,
+        records: u64,
+        failed: u64,
+    ) -> Result<()> {
+        self.state.stages.insert(
+            self.stage.name().to_string(),
+            StageState {
+                completed: false,
+                output_len,
+                next_height: Some(next_height),
+                records,
+                failed,
+                updated: Utc::now().to_rfc3339(),
+            },
+        );
+        self.last_saved = Instant::now();
+        self.state.save()?;
+        // Everything up to here is durable, so stopping for disk space loses nothing
+        crate::disk_watchdog::checkpoint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn stages_resume_from_checkpoint_and_invalidate_downstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = dir.path().join("inputs_unsorted.bin");
+        let sorted = dir.path().join("inputs_sorted.bin");
+
+        // Step 1 dies after a checkpoint at 100 bytes, having written 150
+        let mut state = PipelineState::load(dir.path(), 0, 1000, false).unwrap();
+        let err = state.run(Stage::ExtractInputs, &inputs, |checkpoint| {
+            assert!(checkpoint.resume().unwrap().is_none());
+            std::fs::write(&inputs, [1u8; 100]).unwrap();
+            checkpoint.save(100, 500, 2, 0).unwrap();
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&inputs)
+                .unwrap()
+                .write_all(&[2u8; 50])
+                .unwrap();
+            anyhow::bail!("killed")
+        });
+        assert!(err.is_err());
+
+        let mut state = PipelineState::load(dir.path(), 0, 1000, false).unwrap();
+        state
+            .run(Stage::ExtractInputs, &inputs, |checkpoint| {
+                let resume = checkpoint.resume().unwrap().unwrap();
+                assert_eq!((resume.next_height, resume.records), (500, 2));
+                assert_eq!(std::fs::read(&inputs).unwrap(), vec![1u8; 100]);
+                Ok(4)
+            })
+            .unwrap();
+        std::fs::write(&sorted, [0u8; 100]).unwrap();
+        state.run(Stage::SortInputs, &sorted, |_| Ok(4)).unwrap();
+
+        // Both complete: nothing runs again
+        let mut state = PipelineState::load(dir.path(), 0, 1000, false).unwrap();
+        state
+            .run(Stage::SortInputs, &sorted, |_| panic!("ran twice"))
+            .unwrap();
+
+        // Redoing step 1 makes step 2 stale
+        std::fs::write(&inputs, [3u8; 10]).unwrap();
+        state.run(Stage::ExtractInputs, &inputs, |_| Ok(1)).unwrap();
+        assert!(state.stage(Stage::SortInputs).is_none());
+
+        // A different block range ignores the old state
+        let state = PipelineState::load(dir.path(), 0, 2000, false).unwrap();
+        assert!(state.stages.is_empty());
+    }
+}
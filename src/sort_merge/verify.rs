@@ -5,8 +5,8 @@
 
 use anyhow::{Context, Result};
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -27,9 +27,13 @@ use blvm_protocol::transaction::is_coinbase;
 use blvm_protocol::types::ForkId;
 use blvm_protocol::types::{BlockHeader, ByteString, Network, TransactionOutput};
 
+use super::compression::RecordReader;
+use super::divergence::{DivergenceWriter, FailedInput};
 use super::merge_join::JoinedPrevout;
+use super::state::StageCheckpoint;
 use crate::chunked_cache::ChunkedBlockIterator;
 use hex;
+use tracing::{error, info, warn};
 
 /// Base script flags for `(height, network)` — matches `blvm-consensus` block connect / `script_cache`.
 pub fn get_script_flags(height: u64, network: Network) -> u32 {
@@ -38,17 +42,17 @@ pub fn get_script_flags(height: u64, network: Network) -> u32 {
 
 /// Prevout reader that streams sorted prevout data
 pub struct PrevoutReader {
-    reader: BufReader<File>,
+    reader: RecordReader,
     buffer: Vec<u8>,
     leftover: Vec<u8>,
 }
 
 impl PrevoutReader {
     pub fn new(path: &Path) -> Result<Self> {
-        let file = File::open(path)
+        let reader = RecordReader::open(path, 64 * 1024 * 1024)
             .with_context(|| format!("Failed to open prevout file: {}", path.display()))?;
         Ok(Self {
-            reader: BufReader::with_capacity(64 * 1024 * 1024, file),
+            reader,
             buffer: vec![0u8; 256 * 1024],
             leftover: Vec::new(),
         })
@@ -59,7 +63,7 @@ impl PrevoutReader {
     pub fn skip_to_block(&mut self, target_height: u32) -> Result<()> {
         use std::io::Seek;
 
-        println!("  Skipping prevouts to block {}...", target_height);
+        info!("  Skipping prevouts to block {}...", target_height);
         let mut skipped_records = 0u64;
         let mut last_reported_block = 0u32;
         let start_time = std::time::Instant::now();
@@ -72,7 +76,7 @@ impl PrevoutReader {
                     if prevout.spending_block >= target_height {
                         // Found it - put it back in leftover for next read_block_prevouts
                         let elapsed = start_time.elapsed();
-                        println!(
+                        info!(
                             "  ✅ Skipped to block {} ({} records in {:.1}s, {:.0} rec/s)",
                             target_height,
                             skipped_records,
@@ -91,7 +95,7 @@ impl PrevoutReader {
                     {
                         let elapsed = start_time.elapsed();
                         let rate = skipped_records as f64 / elapsed.as_secs_f64();
-                        println!(
+                        info!(
                             "  ⏩ Skipped {} records (at block {}, {:.0} rec/s, {:.1}s elapsed)",
                             skipped_records,
                             prevout.spending_block,
@@ -109,7 +113,7 @@ impl PrevoutReader {
             if n == 0 {
                 // EOF - no more prevouts, we've passed the target
                 let elapsed = start_time.elapsed();
-                eprintln!("  ⚠️  Warning: Reached EOF in prevout file before target block {} (skipped {} records in {:.1}s)", 
+                warn!("  ⚠️  Warning: Reached EOF in prevout file before target block {} (skipped {} records in {:.1}s)", 
                     target_height, skipped_records, elapsed.as_secs_f64());
                 return Ok(());
             }
@@ -128,7 +132,7 @@ impl PrevoutReader {
                 if let Some((prevout, consumed)) = JoinedPrevout::from_bytes(&self.leftover) {
                     if prevout.spending_block < block_height {
                         // This shouldn't happen if files are correct
-                        eprintln!(
+                        warn!(
                             "Warning: Prevout for past block {} (expecting {})",
                             prevout.spending_block, block_height
                         );
@@ -159,10 +163,16 @@ impl PrevoutReader {
     }
 }
 
+/// Sampled failures, written next to the prevout file
+pub const FAILURES_LOG: &str = "failures.log";
+
 // Note: We don't use a struct here - we build tasks on-the-fly to reduce memory
 // The key optimization is using Arc<> to share all_prevouts across inputs in the same transaction
 
 /// Verify all scripts in the blockchain using streamed prevout data
+///
+/// With a `checkpoint` (whose output is the failures log), an interrupted run continues from
+/// its last checkpoint with the counts and failures logged up to it.
 pub fn verify_scripts(
     chunks_dir: &Path,
     prevouts_file: &Path,
@@ -170,17 +180,25 @@ pub fn verify_scripts(
     end_height: u64,
     progress_interval: u64,
     network: Network,
+    mut checkpoint: Option<&mut StageCheckpoint>,
 ) -> Result<(u64, u64, Vec<(u64, String)>)> {
-    println!("\n{}", "═".repeat(60));
-    println!("STEP 6: Parallel Script Verification");
-    println!("{}", "═".repeat(60));
-    println!("  Chunks dir: {}", chunks_dir.display());
-    println!("  Blocks: {} to {}", start_height, end_height);
-    println!("  Prevouts: {}", prevouts_file.display());
-    println!("  Using {} threads", rayon::current_num_threads());
+    crate::progress::ensure_logging();
+    info!("\n{}", "═".repeat(60));
+    info!("STEP 6: Parallel Script Verification");
+    info!("{}", "═".repeat(60));
+    info!("  Chunks dir: {}", chunks_dir.display());
+    info!("  Blocks: {} to {}", start_height, end_height);
+    info!("  Prevouts: {}", prevouts_file.display());
+    info!("  Using {} threads", rayon::current_num_threads());
 
     let start_time = Instant::now();
 
+    let resume = match &checkpoint {
+        Some(checkpoint) => checkpoint.resume()?,
+        None => None,
+    };
+    let start_height = resume.map_or(start_height, |r| r.next_height);
+
     // Create block iterator
     let mut block_iter = ChunkedBlockIterator::new(chunks_dir, Some(start_height), None)?
         .ok_or_else(|| {
@@ -192,13 +210,13 @@ pub fn verify_scripts(
 
     // Skip to the start block if resuming
     if start_height > 0 {
-        println!("  Skipping prevouts to block {}...", start_height);
+        info!("  Skipping prevouts to block {}...", start_height);
         prevout_reader.skip_to_block(start_height as u32)?;
-        println!("  ✅ Skipped to block {}", start_height);
+        info!("  ✅ Skipped to block {}", start_height);
     }
 
-    let total_verified = Arc::new(AtomicU64::new(0));
-    let total_failed = Arc::new(AtomicU64::new(0));
+    let total_verified = Arc::new(AtomicU64::new(resume.map_or(0, |r| r.records)));
+    let total_failed = Arc::new(AtomicU64::new(resume.map_or(0, |r| r.failed)));
     let mut divergences: Vec<(u64, String)> = Vec::new();
 
     // Failure statistics by type
@@ -211,18 +229,26 @@ pub fn verify_scripts(
     let failures_file = prevouts_file
         .parent()
         .unwrap_or(Path::new("."))
-        .join("failures.log");
-    let mut failures_writer = BufWriter::new(
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true) // CRITICAL FIX: Clear log on restart, don't append
-            .open(&failures_file)?,
-    );
-    writeln!(
-        failures_writer,
-        "# Block Height | Error Type | Details | TX Hex"
-    )?;
+        .join(FAILURES_LOG);
+    let mut failures_writer = if resume.is_some() {
+        // Resuming: the checkpoint already cut the log back to the failures before it
+        BufWriter::new(OpenOptions::new().append(true).open(&failures_file)?)
+    } else {
+        let mut writer = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true) // CRITICAL FIX: Clear log on restart, don't append
+                .open(&failures_file)?,
+        );
+        writeln!(writer, "# Block Height | Error Type | Details | TX Hex")?;
+        writer
+    };
+
+    // Full context of failing inputs, for replaying them one at a time
+    let data_dir = prevouts_file.parent().unwrap_or(Path::new("."));
+    let mut divergence_writer =
+        DivergenceWriter::from_env(data_dir, network, (start_height, end_height))?;
 
     let mut height = start_height;
     let mut last_report = Instant::now();
@@ -245,7 +271,7 @@ pub fn verify_scripts(
             || height % 1000 == 0
             || (height >= start_height && height < start_height + 10)
         {
-            println!(
+            info!(
                 "  🔄 Loading block {} (height < end_height: {}, end_height: {})",
                 height,
                 height < end_height,
@@ -255,7 +281,7 @@ pub fn verify_scripts(
         let block_data = match block_iter.next_block() {
             Ok(Some(data)) => {
                 if height <= start_height + 10 || height % 1000 == 0 {
-                    println!(
+                    info!(
                         "  ✅ Got block {} from iterator ({} bytes)",
                         height,
                         data.len()
@@ -264,7 +290,7 @@ pub fn verify_scripts(
                 data
             }
             Ok(None) => {
-                println!(
+                info!(
                     "  ✅ Reached end of blocks at height {} (current_height < end_height: {})",
                     height,
                     height < end_height
@@ -272,8 +298,8 @@ pub fn verify_scripts(
                 break;
             }
             Err(e) => {
-                eprintln!("  ❌ FATAL: Error loading block {}: {:?}", height, e);
-                eprintln!("  ❌ This will cause the process to exit. Check block iterator.");
+                error!("  ❌ FATAL: Error loading block {}: {:?}", height, e);
+                error!("  ❌ This will cause the process to exit. Check block iterator.");
                 return Err(e).context("Failed to load block from iterator");
             }
         };
@@ -320,7 +346,7 @@ pub fn verify_scripts(
         let block_prevouts = match prevout_reader.read_block_prevouts(height as u32) {
             Ok(prevouts) => prevouts,
             Err(e) => {
-                eprintln!(
+                error!(
                     "  ❌ FATAL: Error reading prevouts for block {}: {:?}",
                     height, e
                 );
@@ -661,7 +687,8 @@ pub fn verify_scripts(
         // Process results
         // NOTE: tx_prevouts must live until after verification_tasks is processed
         // because verification_tasks contains references to tx_witnesses from tx_prevouts
-        for (success, indices_opt, failure_type_tag) in results {
+        for (task_idx, (success, indices_opt, failure_type_tag)) in results.into_iter().enumerate()
+        {
             if success {
                 total_verified.fetch_add(1, Ordering::Relaxed);
                 continue;
@@ -718,6 +745,27 @@ pub fn verify_scripts(
                     failures_writer.flush()?;
                 }
 
+                let (prevouts, tx_witnesses, _) = &tx_prevouts[tx_idx];
+                divergence_writer.record(&FailedInput {
+                    network,
+                    height,
+                    median_time_past,
+                    tx: &block.transactions[tx_idx],
+                    tx_index: tx_idx,
+                    input_index: input_idx,
+                    prevouts,
+                    prevout_origins: (0..prevouts.len())
+                        .map(|i| {
+                            prevout_map
+                                .get(&(tx_idx as u32, i as u32))
+                                .map(|p| (p.prevout_height, p.is_coinbase))
+                        })
+                        .collect(),
+                    witnesses: *tx_witnesses,
+                    flags: verification_tasks[task_idx].5,
+                    failure: failure_type,
+                })?;
+
                 // Keep first 100 in memory for final report
                 if divergences.len() < 100 {
                     divergences.push((height, msg));
@@ -742,7 +790,7 @@ pub fn verify_scripts(
             let script_false = failure_stats.get("Script returned false").unwrap_or(&0);
             let script_err = failure_stats.get("Script error").unwrap_or(&0);
 
-            println!(
+            info!(
                 "  Block {}/{} ({:.1}%) - ✓{} ✗{} (M:{} F:{} E:{}) - {:.0} blk/s - ETA: {:.0}m",
                 height,
                 end_height,
@@ -759,9 +807,22 @@ pub fn verify_scripts(
         }
 
         height += 1;
+
+        if let Some(checkpoint) = checkpoint.as_mut() {
+            if checkpoint.due() {
+                failures_writer.flush()?;
+                failures_writer.get_ref().sync_data()?;
+                checkpoint.save(
+                    failures_writer.get_ref().metadata()?.len(),
+                    height,
+                    total_verified.load(Ordering::Relaxed),
+                    total_failed.load(Ordering::Relaxed),
+                )?;
+            }
+        }
     }
 
-    println!(
+    info!(
         "  📍 Loop exited: height={}, end_height={}, height < end_height: {}",
         height,
         end_height,
@@ -774,28 +835,41 @@ pub fn verify_scripts(
     let blocks_processed = height - start_height;
 
     failures_writer.flush()?;
+    if let Some(checkpoint) = checkpoint.as_mut() {
+        failures_writer.get_ref().sync_data()?;
+        let len = failures_writer.get_ref().metadata()?.len();
+        checkpoint.save(len, height, verified_final, failed_final)?;
+    }
 
-    println!("{}", "─".repeat(60));
-    println!("  ✅ Step 6 Complete!");
-    println!("  Verified: {}", verified_final);
-    println!("  Failed: {}", failed_final);
-    println!("  Failure breakdown:");
+    info!("{}", "─".repeat(60));
+    info!("  ✅ Step 6 Complete!");
+    info!("  Verified: {}", verified_final);
+    info!("  Failed: {}", failed_final);
+    info!("  Failure breakdown:");
     for (failure_type, count) in &failure_stats {
-        println!(
+        info!(
             "    {}: {} ({:.2}%)",
             failure_type,
             count,
             *count as f64 / failed_final as f64 * 100.0
         );
     }
-    println!(
+    info!(
         "  Divergences sampled: {} (see {})",
         divergences.len(),
         failures_file.display()
     );
-    println!("  Blocks processed: {}", blocks_processed);
-    println!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
-    println!(
+    if divergence_writer.written() > 0 {
+        info!(
+            "  Divergence artifacts: {} in {} ({} where Core disagrees)",
+            divergence_writer.written(),
+            divergence_writer.dir().display(),
+            divergence_writer.disagreements()
+        );
+    }
+    info!("  Blocks processed: {}", blocks_processed);
+    info!("  Time: {:.1}m", elapsed.as_secs_f64() / 60.0);
+    info!(
         "  Rate: {:.0} blocks/sec",
         blocks_processed as f64 / elapsed.as_secs_f64()
     );
@@ -0,0 +1,161 @@
+//! Storage tiers for the chunk cache
+//!
+//! Chunks are compressed in a scratch dir (fast local disk), then copied to an archive dir. The
+//! archives are the chunk dir (`chunks.dir` / `BLOCK_CACHE_DIR`) followed by `storage.archives`
+//! (see [`crate::config::StorageTuning`]):
+//!
+//! - [`StorageTiers::place`] picks the first archive that still has room for a new chunk plus
+//!   `storage.min_free_bytes`, so a full disk spills over to the next one.
+//! - [`StorageTiers::locate`] finds an existing chunk in whichever archive holds it;
+//!   [`crate::chunk_manifest::chunk_path`] goes through it, so readers need not know about tiers.
+//!
+//! Chunk metadata, the block index and the manifest stay in the chunk dir, the first archive.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::StorageTuning;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageTiers {
+    scratch: Option<PathBuf>,
+    /// Chunk dir first
+    archives: Vec<PathBuf>,
+    min_free_bytes: u64,
+}
+
+impl StorageTiers {
+    /// Tiers with `chunk_dir` as the first archive.
+    pub fn new(chunk_dir: Option<PathBuf>, storage: &StorageTuning) -> Self {
+        let mut archives: Vec<PathBuf> = chunk_dir.into_iter().collect();
+        for dir in &storage.archives {
+            if !archives.contains(dir) {
+                archives.push(dir.clone());
+            }
+        }
+        Self {
+            scratch: storage.scratch.clone(),
+            archives,
+            min_free_bytes: storage.min_free_bytes,
+        }
+    }
+
+    /// Tiers of the process-wide config, with [`crate::chunked_cache::get_chunks_dir`] as the
+    /// chunk dir.
+    pub fn from_config() -> Self {
+        Self::new(
+            crate::chunked_cache::get_chunks_dir(),
+            &crate::config::global().storage,
+        )
+    }
+
+    pub fn archives(&self) -> &[PathBuf] {
+        &self.archives
+    }
+
+    /// Scratch dir for chunks built from `temp_file`: `storage.scratch`, else `chunks/` next to
+    /// it.
+    pub fn scratch_dir(&self, temp_file: &Path) -> PathBuf {
+        self.scratch.clone().unwrap_or_else(|| {
+            temp_file
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join("chunks")
+        })
+    }
+
+    /// Whether `dir` is the configured scratch dir (its chunks are always temporary copies).
+    pub fn is_scratch(&self, dir: &Path) -> bool {
+        self.scratch.as_deref() == Some(dir)
+    }
+
+    /// Whether `path` is inside an archive (never deleted by collection).
+    pub fn is_archived(&self, path: &Path) -> bool {
+        self.archives
+            .iter()
+            .any(|archive| path.starts_with(archive))
+    }
+
+    /// Archive holding chunk `chunk_num`: `chunks_dir` if the file is there, else, when
+    /// `chunks_dir` is one of the tiers, the first archive that has it.
+    pub fn locate(&self, chunks_dir: &Path, chunk_num: u64) -> Option<PathBuf> {
+        let name = chunk_file_name(chunk_num);
+        if chunks_dir.join(&name).exists() {
+            return Some(chunks_dir.to_path_buf());
+        }
+        if !self.archives.iter().any(|archive| archive == chunks_dir) {
+            return None;
+        }
+        self.archives
+            .iter()
+            .find(|archive| archive.join(&name).exists())
+            .cloned()
+    }
+
+    /// Archive to copy a new chunk of `bytes` to: the first with `bytes + storage.min_free_bytes`
+    /// free (or whose free space is unknown).
+    pub fn place(&self, bytes: u64) -> Result<PathBuf> {
+        anyhow::ensure!(
+            !self.archives.is_empty(),
+            "No chunk archive configured (set BLOCK_CACHE_DIR or chunks.dir)"
+        );
+        let needed = bytes.saturating_add(self.min_free_bytes);
+        let mut full = Vec::new();
+        for archive in &self.archives {
+            std::fs::create_dir_all(archive)
+                .with_context(|| format!("Failed to create {}", archive.display()))?;
+            match crate::platform::free_space(archive) {
+                Some(free) if free < needed => {
+                    full.push(format!("{} ({} free)", archive.display(), free))
+                }
+                _ => return Ok(archive.clone()),
+            }
+        }
+        anyhow::bail!(
+            "No chunk archive has {} bytes free ({} for the chunk, {} reserved): {}",
+            needed,
+            bytes,
+            self.min_free_bytes,
+            full.join(", ")
+        )
+    }
+}
+
+/// `chunk_<chunk_num>.bin.zst`
+pub fn chunk_file_name(chunk_num: u64) -> String {
+    format!("chunk_{}.bin.zst", chunk_num)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_by_free_space_and_finds_chunks_in_any_tier() {
+        let home = tempfile::tempdir().unwrap();
+        let spill = tempfile::tempdir().unwrap();
+        let storage = StorageTuning {
+            archives: vec![spill.path().to_path_buf(), home.path().to_path_buf()],
+            min_free_bytes: 0,
+            ..StorageTuning::default()
+        };
+        let tiers = StorageTiers::new(Some(home.path().to_path_buf()), &storage);
+        assert_eq!(tiers.archives().len(), 2);
+
+        assert_eq!(tiers.place(1).unwrap(), home.path());
+        let full = StorageTiers {
+            min_free_bytes: u64::MAX / 2,
+            ..tiers.clone()
+        };
+        assert!(full.place(u64::MAX / 2).is_err());
+
+        std::fs::write(spill.path().join(chunk_file_name(3)), b"chunk").unwrap();
+        assert_eq!(tiers.locate(home.path(), 3).as_deref(), Some(spill.path()));
+        assert_eq!(tiers.locate(home.path(), 4), None);
+        // A directory outside the tiers is only checked itself
+        let other = tempfile::tempdir().unwrap();
+        assert_eq!(tiers.locate(other.path(), 3), None);
+        assert!(tiers.is_archived(&spill.path().join(chunk_file_name(3))));
+        assert!(!tiers.is_archived(&other.path().join(chunk_file_name(3))));
+    }
+}
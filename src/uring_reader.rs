@@ -0,0 +1,266 @@
+//! io_uring read-ahead for sequential file reads (`io-uring` feature, Linux only)
+//!
+//! `BufReader<File>` issues one blocking `read` at a time, so on SSHFS and NVMe the device sits
+//! idle while a buffer is being consumed. [`UringReader`] keeps up to `queue_depth` reads of
+//! [`READ_SIZE`] bytes in flight ahead of the read position and hands them out in file order.
+//!
+//! It implements [`Seek`]: a target inside the read-ahead window only skips ahead, anything else
+//! drains the queue and restarts it at the target. Callers normally get one through
+//! [`crate::platform::open_sequential`], which falls back to a plain file where io_uring is
+//! unavailable (old kernels, seccomp-filtered containers).
+
+use io_uring::{opcode, types, IoUring};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// Bytes per submitted read
+pub const READ_SIZE: usize = 1024 * 1024;
+
+/// Sequential reader with `queue_depth` reads in flight.
+pub struct UringReader {
+    ring: IoUring,
+    file: File,
+    len: u64,
+    buffers: Vec<Vec<u8>>,
+    /// Completion result per buffer, until the buffer is taken
+    results: Vec<Option<i32>>,
+    /// Buffers with no read in flight and not being consumed
+    free: Vec<usize>,
+    /// Buffers with a read in flight or completed, as `(buffer, file offset)`, in file order
+    queued: VecDeque<(usize, u64)>,
+    /// Offset the next submitted read starts at
+    next_offset: u64,
+    /// Buffer being consumed, as `(buffer, file offset, valid bytes)`
+    current: Option<(usize, u64, usize)>,
+    pos: u64,
+}
+
+impl UringReader {
+    pub fn open(path: &Path, queue_depth: u32) -> io::Result<Self> {
+        Self::new(File::open(path)?, queue_depth)
+    }
+
+    /// Read `file` from its start.
+    pub fn new(file: File, queue_depth: u32) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        let depth = queue_depth.max(1);
+        let ring = IoUring::new(depth.next_power_of_two())?;
+        let mut reader = Self {
+            ring,
+            file,
+            len,
+            buffers: vec![vec![0u8; READ_SIZE]; depth as usize],
+            results: vec![None; depth as usize],
+            free: (0..depth as usize).rev().collect(),
+            queued: VecDeque::with_capacity(depth as usize),
+            next_offset: 0,
+            current: None,
+            pos: 0,
+        };
+        reader.fill()?;
+        Ok(reader)
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Submit a read into every free buffer, up to the end of the file.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut submitted = false;
+        while self.next_offset < self.len {
+            let Some(buffer) = self.free.pop() else {
+                break;
+            };
+            let want = (self.len - self.next_offset).min(READ_SIZE as u64) as u32;
+            let entry = opcode::Read::new(
+                types::Fd(self.file.as_raw_fd()),
+                self.buffers[buffer].as_mut_ptr(),
+                want,
+            )
+            .offset(self.next_offset)
+            .build()
+            .user_data(buffer as u64);
+            // SAFETY: the buffer and the file live in `self` and are not touched until the
+            // completion for `buffer` is reaped; `Drop` waits for every queued read.
+            unsafe { self.ring.submission().push(&entry) }
+                .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+            self.queued.push_back((buffer, self.next_offset));
+            self.next_offset += u64::from(want);
+            submitted = true;
+        }
+        if submitted {
+            self.ring.submit()?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the read into `buffer` to complete.
+    fn reap(&mut self, buffer: usize) -> io::Result<i32> {
+        loop {
+            if let Some(result) = self.results[buffer].take() {
+                return Ok(result);
+            }
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            for completion in self.ring.completion() {
+                self.results[completion.user_data() as usize] = Some(completion.result());
+            }
+        }
+    }
+
+    /// Recycle the current buffer and move on to the oldest queued read; `false` at the end of
+    /// the file.
+    fn advance(&mut self) -> io::Result<bool> {
+        if let Some((buffer, _, _)) = self.current.take() {
+            self.free.push(buffer);
+        }
+        self.fill()?;
+        let Some((buffer, offset)) = self.queued.pop_front() else {
+            return Ok(false);
+        };
+        let result = self.reap(buffer)?;
+        if result < 0 {
+            self.free.push(buffer);
+            return Err(io::Error::from_raw_os_error(-result));
+        }
+        // Short reads (FUSE mounts such as SSHFS) are completed synchronously
+        let want = (self.len - offset).min(READ_SIZE as u64) as usize;
+        let mut valid = result as usize;
+        while valid < want {
+            match self.file.read_at(
+                &mut self.buffers[buffer][valid..want],
+                offset + valid as u64,
+            )? {
+                0 => break,
+                n => valid += n,
+            }
+        }
+        self.current = Some((buffer, offset, valid));
+        Ok(true)
+    }
+
+    /// Drop everything queued and start reading ahead from `offset`.
+    fn restart(&mut self, offset: u64) -> io::Result<()> {
+        self.drain();
+        if let Some((buffer, _, _)) = self.current.take() {
+            self.free.push(buffer);
+        }
+        self.next_offset = offset;
+        self.fill()
+    }
+
+    /// Wait for every queued read; the buffers become free.
+    fn drain(&mut self) {
+        while let Some((buffer, _)) = self.queued.pop_front() {
+            if self.reap(buffer).is_err() {
+                // The ring itself failed: nothing more will complete
+                break;
+            }
+            self.free.push(buffer);
+        }
+    }
+
+    fn window_start(&self) -> u64 {
+        match (self.current, self.queued.front()) {
+            (Some((_, offset, _)), _) | (None, Some(&(_, offset))) => offset,
+            (None, None) => self.next_offset,
+        }
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((buffer, offset, valid)) = self.current {
+                let Some(start) = self.pos.checked_sub(offset) else {
+                    // A short read at the end of a shrinking file left a gap
+                    self.restart(self.pos)?;
+                    continue;
+                };
+                if (start as usize) < valid {
+                    let start = start as usize;
+                    let n = buf.len().min(valid - start);
+                    buf[..n].copy_from_slice(&self.buffers[buffer][start..start + n]);
+                    self.pos += n as u64;
+                    return Ok(n);
+                }
+            }
+            if self.pos >= self.len || !self.advance()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl Seek for UringReader {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let target = match from {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        if target < self.window_start() || target > self.next_offset {
+            self.restart(target)?;
+        }
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // The kernel may still write into the buffers
+        self.drain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_seeks_like_a_file() {
+        let data: Vec<u8> = (0..READ_SIZE * 3 + 12_345)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blk00000.dat");
+        std::fs::write(&path, &data).unwrap();
+
+        let mut reader = match UringReader::open(&path, 2) {
+            Ok(reader) => reader,
+            // io_uring disabled in this sandbox
+            Err(_) => return,
+        };
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        let mut buf = [0u8; 100];
+        for offset in [10, READ_SIZE as u64 - 50, 3 * READ_SIZE as u64, 5] {
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..], data[offset as usize..offset as usize + 100]);
+        }
+        reader.seek(SeekFrom::Current(READ_SIZE as i64)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        let at = 5 + 100 + READ_SIZE;
+        assert_eq!(buf[..], data[at..at + 100]);
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), data.len() as u64);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+}
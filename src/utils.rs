@@ -1,6 +1,7 @@
 //! Benchmark utilities and helpers
 
-use std::path::PathBuf;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
 
 /// Get the path to the benchmarks directory
 pub fn benchmarks_dir() -> PathBuf {
@@ -22,3 +23,52 @@ pub fn results_dir() -> PathBuf {
 pub fn is_production_mode() -> bool {
     cfg!(feature = "production")
 }
+
+/// Write `value` to `path` as pretty JSON, creating the parent directory. The JSON goes to a temp
+/// file first and is renamed into place, so readers never see a partial file.
+pub fn write_json_atomic<T: serde::Serialize + ?Sized>(
+    path: &Path,
+    value: &T,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(value)?)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to rename {} into place", tmp.display()))?;
+    Ok(())
+}
+
+/// `len` synthetic 80-byte headers chained above `root_hash` (big-endian), lowest height first.
+///
+/// Each header commits to its parent's double-SHA256 like a real chain; there is no proof of
+/// work, so these only exercise ordering by prev_block_hash.
+pub fn synthetic_header_chain(root_hash: [u8; 32], len: usize) -> Vec<[u8; 80]> {
+    let mut prev_le = root_hash;
+    prev_le.reverse();
+    (0..len as u32)
+        .map(|height| {
+            let mut header = [0u8; 80];
+            header[0..4].copy_from_slice(&4u32.to_le_bytes());
+            header[4..36].copy_from_slice(&prev_le);
+            header[68..72].copy_from_slice(&(1_231_006_505 + height * 600).to_le_bytes());
+            header[72..76].copy_from_slice(&0x1d00_ffffu32.to_le_bytes());
+            header[76..80].copy_from_slice(&height.to_le_bytes());
+            prev_le = crate::wire::sha256d(&header);
+            header
+        })
+        .collect()
+}
+
+/// Seeded permutation of `0..len`: the same seed always gives the same order.
+pub fn shuffled_order(len: usize, seed: u64) -> Vec<usize> {
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    let mut order: Vec<usize> = (0..len).collect();
+    order.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+    order
+}
@@ -0,0 +1,204 @@
+//! Shared checkpoints with per-chunk overlays
+//!
+//! Every chunk of a parallel run starts from a UTXO checkpoint. Handing each [`BlockChunk`]
+//! its own `UtxoSet` meant one full copy per chunk up front, plus another per block inside
+//! `connect_block`, so twelve workers near the tip held a dozen multi-GB sets. Instead:
+//!
+//! - checkpoints are immutable `Arc<UtxoSet>`s, shared by the chunks that start from them (and
+//!   by nothing else once those chunks finish)
+//! - each chunk validates on an [`OverlayUtxoSet`]: the shared checkpoint plus the coins the
+//!   chunk created and the checkpoint coins it spent
+//! - each block is connected against only the coins it touches, and the change is applied to
+//!   the overlay, the same way as with the disk-backed store (see [`crate::utxo_spill`])
+//!
+//! Memory per worker is then its overlay, which grows with the chunk's own blocks rather than
+//! with the chain. Validation hooks get the coins each block spends; whole sets are only
+//! materialized per block ([`OverlayUtxoSet::to_utxo_set`]) for hooks that ask for them.
+//!
+//! [`BlockChunk`]: crate::parallel_differential::BlockChunk
+
+use anyhow::Result;
+use blvm_protocol::block::BlockValidationContext;
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::{Block, OutPoint, UtxoSet, ValidationResult, UTXO};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::consensus_compat::{get_utxo, insert_utxo, utxo_entries};
+use crate::utxo_spill::connect_touched;
+
+/// A shared checkpoint with one chunk's changes on top.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayUtxoSet {
+    base: Arc<UtxoSet>,
+    /// Coins created since the checkpoint, including checkpoint coins a BIP30 exception
+    /// overwrote
+    added: UtxoSet,
+    /// Checkpoint coins spent since
+    spent: HashSet<OutPoint>,
+    len: usize,
+}
+
+impl OverlayUtxoSet {
+    pub fn new(base: Arc<UtxoSet>) -> Self {
+        Self {
+            len: base.len(),
+            base,
+            added: UtxoSet::default(),
+            spent: HashSet::new(),
+        }
+    }
+
+    /// Connect `block` at `height`; the set only changes when the result is `Valid`.
+    pub fn connect(
+        &mut self,
+        block: &Block,
+        witnesses: &[Vec<Witness>],
+        height: u64,
+        ctx: &BlockValidationContext,
+    ) -> Result<ValidationResult> {
+        let (result, changes) = connect_touched(block, witnesses, height, ctx, |touched| {
+            Ok(self.fetch(&touched))
+        })?;
+        for (outpoint, utxo, new) in changes.created {
+            self.insert(outpoint, utxo);
+            if new {
+                self.len += 1;
+            }
+        }
+        for outpoint in &changes.spent {
+            self.spend(outpoint);
+        }
+        Ok(result)
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&UTXO> {
+        get_utxo(&self.added, outpoint).or_else(|| {
+            if self.spent.contains(outpoint) {
+                None
+            } else {
+                get_utxo(&self.base, outpoint)
+            }
+        })
+    }
+
+    /// Every coin, the chunk's own first.
+    pub fn iter(&self) -> impl Iterator<Item = (&OutPoint, &UTXO)> + '_ {
+        let unchanged = utxo_entries(&self.base).filter(|(outpoint, _)| {
+            !self.spent.contains(*outpoint) && !self.added.contains_key(*outpoint)
+        });
+        utxo_entries(&self.added).chain(unchanged)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Coins held by this overlay rather than the shared checkpoint.
+    pub fn delta_len(&self) -> usize {
+        self.added.len() + self.spent.len()
+    }
+
+    /// The whole set as a `UtxoSet` (a full copy of the checkpoint).
+    pub fn to_utxo_set(&self) -> UtxoSet {
+        let mut set = UtxoSet::clone(&self.base);
+        for outpoint in &self.spent {
+            set.remove(outpoint);
+        }
+        for (outpoint, utxo) in utxo_entries(&self.added) {
+            insert_utxo(&mut set, outpoint.clone(), utxo.clone());
+        }
+        set
+    }
+
+    /// The existing coins among `outpoints`.
+    fn fetch(&self, outpoints: &[OutPoint]) -> UtxoSet {
+        let mut set = UtxoSet::default();
+        for outpoint in outpoints {
+            if let Some(utxo) = self.get(outpoint) {
+                insert_utxo(&mut set, outpoint.clone(), utxo.clone());
+            }
+        }
+        set
+    }
+
+    fn insert(&mut self, outpoint: OutPoint, utxo: UTXO) {
+        insert_utxo(&mut self.added, outpoint, utxo);
+    }
+
+    fn spend(&mut self, outpoint: &OutPoint) {
+        self.len -= 1;
+        self.added.remove(outpoint);
+        if self.base.contains_key(outpoint) {
+            self.spent.insert(outpoint.clone());
+        }
+    }
+}
+
+impl From<UtxoSet> for OverlayUtxoSet {
+    fn from(set: UtxoSet) -> Self {
+        Self::new(Arc::new(set))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(n: u8) -> (OutPoint, UTXO) {
+        let outpoint = OutPoint {
+            hash: [n; 32],
+            index: 0,
+        };
+        let utxo = UTXO {
+            value: i64::from(n) * 1000,
+            script_pubkey: vec![0x51].into(),
+            height: u64::from(n),
+            is_coinbase: false,
+        };
+        (outpoint, utxo)
+    }
+
+    #[test]
+    fn overlays_share_one_checkpoint() {
+        let mut base = UtxoSet::default();
+        for n in 1..=3 {
+            let (outpoint, utxo) = coin(n);
+            insert_utxo(&mut base, outpoint, utxo);
+        }
+        let base = Arc::new(base);
+        let mut chunk = OverlayUtxoSet::new(Arc::clone(&base));
+        let other = OverlayUtxoSet::new(Arc::clone(&base));
+        assert_eq!(Arc::strong_count(&base), 3);
+
+        // Spend checkpoint coin 1 and overlay coin 5, add 4 and 5, overwrite 2
+        for n in [4, 5] {
+            let (outpoint, utxo) = coin(n);
+            chunk.insert(outpoint, utxo);
+            chunk.len += 1;
+        }
+        let (o2, mut u2) = coin(2);
+        u2.height = 20;
+        chunk.insert(o2.clone(), u2);
+        chunk.spend(&coin(1).0);
+        chunk.spend(&coin(5).0);
+
+        assert_eq!(chunk.len(), 3);
+        assert_eq!(chunk.delta_len(), 3);
+        assert_eq!(chunk.get(&coin(1).0), None);
+        assert_eq!(chunk.get(&o2).map(|u| u.height), Some(20));
+        let mut heights: Vec<u64> = chunk.iter().map(|(_, u)| u.height).collect();
+        heights.sort();
+        assert_eq!(heights, [3, 4, 20]);
+        assert_eq!(chunk.to_utxo_set().len(), 3);
+
+        // The checkpoint and the other overlay are untouched
+        assert_eq!(base.len(), 3);
+        assert_eq!(other.get(&coin(1).0), Some(&coin(1).1));
+        assert_eq!(other.iter().count(), 3);
+    }
+}
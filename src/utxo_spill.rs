@@ -0,0 +1,577 @@
+//! Disk-backed UTXO set for checkpoint generation on low-memory machines
+//!
+//! [`generate_checkpoints`](crate::parallel_differential::generate_checkpoints) replays the chain
+//! into one `UtxoSet` and hands `connect_block` a copy of it per block, which runs a 16GB machine
+//! out of memory around height 500k. With `BLVM_UTXO_BACKEND=spill` the live set is a
+//! [`SpillStore`] instead:
+//!
+//! - coins live in a redb table in `BLVM_UTXO_SPILL_DIR` (default: the system temp dir), keyed
+//!   by bincode outpoint; the file is deleted when the store is dropped
+//! - a hot cache of `BLVM_UTXO_CACHE_ENTRIES` coins (default [`DEFAULT_CACHE_ENTRIES`]) holds
+//!   the most recently created or read ones; when it fills, new coins and spends are written
+//!   out and it becomes the warm generation, replacing the previous one, so at most twice that
+//!   many coins stay in memory
+//! - each block is connected against a `UtxoSet` of only the coins it touches (its prevouts,
+//!   plus its own outpoints below BIP34 activation for the BIP30 check) and the change
+//!   `connect_block` makes to that set is applied back
+//!
+//! [`UtxoStore`] puts both backends behind one interface; `memory` (the default) is the
+//! original in-memory set. Boundary checkpoints are streamed between the redb table and the
+//! [`CheckpointStore`] ([`UtxoStore::save_checkpoint`], [`UtxoStore::reset_from`]) without
+//! building a whole `UtxoSet`, and each parallel chunk loads its own only when it runs, so with
+//! spilling peak memory is the two cache generations plus the sets of the running chunks.
+//!
+//! This is not the `disk-utxo` feature: that RocksDB store backs the standalone
+//! `chunk_utxo_checkpoints` tool under `scan` and builds RocksDB from source, while redb already
+//! comes with `chunk-cache`, which `differential` enables.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::{calculate_tx_id, BlockValidationContext};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::{Block, OutPoint, UtxoSet, ValidationResult, UTXO};
+use redb::{Database, Durability, ReadableTable, TableDefinition};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::info;
+
+use crate::checkpoint_store::CheckpointStore;
+use crate::consensus_compat::{connect_block, insert_utxo, utxo_entries};
+use crate::utxo_stats::UtxoSetStats;
+
+/// Hot cache size when `BLVM_UTXO_CACHE_ENTRIES` is unset (about 1 GiB per generation)
+pub const DEFAULT_CACHE_ENTRIES: usize = 4_000_000;
+
+/// Mainnet BIP34 activation: from here coinbases are unique, so blocks can't recreate an
+/// existing outpoint and BIP30 needs no lookups.
+const BIP34_HEIGHT: u64 = 227_931;
+
+const COINS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("coins");
+
+/// Live UTXO set of a chain replay.
+pub enum UtxoStore {
+    Memory(UtxoSet),
+    Spill(Box<SpillStore>),
+}
+
+impl UtxoStore {
+    /// `base` in the backend chosen by `BLVM_UTXO_BACKEND` (`memory` or `spill`).
+    pub fn from_env(base: UtxoSet) -> Result<Self> {
+        match std::env::var("BLVM_UTXO_BACKEND").as_deref() {
+            Err(_) | Ok("" | "memory") => Ok(Self::Memory(base)),
+            Ok("spill") => {
+                let dir = std::env::var_os("BLVM_UTXO_SPILL_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(std::env::temp_dir);
+                let cache_entries = match std::env::var("BLVM_UTXO_CACHE_ENTRIES") {
+                    Ok(n) => n
+                        .parse()
+                        .with_context(|| format!("Invalid BLVM_UTXO_CACHE_ENTRIES={:?}", n))?,
+                    Err(_) => DEFAULT_CACHE_ENTRIES,
+                };
+                let mut store = SpillStore::create(&dir, cache_entries)?;
+                store.load(&base)?;
+                info!(
+                    "💾 UTXO set spilled to {} (hot cache {} coins)",
+                    store.path.display(),
+                    cache_entries
+                );
+                Ok(Self::Spill(Box::new(store)))
+            }
+            Ok(other) => anyhow::bail!("Unknown BLVM_UTXO_BACKEND {:?} (memory or spill)", other),
+        }
+    }
+
+    /// Connect `block` at `height`; the set only changes when the result is `Valid`.
+    pub fn connect(
+        &mut self,
+        block: &Block,
+        witnesses: &[Vec<Witness>],
+        height: u64,
+        ctx: &BlockValidationContext,
+    ) -> Result<ValidationResult> {
+        match self {
+            Self::Memory(set) => {
+                let (result, new_set) = connect_block(block, witnesses, set.clone(), height, ctx)?;
+                if matches!(result, ValidationResult::Valid) {
+                    *set = new_set;
+                }
+                Ok(result)
+            }
+            Self::Spill(store) => store.connect(block, witnesses, height, ctx),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Memory(set) => set.len(),
+            Self::Spill(store) => store.len as usize,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The whole set as a `UtxoSet`, for small sets; checkpoints are streamed instead.
+    pub fn snapshot(&mut self) -> Result<UtxoSet> {
+        match self {
+            Self::Memory(set) => Ok(set.clone()),
+            Self::Spill(store) => store.snapshot(),
+        }
+    }
+
+    /// Write the set to `store` as the checkpoint after block `height` and return its totals.
+    pub fn save_checkpoint(
+        &mut self,
+        store: &CheckpointStore,
+        height: u64,
+        block_hash: &[u8; 32],
+    ) -> Result<UtxoSetStats> {
+        match self {
+            Self::Memory(set) => {
+                store.save(height, block_hash, set)?;
+                Ok(UtxoSetStats::of(set))
+            }
+            Self::Spill(spill) => spill.save_checkpoint(store, height, block_hash),
+        }
+    }
+
+    /// Replace the contents with the checkpoint `store` has after block `height` (resuming).
+    pub fn reset_from(
+        &mut self,
+        store: &CheckpointStore,
+        height: u64,
+        block_hash: &[u8; 32],
+    ) -> Result<()> {
+        match self {
+            Self::Memory(set) => *set = store.load(height, block_hash)?,
+            Self::Spill(spill) => spill.reset_from(store, height, block_hash)?,
+        }
+        Ok(())
+    }
+
+    /// Replace the contents with `set`.
+    pub fn reset(&mut self, set: UtxoSet) -> Result<()> {
+        match self {
+            Self::Memory(current) => *current = set,
+            Self::Spill(store) => {
+                store.clear()?;
+                store.load(&set)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One coin; a linear scan in memory (`UtxoSet` values differ between consensus branches).
+    pub fn get(&mut self, outpoint: &OutPoint) -> Result<Option<UTXO>> {
+        match self {
+            Self::Memory(set) => Ok(utxo_entries(set)
+                .find(|(o, _)| *o == outpoint)
+                .map(|(_, utxo)| utxo.clone())),
+            Self::Spill(store) => store.get(outpoint),
+        }
+    }
+
+    /// Every coin; for inspecting small sets.
+    pub fn entries(&mut self) -> Result<Vec<(OutPoint, UTXO)>> {
+        let set = self.snapshot()?;
+        Ok(utxo_entries(&set)
+            .map(|(outpoint, utxo)| (outpoint.clone(), utxo.clone()))
+            .collect())
+    }
+}
+
+/// What connecting one block changed, from [`connect_touched`].
+#[derive(Debug, Default)]
+pub(crate) struct CoinChanges {
+    /// Created coins; `false` for an existing coin a BIP30 exception overwrote
+    pub created: Vec<(OutPoint, UTXO, bool)>,
+    pub spent: Vec<OutPoint>,
+}
+
+/// Connect `block` against only the coins it touches, as returned by `fetch` for its prevouts
+/// (plus its own outpoints below BIP34), and return what changed. The changes are empty unless
+/// the result is `Valid`.
+pub(crate) fn connect_touched(
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    height: u64,
+    ctx: &BlockValidationContext,
+    fetch: impl FnOnce(Vec<OutPoint>) -> Result<UtxoSet>,
+) -> Result<(ValidationResult, CoinChanges)> {
+    let mut touched = Vec::new();
+    for (tx_idx, tx) in block.transactions.iter().enumerate() {
+        if tx_idx > 0 {
+            touched.extend(tx.inputs.iter().map(|input| input.prevout.clone()));
+        }
+        if height < BIP34_HEIGHT {
+            let tx_id = calculate_tx_id(tx);
+            touched.extend((0..tx.outputs.len()).map(|index| OutPoint {
+                hash: tx_id,
+                index: index as _,
+            }));
+        }
+    }
+    let touched_coins = fetch(touched)?;
+    let (result, after) = connect_block(block, witnesses, touched_coins.clone(), height, ctx)?;
+    let mut changes = CoinChanges::default();
+    if !matches!(result, ValidationResult::Valid) {
+        return Ok((result, changes));
+    }
+
+    let before: HashMap<&OutPoint, &UTXO> = utxo_entries(&touched_coins).collect();
+    let mut kept = HashSet::new();
+    for (outpoint, utxo) in utxo_entries(&after) {
+        match before.get(outpoint) {
+            Some(old) if old.height == utxo.height => {}
+            // Not new when a BIP30 exception overwrote the coin
+            old => changes
+                .created
+                .push((outpoint.clone(), utxo.clone(), old.is_none())),
+        }
+        kept.insert(outpoint);
+    }
+    changes.spent = before
+        .keys()
+        .filter(|o| !kept.contains(*o))
+        .map(|o| (*o).clone())
+        .collect();
+    Ok((result, changes))
+}
+
+/// UTXO set in a redb file with a two-generation in-memory cache (see the module docs).
+pub struct SpillStore {
+    db: Database,
+    path: PathBuf,
+    /// Coins created or read since the last flush; `true` when not on disk yet
+    hot: HashMap<OutPoint, (UTXO, bool)>,
+    /// The generation before, all on disk
+    warm: HashMap<OutPoint, UTXO>,
+    /// On-disk coins spent since the last flush
+    spent: Vec<OutPoint>,
+    cache_entries: usize,
+    len: u64,
+}
+
+impl SpillStore {
+    /// Empty store in a new file under `dir`.
+    pub fn create(dir: &Path, cache_entries: usize) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = dir.join(format!(
+            "blvm-utxo-spill-{}-{}.redb",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let db = Database::create(&path)
+            .with_context(|| format!("Failed to create UTXO spill file {}", path.display()))?;
+        let txn = db.begin_write()?;
+        txn.open_table(COINS)?;
+        txn.commit()?;
+        Ok(Self {
+            db,
+            path,
+            hot: HashMap::new(),
+            warm: HashMap::new(),
+            spent: Vec::new(),
+            cache_entries: cache_entries.max(1),
+            len: 0,
+        })
+    }
+
+    /// Remove every coin.
+    pub fn clear(&mut self) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        txn.delete_table(COINS)?;
+        txn.open_table(COINS)?;
+        txn.commit()?;
+        self.hot.clear();
+        self.warm.clear();
+        self.spent.clear();
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Add every coin of `set`.
+    pub fn load(&mut self, set: &UtxoSet) -> Result<()> {
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(Durability::None);
+        {
+            let mut table = txn.open_table(COINS)?;
+            for (outpoint, utxo) in utxo_entries(set) {
+                let key = bincode::serialize(outpoint)?;
+                if table
+                    .insert(key.as_slice(), bincode::serialize(utxo)?.as_slice())?
+                    .is_none()
+                {
+                    self.len += 1;
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn connect(
+        &mut self,
+        block: &Block,
+        witnesses: &[Vec<Witness>],
+        height: u64,
+        ctx: &BlockValidationContext,
+    ) -> Result<ValidationResult> {
+        let (result, changes) =
+            connect_touched(block, witnesses, height, ctx, |touched| self.fetch(touched))?;
+        for (outpoint, utxo, new) in changes.created {
+            self.insert(outpoint, &utxo);
+            if new {
+                self.len += 1;
+            }
+        }
+        for outpoint in &changes.spent {
+            self.spend(outpoint);
+        }
+        if self.hot.len() >= self.cache_entries {
+            self.flush()?;
+        }
+        Ok(result)
+    }
+
+    /// The existing coins among `outpoints`, looked up in the cache, then on disk.
+    fn fetch(&mut self, outpoints: Vec<OutPoint>) -> Result<UtxoSet> {
+        let mut set = UtxoSet::default();
+        let mut misses = Vec::new();
+        for outpoint in outpoints {
+            match self.cached(&outpoint) {
+                Some(utxo) => {
+                    insert_utxo(&mut set, outpoint, utxo);
+                }
+                None => misses.push(outpoint),
+            }
+        }
+        if misses.is_empty() {
+            return Ok(set);
+        }
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(COINS)?;
+        for outpoint in misses {
+            let key = bincode::serialize(&outpoint)?;
+            if let Some(value) = table.get(key.as_slice())? {
+                let utxo: UTXO = bincode::deserialize(value.value())?;
+                self.hot.insert(outpoint.clone(), (utxo.clone(), false));
+                insert_utxo(&mut set, outpoint, utxo);
+            }
+        }
+        Ok(set)
+    }
+
+    fn cached(&self, outpoint: &OutPoint) -> Option<UTXO> {
+        self.hot
+            .get(outpoint)
+            .map(|(utxo, _)| utxo.clone())
+            .or_else(|| self.warm.get(outpoint).cloned())
+    }
+
+    fn get(&mut self, outpoint: &OutPoint) -> Result<Option<UTXO>> {
+        let set = self.fetch(vec![outpoint.clone()])?;
+        Ok(utxo_entries(&set).next().map(|(_, utxo)| utxo.clone()))
+    }
+
+    fn insert(&mut self, outpoint: OutPoint, utxo: &UTXO) {
+        self.warm.remove(&outpoint);
+        self.hot.insert(outpoint, (utxo.clone(), true));
+    }
+
+    fn spend(&mut self, outpoint: &OutPoint) {
+        self.len -= 1;
+        let on_disk = match self.hot.remove(outpoint) {
+            Some((_, dirty)) => !dirty,
+            None => {
+                self.warm.remove(outpoint);
+                true
+            }
+        };
+        if on_disk {
+            self.spent.push(outpoint.clone());
+        }
+    }
+
+    /// Write new coins and spends, then demote the hot cache to the warm generation.
+    fn flush(&mut self) -> Result<()> {
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(Durability::None);
+        {
+            let mut table = txn.open_table(COINS)?;
+            for outpoint in self.spent.drain(..) {
+                table.remove(bincode::serialize(&outpoint)?.as_slice())?;
+            }
+            for (outpoint, (utxo, _)) in self.hot.iter().filter(|(_, (_, dirty))| *dirty) {
+                let key = bincode::serialize(outpoint)?;
+                table.insert(key.as_slice(), bincode::serialize(utxo)?.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        self.warm = std::mem::take(&mut self.hot)
+            .into_iter()
+            .map(|(outpoint, (utxo, _))| (outpoint, utxo))
+            .collect();
+        Ok(())
+    }
+
+    /// Stream the table into a checkpoint file, totalling the coins on the way.
+    fn save_checkpoint(
+        &mut self,
+        store: &CheckpointStore,
+        height: u64,
+        block_hash: &[u8; 32],
+    ) -> Result<UtxoSetStats> {
+        self.flush()?;
+        let mut stats = UtxoSetStats::default();
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(COINS)?;
+        let entries = table.iter()?.map(|entry| -> Result<(OutPoint, UTXO)> {
+            let (key, value) = entry?;
+            let utxo: UTXO = bincode::deserialize(value.value())?;
+            stats.add(&utxo);
+            Ok((bincode::deserialize(key.value())?, utxo))
+        });
+        store.save_entries(height, block_hash, self.len, entries)?;
+        Ok(stats)
+    }
+
+    /// Replace every coin with those of a checkpoint file, in one write transaction.
+    fn reset_from(
+        &mut self,
+        store: &CheckpointStore,
+        height: u64,
+        block_hash: &[u8; 32],
+    ) -> Result<()> {
+        self.clear()?;
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(Durability::None);
+        let len = {
+            let mut table = txn.open_table(COINS)?;
+            store.for_each(height, block_hash, |outpoint, utxo| {
+                let key = bincode::serialize(&outpoint)?;
+                table.insert(key.as_slice(), bincode::serialize(&utxo)?.as_slice())?;
+                Ok(())
+            })?
+        };
+        txn.commit()?;
+        self.len = len;
+        Ok(())
+    }
+
+    fn snapshot(&mut self) -> Result<UtxoSet> {
+        self.flush()?;
+        let mut set = UtxoSet::default();
+        let txn = self.db.begin_read()?;
+        for entry in txn.open_table(COINS)?.iter()? {
+            let (key, value) = entry?;
+            insert_utxo(
+                &mut set,
+                bincode::deserialize(key.value())?,
+                bincode::deserialize(value.value())?,
+            );
+        }
+        Ok(set)
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_file_reader::Network;
+
+    fn coin(n: u8) -> (OutPoint, UTXO) {
+        let outpoint = OutPoint {
+            hash: [n; 32],
+            index: 0,
+        };
+        let utxo = UTXO {
+            value: i64::from(n) * 1000,
+            script_pubkey: vec![0x51].into(),
+            height: u64::from(n),
+            is_coinbase: false,
+        };
+        (outpoint, utxo)
+    }
+
+    #[test]
+    fn spills_through_cache_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = SpillStore::create(dir.path(), 2).unwrap();
+        let mut base = UtxoSet::default();
+        let (o1, u1) = coin(1);
+        insert_utxo(&mut base, o1.clone(), u1.clone());
+        store.load(&base).unwrap();
+
+        // Coins 2..=6 pass through the hot and warm generations to disk; 1 (read back from disk),
+        // 3 (on disk only) and 6 (hot, never written) are spent
+        for n in 2..=6 {
+            let (outpoint, utxo) = coin(n);
+            store.insert(outpoint, &utxo);
+            store.len += 1;
+            if store.hot.len() >= store.cache_entries {
+                store.flush().unwrap();
+            }
+        }
+        assert_eq!(store.get(&o1).unwrap(), Some(u1));
+        for n in [1, 3, 6] {
+            store.spend(&coin(n).0);
+        }
+        assert_eq!(store.get(&coin(3).0).unwrap(), None);
+
+        let snapshot = store.snapshot().unwrap();
+        let mut heights: Vec<u64> = utxo_entries(&snapshot).map(|(_, u)| u.height).collect();
+        heights.sort();
+        assert_eq!(heights, [2, 4, 5]);
+        assert_eq!(store.len, 3);
+
+        let path = store.path.clone();
+        drop(store);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn checkpoints_stream_through_the_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoints =
+            CheckpointStore::new(dir.path().join("checkpoints"), Network::Mainnet).unwrap();
+        let mut base = UtxoSet::default();
+        for n in 1..=5 {
+            let (outpoint, utxo) = coin(n);
+            insert_utxo(&mut base, outpoint, utxo);
+        }
+        let mut spill = SpillStore::create(dir.path(), 2).unwrap();
+        spill.load(&base).unwrap();
+        let (o6, u6) = coin(6);
+        spill.insert(o6.clone(), &u6);
+        spill.len += 1;
+        spill.spend(&coin(2).0);
+        let mut store = UtxoStore::Spill(Box::new(spill));
+
+        let stats = store.save_checkpoint(&checkpoints, 6, &[6; 32]).unwrap();
+        let saved = checkpoints.load(6, &[6; 32]).unwrap();
+        assert_eq!(saved.len(), 5);
+        assert_eq!(stats, UtxoSetStats::of(&saved));
+        assert_eq!(
+            crate::consensus_compat::get_utxo(&saved, &o6).cloned(),
+            Some(u6)
+        );
+
+        let mut resumed = UtxoStore::Spill(Box::new(SpillStore::create(dir.path(), 2).unwrap()));
+        resumed.reset_from(&checkpoints, 6, &[6; 32]).unwrap();
+        assert_eq!(resumed.len(), 5);
+        assert_eq!(resumed.get(&coin(2).0).unwrap(), None);
+        assert_eq!(resumed.get(&coin(3).0).unwrap(), Some(coin(3).1));
+    }
+}
@@ -0,0 +1,255 @@
+//! UTXO set totals against Core's `gettxoutsetinfo`
+//!
+//! Every checkpoint records BLVM's UTXO count, total amount and bogosize ([`UtxoSetStats`]).
+//! [`compare_checkpoints`] asks Core for the same totals at each checkpoint height
+//! (`gettxoutsetinfo none <height>`, which needs `-coinstatsindex` for heights below the tip) and
+//! lists the per-height deltas, so drift can be charted over the chain. The comparison does not
+//! hash the set, so it costs one cheap RPC per checkpoint and can run on every validation; a
+//! non-zero delta says a range is worth a full [`checkpoint_diff`](crate::checkpoint_diff).
+//!
+//! Core never adds unspendable outputs (`OP_RETURN`, scripts over 10,000 bytes) to its set, so
+//! [`UtxoSetStats::of`] skips them too; a BLVM set that keeps them would otherwise drift further
+//! with every data-carrier output. The genesis coinbase, which Core also leaves out, is a
+//! constant offset when BLVM's set holds it.
+
+use anyhow::{Context, Result};
+use blvm_protocol::types::{UtxoSet, UTXO};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::Path;
+
+use crate::node_rpc_client::{NodeRpcClient, RpcConfig};
+use crate::parallel_differential::CheckpointTiming;
+
+/// Core RPC for the comparison, when `BLVM_COMPARE_TXOUTSETINFO=1` (connection from
+/// [`RpcConfig::from_env`]).
+pub fn rpc_config_from_env() -> Option<RpcConfig> {
+    std::env::var("BLVM_COMPARE_TXOUTSETINFO")
+        .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .then(RpcConfig::from_env)
+}
+
+/// The non-hash totals `gettxoutsetinfo` reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoSetStats {
+    pub txouts: u64,
+    /// Satoshis
+    pub total_amount: i64,
+    /// Core's serialized-size estimate: 50 bytes plus the script length per coin
+    pub bogosize: u64,
+}
+
+impl UtxoSetStats {
+    pub fn of(utxo_set: &UtxoSet) -> Self {
+        let mut stats = Self::default();
+        for (_, utxo) in crate::consensus_compat::utxo_entries(utxo_set) {
+            stats.add(utxo);
+        }
+        stats
+    }
+
+    /// Count one coin (skipped when unspendable), for totals over a set that is not in memory.
+    pub fn add(&mut self, utxo: &UTXO) {
+        if is_unspendable(&utxo.script_pubkey[..]) {
+            return;
+        }
+        self.txouts += 1;
+        self.total_amount += utxo.value;
+        self.bogosize += 50 + utxo.script_pubkey.len() as u64;
+    }
+
+    /// Parse a `gettxoutsetinfo` result (`total_amount` is in BTC).
+    pub fn from_txoutsetinfo(info: &Value) -> Result<Self> {
+        let field = |name: &str| {
+            info.get(name)
+                .with_context(|| format!("gettxoutsetinfo result has no `{}`", name))
+        };
+        Ok(Self {
+            txouts: field("txouts")?.as_u64().context("Invalid `txouts`")?,
+            total_amount: (field("total_amount")?
+                .as_f64()
+                .context("Invalid `total_amount`")?
+                * 100_000_000.0)
+                .round() as i64,
+            bogosize: field("bogosize")?.as_u64().context("Invalid `bogosize`")?,
+        })
+    }
+}
+
+/// Core's `CScript::IsUnspendable`: an `OP_RETURN` script or one over `MAX_SCRIPT_SIZE`
+fn is_unspendable(script_pubkey: &[u8]) -> bool {
+    script_pubkey.first() == Some(&0x6a) || script_pubkey.len() > 10_000
+}
+
+/// BLVM and Core totals at one checkpoint height.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoDrift {
+    pub height: u64,
+    pub blvm: UtxoSetStats,
+    pub core: UtxoSetStats,
+}
+
+impl UtxoDrift {
+    pub fn txouts_delta(&self) -> i64 {
+        self.blvm.txouts as i64 - self.core.txouts as i64
+    }
+
+    pub fn amount_delta(&self) -> i64 {
+        self.blvm.total_amount - self.core.total_amount
+    }
+
+    pub fn bogosize_delta(&self) -> i64 {
+        self.blvm.bogosize as i64 - self.core.bogosize as i64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.blvm == self.core
+    }
+}
+
+/// Compare each distinct checkpoint against Core at its height, by height.
+///
+/// Heights Core cannot answer for (no `-coinstatsindex`, not synced that far) are skipped with
+/// a warning; the first failure is reported in full.
+pub async fn compare_checkpoints(
+    rpc: &NodeRpcClient,
+    checkpoints: &[CheckpointTiming],
+) -> Vec<UtxoDrift> {
+    let mut checkpoints: Vec<&CheckpointTiming> = checkpoints.iter().collect();
+    checkpoints.sort_by_key(|cp| cp.height);
+    checkpoints.dedup_by_key(|cp| cp.height);
+
+    let mut drift = Vec::with_capacity(checkpoints.len());
+    let mut failures = 0usize;
+    for cp in checkpoints {
+        let core = rpc
+            .gettxoutsetinfo("none", Some(cp.height))
+            .await
+            .and_then(|info| UtxoSetStats::from_txoutsetinfo(&info));
+        match core {
+            Ok(core) => drift.push(UtxoDrift {
+                height: cp.height,
+                blvm: cp.stats,
+                core,
+            }),
+            Err(e) => {
+                if failures == 0 {
+                    eprintln!(
+                        "⚠️  gettxoutsetinfo at height {} failed (is -coinstatsindex enabled?): {:#}",
+                        cp.height, e
+                    );
+                }
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        eprintln!(
+            "⚠️  {} checkpoint(s) not compared against gettxoutsetinfo",
+            failures
+        );
+    }
+    drift
+}
+
+/// One-line verdict plus the first height that drifted.
+pub fn print_summary(drift: &[UtxoDrift]) {
+    match drift.iter().find(|d| !d.is_zero()) {
+        None => println!(
+            "✅ UTXO totals match gettxoutsetinfo at {} checkpoint(s)",
+            drift.len()
+        ),
+        Some(first) => {
+            let drifted = drift.iter().filter(|d| !d.is_zero()).count();
+            println!(
+                "⚠️  UTXO totals drift from gettxoutsetinfo at {} of {} checkpoint(s); first at \
+                 height {} (txouts {:+}, amount {:+} sat, bogosize {:+})",
+                drifted,
+                drift.len(),
+                first.height,
+                first.txouts_delta(),
+                first.amount_delta(),
+                first.bogosize_delta()
+            );
+        }
+    }
+}
+
+/// One row per height, BLVM and Core columns side by side with their deltas.
+pub fn write_csv(path: &Path, drift: &[UtxoDrift]) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    writeln!(
+        out,
+        "height,blvm_txouts,core_txouts,txouts_delta,blvm_total_amount,core_total_amount,\
+         amount_delta,blvm_bogosize,core_bogosize,bogosize_delta"
+    )?;
+    for d in drift {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{}",
+            d.height,
+            d.blvm.txouts,
+            d.core.txouts,
+            d.txouts_delta(),
+            d.blvm.total_amount,
+            d.core.total_amount,
+            d.amount_delta(),
+            d.blvm.bogosize,
+            d.core.bogosize,
+            d.bogosize_delta()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_txoutsetinfo_amount_in_btc() {
+        let info = serde_json::json!({
+            "height": 170,
+            "bestblock": "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee",
+            "txouts": 171,
+            "bogosize": 19107,
+            "total_amount": 8500.00000001,
+            "transactions": 170,
+        });
+        let stats = UtxoSetStats::from_txoutsetinfo(&info).unwrap();
+        assert_eq!(
+            stats,
+            UtxoSetStats {
+                txouts: 171,
+                total_amount: 850_000_000_001,
+                bogosize: 19107,
+            }
+        );
+
+        let drift = UtxoDrift {
+            height: 170,
+            blvm: UtxoSetStats {
+                txouts: 172,
+                total_amount: 855_000_000_001,
+                bogosize: 19174,
+            },
+            core: stats,
+        };
+        assert!(!drift.is_zero());
+        assert_eq!(drift.txouts_delta(), 1);
+        assert_eq!(drift.amount_delta(), 5_000_000_000);
+        assert_eq!(drift.bogosize_delta(), 67);
+    }
+
+    #[test]
+    fn unspendable_outputs_are_not_counted() {
+        assert!(is_unspendable(&[0x6a, 0x04, 1, 2, 3, 4]));
+        assert!(is_unspendable(&[0x51; 10_001]));
+        assert!(!is_unspendable(&[0x51]));
+        assert!(!is_unspendable(&[]));
+    }
+}
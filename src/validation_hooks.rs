@@ -0,0 +1,585 @@
+//! Validation hooks around `connect_block`
+//!
+//! A [`ValidationHook`] sees every block of a differential pass: before `connect_block` runs
+//! ([`pre_block`](ValidationHook::pre_block)), after both BLVM and Core have a verdict
+//! ([`post_block`](ValidationHook::post_block)) and whenever the two disagree
+//! ([`on_divergence`](ValidationHook::on_divergence)). Hooks are registered on
+//! [`ParallelConfig::hooks`](crate::parallel_differential::ParallelConfig::hooks), so one
+//! full-chain pass can feed several analyses instead of each one re-running validation.
+//!
+//! Chunks run in parallel and share the same hook instances, so hooks must be `Send + Sync` and
+//! must not assume blocks arrive in height order. An `Err` from a hook is an invariant
+//! violation: it fails the chunk like a read error would.
+//!
+//! In-crate hooks can be enabled by name with `BLVM_VALIDATION_HOOKS=utxo-size,utxo-accounting`
+//! (see [`builtin_hook`]). `divergence-artifacts` saves every diverging block for
+//! [`crate::replay`].
+
+use anyhow::{Context, Result};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::types::Block;
+use blvm_protocol::UtxoSet;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::artifacts::{artifact_dir_from_env, ArtifactContext, ArtifactKind};
+use crate::core_debug_log::{CoreDebugLog, CoreLogVerdict};
+use crate::differential::{CoreValidationResult, ValidationResult};
+use crate::wire::{display_hex, sha256d};
+
+/// Block as seen by hooks before `connect_block`.
+pub struct BlockContext<'a> {
+    pub height: u64,
+    pub block: &'a Block,
+    /// Raw serialized block (with witnesses)
+    pub block_bytes: &'a [u8],
+    /// Witness stacks per transaction, per input
+    pub witnesses: &'a [Vec<Witness>],
+    /// The coins the block's inputs spend, as they were before it (outputs created earlier in
+    /// the same block are not in here)
+    pub spent_coins: &'a UtxoSet,
+    /// Whole UTXO set the block is connected against; only materialized when a registered hook
+    /// asks for it with [`ValidationHook::wants_full_utxo_set`], and never by the ZMQ listener
+    pub utxo_set: Option<&'a UtxoSet>,
+}
+
+/// Verdicts and resulting state, passed to [`ValidationHook::post_block`].
+pub struct BlockOutcome<'a> {
+    pub blvm: &'a ValidationResult,
+    pub core: &'a CoreValidationResult,
+    /// UTXO set size before the block was connected; `None` where the whole set is not kept (the
+    /// ZMQ listener), see [`ValidationHook::needs_utxo_len`]
+    pub utxo_len_before: Option<usize>,
+    /// UTXO set size after the block (unchanged if BLVM rejected it), under the same conditions
+    pub utxo_len_after: Option<usize>,
+    /// Whole UTXO set after the block, under the same conditions as [`BlockContext::utxo_set`]
+    pub utxo_set_after: Option<&'a UtxoSet>,
+    /// Time BLVM's `connect_block` took
+    pub blvm_duration: Duration,
+}
+
+/// A BLVM / Core disagreement, passed to [`ValidationHook::on_divergence`].
+pub struct DivergenceEvent<'a> {
+    pub height: u64,
+    pub block_bytes: &'a [u8],
+    /// `Valid` or `Invalid(<reason>)`, as recorded in `ChunkResult::divergences`
+    pub blvm: &'a str,
+    pub core: &'a str,
+}
+
+/// Plugin point called by the differential pass. Every method defaults to a no-op.
+pub trait ValidationHook: Send + Sync {
+    /// Short identifier used in logs and error messages.
+    fn name(&self) -> &str;
+
+    /// Whether the hook needs [`BlockContext::utxo_set`] and [`BlockOutcome::utxo_set_after`].
+    /// Materializing them copies the whole set twice per block, so only opt in when the spent
+    /// coins and set sizes are not enough.
+    fn wants_full_utxo_set(&self) -> bool {
+        false
+    }
+
+    /// Whether the hook reads [`BlockOutcome::utxo_len_before`] / [`utxo_len_after`]. Passes
+    /// that only see each block's spent coins refuse such hooks (and full-set ones) up front
+    /// rather than feed them `None`.
+    ///
+    /// [`utxo_len_after`]: BlockOutcome::utxo_len_after
+    fn needs_utxo_len(&self) -> bool {
+        false
+    }
+
+    fn pre_block(&self, _ctx: &BlockContext<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    fn post_block(&self, _ctx: &BlockContext<'_>, _outcome: &BlockOutcome<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_divergence(&self, _event: &DivergenceEvent<'_>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Ordered set of hooks; cheap to clone into chunk tasks.
+#[derive(Clone, Default)]
+pub struct HookRegistry {
+    hooks: Arc<Vec<Arc<dyn ValidationHook>>>,
+}
+
+impl fmt::Debug for HookRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.hooks.iter().map(|h| h.name()))
+            .finish()
+    }
+}
+
+impl HookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Built-in hooks named in `BLVM_VALIDATION_HOOKS` (comma-separated). Unknown names are an
+    /// error so a typo does not silently skip an analysis.
+    ///
+    /// `BLVM_CORE_DEBUG_LOG=<path to debug.log>` adds a [`CoreLogReplayHook`].
+    pub fn from_env() -> Result<Self> {
+        let mut registry = match std::env::var("BLVM_VALIDATION_HOOKS") {
+            Ok(names) => Self::from_names(&names)?,
+            Err(_) => Self::default(),
+        };
+        if let Ok(path) = std::env::var("BLVM_CORE_DEBUG_LOG") {
+            let log = CoreDebugLog::open(std::path::Path::new(&path))?;
+            println!(
+                "📜 Core debug.log: {} tips, {} invalid blocks ({})",
+                log.tips.len(),
+                log.invalid.len(),
+                path
+            );
+            registry.register(Arc::new(CoreLogReplayHook::new(Arc::new(log))));
+        }
+        Ok(registry)
+    }
+
+    /// Built-in hooks from a comma-separated list of names.
+    pub fn from_names(names: &str) -> Result<Self> {
+        let mut registry = Self::default();
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let hook = builtin_hook(name).with_context(|| {
+                format!(
+                    "Unknown validation hook '{}' (available: {})",
+                    name,
+                    BUILTIN_HOOKS.join(", ")
+                )
+            })?;
+            registry.register(hook);
+        }
+        Ok(registry)
+    }
+
+    /// Add a hook; hooks run in registration order.
+    pub fn register(&mut self, hook: Arc<dyn ValidationHook>) -> &mut Self {
+        Arc::make_mut(&mut self.hooks).push(hook);
+        self
+    }
+
+    pub fn with(mut self, hook: Arc<dyn ValidationHook>) -> Self {
+        self.register(hook);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Any hook asked for whole UTXO sets.
+    pub fn wants_full_utxo_set(&self) -> bool {
+        self.hooks.iter().any(|h| h.wants_full_utxo_set())
+    }
+
+    /// Names of the hooks that need the UTXO set size or the whole set
+    /// ([`ValidationHook::needs_utxo_len`], [`ValidationHook::wants_full_utxo_set`]).
+    pub fn needing_utxo_set(&self) -> Vec<&str> {
+        self.hooks
+            .iter()
+            .filter(|h| h.needs_utxo_len() || h.wants_full_utxo_set())
+            .map(|h| h.name())
+            .collect()
+    }
+
+    pub fn pre_block(&self, ctx: &BlockContext<'_>) -> Result<()> {
+        for hook in self.hooks.iter() {
+            hook.pre_block(ctx).with_context(|| {
+                format!("Hook '{}' failed before block {}", hook.name(), ctx.height)
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        for hook in self.hooks.iter() {
+            hook.post_block(ctx, outcome).with_context(|| {
+                format!("Hook '{}' failed after block {}", hook.name(), ctx.height)
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn on_divergence(&self, event: &DivergenceEvent<'_>) -> Result<()> {
+        for hook in self.hooks.iter() {
+            hook.on_divergence(event).with_context(|| {
+                format!(
+                    "Hook '{}' failed on divergence at {}",
+                    hook.name(),
+                    event.height
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Names accepted by [`builtin_hook`].
+pub const BUILTIN_HOOKS: &[&str] = &["utxo-size", "utxo-accounting", "divergence-artifacts"];
+
+/// In-crate hook by name.
+pub fn builtin_hook(name: &str) -> Option<Arc<dyn ValidationHook>> {
+    match name {
+        "utxo-size" => Some(Arc::new(UtxoSetSizeHook::default())),
+        "utxo-accounting" => Some(Arc::new(UtxoAccountingInvariant)),
+        "divergence-artifacts" => Some(Arc::new(DivergenceArtifactHook::from_env())),
+        _ => None,
+    }
+}
+
+/// Records the UTXO set size after every block (metric).
+#[derive(Default)]
+pub struct UtxoSetSizeHook {
+    sizes: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl UtxoSetSizeHook {
+    /// `(height, utxo count)` in height order.
+    pub fn samples(&self) -> Vec<(u64, usize)> {
+        self.sizes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&h, &n)| (h, n))
+            .collect()
+    }
+}
+
+impl ValidationHook for UtxoSetSizeHook {
+    fn name(&self) -> &str {
+        "utxo-size"
+    }
+
+    fn needs_utxo_len(&self) -> bool {
+        true
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        let len = outcome.utxo_len_after.context("No UTXO set size")?;
+        self.sizes.lock().unwrap().insert(ctx.height, len);
+        Ok(())
+    }
+}
+
+/// A block BLVM accepted can only shrink the UTXO set by the inputs it spends and grow it by
+/// the outputs it creates.
+pub struct UtxoAccountingInvariant;
+
+impl ValidationHook for UtxoAccountingInvariant {
+    fn name(&self) -> &str {
+        "utxo-accounting"
+    }
+
+    fn needs_utxo_len(&self) -> bool {
+        true
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        if !matches!(outcome.blvm, ValidationResult::Valid) {
+            return Ok(());
+        }
+        let inputs: usize = ctx
+            .block
+            .transactions
+            .iter()
+            .filter(|tx| !blvm_protocol::transaction::is_coinbase(tx))
+            .map(|tx| tx.inputs.len())
+            .sum();
+        let outputs: usize = ctx
+            .block
+            .transactions
+            .iter()
+            .map(|tx| tx.outputs.len())
+            .sum();
+        check_utxo_bounds(
+            outcome.utxo_len_before.context("No UTXO set size")?,
+            outcome.utxo_len_after.context("No UTXO set size")?,
+            inputs,
+            outputs,
+        )
+    }
+}
+
+/// BLVM's verdict and `connect_block` time next to what Core logged for the same block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreLogComparison {
+    pub height: u64,
+    pub blvm_valid: bool,
+    pub blvm_ms: f64,
+    pub core: CoreLogVerdict,
+    /// Core's "Connect total" for the block (needs `-debug=bench` in the logged sync)
+    pub core_ms: Option<f64>,
+}
+
+impl CoreLogComparison {
+    /// BLVM and Core's log disagree on validity (blocks Core never logged do not count).
+    pub fn disagrees(&self) -> bool {
+        match &self.core {
+            CoreLogVerdict::Connected => !self.blvm_valid,
+            CoreLogVerdict::Invalid(_) => self.blvm_valid,
+            CoreLogVerdict::OtherBlock(_) | CoreLogVerdict::NotLogged => false,
+        }
+    }
+}
+
+/// Replays Core's `debug.log` next to the differential pass: checks every block against the
+/// tip / rejection Core logged at that height and pairs BLVM's timing with Core's bench timing.
+pub struct CoreLogReplayHook {
+    log: Arc<CoreDebugLog>,
+    comparisons: Mutex<BTreeMap<u64, CoreLogComparison>>,
+}
+
+impl CoreLogReplayHook {
+    pub fn new(log: Arc<CoreDebugLog>) -> Self {
+        Self {
+            log,
+            comparisons: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Compared blocks in height order.
+    pub fn comparisons(&self) -> Vec<CoreLogComparison> {
+        self.comparisons.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Blocks compared, disagreements, and total BLVM / Core ms over blocks that have both.
+    pub fn print_summary(&self) {
+        let comparisons = self.comparisons.lock().unwrap();
+        let disagreements = comparisons.values().filter(|c| c.disagrees()).count();
+        let (blvm_ms, core_ms, timed) = comparisons
+            .values()
+            .filter_map(|c| Some((c.blvm_ms, c.core_ms?)))
+            .fold((0.0, 0.0, 0usize), |(b, c, n), (blvm, core)| {
+                (b + blvm, c + core, n + 1)
+            });
+        println!(
+            "📜 Core log replay: {} blocks compared, {} disagreements",
+            comparisons.len(),
+            disagreements
+        );
+        if timed > 0 {
+            println!(
+                "   connect time over {} blocks: BLVM {:.1}ms, Core {:.1}ms ({:.2}x)",
+                timed,
+                blvm_ms,
+                core_ms,
+                blvm_ms / core_ms.max(f64::EPSILON)
+            );
+        }
+    }
+}
+
+impl ValidationHook for CoreLogReplayHook {
+    fn name(&self) -> &str {
+        "core-log-replay"
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        let Some(header) = ctx.block_bytes.get(..80) else {
+            return Ok(());
+        };
+        let core = self.log.verdict(ctx.height, &display_hex(&sha256d(header)));
+        let comparison = CoreLogComparison {
+            height: ctx.height,
+            blvm_valid: matches!(outcome.blvm, ValidationResult::Valid),
+            blvm_ms: outcome.blvm_duration.as_secs_f64() * 1000.0,
+            core_ms: match core {
+                CoreLogVerdict::Connected => self.log.tips[&ctx.height].connect_total_ms(),
+                _ => None,
+            },
+            core,
+        };
+        if comparison.disagrees() {
+            eprintln!(
+                "⚠️  Core's debug.log disagrees at height {}: BLVM valid={}, Core logged {:?}",
+                ctx.height, comparison.blvm_valid, comparison.core
+            );
+        }
+        self.comparisons
+            .lock()
+            .unwrap()
+            .insert(ctx.height, comparison);
+        Ok(())
+    }
+}
+
+/// Saves a [`BlockDivergenceArtifact`](crate::replay::BlockDivergenceArtifact) for each block
+/// BLVM and Core disagree on, for `blvm-bench replay`.
+pub struct DivergenceArtifactHook {
+    dir: PathBuf,
+    limit: usize,
+    written: AtomicUsize,
+}
+
+impl DivergenceArtifactHook {
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            limit,
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// The divergences artifact dir if an output dir is configured, else `divergences/`; at most
+    /// `DIVERGENCE_ARTIFACT_LIMIT` blocks.
+    pub fn from_env() -> Self {
+        let dir = artifact_dir_from_env(ArtifactKind::Divergences, &ArtifactContext::now())
+            .unwrap_or_else(|| PathBuf::from("divergences"));
+        let limit = crate::sort_merge::divergence::artifact_limit_from_env().unwrap_or_else(|e| {
+            eprintln!("⚠️  {:#}, using the default", e);
+            crate::sort_merge::divergence::DEFAULT_ARTIFACT_LIMIT
+        });
+        Self::new(dir, limit)
+    }
+
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Relaxed).min(self.limit)
+    }
+}
+
+impl ValidationHook for DivergenceArtifactHook {
+    fn name(&self) -> &str {
+        "divergence-artifacts"
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        let (blvm, core) = match (outcome.blvm, outcome.core) {
+            (ValidationResult::Valid, CoreValidationResult::Valid)
+            | (ValidationResult::Invalid(_), CoreValidationResult::Invalid(_)) => return Ok(()),
+            (ValidationResult::Valid, CoreValidationResult::Invalid(core)) => {
+                ("Valid".to_string(), format!("Invalid({})", core))
+            }
+            (ValidationResult::Invalid(blvm), CoreValidationResult::Valid) => {
+                (format!("Invalid({})", blvm), "Valid".to_string())
+            }
+        };
+        if self.written.fetch_add(1, Ordering::Relaxed) >= self.limit {
+            return Ok(());
+        }
+        let artifact = crate::replay::BlockDivergenceArtifact::capture(
+            &ArtifactContext::now().network,
+            ctx.height,
+            ctx.block,
+            ctx.block_bytes,
+            ctx.spent_coins,
+            &blvm,
+            &core,
+        )?;
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(artifact.file_name());
+        artifact.save(&path)?;
+        eprintln!(
+            "📁 Divergence at height {} saved to {}",
+            ctx.height,
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+fn check_utxo_bounds(before: usize, after: usize, inputs: usize, outputs: usize) -> Result<()> {
+    anyhow::ensure!(
+        after <= before + outputs,
+        "UTXO set grew from {} to {} with only {} outputs",
+        before,
+        after,
+        outputs
+    );
+    anyhow::ensure!(
+        after + inputs >= before,
+        "UTXO set shrank from {} to {} with only {} inputs",
+        before,
+        after,
+        inputs
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CountDivergences(AtomicUsize);
+
+    impl ValidationHook for CountDivergences {
+        fn name(&self) -> &str {
+            "count"
+        }
+
+        fn on_divergence(&self, event: &DivergenceEvent<'_>) -> Result<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            anyhow::ensure!(event.height != 13, "unlucky height");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registry_dispatches_and_names_failing_hook() {
+        let counter = Arc::new(CountDivergences(AtomicUsize::new(0)));
+        let registry = HookRegistry::new().with(counter.clone());
+        let event = |height| DivergenceEvent {
+            height,
+            block_bytes: &[],
+            blvm: "Valid",
+            core: "Invalid(x)",
+        };
+        registry.on_divergence(&event(12)).unwrap();
+        let err = registry.on_divergence(&event(13)).unwrap_err();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+        assert!(format!("{:#}", err).contains("Hook 'count'"));
+        assert!(HookRegistry::from_names("utxo-size, nope").is_err());
+        assert_eq!(HookRegistry::from_names("utxo-size,").unwrap().len(), 1);
+    }
+
+    struct WantsFullSet;
+
+    impl ValidationHook for WantsFullSet {
+        fn name(&self) -> &str {
+            "full-set"
+        }
+
+        fn wants_full_utxo_set(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn full_utxo_sets_only_on_request() {
+        let registry = HookRegistry::from_names("utxo-size,utxo-accounting")
+            .unwrap()
+            .with(Arc::new(CountDivergences(AtomicUsize::new(0))));
+        assert!(!registry.wants_full_utxo_set());
+        assert_eq!(
+            registry.needing_utxo_set(),
+            ["utxo-size", "utxo-accounting"]
+        );
+        let registry = registry.with(Arc::new(WantsFullSet));
+        assert!(registry.wants_full_utxo_set());
+        assert_eq!(
+            registry.needing_utxo_set(),
+            ["utxo-size", "utxo-accounting", "full-set"]
+        );
+    }
+
+    #[test]
+    fn utxo_bounds() {
+        assert!(check_utxo_bounds(10, 12, 1, 3).is_ok());
+        assert!(check_utxo_bounds(10, 14, 1, 3).is_err());
+        assert!(check_utxo_bounds(10, 7, 2, 0).is_err());
+    }
+}
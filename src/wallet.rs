@@ -0,0 +1,535 @@
+//! Minimal key manager for scenario tests
+//!
+//! Custom Core builds from [`node_builder`](crate::node_builder) are not always compiled with a
+//! wallet, so scenarios that need signed transactions hold their own keys: BIP32 derivation from
+//! a seed, P2WPKH (BIP84 paths) and key-path P2TR (BIP86 paths) addresses, and signing with
+//! `SIGHASH_ALL` / `SIGHASH_DEFAULT`. There is no coin selection or change handling — callers
+//! pick the inputs and outputs.
+//!
+//! Sighashes (BIP143 / BIP341) and the witness serialization are computed here rather than with
+//! `blvm_protocol`, so a signed transaction does not depend on the code it is used to test.
+
+use anyhow::{bail, Context, Result};
+use bech32::{hrp, segwit, Hrp};
+use blvm_protocol::{OutPoint, Transaction};
+use hmac::{Hmac, Mac};
+use ripemd::Ripemd160;
+use secp256k1::{All, Keypair, Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::block_file_reader::Network;
+use crate::node_rpc_client::NodeRpcClient;
+use crate::wire::{sha256d, write_compact_size};
+
+/// Witness stack of one input.
+pub type WitnessStack = Vec<Vec<u8>>;
+
+const HARDENED: u32 = 1 << 31;
+const SIGHASH_ALL: u8 = 0x01;
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub(crate) fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+/// BIP340 tagged hash.
+pub(crate) fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag = sha256(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update(tag);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+pub(crate) fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+pub(crate) fn write_outpoint(out: &mut Vec<u8>, outpoint: &OutPoint) {
+    out.extend_from_slice(&outpoint.hash);
+    out.extend((outpoint.index as u32).to_le_bytes());
+}
+
+pub(crate) fn serialize_outputs(tx: &Transaction) -> Vec<u8> {
+    let mut out = Vec::new();
+    for output in tx.outputs.iter() {
+        out.extend(output.value.to_le_bytes());
+        write_bytes(&mut out, &output.script_pubkey);
+    }
+    out
+}
+
+/// BIP32 extended private key.
+#[derive(Clone)]
+pub struct ExtendedKey {
+    secret: SecretKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    /// Master key for `seed` (16 to 64 bytes).
+    pub fn master(seed: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            (16..=64).contains(&seed.len()),
+            "BIP32 seed must be 16-64 bytes, got {}",
+            seed.len()
+        );
+        Self::from_hmac(hmac_sha512(b"Bitcoin seed", seed))
+    }
+
+    fn from_hmac(i: [u8; 64]) -> Result<Self> {
+        let secret = SecretKey::from_slice(&i[..32]).context("Derived key out of range")?;
+        Ok(Self {
+            secret,
+            chain_code: i[32..].try_into().unwrap(),
+        })
+    }
+
+    pub fn secret_key(&self) -> SecretKey {
+        self.secret
+    }
+
+    /// Child `index` (`>= 2^31` is hardened).
+    pub fn derive_child(&self, secp: &Secp256k1<All>, index: u32) -> Result<Self> {
+        let mut data = Vec::with_capacity(37);
+        if index >= HARDENED {
+            data.push(0);
+            data.extend_from_slice(&self.secret.secret_bytes());
+        } else {
+            data.extend_from_slice(&PublicKey::from_secret_key(secp, &self.secret).serialize());
+        }
+        data.extend(index.to_be_bytes());
+        let i = hmac_sha512(&self.chain_code, &data);
+        let tweak = Scalar::from_be_bytes(i[..32].try_into().unwrap())
+            .map_err(|_| anyhow::anyhow!("Child {} is invalid (IL >= n)", index))?;
+        Ok(Self {
+            secret: self
+                .secret
+                .add_tweak(&tweak)
+                .with_context(|| format!("Child {} is invalid (zero key)", index))?,
+            chain_code: i[32..].try_into().unwrap(),
+        })
+    }
+
+    /// Derive along a path like `m/84'/1'/0'/0/5` (`h` also marks hardened steps).
+    pub fn derive_path(&self, secp: &Secp256k1<All>, path: &str) -> Result<Self> {
+        let mut steps = path.split('/');
+        if steps.next() != Some("m") {
+            bail!("Derivation path {:?} must start with m", path);
+        }
+        let mut key = self.clone();
+        for step in steps {
+            let (number, hardened) = match step.strip_suffix(['\'', 'h']) {
+                Some(n) => (n, true),
+                None => (step, false),
+            };
+            let index: u32 = number
+                .parse()
+                .ok()
+                .filter(|i| *i < HARDENED)
+                .with_context(|| format!("Bad path step {:?} in {:?}", step, path))?;
+            key = key.derive_child(secp, if hardened { index | HARDENED } else { index })?;
+        }
+        Ok(key)
+    }
+}
+
+/// Output type a [`WalletKey`] is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressKind {
+    /// BIP84, witness v0 key hash
+    P2wpkh,
+    /// BIP86, witness v1 key path without a script tree
+    P2tr,
+}
+
+impl AddressKind {
+    fn purpose(self) -> u32 {
+        match self {
+            AddressKind::P2wpkh => 84,
+            AddressKind::P2tr => 86,
+        }
+    }
+}
+
+/// One derived key and its output script.
+#[derive(Clone)]
+pub struct WalletKey {
+    pub kind: AddressKind,
+    pub path: String,
+    secret: SecretKey,
+    /// Witness program: HASH160 of the compressed key, or the tweaked x-only output key
+    program: Vec<u8>,
+}
+
+impl WalletKey {
+    fn new(secp: &Secp256k1<All>, kind: AddressKind, path: String, secret: SecretKey) -> Self {
+        let program = match kind {
+            AddressKind::P2wpkh => {
+                hash160(&PublicKey::from_secret_key(secp, &secret).serialize()).to_vec()
+            }
+            AddressKind::P2tr => {
+                let (output_key, _) = taproot_tweak(secp, &secret).x_only_public_key();
+                output_key.serialize().to_vec()
+            }
+        };
+        Self {
+            kind,
+            path,
+            secret,
+            program,
+        }
+    }
+
+    pub fn public_key(&self, secp: &Secp256k1<All>) -> PublicKey {
+        PublicKey::from_secret_key(secp, &self.secret)
+    }
+
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        let version = match self.kind {
+            AddressKind::P2wpkh => 0x00,
+            AddressKind::P2tr => 0x51,
+        };
+        let mut script = vec![version, self.program.len() as u8];
+        script.extend_from_slice(&self.program);
+        script
+    }
+
+    /// Bech32 (v0) / bech32m (v1) address.
+    pub fn address(&self, network: Network) -> String {
+        let hrp: Hrp = match network {
+            Network::Mainnet => hrp::BC,
+            Network::Regtest => hrp::BCRT,
+            Network::Testnet | Network::Testnet4 | Network::Signet => hrp::TB,
+        };
+        let version = match self.kind {
+            AddressKind::P2wpkh => segwit::VERSION_0,
+            AddressKind::P2tr => segwit::VERSION_1,
+        };
+        segwit::encode(hrp, version, &self.program).expect("valid witness program")
+    }
+}
+
+/// BIP86 output key: internal key tweaked with `TapTweak(P)` and no script tree.
+pub(crate) fn taproot_tweak(secp: &Secp256k1<All>, secret: &SecretKey) -> Keypair {
+    let keypair = Keypair::from_secret_key(secp, secret);
+    let (internal, _) = keypair.x_only_public_key();
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &internal.serialize()))
+        .expect("tagged hash below curve order");
+    keypair
+        .add_xonly_tweak(secp, &tweak)
+        .expect("tweak of a valid key")
+}
+
+/// An output a [`Wallet`] can spend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendableOutput {
+    pub outpoint: OutPoint,
+    pub value: i64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// Seeded key chain: BIP84 / BIP86 receive paths, signing for any key it handed out.
+pub struct Wallet {
+    secp: Secp256k1<All>,
+    master: ExtendedKey,
+    network: Network,
+    keys: Vec<WalletKey>,
+}
+
+impl Wallet {
+    pub fn from_seed(seed: &[u8], network: Network) -> Result<Self> {
+        Ok(Self {
+            secp: Secp256k1::new(),
+            master: ExtendedKey::master(seed)?,
+            network,
+            keys: Vec::new(),
+        })
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Next unused receive key of `kind` (`m/<purpose>'/<coin>'/0'/0/<n>`).
+    pub fn new_key(&mut self, kind: AddressKind) -> Result<&WalletKey> {
+        let coin = if self.network == Network::Mainnet {
+            0
+        } else {
+            1
+        };
+        let n = self.keys.iter().filter(|k| k.kind == kind).count();
+        let path = format!("m/{}'/{}'/0'/0/{}", kind.purpose(), coin, n);
+        let secret = self.master.derive_path(&self.secp, &path)?.secret_key();
+        self.keys
+            .push(WalletKey::new(&self.secp, kind, path, secret));
+        Ok(self.keys.last().unwrap())
+    }
+
+    pub fn keys(&self) -> &[WalletKey] {
+        &self.keys
+    }
+
+    /// The key that owns `script_pubkey`, if this wallet derived it.
+    pub fn key_for_script(&self, script_pubkey: &[u8]) -> Option<&WalletKey> {
+        self.keys
+            .iter()
+            .find(|k| k.script_pubkey() == script_pubkey)
+    }
+
+    /// Witness stacks for every input of `tx`. `prevouts[i]` is the `(value, scriptPubKey)`
+    /// input `i` spends; every input must belong to this wallet.
+    pub fn sign(&self, tx: &Transaction, prevouts: &[(i64, Vec<u8>)]) -> Result<Vec<WitnessStack>> {
+        anyhow::ensure!(
+            prevouts.len() == tx.inputs.len(),
+            "{} prevouts for {} inputs",
+            prevouts.len(),
+            tx.inputs.len()
+        );
+        let sighashes = SighashCache::new(tx, prevouts);
+        let mut witnesses = Vec::with_capacity(tx.inputs.len());
+        for (index, (value, script_pubkey)) in prevouts.iter().enumerate() {
+            let key = self.key_for_script(script_pubkey).with_context(|| {
+                format!(
+                    "Input {} spends {} which is not a wallet script",
+                    index,
+                    hex::encode(script_pubkey)
+                )
+            })?;
+            let witness = match key.kind {
+                AddressKind::P2wpkh => {
+                    let public_key = key.public_key(&self.secp);
+                    let digest = sighashes.segwit_v0(index, &public_key, *value);
+                    let signature = self
+                        .secp
+                        .sign_ecdsa(&Message::from_digest(digest), &key.secret);
+                    let mut sig = signature.serialize_der().to_vec();
+                    sig.push(SIGHASH_ALL);
+                    vec![sig, public_key.serialize().to_vec()]
+                }
+                AddressKind::P2tr => {
+                    let digest = sighashes.taproot_key_path(index);
+                    let keypair = taproot_tweak(&self.secp, &key.secret);
+                    let signature = self
+                        .secp
+                        .sign_schnorr_no_aux_rand(&Message::from_digest(digest), &keypair);
+                    vec![signature.as_ref().to_vec()]
+                }
+            };
+            witnesses.push(witness);
+        }
+        Ok(witnesses)
+    }
+}
+
+/// Per-transaction hashes shared by every input's sighash.
+struct SighashCache<'a> {
+    tx: &'a Transaction,
+    prevouts: &'a [(i64, Vec<u8>)],
+    prevouts_sha: [u8; 32],
+    amounts_sha: [u8; 32],
+    script_pubkeys_sha: [u8; 32],
+    sequences_sha: [u8; 32],
+    outputs_sha: [u8; 32],
+}
+
+impl<'a> SighashCache<'a> {
+    fn new(tx: &'a Transaction, prevouts: &'a [(i64, Vec<u8>)]) -> Self {
+        let mut outpoints = Vec::new();
+        let mut sequences = Vec::new();
+        for input in tx.inputs.iter() {
+            write_outpoint(&mut outpoints, &input.prevout);
+            sequences.extend((input.sequence as u32).to_le_bytes());
+        }
+        let mut amounts = Vec::new();
+        let mut script_pubkeys = Vec::new();
+        for (value, script_pubkey) in prevouts {
+            amounts.extend(value.to_le_bytes());
+            write_bytes(&mut script_pubkeys, script_pubkey);
+        }
+        Self {
+            tx,
+            prevouts,
+            prevouts_sha: sha256(&outpoints),
+            amounts_sha: sha256(&amounts),
+            script_pubkeys_sha: sha256(&script_pubkeys),
+            sequences_sha: sha256(&sequences),
+            outputs_sha: sha256(&serialize_outputs(tx)),
+        }
+    }
+
+    /// BIP143 `SIGHASH_ALL` for a P2WPKH input.
+    fn segwit_v0(&self, index: usize, public_key: &PublicKey, value: i64) -> [u8; 32] {
+        let input = &self.tx.inputs[index];
+        let mut preimage = Vec::with_capacity(182);
+        preimage.extend((self.tx.version as u32).to_le_bytes());
+        // BIP143's hashPrevouts / hashSequence / hashOutputs are double SHA-256
+        preimage.extend(sha256(&self.prevouts_sha));
+        preimage.extend(sha256(&self.sequences_sha));
+        write_outpoint(&mut preimage, &input.prevout);
+        preimage.extend([0x19, 0x76, 0xa9, 0x14]);
+        preimage.extend(hash160(&public_key.serialize()));
+        preimage.extend([0x88, 0xac]);
+        preimage.extend(value.to_le_bytes());
+        preimage.extend((input.sequence as u32).to_le_bytes());
+        preimage.extend(sha256(&self.outputs_sha));
+        preimage.extend((self.tx.lock_time as u32).to_le_bytes());
+        preimage.extend((SIGHASH_ALL as u32).to_le_bytes());
+        sha256d(&preimage)
+    }
+
+    /// BIP341 `SIGHASH_DEFAULT` for a key-path spend without annex.
+    fn taproot_key_path(&self, index: usize) -> [u8; 32] {
+        debug_assert_eq!(self.prevouts.len(), self.tx.inputs.len());
+        let mut msg = Vec::with_capacity(175);
+        msg.push(0x00); // sighash epoch
+        msg.push(0x00); // SIGHASH_DEFAULT
+        msg.extend((self.tx.version as u32).to_le_bytes());
+        msg.extend((self.tx.lock_time as u32).to_le_bytes());
+        msg.extend(self.prevouts_sha);
+        msg.extend(self.amounts_sha);
+        msg.extend(self.script_pubkeys_sha);
+        msg.extend(self.sequences_sha);
+        msg.extend(self.outputs_sha);
+        msg.push(0x00); // key path, no annex
+        msg.extend((index as u32).to_le_bytes());
+        tagged_hash("TapSighash", &msg)
+    }
+}
+
+/// BIP144 serialization of `tx` with `witnesses` (what `sendrawtransaction` takes).
+pub fn serialize_signed(tx: &Transaction, witnesses: &[WitnessStack]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((tx.version as u32).to_le_bytes());
+    out.extend([0x00, 0x01]);
+    write_compact_size(&mut out, tx.inputs.len());
+    for input in tx.inputs.iter() {
+        write_outpoint(&mut out, &input.prevout);
+        write_bytes(&mut out, &input.script_sig);
+        out.extend((input.sequence as u32).to_le_bytes());
+    }
+    write_compact_size(&mut out, tx.outputs.len());
+    out.extend(serialize_outputs(tx));
+    for index in 0..tx.inputs.len() {
+        let stack = witnesses.get(index).map_or(&[][..], |w| &w[..]);
+        write_compact_size(&mut out, stack.len());
+        for item in stack {
+            write_bytes(&mut out, item);
+        }
+    }
+    out.extend((tx.lock_time as u32).to_le_bytes());
+    out
+}
+
+/// Mine `blocks` regtest blocks to `key` and return the coinbase outputs paying it (spendable
+/// once 100 more blocks are mined on top).
+pub async fn mine_to_key(
+    client: &NodeRpcClient,
+    key: &WalletKey,
+    blocks: u64,
+) -> Result<Vec<SpendableOutput>> {
+    let script_pubkey = key.script_pubkey();
+    let script_hex = hex::encode(&script_pubkey);
+    let mut outputs = Vec::new();
+    for block_hash in client
+        .generatetoaddress(blocks, &key.address(Network::Regtest))
+        .await?
+    {
+        let block = client.getblock(&block_hash, 2).await?;
+        let coinbase = block["tx"]
+            .get(0)
+            .with_context(|| format!("Block {} has no transactions", block_hash))?;
+        let mut hash: [u8; 32] = hex::decode(coinbase["txid"].as_str().context("Missing txid")?)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Bad txid length"))?;
+        hash.reverse();
+        for vout in coinbase["vout"].as_array().context("Missing vout")? {
+            if vout["scriptPubKey"]["hex"].as_str() != Some(&script_hex) {
+                continue;
+            }
+            let btc = vout["value"].as_f64().context("Missing output value")?;
+            outputs.push(SpendableOutput {
+                outpoint: OutPoint {
+                    hash,
+                    index: vout["n"].as_u64().context("Missing vout n")? as _,
+                },
+                value: (btc * 1e8).round() as i64,
+                script_pubkey: script_pubkey.clone(),
+            });
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blvm_protocol::{tx_inputs, tx_outputs, TransactionInput, TransactionOutput};
+
+    /// BIP39 seed of "abandon abandon ... about" (empty passphrase).
+    const ABANDON_SEED: &str = "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+                                9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4";
+
+    #[test]
+    fn derives_bip84_and_bip86_test_vectors() {
+        let mut wallet =
+            Wallet::from_seed(&hex::decode(ABANDON_SEED).unwrap(), Network::Mainnet).unwrap();
+        let p2wpkh = wallet.new_key(AddressKind::P2wpkh).unwrap();
+        assert_eq!(p2wpkh.path, "m/84'/0'/0'/0/0");
+        assert_eq!(
+            p2wpkh.address(Network::Mainnet),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        let p2tr = wallet.new_key(AddressKind::P2tr).unwrap();
+        assert_eq!(
+            p2tr.address(Network::Mainnet),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn p2wpkh_signature_verifies_in_libbitcoinconsensus() {
+        let mut wallet = Wallet::from_seed(&[7u8; 32], Network::Regtest).unwrap();
+        let script_pubkey = wallet.new_key(AddressKind::P2wpkh).unwrap().script_pubkey();
+        let value = 50_000i64;
+        let tx = Transaction {
+            version: 2,
+            inputs: tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [3u8; 32],
+                    index: 1,
+                },
+                script_sig: Vec::new(),
+                sequence: 0xffff_fffd,
+            }],
+            outputs: tx_outputs![TransactionOutput {
+                value: 49_000,
+                script_pubkey: vec![0x6a],
+            }],
+            lock_time: 0,
+        };
+        let prevouts = [(value, script_pubkey.clone())];
+        let witnesses = wallet.sign(&tx, &prevouts).unwrap();
+        let bytes = serialize_signed(&tx, &witnesses);
+        bitcoinconsensus::verify_with_flags(
+            &script_pubkey,
+            value as u64,
+            &bytes,
+            None,
+            0,
+            bitcoinconsensus::VERIFY_ALL_PRE_TAPROOT,
+        )
+        .expect("wallet signature accepted by Core");
+    }
+}
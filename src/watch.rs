@@ -0,0 +1,394 @@
+//! Watch mode for consensus development loops
+//!
+//! Polls the local `blvm-consensus` checkout (the `[patch.crates-io]` path in this crate's
+//! `Cargo.toml`) and, once an edit settles, rebuilds and re-runs a chosen set of Criterion benches
+//! and differential tests. Each run is printed against the previous one (bench mean time change,
+//! tests that started or stopped passing), so a consensus change can be measured without leaving
+//! the editor.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cancel::CancellationToken;
+
+/// Bench mean changes smaller than this are shown as noise (`~`).
+const NOISE_PCT: f64 = 2.0;
+
+/// What to re-run on each change.
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// Source tree to watch (`.rs` files and `Cargo.toml`, `target/` ignored)
+    pub consensus_dir: PathBuf,
+    /// Criterion bench targets (`cargo bench --bench <name>`)
+    pub benches: Vec<String>,
+    /// Integration test targets (`cargo test --test <name>`)
+    pub tests: Vec<String>,
+    /// Cargo features for the build, benches and tests
+    pub features: Vec<String>,
+    pub poll_interval: Duration,
+    /// Wait until the tree has been quiet this long before rebuilding (editors save in bursts)
+    pub settle: Duration,
+}
+
+impl WatchConfig {
+    pub fn new(consensus_dir: PathBuf) -> Self {
+        Self {
+            consensus_dir,
+            benches: Vec::new(),
+            tests: Vec::new(),
+            features: Vec::new(),
+            poll_interval: Duration::from_millis(500),
+            settle: Duration::from_millis(750),
+        }
+    }
+}
+
+/// `blvm-consensus` path from `[patch.crates-io]` in `<manifest_dir>/Cargo.toml`, relative to
+/// `manifest_dir`.
+pub fn consensus_dir_from_manifest(manifest_dir: &Path) -> Result<PathBuf> {
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    let text = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let manifest: toml::Value = toml::from_str(&text)
+        .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+    let path = manifest
+        .get("patch")
+        .and_then(|p| p.get("crates-io"))
+        .and_then(|p| p.get("blvm-consensus"))
+        .and_then(|c| c.get("path"))
+        .and_then(|p| p.as_str())
+        .context("No [patch.crates-io] blvm-consensus path in Cargo.toml")?;
+    Ok(manifest_dir.join(path))
+}
+
+/// Modification time and size of every watched file.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct TreeSnapshot(BTreeMap<PathBuf, (SystemTime, u64)>);
+
+impl TreeSnapshot {
+    fn take(dir: &Path) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let entries = std::fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read {}", dir.display()))?;
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    if name != "target" && !name.starts_with('.') {
+                        pending.push(path);
+                    }
+                } else if name.ends_with(".rs") || name == "Cargo.toml" {
+                    let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.insert(path, (mtime, meta.len()));
+                }
+            }
+        }
+        Ok(Self(files))
+    }
+
+    /// Files added, removed or modified since `older`.
+    fn changed_since(&self, older: &TreeSnapshot) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .0
+            .iter()
+            .filter(|(path, stamp)| older.0.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            older
+                .0
+                .keys()
+                .filter(|path| !self.0.contains_key(*path))
+                .cloned(),
+        );
+        changed
+    }
+}
+
+/// Outcome of one build + bench + test pass.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatchRun {
+    pub build_ok: bool,
+    /// Criterion benchmark id -> mean time (ns)
+    pub bench_means: BTreeMap<String, f64>,
+    /// Test target -> (passed, duration seconds)
+    pub tests: BTreeMap<String, (bool, f64)>,
+}
+
+fn cargo(subcommand: &str, features: &[String]) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.arg(subcommand)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if !features.is_empty() {
+        cmd.arg("--features").arg(features.join(","));
+    }
+    cmd
+}
+
+/// Build, then run the configured benches and tests once.
+pub fn run_once(config: &WatchConfig) -> Result<WatchRun> {
+    let mut run = WatchRun::default();
+    let mut build = cargo("bench", &config.features);
+    build.arg("--no-run");
+    for bench in &config.benches {
+        build.arg("--bench").arg(bench);
+    }
+    run.build_ok = build.status().context("Failed to build benches")?.success();
+    if !run.build_ok {
+        return Ok(run);
+    }
+
+    let started = SystemTime::now();
+    for bench in &config.benches {
+        let status = cargo("bench", &config.features)
+            .arg("--bench")
+            .arg(bench)
+            .arg("--")
+            .arg("--noplot")
+            .status()
+            .context("Failed to run cargo bench")?;
+        if !status.success() {
+            eprintln!("⚠️  Bench {} failed ({:?})", bench, status.code());
+        }
+    }
+    let criterion_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/criterion");
+    run.bench_means = criterion_means(&criterion_dir, started)?;
+
+    for test in &config.tests {
+        let start = Instant::now();
+        let status = cargo("test", &config.features)
+            .arg("--release")
+            .arg("--test")
+            .arg(test)
+            .status()
+            .context("Failed to run cargo test")?;
+        run.tests.insert(
+            test.clone(),
+            (status.success(), start.elapsed().as_secs_f64()),
+        );
+    }
+    Ok(run)
+}
+
+/// Mean estimates Criterion wrote at or after `since` (`<id>/new/estimates.json`).
+pub(crate) fn criterion_means(
+    criterion_dir: &Path,
+    since: SystemTime,
+) -> Result<BTreeMap<String, f64>> {
+    let mut means = BTreeMap::new();
+    if !criterion_dir.is_dir() {
+        return Ok(means);
+    }
+    let mut pending = vec![criterion_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)?.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.file_name().is_none_or(|n| n != "estimates.json")
+                || path
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .is_none_or(|n| n != "new")
+            {
+                continue;
+            }
+            let fresh = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t >= since);
+            if !fresh {
+                continue;
+            }
+            let estimates: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            let Some(mean) = estimates["mean"]["point_estimate"].as_f64() else {
+                continue;
+            };
+            // target/criterion/<group>/<bench>/new/estimates.json -> "<group>/<bench>"
+            let id_dir = path
+                .parent()
+                .and_then(Path::parent)
+                .unwrap_or(criterion_dir);
+            let id = id_dir
+                .strip_prefix(criterion_dir)
+                .unwrap_or(id_dir)
+                .to_string_lossy()
+                .into_owned();
+            means.insert(id, mean);
+        }
+    }
+    Ok(means)
+}
+
+/// `1234567.0` -> `1.23 ms`
+pub fn format_ns(ns: f64) -> String {
+    if ns >= 1e9 {
+        format!("{:.2} s", ns / 1e9)
+    } else if ns >= 1e6 {
+        format!("{:.2} ms", ns / 1e6)
+    } else if ns >= 1e3 {
+        format!("{:.2} µs", ns / 1e3)
+    } else {
+        format!("{:.0} ns", ns)
+    }
+}
+
+/// `current` against `previous` (absolute numbers only on the first run).
+pub fn render_delta(previous: Option<&WatchRun>, current: &WatchRun) -> String {
+    let mut out = String::new();
+    if !current.build_ok {
+        let _ = writeln!(out, "❌ Build failed - waiting for the next change");
+        return out;
+    }
+    for (id, &mean) in &current.bench_means {
+        let before = previous.and_then(|p| p.bench_means.get(id));
+        match before {
+            Some(&before) if before > 0.0 => {
+                let pct = (mean - before) / before * 100.0;
+                let marker = if pct.abs() < NOISE_PCT {
+                    "~"
+                } else if pct < 0.0 {
+                    "🟢"
+                } else {
+                    "🔴"
+                };
+                let _ = writeln!(
+                    out,
+                    "   {} {:<40} {} -> {} ({:+.1}%)",
+                    marker,
+                    id,
+                    format_ns(before),
+                    format_ns(mean),
+                    pct
+                );
+            }
+            _ => {
+                let _ = writeln!(out, "   • {:<40} {}", id, format_ns(mean));
+            }
+        }
+    }
+    for (name, &(passed, secs)) in &current.tests {
+        let was = previous.and_then(|p| p.tests.get(name)).map(|&(p, _)| p);
+        let status = match (was, passed) {
+            (Some(false), true) => "✅ now passing",
+            (Some(true), false) => "❌ now failing",
+            (_, true) => "✅ passed",
+            (_, false) => "❌ failed",
+        };
+        let _ = writeln!(out, "   {} test {} ({:.1}s)", status, name, secs);
+    }
+    out
+}
+
+/// Run once, then again after every settled change to `config.consensus_dir`, until `cancel`.
+pub fn watch(config: &WatchConfig, cancel: &CancellationToken) -> Result<()> {
+    if config.benches.is_empty() && config.tests.is_empty() {
+        anyhow::bail!("Nothing to watch: select at least one bench or test");
+    }
+    println!(
+        "👀 Watching {} ({} bench(es), {} test(s))",
+        config.consensus_dir.display(),
+        config.benches.len(),
+        config.tests.len()
+    );
+    let mut seen = TreeSnapshot::take(&config.consensus_dir)?;
+    let mut previous: Option<WatchRun> = None;
+    loop {
+        let run = run_once(config)?;
+        print!("{}", render_delta(previous.as_ref(), &run));
+        if run.build_ok {
+            previous = Some(run);
+        }
+
+        // Wait for a change, then for the tree to go quiet
+        let mut changed = Vec::new();
+        let mut last_change = Instant::now();
+        loop {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            std::thread::sleep(config.poll_interval);
+            let now = TreeSnapshot::take(&config.consensus_dir)?;
+            let delta = now.changed_since(&seen);
+            if !delta.is_empty() {
+                changed.extend(delta);
+                last_change = Instant::now();
+                seen = now;
+            } else if !changed.is_empty() && last_change.elapsed() >= config.settle {
+                break;
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        println!(
+            "\n🔄 {} file(s) changed (first: {}) - rebuilding",
+            changed.len(),
+            changed[0].display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_detects_edits_and_ignores_target() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "fn a() {}").unwrap();
+        let before = TreeSnapshot::take(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("target/out.rs"), "x").unwrap();
+        std::fs::write(dir.path().join("src/notes.txt"), "x").unwrap();
+        assert!(TreeSnapshot::take(dir.path())
+            .unwrap()
+            .changed_since(&before)
+            .is_empty());
+
+        std::fs::write(dir.path().join("src/lib.rs"), "fn a() { 1; }").unwrap();
+        std::fs::write(dir.path().join("src/new.rs"), "").unwrap();
+        let changed = TreeSnapshot::take(dir.path())
+            .unwrap()
+            .changed_since(&before);
+        assert_eq!(changed.len(), 2);
+    }
+
+    #[test]
+    fn delta_against_previous_run() {
+        let mut first = WatchRun {
+            build_ok: true,
+            ..Default::default()
+        };
+        first
+            .bench_means
+            .insert("connect_block/100tx".into(), 2_000_000.0);
+        first.tests.insert("integration".into(), (false, 3.0));
+        let mut second = first.clone();
+        second
+            .bench_means
+            .insert("connect_block/100tx".into(), 1_500_000.0);
+        second.tests.insert("integration".into(), (true, 2.5));
+
+        let out = render_delta(Some(&first), &second);
+        assert!(out.contains("2.00 ms -> 1.50 ms (-25.0%)"), "{}", out);
+        assert!(out.contains("now passing"), "{}", out);
+        assert!(render_delta(None, &first).contains("• connect_block/100tx"));
+    }
+}
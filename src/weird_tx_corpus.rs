@@ -0,0 +1,545 @@
+//! Corpus of weird-but-valid historical transactions
+//!
+//! Mainnet carries transactions that only validate because of quirks Core has to preserve: the
+//! SIGHASH_SINGLE "hash of one" bug, CHECKMULTISIG dummies other than OP_0 (before BIP147),
+//! signatures that are not strict DER (before BIP66), zero-value outputs and OP_RETURN outputs
+//! larger than the relay limit. A regression in any of these forks BLVM off the chain, yet each
+//! is a handful of transactions among hundreds of millions.
+//!
+//! [`extract_corpus`] finds examples of each [`WeirdKind`] in a Core data dir (blocks plus undo
+//! data for the prevouts) and keeps a few per kind with everything needed to verify them in
+//! isolation; the result is saved as JSON ([`WeirdTxCorpus::save`]) so later runs need no
+//! chain data. [`verify_corpus`] runs every input through BLVM and, for legacy inputs,
+//! `libbitcoinconsensus`, with the script flags of the block the transaction was mined in.
+
+use anyhow::{Context, Result};
+use bitcoinconsensus::{verify_with_flags, VERIFY_ALL_PRE_TAPROOT};
+use blvm_protocol::script::{verify_script_with_context_full, SigVersion};
+use blvm_protocol::segwit::Witness;
+use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+use blvm_protocol::serialization::transaction::serialize_transaction;
+use blvm_protocol::types::{Network, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::block_file_reader::BlockFileReader;
+use crate::cancel::CancellationToken;
+use crate::rev_file_reader::RevFileReader;
+use crate::script_resources::{is_p2sh, last_push, next_op};
+use crate::sort_merge::verify::get_script_flags;
+use crate::wire::{display_hex, sha256d};
+
+/// Format version written to the corpus file.
+pub const CORPUS_VERSION: u32 = 1;
+/// BIP66 (strict DER) activation height on mainnet.
+const BIP66_HEIGHT: u64 = 363_725;
+/// BIP147 (NULLDUMMY) activates with SegWit on mainnet.
+const BIP147_HEIGHT: u64 = 481_824;
+/// Largest OP_RETURN scriptPubKey relayed by default (`MAX_OP_RETURN_RELAY`).
+const MAX_OP_RETURN_RELAY: usize = 83;
+
+const OP_0: u8 = 0x00;
+const OP_RETURN: u8 = 0x6a;
+const OP_CHECKMULTISIG: u8 = 0xae;
+const SIGHASH_SINGLE: u8 = 0x03;
+
+/// Quirk a corpus transaction exercises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WeirdKind {
+    /// SIGHASH_SINGLE signature on an input with no matching output (signs the constant 1)
+    SighashSingleBug,
+    /// CHECKMULTISIG dummy element that is not OP_0
+    NonNullMultisigDummy,
+    /// Signature that fails BIP66 strict DER encoding
+    NonDerSignature,
+    /// Spendable (non-OP_RETURN) output worth 0 satoshis
+    ZeroValueOutput,
+    /// OP_RETURN output above the default relay limit
+    LargeOpReturn,
+}
+
+impl WeirdKind {
+    pub const ALL: [WeirdKind; 5] = [
+        WeirdKind::SighashSingleBug,
+        WeirdKind::NonNullMultisigDummy,
+        WeirdKind::NonDerSignature,
+        WeirdKind::ZeroValueOutput,
+        WeirdKind::LargeOpReturn,
+    ];
+}
+
+/// One transaction, with the prevouts and witnesses needed to verify it on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusEntry {
+    pub kind: WeirdKind,
+    pub height: u64,
+    /// Display-order txid
+    pub txid: String,
+    /// Legacy (non-witness) serialization
+    pub tx_hex: String,
+    /// Witness stack per input, hex elements (empty stacks for legacy inputs)
+    pub witnesses: Vec<Vec<String>>,
+    /// `(value, scriptPubKey hex)` of the output each input spends
+    pub prevouts: Vec<(u64, String)>,
+    /// Whether every input should verify under the flags of `height`
+    pub expected_valid: bool,
+}
+
+/// The saved corpus.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeirdTxCorpus {
+    pub version: u32,
+    pub entries: Vec<CorpusEntry>,
+}
+
+impl WeirdTxCorpus {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let corpus: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        anyhow::ensure!(
+            corpus.version == CORPUS_VERSION,
+            "{} is corpus version {}, expected {}",
+            path.display(),
+            corpus.version,
+            CORPUS_VERSION
+        );
+        Ok(corpus)
+    }
+
+    /// Write to `path` ([`write_json_atomic`](crate::utils::write_json_atomic)).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::utils::write_json_atomic(path, self)
+    }
+
+    /// Entries per kind.
+    pub fn counts(&self) -> BTreeMap<WeirdKind, usize> {
+        let mut counts = BTreeMap::new();
+        for entry in &self.entries {
+            *counts.entry(entry.kind).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// BIP66 `IsValidSignatureEncoding` (signature including its sighash byte).
+pub fn is_strict_der(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    !(len_s > 1 && sig[len_r + 6] == 0 && sig[len_r + 7] & 0x80 == 0)
+}
+
+/// Pushes of a scriptSig that look like signatures (DER sequence tag, plausible length).
+fn signature_pushes(script_sig: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut pc = 0;
+    std::iter::from_fn(move || loop {
+        let op = next_op(script_sig, &mut pc)?.ok()?;
+        if let Some(data) = op.push {
+            if data.len() >= 9 && data.len() <= 80 && data[0] == 0x30 {
+                return Some(data);
+            }
+        }
+    })
+}
+
+/// Input-side quirks of input `input_index` spending `prevout_spk` in a tx with `n_outputs`.
+pub fn classify_input(
+    script_sig: &[u8],
+    prevout_spk: &[u8],
+    input_index: usize,
+    n_outputs: usize,
+    height: u64,
+) -> Vec<WeirdKind> {
+    let mut kinds = Vec::new();
+    if input_index >= n_outputs
+        && signature_pushes(script_sig).any(|sig| sig[sig.len() - 1] & 0x1f == SIGHASH_SINGLE)
+    {
+        kinds.push(WeirdKind::SighashSingleBug);
+    }
+    if height < BIP66_HEIGHT && signature_pushes(script_sig).any(|sig| !is_strict_der(sig)) {
+        kinds.push(WeirdKind::NonDerSignature);
+    }
+    if height < BIP147_HEIGHT {
+        let executed = if is_p2sh(prevout_spk) {
+            last_push(script_sig).unwrap_or_default()
+        } else {
+            prevout_spk
+        };
+        let mut pc = 0;
+        let dummy = next_op(script_sig, &mut pc).and_then(|op| op.ok());
+        if executed.last() == Some(&OP_CHECKMULTISIG) && dummy.is_some_and(|op| op.opcode != OP_0) {
+            kinds.push(WeirdKind::NonNullMultisigDummy);
+        }
+    }
+    kinds
+}
+
+/// Output-side quirks of a transaction's `(value, scriptPubKey)` outputs.
+pub fn classify_outputs<'a>(outputs: impl IntoIterator<Item = (i64, &'a [u8])>) -> Vec<WeirdKind> {
+    let mut kinds = Vec::new();
+    for (value, spk) in outputs {
+        let is_op_return = spk.first() == Some(&OP_RETURN);
+        if value == 0 && !is_op_return && !kinds.contains(&WeirdKind::ZeroValueOutput) {
+            kinds.push(WeirdKind::ZeroValueOutput);
+        }
+        if is_op_return
+            && spk.len() > MAX_OP_RETURN_RELAY
+            && !kinds.contains(&WeirdKind::LargeOpReturn)
+        {
+            kinds.push(WeirdKind::LargeOpReturn);
+        }
+    }
+    kinds
+}
+
+fn txid_hex(tx: &Transaction) -> String {
+    display_hex(&sha256d(&serialize_transaction(tx)))
+}
+
+/// Where and how much to search in [`extract_corpus`].
+#[derive(Debug, Clone)]
+pub struct ExtractConfig {
+    /// Inclusive height ranges, scanned in order
+    pub ranges: Vec<(u64, u64)>,
+    /// Transactions kept per kind; scanning stops once every kind is full
+    pub per_kind: usize,
+}
+
+impl Default for ExtractConfig {
+    fn default() -> Self {
+        Self {
+            // 2012-2014 (pre-BIP66 / BIP147 quirks), then the large OP_RETURN era
+            ranges: vec![(180_000, 300_000), (780_000, 800_000)],
+            per_kind: 3,
+        }
+    }
+}
+
+/// Scan `config.ranges` of a Core data dir (needs `blocks/index` and `rev*.dat`) for weird
+/// transactions. Each transaction is stored once, under the first kind it matched that still had
+/// room.
+pub fn extract_corpus(
+    reader: &BlockFileReader,
+    config: &ExtractConfig,
+    cancel: &CancellationToken,
+) -> Result<WeirdTxCorpus> {
+    let undo = RevFileReader::for_block_reader(reader);
+    let mut corpus = WeirdTxCorpus {
+        version: CORPUS_VERSION,
+        entries: Vec::new(),
+    };
+    let mut counts: BTreeMap<WeirdKind, usize> = BTreeMap::new();
+    let full = |counts: &BTreeMap<WeirdKind, usize>| {
+        WeirdKind::ALL
+            .iter()
+            .all(|k| counts.get(k).copied().unwrap_or(0) >= config.per_kind)
+    };
+
+    'ranges: for &(start, end) in &config.ranges {
+        for height in start..=end {
+            crate::cancel::check(cancel, || {
+                format!("weird tx extraction at block {}", height)
+            })?;
+            if full(&counts) {
+                break 'ranges;
+            }
+            let raw = reader.read_block_by_height(height)?;
+            let (block, witnesses) = deserialize_block_with_witnesses(&raw)
+                .with_context(|| format!("Failed to deserialize block {}", height))?;
+            let block_undo = undo.read_undo_by_height(height)?;
+
+            for (tx_idx, tx) in block.transactions.iter().enumerate().skip(1) {
+                let prevouts: Vec<(u64, String)> = (0..tx.inputs.len())
+                    .map(|i| {
+                        block_undo
+                            .prevout(tx_idx, i)
+                            .map(|p| (p.value, hex::encode(&p.script_pubkey)))
+                            .with_context(|| {
+                                format!(
+                                    "No undo entry for block {} tx {} input {}",
+                                    height, tx_idx, i
+                                )
+                            })
+                    })
+                    .collect::<Result<_>>()?;
+                let mut kinds =
+                    classify_outputs(tx.outputs.iter().map(|o| (o.value, &o.script_pubkey[..])));
+                for (i, input) in tx.inputs.iter().enumerate() {
+                    let spk = hex::decode(&prevouts[i].1)?;
+                    for kind in classify_input(&input.script_sig, &spk, i, tx.outputs.len(), height)
+                    {
+                        if !kinds.contains(&kind) {
+                            kinds.push(kind);
+                        }
+                    }
+                }
+                let Some(kind) = kinds
+                    .into_iter()
+                    .find(|k| counts.get(k).copied().unwrap_or(0) < config.per_kind)
+                else {
+                    continue;
+                };
+                *counts.entry(kind).or_default() += 1;
+                let tx_witnesses = witnesses.get(tx_idx).map(Vec::as_slice).unwrap_or_default();
+                corpus.entries.push(CorpusEntry {
+                    kind,
+                    height,
+                    txid: txid_hex(tx),
+                    tx_hex: hex::encode(serialize_transaction(tx)),
+                    witnesses: (0..tx.inputs.len())
+                        .map(|i| {
+                            tx_witnesses
+                                .get(i)
+                                .map(|w| w.iter().map(hex::encode).collect())
+                                .unwrap_or_default()
+                        })
+                        .collect(),
+                    prevouts,
+                    expected_valid: true,
+                });
+                println!(
+                    "   🧪 {:?} at {}: {}",
+                    kind,
+                    height,
+                    corpus.entries.last().unwrap().txid
+                );
+            }
+        }
+    }
+
+    for kind in WeirdKind::ALL {
+        let found = counts.get(&kind).copied().unwrap_or(0);
+        if found < config.per_kind {
+            eprintln!(
+                "⚠️  Only {}/{} {:?} transactions in the scanned ranges",
+                found, config.per_kind, kind
+            );
+        }
+    }
+    Ok(corpus)
+}
+
+/// Verdict for one corpus input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorpusVerdict {
+    pub kind: WeirdKind,
+    pub txid: String,
+    pub height: u64,
+    pub input_index: usize,
+    pub expected_valid: bool,
+    pub blvm: bool,
+    /// `None` for witness / taproot inputs, which `libbitcoinconsensus` is not given
+    pub core: Option<bool>,
+}
+
+impl CorpusVerdict {
+    pub fn passed(&self) -> bool {
+        self.blvm == self.expected_valid && self.core.is_none_or(|core| core == self.expected_valid)
+    }
+}
+
+/// Decode an entry's legacy serialization, wrapped as the only transaction of an empty-header
+/// block so the block deserializer can parse it.
+fn decode_entry_tx(entry: &CorpusEntry) -> Result<Transaction> {
+    let mut block = vec![0u8; 80];
+    block.push(1);
+    block.extend(hex::decode(&entry.tx_hex).context("Invalid tx hex")?);
+    let (block, _) = deserialize_block_with_witnesses(&block)
+        .with_context(|| format!("Failed to deserialize tx {}", entry.txid))?;
+    block
+        .transactions
+        .into_iter()
+        .next()
+        .context("Decoded block has no transaction")
+}
+
+/// Verify every input of `entry` with BLVM and (legacy inputs) `libbitcoinconsensus`.
+pub fn verify_entry(entry: &CorpusEntry) -> Result<Vec<CorpusVerdict>> {
+    let tx = decode_entry_tx(entry)?;
+    anyhow::ensure!(
+        entry.prevouts.len() == tx.inputs.len() && entry.witnesses.len() == tx.inputs.len(),
+        "{}: {} prevouts / {} witnesses for {} inputs",
+        entry.txid,
+        entry.prevouts.len(),
+        entry.witnesses.len(),
+        tx.inputs.len()
+    );
+    let prevout_values: Vec<i64> = entry.prevouts.iter().map(|(v, _)| *v as i64).collect();
+    let spks: Vec<Vec<u8>> = entry
+        .prevouts
+        .iter()
+        .map(|(_, spk)| hex::decode(spk))
+        .collect::<Result<_, _>>()?;
+    let prevout_script_pubkeys: Vec<&[u8]> = spks.iter().map(Vec::as_slice).collect();
+    let witnesses: Vec<Witness> = entry
+        .witnesses
+        .iter()
+        .map(|stack| stack.iter().map(hex::decode).collect::<Result<_, _>>())
+        .collect::<Result<_, _>>()?;
+    let flags = get_script_flags(entry.height, Network::Mainnet);
+    let tx_bytes = serialize_transaction(&tx);
+
+    let mut verdicts = Vec::with_capacity(tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let witness = (!witnesses[i].is_empty()).then_some(&witnesses[i]);
+        let blvm = verify_script_with_context_full(
+            &input.script_sig,
+            prevout_script_pubkeys[i],
+            witness,
+            flags,
+            &tx,
+            i,
+            &prevout_values,
+            &prevout_script_pubkeys,
+            Some(entry.height),
+            None,
+            Network::Mainnet,
+            SigVersion::Base,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap_or(false);
+        let core = witness.is_none().then(|| {
+            verify_with_flags(
+                prevout_script_pubkeys[i],
+                entry.prevouts[i].0,
+                &tx_bytes,
+                None,
+                i,
+                flags & VERIFY_ALL_PRE_TAPROOT,
+            )
+            .is_ok()
+        });
+        verdicts.push(CorpusVerdict {
+            kind: entry.kind,
+            txid: entry.txid.clone(),
+            height: entry.height,
+            input_index: i,
+            expected_valid: entry.expected_valid,
+            blvm,
+            core,
+        });
+    }
+    Ok(verdicts)
+}
+
+/// Verify the whole corpus; failures are the verdicts with `!passed()`.
+pub fn verify_corpus(corpus: &WeirdTxCorpus) -> Result<Vec<CorpusVerdict>> {
+    let mut verdicts = Vec::new();
+    for entry in &corpus.entries {
+        verdicts.extend(verify_entry(entry)?);
+    }
+    Ok(verdicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 71-byte low-S DER signature with `sighash` appended.
+    fn der_sig(sighash: u8) -> Vec<u8> {
+        let mut sig = vec![0x30, 0x44, 0x02, 0x20];
+        sig.extend([0x11; 32]);
+        sig.extend([0x02, 0x20]);
+        sig.extend([0x22; 32]);
+        sig.push(sighash);
+        sig
+    }
+
+    fn push(data: &[u8]) -> Vec<u8> {
+        let mut s = vec![data.len() as u8];
+        s.extend_from_slice(data);
+        s
+    }
+
+    #[test]
+    fn strict_der_rules() {
+        assert!(is_strict_der(&der_sig(0x01)));
+        // Negative R (high bit set without a 0x00 pad)
+        let mut negative_r = der_sig(0x01);
+        negative_r[4] = 0x81;
+        assert!(!is_strict_der(&negative_r));
+        // Superfluous 0x00 padding on R
+        let mut padded = der_sig(0x01);
+        padded[4] = 0x00;
+        assert!(!is_strict_der(&padded));
+        // Wrong total length byte
+        let mut bad_len = der_sig(0x01);
+        bad_len[1] = 0x45;
+        assert!(!is_strict_der(&bad_len));
+    }
+
+    #[test]
+    fn classifies_quirks() {
+        let p2pkh = {
+            let mut s = vec![0x76, 0xa9, 0x14];
+            s.extend([0u8; 20]);
+            s.extend([0x88, 0xac]);
+            s
+        };
+        let mut sig_single = push(&der_sig(SIGHASH_SINGLE));
+        sig_single.extend(push(&[0x02; 33]));
+        assert_eq!(
+            classify_input(&sig_single, &p2pkh, 1, 1, 250_000),
+            [WeirdKind::SighashSingleBug]
+        );
+        assert!(classify_input(&sig_single, &p2pkh, 0, 1, 250_000).is_empty());
+
+        let mut padded = der_sig(0x01);
+        padded[4] = 0x00;
+        let non_der = push(&padded);
+        assert_eq!(
+            classify_input(&non_der, &p2pkh, 0, 1, 250_000),
+            [WeirdKind::NonDerSignature]
+        );
+        assert!(classify_input(&non_der, &p2pkh, 0, 1, BIP66_HEIGHT).is_empty());
+
+        // 1-of-1 bare multisig with OP_1 as the dummy
+        let mut multisig = vec![0x51];
+        multisig.extend(push(&[0x02; 33]));
+        multisig.extend([0x51, OP_CHECKMULTISIG]);
+        let mut dummy_one = vec![0x51];
+        dummy_one.extend(push(&der_sig(0x01)));
+        assert_eq!(
+            classify_input(&dummy_one, &multisig, 0, 1, 300_000),
+            [WeirdKind::NonNullMultisigDummy]
+        );
+        let mut null_dummy = vec![OP_0];
+        null_dummy.extend(push(&der_sig(0x01)));
+        assert!(classify_input(&null_dummy, &multisig, 0, 1, 300_000).is_empty());
+
+        let big_op_return = [vec![OP_RETURN, 0x4c, 0x51], vec![0xab; 81]].concat();
+        assert_eq!(
+            classify_outputs([
+                (0, &p2pkh[..]),
+                (0, &big_op_return[..]),
+                (0, &[OP_RETURN][..])
+            ]),
+            [WeirdKind::ZeroValueOutput, WeirdKind::LargeOpReturn]
+        );
+        assert!(classify_outputs([(1000, &p2pkh[..]), (0, &[OP_RETURN, 0x00][..])]).is_empty());
+    }
+}
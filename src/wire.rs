@@ -0,0 +1,168 @@
+//! Hashing and serialization primitives for hand-built blocks and transactions
+//!
+//! Scenario builders, fixtures and sidecar formats serialize blocks and transactions themselves
+//! rather than through `blvm_protocol`, so what they produce does not depend on the code under
+//! test. These are the pieces they share, along with the CompactSize and `VARINT` readers the raw
+//! block, undo, snapshot and P2P parsers use.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+/// Double SHA-256 (txids, block hashes, merkle nodes), in internal byte order.
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+/// Hex of an internal-order hash in display (RPC) order.
+pub fn display_hex(hash: &[u8; 32]) -> String {
+    let mut display = *hash;
+    display.reverse();
+    hex::encode(display)
+}
+
+/// Append `n` as a CompactSize.
+pub fn write_compact_size(out: &mut Vec<u8>, n: usize) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend((n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend((n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend((n as u64).to_le_bytes());
+        }
+    }
+}
+
+/// Bytes [`write_compact_size`] uses for `n`.
+pub fn compact_size_len(n: usize) -> usize {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Read a CompactSize from a stream.
+pub fn read_compact_size_from(reader: &mut impl Read) -> Result<u64> {
+    let mut read = |buf: &mut [u8]| reader.read_exact(buf).context("truncated compact size");
+    let mut first = [0u8];
+    read(&mut first)?;
+    let width = match first[0] {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        n => return Ok(u64::from(n)),
+    };
+    let mut buf = [0u8; 8];
+    read(&mut buf[..width])?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Read a CompactSize at `data[*pos..]` and move `pos` past it.
+pub fn read_compact_size(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut rest = data.get(*pos..).context("truncated compact size")?;
+    let n = read_compact_size_from(&mut rest)?;
+    *pos = data.len() - rest.len();
+    Ok(n)
+}
+
+/// Read Core's `VARINT` (MSB base-128 with one added per continuation byte; coins, undo data
+/// and the block index use it) from a stream. Not a CompactSize.
+pub fn read_varint_from(reader: &mut impl Read) -> Result<u64> {
+    let mut n: u64 = 0;
+    loop {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte).context("truncated VARINT")?;
+        anyhow::ensure!(n <= u64::MAX >> 7, "VARINT overflows u64");
+        n = (n << 7) | u64::from(byte[0] & 0x7f);
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+        n = n.checked_add(1).context("VARINT overflows u64")?;
+    }
+}
+
+/// Read a `VARINT` at `data[*pos..]` and move `pos` past it.
+pub fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut rest = data.get(*pos..).context("truncated VARINT")?;
+    let n = read_varint_from(&mut rest)?;
+    *pos = data.len() - rest.len();
+    Ok(n)
+}
+
+/// Merkle root over internal-order txids (last hash of an odd level duplicated); all zeros for
+/// no txids.
+pub fn merkle_root(txids: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(*level.last().unwrap());
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| sha256d(&[pair[0], pair[1]].concat()))
+            .collect();
+    }
+    level.first().copied().unwrap_or([0; 32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkle_root_matches_genesis_and_pairs() {
+        let mut genesis_txid: [u8; 32] =
+            hex::decode("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        genesis_txid.reverse();
+        assert_eq!(merkle_root(&[genesis_txid]), genesis_txid);
+        assert_eq!(merkle_root(&[]), [0; 32]);
+
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        assert_eq!(merkle_root(&[a, b, b]), merkle_root(&[a, b, b, b]));
+        assert_ne!(merkle_root(&[a, b]), merkle_root(&[b, a]));
+    }
+
+    #[test]
+    fn compact_size_boundaries() {
+        for (n, expected) in [
+            (0xfc, vec![0xfc]),
+            (0xfd, vec![0xfd, 0xfd, 0x00]),
+            (0x1_0000, vec![0xfe, 0x00, 0x00, 0x01, 0x00]),
+        ] {
+            let mut out = Vec::new();
+            write_compact_size(&mut out, n);
+            assert_eq!(out, expected, "{:#x}", n);
+            assert_eq!(compact_size_len(n), out.len());
+            out.push(0xaa);
+            let mut pos = 0;
+            assert_eq!(read_compact_size(&out, &mut pos).unwrap(), n as u64);
+            assert_eq!(pos, out.len() - 1);
+        }
+        assert!(read_compact_size(&[0xfe, 0x00, 0x00], &mut 0).is_err());
+    }
+
+    #[test]
+    fn varint_offsets_continuation_bytes() {
+        let mut pos = 1;
+        assert_eq!(
+            read_varint(&[0xaa, 0xff, 0x7f, 0xaa], &mut pos).unwrap(),
+            16511
+        );
+        assert_eq!(pos, 3);
+        assert_eq!(read_varint(&[0x80, 0x00], &mut 0).unwrap(), 128);
+        assert!(read_varint(&[0x80], &mut 0).is_err());
+        assert!(read_varint(&[0xff; 11], &mut 0).is_err());
+    }
+}
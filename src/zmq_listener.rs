@@ -0,0 +1,487 @@
+//! Live differential validation from Core's ZMQ notifications
+//!
+//! A differential run stops at the tip it started from. [`run`] subscribes to a Core node's
+//! `rawblock` publisher (`-zmqpubrawblock=tcp://127.0.0.1:28332`) and validates every block with
+//! BLVM as soon as Core connects it, so after the historical sync the suite keeps following the
+//! chain tip and reports a divergence within seconds of the block arriving.
+//!
+//! Core only publishes blocks it has connected to its active chain, so Core's verdict is always
+//! `Valid`. The height and the coins each block spends come from the same node over RPC
+//! (`getblockheader`, `getblock <hash> 3`, Core 23+), so no UTXO set has to be carried forward
+//! from the historical run. Blocks go through the same [validation hooks](crate::validation_hooks)
+//! as a differential chunk: `divergence-artifacts` saves diverging blocks for `blvm-bench replay`,
+//! and a custom [`on_divergence`](crate::validation_hooks::ValidationHook::on_divergence) hook is
+//! the place to page someone. Hooks that need the UTXO set size or the whole set (`utxo-size`,
+//! `utxo-accounting`) are refused at startup, since only each block's spent coins are known.
+//!
+//! Every notification carries a per-topic sequence number. A gap means the publisher dropped
+//! messages (high-water mark, reconnect); blocks skipped that way are noticed by height and
+//! fetched over RPC, up to [`MAX_CATCH_UP`]. `rawtx` notifications, if subscribed, are only
+//! counted and gap-checked as a liveness signal between blocks: their prevouts may still be
+//! unconfirmed, so they are not validated here.
+
+use anyhow::{Context, Result};
+use blvm_protocol::block::block_validation_context_for_connect_ibd;
+use blvm_protocol::types::{BlockHeader, Network};
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+use crate::cancel::CancellationToken;
+use crate::consensus_compat::connect_block;
+use crate::differential::{CoreValidationResult, ValidationResult};
+use crate::micro_bench::MicroBlock;
+use crate::node_rpc_client::{NodeRpcClient, RpcConfig};
+use crate::prevout_blocks::{block_hash, spent_from_getblock, PrevoutBlock};
+use crate::validation_hooks::{BlockContext, BlockOutcome, DivergenceEvent, HookRegistry};
+use crate::wire::display_hex;
+
+/// Most missed blocks fetched over RPC after a gap; a longer outage needs a differential run.
+pub const MAX_CATCH_UP: u64 = 144;
+
+/// A ZMQ topic published by Core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    RawBlock,
+    RawTx,
+}
+
+impl Topic {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RawBlock => "rawblock",
+            Self::RawTx => "rawtx",
+        }
+    }
+
+    fn from_bytes(topic: &[u8]) -> Option<Self> {
+        match topic {
+            b"rawblock" => Some(Self::RawBlock),
+            b"rawtx" => Some(Self::RawTx),
+            _ => None,
+        }
+    }
+}
+
+/// One notification: `[topic, body, sequence (u32 LE)]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub topic: Topic,
+    /// Serialized block or transaction
+    pub body: Vec<u8>,
+    pub sequence: u32,
+}
+
+impl Notification {
+    pub fn parse<F: AsRef<[u8]>>(frames: &[F]) -> Result<Self> {
+        let [topic, body, sequence] = frames else {
+            anyhow::bail!("Expected 3 ZMQ frames, got {}", frames.len());
+        };
+        let topic = Topic::from_bytes(topic.as_ref()).with_context(|| {
+            format!(
+                "Unexpected ZMQ topic {:?}",
+                String::from_utf8_lossy(topic.as_ref())
+            )
+        })?;
+        let sequence: [u8; 4] = sequence
+            .as_ref()
+            .try_into()
+            .context("ZMQ sequence frame is not 4 bytes")?;
+        Ok(Self {
+            topic,
+            body: body.as_ref().to_vec(),
+            sequence: u32::from_le_bytes(sequence),
+        })
+    }
+}
+
+/// How a notification's sequence number relates to the previous one on its topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    First,
+    InOrder,
+    /// This many notifications were dropped in between
+    Gap(u32),
+    /// The number went backwards: Core restarted and counts from 0 again
+    Restarted,
+}
+
+/// Last sequence number seen per topic.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last: HashMap<Topic, u32>,
+}
+
+impl SequenceTracker {
+    pub fn observe(&mut self, topic: Topic, sequence: u32) -> Sequence {
+        let Some(previous) = self.last.insert(topic, sequence) else {
+            return Sequence::First;
+        };
+        match sequence.wrapping_sub(previous) {
+            1 => Sequence::InOrder,
+            // More than half the range ahead is a counter that went backwards
+            step if step == 0 || step > u32::MAX / 2 => Sequence::Restarted,
+            step => Sequence::Gap(step - 1),
+        }
+    }
+}
+
+/// Where to subscribe and what to run on each block.
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    /// `rawblock` publisher, e.g. `tcp://127.0.0.1:28332`
+    pub block_endpoint: String,
+    /// `rawtx` publisher (may be the same endpoint); `None` to skip transactions
+    pub tx_endpoint: Option<String>,
+    pub rpc: RpcConfig,
+    pub hooks: HookRegistry,
+    /// Return an error at the first divergence instead of continuing
+    pub stop_on_divergence: bool,
+}
+
+impl LiveConfig {
+    pub fn new(block_endpoint: impl Into<String>, rpc: RpcConfig) -> Self {
+        Self {
+            block_endpoint: block_endpoint.into(),
+            tx_endpoint: None,
+            rpc,
+            hooks: HookRegistry::default(),
+            stop_on_divergence: false,
+        }
+    }
+
+    /// `BITCOIN_ZMQ_RAWBLOCK` (default `tcp://127.0.0.1:28332`), `BITCOIN_ZMQ_RAWTX`, the RPC
+    /// settings from `BITCOIN_RPC_*` and the hooks from `BLVM_VALIDATION_HOOKS`.
+    pub fn from_env() -> Result<Self> {
+        let block_endpoint = std::env::var("BITCOIN_ZMQ_RAWBLOCK")
+            .unwrap_or_else(|_| "tcp://127.0.0.1:28332".to_string());
+        let mut config =
+            Self::new(block_endpoint, RpcConfig::from_env()).with_hooks(HookRegistry::from_env()?);
+        config.tx_endpoint = std::env::var("BITCOIN_ZMQ_RAWTX")
+            .ok()
+            .filter(|e| !e.is_empty());
+        Ok(config)
+    }
+
+    pub fn with_tx_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.tx_endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: HookRegistry) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn with_stop_on_divergence(mut self, stop: bool) -> Self {
+        self.stop_on_divergence = stop;
+        self
+    }
+}
+
+/// What a live session has seen so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveStats {
+    pub blocks: usize,
+    pub matched: usize,
+    /// `(height, blvm_result, core_result)`, as in `ChunkResult::divergences`
+    pub divergences: Vec<(u64, String, String)>,
+    /// Blocks fetched over RPC because their notification was missed
+    pub caught_up: usize,
+    /// Notifications at or below the last validated height
+    pub reorgs: usize,
+    pub txs: usize,
+    /// Notifications the sequence numbers say were dropped
+    pub missed_notifications: u64,
+    pub tip_height: Option<u64>,
+}
+
+/// Validates blocks at the tip against the coins Core reports they spend.
+pub struct LiveValidator {
+    client: NodeRpcClient,
+    network: Network,
+    hooks: HookRegistry,
+    stop_on_divergence: bool,
+    stats: LiveStats,
+}
+
+impl LiveValidator {
+    /// Fails for hooks that need the UTXO set size or the whole set: only each block's spent
+    /// coins are known here.
+    pub fn new(client: NodeRpcClient, network: Network, config: &LiveConfig) -> Result<Self> {
+        let needing = config.hooks.needing_utxo_set();
+        anyhow::ensure!(
+            needing.is_empty(),
+            "Validation hooks {} need the UTXO set, which live validation does not keep",
+            needing.join(", ")
+        );
+        Ok(Self {
+            client,
+            network,
+            hooks: config.hooks.clone(),
+            stop_on_divergence: config.stop_on_divergence,
+            stats: LiveStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> &LiveStats {
+        &self.stats
+    }
+
+    /// Validate a block from a `rawblock` notification, first catching up on any blocks between
+    /// the last validated height and this one.
+    pub async fn handle_block(&mut self, block_bytes: Vec<u8>) -> Result<()> {
+        let hash = display_hex(&block_hash(&block_bytes)?);
+        let header = self.client.getblockheader(&hash, true).await?;
+        let height = header["height"]
+            .as_u64()
+            .with_context(|| format!("getblockheader {} has no height", hash))?;
+
+        match self.stats.tip_height {
+            Some(tip) if height <= tip => {
+                self.stats.reorgs += 1;
+                warn!(
+                    "🔀 Block {} at height {} replaces validated tip {} (reorg)",
+                    hash, height, tip
+                );
+            }
+            Some(tip) if height > tip + 1 => {
+                let missed = height - tip - 1;
+                if missed > MAX_CATCH_UP {
+                    warn!(
+                        "⚠️  {} blocks missed before {}, validating only the last {}",
+                        missed, height, MAX_CATCH_UP
+                    );
+                }
+                for missed_height in height.saturating_sub(MAX_CATCH_UP).max(tip + 1)..height {
+                    let bytes = self.client.getblock_bytes_at_height(missed_height).await?;
+                    self.validate(missed_height, bytes).await?;
+                    self.stats.caught_up += 1;
+                }
+            }
+            _ => {}
+        }
+        self.validate(height, block_bytes).await
+    }
+
+    /// Validate the block at `height` with BLVM and record the result. Core has the block on its
+    /// active chain, so its verdict is `Valid`.
+    pub async fn validate(&mut self, height: u64, block_bytes: Vec<u8>) -> Result<()> {
+        let hash = display_hex(&block_hash(&block_bytes)?);
+        let spent = spent_from_getblock(&self.client.getblock(&hash, 3).await?)?;
+        let micro = MicroBlock::prepare(&PrevoutBlock::new(height, block_bytes, spent)?)?;
+
+        // The block's prevouts are all the listener has, so hooks get no set or set sizes here
+        let hook_spent_coins = if self.hooks.is_empty() {
+            None
+        } else {
+            self.hooks.pre_block(&BlockContext {
+                height,
+                block: &micro.block,
+                block_bytes: &micro.block_bytes,
+                witnesses: &micro.witnesses,
+                spent_coins: &micro.utxo_set,
+                utxo_set: None,
+            })?;
+            Some(micro.utxo_set.clone())
+        };
+
+        let ctx = block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            micro.block.header.timestamp,
+            self.network,
+        );
+        let started = Instant::now();
+        let connected = connect_block(&micro.block, &micro.witnesses, micro.utxo_set, height, &ctx);
+        let blvm_duration = started.elapsed();
+        let blvm = match connected {
+            Ok((blvm_protocol::types::ValidationResult::Valid, _)) => ValidationResult::Valid,
+            Ok((blvm_protocol::types::ValidationResult::Invalid(msg), _)) => {
+                ValidationResult::Invalid(msg)
+            }
+            Err(e) => ValidationResult::Invalid(format!("{:?}", e)),
+        };
+        let core = CoreValidationResult::Valid;
+
+        if let Some(spent_coins) = &hook_spent_coins {
+            self.hooks.post_block(
+                &BlockContext {
+                    height,
+                    block: &micro.block,
+                    block_bytes: &micro.block_bytes,
+                    witnesses: &micro.witnesses,
+                    spent_coins,
+                    utxo_set: None,
+                },
+                &BlockOutcome {
+                    blvm: &blvm,
+                    core: &core,
+                    utxo_len_before: None,
+                    utxo_len_after: None,
+                    utxo_set_after: None,
+                    blvm_duration,
+                },
+            )?;
+        }
+
+        self.stats.blocks += 1;
+        self.stats.tip_height = Some(height);
+        match blvm {
+            ValidationResult::Valid => {
+                self.stats.matched += 1;
+                info!(
+                    "✅ Block {} ({}) valid, {} txs in {:.1}ms",
+                    height,
+                    hash,
+                    micro.block.transactions.len(),
+                    blvm_duration.as_secs_f64() * 1000.0
+                );
+            }
+            ValidationResult::Invalid(msg) => {
+                let blvm = format!("Invalid({})", msg);
+                self.hooks.on_divergence(&DivergenceEvent {
+                    height,
+                    block_bytes: &micro.block_bytes,
+                    blvm: &blvm,
+                    core: "Valid",
+                })?;
+                error!(
+                    "❌ DIVERGENCE at height {} ({}): BLVM={}, Core=Valid",
+                    height, hash, blvm
+                );
+                self.stats
+                    .divergences
+                    .push((height, blvm.clone(), "Valid".to_string()));
+                if self.stop_on_divergence {
+                    anyhow::bail!("BLVM rejected block {} ({}): {}", height, hash, blvm);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_tx(&mut self, tx_bytes: &[u8]) {
+        self.stats.txs += 1;
+        debug!("rawtx: {} bytes ({} seen)", tx_bytes.len(), self.stats.txs);
+    }
+}
+
+/// Subscribe to `config`'s endpoints and validate blocks until `cancel` fires (returning the
+/// stats so far) or, with `stop_on_divergence`, until BLVM rejects one.
+pub async fn run(config: LiveConfig, cancel: CancellationToken) -> Result<LiveStats> {
+    crate::progress::ensure_logging();
+    let client = NodeRpcClient::new(config.rpc.clone());
+    let network = crate::replay::parse_network(client.detect_network().await?.as_str())?;
+
+    let mut socket = SubSocket::new();
+    socket
+        .connect(&config.block_endpoint)
+        .await
+        .with_context(|| format!("Failed to connect to ZMQ {}", config.block_endpoint))?;
+    socket.subscribe(Topic::RawBlock.as_str()).await?;
+    if let Some(endpoint) = &config.tx_endpoint {
+        if *endpoint != config.block_endpoint {
+            socket
+                .connect(endpoint)
+                .await
+                .with_context(|| format!("Failed to connect to ZMQ {}", endpoint))?;
+        }
+        socket.subscribe(Topic::RawTx.as_str()).await?;
+    }
+
+    info!("📡 Live differential validation ({:?})", network);
+    info!("   rawblock: {}", config.block_endpoint);
+    if let Some(endpoint) = &config.tx_endpoint {
+        info!("   rawtx: {}", endpoint);
+    }
+    if !config.hooks.is_empty() {
+        info!("   Hooks: {:?}", config.hooks);
+    }
+
+    let mut validator = LiveValidator::new(client, network, &config)?;
+    let mut sequences = SequenceTracker::default();
+    loop {
+        let message = tokio::select! {
+            _ = cancel.cancelled() => break,
+            message = socket.recv() => message.context("ZMQ receive failed")?,
+        };
+        let notification = match Notification::parse(&message.into_vec()) {
+            Ok(notification) => notification,
+            Err(e) => {
+                warn!("⚠️  Ignoring ZMQ message: {:#}", e);
+                continue;
+            }
+        };
+        match sequences.observe(notification.topic, notification.sequence) {
+            Sequence::Gap(missed) => {
+                validator.stats.missed_notifications += u64::from(missed);
+                warn!(
+                    "⚠️  Missed {} {} notification(s) before #{}",
+                    missed,
+                    notification.topic.as_str(),
+                    notification.sequence
+                );
+            }
+            Sequence::Restarted => warn!(
+                "⚠️  {} sequence restarted at #{} (node restart?)",
+                notification.topic.as_str(),
+                notification.sequence
+            ),
+            Sequence::First | Sequence::InOrder => {}
+        }
+        match notification.topic {
+            Topic::RawBlock => validator.handle_block(notification.body).await?,
+            Topic::RawTx => validator.handle_tx(&notification.body),
+        }
+    }
+
+    let _ = socket.close().await;
+    let stats = validator.stats;
+    info!(
+        "📡 Live validation stopped: {}/{} blocks matched, {} divergences, {} txs seen",
+        stats.matched,
+        stats.blocks,
+        stats.divergences.len(),
+        stats.txs
+    );
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_core_notifications() {
+        let frames: Vec<Vec<u8>> = vec![b"rawblock".to_vec(), vec![1, 2, 3], vec![7, 1, 0, 0]];
+        let notification = Notification::parse(&frames).unwrap();
+        assert_eq!(notification.topic, Topic::RawBlock);
+        assert_eq!(notification.body, vec![1, 2, 3]);
+        assert_eq!(notification.sequence, 263);
+
+        let tx: Vec<Vec<u8>> = vec![b"rawtx".to_vec(), vec![9], 0u32.to_le_bytes().to_vec()];
+        assert_eq!(Notification::parse(&tx).unwrap().topic, Topic::RawTx);
+
+        let hashblock: Vec<Vec<u8>> = vec![b"hashblock".to_vec(), vec![0; 32], vec![0; 4]];
+        assert!(Notification::parse(&hashblock).is_err());
+        assert!(Notification::parse(&frames[..2]).is_err());
+        let short_sequence: Vec<Vec<u8>> = vec![b"rawtx".to_vec(), vec![9], vec![0; 2]];
+        assert!(Notification::parse(&short_sequence).is_err());
+    }
+
+    #[test]
+    fn tracks_sequence_gaps_per_topic() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.observe(Topic::RawBlock, 5), Sequence::First);
+        assert_eq!(tracker.observe(Topic::RawTx, 100), Sequence::First);
+        assert_eq!(tracker.observe(Topic::RawBlock, 6), Sequence::InOrder);
+        assert_eq!(tracker.observe(Topic::RawBlock, 9), Sequence::Gap(2));
+        assert_eq!(tracker.observe(Topic::RawTx, 101), Sequence::InOrder);
+        assert_eq!(tracker.observe(Topic::RawBlock, 0), Sequence::Restarted);
+        assert_eq!(tracker.observe(Topic::RawBlock, 1), Sequence::InOrder);
+
+        tracker.observe(Topic::RawTx, u32::MAX);
+        assert_eq!(tracker.observe(Topic::RawTx, 0), Sequence::InOrder);
+    }
+}
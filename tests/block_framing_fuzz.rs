@@ -0,0 +1,187 @@
+//! In-tree fuzz harness for block framing (no libFuzzer / nightly needed)
+//!
+//! Drives [`block_framing`](blvm_bench::block_framing) with seeded pseudo-random inputs: valid
+//! frame streams (plain and XOR-packaged) with padding, then byte flips, truncation and splices
+//! of random noise. Every run must satisfy `check_scan_invariants` (no panic, frames within
+//! limits, resyncs within the search cap, strictly advancing scan).
+//!
+//! Files in `fuzz/corpus/block_framing/` (cargo-fuzz layout) are replayed too, so crashes found
+//! with `cargo fuzz` stay covered on stable.
+//!
+//!   BLVM_FUZZ_ITERS=200000 BLVM_FUZZ_SEED=7 cargo test --test block_framing_fuzz --release
+
+use blvm_bench::block_framing::{
+    check_scan_invariants, find_encrypted_frame, find_encrypted_magic, xor_deobfuscate, FrameEvent,
+    FrameLimits, FrameScanner, RESYNC_SIZE_RANGE,
+};
+use std::path::Path;
+
+const MAINNET_MAGIC: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
+
+/// xorshift64* — deterministic and dependency-free.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn small_limits() -> FrameLimits {
+    FrameLimits {
+        min_block_size: 80,
+        max_block_size: 2048,
+        max_search_distance: 256,
+    }
+}
+
+/// A well-formed stream of `n` frames, each starting 8-byte aligned (a XOR_KEY1 offset, as in
+/// packaged files), with zero padding between some of them.
+fn frame_stream(rng: &mut Rng, n: usize, limits: FrameLimits) -> (Vec<u8>, usize) {
+    let mut out = Vec::new();
+    for _ in 0..n {
+        let size = limits.min_block_size + rng.below(limits.max_block_size - limits.min_block_size);
+        out.extend_from_slice(&MAINNET_MAGIC);
+        out.extend_from_slice(&(size as u32).to_le_bytes());
+        // Payload bytes avoid 0x7d / 0xf9 so they can't form a magic by accident.
+        out.extend((0..size).map(|_| (rng.below(0x70) as u8) + 1));
+        out.resize(out.len().next_multiple_of(8), 0);
+        if rng.below(4) == 0 {
+            out.extend(std::iter::repeat_n(0u8, 8 * rng.below(4)));
+        }
+    }
+    (out, n)
+}
+
+fn mutate(rng: &mut Rng, data: &mut Vec<u8>) {
+    for _ in 0..1 + rng.below(8) {
+        if data.is_empty() {
+            data.push(rng.next() as u8);
+            continue;
+        }
+        match rng.below(5) {
+            0 => {
+                let i = rng.below(data.len());
+                data[i] ^= 1 << rng.below(8);
+            }
+            1 => {
+                let i = rng.below(data.len());
+                data[i] = rng.next() as u8;
+            }
+            2 => data.truncate(rng.below(data.len())),
+            3 => {
+                let at = rng.below(data.len());
+                let noise: Vec<u8> = (0..rng.below(64)).map(|_| rng.next() as u8).collect();
+                data.splice(at..at, noise);
+            }
+            _ => {
+                // Plant a (possibly misaligned) magic with a random size field.
+                let at = rng.below(data.len());
+                let mut hdr = MAINNET_MAGIC.to_vec();
+                hdr.extend_from_slice(&(rng.next() as u32).to_le_bytes());
+                data.splice(at..at, hdr);
+            }
+        }
+    }
+}
+
+fn check_all(data: &[u8], base_offset: u64) {
+    for xor in [false, true] {
+        for limits in [FrameLimits::default(), small_limits()] {
+            check_scan_invariants(data, base_offset, MAINNET_MAGIC, xor, limits);
+        }
+    }
+    if let Some(i) = find_encrypted_magic(data, base_offset, &MAINNET_MAGIC) {
+        assert!(i + 4 <= data.len());
+    }
+    if let Some(i) = find_encrypted_frame(data, base_offset, &MAINNET_MAGIC, RESYNC_SIZE_RANGE) {
+        assert!(i + 8 <= data.len());
+    }
+}
+
+#[test]
+fn well_formed_streams_frame_exactly() {
+    let mut rng = Rng(env_u64("BLVM_FUZZ_SEED", 0x5eed) | 1);
+    for _ in 0..200 {
+        let limits = small_limits();
+        let frames = 1 + rng.below(6);
+        let (plain, n) = frame_stream(&mut rng, frames, limits);
+        assert_eq!(
+            check_scan_invariants(&plain, 0, MAINNET_MAGIC, false, limits),
+            n
+        );
+
+        let base = 8 * rng.below(1 << 20) as u64;
+        let mut enc = plain.clone();
+        xor_deobfuscate(&mut enc, base);
+        let plain_events: Vec<FrameEvent> = FrameScanner::new(&plain, 0, MAINNET_MAGIC, false)
+            .with_limits(limits)
+            .collect();
+        let enc_events: Vec<FrameEvent> = FrameScanner::new(&enc, base, MAINNET_MAGIC, true)
+            .with_limits(limits)
+            .collect();
+        let blocks = |evs: &[FrameEvent]| {
+            evs.iter()
+                .filter(|e| matches!(e, FrameEvent::Block { .. }))
+                .count()
+        };
+        assert_eq!(blocks(&enc_events), blocks(&plain_events));
+    }
+}
+
+#[test]
+fn mutated_streams_hold_invariants() {
+    let iters = env_u64("BLVM_FUZZ_ITERS", 2_000);
+    let mut rng = Rng(env_u64("BLVM_FUZZ_SEED", 0x5eed) | 1);
+    for _ in 0..iters {
+        let frames = rng.below(5);
+        let (mut data, _) = frame_stream(&mut rng, frames, small_limits());
+        let base = rng.next() >> 16;
+        if rng.below(2) == 0 {
+            xor_deobfuscate(&mut data, base);
+        }
+        mutate(&mut rng, &mut data);
+        check_all(&data, base);
+    }
+}
+
+#[test]
+fn random_noise_holds_invariants() {
+    let iters = env_u64("BLVM_FUZZ_ITERS", 2_000);
+    let mut rng = Rng(env_u64("BLVM_FUZZ_SEED", 0x5eed).rotate_left(17) | 1);
+    for _ in 0..iters {
+        let data: Vec<u8> = (0..rng.below(4096)).map(|_| rng.next() as u8).collect();
+        check_all(&data, rng.next() >> 16);
+    }
+}
+
+#[test]
+fn replay_cargo_fuzz_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/block_framing");
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let input = std::fs::read(entry.path()).unwrap();
+        // Same layout as fuzz/fuzz_targets/block_framing.rs.
+        if input.len() < 9 {
+            continue;
+        }
+        let base = u64::from_le_bytes(input[1..9].try_into().unwrap()) >> 8;
+        check_all(&input[9..], base);
+    }
+}
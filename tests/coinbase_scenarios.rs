@@ -0,0 +1,51 @@
+//! Duplicate-coinbase and BIP34 scenarios against a local regtest Core
+//!
+//! Starts a fresh regtest node with BIP34 moved to height 120 (skipped without Core binaries)
+//! and runs every built-in scenario: Core and BLVM must return the expected verdict for every
+//! block and end with the same UTXO set.
+#![cfg(feature = "differential")]
+
+use anyhow::Result;
+use blvm_bench::node_builder::NodeBuilder;
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::regtest_node::coinbase_scenarios::{core_args, CoinbaseHarness};
+use blvm_bench::regtest_node::{PortManager, RegtestNode};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn core_and_blvm_agree_on_coinbase_rules() -> Result<()> {
+    let binaries = match NodeBuilder::new().find_existing_core() {
+        Ok(binaries) => binaries,
+        Err(_) => {
+            eprintln!("⚠️  Bitcoin Core not found, skipping coinbase scenarios");
+            return Ok(());
+        }
+    };
+    let node = RegtestNode::start_with_port_manager_and_args(
+        binaries,
+        Arc::new(PortManager::new(18743)),
+        core_args(),
+    )
+    .await?;
+    let mut harness =
+        CoinbaseHarness::new(NodeRpcClient::new(RpcConfig::from_regtest_node(&node))).await?;
+
+    let report = harness.run_all().await?;
+    for block in &report.blocks {
+        println!("{}", block);
+    }
+    let failed: Vec<String> = report
+        .blocks
+        .iter()
+        .filter(|b| !b.as_expected())
+        .map(|b| format!("{} at {}", b.scenario, b.height))
+        .collect();
+    assert!(failed.is_empty(), "Coinbase scenarios failed: {:?}", failed);
+    assert_eq!(report.blvm_utxos, Some(report.core_utxos));
+    assert!(
+        report.coin_mismatches.is_empty(),
+        "Repeated coins differ: {:?}",
+        report.coin_mismatches
+    );
+    Ok(())
+}
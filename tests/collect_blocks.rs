@@ -14,6 +14,8 @@ use std::path::PathBuf;
 #[ignore = "local BITCOIN_DATA_DIR / BLOCK_CACHE_DIR: run with --ignored"]
 #[cfg(feature = "differential")]
 async fn collect_blocks_only() -> Result<()> {
+    use blvm_bench::collect_only::{collect_blocks, CollectionConfig};
+
     println!("🚀 Starting collection-only mode");
     println!("   Blocks will be validated during chunking");
 
@@ -22,80 +24,33 @@ async fn collect_blocks_only() -> Result<()> {
 
     let cache_dir = std::env::var("BLOCK_CACHE_DIR").ok().map(PathBuf::from);
 
-    // Create block file reader
-    let reader = if let Some(dir) = data_dir {
-        BlockFileReader::new(dir, BlockFileNetwork::Mainnet)?
-    } else {
-        BlockFileReader::auto_detect(BlockFileNetwork::Mainnet)?
-    };
-
-    println!("📂 Block file reader created");
     println!("💾 Cache directory: {:?}", cache_dir);
     println!("");
     println!("   Collection will:");
-    println!("   - Read blocks sequentially (fast)");
+    println!("   - Read all block files in parallel batches");
     println!("   - Write to temp file");
     println!("   - Chunk every 125,000 blocks");
     println!("   - Validate blocks during chunking");
     println!("   - Compress and move chunks to secondary drive");
     println!("");
 
-    // Read all blocks sequentially - this triggers collection
-    // The iterator will automatically write to temp file and chunk incrementally
-    let mut iterator = reader.read_blocks_sequential(None, None)?;
-
-    let mut count = 0;
-    let start_time = std::time::Instant::now();
-    let mut last_report = std::time::Instant::now();
-
-    while let Some(block_result) = iterator.next() {
-        match block_result {
-            Ok(_block_data) => {
-                count += 1;
-
-                // Progress reporting every 10k blocks
-                if count % 10000 == 0 {
-                    let elapsed = last_report.elapsed().as_secs_f64();
-                    let rate = if elapsed > 0.0 {
-                        10000.0 / elapsed
-                    } else {
-                        0.0
-                    };
-                    let total_elapsed = start_time.elapsed().as_secs_f64();
-                    let avg_rate = if total_elapsed > 0.0 {
-                        count as f64 / total_elapsed
-                    } else {
-                        0.0
-                    };
-
-                    println!(
-                        "   📊 Collected {} blocks | Rate: {:.0} blocks/sec (avg: {:.0})",
-                        count, rate, avg_rate
-                    );
-                    last_report = std::time::Instant::now();
-                }
-            }
-            Err(e) => {
-                eprintln!("   ⚠️  Error reading block {}: {}", count, e);
-                return Err(e);
-            }
-        }
-    }
+    let report = collect_blocks(CollectionConfig {
+        data_dir,
+        network: BlockFileNetwork::Mainnet,
+        ..Default::default()
+    })?;
 
-    let total_time = start_time.elapsed();
-    let avg_rate = if total_time.as_secs_f64() > 0.0 {
-        count as f64 / total_time.as_secs_f64()
+    let avg_rate = if report.duration_secs > 0.0 {
+        report.blocks_collected as f64 / report.duration_secs
     } else {
         0.0
     };
 
     println!("");
     println!("✅ Collection complete!");
-    println!("   Total blocks: {}", count);
-    println!(
-        "   Total time: {:.1} minutes",
-        total_time.as_secs_f64() / 60.0
-    );
+    println!("   Total blocks: {}", report.blocks_collected);
+    println!("   Chunked blocks: {}", report.chunked_blocks);
+    println!("   Total time: {:.1} minutes", report.duration_secs / 60.0);
     println!("   Average rate: {:.0} blocks/sec", avg_rate);
 
     Ok(())
@@ -156,7 +111,12 @@ async fn create_final_chunk() -> Result<()> {
 
     // Create chunk_9 with remaining blocks
     println!("📦 Creating chunk_9 with {} blocks...", count);
-    BlockFileReader::create_and_move_chunk_from_file(&temp_file, 9, count as usize)?;
+    BlockFileReader::create_and_move_chunk_from_file(
+        &temp_file,
+        9,
+        count as usize,
+        blvm_bench::config::global(),
+    )?;
 
     println!("✅ Created chunk_9 with {} blocks", count);
 
@@ -42,12 +42,7 @@ fn test_find_block1_in_chunks() -> Result<()> {
 
         println!("   📦 Checking chunk {}...", chunk_num);
 
-        let mut zstd_proc = decompress_chunk_streaming(&chunk_file)?;
-        let stdout = zstd_proc
-            .stdout
-            .take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get zstd stdout"))?;
-        let mut reader = std::io::BufReader::new(stdout);
+        let mut reader = std::io::BufReader::new(decompress_chunk_streaming(&chunk_file)?);
 
         let mut offset: u64 = 0;
         let mut block_num = 0;
@@ -0,0 +1,368 @@
+//! Genesis and first-block handling across networks
+//!
+//! Genesis is special-cased in several places: sanity (`prev_hash` all zeros must be a real
+//! genesis), the reader's prev-hash ordering and chunk indexing (genesis found by its zero
+//! `prev_hash`), and header validation (first header is PoW-only). These tests feed the real
+//! genesis blocks of mainnet, testnet3, testnet4, signet and regtest through those paths, plus a
+//! synthetic 1000-block chain on top of each genesis.
+//!
+//! The synthetic blocks are coinbase-only and mined to the regtest target, so only the regtest
+//! chain is also valid under its network's header rules; that chain is also connected block by
+//! block through `connect_block`.
+#![cfg(feature = "differential")]
+
+use blvm_bench::block_file_reader::{BlockFileReader, Network, ParsedBlock};
+use blvm_bench::config::BenchConfig;
+use blvm_bench::consensus_compat::connect_block;
+use blvm_bench::header_sync::{Header, HeaderChain, HeaderParams};
+use blvm_bench::node_rpc_client::BitcoinNetwork;
+use blvm_bench::sanity::{SanityConfig, SanityFilter, SanityRule};
+use blvm_bench::wire::{display_hex, sha256d};
+use std::path::Path;
+
+const CHAIN_LEN: usize = 1000;
+const REGTEST_BITS: u32 = 0x207f_ffff;
+const REGTEST_HALVING_INTERVAL: u32 = 150;
+
+/// Genesis coinbase shared by mainnet, testnet3, signet and regtest ("The Times 03/Jan/2009 ...")
+const SATOSHI_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+const TESTNET4_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff5504ffff001d01044c4c30332f4d61792f323032342030303030303030303030303030303030303030303165626435386332343439373062336161396437383362623030313031316662653865613865393865303065ffffffff0100f2052a010000002321000000000000000000000000000000000000000000000000000000000000000000ac00000000";
+
+struct GenesisFixture {
+    name: &'static str,
+    header: &'static str,
+    coinbase: &'static str,
+    hash: &'static str,
+    /// Reader network and its block file magic
+    reader_network: (Network, [u8; 4]),
+    header_params: fn() -> HeaderParams,
+}
+
+const GENESIS: [GenesisFixture; 5] = [
+    GenesisFixture {
+        name: "mainnet",
+        header: "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        coinbase: SATOSHI_COINBASE,
+        hash: "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        reader_network: (Network::Mainnet, [0xf9, 0xbe, 0xb4, 0xd9]),
+        header_params: || HeaderParams::for_network(BitcoinNetwork::Mainnet),
+    },
+    GenesisFixture {
+        name: "testnet3",
+        header: "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae18",
+        coinbase: SATOSHI_COINBASE,
+        hash: "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+        reader_network: (Network::Testnet, [0x0b, 0x11, 0x09, 0x07]),
+        header_params: || HeaderParams::for_network(BitcoinNetwork::Testnet),
+    },
+    GenesisFixture {
+        name: "testnet4",
+        header: "0100000000000000000000000000000000000000000000000000000000000000000000004e7b2b9128fe0291db0693af2ae418b767e657cd407e80cb1434221eaea7a07a046f3566ffff001dbb0c7817",
+        coinbase: TESTNET4_COINBASE,
+        hash: "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043",
+        reader_network: (Network::Testnet4, [0x1c, 0x16, 0x3f, 0x28]),
+        header_params: HeaderParams::testnet4,
+    },
+    GenesisFixture {
+        name: "signet",
+        header: "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a008f4d5fae77031e8ad22203",
+        coinbase: SATOSHI_COINBASE,
+        hash: "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+        reader_network: (Network::Signet, [0x0a, 0x03, 0xcf, 0x40]),
+        header_params: || HeaderParams::for_network(BitcoinNetwork::Signet),
+    },
+    GenesisFixture {
+        name: "regtest",
+        header: "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000",
+        coinbase: SATOSHI_COINBASE,
+        hash: "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        reader_network: (Network::Regtest, [0xfa, 0xbf, 0xb5, 0xda]),
+        header_params: || HeaderParams::for_network(BitcoinNetwork::Regtest),
+    },
+];
+
+fn display_hash(block: &[u8]) -> String {
+    display_hex(&sha256d(&block[..80]))
+}
+
+impl GenesisFixture {
+    fn block(&self) -> Vec<u8> {
+        let mut block = hex::decode(self.header).unwrap();
+        block.push(1);
+        block.extend(hex::decode(self.coinbase).unwrap());
+        block
+    }
+}
+
+/// `CScript() << height`, the minimal push BIP34 compares the coinbase against.
+fn height_push(height: u32) -> Vec<u8> {
+    if (1..=16).contains(&height) {
+        return vec![0x50 + height as u8];
+    }
+    let mut bytes = height.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        bytes.push(0);
+    }
+    let mut push = vec![bytes.len() as u8];
+    push.extend(bytes);
+    push
+}
+
+/// Coinbase-only child of `parent` at `height`, mined to the regtest target and claiming the
+/// regtest subsidy (never more than any other network's at the same height).
+fn mine_child(parent: &[u8], height: u32) -> Vec<u8> {
+    // BIP34 height push plus a tag byte (scriptSig must be 2-100 bytes)
+    let mut script_sig = height_push(height);
+    script_sig.push(0x51);
+    let subsidy = 5_000_000_000u64 >> (height / REGTEST_HALVING_INTERVAL);
+
+    let mut coinbase = Vec::new();
+    coinbase.extend_from_slice(&1u32.to_le_bytes());
+    coinbase.push(1);
+    coinbase.extend_from_slice(&[0u8; 32]);
+    coinbase.extend_from_slice(&u32::MAX.to_le_bytes());
+    coinbase.push(script_sig.len() as u8);
+    coinbase.extend_from_slice(&script_sig);
+    coinbase.extend_from_slice(&u32::MAX.to_le_bytes());
+    coinbase.push(1);
+    coinbase.extend_from_slice(&subsidy.to_le_bytes());
+    coinbase.extend_from_slice(&[1, 0x51]); // OP_TRUE
+    coinbase.extend_from_slice(&0u32.to_le_bytes());
+
+    let parent_time = u32::from_le_bytes(parent[68..72].try_into().unwrap());
+    let mut header = Vec::with_capacity(80);
+    header.extend_from_slice(&4u32.to_le_bytes());
+    header.extend_from_slice(&sha256d(&parent[..80]));
+    header.extend_from_slice(&sha256d(&coinbase));
+    header.extend_from_slice(&(parent_time + 600).to_le_bytes());
+    header.extend_from_slice(&REGTEST_BITS.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    // Regtest target is 0x7fffff << 232: a hash whose top byte is below 0x7f always meets it
+    while sha256d(&header)[31] >= 0x7f {
+        let nonce = u32::from_le_bytes(header[76..80].try_into().unwrap()) + 1;
+        header[76..80].copy_from_slice(&nonce.to_le_bytes());
+    }
+
+    let mut block = header;
+    block.push(1);
+    block.extend(coinbase);
+    block
+}
+
+/// Genesis followed by `CHAIN_LEN` mined children.
+fn chain_from(genesis: Vec<u8>) -> Vec<Vec<u8>> {
+    let mut chain = vec![genesis];
+    for height in 1..=CHAIN_LEN as u32 {
+        let child = mine_child(chain.last().unwrap(), height);
+        chain.push(child);
+    }
+    chain
+}
+
+/// `<data_dir>/blocks/blk00000.dat` with `magic | len | block` frames, XORed with `xor_key`
+/// (written to `xor.dat`) when given.
+fn write_block_file(data_dir: &Path, magic: [u8; 4], blocks: &[Vec<u8>], xor_key: Option<[u8; 8]>) {
+    let blocks_dir = data_dir.join("blocks");
+    std::fs::create_dir_all(&blocks_dir).unwrap();
+    let mut file = Vec::new();
+    for block in blocks {
+        file.extend_from_slice(&magic);
+        file.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        file.extend_from_slice(block);
+    }
+    if let Some(key) = xor_key {
+        for (i, byte) in file.iter_mut().enumerate() {
+            *byte ^= key[i % 8];
+        }
+        std::fs::write(blocks_dir.join("xor.dat"), key).unwrap();
+    }
+    std::fs::write(blocks_dir.join("blk00000.dat"), file).unwrap();
+}
+
+fn small_buffers() -> BenchConfig {
+    let mut tuning = BenchConfig::default();
+    tuning.io.buffer_size = 1 << 20;
+    tuning.io.search_buffer_size = 1 << 20;
+    tuning
+}
+
+#[test]
+fn genesis_blocks_hash_and_pass_sanity_on_every_network() {
+    let strict = SanityFilter::new(SanityConfig::strict());
+    for fixture in &GENESIS {
+        let block = fixture.block();
+        assert_eq!(display_hash(&block), fixture.hash, "{}", fixture.name);
+        // Single-transaction merkle root is the coinbase txid
+        assert_eq!(
+            block[36..68],
+            sha256d(&hex::decode(fixture.coinbase).unwrap()),
+            "{}",
+            fixture.name
+        );
+        strict
+            .check(&block)
+            .unwrap_or_else(|e| panic!("{} genesis rejected: {}", fixture.name, e));
+
+        // Same zero prev_hash, different nonce: no longer a genesis block
+        let mut fake = block.clone();
+        fake[76] ^= 1;
+        assert_eq!(
+            strict.check(&fake).unwrap_err().rule,
+            SanityRule::FakeGenesis,
+            "{}",
+            fixture.name
+        );
+    }
+    assert_eq!(strict.total_rejected(), GENESIS.len() as u64);
+}
+
+#[test]
+fn genesis_headers_validate_under_their_network_rules() {
+    for fixture in &GENESIS {
+        let mut chain = HeaderChain::new((fixture.header_params)());
+        let genesis = Header::parse(&hex::decode(fixture.header).unwrap()).unwrap();
+        assert_eq!(genesis.hash_hex(), fixture.hash);
+        chain
+            .push(genesis)
+            .unwrap_or_else(|e| panic!("{} genesis header rejected: {:?}", fixture.name, e));
+        assert_eq!(chain.len(), 1);
+    }
+}
+
+#[test]
+fn regtest_first_1000_headers_validate() {
+    let regtest = GENESIS.iter().find(|f| f.name == "regtest").unwrap();
+    let blocks = chain_from(regtest.block());
+    let mut chain = HeaderChain::new(HeaderParams::for_network(BitcoinNetwork::Regtest));
+    for (height, block) in blocks.iter().enumerate() {
+        let header = Header::parse(&block[..80]).unwrap();
+        chain
+            .push(header)
+            .unwrap_or_else(|e| panic!("regtest header {} rejected: {:?}", height, e));
+    }
+    assert_eq!(chain.len(), CHAIN_LEN + 1);
+    assert_eq!(chain.header(0).unwrap().hash_hex(), regtest.hash);
+}
+
+#[test]
+fn regtest_first_1000_blocks_connect() {
+    use blvm_protocol::block::block_validation_context_for_connect_ibd;
+    use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+    use blvm_protocol::types::{BlockHeader, UtxoSet, ValidationResult};
+
+    let regtest = GENESIS.iter().find(|f| f.name == "regtest").unwrap();
+    let blocks = chain_from(regtest.block());
+    // Genesis outputs are never added to the UTXO set, so connecting starts at height 1
+    let mut utxo_set = UtxoSet::default();
+    for (height, bytes) in blocks.iter().enumerate().skip(1) {
+        let (block, witnesses) = deserialize_block_with_witnesses(bytes).unwrap();
+        let ctx = block_validation_context_for_connect_ibd(
+            None::<&[BlockHeader]>,
+            block.header.timestamp,
+            blvm_protocol::types::Network::Regtest,
+        );
+        let (result, next) = connect_block(&block, &witnesses, utxo_set, height as u64, &ctx)
+            .unwrap_or_else(|e| panic!("regtest block {} failed: {:#}", height, e));
+        if let ValidationResult::Invalid(reason) = result {
+            panic!("regtest block {} rejected: {}", height, reason);
+        }
+        utxo_set = next;
+    }
+    // One OP_TRUE coinbase output per connected block
+    assert_eq!(utxo_set.len(), CHAIN_LEN);
+}
+
+#[test]
+fn reader_yields_genesis_then_first_1000_blocks() {
+    let strict = SanityFilter::new(SanityConfig::strict());
+    for fixture in &GENESIS {
+        let (network, magic) = fixture.reader_network;
+        assert_eq!(network.genesis_hash(), fixture.hash);
+        let blocks = chain_from(fixture.block());
+
+        // Plain files, and the same chain obfuscated the Core 28+ way (blocks/xor.dat)
+        for xor_key in [None, Some([0x5a, 0x01, 0xc3, 0x77, 0x00, 0x9e, 0x42, 0xf0])] {
+            let dir = tempfile::tempdir().unwrap();
+            write_block_file(dir.path(), magic, &blocks, xor_key);
+            let reader = BlockFileReader::new(dir.path(), network)
+                .unwrap()
+                .with_tuning(small_buffers());
+            assert_eq!(reader.xor_key().is_some(), xor_key.is_some());
+
+            let read: Vec<Vec<u8>> = reader
+                .read_blocks_sequential(None, None)
+                .unwrap()
+                .collect::<anyhow::Result<_>>()
+                .unwrap();
+            assert_eq!(
+                read.len(),
+                CHAIN_LEN + 1,
+                "{} xor={:?}",
+                fixture.name,
+                xor_key
+            );
+            assert_eq!(display_hash(&read[0]), fixture.hash);
+            assert!(read[0][4..36].iter().all(|&b| b == 0));
+            for (height, block) in read.iter().enumerate() {
+                assert_eq!(block, &blocks[height], "{} block {}", fixture.name, height);
+                if height > 0 {
+                    assert_eq!(block[4..36], sha256d(&read[height - 1][..80]));
+                }
+                strict.check(block).unwrap();
+            }
+
+            // Bounded read from genesis stops where asked
+            let first_ten = reader
+                .read_blocks_sequential(None, Some(10))
+                .unwrap()
+                .count();
+            assert_eq!(first_ten, 10);
+
+            // Typed adapter: same order, heights counted from genesis, odd batch size
+            let parsed: Vec<ParsedBlock> = reader
+                .read_blocks_sequential(None, None)
+                .unwrap()
+                .parsed_with_batch(7)
+                .collect::<anyhow::Result<_>>()
+                .unwrap();
+            assert_eq!(parsed.len(), CHAIN_LEN + 1);
+            for (height, p) in parsed.iter().enumerate() {
+                assert_eq!(p.height_hint, height as u64);
+                assert_eq!(p.raw_len, blocks[height].len());
+                assert_eq!(p.block.header.prev_block_hash[..], blocks[height][4..36]);
+                assert_eq!(p.witnesses.len(), 1);
+            }
+        }
+    }
+    assert_eq!(strict.total_rejected(), 0);
+}
+
+#[test]
+fn first_blocks_deserialize_with_genesis_prev_hash_zero() {
+    use blvm_protocol::serialization::block::deserialize_block_with_witnesses;
+
+    for fixture in &GENESIS {
+        let blocks = chain_from(fixture.block());
+        for (height, bytes) in blocks.iter().enumerate() {
+            let (block, witnesses) = deserialize_block_with_witnesses(bytes)
+                .unwrap_or_else(|e| panic!("{} block {}: {:?}", fixture.name, height, e));
+            assert_eq!(block.transactions.len(), 1);
+            assert_eq!(witnesses.len(), 1);
+            assert!(blvm_protocol::transaction::is_coinbase(
+                &block.transactions[0]
+            ));
+            let expected_prev = match height {
+                0 => [0u8; 32],
+                _ => sha256d(&blocks[height - 1][..80]),
+            };
+            assert_eq!(
+                block.header.prev_block_hash, expected_prev,
+                "{} block {}",
+                fixture.name, height
+            );
+        }
+    }
+}
@@ -1024,6 +1024,12 @@ async fn test_historical_blocks_parallel_fallback() -> Result<()> {
         blvm_bench::parallel_differential::BlockDataSource::RemoteCoreRpc(_) => {
             println!("✅ Using remote-Core RPC (for encrypted / XOR-packaged files)");
         }
+        blvm_bench::parallel_differential::BlockDataSource::P2p(source) => {
+            println!("✅ Using P2P block download (no RPC): {}", source.peer());
+        }
+        blvm_bench::parallel_differential::BlockDataSource::Esplora(source) => {
+            println!("✅ Using Esplora block download (no node): {}", source.base_url());
+        }
     }
 
     let block_source = Arc::new(block_source);
@@ -1035,10 +1041,15 @@ async fn test_historical_blocks_parallel_fallback() -> Result<()> {
     let config = ParallelConfig {
         num_workers,
         chunk_size,
+        scheduling: blvm_bench::chunk_schedule::ChunkScheduling::from_env(),
         use_checkpoints: std::env::var("USE_CHECKPOINTS")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(false),
+        hooks: blvm_bench::validation_hooks::HookRegistry::from_env()?,
+        checkpoint_dir: blvm_bench::checkpoint_store::checkpoint_dir_from_env(),
+        assumeutxo_snapshot: None,
+        run_state_dir: None,
     };
 
     let results =
@@ -0,0 +1,205 @@
+//! Differential runner against a mocked Core oracle
+//!
+//! [`MockCore`] serves a synthetic mainnet-genesis chain and scripts what Core says about each
+//! block, so these tests pin down how `validate_chunk` classifies agreement and divergence,
+//! how a failing block fetch surfaces, and what ends up in the CI report - deterministically
+//! and without a node. BLVM accepts every synthetic block (checked here per height, and in
+//! `mock_core`'s own tests), so the divergences are exactly the heights scripted `Invalid`.
+//!
+//! The runner asks Core once per block and does not retry: an `Unavailable` answer counts as
+//! valid. Retrying a failed call is left to the RPC transports.
+//!
+//! Remote-Core RPC must not be configured in the environment (it takes precedence over the
+//! block source for Core verdicts).
+#![cfg(feature = "differential")]
+
+use anyhow::Result;
+use blvm_bench::cancel::CancellationToken;
+use blvm_bench::collect_only::ValidationReport;
+use blvm_bench::differential::{CoreValidationResult, ValidationResult};
+use blvm_bench::mock_core::{synthetic_chain, CoreResponse, MockCore};
+use blvm_bench::parallel_differential::{validate_chunk, BlockChunk, ChunkResult};
+use blvm_bench::report::DifferentialReport;
+use blvm_bench::validation_hooks::{
+    BlockContext, BlockOutcome, DivergenceEvent, HookRegistry, ValidationHook,
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CHAIN_LEN: usize = 20;
+
+/// Per height: (BLVM valid, Core valid), plus the heights hooks were told diverged.
+#[derive(Default)]
+struct Recorder {
+    verdicts: Mutex<BTreeMap<u64, (bool, bool)>>,
+    divergences: Mutex<Vec<u64>>,
+}
+
+impl ValidationHook for Recorder {
+    fn name(&self) -> &str {
+        "recorder"
+    }
+
+    fn post_block(&self, ctx: &BlockContext<'_>, outcome: &BlockOutcome<'_>) -> Result<()> {
+        self.verdicts.lock().unwrap().insert(
+            ctx.height,
+            (
+                matches!(outcome.blvm, ValidationResult::Valid),
+                matches!(outcome.core, CoreValidationResult::Valid),
+            ),
+        );
+        Ok(())
+    }
+
+    fn on_divergence(&self, event: &DivergenceEvent<'_>) -> Result<()> {
+        self.divergences.lock().unwrap().push(event.height);
+        Ok(())
+    }
+}
+
+impl Recorder {
+    fn core_verdict(&self, height: u64) -> bool {
+        self.verdicts.lock().unwrap()[&height].1
+    }
+}
+
+async fn run(core: MockCore) -> (Arc<MockCore>, Arc<Recorder>, Result<ChunkResult>) {
+    let core = Arc::new(core);
+    let recorder = Arc::new(Recorder::default());
+    let chunk = BlockChunk {
+        start_height: 0,
+        end_height: CHAIN_LEN as u64,
+        checkpoint_utxo: None,
+        checkpoint_timing: None,
+        skip_validation: false,
+    };
+    let result = validate_chunk(
+        chunk,
+        core.clone(),
+        CancellationToken::new(),
+        HookRegistry::new().with(recorder.clone()),
+    )
+    .await;
+    (core, recorder, result)
+}
+
+/// BLVM accepted every block, and exactly the `expected` heights diverged.
+fn assert_classified(result: &ChunkResult, recorder: &Recorder, expected: &[u64]) {
+    let rejected: Vec<u64> = recorder
+        .verdicts
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, (blvm, _))| !blvm)
+        .map(|(&height, _)| height)
+        .collect();
+    assert!(rejected.is_empty(), "BLVM rejected {:?}", rejected);
+    let recorded: Vec<u64> = result.divergences.iter().map(|d| d.0).collect();
+    assert_eq!(recorded, expected);
+    assert_eq!(*recorder.divergences.lock().unwrap(), expected);
+    assert_eq!(result.tested, CHAIN_LEN + 1);
+    assert_eq!(result.matched, result.tested - expected.len());
+}
+
+#[tokio::test]
+async fn agreeing_core_and_scripted_rejections_are_classified() {
+    let (core, recorder, result) = run(MockCore::new(synthetic_chain(CHAIN_LEN))
+        .respond(5, CoreResponse::Invalid)
+        .respond(12, CoreResponse::Invalid))
+    .await;
+    let result = result.unwrap();
+
+    assert!(!recorder.core_verdict(5));
+    assert!(!recorder.core_verdict(12));
+    assert!(recorder.core_verdict(6));
+    assert_classified(&result, &recorder, &[5, 12]);
+    for (height, blvm, core_str) in &result.divergences {
+        assert_eq!(blvm, "Valid", "height {}", height);
+        assert_ne!(core_str, "Valid", "height {}", height);
+    }
+
+    // One fetch and one Core query per block: nothing is silently re-asked
+    assert_eq!(core.fetches(), CHAIN_LEN as u64 + 1);
+    for height in 0..=CHAIN_LEN as u64 {
+        assert_eq!(core.queries(height), 1, "height {}", height);
+    }
+}
+
+#[tokio::test]
+async fn unavailable_core_counts_as_valid() {
+    let (core, recorder, result) = run(MockCore::new(synthetic_chain(CHAIN_LEN))
+        .respond(3, CoreResponse::Unavailable)
+        .respond_sequence(4, [CoreResponse::Unavailable, CoreResponse::Invalid]))
+    .await;
+    let result = result.unwrap();
+
+    assert!(recorder.core_verdict(3));
+    // No retry: the first answer is the one that counts, though a second would say Invalid
+    assert!(recorder.core_verdict(4));
+    assert_eq!(core.queries(3), 1);
+    assert_eq!(core.queries(4), 1);
+    assert_classified(&result, &recorder, &[]);
+}
+
+#[tokio::test]
+async fn delayed_answers_keep_their_verdict() {
+    let delay = Duration::from_millis(50);
+    let start = Instant::now();
+    let (_, recorder, result) = run(MockCore::new(synthetic_chain(CHAIN_LEN))
+        .respond(7, CoreResponse::delayed(delay, CoreResponse::Invalid))
+        .respond(8, CoreResponse::delayed(delay, CoreResponse::Valid)))
+    .await;
+    let result = result.unwrap();
+
+    assert!(start.elapsed() >= delay * 2);
+    assert!(!recorder.core_verdict(7));
+    assert!(recorder.core_verdict(8));
+    assert_classified(&result, &recorder, &[7]);
+}
+
+#[tokio::test]
+async fn failed_block_fetch_fails_the_chunk() {
+    let (core, recorder, result) =
+        run(MockCore::new(synthetic_chain(CHAIN_LEN)).fail_fetch(9)).await;
+
+    let err = result.unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("block 9 unavailable"),
+        "{:#}",
+        err
+    );
+    // Blocks before the failure were validated, nothing after it was fetched
+    assert_eq!(recorder.verdicts.lock().unwrap().len(), 9);
+    assert_eq!(core.fetches(), 10);
+}
+
+#[tokio::test]
+async fn report_lists_scripted_divergences() {
+    let (_, recorder, result) = run(MockCore::new(synthetic_chain(CHAIN_LEN))
+        .respond(2, CoreResponse::Invalid)
+        .respond(17, CoreResponse::Invalid))
+    .await;
+    let chunk = result.unwrap();
+    let expected = [2u64, 17];
+    assert_classified(&chunk, &recorder, &expected);
+
+    let report = DifferentialReport::from_validation(&ValidationReport {
+        start_height: 0,
+        end_height: CHAIN_LEN as u64,
+        tested: chunk.tested,
+        matched: chunk.matched,
+        divergences: chunk.divergences.len(),
+        chunks: vec![chunk],
+        cancelled: false,
+        duration_secs: 1.0,
+    });
+
+    let heights: Vec<u64> = report.divergence_entries.iter().map(|e| e.height).collect();
+    assert_eq!(heights, expected);
+    assert_eq!(report.divergences.total() as usize, expected.len());
+    assert!(!report.passed);
+    assert!(report.ensure_passed().is_err());
+    assert_eq!(report.chunks.len(), 1);
+    assert_eq!(report.chunks[0].divergences as usize, expected.len());
+}
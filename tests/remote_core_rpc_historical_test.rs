@@ -65,7 +65,12 @@ async fn test_remote_core_historical_100_blocks() -> Result<()> {
     let config = ParallelConfig {
         num_workers,
         chunk_size,
+        scheduling: blvm_bench::chunk_schedule::ChunkScheduling::from_env(),
         use_checkpoints,
+        hooks: blvm_bench::validation_hooks::HookRegistry::from_env()?,
+        checkpoint_dir: blvm_bench::checkpoint_store::checkpoint_dir_from_env(),
+        assumeutxo_snapshot: None,
+        run_state_dir: None,
     };
 
     println!("🔧 Configuration:");
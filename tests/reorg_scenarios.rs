@@ -0,0 +1,41 @@
+//! Reorg scenarios against a local regtest Core
+//!
+//! Starts a fresh regtest node from the Core binaries [`NodeBuilder`] finds (skipped without
+//! one) and runs every built-in scenario: Core and BLVM must end on the same, expected, tip with
+//! the same UTXO set.
+#![cfg(feature = "differential")]
+
+use anyhow::Result;
+use blvm_bench::node_builder::NodeBuilder;
+use blvm_bench::node_rpc_client::{NodeRpcClient, RpcConfig};
+use blvm_bench::regtest_node::reorg_scenarios::{ReorgHarness, SCENARIOS};
+use blvm_bench::regtest_node::{PortManager, RegtestNode};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn core_and_blvm_agree_on_reorgs() -> Result<()> {
+    let binaries = match NodeBuilder::new().find_existing_core() {
+        Ok(binaries) => binaries,
+        Err(_) => {
+            eprintln!("⚠️  Bitcoin Core not found, skipping reorg scenarios");
+            return Ok(());
+        }
+    };
+    let node =
+        RegtestNode::start_with_port_manager(binaries, Arc::new(PortManager::new(18643))).await?;
+    let mut harness =
+        ReorgHarness::new(NodeRpcClient::new(RpcConfig::from_regtest_node(&node))).await?;
+
+    let outcomes = harness.run_all().await?;
+    assert_eq!(outcomes.len(), SCENARIOS.len());
+    for outcome in &outcomes {
+        println!("{}", outcome);
+    }
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|o| !o.as_expected())
+        .map(|o| o.name)
+        .collect();
+    assert!(failed.is_empty(), "Reorg scenarios failed: {:?}", failed);
+    Ok(())
+}
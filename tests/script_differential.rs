@@ -0,0 +1,72 @@
+//! Script interpreter differential against `libbitcoinconsensus`
+//!
+//! Replays Core's `script_tests.json` (`BLVM_SCRIPT_TESTS_JSON`, or `tests/data/script_tests.json`
+//! copied from `bitcoin/src/test/data/`) and runs a short seeded fuzz pass. Fuzz length can be
+//! raised with `BLVM_SCRIPT_FUZZ_ITERATIONS`.
+#![cfg(feature = "differential")]
+
+use anyhow::Result;
+use blvm_bench::script_differential::{
+    load_script_tests, replay_cases, ScriptDiffReport, ScriptFuzzer,
+};
+use std::path::PathBuf;
+
+fn print_divergences(report: &ScriptDiffReport) {
+    for d in &report.divergences {
+        eprintln!(
+            "❌ {:?}: sig={} spk={} flags={:#x} expected={:?} BLVM={} ({:?}) Core={:?} {}",
+            d.divergence,
+            hex::encode(&d.case.script_sig),
+            hex::encode(&d.case.script_pubkey),
+            d.case.flags,
+            d.case.expected,
+            d.outcome.blvm_ok,
+            d.outcome.blvm_error,
+            d.outcome.core_ok,
+            d.case.comment
+        );
+    }
+}
+
+#[test]
+fn core_script_tests_replay() -> Result<()> {
+    let path = std::env::var_os("BLVM_SCRIPT_TESTS_JSON")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/script_tests.json")
+        });
+    if !path.exists() {
+        eprintln!("⚠️  No script_tests.json at {}, skipping", path.display());
+        return Ok(());
+    }
+    let (cases, skipped) = load_script_tests(&path)?;
+    println!(
+        "📜 {} script test cases ({} taproot template rows skipped)",
+        cases.len(),
+        skipped
+    );
+
+    let report = replay_cases(&cases);
+    println!(
+        "   Evaluated {}, compared with Core {}, divergences {}",
+        report.evaluated, report.core_compared, report.divergence_count
+    );
+    print_divergences(&report);
+    assert_eq!(report.divergence_count, 0);
+    Ok(())
+}
+
+#[test]
+fn seeded_script_fuzz() {
+    let iterations = std::env::var("BLVM_SCRIPT_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(2_000);
+    let report = ScriptFuzzer::new(0x5c12_7d1f).run(iterations);
+    println!(
+        "🎲 {} fuzzed scripts, {} divergences from Core",
+        report.evaluated, report.divergence_count
+    );
+    print_divergences(&report);
+    assert_eq!(report.divergence_count, 0);
+}
@@ -0,0 +1,65 @@
+//! Weird-but-valid historical transactions, verified in isolation
+//!
+//! Uses `tests/data/weird_tx_corpus.json` (or `BLVM_WEIRD_TX_CORPUS`). When the corpus does not
+//! exist yet it is extracted once from a local mainnet data dir (`BITCOIN_DATA_DIR` or the default
+//! locations; needs `blocks/index` and `rev*.dat`) and written there, so it can be committed and
+//! later runs need no chain data.
+#![cfg(feature = "differential")]
+
+use anyhow::Result;
+use blvm_bench::block_file_reader::{BlockFileReader, Network};
+use blvm_bench::cancel::CancellationToken;
+use blvm_bench::weird_tx_corpus::{extract_corpus, verify_corpus, ExtractConfig, WeirdTxCorpus};
+use std::path::PathBuf;
+
+fn corpus_path() -> PathBuf {
+    std::env::var_os("BLVM_WEIRD_TX_CORPUS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/weird_tx_corpus.json")
+        })
+}
+
+#[test]
+fn weird_historical_transactions_verify() -> Result<()> {
+    let path = corpus_path();
+    let corpus = if path.exists() {
+        WeirdTxCorpus::load(&path)?
+    } else {
+        let Ok(reader) = BlockFileReader::auto_detect(Network::Mainnet) else {
+            eprintln!(
+                "⚠️  No corpus at {} and no mainnet data dir to extract one, skipping",
+                path.display()
+            );
+            return Ok(());
+        };
+        println!(
+            "🔍 Extracting weird transaction corpus to {}",
+            path.display()
+        );
+        let corpus = extract_corpus(
+            &reader,
+            &ExtractConfig::default(),
+            &CancellationToken::new(),
+        )?;
+        corpus.save(&path)?;
+        corpus
+    };
+    println!("   Corpus: {:?}", corpus.counts());
+
+    let verdicts = verify_corpus(&corpus)?;
+    let failures: Vec<_> = verdicts.iter().filter(|v| !v.passed()).collect();
+    for f in &failures {
+        eprintln!(
+            "❌ {:?} {} input {} at {}: expected valid={}, BLVM={}, Core={:?}",
+            f.kind, f.txid, f.input_index, f.height, f.expected_valid, f.blvm, f.core
+        );
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} corpus inputs failed",
+        failures.len(),
+        verdicts.len()
+    );
+    Ok(())
+}